@@ -8,7 +8,6 @@
  */
 
 use std::future::Future;
-use std::str::FromStr;
 
 use anyhow::Context;
 use buck2_common::invocation_paths::InvocationPaths;
@@ -27,6 +26,7 @@ use crate::common::HostArchOverride;
 use crate::common::HostPlatformOverride;
 use crate::daemon::client::connect::BuckdConnectOptions;
 use crate::daemon::client::BuckdClientConnector;
+use crate::immediate_config::ImmediateConfig;
 use crate::replayer::Replayer;
 use crate::stdin::Stdin;
 use crate::verbosity::Verbosity;
@@ -68,6 +68,15 @@ pub struct ClientCommandContext {
     pub start_in_process_daemon: Option<Box<dyn FnOnce() -> anyhow::Result<()> + Send + Sync>>,
     pub command_name: String,
     pub sanitized_argv: Vec<String>,
+    /// The trace ID of the commands sent to the daemon.
+    pub trace_id: TraceId,
+    /// The trace ID of the invocation which started this one, e.g. a wrapper or an action running
+    /// buck2, to link their event logs.
+    pub parent_trace_id: Option<TraceId>,
+    /// Set when this invocation re-runs a command after restarting the daemon, to the reason of
+    /// the restart.
+    pub restarted_after: Option<String>,
+    pub immediate_config: ImmediateConfig,
 }
 
 impl ClientCommandContext {
@@ -110,6 +119,7 @@ impl ClientCommandContext {
         Ok(ClientContext {
             config_overrides: config_opts.config_overrides(arg_matches)?,
             target_platform: config_opts.target_platforms.clone().unwrap_or_default(),
+            target_modifiers: config_opts.modifiers.clone(),
             host_platform: match config_opts.host_platform_override() {
                 HostPlatformOverride::Default => GrpcHostPlatformOverride::DefaultPlatform,
                 HostPlatformOverride::Linux => GrpcHostPlatformOverride::Linux,
@@ -137,13 +147,6 @@ impl ClientCommandContext {
         #[error("Current directory is not UTF-8")]
         struct CurrentDirIsNotUtf8;
 
-        let trace_id = match std::env::var("BUCK_WRAPPER_UUID") {
-            Ok(uuid_str) => {
-                TraceId::from_str(&uuid_str).context("invalid trace ID in BUCK_WRAPPER_UUID")?
-            }
-            _ => TraceId::new(),
-        };
-
         let daemon_uuid = match std::env::var("BUCK2_DAEMON_UUID") {
             Ok(daemon_uuid) => Some(daemon_uuid),
             _ => None,
@@ -158,11 +161,12 @@ impl ClientCommandContext {
                 .to_owned(),
             config_overrides: Default::default(),
             target_platform: Default::default(),
+            target_modifiers: Vec::new(),
             host_platform: Default::default(),
             host_arch: Default::default(),
             oncall: Default::default(),
             disable_starlark_types: false,
-            trace_id: format!("{}", trace_id),
+            trace_id: format!("{}", self.trace_id),
             reuse_current_config: false,
             daemon_uuid,
             sanitized_argv: Vec::new(),
@@ -22,15 +22,19 @@
 //! }
 //! ```
 use std::path::Path;
+use std::time::Duration;
 
+use buck2_common::legacy_configs::LegacyBuckConfig;
 use buck2_core::fs::fs_util;
 use cli_proto::common_build_options::ExecutionStrategy;
 use cli_proto::config_override::ConfigType;
 use cli_proto::ConfigOverride;
 use gazebo::prelude::*;
 use termwiz::istty::IsTty;
+use thiserror::Error;
 
 use crate::final_console::FinalConsole;
+use crate::immediate_config::ImmediateConfig;
 use crate::path_arg::PathArg;
 use crate::subscribers::superconsole::SuperConsoleConfig;
 
@@ -53,6 +57,11 @@ pub enum ConsoleType {
     SimpleTty,
     Super,
     Auto,
+    /// Superconsole with only the summary line, no open spans or stats.
+    Quiet,
+    /// Simple console that also prints a timestamped progress line periodically, for logs of CI
+    /// jobs that aren't attached to a terminal.
+    Ci,
     None,
 }
 
@@ -71,6 +80,30 @@ pub enum UiOptions {
     DebugEvents,
 }
 
+/// Superconsole components that are shown by default, and can be hidden.
+#[derive(
+    Debug,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Dupe,
+    Copy,
+    PartialEq,
+    Eq,
+    clap::ArgEnum
+)]
+#[clap(rename_all = "lower")]
+pub enum UiComponent {
+    /// Build id and test session.
+    Session,
+    /// Remote execution stats.
+    Re,
+    /// I/O counters.
+    Io,
+    /// The open spans below the job counts.
+    Spans,
+}
+
 #[derive(
     Debug,
     serde::Serialize,
@@ -117,6 +150,12 @@ pub struct CommonDaemonCommandOptions {
 
     #[clap(long = "--write-build-id")]
     pub build_id_file: Option<PathArg>,
+
+    /// Print the resources used while the command ran once it's done: CPU time of the daemon and
+    /// of local actions, peak memory of the daemon, disk usage of local actions and remote
+    /// execution transfers.
+    #[clap(long)]
+    pub print_resource_summary: bool,
 }
 
 impl CommonDaemonCommandOptions {
@@ -125,6 +164,7 @@ impl CommonDaemonCommandOptions {
             event_log: None,
             no_event_log: false,
             build_id_file: None,
+            print_resource_summary: false,
         };
         &DEFAULT
     }
@@ -160,6 +200,17 @@ pub struct CommonBuildConfigurationOptions {
     )]
     pub target_platforms: Option<String>,
 
+    /// Configuration modifier to apply on top of the target platform of the targets being built
+    /// (for example `-m //config:asan`, or `-m asan` if `asan` is declared in the `[alias]`
+    /// buckconfig section). Can be passed multiple times, later modifiers take precedence.
+    #[clap(
+        long = "modifier",
+        short = 'm',
+        number_of_values = 1,
+        value_name = "MODIFIER"
+    )]
+    pub modifiers: Vec<String>,
+
     #[clap(long, ignore_case = true, value_name = "HOST", arg_enum)]
     fake_host: Option<HostPlatformOverride>,
 
@@ -182,6 +233,18 @@ pub struct CommonBuildConfigurationOptions {
 }
 
 impl CommonBuildConfigurationOptions {
+    /// Replaces the `--modifier` argument with one without the `-m` short form, for commands
+    /// where `-m` means something else: `#[clap(mut_arg("modifiers", ...))]`.
+    pub fn modifier_arg_without_short(_arg: clap::Arg) -> clap::Arg {
+        clap::Arg::new("modifiers")
+            .long("modifier")
+            .help("Configuration modifier to apply on top of the target platform")
+            .takes_value(true)
+            .number_of_values(1)
+            .multiple_occurrences(true)
+            .value_name("MODIFIER")
+    }
+
     /// Produces a single, ordered list of config overrides. A `ConfigOverride`
     /// represents either a file, passed via `--config-file`, or a config value,
     /// passed via `-c`/`--config`. The relative order of those are important,
@@ -273,6 +336,7 @@ impl CommonBuildConfigurationOptions {
             config_values: vec![],
             config_files: vec![],
             target_platforms: None,
+            modifiers: vec![],
             fake_host: None,
             fake_arch: None,
             oncall: None,
@@ -338,8 +402,33 @@ pub struct CommonBuildOptions {
     #[clap(long)]
     eager_dep_files: bool,
 
+    /// Upload every action that runs locally, with its inputs, to the CAS, and its result to the
+    /// action cache if it succeeds, whether or not cache uploads are enabled for it. This is meant
+    /// for debugging: any action of the build, including failed ones, can then be fetched and
+    /// reproduced on another machine from its digest.
     #[clap(long)]
     upload_all_actions: bool,
+
+    /// Experimental: Run a sample of the actions twice locally and report the ones whose outputs
+    /// differ between the two runs. Takes the fraction of actions to sample, 0.1 by default.
+    #[clap(
+        long,
+        value_name = "FRACTION",
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "0.1",
+        parse(try_from_str = parse_fraction)
+    )]
+    unstable_determinism_check: Option<f64>,
+}
+
+/// Parses a fraction of things to pick, between 0 and 1.
+fn parse_fraction(s: &str) -> anyhow::Result<f64> {
+    let fraction: f64 = s.parse()?;
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err(anyhow::anyhow!("must be between 0 and 1"));
+    }
+    Ok(fraction)
 }
 
 impl CommonBuildOptions {
@@ -377,6 +466,9 @@ impl CommonBuildOptions {
             eager_dep_files: self.eager_dep_files,
             upload_all_actions: self.upload_all_actions,
             no_remote_cache: self.no_remote_cache,
+            unstable_determinism_check: self.unstable_determinism_check.unwrap_or(0.0),
+            // Set by the commands which take `--materializations`.
+            materialize_intermediates: false,
         }
     }
 }
@@ -413,6 +505,25 @@ pub struct CommonConsoleOptions {
     )]
     pub ui: Vec<UiOptions>,
 
+    /// Hide superconsole components that are shown by default.
+    ///
+    /// Accepts a comma-separated list of components, in addition to the ones listed in the
+    /// `ui.hide` buckconfig. Possible values are:
+    ///
+    ///   session - build id and test session
+    ///   re - remote execution stats
+    ///   io - I/O counters
+    ///   spans - the open spans below the job counts
+    #[clap(
+        long = "--ui-hide",
+        ignore_case = true,
+        multiple = true,
+        number_of_values = 1,
+        use_delimiter = true,
+        arg_enum
+    )]
+    pub ui_hide: Vec<UiComponent>,
+
     #[clap(
         long,
         help = "Disable console interactions",
@@ -426,16 +537,78 @@ impl Default for CommonConsoleOptions {
         Self {
             console_type: ConsoleType::Auto,
             ui: Vec::new(),
+            ui_hide: Vec::new(),
             no_interactive_console: false,
         }
     }
 }
 
+#[derive(Debug, Error)]
+enum UiConfigError {
+    #[error("Invalid value `{1}` for buckconfig `ui.{0}`: {2}")]
+    InvalidValue(&'static str, String, String),
+}
+
+/// How often the `ci` console prints a progress line, unless set by `ui.ci_progress_interval_s`.
+const DEFAULT_CI_PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Console settings from the `[ui]` section of the root buckconfig:
+///
+/// ```text
+/// [ui]
+///   hide = re, io
+///   ci_progress_interval_s = 30
+/// ```
+#[derive(Debug)]
+pub struct UiConfig {
+    pub hide: Vec<UiComponent>,
+    pub ci_progress_interval: Duration,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            hide: Vec::new(),
+            ci_progress_interval: DEFAULT_CI_PROGRESS_INTERVAL,
+        }
+    }
+}
+
+impl UiConfig {
+    /// Reads the settings from the root cell's buckconfig. Errors are ignored and the defaults
+    /// used instead, since invalid buckconfig is reported by the daemon.
+    pub fn from_immediate_config(immediate_config: &ImmediateConfig) -> Self {
+        match immediate_config.get().and_then(Self::from_config) {
+            Ok(ui_config) => ui_config,
+            Err(e) => {
+                tracing::debug!("Not reading `[ui]` buckconfig: {:#}", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn from_config(config: &LegacyBuckConfig) -> anyhow::Result<Self> {
+        let mut ui_config = Self::default();
+        if let Some(hide) = config.get("ui", "hide") {
+            for component in hide.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+                let component = <UiComponent as clap::ArgEnum>::from_str(component, true)
+                    .map_err(|e| UiConfigError::InvalidValue("hide", component.to_owned(), e))?;
+                ui_config.hide.push(component);
+            }
+        }
+        if let Some(interval) = config.parse::<u64>("ui", "ci_progress_interval_s")? {
+            ui_config.ci_progress_interval = Duration::from_secs(interval);
+        }
+        Ok(ui_config)
+    }
+}
+
 impl CommonConsoleOptions {
     pub fn default_ref() -> &'static Self {
         static OPTS: CommonConsoleOptions = CommonConsoleOptions {
             console_type: ConsoleType::Auto,
             ui: vec![],
+            ui_hide: vec![],
             no_interactive_console: false,
         };
         &OPTS
@@ -445,6 +618,7 @@ impl CommonConsoleOptions {
         static OPTS: CommonConsoleOptions = CommonConsoleOptions {
             console_type: ConsoleType::Simple,
             ui: vec![],
+            ui_hide: vec![],
             no_interactive_console: false,
         };
         &OPTS
@@ -454,6 +628,7 @@ impl CommonConsoleOptions {
         static OPTS: CommonConsoleOptions = CommonConsoleOptions {
             console_type: ConsoleType::None,
             ui: vec![],
+            ui_hide: vec![],
             no_interactive_console: false,
         };
         &OPTS
@@ -461,10 +636,13 @@ impl CommonConsoleOptions {
 
     pub fn final_console(&self) -> FinalConsole {
         let is_tty = match self.console_type {
-            ConsoleType::Auto | ConsoleType::Simple => std::io::stderr().is_tty(),
+            ConsoleType::Auto | ConsoleType::Simple | ConsoleType::Quiet => {
+                std::io::stderr().is_tty()
+            }
             ConsoleType::Super => true,
             ConsoleType::SimpleNoTty => false,
             ConsoleType::SimpleTty => true,
+            ConsoleType::Ci => false,
             ConsoleType::None => false,
         };
         if is_tty {
@@ -474,7 +652,7 @@ impl CommonConsoleOptions {
         }
     }
 
-    pub(crate) fn superconsole_config(&self) -> SuperConsoleConfig {
+    pub(crate) fn superconsole_config(&self, ui_config: &UiConfig) -> SuperConsoleConfig {
         let mut config = SuperConsoleConfig::default();
         for option in &self.ui {
             match option {
@@ -482,6 +660,52 @@ impl CommonConsoleOptions {
                 UiOptions::DebugEvents => config.enable_debug_events = true,
             }
         }
+        config.hidden = match self.console_type {
+            ConsoleType::Quiet => vec![
+                UiComponent::Session,
+                UiComponent::Re,
+                UiComponent::Io,
+                UiComponent::Spans,
+            ],
+            _ => ui_config
+                .hide
+                .iter()
+                .chain(&self.ui_hide)
+                .copied()
+                .collect(),
+        };
         config
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::legacy_configs::testing::legacy_buck_config_from_entries;
+
+    use super::*;
+
+    #[test]
+    fn test_ui_config() -> anyhow::Result<()> {
+        let config = legacy_buck_config_from_entries([
+            ("ui", "hide", "re, Spans"),
+            ("ui", "ci_progress_interval_s", "30"),
+        ])?;
+        let ui_config = UiConfig::from_config(&config)?;
+        assert_eq!(vec![UiComponent::Re, UiComponent::Spans], ui_config.hide);
+        assert_eq!(Duration::from_secs(30), ui_config.ci_progress_interval);
+
+        let config = legacy_buck_config_from_entries([("ui", "hide", "dice")])?;
+        assert!(UiConfig::from_config(&config).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_fraction() {
+        assert_eq!(0.1, parse_fraction("0.1").unwrap());
+        assert_eq!(1.0, parse_fraction("1").unwrap());
+        assert!(parse_fraction("1.5").is_err());
+        assert!(parse_fraction("-0.1").is_err());
+        assert!(parse_fraction("NaN").is_err());
+        assert!(parse_fraction("some").is_err());
+    }
+}
@@ -325,6 +325,15 @@ impl BuckdConnectOptions {
         }
     }
 
+    /// Connect to an existing daemon only, and hand the events of the commands to `subscribers`.
+    /// This is for programs driving the daemon, which consume the events themselves.
+    pub fn existing_only_with_subscribers(subscribers: Vec<Box<dyn EventSubscriber>>) -> Self {
+        Self {
+            existing_only: true,
+            subscribers,
+        }
+    }
+
     pub async fn connect(self, paths: &InvocationPaths) -> anyhow::Result<BuckdClientConnector> {
         let daemon_dir = paths.daemon_dir()?;
         buck2_core::fs::fs_util::create_dir_all(&daemon_dir.path)
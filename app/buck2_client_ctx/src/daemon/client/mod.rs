@@ -105,6 +105,11 @@ impl BuckdClientConnector {
             inner: &mut self.client,
         }
     }
+
+    /// The daemon this is connected to.
+    pub fn daemon_info(&self) -> &DaemonProcessInfo {
+        &self.client.info
+    }
 }
 
 pub struct BuckdLifecycleLock {
@@ -561,6 +566,7 @@ macro_rules! wrap_method {
 
 impl<'a> FlushingBuckdClient<'a> {
     stream_method!(aquery, AqueryRequest, AqueryResponse);
+    stream_method!(action_exec, ActionExecRequest, ActionExecResponse);
     stream_method!(cquery, CqueryRequest, CqueryResponse);
     stream_method!(uquery, UqueryRequest, UqueryResponse);
     stream_method!(targets, TargetsRequest, TargetsResponse);
@@ -582,7 +588,9 @@ impl<'a> FlushingBuckdClient<'a> {
 
     bidirectional_stream_method!(lsp, LspRequest, LspResponse);
 
+    oneshot_method!(handshake, HandshakeRequest, HandshakeResponse);
     oneshot_method!(flush_dep_files, FlushDepFilesRequest, GenericResponse);
+    oneshot_method!(import_digests, ImportDigestsRequest, ImportDigestsResponse);
 
     debug_method!(unstable_crash, UnstableCrashRequest, UnstableCrashResponse);
     debug_method!(segfault, SegfaultRequest, SegfaultResponse);
@@ -0,0 +1,44 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use buck2_common::legacy_configs::cells::BuckConfigBasedCells;
+use buck2_common::legacy_configs::LegacyBuckConfig;
+use buck2_common::result::SharedResult;
+use buck2_common::result::ToSharedResultExt;
+use buck2_core::fs::project::ProjectRoot;
+use gazebo::prelude::*;
+use once_cell::sync::OnceCell;
+
+/// The root cell's buckconfig, as read by the client for the few settings it needs without
+/// waiting for the daemon. It is parsed on first use, once per command, and shared by everything
+/// in the client that reads it.
+pub struct ImmediateConfig {
+    project_root: ProjectRoot,
+    config: OnceCell<SharedResult<LegacyBuckConfig>>,
+}
+
+impl ImmediateConfig {
+    pub fn new(project_root: ProjectRoot) -> Self {
+        Self {
+            project_root,
+            config: OnceCell::new(),
+        }
+    }
+
+    /// Fails if the buckconfig couldn't be parsed. Each caller decides whether it can do without
+    /// it: invalid buckconfig is reported by the daemon anyway.
+    pub fn get(&self) -> anyhow::Result<&LegacyBuckConfig> {
+        self.config
+            .get_or_init(|| {
+                BuckConfigBasedCells::parse_immediate_config(&self.project_root).shared_error()
+            })
+            .as_ref()
+            .map_err(|e| e.dupe().into())
+    }
+}
@@ -25,9 +25,11 @@ pub mod exit_result;
 pub mod file_tailer;
 pub mod final_console;
 pub mod find_certs;
+pub mod immediate_config;
 pub mod manifold;
 pub mod path_arg;
 pub mod replayer;
+pub mod restarter;
 pub mod stdin;
 pub mod stdio;
 pub mod stream_value;
@@ -36,6 +38,7 @@ pub mod subscribers;
 pub mod ticker;
 pub mod verbosity;
 pub mod version;
+pub mod version_required;
 pub mod what_ran;
 
 pub const LSP_COMMAND_NAME: &str = "lsp";
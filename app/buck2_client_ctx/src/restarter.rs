@@ -0,0 +1,281 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Restarts the daemon and retries the command when a command fails because the daemon's state is
+//! corrupted, rather than asking users to `buck2 killall`. That's the case when the daemon reports
+//! it, and when the daemon died while running the command, e.g. because DICE panicked: panics
+//! abort the daemon, so it can't report them itself.
+//!
+//! Only commands which are safe to run twice are retried, and at most once: the retried
+//! invocation is passed `--restarted-after` with the reason of the restart, which is also recorded
+//! in its invocation record.
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use buck2_common::invocation_paths::InvocationPaths;
+use buck2_events::BuckEvent;
+use gazebo::prelude::*;
+
+use crate::daemon::client::connect::BuckdConnectOptions;
+use crate::exit_result::ExitResult;
+use crate::subscribers::subscriber::EventSubscriber;
+
+/// The flag passed to a command retried after restarting the daemon.
+const RESTARTED_AFTER_FLAG: &str = "--restarted-after";
+
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq)]
+pub(crate) enum RestartReason {
+    /// The daemon reported that its state is corrupted.
+    Corrupted(buck2_data::DaemonCorruption),
+    /// The daemon exited without sending the result of the command, nor telling why it shut down.
+    DaemonDied,
+}
+
+impl RestartReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            RestartReason::Corrupted(buck2_data::DaemonCorruption::MaterializerCorruption) => {
+                "materializer_corruption"
+            }
+            RestartReason::DaemonDied => "daemon_died",
+        }
+    }
+}
+
+impl fmt::Display for RestartReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestartReason::Corrupted(buck2_data::DaemonCorruption::MaterializerCorruption) => {
+                write!(f, "the materializer state is inconsistent")
+            }
+            RestartReason::DaemonDied => write!(f, "the daemon died while running the command"),
+        }
+    }
+}
+
+/// What the command's events and result tell about the state of the daemon.
+#[derive(Default)]
+struct Observed {
+    corruption: Option<buck2_data::DaemonCorruption>,
+    /// The daemon was shut down on purpose, e.g. by `buck2 kill`.
+    shutdown: bool,
+    command_result: bool,
+}
+
+/// Watches the command's events for the daemon reporting that its state is corrupted, or dying.
+#[derive(Clone, Dupe, Default)]
+pub(crate) struct Restarter {
+    observed: Arc<Mutex<Observed>>,
+}
+
+impl Restarter {
+    fn observe(&self, event: &BuckEvent) {
+        if let buck2_data::buck_event::Data::Instant(instant) = event.data() {
+            let mut observed = self.observed.lock().unwrap();
+            match &instant.data {
+                Some(buck2_data::instant_event::Data::DaemonStateCorrupted(corrupted)) => {
+                    if let Some(reason) = buck2_data::DaemonCorruption::from_i32(corrupted.reason) {
+                        observed.corruption.get_or_insert(reason);
+                    }
+                }
+                Some(buck2_data::instant_event::Data::DaemonShutdown(..)) => {
+                    observed.shutdown = true;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn observe_command_result(&self) {
+        self.observed.lock().unwrap().command_result = true;
+    }
+
+    /// Why the daemon needs restarting, as far as the command's events tell. A daemon that may
+    /// have died must still be checked with `daemon_is_gone`.
+    fn reason(&self) -> Option<RestartReason> {
+        let observed = self.observed.lock().unwrap();
+        match observed.corruption {
+            Some(corruption) => Some(RestartReason::Corrupted(corruption)),
+            None if !observed.command_result && !observed.shutdown => {
+                Some(RestartReason::DaemonDied)
+            }
+            None => None,
+        }
+    }
+
+    pub(crate) fn subscriber(&self) -> Box<dyn EventSubscriber> {
+        box RestartDetector {
+            restarter: self.dupe(),
+        }
+    }
+
+    /// Called once the command is done: if it failed because the daemon's state is corrupted or
+    /// the daemon died, and it is not already a retry, kill the daemon and re-run the command.
+    /// Otherwise, return the result of the command.
+    pub(crate) async fn maybe_restart(
+        &self,
+        result: ExitResult,
+        paths: &InvocationPaths,
+        restarted_after: Option<&str>,
+    ) -> ExitResult {
+        if result.is_success() || restarted_after.is_some() {
+            return result;
+        }
+        let reason = match self.reason() {
+            Some(reason) => reason,
+            None => return result,
+        };
+        let prog = match std::env::current_exe() {
+            Ok(prog) => prog.to_string_lossy().into_owned(),
+            Err(_) => return result,
+        };
+
+        let daemon = BuckdConnectOptions::existing_only_no_console()
+            .connect(paths)
+            .await;
+        if reason == RestartReason::DaemonDied && daemon.is_ok() {
+            // The command failed for some other reason, e.g. a connection error.
+            return result;
+        }
+
+        crate::eprintln!(
+            "The command failed because {}. Restarting the daemon and retrying the command.",
+            reason
+        )?;
+        // The daemon may already be gone, e.g. if it crashed.
+        if let Ok(mut client) = daemon {
+            let _ignored = client
+                .with_flushing()
+                .kill(&format!("restarting the daemon because {}", reason))
+                .await;
+        }
+
+        ExitResult::exec(prog, retry_argv(std::env::args().collect(), reason), None)
+    }
+}
+
+/// The arguments of the retried command. The flag goes right after the program, where it can't be
+/// mistaken for an argument passed through to something else, e.g. after `--` in `buck2 run`.
+fn retry_argv(mut argv: Vec<String>, reason: RestartReason) -> Vec<String> {
+    let at = argv.len().min(1);
+    argv.insert(at, format!("{}={}", RESTARTED_AFTER_FLAG, reason.as_str()));
+    argv
+}
+
+struct RestartDetector {
+    restarter: Restarter,
+}
+
+#[async_trait]
+impl EventSubscriber for RestartDetector {
+    async fn handle_events(&mut self, events: &[Arc<BuckEvent>]) -> anyhow::Result<()> {
+        for event in events {
+            self.restarter.observe(event);
+        }
+        Ok(())
+    }
+
+    async fn handle_command_result(
+        &mut self,
+        _result: &cli_proto::CommandResult,
+    ) -> anyhow::Result<()> {
+        self.restarter.observe_command_result();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use buck2_events::trace::TraceId;
+
+    use super::*;
+
+    fn instant(data: buck2_data::instant_event::Data) -> BuckEvent {
+        BuckEvent::new(
+            SystemTime::now(),
+            TraceId::new(),
+            None,
+            None,
+            buck2_data::InstantEvent { data: Some(data) }.into(),
+        )
+    }
+
+    fn corrupted(reason: buck2_data::DaemonCorruption) -> BuckEvent {
+        instant(
+            buck2_data::DaemonStateCorrupted {
+                reason: reason as i32,
+            }
+            .into(),
+        )
+    }
+
+    #[test]
+    fn test_reason() {
+        let restarter = Restarter::default();
+        restarter.observe(&instant(
+            buck2_data::ConsoleMessage {
+                message: "The daemon state is corrupted".to_owned(),
+            }
+            .into(),
+        ));
+        // No result yet, so the daemon may have died.
+        assert_eq!(Some(RestartReason::DaemonDied), restarter.reason());
+        restarter.observe_command_result();
+        assert_eq!(None, restarter.reason());
+
+        restarter.observe(&corrupted(
+            buck2_data::DaemonCorruption::MaterializerCorruption,
+        ));
+        assert_eq!(
+            Some(RestartReason::Corrupted(
+                buck2_data::DaemonCorruption::MaterializerCorruption
+            )),
+            restarter.reason()
+        );
+    }
+
+    #[test]
+    fn test_no_restart_after_shutdown() {
+        let restarter = Restarter::default();
+        restarter.observe(&instant(
+            buck2_data::DaemonShutdown {
+                reason: "killed".to_owned(),
+                ..Default::default()
+            }
+            .into(),
+        ));
+        assert_eq!(None, restarter.reason());
+    }
+
+    #[test]
+    fn test_retry_argv() {
+        assert_eq!(
+            vec![
+                "buck2",
+                "--restarted-after=daemon_died",
+                "run",
+                "//:bin",
+                "--",
+                "arg"
+            ],
+            retry_argv(
+                ["buck2", "run", "//:bin", "--", "arg"]
+                    .iter()
+                    .map(|s| (*s).to_owned())
+                    .collect(),
+                RestartReason::DaemonDied
+            )
+        );
+    }
+}
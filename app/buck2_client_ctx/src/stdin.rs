@@ -23,7 +23,9 @@ use gazebo::dupe::Dupe;
 use parking_lot::Condvar;
 use parking_lot::Mutex;
 use pin_project::pin_project;
+use tokio::io::AsyncBufReadExt;
 use tokio::io::AsyncRead;
+use tokio::io::BufReader;
 use tokio::io::ReadBuf;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
@@ -120,6 +122,20 @@ impl Stdin {
 
         ConsoleInteractionStream::new(self)
     }
+
+    /// Read newline-delimited literals (e.g. target patterns, for commands taking `--stdin`),
+    /// skipping blank lines and lines starting with `#`.
+    pub async fn read_literals(&mut self) -> anyhow::Result<Vec<String>> {
+        let mut lines = BufReader::new(self).lines();
+        let mut literals = Vec::new();
+        while let Some(line) = lines.next_line().await? {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                literals.push(line.to_owned());
+            }
+        }
+        Ok(literals)
+    }
 }
 
 fn read_and_forward(
@@ -229,4 +245,18 @@ mod tests {
         std::thread::sleep(std::time::Duration::from_millis(100));
         assert!(!thread.is_finished());
     }
+
+    #[tokio::test]
+    async fn test_read_literals() -> anyhow::Result<()> {
+        // A small buffer, so that lines are split across reads.
+        let (mut stdin, _thread) = Stdin::new_for_reader(
+            || io::Cursor::new(b"//foo:bar\n\n# comment\n  //foo:baz  \r\n//qux:quux".to_vec()),
+            3,
+        );
+        assert_eq!(
+            stdin.read_literals().await?,
+            vec!["//foo:bar", "//foo:baz", "//qux:quux"]
+        );
+        Ok(())
+    }
 }
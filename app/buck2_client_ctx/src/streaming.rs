@@ -16,15 +16,19 @@ use crate::client_ctx::ClientCommandContext;
 use crate::common::CommonBuildConfigurationOptions;
 use crate::common::CommonConsoleOptions;
 use crate::common::CommonDaemonCommandOptions;
+use crate::common::UiConfig;
 use crate::daemon::client::connect::BuckdConnectOptions;
 use crate::daemon::client::BuckdClientConnector;
 use crate::exit_result::ExitResult;
 use crate::exit_result::FailureExitCode;
+use crate::restarter::Restarter;
 use crate::subscribers::get::get_console_with_root;
 use crate::subscribers::get::try_get_build_id_writer;
 use crate::subscribers::get::try_get_event_log_subscriber;
 use crate::subscribers::get::try_get_re_log_subscriber;
+use crate::subscribers::phase_profile::PhaseProfileReporter;
 use crate::subscribers::recorder::try_get_invocation_recorder;
+use crate::subscribers::resource_summary::ResourceSummaryReporter;
 use crate::subscribers::subscriber::EventSubscriber;
 use crate::subscribers::superconsole::StatefulSuperConsole;
 use crate::subscribers::superconsole::SuperConsoleConfig;
@@ -35,12 +39,13 @@ fn default_subscribers<T: StreamingCommand>(
     ctx: &ClientCommandContext,
 ) -> anyhow::Result<Vec<Box<dyn EventSubscriber>>> {
     let console_opts = cmd.console_opts();
+    let ui_config = UiConfig::from_immediate_config(&ctx.immediate_config);
     let mut subscribers = vec![];
     let root = StatefulSuperConsole::default_layout(
         T::COMMAND_NAME,
         SuperConsoleConfig {
             sandwiched: cmd.extra_superconsole_component(),
-            ..console_opts.superconsole_config()
+            ..console_opts.superconsole_config(&ui_config)
         },
     );
 
@@ -53,7 +58,8 @@ fn default_subscribers<T: StreamingCommand>(
         show_waiting_message,
         ctx.replay_speed,
         root,
-        console_opts.superconsole_config(),
+        console_opts.superconsole_config(&ui_config),
+        ui_config.ci_progress_interval,
     )? {
         subscribers.push(v)
     }
@@ -71,6 +77,12 @@ fn default_subscribers<T: StreamingCommand>(
     if let Some(recorder) = try_get_invocation_recorder(ctx, cmd.sanitized_argv())? {
         subscribers.push(recorder);
     }
+    if cmd.profile_phases() {
+        subscribers.push(box PhaseProfileReporter::default());
+    }
+    if cmd.event_log_opts().print_resource_summary {
+        subscribers.push(box ResourceSummaryReporter::default());
+    }
     Ok(subscribers)
 }
 
@@ -109,9 +121,21 @@ pub trait StreamingCommand: Sized + Send + Sync {
         None
     }
 
+    /// Whether to print a breakdown of the time spent in each phase of the command when it's done.
+    fn profile_phases(&self) -> bool {
+        false
+    }
+
     fn sanitized_argv(&self) -> Vec<String> {
         std::env::args().collect()
     }
+
+    /// Whether the command can be run again after restarting the daemon, if it failed while the
+    /// daemon's state was corrupted. Only commands without side effects beyond `buck-out`, which
+    /// don't consume stdin, should return `true`. Defaults to `false`.
+    fn retry_after_restart(&self) -> bool {
+        false
+    }
 }
 
 /// Just provides a common interface for buck subcommands for us to interact with here.
@@ -125,9 +149,18 @@ impl<T: StreamingCommand> BuckSubcommand for T {
     fn exec(self, matches: &clap::ArgMatches, ctx: ClientCommandContext) -> ExitResult {
         ctx.with_runtime(async move |mut ctx| {
             let work = async {
+                let restarter = Restarter::default();
+                let mut subscribers = default_subscribers(&self, &ctx)?;
+                // Don't restart the daemon when replaying a command, or when it runs in process.
+                let restartable = self.retry_after_restart()
+                    && ctx.replayer.is_none()
+                    && ctx.start_in_process_daemon.is_none();
+                if restartable {
+                    subscribers.push(restarter.subscriber());
+                }
                 let mut connect_options = BuckdConnectOptions {
                     existing_only: T::existing_only(),
-                    subscribers: default_subscribers(&self, &ctx)?,
+                    subscribers,
                 };
 
                 let buckd = match (ctx.replayer.take(), ctx.start_in_process_daemon.take()) {
@@ -147,7 +180,15 @@ impl<T: StreamingCommand> BuckSubcommand for T {
                     }
                 };
 
-                self.exec_impl(buckd, matches, ctx).await
+                let paths = ctx.paths.clone();
+                let restarted_after = ctx.restarted_after.clone();
+                let result = self.exec_impl(buckd, matches, ctx).await;
+                if !restartable {
+                    return result;
+                }
+                restarter
+                    .maybe_restart(result, &paths, restarted_after.as_deref())
+                    .await
             };
 
             // Race our work with a ctrl+c future. If we hit ctrl+c, then we'll drop the work
@@ -0,0 +1,283 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+
+use buck2_events::span::SpanId;
+use buck2_events::BuckEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RunningAction {
+    start: Instant,
+    expected: Duration,
+    /// How long the actions that are still pending because they depend on this one are expected
+    /// to take, along the longest chain of them.
+    pending: Duration,
+}
+
+/// How far along the running actions are, estimated from how long the daemon expects each of
+/// them to take (based on previous runs).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ProgressEstimate {
+    /// Percentage of the expected work that is done, counting both finished actions and the
+    /// time spent so far on running ones, out of the work that also includes the actions pending
+    /// on the running ones.
+    pub(crate) percent: u8,
+    /// Time until the running action expected to finish last does, followed by the actions
+    /// pending on it.
+    pub(crate) remaining: Duration,
+}
+
+/// Tracks the actions that have an expected duration, to estimate progress by time rather than by
+/// the number of jobs, which is misleading when a few long actions (e.g. links) dominate the build.
+/// Actions that never ran before don't have an expected duration and aren't counted.
+#[derive(Default)]
+pub(crate) struct ActionProgress {
+    running: HashMap<SpanId, RunningAction>,
+    /// Sum of the expected durations of the actions that finished.
+    finished: Duration,
+}
+
+impl ActionProgress {
+    pub(crate) fn handle_event(&mut self, event: &BuckEvent) {
+        self.handle_event_at(event, Instant::now())
+    }
+
+    fn handle_event_at(&mut self, event: &BuckEvent, now: Instant) {
+        use buck2_data::span_end_event;
+        use buck2_data::span_start_event;
+
+        let span_id = match event.span_id() {
+            Some(span_id) => span_id,
+            None => return,
+        };
+
+        if let Some(start) = event.span_start_event() {
+            if let Some(span_start_event::Data::ActionExecution(action)) = &start.data {
+                let expected = action
+                    .expected_duration
+                    .clone()
+                    .and_then(|d| Duration::try_from(d).ok());
+                if let Some(expected) = expected {
+                    let critical_path = action
+                        .expected_critical_path
+                        .clone()
+                        .and_then(|d| Duration::try_from(d).ok())
+                        .unwrap_or_default();
+                    self.running.insert(
+                        span_id,
+                        RunningAction {
+                            start: now,
+                            expected,
+                            pending: critical_path.saturating_sub(expected),
+                        },
+                    );
+                }
+            }
+        } else if let Some(end) = event.span_end_event() {
+            if let Some(span_end_event::Data::ActionExecution(..)) = &end.data {
+                if let Some(action) = self.running.remove(&span_id) {
+                    self.finished += action.expected;
+                }
+            }
+        }
+    }
+
+    /// `None` if no running action has an expected duration.
+    pub(crate) fn estimate(&self) -> Option<ProgressEstimate> {
+        self.estimate_at(Instant::now())
+    }
+
+    fn estimate_at(&self, now: Instant) -> Option<ProgressEstimate> {
+        if self.running.is_empty() {
+            return None;
+        }
+
+        let mut done = self.finished;
+        let mut total = self.finished;
+        let mut remaining = Duration::ZERO;
+        let mut pending = Duration::ZERO;
+        for action in self.running.values() {
+            let elapsed = now.saturating_duration_since(action.start);
+            // An action taking longer than expected is assumed to be just about done.
+            done += elapsed.min(action.expected);
+            total += action.expected;
+            remaining = remaining.max(action.expected.saturating_sub(elapsed) + action.pending);
+            pending = pending.max(action.pending);
+        }
+        // The chains of actions pending on different running actions usually end in the same
+        // ones (e.g. the final link), so only the longest is counted.
+        total += pending;
+
+        let percent = if total.is_zero() {
+            100
+        } else {
+            // Never claim 100% while actions are still running.
+            ((done.as_secs_f64() / total.as_secs_f64() * 100.0) as u8).min(99)
+        };
+
+        Some(ProgressEstimate { percent, remaining })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use buck2_events::trace::TraceId;
+
+    use super::*;
+
+    fn action_start(
+        span_id: SpanId,
+        expected: Option<Duration>,
+        critical_path: Option<Duration>,
+    ) -> BuckEvent {
+        BuckEvent::new(
+            SystemTime::now(),
+            TraceId::new(),
+            Some(span_id),
+            None,
+            buck2_data::SpanStartEvent {
+                data: Some(
+                    buck2_data::ActionExecutionStart {
+                        expected_duration: expected.map(|d| d.try_into().unwrap()),
+                        expected_critical_path: critical_path.map(|d| d.try_into().unwrap()),
+                        ..Default::default()
+                    }
+                    .into(),
+                ),
+            }
+            .into(),
+        )
+    }
+
+    fn action_end(span_id: SpanId) -> BuckEvent {
+        BuckEvent::new(
+            SystemTime::now(),
+            TraceId::new(),
+            Some(span_id),
+            None,
+            buck2_data::SpanEndEvent {
+                data: Some(buck2_data::ActionExecutionEnd::default().into()),
+                ..Default::default()
+            }
+            .into(),
+        )
+    }
+
+    #[test]
+    fn test_estimate() {
+        let t0 = Instant::now();
+        let mut progress = ActionProgress::default();
+        assert_eq!(None, progress.estimate_at(t0));
+
+        let link = SpanId::new();
+        let compile = SpanId::new();
+        let unknown = SpanId::new();
+        progress.handle_event_at(&action_start(link, Some(Duration::from_secs(60)), None), t0);
+        progress.handle_event_at(
+            &action_start(compile, Some(Duration::from_secs(20)), None),
+            t0,
+        );
+        progress.handle_event_at(&action_start(unknown, None, None), t0);
+
+        // The long link dominates what's left.
+        let t1 = t0 + Duration::from_secs(20);
+        assert_eq!(
+            Some(ProgressEstimate {
+                percent: 50,
+                remaining: Duration::from_secs(40),
+            }),
+            progress.estimate_at(t1)
+        );
+
+        progress.handle_event_at(&action_end(compile), t1);
+        progress.handle_event_at(&action_end(unknown), t1);
+        let t2 = t0 + Duration::from_secs(50);
+        assert_eq!(
+            Some(ProgressEstimate {
+                percent: 87,
+                remaining: Duration::from_secs(10),
+            }),
+            progress.estimate_at(t2)
+        );
+
+        // Taking longer than expected.
+        let t3 = t0 + Duration::from_secs(90);
+        assert_eq!(
+            Some(ProgressEstimate {
+                percent: 99,
+                remaining: Duration::ZERO,
+            }),
+            progress.estimate_at(t3)
+        );
+
+        progress.handle_event_at(&action_end(link), t3);
+        assert_eq!(None, progress.estimate_at(t3));
+    }
+
+    #[test]
+    fn test_estimate_with_pending_actions() {
+        let t0 = Instant::now();
+        let mut progress = ActionProgress::default();
+
+        // Two compiles that a 60s link is pending on.
+        let compile1 = SpanId::new();
+        let compile2 = SpanId::new();
+        progress.handle_event_at(
+            &action_start(
+                compile1,
+                Some(Duration::from_secs(20)),
+                Some(Duration::from_secs(80)),
+            ),
+            t0,
+        );
+        progress.handle_event_at(
+            &action_start(
+                compile2,
+                Some(Duration::from_secs(10)),
+                Some(Duration::from_secs(70)),
+            ),
+            t0,
+        );
+
+        // The link counts once, and is still ahead once the compiles are done.
+        let t1 = t0 + Duration::from_secs(10);
+        assert_eq!(
+            Some(ProgressEstimate {
+                percent: 22,
+                remaining: Duration::from_secs(70),
+            }),
+            progress.estimate_at(t1)
+        );
+
+        progress.handle_event_at(&action_end(compile1), t0 + Duration::from_secs(20));
+        progress.handle_event_at(&action_end(compile2), t0 + Duration::from_secs(20));
+        let link = SpanId::new();
+        let t2 = t0 + Duration::from_secs(20);
+        progress.handle_event_at(
+            &action_start(
+                link,
+                Some(Duration::from_secs(60)),
+                Some(Duration::from_secs(60)),
+            ),
+            t2,
+        );
+        assert_eq!(
+            Some(ProgressEstimate {
+                percent: 33,
+                remaining: Duration::from_secs(60),
+            }),
+            progress.estimate_at(t2)
+        );
+    }
+}
@@ -118,7 +118,7 @@ pub(crate) fn display_action_owner(
     }
 }
 
-pub(crate) fn display_action_key(
+pub fn display_action_key(
     action_key: &ActionKey,
     opts: TargetDisplayOptions,
 ) -> anyhow::Result<String> {
@@ -251,6 +251,8 @@ pub(crate) fn display_event(
             Data::Materialization(..) => Ok("materializing".to_owned()),
             Data::DiceCriticalSection(..) => Err(ParseEventError::UnexpectedEvent.into()),
             Data::DiceBlockConcurrentCommand(..) => Err(ParseEventError::UnexpectedEvent.into()),
+            Data::ResolveTargetPatterns(..) => Ok("Resolving target patterns".to_owned()),
+            Data::ConfigureTarget(..) => Err(ParseEventError::UnexpectedEvent.into()),
             Data::Fake(fake) => Ok(format!("{} -- speak of the devil", fake.caramba)),
         };
 
@@ -306,6 +308,7 @@ pub fn display_executor_stage(
             use buck2_data::local_stage::Stage;
 
             match local.stage.as_ref().context("local stage is missing")? {
+                Stage::Queued(queued) if queued.prefetching_inputs => "local_queued_prefetching",
                 Stage::Queued(..) => "local_queued",
                 Stage::Execute(..) => "local_execute",
                 Stage::MaterializeInputs(..) => "local_materialize_inputs",
@@ -0,0 +1,196 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Chunked zstd event logs.
+//!
+//! A chunked log is a sequence of independent zstd frames ("chunks"). A new chunk is started
+//! before a snapshot event, so a reader can decompress the log starting from any chunk. When the
+//! log is closed, the offsets of the chunks are written at the end of the file in a zstd
+//! skippable frame, which regular zstd decoders ignore:
+//!
+//! ```text
+//! magic: u32 LE | size: u32 LE | offsets: [u64 LE] | count: u32 LE | INDEX_TAG
+//! ```
+//!
+//! Logs of commands which did not exit cleanly have no index, and have to be read in full.
+
+use std::io::SeekFrom;
+
+use anyhow::Context as _;
+use async_compression::tokio::write::ZstdEncoder;
+use thiserror::Error;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncSeekExt;
+use tokio::io::AsyncWriteExt;
+
+/// Magic number of the zstd skippable frame holding the index.
+const SKIPPABLE_FRAME_MAGIC: u32 = 0x184D2A5E;
+
+/// Marks the end of a file which has an index, to tell it apart from a truncated log.
+const INDEX_TAG: &[u8; 4] = b"B2IX";
+
+/// Don't start chunks smaller than this (uncompressed), snapshots are frequent and compressing
+/// them separately would hurt the compression ratio.
+const MIN_CHUNK_SIZE: u64 = 1 << 20;
+
+#[derive(Error, Debug)]
+enum ChunkedLogError {
+    #[error("Chunked event log was already closed")]
+    Closed,
+    #[error("Chunked event log has an invalid index")]
+    InvalidIndex,
+}
+
+pub(crate) struct ChunkedZstdWriter {
+    /// `None` once the log is closed.
+    encoder: Option<ZstdEncoder<File>>,
+    /// Uncompressed bytes written in the current chunk.
+    chunk_size: u64,
+    /// Offsets of the chunks started after a snapshot.
+    chunk_offsets: Vec<u64>,
+}
+
+fn new_encoder(file: File) -> ZstdEncoder<File> {
+    ZstdEncoder::with_quality(file, async_compression::Level::Default)
+}
+
+impl ChunkedZstdWriter {
+    pub(crate) fn new(file: File) -> Self {
+        Self {
+            encoder: Some(new_encoder(file)),
+            chunk_size: 0,
+            chunk_offsets: Vec::new(),
+        }
+    }
+
+    fn encoder(&mut self) -> anyhow::Result<&mut ZstdEncoder<File>> {
+        self.encoder
+            .as_mut()
+            .ok_or_else(|| ChunkedLogError::Closed.into())
+    }
+
+    pub(crate) async fn write_all(&mut self, buf: &[u8]) -> anyhow::Result<()> {
+        self.encoder()?.write_all(buf).await?;
+        self.chunk_size += buf.len() as u64;
+        Ok(())
+    }
+
+    pub(crate) async fn flush(&mut self) -> anyhow::Result<()> {
+        self.encoder()?.flush().await?;
+        Ok(())
+    }
+
+    /// Terminate the current zstd frame and return the underlying file.
+    async fn finish_chunk(&mut self) -> anyhow::Result<File> {
+        let mut encoder = self.encoder.take().context(ChunkedLogError::Closed)?;
+        // This also flushes the file, which is all shutting down a `File` does.
+        encoder.shutdown().await?;
+        Ok(encoder.into_inner())
+    }
+
+    /// Called before writing a snapshot: start a new chunk, unless the current one is too small.
+    pub(crate) async fn start_chunk(&mut self) -> anyhow::Result<()> {
+        if self.chunk_size < MIN_CHUNK_SIZE {
+            return Ok(());
+        }
+        let file = self.finish_chunk().await?;
+        let offset = file.metadata().await?.len();
+        self.chunk_offsets.push(offset);
+        self.encoder = Some(new_encoder(file));
+        self.chunk_size = 0;
+        Ok(())
+    }
+
+    /// Terminate the last chunk and write the index.
+    pub(crate) async fn shutdown(&mut self) -> anyhow::Result<()> {
+        let mut file = self.finish_chunk().await?;
+        file.write_all(&encode_index(&self.chunk_offsets)).await?;
+        file.shutdown().await?;
+        Ok(())
+    }
+}
+
+fn encode_index(offsets: &[u64]) -> Vec<u8> {
+    let payload_len = offsets.len() * 8 + 4 + INDEX_TAG.len();
+    let mut buf = Vec::with_capacity(8 + payload_len);
+    buf.extend_from_slice(&SKIPPABLE_FRAME_MAGIC.to_le_bytes());
+    buf.extend_from_slice(&(payload_len as u32).to_le_bytes());
+    for offset in offsets {
+        buf.extend_from_slice(&offset.to_le_bytes());
+    }
+    buf.extend_from_slice(&(offsets.len() as u32).to_le_bytes());
+    buf.extend_from_slice(INDEX_TAG);
+    buf
+}
+
+/// Read the chunk offsets from the end of a chunked log. Returns `None` if the log has no index.
+pub(crate) async fn read_index(file: &mut File) -> anyhow::Result<Option<Vec<u64>>> {
+    let len = file.metadata().await?.len();
+    let trailer_len = (4 + INDEX_TAG.len()) as u64;
+    if len < 8 + trailer_len {
+        return Ok(None);
+    }
+
+    let mut trailer = [0; 8];
+    file.seek(SeekFrom::Start(len - trailer_len)).await?;
+    file.read_exact(&mut trailer).await?;
+    if &trailer[4..] != INDEX_TAG {
+        return Ok(None);
+    }
+
+    let count = u32::from_le_bytes(trailer[..4].try_into().unwrap()) as u64;
+    let payload_len = count * 8 + trailer_len;
+    let frame_start = len
+        .checked_sub(8 + payload_len)
+        .context(ChunkedLogError::InvalidIndex)?;
+
+    let mut frame = vec![0; (8 + payload_len) as usize];
+    file.seek(SeekFrom::Start(frame_start)).await?;
+    file.read_exact(&mut frame).await?;
+
+    let magic = u32::from_le_bytes(frame[..4].try_into().unwrap());
+    let size = u32::from_le_bytes(frame[4..8].try_into().unwrap()) as u64;
+    if magic != SKIPPABLE_FRAME_MAGIC || size != payload_len {
+        return Err(ChunkedLogError::InvalidIndex.into());
+    }
+
+    Ok(Some(
+        frame[8..8 + count as usize * 8]
+            .chunks_exact(8)
+            .map(|offset| u64::from_le_bytes(offset.try_into().unwrap()))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_index_roundtrip() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("index");
+
+        let offsets = vec![3, 1 << 40];
+        let mut data = b"abc".to_vec();
+        data.extend(encode_index(&offsets));
+        tokio::fs::write(&path, &data).await?;
+        let mut file = File::open(&path).await?;
+        assert_eq!(Some(offsets), read_index(&mut file).await?);
+
+        tokio::fs::write(&path, b"no index in this file").await?;
+        let mut file = File::open(&path).await?;
+        assert_eq!(None, read_index(&mut file).await?);
+
+        Ok(())
+    }
+}
@@ -7,10 +7,13 @@
  * of this source tree.
  */
 
+mod chunked;
 pub mod file_names;
+pub(crate) mod sink;
 pub mod upload;
 
 use std::io::Cursor;
+use std::io::SeekFrom;
 use std::mem;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -25,6 +28,7 @@ use async_trait::async_trait;
 use buck2_core::env_helper::EnvHelper;
 use buck2_core::fs::async_fs_util;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::abs_path::AbsPath;
 use buck2_core::fs::paths::abs_path::AbsPathBuf;
 use buck2_core::fs::working_dir::WorkingDir;
 use buck2_data::buck_event;
@@ -47,6 +51,7 @@ use thiserror::Error;
 use tokio::fs::OpenOptions;
 use tokio::io::AsyncBufReadExt;
 use tokio::io::AsyncRead;
+use tokio::io::AsyncSeekExt;
 use tokio::io::AsyncWrite;
 use tokio::io::AsyncWriteExt;
 use tokio::io::BufReader;
@@ -57,8 +62,11 @@ use tokio_util::codec::FramedRead;
 use crate::cleanup_ctx::AsyncCleanupContext;
 use crate::stream_value::StreamValue;
 use crate::stream_value::StreamValueRef;
+use crate::subscribers::event_log::chunked::read_index;
+use crate::subscribers::event_log::chunked::ChunkedZstdWriter;
 use crate::subscribers::event_log::file_names::get_logfile_name;
 use crate::subscribers::event_log::file_names::remove_old_logs;
+use crate::subscribers::event_log::sink::LogSinks;
 use crate::subscribers::event_log::upload::log_upload;
 use crate::subscribers::event_log::upload::LogUploadError;
 use crate::subscribers::subscriber::EventSubscriber;
@@ -125,6 +133,13 @@ impl Encoding {
         compression: Compression::Zstd,
         extensions: &[".pb.zst"],
     };
+
+    const PROTO_ZSTD_CHUNKED: Encoding = Encoding {
+        mode: LogMode::Protobuf,
+        compression: Compression::ZstdChunked,
+        // Not `.pb.zst`: older readers would stop after the first chunk.
+        extensions: &[".pbc.zst"],
+    };
 }
 
 const KNOWN_ENCODINGS: &[Encoding] = &[
@@ -137,6 +152,7 @@ const KNOWN_ENCODINGS: &[Encoding] = &[
     Encoding::PROTO,
     Encoding::PROTO_GZIP,
     Encoding::PROTO_ZSTD,
+    Encoding::PROTO_ZSTD_CHUNKED,
 ];
 
 type EventLogWriter = Box<dyn AsyncWrite + Send + Sync + Unpin + 'static>;
@@ -248,28 +264,19 @@ impl EventLogPathBuf {
             Frame::Invocation(inv) => Invocation {
                 command_line_args: inv.command_line_args,
                 working_dir: inv.working_dir,
+                trace_id: inv.trace_id,
+                parent_trace_id: inv.parent_trace_id,
             },
             Frame::Value(_) => {
                 return Err(anyhow::anyhow!("Expected Invocation, found StreamValue"));
             }
         };
 
-        let events = stream.and_then(|frame| async move {
-            match frame {
-                Frame::Invocation(_) => {
-                    Err(anyhow::anyhow!("Expected StreamValue, found Invocation"))
-                }
-                Frame::Value(val) => match val.progress {
-                    Some(command_progress::Progress::Event(event)) => Ok(StreamValue::Event(event)),
-                    Some(command_progress::Progress::Result(result)) => {
-                        Ok(StreamValue::Result(result))
-                    }
-                    None => Err(anyhow::anyhow!("Event type not recognized")),
-                },
-            }
-        });
+        Ok((invocation, decode_frames(stream)))
+    }
 
-        Ok((invocation, events.boxed()))
+    pub fn path(&self) -> &AbsPath {
+        &self.path
     }
 
     /// Read the invocation line then the event stream.
@@ -282,6 +289,36 @@ impl EventLogPathBuf {
         }
     }
 
+    /// Like `unpack_stream`, but for chunked logs with an index, only read the events from the
+    /// last chunk, which starts with a snapshot. Other logs are read in full.
+    pub async fn unpack_stream_from_last_snapshot(
+        &self,
+    ) -> anyhow::Result<(
+        Invocation,
+        Pin<Box<dyn Stream<Item = anyhow::Result<StreamValue>> + Send>>,
+    )> {
+        if let Compression::ZstdChunked = self.encoding.compression {
+            let mut file = async_fs_util::open(&self.path).await?;
+            if let Some(offset) = read_index(&mut file).await?.and_then(|o| o.last().copied()) {
+                // The invocation is at the start of the first chunk.
+                let (invocation, _) = self.unpack_stream_protobuf().await?;
+                file.seek(SeekFrom::Start(offset)).await?;
+                let stream = FramedRead::new(
+                    chunked_decoder(file),
+                    EventLogDecoder {
+                        saw_invocation: true,
+                    },
+                );
+                return Ok((invocation, decode_frames(stream)));
+            }
+        }
+
+        match self.encoding.mode {
+            LogMode::Json => self.unpack_stream_json().await,
+            LogMode::Protobuf => self.unpack_stream_protobuf().await,
+        }
+    }
+
     async fn open(&self) -> anyhow::Result<EventLogReader> {
         tracing::info!(
             "Open {} using encoding {:?}",
@@ -294,6 +331,7 @@ impl EventLogPathBuf {
             Compression::None => box file as EventLogReader,
             Compression::Gzip => box GzipDecoder::new(BufReader::new(file)) as EventLogReader,
             Compression::Zstd => box ZstdDecoder::new(BufReader::new(file)) as EventLogReader,
+            Compression::ZstdChunked => box chunked_decoder(file) as EventLogReader,
         };
 
         Ok(file)
@@ -325,14 +363,78 @@ impl EventLogPathBuf {
     }
 }
 
+fn decode_frames(
+    frames: impl Stream<Item = anyhow::Result<Frame>> + Send + 'static,
+) -> Pin<Box<dyn Stream<Item = anyhow::Result<StreamValue>> + Send>> {
+    frames
+        .and_then(|frame| async move {
+            match frame {
+                Frame::Invocation(_) => {
+                    Err(anyhow::anyhow!("Expected StreamValue, found Invocation"))
+                }
+                Frame::Value(val) => match val.progress {
+                    Some(command_progress::Progress::Event(event)) => Ok(StreamValue::Event(event)),
+                    Some(command_progress::Progress::Result(result)) => {
+                        Ok(StreamValue::Result(result))
+                    }
+                    None => Err(anyhow::anyhow!("Event type not recognized")),
+                },
+            }
+        })
+        .boxed()
+}
+
+fn chunked_decoder<R: AsyncRead + Unpin>(file: R) -> ZstdDecoder<BufReader<R>> {
+    let mut decoder = ZstdDecoder::new(BufReader::new(file));
+    // Every chunk is a separate zstd frame.
+    decoder.multiple_members(true);
+    decoder
+}
+
 struct NoInference(AbsPathBuf);
 
 struct NamedEventLogWriter {
     path: EventLogPathBuf,
-    file: EventLogWriter,
+    file: LogWriter,
     trace_id: TraceId,
 }
 
+enum LogWriter {
+    Stream(EventLogWriter),
+    Chunked(ChunkedZstdWriter),
+}
+
+impl LogWriter {
+    async fn write_all(&mut self, buf: &[u8]) -> anyhow::Result<()> {
+        match self {
+            Self::Stream(file) => Ok(file.write_all(buf).await?),
+            Self::Chunked(file) => file.write_all(buf).await,
+        }
+    }
+
+    async fn flush(&mut self) -> anyhow::Result<()> {
+        match self {
+            Self::Stream(file) => Ok(file.flush().await?),
+            Self::Chunked(file) => file.flush().await,
+        }
+    }
+
+    async fn shutdown(&mut self) -> anyhow::Result<()> {
+        match self {
+            Self::Stream(file) => Ok(file.shutdown().await?),
+            Self::Chunked(file) => file.shutdown().await,
+        }
+    }
+
+    /// Called before writing a snapshot. Only chunked logs care.
+    async fn start_chunk(&mut self) -> anyhow::Result<()> {
+        match self {
+            Self::Stream(_) => Ok(()),
+            Self::Chunked(file) => file.start_chunk().await,
+        }
+    }
+}
+
 enum LogFileState {
     Unopened(AbsNormPathBuf, Option<AbsPathBuf>),
     Opened(Vec<NamedEventLogWriter>),
@@ -350,6 +452,8 @@ enum Compression {
     None,
     Gzip,
     Zstd,
+    /// A sequence of zstd frames with an index, see `chunked`.
+    ZstdChunked,
 }
 
 /// This EventLog lets us to events emitted by Buck and log them to a file. The events are
@@ -360,14 +464,23 @@ pub(crate) struct EventLog {
     sanitized_argv: Vec<String>,
     command_name: String,
     working_dir: WorkingDir,
+    parent_trace_id: Option<TraceId>,
+    /// Where to persist the log once the command exits.
+    sinks: LogSinks,
     /// Allocation cache. Must be cleaned before use.
     buf: Vec<u8>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Invocation {
     pub command_line_args: Vec<String>,
     pub working_dir: String,
+    /// Absent in logs written before trace IDs were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    /// The trace ID of the invocation which started this one, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_trace_id: Option<String>,
 }
 
 impl EventLog {
@@ -378,6 +491,8 @@ impl EventLog {
         sanitized_argv: Vec<String>,
         async_cleanup_context: AsyncCleanupContext,
         command_name: String,
+        parent_trace_id: Option<TraceId>,
+        sinks: LogSinks,
     ) -> anyhow::Result<EventLog> {
         Ok(Self {
             state: LogFileState::Unopened(logdir, extra_path),
@@ -385,16 +500,20 @@ impl EventLog {
             sanitized_argv,
             command_name,
             working_dir,
+            parent_trace_id,
+            sinks,
             buf: Vec::new(),
         })
     }
 
     /// Get the command line arguments and cwd and serialize them for replaying later.
-    async fn log_invocation(&mut self) -> anyhow::Result<()> {
+    async fn log_invocation(&mut self, trace_id: &TraceId) -> anyhow::Result<()> {
         let command_line_args = self.sanitized_argv.clone();
         let invocation = Invocation {
             command_line_args,
             working_dir: self.working_dir.to_string(),
+            trace_id: Some(trace_id.to_string()),
+            parent_trace_id: self.parent_trace_id.as_ref().map(|t| t.to_string()),
         };
         self.write_ln(&[invocation]).await
     }
@@ -474,7 +593,7 @@ impl EventLog {
         // Open our log fie, gzip encoded.
         let encoding = match log_mode {
             LogMode::Json => Encoding::JSON_GZIP,
-            LogMode::Protobuf => Encoding::PROTO_ZSTD,
+            LogMode::Protobuf => Encoding::PROTO_ZSTD_CHUNKED,
         };
 
         let path = EventLogPathBuf {
@@ -502,7 +621,18 @@ impl EventLog {
         }
 
         self.state = LogFileState::Opened(log_files);
-        self.log_invocation().await
+        self.log_invocation(event.trace_id()?).await
+    }
+
+    async fn start_chunk(&mut self) -> anyhow::Result<()> {
+        if let LogFileState::Opened(files) = &mut self.state {
+            for f in files.iter_mut() {
+                f.file.start_chunk().await.with_context(|| {
+                    format!("Error starting chunk in log file at {}", f.path.path.display())
+                })?;
+            }
+        }
+        Ok(())
     }
 
     fn exit(&mut self) -> impl Future<Output = anyhow::Result<()>> + 'static + Send + Sync {
@@ -517,6 +647,7 @@ impl EventLog {
         };
 
         self.state = LogFileState::Closed;
+        let sinks = mem::take(&mut self.sinks);
 
         async move {
             for file in log_files.iter_mut() {
@@ -542,6 +673,9 @@ impl EventLog {
                 }
             }
 
+            let log = log_file_to_upload.path.path().to_owned();
+            tokio::task::spawn_blocking(move || sinks.persist(&log)).await?;
+
             Ok(())
         }
     }
@@ -584,13 +718,14 @@ async fn open_event_log_for_writing(
         })?;
 
     let file = match path.encoding.compression {
-        Compression::None => box file as EventLogWriter,
-        Compression::Gzip => {
-            box GzipEncoder::with_quality(file, async_compression::Level::Fastest) as EventLogWriter
-        }
-        Compression::Zstd => {
-            box ZstdEncoder::with_quality(file, async_compression::Level::Default) as EventLogWriter
-        }
+        Compression::None => LogWriter::Stream(box file as EventLogWriter),
+        Compression::Gzip => LogWriter::Stream(
+            box GzipEncoder::with_quality(file, async_compression::Level::Fastest) as EventLogWriter,
+        ),
+        Compression::Zstd => LogWriter::Stream(
+            box ZstdEncoder::with_quality(file, async_compression::Level::Default) as EventLogWriter,
+        ),
+        Compression::ZstdChunked => LogWriter::Chunked(ChunkedZstdWriter::new(file)),
     };
 
     Ok(NamedEventLogWriter {
@@ -615,6 +750,14 @@ impl EventSubscriber for EventLog {
                 self.ensure_log_files_opened(event).await?;
                 first = false;
             }
+            if is_snapshot(event) {
+                // Snapshots must start a chunk, so write what came before first.
+                if !event_refs.is_empty() {
+                    self.write_ln(&event_refs).await?;
+                    event_refs.clear();
+                }
+                self.start_chunk().await?;
+            }
             event_refs.push(StreamValueRef::Event(event.event()));
         }
 
@@ -668,6 +811,21 @@ impl EventSubscriber for EventLog {
     }
 }
 
+/// Snapshots carry cumulative state, so readers only interested in the latest state can start
+/// reading a chunked log from the last one.
+fn is_snapshot(event: &BuckEvent) -> bool {
+    match event.data() {
+        buck_event::Data::Instant(instant) => matches!(
+            instant.data,
+            Some(
+                instant_event::Data::Snapshot(_)
+                    | instant_event::Data::ActionCategoryStatsSnapshot(_)
+            )
+        ),
+        _ => false,
+    }
+}
+
 pub trait SerializeForLog {
     fn serialize_to_json(&self, buf: &mut Vec<u8>) -> anyhow::Result<()>;
     fn serialize_to_protobuf_length_delimited(&self, buf: &mut Vec<u8>) -> anyhow::Result<()>;
@@ -682,6 +840,8 @@ impl SerializeForLog for Invocation {
         let invocation = buck2_data::Invocation {
             command_line_args: self.command_line_args.clone(),
             working_dir: self.working_dir.clone(),
+            trace_id: self.trace_id.clone(),
+            parent_trace_id: self.parent_trace_id.clone(),
         };
         invocation.encode_length_delimited(buf)?;
         Ok(())
@@ -787,6 +947,8 @@ mod tests {
                 async_cleanup_context: None,
                 command_name: "testtest".to_owned(),
                 working_dir: WorkingDir::current_dir()?,
+                parent_trace_id: None,
+                sinks: LogSinks::default(),
                 buf: Vec::new(),
             })
         }
@@ -819,6 +981,11 @@ mod tests {
         test_protobuf_decoding(Encoding::PROTO_ZSTD).await
     }
 
+    #[tokio::test]
+    async fn test_protobuf_decoding_zstd_chunked() -> anyhow::Result<()> {
+        test_protobuf_decoding(Encoding::PROTO_ZSTD_CHUNKED).await
+    }
+
     async fn test_protobuf_decoding(encoding: Encoding) -> anyhow::Result<()> {
         //Create log dir
         let tmp_dir = TempDir::new()?;
@@ -876,6 +1043,11 @@ mod tests {
         test_tick_makes_valid_log(Encoding::PROTO_ZSTD).await
     }
 
+    #[tokio::test]
+    async fn test_tick_makes_valid_log_zstd_chunked() -> anyhow::Result<()> {
+        test_tick_makes_valid_log(Encoding::PROTO_ZSTD_CHUNKED).await
+    }
+
     async fn test_tick_makes_valid_log(encoding: Encoding) -> anyhow::Result<()> {
         if cfg!(windows) {
             // Do not want to deal with exclusivity issues on Windows.
@@ -928,7 +1100,7 @@ mod tests {
                 // assert!(events.try_next().await.unwrap().is_none(), "expecting no more events");
                 assert!(events.try_next().await.is_err());
             }
-            Compression::Zstd => {
+            Compression::Zstd | Compression::ZstdChunked => {
                 assert!(
                     events.try_next().await.unwrap().is_none(),
                     "expecting no more events"
@@ -940,6 +1112,106 @@ mod tests {
         Ok(())
     }
 
+    fn make_load_event(module_id: String) -> BuckEvent {
+        BuckEvent::new(
+            SystemTime::now(),
+            TraceId::new(),
+            None,
+            None,
+            buck2_data::buck_event::Data::SpanStart(SpanStartEvent {
+                data: Some(buck2_data::span_start_event::Data::Load(
+                    LoadBuildFileStart {
+                        module_id,
+                        cell: "bar".to_owned(),
+                    },
+                )),
+            }),
+        )
+    }
+
+    fn make_snapshot_event() -> BuckEvent {
+        BuckEvent::new(
+            SystemTime::now(),
+            TraceId::new(),
+            None,
+            None,
+            buck2_data::buck_event::Data::Instant(buck2_data::InstantEvent {
+                data: Some(instant_event::Data::Snapshot(
+                    buck2_data::Snapshot::default(),
+                )),
+            }),
+        )
+    }
+
+    async fn read_module_ids(
+        events: impl Stream<Item = anyhow::Result<StreamValue>>,
+    ) -> anyhow::Result<Vec<String>> {
+        events
+            .try_filter_map(|value| async move {
+                let event = match value {
+                    StreamValue::Event(event) => event,
+                    StreamValue::Result(_) => panic!("found result"),
+                };
+                Ok(match event.data {
+                    Some(buck_event::Data::SpanStart(SpanStartEvent {
+                        data: Some(buck2_data::span_start_event::Data::Load(load)),
+                    })) => Some(load.module_id),
+                    Some(buck_event::Data::Instant(_)) => Some("snapshot".to_owned()),
+                    _ => None,
+                })
+            })
+            .try_collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_unpack_stream_from_last_snapshot() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new()?;
+
+        let log = EventLogPathBuf {
+            path: AbsPathBuf::try_from(tmp_dir.path().join("log.pbc.zst")).unwrap(),
+            encoding: Encoding::PROTO_ZSTD_CHUNKED,
+        };
+
+        let mut event_log = EventLog::new_test_event_log(log.clone()).await?;
+        event_log.log_invocation().await?;
+
+        // Big enough for the snapshots to start chunks.
+        let big = "x".repeat(2 << 20);
+        let events = vec![
+            Arc::new(make_load_event(big.clone())),
+            Arc::new(make_snapshot_event()),
+            Arc::new(make_load_event("a".to_owned())),
+            Arc::new(make_load_event(big.clone())),
+            Arc::new(make_snapshot_event()),
+            Arc::new(make_load_event("b".to_owned())),
+        ];
+        event_log.handle_events(&events).await?;
+        event_log.exit().await?;
+
+        let (invocation, events) = log.unpack_stream_from_last_snapshot().await?;
+        assert_eq!(vec!["buck2".to_owned()], invocation.command_line_args);
+        assert_eq!(
+            vec!["snapshot".to_owned(), "b".to_owned()],
+            read_module_ids(events).await?
+        );
+
+        let (_invocation, events) = log.unpack_stream().await?;
+        assert_eq!(
+            vec![
+                big.clone(),
+                "snapshot".to_owned(),
+                "a".to_owned(),
+                big,
+                "snapshot".to_owned(),
+                "b".to_owned()
+            ],
+            read_module_ids(events).await?
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_stream_value_serialize_to_protobuf_length_delimited() {
         let event = make_event();
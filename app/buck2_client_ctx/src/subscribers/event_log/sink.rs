@@ -0,0 +1,337 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Places event logs are copied to when a command exits, configured in the root buckconfig:
+//!
+//! ```text
+//! [event_log]
+//!   sinks = /mnt/ci-logs, https://logs.example.com/buck2, s3://ci-logs/buck2
+//!   s3_endpoint_url = https://minio.example.com
+//! ```
+//!
+//! Every sink receives the log under its file name. Uploads run in the background, in processes
+//! that the client doesn't wait for, so they don't delay its exit. Failing to persist a log to a
+//! sink is logged and otherwise ignored: a command does not fail because its log could not be
+//! copied. The errors of the latest upload to each sink are kept in
+//! `buck-out/<isolation dir>/log_upload_errors/sink-<n>.txt`, `n` being the position of the sink
+//! in the list.
+
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use buck2_common::legacy_configs::LegacyBuckConfig;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::abs_path::AbsPath;
+use buck2_core::fs::paths::abs_path::AbsPathBuf;
+use buck2_core::process::background_command;
+use thiserror::Error;
+
+use crate::immediate_config::ImmediateConfig;
+
+#[derive(Debug, Error)]
+enum LogSinkError {
+    #[error(
+        "Invalid event log sink `{0}`: expected an absolute path, an `http(s)://` URL or an `s3://` URL"
+    )]
+    InvalidSink(String),
+}
+
+/// Somewhere to persist event logs off-box.
+pub(crate) trait LogSink: Send + Sync + 'static {
+    /// Describes the destination, for error messages.
+    fn describe(&self) -> String;
+
+    /// Start persisting the log at `log` under the name `file_name`. Uploads must not be waited
+    /// for, but run in a process of their own that outlives the client, with `errors` as its
+    /// stderr.
+    fn persist(&self, log: &AbsPath, file_name: &str, errors: Stdio) -> anyhow::Result<()>;
+}
+
+/// Copies logs to a directory, typically a mounted network filesystem.
+struct LocalDirSink {
+    dir: AbsPathBuf,
+}
+
+impl LogSink for LocalDirSink {
+    fn describe(&self) -> String {
+        self.dir.display().to_string()
+    }
+
+    fn persist(&self, log: &AbsPath, file_name: &str, _errors: Stdio) -> anyhow::Result<()> {
+        fs_util::create_dir_all(&self.dir)?;
+        fs_util::copy(log, self.dir.join(file_name))?;
+        Ok(())
+    }
+}
+
+/// `PUT`s logs to `{url}/{file_name}`.
+struct HttpSink {
+    url: String,
+}
+
+impl LogSink for HttpSink {
+    fn describe(&self) -> String {
+        self.url.clone()
+    }
+
+    fn persist(&self, log: &AbsPath, file_name: &str, errors: Stdio) -> anyhow::Result<()> {
+        let destination = format!("{}/{}", self.url.trim_end_matches('/'), file_name);
+        spawn_detached(
+            background_command("curl")
+                .args(["--fail", "--silent", "--show-error", "--upload-file"])
+                .arg(log.as_os_str())
+                .arg(destination),
+            errors,
+        )
+    }
+}
+
+/// Copies logs to `{url}/{file_name}` with the `aws` CLI, which picks up credentials from the
+/// environment. Works with any S3-compatible store given `endpoint_url`.
+struct S3Sink {
+    url: String,
+    endpoint_url: Option<String>,
+}
+
+impl LogSink for S3Sink {
+    fn describe(&self) -> String {
+        self.url.clone()
+    }
+
+    fn persist(&self, log: &AbsPath, file_name: &str, errors: Stdio) -> anyhow::Result<()> {
+        let destination = format!("{}/{}", self.url.trim_end_matches('/'), file_name);
+        let mut command = background_command("aws");
+        command
+            .args(["s3", "cp", "--only-show-errors"])
+            .arg(log.as_os_str())
+            .arg(destination);
+        if let Some(endpoint_url) = &self.endpoint_url {
+            command.args(["--endpoint-url", endpoint_url]);
+        }
+        spawn_detached(&mut command, errors)
+    }
+}
+
+/// Start `command` without waiting for it, so that it keeps running once the client exits. Only
+/// failing to start it is reported here, anything else goes to `errors`.
+fn spawn_detached(command: &mut Command, errors: Stdio) -> anyhow::Result<()> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(errors)
+        .spawn()
+        .with_context(|| {
+            format!(
+                "Error running `{}`",
+                command.get_program().to_string_lossy()
+            )
+        })?;
+    Ok(())
+}
+
+/// The sinks configured in the root buckconfig.
+#[derive(Clone, Default)]
+pub(crate) struct LogSinks {
+    sinks: Vec<Arc<dyn LogSink>>,
+    /// Where the errors of uploads go, if anywhere.
+    errors_dir: Option<AbsNormPathBuf>,
+}
+
+impl LogSinks {
+    /// Reads the sinks from the root cell's buckconfig. A log must not be lost silently, so
+    /// unlike most client-side settings, invalid configuration is reported.
+    pub(crate) fn from_immediate_config(
+        immediate_config: &ImmediateConfig,
+        errors_dir: AbsNormPathBuf,
+    ) -> Self {
+        match immediate_config.get().and_then(Self::from_config) {
+            Ok(sinks) => Self {
+                errors_dir: Some(errors_dir),
+                ..sinks
+            },
+            Err(e) => {
+                tracing::warn!("Event logs will not be persisted: {:#}", e);
+                Self::default()
+            }
+        }
+    }
+
+    fn from_config(config: &LegacyBuckConfig) -> anyhow::Result<Self> {
+        let endpoint_url = config.get("event_log", "s3_endpoint_url");
+        let sinks = match config.get("event_log", "sinks") {
+            Some(sinks) => sinks
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| parse_sink(s, endpoint_url))
+                .collect::<anyhow::Result<_>>()?,
+            None => Vec::new(),
+        };
+        Ok(Self {
+            sinks,
+            errors_dir: None,
+        })
+    }
+
+    /// Start persisting the log to all sinks. Errors are logged, not returned. Blocks while
+    /// copying to local directories.
+    pub(crate) fn persist(&self, log: &AbsPath) {
+        let file_name = match log.file_name().and_then(|n| n.to_str()) {
+            Some(file_name) => file_name,
+            None => return,
+        };
+        for (i, sink) in self.sinks.iter().enumerate() {
+            let res: anyhow::Result<()> = try {
+                let errors = self.errors(i, sink.as_ref(), log)?;
+                sink.persist(log, file_name, errors)?;
+            };
+            if let Err(e) = res {
+                tracing::warn!("Error persisting event log to {}: {:#}", sink.describe(), e);
+            }
+        }
+    }
+
+    /// The stderr of the processes uploading `log` to the `i`-th sink. Replaces that of the
+    /// previous upload, so that the errors don't accumulate.
+    fn errors(&self, i: usize, sink: &dyn LogSink, log: &AbsPath) -> anyhow::Result<Stdio> {
+        let errors_dir = match &self.errors_dir {
+            Some(errors_dir) => errors_dir,
+            None => return Ok(Stdio::null()),
+        };
+        fs_util::create_dir_all(errors_dir)?;
+        let path = errors_dir.as_path().join(format!("sink-{}.txt", i));
+        let mut file = std::fs::File::create(&path)
+            .with_context(|| format!("Error creating `{}`", path.display()))?;
+        writeln!(
+            file,
+            "Persisting `{}` to {}",
+            log.display(),
+            sink.describe()
+        )?;
+        Ok(file.into())
+    }
+}
+
+fn parse_sink(sink: &str, endpoint_url: Option<&str>) -> anyhow::Result<Arc<dyn LogSink>> {
+    if sink.starts_with("http://") || sink.starts_with("https://") {
+        Ok(Arc::new(HttpSink {
+            url: sink.to_owned(),
+        }))
+    } else if sink.starts_with("s3://") {
+        Ok(Arc::new(S3Sink {
+            url: sink.to_owned(),
+            endpoint_url: endpoint_url.map(str::to_owned),
+        }))
+    } else {
+        let dir = AbsPathBuf::try_from(sink.to_owned())
+            .map_err(|_| LogSinkError::InvalidSink(sink.to_owned()))?;
+        Ok(Arc::new(LocalDirSink { dir }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::legacy_configs::testing::legacy_buck_config_from_entries;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_from_config() -> anyhow::Result<()> {
+        let config = legacy_buck_config_from_entries([(
+            "event_log",
+            "sinks",
+            "/mnt/logs, https://logs.example.com/buck2/, s3://bucket/buck2",
+        )])?;
+        let sinks = LogSinks::from_config(&config)?;
+        assert_eq!(
+            vec![
+                "/mnt/logs",
+                "https://logs.example.com/buck2/",
+                "s3://bucket/buck2"
+            ],
+            sinks.sinks.iter().map(|s| s.describe()).collect::<Vec<_>>()
+        );
+
+        let config = legacy_buck_config_from_entries([("event_log", "sinks", "logs")])?;
+        assert!(LogSinks::from_config(&config).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_dir_sink() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let log = AbsPathBuf::try_from(tmp_dir.path().join("log.pbc.zst")).unwrap();
+        fs_util::write(&log, b"events")?;
+
+        let dir = tmp_dir.path().join("persisted");
+        let config =
+            legacy_buck_config_from_entries([("event_log", "sinks", dir.to_str().unwrap())])?;
+        LogSinks::from_config(&config)?.persist(&log);
+
+        assert_eq!(b"events".to_vec(), std::fs::read(dir.join("log.pbc.zst"))?);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_upload_errors() -> anyhow::Result<()> {
+        /// Fails the way an upload would, in the background.
+        struct FailingSink;
+
+        impl LogSink for FailingSink {
+            fn describe(&self) -> String {
+                "failing".to_owned()
+            }
+
+            fn persist(
+                &self,
+                _log: &AbsPath,
+                file_name: &str,
+                errors: Stdio,
+            ) -> anyhow::Result<()> {
+                spawn_detached(
+                    background_command("sh")
+                        .arg("-c")
+                        .arg(format!("echo \"cannot upload {}\" >&2", file_name)),
+                    errors,
+                )
+            }
+        }
+
+        let tmp_dir = TempDir::new()?;
+        let log = AbsPathBuf::try_from(tmp_dir.path().join("log.pbc.zst")).unwrap();
+        let errors_dir = AbsNormPathBuf::try_from(tmp_dir.path().join("errors")).unwrap();
+        let sinks = LogSinks {
+            sinks: vec![Arc::new(FailingSink)],
+            errors_dir: Some(errors_dir.clone()),
+        };
+        sinks.persist(&log);
+
+        let errors = errors_dir.as_path().join("sink-0.txt");
+        let expected = format!(
+            "Persisting `{}` to failing\ncannot upload log.pbc.zst\n",
+            log.display()
+        );
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while fs_util::read_to_string(&errors)? != expected {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "errors were not written"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        Ok(())
+    }
+}
@@ -7,6 +7,8 @@
  * of this source tree.
  */
 
+use std::time::Duration;
+
 use ::superconsole::Component;
 use gazebo::prelude::*;
 
@@ -14,6 +16,7 @@ use crate::client_ctx::ClientCommandContext;
 use crate::common::CommonDaemonCommandOptions;
 use crate::common::ConsoleType;
 use crate::subscribers::build_id_writer::BuildIdWriter;
+use crate::subscribers::event_log::sink::LogSinks;
 use crate::subscribers::event_log::EventLog;
 use crate::subscribers::re_log::ReLog;
 use crate::subscribers::simpleconsole::SimpleConsole;
@@ -31,6 +34,7 @@ pub(crate) fn get_console_with_root(
     replay_speed: Option<f64>,
     root: Box<dyn Component>,
     config: SuperConsoleConfig,
+    ci_progress_interval: Duration,
 ) -> anyhow::Result<Option<Box<dyn EventSubscriber>>> {
     match console_type {
         ConsoleType::Simple => Ok(Some(box UnpackingEventSubscriberAsEventSubscriber(
@@ -52,7 +56,10 @@ pub(crate) fn get_console_with_root(
                 config,
             )?,
         ))),
-        ConsoleType::Auto => {
+        ConsoleType::Ci => Ok(Some(box UnpackingEventSubscriberAsEventSubscriber(
+            SimpleConsole::ci(verbosity, show_waiting_message, ci_progress_interval),
+        ))),
+        ConsoleType::Auto | ConsoleType::Quiet => {
             match StatefulSuperConsole::new_with_root(
                 root,
                 verbosity,
@@ -96,6 +103,8 @@ pub(crate) fn try_get_event_log_subscriber(
         sanitized_argv,
         ctx.async_cleanup_context().dupe(),
         ctx.command_name.clone(),
+        ctx.parent_trace_id.dupe(),
+        LogSinks::from_immediate_config(&ctx.immediate_config, ctx.paths.log_upload_errors_dir()),
     )?;
     Ok(Some(box log))
 }
@@ -106,6 +106,12 @@ impl IoState {
                 snapshot.deferred_materializer_queue_size
             ));
         }
+        if snapshot.analysis_queued > 0 {
+            parts.push(format!(
+                "Analysis = {} (queued {})",
+                snapshot.analysis_running, snapshot.analysis_queued
+            ));
+        }
         if snapshot.blocking_executor_io_queue_size > 0 {
             parts.push(format!(
                 "IO Queue = {}",
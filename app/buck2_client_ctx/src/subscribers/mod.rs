@@ -9,6 +9,7 @@
 
 use buck2_core::env_helper::EnvHelper;
 
+pub(crate) mod action_progress;
 pub(crate) mod build_id_writer;
 pub mod display;
 pub mod event_log;
@@ -16,9 +17,11 @@ pub(crate) mod get;
 pub(crate) mod humanized_bytes;
 pub(crate) mod io;
 pub(crate) mod last_command_execution_kind;
+pub(crate) mod phase_profile;
 pub mod re_log;
 pub(crate) mod re_panel;
 pub(crate) mod recorder;
+pub(crate) mod resource_summary;
 pub(crate) mod simpleconsole;
 pub(crate) mod span_tracker;
 pub(crate) mod stdout_stderr_forwarder;
@@ -26,6 +29,7 @@ pub mod subscriber;
 pub mod subscriber_unpack;
 pub mod superconsole;
 pub(crate) mod two_snapshots;
+pub(crate) mod warnings;
 
 pub fn disable_log_upload() -> anyhow::Result<bool> {
     static DISABLE_LOG_UPLOAD: EnvHelper<bool> = EnvHelper::new("BUCK2_TEST_DISABLE_LOG_UPLOAD");
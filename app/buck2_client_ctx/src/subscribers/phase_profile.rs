@@ -0,0 +1,378 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! `--profile-phases`: a breakdown of where the time of a command went, by phase, to help answer
+//! questions like "why does my null build take 20 seconds".
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use buck2_events::span::SpanId;
+use buck2_events::BuckEvent;
+
+use crate::subscribers::subscriber::EventSubscriber;
+
+/// How many of the slowest packages to report.
+const TOP_PACKAGES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Phase {
+    FileChanges,
+    PatternResolution,
+    Loading,
+    Configuration,
+    Analysis,
+    Execution,
+}
+
+impl Phase {
+    const ALL: [Phase; 6] = [
+        Phase::FileChanges,
+        Phase::PatternResolution,
+        Phase::Loading,
+        Phase::Configuration,
+        Phase::Analysis,
+        Phase::Execution,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Phase::FileChanges => "File changes",
+            Phase::PatternResolution => "Pattern resolution",
+            Phase::Loading => "Package loading",
+            Phase::Configuration => "Configuration",
+            Phase::Analysis => "Analysis",
+            Phase::Execution => "Execution",
+        }
+    }
+}
+
+#[derive(Default)]
+struct PhaseStats {
+    /// When each span of this phase ran, to compute the wall time they covered together.
+    intervals: Vec<(SystemTime, SystemTime)>,
+    /// Sum of the durations of the spans, which exceeds the wall time when they run in parallel.
+    cumulative: Duration,
+    /// Sum of the time spent polling the spans, which approximates their CPU time.
+    cpu: Duration,
+    spans: u64,
+}
+
+impl PhaseStats {
+    fn wall(&self) -> Duration {
+        let mut intervals = self.intervals.clone();
+        intervals.sort();
+        let mut wall = Duration::ZERO;
+        let mut current: Option<(SystemTime, SystemTime)> = None;
+        for (start, end) in intervals {
+            current = match current {
+                Some((cur_start, cur_end)) if start <= cur_end => {
+                    Some((cur_start, cur_end.max(end)))
+                }
+                Some((cur_start, cur_end)) => {
+                    wall += cur_end.duration_since(cur_start).unwrap_or_default();
+                    Some((start, end))
+                }
+                None => Some((start, end)),
+            };
+        }
+        if let Some((start, end)) = current {
+            wall += end.duration_since(start).unwrap_or_default();
+        }
+        wall
+    }
+}
+
+struct OpenSpan {
+    phase: Phase,
+    start: SystemTime,
+    /// The build file, for spans loading one.
+    package: Option<String>,
+}
+
+#[derive(Default)]
+struct PackageStats {
+    wall: Duration,
+    cpu: Duration,
+}
+
+/// Collects the spans of each phase of a command, and reports them once it's done.
+#[derive(Default)]
+pub(crate) struct PhaseProfile {
+    open: HashMap<SpanId, OpenSpan>,
+    phases: HashMap<Phase, PhaseStats>,
+    packages: HashMap<String, PackageStats>,
+    command: Option<(SystemTime, Option<SystemTime>)>,
+}
+
+impl PhaseProfile {
+    fn handle_event(&mut self, event: &BuckEvent) {
+        use buck2_data::span_start_event::Data;
+
+        let span_id = match event.span_id() {
+            Some(span_id) => span_id,
+            None => return,
+        };
+
+        if let Some(start) = event.span_start_event() {
+            let (phase, package) = match &start.data {
+                Some(Data::Command(..)) => {
+                    self.command = Some((event.timestamp(), None));
+                    return;
+                }
+                Some(Data::FileWatcher(..) | Data::DiceStateUpdate(..)) => {
+                    (Phase::FileChanges, None)
+                }
+                Some(Data::ResolveTargetPatterns(..)) => (Phase::PatternResolution, None),
+                Some(Data::Load(load)) => (Phase::Loading, Some(load.module_id.clone())),
+                Some(Data::LoadPackage(..)) => (Phase::Loading, None),
+                Some(Data::ConfigureTarget(..)) => (Phase::Configuration, None),
+                Some(Data::Analysis(..)) => (Phase::Analysis, None),
+                Some(Data::ActionExecution(..) | Data::FinalMaterialization(..)) => {
+                    (Phase::Execution, None)
+                }
+                _ => return,
+            };
+            self.open.insert(
+                span_id,
+                OpenSpan {
+                    phase,
+                    start: event.timestamp(),
+                    package,
+                },
+            );
+        } else if let Some(end) = event.span_end_event() {
+            if let Some(buck2_data::span_end_event::Data::Command(..)) = &end.data {
+                if let Some((_, command_end)) = &mut self.command {
+                    *command_end = Some(event.timestamp());
+                }
+                return;
+            }
+
+            let span = match self.open.remove(&span_id) {
+                Some(span) => span,
+                None => return,
+            };
+            let end_time = event.timestamp().max(span.start);
+            let duration = end_time.duration_since(span.start).unwrap_or_default();
+            let cpu = Duration::from_micros(
+                end.stats
+                    .as_ref()
+                    .map_or(0, |stats| stats.total_poll_time_us),
+            );
+
+            let stats = self.phases.entry(span.phase).or_default();
+            stats.intervals.push((span.start, end_time));
+            stats.cumulative += duration;
+            stats.cpu += cpu;
+            stats.spans += 1;
+
+            if let Some(package) = span.package {
+                let package = self.packages.entry(package).or_default();
+                package.wall += duration;
+                package.cpu += cpu;
+            }
+        }
+    }
+
+    fn report(&self) -> String {
+        fn secs(d: Duration) -> String {
+            format!("{:.3}s", d.as_secs_f64())
+        }
+
+        let mut out = String::new();
+        // Writing to a String can't fail.
+        let _ = writeln!(
+            out,
+            "Phase profile (phases overlap, so their wall times don't add up to the total):"
+        );
+        let _ = writeln!(
+            out,
+            "  {:<20} {:>10} {:>12} {:>10} {:>8}",
+            "Phase", "Wall", "Cumulative", "CPU", "Spans"
+        );
+        for phase in Phase::ALL {
+            let stats = match self.phases.get(&phase) {
+                Some(stats) => stats,
+                None => continue,
+            };
+            let _ = writeln!(
+                out,
+                "  {:<20} {:>10} {:>12} {:>10} {:>8}",
+                phase.name(),
+                secs(stats.wall()),
+                secs(stats.cumulative),
+                secs(stats.cpu),
+                stats.spans
+            );
+        }
+        if let Some((start, Some(end))) = self.command {
+            let _ = writeln!(
+                out,
+                "  {:<20} {:>10}",
+                "Total",
+                secs(end.duration_since(start).unwrap_or_default())
+            );
+        }
+
+        if !self.packages.is_empty() {
+            let mut packages: Vec<_> = self.packages.iter().collect();
+            packages.sort_by(|(a_name, a), (b_name, b)| {
+                b.wall.cmp(&a.wall).then_with(|| a_name.cmp(b_name))
+            });
+            let _ = writeln!(out, "Slowest packages to load:");
+            for (name, stats) in packages.into_iter().take(TOP_PACKAGES) {
+                let _ = writeln!(
+                    out,
+                    "  {:>10} {:>10} CPU  {}",
+                    secs(stats.wall),
+                    secs(stats.cpu),
+                    name
+                );
+            }
+        }
+        out
+    }
+}
+
+/// Prints the phase profile to stderr when the command finishes.
+#[derive(Default)]
+pub(crate) struct PhaseProfileReporter {
+    profile: PhaseProfile,
+}
+
+#[async_trait]
+impl EventSubscriber for PhaseProfileReporter {
+    async fn handle_events(&mut self, events: &[Arc<BuckEvent>]) -> anyhow::Result<()> {
+        for event in events {
+            self.profile.handle_event(event);
+        }
+        Ok(())
+    }
+
+    async fn exit(&mut self) -> anyhow::Result<()> {
+        crate::eprint!("{}", self.profile.report())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_events::trace::TraceId;
+
+    use super::*;
+
+    fn event(at: u64, span_id: SpanId, data: buck2_data::buck_event::Data) -> BuckEvent {
+        BuckEvent::new(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(at),
+            TraceId::new(),
+            Some(span_id),
+            None,
+            data,
+        )
+    }
+
+    fn start(at: u64, span_id: SpanId, data: buck2_data::span_start_event::Data) -> BuckEvent {
+        event(
+            at,
+            span_id,
+            buck2_data::SpanStartEvent { data: Some(data) }.into(),
+        )
+    }
+
+    fn end(
+        at: u64,
+        span_id: SpanId,
+        poll_secs: u64,
+        data: buck2_data::span_end_event::Data,
+    ) -> BuckEvent {
+        event(
+            at,
+            span_id,
+            buck2_data::SpanEndEvent {
+                data: Some(data),
+                stats: Some(buck2_data::SpanStats {
+                    total_poll_time_us: poll_secs * 1_000_000,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+            .into(),
+        )
+    }
+
+    fn load(at: u64, span_id: SpanId, module_id: &str) -> BuckEvent {
+        start(
+            at,
+            span_id,
+            buck2_data::LoadBuildFileStart {
+                module_id: module_id.to_owned(),
+                ..Default::default()
+            }
+            .into(),
+        )
+    }
+
+    #[test]
+    fn test_phase_profile() {
+        let mut profile = PhaseProfile::default();
+        let command = SpanId::new();
+        let patterns = SpanId::new();
+        let (a, b, c) = (SpanId::new(), SpanId::new(), SpanId::new());
+
+        for e in [
+            start(0, command, buck2_data::CommandStart::default().into()),
+            start(
+                0,
+                patterns,
+                buck2_data::ResolveTargetPatternsStart {}.into(),
+            ),
+            end(
+                1,
+                patterns,
+                1,
+                buck2_data::ResolveTargetPatternsEnd {}.into(),
+            ),
+            // Two loads in parallel, then another one after a gap.
+            load(1, a, "root//a:BUCK"),
+            load(2, b, "root//b:BUCK"),
+            end(5, a, 3, buck2_data::LoadBuildFileEnd::default().into()),
+            end(4, b, 1, buck2_data::LoadBuildFileEnd::default().into()),
+            load(6, c, "root//c:BUCK"),
+            end(8, c, 2, buck2_data::LoadBuildFileEnd::default().into()),
+            end(10, command, 0, buck2_data::CommandEnd::default().into()),
+        ] {
+            profile.handle_event(&e);
+        }
+
+        let loading = &profile.phases[&Phase::Loading];
+        assert_eq!(Duration::from_secs(6), loading.wall());
+        assert_eq!(Duration::from_secs(8), loading.cumulative);
+        assert_eq!(Duration::from_secs(6), loading.cpu);
+        assert_eq!(3, loading.spans);
+
+        let report = profile.report();
+        assert!(report.contains("Pattern resolution"), "{}", report);
+        assert!(report.contains("Total"), "{}", report);
+        assert!(!report.contains("Analysis"), "{}", report);
+        let packages: Vec<&str> = report
+            .lines()
+            .skip_while(|l| !l.starts_with("Slowest packages"))
+            .skip(1)
+            .map(|l| l.rsplit(' ').next().unwrap())
+            .collect();
+        assert_eq!(
+            vec!["root//a:BUCK", "root//b:BUCK", "root//c:BUCK"],
+            packages
+        );
+    }
+}
@@ -90,6 +90,7 @@ mod imp {
         time_to_first_analysis: Option<Duration>,
         time_to_load_first_build_file: Option<Duration>,
         time_to_first_command_execution_start: Option<Duration>,
+        restarted_after: Option<String>,
     }
 
     impl InvocationRecorder {
@@ -99,6 +100,7 @@ mod imp {
             sanitized_argv: Vec<String>,
             build_count_manager: BuildCountManager,
             invocation_root_path: AbsNormPathBuf,
+            restarted_after: Option<String>,
         ) -> Self {
             Self {
                 cli_args: sanitized_argv,
@@ -144,6 +146,7 @@ mod imp {
                 time_to_first_analysis: None,
                 time_to_load_first_build_file: None,
                 time_to_first_command_execution_start: None,
+                restarted_after,
             }
         }
 
@@ -224,6 +227,7 @@ mod imp {
                     time_to_first_command_execution_start_ms: self
                         .time_to_first_command_execution_start
                         .and_then(|d| u64::try_from(d.as_millis()).ok()),
+                    restarted_after: self.restarted_after.clone(),
                 };
                 let event = BuckEvent::new(
                     SystemTime::now(),
@@ -562,6 +566,7 @@ pub(crate) fn try_get_invocation_recorder(
                 sanitized_argv,
                 BuildCountManager::new(ctx.paths.build_count_dir()),
                 ctx.paths.project_root().root().to_buf(),
+                ctx.restarted_after.clone(),
             );
             return Ok(Some(Box::new(UnpackingEventSubscriberAsEventSubscriber(
                 recorder,
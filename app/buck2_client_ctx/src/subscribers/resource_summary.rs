@@ -0,0 +1,158 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! `--print-resource-summary`: the resources a command used, as summarized by the daemon when the
+//! command ends.
+
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use buck2_events::BuckEvent;
+
+use crate::subscribers::humanized_bytes::HumanizedBytes;
+use crate::subscribers::subscriber::EventSubscriber;
+
+fn report(summary: &buck2_data::ResourceUsageSummary) -> String {
+    fn cpu(user_us: u64, system_us: u64) -> String {
+        format!(
+            "{:.3}s user, {:.3}s system",
+            Duration::from_micros(user_us).as_secs_f64(),
+            Duration::from_micros(system_us).as_secs_f64()
+        )
+    }
+
+    let mut out = String::new();
+    // Writing to a String can't fail.
+    let _ = writeln!(out, "Resource usage:");
+    // The daemon-wide figures are left out when other commands ran at the same time.
+    const CONCURRENT: &str = "not measured, other commands were running";
+    let _ = writeln!(
+        out,
+        "  {:<16} {}",
+        "Daemon CPU",
+        match (summary.daemon_user_cpu_us, summary.daemon_system_cpu_us) {
+            (Some(user_us), Some(system_us)) => cpu(user_us, system_us),
+            _ => CONCURRENT.to_owned(),
+        }
+    );
+    let _ = writeln!(
+        out,
+        "  {:<16} {}",
+        "Local action CPU",
+        cpu(summary.actions_user_cpu_us, summary.actions_system_cpu_us)
+    );
+    let _ = writeln!(
+        out,
+        "  {:<16} {}",
+        "Peak daemon RSS",
+        HumanizedBytes::new(summary.peak_daemon_rss_bytes)
+    );
+    if let (Some(read), Some(write)) = (summary.disk_read_bytes, summary.disk_write_bytes) {
+        let _ = writeln!(
+            out,
+            "  {:<16} {} read, {} written",
+            "Disk",
+            HumanizedBytes::new(read),
+            HumanizedBytes::new(write)
+        );
+    }
+    let _ = match (summary.re_download_bytes, summary.re_upload_bytes) {
+        (Some(download), Some(upload)) => writeln!(
+            out,
+            "  {:<16} {} downloaded, {} uploaded",
+            "RE",
+            HumanizedBytes::new(download),
+            HumanizedBytes::new(upload)
+        ),
+        _ => writeln!(out, "  {:<16} {}", "RE", CONCURRENT),
+    };
+    out
+}
+
+/// Prints the resource usage summary to stderr when the command finishes.
+#[derive(Default)]
+pub(crate) struct ResourceSummaryReporter {
+    summary: Option<buck2_data::ResourceUsageSummary>,
+}
+
+#[async_trait]
+impl EventSubscriber for ResourceSummaryReporter {
+    async fn handle_events(&mut self, events: &[Arc<BuckEvent>]) -> anyhow::Result<()> {
+        for event in events {
+            if let buck2_data::buck_event::Data::Instant(instant) = event.data() {
+                if let Some(buck2_data::instant_event::Data::ResourceUsageSummary(summary)) =
+                    &instant.data
+                {
+                    self.summary = Some(summary.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn exit(&mut self) -> anyhow::Result<()> {
+        match &self.summary {
+            Some(summary) => crate::eprint!("{}", report(summary)),
+            None => crate::eprintln!("Resource usage: not reported by the daemon"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let summary = buck2_data::ResourceUsageSummary {
+            daemon_user_cpu_us: Some(1_500_000),
+            daemon_system_cpu_us: Some(0),
+            actions_system_cpu_us: 250_000,
+            peak_daemon_rss_bytes: 3 * 1024 * 1024 * 1024,
+            disk_read_bytes: Some(0),
+            disk_write_bytes: Some(2048),
+            re_download_bytes: Some(512),
+            re_upload_bytes: Some(0),
+            ..Default::default()
+        };
+        let report = report(&summary);
+        assert!(
+            report.contains("Daemon CPU       1.500s user, 0.000s system"),
+            "{}",
+            report
+        );
+        assert!(
+            report.contains("Local action CPU 0.000s user, 0.250s system"),
+            "{}",
+            report
+        );
+        assert!(report.contains("3.0 GiB"), "{}", report);
+        assert!(report.contains("0 B read, 2.0 KiB written"), "{}", report);
+        assert!(
+            report.contains("512 B downloaded, 0 B uploaded"),
+            "{}",
+            report
+        );
+
+        let report = super::report(&buck2_data::ResourceUsageSummary::default());
+        assert!(!report.contains("Disk"), "{}", report);
+        assert!(
+            report.contains("Daemon CPU       not measured, other commands were running"),
+            "{}",
+            report
+        );
+        assert!(
+            report.contains("RE               not measured, other commands were running"),
+            "{}",
+            report
+        );
+    }
+}
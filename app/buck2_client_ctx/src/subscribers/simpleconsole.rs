@@ -26,6 +26,7 @@ use superconsole::SuperConsole;
 use termwiz::escape::Action;
 use termwiz::escape::ControlCode;
 
+use crate::subscribers::action_progress::ActionProgress;
 use crate::subscribers::display;
 use crate::subscribers::display::TargetDisplayOptions;
 use crate::subscribers::humanized_bytes::HumanizedBytes;
@@ -38,6 +39,7 @@ use crate::subscribers::span_tracker::BuckEventSpanTracker;
 use crate::subscribers::subscriber::Tick;
 use crate::subscribers::subscriber_unpack::UnpackingEventSubscriber;
 use crate::subscribers::two_snapshots::TwoSnapshots;
+use crate::subscribers::warnings::WarningsSummary;
 use crate::verbosity::Verbosity;
 use crate::what_ran;
 use crate::what_ran::local_command_to_string;
@@ -140,6 +142,12 @@ fn eprint_command_details(
     };
     echo!("Stdout: {}", stdout)?;
     echo!("Stderr: {}", stderr)?;
+    for path in &command_failed.diagnostic_outputs {
+        echo!("Diagnostic output: {}", path)?;
+    }
+    if let Some(scratch_dir) = &command_failed.scratch_dir {
+        echo!("Temporary directory: {}", scratch_dir)?;
+    }
     Ok(())
 }
 
@@ -228,9 +236,14 @@ pub(crate) struct SimpleConsole {
     last_print_time: Instant,
     test_session: Option<String>,
     action_stats: ActionStats,
+    action_progress: ActionProgress,
+    warnings: WarningsSummary,
     re_panel: RePanel,
     pub(crate) io_state: IoState,
     two_snapshots: TwoSnapshots,
+    /// Print a progress line this often, regardless of what else was printed (`--console=ci`).
+    progress_interval: Option<Duration>,
+    last_progress_time: Instant,
 }
 
 impl SimpleConsole {
@@ -244,9 +257,13 @@ impl SimpleConsole {
             last_print_time: Instant::now(),
             test_session: None,
             action_stats: ActionStats::default(),
+            action_progress: ActionProgress::default(),
+            warnings: WarningsSummary::default(),
             re_panel: RePanel::new(),
             io_state: IoState::default(),
             two_snapshots: TwoSnapshots::default(),
+            progress_interval: None,
+            last_progress_time: Instant::now(),
         }
     }
 
@@ -260,9 +277,26 @@ impl SimpleConsole {
             last_print_time: Instant::now(),
             test_session: None,
             action_stats: ActionStats::default(),
+            action_progress: ActionProgress::default(),
+            warnings: WarningsSummary::default(),
             re_panel: RePanel::new(),
             io_state: IoState::default(),
             two_snapshots: TwoSnapshots::default(),
+            progress_interval: None,
+            last_progress_time: Instant::now(),
+        }
+    }
+
+    /// Create a SimpleConsole for CI logs, which doesn't use a TTY and prints a compact
+    /// timestamped progress line every `progress_interval`.
+    pub(crate) fn ci(
+        verbosity: Verbosity,
+        show_waiting_message: bool,
+        progress_interval: Duration,
+    ) -> Self {
+        SimpleConsole {
+            progress_interval: Some(progress_interval),
+            ..Self::without_tty(verbosity, show_waiting_message)
         }
     }
 
@@ -286,6 +320,18 @@ impl SimpleConsole {
         &mut self.action_stats
     }
 
+    pub(crate) fn action_progress(&self) -> &ActionProgress {
+        &self.action_progress
+    }
+
+    pub(crate) fn warnings(&self) -> &WarningsSummary {
+        &self.warnings
+    }
+
+    pub(crate) fn warnings_mut(&mut self) -> &mut WarningsSummary {
+        &mut self.warnings
+    }
+
     pub(crate) fn re_panel(&self) -> &RePanel {
         &self.re_panel
     }
@@ -295,6 +341,7 @@ impl SimpleConsole {
     }
 
     pub(crate) fn update_span_tracker(&mut self, event: &Arc<BuckEvent>) -> anyhow::Result<()> {
+        self.action_progress.handle_event(event);
         self.span_tracker
             .handle_event(event)
             .context("Error tracking event")
@@ -320,6 +367,40 @@ impl SimpleConsole {
         self.last_print_time = Instant::now();
     }
 
+    fn print_progress(&mut self, tick: &Tick) -> anyhow::Result<()> {
+        let in_progress = self.span_tracker.iter_roots().len();
+        let finished = self.span_tracker.roots_completed();
+        let elapsed = display::duration_as_secs_elapsed(tick.elapsed_time, 1.0);
+        let estimate = match self.action_progress.estimate() {
+            Some(estimate) => format!(
+                " Estimated: {}%, {} left.",
+                estimate.percent,
+                display::duration_as_secs_elapsed(estimate.remaining, 1.0)
+            ),
+            None => String::new(),
+        };
+        if self.action_stats.log_stats() {
+            echo!(
+                "Jobs: In progress: {}. Finished: {}.{} Time elapsed: {}. {}",
+                in_progress,
+                finished,
+                estimate,
+                elapsed,
+                self.action_stats
+            )?;
+        } else {
+            echo!(
+                "Jobs: In progress: {}. Finished: {}.{} Time elapsed: {}",
+                in_progress,
+                finished,
+                estimate,
+                elapsed
+            )?;
+        }
+        self.last_progress_time = Instant::now();
+        Ok(())
+    }
+
     fn print_stats_while_waiting(&mut self) -> anyhow::Result<()> {
         if let Some(h) = self.re_panel.render_header(DrawMode::Normal) {
             echo!("{}", h)?;
@@ -416,6 +497,15 @@ impl UnpackingEventSubscriber for SimpleConsole {
             self.notify_printed();
         }
 
+        let warnings = self.warnings.render();
+        if !warnings.is_empty() {
+            echo!()?;
+            for line in warnings {
+                echo!("{}", line)?;
+            }
+            self.notify_printed();
+        }
+
         let errors = std::mem::take(&mut self.action_errors);
 
         if !errors.is_empty() {
@@ -468,6 +558,14 @@ impl UnpackingEventSubscriber for SimpleConsole {
         self.handle_stderr(&message.message).await
     }
 
+    async fn handle_structured_warning(
+        &mut self,
+        warning: &buck2_data::StructuredWarning,
+    ) -> anyhow::Result<()> {
+        self.warnings.add(warning);
+        Ok(())
+    }
+
     async fn handle_re_session_created(
         &mut self,
         session: &buck2_data::RemoteExecutionSessionCreated,
@@ -581,7 +679,13 @@ impl UnpackingEventSubscriber for SimpleConsole {
         Ok(())
     }
 
-    async fn tick(&mut self, _: &Tick) -> anyhow::Result<()> {
+    async fn tick(&mut self, tick: &Tick) -> anyhow::Result<()> {
+        if let Some(progress_interval) = self.progress_interval {
+            if self.last_progress_time.elapsed() >= progress_interval {
+                self.print_progress(tick)?;
+            }
+        }
+
         if self.verbosity.print_status() && self.last_print_time.elapsed() > KEEPALIVE_TIME_LIMIT {
             let mut show_stats = self.show_waiting_message;
 
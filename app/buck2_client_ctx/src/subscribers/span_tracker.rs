@@ -383,7 +383,8 @@ impl SpanTrackable for Arc<BuckEvent> {
                 | Data::CommandCritical(..)
                 | Data::Materialization(..)
                 | Data::DiceCriticalSection(..)
-                | Data::DiceBlockConcurrentCommand(..),
+                | Data::DiceBlockConcurrentCommand(..)
+                | Data::ConfigureTarget(..),
             ) => false,
             Some(
                 Data::ActionExecution(..)
@@ -402,7 +403,8 @@ impl SpanTrackable for Arc<BuckEvent> {
                 | Data::AnalysisStage(..)
                 | Data::ExecutorStage(..)
                 | Data::MatchDepFiles(..)
-                | Data::CacheUpload(..),
+                | Data::CacheUpload(..)
+                | Data::ResolveTargetPatterns(..),
             ) => true,
             None => false,
         }
@@ -135,6 +135,12 @@ pub trait UnpackingEventSubscriber: Send {
                 dice_block_concurrent_command,
             ) => self
                 .handle_dice_block_concurrent_command_start(dice_block_concurrent_command, event),
+            buck2_data::span_start_event::Data::ResolveTargetPatterns(resolve) => {
+                self.handle_resolve_target_patterns_start(resolve, event)
+            }
+            buck2_data::span_start_event::Data::ConfigureTarget(configure) => {
+                self.handle_configure_target_start(configure, event)
+            }
             buck2_data::span_start_event::Data::Fake(fake) => self.handle_fake_start(fake, event),
         }
         .await
@@ -216,6 +222,12 @@ pub trait UnpackingEventSubscriber: Send {
             ) => {
                 self.handle_dice_block_concurrent_command_end(dice_block_concurrent_command, event)
             }
+            buck2_data::span_end_event::Data::ResolveTargetPatterns(resolve) => {
+                self.handle_resolve_target_patterns_end(resolve, event)
+            }
+            buck2_data::span_end_event::Data::ConfigureTarget(configure) => {
+                self.handle_configure_target_end(configure, event)
+            }
             buck2_data::span_end_event::Data::Fake(fake) => self.handle_fake_end(fake, event),
         }
         .await
@@ -248,6 +260,9 @@ pub trait UnpackingEventSubscriber: Send {
             buck2_data::instant_event::Data::TestResult(result) => {
                 self.handle_test_result(result, event)
             }
+            buck2_data::instant_event::Data::TestStarted(started) => {
+                self.handle_test_started(started, event)
+            }
             buck2_data::instant_event::Data::RageInvoked(result) => {
                 self.handle_rage_invoked(result, event)
             }
@@ -277,6 +292,27 @@ pub trait UnpackingEventSubscriber: Send {
             buck2_data::instant_event::Data::DaemonShutdown(daemon_shutdown) => {
                 self.handle_daemon_shutdown(daemon_shutdown)
             }
+            buck2_data::instant_event::Data::ActionQuotaViolation(violation) => {
+                self.handle_action_quota_violation(violation)
+            }
+            buck2_data::instant_event::Data::ActionCategoryStatsSnapshot(snapshot) => {
+                self.handle_action_category_stats_snapshot(snapshot)
+            }
+            buck2_data::instant_event::Data::StructuredWarning(warning) => {
+                self.handle_structured_warning(warning)
+            }
+            buck2_data::instant_event::Data::ResourceUsageSummary(summary) => {
+                self.handle_resource_usage_summary(summary)
+            }
+            buck2_data::instant_event::Data::NondeterministicAction(action) => {
+                self.handle_nondeterministic_action(action)
+            }
+            buck2_data::instant_event::Data::DaemonStateCorrupted(corrupted) => {
+                self.handle_daemon_state_corrupted(corrupted)
+            }
+            buck2_data::instant_event::Data::TargetOutputSize(size) => {
+                self.handle_target_output_size(size)
+            }
         }
         .await
     }
@@ -449,6 +485,13 @@ pub trait UnpackingEventSubscriber: Send {
     ) -> anyhow::Result<()> {
         Ok(())
     }
+    async fn handle_test_started(
+        &mut self,
+        _started: &buck2_data::TestStarted,
+        _event: &BuckEvent,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
     async fn handle_test_result(
         &mut self,
         _result: &buck2_data::TestResult,
@@ -638,6 +681,34 @@ pub trait UnpackingEventSubscriber: Send {
     ) -> anyhow::Result<()> {
         Ok(())
     }
+    async fn handle_resolve_target_patterns_start(
+        &mut self,
+        _resolve: &buck2_data::ResolveTargetPatternsStart,
+        _event: &BuckEvent,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn handle_resolve_target_patterns_end(
+        &mut self,
+        _resolve: &buck2_data::ResolveTargetPatternsEnd,
+        _event: &BuckEvent,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn handle_configure_target_start(
+        &mut self,
+        _configure: &buck2_data::ConfigureTargetStart,
+        _event: &BuckEvent,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn handle_configure_target_end(
+        &mut self,
+        _configure: &buck2_data::ConfigureTargetEnd,
+        _event: &BuckEvent,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
     async fn handle_materialization_end(
         &mut self,
         _materialization: &buck2_data::MaterializationEnd,
@@ -689,6 +760,48 @@ pub trait UnpackingEventSubscriber: Send {
     ) -> anyhow::Result<()> {
         Ok(())
     }
+    async fn handle_action_quota_violation(
+        &mut self,
+        _violation: &buck2_data::ActionQuotaViolation,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn handle_action_category_stats_snapshot(
+        &mut self,
+        _snapshot: &buck2_data::ActionCategoryStatsSnapshot,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn handle_structured_warning(
+        &mut self,
+        _warning: &buck2_data::StructuredWarning,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn handle_resource_usage_summary(
+        &mut self,
+        _summary: &buck2_data::ResourceUsageSummary,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn handle_nondeterministic_action(
+        &mut self,
+        _action: &buck2_data::NondeterministicAction,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn handle_daemon_state_corrupted(
+        &mut self,
+        _corrupted: &buck2_data::DaemonStateCorrupted,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn handle_target_output_size(
+        &mut self,
+        _size: &buck2_data::TargetOutputSize,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
     async fn handle_tag(&mut self, _tag: &buck2_data::TagEvent) -> anyhow::Result<()> {
         Ok(())
     }
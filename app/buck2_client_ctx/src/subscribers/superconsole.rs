@@ -40,6 +40,7 @@ use superconsole::Span;
 use superconsole::State;
 pub(crate) use superconsole::SuperConsole;
 
+use crate::common::UiComponent;
 use crate::subscribers::display;
 use crate::subscribers::display::TargetDisplayOptions;
 use crate::subscribers::io::IoHeader;
@@ -138,6 +139,8 @@ pub struct SuperConsoleConfig {
     pub(crate) sandwiched: Option<Box<dyn Component>>,
     pub(crate) enable_dice: bool,
     pub(crate) enable_debug_events: bool,
+    /// Components of the default layout that aren't rendered.
+    pub(crate) hidden: Vec<UiComponent>,
 }
 
 impl StatefulSuperConsole {
@@ -146,15 +149,28 @@ impl StatefulSuperConsole {
         config: SuperConsoleConfig,
     ) -> Box<dyn Component> {
         let header = format!("Command: `{}`.", command_name);
-        let mut components: Vec<Box<dyn Component>> =
-            vec![box SessionInfoComponent, ReHeader::boxed(), box IoHeader];
+        let shown = |component| !config.hidden.contains(&component);
+        let mut components: Vec<Box<dyn Component>> = Vec::new();
+        if shown(UiComponent::Session) {
+            components.push(box SessionInfoComponent);
+        }
+        if shown(UiComponent::Re) {
+            components.push(ReHeader::boxed());
+        }
+        if shown(UiComponent::Io) {
+            components.push(box IoHeader);
+        }
         if let Some(sandwiched) = config.sandwiched {
             components.push(sandwiched);
         }
         components.push(box DebugEventsComponent);
         components.push(box DiceComponent);
         components.push(box CommandsComponent);
-        components.push(box TimedList::new(MAX_EVENTS, CUTOFFS, header));
+        if shown(UiComponent::Spans) {
+            components.push(box TimedList::new(MAX_EVENTS, CUTOFFS, header));
+        } else {
+            components.push(box TimedList::header_only(header));
+        }
         let root = box Split::new(components, Direction::Vertical, SplitKind::Adaptive);
         // bound all components to our recommended grapheme-width
         box Bounded::new(root, Some(SUPERCONSOLE_WIDTH), None)
@@ -260,6 +276,7 @@ impl SuperConsoleState {
         superconsole::state![
             self.simple_console.spans(),
             self.simple_console.action_stats(),
+            self.simple_console.action_progress(),
             &self.test_state,
             &self.session_info,
             &self.current_tick,
@@ -405,6 +422,14 @@ impl UnpackingEventSubscriber for StatefulSuperConsole {
     ) -> anyhow::Result<()> {
         match self.super_console.take() {
             Some(mut super_console) => {
+                let warnings = self.state.simple_console.warnings().render();
+                if !warnings.is_empty() {
+                    let style = ContentStyle {
+                        foreground_color: Some(Color::Yellow),
+                        ..Default::default()
+                    };
+                    super_console.emit(lines_from_multiline_string(&warnings.join("\n"), style));
+                }
                 if let cli_proto::CommandResult {
                     result: Some(cli_proto::command_result::Result::Error(e)),
                 } = result
@@ -446,6 +471,14 @@ impl UnpackingEventSubscriber for StatefulSuperConsole {
         }
     }
 
+    async fn handle_structured_warning(
+        &mut self,
+        warning: &buck2_data::StructuredWarning,
+    ) -> anyhow::Result<()> {
+        self.state.simple_console.warnings_mut().add(warning);
+        Ok(())
+    }
+
     async fn handle_re_session_created(
         &mut self,
         session: &buck2_data::RemoteExecutionSessionCreated,
@@ -580,6 +613,15 @@ impl UnpackingEventSubscriber for StatefulSuperConsole {
         Ok(())
     }
 
+    async fn handle_test_started(
+        &mut self,
+        started: &buck2_data::TestStarted,
+        _event: &BuckEvent,
+    ) -> anyhow::Result<()> {
+        self.state.test_state.start(started);
+        Ok(())
+    }
+
     async fn handle_test_result(
         &mut self,
         result: &buck2_data::TestResult,
@@ -673,6 +715,16 @@ fn lines_for_command_details(
             .attribute(Attribute::Bold),
     )]));
     lines.extend(colored_lines_from_multiline_string(&command_failed.stderr));
+    for path in &command_failed.diagnostic_outputs {
+        lines.push(Line::from_iter([Span::new_styled_lossy(
+            format!("Diagnostic output: {}", path).with(Color::DarkRed),
+        )]));
+    }
+    if let Some(scratch_dir) = &command_failed.scratch_dir {
+        lines.push(Line::from_iter([Span::new_styled_lossy(
+            format!("Temporary directory: {}", scratch_dir).with(Color::DarkRed),
+        )]));
+    }
 }
 
 // Truncates a string to a reasonable number characters, or returns None if it doesn't need truncating.
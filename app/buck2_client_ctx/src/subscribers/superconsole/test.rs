@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+use std::collections::HashMap;
 use std::fmt;
 
 use buck2_test_api::data::TestStatus;
@@ -36,10 +37,24 @@ pub(crate) struct TestState {
     pub unknown: u64,
     pub listing_success: u64,
     pub listing_failed: u64,
+    /// Tests started and not finished yet, by name. Only tests of executors streaming their
+    /// results are reported as started.
+    running: HashMap<String, u64>,
 }
 
 impl TestState {
+    pub(crate) fn start(&mut self, started: &buck2_data::TestStarted) {
+        *self.running.entry(started.name.clone()).or_default() += 1;
+    }
+
     pub(crate) fn update(&mut self, result: &buck2_data::TestResult) -> anyhow::Result<()> {
+        if let Some(running) = self.running.get_mut(&result.name) {
+            *running -= 1;
+            if *running == 0 {
+                self.running.remove(&result.name);
+            }
+        }
+
         let status = TestStatus::try_from(result.status)?;
         let counter = match status {
             TestStatus::PASS => &mut self.pass,
@@ -61,6 +76,10 @@ impl TestState {
     pub(crate) fn not_executed(&self) -> u64 {
         self.skipped + self.omitted
     }
+
+    pub(crate) fn running(&self) -> u64 {
+        self.running.values().sum()
+    }
 }
 
 #[derive(Debug)]
@@ -102,6 +121,17 @@ impl Component for TestCounterComponent {
             .to_span()?,
         );
         spans.push(". ".try_into()?);
+        if test_state.running() > 0 {
+            spans.push(
+                StylizedCount {
+                    label: "Running",
+                    count: test_state.running(),
+                    color: Color::White,
+                }
+                .to_span()?,
+            );
+            spans.push(". ".try_into()?);
+        }
         spans.push(
             StylizedCount {
                 label: "Pass",
@@ -217,3 +247,32 @@ impl<'a> fmt::Display for StylizedCountForStdio<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_running() -> anyhow::Result<()> {
+        let mut state = TestState::default();
+        let started = |name: &str| buck2_data::TestStarted {
+            name: name.to_owned(),
+        };
+        let result = |name: &str| buck2_data::TestResult {
+            name: name.to_owned(),
+            status: TestStatus::PASS.try_into().unwrap(),
+            ..Default::default()
+        };
+
+        state.start(&started("a"));
+        state.start(&started("b"));
+        assert_eq!(2, state.running());
+
+        state.update(&result("a"))?;
+        // Results of tests not reported as started are only counted.
+        state.update(&result("c"))?;
+        assert_eq!(1, state.running());
+        assert_eq!(2, state.pass);
+        Ok(())
+    }
+}
@@ -27,6 +27,7 @@ use superconsole::Span;
 use superconsole::State;
 
 use self::table_builder::Table;
+use crate::subscribers::action_progress::ActionProgress;
 use crate::subscribers::display;
 use crate::subscribers::display::TargetDisplayOptions;
 use crate::subscribers::simpleconsole::ActionStats;
@@ -233,6 +234,7 @@ impl Component for CountComponent {
     ) -> anyhow::Result<Lines> {
         let spans = state.get::<BuckEventSpanTracker>()?;
         let action_stats = state.get::<ActionStats>()?;
+        let action_progress = state.get::<ActionProgress>()?;
         let time_speed = state.get::<TimeSpeed>()?;
 
         let finished = spans.roots_completed();
@@ -245,18 +247,29 @@ impl Component for CountComponent {
 
         let contents = match mode {
             DrawMode::Normal => {
+                // Based on how long the running actions took previously, which is a lot more
+                // meaningful than the job counts when a few long actions dominate.
+                let estimate = match action_progress.estimate() {
+                    Some(estimate) => format!(
+                        " Estimated: {}%, {} left.",
+                        estimate.percent,
+                        display::duration_as_secs_elapsed(estimate.remaining, time_speed.speed())
+                    ),
+                    None => String::new(),
+                };
                 if action_stats.log_stats() {
                     format!(
-                        "Jobs: In progress: {}. Finished: {}. Cache hits: {}%. Time elapsed: {}",
+                        "Jobs: In progress: {}. Finished: {}.{} Cache hits: {}%. Time elapsed: {}",
                         progress,
                         finished,
+                        estimate,
                         action_stats.action_cache_hit_percentage(),
                         elapsed
                     )
                 } else {
                     format!(
-                        "Jobs: In progress: {}. Finished: {}. Time elapsed: {}",
-                        progress, finished, elapsed
+                        "Jobs: In progress: {}. Finished: {}.{} Time elapsed: {}",
+                        progress, finished, estimate, elapsed
                     )
                 }
             }
@@ -328,6 +341,15 @@ impl TimedList {
             child: Split::new(vec![head, body], Direction::Vertical, SplitKind::Adaptive),
         }
     }
+
+    /// Like `new`, but only displays the header and summary stats, not the ongoing events.
+    pub fn header_only(header: String) -> Self {
+        let head = box TimedListHeader::new(header);
+
+        Self {
+            child: Split::new(vec![head], Direction::Vertical, SplitKind::Adaptive),
+        }
+    }
 }
 
 impl Component for TimedList {
@@ -415,11 +437,19 @@ mod tests {
             remote_actions: 0,
             cached_actions: 1,
         };
+        let action_progress = ActionProgress::default();
 
         let timed_list_state = TimedListState::default();
 
         let output = timed_list.draw(
-            &superconsole::state!(&state, &tick, &time_speed, &action_stats, &timed_list_state),
+            &superconsole::state!(
+                &state,
+                &tick,
+                &time_speed,
+                &action_stats,
+                &action_progress,
+                &timed_list_state
+            ),
             Dimensions {
                 width: 40,
                 height: 10,
@@ -505,11 +535,19 @@ mod tests {
             remote_actions: 0,
             cached_actions: 1,
         };
+        let action_progress = ActionProgress::default();
 
         let timed_list_state = TimedListState::default();
 
         let output = timed_list.draw(
-            &superconsole::state!(&state, &tick, &time_speed, &action_stats, &timed_list_state),
+            &superconsole::state!(
+                &state,
+                &tick,
+                &time_speed,
+                &action_stats,
+                &action_progress,
+                &timed_list_state
+            ),
             Dimensions {
                 width: 40,
                 height: 10,
@@ -574,6 +612,8 @@ mod tests {
                             identifier: "identifier".into(),
                         }),
                         kind: buck2_data::ActionKind::NotSet as i32,
+                        expected_duration: None,
+                        expected_critical_path: None,
                     }
                     .into(),
                 ),
@@ -612,11 +652,19 @@ mod tests {
             remote_actions: 0,
             cached_actions: 1,
         };
+        let action_progress = ActionProgress::default();
 
         let timed_list_state = TimedListState::default();
 
         let output = timed_list.draw(
-            &superconsole::state!(&state, &tick, &time_speed, &action_stats, &timed_list_state),
+            &superconsole::state!(
+                &state,
+                &tick,
+                &time_speed,
+                &action_stats,
+                &action_progress,
+                &timed_list_state
+            ),
             Dimensions {
                 width: 80,
                 height: 10,
@@ -673,7 +721,14 @@ mod tests {
             .unwrap();
 
         let output = timed_list.draw(
-            &superconsole::state!(&state, &tick, &time_speed, &action_stats, &timed_list_state),
+            &superconsole::state!(
+                &state,
+                &tick,
+                &time_speed,
+                &action_stats,
+                &action_progress,
+                &timed_list_state
+            ),
             Dimensions {
                 width: 80,
                 height: 10,
@@ -0,0 +1,101 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use buck2_events::warnings::category_name;
+
+/// The warnings received during a command, which are printed together at the end of it rather
+/// than as they arrive. The daemon already deduplicates them.
+#[derive(Default)]
+pub(crate) struct WarningsSummary {
+    warnings: Vec<buck2_data::StructuredWarning>,
+}
+
+impl WarningsSummary {
+    pub(crate) fn add(&mut self, warning: &buck2_data::StructuredWarning) {
+        self.warnings.push(warning.clone());
+    }
+
+    /// The summary, grouped by category. Empty if there were no warnings.
+    pub(crate) fn render(&self) -> Vec<String> {
+        if self.warnings.is_empty() {
+            return Vec::new();
+        }
+
+        let mut warnings: Vec<_> = self.warnings.iter().collect();
+        // Stable, so warnings stay in the order they were emitted within a category.
+        warnings.sort_by_key(|w| w.category);
+
+        let mut lines = vec![format!("WARNINGS ({})", warnings.len())];
+        let mut current = None;
+        for warning in warnings {
+            let category = buck2_data::WarningCategory::from_i32(warning.category)
+                .unwrap_or(buck2_data::WarningCategory::Other);
+            if current != Some(category) {
+                lines.push(format!("{}:", category_name(category)));
+                current = Some(category);
+            }
+            if warning.location.is_empty() {
+                lines.push(format!("  {}", warning.message));
+            } else {
+                lines.push(format!("  {}: {}", warning.location, warning.message));
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warning(
+        category: buck2_data::WarningCategory,
+        location: &str,
+        message: &str,
+    ) -> buck2_data::StructuredWarning {
+        buck2_data::StructuredWarning {
+            category: category as i32,
+            location: location.to_owned(),
+            message: message.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let mut summary = WarningsSummary::default();
+        assert!(summary.render().is_empty());
+
+        summary.add(&warning(
+            buck2_data::WarningCategory::SlowGlob,
+            "root//foo",
+            "glob took 600ms",
+        ));
+        summary.add(&warning(
+            buck2_data::WarningCategory::Other,
+            "",
+            "something odd",
+        ));
+        summary.add(&warning(
+            buck2_data::WarningCategory::SlowGlob,
+            "root//bar",
+            "glob took 700ms",
+        ));
+        assert_eq!(
+            vec![
+                "WARNINGS (3)",
+                "other:",
+                "  something odd",
+                "slow glob:",
+                "  root//foo: glob took 600ms",
+                "  root//bar: glob took 700ms",
+            ],
+            summary.render()
+        );
+    }
+}
@@ -32,6 +32,17 @@ impl BuckVersion {
         Self::get().version()
     }
 
+    /// The revision this binary was built from, if set at build time.
+    pub fn get_revision() -> Option<&'static str> {
+        Self::compute_revision()
+    }
+
+    /// The release version (dot separated numbers, e.g. `2023.3.1`) of this binary, if set at
+    /// build time.
+    pub fn get_release_version() -> Option<&'static str> {
+        std::option_env!("BUCK2_RELEASE_VERSION").filter(|v| !v.is_empty())
+    }
+
     fn compute_revision() -> Option<&'static str> {
         if let Some(rev) = std::option_env!("BUCK2_SET_EXPLICIT_VERSION") {
             if !rev.is_empty() {
@@ -0,0 +1,192 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! `[buck2] version_required` lets a project require a recent enough buck2, so that users of an
+//! old binary get a clear error rather than failures on features it doesn't have.
+//!
+//! The value is either a minimum release version (e.g. `2023.3.1` or `>=2023.3.1`), compared
+//! against the release version embedded at build time, or a comma separated list of revisions
+//! (or revision prefixes) one of which the binary must have been built from. Binaries built
+//! without a release version or revision (e.g. local builds) aren't checked.
+
+use buck2_common::legacy_configs::LegacyBuckConfig;
+use buck2_core::env_helper::EnvHelper;
+
+use crate::immediate_config::ImmediateConfig;
+use crate::version::BuckVersion;
+
+#[derive(Debug, thiserror::Error)]
+enum VersionRequiredError {
+    #[error(
+        "Invalid `[buck2] version_required` `{0}`: expected a version such as `2023.3.1` or a list of revisions"
+    )]
+    Invalid(String),
+    #[error(
+        "This project requires buck2 {required} or newer (see `[buck2] version_required`), but this buck2 is {actual}. \
+        Please upgrade buck2. To ignore this check, set `BUCK2_IGNORE_VERSION_REQUIRED=true`."
+    )]
+    TooOld { required: String, actual: String },
+    #[error(
+        "This project requires buck2 built from one of the revisions `{required}` (see `[buck2] version_required`), \
+        but this buck2 was built from `{actual}`. Please upgrade buck2. To ignore this check, set `BUCK2_IGNORE_VERSION_REQUIRED=true`."
+    )]
+    WrongRevision { required: String, actual: String },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum VersionRequirement {
+    /// A minimum release version, as dot separated numbers.
+    AtLeast(Vec<u64>),
+    /// Revisions, or prefixes of revisions, one of which is required.
+    Revisions(Vec<String>),
+}
+
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version.split('.').map(|x| x.parse().ok()).collect()
+}
+
+impl VersionRequirement {
+    fn parse(value: &str) -> anyhow::Result<Self> {
+        let value = value.trim();
+        let version = value.strip_prefix(">=").unwrap_or(value).trim();
+        if let Some(version) = parse_version(version) {
+            return Ok(VersionRequirement::AtLeast(version));
+        }
+
+        let revisions: Vec<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+            .map(str::to_owned)
+            .collect();
+        if revisions.is_empty()
+            || !revisions
+                .iter()
+                .all(|r| r.chars().all(|c| c.is_ascii_alphanumeric()))
+        {
+            return Err(VersionRequiredError::Invalid(value.to_owned()).into());
+        }
+        Ok(VersionRequirement::Revisions(revisions))
+    }
+
+    /// Checks the requirement against the release version and revision of this binary. What
+    /// this binary doesn't know about itself isn't checked.
+    fn check(&self, release: Option<&str>, revision: Option<&str>) -> anyhow::Result<()> {
+        match self {
+            VersionRequirement::AtLeast(required) => {
+                let release = match release {
+                    Some(release) => release,
+                    None => return Ok(()),
+                };
+                // A release version that doesn't parse is newer than this code, so accept it.
+                if let Some(actual) = parse_version(release) {
+                    if actual < *required {
+                        return Err(VersionRequiredError::TooOld {
+                            required: required
+                                .iter()
+                                .map(|x| x.to_string())
+                                .collect::<Vec<_>>()
+                                .join("."),
+                            actual: release.to_owned(),
+                        }
+                        .into());
+                    }
+                }
+                Ok(())
+            }
+            VersionRequirement::Revisions(required) => {
+                let revision = match revision {
+                    Some(revision) => revision,
+                    None => return Ok(()),
+                };
+                if required.iter().any(|r| revision.starts_with(r.as_str())) {
+                    Ok(())
+                } else {
+                    Err(VersionRequiredError::WrongRevision {
+                        required: required.join(", "),
+                        actual: revision.to_owned(),
+                    }
+                    .into())
+                }
+            }
+        }
+    }
+}
+
+fn check_config(
+    config: &LegacyBuckConfig,
+    release: Option<&str>,
+    revision: Option<&str>,
+) -> anyhow::Result<()> {
+    match config.get("buck2", "version_required") {
+        Some(value) => VersionRequirement::parse(value)?.check(release, revision),
+        None => Ok(()),
+    }
+}
+
+/// Fails if the project requires a newer buck2 than this one.
+pub fn check_version_required(immediate_config: &ImmediateConfig) -> anyhow::Result<()> {
+    static IGNORE: EnvHelper<bool> = EnvHelper::new("BUCK2_IGNORE_VERSION_REQUIRED");
+    if IGNORE.get_copied()?.unwrap_or_default() {
+        return Ok(());
+    }
+
+    let config = match immediate_config.get() {
+        Ok(config) => config,
+        Err(e) => {
+            // Invalid buckconfig is reported by the daemon.
+            tracing::debug!("Not checking `[buck2] version_required`: {:#}", e);
+            return Ok(());
+        }
+    };
+    check_config(
+        config,
+        BuckVersion::get_release_version(),
+        BuckVersion::get_revision(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            VersionRequirement::AtLeast(vec![2023, 3, 1]),
+            VersionRequirement::parse(">= 2023.3.1").unwrap()
+        );
+        assert_eq!(
+            VersionRequirement::AtLeast(vec![7]),
+            VersionRequirement::parse("7").unwrap()
+        );
+        assert_eq!(
+            VersionRequirement::Revisions(vec!["abc123".to_owned(), "def456".to_owned()]),
+            VersionRequirement::parse("abc123, def456").unwrap()
+        );
+        assert!(VersionRequirement::parse("").is_err());
+        assert!(VersionRequirement::parse("1.2-beta").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let at_least = VersionRequirement::AtLeast(vec![2023, 3, 1]);
+        assert!(at_least.check(Some("2023.3.1"), None).is_ok());
+        assert!(at_least.check(Some("2023.10.0"), None).is_ok());
+        assert!(at_least.check(Some("2023.2.28"), None).is_err());
+        assert!(at_least.check(Some("2023.3"), None).is_err());
+        assert!(at_least.check(None, Some("abc")).is_ok());
+
+        let revisions = VersionRequirement::Revisions(vec!["abc1".to_owned(), "def".to_owned()]);
+        assert!(revisions.check(None, Some("abc123456")).is_ok());
+        assert!(revisions.check(None, Some("def")).is_ok());
+        assert!(revisions.check(None, Some("123abc")).is_err());
+        assert!(revisions.check(Some("2023.3.1"), None).is_ok());
+    }
+}
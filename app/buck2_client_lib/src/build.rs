@@ -0,0 +1,37 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use buck2_core::fs::project::ProjectRelativePathBuf;
+use cli_proto::BuildTarget;
+
+use crate::query::ConfiguredLabel;
+
+/// A target built by [`Buck2Client::build`](crate::Buck2Client::build).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuiltTarget {
+    pub label: ConfiguredLabel,
+    /// The default outputs of the target, relative to the project root.
+    pub outputs: Vec<ProjectRelativePathBuf>,
+    /// The arguments of the `RunInfo` of the target, if it has one.
+    pub run_args: Vec<String>,
+}
+
+impl BuiltTarget {
+    pub(crate) fn from_proto(target: BuildTarget) -> anyhow::Result<Self> {
+        Ok(Self {
+            label: ConfiguredLabel::parse(&target.target, target.configuration)?,
+            outputs: target
+                .outputs
+                .into_iter()
+                .map(|output| ProjectRelativePathBuf::try_from(output.path))
+                .collect::<anyhow::Result<_>>()?,
+            run_args: target.run_args,
+        })
+    }
+}
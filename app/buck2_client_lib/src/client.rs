@@ -0,0 +1,306 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use anyhow::Context;
+use buck2_client_ctx::command_outcome::CommandOutcome;
+use buck2_client_ctx::daemon::client::connect::BuckdConnectOptions;
+use buck2_client_ctx::daemon::client::BuckdClientConnector;
+use buck2_common::invocation_paths::InvocationPaths;
+use buck2_common::invocation_roots::find_invocation_roots;
+use buck2_core::fs::paths::file_name::FileNameBuf;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::working_dir::WorkingDir;
+use buck2_events::trace::TraceId;
+use cli_proto::build_request::build_providers;
+use cli_proto::build_request::BuildProviders;
+use cli_proto::build_request::ResponseOptions;
+use cli_proto::config_override::ConfigType;
+use cli_proto::protocol;
+use cli_proto::BuildRequest;
+use cli_proto::ClientContext;
+use cli_proto::ConfigOverride;
+use cli_proto::CqueryRequest;
+use cli_proto::CqueryTarget;
+use cli_proto::HandshakeRequest;
+use parking_lot::Mutex;
+
+use crate::build::BuiltTarget;
+use crate::output::CapturedOutput;
+use crate::query::ConfiguredLabel;
+use crate::query::TargetProviders;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Buck2ClientError {
+    #[error("Working directory is not UTF-8: `{0}`")]
+    WorkingDirNotUtf8(WorkingDir),
+    #[error("`{command}` failed:\n{}", .errors.join("\n"))]
+    CommandFailed {
+        command: &'static str,
+        errors: Vec<String>,
+    },
+    #[error("The buck2 daemon (version `{daemon_version}`) doesn't support `{command}`")]
+    Unsupported {
+        command: &'static str,
+        daemon_version: String,
+    },
+}
+
+/// The commands run by this client, whose support is checked once per daemon.
+const COMMANDS: &[&str] = &["build", "cquery"];
+
+/// The result of the handshake with a daemon.
+#[derive(Clone)]
+struct Handshake {
+    /// The pid and endpoint of the daemon, to handshake again when it is restarted.
+    daemon: (i64, String),
+    daemon_version: String,
+    /// The commands in `COMMANDS` the daemon supports.
+    commands: Vec<String>,
+}
+
+/// A client running commands against the buck2 daemon of a project.
+///
+/// Each command connects to the daemon separately, so a client can be kept around for the
+/// lifetime of the program, and used concurrently.
+pub struct Buck2Client {
+    paths: InvocationPaths,
+    working_dir: WorkingDir,
+    target_platforms: Option<String>,
+    config_overrides: Vec<String>,
+    /// The handshake with the daemon last connected to.
+    handshake: Mutex<Option<Handshake>>,
+}
+
+impl Buck2Client {
+    /// A client for the project containing `working_dir`, which target patterns and queries are
+    /// relative to.
+    pub fn new(working_dir: WorkingDir) -> anyhow::Result<Self> {
+        let roots = find_invocation_roots(working_dir.path())?;
+        Ok(Self {
+            paths: InvocationPaths {
+                roots,
+                // Same as the default of `buck2 --isolation-dir`.
+                isolation: FileNameBuf::unchecked_new("v2"),
+            },
+            working_dir,
+            target_platforms: None,
+            config_overrides: Vec::new(),
+            handshake: Mutex::new(None),
+        })
+    }
+
+    /// Talk to the daemon of this isolation dir, as `buck2 --isolation-dir` does.
+    pub fn with_isolation_dir(mut self, isolation: FileNameBuf) -> Self {
+        self.paths.isolation = isolation;
+        self
+    }
+
+    /// Configure the targets for these platforms, as `--target-platforms` does.
+    pub fn with_target_platforms(mut self, target_platforms: String) -> Self {
+        self.target_platforms = Some(target_platforms);
+        self
+    }
+
+    /// Override a buckconfig, given as `section.key=value`, as `--config` does.
+    pub fn with_config_override(mut self, config_override: String) -> Self {
+        self.config_overrides.push(config_override);
+        self
+    }
+
+    pub fn project_root(&self) -> &ProjectRoot {
+        self.paths.project_root()
+    }
+
+    /// Evaluate a `cquery` expression, returning the targets it matches.
+    pub async fn cquery(&self, query: &str) -> anyhow::Result<Vec<ConfiguredLabel>> {
+        self.run_cquery(query, false)
+            .await?
+            .into_iter()
+            .map(|target| {
+                ConfiguredLabel::from_proto(
+                    target
+                        .label
+                        .context("The daemon returned a target without a label")?,
+                )
+            })
+            .collect()
+    }
+
+    /// Evaluate a `cquery` expression, returning the providers of the targets it matches.
+    pub async fn cquery_providers(&self, query: &str) -> anyhow::Result<Vec<TargetProviders>> {
+        self.run_cquery(query, true)
+            .await?
+            .into_iter()
+            .map(TargetProviders::from_proto)
+            .collect()
+    }
+
+    /// Build the default outputs of the targets matching `patterns`.
+    pub async fn build(&self, patterns: &[String]) -> anyhow::Result<Vec<BuiltTarget>> {
+        let output = CapturedOutput::default();
+        let mut buckd = self.connect("build", &output).await?;
+        let response = buckd
+            .with_flushing()
+            .build(
+                BuildRequest {
+                    context: Some(self.client_context()?),
+                    target_patterns: patterns
+                        .iter()
+                        .map(|value| buck2_data::TargetPattern {
+                            value: value.clone(),
+                        })
+                        .collect(),
+                    build_providers: Some(BuildProviders {
+                        default_info: build_providers::Action::Build as i32,
+                        run_info: build_providers::Action::BuildIfAvailable as i32,
+                        test_info: build_providers::Action::Skip as i32,
+                    }),
+                    response_options: Some(ResponseOptions {
+                        return_outputs: true,
+                        return_default_other_outputs: false,
+                    }),
+                    build_opts: Some(Default::default()),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await?;
+        let response = check_outcome("build", response, &output)?;
+        check_errors("build", response.error_messages)?;
+        response
+            .build_targets
+            .into_iter()
+            .map(BuiltTarget::from_proto)
+            .collect()
+    }
+
+    async fn run_cquery(
+        &self,
+        query: &str,
+        show_providers: bool,
+    ) -> anyhow::Result<Vec<CqueryTarget>> {
+        let output = CapturedOutput::default();
+        let mut buckd = self.connect("cquery", &output).await?;
+        let response = buckd
+            .with_flushing()
+            .cquery(
+                CqueryRequest {
+                    context: Some(self.client_context()?),
+                    query: query.to_owned(),
+                    show_providers,
+                    correct_owner: true,
+                    return_targets: true,
+                    ..Default::default()
+                },
+                None,
+            )
+            .await?;
+        let response = check_outcome("cquery", response, &output)?;
+        check_errors("cquery", response.error_messages)?;
+        Ok(response.targets)
+    }
+
+    /// Connect to the daemon, checking it can run `command`. The daemon is only asked which
+    /// commands it supports on the first connection to it.
+    async fn connect(
+        &self,
+        command: &'static str,
+        output: &CapturedOutput,
+    ) -> anyhow::Result<BuckdClientConnector> {
+        let mut buckd =
+            BuckdConnectOptions::existing_only_with_subscribers(vec![output.subscriber()])
+                .connect(&self.paths)
+                .await
+                .context("Failed to connect to buck daemon, it must be started by running buck2")?;
+        let handshake = self.handshake(&mut buckd, output).await?;
+        if !handshake.commands.iter().any(|c| c == command) {
+            return Err(Buck2ClientError::Unsupported {
+                command,
+                daemon_version: handshake.daemon_version,
+            }
+            .into());
+        }
+        Ok(buckd)
+    }
+
+    async fn handshake(
+        &self,
+        buckd: &mut BuckdClientConnector,
+        output: &CapturedOutput,
+    ) -> anyhow::Result<Handshake> {
+        let info = buckd.daemon_info();
+        let daemon = (info.pid, info.endpoint.clone());
+        if let Some(handshake) = &*self.handshake.lock() {
+            if handshake.daemon == daemon {
+                return Ok(handshake.clone());
+            }
+        }
+
+        let response = buckd
+            .with_flushing()
+            .handshake(HandshakeRequest {
+                protocol_version: protocol::PROTOCOL_VERSION,
+                capabilities: COMMANDS.iter().map(|c| (*c).to_owned()).collect(),
+            })
+            .await?;
+        let response = check_outcome("handshake", response, output)?;
+        let handshake = Handshake {
+            daemon,
+            daemon_version: response.daemon_version,
+            commands: response.capabilities,
+        };
+        *self.handshake.lock() = Some(handshake.clone());
+        Ok(handshake)
+    }
+
+    fn client_context(&self) -> anyhow::Result<ClientContext> {
+        Ok(ClientContext {
+            working_dir: self
+                .working_dir
+                .path()
+                .to_str()
+                .ok_or_else(|| Buck2ClientError::WorkingDirNotUtf8(self.working_dir.clone()))?
+                .to_owned(),
+            config_overrides: self
+                .config_overrides
+                .iter()
+                .map(|config_override| ConfigOverride {
+                    config_override: config_override.clone(),
+                    config_type: ConfigType::Value as i32,
+                })
+                .collect(),
+            target_platform: self.target_platforms.clone().unwrap_or_default(),
+            trace_id: TraceId::new().to_string(),
+            ..Default::default()
+        })
+    }
+}
+
+fn check_outcome<R>(
+    command: &'static str,
+    outcome: CommandOutcome<R>,
+    output: &CapturedOutput,
+) -> anyhow::Result<R> {
+    match outcome {
+        CommandOutcome::Success(response) => Ok(response),
+        CommandOutcome::Failure(_) => Err(Buck2ClientError::CommandFailed {
+            command,
+            errors: output.take_errors(),
+        }
+        .into()),
+    }
+}
+
+fn check_errors(command: &'static str, errors: Vec<String>) -> anyhow::Result<()> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Buck2ClientError::CommandFailed { command, errors }.into())
+    }
+}
@@ -0,0 +1,41 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Rust API to run queries and builds against a running buck2 daemon.
+//!
+//! Programs embedding buck2 can link this crate instead of running `buck2` and parsing its
+//! stdout. Commands are sent to the daemon of the project containing the working directory,
+//! which must already be running (e.g. started by a previous `buck2` command), and their
+//! results are returned as Rust values:
+//!
+//! ```ignore
+//! let client = Buck2Client::new(WorkingDir::current_dir()?)?;
+//! for label in client.cquery("deps(//foo:bar)").await? {
+//!     println!("{} in {}", label.target, label.configuration);
+//! }
+//! ```
+//!
+//! The daemon isn't restarted when its version differs from the version of this crate: commands
+//! fail instead when the daemon speaks another version of the protocol, or doesn't support them
+//! (see `cli_proto::protocol`).
+
+#![feature(box_syntax)]
+
+pub mod build;
+pub mod client;
+mod output;
+pub mod query;
+
+pub use crate::build::BuiltTarget;
+pub use crate::client::Buck2Client;
+pub use crate::client::Buck2ClientError;
+pub use crate::query::ConfigurationName;
+pub use crate::query::ConfiguredLabel;
+pub use crate::query::ProviderSummary;
+pub use crate::query::TargetProviders;
@@ -0,0 +1,51 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use buck2_client_ctx::subscribers::subscriber::EventSubscriber;
+use cli_proto::command_result;
+use gazebo::prelude::*;
+use parking_lot::Mutex;
+
+#[derive(Default)]
+struct CapturedOutputData {
+    errors: Vec<String>,
+}
+
+/// The errors a command failed with, which the client returns instead of printing them.
+#[derive(Default, Clone, Dupe)]
+pub(crate) struct CapturedOutput(Arc<Mutex<CapturedOutputData>>);
+
+impl CapturedOutput {
+    pub(crate) fn subscriber(&self) -> Box<dyn EventSubscriber> {
+        box CapturingSubscriber(self.dupe())
+    }
+
+    /// The errors the command failed with.
+    pub(crate) fn take_errors(&self) -> Vec<String> {
+        std::mem::take(&mut self.0.lock().errors)
+    }
+}
+
+struct CapturingSubscriber(CapturedOutput);
+
+#[async_trait]
+impl EventSubscriber for CapturingSubscriber {
+    async fn handle_command_result(
+        &mut self,
+        result: &cli_proto::CommandResult,
+    ) -> anyhow::Result<()> {
+        if let Some(command_result::Result::Error(e)) = &result.result {
+            (self.0).0.lock().errors.extend(e.messages.iter().cloned());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::fmt;
+use std::fmt::Display;
+
+use buck2_core::cells::paths::CellRelativePath;
+use buck2_core::cells::CellName;
+use buck2_core::package::Package;
+use buck2_core::target::TargetLabel;
+use buck2_core::target::TargetName;
+use cli_proto::CqueryTarget;
+
+#[derive(Debug, thiserror::Error)]
+enum QueryOutputError {
+    #[error("Expected a package like `cell//pkg`, got `{0}`")]
+    InvalidPackage(String),
+    #[error("Expected a target label like `cell//pkg:name`, got `{0}`")]
+    InvalidTargetLabel(String),
+    #[error("The daemon returned a target without a label")]
+    MissingLabel,
+}
+
+/// The configuration of a target, as named by the daemon, e.g.
+/// `root//platforms:linux#0123456789abcdef`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConfigurationName(String);
+
+impl ConfigurationName {
+    pub fn new(full_name: String) -> Self {
+        Self(full_name)
+    }
+
+    pub fn full_name(&self) -> &str {
+        &self.0
+    }
+
+    /// The label of the platform the configuration comes from, if it comes from one (rather than
+    /// being e.g. `<unspecified>`).
+    pub fn platform(&self) -> Option<&str> {
+        self.0.rsplit_once('#').map(|(platform, _hash)| platform)
+    }
+}
+
+impl Display for ConfigurationName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A target label in a configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConfiguredLabel {
+    pub target: TargetLabel,
+    pub configuration: ConfigurationName,
+}
+
+impl ConfiguredLabel {
+    pub(crate) fn from_proto(label: buck2_data::ConfiguredTargetLabel) -> anyhow::Result<Self> {
+        let target = label.label.ok_or(QueryOutputError::MissingLabel)?;
+        Ok(Self {
+            target: target_label(&target.package, &target.name)?,
+            configuration: ConfigurationName::new(
+                label.configuration.unwrap_or_default().full_name,
+            ),
+        })
+    }
+
+    /// From a label printed like `cell//pkg:name`, and the full name of its configuration.
+    pub(crate) fn parse(target: &str, configuration: String) -> anyhow::Result<Self> {
+        let (package, name) = target
+            .rsplit_once(':')
+            .ok_or_else(|| QueryOutputError::InvalidTargetLabel(target.to_owned()))?;
+        Ok(Self {
+            target: target_label(package, name)?,
+            configuration: ConfigurationName::new(configuration),
+        })
+    }
+}
+
+impl Display for ConfiguredLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.target, self.configuration)
+    }
+}
+
+fn target_label(package: &str, name: &str) -> anyhow::Result<TargetLabel> {
+    let (cell, path) = package
+        .split_once("//")
+        .ok_or_else(|| QueryOutputError::InvalidPackage(package.to_owned()))?;
+    Ok(TargetLabel::new(
+        Package::new(
+            &CellName::unchecked_new(cell.to_owned()),
+            <&CellRelativePath>::try_from(path)?,
+        ),
+        TargetName::new(name)?,
+    ))
+}
+
+/// A provider returned by the analysis of a target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderSummary {
+    /// The name of the provider, e.g. `DefaultInfo`.
+    pub name: String,
+    /// The fields of the provider, as printed by `cquery --show-providers --json`.
+    pub value: serde_json::Value,
+}
+
+/// The providers of a target, in the order the rule returned them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetProviders {
+    pub label: ConfiguredLabel,
+    pub providers: Vec<ProviderSummary>,
+}
+
+impl TargetProviders {
+    pub(crate) fn from_proto(target: CqueryTarget) -> anyhow::Result<Self> {
+        Ok(Self {
+            label: ConfiguredLabel::from_proto(
+                target.label.ok_or(QueryOutputError::MissingLabel)?,
+            )?,
+            providers: target
+                .providers
+                .into_iter()
+                .map(|provider| {
+                    Ok(ProviderSummary {
+                        value: serde_json::from_str(&provider.json)?,
+                        name: provider.name,
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::configuration::Configuration;
+    use buck2_core::target::testing::TargetLabelExt;
+    use buck2_data::ToProtoMessage;
+    use cli_proto::CqueryProvider;
+
+    use super::*;
+
+    #[test]
+    fn test_configured_label_from_proto() -> anyhow::Result<()> {
+        // What the daemon sends for a target.
+        let target = TargetLabel::testing_parse("root//foo/bar:baz");
+        let configured = target.configure(Configuration::testing_new());
+
+        let label = ConfiguredLabel::from_proto(configured.as_proto())?;
+        assert_eq!(target, label.target);
+        assert_eq!(
+            Configuration::testing_new().full_name(),
+            label.configuration.full_name()
+        );
+        assert_eq!(configured.to_string(), label.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_configured_label_parse() -> anyhow::Result<()> {
+        let label = ConfiguredLabel::parse(
+            "root//foo:bar",
+            "root//platforms:linux#0123456789abcdef".to_owned(),
+        )?;
+        assert_eq!(TargetLabel::testing_parse("root//foo:bar"), label.target);
+        assert_eq!(
+            Some("root//platforms:linux"),
+            label.configuration.platform()
+        );
+        assert_eq!(
+            None,
+            ConfigurationName::new("<unspecified>".to_owned()).platform()
+        );
+
+        assert!(ConfiguredLabel::parse("root//foo", "<unspecified>".to_owned()).is_err());
+        assert!(ConfiguredLabel::parse("foo:bar", "<unspecified>".to_owned()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_target_providers_from_proto() -> anyhow::Result<()> {
+        let target = TargetProviders::from_proto(CqueryTarget {
+            label: Some(
+                TargetLabel::testing_parse("root//foo:bar")
+                    .configure(Configuration::testing_new())
+                    .as_proto(),
+            ),
+            providers: vec![
+                CqueryProvider {
+                    name: "RunInfo".to_owned(),
+                    json: r#"{"args": []}"#.to_owned(),
+                },
+                CqueryProvider {
+                    name: "DefaultInfo".to_owned(),
+                    json: r#"{"sub_targets": {}, "default_outputs": []}"#.to_owned(),
+                },
+            ],
+        })?;
+        assert_eq!(
+            TargetLabel::testing_parse("root//foo:bar"),
+            target.label.target
+        );
+        assert_eq!(
+            vec!["RunInfo", "DefaultInfo"],
+            target
+                .providers
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(serde_json::json!({"args": []}), target.providers[0].value);
+
+        assert!(TargetProviders::from_proto(CqueryTarget {
+            label: None,
+            providers: Vec::new(),
+        })
+        .is_err());
+        Ok(())
+    }
+}
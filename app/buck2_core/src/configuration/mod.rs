@@ -222,6 +222,18 @@ impl Configuration {
         }
     }
 
+    /// Looks up a known configuration from its hash alone, as found in output paths. Like
+    /// `lookup_from_string`, this can only find configurations that have already been
+    /// encountered by the current daemon process.
+    pub fn lookup_from_hash(hash: &str) -> anyhow::Result<Self> {
+        match INTERNER.get(hash) {
+            Some(cfg) => Ok(Self(cfg)),
+            None => Err(
+                ConfigurationLookupError::ConfigNotFound(hash.to_owned(), hash.to_owned()).into(),
+            ),
+        }
+    }
+
     pub fn get_constraint_value(
         &self,
         key: &ConstraintKey,
@@ -274,6 +274,16 @@ pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> anyhow::Re
     Ok(())
 }
 
+/// Writes to a temporary file next to `path`, then renames it to `path`, so that `path` has
+/// either its previous or its new contents in full, even if the process dies while writing.
+pub fn write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    write(&tmp, contents)?;
+    rename(&tmp, path)
+}
+
 pub fn metadata<P: AsRef<Path>>(path: P) -> anyhow::Result<fs::Metadata> {
     let _guard = IoCounterKey::Stat.guard();
     fs::metadata(&path).with_context(|| format!("metadata({})", P::as_ref(&path).display()))
@@ -638,4 +648,17 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_write_atomic() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let f = tempdir.path().join("f");
+
+        fs_util::write_atomic(&f, b"old")?;
+        fs_util::write_atomic(&f, b"new")?;
+        assert_eq!(fs_util::read_to_string(&f)?, "new");
+        assert_eq!(fs::read_dir(tempdir.path())?.count(), 1);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,275 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Creating symlinks on Windows requires either administrator privileges or developer mode.
+//! When neither is available, we can still produce a usable output tree by substituting the
+//! symlink with something else. This module implements those substitutions.
+
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use allocative::Allocative;
+use anyhow::Context;
+use gazebo::dupe::Dupe;
+use thiserror::Error;
+
+use crate::fs::fs_util;
+
+/// What to do when the OS refuses to create a symlink because we lack the privilege to do so.
+#[derive(Copy, Clone, Dupe, Debug, Eq, PartialEq, Allocative)]
+pub enum SymlinkFallback {
+    /// Do not fall back, propagate the error.
+    None,
+    /// Copy the symlink target (recursively, for directories).
+    Copy,
+    /// Hardlink the symlink target (file by file, for directories).
+    Hardlink,
+    /// Create a directory junction for directories, and a hardlink for files.
+    Junction,
+}
+
+#[derive(Debug, Error)]
+enum SymlinkFallbackError {
+    #[error(
+        "Invalid value for buckconfig `[buck2] windows_symlink_fallback`. Got `{0}`. Expected one of `none`, `copy`, `hardlink` or `junction`."
+    )]
+    InvalidValueForConfig(String),
+    #[error("Cannot fall back to `{0}` for symlink `{1}`: its target `{2}` does not exist")]
+    MissingTarget(SymlinkFallback, PathBuf, PathBuf),
+}
+
+impl Default for SymlinkFallback {
+    fn default() -> Self {
+        SymlinkFallback::None
+    }
+}
+
+impl FromStr for SymlinkFallback {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "" | "none" => Ok(SymlinkFallback::None),
+            "copy" => Ok(SymlinkFallback::Copy),
+            "hardlink" => Ok(SymlinkFallback::Hardlink),
+            "junction" => Ok(SymlinkFallback::Junction),
+            v => Err(SymlinkFallbackError::InvalidValueForConfig(v.to_owned()).into()),
+        }
+    }
+}
+
+impl fmt::Display for SymlinkFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            SymlinkFallback::None => "none",
+            SymlinkFallback::Copy => "copy",
+            SymlinkFallback::Hardlink => "hardlink",
+            SymlinkFallback::Junction => "junction",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Create a symlink at `link` pointing to `original`. If that fails because we do not have the
+/// privilege to create symlinks and `fallback` is set, nothing is created and this returns
+/// `false`: the fallback must then be applied with `apply_symlink_fallbacks`, once the target of
+/// the symlink exists.
+pub fn symlink_or_defer<P, Q>(
+    original: P,
+    link: Q,
+    fallback: SymlinkFallback,
+) -> anyhow::Result<bool>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    match fs_util::symlink(original, link) {
+        Ok(()) => Ok(true),
+        Err(e) if fallback != SymlinkFallback::None && is_symlink_privilege_error(&e) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Substitute the symlinks that `symlink_or_defer` could not create, given as `(original, link)`
+/// pairs, according to `fallback`. Their targets must exist by now, but may themselves be
+/// substituted symlinks of this batch, so the links whose targets exist are substituted first,
+/// until all of them are.
+pub fn apply_symlink_fallbacks(
+    links: Vec<(PathBuf, PathBuf)>,
+    fallback: SymlinkFallback,
+) -> anyhow::Result<()> {
+    let mut pending = links
+        .into_iter()
+        .map(|(original, link)| {
+            let target = if original.is_absolute() {
+                original
+            } else {
+                link.parent()
+                    .context("Expected path with a parent in symlink target")?
+                    .join(original)
+            };
+            Ok((target, link))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    while !pending.is_empty() {
+        let before = pending.len();
+        let mut blocked = Vec::new();
+        for (target, link) in pending {
+            match fs_util::metadata(&target) {
+                Ok(meta) => substitute(&target, &link, meta.is_dir(), fallback)?,
+                Err(_) => blocked.push((target, link)),
+            }
+        }
+        if blocked.len() == before {
+            let (target, link) = blocked.swap_remove(0);
+            return Err(SymlinkFallbackError::MissingTarget(fallback, link, target).into());
+        }
+        pending = blocked;
+    }
+    Ok(())
+}
+
+fn substitute(
+    target: &Path,
+    link: &Path,
+    is_dir: bool,
+    fallback: SymlinkFallback,
+) -> anyhow::Result<()> {
+    match (fallback, is_dir) {
+        (SymlinkFallback::None, _) => {
+            return Err(anyhow::anyhow!(
+                "Cannot create symlink `{}` -> `{}` without a fallback",
+                link.display(),
+                target.display()
+            ));
+        }
+        (SymlinkFallback::Copy, true) => copy_dir_with(target, link, |s, d| {
+            fs_util::copy(s, d)?;
+            Ok(())
+        })?,
+        (SymlinkFallback::Copy, false) => {
+            fs_util::copy(target, link)?;
+        }
+        (SymlinkFallback::Hardlink, true) => {
+            copy_dir_with(target, link, |s, d| fs_util::hard_link(s, d))?
+        }
+        (SymlinkFallback::Hardlink, false) | (SymlinkFallback::Junction, false) => {
+            fs_util::hard_link(target, link)?
+        }
+        (SymlinkFallback::Junction, true) => junction(target, link)?,
+    }
+    Ok(())
+}
+
+/// Whether this error is the OS telling us we are not allowed to create symlinks.
+fn is_symlink_privilege_error(err: &anyhow::Error) -> bool {
+    // ERROR_PRIVILEGE_NOT_HELD
+    const PRIVILEGE_NOT_HELD: i32 = 1314;
+
+    cfg!(windows)
+        && err.chain().any(|e| {
+            e.downcast_ref::<std::io::Error>()
+                .and_then(|e| e.raw_os_error())
+                == Some(PRIVILEGE_NOT_HELD)
+        })
+}
+
+/// Recreate the directory tree at `src` under `dest`, using `file_op` to create each file.
+fn copy_dir_with(
+    src: &Path,
+    dest: &Path,
+    file_op: impl Fn(&Path, &Path) -> anyhow::Result<()> + Copy,
+) -> anyhow::Result<()> {
+    fs_util::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(src).with_context(|| format!("read_dir({})", src.display()))? {
+        let entry = entry?;
+        let src_path = entry.path();
+        let dest_path = dest.join(entry.file_name());
+        if fs_util::metadata(&src_path)?.is_dir() {
+            copy_dir_with(&src_path, &dest_path, file_op)?;
+        } else {
+            file_op(&src_path, &dest_path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn junction(target: &Path, link: &Path) -> anyhow::Result<()> {
+    let target = std::path::absolute(target)?;
+    ::junction::create(&target, link).with_context(|| {
+        format!(
+            "junction(target={}, link={})",
+            target.display(),
+            link.display()
+        )
+    })
+}
+
+#[cfg(not(windows))]
+fn junction(target: &Path, link: &Path) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!(
+        "Cannot create junction `{}` -> `{}`: junctions are only supported on Windows",
+        link.display(),
+        target.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::fs::fs_util;
+    use crate::fs::symlink_fallback::apply_symlink_fallbacks;
+    use crate::fs::symlink_fallback::SymlinkFallback;
+
+    #[test]
+    fn test_parse() -> anyhow::Result<()> {
+        assert_eq!(SymlinkFallback::None, "".parse()?);
+        assert_eq!(SymlinkFallback::None, "none".parse()?);
+        assert_eq!(SymlinkFallback::Copy, "copy".parse()?);
+        assert_eq!(SymlinkFallback::Hardlink, "hardlink".parse()?);
+        assert_eq!(SymlinkFallback::Junction, "junction".parse()?);
+        assert!("symlink".parse::<SymlinkFallback>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_symlink_fallbacks() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let root = tempdir.path();
+        fs_util::create_dir_all(root.join("dir"))?;
+        fs_util::write(root.join("dir/file"), "contents")?;
+
+        // `to_link` points to `link_to_dir`, which is only substituted in the same batch.
+        apply_symlink_fallbacks(
+            vec![
+                (PathBuf::from("link_to_dir/file"), root.join("to_link")),
+                (PathBuf::from("dir"), root.join("link_to_dir")),
+            ],
+            SymlinkFallback::Copy,
+        )?;
+        assert_eq!("contents", fs_util::read_to_string(root.join("to_link"))?);
+        assert_eq!(
+            "contents",
+            fs_util::read_to_string(root.join("link_to_dir/file"))?
+        );
+
+        let err = apply_symlink_fallbacks(
+            vec![(PathBuf::from("missing"), root.join("dangling"))],
+            SymlinkFallback::Copy,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("does not exist"), "{:#}", err);
+        Ok(())
+    }
+}
@@ -16,6 +16,7 @@ use futures::stream::StreamExt;
 
 use crate::run::CommandEvent;
 use crate::run::GatherOutputStatus;
+use crate::run::ResourceUsage;
 
 pub fn encode_event_stream<S>(
     s: S,
@@ -33,22 +34,35 @@ where
             CommandEvent::Stderr(bytes) => Data::Stderr(buck2_forkserver_proto::StreamEvent {
                 data: bytes.to_vec(),
             }),
-            CommandEvent::Exit(GatherOutputStatus::Finished(status)) => {
+            CommandEvent::Exit(GatherOutputStatus::Finished {
+                exit_status,
+                resource_usage,
+            }) => {
                 let exit_code;
 
                 #[cfg(unix)]
                 {
                     use std::os::unix::process::ExitStatusExt;
-                    exit_code = status.into_raw();
+                    exit_code = exit_status.into_raw();
                 }
 
                 #[cfg(not(unix))]
                 {
                     // Windows will always set an exit code.
-                    exit_code = status.code().unwrap_or(1);
+                    exit_code = exit_status.code().unwrap_or(1);
                 }
 
-                Data::Exit(buck2_forkserver_proto::ExitEvent { exit_code })
+                Data::Exit(buck2_forkserver_proto::ExitEvent {
+                    exit_code,
+                    resource_usage: resource_usage.map(|usage| {
+                        buck2_forkserver_proto::ResourceUsage {
+                            user_cpu_us: usage.user_cpu_us,
+                            system_cpu_us: usage.system_cpu_us,
+                            disk_read_bytes: usage.disk_read_bytes,
+                            disk_write_bytes: usage.disk_write_bytes,
+                        }
+                    }),
+                })
             }
             CommandEvent::Exit(GatherOutputStatus::TimedOut(duration)) => {
                 Data::Timeout(buck2_forkserver_proto::TimeoutEvent {
@@ -87,7 +101,10 @@ where
             Data::Stderr(buck2_forkserver_proto::StreamEvent { data }) => {
                 CommandEvent::Stderr(data.into())
             }
-            Data::Exit(buck2_forkserver_proto::ExitEvent { exit_code }) => {
+            Data::Exit(buck2_forkserver_proto::ExitEvent {
+                exit_code,
+                resource_usage,
+            }) => {
                 let exit_status;
 
                 #[cfg(unix)]
@@ -102,7 +119,15 @@ where
                     exit_status = ExitStatus::from_raw(exit_code as _)
                 }
 
-                CommandEvent::Exit(GatherOutputStatus::Finished(exit_status))
+                CommandEvent::Exit(GatherOutputStatus::Finished {
+                    exit_status,
+                    resource_usage: resource_usage.map(|usage| ResourceUsage {
+                        user_cpu_us: usage.user_cpu_us,
+                        system_cpu_us: usage.system_cpu_us,
+                        disk_read_bytes: usage.disk_read_bytes,
+                        disk_write_bytes: usage.disk_write_bytes,
+                    }),
+                })
             }
             Data::Timeout(buck2_forkserver_proto::TimeoutEvent { duration }) => {
                 CommandEvent::Exit(GatherOutputStatus::TimedOut(
@@ -33,9 +33,24 @@ use tokio_util::codec::FramedRead;
 use self::interruptible_async_read::InterruptNotifiable;
 use self::interruptible_async_read::InterruptibleAsyncRead;
 
+/// The resources a command used, as reported by the OS when it's reaped. They include the
+/// resources of the processes it spawned and waited for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub user_cpu_us: u64,
+    pub system_cpu_us: u64,
+    /// Bytes read from and written to storage.
+    pub disk_read_bytes: Option<u64>,
+    pub disk_write_bytes: Option<u64>,
+}
+
 #[derive(Debug)]
 pub enum GatherOutputStatus {
-    Finished(ExitStatus),
+    Finished {
+        exit_status: ExitStatus,
+        /// Only available on Linux.
+        resource_usage: Option<ResourceUsage>,
+    },
     TimedOut(Duration),
     Cancelled,
     SpawnFailed(String),
@@ -177,7 +192,7 @@ where
     let status = async move {
         let (result, cancelled) = {
             let wait = async {
-                let status = GatherOutputStatus::Finished(child.wait().await?);
+                let status = wait_for_exit(&mut child).await?;
                 anyhow::Ok((status, false))
             };
 
@@ -212,6 +227,74 @@ where
     Ok(CommandEventStream::new(status, stdio).right_stream())
 }
 
+/// Waits for the child to exit. On Linux, the resources it used are read with `waitid(WNOWAIT)`
+/// once it has exited, which leaves the child for tokio to reap. Like tokio, this checks whether
+/// it exited every time a `SIGCHLD` is received.
+#[cfg(target_os = "linux")]
+async fn wait_for_exit(child: &mut Child) -> anyhow::Result<GatherOutputStatus> {
+    use tokio::signal::unix::signal;
+    use tokio::signal::unix::SignalKind;
+
+    let pid = match child.id() {
+        Some(pid) => pid as libc::id_t,
+        None => {
+            // Already reaped.
+            return Ok(GatherOutputStatus::Finished {
+                exit_status: child.wait().await?,
+                resource_usage: None,
+            });
+        }
+    };
+
+    // Listen before checking, so that we can't miss the child exiting in between.
+    let mut sigchld = signal(SignalKind::child()).context("Failed to listen to SIGCHLD")?;
+    let usage = loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        // The libc wrapper of `waitid` doesn't take the `rusage` argument of the syscall.
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_waitid,
+                libc::P_PID,
+                pid,
+                &mut info as *mut libc::siginfo_t,
+                libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+                &mut usage as *mut libc::rusage,
+            )
+        };
+        if res == -1 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to wait for process {}", pid));
+        }
+        // With `WNOHANG`, the pid is left zeroed if the child hasn't exited yet.
+        if unsafe { info.si_pid() } != 0 {
+            break usage;
+        }
+        sigchld.recv().await;
+    };
+
+    let micros = |tv: libc::timeval| tv.tv_sec as u64 * 1_000_000 + tv.tv_usec as u64;
+    // Counted in blocks of 512 bytes.
+    let disk_bytes = |blocks: libc::c_long| Some(blocks as u64 * 512);
+    Ok(GatherOutputStatus::Finished {
+        exit_status: child.wait().await?,
+        resource_usage: Some(ResourceUsage {
+            user_cpu_us: micros(usage.ru_utime),
+            system_cpu_us: micros(usage.ru_stime),
+            disk_read_bytes: disk_bytes(usage.ru_inblock),
+            disk_write_bytes: disk_bytes(usage.ru_oublock),
+        }),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn wait_for_exit(child: &mut Child) -> anyhow::Result<GatherOutputStatus> {
+    Ok(GatherOutputStatus::Finished {
+        exit_status: child.wait().await?,
+        resource_usage: None,
+    })
+}
+
 pub(crate) async fn decode_command_event_stream<S>(
     stream: S,
 ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>
@@ -369,13 +452,61 @@ mod tests {
         cmd.args(["-c", "echo hello"]);
 
         let (status, stdout, stderr) = gather_output(cmd, futures::future::pending()).await?;
-        assert!(matches!(status, GatherOutputStatus::Finished(s) if s.code() == Some(0)));
+        assert!(
+            matches!(status, GatherOutputStatus::Finished { exit_status, .. } if exit_status.code() == Some(0))
+        );
         assert_eq!(str::from_utf8(&stdout)?.trim(), "hello");
         assert_eq!(stderr, b"");
 
         Ok(())
     }
 
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_gather_output_resource_usage() -> anyhow::Result<()> {
+        let mut cmd = background_command("sh");
+        cmd.args([
+            "-c",
+            "i=0; while [ $i -lt 100000 ]; do i=$((i+1)); done; exit 3",
+        ]);
+
+        let (status, _stdout, _stderr) = gather_output(cmd, futures::future::pending()).await?;
+        let (exit_status, usage) = match status {
+            GatherOutputStatus::Finished {
+                exit_status,
+                resource_usage: Some(usage),
+            } => (exit_status, usage),
+            status => panic!("Unexpected status: {:?}", status),
+        };
+        assert_eq!(Some(3), exit_status.code());
+        assert!(usage.user_cpu_us + usage.system_cpu_us > 0);
+        assert!(usage.disk_read_bytes.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_exit_leaves_reaping_to_tokio() -> anyhow::Result<()> {
+        let mut cmd = if cfg!(windows) {
+            background_command("powershell")
+        } else {
+            background_command("sh")
+        };
+        cmd.args(["-c", "exit 2"]);
+
+        let mut child = prepare_command(cmd).spawn()?;
+        let status = wait_for_exit(&mut child).await?;
+        assert_matches!(
+            status,
+            GatherOutputStatus::Finished { exit_status, .. } if exit_status.code() == Some(2)
+        );
+        // Tokio reaped the child, so it won't try to reap its pid again later.
+        assert_eq!(None, child.id());
+        assert_eq!(Some(2), child.try_wait()?.and_then(|s| s.code()));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_gather_does_not_wait_for_children() -> anyhow::Result<()> {
         // If we wait for sleep, this will time out.
@@ -399,7 +530,9 @@ mod tests {
             timeout_into_cancellation(Some(Duration::from_secs(timeout))),
         )
         .await?;
-        assert!(matches!(status, GatherOutputStatus::Finished(s) if s.code() == Some(0)));
+        assert!(
+            matches!(status, GatherOutputStatus::Finished { exit_status, .. } if exit_status.code() == Some(0))
+        );
         assert_eq!(str::from_utf8(&stdout)?.trim(), "hello");
         assert_eq!(stderr, b"");
 
@@ -90,7 +90,7 @@ async fn main() -> anyhow::Result<()> {
             let (status, out, err) = forkserver
                 .execute(req.clone(), futures::future::pending())
                 .await?;
-            if !matches!(status, GatherOutputStatus::Finished(s) if s.success()) {
+            if !matches!(status, GatherOutputStatus::Finished { exit_status, .. } if exit_status.success()) {
                 failures.fetch_add(1, Ordering::Relaxed);
             }
             if !no_stdout {
@@ -36,6 +36,7 @@ fn to_literal(value: Value) -> AttrLiteral<CoercedAttr> {
             x.iter()
                 .map(|(k, v)| (to_coerced_literal(k), to_coerced_literal(v)))
                 .collect(),
+            false,
         )
     } else if let Some(x) = Tuple::from_value(value) {
         AttrLiteral::Tuple(x.iter().map(to_coerced_literal).collect())
@@ -50,7 +50,7 @@ impl AttrTypeCoerce for DictAttrType {
                     ));
                 }
             }
-            Ok(AttrLiteral::Dict(res))
+            Ok(AttrLiteral::Dict(res, self.sorted))
         } else {
             Err(anyhow::anyhow!(CoercionError::type_error(
                 Dict::TYPE,
@@ -145,6 +145,7 @@ mod tests {
     use buck2_core::target::TargetLabel;
     use buck2_node::attrs::attr_type::attr_literal::AttrLiteral;
     use buck2_node::attrs::coerced_attr::CoercedAttr;
+    use buck2_node::attrs::coerced_attr::SelectResolution;
     use buck2_node::attrs::configuration_context::AttrConfigurationContext;
     use gazebo::prelude::Dupe;
 
@@ -315,12 +316,44 @@ mod tests {
                 .unwrap_err()
                 .to_string()
         );
+
+        // Resolutions record the chosen key of each select, including nested ones.
+        let macos = TargetLabel::testing_parse("//:macos");
+        let attr = CoercedAttr::Concat(vec![
+            CoercedAttr::Selector(box (
+                OrderedMap::from_iter([
+                    (linux.dupe(), literal_true()),
+                    (
+                        linux_x86_64.dupe(),
+                        CoercedAttr::Selector(box (
+                            OrderedMap::from_iter([(macos.dupe(), literal_str())]),
+                            Some(literal_true()),
+                        )),
+                    ),
+                ]),
+                None,
+            )),
+            CoercedAttr::Literal(AttrLiteral::Bool(false)),
+        ]);
+        assert_eq!(
+            vec![
+                SelectResolution {
+                    keys: vec![linux.dupe(), linux_x86_64.dupe()],
+                    chosen: Some(linux_x86_64.dupe()),
+                },
+                SelectResolution {
+                    keys: vec![macos],
+                    chosen: None,
+                },
+            ],
+            attr.select_resolutions(&ctx).unwrap()
+        );
     }
 
     #[test]
     fn test_to_json_concat() {
         assert_eq!(
-            r#"{"__type":"concat","items":["a","b","c","d"]}"#,
+            r#"{"__concat__":["a","b","c","d"]}"#,
             CoercedAttr::Concat(vec![
                 CoercedAttr::Literal(AttrLiteral::String("a".to_owned())),
                 CoercedAttr::Literal(AttrLiteral::String("b".to_owned())),
@@ -336,7 +369,7 @@ mod tests {
     #[test]
     fn test_to_json_selector() {
         assert_eq!(
-            r#"{"__type":"selector","entries":{"//:a":true,"//:b":10,"DEFAULT":"ddd"}}"#,
+            r#"{"__select__":[["//:a",true],["//:b",10],["DEFAULT","ddd"]]}"#,
             CoercedAttr::Selector(box (
                 OrderedMap::from_iter([
                     (
@@ -21,6 +21,7 @@ use buck2_core::pattern::ProvidersPattern;
 use buck2_core::pattern::TargetPattern;
 use buck2_core::provider::label::ProvidersLabel;
 use buck2_core::soft_error;
+use buck2_data::WarningCategory;
 use buck2_node::attrs::coerced_path::CoercedPath;
 use buck2_node::attrs::coercion_context::AttrCoercionContext;
 use buck2_query::query::syntax::simple::eval::error::QueryError;
@@ -31,7 +32,6 @@ use buck2_query_parser::Expr;
 use bumpalo::Bump;
 use gazebo::dupe::Dupe;
 use hashbrown::raw::RawTable;
-use tracing::info;
 use twox_hash::xxh3;
 
 #[derive(Debug, thiserror::Error)]
@@ -176,7 +176,11 @@ impl AttrCoercionContext for BuildAttrCoercionContext {
                         subpackage.to_owned(),
                     );
                     if self.package_boundary_exception {
-                        info!("{} (could be due to a package boundary violation)", e);
+                        buck2_events::dispatch::warning(
+                            WarningCategory::PackageBoundary,
+                            package.to_string(),
+                            format!("{} (could be due to a package boundary violation)", e),
+                        );
                     } else {
                         soft_error!("source_directory_includes_subpackage", e.into())?;
                     }
@@ -195,7 +199,11 @@ impl AttrCoercionContext for BuildAttrCoercionContext {
                     value.to_owned(),
                 );
                 if self.package_boundary_exception {
-                    info!("{} (could be due to a package boundary violation)", e);
+                    buck2_events::dispatch::warning(
+                        WarningCategory::PackageBoundary,
+                        package.to_string(),
+                        format!("{} (could be due to a package boundary violation)", e),
+                    );
                 } else {
                     soft_error!("source_file_missing", e.into())?;
                 }
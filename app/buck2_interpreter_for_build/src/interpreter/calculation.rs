@@ -16,9 +16,11 @@ use buck2_common::result::SharedResult;
 use buck2_core::bzl::ImportPath;
 use buck2_core::cells::build_file_cell::BuildFileCell;
 use buck2_core::package::Package;
+use buck2_events::warnings::WithWarnings;
 use buck2_interpreter::common::StarlarkModulePath;
 use buck2_interpreter::dice::starlark_profiler::GetStarlarkProfilerInstrumentation;
 use buck2_interpreter::dice::HasCalculationDelegate;
+use buck2_interpreter::dice::HasEvents;
 use buck2_interpreter::file_loader::LoadedModule;
 use buck2_interpreter::starlark_profiler::StarlarkProfilerOrInstrumentation;
 use buck2_node::nodes::eval_result::EvaluationResult;
@@ -55,26 +57,29 @@ impl<'c> InterpreterCalculation<'c> for DiceComputations {
     ) -> SharedResult<Arc<EvaluationResult>> {
         #[async_trait]
         impl Key for InterpreterResultsKey {
-            type Value = SharedResult<Arc<EvaluationResult>>;
+            type Value = WithWarnings<SharedResult<Arc<EvaluationResult>>>;
             async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
-                let starlark_profiler_instrumentation =
-                    ctx.get_starlark_profiler_instrumentation().await?;
-                let interpreter = ctx
-                    .get_interpreter_calculator(
-                        self.0.cell_name(),
-                        &BuildFileCell::new(self.0.cell_name().clone()),
-                    )
-                    .await?;
-                Ok(Arc::new(
-                    interpreter
-                        .eval_build_file::<ModuleInternals>(
-                            &self.0,
-                            &mut StarlarkProfilerOrInstrumentation::maybe_instrumentation(
-                                starlark_profiler_instrumentation,
-                            ),
+                WithWarnings::compute(async {
+                    let starlark_profiler_instrumentation =
+                        ctx.get_starlark_profiler_instrumentation().await?;
+                    let interpreter = ctx
+                        .get_interpreter_calculator(
+                            self.0.cell_name(),
+                            &BuildFileCell::new(self.0.cell_name().clone()),
                         )
-                        .await?,
-                ))
+                        .await?;
+                    Ok(Arc::new(
+                        interpreter
+                            .eval_build_file::<ModuleInternals>(
+                                &self.0,
+                                &mut StarlarkProfilerOrInstrumentation::maybe_instrumentation(
+                                    starlark_profiler_instrumentation,
+                                ),
+                            )
+                            .await?,
+                    ))
+                })
+                .await
             }
 
             fn equality(_: &Self::Value, _: &Self::Value) -> bool {
@@ -83,11 +88,13 @@ impl<'c> InterpreterCalculation<'c> for DiceComputations {
             }
 
             fn validity(x: &Self::Value) -> bool {
-                x.is_ok()
+                x.value.is_ok()
             }
         }
 
-        self.compute(&InterpreterResultsKey(package.dupe())).await?
+        self.compute(&InterpreterResultsKey(package.dupe()))
+            .await?
+            .into_replayed(self.per_transaction_data().get_dispatcher())
     }
 
     async fn get_loaded_module(&self, path: StarlarkModulePath<'_>) -> SharedResult<LoadedModule> {
@@ -18,6 +18,7 @@ use buck2_interpreter::package_imports::ImplicitImport;
 use buck2_node::nodes::eval_result::EvaluationResult;
 use buck2_node::nodes::unconfigured::TargetNode;
 use buck2_node::nodes::unconfigured::TargetsMap;
+use buck2_node::visibility::WithinViewSpecification;
 use gazebo::prelude::*;
 use starlark::environment::FrozenModule;
 use starlark::values::OwnedFrozenValue;
@@ -47,6 +48,10 @@ pub struct ModuleInternals {
     buildfile_path: Arc<BuildFilePath>,
     /// Have you seen an oncall annotation yet
     oncall: RefCell<Option<Arc<String>>>,
+    /// The default target platform declared for the package, if any
+    default_target_platform: RefCell<Option<TargetLabel>>,
+    /// The default `within_view` declared for the package, if any
+    default_within_view: RefCell<Option<WithinViewSpecification>>,
     /// Directly imported modules.
     imports: Vec<ImportPath>,
     recorder: TargetsRecorder,
@@ -90,6 +95,8 @@ impl ModuleInternals {
             attr_coercion_context,
             buildfile_path,
             oncall: RefCell::new(None),
+            default_target_platform: RefCell::new(None),
+            default_within_view: RefCell::new(None),
             imports,
             package_implicits,
             recorder: TargetsRecorder::new(),
@@ -122,6 +129,30 @@ impl ModuleInternals {
         self.oncall.borrow().dupe()
     }
 
+    pub(crate) fn has_seen_default_target_platform(&self) -> bool {
+        self.default_target_platform.borrow().is_some()
+    }
+
+    pub(crate) fn set_default_target_platform(&self, platform: TargetLabel) {
+        *self.default_target_platform.borrow_mut() = Some(platform)
+    }
+
+    pub fn get_default_target_platform(&self) -> Option<TargetLabel> {
+        self.default_target_platform.borrow().dupe()
+    }
+
+    pub(crate) fn has_seen_default_within_view(&self) -> bool {
+        self.default_within_view.borrow().is_some()
+    }
+
+    pub(crate) fn set_default_within_view(&self, within_view: WithinViewSpecification) {
+        *self.default_within_view.borrow_mut() = Some(within_view)
+    }
+
+    pub(crate) fn get_default_within_view(&self) -> Option<WithinViewSpecification> {
+        self.default_within_view.borrow().clone()
+    }
+
     pub(crate) fn target_exists(&self, name: &str) -> bool {
         (*self.recorder.targets.borrow()).contains_key(name)
     }
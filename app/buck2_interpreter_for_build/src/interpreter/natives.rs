@@ -8,6 +8,7 @@
  */
 
 use buck2_interpreter::extra::ExtraContext;
+use buck2_node::attrs::coercion_context::AttrCoercionContext;
 use starlark::environment::GlobalsBuilder;
 use starlark::eval::Evaluator;
 use starlark::starlark_module;
@@ -15,6 +16,7 @@ use starlark::values::none::NoneType;
 use starlark::values::Value;
 
 use crate::interpreter::module_internals::ModuleInternals;
+use crate::nodes::unconfigured::within_view_specification;
 
 #[derive(Debug, thiserror::Error)]
 enum OncallErrors {
@@ -24,6 +26,26 @@ enum OncallErrors {
     DuplicateOncall,
 }
 
+#[derive(Debug, thiserror::Error)]
+enum DefaultTargetPlatformErrors {
+    #[error(
+        "Called `default_target_platform` after one or more targets were declared, `default_target_platform` must be first."
+    )]
+    DefaultTargetPlatformAfterTargets,
+    #[error("Called `default_target_platform` more than once in the file.")]
+    DuplicateDefaultTargetPlatform,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum DefaultWithinViewErrors {
+    #[error(
+        "Called `default_within_view` after one or more targets were declared, `default_within_view` must be first."
+    )]
+    DefaultWithinViewAfterTargets,
+    #[error("Called `default_within_view` more than once in the file.")]
+    DuplicateDefaultWithinView,
+}
+
 #[starlark_module]
 pub fn register_module_natives(globals: &mut GlobalsBuilder) {
     /// This should be called "target exists", not "rule exists"
@@ -52,6 +74,47 @@ pub fn register_module_natives(globals: &mut GlobalsBuilder) {
         }
     }
 
+    /// Called in a TARGETS/BUCK file to declare the default target platform of
+    /// all the targets defined. Targets that set their own `default_target_platform`
+    /// attribute use that instead. Must be called at most once, before any targets
+    /// have been declared. Errors if called from a `.bzl` file.
+    fn default_target_platform(
+        #[starlark(require = pos)] platform: &str,
+        eval: &mut Evaluator,
+    ) -> anyhow::Result<NoneType> {
+        let internals = ModuleInternals::from_context(eval)?;
+        if !internals.recorded_is_empty() {
+            Err(DefaultTargetPlatformErrors::DefaultTargetPlatformAfterTargets.into())
+        } else if internals.has_seen_default_target_platform() {
+            Err(DefaultTargetPlatformErrors::DuplicateDefaultTargetPlatform.into())
+        } else {
+            let platform = internals.attr_coercion_context().coerce_target(platform)?;
+            internals.set_default_target_platform(platform);
+            Ok(NoneType)
+        }
+    }
+
+    /// Called in a TARGETS/BUCK file to restrict what all the targets defined
+    /// can depend on to the given visibility patterns. Targets that set their own
+    /// `within_view` attribute use that instead. Must be called at most once,
+    /// before any targets have been declared. Errors if called from a `.bzl` file.
+    fn default_within_view(
+        #[starlark(require = pos)] patterns: Vec<&str>,
+        eval: &mut Evaluator,
+    ) -> anyhow::Result<NoneType> {
+        let internals = ModuleInternals::from_context(eval)?;
+        if !internals.recorded_is_empty() {
+            Err(DefaultWithinViewErrors::DefaultWithinViewAfterTargets.into())
+        } else if internals.has_seen_default_within_view() {
+            Err(DefaultWithinViewErrors::DuplicateDefaultWithinView.into())
+        } else {
+            let within_view =
+                within_view_specification(internals.attr_coercion_context(), patterns)?;
+            internals.set_default_within_view(within_view);
+            Ok(NoneType)
+        }
+    }
+
     fn implicit_package_symbol<'v>(
         name: &str,
         default: Option<Value<'v>>,
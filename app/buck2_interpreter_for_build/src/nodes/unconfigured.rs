@@ -21,6 +21,7 @@ use buck2_node::attrs::coercion_context::AttrCoercionContext;
 use buck2_node::attrs::inspect_options::AttrInspectOptions;
 use buck2_node::attrs::internal::NAME_ATTRIBUTE_FIELD;
 use buck2_node::attrs::internal::VISIBILITY_ATTRIBUTE_FIELD;
+use buck2_node::attrs::internal::WITHIN_VIEW_ATTRIBUTE_FIELD;
 use buck2_node::attrs::spec::AttributeSpec;
 use buck2_node::attrs::values::AttrValues;
 use buck2_node::call_stack::StarlarkCallStack;
@@ -30,6 +31,7 @@ use buck2_node::rule_type::RuleType;
 use buck2_node::rule_type::StarlarkRuleType;
 use buck2_node::visibility::VisibilityPattern;
 use buck2_node::visibility::VisibilitySpecification;
+use buck2_node::visibility::WithinViewSpecification;
 use gazebo::dupe::Dupe;
 use starlark::eval::CallStack;
 use starlark::eval::ParametersParser;
@@ -38,6 +40,12 @@ use starlark::values::Value;
 use crate::interpreter::module_internals::ModuleInternals;
 use crate::nodes::attr_spec::AttributeSpecExt;
 
+#[derive(Debug, thiserror::Error)]
+enum WithinViewError {
+    #[error("`within_view` must be a list of visibility patterns, got `{0}`")]
+    NotAListOfPatterns(String),
+}
+
 pub trait TargetNodeExt: Sized {
     fn from_params_ignore_attrs_for_profiling<'v>(
         internals: &ModuleInternals,
@@ -61,6 +69,7 @@ pub trait TargetNodeExt: Sized {
         attr_spec: Arc<AttributeSpec>,
         call_stack: Option<CallStack>,
         oncall: Option<Arc<String>>,
+        package_default_target_platform: Option<TargetLabel>,
     ) -> anyhow::Result<Self>;
 }
 
@@ -92,6 +101,8 @@ impl TargetNodeExt for TargetNode {
                     AttrValues::with_capacity(0),
                     CoercedDepsCollector::new(),
                     VisibilitySpecification::Public,
+                    WithinViewSpecification::Public,
+                    None,
                     None,
                     None,
                 ));
@@ -114,6 +125,7 @@ impl TargetNodeExt for TargetNode {
         attr_spec: Arc<AttributeSpec>,
         call_stack: Option<CallStack>,
         oncall: Option<Arc<String>>,
+        package_default_target_platform: Option<TargetLabel>,
     ) -> anyhow::Result<Self> {
         if ignore_attrs_for_profiling {
             return Self::from_params_ignore_attrs_for_profiling(
@@ -147,6 +159,21 @@ impl TargetNodeExt for TargetNode {
             visibility = VisibilitySpecification::Public;
         }
 
+        // Rules opt into `within_view` by declaring the attribute. Targets that don't set it
+        // use the package default, if any.
+        let within_view = match attr_spec.attr_or_none(
+            &attr_values,
+            WITHIN_VIEW_ATTRIBUTE_FIELD,
+            AttrInspectOptions::All,
+        ) {
+            Some(within_view) => parse_within_view(internals.attr_coercion_context(), within_view)
+                .context("When parsing `within_view` attribute")?,
+            None => None,
+        };
+        let within_view = within_view
+            .or_else(|| internals.get_default_within_view())
+            .unwrap_or(WithinViewSpecification::Public);
+
         let label = TargetLabel::new(package.dupe(), target_name);
         let mut deps_cache = CoercedDepsCollector::new();
 
@@ -164,8 +191,10 @@ impl TargetNodeExt for TargetNode {
             attr_values,
             deps_cache,
             visibility,
+            within_view,
             call_stack.map(StarlarkCallStack::new),
             oncall,
+            package_default_target_platform,
         ))
     }
 }
@@ -209,3 +238,43 @@ fn parse_visibility(
         Ok(VisibilitySpecification::VisibleTo(specs))
     }
 }
+
+/// Parse a `within_view` attribute, which is `None` when the target doesn't set it.
+fn parse_within_view(
+    ctx: &dyn AttrCoercionContext,
+    attr: &CoercedAttr,
+) -> anyhow::Result<Option<WithinViewSpecification>> {
+    let values = match attr {
+        CoercedAttr::Literal(AttrLiteral::None) => return Ok(None),
+        CoercedAttr::Literal(AttrLiteral::List(values, _)) => values,
+        _ => return Err(WithinViewError::NotAListOfPatterns(attr.to_string()).into()),
+    };
+    let patterns = values
+        .iter()
+        .map(|value| match value {
+            CoercedAttr::Literal(AttrLiteral::String(value)) => Ok(value.as_str()),
+            _ => Err(WithinViewError::NotAListOfPatterns(attr.to_string()).into()),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(Some(within_view_specification(ctx, patterns)?))
+}
+
+/// The `within_view` of a target from its patterns. Unlike an empty `visibility`, an empty
+/// `within_view` places no restriction.
+pub(crate) fn within_view_specification<'a>(
+    ctx: &dyn AttrCoercionContext,
+    patterns: impl IntoIterator<Item = &'a str>,
+) -> anyhow::Result<WithinViewSpecification> {
+    let mut specs = Vec::new();
+    for pattern in patterns {
+        if pattern == "PUBLIC" {
+            return Ok(WithinViewSpecification::Public);
+        }
+        specs.push(VisibilityPattern(ctx.coerce_target_pattern(pattern)?));
+    }
+    if specs.is_empty() {
+        Ok(WithinViewSpecification::Public)
+    } else {
+        Ok(WithinViewSpecification::VisibleTo(specs))
+    }
+}
@@ -10,6 +10,7 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Context;
 use async_trait::async_trait;
@@ -18,10 +19,12 @@ use buck2_build_api::calculation::Calculation;
 use buck2_build_api::interpreter::rule_defs::cmd_args::SimpleCommandLineArtifactVisitor;
 use buck2_build_api::interpreter::rule_defs::provider::collection::FrozenProviderCollection;
 use buck2_build_api::interpreter::rule_defs::provider::test_provider::TestProvider;
+use buck2_build_api::nodes::calculation::resolve_alias_patterns;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::dice::file_ops::HasFileOps;
 use buck2_common::legacy_configs::dice::HasLegacyConfigs;
 use buck2_common::liveliness_manager::LivelinessGuard;
+use buck2_common::pattern::resolve::ExcludedTargets;
 use buck2_common::pattern::resolve::ResolvedPattern;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
@@ -36,11 +39,12 @@ use buck2_core::target::TargetLabel;
 use buck2_execute::materialize::materializer::HasMaterializer;
 use buck2_interpreter::dice::HasEvents;
 use buck2_interpreter_for_build::interpreter::calculation::InterpreterCalculation;
+use buck2_node::compatibility::IncompatiblePlatformReason;
 use buck2_node::compatibility::MaybeCompatible;
 use buck2_node::nodes::eval_result::EvaluationResult;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
-use buck2_server_ctx::pattern::parse_patterns_from_cli_args;
-use buck2_server_ctx::pattern::resolve_patterns;
+use buck2_server_ctx::pattern::parse_patterns_with_exclusions_from_cli_args;
+use buck2_server_ctx::pattern::resolve_patterns_with_exclusions;
 use buck2_server_ctx::pattern::target_platform_from_client_context;
 use buck2_server_ctx::template::run_server_command;
 use buck2_server_ctx::template::ServerCommandTemplate;
@@ -63,6 +67,7 @@ use indexmap::indexset;
 use indexmap::IndexMap;
 use indexmap::IndexSet;
 use more_futures::spawn::dropcancel_critical_section;
+use regex::Regex;
 use serde::Serialize;
 
 use crate::downward_api::BuckTestDownwardApi;
@@ -71,6 +76,7 @@ use crate::executor_launcher::ExecutorLauncher;
 use crate::executor_launcher::OutOfProcessTestExecutor;
 use crate::orchestrator::BuckTestOrchestrator;
 use crate::orchestrator::TestResultOrExitCode;
+use crate::session::TestOverrides;
 use crate::session::TestSession;
 use crate::session::TestSessionOptions;
 use crate::translations::build_configured_target_handle;
@@ -83,6 +89,9 @@ pub(crate) struct TestReport {
 
 struct TestOutcome {
     error_messages: Vec<String>,
+    /// Targets that weren't tested because they are incompatible with their platform, with the
+    /// reason why.
+    skipped_incompatible: Vec<String>,
     executor_report: ExecutorReport,
 }
 
@@ -231,20 +240,22 @@ async fn test(
         .as_ref()
         .to_owned();
 
-    let parsed_patterns = parse_patterns_from_cli_args(
+    let parsed_patterns = parse_patterns_with_exclusions_from_cli_args(
         &request.target_patterns,
         &cell_resolver,
         &ctx.get_legacy_configs().await?,
         cwd,
     )?;
-    server_ctx.log_target_pattern(&parsed_patterns);
+    server_ctx.log_target_pattern(&parsed_patterns.patterns);
 
     ctx.per_transaction_data()
         .get_materializer()
         .log_materializer_state(server_ctx.events());
 
-    let resolved_pattern =
-        resolve_patterns(&parsed_patterns, &cell_resolver, &ctx.file_ops()).await?;
+    let (resolved_pattern, excluded_targets) =
+        resolve_patterns_with_exclusions(&parsed_patterns, &cell_resolver, &ctx.file_ops())
+            .await?;
+    let resolved_pattern = resolve_alias_patterns(&ctx, resolved_pattern).await?;
 
     let launcher: Box<dyn ExecutorLauncher> = box OutOfProcessTestExecutor {
         name: test_executor,
@@ -256,15 +267,45 @@ async fn test(
         .as_ref()
         .context("Missing `options`")?;
 
-    let session = TestSession::new(TestSessionOptions {
-        allow_re: options.allow_re,
-        force_use_project_relative_paths: options.force_use_project_relative_paths,
-        force_run_from_project_root: options.force_run_from_project_root,
-    });
+    let test_name_filter = request
+        .test_name_filter
+        .as_ref()
+        .map(|filter| {
+            Regex::new(filter).with_context(|| format!("Invalid test name filter `{}`", filter))?;
+            anyhow::Ok(filter.clone())
+        })
+        .transpose()?;
+
+    let overrides = match &request.overrides {
+        Some(overrides) => TestOverrides {
+            env: overrides.env.clone(),
+            timeout: overrides
+                .timeout
+                .clone()
+                .map(|t| {
+                    Duration::try_from(t)
+                        .map_err(|_| anyhow::anyhow!("Test timeout override is negative"))
+                })
+                .transpose()?,
+            args: overrides.args.clone(),
+        },
+        None => TestOverrides::default(),
+    };
+
+    let session = TestSession::new(
+        TestSessionOptions {
+            allow_re: options.allow_re,
+            force_use_project_relative_paths: options.force_use_project_relative_paths,
+            force_run_from_project_root: options.force_run_from_project_root,
+        },
+        overrides,
+    )
+    .with_test_name_filter(test_name_filter);
 
     let test_outcome = test_targets(
         &ctx,
         resolved_pattern,
+        excluded_targets,
         global_target_platform,
         request.test_executor_args.clone(),
         Arc::new(TestLabelFiltering::new(
@@ -331,12 +372,14 @@ async fn test(
         exit_code,
         error_messages: test_outcome.error_messages,
         test_statuses: Some(test_statuses),
+        skipped_incompatible: test_outcome.skipped_incompatible,
     })
 }
 
 async fn test_targets(
     ctx: &DiceComputations,
     pattern: ResolvedPattern<ProvidersPattern>,
+    excluded_targets: ExcludedTargets,
     global_target_platform: Option<TargetLabel>,
     external_runner_args: Vec<String>,
     label_filtering: Arc<TestLabelFiltering>,
@@ -391,6 +434,7 @@ async fn test_targets(
 
                 let mut driver = TestDriver::new(TestDriverState {
                     ctx: &ctx,
+                    excluded_targets: &excluded_targets,
                     label_filtering: &label_filtering,
                     global_target_platform: &global_target_platform,
                     session: &session,
@@ -438,7 +482,11 @@ async fn test_targets(
 
                 // And finally return our results;
 
-                anyhow::Ok((driver.build_errors, test_statuses))
+                anyhow::Ok((
+                    driver.build_errors,
+                    driver.skipped_incompatible,
+                    test_statuses,
+                ))
             })
         }
     });
@@ -461,12 +509,15 @@ async fn test_targets(
         "Executor exited without reporting end-of-tests",
     )));
 
-    let (build_errors, executor_report) = test_run
+    let (build_errors, mut skipped_incompatible, executor_report) = test_run
         .await
         .context("Failed to collect executor report")?;
 
+    skipped_incompatible.sort();
+
     Ok(TestOutcome {
         error_messages: build_errors,
+        skipped_incompatible,
         executor_report,
     })
 }
@@ -483,12 +534,16 @@ enum TestDriverTask {
     TestTargets {
         labels: Vec<ConfiguredProvidersLabel>,
     },
+    SkippedIncompatible {
+        reason: Arc<IncompatiblePlatformReason>,
+    },
     Done,
 }
 
 #[derive(Copy, Clone, Dupe)]
 pub(crate) struct TestDriverState<'a, 'e> {
     ctx: &'a DiceComputations,
+    excluded_targets: &'a ExcludedTargets,
     label_filtering: &'a Arc<TestLabelFiltering>,
     global_target_platform: &'a Option<TargetLabel>,
     session: &'a TestSession,
@@ -502,6 +557,7 @@ struct TestDriver<'a, 'e> {
     work: FuturesUnordered<BoxFuture<'a, anyhow::Result<TestDriverTask>>>,
     labels_seen: HashSet<ConfiguredProvidersLabel>,
     build_errors: Vec<String>,
+    skipped_incompatible: Vec<String>,
 }
 
 impl<'a, 'e> TestDriver<'a, 'e> {
@@ -511,6 +567,7 @@ impl<'a, 'e> TestDriver<'a, 'e> {
             work: FuturesUnordered::new(),
             labels_seen: HashSet::new(),
             build_errors: Vec::new(),
+            skipped_incompatible: Vec::new(),
         }
     }
 
@@ -540,6 +597,9 @@ impl<'a, 'e> TestDriver<'a, 'e> {
                 Ok(TestDriverTask::TestTargets { labels }) => {
                     self.test_targets(labels);
                 }
+                Ok(TestDriverTask::SkippedIncompatible { reason }) => {
+                    self.skipped_incompatible.push(reason.to_string());
+                }
                 Ok(TestDriverTask::Done) => {
                     // Nothing to do here
                 }
@@ -557,7 +617,8 @@ impl<'a, 'e> TestDriver<'a, 'e> {
         self.work.push(
             async move {
                 let res = state.ctx.get_interpreter_results(&package).await?;
-                let SpecTargets { labels, skippable } = spec_to_targets(spec, res)?;
+                let SpecTargets { labels, skippable } =
+                    spec_to_targets(&package, spec, res, state.excluded_targets)?;
 
                 let labels =
                     labels.into_map(|pattern| pattern.into_providers_label(package.dupe()));
@@ -584,7 +645,7 @@ impl<'a, 'e> TestDriver<'a, 'e> {
                     MaybeCompatible::Incompatible(reason) => {
                         if skippable {
                             eprintln!("{}", reason.skipping_message(label.target()));
-                            return Ok(TestDriverTask::Done);
+                            return Ok(TestDriverTask::SkippedIncompatible { reason });
                         } else {
                             return Err(reason.to_err());
                         }
@@ -642,8 +703,10 @@ struct SpecTargets {
 }
 
 fn spec_to_targets(
+    package: &Package,
     spec: PackageSpec<ProvidersPattern>,
     res: Arc<EvaluationResult>,
+    excluded_targets: &ExcludedTargets,
 ) -> anyhow::Result<SpecTargets> {
     let available_targets = res.targets();
 
@@ -651,6 +714,7 @@ fn spec_to_targets(
         PackageSpec::All => {
             let labels = available_targets
                 .keys()
+                .filter(|target| !excluded_targets.is_excluded(package, target))
                 .duped()
                 .map(|target| ProvidersPattern {
                     target,
@@ -774,7 +838,11 @@ fn run_tests<'a, 'b>(
 
     match maybe_handle {
         Ok(handle) => {
-            let fut = test_info.dispatch(handle, test_executor);
+            let fut = test_info.dispatch(
+                handle,
+                session.test_name_filter().map(|f| f.to_owned()),
+                test_executor,
+            );
 
             (async move {
                 fut.await
@@ -13,6 +13,7 @@ use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
 
 use anyhow::Context as _;
 use async_trait::async_trait;
@@ -96,6 +97,7 @@ use dice::DiceTransaction;
 use futures::channel::mpsc::UnboundedSender;
 use gazebo::prelude::*;
 use host_sharing::HostSharingRequirements;
+use host_sharing::Priority;
 use indexmap::IndexMap;
 use indexmap::IndexSet;
 use once_cell::sync::Lazy;
@@ -123,6 +125,9 @@ pub struct BuckTestOrchestrator {
     /// identifiers (e.g. Uuid or similar) because each might create some temporary outputs on disk,
     /// so use sequential identifiers for each target.
     identifiers: DashMap<ConfiguredTargetLabel, usize>,
+    /// When the tests reported as started by the test executor started, to time the tests whose
+    /// results come without a duration.
+    started_tests: DashMap<(ConfiguredTargetHandle, String), Instant>,
     liveliness_manager: Arc<dyn LivelinessManager>,
 }
 
@@ -157,6 +162,7 @@ impl BuckTestOrchestrator {
             results_channel,
             events,
             identifiers: Default::default(),
+            started_tests: Default::default(),
         }
     }
 
@@ -186,9 +192,9 @@ impl TestOrchestrator for BuckTestOrchestrator {
         &self,
         metadata: DisplayMetadata,
         test_target: ConfiguredTargetHandle,
-        cmd: Vec<ArgValue>,
-        env: HashMap<String, ArgValue>,
-        timeout: Duration,
+        mut cmd: Vec<ArgValue>,
+        mut env: HashMap<String, ArgValue>,
+        mut timeout: Duration,
         host_sharing_requirements: HostSharingRequirements,
         pre_create_dirs: Vec<DeclaredOutput>,
         executor_override: Option<ExecutorConfigOverride>,
@@ -196,6 +202,9 @@ impl TestOrchestrator for BuckTestOrchestrator {
         self.liveliness_manager.require_alive().await?;
 
         let test_target = self.session.get(test_target)?;
+        self.session
+            .overrides()
+            .apply(&metadata, &mut cmd, &mut env, &mut timeout);
 
         let fs = self.dice.get_artifact_fs().await?;
 
@@ -240,6 +249,7 @@ impl TestOrchestrator for BuckTestOrchestrator {
             owner: &owner,
             category: &TEST_CATEGORY,
             identifier: Some(&identifier),
+            priority: Priority::default(),
             action_key: &action_key as _,
         };
 
@@ -289,7 +299,25 @@ impl TestOrchestrator for BuckTestOrchestrator {
         })
     }
 
-    async fn report_test_result(&self, r: TestResult) -> anyhow::Result<()> {
+    async fn report_test_started(
+        &self,
+        target: ConfiguredTargetHandle,
+        name: String,
+    ) -> anyhow::Result<()> {
+        self.events
+            .instant_event(buck2_data::instant_event::Data::TestStarted(
+                buck2_data::TestStarted { name: name.clone() },
+            ));
+        self.started_tests.insert((target, name), Instant::now());
+        Ok(())
+    }
+
+    async fn report_test_result(&self, mut r: TestResult) -> anyhow::Result<()> {
+        if let Some((_, started)) = self.started_tests.remove(&(r.target, r.name.clone())) {
+            if r.duration.is_none() {
+                r.duration = Some(started.elapsed());
+            }
+        }
         let event = buck2_data::instant_event::Data::TestResult(translations::convert_test_result(
             r.clone(),
         )?);
@@ -459,6 +487,7 @@ impl BuckTestOrchestrator {
             rejected_execution: _,
             did_cache_upload: _,
             eligible_for_full_hybrid: _,
+            input_bytes_uploaded: _,
         } = match metadata {
             DisplayMetadata::Listing(listing) => {
                 let start = TestDiscoveryStart {
@@ -1001,7 +1030,7 @@ mod tests {
         Ok((
             BuckTestOrchestrator::from_parts(
                 dice,
-                Arc::new(TestSession::new(Default::default())),
+                Arc::new(TestSession::new(Default::default(), Default::default())),
                 NoopLivelinessManager::create(),
                 sender,
                 EventDispatcher::null(),
@@ -1072,6 +1101,43 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn orchestrator_streamed_results_are_timed() -> anyhow::Result<()> {
+        let (orchestrator, channel) = make()?;
+
+        let jobs = async {
+            orchestrator
+                .report_test_started(ConfiguredTargetHandle::testing_new(0), "test".to_owned())
+                .await?;
+            orchestrator
+                .report_test_result(TestResult {
+                    target: ConfiguredTargetHandle::testing_new(0),
+                    status: TestStatus::PASS,
+                    msg: None,
+                    name: "test".to_owned(),
+                    duration: None,
+                    details: "".to_owned(),
+                })
+                .await?;
+            orchestrator.end_of_test_results(0).await?;
+
+            anyhow::Ok(())
+        };
+
+        let ((), results) = future::try_join(jobs, channel.try_collect::<Vec<_>>()).await?;
+
+        match results.as_slice() {
+            [
+                TestResultOrExitCode::TestResult(result),
+                TestResultOrExitCode::ExitCode(0),
+            ] => assert!(result.duration.is_some()),
+            results => panic!("Unexpected results: {:?}", results),
+        }
+        assert!(orchestrator.started_tests.is_empty());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_orchestrator_channel_drop() -> anyhow::Result<()> {
         let (orchestrator, channel) = make()?;
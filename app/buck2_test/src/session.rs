@@ -7,14 +7,20 @@
  * of this source tree.
  */
 
+use std::collections::HashMap;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use anyhow::Context as _;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
+use buck2_test_api::data::ArgValue;
+use buck2_test_api::data::ArgValueContent;
 use buck2_test_api::data::ConfiguredTargetHandle;
+use buck2_test_api::data::DisplayMetadata;
+use buck2_test_api::data::ExternalRunnerSpecValue;
 use chrono::Local;
 use dashmap::DashMap;
 use gazebo::prelude::*;
@@ -27,6 +33,52 @@ pub struct TestSessionOptions {
     pub force_run_from_project_root: bool,
 }
 
+/// Overrides, from the command line, applied to every test executed in this session. This lets
+/// one debug a failing test without editing its definition.
+#[derive(Debug, Clone, Default)]
+pub struct TestOverrides {
+    /// Environment variables set on tests, taking precedence over their own.
+    pub env: HashMap<String, String>,
+    /// Timeout of each test execution, replacing the one requested by the test executor.
+    pub timeout: Option<Duration>,
+    /// Arguments appended to the command of tests.
+    pub args: Vec<String>,
+}
+
+impl TestOverrides {
+    /// Apply the overrides to an execution requested by the test executor. Only executions running
+    /// tests are affected: listing tests must not be given arguments or a timeout meant for tests.
+    pub(crate) fn apply(
+        &self,
+        metadata: &DisplayMetadata,
+        cmd: &mut Vec<ArgValue>,
+        env: &mut HashMap<String, ArgValue>,
+        timeout: &mut Duration,
+    ) {
+        match metadata {
+            DisplayMetadata::Testing { .. } => {}
+            DisplayMetadata::Listing(..) => return,
+        }
+
+        fn verbatim(value: &str) -> ArgValue {
+            ArgValue {
+                content: ArgValueContent::ExternalRunnerSpecValue(
+                    ExternalRunnerSpecValue::Verbatim(value.to_owned()),
+                ),
+                format: None,
+            }
+        }
+
+        cmd.extend(self.args.iter().map(|arg| verbatim(arg)));
+        for (key, value) in &self.env {
+            env.insert(key.clone(), verbatim(value));
+        }
+        if let Some(t) = self.timeout {
+            *timeout = t;
+        }
+    }
+}
+
 /// The state of a buck2 test command.
 pub struct TestSession {
     /// The next ConfiguredTargetHandle that will be assigned.
@@ -41,10 +93,13 @@ pub struct TestSession {
     /// Options overriding the behavior of tests executed in this session. This is primarily
     /// intended for unstable or debugging features.
     options: TestSessionOptions,
+    overrides: TestOverrides,
+    /// Regex that the names of test cases must match to be run, passed on to the test executor.
+    test_name_filter: Option<String>,
 }
 
 impl TestSession {
-    pub fn new(options: TestSessionOptions) -> Self {
+    pub fn new(options: TestSessionOptions, overrides: TestOverrides) -> Self {
         // NOTE: This is the format that Tpx has historically used. We don't really *have* to use
         // this considering we don't even put it in the same place (we do it in ./buck-out/v2/tmp,
         // but Tpx put it in /tmp), but it's a reasonable one.
@@ -57,13 +112,28 @@ impl TestSession {
             labels: DashMap::new(),
             prefix,
             options,
+            overrides,
+            test_name_filter: None,
         }
     }
 
+    pub fn with_test_name_filter(mut self, test_name_filter: Option<String>) -> Self {
+        self.test_name_filter = test_name_filter;
+        self
+    }
+
     pub fn options(&self) -> TestSessionOptions {
         self.options
     }
 
+    pub fn overrides(&self) -> &TestOverrides {
+        &self.overrides
+    }
+
+    pub fn test_name_filter(&self) -> Option<&str> {
+        self.test_name_filter.as_deref()
+    }
+
     pub fn prefix(&self) -> &ForwardRelativePath {
         self.prefix.as_ref()
     }
@@ -86,3 +156,59 @@ impl TestSession {
         Ok(res.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_overrides() {
+        let overrides = TestOverrides {
+            env: HashMap::from([("FOO".to_owned(), "override".to_owned())]),
+            timeout: Some(Duration::from_secs(600)),
+            args: vec!["--verbose".to_owned()],
+        };
+        let arg = |s: &str| ArgValue {
+            content: ArgValueContent::ExternalRunnerSpecValue(ExternalRunnerSpecValue::Verbatim(
+                s.to_owned(),
+            )),
+            format: None,
+        };
+
+        let mut cmd = vec![arg("test_binary")];
+        let mut env = HashMap::from([
+            ("FOO".to_owned(), arg("original")),
+            ("BAR".to_owned(), arg("kept")),
+        ]);
+        let mut timeout = Duration::from_secs(10);
+        overrides.apply(
+            &DisplayMetadata::Testing {
+                suite: "suite".to_owned(),
+                testcases: vec!["case".to_owned()],
+            },
+            &mut cmd,
+            &mut env,
+            &mut timeout,
+        );
+
+        assert_eq!(vec![arg("test_binary"), arg("--verbose")], cmd);
+        assert_eq!(Some(&arg("override")), env.get("FOO"));
+        assert_eq!(Some(&arg("kept")), env.get("BAR"));
+        assert_eq!(Duration::from_secs(600), timeout);
+
+        // Listing is left alone.
+        let mut cmd = vec![arg("test_binary")];
+        let mut env = HashMap::from([("FOO".to_owned(), arg("original"))]);
+        let mut timeout = Duration::from_secs(10);
+        overrides.apply(
+            &DisplayMetadata::Listing("suite".to_owned()),
+            &mut cmd,
+            &mut env,
+            &mut timeout,
+        );
+
+        assert_eq!(vec![arg("test_binary")], cmd);
+        assert_eq!(Some(&arg("original")), env.get("FOO"));
+        assert_eq!(Duration::from_secs(10), timeout);
+    }
+}
@@ -297,6 +297,7 @@ impl TryFrom<buck2_test_proto::ExternalRunnerSpec> for ExternalRunnerSpec {
             labels,
             contacts,
             oncall,
+            test_name_filter,
         } = s;
 
         Ok(Self {
@@ -315,6 +316,7 @@ impl TryFrom<buck2_test_proto::ExternalRunnerSpec> for ExternalRunnerSpec {
             labels,
             contacts,
             oncall,
+            test_name_filter,
         })
     }
 }
@@ -331,6 +333,7 @@ impl TryInto<buck2_test_proto::ExternalRunnerSpec> for ExternalRunnerSpec {
             labels,
             contacts,
             oncall,
+            test_name_filter,
         } = self;
         Ok(buck2_test_proto::ExternalRunnerSpec {
             target: Some(target.try_into().context("Invalid `target`")?),
@@ -345,6 +348,7 @@ impl TryInto<buck2_test_proto::ExternalRunnerSpec> for ExternalRunnerSpec {
             labels,
             contacts,
             oncall,
+            test_name_filter,
         })
     }
 }
@@ -823,6 +827,7 @@ mod tests {
             labels: vec!["label1".to_owned(), "label2".to_owned()],
             contacts: vec!["contact1".to_owned(), "contact2".to_owned()],
             oncall: Some("contact1".to_owned()),
+            test_name_filter: Some("^test_foo".to_owned()),
         };
         assert_roundtrips::<buck2_test_proto::ExternalRunnerSpec, ExternalRunnerSpec>(&test_spec);
     }
@@ -132,6 +132,8 @@ pub struct ExternalRunnerSpec {
     pub contacts: Vec<String>,
     /// Oncall for the test
     pub oncall: Option<String>,
+    /// Regex that the names of the test cases to run must match, if set.
+    pub test_name_filter: Option<String>,
 }
 
 /// Command line argument or environment variable value
@@ -33,6 +33,7 @@ use buck2_test_proto::ExecuteResponse2;
 use buck2_test_proto::PrepareForLocalExecutionResponse;
 use buck2_test_proto::ReportTestResultRequest;
 use buck2_test_proto::ReportTestSessionRequest;
+use buck2_test_proto::ReportTestStartedRequest;
 use buck2_test_proto::ReportTestsDiscoveredRequest;
 use buck2_test_proto::Testing;
 use futures::future::BoxFuture;
@@ -166,6 +167,24 @@ impl TestOrchestrator for TestOrchestratorClient {
         Ok(result)
     }
 
+    async fn report_test_started(
+        &self,
+        target: ConfiguredTargetHandle,
+        name: String,
+    ) -> anyhow::Result<()> {
+        let target = target.try_into().context("Invalid `target`")?;
+
+        self.test_orchestrator_client
+            .clone()
+            .report_test_started(ReportTestStartedRequest {
+                target: Some(target),
+                name,
+            })
+            .await?;
+
+        Ok(())
+    }
+
     async fn report_test_result(&self, result: TestResult) -> anyhow::Result<()> {
         let result = result.try_into().context("Invalid `result`")?;
 
@@ -353,6 +372,28 @@ where
         .await
     }
 
+    async fn report_test_started(
+        &self,
+        request: tonic::Request<ReportTestStartedRequest>,
+    ) -> Result<tonic::Response<Empty>, tonic::Status> {
+        to_tonic(async move {
+            let ReportTestStartedRequest { target, name } = request.into_inner();
+
+            let target = target
+                .context("Missing `target`")?
+                .try_into()
+                .context("Invalid `target`")?;
+
+            self.inner
+                .report_test_started(target, name)
+                .await
+                .context("Failed to report test start")?;
+
+            Ok(Empty {})
+        })
+        .await
+    }
+
     async fn report_tests_discovered(
         &self,
         request: tonic::Request<ReportTestsDiscoveredRequest>,
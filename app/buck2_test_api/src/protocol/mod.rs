@@ -65,6 +65,14 @@ pub trait TestOrchestrator: Send + Sync {
         executor_override: Option<ExecutorConfigOverride>,
     ) -> anyhow::Result<ExecutionResult2>;
 
+    /// reports a test is about to run. Executors may report this to stream the progress of tests,
+    /// it must then be followed by a `report_test_result` for the same target and test name.
+    async fn report_test_started(
+        &self,
+        target: ConfiguredTargetHandle,
+        name: String,
+    ) -> anyhow::Result<()>;
+
     /// reports a test is done
     async fn report_test_result(&self, r: TestResult) -> anyhow::Result<()>;
 
@@ -8,6 +8,7 @@
  */
 
 use std::io::Write;
+use std::path::Path;
 
 use async_trait::async_trait;
 use buck2_common::dice::cells::HasCellResolver;
@@ -44,9 +45,16 @@ pub struct AuditCellCommand {
     )]
     aliases: bool,
 
+    #[clap(
+        long = "in",
+        value_name = "PATH",
+        help = "Resolve aliases as seen from the cell containing this path (relative to the working directory, or absolute) instead of the working directory cell."
+    )]
+    context_path: Option<String>,
+
     #[clap(
         name = "CELL_ALIASES",
-        help = "Cell aliases to query. These aliases will be resolved in the working directory cell."
+        help = "Cell aliases to query. These aliases will be resolved in the working directory cell (or the cell given by `--in`)."
     )]
     aliases_to_resolve: Vec<String>,
 }
@@ -63,7 +71,14 @@ impl AuditSubcommand for AuditCellCommand {
                 let cells = ctx.get_cell_resolver().await?;
                 let fs = server_ctx.project_root();
                 let cwd = server_ctx.working_dir();
-                let this_cell = cells.get(cells.find(cwd)?).unwrap();
+                let this_cell = match &self.context_path {
+                    Some(path) => cells.get(
+                        cells
+                            .get_cell_path_from_abs_or_rel_path(Path::new(path), fs, cwd)?
+                            .cell(),
+                    )?,
+                    None => cells.get(cells.find(cwd)?).unwrap(),
+                };
 
                 let mappings: IndexMap<_, _> = {
                     if self.aliases_to_resolve.is_empty() {
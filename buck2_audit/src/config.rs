@@ -100,6 +100,12 @@ pub struct AuditConfigCommand {
     #[clap(long = "value", default_value = "resolved", possible_values=&["resolved", "raw", "both"])]
     value_style: ValueStyle,
 
+    #[clap(
+        long,
+        help = "show which layer of the config stack (global, user, repo, repo local or command line) each value was set in"
+    )]
+    show_origin: bool,
+
     #[clap(
         name = "SPECS",
         help = "config section/key specs of the form `section` or `section.key`. If any specs are provided, only values matching a spec will be printed (section headers will be printed only for sections with a key matching the spec)."
@@ -151,6 +157,11 @@ fn print_location(
     Ok(())
 }
 
+fn print_origin(writer: &mut impl Write, value: &LegacyBuckConfigValue) -> anyhow::Result<()> {
+    writeln!(writer, "  (from {} config)", value.layer())?;
+    Ok(())
+}
+
 fn print_value(
     writer: &mut impl Write,
     key: &str,
@@ -179,6 +190,17 @@ fn print_value(
 }
 
 impl AuditConfigCommand {
+    fn json_value(&self, value: &LegacyBuckConfigValue) -> serde_json::Value {
+        if self.show_origin {
+            json!({
+                "value": value.as_str(),
+                "origin": value.layer().to_string(),
+            })
+        } else {
+            json!(value.as_str())
+        }
+    }
+
     fn output_format(&self) -> OutputFormat {
         if let Some(format) = &self.output_format {
             *format
@@ -257,11 +279,11 @@ impl AuditSubcommand for AuditConfigCommand {
                                     cfg.iter()
                                         .filter_map(|(key, value)| {
                                             filter(cell, section, key)
-                                                .map(|spec| (spec, value.as_str().to_owned()))
+                                                .map(|spec| (spec, self.json_value(&value)))
                                         })
-                                        .collect::<HashMap<String, String>>()
+                                        .collect::<HashMap<String, serde_json::Value>>()
                                 })
-                                .collect::<HashMap<String, String>>()
+                                .collect::<HashMap<String, serde_json::Value>>()
                         )
                     )?,
                     OutputFormat::Simple => {
@@ -277,6 +299,9 @@ impl AuditSubcommand for AuditConfigCommand {
                                         }
                                         print_value(&mut stdout, key, &value, self.value_style)?;
                                         print_location(&mut stdout, &value, self.location_style)?;
+                                        if self.show_origin {
+                                            print_origin(&mut stdout, &value)?;
+                                        }
                                     }
                                 }
                             }
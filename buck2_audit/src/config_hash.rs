@@ -0,0 +1,158 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::Write;
+
+use async_trait::async_trait;
+use buck2_core::configuration::Configuration;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use cli_proto::ClientContext;
+
+use crate::AuditCommandCommonOptions;
+use crate::AuditSubcommand;
+
+#[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize)]
+#[clap(
+    name = "audit-config-hash",
+    about = "compares two configurations, to find out why a target was configured twice"
+)]
+pub struct AuditConfigHashCommand {
+    #[clap(flatten)]
+    common_opts: AuditCommandCommonOptions,
+
+    #[clap(
+        name = "CONFIGURATION_A",
+        help = "configuration to compare, either its full name (example: `cell//package:target-105fe3389fc7e436`) or just its hash, as found in output paths"
+    )]
+    a: String,
+
+    #[clap(name = "CONFIGURATION_B", help = "configuration to compare it to")]
+    b: String,
+}
+
+#[async_trait]
+impl AuditSubcommand for AuditConfigHashCommand {
+    async fn server_execute(
+        &self,
+        server_ctx: Box<dyn ServerCommandContextTrait>,
+        _client_ctx: ClientContext,
+    ) -> anyhow::Result<()> {
+        let mut stdout = server_ctx.stdout()?;
+
+        let a = lookup(&self.a)?;
+        let b = lookup(&self.b)?;
+
+        writeln!(stdout, "- {}", a.full_name())?;
+        writeln!(stdout, "+ {}", b.full_name())?;
+
+        if a == b {
+            writeln!(stdout, "Configurations are the same")?;
+            return Ok(());
+        }
+
+        let a_data = a.data()?;
+        let b_data = b.data()?;
+        let constraints = diff(&a_data.constraints, &b_data.constraints);
+        let buckconfigs = diff(&a_data.buckconfigs, &b_data.buckconfigs);
+
+        if constraints.is_empty() && buckconfigs.is_empty() {
+            writeln!(
+                stdout,
+                "Constraints and config values are the same, only the platform label differs:"
+            )?;
+            writeln!(stdout, "  - {}", a.configuration_platform())?;
+            writeln!(stdout, "  + {}", b.configuration_platform())?;
+            return Ok(());
+        }
+
+        if !constraints.is_empty() {
+            writeln!(stdout, "Constraints:")?;
+            write_diff(&mut stdout, &constraints)?;
+        }
+        if !buckconfigs.is_empty() {
+            writeln!(stdout, "Config values:")?;
+            write_diff(&mut stdout, &buckconfigs)?;
+        }
+
+        Ok(())
+    }
+
+    fn common_opts(&self) -> &AuditCommandCommonOptions {
+        &self.common_opts
+    }
+}
+
+fn lookup(cfg: &str) -> anyhow::Result<Configuration> {
+    if cfg.contains('-') {
+        Configuration::lookup_from_string(cfg)
+    } else {
+        Configuration::lookup_from_hash(cfg)
+    }
+}
+
+/// The keys whose values differ between `a` and `b`, with their value on each side (`None` if
+/// the key isn't set on that side).
+fn diff<'a, K: Ord, V: PartialEq>(
+    a: &'a BTreeMap<K, V>,
+    b: &'a BTreeMap<K, V>,
+) -> BTreeMap<&'a K, (Option<&'a V>, Option<&'a V>)> {
+    let mut diff = BTreeMap::new();
+    for (k, v) in a {
+        if b.get(k) != Some(v) {
+            diff.insert(k, (Some(v), b.get(k)));
+        }
+    }
+    for (k, v) in b {
+        if !a.contains_key(k) {
+            diff.insert(k, (None, Some(v)));
+        }
+    }
+    diff
+}
+
+fn write_diff<K: Display, V: Display>(
+    stdout: &mut impl Write,
+    diff: &BTreeMap<&K, (Option<&V>, Option<&V>)>,
+) -> anyhow::Result<()> {
+    fn value(v: Option<&impl Display>) -> String {
+        match v {
+            Some(v) => v.to_string(),
+            None => "<unset>".to_owned(),
+        }
+    }
+
+    for (k, (a, b)) in diff {
+        writeln!(stdout, "  {}", k)?;
+        writeln!(stdout, "    - {}", value(*a))?;
+        writeln!(stdout, "    + {}", value(*b))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let a = BTreeMap::from_iter([("os", "linux"), ("cpu", "x86_64"), ("mode", "dev")]);
+        let b = BTreeMap::from_iter([("os", "linux"), ("cpu", "arm64"), ("san", "asan")]);
+        assert_eq!(
+            BTreeMap::from_iter([
+                (&"cpu", (Some(&"x86_64"), Some(&"arm64"))),
+                (&"mode", (Some(&"dev"), None)),
+                (&"san", (None, Some(&"asan"))),
+            ]),
+            diff(&a, &b)
+        );
+        assert!(diff(&a, &a).is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::io::Write;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use buck2_build_api::actions::RegisteredAction;
+use buck2_build_api::analysis::calculation::RuleAnalysisCalculation;
+use buck2_build_api::calculation::Calculation;
+use buck2_build_api::deferred::types::DeferredAny;
+use buck2_build_api::deferred::types::DeferredTableEntry;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::dice::file_ops::HasFileOps;
+use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+use buck2_core::pattern::TargetPattern;
+use buck2_core::target::TargetLabel;
+use buck2_interpreter_for_build::interpreter::calculation::InterpreterCalculation;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::ctx::ServerCommandDiceContext;
+use buck2_server_ctx::pattern::parse_patterns_from_cli_args;
+use buck2_server_ctx::pattern::resolve_patterns;
+use buck2_server_ctx::pattern::target_platform_from_client_context;
+use cli_proto::ClientContext;
+use gazebo::prelude::*;
+
+use crate::AuditCommandCommonOptions;
+use crate::AuditSubcommand;
+
+#[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize)]
+#[clap(
+    name = "audit-deferred",
+    about = "prints out the deferreds (including actions) registered by the analysis of targets"
+)]
+pub struct AuditDeferredCommand {
+    #[clap(flatten)]
+    common_opts: AuditCommandCommonOptions,
+
+    #[clap(name = "TARGET_PATTERNS", help = "Patterns to analyze")]
+    patterns: Vec<String>,
+}
+
+#[async_trait]
+impl AuditSubcommand for AuditDeferredCommand {
+    async fn server_execute(
+        &self,
+        server_ctx: Box<dyn ServerCommandContextTrait>,
+        client_ctx: ClientContext,
+    ) -> anyhow::Result<()> {
+        server_ctx
+            .with_dice_ctx(async move |server_ctx, ctx| {
+                let cells = ctx.get_cell_resolver().await?;
+
+                let target_platform = target_platform_from_client_context(
+                    Some(&client_ctx),
+                    &cells,
+                    server_ctx.working_dir(),
+                )
+                .await?;
+
+                let parsed_patterns = parse_patterns_from_cli_args::<TargetPattern>(
+                    &self
+                        .patterns
+                        .map(|pat| buck2_data::TargetPattern { value: pat.clone() }),
+                    &cells,
+                    &ctx.get_legacy_configs().await?,
+                    server_ctx.working_dir(),
+                )?;
+                let resolved_pattern =
+                    resolve_patterns(&parsed_patterns, &cells, &ctx.file_ops()).await?;
+
+                let mut stdout = server_ctx.stdout()?;
+
+                for (package, spec) in resolved_pattern.specs {
+                    let targets = match spec {
+                        buck2_core::pattern::PackageSpec::Targets(targets) => targets,
+                        buck2_core::pattern::PackageSpec::All => ctx
+                            .get_interpreter_results(&package)
+                            .await?
+                            .targets()
+                            .keys()
+                            .duped()
+                            .collect(),
+                    };
+
+                    for target in targets {
+                        let label = TargetLabel::new(package.dupe(), target);
+                        let configured_target = ctx
+                            .get_configured_target(&label, target_platform.as_ref())
+                            .await?;
+                        let analysis = ctx
+                            .get_analysis_result(&configured_target)
+                            .await?
+                            .require_compatible()?;
+
+                        writeln!(stdout, "{}:", configured_target)?;
+                        let table = analysis.deferred_table();
+                        for (id, entry) in table.iter() {
+                            let kind = match entry {
+                                DeferredTableEntry::Trivial(..) => "trivial",
+                                DeferredTableEntry::Complex(..) => "complex",
+                            };
+                            writeln!(
+                                stdout,
+                                "  {}: {} `{}`",
+                                id,
+                                kind,
+                                entry.deferred_type_name()
+                            )?;
+                            for input in entry.inputs() {
+                                writeln!(stdout, "    -> {}", input)?;
+                            }
+                            // Actions are trivial deferreds, their dependencies are their inputs.
+                            let action =
+                                table.lookup_deferred(id)?.as_trivial().and_then(|value| {
+                                    (**value).into_any().downcast_ref::<Arc<RegisteredAction>>()
+                                });
+                            if let Some(action) = action {
+                                for input in action.inputs()?.iter() {
+                                    writeln!(stdout, "    -> input {}", input)?;
+                                }
+                            }
+                        }
+                    }
+                }
+
+                Ok(())
+            })
+            .await
+    }
+
+    fn common_opts(&self) -> &AuditCommandCommonOptions {
+        &self.common_opts
+    }
+}
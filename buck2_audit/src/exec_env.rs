@@ -0,0 +1,278 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io::Write;
+
+use async_trait::async_trait;
+use buck2_build_api::actions::impls::run::environment::apply_local_environment;
+use buck2_build_api::actions::impls::run::knobs::HasRunActionKnobs;
+use buck2_build_api::calculation::load_patterns;
+use buck2_build_api::calculation::Calculation;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::executor_config::CommandExecutorConfig;
+use buck2_common::executor_config::CommandExecutorKind;
+use buck2_common::executor_config::RemoteExecutorOptions;
+use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+use buck2_core::pattern::TargetPattern;
+use buck2_execute::execute::environment_inheritance::EnvironmentInheritance;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::ctx::ServerCommandDiceContext;
+use buck2_server_ctx::pattern::parse_patterns_from_cli_args;
+use buck2_server_ctx::pattern::target_platform_from_client_context;
+use cli_proto::ClientContext;
+
+use crate::AuditCommandCommonOptions;
+use crate::AuditSubcommand;
+
+#[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize)]
+#[clap(
+    name = "audit-exec-env",
+    about = "prints out the environment actions of a target would run with, locally and remotely"
+)]
+pub struct AuditExecEnvCommand {
+    #[clap(flatten)]
+    common_opts: AuditCommandCommonOptions,
+
+    #[clap(name = "TARGET_PATTERNS", help = "Patterns to analyze")]
+    patterns: Vec<String>,
+}
+
+#[async_trait]
+impl AuditSubcommand for AuditExecEnvCommand {
+    async fn server_execute(
+        &self,
+        server_ctx: Box<dyn ServerCommandContextTrait>,
+        client_ctx: ClientContext,
+    ) -> anyhow::Result<()> {
+        server_ctx
+            .with_dice_ctx(async move |server_ctx, ctx| {
+                let cells = ctx.get_cell_resolver().await?;
+
+                let target_platform = target_platform_from_client_context(
+                    Some(&client_ctx),
+                    &cells,
+                    server_ctx.working_dir(),
+                )
+                .await?;
+
+                let patterns = parse_patterns_from_cli_args::<TargetPattern>(
+                    &self
+                        .patterns
+                        .iter()
+                        .map(|value| buck2_data::TargetPattern {
+                            value: value.clone(),
+                        })
+                        .collect::<Vec<_>>(),
+                    &cells,
+                    &ctx.get_legacy_configs().await?,
+                    server_ctx.working_dir(),
+                )?;
+
+                let scrubbed_local_env = ctx
+                    .per_transaction_data()
+                    .get_run_action_knobs()
+                    .scrubbed_local_env;
+
+                let mut stdout = server_ctx.stdout()?;
+
+                for (_, targets) in load_patterns(&ctx, patterns).await?.into_iter() {
+                    for (_, node) in targets? {
+                        let configured_target = ctx
+                            .get_configured_target(node.label(), target_platform.as_ref())
+                            .await?;
+                        let configured_node =
+                            ctx.get_configured_target_node(&configured_target).await?;
+                        let configured_node = configured_node.require_compatible()?;
+
+                        writeln!(stdout, "{}:", configured_target)?;
+                        let platform =
+                            match configured_node.execution_platform_resolution().platform() {
+                                Ok(platform) => platform,
+                                Err(e) => {
+                                    writeln!(stdout, "  {}", e)?;
+                                    continue;
+                                }
+                            };
+                        writeln!(stdout, "  Execution platform: {}", platform.id())?;
+                        write_exec_env(
+                            &mut stdout,
+                            platform.executor_config(),
+                            scrubbed_local_env,
+                        )?;
+                    }
+                }
+
+                Ok(())
+            })
+            .await
+    }
+
+    fn common_opts(&self) -> &AuditCommandCommonOptions {
+        &self.common_opts
+    }
+}
+
+fn write_exec_env(
+    mut stdout: impl Write,
+    executor_config: &CommandExecutorConfig,
+    scrubbed_local_env: Option<EnvironmentInheritance>,
+) -> anyhow::Result<()> {
+    let (has_local, remote) = match &executor_config.executor_kind {
+        CommandExecutorKind::Local(_) => (true, None),
+        CommandExecutorKind::Remote(remote) => (false, Some(remote)),
+        CommandExecutorKind::Hybrid { remote, .. } => (true, Some(remote)),
+    };
+
+    // This is what `RunAction` adds on top of the variables the action declares itself.
+    let mut added = HashMap::new();
+    let inheritance = apply_local_environment(
+        &mut added,
+        &executor_config.executor_kind,
+        scrubbed_local_env,
+    );
+
+    if has_local {
+        writeln!(stdout, "  Local:")?;
+        let daemon_env = std::env::vars_os().map(|(k, v)| {
+            (
+                k.to_string_lossy().into_owned(),
+                v.to_string_lossy().into_owned(),
+            )
+        });
+        let env = local_env(daemon_env, &inheritance, &added);
+        if inheritance.clear() {
+            writeln!(stdout, "    Environment (scrubbed to the allowlist):")?;
+        } else {
+            writeln!(stdout, "    Environment (inherited from the daemon):")?;
+        }
+        for (k, v) in &env {
+            writeln!(stdout, "      {}={}", k, v)?;
+        }
+        writeln!(stdout, "    PATH:")?;
+        match env.get("PATH") {
+            Some(path) => {
+                for entry in std::env::split_paths(path) {
+                    writeln!(stdout, "      {}", entry.display())?;
+                }
+            }
+            None => writeln!(stdout, "      <unset>")?,
+        }
+    } else {
+        writeln!(stdout, "  Local: not enabled on this platform")?;
+    }
+
+    match remote {
+        Some(remote) => {
+            writeln!(stdout, "  Remote:")?;
+            write_remote(&mut stdout, remote, &added)?;
+        }
+        None => writeln!(stdout, "  Remote: not enabled on this platform")?,
+    }
+
+    Ok(())
+}
+
+/// The environment a local command that declares no variables of its own would see.
+fn local_env(
+    daemon_env: impl IntoIterator<Item = (String, String)>,
+    inheritance: &EnvironmentInheritance,
+    added: &HashMap<String, String>,
+) -> BTreeMap<String, String> {
+    let mut env = if inheritance.clear() {
+        BTreeMap::new()
+    } else {
+        daemon_env.into_iter().collect()
+    };
+    for key in inheritance.exclusions() {
+        env.remove(key);
+    }
+    for (k, v) in inheritance.values() {
+        env.insert(k.to_owned(), v.to_string_lossy().into_owned());
+    }
+    for (k, v) in added {
+        env.insert(k.clone(), v.clone());
+    }
+    env
+}
+
+fn write_remote(
+    mut stdout: impl Write,
+    remote: &RemoteExecutorOptions,
+    added: &HashMap<String, String>,
+) -> anyhow::Result<()> {
+    writeln!(stdout, "    Use case: {}", remote.re_use_case)?;
+    if let Some(action_key) = &remote.re_action_key {
+        writeln!(stdout, "    Action key: {}", action_key)?;
+    }
+    writeln!(
+        stdout,
+        "    Persistent workers: {}",
+        if remote.re_persistent_workers {
+            "supported"
+        } else {
+            "not supported"
+        }
+    )?;
+    writeln!(stdout, "    Platform properties:")?;
+    for (k, v) in remote.re_properties.iter() {
+        writeln!(stdout, "      {}={}", k, v)?;
+    }
+    // Remote commands only get the variables that end up in the action, the rest (including
+    // PATH) is up to the worker.
+    writeln!(
+        stdout,
+        "    Environment (in addition to what actions declare):"
+    )?;
+    let mut added = added.iter().collect::<Vec<_>>();
+    added.sort();
+    for (k, v) in added {
+        writeln!(stdout, "      {}={}", k, v)?;
+    }
+    writeln!(
+        stdout,
+        "    PATH: set by the RE worker, unless actions declare it"
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_env() {
+        let daemon_env = vec![
+            ("PATH".to_owned(), "/usr/bin".to_owned()),
+            ("PYTHONPATH".to_owned(), "/lib".to_owned()),
+        ];
+        let mut added = HashMap::new();
+        added.insert("LANG".to_owned(), "C".to_owned());
+
+        let env = local_env(
+            daemon_env.clone(),
+            &EnvironmentInheritance::local_command_exclusions(),
+            &added,
+        );
+        assert_eq!(
+            env.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("LANG".to_owned(), "C".to_owned()),
+                ("PATH".to_owned(), "/usr/bin".to_owned()),
+            ]
+        );
+
+        let env = local_env(daemon_env, &EnvironmentInheritance::empty(), &added);
+        assert_eq!(
+            env.into_iter().collect::<Vec<_>>(),
+            vec![("LANG".to_owned(), "C".to_owned())]
+        );
+    }
+}
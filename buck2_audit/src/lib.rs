@@ -25,26 +25,36 @@ use cli_proto::GenericRequest;
 use crate::analysis_queries::AuditAnalysisQueriesCommand;
 use crate::cell::AuditCellCommand;
 use crate::config::AuditConfigCommand;
+use crate::config_hash::AuditConfigHashCommand;
 use crate::configurations::AuditConfigurationsCommand;
+use crate::deferred::AuditDeferredCommand;
 use crate::deferred_materializer::DeferredMaterializerCommand;
 use crate::dep_files::AuditDepFilesCommand;
+use crate::exec_env::AuditExecEnvCommand;
 use crate::execution_platform_resolution::AuditExecutionPlatformResolutionCommand;
 use crate::includes::AuditIncludesCommand;
+use crate::parse::AuditParseCommand;
 use crate::prelude::AuditPreludeCommand;
 use crate::providers::AuditProvidersCommand;
+use crate::select_resolution::AuditSelectResolutionCommand;
 use crate::starlark::StarlarkCommand;
 use crate::visibility::AuditVisibilityCommand;
 
 pub mod analysis_queries;
 pub mod cell;
 pub mod config;
+pub mod config_hash;
 pub mod configurations;
+pub mod deferred;
 pub mod deferred_materializer;
 pub mod dep_files;
+pub mod exec_env;
 pub mod execution_platform_resolution;
 pub mod includes;
+pub mod parse;
 pub mod prelude;
 pub mod providers;
+pub mod select_resolution;
 pub mod server;
 pub mod starlark;
 pub mod visibility;
@@ -65,6 +75,11 @@ pub enum AuditCommand {
     Starlark(StarlarkCommand),
     DepFiles(AuditDepFilesCommand),
     DeferredMaterializer(DeferredMaterializerCommand),
+    ExecEnv(AuditExecEnvCommand),
+    SelectResolution(AuditSelectResolutionCommand),
+    ConfigHash(AuditConfigHashCommand),
+    Deferred(AuditDeferredCommand),
+    Parse(AuditParseCommand),
 }
 
 #[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize, Default)]
@@ -122,6 +137,11 @@ impl AuditCommand {
             AuditCommand::DepFiles(cmd) => cmd,
             AuditCommand::DeferredMaterializer(cmd) => cmd,
             AuditCommand::Visibility(cmd) => cmd,
+            AuditCommand::ExecEnv(cmd) => cmd,
+            AuditCommand::SelectResolution(cmd) => cmd,
+            AuditCommand::ConfigHash(cmd) => cmd,
+            AuditCommand::Deferred(cmd) => cmd,
+            AuditCommand::Parse(cmd) => cmd,
         }
     }
 }
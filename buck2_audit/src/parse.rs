@@ -0,0 +1,137 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::io::Write;
+
+use async_trait::async_trait;
+use buck2_build_api::calculation::load_patterns;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+use buck2_core::pattern::TargetPattern;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::ctx::ServerCommandDiceContext;
+use buck2_server_ctx::pattern::parse_patterns_from_cli_args;
+use cli_proto::ClientContext;
+use gazebo::prelude::*;
+use serde::Serialize;
+use starlark::errors::Diagnostic;
+
+use crate::AuditCommandCommonOptions;
+use crate::AuditSubcommand;
+
+#[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize)]
+#[clap(
+    name = "audit-parse",
+    about = "Load every package matched by the patterns (e.g. `//...`) and report all the errors \
+    at once, as JSON. Fails if any package failed to load."
+)]
+pub struct AuditParseCommand {
+    #[clap(flatten)]
+    common_opts: AuditCommandCommonOptions,
+
+    #[clap(name = "TARGET_PATTERNS", help = "Patterns of the packages to load.")]
+    patterns: Vec<String>,
+}
+
+/// Where in a file an error was reported, with 1-based lines and columns.
+#[derive(Serialize)]
+struct ErrorSpan {
+    file: String,
+    line: usize,
+    column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+#[derive(Serialize)]
+struct PackageError {
+    package: String,
+    /// Where Starlark reported the error, if it did.
+    span: Option<ErrorSpan>,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct ParseReport {
+    /// Number of packages loaded, including the ones which failed.
+    packages: usize,
+    errors: Vec<PackageError>,
+}
+
+fn error_span(e: &anyhow::Error) -> Option<ErrorSpan> {
+    let span = e
+        .chain()
+        .find_map(|e| e.downcast_ref::<Diagnostic>()?.span.as_ref())?;
+    let resolved = span.resolve_span();
+    Some(ErrorSpan {
+        file: span.filename().to_owned(),
+        line: resolved.begin_line + 1,
+        column: resolved.begin_column + 1,
+        end_line: resolved.end_line + 1,
+        end_column: resolved.end_column + 1,
+    })
+}
+
+#[async_trait]
+impl AuditSubcommand for AuditParseCommand {
+    async fn server_execute(
+        &self,
+        server_ctx: Box<dyn ServerCommandContextTrait>,
+        _client_ctx: ClientContext,
+    ) -> anyhow::Result<()> {
+        server_ctx
+            .with_dice_ctx(async move |server_ctx, ctx| {
+                let parsed_patterns = parse_patterns_from_cli_args::<TargetPattern>(
+                    &self
+                        .patterns
+                        .map(|pat| buck2_data::TargetPattern { value: pat.clone() }),
+                    &ctx.get_cell_resolver().await?,
+                    &ctx.get_legacy_configs().await?,
+                    server_ctx.working_dir(),
+                )?;
+
+                // Errors are per package, so loading carries on past the broken ones.
+                let loaded = load_patterns(&ctx, parsed_patterns).await?;
+
+                let mut report = ParseReport {
+                    packages: 0,
+                    errors: Vec::new(),
+                };
+                for (package, result) in loaded.iter() {
+                    report.packages += 1;
+                    if let Err(e) = result {
+                        let e = e.inner();
+                        report.errors.push(PackageError {
+                            package: package.to_string(),
+                            span: error_span(e),
+                            message: format!("{:#}", e),
+                        });
+                    }
+                }
+
+                let mut stdout = server_ctx.stdout()?;
+                serde_json::to_writer_pretty(&mut stdout, &report)?;
+                writeln!(stdout)?;
+
+                if !report.errors.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "{} of {} packages failed to load",
+                        report.errors.len(),
+                        report.packages
+                    ));
+                }
+                Ok(())
+            })
+            .await
+    }
+
+    fn common_opts(&self) -> &AuditCommandCommonOptions {
+        &self.common_opts
+    }
+}
@@ -0,0 +1,155 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::io::Write;
+
+use async_trait::async_trait;
+use buck2_build_api::calculation::load_patterns;
+use buck2_build_api::calculation::Calculation;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+use buck2_core::pattern::TargetPattern;
+use buck2_node::attrs::coerced_attr::SelectResolution;
+use buck2_node::attrs::inspect_options::AttrInspectOptions;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::ctx::ServerCommandDiceContext;
+use buck2_server_ctx::pattern::parse_patterns_from_cli_args;
+use buck2_server_ctx::pattern::target_platform_from_client_context;
+use cli_proto::ClientContext;
+use itertools::Itertools;
+
+use crate::AuditCommandCommonOptions;
+use crate::AuditSubcommand;
+
+#[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize)]
+#[clap(
+    name = "audit-select-resolution",
+    about = "prints out which select() branch was chosen for each attribute of the configured targets"
+)]
+pub struct AuditSelectResolutionCommand {
+    #[clap(flatten)]
+    common_opts: AuditCommandCommonOptions,
+
+    #[clap(name = "TARGET_PATTERNS", help = "Patterns to analyze")]
+    patterns: Vec<String>,
+
+    #[clap(
+        long = "attr",
+        help = "Only print the resolutions for these attributes",
+        value_name = "ATTR"
+    )]
+    attrs: Vec<String>,
+}
+
+#[async_trait]
+impl AuditSubcommand for AuditSelectResolutionCommand {
+    async fn server_execute(
+        &self,
+        server_ctx: Box<dyn ServerCommandContextTrait>,
+        client_ctx: ClientContext,
+    ) -> anyhow::Result<()> {
+        server_ctx
+            .with_dice_ctx(async move |server_ctx, ctx| {
+                let cells = ctx.get_cell_resolver().await?;
+
+                let target_platform = target_platform_from_client_context(
+                    Some(&client_ctx),
+                    &cells,
+                    server_ctx.working_dir(),
+                )
+                .await?;
+
+                let patterns = parse_patterns_from_cli_args::<TargetPattern>(
+                    &self
+                        .patterns
+                        .iter()
+                        .map(|value| buck2_data::TargetPattern {
+                            value: value.clone(),
+                        })
+                        .collect::<Vec<_>>(),
+                    &cells,
+                    &ctx.get_legacy_configs().await?,
+                    server_ctx.working_dir(),
+                )?;
+
+                let mut stdout = server_ctx.stdout()?;
+
+                for (_, targets) in load_patterns(&ctx, patterns).await?.into_iter() {
+                    for (_, node) in targets? {
+                        let configured_target = ctx
+                            .get_configured_target(node.label(), target_platform.as_ref())
+                            .await?;
+                        let configured_node =
+                            ctx.get_configured_target_node(&configured_target).await?;
+                        let configured_node = configured_node.require_compatible()?;
+
+                        writeln!(stdout, "{}:", configured_target)?;
+                        for (attr, resolutions) in
+                            configured_node.select_resolutions(AttrInspectOptions::All)
+                        {
+                            if !self.attrs.is_empty() && !self.attrs.iter().any(|a| a == attr) {
+                                continue;
+                            }
+                            writeln!(stdout, "  {}:", attr)?;
+                            for resolution in &resolutions {
+                                writeln!(stdout, "    {}", format_resolution(resolution))?;
+                            }
+                        }
+                    }
+                }
+
+                Ok(())
+            })
+            .await
+    }
+
+    fn common_opts(&self) -> &AuditCommandCommonOptions {
+        &self.common_opts
+    }
+}
+
+fn format_resolution(resolution: &SelectResolution) -> String {
+    format!(
+        "select({}) -> {}",
+        resolution.keys.iter().join(", "),
+        match &resolution.chosen {
+            Some(chosen) => chosen.to_string(),
+            None => "DEFAULT".to_owned(),
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::target::testing::TargetLabelExt;
+    use buck2_core::target::TargetLabel;
+    use gazebo::dupe::Dupe;
+
+    use super::*;
+
+    #[test]
+    fn test_format_resolution() {
+        let linux = TargetLabel::testing_parse("root//:linux");
+        let macos = TargetLabel::testing_parse("root//:macos");
+        assert_eq!(
+            "select(root//:linux, root//:macos) -> root//:macos",
+            format_resolution(&SelectResolution {
+                keys: vec![linux.dupe(), macos.dupe()],
+                chosen: Some(macos.dupe()),
+            })
+        );
+        assert_eq!(
+            "select(root//:linux, root//:macos) -> DEFAULT",
+            format_resolution(&SelectResolution {
+                keys: vec![linux, macos],
+                chosen: None,
+            })
+        );
+    }
+}
@@ -0,0 +1,496 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! `buck2 audit starlark edit`: structured edits of the targets declared in build files, e.g.
+//! adding a dependency, for codemods and tools fixing dependencies automatically.
+//!
+//! Edits only touch the text they change, so the formatting and comments of the rest of the file
+//! are preserved.
+
+use std::io::Write;
+use std::ops::Range;
+
+use anyhow::Context;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+use buck2_common::package_listing::dice::HasPackageListingResolver;
+use buck2_core::fs::fs_util;
+use buck2_core::pattern::TargetPattern;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::ctx::ServerCommandDiceContext;
+use buck2_server_ctx::pattern::PatternParser;
+use cli_proto::ClientContext;
+use starlark::syntax::AstModule;
+use starlark::syntax::Dialect;
+use starlark::syntax::TargetCall;
+
+use crate::starlark::lint::label_sort_key;
+use crate::AuditCommandCommonOptions;
+
+#[derive(Debug, thiserror::Error)]
+enum StarlarkEditError {
+    #[error("No target named `{0}` is declared in `{1}`")]
+    TargetNotFound(String, String),
+    #[error("Attribute `{0}` of target `{1}` is not a list literal, so it can't be edited")]
+    NotAList(String, String),
+    #[error("`{0}` is not a valid Starlark expression")]
+    InvalidValue(String),
+}
+
+#[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize)]
+#[clap(
+    name = "edit",
+    about = "Edit the targets declared in build files, preserving formatting and comments"
+)]
+pub struct StarlarkEditCommand {
+    #[clap(subcommand)]
+    action: TargetEdit,
+
+    #[clap(flatten)]
+    pub(crate) common_opts: AuditCommandCommonOptions,
+}
+
+#[derive(Debug, Clone, clap::Subcommand, serde::Serialize, serde::Deserialize)]
+enum TargetEdit {
+    #[clap(
+        about = "Add a dependency to a target. If the list of dependencies is sorted, it is kept sorted"
+    )]
+    AddDep {
+        #[clap(name = "TARGET", help = "The target to edit, e.g. `//foo:bar`")]
+        target: String,
+        #[clap(
+            name = "DEP",
+            help = "The dependency, written to the build file as given"
+        )]
+        dep: String,
+        #[clap(
+            long,
+            default_value = "deps",
+            help = "The attribute to add the dependency to"
+        )]
+        attr: String,
+    },
+    #[clap(about = "Remove a dependency from a target")]
+    RemoveDep {
+        #[clap(name = "TARGET", help = "The target to edit, e.g. `//foo:bar`")]
+        target: String,
+        #[clap(name = "DEP", help = "The dependency, as written in the build file")]
+        dep: String,
+        #[clap(
+            long,
+            default_value = "deps",
+            help = "The attribute to remove the dependency from"
+        )]
+        attr: String,
+    },
+    #[clap(about = "Set an attribute of a target to a Starlark expression")]
+    Set {
+        #[clap(name = "TARGET", help = "The target to edit, e.g. `//foo:bar`")]
+        target: String,
+        #[clap(name = "ATTR", help = "The attribute to set")]
+        attr: String,
+        #[clap(name = "VALUE", help = "The new value, e.g. `[\"PUBLIC\"]`")]
+        value: String,
+    },
+}
+
+impl TargetEdit {
+    fn target(&self) -> &str {
+        match self {
+            TargetEdit::AddDep { target, .. }
+            | TargetEdit::RemoveDep { target, .. }
+            | TargetEdit::Set { target, .. } => target,
+        }
+    }
+}
+
+/// A replacement of a range of bytes of a file. Edits of a change are in order and don't
+/// overlap.
+type Edit = (Range<usize>, String);
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The position of the opening parenthesis of a call.
+fn call_open(content: &str, call: &TargetCall) -> usize {
+    let range = call.span.byte_range();
+    range.start + content[range].find('(').unwrap_or(0)
+}
+
+/// The edits inserting `text` as the `index`th item of the list or call with its opening bracket
+/// at `open`, following the layout of the existing items.
+fn insert_item(
+    content: &str,
+    open: usize,
+    items: &[Range<usize>],
+    index: usize,
+    text: &str,
+) -> Vec<Edit> {
+    let first = match items.first() {
+        Some(first) => first,
+        None => return vec![(open + 1..open + 1, text.to_owned())],
+    };
+
+    if !content[open + 1..first.start].contains('\n') {
+        // On one line.
+        return if index < items.len() {
+            let start = items[index].start;
+            vec![(start..start, format!("{}, ", text))]
+        } else {
+            let end = items[index - 1].end;
+            vec![(end..end, format!(", {}", text))]
+        };
+    }
+
+    // One item per line.
+    let line_start = content[..first.start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = match &content[line_start..first.start] {
+        indent if indent.trim().is_empty() => indent,
+        _ => "    ",
+    };
+    if index == 0 {
+        let pos = open + content[open..].find('\n').map_or(0, |i| i + 1);
+        return vec![(pos..pos, format!("{}{},\n", indent, text))];
+    }
+
+    let prev = &items[index - 1];
+    let line_end = prev.end
+        + content[prev.end..]
+            .find('\n')
+            .unwrap_or(content.len() - prev.end);
+    let rest = content[prev.end..line_end]
+        .trim_start()
+        .trim_start_matches(',')
+        .trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        // Something else follows the item on its line, e.g. the closing bracket.
+        return vec![(prev.end..prev.end, format!(", {}", text))];
+    }
+    let mut edits = Vec::new();
+    if !content[prev.end..line_end].trim_start().starts_with(',') {
+        edits.push((prev.end..prev.end, ",".to_owned()));
+    }
+    // After the line of the previous item, so a comment on that line stays with it.
+    edits.push((line_end..line_end, format!("\n{}{},", indent, text)));
+    edits
+}
+
+/// The edits removing the `index`th item of the list `list`.
+fn remove_item(
+    content: &str,
+    list: Range<usize>,
+    items: &[Range<usize>],
+    index: usize,
+) -> Vec<Edit> {
+    if items.len() == 1 {
+        return vec![(list, "[]".to_owned())];
+    }
+
+    // If the item has a line of its own, remove the line, with any comment on it.
+    let item = &items[index];
+    let line_start = content[..item.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = item.end
+        + content[item.end..]
+            .find('\n')
+            .map_or(content.len() - item.end, |i| i + 1);
+    let before = &content[line_start..item.start];
+    let after = content[item.end..line_end]
+        .trim_start()
+        .trim_start_matches(',')
+        .trim_start();
+    if before.trim().is_empty() && (after.is_empty() || after.starts_with('#')) {
+        return vec![(line_start..line_end, String::new())];
+    }
+
+    // Otherwise remove the item with the separator after it, or before it for the last item.
+    let range = if index + 1 < items.len() {
+        item.start..items[index + 1].start
+    } else {
+        items[index - 1].end..item.end
+    };
+    vec![(range, String::new())]
+}
+
+/// Applies `edit` to the target `name` in `content`, the build file `filename`. Returns `None` if
+/// there is nothing to change, e.g. the dependency to add is already there.
+fn edit_file(
+    filename: &str,
+    content: &str,
+    name: &str,
+    edit: &TargetEdit,
+) -> anyhow::Result<Option<String>> {
+    let ast = AstModule::parse(filename, content.to_owned(), &Dialect::Extended)?;
+    let calls = ast.target_calls();
+    let target = edit.target();
+    let call = calls
+        .iter()
+        .find(|c| c.name == name)
+        .ok_or_else(|| StarlarkEditError::TargetNotFound(target.to_owned(), filename.to_owned()))?;
+    let find_arg = |attr: &str| call.arguments.iter().find(|a| a.name == Some(attr));
+    let args: Vec<Range<usize>> = call.arguments.iter().map(|a| a.span.byte_range()).collect();
+
+    let edits = match edit {
+        TargetEdit::AddDep { dep, attr, .. } => match find_arg(attr) {
+            None => insert_item(
+                content,
+                call_open(content, call),
+                &args,
+                args.len(),
+                &format!("{} = [{}]", attr, quote(dep)),
+            ),
+            Some(arg) => {
+                let list = arg
+                    .list
+                    .as_ref()
+                    .ok_or_else(|| StarlarkEditError::NotAList(attr.clone(), target.to_owned()))?;
+                if list.items.iter().any(|(_, x)| *x == Some(dep.as_str())) {
+                    return Ok(None);
+                }
+                let items: Vec<Range<usize>> =
+                    list.items.iter().map(|(x, _)| x.byte_range()).collect();
+                let strings: Option<Vec<&str>> = list.items.iter().map(|(_, x)| *x).collect();
+                let index = match strings {
+                    Some(xs)
+                        if xs
+                            .windows(2)
+                            .all(|w| label_sort_key(w[0]) <= label_sort_key(w[1])) =>
+                    {
+                        xs.partition_point(|x| label_sort_key(x) < label_sort_key(dep))
+                    }
+                    _ => items.len(),
+                };
+                insert_item(
+                    content,
+                    list.span.byte_range().start,
+                    &items,
+                    index,
+                    &quote(dep),
+                )
+            }
+        },
+        TargetEdit::RemoveDep { dep, attr, .. } => {
+            let list = match find_arg(attr).and_then(|a| a.list.as_ref()) {
+                Some(list) => list,
+                None => return Ok(None),
+            };
+            let index = match list
+                .items
+                .iter()
+                .position(|(_, x)| *x == Some(dep.as_str()))
+            {
+                Some(index) => index,
+                None => return Ok(None),
+            };
+            let items: Vec<Range<usize>> = list.items.iter().map(|(x, _)| x.byte_range()).collect();
+            remove_item(content, list.span.byte_range(), &items, index)
+        }
+        TargetEdit::Set { attr, value, .. } => {
+            AstModule::parse("VALUE", format!("_ = {}\n", value), &Dialect::Extended)
+                .map_err(|_| StarlarkEditError::InvalidValue(value.clone()))?;
+            match find_arg(attr) {
+                Some(arg) if arg.value.source_span() == value => return Ok(None),
+                Some(arg) => vec![(arg.value.byte_range(), value.clone())],
+                None => insert_item(
+                    content,
+                    call_open(content, call),
+                    &args,
+                    args.len(),
+                    &format!("{} = {}", attr, value),
+                ),
+            }
+        }
+    };
+
+    let mut res = content.to_owned();
+    for (range, text) in edits.into_iter().rev() {
+        res.replace_range(range, &text);
+    }
+    // The edits should keep the file valid, but check rather than write a broken file.
+    AstModule::parse(filename, res.clone(), &Dialect::Extended)
+        .with_context(|| format!("Editing `{}` would make it invalid", filename))?;
+    Ok(Some(res))
+}
+
+impl StarlarkEditCommand {
+    pub async fn server_execute(
+        &self,
+        server_ctx: Box<dyn ServerCommandContextTrait>,
+        _client_ctx: ClientContext,
+    ) -> anyhow::Result<()> {
+        server_ctx
+            .with_dice_ctx(async move |server_ctx, dice_ctx| {
+                let cell_resolver = dice_ctx.get_cell_resolver().await?;
+                let pattern_parser = PatternParser::new(
+                    &cell_resolver,
+                    &dice_ctx.get_legacy_configs().await?,
+                    server_ctx.working_dir(),
+                )?;
+                let target = self.action.target();
+                let (package, name) = pattern_parser
+                    .parse_pattern::<TargetPattern>(target)?
+                    .as_literal(target)?;
+                let listing = dice_ctx
+                    .get_package_listing_resolver()
+                    .resolve(&package)
+                    .await?;
+                let path = cell_resolver
+                    .resolve_package(&package)?
+                    .join(listing.buildfile());
+
+                let abs = server_ctx.project_root().resolve(&path);
+                let content = fs_util::read_to_string(&abs)?;
+                let mut stdout = server_ctx.stdout()?;
+                match edit_file(path.as_str(), &content, name.value(), &self.action)? {
+                    Some(new_content) => {
+                        fs_util::write(&abs, new_content)?;
+                        writeln!(stdout, "Edited {}", path)?;
+                    }
+                    None => writeln!(stdout, "{} is unchanged", path)?,
+                }
+                Ok(())
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(content: &str, edit: TargetEdit) -> String {
+        edit_file("BUCK", content, "foo", &edit).unwrap().unwrap()
+    }
+
+    fn add_dep(dep: &str) -> TargetEdit {
+        TargetEdit::AddDep {
+            target: "//foo:foo".to_owned(),
+            dep: dep.to_owned(),
+            attr: "deps".to_owned(),
+        }
+    }
+
+    fn remove_dep(dep: &str) -> TargetEdit {
+        TargetEdit::RemoveDep {
+            target: "//foo:foo".to_owned(),
+            dep: dep.to_owned(),
+            attr: "deps".to_owned(),
+        }
+    }
+
+    const MULTILINE: &str = r#"# Header.
+rust_library(
+    name = "foo",
+    deps = [
+        ":a",  # Comment.
+        "//c:c"
+    ],
+)
+"#;
+
+    #[test]
+    fn test_add_dep() {
+        assert_eq!(
+            edit(MULTILINE, add_dep(":b")),
+            r#"# Header.
+rust_library(
+    name = "foo",
+    deps = [
+        ":a",  # Comment.
+        ":b",
+        "//c:c"
+    ],
+)
+"#
+        );
+        assert_eq!(
+            edit(MULTILINE, add_dep("//d:d")),
+            r#"# Header.
+rust_library(
+    name = "foo",
+    deps = [
+        ":a",  # Comment.
+        "//c:c",
+        "//d:d",
+    ],
+)
+"#
+        );
+        assert_eq!(
+            edit(
+                "rust_library(name = 'foo', deps = [':b'] + select({}))\n",
+                add_dep(":a")
+            ),
+            "rust_library(name = 'foo', deps = [\":a\", ':b'] + select({}))\n"
+        );
+        assert_eq!(
+            edit("rust_library(name = 'foo')\n", add_dep(":a")),
+            "rust_library(name = 'foo', deps = [\":a\"])\n"
+        );
+        assert_eq!(
+            edit_file("BUCK", MULTILINE, "foo", &add_dep(":a")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_remove_dep() {
+        assert_eq!(
+            edit(MULTILINE, remove_dep(":a")),
+            r#"# Header.
+rust_library(
+    name = "foo",
+    deps = [
+        "//c:c"
+    ],
+)
+"#
+        );
+        assert_eq!(
+            edit(
+                "rust_library(name = 'foo', deps = [':a', ':b'])\n",
+                remove_dep(":b")
+            ),
+            "rust_library(name = 'foo', deps = [':a'])\n"
+        );
+        assert_eq!(
+            edit_file("BUCK", MULTILINE, "foo", &remove_dep(":z")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_set() {
+        let set = |attr: &str, value: &str| TargetEdit::Set {
+            target: ":foo".to_owned(),
+            attr: attr.to_owned(),
+            value: value.to_owned(),
+        };
+        assert_eq!(
+            edit(MULTILINE, set("deps", "[]")),
+            "# Header.\nrust_library(\n    name = \"foo\",\n    deps = [],\n)\n"
+        );
+        assert_eq!(
+            edit(MULTILINE, set("visibility", "[\"PUBLIC\"]")),
+            MULTILINE.replace("    ],\n", "    ],\n    visibility = [\"PUBLIC\"],\n")
+        );
+        assert!(edit_file("BUCK", MULTILINE, "foo", &set("deps", "[")).is_err());
+        assert!(edit_file(
+            "BUCK",
+            MULTILINE,
+            "bar",
+            &TargetEdit::Set {
+                target: ":bar".to_owned(),
+                attr: "deps".to_owned(),
+                value: "[]".to_owned(),
+            }
+        )
+        .is_err());
+    }
+}
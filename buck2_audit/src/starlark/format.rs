@@ -0,0 +1,195 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! `buck2 audit starlark format`: rewrites `BUCK` and `.bzl` files in a canonical layout, see
+//! [`AstModule::format`].
+
+use std::io::Write;
+
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_core::fs::fs_util;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::ctx::ServerCommandDiceContext;
+use cli_proto::ClientContext;
+use starlark::syntax::AstModule;
+use starlark::syntax::Dialect;
+use starlark::syntax::FormatOptions;
+
+use crate::starlark::lint::collect_files;
+use crate::AuditCommandCommonOptions;
+
+#[derive(Debug, thiserror::Error)]
+enum StarlarkFormatError {
+    #[error("{0} file(s) are not formatted")]
+    NotFormatted(usize),
+    #[error("{0} file(s) could not be parsed")]
+    ParseErrors(usize),
+}
+
+#[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize)]
+#[clap(name = "format", about = "Format BUCK and .bzl files")]
+pub struct StarlarkFormatCommand {
+    #[clap(
+        name = "PATH",
+        help = "Files or directories to format, relative to the current directory. Directories are searched recursively for build files, .bzl and .bxl files. Defaults to the current directory."
+    )]
+    paths: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Don't rewrite any files, print those which are not formatted and fail if there are any"
+    )]
+    check: bool,
+
+    #[clap(
+        long,
+        help = "Sort the named arguments of top-level calls, e.g. the attributes of targets, with `name` first"
+    )]
+    sort_kwargs: bool,
+
+    #[clap(flatten)]
+    pub(crate) common_opts: AuditCommandCommonOptions,
+}
+
+/// The formatted content of a file, with the line endings of its first line. Fails if the file
+/// doesn't parse.
+fn format_file(filename: &str, content: String, options: &FormatOptions) -> anyhow::Result<String> {
+    let crlf = content
+        .find('\n')
+        .map_or(false, |i| content[..i].ends_with('\r'));
+    let content = if crlf {
+        content.replace("\r\n", "\n")
+    } else {
+        content
+    };
+    let ast = AstModule::parse(filename, content, &Dialect::Extended)?;
+    let formatted = ast.format(options);
+    Ok(if crlf {
+        formatted.replace('\n', "\r\n")
+    } else {
+        formatted
+    })
+}
+
+impl StarlarkFormatCommand {
+    pub async fn server_execute(
+        &self,
+        server_ctx: Box<dyn ServerCommandContextTrait>,
+        _client_ctx: ClientContext,
+    ) -> anyhow::Result<()> {
+        server_ctx
+            .with_dice_ctx(async move |server_ctx, dice_ctx| {
+                let cell_resolver = dice_ctx.get_cell_resolver().await?;
+                let options = FormatOptions {
+                    sort_named_arguments: self.sort_kwargs,
+                };
+
+                let paths = if self.paths.is_empty() {
+                    vec![".".to_owned()]
+                } else {
+                    self.paths.clone()
+                };
+                let mut files = Vec::new();
+                for path in &paths {
+                    let path = server_ctx.working_dir().join_normalized(path.as_str())?;
+                    collect_files(
+                        server_ctx.project_root(),
+                        &cell_resolver,
+                        &path,
+                        true,
+                        &mut files,
+                    )?;
+                }
+
+                let mut stdout = server_ctx.stdout()?;
+                let mut unformatted = 0;
+                let mut parse_errors = 0;
+                for file in files {
+                    let abs = server_ctx.project_root().resolve(&file);
+                    let content = fs_util::read_to_string(&abs)?;
+                    let formatted = match format_file(file.as_str(), content.clone(), &options) {
+                        Ok(formatted) => formatted,
+                        Err(e) => {
+                            writeln!(stdout, "{}: parse-error: {:#}", file, e)?;
+                            parse_errors += 1;
+                            continue;
+                        }
+                    };
+                    if formatted == content {
+                        continue;
+                    }
+                    if self.check {
+                        writeln!(stdout, "{}", file)?;
+                        unformatted += 1;
+                    } else {
+                        // Files named explicitly may be symlinks, which are kept.
+                        fs_util::write_atomic(fs_util::canonicalize(&abs)?, formatted)?;
+                        writeln!(stdout, "Formatted {}", file)?;
+                    }
+                }
+
+                if parse_errors != 0 {
+                    Err(StarlarkFormatError::ParseErrors(parse_errors).into())
+                } else if unformatted != 0 {
+                    Err(StarlarkFormatError::NotFormatted(unformatted).into())
+                } else {
+                    Ok(())
+                }
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_file() {
+        let options = FormatOptions {
+            sort_named_arguments: true,
+        };
+        let formatted = format_file(
+            "BUCK",
+            "rust_library(srcs = ['lib.rs'], name = 'lib')\n".to_owned(),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            formatted,
+            "rust_library(\n    name = \"lib\",\n    srcs = [\"lib.rs\"],\n)\n"
+        );
+        assert_eq!(
+            format_file("BUCK", formatted.clone(), &options).unwrap(),
+            formatted
+        );
+        assert!(format_file("BUCK", "x = (".to_owned(), &options).is_err());
+    }
+
+    #[test]
+    fn test_format_file_crlf() {
+        let options = FormatOptions {
+            sort_named_arguments: false,
+        };
+        let formatted = format_file(
+            "BUCK",
+            "# Library\r\nrust_library(name = 'lib', srcs = ['lib.rs'])\r\n".to_owned(),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            formatted,
+            "# Library\r\nrust_library(\r\n    name = \"lib\",\r\n    srcs = [\"lib.rs\"],\r\n)\r\n"
+        );
+        assert_eq!(
+            format_file("BUCK", formatted.clone(), &options).unwrap(),
+            formatted
+        );
+    }
+}
@@ -0,0 +1,370 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! `buck2 audit starlark heap`: the memory a Starlark module keeps alive once it's loaded.
+//!
+//! The module is evaluated again (the modules it loads are not), and the values left in its frozen
+//! heap are attributed to the definition that retains them, along with the definitions whose
+//! memory they reference. Build files are never frozen, so for those the memory allocated during
+//! their evaluation is reported instead, by the function that allocated it.
+
+use std::fmt::Write as _;
+use std::io::Write;
+
+use anyhow::Context as _;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::package_listing::dice::HasPackageListingResolver;
+use buck2_common::package_listing::resolver::PackageListingResolver;
+use buck2_core::bzl::ImportPath;
+use buck2_core::cells::build_file_cell::BuildFileCell;
+use buck2_core::package::Package;
+use buck2_interpreter::common::BxlFilePath;
+use buck2_interpreter::common::StarlarkModulePath;
+use buck2_interpreter::dice::HasCalculationDelegate;
+use buck2_interpreter::starlark_profiler::StarlarkProfiler;
+use buck2_interpreter::starlark_profiler::StarlarkProfilerOrInstrumentation;
+use buck2_interpreter_for_build::interpreter::module_internals::ModuleInternals;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::ctx::ServerCommandDiceContext;
+use cli_proto::ClientContext;
+use starlark::environment::ModuleRetention;
+use starlark::eval::ProfileMode;
+
+use crate::AuditCommandCommonOptions;
+
+#[derive(Debug, thiserror::Error)]
+enum StarlarkHeapError {
+    #[error("`{0}` is not a `.bzl` or `.bxl` file, nor the build file of its package (`{1}`)")]
+    NotABuildFile(String, String),
+    #[error("Build files are not frozen, so `{0}` has no retention graph")]
+    NoGraphForBuildFile(String),
+}
+
+#[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize)]
+#[clap(
+    name = "heap",
+    about = "Show the memory a .bzl, .bxl or build file retains, by the definition that retains it"
+)]
+pub struct StarlarkHeapCommand {
+    #[clap(
+        name = "PATH",
+        help = "The file to profile, relative to the current directory"
+    )]
+    path: String,
+
+    #[clap(
+        long,
+        help = "Print the graph of which definitions reference the memory of which, in DOT format, instead of a summary by definition"
+    )]
+    graph: bool,
+
+    #[clap(flatten)]
+    pub(crate) common_opts: AuditCommandCommonOptions,
+}
+
+impl StarlarkHeapCommand {
+    pub async fn server_execute(
+        &self,
+        server_ctx: Box<dyn ServerCommandContextTrait>,
+        _client_ctx: ClientContext,
+    ) -> anyhow::Result<()> {
+        server_ctx
+            .with_dice_ctx(async move |server_ctx, dice_ctx| {
+                let cell_resolver = dice_ctx.get_cell_resolver().await?;
+                let path = server_ctx
+                    .working_dir()
+                    .join_normalized(self.path.as_str())?;
+                let cell_path = cell_resolver.get_cell_path(&path)?;
+                let build_file_cell = BuildFileCell::new(cell_path.cell().clone());
+
+                let output = match cell_path.path().extension() {
+                    Some(ext @ ("bzl" | "bxl")) => {
+                        let calculation = dice_ctx
+                            .get_interpreter_calculator(cell_path.cell(), &build_file_cell)
+                            .await?;
+                        let module = if ext == "bxl" {
+                            let bxl_path = BxlFilePath::new(cell_path)?;
+                            calculation
+                                .eval_module_with_retention(StarlarkModulePath::BxlFile(&bxl_path))
+                                .await?
+                        } else {
+                            let import_path = ImportPath::new(cell_path, build_file_cell)?;
+                            calculation
+                                .eval_module_with_retention(StarlarkModulePath::LoadFile(
+                                    &import_path,
+                                ))
+                                .await?
+                        };
+                        let retention = module.retention()?;
+                        if self.graph {
+                            retention_graph(retention)
+                        } else {
+                            retention_summary(retention)
+                        }
+                    }
+                    _ => {
+                        let package = Package::from_cell_path(
+                            &cell_path
+                                .parent()
+                                .with_context(|| format!("`{}` is not a file", cell_path))?,
+                        );
+                        let buildfile = dice_ctx
+                            .get_package_listing_resolver()
+                            .resolve(&package)
+                            .await?
+                            .buildfile()
+                            .to_owned();
+                        if cell_path.path().file_name() != Some(&*buildfile) {
+                            return Err(StarlarkHeapError::NotABuildFile(
+                                cell_path.to_string(),
+                                buildfile.to_string(),
+                            )
+                            .into());
+                        }
+                        if self.graph {
+                            return Err(
+                                StarlarkHeapError::NoGraphForBuildFile(cell_path.to_string()).into(),
+                            );
+                        }
+
+                        writeln!(
+                            server_ctx.stderr()?,
+                            "Build files are not frozen, showing the memory allocated while evaluating `{}`",
+                            cell_path
+                        )?;
+                        let mut profiler =
+                            StarlarkProfiler::new(ProfileMode::HeapSummaryAllocated, false);
+                        dice_ctx
+                            .get_interpreter_calculator(package.cell_name(), &build_file_cell)
+                            .await?
+                            .eval_build_file::<ModuleInternals>(
+                                &package,
+                                &mut StarlarkProfilerOrInstrumentation::for_profiler(
+                                    &mut profiler,
+                                ),
+                            )
+                            .await?;
+                        profiler.finish()?.profile_data.gen()?
+                    }
+                };
+
+                let mut stdout = server_ctx.stdout()?;
+                write!(stdout, "{}", output)?;
+                Ok(())
+            })
+            .await
+    }
+}
+
+fn definition_name(retention: &ModuleRetention, index: usize) -> String {
+    match &retention.definitions[index].name {
+        Some(name) => name.clone(),
+        None => format!("<slot {}>", index),
+    }
+}
+
+/// The definitions that retain memory, by retained bytes, as CSV, with the definitions whose
+/// memory each references.
+fn retention_summary(retention: &ModuleRetention) -> String {
+    let mut definitions = retention
+        .definitions
+        .iter()
+        .enumerate()
+        .filter(|(index, d)| d.bytes != 0 || retention.references.iter().any(|r| r.from == *index))
+        .collect::<Vec<_>>();
+    definitions
+        .sort_by(|(a_index, a), (b_index, b)| b.bytes.cmp(&a.bytes).then(a_index.cmp(b_index)));
+
+    let mut out = String::new();
+    writeln!(out, "Definition,Bytes,Values,References").unwrap();
+    writeln!(
+        out,
+        "(module code and constants),{},,",
+        retention.unattributed_bytes
+    )
+    .unwrap();
+    for (index, definition) in definitions {
+        let references = retention
+            .references
+            .iter()
+            .filter(|r| r.from == index)
+            .map(|r| format!("{} ({})", definition_name(retention, r.to), r.count))
+            .collect::<Vec<_>>();
+        writeln!(
+            out,
+            "{},{},{},{}",
+            definition_name(retention, index),
+            definition.bytes,
+            definition.values,
+            references.join(" ")
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// The retention graph in DOT format: a node per definition that retains memory, and an edge from
+/// each definition to the definitions whose memory it references.
+fn retention_graph(retention: &ModuleRetention) -> String {
+    let mut out = String::new();
+    writeln!(out, "digraph retention {{").unwrap();
+    for (index, definition) in retention.definitions.iter().enumerate() {
+        let referenced = retention
+            .references
+            .iter()
+            .any(|r| r.from == index || r.to == index);
+        if definition.bytes != 0 || referenced {
+            writeln!(
+                out,
+                "  \"{}\" [label=\"{}\\n{} bytes\"];",
+                definition_name(retention, index),
+                definition_name(retention, index),
+                definition.bytes
+            )
+            .unwrap();
+        }
+    }
+    for reference in &retention.references {
+        writeln!(
+            out,
+            "  \"{}\" -> \"{}\" [label=\"{}\"];",
+            definition_name(retention, reference.from),
+            definition_name(retention, reference.to),
+            reference.count
+        )
+        .unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use starlark::environment::Globals;
+    use starlark::environment::Module;
+    use starlark::environment::ModuleRetention;
+    use starlark::environment::RetainedDefinition;
+    use starlark::environment::RetentionReference;
+    use starlark::eval::Evaluator;
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+
+    use super::*;
+
+    fn definition(name: Option<&str>, bytes: usize, values: usize) -> RetainedDefinition {
+        RetainedDefinition {
+            name: name.map(str::to_owned),
+            bytes,
+            values,
+        }
+    }
+
+    fn retention() -> ModuleRetention {
+        ModuleRetention {
+            unattributed_bytes: 100,
+            definitions: vec![
+                definition(Some("small"), 10, 1),
+                definition(Some("table"), 1000, 20),
+                definition(Some("alias"), 0, 0),
+                definition(Some("uses"), 30, 1),
+                definition(None, 0, 0),
+            ],
+            references: vec![
+                RetentionReference {
+                    from: 2,
+                    to: 1,
+                    count: 1,
+                },
+                RetentionReference {
+                    from: 3,
+                    to: 0,
+                    count: 1,
+                },
+                RetentionReference {
+                    from: 3,
+                    to: 1,
+                    count: 2,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_retention_summary() {
+        assert_eq!(
+            "Definition,Bytes,Values,References\n\
+             (module code and constants),100,,\n\
+             table,1000,20,\n\
+             uses,30,1,small (1) table (2)\n\
+             small,10,1,\n\
+             alias,0,0,table (1)\n",
+            retention_summary(&retention())
+        );
+    }
+
+    #[test]
+    fn test_retention_graph() {
+        assert_eq!(
+            "digraph retention {\n\
+             \x20 \"small\" [label=\"small\\n10 bytes\"];\n\
+             \x20 \"table\" [label=\"table\\n1000 bytes\"];\n\
+             \x20 \"alias\" [label=\"alias\\n0 bytes\"];\n\
+             \x20 \"uses\" [label=\"uses\\n30 bytes\"];\n\
+             \x20 \"alias\" -> \"table\" [label=\"1\"];\n\
+             \x20 \"uses\" -> \"small\" [label=\"1\"];\n\
+             \x20 \"uses\" -> \"table\" [label=\"2\"];\n\
+             }\n",
+            retention_graph(&retention())
+        );
+    }
+
+    #[test]
+    fn test_retention_of_module() -> anyhow::Result<()> {
+        let module = Module::new();
+        module.enable_retention();
+        {
+            let mut eval = Evaluator::new(&module);
+            let ast = AstModule::parse(
+                "defs.bzl",
+                r#"
+_TABLE = {"k" + str(i): ["v" + str(i)] for i in range(100)}
+
+def lookup(key):
+    return _TABLE[key]
+
+TABLES = [_TABLE]
+"#
+                .to_owned(),
+                &Dialect::Extended,
+            )?;
+            eval.eval_module(ast, &Globals::standard())?;
+        }
+        let module = module.freeze()?;
+        let summary = retention_summary(module.retention()?);
+        let lines = summary.lines().collect::<Vec<_>>();
+        // The table is attributed to the definition that retains it, not to the comprehension
+        // that allocated it, and the other definitions reference it.
+        assert!(lines[2].starts_with("_TABLE,"), "{}", summary);
+        assert!(
+            lines
+                .iter()
+                .any(|l| l.starts_with("TABLES,") && l.ends_with(",_TABLE (1)")),
+            "{}",
+            summary
+        );
+
+        let graph = retention_graph(module.retention()?);
+        assert!(
+            graph.contains("\"TABLES\" -> \"_TABLE\" [label=\"1\"];"),
+            "{}",
+            graph
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,644 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! `buck2 audit starlark lint`: static checks over `BUCK` and `.bzl` files, some of which can be
+//! fixed in place.
+//!
+//! Besides the checks of the Starlark linter itself (e.g. unused loads), this checks that the lists
+//! of labels named in `starlark_lint.sorted_attrs` (by default `deps`) are sorted and that none of
+//! the globals in `starlark_lint.deprecated_symbols` are used. The latter is a comma separated
+//! list of `name` or `name=replacement`. Variables shadowing top-level ones are only reported if
+//! `starlark_lint.shadowed_variables` is true, as shadowing is often deliberate.
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::ops::Range;
+
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+use buck2_core::cells::CellResolver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRelativePath;
+use buck2_core::fs::project::ProjectRelativePathBuf;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::ctx::ServerCommandDiceContext;
+use cli_proto::ClientContext;
+use gazebo::prelude::*;
+use starlark::codemap::FileSpan;
+use starlark::syntax::AstModule;
+use starlark::syntax::Dialect;
+
+use crate::AuditCommandCommonOptions;
+
+const CONFIG_SECTION: &str = "starlark_lint";
+
+#[derive(Debug, thiserror::Error)]
+enum StarlarkLintError {
+    #[error("Found {0} Starlark lint problem(s)")]
+    ProblemsFound(usize),
+}
+
+#[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize)]
+#[clap(
+    name = "lint",
+    about = "Lint BUCK and .bzl files, optionally fixing what can be fixed automatically"
+)]
+pub struct StarlarkLintCommand {
+    #[clap(
+        name = "PATH",
+        help = "Files or directories to lint, relative to the current directory. Directories are searched recursively for build files, .bzl and .bxl files. Defaults to the current directory."
+    )]
+    paths: Vec<String>,
+
+    #[clap(long, help = "Rewrite the files to fix the problems that can be fixed")]
+    fix: bool,
+
+    #[clap(flatten)]
+    pub(crate) common_opts: AuditCommandCommonOptions,
+}
+
+/// What to check beyond the linter built into Starlark, from buckconfig.
+#[derive(Debug, Default)]
+struct LintConfig {
+    /// Named arguments whose lists of strings must be sorted.
+    sorted_attrs: Vec<String>,
+    /// Deprecated globals, with what to replace them with, if anything.
+    deprecated_symbols: Vec<(String, Option<String>)>,
+    /// Whether to report variables that shadow top-level ones.
+    shadowed_variables: bool,
+}
+
+impl LintConfig {
+    fn new(sorted_attrs: Option<&str>, deprecated_symbols: Option<&str>) -> Self {
+        fn split(list: &str) -> impl Iterator<Item = &str> {
+            list.split(',').map(str::trim).filter(|x| !x.is_empty())
+        }
+
+        Self {
+            sorted_attrs: split(sorted_attrs.unwrap_or("deps"))
+                .map(str::to_owned)
+                .collect(),
+            deprecated_symbols: split(deprecated_symbols.unwrap_or(""))
+                .map(|x| match x.split_once('=') {
+                    Some((name, replacement)) => {
+                        (name.trim().to_owned(), Some(replacement.trim().to_owned()))
+                    }
+                    None => (x.to_owned(), None),
+                })
+                .collect(),
+            shadowed_variables: false,
+        }
+    }
+}
+
+/// A replacement of a range of bytes of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Edit {
+    range: Range<usize>,
+    replacement: String,
+}
+
+#[derive(Debug)]
+struct Problem {
+    location: FileSpan,
+    short_name: String,
+    problem: String,
+    /// Edits fixing the problem, empty if it can't be fixed automatically.
+    fix: Vec<Edit>,
+}
+
+/// Labels in the same package come first, then labels in the same cell, then everything else.
+pub(crate) fn label_sort_key(label: &str) -> (u8, &str) {
+    let phase = if label.starts_with(':') {
+        0
+    } else if label.starts_with("//") {
+        1
+    } else {
+        2
+    };
+    (phase, label)
+}
+
+/// Runs all the checks over a file. Fails if the file doesn't parse.
+fn lint_file(filename: &str, content: String, config: &LintConfig) -> anyhow::Result<Vec<Problem>> {
+    let ast = AstModule::parse(filename, content.clone(), &Dialect::Extended)?;
+
+    let mut problems = Vec::new();
+    let mut unused_loads = Vec::new();
+    let shadowed = if config.shadowed_variables {
+        ast.lint_shadowed_variables()
+    } else {
+        Vec::new()
+    };
+    for lint in ast.lint(None).into_iter().chain(shadowed) {
+        if lint.short_name == "unused-load" {
+            unused_loads.push(lint.location.dupe());
+        }
+        problems.push(Problem {
+            location: lint.location,
+            short_name: lint.short_name,
+            problem: lint.problem,
+            fix: Vec::new(),
+        });
+    }
+    let load_fixes = unused_load_fixes(&ast, &content, &unused_loads);
+    for problem in &mut problems {
+        if problem.short_name == "unused-load" {
+            if let Some((_, fix)) = load_fixes.iter().find(|(l, _)| l == &problem.location) {
+                problem.fix = fix.clone();
+            }
+        }
+    }
+
+    for attr in &config.sorted_attrs {
+        for list in ast.string_list_arguments(attr) {
+            let mut sorted = list.items.clone();
+            sorted.sort_by_key(|(_, x)| label_sort_key(x));
+            if sorted
+                .iter()
+                .map(|(_, x)| x)
+                .eq(list.items.iter().map(|(_, x)| x))
+            {
+                continue;
+            }
+            let fix = list
+                .items
+                .iter()
+                .zip(&sorted)
+                .filter(|((_, x), (_, y))| x != y)
+                .map(|((span, _), (sorted_span, _))| Edit {
+                    range: span.byte_range(),
+                    replacement: sorted_span.source_span().to_owned(),
+                })
+                .collect();
+            problems.push(Problem {
+                location: list.span,
+                short_name: "unsorted-deps".to_owned(),
+                problem: format!("Items of `{}` are not sorted", attr),
+                fix,
+            });
+        }
+    }
+
+    if !config.deprecated_symbols.is_empty() {
+        for (span, name) in ast.global_references() {
+            if let Some((_, replacement)) =
+                config.deprecated_symbols.iter().find(|(x, _)| x == name)
+            {
+                let (problem, fix) = match replacement {
+                    Some(replacement) => (
+                        format!("`{}` is deprecated, use `{}` instead", name, replacement),
+                        vec![Edit {
+                            range: span.byte_range(),
+                            replacement: replacement.clone(),
+                        }],
+                    ),
+                    None => (format!("`{}` is deprecated", name), Vec::new()),
+                };
+                problems.push(Problem {
+                    location: span,
+                    short_name: "deprecated-symbol".to_owned(),
+                    problem,
+                    fix,
+                });
+            }
+        }
+    }
+
+    problems.sort_by_key(|p| p.location.byte_range().start);
+    Ok(problems)
+}
+
+/// The edits removing each unused loaded symbol, identified by where it is bound. When all the
+/// symbols of a `load()` are unused, the whole statement goes, with the same edit for each of them.
+fn unused_load_fixes(
+    ast: &AstModule,
+    source: &str,
+    unused: &[FileSpan],
+) -> Vec<(FileSpan, Vec<Edit>)> {
+    let mut res = Vec::new();
+    for load in ast.load_statements() {
+        let removed: Vec<bool> = load
+            .symbols
+            .iter()
+            .map(|s| unused.contains(&s.local_span))
+            .collect();
+        if !removed.contains(&true) {
+            continue;
+        }
+
+        let args: Vec<Range<usize>> = load.symbols.iter().map(|s| s.span.byte_range()).collect();
+        match removed.iter().rposition(|x| !x) {
+            None => {
+                let mut range = load.span.byte_range();
+                if source[range.end..].starts_with('\n') {
+                    range.end += 1;
+                }
+                let edit = Edit {
+                    range,
+                    replacement: String::new(),
+                };
+                for symbol in &load.symbols {
+                    res.push((symbol.local_span.dupe(), vec![edit.clone()]));
+                }
+            }
+            Some(last_kept) => {
+                for (i, symbol) in load.symbols.iter().enumerate() {
+                    if !removed[i] {
+                        continue;
+                    }
+                    // Remove the argument with the separator after it, or before it for the
+                    // arguments after the last one kept.
+                    let range = if i < last_kept {
+                        args[i].start..args[i + 1].start
+                    } else {
+                        args[i - 1].end..args[i].end
+                    };
+                    res.push((
+                        symbol.local_span.dupe(),
+                        vec![Edit {
+                            range,
+                            replacement: String::new(),
+                        }],
+                    ));
+                }
+            }
+        }
+    }
+    res
+}
+
+/// Applies the edits to `content`, skipping the fixes that overlap with fixes applied before them.
+/// Identical edits are only applied once. Returns the new content and which fixes were applied.
+fn apply_fixes(content: &str, fixes: &[&[Edit]]) -> (String, Vec<bool>) {
+    let mut edits: Vec<(usize, &Edit)> = fixes
+        .iter()
+        .enumerate()
+        .flat_map(|(i, fix)| fix.iter().map(move |e| (i, e)))
+        .collect();
+    edits.sort_by_key(|(_, e)| (e.range.start, e.range.end));
+    edits.dedup_by(|(_, a), (_, b)| a == b);
+
+    // A fix is applied only if none of its edits overlap with another fix.
+    let mut rejected = HashSet::new();
+    let mut end = 0;
+    let mut last_fix = None;
+    for (i, e) in &edits {
+        if e.range.start < end && last_fix != Some(*i) {
+            rejected.insert(*i);
+        } else {
+            end = end.max(e.range.end);
+            last_fix = Some(*i);
+        }
+    }
+
+    let mut res = content.to_owned();
+    for (i, e) in edits.iter().rev() {
+        if !rejected.contains(i) {
+            res.replace_range(e.range.clone(), &e.replacement);
+        }
+    }
+    let applied = (0..fixes.len())
+        .map(|i| !fixes[i].is_empty() && !rejected.contains(&i))
+        .collect();
+    (res, applied)
+}
+
+/// The build files, `.bzl` and `.bxl` files under `path`, in a stable order. If `explicit`, `path`
+/// is included even if it is a file of another kind. Symlinks found in directories are skipped, as
+/// they may lead out of the project, back into a directory being searched, or to a file that is
+/// also found elsewhere.
+pub(crate) fn collect_files(
+    project_root: &ProjectRoot,
+    cell_resolver: &CellResolver,
+    path: &ProjectRelativePath,
+    explicit: bool,
+    res: &mut Vec<ProjectRelativePathBuf>,
+) -> anyhow::Result<()> {
+    let abs = project_root.resolve(path);
+    let metadata = if explicit {
+        fs_util::metadata(&abs)?
+    } else {
+        fs_util::symlink_metadata(&abs)?
+    };
+    if metadata.file_type().is_symlink() {
+        return Ok(());
+    }
+    if !metadata.is_dir() {
+        let name = path.file_name().map_or("", |x| x.as_str());
+        let is_buildfile = cell_resolver
+            .get(cell_resolver.get_cell_path(path)?.cell())?
+            .buildfiles()
+            .iter()
+            .any(|x| x.as_str() == name);
+        if explicit || is_buildfile || name.ends_with(".bzl") || name.ends_with(".bxl") {
+            res.push(path.to_buf());
+        }
+        return Ok(());
+    }
+
+    let mut names = Vec::new();
+    for entry in fs_util::read_dir(&abs)? {
+        let entry = entry?;
+        if let Some(name) = entry.file_name().to_str() {
+            if !name.starts_with('.') && name != "buck-out" {
+                names.push(name.to_owned());
+            }
+        }
+    }
+    names.sort();
+    for name in names {
+        let child = path.join(ForwardRelativePath::new(&name)?);
+        collect_files(project_root, cell_resolver, &child, false, res)?;
+    }
+    Ok(())
+}
+
+impl StarlarkLintCommand {
+    pub async fn server_execute(
+        &self,
+        server_ctx: Box<dyn ServerCommandContextTrait>,
+        _client_ctx: ClientContext,
+    ) -> anyhow::Result<()> {
+        server_ctx
+            .with_dice_ctx(async move |server_ctx, dice_ctx| {
+                let cell_resolver = dice_ctx.get_cell_resolver().await?;
+                let current_cell = cell_resolver
+                    .get_cell_path(server_ctx.working_dir())?
+                    .cell()
+                    .clone();
+                let config = LintConfig {
+                    shadowed_variables: dice_ctx
+                        .parse_legacy_config_property(
+                            &current_cell,
+                            CONFIG_SECTION,
+                            "shadowed_variables",
+                        )
+                        .await?
+                        .unwrap_or(false),
+                    ..LintConfig::new(
+                        dice_ctx
+                            .get_legacy_config_property(
+                                &current_cell,
+                                CONFIG_SECTION,
+                                "sorted_attrs",
+                            )
+                            .await?
+                            .as_deref(),
+                        dice_ctx
+                            .get_legacy_config_property(
+                                &current_cell,
+                                CONFIG_SECTION,
+                                "deprecated_symbols",
+                            )
+                            .await?
+                            .as_deref(),
+                    )
+                };
+
+                let paths = if self.paths.is_empty() {
+                    vec![".".to_owned()]
+                } else {
+                    self.paths.clone()
+                };
+                let mut files = Vec::new();
+                for path in &paths {
+                    let path = server_ctx.working_dir().join_normalized(path.as_str())?;
+                    collect_files(
+                        server_ctx.project_root(),
+                        &cell_resolver,
+                        &path,
+                        true,
+                        &mut files,
+                    )?;
+                }
+
+                let mut stdout = server_ctx.stdout()?;
+                let mut unfixed = 0;
+                for file in files {
+                    let abs = server_ctx.project_root().resolve(&file);
+                    let content = fs_util::read_to_string(&abs)?;
+                    let problems = match lint_file(file.as_str(), content.clone(), &config) {
+                        Ok(problems) => problems,
+                        Err(e) => {
+                            writeln!(stdout, "{}: parse-error: {:#}", file, e)?;
+                            unfixed += 1;
+                            continue;
+                        }
+                    };
+                    if problems.is_empty() {
+                        continue;
+                    }
+
+                    let fixed = if self.fix {
+                        let fixes: Vec<&[Edit]> = problems.iter().map(|p| &*p.fix).collect();
+                        let (new_content, fixed) = apply_fixes(&content, &fixes);
+                        if new_content != content {
+                            fs_util::write(&abs, new_content)?;
+                        }
+                        fixed
+                    } else {
+                        vec![false; problems.len()]
+                    };
+
+                    for (problem, fixed) in problems.iter().zip(fixed) {
+                        if fixed {
+                            writeln!(
+                                stdout,
+                                "{}: {}: {} (fixed)",
+                                problem.location, problem.short_name, problem.problem
+                            )?;
+                        } else {
+                            let fixable = if problem.fix.is_empty() {
+                                ""
+                            } else {
+                                " (fixable with --fix)"
+                            };
+                            writeln!(
+                                stdout,
+                                "{}: {}: {}{}",
+                                problem.location, problem.short_name, problem.problem, fixable
+                            )?;
+                            unfixed += 1;
+                        }
+                    }
+                }
+
+                if unfixed != 0 {
+                    return Err(StarlarkLintError::ProblemsFound(unfixed).into());
+                }
+                Ok(())
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::cells::testing::CellResolverExt;
+    use buck2_core::cells::CellName;
+    use buck2_core::fs::project::ProjectRootTemp;
+
+    use super::*;
+
+    fn fix(content: &str, config: &LintConfig) -> String {
+        let problems = lint_file("BUCK", content.to_owned(), config).unwrap();
+        let fixes: Vec<&[Edit]> = problems.iter().map(|p| &*p.fix).collect();
+        apply_fixes(content, &fixes).0
+    }
+
+    #[test]
+    fn test_fix_unused_loads() {
+        let config = LintConfig::default();
+        assert_eq!(
+            "load(\":b.bzl\", \"b\")\nb()\n",
+            fix(
+                "load(\":a.bzl\", \"a\")\nload(\":b.bzl\", \"b\")\nb()\n",
+                &config
+            )
+        );
+        assert_eq!(
+            "load(\":a.bzl\", \"b\", d = \"d\")\nb(d)\n",
+            fix(
+                "load(\":a.bzl\", \"a\", \"b\", \"c\", d = \"d\", \"e\", \"f\")\nb(d)\n",
+                &config
+            )
+        );
+    }
+
+    #[test]
+    fn test_fix_unsorted_deps() {
+        let config = LintConfig::new(None, None);
+        assert_eq!(
+            "rule(deps = [\":a\", ':c', \"//b:b\", \"cell//d:d\"], srcs = [\"z\", \"y\"])\n",
+            fix(
+                "rule(deps = [\"cell//d:d\", ':c', \":a\", \"//b:b\"], srcs = [\"z\", \"y\"])\n",
+                &config
+            )
+        );
+    }
+
+    #[test]
+    fn test_deprecated_symbols() {
+        let config = LintConfig::new(Some(""), Some("old_rule=new_rule, gone"));
+        let content = "def old_rule():\n    pass\nold_rule()\nnew_rule()\n";
+        // Locally defined, so not the deprecated global.
+        assert!(lint_file("BUCK", content.to_owned(), &config)
+            .unwrap()
+            .iter()
+            .all(|p| p.short_name != "deprecated-symbol"));
+
+        let content = "old_rule(name = \"x\")\ngone()\n";
+        let problems = lint_file("BUCK", content.to_owned(), &config).unwrap();
+        assert_eq!(
+            vec![
+                "`old_rule` is deprecated, use `new_rule` instead",
+                "`gone` is deprecated"
+            ],
+            problems
+                .iter()
+                .map(|p| p.problem.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("new_rule(name = \"x\")\ngone()\n", fix(content, &config));
+    }
+
+    #[test]
+    fn test_shadowed_variables_opt_in() {
+        let content = "x = 1\ndef f(x):\n    return x\n";
+        let shadowed = |config: &LintConfig| {
+            lint_file("BUCK", content.to_owned(), config)
+                .unwrap()
+                .iter()
+                .filter(|p| p.short_name == "shadowed-variable")
+                .count()
+        };
+        assert_eq!(0, shadowed(&LintConfig::default()));
+        assert_eq!(
+            1,
+            shadowed(&LintConfig {
+                shadowed_variables: true,
+                ..LintConfig::default()
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_files_skips_symlinks() -> anyhow::Result<()> {
+        let fs = ProjectRootTemp::new()?;
+        let fs = fs.path();
+        for file in ["BUCK", "a.bzl", "README", "sub/b.bzl"] {
+            fs.write_file(ProjectRelativePath::unchecked_new(file), "", false)?;
+        }
+        fs_util::symlink(
+            ".",
+            fs.resolve(ProjectRelativePath::unchecked_new("sub/loop")),
+        )?;
+        fs_util::symlink(
+            "../a.bzl",
+            fs.resolve(ProjectRelativePath::unchecked_new("sub/link.bzl")),
+        )?;
+        let cell_resolver = CellResolver::of_names_and_paths(&[(
+            CellName::unchecked_new("root".to_owned()),
+            CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new("".to_owned())),
+        )]);
+
+        let mut files = Vec::new();
+        collect_files(
+            fs,
+            &cell_resolver,
+            ProjectRelativePath::unchecked_new(""),
+            true,
+            &mut files,
+        )?;
+        assert_eq!(
+            vec!["BUCK", "a.bzl", "sub/b.bzl"],
+            files.iter().map(|f| f.as_str()).collect::<Vec<_>>()
+        );
+
+        // Named explicitly, so followed.
+        let mut files = Vec::new();
+        collect_files(
+            fs,
+            &cell_resolver,
+            ProjectRelativePath::unchecked_new("sub/link.bzl"),
+            true,
+            &mut files,
+        )?;
+        assert_eq!(
+            vec!["sub/link.bzl"],
+            files.iter().map(|f| f.as_str()).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_fixes_skips_overlapping() {
+        let a = [Edit {
+            range: 0..3,
+            replacement: "x".to_owned(),
+        }];
+        let b = [Edit {
+            range: 2..4,
+            replacement: "y".to_owned(),
+        }];
+        let c = [Edit {
+            range: 5..6,
+            replacement: "z".to_owned(),
+        }];
+        assert_eq!(
+            ("xdezg".to_owned(), vec![true, false, true, false]),
+            apply_fixes("abcdefg", &[&a[..], &b[..], &c[..], &[]])
+        );
+    }
+}
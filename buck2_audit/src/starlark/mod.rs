@@ -9,6 +9,10 @@
 
 //! Starlark debugging.
 
+mod edit;
+mod format;
+mod heap;
+mod lint;
 mod module;
 mod package_deps;
 
@@ -16,16 +20,27 @@ use async_trait::async_trait;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use cli_proto::ClientContext;
 
+use crate::starlark::edit::StarlarkEditCommand;
+use crate::starlark::format::StarlarkFormatCommand;
+use crate::starlark::heap::StarlarkHeapCommand;
+use crate::starlark::lint::StarlarkLintCommand;
 use crate::starlark::module::StarlarkModuleCommand;
 use crate::starlark::package_deps::StarlarkPackageDepsCommand;
 use crate::AuditCommandCommonOptions;
 use crate::AuditSubcommand;
 
 #[derive(Debug, clap::Subcommand, serde::Serialize, serde::Deserialize)]
-#[clap(name = "starlark", about = "Debug Starlark interpreter")]
+#[clap(
+    name = "starlark",
+    about = "Debug, lint, format, edit and profile Starlark code"
+)]
 pub enum StarlarkCommand {
     Module(StarlarkModuleCommand),
     PackageDeps(StarlarkPackageDepsCommand),
+    Lint(StarlarkLintCommand),
+    Format(StarlarkFormatCommand),
+    Edit(StarlarkEditCommand),
+    Heap(StarlarkHeapCommand),
 }
 
 #[async_trait]
@@ -38,6 +53,10 @@ impl AuditSubcommand for StarlarkCommand {
         match self {
             StarlarkCommand::Module(cmd) => cmd.server_execute(server_ctx, client_ctx).await,
             StarlarkCommand::PackageDeps(cmd) => cmd.server_execute(server_ctx, client_ctx).await,
+            StarlarkCommand::Lint(cmd) => cmd.server_execute(server_ctx, client_ctx).await,
+            StarlarkCommand::Format(cmd) => cmd.server_execute(server_ctx, client_ctx).await,
+            StarlarkCommand::Edit(cmd) => cmd.server_execute(server_ctx, client_ctx).await,
+            StarlarkCommand::Heap(cmd) => cmd.server_execute(server_ctx, client_ctx).await,
         }
     }
 
@@ -45,6 +64,10 @@ impl AuditSubcommand for StarlarkCommand {
         match self {
             StarlarkCommand::Module(cmd) => &cmd.common_opts,
             StarlarkCommand::PackageDeps(cmd) => &cmd.common_opts,
+            StarlarkCommand::Lint(cmd) => &cmd.common_opts,
+            StarlarkCommand::Format(cmd) => &cmd.common_opts,
+            StarlarkCommand::Edit(cmd) => &cmd.common_opts,
+            StarlarkCommand::Heap(cmd) => &cmd.common_opts,
         }
     }
 }
@@ -16,6 +16,8 @@ use dice::DiceComputations;
 
 use crate::actions::artifact::build_artifact::BuildArtifact;
 use crate::actions::artifact::Artifact;
+use crate::actions::calculation::ActionCalculation;
+use crate::actions::execute::error::ActionProvenance;
 use crate::calculation::Calculation;
 
 #[async_trait]
@@ -49,12 +51,13 @@ impl ArtifactMaterializer for DiceComputations {
         let materializer = self.per_transaction_data().get_materializer();
         let artifact_fs = self.get_artifact_fs().await?;
         let path = artifact_fs.resolve_build(artifact.get_path());
+        let error_path = path.clone();
 
         let start_event = buck2_data::MaterializeRequestedArtifactStart {
             artifact: Some(artifact.as_proto()),
         };
 
-        span_async(start_event, async move {
+        let result = span_async(start_event, async move {
             let result: anyhow::Result<_> = try {
                 if required {
                     materializer.ensure_materialized(vec![path]).await?;
@@ -70,6 +73,23 @@ impl ArtifactMaterializer for DiceComputations {
                 },
             )
         })
-        .await
+        .await;
+
+        match result {
+            Ok(()) => Ok(()),
+            // Materializer errors (e.g. failing to download from RE) don't say what the
+            // artifact belongs to.
+            Err(e) => {
+                let context = match self.get_action(artifact.key()).await {
+                    Ok(action) => format!(
+                        "Error materializing `{}`, an output of {}",
+                        error_path,
+                        ActionProvenance::new(&action)
+                    ),
+                    Err(_) => format!("Error materializing `{}`", error_path),
+                };
+                Err(e.context(context))
+            }
+        }
     }
 }
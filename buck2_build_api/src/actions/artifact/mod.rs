@@ -465,6 +465,13 @@ pub mod testing {
             path: ForwardRelativePathBuf,
             id: DeferredId,
         ) -> BuildArtifact;
+
+        /// An artifact owned by `owner`, which need not be a target, e.g. an anon target.
+        fn testing_new_with_owner(
+            owner: BaseDeferredKey,
+            path: ForwardRelativePathBuf,
+            id: DeferredId,
+        ) -> BuildArtifact;
     }
 
     impl BuildArtifactTestingExt for BuildArtifact {
@@ -472,13 +479,18 @@ pub mod testing {
             target: ConfiguredTargetLabel,
             path: ForwardRelativePathBuf,
             id: DeferredId,
+        ) -> BuildArtifact {
+            Self::testing_new_with_owner(BaseDeferredKey::TargetLabel(target), path, id)
+        }
+
+        fn testing_new_with_owner(
+            owner: BaseDeferredKey,
+            path: ForwardRelativePathBuf,
+            id: DeferredId,
         ) -> BuildArtifact {
             BuildArtifact::new(
-                BuckOutPath::new(BaseDeferredKey::TargetLabel(target.dupe()), path),
-                ActionKey::new(DeferredData::testing_new(DeferredKey::Base(
-                    BaseDeferredKey::TargetLabel(target),
-                    id,
-                ))),
+                BuckOutPath::new(owner.dupe(), path),
+                ActionKey::new(DeferredData::testing_new(DeferredKey::Base(owner, id))),
                 OutputType::File,
             )
         }
@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::future::Future;
@@ -14,6 +15,8 @@ use std::hash::Hash;
 use std::sync::Arc;
 use std::time::Duration;
 
+use buck2_data::ActionCategoryStats;
+use buck2_data::ActionCategoryStatsSnapshot;
 use buck2_data::BuildGraphExecutionInfo;
 use buck2_data::CriticalPathEntry;
 use buck2_data::ToProtoMessage;
@@ -31,6 +34,11 @@ use tokio_stream::wrappers::UnboundedReceiverStream;
 use tokio_stream::StreamExt;
 
 use crate::actions::key::ActionKey;
+use crate::actions::signal_export::ActionFeatures;
+use crate::actions::signal_export::ActionName;
+use crate::actions::signal_export::BuildSignalRecord;
+use crate::actions::signal_export::BuildSignalSink;
+use crate::actions::signal_export::SignalExporter;
 use crate::actions::RegisteredAction;
 use crate::artifact_groups::ArtifactGroup;
 use crate::artifact_groups::TransitiveSetProjectionKey;
@@ -38,6 +46,13 @@ use crate::artifact_groups::TransitiveSetProjectionKey;
 pub struct ActionExecutionSignal {
     pub action: Arc<RegisteredAction>,
     pub duration: Duration,
+    /// Only computed when features are exported, see `BuildSignalSender::exports_features`.
+    pub input_size: Option<u64>,
+    pub output_size: u64,
+    pub execution_kind: buck2_data::ActionExecutionKind,
+    /// Command attempts that did not succeed before the one that did.
+    pub retries: u64,
+    pub did_cache_upload: bool,
 }
 
 pub struct TransitiveSetComputationSignal {
@@ -71,12 +86,18 @@ pub enum BuildSignal {
 #[derive(Clone, Dupe)]
 pub struct BuildSignalSender {
     sender: Arc<UnboundedSender<BuildSignal>>,
+    exports_features: bool,
 }
 
 impl BuildSignalSender {
     pub fn signal(&self, signal: impl Into<BuildSignal>) {
         let _ignore_error = self.sender.send(signal.into());
     }
+
+    /// Whether the features of executed actions are exported, and so should be computed.
+    pub fn exports_features(&self) -> bool {
+        self.exports_features
+    }
 }
 
 #[derive(Clone, Dupe)]
@@ -94,9 +115,103 @@ enum NodeKey {
     TransitiveSetProjection(TransitiveSetProjectionKey),
 }
 
+#[derive(Default)]
+struct CategoryStats {
+    action_count: u64,
+    total_output_size: u64,
+    max_output_size: u64,
+    total_wall_time: Duration,
+    cache_hits: u64,
+    retries: u64,
+    remote_output_size: u64,
+    bytes_uploaded: u64,
+    max_wall_time: Duration,
+    /// A sample of at most `MAX_WALL_TIMES` wall times, for percentiles. Kept sorted lazily, see
+    /// `percentile`.
+    wall_times: Vec<Duration>,
+    /// Only one in `2^wall_times_shift` wall times is sampled, which is increased whenever
+    /// `wall_times` fills up.
+    wall_times_shift: u32,
+}
+
+/// The most wall times sampled per category.
+const MAX_WALL_TIMES: usize = 1024;
+
+impl CategoryStats {
+    fn add(&mut self, execution: &ActionExecutionSignal) {
+        self.action_count += 1;
+        self.total_output_size += execution.output_size;
+        self.max_output_size = std::cmp::max(self.max_output_size, execution.output_size);
+        self.total_wall_time += execution.duration;
+        self.retries += execution.retries;
+        match execution.execution_kind {
+            buck2_data::ActionExecutionKind::ActionCache => {
+                self.cache_hits += 1;
+                self.remote_output_size += execution.output_size;
+            }
+            buck2_data::ActionExecutionKind::Remote => {
+                self.remote_output_size += execution.output_size;
+            }
+            _ => {}
+        }
+        if execution.did_cache_upload {
+            self.bytes_uploaded += execution.output_size;
+        }
+        self.max_wall_time = std::cmp::max(self.max_wall_time, execution.duration);
+        self.sample_wall_time(execution.duration);
+    }
+
+    fn sample_wall_time(&mut self, wall_time: Duration) {
+        if self.action_count % (1 << self.wall_times_shift) != 0 {
+            return;
+        }
+        if self.wall_times.len() == MAX_WALL_TIMES {
+            // Halve the sample, keeping its distribution, and sample half as often from now on.
+            self.wall_times.sort_unstable();
+            self.wall_times = self.wall_times.iter().copied().skip(1).step_by(2).collect();
+            self.wall_times_shift += 1;
+        }
+        self.wall_times.push(wall_time);
+    }
+
+    fn to_proto(&mut self, category: &str) -> anyhow::Result<ActionCategoryStats> {
+        self.wall_times.sort_unstable();
+        Ok(ActionCategoryStats {
+            category: category.to_owned(),
+            action_count: self.action_count,
+            total_output_size: self.total_output_size,
+            max_output_size: self.max_output_size,
+            total_wall_time: Some(self.total_wall_time.try_into()?),
+            cache_hits: self.cache_hits,
+            retries: self.retries,
+            remote_output_size: self.remote_output_size,
+            bytes_uploaded: self.bytes_uploaded,
+            p50_wall_time: Some(percentile(&self.wall_times, 50).try_into()?),
+            p90_wall_time: Some(percentile(&self.wall_times, 90).try_into()?),
+            p99_wall_time: Some(percentile(&self.wall_times, 99).try_into()?),
+            max_wall_time: Some(self.max_wall_time.try_into()?),
+        })
+    }
+}
+
+/// Nearest-rank percentile of `sorted`, which must be sorted.
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (p * sorted.len() + 99) / 100;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// How often we send the statistics of the actions that finished so far, if any did.
+const CATEGORY_STATS_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+
 pub struct BuildSignalReceiver {
     receiver: UnboundedReceiverStream<BuildSignal>,
     predecessors: HashMap<NodeKey, CriticalPathNode<NodeKey, Arc<RegisteredAction>>>,
+    category_stats: BTreeMap<String, CategoryStats>,
+    /// Where to export the features of executed actions, if anywhere.
+    exporter: Option<SignalExporter>,
 }
 
 fn extract_critical_path<TKey: Hash + Eq, TValue>(
@@ -125,28 +240,61 @@ fn extract_critical_path<TKey: Hash + Eq, TValue>(
     path
 }
 
+fn action_name(action: &RegisteredAction) -> ActionName {
+    ActionName {
+        owner: action.owner().to_string(),
+        category: action.category().as_str().to_owned(),
+        identifier: action.identifier().map(|i| i.to_owned()),
+    }
+}
+
 impl BuildSignalReceiver {
-    fn new(receiver: UnboundedReceiver<BuildSignal>) -> Self {
+    fn new(
+        receiver: UnboundedReceiver<BuildSignal>,
+        sink: Option<Arc<dyn BuildSignalSink>>,
+    ) -> Self {
         Self {
             receiver: UnboundedReceiverStream::new(receiver),
             predecessors: HashMap::new(),
+            category_stats: BTreeMap::new(),
+            exporter: sink.map(SignalExporter::start),
         }
     }
 
     pub async fn run_and_log(&mut self) -> anyhow::Result<()> {
-        while let Some(event) = self.receiver.next().await {
-            match event {
-                BuildSignal::ActionExecution(execution) => self.process_action(execution)?,
-                BuildSignal::TransitiveSetComputation(tset) => {
-                    self.process_transitive_set_computation(tset)?
+        let mut needs_snapshot = false;
+        let mut interval = tokio::time::interval(CATEGORY_STATS_SNAPSHOT_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                event = self.receiver.next() => {
+                    match event {
+                        Some(BuildSignal::ActionExecution(execution)) => {
+                            needs_snapshot = true;
+                            self.process_action(execution)?
+                        }
+                        Some(BuildSignal::TransitiveSetComputation(tset)) => {
+                            self.process_transitive_set_computation(tset)?
+                        }
+                        Some(BuildSignal::ActionRedirection(redirection)) => {
+                            self.process_action_redirection(redirection)?
+                        }
+                        Some(BuildSignal::BuildFinished) | None => break,
+                    }
                 }
-                BuildSignal::ActionRedirection(redirection) => {
-                    self.process_action_redirection(redirection)?
+                _ = interval.tick() => {
+                    if needs_snapshot {
+                        needs_snapshot = false;
+                        instant_event(ActionCategoryStatsSnapshot {
+                            category_stats: self.category_stats_proto()?,
+                        });
+                    }
                 }
-                BuildSignal::BuildFinished => break,
             }
         }
 
+        self.export_critical_path().await;
+
         instant_event(BuildGraphExecutionInfo {
             critical_path: self.extract_critical_path().into_try_map(
                 |(name, duration, action)| {
@@ -158,11 +306,50 @@ impl BuildSignalReceiver {
                 },
             )?,
             metadata: metadata::collect(),
+            category_stats: self.category_stats_proto()?,
         });
         Ok(())
     }
 
+    /// Export which actions were on the critical path of this build, and wait for everything to
+    /// be exported. Failing to do so doesn't fail the build.
+    async fn export_critical_path(&mut self) {
+        let mut exporter = match self.exporter.take() {
+            Some(exporter) => exporter,
+            None => return,
+        };
+
+        let actions = self
+            .extract_critical_path()
+            .into_map(|(_name, _duration, action)| action_name(&action));
+        exporter.export(BuildSignalRecord::CriticalPath { actions });
+        exporter.finish().await;
+    }
+
+    fn category_stats_proto(&mut self) -> anyhow::Result<Vec<ActionCategoryStats>> {
+        self.category_stats
+            .iter_mut()
+            .map(|(category, stats)| stats.to_proto(category))
+            .collect()
+    }
+
     fn process_action(&mut self, execution: ActionExecutionSignal) -> Result<(), anyhow::Error> {
+        let stats = self
+            .category_stats
+            .entry(execution.action.category().as_str().to_owned())
+            .or_default();
+        stats.add(&execution);
+
+        if let Some(exporter) = &mut self.exporter {
+            exporter.export(BuildSignalRecord::Action(ActionFeatures {
+                name: action_name(&execution.action),
+                input_size: execution.input_size.unwrap_or_default(),
+                output_size: execution.output_size,
+                duration: execution.duration,
+                execution_kind: format!("{:?}", execution.execution_kind),
+            }));
+        }
+
         // Identify most costly predecessor.
         let inputs = execution.action.inputs()?;
 
@@ -291,13 +478,16 @@ impl HasBuildSignals for UserComputationData {
     }
 }
 
-fn create_matched_pair() -> (BuildSignalSender, BuildSignalReceiver) {
+fn create_matched_pair(
+    sink: Option<Arc<dyn BuildSignalSink>>,
+) -> (BuildSignalSender, BuildSignalReceiver) {
     let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
     (
         BuildSignalSender {
             sender: Arc::new(sender),
+            exports_features: sink.is_some(),
         },
-        BuildSignalReceiver::new(receiver),
+        BuildSignalReceiver::new(receiver, sink),
     )
 }
 
@@ -312,12 +502,19 @@ fn create_matched_pair() -> (BuildSignalSender, BuildSignalReceiver) {
 /// This function arranges for a background task to be spawned that drives the receiver, while invoking the called
 /// function with a live BuildSignalSender that can be used to send events to the listening receiver. Upon return of
 /// `scope`, the sender terminates the receiver by sending a `BuildFinished` signal and joins the receiver task.
-pub async fn scope<F, R, Fut>(events: EventDispatcher, func: F) -> anyhow::Result<R>
+///
+/// If a `sink` is given, the features of each action executed while `func` runs are exported to it
+/// as the action completes, followed by the critical path once `func` returns.
+pub async fn scope<F, R, Fut>(
+    events: EventDispatcher,
+    sink: Option<Arc<dyn BuildSignalSink>>,
+    func: F,
+) -> anyhow::Result<R>
 where
     F: FnOnce(BuildSignalSender) -> Fut,
     Fut: Future<Output = anyhow::Result<R>>,
 {
-    let (sender, mut receiver) = create_matched_pair();
+    let (sender, mut receiver) = create_matched_pair(sink);
     let receiver_task_handle = tokio::spawn(with_dispatcher_async(events.dupe(), async move {
         receiver.run_and_log().await
     }));
@@ -329,7 +526,30 @@ where
 
 #[cfg(test)]
 mod tests {
+    use buck2_common::executor_config::CommandExecutorConfig;
+    use buck2_core::category::Category;
+    use buck2_core::cells::paths::CellRelativePath;
+    use buck2_core::cells::CellName;
+    use buck2_core::configuration::Configuration;
+    use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+    use buck2_core::package::Package;
+    use buck2_core::target::testing::ConfiguredTargetLabelExt;
+    use buck2_core::target::ConfiguredTargetLabel;
+    use buck2_core::target::TargetName;
+    use buck2_execute::base_deferred_key::BaseDeferredKey;
+    use indexmap::indexset;
+    use parking_lot::Mutex;
+
     use super::*;
+    use crate::actions::artifact::build_artifact::BuildArtifact;
+    use crate::actions::artifact::testing::BuildArtifactTestingExt;
+    use crate::actions::artifact::Artifact;
+    use crate::actions::testings::SimpleAction;
+    use crate::deferred::types::testing::DeferredDataExt;
+    use crate::deferred::types::testing::DeferredIdExt;
+    use crate::deferred::types::DeferredData;
+    use crate::deferred::types::DeferredId;
+    use crate::deferred::types::DeferredKey;
 
     type CriticalPathMap = HashMap<i32, CriticalPathNode<i32, i32>>;
 
@@ -386,4 +606,140 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(&[], 50), Duration::ZERO);
+
+        let sorted = (1..=10).map(Duration::from_secs).collect::<Vec<_>>();
+        assert_eq!(percentile(&sorted, 0), Duration::from_secs(1));
+        assert_eq!(percentile(&sorted, 50), Duration::from_secs(5));
+        assert_eq!(percentile(&sorted, 90), Duration::from_secs(9));
+        assert_eq!(percentile(&sorted, 99), Duration::from_secs(10));
+        assert_eq!(percentile(&sorted, 100), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_category_stats_sample_wall_times() -> anyhow::Result<()> {
+        let a = action("a", &[], 0);
+        let mut stats = CategoryStats::default();
+        for millis in 1..=10_000 {
+            stats.add(&execution(&a, Duration::from_millis(millis)));
+            assert!(stats.wall_times.len() <= MAX_WALL_TIMES);
+        }
+
+        let stats = stats.to_proto("testing")?;
+        assert_eq!(stats.action_count, 10_000);
+        assert_eq!(
+            stats.max_wall_time,
+            Some(Duration::from_secs(10).try_into()?)
+        );
+        let p50 = Duration::try_from(stats.p50_wall_time.unwrap())?;
+        assert!(
+            p50 > Duration::from_millis(4_800) && p50 < Duration::from_millis(5_200),
+            "{:?}",
+            p50
+        );
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct CollectingSink {
+        records: Mutex<Vec<BuildSignalRecord>>,
+    }
+
+    impl BuildSignalSink for CollectingSink {
+        fn export(&self, records: &[BuildSignalRecord]) -> anyhow::Result<()> {
+            self.records.lock().extend(records.iter().cloned());
+            Ok(())
+        }
+    }
+
+    fn action(name: &str, inputs: &[&BuildArtifact], id: u32) -> Arc<RegisteredAction> {
+        let label = ConfiguredTargetLabel::testing_new(
+            Package::new(
+                &CellName::unchecked_new("cell".into()),
+                CellRelativePath::unchecked_new("pkg"),
+            ),
+            TargetName::unchecked_new(name),
+            Configuration::testing_new(),
+        );
+        let output = BuildArtifact::testing_new(
+            label.dupe(),
+            ForwardRelativePathBuf::unchecked_new(name.to_owned()),
+            DeferredId::testing_new(id),
+        );
+        Arc::new(RegisteredAction::new(
+            ActionKey::new(DeferredData::testing_new(DeferredKey::Base(
+                BaseDeferredKey::TargetLabel(label),
+                DeferredId::testing_new(id),
+            ))),
+            box SimpleAction::new(
+                inputs
+                    .iter()
+                    .map(|i| ArtifactGroup::Artifact(Artifact::from((*i).dupe())))
+                    .collect(),
+                indexset![output],
+                vec![],
+                Category::try_from("testing").unwrap(),
+                Some(name.to_owned()),
+            ),
+            CommandExecutorConfig::testing_local(),
+        ))
+    }
+
+    fn execution(action: &Arc<RegisteredAction>, duration: Duration) -> ActionExecutionSignal {
+        ActionExecutionSignal {
+            action: action.dupe(),
+            duration,
+            input_size: Some(1),
+            output_size: 2,
+            execution_kind: buck2_data::ActionExecutionKind::Local,
+            retries: 0,
+            did_cache_upload: false,
+        }
+    }
+
+    #[test]
+    fn no_features_without_sink() {
+        let (sender, _receiver) = create_matched_pair(None);
+        assert!(!sender.exports_features());
+    }
+
+    #[tokio::test]
+    async fn exports_each_action_then_critical_path() -> anyhow::Result<()> {
+        let a = action("a", &[], 0);
+        let a_output = a.outputs()?.iter().next().unwrap().dupe();
+        let b = action("b", &[&a_output], 1);
+
+        let sink = Arc::new(CollectingSink::default());
+        let (sender, mut receiver) =
+            create_matched_pair(Some(sink.dupe() as Arc<dyn BuildSignalSink>));
+        assert!(sender.exports_features());
+        sender.signal(execution(&a, Duration::from_secs(1)));
+        sender.signal(execution(&b, Duration::from_secs(2)));
+        sender.signal(BuildSignal::BuildFinished);
+        with_dispatcher_async(EventDispatcher::null(), receiver.run_and_log()).await?;
+
+        let features = |action: &Arc<RegisteredAction>, duration| {
+            BuildSignalRecord::Action(ActionFeatures {
+                name: action_name(action),
+                input_size: 1,
+                output_size: 2,
+                duration,
+                execution_kind: "Local".to_owned(),
+            })
+        };
+        assert_eq!(
+            vec![
+                features(&a, Duration::from_secs(1)),
+                features(&b, Duration::from_secs(2)),
+                BuildSignalRecord::CriticalPath {
+                    actions: vec![action_name(&a), action_name(&b)],
+                },
+            ],
+            *sink.records.lock()
+        );
+        Ok(())
+    }
 }
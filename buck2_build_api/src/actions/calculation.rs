@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use allocative::Allocative;
@@ -16,13 +17,19 @@ use buck2_common::result::SharedResult;
 use buck2_common::result::ToSharedResultExt;
 use buck2_data::ToProtoMessage;
 use buck2_events::dispatch::span_async;
+use buck2_events::warnings::WithWarnings;
+use buck2_execute::artifact_value::ArtifactValue;
 use buck2_execute::execute::kind::CommandExecutionKind;
 use buck2_execute::execute::result::CommandExecutionReport;
 use buck2_execute::execute::result::CommandExecutionStatus;
 use buck2_execute::output_size::OutputSize;
+use buck2_execute::path::buck_out_path::BuckOutPath;
+use buck2_interpreter::dice::HasEvents;
 use derive_more::Display;
 use dice::DiceComputations;
+use dice::DiceProjectionComputations;
 use dice::Key;
+use dice::ProjectionKey;
 use futures::future;
 use futures::stream::FuturesUnordered;
 use gazebo::prelude::*;
@@ -32,11 +39,16 @@ use crate::actions::artifact::build_artifact::BuildArtifact;
 use crate::actions::build_listener::ActionExecutionSignal;
 use crate::actions::build_listener::ActionRedirectionSignal;
 use crate::actions::build_listener::HasBuildSignals;
+use crate::actions::duration_history::ActionDurationHistory;
+use crate::actions::duration_history::HasActionDurationHistory;
 use crate::actions::execute::action_executor::ActionOutputs;
 use crate::actions::execute::action_executor::HasActionExecutor;
+use crate::actions::execute::error::with_anon_target_parent;
+use crate::actions::execute::error::ActionProvenance;
 use crate::actions::key::ActionKey;
 use crate::actions::RegisteredAction;
 use crate::artifact_groups::calculation::ArtifactGroupCalculation;
+use crate::artifact_groups::ArtifactGroup;
 use crate::deferred::calculation::DeferredCalculation;
 use crate::keep_going;
 
@@ -50,6 +62,11 @@ pub(crate) trait ActionCalculation {
 
     /// Builds and materializes the given 'BuildArtifact'
     async fn build_artifact(&self, artifact: &BuildArtifact) -> SharedResult<ActionOutputs>;
+
+    /// Builds the given 'BuildArtifact' and returns its value. Unlike `build_artifact`, the caller
+    /// isn't invalidated when the action re-runs and produces identical contents for this
+    /// artifact, even if its other outputs changed.
+    async fn build_artifact_value(&self, artifact: &BuildArtifact) -> SharedResult<ArtifactValue>;
 }
 
 async fn build_action_impl(ctx: &DiceComputations, key: &ActionKey) -> SharedResult<ActionOutputs> {
@@ -92,8 +109,16 @@ async fn build_action_no_redirect(
             .iter()
             .map(|a| {
                 let a = a.dupe();
+                let action = &action;
                 async move {
-                    let val = ctx.ensure_artifact_group(&a).await?;
+                    let val = ctx.ensure_artifact_group(&a).await.map_err(|e| {
+                        with_anon_target_parent(
+                            e,
+                            &a,
+                            action.owner(),
+                            ActionProvenance::new(action),
+                        )
+                    })?;
                     SharedResult::Ok((a, val))
                 }
             })
@@ -101,6 +126,19 @@ async fn build_action_no_redirect(
     ))
     .await?;
 
+    let input_size = match ctx.per_transaction_data().get_build_signals() {
+        Some(signals) if signals.exports_features() => Some(
+            materialized_inputs
+                .values()
+                .flat_map(|values| values.iter())
+                .map(|(_artifact, value)| value.calc_output_count_and_bytes().bytes)
+                .sum(),
+        ),
+        _ => None,
+    };
+
+    let duration_history = ctx.per_transaction_data().get_action_duration_history();
+
     let start_event = buck2_data::ActionExecutionStart {
         key: Some(action.key().as_proto()),
         kind: action.kind().into(),
@@ -108,12 +146,26 @@ async fn build_action_no_redirect(
             category: action.category().as_str().to_owned(),
             identifier: action.identifier().unwrap_or("").to_owned(),
         }),
+        expected_duration: duration_history
+            .as_ref()
+            .and_then(|history| history.expected_duration(&action))
+            .and_then(|d| d.try_into().ok()),
+        expected_critical_path: duration_history
+            .as_ref()
+            .map(|history| history.critical_path(&action))
+            .filter(|d| !d.is_zero())
+            .and_then(|d| d.try_into().ok()),
     };
 
     let executor = ctx
         .get_action_executor(action.execution_config())
         .await
-        .context(format!("for action `{}`", action))?;
+        .with_context(|| {
+            format!(
+                "Error getting the executor for {}",
+                ActionProvenance::new(&action)
+            )
+        })?;
 
     // this can be RE
     span_async(start_event, async move {
@@ -129,6 +181,17 @@ async fn build_action_no_redirect(
         )
         .await;
 
+        let action_resource_usage = ctx
+            .per_transaction_data()
+            .get_dispatcher()
+            .action_resource_usage();
+        for usage in commands
+            .iter()
+            .filter_map(|c| c.details.as_ref()?.resource_usage.as_ref())
+        {
+            action_resource_usage.add(usage);
+        }
+
         let action_result;
         let execution_kind;
         let wall_time;
@@ -140,6 +203,7 @@ async fn build_action_no_redirect(
         let mut allows_cache_upload = None;
         let mut did_cache_upload = None;
         let mut eligible_for_full_hybrid = None;
+        let mut input_bytes_uploaded = None;
 
         #[allow(unused_mut)] // Not set in all configurations
         let mut buck2_revision = None;
@@ -148,14 +212,46 @@ async fn build_action_no_redirect(
 
         match execute_result {
             Ok((outputs, meta)) => {
+                output_size = outputs.calc_output_count_and_bytes().bytes;
+
                 if let Some(signals) = ctx.per_transaction_data().get_build_signals() {
                     signals.signal(ActionExecutionSignal {
                         action: action.dupe(),
                         duration: meta.timing.wall_time,
+                        input_size,
+                        output_size,
+                        execution_kind: meta.execution_kind.as_enum(),
+                        retries: command_reports
+                            .iter()
+                            .filter(|r| {
+                                matches!(
+                                    r.status,
+                                    CommandExecutionStatus::Failure { .. }
+                                        | CommandExecutionStatus::Error { .. }
+                                        | CommandExecutionStatus::TimedOut { .. }
+                                )
+                            })
+                            .count() as u64,
+                        did_cache_upload: meta
+                            .execution_kind
+                            .command()
+                            .map_or(false, |c| c.did_cache_upload),
                     });
                 }
 
-                output_size = outputs.calc_output_count_and_bytes().bytes;
+                // Only record actions that actually ran, cache hits don't say how long running
+                // them takes.
+                if let Some(history) = &duration_history {
+                    if matches!(
+                        meta.execution_kind.as_enum(),
+                        buck2_data::ActionExecutionKind::Local
+                            | buck2_data::ActionExecutionKind::Remote
+                    ) {
+                        history.record(&action, meta.timing.wall_time);
+                    }
+                    record_critical_path(ctx, history, &action).await;
+                }
+
                 action_result = Ok(outputs);
                 execution_kind = Some(meta.execution_kind.as_enum());
                 wall_time = Some(meta.timing.wall_time);
@@ -167,6 +263,7 @@ async fn build_action_no_redirect(
                     allows_cache_upload = Some(command.allows_cache_upload);
                     did_cache_upload = Some(command.did_cache_upload);
                     eligible_for_full_hybrid = Some(command.eligible_for_full_hybrid);
+                    input_bytes_uploaded = Some(command.input_bytes_uploaded);
                 }
             }
             Err(e) => {
@@ -175,7 +272,11 @@ async fn build_action_no_redirect(
                 // We can then unconditionally print the error message for compute(),
                 // including ones near the beginning of this method, and also not
                 // duplicate any error messages.
-                action_result = Err(anyhow::anyhow!("Failed to build '{}'", action.owner()).into());
+                action_result = Err(anyhow::anyhow!(
+                    "Failed to build {}",
+                    ActionProvenance::new(&action)
+                )
+                .into());
                 // TODO (torozco): Remove (see protobuf file)?
                 execution_kind = command_reports
                     .last()
@@ -231,6 +332,7 @@ async fn build_action_no_redirect(
                 allows_cache_upload: allows_cache_upload.unwrap_or_default(),
                 did_cache_upload: did_cache_upload.unwrap_or_default(),
                 eligible_for_full_hybrid,
+                input_bytes_uploaded: input_bytes_uploaded.unwrap_or_default(),
                 buck2_revision,
                 buck2_build_time,
             },
@@ -239,6 +341,42 @@ async fn build_action_no_redirect(
     .await
 }
 
+/// Extends the critical path of the actions producing the inputs of `action` with its own, so that
+/// they get ahead of the queue in the next builds.
+async fn record_critical_path(
+    ctx: &DiceComputations,
+    history: &ActionDurationHistory,
+    action: &RegisteredAction,
+) {
+    let priority = history.priority(action);
+    let inputs = match action.inputs() {
+        Ok(inputs) => inputs,
+        Err(_) => return,
+    };
+    // Transitive sets were already expanded to run the action, so this doesn't compute anything
+    // new. Their artifacts are often shared with other inputs.
+    let mut producers = HashSet::new();
+    for input in inputs.iter() {
+        match input {
+            ArtifactGroup::Artifact(artifact) => producers.extend(artifact.action_key().cloned()),
+            ArtifactGroup::TransitiveSetProjection(..) => {
+                if let Ok(values) = ctx.ensure_artifact_group(input).await {
+                    producers.extend(
+                        values
+                            .iter()
+                            .filter_map(|(artifact, _)| artifact.action_key().cloned()),
+                    );
+                }
+            }
+        }
+    }
+    for key in producers {
+        if let Ok(producer) = ctx.get_action(&key).await {
+            history.record_tail(&producer, priority);
+        }
+    }
+}
+
 #[async_trait]
 impl ActionCalculation for DiceComputations {
     async fn get_action(&self, action_key: &ActionKey) -> SharedResult<Arc<RegisteredAction>> {
@@ -255,39 +393,93 @@ impl ActionCalculation for DiceComputations {
         // We don't currently consume this in buck_e2e but it's good to log for debugging purposes.
         debug!("build_action {}", action_key);
 
-        #[derive(Clone, Dupe, Display, Debug, Eq, PartialEq, Hash, Allocative)]
-        #[display(fmt = "{}", _0)]
-        struct BuildKey(ActionKey);
+        self.compute(&BuildKey(action_key.dupe()))
+            .await?
+            .into_replayed(self.per_transaction_data().get_dispatcher())
+    }
 
-        #[async_trait]
-        impl Key for BuildKey {
-            type Value = SharedResult<ActionOutputs>;
+    async fn build_artifact(&self, artifact: &BuildArtifact) -> SharedResult<ActionOutputs> {
+        self.build_action(artifact.key()).await
+    }
 
-            async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
-                build_action_impl(ctx, &self.0).await
-            }
+    async fn build_artifact_value(&self, artifact: &BuildArtifact) -> SharedResult<ArtifactValue> {
+        self.compute_opaque(&BuildKey(artifact.key().dupe()))
+            .await?
+            .projection(&BuildArtifactKey(artifact.get_path().dupe()))?
+            .into_replayed(self.per_transaction_data().get_dispatcher())
+    }
+}
 
-            fn equality(x: &Self::Value, y: &Self::Value) -> bool {
-                match (x, y) {
-                    (Ok(x), Ok(y)) => x == y,
-                    _ => false,
-                }
-            }
+#[derive(Clone, Dupe, Display, Debug, Eq, PartialEq, Hash, Allocative)]
+#[display(fmt = "{}", _0)]
+struct BuildKey(ActionKey);
+
+#[async_trait]
+impl Key for BuildKey {
+    type Value = WithWarnings<SharedResult<ActionOutputs>>;
+
+    async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
+        WithWarnings::compute(build_action_impl(ctx, &self.0)).await
+    }
 
-            fn validity(x: &Self::Value) -> bool {
-                // we don't cache any kind of errors. Ideally, we could try to distinguish different
-                // error types and try to cache non-transient error types, but practically there
-                // are too many unknowns that may cause more harm than good if we cached errors.
-                // So, don't cache it for now, until someday we decide to really need to.
-                x.is_ok()
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        match (&x.value, &y.value) {
+            (Ok(x_value), Ok(y_value)) => x_value == y_value && x.same_warnings(y),
+            _ => false,
+        }
+    }
+
+    fn validity(x: &Self::Value) -> bool {
+        // we don't cache any kind of errors. Ideally, we could try to distinguish different
+        // error types and try to cache non-transient error types, but practically there
+        // are too many unknowns that may cause more harm than good if we cached errors.
+        // So, don't cache it for now, until someday we decide to really need to.
+        x.value.is_ok()
+    }
+}
+
+/// A single output of the action built by a `BuildKey`. The action re-running only invalidates
+/// the computations depending on this output if its contents changed, regardless of what
+/// happened to the other outputs of the action.
+#[derive(Clone, Dupe, Display, Debug, Eq, PartialEq, Hash, Allocative)]
+#[display(fmt = "{}", _0)]
+struct BuildArtifactKey(BuckOutPath);
+
+impl ProjectionKey for BuildArtifactKey {
+    type DeriveFromKey = BuildKey;
+    type Value = WithWarnings<SharedResult<ArtifactValue>>;
+
+    /// The warnings of the action are kept with each of its artifacts, for the commands that only
+    /// request some of them.
+    fn compute(
+        &self,
+        outputs: &WithWarnings<SharedResult<ActionOutputs>>,
+        _ctx: &DiceProjectionComputations,
+    ) -> Self::Value {
+        let value = outputs.value.as_ref().map_err(|e| e.dupe()).map(|outputs| {
+            match outputs.get(&self.0) {
+                Some(value) => value.dupe(),
+                None => panic!(
+                    "Building an artifact didn't produce it. Expected `{:?}` but only have `{:?}`",
+                    self.0, outputs
+                ),
             }
+        });
+        WithWarnings {
+            value,
+            warnings: outputs.warnings.dupe(),
         }
+    }
 
-        self.compute(&BuildKey(action_key.dupe())).await?
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        match (&x.value, &y.value) {
+            (Ok(x_value), Ok(y_value)) => x_value == y_value && x.same_warnings(y),
+            _ => false,
+        }
     }
 
-    async fn build_artifact(&self, artifact: &BuildArtifact) -> SharedResult<ActionOutputs> {
-        self.build_action(artifact.key()).await
+    fn validity(x: &Self::Value) -> bool {
+        x.value.is_ok()
     }
 }
 
@@ -348,6 +540,23 @@ async fn command_details(
         stderr = pair.stderr;
     };
 
+    let diagnostic_outputs = command
+        .diagnostic_outputs
+        .iter()
+        .map(|p| p.to_string())
+        .collect();
+
+    let scratch_dir = command.scratch_dir.as_ref().map(|p| p.to_string());
+    let scratch_dir_bytes = command.scratch_dir_bytes;
+    let resource_usage = command
+        .resource_usage
+        .map(|usage| buck2_data::CommandResourceUsage {
+            user_cpu_us: usage.user_cpu_us,
+            system_cpu_us: usage.system_cpu_us,
+            disk_read_bytes: usage.disk_read_bytes,
+            disk_write_bytes: usage.disk_write_bytes,
+        });
+
     let command = command.status.execution_kind().map(|kind| match kind {
         CommandExecutionKind::Local {
             command,
@@ -393,16 +602,25 @@ async fn command_details(
         stdout,
         stderr,
         command,
+        diagnostic_outputs,
+        scratch_dir,
+        scratch_dir_bytes,
+        resource_usage,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
     use std::path::PathBuf;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering;
     use std::sync::Arc;
     use std::sync::Mutex;
 
+    use allocative::Allocative;
     use assert_matches::assert_matches;
+    use async_trait::async_trait;
     use buck2_common::dice::cells::HasCellResolver;
     use buck2_common::dice::data::testing::SetTestingIoProvider;
     use buck2_common::dice::file_ops::keys::FileOpsValue;
@@ -413,6 +631,7 @@ mod tests {
     use buck2_common::file_ops::FileDigest;
     use buck2_common::file_ops::FileMetadata;
     use buck2_common::file_ops::TrackedFileDigest;
+    use buck2_common::result::SharedResult;
     use buck2_common::result::ToSharedResultExt;
     use buck2_core::buck_path::BuckPath;
     use buck2_core::category::Category;
@@ -441,6 +660,7 @@ mod tests {
     use buck2_execute::execute::action_digest::ActionDigest;
     use buck2_execute::execute::blocking::testing::DummyBlockingExecutor;
     use buck2_execute::execute::blocking::SetBlockingExecutor;
+    use buck2_execute::execute::command_executor::ActionExecutionTimingData;
     use buck2_execute::execute::dice_data::set_fallback_executor_config;
     use buck2_execute::execute::dice_data::HasCommandExecutor;
     use buck2_execute::execute::dice_data::SetCommandExecutor;
@@ -456,12 +676,17 @@ mod tests {
     use buck2_execute::execute::testing_dry_run::DryRunExecutor;
     use buck2_execute::materialize::materializer::SetMaterializer;
     use buck2_execute::materialize::nodisk::NoDiskMaterializer;
+    use buck2_execute::path::buck_out_path::BuckOutPath;
     use buck2_execute::re::manager::ManagedRemoteExecutionClient;
+    use derive_more::Display;
     use dice::testing::DiceBuilder;
+    use dice::DiceComputations;
     use dice::DiceTransaction;
+    use dice::Key;
     use dice::UserComputationData;
     use gazebo::prelude::*;
     use indexmap::indexset;
+    use indexmap::IndexSet;
     use maplit::btreemap;
     use maplit::hashmap;
 
@@ -470,10 +695,17 @@ mod tests {
     use crate::actions::artifact::Artifact;
     use crate::actions::calculation::command_details;
     use crate::actions::calculation::ActionCalculation;
+    use crate::actions::calculation::BuildKey;
+    use crate::actions::execute::action_executor::ActionExecutionKind;
+    use crate::actions::execute::action_executor::ActionExecutionMetadata;
+    use crate::actions::execute::action_executor::ActionOutputs;
     use crate::actions::impls::run::knobs::RunActionKnobs;
     use crate::actions::testings::SimpleAction;
     use crate::actions::Action;
+    use crate::actions::ActionExecutable;
+    use crate::actions::ActionExecutionCtx;
     use crate::actions::ArtifactFs;
+    use crate::actions::PristineActionExecutable;
     use crate::actions::RegisteredAction;
     use crate::artifact_groups::calculation::ArtifactGroupCalculation;
     use crate::artifact_groups::ArtifactGroup;
@@ -715,6 +947,218 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_build_artifact_value() -> anyhow::Result<()> {
+        let temp_fs = ProjectRootTemp::new()?;
+        let build_artifact = create_test_build_artifact("cell", "pkg", "foo");
+        let deferred_resolve = DeferredResolve(build_artifact.key().deferred_key().dupe());
+        let registered_action = registered_action(
+            build_artifact.dupe(),
+            box SimpleAction::new(
+                indexset![],
+                indexset![build_artifact.dupe()],
+                vec!["value".to_owned(), "cmd".to_owned()],
+                Category::try_from("fake_action").unwrap(),
+                None,
+            ),
+        );
+
+        let dry_run_tracker = Arc::new(Mutex::new(vec![]));
+        let dice_computations = make_default_dice_state(dry_run_tracker.dupe(), &temp_fs, {
+            let registered_action = registered_action.dupe();
+            vec![box move |builder| {
+                mock_deferred_resolution_calculation(builder, deferred_resolve, registered_action)
+            }]
+        })?;
+
+        let (value, outputs) = with_dispatcher_async(EventDispatcher::null(), async {
+            let value = dice_computations
+                .build_artifact_value(&build_artifact)
+                .await?;
+            let outputs = dice_computations.build_artifact(&build_artifact).await?;
+            anyhow::Ok((value, outputs))
+        })
+        .await?;
+
+        assert_eq!(Some(&value), outputs.get(build_artifact.get_path()));
+        // The action only ran once.
+        assert_eq!(dry_run_tracker.lock().unwrap().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_build_artifact_value_early_cutoff() -> anyhow::Result<()> {
+        /// An action with two outputs, only the first of which changes when `version` does.
+        #[derive(Debug, Allocative)]
+        struct VersionedAction {
+            outputs: IndexSet<BuildArtifact>,
+            #[allocative(skip)]
+            version: Arc<AtomicU64>,
+            category: Category,
+        }
+
+        #[async_trait]
+        impl Action for VersionedAction {
+            fn kind(&self) -> buck2_data::ActionKind {
+                buck2_data::ActionKind::NotSet
+            }
+
+            fn inputs(&self) -> anyhow::Result<Cow<'_, IndexSet<ArtifactGroup>>> {
+                Ok(Cow::Owned(IndexSet::new()))
+            }
+
+            fn outputs(&self) -> anyhow::Result<Cow<'_, IndexSet<BuildArtifact>>> {
+                Ok(Cow::Borrowed(&self.outputs))
+            }
+
+            fn as_executable(&self) -> ActionExecutable<'_> {
+                ActionExecutable::Pristine(self)
+            }
+
+            fn category(&self) -> &Category {
+                &self.category
+            }
+        }
+
+        #[async_trait]
+        impl PristineActionExecutable for VersionedAction {
+            async fn execute(
+                &self,
+                _ctx: &mut dyn ActionExecutionCtx,
+            ) -> anyhow::Result<(ActionOutputs, ActionExecutionMetadata)> {
+                let version = self.version.load(Ordering::SeqCst).to_string();
+                let mut outputs = self.outputs.iter();
+                let changing = ArtifactValue::file(FileMetadata {
+                    digest: TrackedFileDigest::new(FileDigest::from_bytes_sha1(version.as_bytes())),
+                    is_executable: false,
+                });
+                let outputs = vec![
+                    (outputs.next().unwrap().get_path().dupe(), changing),
+                    (
+                        outputs.next().unwrap().get_path().dupe(),
+                        ArtifactValue::empty_file(),
+                    ),
+                ];
+                Ok((
+                    ActionOutputs::new(outputs.into_iter().collect()),
+                    ActionExecutionMetadata {
+                        execution_kind: ActionExecutionKind::Simple,
+                        timing: ActionExecutionTimingData::default(),
+                    },
+                ))
+            }
+        }
+
+        /// Records every computation of the artifacts consumed through `ConsumeArtifact`.
+        #[derive(Default)]
+        struct Consumed(Mutex<Vec<BuckOutPath>>);
+
+        /// Depends on the value of a single artifact, like the actions consuming it do.
+        #[derive(Clone, Display, Debug, Eq, PartialEq, Hash, Allocative)]
+        struct ConsumeArtifact(BuildArtifact);
+
+        #[async_trait]
+        impl Key for ConsumeArtifact {
+            type Value = SharedResult<ArtifactValue>;
+
+            async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
+                ctx.global_data()
+                    .get::<Arc<Consumed>>()
+                    .unwrap()
+                    .0
+                    .lock()
+                    .unwrap()
+                    .push(self.0.get_path().dupe());
+                ctx.build_artifact_value(&self.0).await
+            }
+
+            fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+                match (x, y) {
+                    (Ok(x), Ok(y)) => x == y,
+                    _ => false,
+                }
+            }
+        }
+
+        let temp_fs = ProjectRootTemp::new()?;
+        let label = ConfiguredTargetLabel::testing_new(
+            Package::testing_new("cell", "pkg"),
+            TargetName::unchecked_new("foo"),
+            Configuration::testing_new(),
+        );
+        let output = |path: &str| {
+            BuildArtifact::testing_new(
+                label.dupe(),
+                ForwardRelativePathBuf::unchecked_new(path.to_owned()),
+                DeferredId::testing_new(0),
+            )
+        };
+        let changing = output("changing.out");
+        let stable = output("stable.out");
+        let deferred_resolve = DeferredResolve(changing.key().deferred_key().dupe());
+        let version = Arc::new(AtomicU64::new(0));
+        let registered_action = registered_action(
+            changing.dupe(),
+            box VersionedAction {
+                outputs: indexset![changing.dupe(), stable.dupe()],
+                version: version.dupe(),
+                category: Category::try_from("fake_action").unwrap(),
+            },
+        );
+
+        let consumed = Arc::new(Consumed::default());
+        let dry_run_tracker = Arc::new(Mutex::new(vec![]));
+        let mut dice_computations = make_default_dice_state(dry_run_tracker, &temp_fs, {
+            let registered_action = registered_action.dupe();
+            let consumed = consumed.dupe();
+            vec![box move |builder| {
+                mock_deferred_resolution_calculation(
+                    builder.set_data(|data| data.set(consumed)),
+                    deferred_resolve,
+                    registered_action,
+                )
+            }]
+        })?;
+
+        let mut values = Vec::new();
+        for v in 0..3 {
+            if v != 0 {
+                // Re-run the action, with the same outputs the first time and a different
+                // `changing.out` the second time.
+                version.store(v - 1, Ordering::SeqCst);
+                dice_computations.changed(vec![BuildKey(changing.key().dupe())])?;
+                dice_computations = dice_computations.commit();
+            }
+            let (changing_value, stable_value) =
+                with_dispatcher_async(EventDispatcher::null(), async {
+                    let changing_value = dice_computations
+                        .compute(&ConsumeArtifact(changing.dupe()))
+                        .await??;
+                    let stable_value = dice_computations
+                        .compute(&ConsumeArtifact(stable.dupe()))
+                        .await??;
+                    anyhow::Ok((changing_value, stable_value))
+                })
+                .await?;
+            assert_eq!(stable_value, ArtifactValue::empty_file());
+            values.push(changing_value);
+        }
+
+        // Re-running the action with identical outputs recomputes none of the consumers, and
+        // changing one output only recomputes the consumers of that output.
+        assert_eq!(values[0], values[1]);
+        assert_ne!(values[1], values[2]);
+        assert_eq!(
+            *consumed.0.lock().unwrap(),
+            vec![
+                changing.get_path().dupe(),
+                stable.get_path().dupe(),
+                changing.get_path().dupe(),
+            ]
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_ensure_artifact_build_artifact() -> anyhow::Result<()> {
         let temp_fs = ProjectRootTemp::new()?;
@@ -877,6 +1321,10 @@ mod tests {
                 stderr: "stderr".to_owned().into_bytes(),
             },
             exit_code: Some(1),
+            diagnostic_outputs: vec![],
+            scratch_dir: None,
+            scratch_dir_bytes: 0,
+            resource_usage: None,
         };
 
         let proto = command_details(&report, false).await;
@@ -0,0 +1,381 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! How long actions took the last times they ran, persisted across daemon restarts.
+//!
+//! When an action starts, the duration we expect it to take is sent in its
+//! `ActionExecutionStart` event, which lets the console estimate how much of the running work is
+//! done and how long is left. Counting jobs is misleading when a few long actions (e.g. links)
+//! dominate the build.
+//!
+//! We also remember how long the chain of actions depending on each action took, which together
+//! with its own duration is how long the build has left at least once it starts, and how many
+//! actions that chain goes through. Actions with the longest such critical path, and then the
+//! deepest, are run first when they have to queue for local resources.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use allocative::Allocative;
+use buck2_common::persisted_records::PersistedRecords;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use dice::UserComputationData;
+use gazebo::prelude::*;
+use host_sharing::Priority;
+use parking_lot::Mutex;
+
+use crate::actions::RegisteredAction;
+
+const HISTORY_FILE: &str = "durations";
+const HISTORY_VERSION: &str = "v3";
+
+/// How much a new sample moves the expected duration. Smooths out noise from a loaded machine
+/// while still following real changes in how long an action takes.
+const SMOOTHING: f64 = 0.3;
+
+/// Actions that didn't run for this long (e.g. because the target was deleted) are forgotten.
+const EXPIRY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct HistoryEntry {
+    expected_millis: u64,
+    /// Seconds since the epoch when the action last ran.
+    last_run: u64,
+    /// The longest critical path through the actions that depend on this one, not including it.
+    tail_millis: u64,
+    /// The most actions on a chain of actions depending on this one, not including it.
+    tail_depth: u64,
+}
+
+#[derive(Allocative)]
+pub struct ActionDurationHistory {
+    file: PersistedRecords,
+    #[allocative(skip)]
+    entries: Mutex<HashMap<String, HistoryEntry>>,
+    /// Entries whose tail was recorded since the last save, i.e. by the current command, see
+    /// `record_tail`.
+    #[allocative(skip)]
+    updated_tails: Mutex<HashSet<String>>,
+    #[allocative(skip)]
+    dirty: AtomicBool,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Identifies an action across invocations. Unlike the `ActionKey`, this doesn't depend on the
+/// order actions were registered in during analysis.
+fn history_key(action: &RegisteredAction) -> String {
+    format!(
+        "{} {} {}",
+        action.owner(),
+        action.category().as_str(),
+        action.identifier().unwrap_or("")
+    )
+}
+
+impl ActionDurationHistory {
+    /// Loads the durations saved in `dir` by previous daemons.
+    pub fn load(dir: &AbsNormPath) -> anyhow::Result<Self> {
+        let file = PersistedRecords::new(dir, HISTORY_FILE, HISTORY_VERSION)?;
+        let entries = file.load(Self::parse_entry)?.into_iter().collect();
+        Ok(Self {
+            file,
+            entries: Mutex::new(entries),
+            updated_tails: Mutex::new(HashSet::new()),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    fn parse_entry(line: &str) -> Option<(String, HistoryEntry)> {
+        let mut fields = line.splitn(5, '\t');
+        let expected_millis = fields.next()?.parse().ok()?;
+        let last_run = fields.next()?.parse().ok()?;
+        let tail_millis = fields.next()?.parse().ok()?;
+        let tail_depth = fields.next()?.parse().ok()?;
+        let key = fields.next()?.to_owned();
+        Some((
+            key,
+            HistoryEntry {
+                expected_millis,
+                last_run,
+                tail_millis,
+                tail_depth,
+            },
+        ))
+    }
+
+    /// How long `action` is expected to take to execute, if it ran before.
+    pub fn expected_duration(&self, action: &RegisteredAction) -> Option<Duration> {
+        self.get(&history_key(action))
+    }
+
+    /// How long the build is expected to take at least once `action` starts executing: its own
+    /// duration plus the longest chain of actions depending on it. Zero if it never ran.
+    pub fn critical_path(&self, action: &RegisteredAction) -> Duration {
+        Duration::from_millis(self.get_priority(&history_key(action)).critical_path_millis)
+    }
+
+    /// Where `action` goes in the queue for local resources: its critical path, then the number
+    /// of actions on it, including `action`. The lowest priority if it never ran.
+    pub fn priority(&self, action: &RegisteredAction) -> Priority {
+        self.get_priority(&history_key(action))
+    }
+
+    /// Records how long `action` took to execute.
+    pub fn record(&self, action: &RegisteredAction, duration: Duration) {
+        self.record_at(history_key(action), duration, now_secs())
+    }
+
+    /// Records that a dependent with the given priority ran after `action`. The tail of `action`
+    /// is the longest and deepest of these over its dependents.
+    pub fn record_tail(&self, action: &RegisteredAction, dependent: Priority) {
+        self.record_tail_at(history_key(action), dependent)
+    }
+
+    fn get(&self, key: &str) -> Option<Duration> {
+        self.entries
+            .lock()
+            .get(key)
+            .map(|entry| Duration::from_millis(entry.expected_millis))
+    }
+
+    fn get_priority(&self, key: &str) -> Priority {
+        self.entries
+            .lock()
+            .get(key)
+            .map_or(Priority::default(), |entry| Priority {
+                critical_path_millis: entry.expected_millis.saturating_add(entry.tail_millis),
+                depth: entry.tail_depth.saturating_add(1),
+            })
+    }
+
+    fn record_at(&self, key: String, duration: Duration, now: u64) {
+        let sample = duration.as_millis() as f64;
+        let mut entries = self.entries.lock();
+        let (expected_millis, tail_millis, tail_depth) = match entries.get(&key) {
+            Some(entry) => (
+                (entry.expected_millis as f64 * (1.0 - SMOOTHING) + sample * SMOOTHING).round()
+                    as u64,
+                entry.tail_millis,
+                entry.tail_depth,
+            ),
+            None => (sample as u64, 0, 0),
+        };
+        entries.insert(
+            key,
+            HistoryEntry {
+                expected_millis,
+                last_run: now,
+                tail_millis,
+                tail_depth,
+            },
+        );
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    fn record_tail_at(&self, key: String, dependent: Priority) {
+        let mut entries = self.entries.lock();
+        // Actions that never ran (e.g. cache hits) don't need a place in the queue.
+        let entry = match entries.get_mut(&key) {
+            Some(entry) => entry,
+            None => return,
+        };
+        // The first sample of a command replaces the tail recorded before, so that it shrinks when
+        // dependents get faster or go away. Later ones only extend it: a quick dependent doesn't
+        // make the path through a slow one any shorter.
+        let (tail_millis, tail_depth) = if self.updated_tails.lock().insert(key) {
+            (dependent.critical_path_millis, dependent.depth)
+        } else {
+            (
+                entry.tail_millis.max(dependent.critical_path_millis),
+                entry.tail_depth.max(dependent.depth),
+            )
+        };
+        if (tail_millis, tail_depth) == (entry.tail_millis, entry.tail_depth) {
+            return;
+        }
+        entry.tail_millis = tail_millis;
+        entry.tail_depth = tail_depth;
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Writes the durations to disk if any were recorded since the last save.
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.save_at(now_secs())
+    }
+
+    fn save_at(&self, now: u64) -> anyhow::Result<()> {
+        // Saving happens between commands, and the next one records tails anew.
+        self.updated_tails.lock().clear();
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let records = {
+            let mut entries = self.entries.lock();
+            entries.retain(|_, entry| now.saturating_sub(entry.last_run) < EXPIRY.as_secs());
+            entries
+                .iter()
+                .map(|(key, entry)| {
+                    format!(
+                        "{}\t{}\t{}\t{}\t{}",
+                        entry.expected_millis,
+                        entry.last_run,
+                        entry.tail_millis,
+                        entry.tail_depth,
+                        key
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        self.file.save(records)
+    }
+}
+
+pub trait HasActionDurationHistory {
+    fn set_action_duration_history(&mut self, history: Arc<ActionDurationHistory>);
+
+    /// Not set outside of the daemon (e.g. in tests), in which case durations aren't tracked.
+    fn get_action_duration_history(&self) -> Option<Arc<ActionDurationHistory>>;
+}
+
+impl HasActionDurationHistory for UserComputationData {
+    fn set_action_duration_history(&mut self, history: Arc<ActionDurationHistory>) {
+        self.data.set(history);
+    }
+
+    fn get_action_duration_history(&self) -> Option<Arc<ActionDurationHistory>> {
+        self.data
+            .get::<Arc<ActionDurationHistory>>()
+            .ok()
+            .map(|history| history.dupe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_reload() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = AbsNormPath::new(dir.path())?;
+        let history = ActionDurationHistory::load(dir)?;
+        assert_eq!(None, history.get("a"));
+
+        let now = 1000 + EXPIRY.as_secs();
+        history.record_at("a".to_owned(), Duration::from_secs(10), now);
+        assert_eq!(Some(Duration::from_secs(10)), history.get("a"));
+        // Moves towards new samples, without jumping to them.
+        history.record_at("a".to_owned(), Duration::from_secs(20), now);
+        assert_eq!(Some(Duration::from_secs(13)), history.get("a"));
+
+        history.record_at("old".to_owned(), Duration::from_secs(1), 1000);
+        history.record_at("b".to_owned(), Duration::from_millis(5), now);
+        history.save_at(now)?;
+
+        let history = ActionDurationHistory::load(dir)?;
+        assert_eq!(Some(Duration::from_secs(13)), history.get("a"));
+        assert_eq!(Some(Duration::from_millis(5)), history.get("b"));
+        assert_eq!(None, history.get("old"));
+        Ok(())
+    }
+
+    fn critical_path(history: &ActionDurationHistory, key: &str) -> Duration {
+        Duration::from_millis(history.get_priority(key).critical_path_millis)
+    }
+
+    fn dependent(critical_path: Duration, depth: u64) -> Priority {
+        Priority {
+            critical_path_millis: critical_path.as_millis() as u64,
+            depth,
+        }
+    }
+
+    #[test]
+    fn test_critical_path() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = AbsNormPath::new(dir.path())?;
+        let history = ActionDurationHistory::load(dir)?;
+
+        // Only actions that ran have a critical path.
+        history.record_tail_at("a".to_owned(), dependent(Duration::from_secs(7), 1));
+        assert_eq!(Priority::default(), history.get_priority("a"));
+
+        let now = 1000;
+        history.record_at("a".to_owned(), Duration::from_secs(2), now);
+        assert_eq!(Duration::from_secs(2), critical_path(&history, "a"));
+        // The longest dependent wins, whatever the order they ran in.
+        history.record_tail_at("a".to_owned(), dependent(Duration::from_secs(5), 1));
+        history.record_tail_at("a".to_owned(), dependent(Duration::from_secs(8), 1));
+        history.record_tail_at("a".to_owned(), dependent(Duration::from_secs(3), 1));
+        assert_eq!(Duration::from_secs(10), critical_path(&history, "a"));
+        history.save_at(now)?;
+
+        // A new daemon starts from what was saved, and replaces it with the first dependent.
+        let history = ActionDurationHistory::load(dir)?;
+        assert_eq!(Duration::from_secs(10), critical_path(&history, "a"));
+        history.record_tail_at("a".to_owned(), dependent(Duration::from_secs(1), 1));
+        assert_eq!(Duration::from_secs(3), critical_path(&history, "a"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tails_are_replaced_by_each_command() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = AbsNormPath::new(dir.path())?;
+        let history = ActionDurationHistory::load(dir)?;
+
+        let now = 1000;
+        history.record_at("a".to_owned(), Duration::from_secs(2), now);
+        history.record_tail_at("a".to_owned(), dependent(Duration::from_secs(8), 1));
+        // Saved between commands.
+        history.save_at(now)?;
+        // The dependent got faster, and the same daemon sees it in the next command.
+        history.record_tail_at("a".to_owned(), dependent(Duration::from_secs(1), 1));
+        assert_eq!(Duration::from_secs(3), critical_path(&history, "a"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_depth() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = AbsNormPath::new(dir.path())?;
+        let history = ActionDurationHistory::load(dir)?;
+
+        let now = 1000;
+        history.record_at("a".to_owned(), Duration::from_millis(1), now);
+        assert_eq!(1, history.get_priority("a").depth);
+        // The deepest chain counts, even if a shallower one is slower.
+        history.record_tail_at("a".to_owned(), dependent(Duration::from_secs(5), 1));
+        history.record_tail_at("a".to_owned(), dependent(Duration::from_millis(3), 4));
+        assert_eq!(
+            Priority {
+                critical_path_millis: 5001,
+                depth: 5,
+            },
+            history.get_priority("a")
+        );
+        history.save_at(now)?;
+
+        let history = ActionDurationHistory::load(dir)?;
+        assert_eq!(5, history.get_priority("a").depth);
+        Ok(())
+    }
+}
@@ -47,12 +47,15 @@ use derivative::Derivative;
 use derive_more::Display;
 use dice::DiceComputations;
 use gazebo::prelude::*;
+use host_sharing::Priority;
 use indexmap::indexmap;
 use indexmap::IndexMap;
 use indexmap::IndexSet;
 use itertools::Itertools;
 
 use crate::actions::artifact::build_artifact::BuildArtifact;
+use crate::actions::duration_history::ActionDurationHistory;
+use crate::actions::duration_history::HasActionDurationHistory;
 use crate::actions::execute::error::CommandExecutionErrorMarker;
 use crate::actions::execute::error::ExecuteError;
 use crate::actions::impls::run::knobs::HasRunActionKnobs;
@@ -108,6 +111,7 @@ pub enum ActionExecutionKind {
         allows_cache_upload: bool,
         did_cache_upload: bool,
         eligible_for_full_hybrid: bool,
+        input_bytes_uploaded: u64,
     },
     /// This action is simple and executed inline within buck2 (e.g. write, symlink_dir)
     #[display(fmt = "simple")]
@@ -127,6 +131,7 @@ pub struct CommandExecutionRef<'a> {
     pub allows_cache_upload: bool,
     pub did_cache_upload: bool,
     pub eligible_for_full_hybrid: bool,
+    pub input_bytes_uploaded: u64,
 }
 
 impl ActionExecutionKind {
@@ -148,6 +153,7 @@ impl ActionExecutionKind {
                 allows_cache_upload,
                 did_cache_upload,
                 eligible_for_full_hybrid,
+                input_bytes_uploaded,
             } => Some(CommandExecutionRef {
                 kind,
                 prefers_local: *prefers_local,
@@ -155,6 +161,7 @@ impl ActionExecutionKind {
                 allows_cache_upload: *allows_cache_upload,
                 did_cache_upload: *did_cache_upload,
                 eligible_for_full_hybrid: *eligible_for_full_hybrid,
+                input_bytes_uploaded: *input_bytes_uploaded,
             }),
             Self::Simple | Self::Skipped | Self::Deferred => None,
         }
@@ -218,6 +225,7 @@ impl HasActionExecutor for DiceComputations {
         let events = self.per_transaction_data().get_dispatcher().dupe();
         let re_client = self.per_transaction_data().get_re_client();
         let run_action_knobs = self.per_transaction_data().get_run_action_knobs();
+        let duration_history = self.per_transaction_data().get_action_duration_history();
 
         Ok(Arc::new(BuckActionExecutor::new(
             CommandExecutor::new(
@@ -230,6 +238,7 @@ impl HasActionExecutor for DiceComputations {
             events,
             re_client,
             run_action_knobs,
+            duration_history,
         )))
     }
 }
@@ -241,6 +250,7 @@ pub struct BuckActionExecutor {
     events: EventDispatcher,
     re_client: ManagedRemoteExecutionClient,
     run_action_knobs: RunActionKnobs,
+    duration_history: Option<Arc<ActionDurationHistory>>,
 }
 
 impl BuckActionExecutor {
@@ -251,6 +261,7 @@ impl BuckActionExecutor {
         events: EventDispatcher,
         re_client: ManagedRemoteExecutionClient,
         run_action_knobs: RunActionKnobs,
+        duration_history: Option<Arc<ActionDurationHistory>>,
     ) -> Self {
         Self {
             command_executor,
@@ -259,6 +270,7 @@ impl BuckActionExecutor {
             events,
             re_client,
             run_action_knobs,
+            duration_history,
         }
     }
 }
@@ -269,6 +281,7 @@ struct BuckActionExecutionContext<'a> {
     inputs: IndexMap<ArtifactGroup, ArtifactGroupValues>,
     outputs: &'a IndexSet<BuildArtifact>,
     command_reports: &'a mut Vec<CommandExecutionReport>,
+    priority: Priority,
 }
 
 #[async_trait]
@@ -278,6 +291,7 @@ impl ActionExecutionCtx for BuckActionExecutionContext<'_> {
             owner: self.action.owner(),
             category: self.action.category(),
             identifier: self.action.identifier(),
+            priority: self.priority,
             action_key: self.action.key() as _,
         }
     }
@@ -290,6 +304,10 @@ impl ActionExecutionCtx for BuckActionExecutionContext<'_> {
         self.executor.command_executor.executor_fs()
     }
 
+    fn executor_config(&self) -> &CommandExecutorConfig {
+        self.action.execution_config()
+    }
+
     fn materializer(&self) -> &dyn Materializer {
         self.executor.materializer.as_ref()
     }
@@ -333,6 +351,7 @@ impl ActionExecutionCtx for BuckActionExecutionContext<'_> {
             rejected_execution,
             did_cache_upload,
             eligible_for_full_hybrid,
+            input_bytes_uploaded,
         } = self
             .executor
             .command_executor
@@ -351,6 +370,7 @@ impl ActionExecutionCtx for BuckActionExecutionContext<'_> {
                         allows_cache_upload: request.allow_cache_upload(),
                         did_cache_upload,
                         eligible_for_full_hybrid,
+                        input_bytes_uploaded,
                     },
                     timing: report.timing.into(),
                 },
@@ -425,6 +445,10 @@ impl ActionExecutor for BuckActionExecutor {
                 inputs,
                 outputs: outputs.as_ref(),
                 command_reports: &mut command_reports,
+                priority: self
+                    .duration_history
+                    .as_ref()
+                    .map_or(Priority::default(), |history| history.priority(action)),
             };
 
             let (result, metadata) = match action.as_executable() {
@@ -488,6 +512,17 @@ impl ActionExecutor for BuckActionExecutor {
                     Err(ExecuteError::MismatchedOutputs { wanted, got })
                 }
             } else {
+                if self.run_action_knobs.materialize_intermediates {
+                    self.materializer
+                        .ensure_materialized(
+                            outputs
+                                .iter()
+                                .map(|o| self.command_executor.fs().resolve_build(o.get_path()))
+                                .collect(),
+                        )
+                        .await
+                        .context("Failed to materialize outputs")?;
+                }
                 Ok((result, metadata))
             }
         }
@@ -604,6 +639,7 @@ mod tests {
             EventDispatcher::null(),
             ManagedRemoteExecutionClient::testing_new_dummy(),
             Default::default(),
+            None,
         );
 
         #[derive(Debug, Allocative)]
@@ -7,13 +7,19 @@
  * of this source tree.
  */
 
+use std::fmt;
 use std::fmt::Display;
 use std::fmt::Write;
 
+use buck2_core::category::Category;
 use buck2_core::fs::project::ProjectRelativePathBuf;
+use buck2_execute::base_deferred_key::BaseDeferredKey;
 use buck2_execute::execute::request::OutputType;
 use thiserror::Error;
 
+use crate::actions::RegisteredAction;
+use crate::artifact_groups::ArtifactGroup;
+
 #[derive(Debug)]
 pub enum ExecuteError {
     MissingOutputs {
@@ -87,3 +93,174 @@ impl From<anyhow::Error> for ExecuteError {
 #[derive(Error, Debug)]
 #[error("Command execution failed. Details are in the command report.")]
 pub struct CommandExecutionErrorMarker;
+
+/// Which action an error comes from, and what it belongs to, to be attached as context to errors
+/// that would otherwise not say which target triggered them (e.g. RE and materializer errors).
+pub(crate) struct ActionProvenance<'a> {
+    owner: &'a BaseDeferredKey,
+    category: &'a Category,
+    identifier: Option<&'a str>,
+}
+
+impl<'a> ActionProvenance<'a> {
+    pub(crate) fn new(action: &'a RegisteredAction) -> Self {
+        Self {
+            owner: action.owner(),
+            category: action.category(),
+            identifier: action.identifier(),
+        }
+    }
+}
+
+impl Display for ActionProvenance<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.identifier {
+            Some(identifier) => write!(f, "action `{} {}`", self.category, identifier)?,
+            None => write!(f, "action `{}`", self.category)?,
+        }
+        match self.owner {
+            BaseDeferredKey::TargetLabel(target) => write!(f, " of target `{}`", target),
+            // Anon targets are shared by all the targets that create them, so the target that
+            // needed this one is added by `with_anon_target_parent`.
+            BaseDeferredKey::AnonTarget(anon) => write!(
+                f,
+                " of anon target `{}` (rule `{}`, attrs hash `{}`)",
+                anon.name(),
+                anon.rule_type(),
+                anon.rule_type_attrs_hash()
+            ),
+            BaseDeferredKey::BxlLabel(bxl) => write!(f, " of BXL `{}`", bxl),
+        }
+    }
+}
+
+/// Errors building the outputs of anon targets don't say which target needed them. If `input` is
+/// the output of an anon target other than `parent`, add `needed_by`, which describes what needed
+/// it in `parent`, to the error building it.
+pub(crate) fn with_anon_target_parent(
+    e: anyhow::Error,
+    input: &ArtifactGroup,
+    parent: &BaseDeferredKey,
+    needed_by: impl Display,
+) -> anyhow::Error {
+    match input {
+        ArtifactGroup::Artifact(artifact) => match artifact.owner() {
+            Some(owner @ BaseDeferredKey::AnonTarget(anon)) if owner != parent => {
+                e.context(format!(
+                    "Error building anon target `{}`, needed by {}",
+                    anon.name(),
+                    needed_by
+                ))
+            }
+            _ => e,
+        },
+        ArtifactGroup::TransitiveSetProjection(_) => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use buck2_core::bzl::ImportPath;
+    use buck2_core::collections::sorted_map::SortedMap;
+    use buck2_core::configuration::Configuration;
+    use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+    use buck2_core::target::testing::TargetLabelExt;
+    use buck2_core::target::TargetLabel;
+    use buck2_execute::anon_target::AnonTarget;
+    use buck2_node::rule_type::StarlarkRuleType;
+    use gazebo::prelude::*;
+
+    use super::*;
+    use crate::actions::artifact::build_artifact::BuildArtifact;
+    use crate::actions::artifact::testing::BuildArtifactTestingExt;
+    use crate::actions::artifact::Artifact;
+    use crate::deferred::types::testing::DeferredIdExt;
+    use crate::deferred::types::DeferredId;
+
+    fn target() -> BaseDeferredKey {
+        BaseDeferredKey::TargetLabel(
+            TargetLabel::testing_parse("root//foo:bar").configure(Configuration::testing_new()),
+        )
+    }
+
+    fn anon_target(name: &str) -> (BaseDeferredKey, String) {
+        let anon = AnonTarget::new(
+            Arc::new(StarlarkRuleType {
+                import_path: ImportPath::unchecked_new("root", "foo", "rules.bzl"),
+                name: "my_rule".to_owned(),
+            }),
+            TargetLabel::testing_parse(name),
+            SortedMap::default(),
+            SortedMap::default(),
+            Configuration::testing_new(),
+        );
+        let hash = anon.rule_type_attrs_hash().to_owned();
+        (BaseDeferredKey::AnonTarget(Arc::new(anon)), hash)
+    }
+
+    fn output_of(owner: &BaseDeferredKey) -> ArtifactGroup {
+        ArtifactGroup::Artifact(Artifact::from(BuildArtifact::testing_new_with_owner(
+            owner.dupe(),
+            ForwardRelativePathBuf::unchecked_new("out.txt".to_owned()),
+            DeferredId::testing_new(0),
+        )))
+    }
+
+    #[test]
+    fn test_action_provenance() -> anyhow::Result<()> {
+        let category = Category::try_from("cxx_link")?;
+        let owner = target();
+        let provenance = ActionProvenance {
+            owner: &owner,
+            category: &category,
+            identifier: Some("libfoo.so"),
+        };
+        assert_eq!(
+            "action `cxx_link libfoo.so` of target `root//foo:bar (<testing>)`",
+            provenance.to_string()
+        );
+
+        let (owner, hash) = anon_target("anon//:baz");
+        let provenance = ActionProvenance {
+            owner: &owner,
+            category: &category,
+            identifier: None,
+        };
+        assert_eq!(
+            format!(
+                "action `cxx_link` of anon target `anon//:baz` (rule `root//foo/rules.bzl:my_rule`, attrs hash `{}`)",
+                hash
+            ),
+            provenance.to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_anon_target_parent() {
+        let parent = target();
+        let (anon, _) = anon_target("anon//:baz");
+        let add_parent = |input: &ArtifactGroup, parent: &BaseDeferredKey| {
+            format!(
+                "{:#}",
+                with_anon_target_parent(
+                    anyhow::anyhow!("exit code 1"),
+                    input,
+                    parent,
+                    "action `cxx_link` of target `root//foo:bar`"
+                )
+            )
+        };
+
+        assert_eq!(
+            "Error building anon target `anon//:baz`, needed by action `cxx_link` of target `root//foo:bar`: exit code 1",
+            add_parent(&output_of(&anon), &parent)
+        );
+        // The outputs of targets, and those of the anon target that is being built, already say
+        // where they come from.
+        assert_eq!("exit code 1", add_parent(&output_of(&parent), &parent));
+        assert_eq!("exit code 1", add_parent(&output_of(&anon), &anon));
+    }
+}
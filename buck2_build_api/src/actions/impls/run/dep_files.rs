@@ -15,6 +15,10 @@ use std::sync::Arc;
 
 use allocative::Allocative;
 use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_common::executor_config::CommandExecutorConfig;
+use buck2_common::executor_config::CommandExecutorKind;
+use buck2_common::executor_config::RemoteExecutorOptions;
 use buck2_common::file_ops::TrackedFileDigest;
 use buck2_core::category::Category;
 use buck2_core::directory::DirectorySelector;
@@ -22,17 +26,23 @@ use buck2_core::directory::FingerprintedDirectory;
 use buck2_core::env_helper::EnvHelper;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::project::ProjectRelativePath;
+use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_core::soft_error;
 use buck2_execute::artifact::fs::ArtifactFs;
 use buck2_execute::base_deferred_key::BaseDeferredKey;
+use buck2_execute::digest::CasDigestToReExt;
 use buck2_execute::directory::expand_selector_for_dependencies;
 use buck2_execute::directory::ActionDirectoryBuilder;
 use buck2_execute::directory::ActionImmutableDirectory;
 use buck2_execute::directory::ActionSharedDirectory;
 use buck2_execute::directory::INTERNER;
+use buck2_execute::execute::action_digest::ActionDigest;
+use buck2_execute::execute::kind::CommandExecutionKind;
+use buck2_execute::execute::request::RemoteDepFileLookup;
 use buck2_execute::execute::target::CommandExecutionTarget;
 use buck2_execute::materialize::materializer::MaterializationError;
 use buck2_execute::materialize::materializer::Materializer;
+use buck2_execute::re::manager::ManagedRemoteExecutionClient;
 use dashmap::DashMap;
 use derive_more::Display;
 use futures::StreamExt;
@@ -41,6 +51,7 @@ use once_cell::sync::Lazy;
 use parking_lot::MappedMutexGuard;
 use parking_lot::Mutex;
 use parking_lot::MutexGuard;
+use remote_execution::TActionResult2;
 use thiserror::Error;
 use tracing::instrument;
 
@@ -335,6 +346,291 @@ pub async fn match_or_clear_dep_file(
     Ok(None)
 }
 
+/// The outputs of the previous run of the action recorded for key, provided it ran with the same
+/// command line and declared the same dep files. This must be called before
+/// `match_or_clear_dep_file`, which drops the state on a miss.
+pub fn previous_outputs(
+    key: &DepFilesKey,
+    cli_digest: &ExpandedCommandLineDigest,
+    declared_dep_files: &DeclaredDepFiles,
+) -> Option<ActionOutputs> {
+    let previous_state = get_dep_files(key)?;
+
+    if declared_dep_files.declares_same_dep_files(&previous_state.declared_dep_files)
+        && *cli_digest == previous_state.cli_digest
+    {
+        Some(previous_state.result.dupe())
+    } else {
+        None
+    }
+}
+
+/// The remote executor options to use to look up and store dep files in the remote action cache,
+/// if the action may use it at all.
+fn remote_executor_options(config: &CommandExecutorConfig) -> Option<&RemoteExecutorOptions> {
+    match &config.executor_kind {
+        CommandExecutorKind::Local(..) => None,
+        CommandExecutorKind::Remote(remote) => Some(remote),
+        CommandExecutorKind::Hybrid { remote, .. } => Some(remote),
+    }
+}
+
+/// The paths the dep files of a command are written to, by label, sorted.
+fn dep_file_paths(
+    declared_dep_files: &DeclaredDepFiles,
+    fs: &ArtifactFs,
+) -> anyhow::Result<Vec<(Arc<str>, ProjectRelativePathBuf)>> {
+    let mut dep_files = declared_dep_files
+        .tagged
+        .values()
+        .map(|d| anyhow::Ok((d.label.dupe(), fs.resolve(d.output.get_path())?)))
+        .collect::<Result<Vec<_>, _>>()?;
+    dep_files.sort();
+    Ok(dep_files)
+}
+
+/// The action cache key under which we store the dep files of a command. This covers everything
+/// but the tagged inputs, which are only known to matter once we have read the dep files.
+fn remote_dep_files_key(
+    cli_digest: &ExpandedCommandLineDigest,
+    untagged: &TrackedFileDigest,
+    dep_file_paths: &[(Arc<str>, ProjectRelativePathBuf)],
+    options: &RemoteExecutorOptions,
+) -> ActionDigest {
+    let mut key = format!(
+        "buck2-remote-dep-files-v1\n{}\n{}\n",
+        hex::encode(cli_digest.as_bytes()),
+        untagged
+    );
+    for (label, path) in dep_file_paths {
+        key.push_str(&format!("dep_file {} {}\n", label, path));
+    }
+    for (k, v) in options.re_properties.iter() {
+        key.push_str(&format!("property {}={}\n", k, v));
+    }
+
+    ActionDigest::from_bytes_sha1(key.as_bytes())
+}
+
+/// The action cache key under which we store the result of a command, given the key its dep files
+/// are stored under and its tagged inputs, filtered using those dep files.
+fn remote_dep_files_filtered_key(
+    dep_files_key: &ActionDigest,
+    filtered: &PartitionedInputs<TrackedFileDigest>,
+) -> ActionDigest {
+    let mut tagged = filtered.tagged.iter().collect::<Vec<_>>();
+    tagged.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut key = format!("{}\n", dep_files_key);
+    for (label, digest) in tagged {
+        key.push_str(&format!("tagged {} {}\n", label, digest));
+    }
+
+    ActionDigest::from_bytes_sha1(key.as_bytes())
+}
+
+/// Looks up the dep files a previous execution of a command stored in the remote action cache.
+/// If there are any, this finds the key under which the result of a command that used the same
+/// inputs would have been stored. The executor only does this once the command itself missed.
+pub struct RemoteDepFiles {
+    cli_digest: ExpandedCommandLineDigest,
+    directories: PartitionedInputs<ActionDirectoryBuilder>,
+    dep_file_paths: Vec<(Arc<str>, ProjectRelativePathBuf)>,
+    options: RemoteExecutorOptions,
+}
+
+impl RemoteDepFiles {
+    /// Returns `None` if the command can't use the remote action cache or has no dep files.
+    pub fn new(
+        cli_digest: &ExpandedCommandLineDigest,
+        declared_inputs: &PartitionedInputs<Vec<ArtifactGroup>>,
+        declared_dep_files: &DeclaredDepFiles,
+        ctx: &dyn ActionExecutionCtx,
+    ) -> anyhow::Result<Option<Self>> {
+        let options = match remote_executor_options(ctx.executor_config()) {
+            Some(options) if !declared_dep_files.is_empty() => options,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(Self {
+            cli_digest: cli_digest.clone(),
+            directories: declared_inputs.to_directories(ctx)?,
+            dep_file_paths: dep_file_paths(declared_dep_files, ctx.fs())?,
+            options: options.clone(),
+        }))
+    }
+}
+
+#[async_trait]
+impl RemoteDepFileLookup for RemoteDepFiles {
+    #[instrument(level = "debug", skip_all)]
+    async fn lookup(
+        &self,
+        re_client: &ManagedRemoteExecutionClient,
+    ) -> anyhow::Result<Option<ActionDigest>> {
+        let untagged = self
+            .directories
+            .clone()
+            .fingerprint()
+            .as_fingerprints()
+            .untagged;
+        let dep_files_key = remote_dep_files_key(
+            &self.cli_digest,
+            &untagged,
+            &self.dep_file_paths,
+            &self.options,
+        );
+
+        let re_use_case = self.options.re_use_case;
+        let dep_files_result = match re_client
+            .action_cache(dep_files_key.dupe(), re_use_case)
+            .await?
+        {
+            Some(response) => response.action_result,
+            None => {
+                tracing::trace!("Remote dep files are a miss");
+                return Ok(None);
+            }
+        };
+
+        let mut contents = HashMap::with_capacity(self.dep_file_paths.len());
+
+        for (label, path) in &self.dep_file_paths {
+            let path = path.to_string();
+
+            let file = match dep_files_result
+                .output_files
+                .iter()
+                .find(|file| file.name == path)
+            {
+                Some(file) => file,
+                None => return Ok(None),
+            };
+
+            let dep_file = re_client
+                .download_blob(&file.digest.digest, re_use_case)
+                .await?;
+
+            let selector: anyhow::Result<_> = try {
+                let dep_file = String::from_utf8(dep_file)?;
+                parse_dep_file(&dep_file)?
+            };
+            let selector = selector.with_context(|| {
+                format!(
+                    "Remote action cache has an invalid `{}` dep file for `{}`",
+                    label, path,
+                )
+            })?;
+
+            contents.insert(label.dupe(), selector);
+        }
+
+        let filtered = self
+            .directories
+            .clone()
+            .filter(ConcreteDepFiles { contents })
+            .fingerprint()
+            .as_fingerprints();
+
+        tracing::trace!("Remote dep files are a hit");
+
+        Ok(Some(remote_dep_files_filtered_key(
+            &dep_files_key,
+            &filtered,
+        )))
+    }
+}
+
+/// The action digest of a command whose dep files should be stored in the remote action cache.
+/// Commands served by the action cache are skipped: whichever execution put them there already
+/// stored their dep files, and re-uploading on every hit would turn cache hits into writes.
+fn remote_dep_files_upload_digest(execution_kind: &CommandExecutionKind) -> Option<&ActionDigest> {
+    match execution_kind {
+        CommandExecutionKind::Local { digest, .. } => Some(digest),
+        CommandExecutionKind::Remote { digest } => Some(digest),
+        CommandExecutionKind::ActionCache { .. } => None,
+    }
+}
+
+/// Store the dep files produced by a command in the remote action cache, along with its result,
+/// keyed by the inputs those dep files say it used. This is only possible if the result of the
+/// command itself made it to the action cache.
+#[instrument(level = "debug", skip_all)]
+pub async fn upload_remote_dep_files(
+    cli_digest: &ExpandedCommandLineDigest,
+    declared_inputs: &PartitionedInputs<Vec<ArtifactGroup>>,
+    declared_dep_files: &DeclaredDepFiles,
+    execution_kind: &CommandExecutionKind,
+    ctx: &dyn ActionExecutionCtx,
+) -> anyhow::Result<()> {
+    let options = match remote_executor_options(ctx.executor_config()) {
+        Some(options) if !declared_dep_files.is_empty() => options,
+        _ => return Ok(()),
+    };
+
+    let digest = match remote_dep_files_upload_digest(execution_kind) {
+        Some(digest) => digest,
+        None => return Ok(()),
+    };
+
+    let re_client = ctx.re_client();
+    let result = match re_client
+        .action_cache(digest.dupe(), options.re_use_case)
+        .await?
+    {
+        Some(response) => response.action_result,
+        None => return Ok(()),
+    };
+
+    match declared_dep_files
+        .materialize(ctx.fs(), ctx.materializer())
+        .await
+    {
+        Ok(()) => {}
+        Err(MaterializeDepFilesError::NotFound) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let dep_files = match declared_dep_files.read(ctx.fs())? {
+        Some(dep_files) => dep_files,
+        None => return Ok(()),
+    };
+
+    let dep_file_paths = dep_file_paths(declared_dep_files, ctx.fs())?;
+    let directories = declared_inputs.to_directories(ctx)?;
+    let untagged = directories.clone().fingerprint().as_fingerprints().untagged;
+    let dep_files_key = remote_dep_files_key(cli_digest, &untagged, &dep_file_paths, options);
+    let filtered = directories
+        .filter(dep_files)
+        .fingerprint()
+        .as_fingerprints();
+    let filtered_key = remote_dep_files_filtered_key(&dep_files_key, &filtered);
+
+    let dep_file_paths = dep_file_paths
+        .iter()
+        .map(|(_, path)| path.to_string())
+        .collect::<HashSet<_>>();
+
+    let dep_files_result = TActionResult2 {
+        output_files: result
+            .output_files
+            .iter()
+            .filter(|file| dep_file_paths.contains(&file.name))
+            .cloned()
+            .collect(),
+        ..Default::default()
+    };
+
+    re_client
+        .write_action_result(dep_files_key.to_re(), dep_files_result, options.re_use_case)
+        .await?;
+    re_client
+        .write_action_result(filtered_key.to_re(), result, options.re_use_case)
+        .await?;
+
+    Ok(())
+}
+
 /// Post-process the dep files produced by an action.
 pub async fn populate_dep_files(
     key: DepFilesKey,
@@ -627,11 +923,9 @@ impl DeclaredDepFiles {
         let mut contents = HashMap::with_capacity(self.tagged.len());
 
         for declared_dep_file in self.tagged.values() {
-            let mut selector = DirectorySelector::empty();
-
             let dep_file = fs.resolve(declared_dep_file.output.get_path())?;
 
-            let read_dep_file: anyhow::Result<()> = try {
+            let read_dep_file: anyhow::Result<DirectorySelector> = try {
                 let dep_file_path = fs.fs().resolve(&dep_file);
                 let dep_file = fs_util::read_to_string_opt(&dep_file_path)?;
 
@@ -646,19 +940,10 @@ impl DeclaredDepFiles {
                     }
                 };
 
-                for line in dep_file.split('\n') {
-                    let line = line.trim();
-                    if line.is_empty() {
-                        continue;
-                    }
-                    let path = ProjectRelativePath::new(line)
-                        .context("Invalid line encountered in dep file")?;
-
-                    selector.select(path);
-                }
+                parse_dep_file(&dep_file)?
             };
 
-            read_dep_file.with_context(|| {
+            let selector = read_dep_file.with_context(|| {
                 format!(
                     "Action execution produced an invalid `{}` dep file at `{}`",
                     declared_dep_file.label, dep_file,
@@ -681,6 +966,24 @@ impl DeclaredDepFiles {
     }
 }
 
+/// Parse the contents of a dep file: one path, relative to the project root, per line.
+fn parse_dep_file(dep_file: &str) -> anyhow::Result<DirectorySelector> {
+    let mut selector = DirectorySelector::empty();
+
+    for line in dep_file.split('\n') {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let path =
+            ProjectRelativePath::new(line).context("Invalid line encountered in dep file")?;
+
+        selector.select(path);
+    }
+
+    Ok(selector)
+}
+
 #[derive(Error, Debug)]
 enum MaterializeDepFilesError {
     #[error("Error materializing dep file")]
@@ -730,6 +1033,7 @@ impl CommandLineArtifactVisitor for DepFilesCommandLineVisitor<'_> {
 
 #[cfg(test)]
 mod test {
+    use buck2_common::file_ops::FileDigest;
     use buck2_core::configuration::Configuration;
     use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
     use buck2_core::package::testing::PackageExt;
@@ -805,4 +1109,78 @@ mod test {
         assert!(!decl2.declares_same_dep_files(&decl3));
         assert!(!decl3.declares_same_dep_files(&decl4));
     }
+
+    #[test]
+    fn test_remote_dep_files_upload_digest() {
+        let digest = ActionDigest::from_bytes_sha1(b"action");
+
+        assert_eq!(
+            Some(&digest),
+            remote_dep_files_upload_digest(&CommandExecutionKind::Local {
+                digest: digest.dupe(),
+                command: Vec::new(),
+                env: HashMap::new(),
+            })
+        );
+        assert_eq!(
+            Some(&digest),
+            remote_dep_files_upload_digest(&CommandExecutionKind::Remote {
+                digest: digest.dupe()
+            })
+        );
+        assert_eq!(
+            None,
+            remote_dep_files_upload_digest(&CommandExecutionKind::ActionCache {
+                digest: digest.dupe()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_dep_file() {
+        assert!(parse_dep_file("foo/bar.h\n\n  foo/baz.h  \n").is_ok());
+        assert!(parse_dep_file("/abs/path.h").is_err());
+        assert!(parse_dep_file("../outside.h").is_err());
+    }
+
+    #[test]
+    fn test_remote_dep_files_filtered_key() {
+        let dep_files_key = ActionDigest::from_bytes_sha1(b"dep files");
+        let digest = |s: &str| TrackedFileDigest::new(FileDigest::from_bytes_sha1(s.as_bytes()));
+        let inputs = |tagged: Vec<(&str, &str)>| PartitionedInputs {
+            untagged: digest("untagged"),
+            tagged: tagged
+                .into_iter()
+                .map(|(label, content)| (Arc::from(label), digest(content)))
+                .collect(),
+        };
+
+        let key = remote_dep_files_filtered_key(
+            &dep_files_key,
+            &inputs(vec![("a", "used a"), ("b", "used b")]),
+        );
+
+        // The key only depends on the used inputs, not on the order we know them in.
+        assert_eq!(
+            key,
+            remote_dep_files_filtered_key(
+                &dep_files_key,
+                &inputs(vec![("b", "used b"), ("a", "used a")])
+            )
+        );
+        assert_ne!(
+            key,
+            remote_dep_files_filtered_key(
+                &dep_files_key,
+                &inputs(vec![("a", "used a"), ("b", "changed b")])
+            )
+        );
+        assert_ne!(
+            key,
+            remote_dep_files_filtered_key(
+                &ActionDigest::from_bytes_sha1(b"other dep files"),
+                &inputs(vec![("a", "used a"), ("b", "used b")])
+            )
+        );
+    }
 }
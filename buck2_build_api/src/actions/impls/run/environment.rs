@@ -0,0 +1,149 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! The environment a `run` action sees when it executes locally.
+//!
+//! By default, local commands inherit most of the daemon's environment, which makes their
+//! results depend on whoever happened to start the daemon. When `[buck2] local_env_allowlist`
+//! is set, the environment is scrubbed instead: the command only sees the variables it declared,
+//! plus the allowlisted ones. Execution platforms can additionally pin variables to a fixed
+//! value. Either way, on platforms that may run the command locally (local-only and hybrid ones),
+//! the variables the command gets on top of what it declared are added to the command itself, so
+//! that they are part of its action digest, and a command gets the same environment whichever
+//! executor ends up running it.
+
+use std::collections::HashMap;
+
+use buck2_common::executor_config::CommandExecutorKind;
+use buck2_execute::execute::environment_inheritance::EnvironmentInheritance;
+
+/// On platforms that may run the command locally, add the allowlisted and pinned variables to
+/// `env`. Return how the rest of the daemon's environment should be inherited when the command
+/// runs locally. Variables declared by the action always take precedence.
+pub fn apply_local_environment(
+    env: &mut HashMap<String, String>,
+    executor_kind: &CommandExecutorKind,
+    scrubbed_local_env: Option<EnvironmentInheritance>,
+) -> EnvironmentInheritance {
+    let env_pins = match executor_kind {
+        CommandExecutorKind::Local(local) | CommandExecutorKind::Hybrid { local, .. } => {
+            &local.env_pins
+        }
+        // Nothing will run locally, so there is no environment to scrub.
+        CommandExecutorKind::Remote(_) => {
+            return EnvironmentInheritance::local_command_exclusions();
+        }
+    };
+
+    for (key, value) in env_pins.iter() {
+        if !env.contains_key(key) {
+            env.insert(key.clone(), value.clone());
+        }
+    }
+
+    match scrubbed_local_env {
+        Some(scrubbed) => {
+            for (key, value) in scrubbed.values() {
+                if !env.contains_key(key) {
+                    env.insert(key.to_owned(), value.to_string_lossy().into_owned());
+                }
+            }
+            // Everything we pass through is now in `env`.
+            EnvironmentInheritance::empty()
+        }
+        None => EnvironmentInheritance::local_command_exclusions(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::executor_config::HybridExecutionLevel;
+    use buck2_common::executor_config::LocalExecutorOptions;
+    use buck2_common::executor_config::RemoteExecutorOptions;
+
+    use super::*;
+
+    fn local(pins: &[(&str, &str)]) -> CommandExecutorKind {
+        CommandExecutorKind::Local(LocalExecutorOptions {
+            env_pins: pins
+                .iter()
+                .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+                .collect(),
+        })
+    }
+
+    #[test]
+    fn test_declared_env_wins_over_pins() {
+        let mut env = HashMap::new();
+        env.insert("PATH".to_owned(), "/declared".to_owned());
+        let inheritance = apply_local_environment(
+            &mut env,
+            &local(&[("PATH", "/pinned"), ("LANG", "C")]),
+            None,
+        );
+        assert!(!inheritance.clear());
+        assert_eq!(env.get("PATH").map(String::as_str), Some("/declared"));
+        assert_eq!(env.get("LANG").map(String::as_str), Some("C"));
+    }
+
+    #[test]
+    fn test_scrubbed() {
+        let mut env = HashMap::new();
+        let inheritance =
+            apply_local_environment(&mut env, &local(&[]), Some(EnvironmentInheritance::empty()));
+        assert!(inheritance.clear());
+        assert_eq!(inheritance.values().count(), 0);
+        assert!(env.is_empty());
+    }
+
+    #[test]
+    fn test_hybrid_is_like_local() {
+        let hybrid = CommandExecutorKind::Hybrid {
+            local: LocalExecutorOptions {
+                env_pins: vec![("LANG".to_owned(), "C".to_owned())]
+                    .into_iter()
+                    .collect(),
+            },
+            remote: RemoteExecutorOptions::default(),
+            level: HybridExecutionLevel::Limited,
+        };
+        for scrubbed_local_env in [
+            None,
+            Some(EnvironmentInheritance::scrubbed(&["PATH".to_owned()])),
+        ] {
+            let mut hybrid_env = HashMap::new();
+            let hybrid_inheritance =
+                apply_local_environment(&mut hybrid_env, &hybrid, scrubbed_local_env);
+            let mut local_env = HashMap::new();
+            let local_inheritance = apply_local_environment(
+                &mut local_env,
+                &local(&[("LANG", "C")]),
+                scrubbed_local_env,
+            );
+            assert_eq!(local_env, hybrid_env);
+            assert_eq!(hybrid_env.get("LANG").map(String::as_str), Some("C"));
+            assert_eq!(local_inheritance.clear(), hybrid_inheritance.clear());
+            assert_eq!(
+                local_inheritance.values().count(),
+                hybrid_inheritance.values().count()
+            );
+        }
+    }
+
+    #[test]
+    fn test_remote_only_is_untouched() {
+        let mut env = HashMap::new();
+        apply_local_environment(
+            &mut env,
+            &CommandExecutorKind::Remote(RemoteExecutorOptions::default()),
+            Some(EnvironmentInheritance::empty()),
+        );
+        assert!(env.is_empty());
+    }
+}
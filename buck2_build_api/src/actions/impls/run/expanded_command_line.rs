@@ -12,18 +12,24 @@ use std::collections::HashMap;
 use allocative::Allocative;
 
 /// A command line's expansion, suitable to actually run it.
-pub(crate) struct ExpandedCommandLine {
-    pub(crate) cli: Vec<String>,
-    pub(crate) env: HashMap<String, String>,
+pub struct ExpandedCommandLine {
+    pub cli: Vec<String>,
+    pub env: HashMap<String, String>,
 }
 
 /// The digest of an ExpandedCommandLine.
-#[derive(Eq, PartialEq, Debug, Allocative)]
+#[derive(Eq, PartialEq, Debug, Clone, Allocative)]
 pub struct ExpandedCommandLineDigest(
     // This is OK to skip because hash is stored inline.
     #[allocative(skip)] blake3::Hash,
 );
 
+impl ExpandedCommandLineDigest {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        self.0.as_bytes()
+    }
+}
+
 impl ExpandedCommandLine {
     /// Obtain a hash of this command line. Conceptually this is as if we serialized the command
     /// line to a length-prefixed list then hashed it, except we never actually produce the
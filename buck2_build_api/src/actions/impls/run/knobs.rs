@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+use buck2_execute::execute::environment_inheritance::EnvironmentInheritance;
 use dice::UserComputationData;
 use gazebo::prelude::*;
 
@@ -19,6 +20,18 @@ pub struct RunActionKnobs {
     /// Hash all commands using the same mechanism as dep files. This allows us to skip
     /// re-executing commands if their inputs and outputs haven't changed.
     pub hash_all_commands: bool,
+
+    /// When set, local commands do not inherit the daemon's environment, beyond the variables
+    /// allowlisted here.
+    pub scrubbed_local_env: Option<EnvironmentInheritance>,
+
+    /// Materialize the outputs of every action once it executes, rather than only when they are
+    /// needed locally or requested.
+    pub materialize_intermediates: bool,
+
+    /// Store commands with dep files in the remote action cache under the inputs their dep files
+    /// say they used, and look them up that way before executing them.
+    pub remote_dep_files: bool,
 }
 
 pub trait HasRunActionKnobs {
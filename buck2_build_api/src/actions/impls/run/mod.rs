@@ -13,15 +13,27 @@ use std::fmt::Display;
 
 use allocative::Allocative;
 use async_trait::async_trait;
+use buck2_common::executor_config::CommandExecutorConfig;
+use buck2_common::executor_config::CommandExecutorKind;
 use buck2_core::category::Category;
+use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use buck2_core::fs::project::ProjectRelativePath;
+use buck2_core::fs::project::ProjectRelativePathBuf;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_data::ToProtoMessage;
 use buck2_events::dispatch::span_async;
 use buck2_execute::artifact::fs::ExecutorFs;
 use buck2_execute::execute::environment_inheritance::EnvironmentInheritance;
+use buck2_execute::execute::network::NetworkPolicy;
+use buck2_execute::execute::persistent_worker::PersistentWorkerProtocol;
+use buck2_execute::execute::persistent_worker::RemotePersistentWorker;
 use buck2_execute::execute::request::ActionMetadataBlob;
 use buck2_execute::execute::request::CommandExecutionInput;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::request::ExecutorPreference;
+use buck2_execute::execute::request::RemoteDepFileLookup;
+use buck2_execute::output_size::OutputSize;
 use buck2_execute::path::buck_out_path::BuckOutPath;
 use gazebo::prelude::*;
 use host_sharing::HostSharingRequirements;
@@ -41,11 +53,18 @@ use crate::actions::execute::action_executor::ActionExecutionMetadata;
 use crate::actions::execute::action_executor::ActionOutputs;
 use crate::actions::impls::run::dep_files::match_or_clear_dep_file;
 use crate::actions::impls::run::dep_files::populate_dep_files;
+use crate::actions::impls::run::dep_files::previous_outputs;
+use crate::actions::impls::run::dep_files::upload_remote_dep_files;
 use crate::actions::impls::run::dep_files::DepFilesCommandLineVisitor;
 use crate::actions::impls::run::dep_files::DepFilesKey;
+use crate::actions::impls::run::dep_files::RemoteDepFiles;
 use crate::actions::impls::run::dep_files::RunActionDepFiles;
+use crate::actions::impls::run::environment::apply_local_environment;
 use crate::actions::impls::run::expanded_command_line::ExpandedCommandLine;
 use crate::actions::impls::run::metadata::metadata_content;
+use crate::actions::impls::run::quotas::ActionQuotaError;
+use crate::actions::impls::run::quotas::QuotaViolationSeverity;
+use crate::actions::impls::run::quotas::RunActionQuotas;
 use crate::actions::Action;
 use crate::actions::ActionExecutable;
 use crate::actions::ActionExecutionCtx;
@@ -55,14 +74,25 @@ use crate::artifact_groups::ArtifactGroup;
 use crate::artifact_groups::ArtifactGroupValues;
 use crate::interpreter::rule_defs::cmd_args::CommandLineArgLike;
 use crate::interpreter::rule_defs::cmd_args::CommandLineArtifactVisitor;
+use crate::interpreter::rule_defs::cmd_args::CommandLineContext;
 use crate::interpreter::rule_defs::cmd_args::DefaultCommandLineContext;
 use crate::interpreter::rule_defs::cmd_args::SimpleCommandLineArtifactVisitor;
 use crate::interpreter::rule_defs::cmd_args::ValueAsCommandLineLike;
 
 pub mod dep_files;
-mod expanded_command_line;
+pub mod environment;
+pub mod expanded_command_line;
 pub mod knobs;
 mod metadata;
+pub(crate) mod quotas;
+
+/// The environment variable holding the directory, relative to the project root, that the
+/// diagnostic outputs of an action go into.
+const DIAGNOSTICS_DIR_ENV_VAR: &str = "BUCK_DIAGNOSTICS_DIR";
+
+/// The environment variable holding the directory, relative to the project root, that the
+/// outputs of the previous run of an incremental action are copied into.
+const PREVIOUS_OUTPUTS_DIR_ENV_VAR: &str = "BUCK_PREVIOUS_OUTPUTS_DIR";
 
 #[derive(Debug, Error)]
 enum RunActionValidationError {
@@ -93,19 +123,99 @@ impl Display for MetadataParameter {
 enum LocalPreferenceError {
     #[error("cannot have `local_only = True` and `prefer_local = True` at the same time")]
     LocalOnlyAndPreferLocal,
+    #[error("cannot have `local_only = True` and `prefer_remote = True` at the same time")]
+    LocalOnlyAndPreferRemote,
+    #[error("cannot have `prefer_local = True` and `prefer_remote = True` at the same time")]
+    PreferLocalAndPreferRemote,
 }
 
 pub fn new_executor_preference(
     local_only: bool,
     prefer_local: bool,
+    prefer_remote: bool,
 ) -> anyhow::Result<ExecutorPreference> {
-    match (local_only, prefer_local) {
-        (true, false) => Ok(ExecutorPreference::LocalRequired),
-        (false, true) => Ok(ExecutorPreference::LocalPreferred),
-        (false, false) => Ok(ExecutorPreference::Default),
-        (true, true) => Err(anyhow::anyhow!(
+    match (local_only, prefer_local, prefer_remote) {
+        (true, false, false) => Ok(ExecutorPreference::LocalRequired),
+        (false, true, false) => Ok(ExecutorPreference::LocalPreferred),
+        (false, false, true) => Ok(ExecutorPreference::RemotePreferred),
+        (false, false, false) => Ok(ExecutorPreference::Default),
+        (true, true, _) => Err(anyhow::anyhow!(
             LocalPreferenceError::LocalOnlyAndPreferLocal
         )),
+        (true, false, true) => Err(anyhow::anyhow!(
+            LocalPreferenceError::LocalOnlyAndPreferRemote
+        )),
+        (false, true, true) => Err(anyhow::anyhow!(
+            LocalPreferenceError::PreferLocalAndPreferRemote
+        )),
+    }
+}
+
+#[derive(Debug, Error)]
+enum RunActionDefaultsError {
+    #[error("`weight` must be a positive integer, got `{0}`")]
+    InvalidWeight(i32),
+}
+
+/// The execution requirements of the actions of a target that don't set them in
+/// `ctx.actions.run`, as declared with `rule()` and overridden by the target.
+#[derive(Debug, Clone, Copy, Dupe, Allocative)]
+pub(crate) struct RunActionDefaults {
+    pub(crate) executor_preference: ExecutorPreference,
+    pub(crate) weight: usize,
+}
+
+impl Default for RunActionDefaults {
+    fn default() -> Self {
+        Self {
+            executor_preference: ExecutorPreference::Default,
+            weight: 1,
+        }
+    }
+}
+
+impl RunActionDefaults {
+    pub(crate) fn new(
+        local_only: bool,
+        prefer_local: bool,
+        prefer_remote: bool,
+        weight: i32,
+    ) -> anyhow::Result<Self> {
+        if weight < 1 {
+            return Err(RunActionDefaultsError::InvalidWeight(weight).into());
+        }
+        Ok(Self {
+            executor_preference: new_executor_preference(local_only, prefer_local, prefer_remote)?,
+            weight: weight as usize,
+        })
+    }
+
+    /// The executor preference of an action that sets some of `local_only`, `prefer_local` and
+    /// `prefer_remote`: setting one to `True` replaces the default, setting the one of the
+    /// default to `False` turns it off, and the others are left as they are.
+    pub(crate) fn executor_preference(
+        &self,
+        local_only: Option<bool>,
+        prefer_local: Option<bool>,
+        prefer_remote: Option<bool>,
+    ) -> anyhow::Result<ExecutorPreference> {
+        if [local_only, prefer_local, prefer_remote].contains(&Some(true)) {
+            return new_executor_preference(
+                local_only == Some(true),
+                prefer_local == Some(true),
+                prefer_remote == Some(true),
+            );
+        }
+        let default_flag = match self.executor_preference {
+            ExecutorPreference::LocalRequired => local_only,
+            ExecutorPreference::LocalPreferred => prefer_local,
+            ExecutorPreference::RemotePreferred => prefer_remote,
+            ExecutorPreference::RemoteRequired | ExecutorPreference::Default => None,
+        };
+        match default_flag {
+            Some(false) => Ok(ExecutorPreference::Default),
+            _ => Ok(self.executor_preference),
+        }
     }
 }
 
@@ -121,6 +231,23 @@ pub(crate) struct UnregisteredRunAction {
     pub no_outputs_cleanup: bool,
     pub allow_cache_upload: bool,
     pub force_full_hybrid_if_capable: bool,
+    pub quotas: RunActionQuotas,
+    pub remote_persistent_worker: Option<PersistentWorkerProtocol>,
+    /// Paths, relative to the diagnostics directory of the action, of files the command may
+    /// write to help debug it. They are only retrieved if the command fails.
+    pub diagnostic_outputs: Vec<ForwardRelativePathBuf>,
+    pub use_jobserver: bool,
+    /// Don't inherit any of the daemon's environment when running locally, even with no
+    /// `local_env_allowlist`: the command only sees the variables it declares and those pinned
+    /// by the execution platform.
+    pub scrub_env: bool,
+    /// The command can update the outputs of its previous run in place. They are copied into a
+    /// directory it is told about before it runs, if its dep files show that the previous run
+    /// used the same command line.
+    pub incremental: bool,
+    /// Whether the command may access the network. Denying it is recorded in the action digest
+    /// and enforced where the executor can.
+    pub network: NetworkPolicy,
 }
 
 impl UnregisteredAction for UnregisteredRunAction {
@@ -169,6 +296,19 @@ impl RunAction {
         Some((cli, env))
     }
 
+    /// The environment this action is scrubbed to when it runs locally, given the one of the
+    /// daemon.
+    fn scrubbed_local_env(
+        &self,
+        daemon_scrubbed_local_env: Option<EnvironmentInheritance>,
+    ) -> Option<EnvironmentInheritance> {
+        if self.inner.scrub_env {
+            Some(EnvironmentInheritance::empty())
+        } else {
+            daemon_scrubbed_local_env
+        }
+    }
+
     /// Get the command line expansion for this RunAction.
     fn expand_command_line(
         &self,
@@ -198,6 +338,134 @@ impl RunAction {
         })
     }
 
+    /// Compare the outputs and runtime of this action against the quotas declared by its rule,
+    /// reporting any violations to the event log.
+    fn check_quotas(
+        &self,
+        ctx: &dyn ActionExecutionCtx,
+        outputs: &ActionOutputs,
+        meta: &ActionExecutionMetadata,
+    ) -> anyhow::Result<()> {
+        let quotas = &self.inner.quotas;
+        if quotas.is_empty() {
+            return Ok(());
+        }
+
+        let violations = quotas.check(
+            outputs.calc_output_count_and_bytes().bytes,
+            meta.timing.wall_time,
+        );
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let is_error = quotas.severity == QuotaViolationSeverity::Error;
+        let target = ctx.target();
+        let mut descriptions = Vec::with_capacity(violations.len());
+        for (kind, limit, actual) in violations {
+            ctx.events()
+                .instant_event(buck2_data::ActionQuotaViolation {
+                    key: Some(target.action_key.as_proto()),
+                    name: Some(buck2_data::ActionName {
+                        category: target.category.as_str().to_owned(),
+                        identifier: target.identifier.unwrap_or("").to_owned(),
+                    }),
+                    kind: kind as i32,
+                    limit,
+                    actual,
+                    is_error,
+                });
+            descriptions.push(RunActionQuotas::describe_violation(kind, limit, actual));
+        }
+
+        if is_error {
+            return Err(ActionQuotaError::Exceeded(descriptions).into());
+        }
+        ctx.events().warning(
+            buck2_data::WarningCategory::ActionQuota,
+            target.to_string(),
+            format!("Exceeded its declared quotas: {}", descriptions.join(", ")),
+        );
+        Ok(())
+    }
+
+    /// Identify the persistent worker this action can run on. The tools are the inputs that the
+    /// command line refers to directly before the flagfile, e.g. the jar in `java -jar
+    /// worker.jar @args`.
+    fn remote_persistent_worker(
+        protocol: PersistentWorkerProtocol,
+        fs: &ExecutorFs,
+        cli: &[String],
+        env: &HashMap<String, String>,
+        artifact_inputs: &[&ArtifactGroupValues],
+    ) -> anyhow::Result<RemotePersistentWorker> {
+        let startup_args = cli.split_last().map_or(cli, |(_, args)| args);
+        let ctx = DefaultCommandLineContext::new(fs);
+
+        let mut tool_digests = Vec::new();
+        for (artifact, value) in artifact_inputs.iter().flat_map(|values| values.iter()) {
+            let path = ctx.resolve_artifact(artifact)?.into_string();
+            if startup_args.iter().any(|arg| arg.contains(&path)) {
+                if let Some(digest) = value.digest() {
+                    tool_digests.push(digest.to_string());
+                }
+            }
+        }
+
+        RemotePersistentWorker::new(
+            protocol,
+            cli,
+            env,
+            tool_digests.iter().map(|d| d.as_str()),
+        )
+    }
+
+    /// Copy the outputs of the previous run of this action into `dir`, laid out like they are in
+    /// the output directory. The directory is left empty if there are none, or if something else
+    /// has since been written to their paths. It is not an input of the command, so its contents
+    /// don't affect the action digest.
+    async fn stage_previous_outputs(
+        ctx: &dyn ActionExecutionCtx,
+        dir: &ProjectRelativePath,
+        previous_outputs: Option<ActionOutputs>,
+    ) -> anyhow::Result<()> {
+        let fs = ctx.fs();
+
+        let mut copies = Vec::new();
+        if let Some(previous_outputs) = previous_outputs {
+            let resolved: Vec<_> = previous_outputs
+                .iter()
+                .map(|(path, value)| (path, fs.buck_out_path_resolver().resolve_gen(path), value))
+                .collect();
+
+            let is_match = ctx
+                .materializer()
+                .declare_match(
+                    resolved
+                        .iter()
+                        .map(|(_, resolved, value)| (resolved.clone(), (*value).dupe()))
+                        .collect(),
+                )
+                .await?
+                .is_match();
+            if is_match {
+                ctx.materializer()
+                    .ensure_materialized(resolved.iter().map(|(_, p, _)| p.clone()).collect())
+                    .await?;
+                copies = resolved
+                    .into_iter()
+                    .map(|(path, resolved, _)| (resolved, dir.join(path.path())))
+                    .collect();
+            } else {
+                tracing::debug!("Previous outputs of {} were overwritten", ctx.target());
+            }
+        }
+
+        ctx.blocking_executor()
+            .execute_io_inline(|| copy_previous_outputs(fs.fs(), dir, &copies))
+            .await
+    }
+
     pub(crate) fn new(
         inner: UnregisteredRunAction,
         starlark_cli: OwnedFrozenValue,
@@ -273,8 +541,36 @@ impl Action for RunAction {
                 Some(x) => x.to_string(),
             },
             "no_outputs_cleanup".to_owned() => self.inner.no_outputs_cleanup.to_string(),
+            "scrub_env".to_owned() => self.inner.scrub_env.to_string(),
+            "incremental".to_owned() => self.inner.incremental.to_string(),
+            "network".to_owned() => self.inner.network.to_string(),
+            "quotas".to_owned() => self.inner.quotas.to_string(),
+            "remote_persistent_worker".to_owned() => match self.inner.remote_persistent_worker {
+                None => "None".to_owned(),
+                Some(protocol) => protocol.to_string(),
+            },
+            "diagnostic_outputs".to_owned() => format!(
+                "[{}]",
+                self.inner.diagnostic_outputs.iter().join(", ")
+            ),
         }
     }
+
+    fn expanded_command_line(
+        &self,
+        fs: &ExecutorFs,
+        executor_config: &CommandExecutorConfig,
+        scrubbed_local_env: Option<EnvironmentInheritance>,
+    ) -> anyhow::Result<Option<(ExpandedCommandLine, EnvironmentInheritance)>> {
+        let mut artifact_visitor = SimpleCommandLineArtifactVisitor::new();
+        let mut command = self.expand_command_line(fs, &mut artifact_visitor)?;
+        let env_inheritance = apply_local_environment(
+            &mut command.env,
+            &executor_config.executor_kind,
+            self.scrubbed_local_env(scrubbed_local_env),
+        );
+        Ok(Some((command, env_inheritance)))
+    }
 }
 
 #[async_trait]
@@ -286,10 +582,10 @@ impl IncrementalActionExecutable for RunAction {
         let knobs = ctx.run_action_knobs();
         let process_dep_files = !self.inner.dep_files.labels.is_empty() || knobs.hash_all_commands;
 
-        let dep_files = if !process_dep_files {
-            None
+        let (dep_files, previous_outputs, remote_dep_files) = if !process_dep_files {
+            (None, None, None)
         } else {
-            let (matching_result, dep_files) =
+            let (matching_result, previous_outputs, dep_files) =
                 span_async(buck2_data::MatchDepFilesStart {}, async {
                     let res: anyhow::Result<_> = try {
                         let dep_files_key =
@@ -307,6 +603,13 @@ impl IncrementalActionExecutable for RunAction {
 
                         let cli_digest = expanded.fingerprint();
 
+                        // This has to be looked up first: a miss drops the previous state.
+                        let previous_outputs = if self.inner.incremental {
+                            previous_outputs(&dep_files_key, &cli_digest, &declared_dep_files)
+                        } else {
+                            None
+                        };
+
                         let matching_result = match_or_clear_dep_file(
                             &dep_files_key,
                             &cli_digest,
@@ -318,6 +621,7 @@ impl IncrementalActionExecutable for RunAction {
 
                         (
                             matching_result,
+                            previous_outputs,
                             (
                                 dep_files_key,
                                 cli_digest,
@@ -341,7 +645,16 @@ impl IncrementalActionExecutable for RunAction {
                 ));
             }
 
-            Some(dep_files)
+            // Only looked up by the executor, once the command itself misses in the action cache.
+            let remote_dep_files = if knobs.remote_dep_files {
+                let (_, cli_digest, declared_inputs, declared_dep_files) = &dep_files;
+                RemoteDepFiles::new(cli_digest, declared_inputs, declared_dep_files, ctx)?
+                    .map(|r| -> Box<dyn RemoteDepFileLookup> { box r })
+            } else {
+                None
+            };
+
+            (Some(dep_files), previous_outputs, remote_dep_files)
         };
 
         let fs = ctx.fs();
@@ -378,6 +691,56 @@ impl IncrementalActionExecutable for RunAction {
             }));
         }
 
+        // The command is told where its diagnostic outputs go through an environment variable,
+        // since their paths depend on the action.
+        let diagnostic_outputs = if self.inner.diagnostic_outputs.is_empty() {
+            Vec::new()
+        } else {
+            let dir = fs
+                .buck_out_path_resolver()
+                .resolve_diagnostics(&ctx.target().scratch_dir());
+            env.insert(DIAGNOSTICS_DIR_ENV_VAR.to_owned(), dir.to_string());
+            self.inner.diagnostic_outputs.map(|path| dir.join(path))
+        };
+
+        let previous_outputs_dir = if self.inner.incremental {
+            let dir = fs
+                .buck_out_path_resolver()
+                .resolve_previous_outputs(&ctx.target().scratch_dir());
+            Self::stage_previous_outputs(ctx, &dir, previous_outputs).await?;
+            env.insert(PREVIOUS_OUTPUTS_DIR_ENV_VAR.to_owned(), dir.to_string());
+            Some(dir)
+        } else {
+            None
+        };
+
+        let env_inheritance = apply_local_environment(
+            &mut env,
+            &ctx.executor_config().executor_kind,
+            self.scrubbed_local_env(ctx.run_action_knobs().scrubbed_local_env),
+        );
+
+        // Platforms without persistent workers run the command as usual, so it doesn't need to
+        // be set up for one.
+        let supports_remote_persistent_workers = match &ctx.executor_config().executor_kind {
+            CommandExecutorKind::Local(..) => false,
+            CommandExecutorKind::Remote(remote) | CommandExecutorKind::Hybrid { remote, .. } => {
+                remote.re_persistent_workers
+            }
+        };
+        let remote_persistent_worker = match self.inner.remote_persistent_worker {
+            Some(protocol) if supports_remote_persistent_workers => {
+                Some(Self::remote_persistent_worker(
+                    protocol,
+                    &ctx.executor_fs(),
+                    &cli,
+                    &env,
+                    &artifact_inputs,
+                )?)
+            }
+            _ => None,
+        };
+
         // Run actions are assumed to be shared
         let host_sharing_requirements =
             HostSharingRequirements::Shared(WeightClass::Permits(self.inner.weight));
@@ -396,10 +759,30 @@ impl IncrementalActionExecutable for RunAction {
         .with_host_sharing_requirements(host_sharing_requirements)
         .with_outputs_cleanup(!self.inner.no_outputs_cleanup)
         .with_allow_cache_upload(self.inner.allow_cache_upload)
-        .with_local_environment_inheritance(EnvironmentInheritance::local_command_exclusions())
-        .with_force_full_hybrid_if_capable(self.inner.force_full_hybrid_if_capable);
+        .with_local_environment_inheritance(env_inheritance)
+        .with_force_full_hybrid_if_capable(self.inner.force_full_hybrid_if_capable)
+        .with_remote_persistent_worker(remote_persistent_worker)
+        .with_diagnostic_outputs(diagnostic_outputs)
+        .with_use_jobserver(self.inner.use_jobserver)
+        .with_network(self.inner.network)
+        .with_remote_dep_files(remote_dep_files);
+
+        let res = ctx.exec_cmd(&req).await;
+
+        // The copies are only for the command, and would otherwise stay around as large as the
+        // outputs themselves. If this is cancelled before, staging them again wipes them.
+        if let Some(dir) = previous_outputs_dir {
+            let project_fs = ctx.fs().fs();
+            if let Err(e) = ctx
+                .blocking_executor()
+                .execute_io_inline(|| project_fs.remove_path_recursive(&dir))
+                .await
+            {
+                tracing::warn!("Error removing previous outputs `{}`: {:#}", dir, e);
+            }
+        }
 
-        let (outputs, meta) = ctx.exec_cmd(&req).await?;
+        let (outputs, meta) = res?;
 
         let outputs = outputs
             .into_iter()
@@ -407,9 +790,27 @@ impl IncrementalActionExecutable for RunAction {
             .collect();
         let outputs = ActionOutputs::new(outputs);
 
+        self.check_quotas(ctx, &outputs, &meta)?;
+
         if let Some(dep_files) = dep_files {
             let (dep_files_key, cli_digest, declared_inputs, declared_dep_files) = dep_files;
 
+            if knobs.remote_dep_files {
+                if let ActionExecutionKind::Command { kind, .. } = &meta.execution_kind {
+                    if let Err(e) = upload_remote_dep_files(
+                        &cli_digest,
+                        &declared_inputs,
+                        &declared_dep_files,
+                        kind,
+                        ctx,
+                    )
+                    .await
+                    {
+                        tracing::warn!("Error uploading remote dep files: {:#}", e);
+                    }
+                }
+            }
+
             populate_dep_files(
                 dep_files_key,
                 cli_digest,
@@ -424,3 +825,85 @@ impl IncrementalActionExecutable for RunAction {
         Ok((outputs, meta))
     }
 }
+
+/// Replace the contents of `dir` with `copies`, as `(source, destination)` pairs.
+fn copy_previous_outputs(
+    fs: &ProjectRoot,
+    dir: &ProjectRelativePath,
+    copies: &[(ProjectRelativePathBuf, ProjectRelativePathBuf)],
+) -> anyhow::Result<()> {
+    fs.remove_path_recursive(dir)?;
+    fs_util::create_dir_all(&*fs.resolve(dir))?;
+    for (source, destination) in copies {
+        fs.copy(source, destination)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_run_action_defaults_executor_preference() -> anyhow::Result<()> {
+        let local_only = RunActionDefaults::new(true, false, false, 1)?;
+        let preference = |local_only_flag, prefer_local, prefer_remote| {
+            local_only
+                .executor_preference(local_only_flag, prefer_local, prefer_remote)
+                .map(|p| p.to_string())
+        };
+
+        assert_eq!("LocalRequired", preference(None, None, None)?);
+        // Turning off another flag keeps the default.
+        assert_eq!("LocalRequired", preference(None, None, Some(false))?);
+        assert_eq!("Default", preference(Some(false), None, None)?);
+        // Setting another one replaces it.
+        assert_eq!("RemotePreferred", preference(None, None, Some(true))?);
+        assert_eq!(
+            "LocalPreferred",
+            preference(Some(false), Some(true), Some(false))?
+        );
+        assert!(preference(Some(true), None, Some(true)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_previous_outputs() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let fs = ProjectRoot::new(AbsNormPathBuf::try_from(tempdir.path().to_owned())?);
+        let path = |p: &str| ProjectRelativePathBuf::unchecked_new(p.to_owned());
+
+        fs.write_file(&path("gen/__app__/app"), "new binary", false)?;
+        fs.write_file(&path("gen/__app__/dir/data"), "data", false)?;
+        // Left by an earlier run.
+        fs.write_file(&path("previous/__app__/stale"), "stale", false)?;
+
+        let dir = path("previous/__app__");
+        copy_previous_outputs(
+            &fs,
+            &dir,
+            &[
+                (path("gen/__app__/app"), path("previous/__app__/app")),
+                (path("gen/__app__/dir"), path("previous/__app__/dir")),
+            ],
+        )?;
+        assert_eq!(
+            "new binary",
+            fs_util::read_to_string(fs.resolve(&path("previous/__app__/app")))?
+        );
+        assert_eq!(
+            "data",
+            fs_util::read_to_string(fs.resolve(&path("previous/__app__/dir/data")))?
+        );
+        assert!(!fs.resolve(&path("previous/__app__/stale")).exists());
+        // The outputs are copied, not moved.
+        assert!(fs.resolve(&path("gen/__app__/app")).exists());
+
+        // Without previous outputs, the directory is empty.
+        copy_previous_outputs(&fs, &dir, &[])?;
+        assert_eq!(0, fs_util::read_dir(fs.resolve(&dir))?.count());
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Output size and runtime ceilings that a rule can declare on a `run` action.
+//!
+//! These are checked once the action has executed. Depending on what the rule asked for, a
+//! violation either fails the action or is merely reported.
+
+use std::fmt;
+use std::fmt::Display;
+use std::time::Duration;
+
+use allocative::Allocative;
+use bytesize::ByteSize;
+use either::Either;
+use gazebo::prelude::*;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub(crate) enum ActionQuotaError {
+    #[error("Invalid `max_output_size`: `{0}` ({1})")]
+    InvalidOutputSize(String, String),
+    #[error("`max_output_size` must be positive, got `{0}`")]
+    NegativeOutputSize(i32),
+    #[error("`max_runtime_seconds` must be positive, got `{0}`")]
+    InvalidRuntime(i32),
+    #[error("Invalid `quota_violation`: `{0}`, expected `warn` or `error`")]
+    InvalidSeverity(String),
+    #[error("Action exceeded its declared quotas: {}", .0.join(", "))]
+    Exceeded(Vec<String>),
+}
+
+#[derive(Debug, Copy, Clone, Dupe, Eq, PartialEq, Allocative)]
+pub(crate) enum QuotaViolationSeverity {
+    /// Report the violation, but let the action succeed.
+    Warn,
+    /// Fail the action.
+    Error,
+}
+
+impl QuotaViolationSeverity {
+    pub(crate) fn parse(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(ActionQuotaError::InvalidSeverity(s.to_owned()).into()),
+        }
+    }
+}
+
+#[derive(Debug, Allocative)]
+pub(crate) struct RunActionQuotas {
+    pub(crate) max_output_size: Option<u64>,
+    pub(crate) max_runtime: Option<Duration>,
+    pub(crate) severity: QuotaViolationSeverity,
+}
+
+impl RunActionQuotas {
+    pub(crate) fn new(
+        max_output_size: Option<Either<i32, &str>>,
+        max_runtime_seconds: Option<i32>,
+        quota_violation: &str,
+    ) -> anyhow::Result<Self> {
+        let max_output_size = match max_output_size {
+            None => None,
+            Some(Either::Left(bytes)) => Some(
+                u64::try_from(bytes).map_err(|_| ActionQuotaError::NegativeOutputSize(bytes))?,
+            ),
+            Some(Either::Right(s)) => Some(
+                s.parse::<ByteSize>()
+                    .map_err(|e| ActionQuotaError::InvalidOutputSize(s.to_owned(), e))?
+                    .as_u64(),
+            ),
+        };
+        let max_runtime = match max_runtime_seconds {
+            None => None,
+            Some(secs) if secs > 0 => Some(Duration::from_secs(secs as u64)),
+            Some(secs) => return Err(ActionQuotaError::InvalidRuntime(secs).into()),
+        };
+        Ok(Self {
+            max_output_size,
+            max_runtime,
+            severity: QuotaViolationSeverity::parse(quota_violation)?,
+        })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.max_output_size.is_none() && self.max_runtime.is_none()
+    }
+
+    /// Returns the quotas this action violated, as `(kind, limit, actual)`.
+    pub(crate) fn check(
+        &self,
+        output_size: u64,
+        wall_time: Duration,
+    ) -> Vec<(buck2_data::ActionQuotaKind, u64, u64)> {
+        let mut violations = Vec::new();
+        if let Some(limit) = self.max_output_size {
+            if output_size > limit {
+                violations.push((buck2_data::ActionQuotaKind::OutputSize, limit, output_size));
+            }
+        }
+        if let Some(limit) = self.max_runtime {
+            if wall_time > limit {
+                violations.push((
+                    buck2_data::ActionQuotaKind::Runtime,
+                    limit.as_millis() as u64,
+                    wall_time.as_millis() as u64,
+                ));
+            }
+        }
+        violations
+    }
+
+    pub(crate) fn describe_violation(
+        kind: buck2_data::ActionQuotaKind,
+        limit: u64,
+        actual: u64,
+    ) -> String {
+        match kind {
+            buck2_data::ActionQuotaKind::OutputSize => format!(
+                "output size {} exceeds `max_output_size` of {}",
+                ByteSize::b(actual),
+                ByteSize::b(limit)
+            ),
+            buck2_data::ActionQuotaKind::Runtime => format!(
+                "runtime {:.1}s exceeds `max_runtime_seconds` of {:.1}s",
+                actual as f64 / 1000.0,
+                limit as f64 / 1000.0
+            ),
+        }
+    }
+}
+
+impl Display for RunActionQuotas {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "None");
+        }
+        let mut parts = Vec::new();
+        if let Some(size) = self.max_output_size {
+            parts.push(format!("max_output_size={}", ByteSize::b(size)));
+        }
+        if let Some(runtime) = self.max_runtime {
+            parts.push(format!("max_runtime_seconds={}", runtime.as_secs()));
+        }
+        parts.push(format!(
+            "quota_violation={}",
+            match self.severity {
+                QuotaViolationSeverity::Warn => "warn",
+                QuotaViolationSeverity::Error => "error",
+            }
+        ));
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_output_size() -> anyhow::Result<()> {
+        let quotas = RunActionQuotas::new(Some(Either::Right("2GB")), None, "error")?;
+        assert_eq!(quotas.max_output_size, Some(2_000_000_000));
+        let quotas = RunActionQuotas::new(Some(Either::Left(1024)), None, "warn")?;
+        assert_eq!(quotas.max_output_size, Some(1024));
+        assert_eq!(quotas.severity, QuotaViolationSeverity::Warn);
+        assert!(RunActionQuotas::new(Some(Either::Left(-1)), None, "warn").is_err());
+        assert!(RunActionQuotas::new(None, None, "ignore").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check() -> anyhow::Result<()> {
+        let quotas = RunActionQuotas::new(Some(Either::Left(100)), Some(10), "error")?;
+        assert!(quotas.check(100, Duration::from_secs(10)).is_empty());
+        assert_eq!(
+            quotas.check(101, Duration::from_secs(11)),
+            vec![
+                (buck2_data::ActionQuotaKind::OutputSize, 100, 101),
+                (buck2_data::ActionQuotaKind::Runtime, 10_000, 11_000),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -26,10 +26,12 @@
 pub mod artifact;
 pub mod build_listener;
 pub mod calculation;
+pub mod duration_history;
 pub mod execute;
 pub mod impls;
 pub(crate) mod key;
 pub(crate) mod registry;
+pub mod signal_export;
 
 use std::borrow::Cow;
 use std::fmt::Debug;
@@ -46,6 +48,7 @@ use buck2_execute::artifact::fs::ExecutorFs;
 use buck2_execute::artifact_value::ArtifactValue;
 use buck2_execute::base_deferred_key::BaseDeferredKey;
 use buck2_execute::execute::blocking::BlockingExecutor;
+use buck2_execute::execute::environment_inheritance::EnvironmentInheritance;
 use buck2_execute::execute::request::CommandExecutionOutput;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::target::CommandExecutionTarget;
@@ -53,6 +56,7 @@ use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::re::manager::ManagedRemoteExecutionClient;
 use derivative::Derivative;
 use derive_more::Display;
+use impls::run::expanded_command_line::ExpandedCommandLine;
 use impls::run::knobs::RunActionKnobs;
 use indexmap::indexmap;
 use indexmap::IndexMap;
@@ -139,6 +143,19 @@ pub trait Action: Allocative + Debug + Send + Sync + 'static {
         indexmap! {}
     }
 
+    /// The command this action runs, if it runs one, along with how it inherits the daemon's
+    /// environment when run locally, given the `scrubbed_local_env` of the daemon. This is used
+    /// to reproduce the action by hand (see `buck2 debug action-exec`), so it should match what
+    /// the action executes.
+    fn expanded_command_line(
+        &self,
+        _fs: &ExecutorFs,
+        _executor_config: &CommandExecutorConfig,
+        _scrubbed_local_env: Option<EnvironmentInheritance>,
+    ) -> anyhow::Result<Option<(ExpandedCommandLine, EnvironmentInheritance)>> {
+        Ok(None)
+    }
+
     // TODO this probably wants more data for execution, like printing a short_name and the target
 }
 
@@ -177,6 +194,9 @@ pub trait ActionExecutionCtx: Send + Sync {
 
     fn executor_fs(&self) -> ExecutorFs;
 
+    /// The executor config of the execution platform this action runs on.
+    fn executor_config(&self) -> &CommandExecutorConfig;
+
     /// A `Materializer` used for expensive materializations
     fn materializer(&self) -> &dyn Materializer;
 
@@ -0,0 +1,294 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Export of a feature record for each action executed during a build.
+//!
+//! The records are meant for training models that predict how long actions take, to in turn
+//! inform scheduling. A record is exported as each action completes, by a background thread so
+//! that a slow sink doesn't hold up the build, and whether actions were on the critical path is
+//! exported as one last record once the build finishes.
+
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
+
+/// How many records can wait for the sink before new ones are dropped.
+const EXPORT_QUEUE_SIZE: usize = 10000;
+
+/// The most records exported at once.
+const MAX_EXPORT_BATCH: usize = 1000;
+
+/// Identifies an action within a build.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActionName {
+    pub owner: String,
+    pub category: String,
+    pub identifier: Option<String>,
+}
+
+/// The features of one executed action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActionFeatures {
+    #[serde(flatten)]
+    pub name: ActionName,
+    /// The total size of the inputs of the action, in bytes.
+    pub input_size: u64,
+    /// The total size of the outputs of the action, in bytes.
+    pub output_size: u64,
+    #[serde(serialize_with = "serialize_millis")]
+    pub duration: Duration,
+    /// How the action was executed: local, remote, action cache, etc.
+    pub execution_kind: String,
+}
+
+fn serialize_millis<S: serde::Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_u64(d.as_millis() as u64)
+}
+
+/// A record exported for a build.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+pub enum BuildSignalRecord {
+    /// An action completed.
+    Action(ActionFeatures),
+    /// The build finished, and these are the actions on its critical path, in order.
+    CriticalPath { actions: Vec<ActionName> },
+}
+
+/// Where the records of the actions executed during a build are sent. Called from a thread that
+/// may block.
+pub trait BuildSignalSink: Send + Sync + 'static {
+    fn export(&self, records: &[BuildSignalRecord]) -> anyhow::Result<()>;
+}
+
+/// Appends records to a file, one JSON object per line.
+pub struct JsonLinesSignalSink {
+    path: AbsNormPathBuf,
+}
+
+impl JsonLinesSignalSink {
+    pub fn new(path: AbsNormPathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl BuildSignalSink for JsonLinesSignalSink {
+    fn export(&self, records: &[BuildSignalRecord]) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs_util::create_dir_all(parent)?;
+        }
+
+        let mut buf = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut buf, record)?;
+            buf.push(b'\n');
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Error opening `{}`", self.path))?;
+        file.write_all(&buf)
+            .with_context(|| format!("Error writing to `{}`", self.path))?;
+        Ok(())
+    }
+}
+
+/// Sends records to a sink from a blocking thread, for the duration of one build.
+pub struct SignalExporter {
+    sender: mpsc::Sender<BuildSignalRecord>,
+    task: JoinHandle<()>,
+    dropped: u64,
+}
+
+impl SignalExporter {
+    pub fn start(sink: Arc<dyn BuildSignalSink>) -> Self {
+        Self::start_with_queue_size(sink, EXPORT_QUEUE_SIZE)
+    }
+
+    fn start_with_queue_size(sink: Arc<dyn BuildSignalSink>, queue_size: usize) -> Self {
+        let (sender, mut receiver) = mpsc::channel(queue_size);
+        let task = tokio::task::spawn_blocking(move || {
+            let mut batch = Vec::new();
+            while let Some(record) = receiver.blocking_recv() {
+                batch.push(record);
+                while batch.len() < MAX_EXPORT_BATCH {
+                    match receiver.try_recv() {
+                        Ok(record) => batch.push(record),
+                        Err(_) => break,
+                    }
+                }
+                if let Err(e) = sink.export(&batch) {
+                    tracing::warn!("Error exporting build signals: {:#}", e);
+                }
+                batch.clear();
+            }
+        });
+        Self {
+            sender,
+            task,
+            dropped: 0,
+        }
+    }
+
+    /// Queues `record` for export, or drops it if the sink is too far behind.
+    pub fn export(&mut self, record: BuildSignalRecord) {
+        match self.sender.try_send(record) {
+            Ok(()) | Err(TrySendError::Closed(_)) => {}
+            Err(TrySendError::Full(_)) => self.dropped += 1,
+        }
+    }
+
+    /// Waits for the queued records to be exported.
+    pub async fn finish(self) {
+        drop(self.sender);
+        if let Err(e) = self.task.await {
+            tracing::warn!("Error exporting build signals: {:#}", e);
+        }
+        if self.dropped > 0 {
+            tracing::warn!(
+                "Dropped {} build signals because the sink was too slow",
+                self.dropped
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gazebo::prelude::*;
+    use parking_lot::Mutex;
+
+    use super::*;
+
+    fn action(owner: &str) -> BuildSignalRecord {
+        BuildSignalRecord::Action(ActionFeatures {
+            name: ActionName {
+                owner: owner.to_owned(),
+                category: "cxx_compile".to_owned(),
+                identifier: Some("bar.cpp".to_owned()),
+            },
+            input_size: 10,
+            output_size: 20,
+            duration: Duration::from_millis(1500),
+            execution_kind: "Local".to_owned(),
+        })
+    }
+
+    #[derive(Default)]
+    struct CollectingSink {
+        batches: Mutex<Vec<Vec<BuildSignalRecord>>>,
+    }
+
+    impl BuildSignalSink for CollectingSink {
+        fn export(&self, records: &[BuildSignalRecord]) -> anyhow::Result<()> {
+            self.batches.lock().push(records.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_string(&action("root//foo:bar")).unwrap(),
+            r#"{"record":"action","owner":"root//foo:bar","category":"cxx_compile","identifier":"bar.cpp","input_size":10,"output_size":20,"duration":1500,"execution_kind":"Local"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&BuildSignalRecord::CriticalPath {
+                actions: vec![ActionName {
+                    owner: "root//foo:bar".to_owned(),
+                    category: "cxx_link".to_owned(),
+                    identifier: None,
+                }]
+            })
+            .unwrap(),
+            r#"{"record":"critical_path","actions":[{"owner":"root//foo:bar","category":"cxx_link","identifier":null}]}"#
+        );
+    }
+
+    #[test]
+    fn test_json_lines_sink_appends() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = AbsNormPathBuf::try_from(dir.path().join("signals/build.jsonl"))?;
+        let sink = JsonLinesSignalSink::new(path.clone());
+        sink.export(&[action("root//:a")])?;
+        sink.export(&[action("root//:b"), action("root//:c")])?;
+
+        let lines = fs_util::read_to_string(&path)?
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["owner"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["root//:a", "root//:b", "root//:c"], lines);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_exporter_exports_everything_in_order() {
+        let sink = Arc::new(CollectingSink::default());
+        let mut exporter = SignalExporter::start(sink.dupe() as Arc<dyn BuildSignalSink>);
+        let records = (0..100)
+            .map(|i| action(&format!("root//:{}", i)))
+            .collect::<Vec<_>>();
+        for record in &records {
+            exporter.export(record.clone());
+        }
+        exporter.finish().await;
+
+        let batches = sink.batches.lock();
+        assert!(batches.iter().all(|batch| !batch.is_empty()));
+        assert_eq!(records, batches.concat());
+    }
+
+    #[tokio::test]
+    async fn test_exporter_drops_when_full() {
+        // Blocks the export of the first record until we let it go.
+        struct BlockedSink {
+            unblock: Mutex<std::sync::mpsc::Receiver<()>>,
+            exported: Mutex<Vec<BuildSignalRecord>>,
+        }
+
+        impl BuildSignalSink for BlockedSink {
+            fn export(&self, records: &[BuildSignalRecord]) -> anyhow::Result<()> {
+                self.unblock.lock().recv()?;
+                self.exported.lock().extend(records.iter().cloned());
+                Ok(())
+            }
+        }
+
+        let (unblock, blocked) = std::sync::mpsc::channel();
+        let sink = Arc::new(BlockedSink {
+            unblock: Mutex::new(blocked),
+            exported: Mutex::new(Vec::new()),
+        });
+        let mut exporter =
+            SignalExporter::start_with_queue_size(sink.dupe() as Arc<dyn BuildSignalSink>, 1);
+
+        // Whether or not the thread took the first record yet, at most two fit.
+        for i in 0..10 {
+            exporter.export(action(&format!("root//:{}", i)));
+        }
+        assert!(exporter.dropped >= 8, "{}", exporter.dropped);
+        let dropped = exporter.dropped;
+
+        for _ in 0..10 {
+            let _ignored = unblock.send(());
+        }
+        exporter.finish().await;
+        assert_eq!(10 - dropped as usize, sink.exported.lock().len());
+    }
+}
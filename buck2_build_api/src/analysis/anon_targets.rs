@@ -37,6 +37,8 @@ use buck2_core::target::TargetLabel;
 use buck2_core::target::TargetName;
 use buck2_core::unsafe_send_future::UnsafeSendFuture;
 use buck2_execute::anon_target::AnonTarget;
+use buck2_execute::anon_target::AnonTargetArtifact;
+use buck2_execute::anon_target::AnonTargetSource;
 use buck2_execute::base_deferred_key::BaseDeferredKey;
 use buck2_interpreter::starlark_promise::StarlarkPromise;
 use buck2_interpreter::types::label::Label;
@@ -46,6 +48,7 @@ use buck2_node::attrs::attr_type::attr_literal::AttrLiteral;
 use buck2_node::attrs::attr_type::dep::DepAttr;
 use buck2_node::attrs::attr_type::dep::DepAttrTransition;
 use buck2_node::attrs::attr_type::dep::DepAttrType;
+use buck2_node::attrs::attr_type::source::SourceAttrType;
 use buck2_node::attrs::attr_type::AttrTypeInner;
 use buck2_node::attrs::coerced_attr::CoercedAttr;
 use buck2_node::attrs::coerced_path::CoercedPath;
@@ -64,28 +67,35 @@ use futures::future;
 use futures::stream::FuturesUnordered;
 use futures::Future;
 use gazebo::prelude::*;
+use more_futures::spawn::current_task_cancellation;
+use more_futures::spawn::CancellationObserver;
 use ref_cast::RefCast;
 use starlark::collections::SmallMap;
 use starlark::environment::Module;
 use starlark::eval::Evaluator;
 use starlark::values::dict::DictOf;
+use starlark::values::list::List;
 use starlark::values::structs::Struct;
 use starlark::values::Trace;
 use starlark::values::Value;
 use starlark::values::ValueTyped;
 use thiserror::Error;
 
+use crate::actions::artifact::Artifact;
+use crate::analysis::analysis_result;
 use crate::analysis::calculation::get_rule_impl;
 use crate::analysis::calculation::RuleAnalysisCalculation;
+use crate::analysis::cancellable_analysis;
 use crate::analysis::registry::AnalysisRegistry;
+use crate::analysis::stop_when_cancelled;
+use crate::analysis::take_analysis_registry;
 use crate::analysis::AnalysisResult;
 use crate::analysis::RuleAnalysisAttrResolutionContext;
 use crate::analysis::RuleImplFunction;
 use crate::attrs::resolve::configured_attr::ConfiguredAttrExt;
-use crate::deferred::types::DeferredTable;
+use crate::interpreter::rule_defs::artifact::StarlarkArtifact;
+use crate::interpreter::rule_defs::artifact::ValueAsArtifactLike;
 use crate::interpreter::rule_defs::context::AnalysisContext;
-use crate::interpreter::rule_defs::provider::collection::FrozenProviderCollectionValue;
-use crate::interpreter::rule_defs::provider::collection::ProviderCollection;
 use crate::interpreter::rule_defs::provider::dependency::Dependency;
 use crate::interpreter::rule_defs::rule::FrozenRuleCallable;
 use crate::keep_going;
@@ -123,6 +133,14 @@ enum AnonTargetsError {
     MissingAttribute(String),
     #[error("Invalid `attr.dep` value, expected `dependency`, got `{0}`")]
     InvalidDep(String),
+    #[error("Invalid `attr.source` value, expected an artifact, got `{0}`")]
+    InvalidSource(String),
+    #[error("Source attributes of anon targets take artifacts rather than paths, got `{0}`")]
+    PathNotArtifact(String),
+    #[error("Anon target source `{0}` is not an artifact")]
+    SourceNotArtifact(String),
+    #[error("Invalid `attr.list(attr.source())` value, expected a list of artifacts, got `{0}`")]
+    InvalidSourceList(String),
 }
 
 #[repr(transparent)]
@@ -143,7 +161,9 @@ impl AnonTargetKey {
 
         let entries = attributes.collect_entries();
         let attrs_spec = rule.attributes();
+        let ctx = AnonAttrCtx::with_artifacts();
         let mut attrs = OrderedMap::with_capacity(attrs_spec.attributes.len());
+        let mut sources = OrderedMap::new();
         for (k, v) in entries {
             if k == "name" {
                 name = Some(Self::coerce_name(v)?);
@@ -153,17 +173,25 @@ impl AnonTargetKey {
                 let attr = attrs_spec
                     .attribute(k)
                     .ok_or_else(|| AnonTargetsError::UnknownAttribute(k.to_owned()))?;
-                attrs.insert(
-                    k.to_owned(),
-                    Self::coerce_attr(attr, v)
-                        .with_context(|| format!("when coercing attribute `{}`", k))?,
-                );
+                let context = || format!("when coercing attribute `{}`", k);
+                match ctx.coerce_source(attr, v).with_context(context)? {
+                    Some(source) => {
+                        sources.insert(k.to_owned(), source);
+                    }
+                    None => {
+                        attrs.insert(
+                            k.to_owned(),
+                            Self::coerce_attr(&ctx, attr, v).with_context(context)?,
+                        );
+                    }
+                }
             }
         }
         for (k, _, a) in attrs_spec.attr_specs() {
-            if !attrs.contains_key(k) && !internal_attrs.contains_key(k) {
+            if !attrs.contains_key(k) && !sources.contains_key(k) && !internal_attrs.contains_key(k)
+            {
                 if let Some(x) = &a.default {
-                    attrs.insert(k.to_owned(), Self::configure_attr(x)?);
+                    attrs.insert(k.to_owned(), configure_default_attr(x)?);
                 } else {
                     return Err(AnonTargetsError::MissingAttribute(k.to_owned()).into());
                 }
@@ -181,6 +209,7 @@ impl AnonTargetKey {
             rule.rule_type().dupe(),
             name,
             attrs.into(),
+            sources.into(),
             execution_platform.cfg(),
         ))))
     }
@@ -223,10 +252,19 @@ impl AnonTargetKey {
         }
     }
 
-    fn coerce_attr(attr: &Attribute, x: Value) -> anyhow::Result<ConfiguredAttr> {
+    fn coerce_attr(
+        ctx: &AnonAttrCtx,
+        attr: &Attribute,
+        x: Value,
+    ) -> anyhow::Result<ConfiguredAttr> {
         fn unpack_dep(x: &AttrTypeInner) -> Option<DepAttrType> {
             match x {
-                AttrTypeInner::Dep(d) => Some(d.dupe()),
+                // Dependencies are passed already configured, so the transitions of
+                // `attr.exec_dep` and `attr.transition_dep` have been applied by the caller.
+                AttrTypeInner::Dep(d) => Some(DepAttrType {
+                    required_providers: d.required_providers.dupe(),
+                    transition: DepAttrTransition::Identity,
+                }),
                 AttrTypeInner::ConfiguredDep(d) => Some(DepAttrType {
                     required_providers: d.required_providers.dupe(),
                     transition: DepAttrTransition::Identity,
@@ -235,7 +273,6 @@ impl AnonTargetKey {
             }
         }
 
-        let ctx = AnonAttrCtx::new();
         let a = match unpack_dep(&attr.coercer.0) {
             Some(attr_type) => match Dependency::from_value(x) {
                 Some(dep) => AttrLiteral::ConfiguredDep(box DepAttr::new(
@@ -244,16 +281,9 @@ impl AnonTargetKey {
                 )),
                 _ => return Err(AnonTargetsError::InvalidDep(x.get_type().to_owned()).into()),
             },
-            _ => attr
-                .coercer
-                .0
-                .coerce_item(AttrIsConfigurable::No, &ctx, x)?,
+            _ => attr.coercer.0.coerce_item(AttrIsConfigurable::No, ctx, x)?,
         };
-        a.configure(&ctx)
-    }
-
-    fn configure_attr(x: &CoercedAttr) -> anyhow::Result<ConfiguredAttr> {
-        x.configure(&AnonAttrCtx::new())
+        a.configure(ctx)
     }
 
     async fn resolve(&self, dice: &DiceComputations) -> anyhow::Result<AnalysisResult> {
@@ -277,7 +307,14 @@ impl AnonTargetKey {
         &'a self,
         dice: &'a DiceComputations,
     ) -> impl Future<Output = anyhow::Result<AnalysisResult>> + Send + 'a {
-        let fut = async move { self.run_analysis_impl(dice).await };
+        let fut = async move {
+            let cancellation = current_task_cancellation();
+            cancellable_analysis(
+                cancellation.as_ref(),
+                self.run_analysis_impl(dice, cancellation.as_ref()),
+            )
+            .await
+        };
         unsafe { UnsafeSendFuture::new_encapsulates_starlark(fut) }
     }
 
@@ -298,10 +335,15 @@ impl AnonTargetKey {
         Ok(traversal.0)
     }
 
-    async fn run_analysis_impl(&self, dice: &DiceComputations) -> anyhow::Result<AnalysisResult> {
+    async fn run_analysis_impl(
+        &self,
+        dice: &DiceComputations,
+        cancellation: Option<&CancellationObserver>,
+    ) -> anyhow::Result<AnalysisResult> {
         let rule_impl = get_rule_impl(dice, self.0.rule_type()).await?;
         let env = Module::new();
         let mut eval = Evaluator::new(&env);
+        stop_when_cancelled(&mut eval, cancellation);
 
         let dep_analysis_results: HashMap<_, _> = keep_going::try_join_all(
             self.deps()?
@@ -322,15 +364,35 @@ impl AnonTargetKey {
             module: &env,
             dep_analysis_results,
             query_results: HashMap::new(),
+            perturb_ordering: false,
         };
 
-        let mut resolved_attrs = SmallMap::with_capacity(self.0.attrs().len());
+        let mut resolved_attrs =
+            SmallMap::with_capacity(self.0.attrs().len() + self.0.sources().len());
         for (name, attr) in self.0.attrs().iter() {
             resolved_attrs.insert(
                 env.heap().alloc_str(name),
                 attr.resolve_single(&resolution_ctx)?,
             );
         }
+        let resolve_artifact = |artifact: &AnonTargetArtifact| -> anyhow::Result<Value> {
+            let artifact = artifact
+                .downcast_ref::<Artifact>()
+                .ok_or_else(|| AnonTargetsError::SourceNotArtifact(format!("{:?}", artifact)))?;
+            Ok(env.heap().alloc(StarlarkArtifact::new(artifact.dupe())))
+        };
+        for (name, source) in self.0.sources().iter() {
+            let value = match source {
+                AnonTargetSource::Artifact(artifact) => resolve_artifact(artifact)?,
+                AnonTargetSource::List(artifacts) => env.heap().alloc_list_iter(
+                    artifacts
+                        .iter()
+                        .map(resolve_artifact)
+                        .collect::<anyhow::Result<Vec<_>>>()?,
+                ),
+            };
+            resolved_attrs.insert(env.heap().alloc_str(name), value);
+        }
         let attributes = env.heap().alloc(Struct::new(resolved_attrs));
 
         let exec_resolution = ExecutionPlatformResolution::new(
@@ -345,6 +407,8 @@ impl AnonTargetKey {
             Vec::new(),
         );
 
+        // Like for regular targets, `rule_impl.invoke` sets the defaults of the run actions from
+        // the rule and the attributes of the anon target.
         let registry = AnalysisRegistry::new_from_owner(
             BaseDeferredKey::AnonTarget(self.0.dupe()),
             exec_resolution,
@@ -364,28 +428,33 @@ impl AnonTargetKey {
 
         let list_res = rule_impl.invoke(&mut eval, ctx)?;
         ctx.run_promises(dice, &mut eval).await?;
-        let res_typed = ProviderCollection::try_from_value(list_res)?;
-        let res = env.heap().alloc(res_typed);
-        env.set("", res);
-
-        // Pull the ctx object back out, and steal ctx.action's state back
-        let analysis_registry = ctx.take_state();
+        let analysis_registry = take_analysis_registry(&env, ctx, list_res)?;
         let (frozen_env, deferreds) = analysis_registry.finalize(&env)(env)?;
+        analysis_result(&frozen_env, deferreds, None)
+    }
+}
 
-        let res = frozen_env.get("").unwrap();
-        let provider_collection = FrozenProviderCollectionValue::try_from_value(res)
-            .expect("just created this, this shouldn't happen");
+/// Configure the default value of an attribute outside of a build, where selects, transitions
+/// and paths aren't available.
+pub(crate) fn configure_default_attr(x: &CoercedAttr) -> anyhow::Result<ConfiguredAttr> {
+    x.configure(&AnonAttrCtx::new())
+}
 
-        // this could look nicer if we had the entire analysis be a deferred
-        let deferred = DeferredTable::new(deferreds.take_result()?);
-        Ok(AnalysisResult::new(provider_collection, deferred, None))
-    }
+/// How `AnonAttrCtx` coerces paths, which it can't resolve, since anon targets have no package.
+#[derive(Clone, Copy, Dupe, Debug, PartialEq, Eq)]
+enum AnonPathCoercion {
+    /// Paths are rejected.
+    Reject,
+    /// Source attributes take artifacts instead of paths (e.g. sources, or outputs of the actions
+    /// of the rule that calls `anon_targets`), which are kept apart from the other attributes.
+    Artifacts,
 }
 
 /// Several attribute functions need a context, make one that is mostly useless.
 struct AnonAttrCtx {
     cfg: Configuration,
     transitions: OrderedMap<Arc<TransitionId>, Arc<TransitionApplied>>,
+    paths: AnonPathCoercion,
 }
 
 impl AnonAttrCtx {
@@ -393,8 +462,69 @@ impl AnonAttrCtx {
         Self {
             cfg: Configuration::unspecified(),
             transitions: OrderedMap::new(),
+            paths: AnonPathCoercion::Reject,
+        }
+    }
+
+    fn with_artifacts() -> Self {
+        Self {
+            paths: AnonPathCoercion::Artifacts,
+            ..Self::new()
         }
     }
+
+    /// Coerces the value of a source attribute (`attr.source()`, or an `attr.option` or
+    /// `attr.list` of them) to its artifacts in the `Artifacts` mode. Returns `None` for the other
+    /// attributes, or in the `Reject` mode, where they are coerced like any attribute.
+    fn coerce_source(
+        &self,
+        attr: &Attribute,
+        x: Value,
+    ) -> anyhow::Result<Option<AnonTargetSource>> {
+        fn unpack_source(x: &AttrTypeInner) -> Option<&SourceAttrType> {
+            match x {
+                AttrTypeInner::Source(source) => Some(source),
+                _ => None,
+            }
+        }
+
+        let coerce_artifact = |source: &SourceAttrType, x: Value| -> anyhow::Result<_> {
+            if let Some(path) = x.unpack_str() {
+                self.coerce_path(path, source.allow_directory)?;
+            }
+            let artifact = x
+                .as_artifact()
+                .ok_or_else(|| AnonTargetsError::InvalidSource(x.get_type().to_owned()))?;
+            Ok(AnonTargetArtifact::new(artifact.get_bound_artifact()?))
+        };
+
+        if self.paths != AnonPathCoercion::Artifacts {
+            return Ok(None);
+        }
+        Ok(match &attr.coercer.0 {
+            AttrTypeInner::Source(source) => {
+                Some(AnonTargetSource::Artifact(coerce_artifact(source, x)?))
+            }
+            AttrTypeInner::Option(option) if !x.is_none() => match unpack_source(&option.inner.0) {
+                Some(source) => Some(AnonTargetSource::Artifact(coerce_artifact(source, x)?)),
+                None => None,
+            },
+            AttrTypeInner::List(list) => match unpack_source(&list.inner.0) {
+                Some(source) => {
+                    let xs = List::from_value(x).ok_or_else(|| {
+                        AnonTargetsError::InvalidSourceList(x.get_type().to_owned())
+                    })?;
+                    Some(AnonTargetSource::List(
+                        xs.iter()
+                            .map(|x| coerce_artifact(source, x))
+                            .collect::<anyhow::Result<_>>()?,
+                    ))
+                }
+                None => None,
+            },
+            _ => None,
+        })
+    }
 }
 
 impl AttrCoercionContext for AnonAttrCtx {
@@ -403,7 +533,11 @@ impl AttrCoercionContext for AnonAttrCtx {
     }
 
     fn coerce_path(&self, value: &str, _allow_directory: bool) -> anyhow::Result<CoercedPath> {
-        Err(AnonTargetsError::CantParseDuringCoerce(value.to_owned()).into())
+        Err(match self.paths {
+            AnonPathCoercion::Reject => AnonTargetsError::CantParseDuringCoerce(value.to_owned()),
+            AnonPathCoercion::Artifacts => AnonTargetsError::PathNotArtifact(value.to_owned()),
+        }
+        .into())
     }
 
     fn coerce_target_pattern(&self, pattern: &str) -> anyhow::Result<ParsedPattern<TargetPattern>> {
@@ -562,7 +696,112 @@ impl<'v> AnonTargetsRegistry<'v> {
 
 #[cfg(test)]
 mod test {
+    use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+    use buck2_core::target::testing::TargetLabelExt;
+    use buck2_node::attrs::attr_type::AttrType;
+    use starlark::values::Heap;
+
     use super::*;
+    use crate::actions::artifact::build_artifact::BuildArtifact;
+    use crate::actions::artifact::testing::BuildArtifactTestingExt;
+    use crate::deferred::types::testing::DeferredIdExt;
+    use crate::deferred::types::DeferredId;
+
+    fn artifact<'v>(heap: &'v Heap, path: &str, id: u32) -> Value<'v> {
+        heap.alloc(StarlarkArtifact::new(Artifact::from(
+            BuildArtifact::testing_new(
+                TargetLabel::testing_parse("root//foo:bar").configure(Configuration::testing_new()),
+                ForwardRelativePathBuf::unchecked_new(path.to_owned()),
+                DeferredId::testing_new(id),
+            ),
+        )))
+    }
+
+    fn attribute(coercer: AttrType) -> Attribute {
+        Attribute::new_internal(None, String::new(), coercer)
+    }
+
+    #[test]
+    fn coerce_source() -> anyhow::Result<()> {
+        let heap = Heap::new();
+        let ctx = AnonAttrCtx::with_artifacts();
+        let source = attribute(AttrType::source(false));
+
+        let out = ctx.coerce_source(&source, artifact(&heap, "out.txt", 0))?;
+        assert!(matches!(out, Some(AnonTargetSource::Artifact(_))));
+        assert_eq!(
+            out,
+            ctx.coerce_source(&source, artifact(&heap, "out.txt", 0))?
+        );
+        // The same path, declared by another action.
+        assert_ne!(
+            out,
+            ctx.coerce_source(&source, artifact(&heap, "out.txt", 1))?
+        );
+
+        let err = ctx
+            .coerce_source(&source, heap.alloc("out.txt"))
+            .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("take artifacts rather than paths"),
+            "{:#}",
+            err
+        );
+        assert!(ctx.coerce_source(&source, heap.alloc(1)).is_err());
+
+        // Only the `Artifacts` mode coerces sources.
+        assert_eq!(
+            None,
+            AnonAttrCtx::new().coerce_source(&source, artifact(&heap, "out.txt", 0))?
+        );
+        let err = AnonAttrCtx::new()
+            .coerce_path("out.txt", false)
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("can't parse strings"));
+        Ok(())
+    }
+
+    #[test]
+    fn coerce_source_option_and_list() -> anyhow::Result<()> {
+        let heap = Heap::new();
+        let ctx = AnonAttrCtx::with_artifacts();
+
+        let option = attribute(AttrType::option(AttrType::source(false)));
+        assert_eq!(None, ctx.coerce_source(&option, Value::new_none())?);
+        assert!(matches!(
+            ctx.coerce_source(&option, artifact(&heap, "out.txt", 0))?,
+            Some(AnonTargetSource::Artifact(_))
+        ));
+
+        let list = attribute(AttrType::list(AttrType::source(false)));
+        match ctx.coerce_source(
+            &list,
+            heap.alloc_list(&[artifact(&heap, "a.txt", 0), artifact(&heap, "b.txt", 0)]),
+        )? {
+            Some(AnonTargetSource::List(artifacts)) => assert_eq!(2, artifacts.len()),
+            x => panic!("expected a list of artifacts, got {:?}", x),
+        }
+        assert!(ctx
+            .coerce_source(&list, heap.alloc_list(&[heap.alloc("a.txt")]))
+            .is_err());
+        assert!(ctx
+            .coerce_source(&list, artifact(&heap, "a.txt", 0))
+            .is_err());
+
+        // Other attributes are coerced like in any target.
+        assert_eq!(
+            None,
+            ctx.coerce_source(&attribute(AttrType::string()), heap.alloc("a.txt"))?
+        );
+        assert_eq!(
+            None,
+            ctx.coerce_source(
+                &attribute(AttrType::list(AttrType::string())),
+                heap.alloc_list(&[heap.alloc("a.txt")])
+            )?
+        );
+        Ok(())
+    }
 
     #[test]
     fn anon_target_name() {
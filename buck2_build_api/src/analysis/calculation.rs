@@ -21,9 +21,11 @@ use buck2_core::target::ConfiguredTargetLabel;
 use buck2_core::target::TargetLabel;
 use buck2_data::ToProtoMessage;
 use buck2_events::dispatch::span_async;
+use buck2_events::warnings::WithWarnings;
 use buck2_interpreter::common::StarlarkModulePath;
 use buck2_interpreter::dice::starlark_profiler::GetStarlarkProfilerInstrumentation;
 use buck2_interpreter::dice::HasCalculationDelegate;
+use buck2_interpreter::dice::HasEvents;
 use buck2_interpreter::starlark_profiler::StarlarkProfileDataAndStats;
 use buck2_interpreter::starlark_profiler::StarlarkProfileModeOrInstrumentation;
 use buck2_node::attrs::attr_type::query::ResolvedQueryLiterals;
@@ -35,7 +37,9 @@ use buck2_node::rule_type::StarlarkRuleType;
 use buck2_query::query::syntax::simple::eval::evaluator::QueryEvaluator;
 use buck2_query::query::syntax::simple::eval::label_indexed::LabelIndexedSet;
 use dice::DiceComputations;
+use dice::DiceProjectionComputations;
 use dice::Key;
+use dice::ProjectionKey;
 use futures::stream::FuturesOrdered;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
@@ -43,13 +47,18 @@ use gazebo::prelude::*;
 use starlark::eval::ProfileMode;
 
 use crate::analysis::calculation::keys::AnalysisKey;
+use crate::analysis::calculation::keys::AnalysisProvidersKey;
 use crate::analysis::configured_graph::AnalysisConfiguredGraphQueryDelegate;
 use crate::analysis::configured_graph::AnalysisDiceQueryDelegate;
 use crate::analysis::get_user_defined_rule_impl;
+use crate::analysis::ordering_audit::audit_ordering;
+use crate::analysis::ordering_audit::ordering_audit_enabled;
 use crate::analysis::run_analysis;
 use crate::analysis::AnalysisResult;
 use crate::analysis::RuleImplFunction;
 use crate::attrs::resolve::ctx::AnalysisQueryResult;
+use crate::graph_limits::GraphLimitError;
+use crate::graph_limits::HasGraphSizeTracker;
 use crate::interpreter::rule_defs::provider::collection::FrozenProviderCollectionValue;
 use crate::keep_going;
 use crate::nodes::calculation::NodeCalculation;
@@ -87,12 +96,22 @@ impl RuleAnalysisCalculation for DiceComputations {
     ) -> SharedResult<MaybeCompatible<AnalysisResult>> {
         #[async_trait]
         impl Key for AnalysisKey {
-            type Value = SharedResult<MaybeCompatible<AnalysisResult>>;
+            type Value = WithWarnings<SharedResult<MaybeCompatible<AnalysisResult>>>;
             async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
-                let profile_mode = ctx.get_profile_mode_for_intermediate_analysis().await?;
-                Ok(get_analysis_result(ctx, &self.0, &profile_mode)
-                    .await
-                    .with_context(|| format!("When running analysis for `{}`", &self.0))?)
+                WithWarnings::compute(async {
+                    let profile_mode = ctx.get_profile_mode_for_intermediate_analysis().await?;
+                    Ok(get_analysis_result(ctx, &self.0, &profile_mode)
+                        .await
+                        .with_context(|| format!("When running analysis for `{}`", &self.0))?)
+                })
+                .await
+            }
+
+            fn validity(x: &Self::Value) -> bool {
+                match &x.value {
+                    Err(e) => !GraphLimitError::is_cause_of(e),
+                    Ok(_) => true,
+                }
             }
 
             fn equality(_: &Self::Value, _: &Self::Value) -> bool {
@@ -102,7 +121,9 @@ impl RuleAnalysisCalculation for DiceComputations {
             }
         }
 
-        self.compute(&AnalysisKey(target.dupe())).await?
+        self.compute(&AnalysisKey(target.dupe()))
+            .await?
+            .into_replayed(self.per_transaction_data().get_dispatcher())
     }
 
     async fn get_configuration_analysis_result(
@@ -121,14 +142,58 @@ impl RuleAnalysisCalculation for DiceComputations {
         &self,
         target: &ConfiguredProvidersLabel,
     ) -> SharedResult<MaybeCompatible<FrozenProviderCollectionValue>> {
-        let analysis = self.get_analysis_result(target.target()).await?;
+        let providers = get_analysis_providers(self, target.target()).await?;
 
-        analysis
-            .try_map(|analysis| analysis.lookup_inner(target))
+        providers
+            .try_map(|providers| providers.lookup_inner(target))
             .shared_error()
     }
 }
 
+/// The providers of a target. Computations depending on them are only invalidated when the target
+/// is reanalyzed to different providers, rather than whenever it is reanalyzed, so no-op changes
+/// don't cascade reanalysis through the graph.
+async fn get_analysis_providers(
+    ctx: &DiceComputations,
+    target: &ConfiguredTargetLabel,
+) -> SharedResult<MaybeCompatible<FrozenProviderCollectionValue>> {
+    impl ProjectionKey for AnalysisProvidersKey {
+        type DeriveFromKey = AnalysisKey;
+        type Value = SharedResult<MaybeCompatible<FrozenProviderCollectionValue>>;
+
+        fn compute(
+            &self,
+            analysis: &WithWarnings<SharedResult<MaybeCompatible<AnalysisResult>>>,
+            _ctx: &DiceProjectionComputations,
+        ) -> Self::Value {
+            Ok(analysis
+                .value
+                .as_ref()
+                .map_err(|e| e.dupe())?
+                .dupe()
+                .map(|analysis| analysis.providers().dupe()))
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            // The actions of the target aren't compared: they are looked up through
+            // `AnalysisKey`, and the artifacts in the providers refer to them by key.
+            match (x, y) {
+                (Ok(MaybeCompatible::Compatible(x)), Ok(MaybeCompatible::Compatible(y))) => {
+                    x.equals(y).unwrap_or(false)
+                }
+                (Ok(MaybeCompatible::Incompatible(x)), Ok(MaybeCompatible::Incompatible(y))) => {
+                    x == y
+                }
+                _ => false,
+            }
+        }
+    }
+
+    ctx.compute_opaque(&AnalysisKey(target.dupe()))
+        .await?
+        .projection(&AnalysisProvidersKey)?
+}
+
 pub async fn resolve_queries(
     ctx: &DiceComputations,
     configured_node: &ConfiguredTargetNode,
@@ -197,10 +262,9 @@ async fn resolve_queries_impl(
             let label = node.label();
             query_results.push((
                 label.dupe(),
-                ctx.get_analysis_result(label)
+                get_analysis_providers(ctx, label)
                     .await?
-                    .require_compatible()?
-                    .provider_collection,
+                    .require_compatible()?,
             ))
         }
 
@@ -213,13 +277,12 @@ async fn resolve_queries_impl(
 pub async fn get_dep_analysis<'v>(
     configured_node: &'v ConfiguredTargetNode,
     ctx: &DiceComputations,
-) -> anyhow::Result<Vec<(&'v ConfiguredTargetLabel, AnalysisResult)>> {
+) -> anyhow::Result<Vec<(&'v ConfiguredTargetLabel, FrozenProviderCollectionValue)>> {
     Ok(keep_going::try_join_all(
         configured_node
             .deps()
             .map(async move |dep| {
-                let res = ctx
-                    .get_analysis_result(dep.name())
+                let res = get_analysis_providers(ctx, dep.name())
                     .await
                     .and_then(|v| v.require_compatible().shared_error());
                 res.map(|x| (dep.name(), x))
@@ -270,6 +333,11 @@ async fn get_analysis_result(
 
                 let result: anyhow::Result<_> = try {
                     let query_results = resolve_queries(ctx, &configured_node).await?;
+                    let ordering_audit_inputs = if ordering_audit_enabled(ctx, target).await? {
+                        Some((dep_analysis.clone(), query_results.clone()))
+                    } else {
+                        None
+                    };
 
                     let result = span_async(
                         buck2_data::AnalysisStageStart {
@@ -286,6 +354,7 @@ async fn get_analysis_result(
                                     &rule_impl,
                                     &configured_node,
                                     profile_mode,
+                                    false,
                                 )
                                 .await,
                                 buck2_data::AnalysisStageEnd {},
@@ -294,6 +363,26 @@ async fn get_analysis_result(
                     )
                     .await?;
 
+                    if let Some((dep_analysis, query_results)) = ordering_audit_inputs {
+                        let perturbed = run_analysis(
+                            ctx,
+                            target,
+                            dep_analysis,
+                            query_results,
+                            configured_node.execution_platform_resolution(),
+                            &rule_impl,
+                            &configured_node,
+                            &StarlarkProfileModeOrInstrumentation::None,
+                            true,
+                        )
+                        .await;
+                        audit_ordering(ctx, target, &result, perturbed).await?;
+                    }
+
+                    if let Some(tracker) = ctx.get_graph_size_tracker() {
+                        tracker.record_actions(target, result.num_deferreds())?;
+                    }
+
                     profile = Some(make_analysis_profile(&result));
 
                     MaybeCompatible::Compatible(result)
@@ -312,7 +401,9 @@ async fn get_analysis_result(
         }
         RuleType::Forward => {
             assert!(dep_analysis.len() == 1);
-            Ok(MaybeCompatible::Compatible(dep_analysis.pop().unwrap().1))
+            // The forward node's result is its dep's, including the actions.
+            let (dep, _) = dep_analysis.pop().unwrap();
+            Ok(ctx.get_analysis_result(dep).await?)
         }
     }
 }
@@ -350,8 +441,8 @@ pub async fn profile_analysis(
     .context("profile_data not set (internal error)")
 }
 
-fn all_deps(node: ConfiguredTargetNode) -> LabelIndexedSet<ConfiguredTargetNode> {
-    let mut stack = vec![node];
+fn all_deps(nodes: Vec<ConfiguredTargetNode>) -> LabelIndexedSet<ConfiguredTargetNode> {
+    let mut stack = nodes;
     let mut visited = LabelIndexedSet::new();
     while let Some(node) = stack.pop() {
         if visited.insert(node.dupe()) {
@@ -361,10 +452,42 @@ fn all_deps(node: ConfiguredTargetNode) -> LabelIndexedSet<ConfiguredTargetNode>
     visited
 }
 
-pub async fn profile_analysis_recursively(
+/// Profile the analysis of `targets`, and of all their deps if `recursive`. Targets that are
+/// incompatible with their configuration are skipped. Each profile is returned along with the
+/// rule type of its target.
+pub async fn profile_analyses(
     ctx: &DiceComputations,
-    target: &ConfiguredTargetLabel,
-) -> anyhow::Result<StarlarkProfileDataAndStats> {
+    targets: &[ConfiguredTargetLabel],
+    profile_mode: &ProfileMode,
+    recursive: bool,
+) -> anyhow::Result<Vec<(RuleType, Arc<StarlarkProfileDataAndStats>)>> {
+    let mut nodes = Vec::with_capacity(targets.len());
+    let mut futures = targets
+        .iter()
+        .map(|target| ctx.get_configured_target_node(target))
+        .collect::<FuturesOrdered<_>>();
+    while let Some(node) = futures.next().await {
+        if let MaybeCompatible::Compatible(node) = node? {
+            nodes.push(node);
+        }
+    }
+
+    if !recursive {
+        let mut futures = nodes
+            .iter()
+            .map(|node| async move {
+                let profile_data = profile_analysis(ctx, node.name(), profile_mode).await?;
+                anyhow::Ok((node.rule_type().dupe(), profile_data))
+            })
+            .collect::<FuturesOrdered<_>>();
+
+        let mut profile_datas = Vec::with_capacity(nodes.len());
+        while let Some(result) = futures.next().await {
+            profile_datas.push(result?);
+        }
+        return Ok(profile_datas);
+    }
+
     // Self check.
     let profile_mode = ctx.get_profile_mode_for_intermediate_analysis().await?;
     if !matches!(
@@ -374,29 +497,29 @@ pub async fn profile_analysis_recursively(
         return Err(ProfileAnalysisError::RecursiveProfileConfiguredIncorrectly.into());
     }
 
-    let node = ctx
-        .get_configured_target_node(target)
-        .await?
-        .require_compatible()?;
-
-    let all_deps = all_deps(node);
+    let all_deps = all_deps(nodes);
 
     let mut futures = all_deps
         .iter()
-        .map(|node| ctx.get_analysis_result(node.name()))
+        .map(|node| async move {
+            (
+                node.rule_type().dupe(),
+                ctx.get_analysis_result(node.name()).await,
+            )
+        })
         .collect::<FuturesOrdered<_>>();
 
-    let mut profile_datas: Vec<Arc<StarlarkProfileDataAndStats>> = Vec::new();
-    while let Some(result) = futures.next().await {
-        profile_datas.push(
+    let mut profile_datas = Vec::with_capacity(all_deps.len());
+    while let Some((rule_type, result)) = futures.next().await {
+        profile_datas.push((
+            rule_type,
             result?
                 .require_compatible()?
                 .profile_data
                 .context("profile_data not set (internal error)")?,
-        );
+        ));
     }
-
-    StarlarkProfileDataAndStats::merge(profile_datas.iter().map(|x| &**x))
+    Ok(profile_datas)
 }
 
 mod keys {
@@ -409,6 +532,10 @@ mod keys {
     #[display(fmt = "{}", "_0")]
     pub(crate) struct AnalysisKey(pub ConfiguredTargetLabel);
 
+    #[derive(Clone, Dupe, Display, Debug, Eq, Hash, PartialEq, Allocative)]
+    #[display(fmt = "{:?}", self)]
+    pub(crate) struct AnalysisProvidersKey;
+
     #[derive(Clone, Dupe, Display, Debug, Eq, Hash, PartialEq)]
     #[display(fmt = "{}", "_0")]
     pub struct ConfiguredGraphKey(pub ConfiguredTargetLabel);
@@ -448,6 +575,7 @@ mod tests {
     use buck2_core::target::testing::TargetLabelExt;
     use buck2_core::target::TargetLabel;
     use buck2_events::dispatch::EventDispatcher;
+    use buck2_events::warnings::WithWarnings;
     use buck2_execute::execute::dice_data::set_fallback_executor_config;
     use buck2_interpreter::common::OwnedStarlarkModulePath;
     use buck2_interpreter::dice::interpreter_setup::setup_interpreter_basic;
@@ -559,7 +687,7 @@ mod tests {
             )
             .mock_and_return(
                 InterpreterResultsKey(Package::testing_new("cell", "pkg")),
-                Ok(Arc::new(eval_res)),
+                WithWarnings::new(Ok(Arc::new(eval_res))),
             )
             .mock_and_return(ExecutionPlatformsKey, Ok(None))
             .set_data(|data| data.set_testing_io_provider(&fs))
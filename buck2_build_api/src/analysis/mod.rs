@@ -25,6 +25,8 @@ use buck2_node::configuration::execution::ExecutionPlatformResolution;
 use dice::DiceComputations;
 use futures::Future;
 use gazebo::prelude::*;
+use more_futures::spawn::current_task_cancellation;
+use more_futures::spawn::CancellationObserver;
 use starlark::collections::SmallMap;
 use starlark::environment::FrozenModule;
 use starlark::environment::Module;
@@ -36,11 +38,14 @@ use starlark::values::ValueLike;
 use starlark::values::ValueTyped;
 use thiserror::Error;
 
+use crate::actions::RegisteredAction;
+use crate::analysis::queue::HasAnalysisQueue;
 use crate::analysis::registry::AnalysisRegistry;
 use crate::attrs::resolve::ctx::AnalysisQueryResult;
 use crate::attrs::resolve::ctx::AttrResolutionContext;
 use crate::deferred::types::DeferredId;
 use crate::deferred::types::DeferredLookup;
+use crate::deferred::types::DeferredRegistry;
 use crate::deferred::types::DeferredTable;
 use crate::interpreter::rule_defs::cmd_args::FrozenCommandLineArgLike;
 use crate::interpreter::rule_defs::context::AnalysisContext;
@@ -51,7 +56,12 @@ use crate::interpreter::rule_defs::rule::FrozenRuleCallable;
 pub(crate) mod anon_targets;
 pub mod calculation;
 pub(crate) mod configured_graph;
+pub(crate) mod ordering_audit;
+pub mod prefetch;
+pub mod queue;
 pub mod registry;
+#[cfg(any(test, feature = "rule_testing"))]
+pub mod rule_testing;
 use allocative::Allocative;
 use buck2_execute::base_deferred_key::BaseDeferredKey;
 use buck2_interpreter::types::label::Label;
@@ -109,6 +119,24 @@ impl AnalysisResult {
     pub fn lookup_deferred(&self, id: DeferredId) -> anyhow::Result<DeferredLookup<'_>> {
         self.deferred.lookup_deferred(id)
     }
+
+    /// The deferreds registered by the analysis, including its actions.
+    pub fn deferred_table(&self) -> &DeferredTable {
+        &self.deferred
+    }
+
+    /// The number of actions (and other deferreds) registered by the analysis.
+    pub fn num_deferreds(&self) -> usize {
+        self.deferred.len()
+    }
+
+    /// The actions registered by the analysis. Actions created later by dynamic outputs aren't
+    /// included.
+    pub fn actions(&self) -> impl Iterator<Item = &Arc<RegisteredAction>> {
+        self.deferred
+            .trivial_values()
+            .filter_map(|value| (**value).into_any().downcast_ref::<Arc<RegisteredAction>>())
+    }
 }
 
 // Contains a `module` that things must live on, and various `FrozenProviderCollectionValue`s
@@ -117,6 +145,7 @@ pub struct RuleAnalysisAttrResolutionContext<'v> {
     pub module: &'v Module,
     pub dep_analysis_results: HashMap<&'v ConfiguredTargetLabel, FrozenProviderCollectionValue>,
     pub query_results: HashMap<String, Arc<AnalysisQueryResult>>,
+    pub perturb_ordering: bool,
 }
 
 impl<'v> AttrResolutionContext<'v> for RuleAnalysisAttrResolutionContext<'v> {
@@ -145,6 +174,10 @@ impl<'v> AttrResolutionContext<'v> for RuleAnalysisAttrResolutionContext<'v> {
     fn resolve_query(&self, query: &str) -> SharedResult<Arc<AnalysisQueryResult>> {
         resolve_query(&self.query_results, query, self.module)
     }
+
+    fn perturb_ordering(&self) -> bool {
+        self.perturb_ordering
+    }
 }
 
 pub fn get_dep<'v>(
@@ -217,17 +250,19 @@ struct AnalysisEnv<'a> {
     query_results: HashMap<String, Arc<AnalysisQueryResult>>,
     execution_platform: &'a ExecutionPlatformResolution,
     label: ConfiguredTargetLabel,
+    perturb_ordering: bool,
 }
 
 async fn run_analysis<'a>(
     dice: &DiceComputations,
     label: &ConfiguredTargetLabel,
-    results: Vec<(&'a ConfiguredTargetLabel, AnalysisResult)>,
+    results: Vec<(&'a ConfiguredTargetLabel, FrozenProviderCollectionValue)>,
     query_results: HashMap<String, Arc<AnalysisQueryResult>>,
     execution_platform: &'a ExecutionPlatformResolution,
     impl_function: &'a dyn RuleImplFunction,
     node: &ConfiguredTargetNode,
     profile_mode: &StarlarkProfileModeOrInstrumentation,
+    perturb_ordering: bool,
 ) -> anyhow::Result<AnalysisResult> {
     let analysis_env = AnalysisEnv::new(
         label,
@@ -235,6 +270,7 @@ async fn run_analysis<'a>(
         query_results,
         execution_platform,
         impl_function,
+        perturb_ordering,
     )?;
     run_analysis_with_env(dice, analysis_env, node, profile_mode).await
 }
@@ -243,10 +279,11 @@ impl<'a> AnalysisEnv<'a> {
     /// Create a new `AnalysisEnv`, ensuring that all heaps are kept alive that need to be
     fn new(
         label: &ConfiguredTargetLabel,
-        results: Vec<(&'a ConfiguredTargetLabel, AnalysisResult)>,
+        results: Vec<(&'a ConfiguredTargetLabel, FrozenProviderCollectionValue)>,
         query_results: HashMap<String, Arc<AnalysisQueryResult>>,
         execution_platform: &'a ExecutionPlatformResolution,
         impl_function: &'a dyn RuleImplFunction,
+        perturb_ordering: bool,
     ) -> anyhow::Result<Self> {
         Ok(AnalysisEnv {
             impl_function,
@@ -254,19 +291,15 @@ impl<'a> AnalysisEnv<'a> {
             query_results,
             execution_platform,
             label: label.dupe(),
+            perturb_ordering,
         })
     }
 }
 
 pub fn get_deps_from_analysis_results<'a>(
-    results: Vec<(&'a ConfiguredTargetLabel, AnalysisResult)>,
+    results: Vec<(&'a ConfiguredTargetLabel, FrozenProviderCollectionValue)>,
 ) -> anyhow::Result<HashMap<&'a ConfiguredTargetLabel, FrozenProviderCollectionValue>> {
-    results
-        .into_iter()
-        .map(|(label, result)| Ok((label, result.providers().dupe())))
-        .collect::<anyhow::Result<
-            HashMap<&'a ConfiguredTargetLabel, FrozenProviderCollectionValue>,
-        >>()
+    Ok(results.into_iter().collect())
 }
 
 fn run_analysis_with_env<'a>(
@@ -276,24 +309,65 @@ fn run_analysis_with_env<'a>(
     profile_mode: &'a StarlarkProfileModeOrInstrumentation,
 ) -> impl Future<Output = anyhow::Result<AnalysisResult>> + Send + 'a {
     let fut = async move {
-        run_analysis_with_env_underlying(dice, analysis_env, node, profile_mode).await
+        let cancellation = current_task_cancellation();
+        cancellable_analysis(
+            cancellation.as_ref(),
+            run_analysis_with_env_underlying(
+                dice,
+                analysis_env,
+                node,
+                profile_mode,
+                cancellation.as_ref(),
+            ),
+        )
+        .await
     };
     unsafe { UnsafeSendFuture::new_encapsulates_starlark(fut) }
 }
 
+/// Make `eval` stop evaluating the rule once nothing waits for the result of the analysis anymore
+/// (e.g. because the command was interrupted), rather than run it to completion.
+pub(crate) fn stop_when_cancelled<'a>(
+    eval: &mut Evaluator<'_, 'a>,
+    cancellation: Option<&'a CancellationObserver>,
+) {
+    if let Some(cancellation) = cancellation {
+        eval.set_cancellation_flag(cancellation.as_flag());
+    }
+}
+
+/// Run an analysis whose evaluator was set up with `stop_when_cancelled`.
+///
+/// Once cancelled, it fails, but that error must not be recorded as the result of the analysis.
+/// A cancelled task can't be resumed: it is dropped once it returns to the executor, which is
+/// notified of the cancellation to do so, so wait for that instead of returning.
+pub(crate) async fn cancellable_analysis(
+    cancellation: Option<&CancellationObserver>,
+    fut: impl Future<Output = anyhow::Result<AnalysisResult>>,
+) -> anyhow::Result<AnalysisResult> {
+    let res = fut.await;
+    if res.is_err() && cancellation.map_or(false, |c| c.is_cancelled()) {
+        futures::future::pending::<()>().await;
+    }
+    res
+}
+
 async fn run_analysis_with_env_underlying(
     dice: &DiceComputations,
     analysis_env: AnalysisEnv<'_>,
     node: &ConfiguredTargetNode,
     profile_mode: &StarlarkProfileModeOrInstrumentation,
+    cancellation: Option<&CancellationObserver>,
 ) -> anyhow::Result<AnalysisResult> {
     let env = Module::new();
     let mut eval = Evaluator::new(&env);
+    stop_when_cancelled(&mut eval, cancellation);
 
     let resolution_ctx = RuleAnalysisAttrResolutionContext {
         module: &env,
         dep_analysis_results: analysis_env.deps,
         query_results: analysis_env.query_results,
+        perturb_ordering: analysis_env.perturb_ordering,
     };
 
     let attrs_iter = node.attrs(AttrInspectOptions::All);
@@ -336,20 +410,23 @@ async fn run_analysis_with_env_underlying(
 
     profiler.initialize(&mut eval)?;
 
-    let list_res = analysis_env.impl_function.invoke(&mut eval, ctx)?;
+    let list_res = {
+        // Only the evaluation of the rule takes a slot: waiting for the analysis of deps or of
+        // the anon targets of promises while holding one could deadlock.
+        let queue = dice.get_analysis_queue();
+        let _slot = match &queue {
+            Some(queue) => Some(queue.acquire(node.name().pkg().cell_name().clone()).await),
+            None => None,
+        };
+        analysis_env.impl_function.invoke(&mut eval, ctx)?
+    };
 
     profiler
         .evaluation_complete(&mut eval)
         .context("Profiler finalization failed")?;
 
     ctx.run_promises(dice, &mut eval).await?;
-    // TODO: Convert the ValueError from `try_from_value` better than just printing its Debug
-    let res_typed = ProviderCollection::try_from_value(list_res)?;
-    let res = env.heap().alloc(res_typed);
-    env.set("", res);
-
-    // Pull the ctx object back out, and steal ctx.action's state back
-    let analysis_registry = ctx.take_state();
+    let analysis_registry = take_analysis_registry(&env, ctx, list_res)?;
     let (frozen_env, deferreds) = analysis_registry.finalize(&env)(env)?;
 
     profiler
@@ -358,6 +435,32 @@ async fn run_analysis_with_env_underlying(
 
     let profile_data = profiler_opt.map(|p| p.finish()).transpose()?.map(Arc::new);
 
+    analysis_result(&frozen_env, deferreds, profile_data)
+}
+
+/// Record the providers returned by the implementation of a rule in `env`, and take back the
+/// registry of `ctx`. The registry is then finalized along with `env`, and turned into the result
+/// of the analysis by `analysis_result`.
+pub(crate) fn take_analysis_registry<'v>(
+    env: &'v Module,
+    ctx: ValueTyped<'v, AnalysisContext<'v>>,
+    list_res: Value<'v>,
+) -> anyhow::Result<AnalysisRegistry<'v>> {
+    // TODO: Convert the ValueError from `try_from_value` better than just printing its Debug
+    let res_typed = ProviderCollection::try_from_value(list_res)?;
+    let res = env.heap().alloc(res_typed);
+    env.set("", res);
+
+    // Pull the ctx object back out, and steal ctx.action's state back
+    Ok(ctx.take_state())
+}
+
+/// The result of an analysis, from its frozen module and the deferreds it registered.
+pub(crate) fn analysis_result(
+    frozen_env: &FrozenModule,
+    deferreds: DeferredRegistry,
+    profile_data: Option<Arc<StarlarkProfileDataAndStats>>,
+) -> anyhow::Result<AnalysisResult> {
     let res = frozen_env.get("").unwrap();
     let provider_collection = FrozenProviderCollectionValue::try_from_value(res)
         .expect("just created this, this shouldn't happen");
@@ -391,7 +494,7 @@ pub fn get_user_defined_rule_impl(
                 .get_any_visibility(&self.name)
                 .with_context(|| format!("Couldn't find rule `{}`", self.name))?
                 .0;
-            let rule_impl = {
+            let (rule_impl, run_action_defaults) = {
                 // Need to free up the starlark_ctx borrow before we return
                 let rule_callable = rule_callable.owned_value(eval.frozen_heap());
 
@@ -403,8 +506,12 @@ pub fn get_user_defined_rule_impl(
                             rule_callable.get_type(),
                         )
                     });
-                frozen_callable.implementation()
+                (
+                    frozen_callable.implementation(),
+                    frozen_callable.run_action_defaults(ctx.attributes(), eval.heap())?,
+                )
             };
+            ctx.set_run_action_defaults(run_action_defaults);
             eval.eval_function(rule_impl.to_value(), &[ctx.to_value()], &[])
         }
     }
@@ -414,3 +521,68 @@ pub fn get_user_defined_rule_impl(
         name: rule_type.name.clone(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+
+    use futures::future::BoxFuture;
+    use more_futures::instrumented_shared::SharedEventsFuture;
+    use more_futures::spawn::spawn_task;
+    use more_futures::spawn::StrongCancellableJoinHandle;
+    use more_futures::spawner::Spawner;
+    use more_futures::spawner::TokioSpawner;
+    use starlark::environment::Globals;
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancelled_analysis_is_dropped() {
+        let (send_poll, recv_poll) = oneshot::channel::<
+            StrongCancellableJoinHandle<SharedEventsFuture<BoxFuture<'static, bool>>>,
+        >();
+        let (notify_finished, recv_finished) = oneshot::channel();
+        let dropped = Arc::new(AtomicBool::new(false));
+
+        let spawner: Arc<dyn Spawner<()>> = Arc::new(TokioSpawner::default());
+        let (_task, poll) = spawn_task(
+            async move {
+                let cancellation = current_task_cancellation();
+                let res = cancellable_analysis(cancellation.as_ref(), async {
+                    // Cancel the analysis while it evaluates a rule that takes forever.
+                    drop(recv_poll.await?);
+                    let env = Module::new();
+                    let mut eval = Evaluator::new(&env);
+                    stop_when_cancelled(&mut eval, cancellation.as_ref());
+                    let ast = AstModule::parse(
+                        "rule.bzl",
+                        "def impl():\n    for _ in range(1000000000):\n        pass\nimpl()\n"
+                            .to_owned(),
+                        &Dialect::Extended,
+                    )?;
+                    eval.eval_module(ast, &Globals::standard())?;
+                    Err(anyhow::anyhow!("the analysis wasn't cancelled"))
+                })
+                .await;
+                let _ignored = notify_finished.send(res.is_ok());
+                true
+            },
+            Some({
+                let dropped = dropped.dupe();
+                box move || dropped.store(true, Ordering::SeqCst)
+            }),
+            &spawner,
+            &(),
+            tracing::debug_span!("test"),
+        );
+        send_poll.send(poll).ok().unwrap();
+
+        // The analysis is dropped rather than returning the error of the evaluation.
+        recv_finished.await.unwrap_err();
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,154 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A debug mode that finds rules whose actions depend on the order of collections that have no
+//! meaningful order.
+//!
+//! Rules get some of their inputs as collections whose order carries no meaning: unsorted dict
+//! attributes and the results of queries in attributes. A rule that iterates over one of these
+//! and puts the result on a command line produces an action whose cache key changes whenever the
+//! order does (e.g. when the entries of a dict in a `BUCK` file are reordered), although nothing
+//! relevant changed.
+//!
+//! With `buck2.analysis_ordering_audit` set, every target is analyzed a second time with these
+//! collections in reverse order, and the actions of the two evaluations are compared. Dict
+//! attributes declared with `sorted = True` are left alone, since their order doesn't depend on
+//! how the `BUCK` file was written.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::Hash;
+use std::hash::Hasher;
+
+use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+use buck2_core::target::ConfiguredTargetLabel;
+use buck2_execute::artifact::fs::ArtifactFs;
+use buck2_execute::artifact::fs::ExecutorFs;
+use buck2_interpreter::dice::HasEvents;
+use dice::DiceComputations;
+
+use crate::analysis::AnalysisResult;
+use crate::calculation::Calculation;
+
+/// Whether targets of the cell of `target` should be audited.
+pub(crate) async fn ordering_audit_enabled(
+    ctx: &DiceComputations,
+    target: &ConfiguredTargetLabel,
+) -> anyhow::Result<bool> {
+    Ok(ctx
+        .parse_legacy_config_property(target.pkg().cell_name(), "buck2", "analysis_ordering_audit")
+        .await?
+        .unwrap_or(false))
+}
+
+/// A hash of the attributes of each action (which include the command line of the actions that
+/// run one), by action name.
+fn action_fingerprints(result: &AnalysisResult, fs: &ArtifactFs) -> BTreeMap<String, u64> {
+    result
+        .actions()
+        .map(|action| {
+            let name = match action.identifier() {
+                Some(identifier) => format!("{} {}", action.category(), identifier),
+                None => action.category().to_string(),
+            };
+            let mut hasher = DefaultHasher::new();
+            for (key, value) in action.action().aquery_attributes(&ExecutorFs::new(
+                fs,
+                action.execution_config().path_separator,
+            )) {
+                key.hash(&mut hasher);
+                value.hash(&mut hasher);
+            }
+            (name, hasher.finish())
+        })
+        .collect()
+}
+
+/// The actions that are registered by only one of the evaluations, or that differ between them.
+fn differing_actions(
+    actions: &BTreeMap<String, u64>,
+    perturbed: &BTreeMap<String, u64>,
+) -> Vec<String> {
+    let mut differing: Vec<String> = actions
+        .iter()
+        .filter(|(name, fingerprint)| perturbed.get(*name) != Some(fingerprint))
+        .map(|(name, _)| name.clone())
+        .collect();
+    differing.extend(
+        perturbed
+            .keys()
+            .filter(|name| !actions.contains_key(*name))
+            .cloned(),
+    );
+    differing.sort();
+    differing
+}
+
+/// Compare the result of the analysis of `target` with the result of its evaluation with
+/// unordered collections reversed, and warn about the actions that differ.
+pub(crate) async fn audit_ordering(
+    ctx: &DiceComputations,
+    target: &ConfiguredTargetLabel,
+    result: &AnalysisResult,
+    perturbed: anyhow::Result<AnalysisResult>,
+) -> anyhow::Result<()> {
+    let message = match perturbed {
+        Ok(perturbed) => {
+            let fs = ctx.get_artifact_fs().await?;
+            let differing = differing_actions(
+                &action_fingerprints(result, &fs),
+                &action_fingerprints(&perturbed, &fs),
+            );
+            if differing.is_empty() {
+                return Ok(());
+            }
+            format!(
+                "Actions depend on the order of unordered collections (unsorted dict attributes \
+                or query results), so their cache keys are unstable. These actions changed when \
+                the order was reversed: {}",
+                differing.join(", ")
+            )
+        }
+        Err(e) => format!(
+            "Analysis fails when unordered collections (unsorted dict attributes or query \
+            results) are reversed: {:#}",
+            e
+        ),
+    };
+    ctx.per_transaction_data().get_dispatcher().warning(
+        buck2_data::WarningCategory::NondeterministicAnalysis,
+        target.to_string(),
+        message,
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprints(actions: &[(&str, u64)]) -> BTreeMap<String, u64> {
+        actions
+            .iter()
+            .map(|(name, fingerprint)| ((*name).to_owned(), *fingerprint))
+            .collect()
+    }
+
+    #[test]
+    fn test_differing_actions() {
+        let actions = fingerprints(&[("cxx_compile a.cpp", 1), ("cxx_link", 2), ("write", 3)]);
+        assert_eq!(Vec::<String>::new(), differing_actions(&actions, &actions));
+
+        let perturbed = fingerprints(&[("cxx_compile b.cpp", 1), ("cxx_link", 4), ("write", 3)]);
+        assert_eq!(
+            vec!["cxx_compile a.cpp", "cxx_compile b.cpp", "cxx_link"],
+            differing_actions(&actions, &perturbed)
+        );
+    }
+}
@@ -0,0 +1,189 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Speculative prefetch of the inputs of the actions of targets being built.
+//!
+//! Outputs of actions that were cache hits are only declared to the materializer, and get
+//! downloaded when something needs them on disk (e.g. an action running locally). As soon as a
+//! target to build is analyzed, we know which of those its actions and the actions of its deps
+//! will need, so we start downloading them while the build goes on rather than once the actions
+//! get to run.
+//!
+//! Downloads are speculative (the actions might run remotely, or be cache hits themselves), so a
+//! command only prefetches up to `[buck2] analysis_prefetch_bytes`. Nothing is prefetched when it's
+//! not set.
+
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use buck2_common::legacy_configs::LegacyBuckConfig;
+use buck2_core::target::ConfiguredTargetLabel;
+use buck2_execute::materialize::materializer::HasMaterializer;
+use buck2_node::compatibility::MaybeCompatible;
+use dice::DiceComputations;
+use dice::UserComputationData;
+use gazebo::prelude::*;
+
+use crate::analysis::calculation::RuleAnalysisCalculation;
+use crate::artifact_groups::ArtifactGroup;
+use crate::calculation::Calculation;
+
+/// How many bytes a command can still prefetch.
+pub struct PrefetchBudget {
+    remaining: AtomicU64,
+}
+
+impl PrefetchBudget {
+    pub fn new(bytes: u64) -> Self {
+        Self {
+            remaining: AtomicU64::new(bytes),
+        }
+    }
+
+    /// `None` if prefetching is disabled.
+    pub fn from_config(config: &LegacyBuckConfig) -> anyhow::Result<Option<Self>> {
+        Ok(config
+            .parse::<u64>("buck2", "analysis_prefetch_bytes")?
+            .filter(|bytes| *bytes > 0)
+            .map(Self::new))
+    }
+
+    fn remaining(&self) -> u64 {
+        self.remaining.load(Ordering::Relaxed)
+    }
+
+    /// Concurrent prefetches may both be handed the same remaining budget, so the total can go
+    /// over by as much as they prefetch at once.
+    fn spend(&self, bytes: u64) {
+        let _ignored =
+            self.remaining
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| {
+                    Some(remaining.saturating_sub(bytes))
+                });
+    }
+}
+
+pub trait SetPrefetchBudget {
+    fn set_prefetch_budget(&mut self, budget: Arc<PrefetchBudget>);
+}
+
+impl SetPrefetchBudget for UserComputationData {
+    fn set_prefetch_budget(&mut self, budget: Arc<PrefetchBudget>) {
+        self.data.set(budget);
+    }
+}
+
+trait HasPrefetchBudget {
+    fn get_prefetch_budget(&self) -> Option<&Arc<PrefetchBudget>>;
+}
+
+impl HasPrefetchBudget for DiceComputations {
+    fn get_prefetch_budget(&self) -> Option<&Arc<PrefetchBudget>> {
+        self.per_transaction_data()
+            .data
+            .get::<Arc<PrefetchBudget>>()
+            .ok()
+    }
+}
+
+/// Starts prefetching, in the background, the inputs of the actions of `target` and of its deps,
+/// if the command has budget for it. This is called on the build path, once the target is
+/// analyzed: a command that only analyzes targets (e.g. `cquery`) won't run their actions.
+pub fn spawn_prefetch_action_inputs(ctx: &DiceComputations, target: &ConfiguredTargetLabel) {
+    if !ctx
+        .get_prefetch_budget()
+        .map_or(false, |budget| budget.remaining() > 0)
+    {
+        return;
+    }
+    let target = target.dupe();
+    // Nothing waits for it: whatever isn't prefetched by the time it's needed gets materialized
+    // as it would be without prefetching.
+    tokio::spawn(ctx.temporary_spawn(async move |ctx| prefetch_action_inputs(&ctx, &target).await));
+}
+
+/// Prefetches the inputs of the actions of `target` and its transitive deps, closest targets
+/// first, until the budget runs out. Errors are ignored.
+async fn prefetch_action_inputs(ctx: &DiceComputations, target: &ConfiguredTargetLabel) {
+    let budget = match ctx.get_prefetch_budget() {
+        Some(budget) => budget.dupe(),
+        None => return,
+    };
+    let artifact_fs = match ctx.get_artifact_fs().await {
+        Ok(artifact_fs) => artifact_fs,
+        Err(_) => return,
+    };
+    let node = match ctx.get_configured_target_node(target).await {
+        Ok(MaybeCompatible::Compatible(node)) => node,
+        _ => return,
+    };
+
+    let mut seen_targets = HashSet::new();
+    let mut seen_artifacts = HashSet::new();
+    let mut queue = VecDeque::from([node]);
+    while let Some(node) = queue.pop_front() {
+        if budget.remaining() == 0 {
+            return;
+        }
+        if !seen_targets.insert(node.name().dupe()) {
+            continue;
+        }
+        queue.extend(node.deps().duped());
+        // Already computed to analyze `target`.
+        let result = match ctx.get_analysis_result(node.name()).await {
+            Ok(MaybeCompatible::Compatible(result)) => result,
+            _ => continue,
+        };
+
+        let mut paths = Vec::new();
+        for action in result.actions() {
+            let inputs = match action.inputs() {
+                Ok(inputs) => inputs,
+                Err(_) => continue,
+            };
+            for input in inputs.iter() {
+                // Source artifacts are never declared, so they are skipped by the materializer.
+                if let ArtifactGroup::Artifact(artifact) = input {
+                    if artifact.action_key().is_none() || !seen_artifacts.insert(artifact.dupe()) {
+                        continue;
+                    }
+                    if let Ok(path) = artifact_fs.resolve(artifact.get_path()) {
+                        paths.push(path);
+                    }
+                }
+            }
+        }
+
+        if let Ok(bytes) = ctx
+            .per_transaction_data()
+            .get_materializer()
+            .prefetch(paths, budget.remaining())
+            .await
+        {
+            budget.spend(bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spend() {
+        let budget = PrefetchBudget::new(10);
+        budget.spend(4);
+        assert_eq!(6, budget.remaining());
+        budget.spend(8);
+        assert_eq!(0, budget.remaining());
+    }
+}
@@ -0,0 +1,347 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Limits how many rule implementations are evaluated at once, across all the commands of the
+//! daemon.
+//!
+//! Analysis is CPU bound, so running more of it than there are cores only makes each analysis
+//! slower, and a huge package can otherwise take over the daemon for every command. With
+//! `[buck2] max_concurrent_analysis` set, analyses beyond that wait for a slot, and slots are
+//! handed out round robin between the cells with analyses waiting (unless
+//! `[buck2] analysis_cell_fairness = false`), so a cell with thousands of targets to analyze
+//! doesn't hold up another cell's. The queue is shared by the commands of the daemon, and the
+//! limits are read again from the config of each command.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use allocative::Allocative;
+use buck2_common::legacy_configs::LegacyBuckConfig;
+use buck2_core::cells::CellName;
+use dice::DiceComputations;
+use dice::UserComputationData;
+use gazebo::prelude::*;
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+#[derive(Default)]
+struct State {
+    /// `None` when analysis isn't limited, in which case analyses are only counted.
+    max_concurrent: Option<usize>,
+    cell_fairness: bool,
+    running: usize,
+    queued: usize,
+    /// The analyses waiting for a slot, by cell (or all under `None` without fairness), in the
+    /// order the cells started waiting.
+    waiting: IndexMap<Option<CellName>, VecDeque<oneshot::Sender<()>>>,
+    /// The index in `waiting` of the cell to hand the next slot to.
+    next: usize,
+}
+
+impl State {
+    fn has_slot(&self) -> bool {
+        self.max_concurrent.map_or(true, |max| self.running < max)
+    }
+}
+
+#[derive(Allocative)]
+pub struct AnalysisQueue {
+    #[allocative(skip)]
+    state: Mutex<State>,
+}
+
+impl AnalysisQueue {
+    pub fn new(max_concurrent: Option<usize>, cell_fairness: bool) -> Self {
+        Self {
+            state: Mutex::new(State {
+                max_concurrent,
+                cell_fairness,
+                ..State::default()
+            }),
+        }
+    }
+
+    pub fn from_config(config: &LegacyBuckConfig) -> anyhow::Result<Self> {
+        let (max_concurrent, cell_fairness) = Self::parse_config(config)?;
+        Ok(Self::new(max_concurrent, cell_fairness))
+    }
+
+    fn parse_config(config: &LegacyBuckConfig) -> anyhow::Result<(Option<usize>, bool)> {
+        let max_concurrent = config
+            .parse::<usize>("buck2", "max_concurrent_analysis")?
+            .filter(|max| *max > 0);
+        let cell_fairness = config
+            .parse("buck2", "analysis_cell_fairness")?
+            .unwrap_or(true);
+        Ok((max_concurrent, cell_fairness))
+    }
+
+    /// Applies the limits of the config of a new command. Analyses already waiting keep their
+    /// place, and are granted a slot right away if the limit was raised.
+    pub fn reconfigure(&self, config: &LegacyBuckConfig) -> anyhow::Result<()> {
+        let (max_concurrent, cell_fairness) = Self::parse_config(config)?;
+        self.set_limits(max_concurrent, cell_fairness);
+        Ok(())
+    }
+
+    fn set_limits(&self, max_concurrent: Option<usize>, cell_fairness: bool) {
+        let mut state = self.state.lock();
+        state.max_concurrent = max_concurrent;
+        state.cell_fairness = cell_fairness;
+        Self::grant(&mut state);
+    }
+
+    /// How many analyses are running.
+    pub fn running(&self) -> usize {
+        self.state.lock().running
+    }
+
+    /// How many analyses are waiting for a slot.
+    pub fn queued(&self) -> usize {
+        self.state.lock().queued
+    }
+
+    /// Waits for a slot to analyze a target in `cell`. The slot is given back when the guard is
+    /// dropped.
+    pub async fn acquire(&self, cell: CellName) -> AnalysisQueueGuard<'_> {
+        let receiver = {
+            let mut state = self.state.lock();
+            if state.has_slot() && state.queued == 0 {
+                state.running += 1;
+                return AnalysisQueueGuard { queue: self };
+            }
+            let (sender, receiver) = oneshot::channel();
+            let key = if state.cell_fairness {
+                Some(cell)
+            } else {
+                None
+            };
+            state.waiting.entry(key).or_default().push_back(sender);
+            state.queued += 1;
+            receiver
+        };
+        let mut pending = PendingSlot {
+            queue: self,
+            receiver,
+            granted: false,
+        };
+        // The sender is only dropped without sending if the receiver was dropped first.
+        let _ignored = (&mut pending.receiver).await;
+        pending.granted = true;
+        AnalysisQueueGuard { queue: self }
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock();
+        state.running -= 1;
+        Self::grant(&mut state);
+    }
+
+    /// Hands free slots to the waiting analyses, one cell at a time.
+    fn grant(state: &mut State) {
+        while state.queued > 0 && state.has_slot() {
+            if state.next >= state.waiting.len() {
+                state.next = 0;
+            }
+            let index = state.next;
+            let (_, senders) = state
+                .waiting
+                .get_index_mut(index)
+                .expect("queued analyses are in `waiting`");
+            let sender = senders
+                .pop_front()
+                .expect("cells without waiters are removed");
+            if senders.is_empty() {
+                // The next cell moves to this index.
+                state.waiting.shift_remove_index(index);
+            } else {
+                state.next += 1;
+            }
+            state.queued -= 1;
+            if sender.send(()).is_ok() {
+                state.running += 1;
+            }
+        }
+    }
+}
+
+/// A slot that was asked for. If it's dropped, e.g. because the computation was cancelled, a slot
+/// granted in the meantime is given back.
+struct PendingSlot<'a> {
+    queue: &'a AnalysisQueue,
+    receiver: oneshot::Receiver<()>,
+    granted: bool,
+}
+
+impl Drop for PendingSlot<'_> {
+    fn drop(&mut self) {
+        if self.granted {
+            return;
+        }
+        self.receiver.close();
+        if self.receiver.try_recv().is_ok() {
+            self.queue.release();
+        }
+        // Otherwise, the sender is skipped when its turn comes.
+    }
+}
+
+pub struct AnalysisQueueGuard<'a> {
+    queue: &'a AnalysisQueue,
+}
+
+impl Drop for AnalysisQueueGuard<'_> {
+    fn drop(&mut self) {
+        self.queue.release();
+    }
+}
+
+pub trait SetAnalysisQueue {
+    fn set_analysis_queue(&mut self, queue: Arc<AnalysisQueue>);
+}
+
+impl SetAnalysisQueue for UserComputationData {
+    fn set_analysis_queue(&mut self, queue: Arc<AnalysisQueue>) {
+        self.data.set(queue);
+    }
+}
+
+pub(crate) trait HasAnalysisQueue {
+    /// Not set outside of the daemon (e.g. in tests), in which case analysis isn't limited.
+    fn get_analysis_queue(&self) -> Option<Arc<AnalysisQueue>>;
+}
+
+impl HasAnalysisQueue for DiceComputations {
+    fn get_analysis_queue(&self) -> Option<Arc<AnalysisQueue>> {
+        self.per_transaction_data()
+            .data
+            .get::<Arc<AnalysisQueue>>()
+            .ok()
+            .map(|queue| queue.dupe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::Poll;
+
+    use futures::poll;
+    use futures::FutureExt;
+
+    use super::*;
+
+    fn cell(name: &str) -> CellName {
+        CellName::unchecked_new(name.to_owned())
+    }
+
+    type Acquire<'a> = Pin<Box<dyn Future<Output = AnalysisQueueGuard<'a>> + 'a>>;
+
+    /// Starts acquiring a slot, which queues the acquire if no slot is free.
+    async fn start<'a>(queue: &'a AnalysisQueue, name: &str) -> Acquire<'a> {
+        let mut acquire = queue.acquire(cell(name)).boxed_local();
+        assert!(poll!(&mut acquire).is_pending());
+        acquire
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_counts() {
+        let queue = AnalysisQueue::new(None, true);
+        let a = queue.acquire(cell("a")).await;
+        let b = queue.acquire(cell("a")).await;
+        assert_eq!((2, 0), (queue.running(), queue.queued()));
+        drop(a);
+        drop(b);
+        assert_eq!((0, 0), (queue.running(), queue.queued()));
+    }
+
+    async fn granted_order(cell_fairness: bool) -> Vec<&'static str> {
+        let queue = AnalysisQueue::new(Some(1), cell_fairness);
+        let mut guard = Some(queue.acquire(cell("root")).await);
+        let mut waiting = Vec::new();
+        for name in ["big", "big", "big", "small", "small"] {
+            waiting.push((name, start(&queue, name).await));
+        }
+        assert_eq!((1, 5), (queue.running(), queue.queued()));
+
+        let mut order = Vec::new();
+        while !waiting.is_empty() {
+            drop(guard.take());
+            let mut granted = Vec::new();
+            for (i, (_, acquire)) in waiting.iter_mut().enumerate() {
+                if let Poll::Ready(g) = poll!(acquire) {
+                    granted.push(i);
+                    guard = Some(g);
+                }
+            }
+            assert_eq!(1, granted.len(), "one slot is granted at a time");
+            order.push(waiting.remove(granted[0]).0);
+        }
+        drop(guard);
+        assert_eq!((0, 0), (queue.running(), queue.queued()));
+        order
+    }
+
+    #[tokio::test]
+    async fn test_cell_fairness() {
+        assert_eq!(
+            vec!["big", "small", "big", "small", "big"],
+            granted_order(true).await
+        );
+        assert_eq!(
+            vec!["big", "big", "big", "small", "small"],
+            granted_order(false).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_acquire() {
+        let queue = AnalysisQueue::new(Some(1), true);
+        let guard = queue.acquire(cell("a")).await;
+        let waiting = start(&queue, "a").await;
+        assert_eq!((1, 1), (queue.running(), queue.queued()));
+        drop(waiting);
+        drop(guard);
+        assert_eq!((0, 0), (queue.running(), queue.queued()));
+        let _guard = queue.acquire(cell("a")).await;
+        assert_eq!(1, queue.running());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_after_grant() {
+        let queue = AnalysisQueue::new(Some(1), true);
+        let guard = queue.acquire(cell("a")).await;
+        let waiting = start(&queue, "a").await;
+        // The slot is handed to the waiting acquire, which is dropped before it sees it.
+        drop(guard);
+        assert_eq!((1, 0), (queue.running(), queue.queued()));
+        drop(waiting);
+        assert_eq!((0, 0), (queue.running(), queue.queued()));
+    }
+
+    #[tokio::test]
+    async fn test_set_limits() {
+        let queue = AnalysisQueue::new(Some(1), true);
+        let _guard = queue.acquire(cell("a")).await;
+        let mut waiting = start(&queue, "a").await;
+
+        queue.set_limits(Some(2), true);
+        assert_eq!((2, 0), (queue.running(), queue.queued()));
+        let _granted = match poll!(&mut waiting) {
+            Poll::Ready(guard) => guard,
+            Poll::Pending => panic!("a slot was freed by raising the limit"),
+        };
+
+        queue.set_limits(None, true);
+        let _unlimited = queue.acquire(cell("a")).await;
+        assert_eq!(3, queue.running());
+    }
+}
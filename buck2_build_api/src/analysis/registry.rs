@@ -40,6 +40,7 @@ use thiserror::Error;
 use crate::actions::artifact::Artifact;
 use crate::actions::artifact::DeclaredArtifact;
 use crate::actions::artifact::OutputArtifact;
+use crate::actions::impls::run::RunActionDefaults;
 use crate::actions::registry::ActionsRegistry;
 use crate::actions::UnregisteredAction;
 use crate::analysis::anon_targets::AnonTargetsRegistry;
@@ -68,6 +69,7 @@ pub struct AnalysisRegistry<'v> {
     dynamic: DynamicRegistry,
     anon_targets: AnonTargetsRegistry<'v>,
     analysis_value_storage: AnalysisValueStorage<'v>,
+    run_action_defaults: RunActionDefaults,
 }
 
 #[derive(Error, Debug)]
@@ -100,9 +102,19 @@ impl<'v> AnalysisRegistry<'v> {
             dynamic: DynamicRegistry::new(owner),
             anon_targets: AnonTargetsRegistry::new(execution_platform),
             analysis_value_storage: AnalysisValueStorage::new(),
+            run_action_defaults: RunActionDefaults::default(),
         }
     }
 
+    pub(crate) fn set_run_action_defaults(&mut self, defaults: RunActionDefaults) {
+        self.run_action_defaults = defaults;
+    }
+
+    /// The execution requirements of the actions that `ctx.actions.run` doesn't set.
+    pub(crate) fn run_action_defaults(&self) -> RunActionDefaults {
+        self.run_action_defaults
+    }
+
     pub(crate) fn set_action_key(&mut self, action_key: Arc<str>) {
         self.actions.set_action_key(action_key);
     }
@@ -248,9 +260,13 @@ impl<'v> AnalysisRegistry<'v> {
         outputs: IndexSet<OutputArtifact>,
         attributes_lambda: Value<'v>,
     ) -> anyhow::Result<()> {
-        let id = self
-            .dynamic
-            .register(dynamic, inputs, outputs, &mut self.deferred)?;
+        let id = self.dynamic.register(
+            dynamic,
+            inputs,
+            outputs,
+            self.run_action_defaults,
+            &mut self.deferred,
+        )?;
         self.analysis_value_storage.set_value(id, attributes_lambda);
         Ok(())
     }
@@ -296,6 +312,7 @@ impl<'v> AnalysisRegistry<'v> {
             artifact_groups,
             anon_targets: _,
             analysis_value_storage,
+            run_action_defaults: _,
         } = self;
         analysis_value_storage.write_to_module(env);
         move |env| {
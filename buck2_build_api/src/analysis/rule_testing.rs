@@ -0,0 +1,734 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Unit testing for the analysis of rules, without a repo or a daemon.
+//!
+//! A [`RuleTester`] evaluates `.bzl` files in a single `root` cell, and runs the implementation of
+//! a rule on a target whose attributes and dependencies are faked by functions of those files:
+//!
+//! * the attributes function returns the attributes of the target as they would be written in a
+//!   `BUCK` file (deps are given by their labels, which must be fake deps). They are coerced by
+//!   the attribute types of the rule, and those it doesn't return take the defaults of the rule.
+//!   Selects, paths and transitions aren't supported;
+//! * each fake dep is a function that returns the list of providers of that dep;
+//! * the check function is called with the providers of the target and the list of the actions
+//!   it registered, each a struct with `category`, `identifier` and `attributes` (as shown by
+//!   `buck2 aquery`), and fails to fail the test.
+//!
+//! Rules that use anonymous targets can't be tested this way.
+//!
+//! This is only available with the `rule_testing` feature.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use buck2_common::legacy_configs::LegacyBuckConfig;
+use buck2_common::legacy_configs::LegacyBuckConfigs;
+use buck2_core::bzl::ImportPath;
+use buck2_core::cells::build_file_cell::BuildFileCell;
+use buck2_core::cells::cell_path::CellPath;
+use buck2_core::cells::cell_root_path::CellRootPathBuf;
+use buck2_core::cells::paths::CellRelativePathBuf;
+use buck2_core::cells::CellAlias;
+use buck2_core::cells::CellAliasResolver;
+use buck2_core::cells::CellName;
+use buck2_core::cells::CellResolver;
+use buck2_core::cells::CellsAggregator;
+use buck2_core::collections::ordered_map::OrderedMap;
+use buck2_core::configuration::transition::applied::TransitionApplied;
+use buck2_core::configuration::transition::id::TransitionId;
+use buck2_core::configuration::Configuration;
+use buck2_core::configuration::ConfigurationData;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::project::ProjectRelativePathBuf;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::pattern::ParsedPattern;
+use buck2_core::pattern::TargetPattern;
+use buck2_core::provider::label::ConfiguredProvidersLabel;
+use buck2_core::provider::label::ProvidersName;
+use buck2_core::target::ConfiguredTargetLabel;
+use buck2_core::target::TargetLabel;
+use buck2_execute::artifact::fs::ArtifactFs;
+use buck2_execute::artifact::fs::ExecutorFs;
+use buck2_execute::base_deferred_key::BaseDeferredKey;
+use buck2_execute::path::buck_out_path::BuckOutPathResolver;
+use buck2_execute::path::buck_out_path::BuckPathResolver;
+use buck2_interpreter::common::OwnedStarlarkModulePath;
+use buck2_interpreter::common::StarlarkModulePath;
+use buck2_interpreter::common::StarlarkPath;
+use buck2_interpreter::extra::InterpreterHostArchitecture;
+use buck2_interpreter::extra::InterpreterHostPlatform;
+use buck2_interpreter::file_loader::LoadedModule;
+use buck2_interpreter::file_loader::LoadedModules;
+use buck2_interpreter::import_paths::ImportPaths;
+use buck2_interpreter::interpreter::GlobalInterpreterState;
+use buck2_interpreter::interpreter::InterpreterConfigForCell;
+use buck2_interpreter::interpreter::InterpreterForCell;
+use buck2_interpreter::interpreter::ParseResult;
+use buck2_interpreter::types::label::Label;
+use buck2_interpreter_for_build::attrs::coerce::attr_type::AttrTypeExt;
+use buck2_interpreter_for_build::attrs::coerce::ctx::BuildAttrCoercionContext;
+use buck2_interpreter_for_build::interpreter::configuror::BuildInterpreterConfiguror;
+use buck2_node::attrs::configurable::AttrIsConfigurable;
+use buck2_node::attrs::configuration_context::AttrConfigurationContext;
+use buck2_node::attrs::internal::internal_attrs;
+use buck2_node::configuration::execution::ExecutionPlatformResolution;
+use buck2_node::rule_type::StarlarkRuleType;
+use gazebo::prelude::*;
+use indexmap::IndexMap;
+use starlark::collections::Hashed;
+use starlark::collections::SmallMap;
+use starlark::environment::Module;
+use starlark::eval::Evaluator;
+use starlark::values::dict::Dict;
+use starlark::values::dict::DictOf;
+use starlark::values::structs::Struct;
+use starlark::values::OwnedFrozenValue;
+use starlark::values::StringValue;
+use starlark::values::UnpackValue;
+use starlark::values::Value;
+use thiserror::Error;
+
+use crate::actions::RegisteredAction;
+use crate::analysis::analysis_result;
+use crate::analysis::get_user_defined_rule_impl;
+use crate::analysis::registry::AnalysisRegistry;
+use crate::analysis::take_analysis_registry;
+use crate::analysis::AnalysisResult;
+use crate::analysis::RuleAnalysisAttrResolutionContext;
+use crate::analysis::RuleImplFunction;
+use crate::attrs::resolve::configured_attr::ConfiguredAttrExt;
+use crate::interpreter::context::configure_build_file_globals;
+use crate::interpreter::context::configure_extension_file_globals;
+use crate::interpreter::rule_defs::context::AnalysisContext;
+use crate::interpreter::rule_defs::provider::collection::FrozenProviderCollectionValue;
+use crate::interpreter::rule_defs::provider::collection::ProviderCollection;
+use crate::interpreter::rule_defs::rule::FrozenRuleCallable;
+use crate::query::analysis::environment::ConfiguredGraphQueryEnvironment;
+
+#[derive(Debug, Error)]
+enum RuleTestingError {
+    #[error("`{0}` was not added to the tester")]
+    UnknownBzl(String),
+    #[error("`{0}` is not defined in `{1}`")]
+    UnknownFunction(String, String),
+    #[error("The attributes function `{0}` must return a dict of strings to values, got `{1}`")]
+    AttrsNotADict(String, String),
+    #[error("Rules that use anonymous targets can't be tested with the rule tester")]
+    AnonTargets,
+    #[error("`{0}` is not a rule defined in `{1}`")]
+    NotARule(String, String),
+    #[error("`{0}` is not an attribute of the rule")]
+    UnknownAttribute(String),
+    #[error("Missing attribute `{0}`, which has no default")]
+    MissingAttribute(String),
+}
+
+const CELL: &str = "root";
+
+/// Evaluates `.bzl` files and analyzes targets of the rules they define. See the module docs.
+pub struct RuleTester {
+    cell_alias_resolver: CellAliasResolver,
+    cell_resolver: CellResolver,
+    configs: LegacyBuckConfigs,
+    loaded_modules: LoadedModules,
+}
+
+/// A target to analyze, with the functions (in a `.bzl` file added to the tester) that provide
+/// its attributes and deps, and check the result.
+pub struct RuleTest {
+    bzl: String,
+    rule: String,
+    target: String,
+    attrs: Option<String>,
+    deps: Vec<(String, String)>,
+    check: Option<String>,
+}
+
+impl RuleTest {
+    /// Analyze `target` (e.g. `root//foo:bar`) as a target of `rule`, which must be defined in
+    /// (or loaded by) `bzl`.
+    pub fn new(bzl: &str, rule: &str, target: &str) -> Self {
+        Self {
+            bzl: bzl.to_owned(),
+            rule: rule.to_owned(),
+            target: target.to_owned(),
+            attrs: None,
+            deps: Vec::new(),
+            check: None,
+        }
+    }
+
+    /// The function that returns the attributes of the target. Without one, all the attributes of
+    /// the rule must have defaults.
+    pub fn with_attrs(mut self, function: &str) -> Self {
+        self.attrs = Some(function.to_owned());
+        self
+    }
+
+    /// Add a fake dep, whose providers are returned by `function`.
+    pub fn with_dep(mut self, label: &str, function: &str) -> Self {
+        self.deps.push((label.to_owned(), function.to_owned()));
+        self
+    }
+
+    /// The function that checks the providers and actions of the target.
+    pub fn with_check(mut self, function: &str) -> Self {
+        self.check = Some(function.to_owned());
+        self
+    }
+}
+
+/// The providers and actions produced by the analysis of a [`RuleTest`].
+pub struct RuleTestResult {
+    result: AnalysisResult,
+    artifact_fs: ArtifactFs,
+}
+
+impl RuleTestResult {
+    pub fn providers(&self) -> &FrozenProviderCollectionValue {
+        self.result.providers()
+    }
+
+    /// The names of the providers, sorted.
+    pub fn provider_names(&self) -> Vec<String> {
+        let mut names = self.providers().provider_collection().provider_names();
+        names.sort();
+        names
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &Arc<RegisteredAction>> {
+        self.result.actions()
+    }
+
+    /// The action with the given category and identifier, if the target registered one.
+    pub fn action(
+        &self,
+        category: &str,
+        identifier: Option<&str>,
+    ) -> Option<&Arc<RegisteredAction>> {
+        self.actions().find(|action| {
+            action.category().as_str() == category && action.identifier() == identifier
+        })
+    }
+
+    /// The attributes of an action as shown by `buck2 aquery`, which include the command line of
+    /// the actions that run one.
+    pub fn action_attributes(&self, action: &RegisteredAction) -> IndexMap<String, String> {
+        action.action().aquery_attributes(&ExecutorFs::new(
+            &self.artifact_fs,
+            action.execution_config().path_separator,
+        ))
+    }
+}
+
+impl RuleTester {
+    pub fn new() -> anyhow::Result<Self> {
+        let mut agg = CellsAggregator::new();
+        agg.add_cell_alias_entry(
+            CellRootPathBuf::new(ProjectRelativePathBuf::try_from("".to_owned())?),
+            CellAlias::new(CELL.to_owned()),
+            CellRootPathBuf::new(ProjectRelativePathBuf::try_from("".to_owned())?),
+        )?;
+        let cell_resolver = agg.make_cell_resolver()?;
+        let cell_alias_resolver = cell_resolver
+            .get(&CellName::unchecked_new(CELL.to_owned()))?
+            .cell_alias_resolver()
+            .dupe();
+        let configs = LegacyBuckConfigs::new(
+            [(
+                CellName::unchecked_new(CELL.to_owned()),
+                LegacyBuckConfig::empty(),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        Ok(Self {
+            cell_alias_resolver,
+            cell_resolver,
+            configs,
+            loaded_modules: LoadedModules::default(),
+        })
+    }
+
+    fn interpreter(&self) -> anyhow::Result<InterpreterForCell> {
+        let import_paths = ImportPaths::parse(
+            self.config(),
+            &BuildFileCell::new(self.cell_alias_resolver.resolve_self().clone()),
+            &self.cell_alias_resolver,
+        )?;
+        Ok(InterpreterForCell::new(
+            Arc::new(InterpreterConfigForCell::new(
+                self.cell_alias_resolver.dupe(),
+                Arc::new(GlobalInterpreterState::new(
+                    &self.configs,
+                    self.cell_resolver.dupe(),
+                    BuildInterpreterConfiguror::new(
+                        None,
+                        InterpreterHostPlatform::Linux,
+                        InterpreterHostArchitecture::X86_64,
+                        false,
+                        configure_build_file_globals,
+                        configure_extension_file_globals,
+                        |_| {},
+                        None,
+                        Arc::new(ConfiguredGraphQueryEnvironment::functions()),
+                    ),
+                    false,
+                )?),
+            )?),
+            Arc::new(import_paths),
+        ))
+    }
+
+    fn config(&self) -> &LegacyBuckConfig {
+        self.configs
+            .get(self.cell_alias_resolver.resolve_self())
+            .unwrap()
+    }
+
+    fn import_path(&self, path: &str) -> anyhow::Result<ImportPath> {
+        let cell = self.cell_alias_resolver.resolve_self().clone();
+        ImportPath::new(
+            CellPath::new(
+                cell.clone(),
+                CellRelativePathBuf::try_from(path.to_owned())?,
+            ),
+            BuildFileCell::new(cell),
+        )
+    }
+
+    fn module(&self, path: &str) -> anyhow::Result<&LoadedModule> {
+        self.loaded_modules
+            .map
+            .get(self.import_path(path)?.id())
+            .ok_or_else(|| RuleTestingError::UnknownBzl(path.to_owned()).into())
+    }
+
+    /// Evaluate a `.bzl` file at `path` (relative to the root of the cell). It can load the
+    /// files that were added before it.
+    pub fn add_bzl(&mut self, path: &str, content: &str) -> anyhow::Result<()> {
+        let path = self.import_path(path)?;
+        let interpreter = self.interpreter()?;
+        let ParseResult(ast, _) =
+            interpreter.parse(StarlarkPath::LoadFile(&path), content.to_owned())?;
+        let env = interpreter.eval_module(
+            StarlarkModulePath::LoadFile(&path),
+            self.config(),
+            ast,
+            self.loaded_modules.clone(),
+            None,
+        )?;
+        let loaded = LoadedModule::new(
+            OwnedStarlarkModulePath::LoadFile(path.clone()),
+            self.loaded_modules.clone(),
+            env,
+        );
+        self.loaded_modules.map.insert(path.id().to_owned(), loaded);
+        Ok(())
+    }
+
+    fn parse_target(&self, target: &str) -> anyhow::Result<ConfiguredTargetLabel> {
+        Ok(
+            ParsedPattern::<TargetPattern>::parse_precise(&self.cell_alias_resolver, target)?
+                .as_target_label(target)?
+                .configure(Configuration::testing_new()),
+        )
+    }
+
+    /// Analyze the target of a test, and run its check function if it has one.
+    pub fn analyze(&self, test: &RuleTest) -> anyhow::Result<RuleTestResult> {
+        let module = self.module(&test.bzl)?.env();
+        let function = |name: &str| {
+            module
+                .get_any_visibility(name)
+                .map(|(value, _)| value)
+                .with_context(|| {
+                    RuleTestingError::UnknownFunction(name.to_owned(), test.bzl.clone())
+                })
+        };
+
+        let label = self.parse_target(&test.target)?;
+        let mut deps = Vec::with_capacity(test.deps.len());
+        for (dep, providers) in &test.deps {
+            deps.push((
+                self.parse_target(dep)?,
+                fake_dep(providers, function(providers)?)?,
+            ));
+        }
+
+        let env = Module::new();
+        let mut eval = Evaluator::new(&env);
+
+        let given = match &test.attrs {
+            Some(attrs_function) => {
+                let attrs_function = function(attrs_function)?.owned_value(env.frozen_heap());
+                let attrs = eval.eval_function(attrs_function, &[], &[])?;
+                DictOf::<StringValue, Value>::unpack_value(attrs)
+                    .ok_or_else(|| {
+                        RuleTestingError::AttrsNotADict(
+                            test.attrs.clone().unwrap(),
+                            attrs.to_repr(),
+                        )
+                    })?
+                    .to_dict()
+            }
+            None => SmallMap::new(),
+        };
+
+        let rule = function(&test.rule)?;
+        let rule = rule
+            .value()
+            .downcast_ref::<FrozenRuleCallable>()
+            .ok_or_else(|| RuleTestingError::NotARule(test.rule.clone(), test.bzl.clone()))?;
+        for name in given.keys() {
+            if rule.attributes().attribute(name.as_str()).is_none() {
+                return Err(RuleTestingError::UnknownAttribute(name.as_str().to_owned()).into());
+            }
+        }
+
+        let coercion_ctx = BuildAttrCoercionContext::new_no_package(
+            self.cell_alias_resolver.dupe(),
+            Arc::new(ConfiguredGraphQueryEnvironment::functions()),
+        );
+        let configuration_ctx = RuleTestAttrCtx::new();
+        let resolution_ctx = RuleAnalysisAttrResolutionContext {
+            module: &env,
+            dep_analysis_results: deps
+                .iter()
+                .map(|(dep, providers)| (dep, providers.dupe()))
+                .collect(),
+            query_results: HashMap::new(),
+            perturb_ordering: false,
+        };
+        let mut attrs = SmallMap::with_capacity(given.len());
+        for (name, _, attr) in rule.attributes().attr_specs() {
+            let value = match given.iter().find(|(k, _)| k.as_str() == name) {
+                Some((_, value)) => attr
+                    .coercer
+                    .coerce(AttrIsConfigurable::No, &coercion_ctx, *value)
+                    .and_then(|coerced| coerced.configure(&configuration_ctx))
+                    .with_context(|| format!("when coercing attribute `{}`", name))?
+                    .resolve_single(&resolution_ctx)?,
+                None if name == "name" => env.heap().alloc(label.name().value()),
+                // Internal attributes, like `visibility`, are only set when given.
+                None if internal_attrs().contains_key(name) => continue,
+                None => match &attr.default {
+                    Some(default) => default
+                        .configure(&configuration_ctx)?
+                        .resolve_single(&resolution_ctx)?,
+                    None => {
+                        return Err(RuleTestingError::MissingAttribute(name.to_owned()).into());
+                    }
+                },
+            };
+            attrs.insert(env.heap().alloc_str(name), value);
+        }
+        let attributes = env.heap().alloc(Struct::new(attrs));
+
+        let registry = AnalysisRegistry::new_from_owner(
+            BaseDeferredKey::TargetLabel(label.dupe()),
+            ExecutionPlatformResolution::unspecified(),
+        );
+        let ctx = env.heap().alloc_typed(AnalysisContext::new(
+            eval.heap(),
+            attributes,
+            Some(
+                eval.heap()
+                    .alloc_typed(Label::new(ConfiguredProvidersLabel::new(
+                        label.dupe(),
+                        ProvidersName::Default,
+                    ))),
+            ),
+            registry,
+        ));
+
+        let rule_impl = get_user_defined_rule_impl(
+            module.dupe(),
+            &StarlarkRuleType {
+                import_path: self.import_path(&test.bzl)?,
+                name: test.rule.clone(),
+            },
+        );
+        let list_res = rule_impl.invoke(&mut eval, ctx)?;
+        ctx.assert_no_promises()
+            .map_err(|_| RuleTestingError::AnonTargets)?;
+
+        let analysis_registry = take_analysis_registry(&env, ctx, list_res)?;
+        let (frozen_env, deferreds) = analysis_registry.finalize(&env)(env)?;
+
+        let result = RuleTestResult {
+            result: analysis_result(&frozen_env, deferreds, None)?,
+            artifact_fs: ArtifactFs::new(
+                BuckPathResolver::new(self.cell_resolver.dupe()),
+                BuckOutPathResolver::new(ProjectRelativePathBuf::unchecked_new(
+                    "buck-out/v2".to_owned(),
+                )),
+                // Only used to render absolute paths, which no action should depend on.
+                ProjectRoot::new(AbsNormPathBuf::try_from(std::env::temp_dir())?),
+            ),
+        };
+
+        if let Some(check) = &test.check {
+            run_check(&result, check, function(check)?)?;
+        }
+        Ok(result)
+    }
+}
+
+/// Configures the attributes of the target under test: there are no selects or transitions, and
+/// deps are configured like the fake deps.
+struct RuleTestAttrCtx {
+    cfg: Configuration,
+    transitions: OrderedMap<Arc<TransitionId>, Arc<TransitionApplied>>,
+}
+
+impl RuleTestAttrCtx {
+    fn new() -> Self {
+        Self {
+            cfg: Configuration::testing_new(),
+            transitions: OrderedMap::new(),
+        }
+    }
+}
+
+impl AttrConfigurationContext for RuleTestAttrCtx {
+    fn matches<'a>(&'a self, _label: &TargetLabel) -> Option<&'a ConfigurationData> {
+        None
+    }
+
+    fn cfg(&self) -> &Configuration {
+        &self.cfg
+    }
+
+    fn exec_cfg(&self) -> &Configuration {
+        &self.cfg
+    }
+
+    fn platform_cfg(&self, _label: &TargetLabel) -> anyhow::Result<&Configuration> {
+        Ok(&self.cfg)
+    }
+
+    fn resolved_transitions(&self) -> &OrderedMap<Arc<TransitionId>, Arc<TransitionApplied>> {
+        &self.transitions
+    }
+}
+
+/// Call the function of a fake dep, and freeze the providers it returns.
+fn fake_dep(
+    name: &str,
+    function: OwnedFrozenValue,
+) -> anyhow::Result<FrozenProviderCollectionValue> {
+    let env = Module::new();
+    let mut eval = Evaluator::new(&env);
+    let providers = eval.eval_function(function.owned_value(env.frozen_heap()), &[], &[])?;
+    let providers = ProviderCollection::try_from_value(providers)
+        .with_context(|| format!("Fake dep `{}` returned invalid providers", name))?;
+    let providers = env.heap().alloc(providers);
+    env.set("", providers);
+    let frozen = env.freeze()?;
+    FrozenProviderCollectionValue::try_from_value(frozen.get("").unwrap())
+}
+
+/// Call the check function with the providers and actions of the target.
+fn run_check(
+    result: &RuleTestResult,
+    name: &str,
+    function: OwnedFrozenValue,
+) -> anyhow::Result<()> {
+    let env = Module::new();
+    let mut eval = Evaluator::new(&env);
+    let heap = env.heap();
+
+    let providers = result.providers().value().owned_value(env.frozen_heap());
+    let actions = result
+        .actions()
+        .map(|action| {
+            let mut fields = SmallMap::with_capacity(3);
+            fields.insert(
+                heap.alloc_str("category"),
+                heap.alloc(action.category().as_str()),
+            );
+            fields.insert(
+                heap.alloc_str("identifier"),
+                match action.identifier() {
+                    Some(identifier) => heap.alloc(identifier),
+                    None => Value::new_none(),
+                },
+            );
+            let mut attributes = SmallMap::new();
+            for (key, value) in result.action_attributes(action) {
+                let key = heap.alloc_str(&key).get_hashed();
+                attributes.insert_hashed(
+                    Hashed::new_unchecked(key.hash(), key.key().to_value()),
+                    heap.alloc(value),
+                );
+            }
+            fields.insert(
+                heap.alloc_str("attributes"),
+                heap.alloc(Dict::new(attributes)),
+            );
+            heap.alloc(Struct::new(fields))
+        })
+        .collect::<Vec<_>>();
+    let actions = heap.alloc(actions);
+
+    eval.eval_function(
+        function.owned_value(env.frozen_heap()),
+        &[providers, actions],
+        &[],
+    )
+    .with_context(|| format!("Check `{}` failed", name))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_rule_tester() -> anyhow::Result<()> {
+        let mut tester = RuleTester::new()?;
+        tester.add_bzl(
+            "rules.bzl",
+            indoc!(
+                r#"
+                FooInfo = provider(fields = ["names"])
+
+                def _foo_impl(ctx):
+                    out = ctx.actions.declare_output("out.txt")
+                    names = [ctx.attrs.name] + [n for dep in ctx.attrs.deps for n in dep[FooInfo].names]
+                    ctx.actions.write(out, names)
+                    return [DefaultInfo(default_outputs = [out]), FooInfo(names = names)]
+
+                foo = rule(impl = _foo_impl, attrs = {"deps": attrs.list(attrs.dep())})
+                "#
+            ),
+        )?;
+        tester.add_bzl(
+            "rules_test.bzl",
+            indoc!(
+                r#"
+                load(":rules.bzl", "FooInfo", "foo")
+
+                def fake_bar():
+                    return [DefaultInfo(), FooInfo(names = ["bar"])]
+
+                def attrs():
+                    return {"deps": ["root//:bar"]}
+
+                def no_deps():
+                    return {"deps": []}
+
+                def wrong_deps():
+                    return {"deps": "root//:bar"}
+
+                def check(providers, actions):
+                    if providers[FooInfo].names != ["foo", "bar"]:
+                        fail("unexpected names: {}".format(providers[FooInfo].names))
+                    if len(actions) != 1 or actions[0].category != "write":
+                        fail("unexpected actions: {}".format(actions))
+                "#
+            ),
+        )?;
+
+        let result = tester.analyze(
+            &RuleTest::new("rules_test.bzl", "foo", "root//:foo")
+                .with_dep("root//:bar", "fake_bar")
+                .with_attrs("attrs")
+                .with_check("check"),
+        )?;
+        assert_eq!(
+            result.provider_names(),
+            vec!["DefaultInfo".to_owned(), "FooInfo".to_owned()]
+        );
+        assert!(result.action("write", Some("out.txt")).is_some());
+
+        let failing = tester.analyze(
+            &RuleTest::new("rules_test.bzl", "foo", "root//:foo")
+                .with_attrs("no_deps")
+                .with_check("check"),
+        );
+        assert!(format!("{:#}", failing.err().unwrap()).contains("unexpected names"));
+
+        // Attributes are coerced by the types of the rule.
+        let wrong = tester.analyze(
+            &RuleTest::new("rules_test.bzl", "foo", "root//:foo")
+                .with_dep("root//:bar", "fake_bar")
+                .with_attrs("wrong_deps"),
+        );
+        assert!(format!("{:#}", wrong.err().unwrap()).contains("when coercing attribute `deps`"));
+        let missing = tester.analyze(&RuleTest::new("rules_test.bzl", "foo", "root//:foo"));
+        assert!(format!("{:#}", missing.err().unwrap()).contains("Missing attribute `deps`"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rule_run_action_defaults() -> anyhow::Result<()> {
+        let mut tester = RuleTester::new()?;
+        tester.add_bzl(
+            "rules.bzl",
+            indoc!(
+                r#"
+                def _foo_impl(ctx):
+                    a = ctx.actions.declare_output("a")
+                    ctx.actions.run(["touch", a.as_output()], category = "touch", identifier = "a")
+                    b = ctx.actions.declare_output("b")
+                    ctx.actions.run(
+                        ["touch", b.as_output()],
+                        category = "touch",
+                        identifier = "b",
+                        local_only = True,
+                    )
+                    return [DefaultInfo()]
+
+                foo = rule(impl = _foo_impl, attrs = {}, prefer_remote = True, weight = 2)
+
+                def overridden_attrs():
+                    return {"prefer_remote": False, "weight": 3}
+                "#
+            ),
+        )?;
+
+        let preference_and_weight = |attrs: Option<&str>, identifier: &str| -> anyhow::Result<_> {
+            let mut test = RuleTest::new("rules.bzl", "foo", "root//:foo");
+            if let Some(attrs) = attrs {
+                test = test.with_attrs(attrs);
+            }
+            let result = tester.analyze(&test)?;
+            let action = result
+                .action("touch", Some(identifier))
+                .context("missing action")?;
+            let attributes = result.action_attributes(action);
+            Ok((
+                attributes["executor_preference"].clone(),
+                attributes["weight"].clone(),
+            ))
+        };
+
+        assert_eq!(
+            ("RemotePreferred".to_owned(), "2".to_owned()),
+            preference_and_weight(None, "a")?
+        );
+        // Requirements passed to `ctx.actions.run` take precedence.
+        assert_eq!(
+            ("LocalRequired".to_owned(), "2".to_owned()),
+            preference_and_weight(None, "b")?
+        );
+        // Targets override the defaults of the rule.
+        assert_eq!(
+            ("Default".to_owned(), "3".to_owned()),
+            preference_and_weight(Some("overridden_attrs"), "a")?
+        );
+        Ok(())
+    }
+}
@@ -124,17 +124,7 @@ async fn ensure_base_artifact(
     artifact: &BaseArtifactKind,
 ) -> anyhow::Result<ArtifactValue> {
     match artifact {
-        BaseArtifactKind::Build(ref built) => {
-            let action_result = dice.build_artifact(built).await?;
-            if let Some(value) = action_result.get(built.get_path()) {
-                Ok(value.dupe())
-            } else {
-                panic!(
-                    "Building an artifact didn't produce it. Expected `{:?}` but only have `{:?}`",
-                    artifact, action_result
-                )
-            }
-        }
+        BaseArtifactKind::Build(ref built) => Ok(dice.build_artifact_value(built).await?),
         BaseArtifactKind::Source(ref source) => Ok(path_artifact_value(
             &dice.file_ops(),
             &source.get_path().to_cell_path(),
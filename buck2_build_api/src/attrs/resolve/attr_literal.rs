@@ -73,7 +73,7 @@ impl UnconfiguredAttrLiteralExt for AttrLiteral<CoercedAttr> {
                 }
                 Ok(heap.alloc_tuple(&v))
             }
-            AttrLiteral::Dict(d) => {
+            AttrLiteral::Dict(d, _) => {
                 let mut m = SmallMap::with_capacity(d.len());
                 for (k, v) in d {
                     m.insert_hashed(k.to_value(heap)?.get_hashed()?, v.to_value(heap)?);
@@ -125,11 +125,16 @@ impl ConfiguredAttrLiteralExt for AttrLiteral<ConfiguredAttr> {
                 }
                 Ok(ctx.heap().alloc_tuple(&values))
             }
-            AttrLiteral::Dict(dict) => {
+            AttrLiteral::Dict(dict, sorted) => {
                 let mut res = SmallMap::with_capacity(dict.len());
                 for (k, v) in dict {
                     res.insert_hashed(k.resolve_single(ctx)?.get_hashed()?, v.resolve_single(ctx)?);
                 }
+                if ctx.perturb_ordering() && !sorted {
+                    let mut entries: Vec<_> = res.into_iter_hashed().collect();
+                    entries.reverse();
+                    res = entries.into_iter().collect();
+                }
                 Ok(ctx.heap().alloc(Dict::new(res)))
             }
             AttrLiteral::None => Ok(Value::new_none()),
@@ -176,7 +181,7 @@ impl ConfiguredAttrLiteralExt for AttrLiteral<ConfiguredAttr> {
             }
             AttrLiteral::List(_, _) => Ok(starlark::values::list::List::TYPE),
             AttrLiteral::Tuple(_) => Ok(starlark::values::tuple::Tuple::TYPE),
-            AttrLiteral::Dict(_) => Ok(Dict::TYPE),
+            AttrLiteral::Dict(..) => Ok(Dict::TYPE),
             AttrLiteral::None => Ok(NoneType::TYPE),
             AttrLiteral::Dep(_) => Ok(Label::get_type_value_static().as_str()),
             AttrLiteral::ConfiguredDep(_) => Ok(Label::get_type_value_static().as_str()),
@@ -200,7 +205,7 @@ impl ConfiguredAttrLiteralExt for AttrLiteral<ConfiguredAttr> {
             AttrLiteral::String(s) | AttrLiteral::EnumVariant(s) => heap.alloc(s),
             AttrLiteral::List(list, _ty) => heap.alloc(list.try_map(|v| v.to_value(heap))?),
             AttrLiteral::Tuple(v) => heap.alloc_tuple(&v.try_map(|v| v.to_value(heap))?),
-            AttrLiteral::Dict(map) => {
+            AttrLiteral::Dict(map, _) => {
                 let mut res = SmallMap::with_capacity(map.len());
 
                 for (k, v) in map {
@@ -56,6 +56,9 @@ impl ConfiguredQueryAttrExt for QueryAttr<ConfiguredAttr> {
                 providers,
             ));
         }
+        if ctx.perturb_ordering() {
+            dependencies.reverse();
+        }
         Ok(ctx.heap().alloc(dependencies))
     }
 }
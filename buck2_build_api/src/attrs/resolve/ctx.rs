@@ -45,4 +45,11 @@ pub trait AttrResolutionContext<'v> {
     /// Provides the result of the query. This will only provide results for queries that are reported during the configured attr traversal.
     // TODO(cjhopman): Ideally, we wouldn't need to split query attr resolution in this way, but processing queries is an async operation and the starlark Heap cannot be used in async code.
     fn resolve_query(&self, query: &str) -> SharedResult<Arc<AnalysisQueryResult>>;
+
+    /// Whether collections with no meaningful order (unsorted dicts and query results) should be
+    /// resolved in reverse order. Used to audit that rules don't depend on their order (see
+    /// `analysis::ordering_audit`).
+    fn perturb_ordering(&self) -> bool {
+        false
+    }
 }
@@ -41,14 +41,27 @@ pub(crate) fn resolution_ctx<'v>(module: &'v Module) -> impl AttrResolutionConte
     resolution_ctx_with_providers(module).0
 }
 
+/// A resolution context that reverses unordered collections, like the ordering audit does.
+pub(crate) fn perturbed_resolution_ctx<'v>(module: &'v Module) -> impl AttrResolutionContext<'v> {
+    resolution_ctx_impl(module, true).0
+}
+
 pub(crate) fn resolution_ctx_with_providers<'v>(
     module: &'v Module,
+) -> (impl AttrResolutionContext<'v>, Vec<Arc<ProviderId>>) {
+    resolution_ctx_impl(module, false)
+}
+
+fn resolution_ctx_impl<'v>(
+    module: &'v Module,
+    perturb_ordering: bool,
 ) -> (impl AttrResolutionContext<'v>, Vec<Arc<ProviderId>>) {
     struct Ctx<'v> {
         module: &'v Module,
         // This module needs to be kept alive in order for the FrozenValues to stick around
         _deps_env: FrozenModule,
         deps: SmallMap<ConfiguredProvidersLabel, FrozenProviderCollectionValue>,
+        perturb_ordering: bool,
     }
 
     impl<'v> Ctx<'v> {
@@ -241,6 +254,10 @@ pub(crate) fn resolution_ctx_with_providers<'v>(
         fn resolve_query(&self, _query: &str) -> SharedResult<Arc<AnalysisQueryResult>> {
             unimplemented!("This test resolution context doesn't handle queries")
         }
+
+        fn perturb_ordering(&self) -> bool {
+            self.perturb_ordering
+        }
     }
     let (deps_env, deps, provider_ids) = Ctx::simple_deps();
     (
@@ -248,6 +265,7 @@ pub(crate) fn resolution_ctx_with_providers<'v>(
             module,
             _deps_env: deps_env,
             deps,
+            perturb_ordering,
         },
         provider_ids,
     )
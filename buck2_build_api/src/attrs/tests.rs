@@ -28,6 +28,7 @@ use buck2_interpreter_for_build::attrs::coerce::testing::to_value;
 use buck2_node::attrs::attr_type::AttrType;
 use buck2_node::attrs::coerced_deps_collector::CoercedDepsCollector;
 use buck2_node::attrs::configurable::AttrIsConfigurable;
+use buck2_node::attrs::configured_attr::ConfiguredAttr;
 use buck2_node::attrs::configured_info::ConfiguredAttrInfo;
 use buck2_node::attrs::testing::configuration_ctx;
 use gazebo::prelude::*;
@@ -38,6 +39,7 @@ use starlark::values::Heap;
 use starlark::values::Value;
 
 use crate::attrs::resolve::configured_attr::ConfiguredAttrExt;
+use crate::attrs::resolve::testing::perturbed_resolution_ctx;
 use crate::attrs::resolve::testing::resolution_ctx;
 use crate::attrs::resolve::testing::resolution_ctx_with_providers;
 use crate::interpreter::rule_defs::cmd_args::DefaultCommandLineContext;
@@ -235,6 +237,52 @@ fn test_dict() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_dict_perturbed_ordering() -> anyhow::Result<()> {
+    let env = Module::new();
+    let globals = GlobalsBuilder::extended()
+        .with(buck2_interpreter::build_defs::native_module)
+        .build();
+    let value = to_value(&env, &globals, r#"{"b":["1"],"c":[],"a":["2"]}"#);
+
+    let configure = |sorted| -> anyhow::Result<ConfiguredAttr> {
+        let attr = AttrType::dict(
+            AttrType::string(),
+            AttrType::list(AttrType::string()),
+            sorted,
+        );
+        let coerced = attr.coerce(AttrIsConfigurable::Yes, &coercion_ctx(), value)?;
+        coerced.configure(&configuration_ctx())
+    };
+    let unsorted = configure(false)?;
+    let sorted = configure(true)?;
+
+    let ctx = resolution_ctx(&env);
+    let perturbed = perturbed_resolution_ctx(&env);
+
+    // The audit reverses the order of the entries of unsorted dicts.
+    assert_eq!(
+        r#"{"b": ["1"], "c": [], "a": ["2"]}"#,
+        unsorted.resolve_single(&ctx)?.to_string()
+    );
+    assert_eq!(
+        r#"{"a": ["2"], "c": [], "b": ["1"]}"#,
+        unsorted.resolve_single(&perturbed)?.to_string()
+    );
+
+    // Sorted dicts have the same order however they are written, so they are left alone.
+    assert_eq!(
+        r#"{"a": ["2"], "b": ["1"], "c": []}"#,
+        sorted.resolve_single(&ctx)?.to_string()
+    );
+    assert_eq!(
+        r#"{"a": ["2"], "b": ["1"], "c": []}"#,
+        sorted.resolve_single(&perturbed)?.to_string()
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_one_of() -> anyhow::Result<()> {
     let heap = Heap::new();
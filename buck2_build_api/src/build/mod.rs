@@ -7,6 +7,8 @@
  * of this source tree.
  */
 
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::sync::Arc;
@@ -17,8 +19,11 @@ use buck2_common::executor_config::PathSeparatorKind;
 use buck2_common::result::SharedResult;
 use buck2_common::result::ToSharedResultExt;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
+use buck2_data::ToProtoMessage;
 use buck2_events::dispatch::console_message;
 use buck2_execute::artifact::fs::ExecutorFs;
+use buck2_execute::base_deferred_key::BaseDeferredKey;
+use buck2_execute::output_size::OutputSize;
 use buck2_node::compatibility::MaybeCompatible;
 use cli_proto::build_request::Materializations;
 use dashmap::mapref::entry::Entry;
@@ -31,13 +36,17 @@ use tokio::sync::Mutex;
 
 use crate::actions::artifact::build_artifact::BuildArtifact;
 use crate::actions::artifact::materializer::ArtifactMaterializer;
+use crate::actions::artifact::Artifact;
 use crate::actions::artifact::BaseArtifactKind;
+use crate::actions::execute::error::with_anon_target_parent;
+use crate::analysis::prefetch::spawn_prefetch_action_inputs;
 use crate::artifact_groups::ArtifactGroup;
 use crate::artifact_groups::ArtifactGroupValues;
 use crate::calculation::Calculation;
 use crate::interpreter::rule_defs::cmd_args::AbsCommandLineContext;
 use crate::interpreter::rule_defs::cmd_args::CommandLineArgLike;
 use crate::interpreter::rule_defs::cmd_args::SimpleCommandLineArtifactVisitor;
+use crate::interpreter::rule_defs::provider::builtin::output_size_info::OutputSizeInfoCallable;
 use crate::interpreter::rule_defs::provider::builtin::run_info::RunInfo;
 use crate::interpreter::rule_defs::provider::collection::FrozenProviderCollectionValue;
 use crate::interpreter::rule_defs::provider::test_provider::TestProvider;
@@ -64,22 +73,23 @@ pub async fn build_configured_label(
     providers_label: &ConfiguredProvidersLabel,
     providers_to_build: &ProvidersToBuild,
     skippable: bool,
-) -> anyhow::Result<Option<BuildTargetResult>> {
+) -> anyhow::Result<MaybeCompatible<BuildTargetResult>> {
     let artifact_fs = ctx.get_artifact_fs().await?;
 
-    let (providers, outputs, run_args) = {
+    let (providers, outputs, run_args, size_outputs) = {
         // A couple of these objects aren't Send and so scope them here so async transform doesn't get concerned.
         let providers = match ctx.get_providers(providers_label).await? {
             MaybeCompatible::Incompatible(reason) => {
                 if skippable {
                     console_message(reason.skipping_message(providers_label.target()));
-                    return Ok(None);
+                    return Ok(MaybeCompatible::Incompatible(reason));
                 } else {
                     return Err(reason.to_err());
                 }
             }
             MaybeCompatible::Compatible(v) => v,
         };
+        spawn_prefetch_action_inputs(ctx, providers_label.target());
 
         // Important we use an an ordered collections, so the order matches the order the rule
         // author wrote.
@@ -141,7 +151,12 @@ pub async fn build_configured_label(
             }
         }
 
-        (providers, outputs, run_args)
+        let size_outputs = match collection.get_provider(OutputSizeInfoCallable::provider_id_t()) {
+            Some(output_size_info) => output_size_info.get_outputs()?,
+            None => Vec::new(),
+        };
+
+        (providers, outputs, run_args, size_outputs)
     };
 
     if !skippable && outputs.is_empty() {
@@ -151,9 +166,13 @@ pub async fn build_configured_label(
         ));
     }
 
+    let owner = &BaseDeferredKey::TargetLabel(providers_label.target().dupe());
     let outputs = future::join_all(outputs.into_iter().map(|(o, provider_type)| async move {
         let values = materialize_artifact_group(ctx, &o, materialization_context)
             .await
+            .map_err(|e| {
+                with_anon_target_parent(e, &o, owner, format_args!("`{}`", providers_label))
+            })
             .shared_error()?;
         Ok(ProviderArtifacts {
             values,
@@ -161,13 +180,55 @@ pub async fn build_configured_label(
         })
     }))
     .await;
-    Ok(Some(BuildTargetResult {
+
+    if !size_outputs.is_empty() {
+        report_output_size(ctx, providers_label, &outputs, size_outputs);
+    }
+
+    Ok(MaybeCompatible::Compatible(BuildTargetResult {
         outputs,
         providers,
         run_args,
     }))
 }
 
+/// Report the total size of the outputs declared in the target's `OutputSizeInfo`, out of those
+/// this build produced. This is done on every build of the target, whether or not its actions had
+/// to run, so that the sizes in a log can be compared to those of any other build. Their sizes are
+/// known from their values, whether or not they were materialized. Outputs that weren't requested
+/// aren't built just to size them, so unless all of them were, there is no total to report.
+fn report_output_size(
+    ctx: &DiceComputations,
+    providers_label: &ConfiguredProvidersLabel,
+    outputs: &[SharedResult<ProviderArtifacts>],
+    size_outputs: Vec<Artifact>,
+) {
+    let size_outputs: HashSet<Artifact> = size_outputs.into_iter().collect();
+    let mut sized = HashMap::new();
+    for output in outputs {
+        let output = match output {
+            Ok(output) => output,
+            // There is no total to report. The failure is reported with the result of the build.
+            Err(_) => return,
+        };
+        for (artifact, value) in output.values.iter() {
+            if size_outputs.contains(artifact) {
+                sized.insert(artifact, value.calc_output_count_and_bytes().bytes);
+            }
+        }
+    }
+    if sized.len() != size_outputs.len() {
+        return;
+    }
+
+    ctx.per_transaction_data()
+        .get_dispatcher()
+        .instant_event(buck2_data::TargetOutputSize {
+            target: Some(providers_label.target().as_proto()),
+            size: sized.values().sum(),
+        });
+}
+
 #[derive(Clone, Allocative)]
 pub struct ProviderArtifacts {
     pub values: ArtifactGroupValues,
@@ -25,7 +25,7 @@ pub static EXECUTION_PLATFORM: Lazy<ExecutionPlatformResolution> = Lazy::new(||
     ExecutionPlatformResolution::new(
         Some(ExecutionPlatform::legacy_execution_platform(
             CommandExecutorConfig {
-                executor_kind: CommandExecutorKind::Local(LocalExecutorOptions {}),
+                executor_kind: CommandExecutorKind::Local(LocalExecutorOptions::default()),
                 path_separator: PathSeparatorKind::system_default(),
                 cache_upload_behavior: CacheUploadBehavior::Disabled,
             },
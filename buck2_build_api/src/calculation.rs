@@ -26,6 +26,7 @@ use buck2_core::provider::label::ProvidersLabel;
 use buck2_core::target::ConfiguredTargetLabel;
 use buck2_core::target::TargetLabel;
 use buck2_core::target::TargetName;
+use buck2_events::dispatch::span_async;
 use buck2_execute::artifact::fs::ArtifactFs;
 use buck2_execute::path::buck_out_path::BuckOutPathResolver;
 use buck2_execute::path::buck_out_path::BuckPathResolver;
@@ -54,8 +55,10 @@ use crate::analysis::calculation as analysis_calculation;
 use crate::artifact_groups::calculation as artifact_group_calculation;
 use crate::artifact_groups::ArtifactGroup;
 use crate::artifact_groups::ArtifactGroupValues;
+use crate::configuration::modifiers::HasConfigurationModifiers;
 use crate::configuration::ConfigurationCalculation;
 use crate::context::HasBuildContextData;
+use crate::context::HasShortOutPaths;
 use crate::deferred::calculation as deferred_calculation;
 use crate::deferred::types::AnyValue;
 use crate::deferred::types::DeferredData;
@@ -132,8 +135,9 @@ pub trait Calculation<'c> {
     /// get its Configuration based on the context it's being requested in (i.e configuration is
     /// passed down from higher nodes). For top-level requested things, though, we will have an
     /// unconfigured (or "lightly"-configured) thing and the Configuration will be determined as
-    /// a mix of the global Configuration, the target's `default_target_platform` and
-    /// (potentially) self-transitions on that node.
+    /// a mix of the global Configuration, the target's `default_target_platform`, the
+    /// configuration modifiers passed to the command and (potentially) self-transitions on that
+    /// node.
     async fn get_configured_target<T: ConfigurableTarget>(
         &self,
         target: &T,
@@ -145,6 +149,13 @@ pub trait Calculation<'c> {
     /// isn't separately cached.
     async fn get_target_node(&self, target: &TargetLabel) -> SharedResult<TargetNode>;
 
+    /// Returns the canonical label of a target, i.e. the label of the target it stands for if it
+    /// is a target of an alias rule (one declared with `is_alias_rule = True`), following chains
+    /// of aliases. Printed by `buck2 targets --resolve-alias`; the targets named by the patterns
+    /// of build, test, run and query are resolved the same way, by
+    /// [`resolve_alias_patterns`](crate::nodes::calculation::resolve_alias_patterns).
+    async fn canonical_target_label(&self, target: &TargetLabel) -> anyhow::Result<TargetLabel>;
+
     /// Returns the ConfiguredTargetNode corresponding to a ConfiguredTargetLabel.
     async fn get_configured_target_node(
         &self,
@@ -180,8 +191,11 @@ pub trait Calculation<'c> {
 #[async_trait]
 impl<'c> Calculation<'c> for DiceComputations {
     async fn get_artifact_fs(&self) -> SharedResult<ArtifactFs> {
-        let buck_out_path_resolver =
+        let mut buck_out_path_resolver =
             BuckOutPathResolver::new((*self.get_buck_out_path().await?).to_buf());
+        if let Some(short_out_paths) = self.per_transaction_data().get_short_out_paths() {
+            buck_out_path_resolver = buck_out_path_resolver.with_short_out_paths(short_out_paths);
+        }
         let project_filesystem = self.global_data().get_io_provider().project_root().dupe();
         let buck_path_resolver = BuckPathResolver::new(self.get_cell_resolver().await?);
         Ok(ArtifactFs::new(
@@ -199,7 +213,7 @@ impl<'c> Calculation<'c> for DiceComputations {
         let node = self.get_target_node(target.target()).await?;
 
         let get_platform_configuration = async || -> SharedResult<Configuration> {
-            Ok(match global_target_platform {
+            let cfg = match global_target_platform {
                 Some(global_target_platform) => {
                     self.get_platform_configuration(global_target_platform)
                         .await?
@@ -208,12 +222,15 @@ impl<'c> Calculation<'c> for DiceComputations {
                     Some(target) => self.get_platform_configuration(target.target()).await?,
                     None => self.get_default_platform(target.target()).await?,
                 },
-            })
+            };
+            self.apply_configuration_modifiers(cfg).await
         };
 
         match node.rule_kind() {
             RuleKind::Configuration => Ok(target.configure(Configuration::unbound())),
-            RuleKind::Normal => Ok(target.configure(get_platform_configuration().await?)),
+            RuleKind::Normal | RuleKind::Alias => {
+                Ok(target.configure(get_platform_configuration().await?))
+            }
             RuleKind::Toolchain => {
                 let cfg = get_platform_configuration().await?;
                 let exec_cfg = get_execution_platform_toolchain_dep(
@@ -232,6 +249,10 @@ impl<'c> Calculation<'c> for DiceComputations {
         node_calculation::NodeCalculation::get_target_node(self, target).await
     }
 
+    async fn canonical_target_label(&self, target: &TargetLabel) -> anyhow::Result<TargetLabel> {
+        node_calculation::resolve_alias_rules(self, target).await
+    }
+
     async fn get_configured_target_node(
         &self,
         target: &ConfiguredTargetLabel,
@@ -418,8 +439,16 @@ pub async fn load_patterns<T: PatternType>(
     ctx: &DiceComputations,
     parsed_patterns: Vec<ParsedPattern<T>>,
 ) -> anyhow::Result<LoadedPatterns<T>> {
+    // The packages are only loaded once the futures are polled below, so this span only covers
+    // finding the packages.
     let (spec, mut load_package_futs) =
-        resolve_patterns_and_load_buildfiles(ctx, parsed_patterns).await?;
+        span_async(buck2_data::ResolveTargetPatternsStart {}, async {
+            (
+                resolve_patterns_and_load_buildfiles(ctx, parsed_patterns).await,
+                buck2_data::ResolveTargetPatternsEnd {},
+            )
+        })
+        .await?;
 
     let mut results: BTreeMap<Package, SharedResult<Arc<EvaluationResult>>> = BTreeMap::new();
     while let Some((pkg, load_res)) = load_package_futs.next().await {
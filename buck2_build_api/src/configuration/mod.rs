@@ -23,6 +23,7 @@ use buck2_node::configuration::toolchain_constraints::ToolchainConstraints;
 use indexmap::IndexSet;
 
 pub mod calculation;
+pub mod modifiers;
 
 pub type ExecutionPlatforms = Arc<Vec<ExecutionPlatform>>;
 
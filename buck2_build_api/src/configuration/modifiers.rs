@@ -0,0 +1,256 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Configuration modifiers are targets passed on the command line (`buck2 build -m //config:asan`)
+//! that change the configuration top-level targets are configured in.
+//!
+//! A modifier is any target that provides `ConfigurationInfo` (for example a `constraint_value()` or
+//! a `config_setting()` grouping several constraint values). Its constraints are applied on top of
+//! the target platform, in the order the modifiers were passed, replacing any value the platform
+//! had for the same constraint setting. Modifiers can be given short names with the `[alias]`
+//! buckconfig section, like any other target passed on the command line.
+
+use std::sync::Arc;
+
+use allocative::Allocative;
+use async_trait::async_trait;
+use buck2_common::result::SharedResult;
+use buck2_core::configuration::Configuration;
+use buck2_core::configuration::ConfigurationData;
+use buck2_core::configuration::ConfigurationPlatform;
+use buck2_core::target::TargetLabel;
+use derive_more::Display;
+use dice::DiceComputations;
+use dice::InjectedKey;
+use dice::Key;
+use gazebo::prelude::*;
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::analysis::calculation::RuleAnalysisCalculation;
+use crate::configuration::calculation::ConfigurationError;
+use crate::interpreter::rule_defs::provider::builtin::configuration_info::FrozenConfigurationInfo;
+
+#[derive(Debug, Error)]
+enum ModifierError {
+    #[error(
+        "Configuration modifier `{0}` sets config values, but modifiers can only set constraints"
+    )]
+    ModifierSetsConfigValues(TargetLabel),
+}
+
+#[derive(Clone, Dupe, Display, Debug, Eq, Hash, PartialEq, Allocative)]
+#[display(fmt = "{:?}", self)]
+struct ConfigurationModifiersKey;
+
+impl InjectedKey for ConfigurationModifiersKey {
+    type Value = Arc<Vec<TargetLabel>>;
+
+    fn compare(x: &Self::Value, y: &Self::Value) -> bool {
+        x == y
+    }
+}
+
+#[derive(Clone, Display, Debug, Eq, Hash, PartialEq, Allocative)]
+#[display(fmt = "{:?}", self)]
+struct ModifiedConfigurationKey {
+    cfg: Configuration,
+    modifiers: Arc<Vec<TargetLabel>>,
+}
+
+#[async_trait]
+impl Key for ModifiedConfigurationKey {
+    type Value = SharedResult<Configuration>;
+
+    async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
+        let mut data = match self.cfg.configuration_platform() {
+            // Without a target platform the modifiers are the whole configuration.
+            ConfigurationPlatform::Unspecified => ConfigurationData::empty(),
+            _ => {
+                let data = self.cfg.data()?;
+                ConfigurationData::new(data.constraints.clone(), data.buckconfigs.clone())
+            }
+        };
+
+        for modifier in self.modifiers.iter() {
+            let analysis_result = ctx.get_configuration_analysis_result(modifier).await?;
+            let modifier_data = FrozenConfigurationInfo::from_providers(
+                analysis_result.providers().provider_collection(),
+            )
+            .ok_or_else(|| {
+                anyhow::anyhow!(ConfigurationError::MissingConfigurationInfoProvider(
+                    modifier.dupe()
+                ))
+            })?
+            .to_configuration_data();
+            data = apply_modifier(data, modifier, modifier_data)?;
+        }
+
+        let label = modified_configuration_label(&self.cfg, &self.modifiers)?;
+        Ok(Configuration::from_platform(label, data)?)
+    }
+
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        match (x, y) {
+            (Ok(x), Ok(y)) => x == y,
+            _ => false,
+        }
+    }
+}
+
+/// Applies the constraints of a modifier on top of `data`, keeping the buckconfigs of `data`.
+fn apply_modifier(
+    data: ConfigurationData,
+    modifier: &TargetLabel,
+    modifier_data: ConfigurationData,
+) -> anyhow::Result<ConfigurationData> {
+    if !modifier_data.buckconfigs.is_empty() {
+        return Err(ModifierError::ModifierSetsConfigValues(modifier.dupe()).into());
+    }
+    // `merge` keeps the buckconfigs of the modifier, which has none.
+    let mut merged = data.merge(modifier_data);
+    merged.buckconfigs = data.buckconfigs;
+    Ok(merged)
+}
+
+/// The label of `cfg` with `modifiers` applied, e.g. `//platforms:linux+//config:asan`.
+fn modified_configuration_label(
+    cfg: &Configuration,
+    modifiers: &[TargetLabel],
+) -> anyhow::Result<String> {
+    let modifiers = modifiers.iter().map(|m| m.to_string());
+    Ok(match cfg.configuration_platform() {
+        ConfigurationPlatform::Unspecified => modifiers.join("+"),
+        _ => std::iter::once(cfg.label()?.to_owned())
+            .chain(modifiers)
+            .join("+"),
+    })
+}
+
+#[async_trait]
+pub trait HasConfigurationModifiers {
+    /// The modifiers passed to the current command, in the order they were passed.
+    async fn get_configuration_modifiers(&self) -> SharedResult<Arc<Vec<TargetLabel>>>;
+
+    fn set_configuration_modifiers(&self, modifiers: Vec<TargetLabel>) -> anyhow::Result<()>;
+
+    /// Applies the modifiers of the current command to the configuration of a top-level target.
+    async fn apply_configuration_modifiers(
+        &self,
+        cfg: Configuration,
+    ) -> SharedResult<Configuration>;
+}
+
+#[async_trait]
+impl HasConfigurationModifiers for DiceComputations {
+    async fn get_configuration_modifiers(&self) -> SharedResult<Arc<Vec<TargetLabel>>> {
+        Ok(self.compute(&ConfigurationModifiersKey).await?)
+    }
+
+    fn set_configuration_modifiers(&self, modifiers: Vec<TargetLabel>) -> anyhow::Result<()> {
+        Ok(self.changed_to(vec![(ConfigurationModifiersKey, Arc::new(modifiers))])?)
+    }
+
+    async fn apply_configuration_modifiers(
+        &self,
+        cfg: Configuration,
+    ) -> SharedResult<Configuration> {
+        let modifiers = self.get_configuration_modifiers().await?;
+        if modifiers.is_empty() {
+            return Ok(cfg);
+        }
+        self.compute(&ModifiedConfigurationKey { cfg, modifiers })
+            .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use buck2_core::configuration::constraints::ConstraintKey;
+    use buck2_core::configuration::constraints::ConstraintValue;
+    use buck2_core::target::testing::TargetLabelExt;
+
+    use super::*;
+
+    fn constraints(values: &[(&str, &str)]) -> BTreeMap<ConstraintKey, ConstraintValue> {
+        values
+            .iter()
+            .map(|(k, v)| {
+                (
+                    ConstraintKey(TargetLabel::testing_parse(k)),
+                    ConstraintValue(TargetLabel::testing_parse(v)),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_modifier() -> anyhow::Result<()> {
+        let modifier = TargetLabel::testing_parse("root//config:asan");
+        let platform = ConfigurationData::new(
+            constraints(&[
+                ("root//constraints:os", "root//constraints:linux"),
+                ("root//constraints:sanitizer", "root//constraints:none"),
+            ]),
+            BTreeMap::from([("build.mode".to_owned(), "opt".to_owned())]),
+        );
+        let modifier_data = ConfigurationData::new(
+            constraints(&[("root//constraints:sanitizer", "root//constraints:asan")]),
+            BTreeMap::new(),
+        );
+
+        let data = apply_modifier(platform, &modifier, modifier_data)?;
+        assert_eq!(
+            constraints(&[
+                ("root//constraints:os", "root//constraints:linux"),
+                ("root//constraints:sanitizer", "root//constraints:asan"),
+            ]),
+            data.constraints
+        );
+        // The config values of the platform are kept.
+        assert_eq!(
+            BTreeMap::from([("build.mode".to_owned(), "opt".to_owned())]),
+            data.buckconfigs
+        );
+
+        let sets_config = ConfigurationData::new(
+            BTreeMap::new(),
+            BTreeMap::from([("build.mode".to_owned(), "dev".to_owned())]),
+        );
+        assert!(apply_modifier(data, &modifier, sets_config).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_modified_configuration_label() -> anyhow::Result<()> {
+        let modifiers = [
+            TargetLabel::testing_parse("root//config:asan"),
+            TargetLabel::testing_parse("root//config:opt"),
+        ];
+
+        let cfg = Configuration::from_platform(
+            "root//platforms:linux".to_owned(),
+            ConfigurationData::empty(),
+        )?;
+        assert_eq!(
+            "root//platforms:linux+root//config:asan+root//config:opt",
+            modified_configuration_label(&cfg, &modifiers)?
+        );
+
+        assert_eq!(
+            "root//config:asan+root//config:opt",
+            modified_configuration_label(&Configuration::unspecified(), &modifiers)?
+        );
+
+        assert!(modified_configuration_label(&Configuration::unbound(), &modifiers).is_err());
+        Ok(())
+    }
+}
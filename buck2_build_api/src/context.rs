@@ -16,9 +16,11 @@ use async_trait::async_trait;
 use buck2_common::result::SharedResult;
 use buck2_core::fs::project::ProjectRelativePath;
 use buck2_core::fs::project::ProjectRelativePathBuf;
+use buck2_execute::path::short_out_paths::ShortOutPaths;
 use derive_more::Display;
 use dice::DiceComputations;
 use dice::InjectedKey;
+use dice::UserComputationData;
 use gazebo::prelude::*;
 use owning_ref::ArcRef;
 
@@ -31,6 +33,28 @@ pub trait SetBuildContextData {
     fn set_buck_out_path(&self, path: Option<ProjectRelativePathBuf>) -> anyhow::Result<()>;
 }
 
+/// The short output path layout is a daemon-wide setting, so it is per-transaction data rather
+/// than part of `BuildData`.
+pub trait HasShortOutPaths {
+    fn set_short_out_paths(&mut self, short_out_paths: Arc<ShortOutPaths>);
+
+    /// Not set when using the default layout.
+    fn get_short_out_paths(&self) -> Option<Arc<ShortOutPaths>>;
+}
+
+impl HasShortOutPaths for UserComputationData {
+    fn set_short_out_paths(&mut self, short_out_paths: Arc<ShortOutPaths>) {
+        self.data.set(short_out_paths);
+    }
+
+    fn get_short_out_paths(&self) -> Option<Arc<ShortOutPaths>> {
+        self.data
+            .get::<Arc<ShortOutPaths>>()
+            .ok()
+            .map(|short_out_paths| short_out_paths.dupe())
+    }
+}
+
 #[derive(PartialEq, Eq, Allocative)]
 pub struct BuildData {
     buck_out_path: ProjectRelativePathBuf,
@@ -19,6 +19,7 @@ use async_trait::async_trait;
 use buck2_common::dice::data::HasIoProvider;
 use buck2_common::result::SharedResult;
 use buck2_execute::base_deferred_key::BaseDeferredKey;
+use buck2_node::compatibility::MaybeCompatible;
 use derive_more::Display;
 use dice::DiceComputations;
 use dice::Key;
@@ -38,6 +39,7 @@ use crate::deferred::calculation::keys::DeferredResolve;
 use crate::deferred::types::AnyValue;
 use crate::deferred::types::BaseKey;
 use crate::deferred::types::DeferredData;
+use crate::deferred::types::DeferredErrors;
 use crate::deferred::types::DeferredId;
 use crate::deferred::types::DeferredInput;
 use crate::deferred::types::DeferredKey;
@@ -62,21 +64,48 @@ impl DeferredCalculation for DiceComputations {
         &self,
         data: &DeferredData<T>,
     ) -> SharedResult<ArcRef<dyn AnyValue, T>> {
-        if data.deferred_key().id().is_trivial() {
+        let deferred = if data.deferred_key().id().is_trivial() {
             let deferred = lookup_deferred(self, data.deferred_key()).await?;
-            let deferred = deferred
+            deferred
                 .get()?
                 .as_trivial()
                 .context("Invalid deferred")?
-                .dupe();
-            return Ok(data.resolve(deferred)?);
-        }
-
-        let deferred = resolve_deferred(self, data.deferred_key()).await?;
-        Ok(data.resolve(deferred)?)
+                .dupe()
+        } else {
+            resolve_deferred(self, data.deferred_key()).await?
+        };
+        Ok(with_producer(self, data.resolve(deferred)).await?)
     }
 }
 
+/// Type errors only name the key of the deferred that was read as the wrong type, so this adds the
+/// rule that registered it, which is usually what needs fixing.
+async fn with_producer<T>(dice: &DiceComputations, result: anyhow::Result<T>) -> anyhow::Result<T> {
+    let error = match result {
+        Ok(value) => return Ok(value),
+        Err(error) => error,
+    };
+    let owner = match error.downcast_ref::<DeferredErrors>() {
+        Some(DeferredErrors::WrongType { key, .. }) => key.owner().dupe(),
+        _ => return Err(error),
+    };
+    let producer = match &owner {
+        BaseDeferredKey::TargetLabel(target) => {
+            match crate::calculation::Calculation::get_configured_target_node(dice, target).await {
+                Ok(MaybeCompatible::Compatible(node)) => {
+                    format!("rule `{}` of target `{}`", node.rule_type(), target)
+                }
+                _ => format!("target `{}`", target),
+            }
+        }
+        BaseDeferredKey::AnonTarget(target) => {
+            format!("rule `{}` of anon target `{}`", target.rule_type(), target)
+        }
+        BaseDeferredKey::BxlLabel(bxl) => format!("BXL `{}`", bxl),
+    };
+    Err(error.context(format!("The deferred was registered by {}", producer)))
+}
+
 async fn lookup_deferred_inner(
     key: &BaseDeferredKey,
     dice: &DiceComputations,
@@ -261,7 +290,7 @@ async fn compute_deferred(
                 .await;
 
             let mut registry = DeferredRegistry::new(BaseKey::Deferred(Arc::new(self.0.dupe())));
-            let mut ctx = ResolveDeferredCtx::new(
+            let mut deferred_ctx = ResolveDeferredCtx::new(
                 self.0.dupe(),
                 targets?,
                 providers?,
@@ -272,10 +301,8 @@ async fn compute_deferred(
                 ctx.global_data().get_io_provider().project_root().dupe(),
             );
             // TODO populate the deferred map
-            Ok(DeferredResult::new(
-                deferred.execute(&mut ctx)?,
-                registry.take_result()?,
-            ))
+            let value = with_producer(ctx, deferred.execute(&mut deferred_ctx)).await?;
+            Ok(DeferredResult::new(value, registry.take_result()?))
         }
 
         fn equality(_: &Self::Value, _: &Self::Value) -> bool {
@@ -355,6 +382,7 @@ mod tests {
     use buck2_core::fs::project::ProjectRootTemp;
     use buck2_core::target::testing::TargetLabelExt;
     use buck2_core::target::TargetLabel;
+    use buck2_events::warnings::WithWarnings;
     use buck2_execute::base_deferred_key::BaseDeferredKey;
     use buck2_execute::execute::dice_data::set_fallback_executor_config;
     use buck2_node::compatibility::MaybeCompatible;
@@ -428,12 +456,14 @@ mod tests {
             .set_data(|data| data.set_testing_io_provider(&fs))
             .mock_and_return(
                 analysis_key,
-                anyhow::Ok(MaybeCompatible::Compatible(AnalysisResult::new(
-                    provider_collection,
-                    deferred_result,
-                    None,
-                )))
-                .shared_error(),
+                WithWarnings::new(
+                    anyhow::Ok(MaybeCompatible::Compatible(AnalysisResult::new(
+                        provider_collection,
+                        deferred_result,
+                        None,
+                    )))
+                    .shared_error(),
+                ),
             )
             .mock_and_return(
                 configured_node_key,
@@ -521,12 +551,14 @@ mod tests {
             .set_data(|data| data.set_testing_io_provider(&fs))
             .mock_and_return(
                 analysis_key,
-                anyhow::Ok(MaybeCompatible::Compatible(AnalysisResult::new(
-                    provider_collection,
-                    deferred_result,
-                    None,
-                )))
-                .shared_error(),
+                WithWarnings::new(
+                    anyhow::Ok(MaybeCompatible::Compatible(AnalysisResult::new(
+                        provider_collection,
+                        deferred_result,
+                        None,
+                    )))
+                    .shared_error(),
+                ),
             )
             .mock_and_return(
                 configured_node_key,
@@ -77,6 +77,20 @@ pub trait DeferredCtx {
     fn project_filesystem(&self) -> &ProjectRoot;
 }
 
+impl dyn DeferredCtx + '_ {
+    /// Gets the value of a deferred that was declared as an input, as the type it was declared
+    /// with.
+    pub fn get_deferred_value<T: Send + Sync + 'static>(
+        &self,
+        data: &DeferredData<T>,
+    ) -> anyhow::Result<ArcRef<dyn AnyValue, T>> {
+        let value = self
+            .get_deferred_data(data.deferred_key())
+            .ok_or_else(|| DeferredErrors::InputNotReady(data.deferred_key().dupe()))?;
+        data.resolve(value)
+    }
+}
+
 /// DeferredCtx with already resolved values
 pub struct ResolveDeferredCtx<'a> {
     key: DeferredKey,
@@ -156,12 +170,17 @@ impl<'a> DeferredCtx for ResolveDeferredCtx<'a> {
 }
 
 /// input to a deferred that needs to be computed first before executing
-#[derive(Clone, Debug, Eq, PartialEq, Hash, Allocative)]
+#[derive(Clone, Debug, Display, Eq, PartialEq, Hash, Allocative)]
 pub enum DeferredInput {
+    #[display(fmt = "target {}", _0)]
     ConfiguredTarget(ConfiguredTargetLabel),
+    #[display(fmt = "providers {}", _0)]
     Provider(ConfiguredProvidersLabel),
+    #[display(fmt = "deferred {}", _0)]
     Deferred(DeferredKey),
+    #[display(fmt = "artifact {}", _0)]
     Artifact(Artifact),
+    #[display(fmt = "materialized artifact {}", _0)]
     MaterializedArtifact(Artifact),
 }
 
@@ -190,11 +209,20 @@ impl<T: Send + Sync + 'static> DeferredData<T> {
     }
 
     pub fn resolve(&self, val: Arc<dyn AnyValue>) -> anyhow::Result<ArcRef<dyn AnyValue, T>> {
-        ArcRef::new(val).try_map(|v| v.downcast::<T>())
+        ArcRef::new(val).try_map(|v| {
+            v.into_any().downcast_ref::<T>().ok_or_else(|| {
+                DeferredErrors::WrongType {
+                    key: self.key.dupe(),
+                    actual: v.type_name().to_owned(),
+                    expected: type_name::<T>(),
+                }
+                .into()
+            })
+        })
     }
 
     /// zip/merges two 'DeferredData' into one deferred returning a tuple of their results
-    pub fn zip<V: Send + 'static>(
+    pub fn zip<V: Send + Sync + 'static>(
         data1: &DeferredData<T>,
         data2: &DeferredData<V>,
     ) -> impl Deferred<Output = (ArcRef<dyn AnyValue, T>, ArcRef<dyn AnyValue, V>)> {
@@ -202,12 +230,11 @@ impl<T: Send + Sync + 'static> DeferredData<T> {
         #[allocative(bound = "")]
         struct Combined<U, V> {
             inputs: IndexSet<DeferredInput>,
-            u: DeferredKey,
-            v: DeferredKey,
-            p: PhantomData<(U, V)>,
+            u: DeferredData<U>,
+            v: DeferredData<V>,
         }
 
-        impl<U: Send + 'static, V: Send + 'static> Deferred for Combined<U, V> {
+        impl<U: Send + Sync + 'static, V: Send + Sync + 'static> Deferred for Combined<U, V> {
             type Output = (ArcRef<dyn AnyValue, U>, ArcRef<dyn AnyValue, V>);
 
             fn inputs(&self) -> &IndexSet<DeferredInput> {
@@ -219,10 +246,8 @@ impl<T: Send + Sync + 'static> DeferredData<T> {
                 ctx: &mut dyn DeferredCtx,
             ) -> anyhow::Result<DeferredValue<Self::Output>> {
                 Ok(DeferredValue::Ready((
-                    ArcRef::new(ctx.get_deferred_data(&self.u).unwrap())
-                        .map(|u| u.downcast().unwrap()),
-                    ArcRef::new(ctx.get_deferred_data(&self.v).unwrap())
-                        .map(|v| v.downcast().unwrap()),
+                    ctx.get_deferred_value(&self.u)?,
+                    ctx.get_deferred_value(&self.v)?,
                 )))
             }
         }
@@ -234,9 +259,8 @@ impl<T: Send + Sync + 'static> DeferredData<T> {
 
         Combined {
             inputs,
-            u: data1.key.dupe(),
-            v: data2.key.dupe(),
-            p: PhantomData,
+            u: data1.dupe(),
+            v: data2.dupe(),
         }
     }
 }
@@ -329,6 +353,10 @@ impl DeferredAny for TrivialDeferredValue {
     fn execute(&self, _ctx: &mut dyn DeferredCtx) -> anyhow::Result<DeferredValueAny> {
         Ok(DeferredValueAny::Ready(self.0.dupe()))
     }
+
+    fn deferred_type_name(&self) -> &str {
+        self.0.type_name()
+    }
 }
 
 #[derive(Allocative)]
@@ -351,6 +379,13 @@ impl DeferredAny for DeferredTableEntry {
             Self::Complex(v) => v.execute(ctx),
         }
     }
+
+    fn deferred_type_name(&self) -> &str {
+        match self {
+            Self::Trivial(v) => v.deferred_type_name(),
+            Self::Complex(v) => v.deferred_type_name(),
+        }
+    }
 }
 
 #[derive(Allocative)]
@@ -526,10 +561,11 @@ impl DeferredRegistry {
         #[derive(Allocative)]
         #[allocative(bound = "")]
         struct Map<T, U, F> {
-            orig: IndexSet<DeferredInput>,
+            inputs: IndexSet<DeferredInput>,
+            orig: DeferredData<T>,
             #[allocative(skip)]
             f: F,
-            p: PhantomData<(T, U)>,
+            p: PhantomData<U>,
         }
 
         impl<T, U, F> Deferred for Map<T, U, F>
@@ -541,29 +577,21 @@ impl DeferredRegistry {
             type Output = U;
 
             fn inputs(&self) -> &IndexSet<DeferredInput> {
-                &self.orig
+                &self.inputs
             }
 
             fn execute(
                 &self,
                 ctx: &mut dyn DeferredCtx,
             ) -> anyhow::Result<DeferredValue<Self::Output>> {
-                let orig = match self.orig.iter().exactly_one() {
-                    Ok(DeferredInput::Deferred(orig)) => orig,
-                    _ => unreachable!(),
-                };
-                Ok((self.f)(
-                    ctx.get_deferred_data(orig)
-                        .unwrap()
-                        .downcast::<T>()
-                        .unwrap(),
-                    ctx,
-                ))
+                let orig = ctx.get_deferred_value(&self.orig)?;
+                Ok((self.f)(&*orig, ctx))
             }
         }
 
         self.defer(Map {
-            orig: indexset![DeferredInput::Deferred(orig.key.dupe())],
+            inputs: indexset![DeferredInput::Deferred(orig.key.dupe())],
+            orig: orig.dupe(),
             f,
             p: PhantomData,
         })
@@ -589,6 +617,14 @@ pub enum DeferredErrors {
     DeferredNotFound(u32),
     #[error("reserved deferred id of `{0:?}` was never bound")]
     UnboundReservedDeferred(usize),
+    #[error("deferred `{0}` was read before it was computed, it must be declared as an input")]
+    InputNotReady(DeferredKey),
+    #[error("deferred `{key}` has a value of type `{actual}`, but it was read as `{expected}`")]
+    WrongType {
+        key: DeferredKey,
+        actual: String,
+        expected: &'static str,
+    },
 }
 
 pub enum DeferredLookup<'a> {
@@ -647,6 +683,33 @@ impl DeferredTable {
         Self(Arc::new(deferreds))
     }
 
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// All the deferreds, with their ids.
+    pub fn iter(&self) -> impl Iterator<Item = (DeferredId, &DeferredTableEntry)> {
+        self.0.iter().enumerate().map(|(id, entry)| {
+            let id = DeferredId {
+                id: id.try_into().unwrap(),
+                trivial: matches!(entry, DeferredTableEntry::Trivial(..)),
+            };
+            (id, entry)
+        })
+    }
+
+    /// The values of the trivial deferreds, which are available without computing anything.
+    pub fn trivial_values(&self) -> impl Iterator<Item = &Arc<dyn AnyValue>> {
+        self.0.iter().filter_map(|entry| match entry {
+            DeferredTableEntry::Trivial(value) => Some(&value.0),
+            DeferredTableEntry::Complex(..) => None,
+        })
+    }
+
     /// looks up an 'Deferred' given the id
     pub fn lookup_deferred(&self, id: DeferredId) -> anyhow::Result<DeferredLookup<'_>> {
         match self.0.get(id.as_usize()) {
@@ -742,6 +805,9 @@ pub trait DeferredAny: Allocative + Send + Sync {
 
     /// executes this 'Deferred', assuming all inputs and input artifacts are already computed
     fn execute(&self, ctx: &mut dyn DeferredCtx) -> anyhow::Result<DeferredValueAny>;
+
+    /// the type of the 'Deferred' (or of the value, for trivial ones), for debugging
+    fn deferred_type_name(&self) -> &str;
 }
 
 /// An id to look up the deferred work
@@ -780,6 +846,10 @@ where
             DeferredValue::Deferred(d) => Ok(DeferredValueAny::defer(d)),
         }
     }
+
+    fn deferred_type_name(&self) -> &str {
+        type_name::<D>()
+    }
 }
 
 pub mod testing {
@@ -898,6 +968,7 @@ mod tests {
     use crate::deferred::types::DeferredAny;
     use crate::deferred::types::DeferredCtx;
     use crate::deferred::types::DeferredData;
+    use crate::deferred::types::DeferredErrors;
     use crate::deferred::types::DeferredId;
     use crate::deferred::types::DeferredInput;
     use crate::deferred::types::DeferredKey;
@@ -1128,6 +1199,57 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn typed_access_errors() -> anyhow::Result<()> {
+        let mut registry = DeferredRegistry::new(BaseKey::Base(dummy_base()));
+        let deferred = FakeDeferred {
+            inputs: IndexSet::new(),
+            val: 1,
+        };
+        let data = registry.defer(deferred.clone());
+        registry.defer_trivial(2);
+        let table = DeferredTable::new(registry.take_result()?);
+        assert_eq!(
+            vec![false, true],
+            table
+                .iter()
+                .map(|(id, _)| id.is_trivial())
+                .collect::<Vec<_>>()
+        );
+
+        let (key, value) = make_resolved(&data, &deferred);
+        assert_eq!(1, *data.resolve(value.dupe())?);
+        let misread = DeferredData::<String>::new(key.dupe());
+        let err = misread.resolve(value).map(|_| ()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DeferredErrors>(),
+            Some(DeferredErrors::WrongType { key: k, actual, .. }) if k == &key && actual == "i32"
+        ));
+
+        // Reading a deferred that isn't an input is an error rather than a panic.
+        let mut registry = DeferredRegistry::new(BaseKey::Deferred(Arc::new(key.dupe())));
+        let resolved = ResolveDeferredCtx::new(
+            key,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            &mut registry,
+            dummy_project_filesystem(),
+        );
+        let err = (&resolved as &dyn DeferredCtx)
+            .get_deferred_value(&data)
+            .map(|_| ())
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DeferredErrors>(),
+            Some(DeferredErrors::InputNotReady(..))
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn register_nested_deferred() -> anyhow::Result<()> {
         let target = ConfiguredTargetLabel::testing_new(
@@ -28,6 +28,7 @@ use thiserror::Error;
 
 use crate::actions::artifact::build_artifact::BuildArtifact;
 use crate::actions::artifact::Artifact;
+use crate::actions::impls::run::RunActionDefaults;
 use crate::actions::key::ActionKey;
 use crate::actions::RegisteredAction;
 use crate::analysis::registry::AnalysisRegistry;
@@ -75,6 +76,9 @@ pub(crate) struct DynamicLambda {
     outputs: Vec<BuildArtifact>,
     /// A Starlark pair of the attributes and a lambda function that binds the outputs given a context
     attributes_lambda: OwnedFrozenValue,
+    /// The execution requirements of the run actions of the owner, which the actions the lambda
+    /// runs default to as well.
+    run_action_defaults: RunActionDefaults,
 }
 
 impl DynamicLambda {
@@ -83,6 +87,7 @@ impl DynamicLambda {
         dynamic: IndexSet<Artifact>,
         inputs: IndexSet<Artifact>,
         outputs: Vec<BuildArtifact>,
+        run_action_defaults: RunActionDefaults,
     ) -> Self {
         let mut depends = IndexSet::with_capacity(dynamic.len() + 1);
         match &owner {
@@ -104,12 +109,17 @@ impl DynamicLambda {
             inputs,
             outputs,
             attributes_lambda: Default::default(),
+            run_action_defaults,
         }
     }
 
     pub(crate) fn bind(&mut self, attributes_lambda: OwnedFrozenValue) {
         self.attributes_lambda = attributes_lambda;
     }
+
+    pub(crate) fn run_action_defaults(&self) -> RunActionDefaults {
+        self.run_action_defaults
+    }
 }
 
 /// The `Output` from `DynamicLambda`.
@@ -205,6 +215,7 @@ impl Deferred for DynamicLambda {
             deferred,
         );
         registry.set_action_key(Arc::from(deferred_ctx.get_action_key()));
+        registry.set_run_action_defaults(self.run_action_defaults);
 
         let mut artifacts = SmallMap::with_capacity(self.inputs.len());
         let fs = deferred_ctx.project_filesystem();
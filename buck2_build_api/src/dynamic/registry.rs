@@ -15,6 +15,7 @@ use indexmap::IndexSet;
 
 use crate::actions::artifact::Artifact;
 use crate::actions::artifact::OutputArtifact;
+use crate::actions::impls::run::RunActionDefaults;
 use crate::actions::key::ActionKey;
 use crate::analysis::registry::AnalysisValueFetcher;
 use crate::deferred::types::DeferredId;
@@ -43,6 +44,7 @@ impl DynamicRegistry {
         dynamic: IndexSet<Artifact>,
         inputs: IndexSet<Artifact>,
         outputs: IndexSet<OutputArtifact>,
+        run_action_defaults: RunActionDefaults,
         registry: &mut DeferredRegistry,
     ) -> anyhow::Result<DeferredId> {
         let reserved = registry.reserve::<DynamicLambdaOutput>();
@@ -58,7 +60,13 @@ impl DynamicRegistry {
                 Ok(bound)
             })
             .collect::<anyhow::Result<_>>()?;
-        let lambda = DynamicLambda::new(self.owner.dupe(), dynamic, inputs, outputs);
+        let lambda = DynamicLambda::new(
+            self.owner.dupe(),
+            dynamic,
+            inputs,
+            outputs,
+            run_action_defaults,
+        );
         let lambda_id = reserved.data().deferred_key().id();
         self.pending.push((reserved, lambda));
         Ok(lambda_id)
@@ -82,3 +90,35 @@ impl DynamicRegistry {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::configuration::Configuration;
+    use buck2_core::target::testing::TargetLabelExt;
+    use buck2_core::target::TargetLabel;
+
+    use super::*;
+    use crate::deferred::types::BaseKey;
+
+    #[test]
+    fn test_register_carries_run_action_defaults() -> anyhow::Result<()> {
+        let owner = BaseDeferredKey::TargetLabel(
+            TargetLabel::testing_parse("cell//pkg:foo").configure(Configuration::testing_new()),
+        );
+        let mut deferred = DeferredRegistry::new(BaseKey::Base(owner.dupe()));
+        let mut dynamic = DynamicRegistry::new(owner);
+        dynamic.register(
+            IndexSet::new(),
+            IndexSet::new(),
+            IndexSet::new(),
+            RunActionDefaults::new(false, false, true, 3)?,
+            &mut deferred,
+        )?;
+
+        let defaults = dynamic.pending[0].1.run_action_defaults();
+        assert!(defaults.executor_preference.prefers_remote());
+        assert!(!defaults.executor_preference.requires_remote());
+        assert_eq!(3, defaults.weight);
+        Ok(())
+    }
+}
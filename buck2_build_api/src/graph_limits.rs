@@ -0,0 +1,462 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Guardrails on the size of the graph computed by a command.
+//!
+//! An accidental graph explosion (e.g. an overeager split transition) would otherwise keep a
+//! command configuring and analyzing for hours. With limits set, the command instead fails as soon
+//! as it crosses them, with a report of the targets and transitions responsible.
+//!
+//! Only the nodes computed by a command are counted: nodes reused from a previous command (i.e.
+//! already in the DICE graph) aren't, so the limits bound how much a single command adds to the
+//! graph rather than the size of the graph it builds. A command that fails on a limit and is
+//! retried may then pass, having computed part of the graph the first time.
+
+use std::cmp::Ordering as CmpOrdering;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use buck2_common::legacy_configs::LegacyBuckConfig;
+use buck2_common::result::recursive_shared_downcast_ref;
+use buck2_common::result::SharedError;
+use buck2_core::configuration::transition::id::TransitionId;
+use buck2_core::target::ConfiguredTargetLabel;
+use buck2_core::target::TargetLabel;
+use dice::DiceComputations;
+use dice::UserComputationData;
+use gazebo::prelude::*;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+
+/// How many entries to show in each section of the report.
+const REPORT_TOP: usize = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GraphLimitError {
+    #[error(
+        "Command configured more than {0} new targets (limit set by `[build] max_configured_targets`, \
+        targets configured by previous commands aren't counted), \
+        the configured graph is likely exploding\n{1}"
+    )]
+    TooManyConfiguredTargets(u64, Arc<String>),
+    #[error(
+        "Analysis registered more than {0} new actions (limit set by `[build] max_actions`, \
+        actions registered by previous commands aren't counted), \
+        the configured graph is likely exploding\n{1}"
+    )]
+    TooManyActions(u64, Arc<String>),
+}
+
+impl GraphLimitError {
+    /// Whether the error was caused by a graph limit. Such errors depend on the command that
+    /// computed the node rather than on the node, so they must not be cached.
+    pub fn is_cause_of(error: &SharedError) -> bool {
+        recursive_shared_downcast_ref::<GraphLimitError>(error.inner()).is_some()
+    }
+}
+
+/// Limits on the size of the graph computed by a command, from the root cell's buckconfig. Both
+/// count only what the command computes, not what it reuses from previous commands.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GraphLimits {
+    /// `[build] max_configured_targets`, counting the targets the command configures.
+    pub max_configured_targets: Option<u64>,
+    /// `[build] max_actions`, counting everything registered by the analyses the command runs
+    /// (actions, as well as dynamic outputs and other deferred work).
+    pub max_actions: Option<u64>,
+}
+
+impl GraphLimits {
+    pub fn from_config(config: &LegacyBuckConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            max_configured_targets: config.parse("build", "max_configured_targets")?,
+            max_actions: config.parse("build", "max_actions")?,
+        })
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_configured_targets.is_none() && self.max_actions.is_none()
+    }
+}
+
+#[derive(PartialEq, Eq)]
+struct NodeRecord {
+    label: ConfiguredTargetLabel,
+    deps: usize,
+    /// Deps in a configuration other than the node's.
+    transitioned_deps: usize,
+}
+
+/// Nodes with more deps first, then by label.
+impl Ord for NodeRecord {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.deps
+            .cmp(&other.deps)
+            .then_with(|| other.label.cmp(&self.label))
+            .then_with(|| self.transitioned_deps.cmp(&other.transitioned_deps))
+    }
+}
+
+impl PartialOrd for NodeRecord {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(PartialEq, Eq)]
+struct ActionsRecord {
+    label: ConfiguredTargetLabel,
+    actions: u64,
+}
+
+/// Targets with more actions first, then by label.
+impl Ord for ActionsRecord {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.actions
+            .cmp(&other.actions)
+            .then_with(|| other.label.cmp(&self.label))
+    }
+}
+
+impl PartialOrd for ActionsRecord {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The greatest `REPORT_TOP` entries recorded, which is all the report shows, so that recording
+/// every node of a large graph doesn't keep them all.
+struct Top<T: Ord>(BinaryHeap<Reverse<T>>);
+
+impl<T: Ord> Default for Top<T> {
+    fn default() -> Self {
+        Self(BinaryHeap::with_capacity(REPORT_TOP + 1))
+    }
+}
+
+impl<T: Ord> Top<T> {
+    fn push(&mut self, entry: T) {
+        self.0.push(Reverse(entry));
+        if self.0.len() > REPORT_TOP {
+            self.0.pop();
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The entries, greatest first.
+    fn sorted(&self) -> Vec<&T> {
+        let mut entries: Vec<_> = self.0.iter().map(|Reverse(entry)| entry).collect();
+        entries.sort_by(|a, b| b.cmp(a));
+        entries
+    }
+}
+
+#[derive(Default)]
+struct TransitionRecord {
+    applications: u64,
+    configurations: u64,
+}
+
+#[derive(Default)]
+struct Records {
+    nodes: Top<NodeRecord>,
+    transitions: HashMap<TransitionId, TransitionRecord>,
+    configurations_per_target: HashMap<TargetLabel, u64>,
+    actions: Top<ActionsRecord>,
+}
+
+/// Counts the configured targets and actions computed by a command, and fails the computations
+/// that cross the limits.
+pub struct GraphSizeTracker {
+    limits: GraphLimits,
+    configured_targets: AtomicU64,
+    actions: AtomicU64,
+    records: Mutex<Records>,
+    /// Many computations fail at once when a limit is crossed, so the report is only built once.
+    report: OnceCell<Arc<String>>,
+}
+
+impl GraphSizeTracker {
+    pub fn new(limits: GraphLimits) -> Self {
+        Self {
+            limits,
+            configured_targets: AtomicU64::new(0),
+            actions: AtomicU64::new(0),
+            records: Mutex::new(Records::default()),
+            report: OnceCell::new(),
+        }
+    }
+
+    /// Records a configured target, before its deps are configured.
+    pub(crate) fn record_configured_target<'a>(
+        &self,
+        label: &ConfiguredTargetLabel,
+        deps: impl IntoIterator<Item = &'a ConfiguredTargetLabel>,
+        transitions: impl IntoIterator<Item = (&'a TransitionId, usize)>,
+    ) -> anyhow::Result<()> {
+        let mut dep_count = 0;
+        let mut transitioned_deps = 0;
+        for dep in deps {
+            dep_count += 1;
+            if dep.cfg() != label.cfg() {
+                transitioned_deps += 1;
+            }
+        }
+
+        {
+            let mut records = self.records.lock();
+            records.nodes.push(NodeRecord {
+                label: label.dupe(),
+                deps: dep_count,
+                transitioned_deps,
+            });
+            for (transition, configurations) in transitions {
+                let record = records.transitions.entry(transition.clone()).or_default();
+                record.applications += 1;
+                record.configurations += configurations as u64;
+            }
+            *records
+                .configurations_per_target
+                .entry(label.unconfigured().dupe())
+                .or_default() += 1;
+        }
+
+        let count = self.configured_targets.fetch_add(1, Ordering::Relaxed) + 1;
+        match self.limits.max_configured_targets {
+            Some(limit) if count > limit => {
+                Err(GraphLimitError::TooManyConfiguredTargets(limit, self.report()).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Records the actions registered by the analysis of a target.
+    pub(crate) fn record_actions(
+        &self,
+        label: &ConfiguredTargetLabel,
+        actions: usize,
+    ) -> anyhow::Result<()> {
+        let actions = actions as u64;
+        self.records.lock().actions.push(ActionsRecord {
+            label: label.dupe(),
+            actions,
+        });
+
+        let count = self.actions.fetch_add(actions, Ordering::Relaxed) + actions;
+        match self.limits.max_actions {
+            Some(limit) if count > limit => {
+                Err(GraphLimitError::TooManyActions(limit, self.report()).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn report(&self) -> Arc<String> {
+        self.report
+            .get_or_init(|| Arc::new(self.records.lock().report()))
+            .dupe()
+    }
+}
+
+impl Records {
+    fn report(&self) -> String {
+        let mut out = String::new();
+
+        // Writing to a String can't fail.
+        let _ = writeln!(out, "Targets with the most dependencies:");
+        for node in self.nodes.sorted() {
+            let _ = writeln!(
+                out,
+                "  {:>8} deps ({} in another configuration)  {}",
+                node.deps, node.transitioned_deps, node.label
+            );
+        }
+
+        if !self.transitions.is_empty() {
+            let mut transitions: Vec<_> = self.transitions.iter().collect();
+            transitions.sort_by(|(a_id, a), (b_id, b)| {
+                b.configurations
+                    .cmp(&a.configurations)
+                    .then_with(|| a_id.to_string().cmp(&b_id.to_string()))
+            });
+            let _ = writeln!(out, "Transitions producing the most configurations:");
+            for (id, record) in transitions.into_iter().take(REPORT_TOP) {
+                let _ = writeln!(
+                    out,
+                    "  {:>8} configurations from {} applications  {}",
+                    record.configurations, record.applications, id
+                );
+            }
+        }
+
+        let mut targets: Vec<_> = self.configurations_per_target.iter().collect();
+        targets.sort_by(|(a_label, a), (b_label, b)| b.cmp(a).then_with(|| a_label.cmp(b_label)));
+        let _ = writeln!(out, "Targets configured in the most configurations:");
+        for (label, configurations) in targets.into_iter().take(REPORT_TOP) {
+            let _ = writeln!(out, "  {:>8} configurations  {}", configurations, label);
+        }
+
+        if !self.actions.is_empty() {
+            let _ = writeln!(out, "Targets registering the most actions:");
+            for record in self.actions.sorted() {
+                let _ = writeln!(out, "  {:>8} actions  {}", record.actions, record.label);
+            }
+        }
+
+        out
+    }
+}
+
+pub trait SetGraphSizeTracker {
+    fn set_graph_size_tracker(&mut self, tracker: Arc<GraphSizeTracker>);
+}
+
+impl SetGraphSizeTracker for UserComputationData {
+    fn set_graph_size_tracker(&mut self, tracker: Arc<GraphSizeTracker>) {
+        self.data.set(tracker);
+    }
+}
+
+pub trait HasGraphSizeTracker {
+    /// The tracker of the current command, if it has any limits set.
+    fn get_graph_size_tracker(&self) -> Option<&Arc<GraphSizeTracker>>;
+}
+
+impl HasGraphSizeTracker for DiceComputations {
+    fn get_graph_size_tracker(&self) -> Option<&Arc<GraphSizeTracker>> {
+        self.per_transaction_data()
+            .data
+            .get::<Arc<GraphSizeTracker>>()
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::bzl::ImportPath;
+    use buck2_core::configuration::Configuration;
+    use buck2_core::target::testing::TargetLabelExt;
+
+    use super::*;
+
+    fn label(s: &str, cfg: Configuration) -> ConfiguredTargetLabel {
+        TargetLabel::testing_parse(s).configure(cfg)
+    }
+
+    #[test]
+    fn test_configured_targets_limit() -> anyhow::Result<()> {
+        let tracker = GraphSizeTracker::new(GraphLimits {
+            max_configured_targets: Some(3),
+            max_actions: None,
+        });
+        let split = TransitionId {
+            path: ImportPath::unchecked_new("root", "defs", "transitions.bzl"),
+            name: "split".to_owned(),
+        };
+
+        let root = label("root//:root", Configuration::testing_new());
+        let lib = label("root//:lib", Configuration::testing_new());
+        let lib_split = label("root//:lib", Configuration::unbound());
+        tracker.record_configured_target(&root, [&lib, &lib_split], [(&split, 2)])?;
+        tracker.record_configured_target(&lib, [], [])?;
+        tracker.record_configured_target(&lib_split, [], [])?;
+
+        let err = tracker
+            .record_configured_target(&label("root//:leaf", Configuration::testing_new()), [], [])
+            .unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("more than 3 new targets"), "{}", message);
+        assert!(
+            message.contains("2 deps (1 in another configuration)  root//:root"),
+            "{}",
+            message
+        );
+        assert!(
+            message
+                .contains("2 configurations from 1 applications  root//defs/transitions.bzl#split"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("2 configurations  root//:lib"),
+            "{}",
+            message
+        );
+        assert!(GraphLimitError::is_cause_of(&SharedError::new(err)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_actions_limit() -> anyhow::Result<()> {
+        let tracker = GraphSizeTracker::new(GraphLimits {
+            max_configured_targets: None,
+            max_actions: Some(10),
+        });
+        tracker.record_actions(&label("root//:a", Configuration::testing_new()), 4)?;
+        tracker.record_actions(&label("root//:b", Configuration::testing_new()), 6)?;
+        let err = tracker
+            .record_actions(&label("root//:c", Configuration::testing_new()), 1)
+            .unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("more than 10 new actions"), "{}", message);
+        assert!(message.contains("6 actions  root//:b"), "{}", message);
+        Ok(())
+    }
+
+    #[test]
+    fn test_records_keep_the_top_entries() -> anyhow::Result<()> {
+        let tracker = GraphSizeTracker::new(GraphLimits {
+            max_configured_targets: Some(1000),
+            max_actions: None,
+        });
+        let deps: Vec<_> = (0..100)
+            .map(|i| label(&format!("root//:dep{}", i), Configuration::testing_new()))
+            .collect();
+        for i in 0..100 {
+            tracker.record_configured_target(
+                &label(&format!("root//:node{}", i), Configuration::testing_new()),
+                &deps[..i],
+                [],
+            )?;
+            tracker.record_actions(
+                &label(&format!("root//:node{}", i), Configuration::testing_new()),
+                i,
+            )?;
+        }
+
+        let records = tracker.records.lock();
+        assert_eq!(REPORT_TOP, records.nodes.0.len());
+        assert_eq!(REPORT_TOP, records.actions.0.len());
+        assert_eq!(
+            (90..100).rev().collect::<Vec<_>>(),
+            records
+                .nodes
+                .sorted()
+                .iter()
+                .map(|node| node.deps)
+                .collect::<Vec<_>>()
+        );
+        let report = records.report();
+        assert!(
+            report.contains("99 deps (0 in another configuration)  root//:node99"),
+            "{}",
+            report
+        );
+        assert!(!report.contains("root//:node89"), "{}", report);
+        Ok(())
+    }
+}
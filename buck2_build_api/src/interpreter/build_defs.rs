@@ -146,6 +146,9 @@ mod tests {
     use buck2_common::package_listing::listing::PackageListing;
     use buck2_core::build_file_path::BuildFilePath;
     use buck2_core::bzl::ImportPath;
+    use buck2_core::target::testing::TargetLabelExt;
+    use buck2_core::target::TargetLabel;
+    use buck2_core::target::TargetName;
     use buck2_interpreter::file_loader::LoadedModules;
     use buck2_node::attrs::inspect_options::AttrInspectOptions;
     use buck2_node::nodes::unconfigured::testing::targets_to_json;
@@ -420,4 +423,138 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_default_target_platform() -> anyhow::Result<()> {
+        let targets = Tester::new()?.run_starlark_test(indoc!(
+            r#"
+            def _impl(ctx):
+                pass
+            export_file = rule(impl=_impl, attrs = {})
+
+            def test():
+                default_target_platform("//platforms:p1")
+                export_file(name = "inherits")
+                export_file(name = "overrides", default_target_platform = "//platforms:p2")
+            "#
+        ))?;
+        let default_target_platform = |name| {
+            targets
+                .get(&TargetName::unchecked_new(name))
+                .unwrap()
+                .get_default_target_platform()
+                .cloned()
+        };
+        assert_eq!(
+            Some(TargetLabel::testing_parse("root//platforms:p1")),
+            default_target_platform("inherits")
+        );
+        assert_eq!(
+            Some(TargetLabel::testing_parse("root//platforms:p2")),
+            default_target_platform("overrides")
+        );
+
+        run_starlark_test_expecting_error(
+            indoc!(
+                r#"
+            def test():
+                default_target_platform("//platforms:p1")
+                default_target_platform("//platforms:p2")
+            "#
+            ),
+            "more than once",
+        );
+        run_starlark_test_expecting_error(
+            indoc!(
+                r#"
+            def _impl(ctx):
+                pass
+            export_file = rule(impl=_impl, attrs = {})
+
+            def test():
+                export_file(name = "rule_name")
+                default_target_platform("//platforms:p1")
+            "#
+            ),
+            "after one or more targets",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_within_view() -> anyhow::Result<()> {
+        let targets = Tester::new()?.run_starlark_test(indoc!(
+            r#"
+            def _impl(ctx):
+                pass
+            library = rule(impl=_impl, attrs = {
+                "within_view": attrs.option(attrs.list(attrs.string()), default = None),
+            })
+            export_file = rule(impl=_impl, attrs = {})
+
+            def test():
+                default_within_view(["//base/..."])
+                library(name = "inherits")
+                library(name = "overrides", within_view = ["//ui/..."])
+                library(name = "public", within_view = ["PUBLIC"])
+                export_file(name = "undeclared")
+            "#
+        ))?;
+        let is_within_view = |name, dep| {
+            targets
+                .get(&TargetName::unchecked_new(name))
+                .unwrap()
+                .is_within_view(&TargetLabel::testing_parse(dep))
+        };
+        assert!(is_within_view("inherits", "root//base/util:util"));
+        assert!(!is_within_view("inherits", "root//ui:ui"));
+        // Targets in the same package are always within view.
+        assert!(is_within_view("inherits", "root//some/package:other"));
+        assert!(is_within_view("overrides", "root//ui:ui"));
+        assert!(!is_within_view("overrides", "root//base/util:util"));
+        assert!(is_within_view("public", "root//ui:ui"));
+        // Rules that don't declare `within_view` still get the package default.
+        assert!(!is_within_view("undeclared", "root//ui:ui"));
+
+        run_starlark_test_expecting_error(
+            indoc!(
+                r#"
+            def test():
+                default_within_view(["//base/..."])
+                default_within_view(["//ui/..."])
+            "#
+            ),
+            "more than once",
+        );
+        run_starlark_test_expecting_error(
+            indoc!(
+                r#"
+            def _impl(ctx):
+                pass
+            library = rule(impl=_impl, attrs = {
+                "within_view": attrs.option(attrs.list(attrs.string()), default = None),
+            })
+
+            def test():
+                library(name = "rule_name")
+                default_within_view(["//base/..."])
+            "#
+            ),
+            "after one or more targets",
+        );
+        run_starlark_test_expecting_error(
+            indoc!(
+                r#"
+            def _impl(ctx):
+                pass
+            library = rule(impl=_impl, attrs = {"within_view": attrs.string()})
+
+            def test():
+                library(name = "rule_name", within_view = "//base/...")
+            "#
+            ),
+            "must be a list of visibility patterns",
+        );
+        Ok(())
+    }
 }
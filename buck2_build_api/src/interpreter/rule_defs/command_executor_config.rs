@@ -40,6 +40,8 @@ use thiserror::Error;
 enum CommandExecutorConfigErrors {
     #[error("expected a dict, got `{0}` (type `{1}`)")]
     RePropertiesNotADict(String, String),
+    #[error("expected a dict for `local_env_pins`, got `{0}` (type `{1}`)")]
+    LocalEnvPinsNotADict(String, String),
 }
 
 #[derive(Clone, Debug, Trace, ProvidesStaticType, Allocative)]
@@ -50,6 +52,8 @@ struct StarlarkCommandExecutorConfig<'v> {
     /// Whether to use local execution for this execution platform. If both
     /// remote_enabled and local_enabled are `True`, we will use the hybrid executor.
     pub(super) local_enabled: bool,
+    /// Environment variables to pin to a fixed value when running locally on this platform.
+    pub(super) local_env_pins: Value<'v>, // [Dict, None]
     /// properties for remote execution for this platform
     pub(super) remote_execution_properties: Value<'v>,
     /// A component to inject into the action key. This should typically used to inject variability
@@ -60,6 +64,8 @@ struct StarlarkCommandExecutorConfig<'v> {
     pub(super) remote_execution_max_input_files_mebibytes: Option<i32>,
     /// The use case to use when communicating with RE.
     pub(super) remote_execution_use_case: Value<'v>, // String
+    /// Whether the RE backend supports persistent workers.
+    pub(super) remote_execution_persistent_workers: bool,
     /// Whether to use the limited hybrid executor
     pub(super) use_limited_hybrid: bool,
     /// Whether to allow fallbacks
@@ -82,6 +88,7 @@ impl<'v> fmt::Display for StarlarkCommandExecutorConfig<'v> {
         write!(f, "CommandExecutorConfig(")?;
         write!(f, "remote_enabled = {}, ", self.remote_enabled)?;
         write!(f, "local_enabled = {}, ", self.local_enabled)?;
+        write!(f, "local_env_pins = {}, ", self.local_env_pins)?;
         write!(
             f,
             "remote_execution_properties = {}, ",
@@ -102,6 +109,11 @@ impl<'v> fmt::Display for StarlarkCommandExecutorConfig<'v> {
             "remote_execution_use_case = {}, ",
             self.remote_execution_use_case
         )?;
+        write!(
+            f,
+            "remote_execution_persistent_workers = {}, ",
+            self.remote_execution_persistent_workers
+        )?;
         write!(f, "use_limited_hybrid = {}, ", self.use_limited_hybrid)?;
         write!(
             f,
@@ -125,7 +137,22 @@ impl<'v> StarlarkValue<'v> for StarlarkCommandExecutorConfig<'v> {
 impl<'v> StarlarkCommandExecutorConfig<'v> {
     pub fn to_command_executor_config(&self) -> anyhow::Result<CommandExecutorConfig> {
         let local_options = if self.local_enabled {
-            Some(LocalExecutorOptions {})
+            let env_pins = if self.local_env_pins.is_none() {
+                Default::default()
+            } else {
+                let env_pins =
+                    Dict::from_value(self.local_env_pins.to_value()).ok_or_else(|| {
+                        CommandExecutorConfigErrors::LocalEnvPinsNotADict(
+                            self.local_env_pins.to_value().to_repr(),
+                            self.local_env_pins.to_value().get_type().to_owned(),
+                        )
+                    })?;
+                env_pins
+                    .iter()
+                    .map(|(k, v)| (k.to_str(), v.to_str()))
+                    .collect()
+            };
+            Some(LocalExecutorOptions { env_pins })
         } else {
             None
         };
@@ -170,6 +197,7 @@ impl<'v> StarlarkCommandExecutorConfig<'v> {
                 re_action_key,
                 re_max_input_files_bytes,
                 re_use_case,
+                re_persistent_workers: self.remote_execution_persistent_workers,
             })
         } else {
             None
@@ -268,11 +296,13 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
     fn CommandExecutorConfig<'v>(
         local_enabled: bool,
         remote_enabled: bool,
+        #[starlark(default = NoneType, require = named)] local_env_pins: Value<'v>,
         #[starlark(default = NoneType, require = named)] remote_execution_properties: Value<'v>,
         #[starlark(default = NoneType, require = named)] remote_execution_action_key: Value<'v>,
         #[starlark(default = NoneOr::None, require = named)]
         remote_execution_max_input_files_mebibytes: NoneOr<i32>,
         #[starlark(default = NoneType, require = named)] remote_execution_use_case: Value<'v>,
+        #[starlark(default = false, require = named)] remote_execution_persistent_workers: bool,
         #[starlark(default = false, require = named)] use_limited_hybrid: bool,
         #[starlark(default = false, require = named)] allow_limited_hybrid_fallbacks: bool,
         #[starlark(default = false, require = named)] allow_hybrid_fallbacks_on_failure: bool,
@@ -287,11 +317,13 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
         let config = StarlarkCommandExecutorConfig {
             remote_enabled,
             local_enabled,
+            local_env_pins,
             remote_execution_properties,
             remote_execution_action_key,
             remote_execution_max_input_files_mebibytes: remote_execution_max_input_files_mebibytes
                 .into_option(),
             remote_execution_use_case,
+            remote_execution_persistent_workers,
             use_limited_hybrid,
             allow_limited_hybrid_fallbacks,
             allow_hybrid_fallbacks_on_failure,
@@ -21,6 +21,8 @@ use buck2_core::category::Category;
 use buck2_core::collections::ordered_set::OrderedSet;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 use buck2_core::fs::paths::RelativePathBuf;
+use buck2_execute::execute::network::NetworkPolicy;
+use buck2_execute::execute::persistent_worker::PersistentWorkerProtocol;
 use buck2_execute::execute::request::OutputType;
 use buck2_execute::materialize::http::Checksum;
 use buck2_interpreter::starlark_promise::StarlarkPromise;
@@ -29,6 +31,7 @@ use chrono::TimeZone;
 use chrono::Utc;
 use derive_more::Display;
 use dice::DiceComputations;
+use either::Either;
 use gazebo::any::ProvidesStaticType;
 use gazebo::prelude::*;
 use indexmap::indexset;
@@ -70,8 +73,9 @@ use crate::actions::impls::copy::CopyMode;
 use crate::actions::impls::copy::UnregisteredCopyAction;
 use crate::actions::impls::download_file::UnregisteredDownloadFileAction;
 use crate::actions::impls::run::dep_files::RunActionDepFiles;
-use crate::actions::impls::run::new_executor_preference;
+use crate::actions::impls::run::quotas::RunActionQuotas;
 use crate::actions::impls::run::MetadataParameter;
+use crate::actions::impls::run::RunActionDefaults;
 use crate::actions::impls::run::UnregisteredRunAction;
 use crate::actions::impls::symlinked_dir::UnregisteredSymlinkedDirAction;
 use crate::actions::impls::write::UnregisteredWriteAction;
@@ -250,6 +254,14 @@ impl<'v> AnalysisContext<'v> {
         self.actions.state().assert_no_promises()
     }
 
+    pub(crate) fn attributes(&self) -> Value<'v> {
+        self.attributes
+    }
+
+    pub(crate) fn set_run_action_defaults(&self, defaults: RunActionDefaults) {
+        self.actions.state().set_run_action_defaults(defaults);
+    }
+
     /// Must take an `AnalysisContext` which has never had `take_state` called on it before.
     pub(crate) fn take_state(&self) -> AnalysisRegistry<'v> {
         self.actions
@@ -348,6 +360,14 @@ enum RunActionError {
         "missing `metadata_env_var` parameter which is required when `metadata_path` parameter is present"
     )]
     MetadataEnvVarMissing,
+    #[error(
+        "`incremental = True` requires `dep_files`, which decide whether the previous outputs can be reused"
+    )]
+    IncrementalWithoutDepFiles,
+    #[error(
+        "`incremental = True` requires `local_only = True`, since the previous outputs are only available locally"
+    )]
+    IncrementalNotLocalOnly,
 }
 
 #[derive(Debug, Error)]
@@ -712,10 +732,11 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
         #[starlark(require = named)] category: String,
         #[starlark(require = named, default = NoneOr::None)] identifier: NoneOr<String>,
         #[starlark(require = named)] env: Option<ValueOf<'v, SmallMap<&'v str, Value<'v>>>>,
-        #[starlark(require = named, default = false)] local_only: bool,
-        #[starlark(require = named, default = false)] prefer_local: bool,
+        #[starlark(require = named)] local_only: Option<bool>,
+        #[starlark(require = named)] prefer_local: Option<bool>,
+        #[starlark(require = named)] prefer_remote: Option<bool>,
         #[starlark(require = named, default = false)] always_print_stderr: bool,
-        #[starlark(require = named, default = 1)] weight: i32,
+        #[starlark(require = named)] weight: Option<i32>,
         #[starlark(require = named)] dep_files: Option<ValueOf<'v, SmallMap<&'v str, Value<'v>>>>,
         #[starlark(require = named)] metadata_env_var: Option<String>,
         #[starlark(require = named)] metadata_path: Option<String>,
@@ -723,6 +744,15 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
         #[starlark(require = named, default = false)] no_outputs_cleanup: bool,
         #[starlark(require = named, default = false)] allow_cache_upload: bool,
         #[starlark(require = named, default = false)] force_full_hybrid_if_capable: bool,
+        #[starlark(require = named)] max_output_size: Option<Either<i32, &str>>,
+        #[starlark(require = named)] max_runtime_seconds: Option<i32>,
+        #[starlark(require = named, default = "error")] quota_violation: &str,
+        #[starlark(require = named)] remote_persistent_worker: Option<&str>,
+        #[starlark(require = named)] diagnostic_outputs: Option<Vec<String>>,
+        #[starlark(require = named, default = false)] use_jobserver: bool,
+        #[starlark(require = named, default = false)] scrub_env: bool,
+        #[starlark(require = named, default = false)] incremental: bool,
+        #[starlark(require = named, default = "allowed")] network: &str,
         heap: &'v Heap,
     ) -> anyhow::Result<NoneType> {
         struct RunCommandArtifactVisitor {
@@ -759,17 +789,23 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
             }
         }
 
-        let executor_preference = new_executor_preference(local_only, prefer_local)?;
+        // Requirements not set here default to those declared by the rule.
+        let defaults = this.state().run_action_defaults();
+        let executor_preference =
+            defaults.executor_preference(local_only, prefer_local, prefer_remote)?;
 
         let mut artifact_visitor = RunCommandArtifactVisitor::new();
 
         let starlark_cli = StarlarkCommandLine::try_from_value(arguments)?;
         starlark_cli.visit_artifacts(&mut artifact_visitor)?;
 
-        if weight < 1 {
-            return Err(RunActionError::InvalidWeight(weight).into());
-        }
-        let weight = weight as usize;
+        let weight = match weight {
+            None => defaults.weight,
+            Some(weight) if weight < 1 => {
+                return Err(RunActionError::InvalidWeight(weight).into());
+            }
+            Some(weight) => weight as usize,
+        };
 
         let starlark_env = match env {
             None => Value::new_none(),
@@ -826,6 +862,14 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
 
         let category = Category::try_from(category)?;
         let identifier = identifier.into_option();
+        let quotas = RunActionQuotas::new(max_output_size, max_runtime_seconds, quota_violation)?;
+        let remote_persistent_worker = remote_persistent_worker
+            .map(PersistentWorkerProtocol::parse)
+            .transpose()?;
+        let diagnostic_outputs = diagnostic_outputs
+            .unwrap_or_default()
+            .into_try_map(ForwardRelativePathBuf::try_from)?;
+        let network = NetworkPolicy::parse(network)?;
 
         let metadata_param = match (metadata_env_var, metadata_path) {
             (Some(env_var), Some(path)) => {
@@ -841,6 +885,14 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
         if artifacts.outputs.is_empty() {
             return Err(RunActionError::NoOutputsSpecified.into());
         }
+        if incremental {
+            if dep_files_configuration.labels.is_empty() {
+                return Err(RunActionError::IncrementalWithoutDepFiles.into());
+            }
+            if !executor_preference.requires_local() {
+                return Err(RunActionError::IncrementalNotLocalOnly.into());
+            }
+        }
         let starlark = heap.alloc((starlark_cli, starlark_env));
 
         let action = UnregisteredRunAction {
@@ -854,6 +906,13 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
             no_outputs_cleanup,
             allow_cache_upload,
             force_full_hybrid_if_capable,
+            quotas,
+            remote_persistent_worker,
+            diagnostic_outputs,
+            use_jobserver,
+            scrub_env,
+            incremental,
+            network,
         };
         this.state().register_action(
             artifacts.inputs,
@@ -7,12 +7,12 @@
  * of this source tree.
  */
 
+use buck2_data::WarningCategory;
 use fancy_regex::Regex;
 use starlark::environment::GlobalsBuilder;
 use starlark::eval::Evaluator;
 use starlark::values::dict::DictRef;
 use starlark::values::none::NoneType;
-use tracing::warn;
 
 use crate::interpreter::rule_defs::provider::registration::register_builtin_providers;
 
@@ -30,6 +30,12 @@ pub mod transition;
 pub mod transitive_set;
 pub mod util;
 
+#[derive(Debug, thiserror::Error)]
+enum WarningError {
+    #[error("Unknown warning category `{0}`, expected `other` or `deprecated`")]
+    UnknownCategory(String),
+}
+
 #[starlark_module]
 fn extra_functions(builder: &mut GlobalsBuilder) {
     // Load symbols into the module. Should only be used by infra_macros/DEFS.bzl
@@ -49,9 +55,19 @@ fn extra_functions(builder: &mut GlobalsBuilder) {
         Ok(re.is_match(str)?)
     }
 
-    /// Produce a warning.
-    fn warning(#[starlark(require = pos)] x: &str) -> anyhow::Result<NoneType> {
-        warn!("{}", x);
+    /// Produce a warning. Warnings are deduplicated and summarized at the end of the command,
+    /// and included in the build report. Set `category = "deprecated"` for uses of deprecated
+    /// features.
+    fn warning(
+        #[starlark(require = pos)] x: &str,
+        #[starlark(require = named, default = "other")] category: &str,
+    ) -> anyhow::Result<NoneType> {
+        let category = match category {
+            "other" => WarningCategory::Other,
+            "deprecated" => WarningCategory::Deprecated,
+            _ => return Err(WarningError::UnknownCategory(category.to_owned()).into()),
+        };
+        buck2_events::dispatch::warning(category, String::new(), x.to_owned());
         Ok(NoneType)
     }
 }
@@ -0,0 +1,107 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use allocative::Allocative;
+use buck2_build_api_derive::internal_provider;
+use gazebo::any::ProvidesStaticType;
+use gazebo::coerce::Coerce;
+use starlark::environment::GlobalsBuilder;
+use starlark::values::list::List;
+use starlark::values::Freeze;
+use starlark::values::Trace;
+use starlark::values::Value;
+use starlark::values::ValueError;
+use starlark::values::ValueLike;
+
+use crate::actions::artifact::Artifact;
+use crate::interpreter::rule_defs::artifact::StarlarkArtifact;
+use crate::interpreter::rule_defs::artifact::ValueAsArtifactLike;
+
+/// Provider that declares the outputs of a rule whose size matters, e.g. binaries or APKs.
+///
+/// When a target with this provider is built, the total size of these outputs is recorded in the
+/// event log, whether or not the actions producing them had to run, so that
+/// `buck2 log size-report` can compare sizes between builds.
+#[internal_provider(output_size_info_creator)]
+#[derive(Clone, Coerce, Debug, Freeze, Trace, ProvidesStaticType, Allocative)]
+#[repr(C)]
+pub struct OutputSizeInfoGen<V> {
+    /// The outputs whose size is reported.
+    #[provider(field_type = "Vec<StarlarkArtifact>")]
+    outputs: V,
+}
+
+impl FrozenOutputSizeInfo {
+    pub fn get_outputs(&self) -> anyhow::Result<Vec<Artifact>> {
+        List::from_value(self.outputs.to_value())
+            .expect("should be a list from constructor")
+            .iter()
+            .map(|v| {
+                v.as_artifact()
+                    .ok_or_else(|| anyhow::anyhow!("not an artifact"))?
+                    .get_bound_artifact()
+            })
+            .collect()
+    }
+}
+
+#[starlark_module]
+fn output_size_info_creator(globals: &mut GlobalsBuilder) {
+    fn OutputSizeInfo<'v>(outputs: Value<'v>) -> anyhow::Result<OutputSizeInfo<'v>> {
+        match List::from_value(outputs) {
+            Some(list) if list.iter().all(|v| v.as_artifact().is_some()) => {
+                Ok(OutputSizeInfo { outputs })
+            }
+            _ => Err(anyhow::anyhow!(ValueError::IncorrectParameterTypeNamed(
+                "outputs".to_owned()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::result::SharedResult;
+    use indoc::indoc;
+
+    use crate::interpreter::rule_defs::artifact::testing::artifactory;
+    use crate::interpreter::rule_defs::provider::collection::tester::collection_creator;
+    use crate::interpreter::testing::run_starlark_bzl_test_expecting_error;
+    use crate::interpreter::testing::Tester;
+
+    #[test]
+    fn output_size_info_works_as_provider_key() -> SharedResult<()> {
+        let mut tester = Tester::new()?;
+        tester.set_additional_globals(|builder| {
+            collection_creator(builder);
+            artifactory(builder);
+        });
+
+        tester.run_starlark_bzl_test(indoc!(
+            r#"
+            def test():
+                binary = source_artifact("foo/bar", "bin")
+                c = create_collection([DefaultInfo(), OutputSizeInfo(outputs = [binary])])
+                assert_eq(True, contains_provider(c, OutputSizeInfo))
+                assert_eq([binary], c[OutputSizeInfo].outputs)
+            "#
+        ))?;
+
+        run_starlark_bzl_test_expecting_error(
+            indoc!(
+                r#"
+                def test():
+                    OutputSizeInfo(outputs = ["bin"])
+                "#
+            ),
+            "Type of parameter `outputs` doesn't match",
+        );
+        Ok(())
+    }
+}
@@ -294,6 +294,23 @@ where
         Ok(self.get_impl(other, GetOp::In)?.is_left())
     }
 
+    fn equals(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        let other = match ProviderCollection::from_value(other) {
+            Some(other) => other,
+            None => return Ok(false),
+        };
+        if self.providers.len() != other.providers.len() {
+            return Ok(false);
+        }
+        for (id, value) in &self.providers {
+            match other.providers.get(id) {
+                Some(other_value) if value.to_value().equals(*other_value)? => {}
+                _ => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
     fn get_methods() -> Option<&'static Methods>
     where
         Self: Sized,
@@ -397,6 +414,13 @@ impl FrozenProviderCollectionValue {
         self.value.as_ref()
     }
 
+    /// Whether the two collections hold equal providers, e.g. when a target is reanalyzed to the
+    /// same result. Values without structural equality (e.g. functions) are only equal to
+    /// themselves, so this errs on the side of returning `false`.
+    pub fn equals(&self, other: &Self) -> anyhow::Result<bool> {
+        self.value.to_value().equals(other.value.to_value())
+    }
+
     pub fn lookup_inner(&self, label: &ConfiguredProvidersLabel) -> anyhow::Result<Self> {
         match label.name() {
             ProvidersName::Default => anyhow::Ok(self.dupe()),
@@ -584,6 +608,25 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn provider_collection_equality() -> SharedResult<()> {
+        let mut tester = provider_collection_tester()?;
+        tester.run_starlark_bzl_test(indoc!(
+            r#"
+            load("//provider:defs1.bzl", "FooInfo")
+            load("//provider:defs2.bzl", "foo1", "foo2", "bar1")
+            def test():
+                col = create_collection([foo1, DefaultInfo(default_outputs=[bound_artifact("//foo:bar", "out")])])
+                same = create_collection([FooInfo(foo="foo1"), DefaultInfo(default_outputs=[bound_artifact("//foo:bar", "out")])])
+                assert_eq(col, same)
+                assert_ne(col, create_collection([foo2, DefaultInfo(default_outputs=[bound_artifact("//foo:bar", "out")])]))
+                assert_ne(col, create_collection([foo1, DefaultInfo(default_outputs=[bound_artifact("//foo:bar", "other")])]))
+                assert_ne(col, create_collection([foo1, bar1, DefaultInfo(default_outputs=[bound_artifact("//foo:bar", "out")])]))
+            "#
+        ))?;
+        Ok(())
+    }
+
     #[test]
     fn provider_collection_fails_to_construct_on_bad_data() -> SharedResult<()> {
         let mut tester = provider_collection_tester()?;
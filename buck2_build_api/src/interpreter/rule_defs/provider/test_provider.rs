@@ -28,9 +28,12 @@ pub trait TestProvider {
 
     fn labels(&self) -> Vec<&str>;
 
+    /// Send the spec of this test to the executor. `test_name_filter` is a regex that the names
+    /// of the test cases it runs must match.
     fn dispatch<'exec>(
         &self,
         target: ConfiguredTarget,
+        test_name_filter: Option<String>,
         executor: Arc<dyn TestExecutor + 'exec>,
     ) -> BoxFuture<'exec, anyhow::Result<()>>;
 }
@@ -47,6 +50,7 @@ impl TestProvider for FrozenExternalRunnerTestInfo {
     fn dispatch<'exec>(
         &self,
         target: ConfiguredTarget,
+        test_name_filter: Option<String>,
         executor: Arc<dyn TestExecutor + 'exec>,
     ) -> BoxFuture<'exec, anyhow::Result<()>> {
         let mut handle_index = 0;
@@ -84,6 +88,7 @@ impl TestProvider for FrozenExternalRunnerTestInfo {
             labels: self.labels().map(|l| l.to_owned()).collect(),
             contacts: self.contacts().map(|l| l.to_owned()).collect(),
             oncall: self.contacts().exactly_one().ok().map(str::to_owned),
+            test_name_filter,
         };
 
         async move { executor.external_runner_spec(spec).await }.boxed()
@@ -23,6 +23,9 @@ use buck2_interpreter_for_build::nodes::attr_spec::AttributeSpecExt;
 use buck2_interpreter_for_build::nodes::unconfigured::TargetNodeExt;
 use buck2_interpreter_for_build::transition::transition_id_from_value;
 use buck2_node::attrs::attr::Attribute;
+use buck2_node::attrs::attr_type::attr_literal::AttrLiteral;
+use buck2_node::attrs::attr_type::AttrType;
+use buck2_node::attrs::coerced_attr::CoercedAttr;
 use buck2_node::attrs::spec::AttributeSpec;
 use buck2_node::nodes::unconfigured::RuleKind;
 use buck2_node::nodes::unconfigured::TargetNode;
@@ -51,8 +54,18 @@ use starlark::values::StarlarkValue;
 use starlark::values::Trace;
 use starlark::values::Value;
 
+use crate::actions::impls::run::RunActionDefaults;
+
 pub static NAME_ATTRIBUTE_FIELD: &str = "name";
 
+/// The execution requirements a rule can declare defaults for, for the actions it runs with
+/// `ctx.actions.run`. Each one declared is also an attribute of the rule, so that targets can
+/// override it.
+pub static LOCAL_ONLY_ATTRIBUTE_FIELD: &str = "local_only";
+pub static PREFER_LOCAL_ATTRIBUTE_FIELD: &str = "prefer_local";
+pub static PREFER_REMOTE_ATTRIBUTE_FIELD: &str = "prefer_remote";
+pub static WEIGHT_ATTRIBUTE_FIELD: &str = "weight";
+
 /// The callable that's returned from a `rule()` call. Once frozen, and called, it adds targets'
 /// parameters to the context
 #[derive(Debug, Clone, ProvidesStaticType, Trace, NoSerialize, Allocative)]
@@ -76,6 +89,8 @@ struct RuleCallable<'v> {
     docs: Option<String>,
     /// When evaluating rule function, take only the `name` argument, ignore the others.
     ignore_attrs_for_profiling: bool,
+    /// The attributes of the execution requirements this rule declares defaults for.
+    exec_requirement_attrs: Vec<&'static str>,
 }
 
 impl<'v> Display for RuleCallable<'v> {
@@ -97,9 +112,13 @@ enum RuleError {
     #[error("Rule defined in `{0}` must be assigned to a variable, e.g. `my_rule = rule(...)`")]
     RuleNotAssigned(ImportPath),
     #[error(
-        "Rule defined with both `is_configuration_rule` and `is_toolchain_rule`, these options are mutually exclusive"
+        "Rule defined with more than one of `is_configuration_rule`, `is_toolchain_rule` and `is_alias_rule`, these options are mutually exclusive"
+    )]
+    ConflictingRuleKinds,
+    #[error(
+        "Rule declares a default for `{0}`, which makes it an attribute, so it can't be in `attrs` too"
     )]
-    IsConfigurationAndToolchain,
+    ExecRequirementAttrDeclared(&'static str),
 }
 
 impl<'v> AllocValue<'v> for RuleCallable<'v> {
@@ -180,6 +199,7 @@ impl<'v> Freeze for RuleCallable<'v> {
             rule_kind: self.rule_kind,
             rule_docs,
             ignore_attrs_for_profiling: self.ignore_attrs_for_profiling,
+            exec_requirement_attrs: self.exec_requirement_attrs,
         })
     }
 }
@@ -195,6 +215,7 @@ pub struct FrozenRuleCallable {
     rule_kind: RuleKind,
     rule_docs: Option<DocItem>,
     ignore_attrs_for_profiling: bool,
+    exec_requirement_attrs: Vec<&'static str>,
 }
 starlark_simple_value!(FrozenRuleCallable);
 
@@ -210,6 +231,36 @@ impl FrozenRuleCallable {
     pub fn attributes(&self) -> &Arc<AttributeSpec> {
         &self.attributes
     }
+
+    /// The execution requirements of the actions of a target of this rule, from the values the
+    /// target has for the attributes of the requirements the rule declares defaults for.
+    pub(crate) fn run_action_defaults<'v>(
+        &self,
+        attrs: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<RunActionDefaults> {
+        let get = |name: &'static str| -> anyhow::Result<Option<Value<'v>>> {
+            if self.exec_requirement_attrs.contains(&name) {
+                attrs.get_attr(name, heap)
+            } else {
+                Ok(None)
+            }
+        };
+        RunActionDefaults::new(
+            get(LOCAL_ONLY_ATTRIBUTE_FIELD)?
+                .and_then(|v| v.unpack_bool())
+                .unwrap_or(false),
+            get(PREFER_LOCAL_ATTRIBUTE_FIELD)?
+                .and_then(|v| v.unpack_bool())
+                .unwrap_or(false),
+            get(PREFER_REMOTE_ATTRIBUTE_FIELD)?
+                .and_then(|v| v.unpack_bool())
+                .unwrap_or(false),
+            get(WEIGHT_ATTRIBUTE_FIELD)?
+                .and_then(|v| v.unpack_int())
+                .unwrap_or(1),
+        )
+    }
 }
 
 impl<'v> StarlarkValue<'v> for FrozenRuleCallable {
@@ -246,6 +297,7 @@ impl<'v> StarlarkValue<'v> for FrozenRuleCallable {
                 self.attributes.dupe(),
                 call_stack,
                 internals.get_oncall(),
+                internals.get_default_target_platform(),
             )?;
             internals.record(target_node)?;
             Ok(Value::new_none())
@@ -266,6 +318,11 @@ pub fn register_rule_function(builder: &mut GlobalsBuilder) {
         #[starlark(require = named, default = "")] doc: &str,
         #[starlark(require = named, default = false)] is_configuration_rule: bool,
         #[starlark(require = named, default = false)] is_toolchain_rule: bool,
+        #[starlark(require = named, default = false)] is_alias_rule: bool,
+        #[starlark(require = named)] local_only: Option<bool>,
+        #[starlark(require = named)] prefer_local: Option<bool>,
+        #[starlark(require = named)] prefer_remote: Option<bool>,
+        #[starlark(require = named)] weight: Option<i32>,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>> {
         // TODO(nmj): Add default attributes in here like 'name', 'visibility', etc
@@ -280,26 +337,46 @@ pub fn register_rule_function(builder: &mut GlobalsBuilder) {
             .unpack_load_file()
             .ok_or_else(|| anyhow::anyhow!("`rule` can only be declared in bzl files"))?)
         .clone();
-        let sorted_validated_attrs = attrs
-            .to_dict()
-            .into_iter()
-            .sorted_by(|(k1, _), (k2, _)| Ord::cmp(k1, k2))
+        // Check the defaults are valid, whether or not targets override them.
+        RunActionDefaults::new(
+            local_only.unwrap_or(false),
+            prefer_local.unwrap_or(false),
+            prefer_remote.unwrap_or(false),
+            weight.unwrap_or(1),
+        )?;
+        let exec_requirement_attrs =
+            exec_requirement_attrs(local_only, prefer_local, prefer_remote, weight);
+
+        let attrs = attrs.to_dict();
+        let mut validated_attrs = attrs
+            .iter()
             .map(|(name, value)| {
-                if name == NAME_ATTRIBUTE_FIELD {
+                if *name == NAME_ATTRIBUTE_FIELD {
                     Err(RuleError::InvalidParameterName(NAME_ATTRIBUTE_FIELD.to_owned()).into())
                 } else {
-                    Ok((name.to_owned(), value.0.clone()))
+                    Ok(((*name).to_owned(), value.0.clone()))
                 }
             })
             .collect::<anyhow::Result<Vec<(String, Attribute)>>>()?;
+        for (name, attr) in &exec_requirement_attrs {
+            if attrs.contains_key(*name) {
+                return Err(RuleError::ExecRequirementAttrDeclared(name).into());
+            }
+            validated_attrs.push(((*name).to_owned(), attr.clone()));
+        }
+        let sorted_validated_attrs = validated_attrs
+            .into_iter()
+            .sorted_by(|(k1, _), (k2, _)| Ord::cmp(k1, k2))
+            .collect::<Vec<_>>();
 
         let cfg = cfg.try_map(|x| transition_id_from_value(*x))?;
 
-        let rule_kind = match (is_configuration_rule, is_toolchain_rule) {
-            (false, false) => RuleKind::Normal,
-            (true, false) => RuleKind::Configuration,
-            (false, true) => RuleKind::Toolchain,
-            (true, true) => return Err(RuleError::IsConfigurationAndToolchain.into()),
+        let rule_kind = match (is_configuration_rule, is_toolchain_rule, is_alias_rule) {
+            (false, false, false) => RuleKind::Normal,
+            (true, false, false) => RuleKind::Configuration,
+            (false, true, false) => RuleKind::Toolchain,
+            (false, false, true) => RuleKind::Alias,
+            _ => return Err(RuleError::ConflictingRuleKinds.into()),
         };
 
         Ok(eval.heap().alloc(RuleCallable {
@@ -311,10 +388,70 @@ pub fn register_rule_function(builder: &mut GlobalsBuilder) {
             rule_kind,
             docs: Some(doc.to_owned()),
             ignore_attrs_for_profiling: build_context.ignore_attrs_for_profiling,
+            exec_requirement_attrs: exec_requirement_attrs.map(|(name, _)| *name),
         }))
     }
 }
 
+/// The attributes for the execution requirements given to `rule()`, with the values given as
+/// their defaults.
+fn exec_requirement_attrs(
+    local_only: Option<bool>,
+    prefer_local: Option<bool>,
+    prefer_remote: Option<bool>,
+    weight: Option<i32>,
+) -> Vec<(&'static str, Attribute)> {
+    let attr = |default, doc: &str, coercer| {
+        Attribute::new_internal(
+            Some(Arc::new(CoercedAttr::Literal(default))),
+            doc.to_owned(),
+            coercer,
+        )
+    };
+    let mut attrs = Vec::new();
+    if let Some(local_only) = local_only {
+        attrs.push((
+            LOCAL_ONLY_ATTRIBUTE_FIELD,
+            attr(
+                AttrLiteral::Bool(local_only),
+                "whether the actions of this target must run locally",
+                AttrType::bool(),
+            ),
+        ));
+    }
+    if let Some(prefer_local) = prefer_local {
+        attrs.push((
+            PREFER_LOCAL_ATTRIBUTE_FIELD,
+            attr(
+                AttrLiteral::Bool(prefer_local),
+                "whether the actions of this target should run locally when possible",
+                AttrType::bool(),
+            ),
+        ));
+    }
+    if let Some(prefer_remote) = prefer_remote {
+        attrs.push((
+            PREFER_REMOTE_ATTRIBUTE_FIELD,
+            attr(
+                AttrLiteral::Bool(prefer_remote),
+                "whether the actions of this target should run remotely when possible",
+                AttrType::bool(),
+            ),
+        ));
+    }
+    if let Some(weight) = weight {
+        attrs.push((
+            WEIGHT_ATTRIBUTE_FIELD,
+            attr(
+                AttrLiteral::Int(weight),
+                "how many local slots each action of this target takes",
+                AttrType::int(),
+            ),
+        ));
+    }
+    attrs
+}
+
 #[cfg(test)]
 mod tests {
     use buck2_common::result::SharedResult;
@@ -427,6 +564,81 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rule_exec_requirements_are_attributes() -> SharedResult<()> {
+        let result = run_starlark_test(indoc!(
+            r#"
+            def impl(ctx):
+                pass
+
+            foo_binary = rule(impl=impl, attrs={}, local_only=True, weight=4)
+
+            def test():
+                foo_binary(name="target1")
+                foo_binary(name="target2", local_only=False)
+            "#
+        ))?;
+        let expected = json!({
+            "target1": {
+                "name": "target1",
+                "__type__": "root//some/package/defs.bzl:foo_binary",
+                "compatible_with": [],
+                "default_target_platform": null,
+                "exec_compatible_with": [],
+                "local_only": true,
+                "target_compatible_with": [],
+                "tests": [],
+                "visibility": [],
+                "weight": 4,
+                "within_view": [],
+            },
+            "target2": {
+                "name": "target2",
+                "__type__": "root//some/package/defs.bzl:foo_binary",
+                "compatible_with": [],
+                "default_target_platform": null,
+                "exec_compatible_with": [],
+                "local_only": false,
+                "target_compatible_with": [],
+                "tests": [],
+                "visibility": [],
+                "weight": 4,
+                "within_view": [],
+            },
+        });
+        let actual = targets_to_json(&result, AttrInspectOptions::All)?;
+        assert_eq!(expected, actual, "`{:#?}` != `{:#?}`", expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn rule_exec_requirements_are_validated() {
+        run_starlark_bzl_test_expecting_error(
+            indoc!(
+                r#"
+            def impl(ctx):
+                pass
+            foo_binary = rule(impl=impl, attrs={}, weight=0)
+            def test():
+                pass
+            "#
+            ),
+            "must be a positive integer",
+        );
+        run_starlark_bzl_test_expecting_error(
+            indoc!(
+                r#"
+            def impl(ctx):
+                pass
+            foo_binary = rule(impl=impl, attrs={"weight": attrs.int(default=1)}, weight=2)
+            def test():
+                pass
+            "#
+            ),
+            "can't be in `attrs` too",
+        );
+    }
+
     #[test]
     fn udr_is_recorded() -> SharedResult<()> {
         let result = run_starlark_test(indoc!(
@@ -37,6 +37,7 @@ pub mod configure_dice;
 pub mod context;
 pub mod deferred;
 pub mod dynamic;
+pub mod graph_limits;
 pub mod interpreter;
 mod keep_going;
 pub mod nodes;
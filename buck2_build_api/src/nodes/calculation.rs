@@ -15,6 +15,7 @@ use std::sync::Arc;
 use allocative::Allocative;
 use anyhow::Context;
 use async_trait::async_trait;
+use buck2_common::pattern::resolve::ResolvedPattern;
 use buck2_common::result::SharedError;
 use buck2_common::result::SharedResult;
 use buck2_common::result::ToSharedResultExt;
@@ -24,9 +25,13 @@ use buck2_core::configuration::transition::applied::TransitionApplied;
 use buck2_core::configuration::transition::id::TransitionId;
 use buck2_core::configuration::Configuration;
 use buck2_core::configuration::ConfigurationData;
+use buck2_core::pattern::PackageSpec;
+use buck2_core::pattern::PatternType;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_core::target::ConfiguredTargetLabel;
 use buck2_core::target::TargetLabel;
+use buck2_data::ToProtoMessage;
+use buck2_events::dispatch::span_async;
 use buck2_execute::execute::dice_data::HasFallbackExecutorConfig;
 use buck2_interpreter_for_build::interpreter::calculation::InterpreterCalculation;
 use buck2_node::attrs::configuration_context::AttrConfigurationContext;
@@ -60,6 +65,8 @@ use thiserror::Error;
 
 use crate::calculation::BuildErrors;
 use crate::configuration::ConfigurationCalculation;
+use crate::graph_limits::GraphLimitError;
+use crate::graph_limits::HasGraphSizeTracker;
 use crate::interpreter::rule_defs::transition::calculation_apply_transition::ApplyTransition;
 
 #[derive(Debug, thiserror::Error)]
@@ -72,6 +79,8 @@ enum NodeCalculationError {
         LEGACY_TARGET_COMPATIBLE_WITH_ATTRIBUTE_FIELD
     )]
     BothTargetCompatibleWith(String),
+    #[error("Cycle of alias rules through `{0}`")]
+    AliasCycle(TargetLabel),
 }
 
 #[async_trait]
@@ -503,6 +512,27 @@ async fn compute_configured_target_node_no_transition(
     target_label: &ConfiguredTargetLabel,
     target_node: TargetNode,
     ctx: &DiceComputations,
+) -> SharedResult<MaybeCompatible<ConfiguredTargetNode>> {
+    span_async(
+        buck2_data::ConfigureTargetStart {
+            target: Some(target_label.as_proto()),
+        },
+        async {
+            (
+                configure_target_node(target_label, target_node, ctx).await,
+                buck2_data::ConfigureTargetEnd {
+                    target: Some(target_label.as_proto()),
+                },
+            )
+        },
+    )
+    .await
+}
+
+async fn configure_target_node(
+    target_label: &ConfiguredTargetLabel,
+    target_node: TargetNode,
+    ctx: &DiceComputations,
 ) -> SharedResult<MaybeCompatible<ConfiguredTargetNode>> {
     let target_cfg = target_label.cfg();
     let target_cell = target_node.label().pkg().cell_name();
@@ -523,6 +553,7 @@ async fn compute_configured_target_node_no_transition(
 
     struct Traversal<'a> {
         deps: &'a mut SmallSet<ConfiguredProvidersLabel>,
+        toolchain_deps: &'a mut SmallSet<ConfiguredProvidersLabel>,
         exec_deps: &'a mut SmallSet<ConfiguredProvidersLabel>,
     }
 
@@ -536,6 +567,11 @@ async fn compute_configured_target_node_no_transition(
             self.exec_deps.insert(dep.clone());
             Ok(())
         }
+
+        fn toolchain_dep(&mut self, dep: &'a ConfiguredProvidersLabel) -> anyhow::Result<()> {
+            self.toolchain_deps.insert(dep.clone());
+            Ok(())
+        }
     }
 
     let mut resolved_transitions = OrderedMap::new();
@@ -576,6 +612,7 @@ async fn compute_configured_target_node_no_transition(
     };
 
     let mut deps = SmallSet::new();
+    let mut toolchain_deps = SmallSet::new();
     let mut exec_deps = SmallSet::new();
 
     let platform_cfgs = compute_platform_cfgs(ctx, &target_node).await?;
@@ -585,6 +622,7 @@ async fn compute_configured_target_node_no_transition(
     for (attr_name, attr) in target_node.attrs(AttrInspectOptions::All) {
         let mut traversal = Traversal {
             deps: &mut deps,
+            toolchain_deps: &mut toolchain_deps,
             exec_deps: &mut exec_deps,
         };
         let attr_cfg_ctx = AttrConfigurationContextImpl {
@@ -600,22 +638,47 @@ async fn compute_configured_target_node_no_transition(
         configured_attr.traverse(&mut traversal)?;
     }
 
+    // Check the graph limits before configuring the deps, so that an exploding graph stops here.
+    if let Some(tracker) = ctx.get_graph_size_tracker() {
+        tracker.record_configured_target(
+            target_label,
+            deps.iter()
+                .chain(toolchain_deps.iter())
+                .chain(exec_deps.iter())
+                .map(|dep| dep.target()),
+            resolved_transitions.iter().map(|(id, applied)| {
+                let configurations = match &**applied {
+                    TransitionApplied::Single(_) => 1,
+                    TransitionApplied::Split(split) => split.len(),
+                };
+                (&**id, configurations)
+            }),
+        )?;
+    }
+
     // Check transitive target compatibility.
     let dep_futures = deps
         .iter()
         .map(|v| ctx.get_configured_target_node(v.target()));
 
+    let toolchain_dep_futures = toolchain_deps
+        .iter()
+        .map(|v| ctx.get_configured_target_node(v.target()));
+
     let exec_dep_futures = exec_deps
         .iter()
         .map(|v| ctx.get_configured_target_node(v.target()));
 
-    let (dep_results, exec_dep_results): (Vec<_>, Vec<_>) = futures::future::join(
-        futures::future::join_all(dep_futures),
-        futures::future::join_all(exec_dep_futures),
-    )
-    .await;
+    let (dep_results, toolchain_dep_results, exec_dep_results): (Vec<_>, Vec<_>, Vec<_>) =
+        futures::future::join3(
+            futures::future::join_all(dep_futures),
+            futures::future::join_all(toolchain_dep_futures),
+            futures::future::join_all(exec_dep_futures),
+        )
+        .await;
 
     let mut deps = OrderedSet::with_capacity(deps.len());
+    let mut toolchain_deps = OrderedSet::with_capacity(toolchain_deps.len());
     let mut exec_deps = OrderedSet::with_capacity(exec_deps.len());
 
     let unpack_dep = |
@@ -638,6 +701,14 @@ async fn compute_configured_target_node_no_transition(
                         )))
                         .shared_error(),
                     )
+                } else if !target_node.is_within_view(dep.name().unconfigured()) {
+                    ControlFlow::Break(
+                        Err(anyhow::anyhow!(VisibilityError::NotWithinView(
+                            dep.name().unconfigured().dupe(),
+                            target_label.unconfigured().dupe(),
+                        )))
+                        .shared_error(),
+                    )
                 } else {
                     ControlFlow::Continue(dep)
                 }
@@ -651,6 +722,12 @@ async fn compute_configured_target_node_no_transition(
             ControlFlow::Break(r) => return r,
         };
     }
+    for dep in toolchain_dep_results {
+        match unpack_dep(dep) {
+            ControlFlow::Continue(dep) => toolchain_deps.insert(dep),
+            ControlFlow::Break(r) => return r,
+        };
+    }
     for dep in exec_dep_results {
         match unpack_dep(dep) {
             ControlFlow::Continue(dep) => exec_deps.insert(dep),
@@ -665,6 +742,7 @@ async fn compute_configured_target_node_no_transition(
         resolved_transitions,
         execution_platform_resolution,
         deps,
+        toolchain_deps,
         exec_deps,
         platform_cfgs,
     )))
@@ -820,6 +898,13 @@ impl NodeCalculation for DiceComputations {
                 Ok(res.with_context(|| format!("when looking up configured node {}", self.0))?)
             }
 
+            fn validity(x: &Self::Value) -> bool {
+                match x {
+                    Err(e) => !GraphLimitError::is_cause_of(e),
+                    Ok(_) => true,
+                }
+            }
+
             fn equality(x: &Self::Value, y: &Self::Value) -> bool {
                 match (x, y) {
                     (Ok(x), Ok(y)) => x == y,
@@ -832,21 +917,84 @@ impl NodeCalculation for DiceComputations {
     }
 }
 
+/// Follows chains of targets of alias rules (rules declared with `is_alias_rule = True`) to the
+/// target they stand for. Stops at an alias target that doesn't have exactly one dependency, e.g.
+/// because it depends on a `select()`, since it has no single canonical target.
+pub(crate) async fn resolve_alias_rules(
+    ctx: &DiceComputations,
+    target: &TargetLabel,
+) -> anyhow::Result<TargetLabel> {
+    let mut node = ctx.get_target_node(target).await?;
+    let mut seen = SmallSet::new();
+    while node.is_alias_rule() {
+        let actual = match node.target_deps().exactly_one() {
+            Ok(actual) => actual.dupe(),
+            Err(_) => break,
+        };
+        if !seen.insert(node.label().dupe()) {
+            return Err(NodeCalculationError::AliasCycle(node.label().dupe()).into());
+        }
+        node = ctx
+            .get_target_node(&actual)
+            .await
+            .with_context(|| format!("Error resolving alias `{}`", node.label()))?;
+    }
+    Ok(node.label().dupe())
+}
+
+/// Replaces the targets named explicitly in `resolved` by their canonical labels (see
+/// [`resolve_alias_rules`]), so that `//foo:old`, an alias of `//bar:new`, is built, tested or
+/// queried as `//bar:new`. Targets matched by package or recursive patterns are kept as they are.
+pub async fn resolve_alias_patterns<T: PatternType>(
+    ctx: &DiceComputations,
+    resolved: ResolvedPattern<T>,
+) -> anyhow::Result<ResolvedPattern<T>> {
+    let mut packages = Vec::new();
+    let mut targets = Vec::new();
+    for (package, spec) in resolved.specs {
+        match spec {
+            PackageSpec::All => packages.push(package),
+            PackageSpec::Targets(names) => targets.extend(names.into_iter().map(|name| {
+                let label = TargetLabel::new(package.dupe(), name.target().dupe());
+                async move { anyhow::Ok((resolve_alias_rules(ctx, &label).await?, name)) }
+            })),
+        }
+    }
+    let targets = futures::future::try_join_all(targets).await?;
+
+    let mut res = ResolvedPattern::new();
+    for package in packages {
+        res.add_package(&package);
+    }
+    for (canonical, name) in targets {
+        let target = T::from_parts(canonical.name().dupe(), name.extra_parts().clone());
+        // An alias and the target it stands for can both be named.
+        match res.specs.get(canonical.pkg()) {
+            Some(PackageSpec::Targets(existing)) if existing.contains(&target) => {}
+            _ => res.add_target(canonical.pkg(), &target),
+        }
+    }
+    Ok(res)
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
 
     use buck2_common::executor_config::CommandExecutorConfig;
+    use buck2_common::pattern::resolve::ResolvedPattern;
     use buck2_core::build_file_path::BuildFilePath;
     use buck2_core::bzl::ImportPath;
     use buck2_core::configuration::Configuration;
     use buck2_core::fs::paths::file_name::FileNameBuf;
     use buck2_core::package::testing::PackageExt;
     use buck2_core::package::Package;
+    use buck2_core::pattern::PackageSpec;
     use buck2_core::provider::label::ProvidersLabel;
     use buck2_core::provider::label::ProvidersName;
     use buck2_core::target::TargetLabel;
     use buck2_core::target::TargetName;
+    use buck2_events::warnings::WithWarnings;
     use buck2_execute::execute::dice_data::set_fallback_executor_config;
     use buck2_interpreter_for_build::attrs::coerce::testing::CoercedAttrExt;
     use buck2_interpreter_for_build::attrs::coerce::testing::ConfiguredAttrExt;
@@ -864,6 +1012,7 @@ mod tests {
     use buck2_node::attrs::inspect_options::AttrInspectOptions;
     use buck2_node::nodes::eval_result::EvaluationResult;
     use buck2_node::nodes::unconfigured::testing::TargetNodeExt;
+    use buck2_node::nodes::unconfigured::RuleKind;
     use buck2_node::nodes::unconfigured::TargetNode;
     use buck2_node::nodes::unconfigured::TargetsMap;
     use buck2_node::rule_type::RuleType;
@@ -875,6 +1024,8 @@ mod tests {
     use starlark_map::smallmap;
 
     use crate::configuration::calculation::ExecutionPlatformsKey;
+    use crate::nodes::calculation::resolve_alias_patterns;
+    use crate::nodes::calculation::resolve_alias_rules;
     use crate::nodes::calculation::NodeCalculation;
 
     #[tokio::test]
@@ -953,7 +1104,10 @@ mod tests {
         let mut data = UserComputationData::new();
         set_fallback_executor_config(&mut data.data, CommandExecutorConfig::testing_local());
         let mut computations = DiceBuilder::new()
-            .mock_and_return(InterpreterResultsKey(pkg), Ok(Arc::new(eval_result)))
+            .mock_and_return(
+                InterpreterResultsKey(pkg),
+                WithWarnings::new(Ok(Arc::new(eval_result))),
+            )
             .mock_and_return(ExecutionPlatformsKey, Ok(None))
             .build(data)?;
         computations = computations.commit();
@@ -1007,4 +1161,193 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_toolchain_deps() -> anyhow::Result<()> {
+        let cfg = Configuration::testing_new();
+        let pkg = Package::testing_new("cell", "foo");
+        let label = |name| TargetLabel::new(pkg.dupe(), TargetName::unchecked_new(name));
+        let rule_type = RuleType::Starlark(Arc::new(StarlarkRuleType {
+            import_path: ImportPath::unchecked_new("cell", "foo", "def.bzl"),
+            name: "some_rule".to_owned(),
+        }));
+
+        let toolchain = TargetNode::testing_new_with_rule_kind(
+            label("toolchain"),
+            rule_type.dupe(),
+            RuleKind::Toolchain,
+            Vec::new(),
+        );
+        let binary = TargetNode::testing_new(
+            label("binary"),
+            rule_type.dupe(),
+            vec![(
+                "toolchain",
+                Attribute::testing_new(None, AttrType::toolchain_dep(Vec::new())),
+                CoercedAttr::from_literal(AttrLiteral::Dep(box DepAttr::new(
+                    DepAttrType::new(Vec::new(), DepAttrTransition::Toolchain),
+                    ProvidersLabel::new(label("toolchain"), ProvidersName::Default),
+                ))),
+            )],
+        );
+        let eval_result = EvaluationResult::new(
+            Arc::new(BuildFilePath::new(
+                pkg.dupe(),
+                FileNameBuf::unchecked_new("BUCK"),
+            )),
+            Vec::new(),
+            TargetsMap::from_iter(
+                [toolchain, binary].map(|node| (node.label().name().dupe(), node)),
+            ),
+        );
+
+        let mut data = UserComputationData::new();
+        set_fallback_executor_config(&mut data.data, CommandExecutorConfig::testing_local());
+        let computations = DiceBuilder::new()
+            .mock_and_return(
+                InterpreterResultsKey(pkg.dupe()),
+                WithWarnings::new(Ok(Arc::new(eval_result))),
+            )
+            .mock_and_return(ExecutionPlatformsKey, Ok(None))
+            .build(data)?
+            .commit();
+
+        let node = computations
+            .get_configured_target_node(&label("binary").configure(cfg.dupe()))
+            .await?
+            .require_compatible()?;
+        let exec_cfg = node.execution_platform_resolution().cfg();
+
+        // The toolchain is configured for the execution platform of the binary.
+        let toolchain_deps = node
+            .toolchain_deps()
+            .map(|dep| dep.name().dupe())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![label("toolchain").configure_with_exec(cfg.dupe(), exec_cfg)],
+            toolchain_deps
+        );
+        assert_eq!(
+            toolchain_deps,
+            node.target_deps()
+                .map(|dep| dep.name().dupe())
+                .collect::<Vec<_>>()
+        );
+
+        let err = computations
+            .get_configured_target_node(&label("toolchain").configure(cfg.dupe()))
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("is a toolchain rule"),
+            "{:#}",
+            err
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_alias_rules() -> anyhow::Result<()> {
+        let pkg = Package::testing_new("cell", "foo");
+        let label = |name| TargetLabel::new(pkg.dupe(), TargetName::unchecked_new(name));
+        let rule_type = RuleType::Starlark(Arc::new(StarlarkRuleType {
+            import_path: ImportPath::unchecked_new("cell", "foo", "def.bzl"),
+            name: "forward".to_owned(),
+        }));
+        let node = |name, rule_kind, deps: &[&str]| {
+            let deps = deps
+                .iter()
+                .map(|dep| {
+                    CoercedAttr::from_literal(AttrLiteral::Dep(box DepAttr::new(
+                        DepAttrType::new(Vec::new(), DepAttrTransition::Identity),
+                        ProvidersLabel::new(label(dep), ProvidersName::Default),
+                    )))
+                })
+                .collect();
+            TargetNode::testing_new_with_rule_kind(
+                label(name),
+                rule_type.dupe(),
+                rule_kind,
+                vec![(
+                    "deps",
+                    Attribute::testing_new(None, AttrType::list(AttrType::dep(Vec::new()))),
+                    CoercedAttr::from_literal(AttrLiteral::List(deps, AttrType::dep(Vec::new()))),
+                )],
+            )
+        };
+
+        let targets = [
+            node("actual", RuleKind::Normal, &[]),
+            node("alias", RuleKind::Alias, &["actual"]),
+            node("alias_of_alias", RuleKind::Alias, &["alias"]),
+            // Not an alias rule, even though it only forwards its dep.
+            node("forward", RuleKind::Normal, &["actual"]),
+            node("ambiguous", RuleKind::Alias, &["actual", "forward"]),
+            node("cycle1", RuleKind::Alias, &["cycle2"]),
+            node("cycle2", RuleKind::Alias, &["cycle1"]),
+        ];
+        let eval_result = EvaluationResult::new(
+            Arc::new(BuildFilePath::new(
+                pkg.dupe(),
+                FileNameBuf::unchecked_new("BUCK"),
+            )),
+            Vec::new(),
+            TargetsMap::from_iter(targets.map(|node| (node.label().name().dupe(), node))),
+        );
+        let computations = DiceBuilder::new()
+            .mock_and_return(
+                InterpreterResultsKey(pkg.dupe()),
+                WithWarnings::new(Ok(Arc::new(eval_result))),
+            )
+            .build(UserComputationData::new())?
+            .commit();
+
+        for (name, canonical) in [
+            ("actual", "actual"),
+            ("alias", "actual"),
+            ("alias_of_alias", "actual"),
+            ("forward", "forward"),
+            ("ambiguous", "ambiguous"),
+        ] {
+            assert_eq!(
+                label(canonical),
+                resolve_alias_rules(&computations, &label(name)).await?
+            );
+        }
+
+        let err = resolve_alias_rules(&computations, &label("cycle1"))
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("Cycle of alias rules"),
+            "{:#}",
+            err
+        );
+
+        let mut resolved = ResolvedPattern::new();
+        for name in ["alias", "forward", "alias_of_alias"] {
+            resolved.add_target(&pkg, &TargetName::unchecked_new(name));
+        }
+        let resolved = resolve_alias_patterns(&computations, resolved).await?;
+        assert_eq!(
+            vec![(
+                pkg.dupe(),
+                PackageSpec::Targets(vec![
+                    TargetName::unchecked_new("actual"),
+                    TargetName::unchecked_new("forward"),
+                ])
+            )],
+            resolved.specs.into_iter().collect::<Vec<_>>()
+        );
+
+        // Packages matched as a whole keep their aliases.
+        let mut resolved = ResolvedPattern::<TargetName>::new();
+        resolved.add_package(&pkg);
+        let resolved = resolve_alias_patterns(&computations, resolved).await?;
+        assert_eq!(
+            vec![(pkg.dupe(), PackageSpec::All)],
+            resolved.specs.into_iter().collect::<Vec<_>>()
+        );
+        Ok(())
+    }
 }
@@ -13,10 +13,14 @@ use buck2_query::query::environment::QueryEnvironment;
 use buck2_query::query::syntax::simple::eval::evaluator::QueryEvaluator;
 use buck2_query::query::syntax::simple::eval::literals::extract_target_literals;
 use buck2_query::query::syntax::simple::eval::multi_query::process_multi_query;
+use buck2_query::query::syntax::simple::eval::multi_query::MultiQueryResult;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use buck2_query::query::syntax::simple::functions::DefaultQueryFunctionsModule;
+use futures::stream::FuturesOrdered;
 use futures::Future;
+use futures::StreamExt;
 use gazebo::prelude::*;
+use indexmap::IndexMap;
 use starlark::collections::SmallSet;
 use thiserror::Error;
 
@@ -74,3 +78,38 @@ pub async fn eval_query<
         ))
     }
 }
+
+/// Evaluates several named queries in a single environment, so they share the loaded graph (and,
+/// for cquery, the universe). The results are keyed by name like those of a multi-query.
+pub async fn eval_named_queries<
+    Env: QueryEnvironment,
+    Fut: Future<Output = anyhow::Result<Env>>,
+    N: AsRef<str>,
+    Q: AsRef<str>,
+>(
+    functions: &DefaultQueryFunctionsModule<Env>,
+    queries: &[(N, Q)],
+    environment: impl FnOnce(Vec<String>) -> Fut,
+) -> anyhow::Result<QueryEvaluationResult<Env::Target>> {
+    let mut literals = SmallSet::new();
+    for (_, query) in queries {
+        extract_target_literals(functions, query.as_ref(), &mut literals)?;
+    }
+    let env = environment(literals.into_iter().collect()).await?;
+    let env = &env;
+    let mut queue: FuturesOrdered<_> = queries
+        .iter()
+        .map(|(name, query)| async move {
+            let evaluator = QueryEvaluator::new(env, functions);
+            (
+                name.as_ref().to_owned(),
+                evaluator.eval_query(query.as_ref()).await,
+            )
+        })
+        .collect();
+    let mut results = IndexMap::new();
+    while let Some((name, result)) = queue.next().await {
+        results.insert(name, result);
+    }
+    Ok(QueryEvaluationResult::Multiple(MultiQueryResult(results)))
+}
@@ -112,6 +112,10 @@ impl ActionQueryNode {
         }
     }
 
+    pub fn action(&self) -> &Arc<RegisteredAction> {
+        &self.action
+    }
+
     pub fn attrs(&self) -> IndexMap<String, String> {
         self.action.action().aquery_attributes(&ExecutorFs::new(
             &self.fs,
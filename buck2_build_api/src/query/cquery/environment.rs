@@ -41,6 +41,8 @@ use crate::query::uquery::environment::UqueryDelegate;
 enum CqueryError {
     #[error("Target universe not specified (internal error)")]
     NoUniverse,
+    #[error("`{0}` matches no target in the universe of the query")]
+    LiteralNotInUniverse(String),
 }
 
 /// CqueryDelegate resolves information needed by the QueryEnvironment.
@@ -62,6 +64,16 @@ pub trait CqueryDelegate: Send + Sync {
         &self,
         target: &TargetLabel,
     ) -> SharedResult<ConfiguredTargetLabel>;
+
+    /// Resolves a literal that names a single target, such as a platform.
+    fn parse_target_literal(&self, literal: &str) -> anyhow::Result<TargetLabel>;
+
+    /// Like `get_node_for_target`, but for `platform` rather than the global target platform.
+    async fn get_node_for_target_on_platform(
+        &self,
+        target: &TargetLabel,
+        platform: &TargetLabel,
+    ) -> SharedResult<MaybeCompatible<ConfiguredTargetNode>>;
 }
 
 /// [Context](https://fburl.com/adiagq2f).
@@ -80,15 +92,46 @@ pub struct CqueryEnvironment<'c> {
     //   ```
     //   buck2 cquery 'deps(//foo:bar)'
     //   ```
-    universe: Option<CqueryUniverse>,
+    universe: Option<Arc<CqueryUniverse>>,
     owner_behavior: CqueryOwnerBehavior,
 }
 
+/// Resolves target literals to the nodes of a universe that match them, rather than by
+/// configuring them for the target platform. Literals that match nothing in the universe are an
+/// error.
+struct UniverseQueryLiterals<'c> {
+    delegate: Arc<dyn CqueryDelegate + 'c>,
+    universe: Arc<CqueryUniverse>,
+}
+
+#[async_trait]
+impl<'c> QueryLiterals<ConfiguredTargetNode> for UniverseQueryLiterals<'c> {
+    async fn eval_literals(
+        &self,
+        literals: &[&str],
+    ) -> anyhow::Result<TargetSet<ConfiguredTargetNode>> {
+        let mut targets = TargetSet::new();
+        for literal in literals {
+            let resolved_pattern = self
+                .delegate
+                .uquery_delegate()
+                .resolve_target_patterns(&[literal])
+                .await?;
+            let matched = self.universe.get(&resolved_pattern);
+            if matched.len() == 0 {
+                return Err(CqueryError::LiteralNotInUniverse((*literal).to_owned()).into());
+            }
+            targets.extend(matched.iter());
+        }
+        Ok(targets)
+    }
+}
+
 impl<'c> CqueryEnvironment<'c> {
     pub fn new(
         delegate: Arc<dyn CqueryDelegate + 'c>,
         literals: Arc<dyn QueryLiterals<ConfiguredTargetNode> + 'c>,
-        universe: Option<CqueryUniverse>,
+        universe: Option<Arc<CqueryUniverse>>,
         owner_behavior: CqueryOwnerBehavior,
     ) -> Self {
         Self {
@@ -99,6 +142,25 @@ impl<'c> CqueryEnvironment<'c> {
         }
     }
 
+    /// An environment where target literals are resolved in `universe`, which is already built
+    /// (e.g. from the results of earlier queries), rather than configured for the target
+    /// platform.
+    pub fn new_in_universe(
+        delegate: Arc<dyn CqueryDelegate + 'c>,
+        universe: Arc<CqueryUniverse>,
+        owner_behavior: CqueryOwnerBehavior,
+    ) -> Self {
+        Self::new(
+            delegate.dupe(),
+            Arc::new(UniverseQueryLiterals {
+                delegate,
+                universe: universe.dupe(),
+            }),
+            Some(universe),
+            owner_behavior,
+        )
+    }
+
     pub fn describe() -> QueryEnvironmentDescription {
         QueryEnvironmentDescription {
             name: "Cquery Environment".to_owned(),
@@ -240,6 +302,27 @@ impl<'c> QueryEnvironment for CqueryEnvironment<'c> {
         }
         Ok(result)
     }
+
+    async fn compatible_with(
+        &self,
+        platform: &str,
+        targets: &TargetSet<Self::Target>,
+    ) -> anyhow::Result<TargetSet<Self::Target>> {
+        let platform = self.delegate.parse_target_literal(platform)?;
+        let nodes = futures::future::try_join_all(targets.iter().map(|node| {
+            self.delegate
+                .get_node_for_target_on_platform(node.name().unconfigured(), &platform)
+        }))
+        .await?;
+
+        let mut result = TargetSet::new();
+        for node in nodes {
+            if let MaybeCompatible::Compatible(node) = node {
+                result.insert(node);
+            }
+        }
+        Ok(result)
+    }
 }
 
 #[async_trait]
@@ -248,3 +331,364 @@ impl<'a> AsyncNodeLookup<ConfiguredTargetNode> for CqueryEnvironment<'a> {
         self.get_node(label).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use buck2_common::pattern::resolve::ResolvedPattern;
+    use buck2_core::bzl::ImportPath;
+    use buck2_core::cells::CellName;
+    use buck2_core::configuration::Configuration;
+    use buck2_core::fs::paths::file_name::FileNameBuf;
+    use buck2_core::package::testing::PackageExt;
+    use buck2_core::package::Package;
+    use buck2_core::target::testing::TargetLabelExt;
+    use buck2_core::target::TargetName;
+    use buck2_node::compatibility::IncompatiblePlatformReason;
+    use buck2_node::compatibility::IncompatiblePlatformReasonCause;
+    use buck2_node::configuration::execution::ExecutionPlatformResolution;
+    use buck2_node::nodes::eval_result::EvaluationResult;
+    use buck2_node::rule_type::RuleType;
+    use buck2_node::rule_type::StarlarkRuleType;
+    use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
+    use buck2_query::query::syntax::simple::eval::values::QueryEvaluationValue;
+
+    use super::*;
+    use crate::query::analysis::evaluator::eval_query;
+
+    const LINUX: &str = "root//platforms:linux";
+    const ARM64: &str = "root//platforms:arm64";
+
+    /// `root//foo:linux_only` is only compatible with the (default) linux platform, and
+    /// `root//foo:arm64_only` only with the arm64 platform.
+    struct CompatibilityDelegate;
+
+    impl CompatibilityDelegate {
+        fn node(
+            target: &TargetLabel,
+            platform: &TargetLabel,
+        ) -> MaybeCompatible<ConfiguredTargetNode> {
+            let label = target.configure(Configuration::testing_new());
+            let compatible_platform =
+                if target == &TargetLabel::testing_parse("root//foo:linux_only") {
+                    LINUX
+                } else {
+                    ARM64
+                };
+            if platform != &TargetLabel::testing_parse(compatible_platform) {
+                return MaybeCompatible::Incompatible(Arc::new(IncompatiblePlatformReason {
+                    target: label,
+                    cause: IncompatiblePlatformReasonCause::UnsatisfiedConfig(
+                        TargetLabel::testing_parse(compatible_platform),
+                    ),
+                }));
+            }
+            MaybeCompatible::Compatible(ConfiguredTargetNode::testing_new(
+                label,
+                RuleType::Starlark(Arc::new(StarlarkRuleType {
+                    import_path: ImportPath::unchecked_new("root", "foo", "rules.bzl"),
+                    name: "foo_lib".to_owned(),
+                })),
+                Vec::new(),
+                ExecutionPlatformResolution::new(None, Vec::new()),
+            ))
+        }
+
+        fn targets() -> Vec<TargetLabel> {
+            vec![
+                TargetLabel::testing_parse("root//foo:linux_only"),
+                TargetLabel::testing_parse("root//foo:arm64_only"),
+            ]
+        }
+    }
+
+    #[async_trait]
+    impl CqueryDelegate for CompatibilityDelegate {
+        fn uquery_delegate(&self) -> &dyn UqueryDelegate {
+            unimplemented!()
+        }
+
+        async fn get_node_for_target(
+            &self,
+            target: &TargetLabel,
+        ) -> SharedResult<MaybeCompatible<ConfiguredTargetNode>> {
+            Ok(Self::node(target, &TargetLabel::testing_parse(LINUX)))
+        }
+
+        async fn get_node_for_configured_target(
+            &self,
+            _target: &ConfiguredTargetLabel,
+        ) -> SharedResult<ConfiguredTargetNode> {
+            unimplemented!()
+        }
+
+        async fn get_configured_target(
+            &self,
+            _target: &TargetLabel,
+        ) -> SharedResult<ConfiguredTargetLabel> {
+            unimplemented!()
+        }
+
+        fn parse_target_literal(&self, literal: &str) -> anyhow::Result<TargetLabel> {
+            Ok(TargetLabel::testing_parse(literal))
+        }
+
+        async fn get_node_for_target_on_platform(
+            &self,
+            target: &TargetLabel,
+            platform: &TargetLabel,
+        ) -> SharedResult<MaybeCompatible<ConfiguredTargetNode>> {
+            Ok(Self::node(target, platform))
+        }
+    }
+
+    #[async_trait]
+    impl QueryLiterals<ConfiguredTargetNode> for CompatibilityDelegate {
+        async fn eval_literals(
+            &self,
+            _literals: &[&str],
+        ) -> anyhow::Result<TargetSet<ConfiguredTargetNode>> {
+            let mut targets = TargetSet::new();
+            for target in Self::targets() {
+                if let MaybeCompatible::Compatible(node) = self.get_node_for_target(&target).await?
+                {
+                    targets.insert(node);
+                }
+            }
+            Ok(targets)
+        }
+    }
+
+    fn names(targets: &TargetSet<ConfiguredTargetNode>) -> Vec<String> {
+        targets
+            .iter()
+            .map(|node| node.name().unconfigured().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_compatible_with() -> anyhow::Result<()> {
+        let env = CqueryEnvironment::new(
+            Arc::new(CompatibilityDelegate),
+            Arc::new(CompatibilityDelegate),
+            None,
+            CqueryOwnerBehavior::Correct,
+        );
+        let linux_targets = env.eval_literals(&["root//foo/..."]).await?;
+        assert_eq!(vec!["root//foo:linux_only"], names(&linux_targets));
+
+        // Reconfigures the targets of the set for the platform...
+        assert_eq!(
+            vec!["root//foo:linux_only"],
+            names(&env.compatible_with(LINUX, &linux_targets).await?)
+        );
+        assert_eq!(
+            Vec::<String>::new(),
+            names(&env.compatible_with(ARM64, &linux_targets).await?)
+        );
+
+        // ... however they were configured.
+        let mut all_targets = TargetSet::new();
+        for (target, platform) in [
+            ("root//foo:linux_only", LINUX),
+            ("root//foo:arm64_only", ARM64),
+        ] {
+            if let MaybeCompatible::Compatible(node) = CompatibilityDelegate::node(
+                &TargetLabel::testing_parse(target),
+                &TargetLabel::testing_parse(platform),
+            ) {
+                all_targets.insert(node);
+            }
+        }
+        assert_eq!(
+            vec!["root//foo:arm64_only"],
+            names(&env.compatible_with(ARM64, &all_targets).await?)
+        );
+        Ok(())
+    }
+
+    /// Resolves `root//foo:name` literals to a target and `root//foo/...` literals to a
+    /// package, and can't configure targets, so literals can only be resolved in a universe.
+    struct UniverseDelegate;
+
+    fn configured_node(target: &str, configuration: Configuration) -> ConfiguredTargetNode {
+        ConfiguredTargetNode::testing_new(
+            TargetLabel::testing_parse(target).configure(configuration),
+            RuleType::Starlark(Arc::new(StarlarkRuleType {
+                import_path: ImportPath::unchecked_new("root", "foo", "rules.bzl"),
+                name: "foo_lib".to_owned(),
+            })),
+            Vec::new(),
+            ExecutionPlatformResolution::new(None, Vec::new()),
+        )
+    }
+
+    #[async_trait]
+    impl CqueryDelegate for UniverseDelegate {
+        fn uquery_delegate(&self) -> &dyn UqueryDelegate {
+            self
+        }
+
+        async fn get_node_for_target(
+            &self,
+            _target: &TargetLabel,
+        ) -> SharedResult<MaybeCompatible<ConfiguredTargetNode>> {
+            unimplemented!()
+        }
+
+        async fn get_node_for_configured_target(
+            &self,
+            _target: &ConfiguredTargetLabel,
+        ) -> SharedResult<ConfiguredTargetNode> {
+            unimplemented!()
+        }
+
+        async fn get_configured_target(
+            &self,
+            _target: &TargetLabel,
+        ) -> SharedResult<ConfiguredTargetLabel> {
+            unimplemented!()
+        }
+
+        fn parse_target_literal(&self, _literal: &str) -> anyhow::Result<TargetLabel> {
+            unimplemented!()
+        }
+
+        async fn get_node_for_target_on_platform(
+            &self,
+            _target: &TargetLabel,
+            _platform: &TargetLabel,
+        ) -> SharedResult<MaybeCompatible<ConfiguredTargetNode>> {
+            unimplemented!()
+        }
+    }
+
+    #[async_trait]
+    impl UqueryDelegate for UniverseDelegate {
+        async fn eval_build_file(&self, _package: &Package) -> SharedResult<Arc<EvaluationResult>> {
+            unimplemented!()
+        }
+
+        async fn eval_module_imports(&self, _path: &ImportPath) -> SharedResult<Vec<ImportPath>> {
+            unimplemented!()
+        }
+
+        fn get_buildfile_names_by_cell(&self) -> anyhow::Result<HashMap<CellName, &[FileNameBuf]>> {
+            unimplemented!()
+        }
+
+        async fn resolve_target_patterns(
+            &self,
+            patterns: &[&str],
+        ) -> anyhow::Result<ResolvedPattern<TargetName>> {
+            let mut resolved = ResolvedPattern::new();
+            for pattern in patterns {
+                match pattern.strip_suffix("/...") {
+                    Some(package) => {
+                        let (cell, path) = package.split_once("//").unwrap();
+                        resolved.add_package(&Package::testing_new(cell, path));
+                    }
+                    None => {
+                        let target = TargetLabel::testing_parse(pattern);
+                        resolved.add_target(target.pkg(), target.name());
+                    }
+                }
+            }
+            Ok(resolved)
+        }
+
+        async fn eval_file_literal(&self, _literal: &str) -> anyhow::Result<FileSet> {
+            unimplemented!()
+        }
+
+        async fn get_enclosing_packages(&self, _path: &CellPath) -> anyhow::Result<Vec<Package>> {
+            unimplemented!()
+        }
+    }
+
+    async fn universe_env() -> anyhow::Result<CqueryEnvironment<'static>> {
+        let mut targets = TargetSet::new();
+        targets.insert(configured_node("root//foo:a", Configuration::testing_new()));
+        targets.insert(configured_node("root//foo:b", Configuration::unspecified()));
+        Ok(CqueryEnvironment::new_in_universe(
+            Arc::new(UniverseDelegate),
+            Arc::new(CqueryUniverse::build(&targets).await?),
+            CqueryOwnerBehavior::Correct,
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_literals_resolve_in_universe() -> anyhow::Result<()> {
+        let env = universe_env().await?;
+
+        // Targets keep the configuration they have in the universe, rather than being
+        // configured for the target platform.
+        let b = env.eval_literals(&["root//foo:b"]).await?;
+        assert_eq!(
+            vec![configured_node("root//foo:b", Configuration::unspecified()).name()],
+            b.iter().map(|node| node.name()).collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            vec!["root//foo:a", "root//foo:b"],
+            names(&env.eval_literals(&["root//foo/..."]).await?)
+        );
+        assert_eq!(
+            vec!["root//foo:a", "root//foo:b"],
+            names(&env.eval_literals(&["root//foo:a", "root//foo:b"]).await?)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_literals_outside_universe_are_an_error() -> anyhow::Result<()> {
+        let env = universe_env().await?;
+        for literals in [
+            &["root//foo:c"][..],
+            &["root//foo:a", "root//foo:c"],
+            &["root//bar/..."],
+        ] {
+            let err = env.eval_literals(literals).await.unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("matches no target in the universe"),
+                "{:#}",
+                err
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_in_universe() -> anyhow::Result<()> {
+        // What `CqueryEvaluator::eval_query_in_universe` evaluates.
+        async fn eval(query: &str) -> anyhow::Result<Vec<String>> {
+            let env = universe_env().await?;
+            match eval_query(
+                &DefaultQueryFunctionsModule::new(),
+                query,
+                &[] as &[&str],
+                async move |_literals| Ok(env),
+            )
+            .await?
+            {
+                QueryEvaluationResult::Single(QueryEvaluationValue::TargetSet(targets)) => {
+                    Ok(names(&targets))
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        assert_eq!(
+            vec!["root//foo:a", "root//foo:b"],
+            eval("root//foo:a + root//foo:b").await?
+        );
+        assert_eq!(
+            vec!["root//foo:b"],
+            eval("root//foo/... except root//foo:a").await?
+        );
+        assert!(eval("root//foo:a + root//bar:c").await.is_err());
+        Ok(())
+    }
+}
@@ -23,6 +23,7 @@ use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use gazebo::prelude::*;
 
+use crate::query::analysis::evaluator::eval_named_queries;
 use crate::query::analysis::evaluator::eval_query;
 use crate::query::cquery::environment::CqueryEnvironment;
 use crate::query::cquery::environment::CqueryOwnerBehavior;
@@ -39,7 +40,7 @@ pub struct CqueryEvaluator<'c> {
     owner_behavior: CqueryOwnerBehavior,
 }
 
-impl CqueryEvaluator<'_> {
+impl<'c> CqueryEvaluator<'c> {
     pub async fn eval_query<A: AsRef<str>, U: AsRef<str>>(
         &self,
         query: &str,
@@ -47,32 +48,71 @@ impl CqueryEvaluator<'_> {
         target_universe: Option<&[U]>,
     ) -> anyhow::Result<QueryEvaluationResult<ConfiguredTargetNode>> {
         eval_query(&self.functions, query, query_args, async move |literals| {
-            let (universe, resolved_literals) = match target_universe {
-                None => {
-                    if literals.is_empty() {
-                        console_message(
-                            "Query has no target literals and `--target-universe` is not specified.\n\
-                            Such query is correct, but the result is always empty.\n\
-                            Consider specifying `--target-universe` for this query\n\
-                            or using `uquery` instead of `cquery`".to_owned());
-                    }
-                    preresolve_literals_and_build_universe(&self.dice_query_delegate, &literals)
-                        .await?
-                }
-                Some(universe) => {
-                    resolve_literals_in_universe(&self.dice_query_delegate, &literals, universe)
-                        .await?
-                }
-            };
-            Ok(CqueryEnvironment::new(
+            self.environment(literals, target_universe).await
+        })
+        .await
+    }
+
+    /// Evaluates `(name, query)` pairs together, with results keyed by name. Without a
+    /// `target_universe`, the universe is built from the literals of all the queries.
+    pub async fn eval_named_queries<U: AsRef<str>>(
+        &self,
+        queries: &[(String, String)],
+        target_universe: Option<&[U]>,
+    ) -> anyhow::Result<QueryEvaluationResult<ConfiguredTargetNode>> {
+        eval_named_queries(&self.functions, queries, async move |literals| {
+            self.environment(literals, target_universe).await
+        })
+        .await
+    }
+
+    /// Evaluates the query in a universe that's already built, e.g. from the results of earlier
+    /// queries, rather than one built from target patterns.
+    pub async fn eval_query_in_universe<A: AsRef<str>>(
+        &self,
+        query: &str,
+        query_args: &[A],
+        universe: Arc<CqueryUniverse>,
+    ) -> anyhow::Result<QueryEvaluationResult<ConfiguredTargetNode>> {
+        eval_query(&self.functions, query, query_args, async move |_literals| {
+            Ok(CqueryEnvironment::new_in_universe(
                 self.dice_query_delegate.dupe(),
-                Arc::new(resolved_literals),
-                Some(universe),
+                universe,
                 self.owner_behavior,
             ))
         })
         .await
     }
+
+    async fn environment<U: AsRef<str>>(
+        &self,
+        literals: Vec<String>,
+        target_universe: Option<&[U]>,
+    ) -> anyhow::Result<CqueryEnvironment<'c>> {
+        let (universe, resolved_literals) = match target_universe {
+            None => {
+                if literals.is_empty() {
+                    console_message(
+                        "Query has no target literals and `--target-universe` is not specified.\n\
+                        Such query is correct, but the result is always empty.\n\
+                        Consider specifying `--target-universe` for this query\n\
+                        or using `uquery` instead of `cquery`"
+                            .to_owned(),
+                    );
+                }
+                preresolve_literals_and_build_universe(&self.dice_query_delegate, &literals).await?
+            }
+            Some(universe) => {
+                resolve_literals_in_universe(&self.dice_query_delegate, &literals, universe).await?
+            }
+        };
+        Ok(CqueryEnvironment::new(
+            self.dice_query_delegate.dupe(),
+            Arc::new(resolved_literals),
+            Some(Arc::new(universe)),
+            self.owner_behavior,
+        ))
+    }
 }
 
 async fn preresolve_literals_and_build_universe(
@@ -44,7 +44,7 @@ impl CqueryUniverse {
         CqueryUniverse { targets }
     }
 
-    pub(crate) async fn build(
+    pub async fn build(
         universe: &TargetSet<ConfiguredTargetNode>,
     ) -> anyhow::Result<CqueryUniverse> {
         let mut targets: BTreeMap<
@@ -43,6 +43,7 @@ use buck2_core::target::ConfiguredTargetLabel;
 use buck2_core::target::TargetLabel;
 use buck2_core::target::TargetName;
 use buck2_events::dispatch::console_message;
+use buck2_events::dispatch::span_async;
 use buck2_interpreter_for_build::interpreter::calculation::InterpreterCalculation;
 use buck2_node::compatibility::IncompatiblePlatformReason;
 use buck2_node::compatibility::MaybeCompatible;
@@ -61,6 +62,7 @@ use starlark::collections::SmallSet;
 
 use crate::calculation::load_patterns;
 use crate::calculation::Calculation;
+use crate::nodes::calculation::resolve_alias_patterns;
 use crate::query::cquery::environment::CqueryDelegate;
 use crate::query::uquery::environment::QueryLiterals;
 use crate::query::uquery::environment::UqueryDelegate;
@@ -216,7 +218,18 @@ impl<'c> UqueryDelegate for DiceQueryDelegate<'c> {
     ) -> anyhow::Result<ResolvedPattern<TargetName>> {
         let parsed_patterns = patterns.try_map(|p| self.literal_parser.parse_target_pattern(p))?;
         let file_ops = self.ctx.file_ops();
-        resolve_target_patterns(&self.cell_resolver, parsed_patterns.iter(), &file_ops).await
+        span_async(buck2_data::ResolveTargetPatternsStart {}, async {
+            let resolved =
+                resolve_target_patterns(&self.cell_resolver, parsed_patterns.iter(), &file_ops)
+                    .await;
+            // Literals naming a target of an alias rule stand for the target it forwards to.
+            let resolved = match resolved {
+                Ok(resolved) => resolve_alias_patterns(self.ctx, resolved).await,
+                Err(e) => Err(e),
+            };
+            (resolved, buck2_data::ResolveTargetPatternsEnd {})
+        })
+        .await
     }
 
     // This returns 1 package normally but can return multiple packages if the path is covered under `self.package_boundary_exceptions`.
@@ -282,6 +295,24 @@ impl<'c> CqueryDelegate for DiceQueryDelegate<'c> {
             .get_configured_target(target, self.global_target_platform.as_ref())
             .await
     }
+
+    fn parse_target_literal(&self, literal: &str) -> anyhow::Result<TargetLabel> {
+        self.literal_parser
+            .parse_target_pattern(literal)?
+            .as_target_label(literal)
+    }
+
+    async fn get_node_for_target_on_platform(
+        &self,
+        target: &TargetLabel,
+        platform: &TargetLabel,
+    ) -> SharedResult<MaybeCompatible<ConfiguredTargetNode>> {
+        let target = self
+            .ctx
+            .get_configured_target(target, Some(platform))
+            .await?;
+        Ok(self.ctx.get_configured_target_node(&target).await?)
+    }
 }
 
 /// Converts target nodes to a set of compatible configured target nodes.
@@ -18,6 +18,7 @@ use buck2_query::query::syntax::simple::functions::DefaultQueryFunctionsModule;
 use dice::DiceComputations;
 use gazebo::prelude::*;
 
+use crate::query::analysis::evaluator::eval_named_queries;
 use crate::query::analysis::evaluator::eval_query;
 use crate::query::dice::get_dice_query_delegate;
 use crate::query::dice::DiceQueryDelegate;
@@ -29,22 +30,37 @@ pub struct UqueryEvaluator<'c> {
     functions: DefaultQueryFunctionsModule<UqueryEnvironment<'c>>,
 }
 
-impl UqueryEvaluator<'_> {
+impl<'c> UqueryEvaluator<'c> {
     pub async fn eval_query(
         &self,
         query: &str,
         query_args: &[String],
     ) -> anyhow::Result<QueryEvaluationResult<TargetNode>> {
         eval_query(&self.functions, query, query_args, async move |literals| {
-            let resolved_literals =
-                PreresolvedQueryLiterals::pre_resolve(&*self.dice_query_delegate, &literals).await;
-            Ok(UqueryEnvironment::new(
-                self.dice_query_delegate.dupe(),
-                Arc::new(resolved_literals),
-            ))
+            self.environment(literals).await
         })
         .await
     }
+
+    /// Evaluates `(name, query)` pairs together, with results keyed by name.
+    pub async fn eval_named_queries(
+        &self,
+        queries: &[(String, String)],
+    ) -> anyhow::Result<QueryEvaluationResult<TargetNode>> {
+        eval_named_queries(&self.functions, queries, async move |literals| {
+            self.environment(literals).await
+        })
+        .await
+    }
+
+    async fn environment(&self, literals: Vec<String>) -> anyhow::Result<UqueryEnvironment<'c>> {
+        let resolved_literals =
+            PreresolvedQueryLiterals::pre_resolve(&*self.dice_query_delegate, &literals).await;
+        Ok(UqueryEnvironment::new(
+            self.dice_query_delegate.dupe(),
+            Arc::new(resolved_literals),
+        ))
+    }
 }
 
 /// Evaluates some query expression. TargetNodes are resolved via the interpreter from
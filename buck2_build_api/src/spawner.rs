@@ -8,6 +8,7 @@
  */
 
 use buck2_events::dispatch::with_dispatcher_async;
+use buck2_events::warnings::propagate_capture;
 use buck2_interpreter::dice::HasEvents;
 use futures::future::BoxFuture;
 use gazebo::dupe::Dupe;
@@ -20,6 +21,8 @@ pub struct BuckSpawner;
 impl<T: HasEvents> Spawner<T> for BuckSpawner {
     fn spawn(&self, ctx: &T, fut: BoxFuture<'static, ()>) -> JoinHandle<()> {
         let dispatcher = ctx.get_dispatcher().dupe();
+        // Warnings emitted by the task count as emitted by the computation that spawned it.
+        let fut = propagate_capture(fut);
         let task = async move { with_dispatcher_async(dispatcher, fut).await };
         tokio::spawn(task)
     }
@@ -300,6 +300,25 @@ impl ProviderCodegen {
                         &dyn crate::interpreter::rule_defs::provider::ProviderLike>(self);
                 }
 
+                fn equals(&self, other: starlark::values::Value<'v>) -> anyhow::Result<bool> {
+                    use crate::interpreter::rule_defs::provider::ProviderLike;
+                    use crate::interpreter::rule_defs::provider::ValueAsProviderLike;
+
+                    let other = match other.as_provider() {
+                        Some(other) => other,
+                        None => return Ok(false),
+                    };
+                    if self.id() != other.id() {
+                        return Ok(false);
+                    }
+                    for ((k1, v1), (k2, v2)) in self.items().into_iter().zip(other.items()) {
+                        if k1 != k2 || !v1.equals(v2)? {
+                            return Ok(false);
+                        }
+                    }
+                    Ok(true)
+                }
+
                 // TODO(cjhopman): UserProvider implements more of the starlark functions. We should probably match them.
             }
         })
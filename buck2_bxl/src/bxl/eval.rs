@@ -47,6 +47,7 @@ use starlark::values::ValueTyped;
 use thiserror::Error;
 
 use crate::bxl::starlark_defs::cli_args::CliArgValueExt;
+use crate::bxl::starlark_defs::context::starlark_async::BxlCancellation;
 use crate::bxl::starlark_defs::context::starlark_async::BxlSafeDiceComputations;
 use crate::bxl::starlark_defs::context::BxlContext;
 use crate::bxl::starlark_defs::FrozenBxlFunction;
@@ -86,10 +87,9 @@ pub async fn eval(
 
     // The bxl function may trigger async operations like builds, analysis, parsing etc, but those
     // will be blocking calls so that starlark can remain synchronous.
-    // To avoid blocking a tokio thread, we spawn bxl as a blocking tokio task
     let dispatcher = ctx.per_transaction_data().get_dispatcher().dupe();
-    tokio::task::spawn_blocking(with_dispatcher(dispatcher, || {
-        move || {
+    evaluate_blocking(move |cancellation| {
+        with_dispatcher(dispatcher, || {
             let env = Module::new();
 
             let resolved_args = env.heap().alloc(Struct::new(
@@ -115,6 +115,7 @@ pub async fn eval(
             let file = RefCell::new(box project_fs.create_file(&file_path, false)?);
 
             let mut eval = Evaluator::new(&env);
+            eval.set_cancellation_flag(cancellation.as_flag());
 
             let mut profiler_opt = profile_mode_or_instrumentation
                 .profile_mode()
@@ -135,7 +136,7 @@ pub async fn eval(
                 project_fs,
                 artifact_fs,
                 bxl_cell,
-                BxlSafeDiceComputations::new(&ctx),
+                BxlSafeDiceComputations::new(&ctx, cancellation.clone()),
                 file,
             );
             let bxl_ctx = ValueTyped::<BxlContext>::new(env.heap().alloc(bxl_ctx)).unwrap();
@@ -187,9 +188,21 @@ pub async fn eval(
             let profile_data = profiler_opt.map(|p| p.finish()).transpose()?;
 
             anyhow::Ok((bxl_result, profile_data))
-        }
-    }))
-    .await?
+        })
+    })
+    .await
+}
+
+/// Scripts can run for a long time and block on the operations they request, so rather than
+/// holding on to a tokio worker, or waiting for a thread of a fixed pool, each evaluation gets a
+/// blocking thread of its own. Dropping the returned future cancels the evaluation.
+async fn evaluate_blocking<R: Send + 'static>(
+    evaluate: impl FnOnce(BxlCancellation) -> anyhow::Result<R> + Send + 'static,
+) -> anyhow::Result<R> {
+    let (_cancel, cancellation) = BxlCancellation::new();
+    tokio::task::spawn_blocking(move || evaluate(cancellation))
+        .await
+        .map_err(|_| BxlThreadPanicked)?
 }
 
 fn eval_bxl<'a>(
@@ -248,6 +261,67 @@ pub async fn resolve_cli_args<'a>(
         .await
 }
 
+#[derive(Debug, Error)]
+#[error("The thread evaluating the bxl function panicked")]
+struct BxlThreadPanicked;
+
 #[derive(Debug, Error)]
 #[error("Expected `NoneType` to be returned from bxl. Got return value `{0}`")]
 struct NotAValidReturnType(&'static str);
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use futures::channel::oneshot;
+    use futures::future;
+    use futures::future::Either;
+    use starlark::environment::Globals;
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_evaluate_blocking_cancelled() {
+        let (started_sender, started) = oneshot::channel();
+        let (result_sender, result) = oneshot::channel();
+        let evaluation = evaluate_blocking(move |cancellation| {
+            let module = Module::new();
+            let mut eval = Evaluator::new(&module);
+            eval.set_cancellation_flag(cancellation.as_flag());
+            let ast = AstModule::parse(
+                "loop.bxl",
+                "def f():\n  for _ in range(1000000000):\n    pass\nf()\n".to_owned(),
+                &Dialect::Extended,
+            )?;
+            started_sender.send(()).unwrap();
+            let _ignored = result_sender.send(eval.eval_module(ast, &Globals::standard()).err());
+            Ok(())
+        });
+
+        match future::select(Box::pin(evaluation), started).await {
+            Either::Left(..) => panic!("The evaluation finished before it was cancelled"),
+            Either::Right((_, evaluation)) => drop(evaluation),
+        }
+
+        let err = result
+            .await
+            .unwrap()
+            .expect("The evaluation was not cancelled");
+        assert!(format!("{:#}", err).contains("cancelled"), "{:#}", err);
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_blocking_result() {
+        assert_eq!(
+            3,
+            evaluate_blocking(|cancellation| {
+                assert!(!cancellation.as_flag().load(Ordering::Relaxed));
+                Ok(3)
+            })
+            .await
+            .unwrap()
+        );
+    }
+}
@@ -328,6 +328,9 @@ fn register_context(builder: &mut MethodsBuilder) {
     /// This returns either a single [`StarlarkTargetNode`] if the given `labels`
     /// is "singular", a dict keyed by target labels of [`StarlarkTargetNode`] if the
     /// given `labels` is list-like
+    ///
+    /// The attributes of unconfigured target nodes are the ones written in the build file,
+    /// `select()`s included, which is what scripts that audit or rewrite build files need.
     fn unconfigured_targets<'v>(
         this: &'v BxlContext<'v>,
         labels: Value<'v>,
@@ -365,13 +368,30 @@ fn register_context(builder: &mut MethodsBuilder) {
     /// configuration used to configured any unconfigured target nodes.
     ///
     /// The `target_platform` is a target label, or a string that is a target label.
+    ///
+    /// The optional `universe` is the set of configured targets the queries run in, e.g. the
+    /// results of an earlier query, or the targets given to `configured_targets()`. It's built
+    /// once, with the transitive dependencies of these targets, and target patterns given to the
+    /// query functions are resolved in it (to the nodes of the universe, in whatever configuration
+    /// they have there) instead of being configured for the `target_platform`. A pattern that
+    /// matches no target of the universe is an error.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_universe(ctx):
+    ///     targets = ctx.configured_targets(["//bin:foo", "//bin:bar"])
+    ///     cquery = ctx.cquery(universe = targets.values())
+    ///     ctx.output.print(cquery.rdeps("//bin/...", "//lib:baz"))
+    /// ```
     fn cquery<'v>(
         this: &'v BxlContext<'v>,
         // TODO(brasselsprouts): I would like to strongly type this.
         #[starlark(default = NoneType)] target_platform: Value<'v>,
+        #[starlark(default = NoneType)] universe: Value<'v>,
+        eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<StarlarkCQueryCtx<'v>> {
         this.async_ctx
-            .via(|| StarlarkCQueryCtx::new(this, target_platform))
+            .via(|| StarlarkCQueryCtx::new(this, target_platform, universe, eval))
     }
 
     /// Returns the action context [`BxlActionsCtx`] for creating and running actions.
@@ -148,7 +148,7 @@ pub(crate) fn build<'v>(
             force: false,
         };
 
-        futures::future::join_all(build_spec.labels().map(|target| {
+        let results = futures::future::join_all(build_spec.labels().map(|target| {
             async {
                 (
                     target.clone(),
@@ -169,8 +169,10 @@ pub(crate) fn build<'v>(
                 )
             }
         }))
-        .await
-    });
+        .await;
+
+        anyhow::Ok(results)
+    })?;
 
     build_result
         .into_iter()
@@ -8,11 +8,70 @@
  */
 
 use std::future::Future;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 use buck2_events::dispatch::with_dispatcher_async;
 use buck2_interpreter::dice::HasEvents;
 use dice::DiceComputations;
+use futures::channel::oneshot;
+use futures::future::Either;
+use futures::future::Shared;
+use futures::FutureExt;
 use gazebo::prelude::*;
+use thiserror::Error;
+
+/// Cancels the evaluation of the bxl function when dropped, i.e. when the future that awaits its
+/// result is dropped because the command was cancelled.
+pub(crate) struct BxlCancellationGuard {
+    cancelled: Arc<AtomicBool>,
+    _notify: oneshot::Sender<()>,
+}
+
+impl Drop for BxlCancellationGuard {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Tells whether the evaluation of the bxl function is no longer wanted.
+#[derive(Clone)]
+pub(crate) struct BxlCancellation {
+    cancelled: Arc<AtomicBool>,
+    /// Resolves once cancelled.
+    notify: Shared<oneshot::Receiver<()>>,
+}
+
+impl BxlCancellation {
+    pub(crate) fn new() -> (BxlCancellationGuard, Self) {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (notify, receiver) = oneshot::channel();
+        (
+            BxlCancellationGuard {
+                cancelled: cancelled.dupe(),
+                _notify: notify,
+            },
+            Self {
+                cancelled,
+                notify: receiver.shared(),
+            },
+        )
+    }
+
+    /// The flag that is set once cancelled, e.g. to stop the Starlark evaluator.
+    pub(crate) fn as_flag(&self) -> &AtomicBool {
+        &self.cancelled
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Evaluation of the bxl function was cancelled")]
+struct BxlCancelled;
 
 /// Provides a safe blocking calls to async functions for starlark that requires operations to
 /// be not async.
@@ -20,28 +79,43 @@ use gazebo::prelude::*;
 /// This is not exposed to starlark but rather, used by operations exposed to starlark to run
 /// code.
 /// This also provides a handle for dice.
-pub struct BxlSafeDiceComputations<'a>(pub(crate) &'a DiceComputations);
+///
+/// Every call is also a cancellation checkpoint: once the evaluation is cancelled, the running
+/// computation is dropped and this call, and all the ones that follow, fail so that the
+/// evaluation unwinds.
+pub struct BxlSafeDiceComputations<'a>(pub(crate) &'a DiceComputations, BxlCancellation);
 
 impl<'a> BxlSafeDiceComputations<'a> {
-    pub fn new(dice: &'a DiceComputations) -> Self {
-        Self(dice)
+    pub(crate) fn new(dice: &'a DiceComputations, cancellation: BxlCancellation) -> Self {
+        Self(dice, cancellation)
     }
 
     /// runs the async computation over dice as sync
-    pub fn via_dice<Fut, R>(&self, f: impl FnOnce(&'a DiceComputations) -> Fut) -> R
+    pub fn via_dice<Fut, R>(&self, f: impl FnOnce(&'a DiceComputations) -> Fut) -> anyhow::Result<R>
     where
-        Fut: Future<Output = R>,
+        Fut: Future<Output = anyhow::Result<R>>,
     {
-        let dispatcher = self.0.per_transaction_data().get_dispatcher().dupe();
-        tokio::runtime::Handle::current().block_on(with_dispatcher_async(dispatcher, f(self.0)))
+        self.via(|| f(self.0))
     }
 
     /// runs any async computation
-    pub fn via<Fut, R>(&self, f: impl FnOnce() -> Fut) -> R
+    pub fn via<Fut, R>(&self, f: impl FnOnce() -> Fut) -> anyhow::Result<R>
     where
-        Fut: Future<Output = R>,
+        Fut: Future<Output = anyhow::Result<R>>,
     {
+        if self.1.is_cancelled() {
+            return Err(BxlCancelled.into());
+        }
+
         let dispatcher = self.0.per_transaction_data().get_dispatcher().dupe();
-        tokio::runtime::Handle::current().block_on(with_dispatcher_async(dispatcher, f()))
+        let cancellation = self.1.notify.clone();
+        tokio::runtime::Handle::current().block_on(with_dispatcher_async(dispatcher, async move {
+            let fut = f();
+            futures::pin_mut!(fut);
+            match futures::future::select(fut, cancellation).await {
+                Either::Left((res, _)) => res,
+                Either::Right(_) => Err(BxlCancelled.into()),
+            }
+        }))
     }
 }
@@ -7,15 +7,19 @@
  * of this source tree.
  */
 
+use std::borrow::Cow;
 use std::sync::Arc;
 
 use allocative::Allocative;
 use buck2_build_api::query::cquery::environment::CqueryEnvironment;
 use buck2_build_api::query::cquery::environment::CqueryOwnerBehavior;
 use buck2_build_api::query::cquery::evaluator::get_cquery_evaluator;
+use buck2_build_api::query::cquery::universe::CqueryUniverse;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_core::target::TargetLabel;
 use buck2_node::nodes::configured::ConfiguredTargetNode;
+use buck2_query::query::environment::QueryEnvironment;
+use buck2_query::query::syntax::simple::eval::set::TargetSet;
 use buck2_query::query::syntax::simple::functions::helpers::CapturedExpr;
 use buck2_query::query::syntax::simple::functions::DefaultQueryFunctions;
 use buck2_query::query::syntax::simple::functions::DefaultQueryFunctionsModule;
@@ -30,6 +34,7 @@ use starlark::environment::MethodsStatic;
 use starlark::eval::Evaluator;
 use starlark::starlark_module;
 use starlark::starlark_type;
+use starlark::values::list::List;
 use starlark::values::none::NoneOr;
 use starlark::values::type_repr::StarlarkTypeRepr;
 use starlark::values::AllocValue;
@@ -50,6 +55,14 @@ use crate::bxl::starlark_defs::target_expr::TargetExpr;
 use crate::bxl::starlark_defs::targetset::StarlarkTargetSet;
 use crate::bxl::value_as_starlark_target_label::ValueAsStarlarkTargetLabel;
 
+#[derive(Debug, thiserror::Error)]
+enum CqueryCtxError {
+    #[error(
+        "`target_universe` can't be given to `eval()` when the cquery context has a `universe`"
+    )]
+    UniverseAlreadyGiven,
+}
+
 #[derive(
     ProvidesStaticType,
     Derivative,
@@ -75,6 +88,10 @@ pub struct StarlarkCQueryCtx<'v> {
     env: CqueryEnvironment<'v>,
     #[derivative(Debug = "ignore")]
     target_platform: Option<TargetLabel>,
+    /// The universe given to `ctx.cquery()`, in which target patterns are resolved.
+    #[trace(unsafe_ignore)]
+    #[derivative(Debug = "ignore")]
+    universe: Option<Arc<CqueryUniverse>>,
 }
 
 impl<'v> StarlarkValue<'v> for StarlarkCQueryCtx<'v> {
@@ -123,18 +140,87 @@ impl<'v> StarlarkCQueryCtx<'v> {
     pub async fn new(
         ctx: &'v BxlContext<'v>,
         global_target_platform: Value<'v>,
+        universe: Value<'v>,
+        eval: &Evaluator<'v, '_>,
     ) -> anyhow::Result<StarlarkCQueryCtx<'v>> {
         let target_platform =
             global_target_platform.parse_target_platforms(&ctx.target_alias_resolver, &ctx.cell)?;
 
         let env = get_cquery_env(ctx.async_ctx.0, target_platform.dupe()).await?;
+        if universe.is_none() {
+            return Ok(Self {
+                ctx,
+                functions: DefaultQueryFunctions::new(),
+                env,
+                target_platform,
+                universe: None,
+            });
+        }
+
+        let universe = Arc::new(
+            CqueryUniverse::build(
+                &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
+                    universe,
+                    &target_platform,
+                    ctx,
+                    eval,
+                )
+                .await?
+                .get(&env)
+                .await?,
+            )
+            .await?,
+        );
+        let dice_query_delegate =
+            BxlContext::dice_query_delegate(ctx.async_ctx.0, target_platform.dupe()).await?;
         Ok(Self {
             ctx,
             functions: DefaultQueryFunctions::new(),
-            env,
+            env: CqueryEnvironment::new_in_universe(
+                Arc::new(dice_query_delegate),
+                universe.dupe(),
+                CqueryOwnerBehavior::Correct,
+            ),
             target_platform,
+            universe: Some(universe),
         })
     }
+
+    /// Resolves `targets` to configured target nodes. With a universe, target patterns (alone or
+    /// in a list) match the nodes of the universe, rather than being configured for the target
+    /// platform.
+    async fn target_set(
+        &self,
+        targets: Value<'v>,
+        eval: &Evaluator<'v, '_>,
+    ) -> anyhow::Result<Cow<'v, TargetSet<ConfiguredTargetNode>>> {
+        if self.universe.is_some() {
+            if let Some(patterns) = universe_literals(targets) {
+                return Ok(Cow::Owned(self.env.eval_literals(&patterns).await?));
+            }
+        }
+        TargetExpr::<'v, ConfiguredTargetNode>::unpack(
+            targets,
+            &self.target_platform,
+            self.ctx,
+            eval,
+        )
+        .await?
+        .get(&self.env)
+        .await
+    }
+}
+
+/// The target patterns to resolve in the universe of a cquery context: a string, or a list of
+/// strings. Other values (e.g. target sets) are resolved as they would be without a universe.
+fn universe_literals<'v>(targets: Value<'v>) -> Option<Vec<&'v str>> {
+    if let Some(pattern) = targets.unpack_str() {
+        return Some(vec![pattern]);
+    }
+    List::from_value(targets)?
+        .iter()
+        .map(|v| v.unpack_str())
+        .collect()
 }
 
 /// The context for performing `cquery` operations in bxl. The functions offered on this ctx are
@@ -155,24 +241,8 @@ fn register_cquery(builder: &mut MethodsBuilder) {
             this.functions
                 .allpaths(
                     &this.env,
-                    &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                        from,
-                        &this.target_platform,
-                        this.ctx,
-                        eval,
-                    )
-                    .await?
-                    .get(&this.env)
-                    .await?,
-                    &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                        to,
-                        &this.target_platform,
-                        this.ctx,
-                        eval,
-                    )
-                    .await?
-                    .get(&this.env)
-                    .await?,
+                    &*this.target_set(from, eval).await?,
+                    &*this.target_set(to, eval).await?,
                 )
                 .await
                 .map(StarlarkTargetSet::from)
@@ -190,24 +260,8 @@ fn register_cquery(builder: &mut MethodsBuilder) {
             this.functions
                 .somepath(
                     &this.env,
-                    &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                        from,
-                        &this.target_platform,
-                        this.ctx,
-                        eval,
-                    )
-                    .await?
-                    .get(&this.env)
-                    .await?,
-                    &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                        to,
-                        &this.target_platform,
-                        this.ctx,
-                        eval,
-                    )
-                    .await?
-                    .get(&this.env)
-                    .await?,
+                    &*this.target_set(from, eval).await?,
+                    &*this.target_set(to, eval).await?,
                 )
                 .await
                 .map(StarlarkTargetSet::from)
@@ -224,19 +278,7 @@ fn register_cquery(builder: &mut MethodsBuilder) {
     ) -> anyhow::Result<StarlarkTargetSet<ConfiguredTargetNode>> {
         this.ctx.async_ctx.via(|| async {
             this.functions
-                .attrfilter(
-                    attr,
-                    value,
-                    &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                        targets,
-                        &this.target_platform,
-                        this.ctx,
-                        eval,
-                    )
-                    .await?
-                    .get(&this.env)
-                    .await?,
-                )
+                .attrfilter(attr, value, &*this.target_set(targets, eval).await?)
                 .map(StarlarkTargetSet::from)
         })
     }
@@ -257,18 +299,7 @@ fn register_cquery(builder: &mut MethodsBuilder) {
     ) -> anyhow::Result<StarlarkTargetSet<ConfiguredTargetNode>> {
         this.ctx.async_ctx.via(|| async {
             this.functions
-                .kind(
-                    regex,
-                    &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                        targets,
-                        &this.target_platform,
-                        this.ctx,
-                        eval,
-                    )
-                    .await?
-                    .get(&this.env)
-                    .await?,
-                )
+                .kind(regex, &*this.target_set(targets, eval).await?)
                 .map(StarlarkTargetSet::from)
         })
     }
@@ -290,19 +321,7 @@ fn register_cquery(builder: &mut MethodsBuilder) {
     ) -> anyhow::Result<StarlarkTargetSet<ConfiguredTargetNode>> {
         this.ctx.async_ctx.via(|| async {
             this.functions
-                .attrregexfilter(
-                    attribute,
-                    value,
-                    &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                        targets,
-                        &this.target_platform,
-                        this.ctx,
-                        eval,
-                    )
-                    .await?
-                    .get(&this.env)
-                    .await?,
-                )
+                .attrregexfilter(attribute, value, &*this.target_set(targets, eval).await?)
                 .map(StarlarkTargetSet::from)
         })
     }
@@ -355,15 +374,7 @@ fn register_cquery(builder: &mut MethodsBuilder) {
                     .deps(
                         &this.env,
                         &DefaultQueryFunctionsModule::new(),
-                        &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                            universe,
-                            &this.target_platform,
-                            this.ctx,
-                            eval,
-                        )
-                        .await?
-                        .get(&this.env)
-                        .await?,
+                        &*this.target_set(universe, eval).await?,
                         depth.into_option(),
                         filter
                             .as_ref()
@@ -392,18 +403,8 @@ fn register_cquery(builder: &mut MethodsBuilder) {
         this.ctx
             .async_ctx
             .via(|| async {
-                this.functions.filter_target_set(
-                    regex,
-                    &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                        targets,
-                        &this.target_platform,
-                        this.ctx,
-                        eval,
-                    )
-                    .await?
-                    .get(&this.env)
-                    .await?,
-                )
+                this.functions
+                    .filter_target_set(regex, &*this.target_set(targets, eval).await?)
             })
             .map(StarlarkTargetSet::from)
     }
@@ -424,17 +425,8 @@ fn register_cquery(builder: &mut MethodsBuilder) {
         this.ctx
             .async_ctx
             .via(|| async {
-                this.functions.inputs(
-                    &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                        targets,
-                        &this.target_platform,
-                        this.ctx,
-                        eval,
-                    )
-                    .await?
-                    .get(&this.env)
-                    .await?,
-                )
+                this.functions
+                    .inputs(&*this.target_set(targets, eval).await?)
             })
             .map(StarlarkFileSet::from)
     }
@@ -449,18 +441,7 @@ fn register_cquery(builder: &mut MethodsBuilder) {
             .async_ctx
             .via(|| async {
                 this.functions
-                    .testsof(
-                        &this.env,
-                        &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                            targets,
-                            &this.target_platform,
-                            this.ctx,
-                            eval,
-                        )
-                        .await?
-                        .get(&this.env)
-                        .await?,
-                    )
+                    .testsof(&this.env, &*this.target_set(targets, eval).await?)
                     .await
             })
             .map(StarlarkTargetSet::from)
@@ -487,24 +468,8 @@ fn register_cquery(builder: &mut MethodsBuilder) {
                 this.functions
                     .rdeps(
                         &this.env,
-                        &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                            universe,
-                            &this.target_platform,
-                            this.ctx,
-                            eval,
-                        )
-                        .await?
-                        .get(&this.env)
-                        .await?,
-                        &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                            from,
-                            &this.target_platform,
-                            this.ctx,
-                            eval,
-                        )
-                        .await?
-                        .get(&this.env)
-                        .await?,
+                        &*this.target_set(universe, eval).await?,
+                        &*this.target_set(from, eval).await?,
                         depth,
                     )
                     .await
@@ -535,20 +500,35 @@ fn register_cquery(builder: &mut MethodsBuilder) {
                     .get(this.ctx.current_bxl.label().bxl_path.cell())?
                     .path(),
                 this.target_platform.dupe(),
-                CqueryOwnerBehavior::Deprecated,
+                match this.universe {
+                    Some(_) => CqueryOwnerBehavior::Correct,
+                    None => CqueryOwnerBehavior::Deprecated,
+                },
             )
             .await
             {
-                Ok(evaluator) => parse_query_evaluation_result::<CqueryEnvironment>(
-                    evaluator
-                        .eval_query(
-                            query,
-                            &query_args,
-                            target_universe.into_option().as_ref().map(|v| &v[..]),
-                        )
-                        .await?,
-                    eval,
-                ),
+                Ok(evaluator) => {
+                    let result = match &this.universe {
+                        Some(universe) => {
+                            if target_universe.into_option().is_some() {
+                                return Err(CqueryCtxError::UniverseAlreadyGiven.into());
+                            }
+                            evaluator
+                                .eval_query_in_universe(query, &query_args, universe.dupe())
+                                .await?
+                        }
+                        None => {
+                            evaluator
+                                .eval_query(
+                                    query,
+                                    &query_args,
+                                    target_universe.into_option().as_ref().map(|v| &v[..]),
+                                )
+                                .await?
+                        }
+                    };
+                    parse_query_evaluation_result::<CqueryEnvironment>(result, eval)
+                }
                 Err(e) => Err(e),
             }
         })
@@ -571,18 +551,38 @@ fn register_cquery(builder: &mut MethodsBuilder) {
         this.ctx
             .async_ctx
             .via(|| async {
-                let targets = &*TargetExpr::<'v, ConfiguredTargetNode>::unpack(
-                    targets,
-                    &this.target_platform,
-                    this.ctx,
-                    eval,
-                )
-                .await?
-                .get(&this.env)
-                .await?;
+                let targets = &*this.target_set(targets, eval).await?;
 
                 Ok(this.functions.buildfile(targets))
             })
             .map(StarlarkFileSet::from)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_universe_literals() {
+        let heap = Heap::new();
+        assert_eq!(
+            Some(vec!["//foo:bar"]),
+            universe_literals(heap.alloc("//foo:bar"))
+        );
+        assert_eq!(
+            Some(vec!["//foo:bar", "//baz/..."]),
+            universe_literals(heap.alloc(vec!["//foo:bar", "//baz/..."]))
+        );
+        assert_eq!(
+            Some(Vec::new()),
+            universe_literals(heap.alloc(Vec::<&str>::new()))
+        );
+        // Anything else, e.g. a target set, is not target patterns.
+        assert_eq!(None, universe_literals(heap.alloc(1)));
+        assert_eq!(
+            None,
+            universe_literals(heap.alloc(vec![heap.alloc("//foo:bar"), heap.alloc(1)]))
+        );
+    }
+}
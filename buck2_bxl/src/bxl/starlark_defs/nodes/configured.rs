@@ -16,10 +16,10 @@ use buck2_build_api::actions::artifact::Artifact;
 use buck2_build_api::analysis::calculation::get_dep_analysis;
 use buck2_build_api::analysis::calculation::resolve_queries;
 use buck2_build_api::analysis::get_deps_from_analysis_results;
-use buck2_build_api::analysis::AnalysisResult;
 use buck2_build_api::analysis::RuleAnalysisAttrResolutionContext;
 use buck2_build_api::attrs::resolve::configured_attr::ConfiguredAttrExt;
 use buck2_build_api::interpreter::rule_defs::artifact::StarlarkArtifact;
+use buck2_build_api::interpreter::rule_defs::provider::collection::FrozenProviderCollectionValue;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::dice::data::HasIoProvider;
 use buck2_core::buck_path::BuckPathRef;
@@ -102,7 +102,10 @@ fn resolved_attrs_eager_impl<'v>(
 ) -> anyhow::Result<Value<'v>> {
     let configured_node = &this.0;
 
-    let dep_analysis: anyhow::Result<Vec<(&ConfiguredTargetLabel, AnalysisResult)>, _> = ctx
+    let dep_analysis: anyhow::Result<
+        Vec<(&ConfiguredTargetLabel, FrozenProviderCollectionValue)>,
+        _,
+    > = ctx
         .async_ctx
         .via_dice(|dice_ctx| async move { get_dep_analysis(configured_node, dice_ctx).await });
 
@@ -114,6 +117,7 @@ fn resolved_attrs_eager_impl<'v>(
         module: eval.module(),
         dep_analysis_results: get_deps_from_analysis_results(dep_analysis?)?,
         query_results,
+        perturb_ordering: false,
     };
 
     let attrs_iter = this.0.attrs(AttrInspectOptions::All);
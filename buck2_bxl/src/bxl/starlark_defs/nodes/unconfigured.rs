@@ -63,7 +63,9 @@ impl<'a> UnpackValue<'a> for StarlarkTargetNode {
 /// Methods for unconfigured target node.
 #[starlark_module]
 fn target_node_value_methods(builder: &mut MethodsBuilder) {
-    /// Gets the coerced attributes from the unconfigured target node.
+    /// Gets the coerced attributes from the unconfigured target node, as an iterable of
+    /// `(name, attr)` pairs. Their `value()` is the value written in the build file, with its
+    /// `select()`s.
     #[starlark(attribute)]
     fn attributes<'v>(this: StarlarkTargetNode, heap: &Heap) -> anyhow::Result<Value<'v>> {
         Ok(heap.alloc(StarlarkTargetNodeCoercedAttributes {
@@ -12,15 +12,31 @@ use std::fmt::Display;
 
 use allocative::Allocative;
 use anyhow::Context;
+use buck2_interpreter::selector::FrozenSelector;
+use buck2_interpreter::selector::Selector;
+use buck2_node::attrs::attr_type::attr_literal::AttrLiteral;
 use buck2_node::attrs::coerced_attr::CoercedAttr;
 use buck2_node::attrs::inspect_options::AttrInspectOptions;
 use derive_more::Display;
 use derive_more::From;
 use gazebo::any::ProvidesStaticType;
 use gazebo::coerce::Coerce;
+use gazebo::prelude::*;
+use starlark::collections::SmallMap;
+use starlark::environment::Methods;
+use starlark::environment::MethodsBuilder;
+use starlark::environment::MethodsStatic;
 use starlark::starlark_complex_value;
+use starlark::starlark_module;
 use starlark::starlark_simple_value;
 use starlark::starlark_type;
+use starlark::values::bool::BOOL_TYPE;
+use starlark::values::dict::Dict;
+use starlark::values::int::INT_TYPE;
+use starlark::values::list::List;
+use starlark::values::none::NoneType;
+use starlark::values::string::STRING_TYPE;
+use starlark::values::tuple::Tuple;
 use starlark::values::Freeze;
 use starlark::values::Heap;
 use starlark::values::NoSerialize;
@@ -90,4 +106,240 @@ starlark_simple_value!(StarlarkCoercedAttr);
 /// Coerced attr from an unconfigured target node.
 impl<'v> StarlarkValue<'v> for StarlarkCoercedAttr {
     starlark_type!("coerced_attr");
+
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(coerced_attr_methods)
+    }
+}
+
+/// Converts the attr to the Starlark value that was written in the build file, keeping its
+/// `select()`s. Dependencies, sources and other labels are given as strings.
+fn coerced_attr_to_value<'v>(attr: &CoercedAttr, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+    Ok(match attr {
+        CoercedAttr::Selector(selector) => {
+            let (entries, default) = &**selector;
+            let mut map = SmallMap::with_capacity(entries.len() + 1);
+            for (condition, value) in entries.iter() {
+                map.insert_hashed(
+                    heap.alloc(condition.to_string()).get_hashed()?,
+                    coerced_attr_to_value(value, heap)?,
+                );
+            }
+            if let Some(default) = default {
+                map.insert_hashed(
+                    heap.alloc("DEFAULT").get_hashed()?,
+                    coerced_attr_to_value(default, heap)?,
+                );
+            }
+            heap.alloc(Selector::new(heap.alloc(Dict::new(map))))
+        }
+        CoercedAttr::Concat(items) => {
+            let mut result = None;
+            for item in items.iter() {
+                let item = coerced_attr_to_value(item, heap)?;
+                result = Some(match result {
+                    None => item,
+                    Some(left) => Selector::added(left, item, heap)?,
+                });
+            }
+            result.unwrap_or_else(Value::new_none)
+        }
+        CoercedAttr::Literal(literal) => match literal {
+            AttrLiteral::None => Value::new_none(),
+            AttrLiteral::Bool(b) => Value::new_bool(*b),
+            AttrLiteral::Int(i) => Value::new_int(*i),
+            AttrLiteral::String(s) | AttrLiteral::EnumVariant(s) => heap.alloc(s.as_str()),
+            AttrLiteral::List(items, _) => {
+                heap.alloc_list(&items.try_map(|v| coerced_attr_to_value(v, heap))?)
+            }
+            AttrLiteral::Tuple(items) => {
+                heap.alloc_tuple(&items.try_map(|v| coerced_attr_to_value(v, heap))?)
+            }
+            AttrLiteral::Dict(entries, _) => {
+                let mut map = SmallMap::with_capacity(entries.len());
+                for (k, v) in entries {
+                    map.insert_hashed(
+                        coerced_attr_to_value(k, heap)?.get_hashed()?,
+                        coerced_attr_to_value(v, heap)?,
+                    );
+                }
+                heap.alloc(Dict::new(map))
+            }
+            AttrLiteral::Dep(d) => heap.alloc(d.to_string()),
+            AttrLiteral::ConfiguredDep(d) => heap.alloc(d.to_string()),
+            AttrLiteral::ConfigurationDep(l) => heap.alloc(l.to_string()),
+            AttrLiteral::SourceLabel(l) | AttrLiteral::Label(l) => heap.alloc(l.to_string()),
+            AttrLiteral::SourceFile(f) => heap.alloc(f.path().to_string()),
+            AttrLiteral::Query(q) => heap.alloc(q.query()),
+            AttrLiteral::Arg(a) => heap.alloc(a.to_string()),
+            x @ (AttrLiteral::ExplicitConfiguredDep(..) | AttrLiteral::SplitTransitionDep(..)) => {
+                heap.alloc(x.to_string())
+            }
+        },
+    })
+}
+
+/// The type of the value [`coerced_attr_to_value`] converts the attr to.
+fn coerced_attr_type(attr: &CoercedAttr) -> &'static str {
+    match attr {
+        CoercedAttr::Selector(..) | CoercedAttr::Concat(..) => FrozenSelector::TYPE,
+        CoercedAttr::Literal(literal) => match literal {
+            AttrLiteral::None => NoneType::TYPE,
+            AttrLiteral::Bool(..) => BOOL_TYPE,
+            AttrLiteral::Int(..) => INT_TYPE,
+            AttrLiteral::List(..) => List::TYPE,
+            AttrLiteral::Tuple(..) => Tuple::TYPE,
+            AttrLiteral::Dict(..) => Dict::TYPE,
+            AttrLiteral::String(..)
+            | AttrLiteral::EnumVariant(..)
+            | AttrLiteral::Dep(..)
+            | AttrLiteral::ConfiguredDep(..)
+            | AttrLiteral::ConfigurationDep(..)
+            | AttrLiteral::SourceLabel(..)
+            | AttrLiteral::Label(..)
+            | AttrLiteral::SourceFile(..)
+            | AttrLiteral::Query(..)
+            | AttrLiteral::Arg(..)
+            | AttrLiteral::ExplicitConfiguredDep(..)
+            | AttrLiteral::SplitTransitionDep(..) => STRING_TYPE,
+        },
+    }
+}
+
+/// Methods on unconfigured target node's attributes.
+#[starlark_module]
+fn coerced_attr_methods(builder: &mut MethodsBuilder) {
+    /// Returns the type name of the attribute, which is `selector` if it's written with a
+    /// `select()`.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_type(ctx):
+    ///     node = ctx.unconfigured_targets("//bin:foo")
+    ///     for name, attr in node.attributes:
+    ///         ctx.output.print(name, attr.type)
+    /// ```
+    #[starlark(attribute)]
+    fn r#type<'v>(this: &StarlarkCoercedAttr) -> anyhow::Result<&'v str> {
+        Ok(coerced_attr_type(&this.0))
+    }
+
+    /// Returns the value of this attribute as it's written in the build file, before it's
+    /// configured: `select()`s and their concatenations are kept, and can be inspected with
+    /// `select_map` and `select_test`. Dependencies and sources are given as strings.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_value(ctx):
+    ///     node = ctx.unconfigured_targets("//bin:foo")
+    ///     for name, attr in node.attributes:
+    ///         ctx.output.print(name, attr.value())
+    /// ```
+    fn value<'v>(this: &StarlarkCoercedAttr, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        coerced_attr_to_value(&this.0, heap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::collections::ordered_map::OrderedMap;
+    use buck2_core::provider::label::ProvidersLabel;
+    use buck2_core::target::testing::TargetLabelExt;
+    use buck2_core::target::TargetLabel;
+    use buck2_node::attrs::attr_type::dep::DepAttr;
+    use buck2_node::attrs::attr_type::dep::DepAttrTransition;
+    use buck2_node::attrs::attr_type::dep::DepAttrType;
+    use buck2_node::attrs::attr_type::dep::ProviderIdSet;
+    use buck2_node::attrs::attr_type::AttrType;
+    use starlark::assert::Assert;
+    use starlark::environment::GlobalsBuilder;
+
+    use super::*;
+
+    fn strings(items: &[&str]) -> CoercedAttr {
+        CoercedAttr::Literal(AttrLiteral::List(
+            items
+                .iter()
+                .map(|s| CoercedAttr::Literal(AttrLiteral::String((*s).to_owned())))
+                .collect(),
+            AttrType::string(),
+        ))
+    }
+
+    fn select() -> CoercedAttr {
+        CoercedAttr::Selector(box (
+            OrderedMap::from_iter([(TargetLabel::testing_parse("root//:linux"), strings(&["a"]))]),
+            Some(strings(&[])),
+        ))
+    }
+
+    fn dep() -> CoercedAttr {
+        CoercedAttr::Literal(AttrLiteral::Dep(box DepAttr::new(
+            DepAttrType::new(ProviderIdSet::new(), DepAttrTransition::Identity),
+            ProvidersLabel::default_for(TargetLabel::testing_parse("root//foo:bar")),
+        )))
+    }
+
+    fn attr(name: &str) -> CoercedAttr {
+        match name {
+            "select" => select(),
+            "concat" => CoercedAttr::Concat(vec![strings(&["b"]), select()]),
+            "dict" => CoercedAttr::Literal(AttrLiteral::Dict(
+                vec![(
+                    CoercedAttr::Literal(AttrLiteral::String("k".to_owned())),
+                    dep(),
+                )],
+                false,
+            )),
+            "dep" => dep(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[starlark_module]
+    fn coerced_attrs(builder: &mut GlobalsBuilder) {
+        fn coerced_attr(name: &str) -> anyhow::Result<StarlarkCoercedAttr> {
+            Ok(StarlarkCoercedAttr(attr(name)))
+        }
+    }
+
+    #[test]
+    fn test_coerced_attr_to_value() -> anyhow::Result<()> {
+        let heap = Heap::new();
+        for (name, repr) in [
+            (
+                "select",
+                r#"select({"root//:linux": ["a"], "DEFAULT": []})"#,
+            ),
+            (
+                "concat",
+                r#"["b"] + select({"root//:linux": ["a"], "DEFAULT": []})"#,
+            ),
+            ("dict", r#"{"k": "root//foo:bar"}"#),
+            ("dep", r#""root//foo:bar""#),
+        ] {
+            let attr = attr(name);
+            let value = coerced_attr_to_value(&attr, &heap)?;
+            assert_eq!(repr, value.to_repr(), "{}", name);
+            assert_eq!(value.get_type(), coerced_attr_type(&attr), "{}", name);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_coerced_attr_methods() {
+        let mut a = Assert::new();
+        a.globals_add(coerced_attrs);
+        a.pass(
+            r#"
+for name, t in [("select", "selector"), ("concat", "selector"), ("dict", "dict"), ("dep", "string")]:
+    attr = coerced_attr(name)
+    assert_eq(attr.type, t)
+    assert_eq(type(attr.value()), t)
+assert_eq(coerced_attr("dict").value(), {"k": "root//foo:bar"})
+assert_eq(coerced_attr("dep").value(), "root//foo:bar")
+"#,
+        );
+    }
 }
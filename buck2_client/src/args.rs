@@ -223,6 +223,115 @@ fn expand_argfiles_with_context(
     Ok(expanded_args)
 }
 
+/// The option of the commands that accept target patterns to exclude.
+const EXCLUDE_TARGET_PATTERN: &str = "exclude-target-pattern";
+
+/// Target patterns prefixed with `-` exclude targets (e.g. `//foo/... -//foo/experimental/...`),
+/// but clap would parse them as short flags, so for the commands accepting
+/// `--exclude-target-pattern`, they are rewritten to that option. This runs after argfiles are
+/// expanded, so it covers patterns listed in argfiles too.
+pub fn rewrite_exclusion_patterns(app: &clap::Command, args: Vec<String>) -> Vec<String> {
+    // The command being parsed, and the ones it is a subcommand of, whose flags also apply.
+    let mut commands = vec![app];
+    let mut rewritten = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    rewritten.extend(args.next());
+
+    while let Some(arg) = args.next() {
+        let cmd = *commands.last().unwrap();
+        if arg == "--" {
+            rewritten.push(arg);
+            rewritten.extend(args);
+            break;
+        }
+        if let Some(sub) = cmd
+            .get_subcommands()
+            .find(|sub| sub.get_name() == arg || sub.get_all_aliases().any(|alias| alias == arg))
+        {
+            commands.push(sub);
+            rewritten.push(arg);
+            continue;
+        }
+        if takes_separate_value(&commands, &arg) {
+            // The value of a flag is never a pattern, even if it starts with `-`.
+            rewritten.push(arg);
+            rewritten.extend(args.next());
+            continue;
+        }
+        let supports_exclusions = cmd
+            .get_arguments()
+            .any(|a| a.get_long() == Some(EXCLUDE_TARGET_PATTERN));
+        match exclusion_pattern(&commands, &arg) {
+            Some(pattern) if supports_exclusions => {
+                rewritten.push(format!("--{}={}", EXCLUDE_TARGET_PATTERN, pattern))
+            }
+            _ => rewritten.push(arg),
+        }
+    }
+
+    rewritten
+}
+
+fn find_argument<'a>(
+    commands: &[&'a clap::Command<'a>],
+    matches: impl Fn(&clap::Arg) -> bool,
+) -> Option<&'a clap::Arg<'a>> {
+    commands
+        .iter()
+        .rev()
+        .find_map(|cmd| cmd.get_arguments().find(|a| matches(a)))
+}
+
+/// Whether `arg` is a flag whose value is the next argument, e.g. `--config` or `-c`, but not
+/// `--config=a.b=c` or `-ca.b=c`.
+fn takes_separate_value(commands: &[&clap::Command], arg: &str) -> bool {
+    let flag = if let Some(long) = arg.strip_prefix("--") {
+        find_argument(commands, |a| a.get_long() == Some(long))
+    } else if let Some(short) = arg.strip_prefix('-') {
+        let mut chars = short.chars();
+        match (chars.next(), chars.next()) {
+            (Some(short), None) => find_argument(commands, |a| a.get_short() == Some(short)),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    flag.map_or(false, |a| a.is_takes_value_set())
+}
+
+/// The pattern excluded by `arg`, if it is `-` followed by a target pattern rather than a short
+/// flag. These are:
+///  - patterns starting with `//` or `:`,
+///  - cell-qualified patterns (`-cell//foo/...`): no flag takes a value containing `//` without
+///    `=`, except modifiers, so `-m` must be separated from its value (`-m root//:linux`),
+///  - relative patterns (`-foo/...`, `-foo:bar`), unless they start with a short flag: those
+///    need to be cell-qualified or passed as `--exclude-target-pattern`.
+fn exclusion_pattern<'a>(commands: &[&clap::Command], arg: &'a str) -> Option<&'a str> {
+    let pattern = arg.strip_prefix('-')?;
+    if pattern.starts_with('/') || pattern.starts_with(':') {
+        return Some(pattern);
+    }
+    // Flags like `-v2` or `--config` and config values like `-ca.b=c/d` are not patterns.
+    if pattern.starts_with('-') || pattern.contains('=') {
+        return None;
+    }
+    if let Some((cell, _)) = pattern.split_once("//") {
+        let is_cell_name = !cell.is_empty()
+            && cell
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        return if is_cell_name { Some(pattern) } else { None };
+    }
+    if !pattern.contains('/') && !pattern.contains(':') {
+        return None;
+    }
+    let first = pattern.chars().next()?;
+    if find_argument(commands, |a| a.get_short() == Some(first)).is_some() {
+        return None;
+    }
+    Some(pattern)
+}
+
 // Resolves a path argument to an absolute path, reads the flag file and expands
 // it into a list of arguments.
 fn resolve_and_expand_argfile(
@@ -358,6 +467,7 @@ mod tests {
     use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
 
     use crate::args::expand_argfile_contents;
+    use crate::args::rewrite_exclusion_patterns;
     use crate::args::ArgFile;
 
     #[test]
@@ -372,4 +482,88 @@ mod tests {
         .unwrap();
         assert_eq!(vec!["a".to_owned(), "b".to_owned()], lines);
     }
+
+    fn app() -> clap::Command<'static> {
+        clap::Command::new("buck2")
+            .arg(clap::Arg::new("verbose").short('v').takes_value(true))
+            .subcommand(
+                clap::Command::new("build")
+                    .arg(
+                        clap::Arg::new("config")
+                            .short('c')
+                            .long("config")
+                            .takes_value(true)
+                            .multiple_occurrences(true),
+                    )
+                    .arg(
+                        clap::Arg::new("exclude")
+                            .long("exclude-target-pattern")
+                            .takes_value(true)
+                            .multiple_occurrences(true),
+                    )
+                    .arg(clap::Arg::new("patterns").multiple_values(true)),
+            )
+            .subcommand(
+                clap::Command::new("targets").arg(clap::Arg::new("patterns").multiple_values(true)),
+            )
+    }
+
+    fn rewrite(args: &[&str]) -> Vec<String> {
+        rewrite_exclusion_patterns(&app(), args.iter().map(|a| (*a).to_owned()).collect())
+    }
+
+    #[test]
+    fn test_rewrite_exclusion_patterns() {
+        assert_eq!(
+            vec![
+                "buck2",
+                "-v",
+                "2",
+                "build",
+                "//foo/...",
+                "--exclude-target-pattern=//foo/bar/...",
+                "--exclude-target-pattern=:baz",
+                "--exclude-target-pattern=cell//foo/...",
+                "--exclude-target-pattern=foo/qux/...",
+                "--exclude-target-pattern=foo:quux",
+            ],
+            rewrite(&[
+                "buck2",
+                "-v",
+                "2",
+                "build",
+                "//foo/...",
+                "-//foo/bar/...",
+                "-:baz",
+                "-cell//foo/...",
+                "-foo/qux/...",
+                "-foo:quux",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_rewrite_exclusion_patterns_keeps_flags() {
+        let args = [
+            "buck2",
+            "build",
+            "-c",
+            "a.b=c/d",
+            "-ca.b=c/d",
+            "--config",
+            "-//not/a/pattern",
+            "-cache/...",
+            "-v2",
+            "//foo/...",
+            "--",
+            "-//foo/bar/...",
+        ];
+        assert_eq!(args.to_vec(), rewrite(&args));
+    }
+
+    #[test]
+    fn test_rewrite_exclusion_patterns_unsupported_command() {
+        let args = ["buck2", "targets", "//foo/...", "-//foo/bar/..."];
+        assert_eq!(args.to_vec(), rewrite(&args));
+    }
 }
@@ -34,6 +34,10 @@ use crate::commands::uquery::CommonQueryArgs;
 ///
 /// `buck2 aquery 'kind(run, deps("//java/com/example/app:amazing+more"))' --output-attribute=cmd`
 ///
+/// List the actions of the anon targets used by a target, which are omitted by default
+///
+/// `buck2 aquery 'deps(//java/com/example/app:amazing)' --include-anon`
+///
 /// Dynamic outputs (`ctx.actions.dynamic_output`):
 ///
 /// Currently, aquery interacts poorly with dynamic outputs. It may return incorrect results or otherwise
@@ -52,6 +56,11 @@ pub struct AqueryCommand {
 
     #[clap(flatten)]
     query_common: CommonQueryArgs,
+
+    /// Output the actions of anon targets (labelled like `anon//:rule@hash`), which are
+    /// otherwise traversed but omitted from the results.
+    #[clap(long)]
+    include_anon: bool,
 }
 
 #[async_trait]
@@ -64,7 +73,10 @@ impl StreamingCommand for AqueryCommand {
         matches: &clap::ArgMatches,
         mut ctx: ClientCommandContext,
     ) -> ExitResult {
-        let (query, query_args) = self.query_common.get_query();
+        if !self.query_common.get_named_queries()?.is_empty() {
+            return ExitResult::bail("`--query-file` is not supported by aquery");
+        }
+        let (query, query_args) = self.query_common.get_query(&mut ctx).await?;
         let unstable_output_format = self.query_common.output_format() as i32;
         let output_attributes = self.query_common.attributes.get()?;
         let context = ctx.client_context(&self.config_opts, matches, self.sanitized_argv())?;
@@ -77,6 +89,9 @@ impl StreamingCommand for AqueryCommand {
                     query_args,
                     context: Some(context),
                     output_attributes,
+                    include_anon: self.include_anon,
+                    exclude_target_patterns: self.query_common.exclude_target_patterns,
+                    query_libs: self.query_common.query_lib,
                     unstable_output_format,
                 },
                 ctx.stdin().console_interaction_stream(&self.console_opts),
@@ -105,4 +120,12 @@ impl StreamingCommand for AqueryCommand {
     fn common_opts(&self) -> &CommonBuildConfigurationOptions {
         &self.config_opts
     }
+
+    fn profile_phases(&self) -> bool {
+        self.query_common.profile_phases
+    }
+
+    fn retry_after_restart(&self) -> bool {
+        !self.query_common.reads_stdin()
+    }
 }
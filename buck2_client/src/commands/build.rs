@@ -79,6 +79,18 @@ pub struct BuildCommand {
     #[clap(name = "TARGET_PATTERNS", help = "Patterns to build")]
     patterns: Vec<String>,
 
+    /// Also read patterns to build from stdin, one per line (blank lines and lines starting with
+    /// `#` are ignored, and patterns prefixed with `-` are exclusions). Use this for lists of
+    /// targets too long to pass as arguments.
+    #[clap(long)]
+    stdin: bool,
+
+    /// Patterns of targets to exclude from the targets matched by the patterns to build. Patterns
+    /// prefixed with `-` (e.g. `-//foo/experimental/...`), on the command line or in `@argfiles`,
+    /// are passed as this option.
+    #[clap(long = "exclude-target-pattern", value_name = "PATTERN")]
+    exclude_target_patterns: Vec<String>,
+
     #[clap(
         long,
         use_delimiter = true,
@@ -126,7 +138,8 @@ pub struct BuildCommand {
 
     #[clap(
         long = "materializations",
-        help = "Materialize (or skip) the final artifacts, bypassing buckconfig.",
+        help = "What to materialize, bypassing buckconfig: nothing, only the outputs of the requested targets, or the outputs of all the actions executed.",
+        value_name = "none|deps|all",
         ignore_case = true,
         arg_enum
     )]
@@ -183,6 +196,11 @@ pub struct BuildCommand {
         help = "Copy the output of the built target to this path (`-` to stdout)"
     )]
     output_path: Option<OutPath>,
+
+    /// Print a breakdown of where the time went, across pattern resolution, package loading,
+    /// configuration, analysis and execution, with the slowest packages to load.
+    #[clap(long)]
+    profile_phases: bool,
 }
 
 impl BuildCommand {
@@ -211,17 +229,24 @@ impl BuildCommand {
 #[derive(Debug, Clone, Dupe, clap::ArgEnum)]
 #[clap(rename_all = "snake_case")]
 pub enum FinalArtifactMaterializations {
-    All,
+    /// Don't materialize anything the build doesn't need: outputs only populate the caches.
     None,
+    /// Materialize the outputs of the requested targets.
+    Deps,
+    /// Materialize the outputs of the requested targets and of all the actions executed.
+    All,
 }
 
 pub trait MaterializationsToProto {
     fn to_proto(&self) -> cli_proto::build_request::Materializations;
+
+    /// Whether outputs of intermediate actions should be materialized too.
+    fn materialize_intermediates(&self) -> bool;
 }
 impl MaterializationsToProto for Option<FinalArtifactMaterializations> {
     fn to_proto(&self) -> cli_proto::build_request::Materializations {
         match self {
-            Some(FinalArtifactMaterializations::All) => {
+            Some(FinalArtifactMaterializations::Deps | FinalArtifactMaterializations::All) => {
                 cli_proto::build_request::Materializations::Materialize
             }
             Some(FinalArtifactMaterializations::None) => {
@@ -230,6 +255,10 @@ impl MaterializationsToProto for Option<FinalArtifactMaterializations> {
             None => cli_proto::build_request::Materializations::Default,
         }
     }
+
+    fn materialize_intermediates(&self) -> bool {
+        matches!(self, Some(FinalArtifactMaterializations::All))
+    }
 }
 
 pub fn print_build_result(console: &FinalConsole, error_messages: &[String]) -> anyhow::Result<()> {
@@ -251,6 +280,11 @@ impl StreamingCommand for BuildCommand {
     ) -> ExitResult {
         let show_default_other_outputs = false;
         let context = ctx.client_context(&self.config_opts, matches, self.sanitized_argv())?;
+        let stdin_patterns = if self.stdin {
+            ctx.stdin().read_literals().await?
+        } else {
+            Vec::new()
+        };
 
         let result = buckd
             .with_flushing()
@@ -259,7 +293,16 @@ impl StreamingCommand for BuildCommand {
                     context: Some(context),
                     target_patterns: self
                         .patterns
-                        .map(|p| buck2_data::TargetPattern { value: p.clone() }),
+                        .iter()
+                        .cloned()
+                        .chain(stdin_patterns)
+                        .chain(
+                            self.exclude_target_patterns
+                                .iter()
+                                .map(|p| format!("-{}", p)),
+                        )
+                        .map(|value| buck2_data::TargetPattern { value })
+                        .collect(),
                     unstable_print_providers: self.print_providers,
                     build_providers: Some(BuildProviders {
                         default_info: self.default_info() as i32,
@@ -274,7 +317,12 @@ impl StreamingCommand for BuildCommand {
                             || self.output_path.is_some(),
                         return_default_other_outputs: show_default_other_outputs,
                     }),
-                    build_opts: Some(self.build_opts.to_proto()),
+                    build_opts: Some(cli_proto::CommonBuildOptions {
+                        materialize_intermediates: self
+                            .materializations
+                            .materialize_intermediates(),
+                        ..self.build_opts.to_proto()
+                    }),
                     final_artifact_materializations: self.materializations.to_proto() as i32,
                     target_universe: self.target_universe,
                 },
@@ -352,6 +400,14 @@ impl StreamingCommand for BuildCommand {
     fn common_opts(&self) -> &CommonBuildConfigurationOptions {
         &self.config_opts
     }
+
+    fn profile_phases(&self) -> bool {
+        self.profile_phases
+    }
+
+    fn retry_after_restart(&self) -> bool {
+        !self.stdin
+    }
 }
 
 pub(crate) fn print_outputs(
@@ -713,6 +769,44 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn materializations() -> anyhow::Result<()> {
+        let opts = parse(&[])?;
+        assert_eq!(
+            cli_proto::build_request::Materializations::Default,
+            opts.materializations.to_proto()
+        );
+        assert!(!opts.materializations.materialize_intermediates());
+
+        let opts = parse(&["--materializations=none"])?;
+        assert_eq!(
+            cli_proto::build_request::Materializations::Skip,
+            opts.materializations.to_proto()
+        );
+        assert!(!opts.materializations.materialize_intermediates());
+
+        let opts = parse(&["--materializations=deps"])?;
+        assert_eq!(
+            cli_proto::build_request::Materializations::Materialize,
+            opts.materializations.to_proto()
+        );
+        assert!(!opts.materializations.materialize_intermediates());
+
+        let opts = parse(&["--materializations=ALL"])?;
+        assert_eq!(
+            cli_proto::build_request::Materializations::Materialize,
+            opts.materializations.to_proto()
+        );
+        assert!(opts.materializations.materialize_intermediates());
+
+        assert_matches!(
+            parse(&["--materializations=all_with_intermediates"]),
+            Err(..)
+        );
+
+        Ok(())
+    }
+
     #[cfg(unix)]
     mod unix {
         use assert_matches::assert_matches;
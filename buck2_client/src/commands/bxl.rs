@@ -46,7 +46,8 @@ pub struct BxlCommandOptions {
 
     #[clap(
         long = "materializations",
-        help = "Materialize (or skip) the final artifacts, bypassing buckconfig.",
+        help = "What to materialize, bypassing buckconfig: nothing, only the outputs of the requested targets, or the outputs of all the actions executed.",
+        value_name = "none|deps|all",
         ignore_case = true,
         arg_enum
     )]
@@ -84,7 +85,13 @@ impl StreamingCommand for BxlCommand {
                     context: Some(context),
                     bxl_label: self.bxl_opts.bxl_label,
                     bxl_args: self.bxl_opts.bxl_args,
-                    build_opts: Some(self.bxl_opts.build_opts.to_proto()),
+                    build_opts: Some(cli_proto::CommonBuildOptions {
+                        materialize_intermediates: self
+                            .bxl_opts
+                            .materializations
+                            .materialize_intermediates(),
+                        ..self.bxl_opts.build_opts.to_proto()
+                    }),
                     final_artifact_materializations: self.bxl_opts.materializations.to_proto()
                         as i32,
                 },
@@ -73,6 +73,20 @@ pub struct CqueryCommand {
     )]
     show_providers: bool,
 
+    #[clap(
+        long = "show-output",
+        help = "Print the path to the default outputs of each target relative to the project root. \
+                The outputs are not built"
+    )]
+    show_output: bool,
+
+    #[clap(
+        long = "show-full-output",
+        conflicts_with = "show_output",
+        help = "Print the absolute path to the default outputs of each target. The outputs are not built"
+    )]
+    show_full_output: bool,
+
     #[allow(rustdoc::bare_urls)]
     /// Enable deprecated `owner()` function behavior.
     ///
@@ -98,7 +112,8 @@ impl StreamingCommand for CqueryCommand {
         matches: &clap::ArgMatches,
         mut ctx: ClientCommandContext,
     ) -> ExitResult {
-        let (query, query_args) = self.query_common.get_query();
+        let (query, query_args) = self.query_common.get_query(&mut ctx).await?;
+        let named_queries = self.query_common.get_named_queries()?;
         let unstable_output_format = self.query_common.output_format() as i32;
         let output_attributes = self.query_common.attributes.get()?;
         let context = ctx.client_context(&self.config_opts, matches, self.sanitized_argv())?;
@@ -124,9 +139,15 @@ impl StreamingCommand for CqueryCommand {
                     output_attributes,
                     target_universe: self.target_universe,
                     show_providers: self.show_providers,
+                    show_outputs: self.show_output,
+                    show_full_outputs: self.show_full_output,
                     unstable_output_format,
                     target_call_stacks: self.query_common.target_call_stacks,
                     correct_owner,
+                    named_queries,
+                    exclude_target_patterns: self.query_common.exclude_target_patterns,
+                    return_targets: false,
+                    query_libs: self.query_common.query_lib,
                 },
                 ctx.stdin().console_interaction_stream(&self.console_opts),
             )
@@ -154,4 +175,12 @@ impl StreamingCommand for CqueryCommand {
     fn common_opts(&self) -> &CommonBuildConfigurationOptions {
         &self.config_opts
     }
+
+    fn profile_phases(&self) -> bool {
+        self.query_common.profile_phases
+    }
+
+    fn retry_after_restart(&self) -> bool {
+        !self.query_common.reads_stdin()
+    }
 }
@@ -0,0 +1,224 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use async_trait::async_trait;
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::common::CommonBuildConfigurationOptions;
+use buck2_client_ctx::common::CommonConsoleOptions;
+use buck2_client_ctx::common::CommonDaemonCommandOptions;
+use buck2_client_ctx::daemon::client::BuckdClientConnector;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::streaming::StreamingCommand;
+use cli_proto::ActionExecRequest;
+use cli_proto::ActionExecResponse;
+
+#[derive(Debug, clap::Parser)]
+pub struct ActionExecCommand {
+    #[clap(flatten)]
+    config_opts: CommonBuildConfigurationOptions,
+
+    #[clap(flatten)]
+    console_opts: CommonConsoleOptions,
+
+    #[clap(flatten)]
+    event_log_opts: CommonDaemonCommandOptions,
+
+    /// The target that registered the action, followed by the action's category and identifier,
+    /// e.g. `//foo:bar cxx_compile foo.cpp`, or a path to one of the action's outputs, e.g.
+    /// `buck-out/v2/gen/root/<hash>/foo/__bar__/foo.o`.
+    #[clap(
+        value_name = "ACTION_OR_OUTPUT",
+        required_unless_present = "query",
+        conflicts_with = "query"
+    )]
+    action: Option<String>,
+
+    /// The category of the action, if `ACTION_OR_OUTPUT` is a target.
+    #[clap(value_name = "CATEGORY")]
+    category: Option<String>,
+
+    /// The identifier of the action, if its target registers several actions of that category.
+    #[clap(value_name = "IDENTIFIER", requires = "category")]
+    identifier: Option<String>,
+
+    /// An aquery expression that resolves to exactly one action, e.g.
+    /// `'filter(category=cxx_compile, deps(//foo:bar, 1))'`, instead of `ACTION_OR_OUTPUT`.
+    #[clap(long, value_name = "QUERY")]
+    query: Option<String>,
+
+    /// Spawn `$SHELL` in the action's working directory and environment instead of printing the
+    /// command.
+    #[clap(long)]
+    shell: bool,
+}
+
+#[async_trait]
+impl StreamingCommand for ActionExecCommand {
+    const COMMAND_NAME: &'static str = "action-exec";
+
+    async fn exec_impl(
+        self,
+        mut buckd: BuckdClientConnector,
+        matches: &clap::ArgMatches,
+        mut ctx: ClientCommandContext,
+    ) -> ExitResult {
+        let context = ctx.client_context(&self.config_opts, matches, self.sanitized_argv())?;
+        let response = buckd
+            .with_flushing()
+            .action_exec(
+                ActionExecRequest {
+                    context: Some(context),
+                    query: self.query.unwrap_or_default(),
+                    action: self.action.unwrap_or_default(),
+                    category: self.category.unwrap_or_default(),
+                    identifier: self.identifier,
+                },
+                ctx.stdin().console_interaction_stream(&self.console_opts),
+            )
+            .await??;
+
+        if self.shell {
+            spawn_shell(&response).await
+        } else {
+            buck2_client_ctx::println!("{}", shell_script(&response))?;
+            ExitResult::success()
+        }
+    }
+
+    fn console_opts(&self) -> &CommonConsoleOptions {
+        &self.console_opts
+    }
+
+    fn event_log_opts(&self) -> &CommonDaemonCommandOptions {
+        &self.event_log_opts
+    }
+
+    fn common_opts(&self) -> &CommonBuildConfigurationOptions {
+        &self.config_opts
+    }
+}
+
+/// A snippet that reproduces the action from any shell.
+fn shell_script(response: &ActionExecResponse) -> String {
+    let mut env = Vec::new();
+    if response.clear_env {
+        env.push("-i".to_owned());
+    }
+    for key in &response.unset_env {
+        env.push(format!("-u {}", shlex::quote(key)));
+    }
+    env.push(format!("TMPDIR={}", shlex::quote(&response.scratch_dir)));
+    env.extend(
+        response
+            .env
+            .iter()
+            .map(|entry| shlex::quote(&format!("{}={}", entry.key, entry.value)).into_owned()),
+    );
+
+    format!(
+        "# {}\ncd {} && env {} {}",
+        response.action,
+        shlex::quote(&response.working_dir),
+        env.join(" "),
+        shlex::join(response.argv.iter().map(|a| a.as_str())),
+    )
+}
+
+async fn spawn_shell(response: &ActionExecResponse) -> ExitResult {
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_owned());
+
+    buck2_client_ctx::eprintln!("Entering the environment of {}", response.action)?;
+    buck2_client_ctx::eprintln!(
+        "Command: {}",
+        shlex::join(response.argv.iter().map(|a| a.as_str()))
+    )?;
+
+    let mut command = tokio::process::Command::new(&shell);
+    if response.clear_env {
+        command.env_clear();
+    }
+    for key in &response.unset_env {
+        command.env_remove(key);
+    }
+    let status = command
+        .current_dir(&response.working_dir)
+        .envs(response.env.iter().map(|entry| (&entry.key, &entry.value)))
+        .env("TMPDIR", &response.scratch_dir)
+        .status()
+        .await?;
+
+    match status.code() {
+        Some(code) => ExitResult::status_extended(code),
+        None => ExitResult::failure(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use cli_proto::action_exec_response::EnvironmentEntry;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let parse = |args: &[&str]| {
+            ActionExecCommand::try_parse_from(
+                std::iter::once("action-exec").chain(args.iter().copied()),
+            )
+        };
+
+        let cmd = parse(&["//foo:bar", "cxx_compile", "foo.cpp"]).unwrap();
+        assert_eq!(Some("//foo:bar"), cmd.action.as_deref());
+        assert_eq!(Some("cxx_compile"), cmd.category.as_deref());
+        assert_eq!(Some("foo.cpp"), cmd.identifier.as_deref());
+
+        let cmd = parse(&["buck-out/v2/gen/root/abc/foo/__bar__/foo.o"]).unwrap();
+        assert_eq!(None, cmd.category);
+
+        let cmd = parse(&["--query", "deps(//foo:bar)"]).unwrap();
+        assert_eq!(None, cmd.action);
+
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["//foo:bar", "--query", "deps(//foo:bar)"]).is_err());
+    }
+
+    #[test]
+    fn test_shell_script() {
+        let response = ActionExecResponse {
+            action: "root//:foo cxx_compile foo.o".to_owned(),
+            argv: vec!["clang".to_owned(), "-c".to_owned(), "foo bar.c".to_owned()],
+            env: vec![EnvironmentEntry {
+                key: "LANG".to_owned(),
+                value: "C".to_owned(),
+            }],
+            working_dir: "/repo".to_owned(),
+            scratch_dir: "/repo/buck-out/tmp".to_owned(),
+            clear_env: false,
+            unset_env: vec!["LD_PRELOAD".to_owned()],
+        };
+        assert_eq!(
+            shell_script(&response),
+            "# root//:foo cxx_compile foo.o\n\
+             cd /repo && env -u LD_PRELOAD TMPDIR=/repo/buck-out/tmp LANG=C clang -c 'foo bar.c'"
+        );
+
+        // A scrubbed environment.
+        let response = ActionExecResponse {
+            clear_env: true,
+            unset_env: Vec::new(),
+            ..response
+        };
+        assert_eq!(
+            shell_script(&response),
+            "# root//:foo cxx_compile foo.o\n\
+             cd /repo && env -i TMPDIR=/repo/buck-out/tmp LANG=C clang -c 'foo bar.c'"
+        );
+    }
+}
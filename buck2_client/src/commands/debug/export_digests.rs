@@ -0,0 +1,35 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::path_arg::PathArg;
+use buck2_common::io::digest_cache::FileDigestCache;
+use buck2_core::fs::fs_util;
+
+/// Writes the source file digest cache as a manifest of `<sha1>:<size> <path>` lines, which can
+/// be loaded on another checkout of the same revision with `buck2 debug import-digests`.
+#[derive(Debug, clap::Parser)]
+pub struct ExportDigestsCommand {
+    /// Where to write the manifest. Prints it to stdout if not set.
+    #[clap(long, short = 'o', value_name = "PATH")]
+    output: Option<PathArg>,
+}
+
+impl ExportDigestsCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext) -> ExitResult {
+        let cache = FileDigestCache::open(&ctx.paths.file_digest_cache_path())?;
+        let manifest = cache.export_manifest();
+        match self.output {
+            Some(output) => fs_util::write(output.resolve(&ctx.working_dir), manifest)?,
+            None => buck2_client_ctx::print!("{}", manifest)?,
+        }
+        ExitResult::success()
+    }
+}
@@ -0,0 +1,70 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use anyhow::Context;
+use async_trait::async_trait;
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::common::CommonBuildConfigurationOptions;
+use buck2_client_ctx::common::CommonConsoleOptions;
+use buck2_client_ctx::common::CommonDaemonCommandOptions;
+use buck2_client_ctx::daemon::client::BuckdClientConnector;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::path_arg::PathArg;
+use buck2_client_ctx::streaming::StreamingCommand;
+use cli_proto::ImportDigestsRequest;
+
+/// Seeds the daemon's source file digest cache from a manifest of `<sha1>:<size> <path>` lines,
+/// so that a fresh checkout (e.g. on CI) doesn't need to hash every source file before building.
+/// Requires `buck2.file_hashing_mode = mtime`. Run this right after checkout, since the digests
+/// are used for the files as they are on disk. The daemon verifies them in the background.
+#[derive(Debug, clap::Parser)]
+pub struct ImportDigestsCommand {
+    /// The manifest to import, as written by `buck2 debug export-digests`. Paths are relative to
+    /// the project root.
+    #[clap(value_name = "PATH")]
+    manifest: PathArg,
+}
+
+#[async_trait]
+impl StreamingCommand for ImportDigestsCommand {
+    const COMMAND_NAME: &'static str = "ImportDigests";
+
+    async fn exec_impl(
+        self,
+        mut buckd: BuckdClientConnector,
+        _matches: &clap::ArgMatches,
+        ctx: ClientCommandContext,
+    ) -> ExitResult {
+        let response = buckd
+            .with_flushing()
+            .import_digests(ImportDigestsRequest {
+                manifest_path: self
+                    .manifest
+                    .resolve(&ctx.working_dir)
+                    .to_str()
+                    .context("path is not UTF-8")?
+                    .to_owned(),
+            })
+            .await??;
+        buck2_client_ctx::eprintln!("Imported {} file digests", response.imported)?;
+        ExitResult::success()
+    }
+
+    fn console_opts(&self) -> &CommonConsoleOptions {
+        CommonConsoleOptions::simple_ref()
+    }
+
+    fn event_log_opts(&self) -> &CommonDaemonCommandOptions {
+        CommonDaemonCommandOptions::default_ref()
+    }
+
+    fn common_opts(&self) -> &CommonBuildConfigurationOptions {
+        CommonBuildConfigurationOptions::default_ref()
+    }
+}
@@ -16,7 +16,10 @@ use buck2_client_ctx::daemon::client::BuckdClientConnector;
 use buck2_client_ctx::exit_result::ExitResult;
 use buck2_client_ctx::streaming::StreamingCommand;
 use cli_proto::MaterializeRequest;
+use cli_proto::TargetsRequest;
 
+/// Materializes outputs known to the daemon from previous builds, without building anything. Useful
+/// when deferred materialization skipped the files you need.
 #[derive(Debug, clap::Parser)]
 pub struct MaterializeCommand {
     #[clap(flatten)]
@@ -28,11 +31,25 @@ pub struct MaterializeCommand {
     #[clap(flatten)]
     event_log_opts: CommonDaemonCommandOptions,
 
-    /// Paths to materialize, relative to project root
-    #[clap(value_name = "PATH")]
+    /// Paths to materialize, relative to project root, or targets whose default outputs to
+    /// materialize. Arguments containing `//` or `:` are treated as targets, except for Windows
+    /// paths starting with a drive like `C:\`.
+    #[clap(value_name = "PATH|TARGET")]
     paths: Vec<String>,
 }
 
+/// Whether `arg` starts with a Windows drive, e.g. `C:\` or `C:/`.
+fn has_drive(arg: &str) -> bool {
+    match arg.as_bytes() {
+        [drive, b':', sep, ..] => drive.is_ascii_alphabetic() && matches!(sep, b'\\' | b'/'),
+        _ => false,
+    }
+}
+
+fn is_target_pattern(arg: &str) -> bool {
+    !has_drive(arg) && (arg.contains("//") || arg.contains(':'))
+}
+
 #[async_trait]
 impl StreamingCommand for MaterializeCommand {
     const COMMAND_NAME: &'static str = "materialize";
@@ -48,12 +65,42 @@ impl StreamingCommand for MaterializeCommand {
         mut ctx: ClientCommandContext,
     ) -> ExitResult {
         let context = ctx.client_context(&self.config_opts, matches, self.sanitized_argv())?;
+        let (target_patterns, mut paths): (Vec<_>, Vec<_>) = self
+            .paths
+            .into_iter()
+            .partition(|arg| is_target_pattern(arg));
+
+        if !target_patterns.is_empty() {
+            // This only analyzes the targets, the outputs must have been built before to be
+            // materialized.
+            let response = buckd
+                .with_flushing()
+                .targets_show_outputs(
+                    TargetsRequest {
+                        context: Some(context.clone()),
+                        target_patterns: target_patterns
+                            .into_iter()
+                            .map(|value| buck2_data::TargetPattern { value })
+                            .collect(),
+                        ..Default::default()
+                    },
+                    ctx.stdin().console_interaction_stream(&self.console_opts),
+                )
+                .await??;
+            paths.extend(
+                response
+                    .targets_paths
+                    .into_iter()
+                    .flat_map(|target_paths| target_paths.paths),
+            );
+        }
+
         buckd
             .with_flushing()
             .materialize(
                 MaterializeRequest {
                     context: Some(context),
-                    paths: self.paths,
+                    paths,
                 },
                 ctx.stdin().console_interaction_stream(&self.console_opts),
             )
@@ -74,3 +121,19 @@ impl StreamingCommand for MaterializeCommand {
         &self.config_opts
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_target_pattern() {
+        assert!(is_target_pattern("//foo:bar"));
+        assert!(is_target_pattern("cell//foo/..."));
+        assert!(is_target_pattern(":bar"));
+        assert!(is_target_pattern("foo:bar"));
+        assert!(!is_target_pattern("buck-out/v2/gen/foo/bar"));
+        assert!(!is_target_pattern("C:\\repo\\buck-out\\v2\\gen\\foo"));
+        assert!(!is_target_pattern("c:/repo/buck-out/v2/gen/foo"));
+    }
+}
@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+use action_exec::ActionExecCommand;
 use allocator_stats::AllocatorStatsCommand;
 use buck2_client_ctx::client_ctx::ClientCommandContext;
 use buck2_client_ctx::client_ctx::ProcessContext;
@@ -17,8 +18,10 @@ use buck2_core::fs::working_dir::WorkingDir;
 use chrome_trace::ChromeTraceCommand;
 use crash::CrashCommand;
 use dice_dump::DiceDumpCommand;
+use export_digests::ExportDigestsCommand;
 use flush_dep_files::FlushDepFilesCommand;
 use heap_dump::HeapDumpCommand;
+use import_digests::ImportDigestsCommand;
 use internal_version::InternalVersionCommand;
 use materialize::MaterializeCommand;
 use replay::ReplayCommand;
@@ -26,11 +29,13 @@ use replay::ReplayCommand;
 use crate::commands::debug::allocative::AllocativeCommand;
 use crate::commands::debug::daemon_dir::DaemonDirCommand;
 use crate::commands::debug::exe::ExeCommand;
+use crate::commands::debug::resolve_out_path::ResolveOutPathCommand;
 use crate::commands::debug::segfault::SegfaultCommand;
 use crate::commands::debug::upload_re_logs::UploadReLogsCommand;
 use crate::commands::log::last_log::LastLogCommand;
 use crate::commands::log::what_ran::WhatRanCommand;
 
+mod action_exec;
 mod allocative;
 mod allocator_stats;
 mod chrome_trace;
@@ -38,11 +43,14 @@ mod crash;
 mod daemon_dir;
 mod dice_dump;
 mod exe;
+mod export_digests;
 mod flush_dep_files;
 mod heap_dump;
+mod import_digests;
 mod internal_version;
 mod materialize;
 pub mod replay;
+mod resolve_out_path;
 mod segfault;
 mod upload_re_logs;
 
@@ -69,10 +77,20 @@ pub enum DebugCommand {
     ChromeTrace(ChromeTraceCommand),
     /// Flushes all dep files known to Buck2.
     FlushDepFiles(FlushDepFilesCommand),
-    /// Forces materialization of a path, even on the deferred materializer
+    /// Forces materialization of paths or of the outputs of targets, even on the deferred
+    /// materializer
     Materialize(MaterializeCommand),
+    /// Builds the inputs of a single action and prints (or spawns a shell with) its command line,
+    /// environment and working directory, so that it can be rerun by hand.
+    ActionExec(ActionExecCommand),
     // Upload RE logs given an RE session ID
     UploadReLogs(UploadReLogsCommand),
+    /// Seeds the source file digest cache from a manifest, to avoid hashing a fresh checkout.
+    ImportDigests(ImportDigestsCommand),
+    /// Writes the source file digest cache as a manifest for `import-digests`.
+    ExportDigests(ExportDigestsCommand),
+    /// Prints the configuration of an output path in the short output path layout.
+    ResolveOutPath(ResolveOutPathCommand),
 
     // Those 2 log commands kept here for historical compatibility
     /// Shows the commands that buck ran
@@ -117,7 +135,11 @@ impl DebugCommand {
             DebugCommand::WhatRan(cmd) => cmd.exec(matches, ctx),
             DebugCommand::LastLog(cmd) => cmd.exec(matches, ctx),
             DebugCommand::Materialize(cmd) => cmd.exec(matches, ctx),
+            DebugCommand::ActionExec(cmd) => cmd.exec(matches, ctx),
             DebugCommand::UploadReLogs(cmd) => cmd.exec(matches, ctx),
+            DebugCommand::ImportDigests(cmd) => cmd.exec(matches, ctx),
+            DebugCommand::ExportDigests(cmd) => cmd.exec(matches, ctx),
+            DebugCommand::ResolveOutPath(cmd) => cmd.exec(matches, ctx),
             DebugCommand::DaemonDir(cmd) => cmd.exec(matches, ctx),
             DebugCommand::Exe(cmd) => cmd.exec(matches, ctx),
             DebugCommand::Allocative(cmd) => cmd.exec(matches, ctx),
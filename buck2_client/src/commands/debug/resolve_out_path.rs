@@ -0,0 +1,58 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_execute::path::short_out_paths::ShortOutPaths;
+
+/// Prints which configuration an output path of the short output path layout (enabled with
+/// `buck2.short_out_paths = true`) was built in, and where the default layout would put it.
+/// Prints one `<path>\t<configuration>\t<default layout path>` line per path.
+#[derive(Debug, clap::Parser)]
+pub struct ResolveOutPathCommand {
+    /// Output paths, e.g. `buck-out/v2/gen/root/1a2b3c/foo/__bar__/out`.
+    #[clap(value_name = "PATH", required = true)]
+    paths: Vec<String>,
+}
+
+impl ResolveOutPathCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext) -> ExitResult {
+        let short_out_paths = ShortOutPaths::load(&ctx.paths.short_out_paths_path())?;
+        for path in &self.paths {
+            let separator = if path.contains('\\') { '\\' } else { '/' };
+            let mut resolved = None;
+            let components = path
+                .split(separator)
+                .map(|component| match short_out_paths.lookup(component) {
+                    Some(entry) if resolved.is_none() => {
+                        let full = entry.full.clone();
+                        resolved = Some(entry);
+                        full
+                    }
+                    _ => component.to_owned(),
+                })
+                .collect::<Vec<_>>();
+            match resolved {
+                Some(entry) => buck2_client_ctx::println!(
+                    "{}\t{}\t{}",
+                    path,
+                    entry.configuration,
+                    components.join(&separator.to_string())
+                )?,
+                None => {
+                    return ExitResult::bail(format!(
+                        "`{}` is not in a directory of the short output path layout",
+                        path
+                    ));
+                }
+            }
+        }
+        ExitResult::success()
+    }
+}
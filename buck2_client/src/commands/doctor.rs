@@ -0,0 +1,520 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::process::Stdio;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_common::legacy_configs::LegacyBuckConfig;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::is_open_source;
+use chrono::DateTime;
+use gazebo::dupe::Dupe;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio::process::Command;
+
+#[derive(Debug, Error)]
+enum DoctorError {
+    #[error("Unknown probe `{0}`, known probes are: {1}")]
+    UnknownProbe(String, String),
+    #[error("`{0}` failed: {1}")]
+    CommandFailed(String, String),
+    #[error("No `Date` header in the response of `{0}`")]
+    NoDateHeader(String),
+}
+
+/// How long a single probe may take before it is reported as failed.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const GIB: u64 = 1 << 30;
+
+/// Checks the environment buck2 runs in and reports problems as JSON.
+///
+/// Each probe results in `pass`, `warn`, `fail`, or `skip` when it does not apply (e.g. probing
+/// remote execution when it is not configured). The command fails if any probe failed.
+#[derive(Debug, clap::Parser)]
+#[clap(name = "doctor")]
+pub struct DoctorCommand {
+    /// Only run this probe. Can be repeated.
+    #[clap(long = "probe", value_name = "NAME")]
+    probes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ProbeStatus {
+    Skip,
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct ProbeResult {
+    status: ProbeStatus,
+    message: String,
+}
+
+impl ProbeResult {
+    fn new(status: ProbeStatus, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ProbeReport {
+    name: &'static str,
+    #[serde(flatten)]
+    result: ProbeResult,
+}
+
+#[derive(Serialize)]
+struct DoctorReport {
+    /// The worst status of all probes.
+    status: ProbeStatus,
+    probes: Vec<ProbeReport>,
+}
+
+/// What probes can inspect.
+struct ProbeContext<'a> {
+    project_root: &'a ProjectRoot,
+    buck_out: AbsNormPathBuf,
+    /// The root cell's buckconfig, empty if it could not be read.
+    config: LegacyBuckConfig,
+}
+
+/// A health check. To add one, implement this and add it to `probes`.
+#[async_trait]
+trait Probe: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn run(&self, ctx: &ProbeContext<'_>) -> anyhow::Result<ProbeResult>;
+}
+
+fn probes() -> Vec<Box<dyn Probe>> {
+    vec![
+        box WatchmanProbe,
+        box RemoteExecutionProbe,
+        box DiskSpaceProbe,
+        box ClockSkewProbe,
+        box UlimitProbe,
+    ]
+}
+
+impl DoctorCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext) -> ExitResult {
+        let mut probes = probes();
+        if !self.probes.is_empty() {
+            for name in &self.probes {
+                if !probes.iter().any(|p| p.name() == name) {
+                    let known: Vec<_> = probes.iter().map(|p| p.name()).collect();
+                    return Err(DoctorError::UnknownProbe(name.clone(), known.join(", ")))?;
+                }
+            }
+            probes.retain(|p| self.probes.iter().any(|name| name == p.name()));
+        }
+
+        ctx.with_runtime(async move |ctx| {
+            let config = match ctx.immediate_config.get() {
+                Ok(config) => config.dupe(),
+                Err(e) => {
+                    buck2_client_ctx::eprintln!("Not reading buckconfig: {:#}", e)?;
+                    LegacyBuckConfig::empty()
+                }
+            };
+            let probe_ctx = ProbeContext {
+                project_root: ctx.paths.project_root(),
+                buck_out: ctx.paths.buck_out_path(),
+                config,
+            };
+
+            let reports = futures::future::join_all(probes.iter().map(|probe| {
+                let probe_ctx = &probe_ctx;
+                async move {
+                    let result =
+                        match tokio::time::timeout(PROBE_TIMEOUT, probe.run(probe_ctx)).await {
+                            Ok(Ok(result)) => result,
+                            Ok(Err(e)) => ProbeResult::new(ProbeStatus::Fail, format!("{:#}", e)),
+                            Err(_) => ProbeResult::new(ProbeStatus::Fail, "Timed out"),
+                        };
+                    ProbeReport {
+                        name: probe.name(),
+                        result,
+                    }
+                }
+            }))
+            .await;
+
+            let report = DoctorReport {
+                status: overall_status(reports.iter().map(|r| r.result.status)),
+                probes: reports,
+            };
+            buck2_client_ctx::println!("{}", serde_json::to_string_pretty(&report)?)?;
+
+            if report.status == ProbeStatus::Fail {
+                ExitResult::failure()
+            } else {
+                ExitResult::success()
+            }
+        })
+    }
+}
+
+fn overall_status(statuses: impl IntoIterator<Item = ProbeStatus>) -> ProbeStatus {
+    statuses
+        .into_iter()
+        .max()
+        .unwrap_or(ProbeStatus::Skip)
+        .max(ProbeStatus::Pass)
+}
+
+/// Runs `command` and returns its stdout.
+async fn command_output(command: &mut Command) -> anyhow::Result<String> {
+    let output = command
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .with_context(|| format!("Error running {:?}", command.as_std()))?;
+    if !output.status.success() {
+        return Err(DoctorError::CommandFailed(
+            format!("{:?}", command.as_std()),
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+struct WatchmanProbe;
+
+#[async_trait]
+impl Probe for WatchmanProbe {
+    fn name(&self) -> &'static str {
+        "watchman"
+    }
+
+    async fn run(&self, ctx: &ProbeContext<'_>) -> anyhow::Result<ProbeResult> {
+        // Same default as the daemon.
+        let default = if is_open_source() {
+            "notify"
+        } else {
+            "watchman"
+        };
+        let file_watcher = ctx.config.get("buck2", "file_watcher").unwrap_or(default);
+        if file_watcher != "watchman" {
+            return Ok(ProbeResult::new(
+                ProbeStatus::Skip,
+                format!("File watcher is `{}`", file_watcher),
+            ));
+        }
+
+        command_output(
+            Command::new("watchman")
+                .args(["--no-spawn", "get-sockname"])
+                .current_dir(ctx.project_root.root().as_path()),
+        )
+        .await?;
+        Ok(ProbeResult::new(ProbeStatus::Pass, "Watchman is running"))
+    }
+}
+
+struct RemoteExecutionProbe;
+
+/// The `host:port` to connect to for an address like `https://host` or `grpc://host:port`.
+fn socket_address(address: &str) -> String {
+    let (scheme, rest) = match address.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, address),
+    };
+    let authority = rest.split('/').next().unwrap_or_default();
+    // The last colon separates the port, unless it's inside an IPv6 address.
+    let has_port = match authority.rsplit_once(':') {
+        Some((_, port)) => !port.contains(']'),
+        None => false,
+    };
+    if has_port {
+        return authority.to_owned();
+    }
+    let port = match scheme {
+        Some("http") => 80,
+        _ => 443,
+    };
+    format!("{}:{}", authority, port)
+}
+
+#[async_trait]
+impl Probe for RemoteExecutionProbe {
+    fn name(&self) -> &'static str {
+        "remote_execution"
+    }
+
+    async fn run(&self, ctx: &ProbeContext<'_>) -> anyhow::Result<ProbeResult> {
+        let addresses: Vec<_> = ["engine_address", "cas_address", "action_cache_address"]
+            .iter()
+            .filter_map(|key| ctx.config.get("buck2_re_client", key))
+            .collect();
+        if addresses.is_empty() {
+            return Ok(ProbeResult::new(
+                ProbeStatus::Skip,
+                "Remote execution is not configured",
+            ));
+        }
+
+        let mut unreachable = Vec::new();
+        for address in &addresses {
+            if let Err(e) = TcpStream::connect(socket_address(address)).await {
+                unreachable.push(format!("{} ({})", address, e));
+            }
+        }
+        if unreachable.is_empty() {
+            Ok(ProbeResult::new(
+                ProbeStatus::Pass,
+                format!("Reached {}", addresses.join(", ")),
+            ))
+        } else {
+            Ok(ProbeResult::new(
+                ProbeStatus::Fail,
+                format!("Could not reach {}", unreachable.join(", ")),
+            ))
+        }
+    }
+}
+
+struct DiskSpaceProbe;
+
+fn disk_space_status(available: u64) -> ProbeStatus {
+    if available < GIB {
+        ProbeStatus::Fail
+    } else if available < 10 * GIB {
+        ProbeStatus::Warn
+    } else {
+        ProbeStatus::Pass
+    }
+}
+
+#[cfg(unix)]
+fn available_space(path: &std::path::Path) -> anyhow::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is a valid C string and `stat` a valid `statvfs`.
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    #[allow(clippy::unnecessary_cast)] // The field types differ between platforms.
+    let available = stat.f_bavail as u64 * stat.f_frsize as u64;
+    Ok(available)
+}
+
+#[async_trait]
+impl Probe for DiskSpaceProbe {
+    fn name(&self) -> &'static str {
+        "disk_space"
+    }
+
+    #[cfg(unix)]
+    async fn run(&self, ctx: &ProbeContext<'_>) -> anyhow::Result<ProbeResult> {
+        // buck-out might not exist yet, in which case it will be on the project's filesystem.
+        let path = if ctx.buck_out.as_path().exists() {
+            ctx.buck_out.as_path()
+        } else {
+            ctx.project_root.root().as_path()
+        };
+        let available = available_space(path)?;
+        Ok(ProbeResult::new(
+            disk_space_status(available),
+            format!(
+                "{:.1} GiB available for {}",
+                available as f64 / GIB as f64,
+                path.display()
+            ),
+        ))
+    }
+
+    #[cfg(not(unix))]
+    async fn run(&self, _ctx: &ProbeContext<'_>) -> anyhow::Result<ProbeResult> {
+        Ok(ProbeResult::new(
+            ProbeStatus::Skip,
+            "Not implemented on this platform",
+        ))
+    }
+}
+
+struct ClockSkewProbe;
+
+fn clock_skew_status(skew: Duration) -> ProbeStatus {
+    if skew >= Duration::from_secs(60) {
+        ProbeStatus::Fail
+    } else if skew >= Duration::from_secs(5) {
+        ProbeStatus::Warn
+    } else {
+        ProbeStatus::Pass
+    }
+}
+
+/// The time in the `Date` header of an HTTP response.
+fn response_date(headers: &str) -> Option<SystemTime> {
+    headers.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if !name.trim().eq_ignore_ascii_case("date") {
+            return None;
+        }
+        let date = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+        Some(date.into())
+    })
+}
+
+#[async_trait]
+impl Probe for ClockSkewProbe {
+    fn name(&self) -> &'static str {
+        "clock_skew"
+    }
+
+    async fn run(&self, ctx: &ProbeContext<'_>) -> anyhow::Result<ProbeResult> {
+        let url = match ctx.config.get("doctor", "time_reference_url") {
+            Some(url) => url,
+            None => {
+                return Ok(ProbeResult::new(
+                    ProbeStatus::Skip,
+                    "No reference, set `doctor.time_reference_url` in buckconfig",
+                ));
+            }
+        };
+
+        let headers =
+            command_output(Command::new("curl").args(["--silent", "--show-error", "--head", url]))
+                .await?;
+        let now = SystemTime::now();
+        let reference =
+            response_date(&headers).context(DoctorError::NoDateHeader(url.to_owned()))?;
+        let skew = match now.duration_since(reference) {
+            Ok(skew) => skew,
+            Err(e) => e.duration(),
+        };
+        Ok(ProbeResult::new(
+            clock_skew_status(skew),
+            format!("Clock is {}s off from {}", skew.as_secs(), url),
+        ))
+    }
+}
+
+struct UlimitProbe;
+
+/// Builds open many files at once, the daemon needs more than the usual default of 1024.
+const MIN_OPEN_FILES: u64 = 10240;
+
+#[async_trait]
+impl Probe for UlimitProbe {
+    fn name(&self) -> &'static str {
+        "ulimit"
+    }
+
+    #[cfg(unix)]
+    async fn run(&self, _ctx: &ProbeContext<'_>) -> anyhow::Result<ProbeResult> {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `limit` is a valid `rlimit`.
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        #[allow(clippy::unnecessary_cast)] // The field types differ between platforms.
+        let soft = limit.rlim_cur as u64;
+        let status = if soft < MIN_OPEN_FILES {
+            ProbeStatus::Warn
+        } else {
+            ProbeStatus::Pass
+        };
+        Ok(ProbeResult::new(
+            status,
+            format!("Open files limit is {} (`ulimit -n`)", soft),
+        ))
+    }
+
+    #[cfg(not(unix))]
+    async fn run(&self, _ctx: &ProbeContext<'_>) -> anyhow::Result<ProbeResult> {
+        Ok(ProbeResult::new(
+            ProbeStatus::Skip,
+            "Not implemented on this platform",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overall_status() {
+        assert_eq!(ProbeStatus::Pass, overall_status([]));
+        assert_eq!(ProbeStatus::Pass, overall_status([ProbeStatus::Skip]));
+        assert_eq!(
+            ProbeStatus::Warn,
+            overall_status([ProbeStatus::Pass, ProbeStatus::Warn, ProbeStatus::Skip])
+        );
+        assert_eq!(
+            ProbeStatus::Fail,
+            overall_status([ProbeStatus::Fail, ProbeStatus::Warn])
+        );
+    }
+
+    #[test]
+    fn test_socket_address() {
+        assert_eq!("re.example.com:443", socket_address("re.example.com"));
+        assert_eq!(
+            "re.example.com:80",
+            socket_address("http://re.example.com/")
+        );
+        assert_eq!(
+            "re.example.com:8980",
+            socket_address("grpc://re.example.com:8980")
+        );
+        assert_eq!("[::1]:443", socket_address("https://[::1]"));
+        assert_eq!("[::1]:8980", socket_address("[::1]:8980"));
+    }
+
+    #[test]
+    fn test_response_date() {
+        let headers = "HTTP/1.1 200 OK\r\ndate: Tue, 15 Nov 1994 08:12:31 GMT\r\n\r\n";
+        assert_eq!(
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(784887151)),
+            response_date(headers)
+        );
+        assert_eq!(None, response_date("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn test_thresholds() {
+        assert_eq!(ProbeStatus::Fail, disk_space_status(GIB / 2));
+        assert_eq!(ProbeStatus::Warn, disk_space_status(5 * GIB));
+        assert_eq!(ProbeStatus::Pass, disk_space_status(50 * GIB));
+        assert_eq!(ProbeStatus::Pass, clock_skew_status(Duration::from_secs(1)));
+        assert_eq!(
+            ProbeStatus::Warn,
+            clock_skew_status(Duration::from_secs(10))
+        );
+        assert_eq!(
+            ProbeStatus::Fail,
+            clock_skew_status(Duration::from_secs(600))
+        );
+    }
+}
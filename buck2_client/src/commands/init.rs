@@ -29,6 +29,10 @@ use walkdir::DirEntry;
 /// This command is intented to be part-tutorial part-convenience
 /// for generating buck2 projects. Given a path and optional name
 /// (in the case that the folder name is not desirable).
+///
+/// With the prelude, the generated project uses the toolchains found
+/// on the `PATH` and has a hello world target in the language of the
+/// existing sources, or of the first toolchain found.
 #[derive(Debug, clap::Parser)]
 #[clap(name = "install", about = "Initialize a buck2 project")]
 pub struct InitCommand {
@@ -51,6 +55,11 @@ pub struct InitCommand {
     /// Initialize the project even if the git repo at \[PATH\] has uncommitted changes.
     #[clap(long)]
     allow_dirty: bool,
+
+    /// The language of the hello world target to generate. Defaults to the language
+    /// of the existing sources, or to the first language whose toolchain is found.
+    #[clap(long, arg_enum)]
+    lang: Option<Language>,
 }
 
 impl InitCommand {
@@ -86,6 +95,152 @@ enum FileType {
     Cxx,
 }
 
+/// The languages `buck2 init` can set up a toolchain and a hello world target for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+#[clap(rename_all = "snake_case")]
+enum Language {
+    Rust,
+    Cxx,
+    Python,
+}
+
+impl Language {
+    const ALL: [Language; 3] = [Language::Rust, Language::Cxx, Language::Python];
+
+    /// The executables providing the toolchain, in order of preference.
+    fn executables(self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &["rustc"],
+            Language::Cxx => &["clang++", "g++", "c++"],
+            Language::Python => &["python3", "python"],
+        }
+    }
+
+    fn file_type(self) -> FileType {
+        match self {
+            Language::Rust => FileType::Rust,
+            Language::Cxx => FileType::Cxx,
+            Language::Python => FileType::Python,
+        }
+    }
+
+    /// The source file and target of the hello world project.
+    fn hello_world(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Language::Rust => (
+                "main.rs",
+                "fn main() {\n    println!(\"Hello, world!\");\n}\n",
+                "rust_binary(\n    name = \"main\",\n    srcs = [\"main.rs\"],\n    crate_root = \"main.rs\",\n)\n\n",
+            ),
+            Language::Cxx => (
+                "main.cpp",
+                "#include <iostream>\n\nint main() {\n  std::cout << \"Hello, world!\" << std::endl;\n  return 0;\n}\n",
+                "cxx_binary(\n    name = \"main\",\n    srcs = [\"main.cpp\"],\n    link_style = \"static\",\n)\n\n",
+            ),
+            Language::Python => (
+                "main.py",
+                "print(\"Hello, world!\")\n",
+                "python_binary(\n    name = \"main\",\n    main = \"main.py\",\n)\n\n",
+            ),
+        }
+    }
+}
+
+/// Looks for the first of `names` on the `PATH`.
+fn find_executable(names: &[&str]) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    names.iter().find_map(|name| {
+        std::env::split_paths(&path).find_map(|dir| {
+            let candidate = dir
+                .join(name)
+                .with_extension(std::env::consts::EXE_EXTENSION);
+            if candidate.is_file() {
+                Some(candidate)
+            } else {
+                None
+            }
+        })
+    })
+}
+
+/// The language to generate a hello world target for: the requested one, otherwise the one
+/// with the most existing sources, otherwise the first one with a toolchain.
+fn choose_language(
+    requested: Option<Language>,
+    discovered_langs: &HashMap<FileType, Vec<PathBuf>>,
+    toolchains: &[Language],
+) -> Option<Language> {
+    requested
+        .or_else(|| {
+            Language::ALL
+                .into_iter()
+                .filter_map(|lang| Some((lang, discovered_langs.get(&lang.file_type())?.len())))
+                .max_by_key(|(lang, n)| (*n, std::cmp::Reverse(*lang as u8)))
+                .map(|(lang, _)| lang)
+        })
+        .or_else(|| toolchains.first().copied())
+}
+
+fn buckconfig(prelude: bool) -> String {
+    let mut buck_config = String::new();
+    buck_config.push_str("[repositories]\nroot = .\n");
+    if prelude {
+        buck_config.push_str(
+            "prelude = prelude\n\
+            toolchains = toolchains\n\
+            ovr_config = prelude\n\
+            fbcode = none\n\
+            fbcode_macros = none\n\
+            fbsource = none\n\
+            buck = none\n",
+        );
+    }
+    buck_config.push_str("\n[buildfile]\nname = BUCK2\n");
+    if prelude {
+        buck_config.push_str(
+            "\n[build]\n\
+            execution_platforms = prelude//platforms:default\n\
+            \n[parser]\n\
+            target_platform_detector_spec = target:root//...->prelude//platforms:default\n",
+        );
+    }
+    buck_config
+}
+
+/// The build file of the `toolchains` cell, with the system toolchains of `langs`, plus those
+/// other toolchains depend on.
+fn toolchains_build_file(langs: &[Language]) -> String {
+    let rust = langs.contains(&Language::Rust);
+    // Rust links with the C++ toolchain.
+    let cxx = rust || langs.contains(&Language::Cxx);
+    let python = langs.contains(&Language::Python);
+
+    let mut loads = Vec::new();
+    let mut rules = Vec::new();
+    if cxx {
+        loads.push("load(\"@prelude//toolchains:cxx.bzl\", \"system_cxx_toolchain\")");
+        rules.push("system_cxx_toolchain(\n    name = \"cxx\",\n    visibility = [\"PUBLIC\"],\n)");
+    }
+    if python {
+        loads.push("load(\"@prelude//toolchains:python.bzl\", \"system_python_bootstrap_toolchain\", \"system_python_toolchain\")");
+        rules.push(
+            "system_python_toolchain(\n    name = \"python\",\n    visibility = [\"PUBLIC\"],\n)",
+        );
+    } else {
+        loads.push(
+            "load(\"@prelude//toolchains:python.bzl\", \"system_python_bootstrap_toolchain\")",
+        );
+    }
+    // Used by tools of the prelude, whatever the language.
+    rules.push("system_python_bootstrap_toolchain(\n    name = \"python_bootstrap\",\n    visibility = [\"PUBLIC\"],\n)");
+    if rust {
+        loads.push("load(\"@prelude//toolchains:rust.bzl\", \"system_rust_toolchain\")");
+        rules.push("system_rust_toolchain(\n    name = \"rust\",\n    default_edition = \"2021\",\n    visibility = [\"PUBLIC\"],\n)");
+    }
+
+    format!("{}\n\n{}\n", loads.join("\n"), rules.join("\n\n"))
+}
+
 impl FileType {
     fn from_ext(ext: &str) -> Self {
         match ext {
@@ -160,7 +315,48 @@ fn exec_impl(
 
     let discovered_langs = discover_project(&absolute);
 
-    set_up_project(name, &absolute, !cmd.no_prelude, &discovered_langs)
+    let mut toolchains = Vec::new();
+    if !cmd.no_prelude {
+        for lang in Language::ALL {
+            match find_executable(lang.executables()) {
+                Some(exe) => {
+                    console.print_stderr(&format!("Found {:?} toolchain: {}", lang, exe.display()))?;
+                    toolchains.push(lang);
+                }
+                None if cmd.lang == Some(lang) => console.print_warning(&format!(
+                    "Warning: none of {} found on path, the {:?} toolchain won't work until one is installed.",
+                    lang.executables().join(", "),
+                    lang
+                ))?,
+                None => {}
+            }
+        }
+    }
+    let lang = choose_language(cmd.lang, &discovered_langs, &toolchains);
+    if let Some(lang) = lang {
+        if !toolchains.contains(&lang) {
+            toolchains.push(lang);
+        }
+    }
+
+    set_up_project(
+        name,
+        &absolute,
+        !cmd.no_prelude,
+        &discovered_langs,
+        lang,
+        &toolchains,
+    )?;
+
+    if let (false, Some(lang)) = (cmd.no_prelude, lang) {
+        if !discovered_langs.contains_key(&lang.file_type()) {
+            console.print_success(&format!(
+                "Initialized a {:?} project, try it with `buck2 run //:main`",
+                lang
+            ))?;
+        }
+    }
+    Ok(())
 }
 
 fn discover_project(path: &Path) -> HashMap<FileType, Vec<PathBuf>> {
@@ -212,6 +408,8 @@ fn set_up_project(
     path: &Path,
     prelude: bool,
     discovered_langs: &HashMap<FileType, Vec<PathBuf>>,
+    lang: Option<Language>,
+    toolchains_langs: &[Language],
 ) -> anyhow::Result<()> {
     if !Command::new("git")
         .arg("init")
@@ -222,15 +420,7 @@ fn set_up_project(
         return Err(anyhow::anyhow!("Failure when running `git init`."));
     };
 
-    let mut buck_config = {
-        let mut buck_config = std::fs::File::create(path.join(".buckconfig"))?;
-        writeln!(buck_config, "[buildfile]")?;
-        writeln!(buck_config, "name = BUCK2")?;
-        writeln!(buck_config)?;
-        writeln!(buck_config, "[repositories]")?;
-        writeln!(buck_config, "root = .")?;
-        buck_config
-    };
+    fs_util::write(path.join(".buckconfig"), buckconfig(prelude))?;
 
     let mut buck2 = std::fs::File::create(path.join("BUCK2"))?;
 
@@ -256,16 +446,27 @@ fn set_up_project(
             std::fs::create_dir(&toolchains)?;
         }
 
-        writeln!(buck_config, "prelude = prelude")?;
-        writeln!(buck_config, "toolchains = toolchains")?;
+        fs_util::write(
+            toolchains.join("BUCK2"),
+            toolchains_build_file(toolchains_langs),
+        )?;
 
         for (lang, files) in discovered_langs.iter() {
             match lang {
                 FileType::Cxx => write!(buck2, "{}", generate_cxx_rule(name, path, files))?,
                 FileType::Python => write!(buck2, "{}", generate_python_rule(name, path, files))?,
+                FileType::Rust => write!(buck2, "{}", generate_rust_rule(name, path, files))?,
                 _ => (),
             }
         }
+
+        if let Some(lang) = lang {
+            if !discovered_langs.contains_key(&lang.file_type()) {
+                let (file, source, rule) = lang.hello_world();
+                fs_util::write(path.join(file), source)?;
+                write!(buck2, "{}", rule)?;
+            }
+        }
     } else {
         writeln!(buck2, "# to get started without using the prelude")?;
         writeln!(
@@ -310,6 +511,36 @@ python_library(
     )
 }
 
+fn generate_rust_rule(name: &str, path: &Path, files: &[PathBuf]) -> String {
+    let relative = |f: &PathBuf| {
+        f.strip_prefix(path)
+            .expect("always a subpath")
+            .to_string_lossy()
+            .into_owned()
+    };
+    let srcs = files
+        .iter()
+        .map(|f| format!("\"{}\"", relative(f)))
+        .join(",\n        ");
+    let crate_root = files
+        .iter()
+        .find(|f| f.ends_with("main.rs"))
+        .or_else(|| files.first())
+        .map(relative)
+        .unwrap_or_default();
+    format!(
+        "# to learn about how to use this, please visit buck2.build/docs/rust
+rust_binary(
+    name = \"{}-rs\",
+    srcs = [
+        {}
+    ],
+    crate_root = \"{}\",
+)\n\n",
+        name, srcs, crate_root
+    )
+}
+
 fn generate_cxx_rule(name: &str, path: &Path, files: &[PathBuf]) -> String {
     let srcs = files
         .iter()
@@ -334,3 +565,41 @@ cxx_binary(
         name, srcs
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_language() {
+        let discovered = HashMap::from_iter([
+            (FileType::Python, vec![PathBuf::from("a.py")]),
+            (
+                FileType::Cxx,
+                vec![PathBuf::from("a.cpp"), PathBuf::from("a.h")],
+            ),
+        ]);
+        assert_eq!(
+            Some(Language::Rust),
+            choose_language(Some(Language::Rust), &discovered, &[])
+        );
+        assert_eq!(
+            Some(Language::Cxx),
+            choose_language(None, &discovered, &[Language::Python])
+        );
+        assert_eq!(
+            Some(Language::Python),
+            choose_language(None, &HashMap::new(), &[Language::Python])
+        );
+        assert_eq!(None, choose_language(None, &HashMap::new(), &[]));
+    }
+
+    #[test]
+    fn test_toolchains_build_file() {
+        let build_file = toolchains_build_file(&[Language::Rust]);
+        assert!(build_file.contains("system_rust_toolchain(\n    name = \"rust\""));
+        assert!(build_file.contains("system_cxx_toolchain(\n    name = \"cxx\""));
+        assert!(build_file.contains("name = \"python_bootstrap\""));
+        assert!(!build_file.contains("system_python_toolchain"));
+    }
+}
@@ -0,0 +1,173 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::path_arg::PathArg;
+use buck2_client_ctx::subscribers::event_log::file_names::get_local_logs;
+use buck2_client_ctx::subscribers::event_log::file_names::retrieve_nth_recent_log;
+use buck2_client_ctx::subscribers::event_log::EventLogPathBuf;
+use buck2_client_ctx::subscribers::event_log::Invocation;
+use tokio::runtime;
+
+/// Shows the invocations linked to a command by their trace IDs: the chain of invocations which
+/// started it, from the outermost, and the invocations it started.
+///
+/// Invocations are linked when buck2 is run with `--parent-trace-id`, or by an action run locally
+/// by another buck2 command. Only the event logs kept locally are searched.
+#[derive(Debug, clap::Parser)]
+#[clap(group = clap::ArgGroup::with_name("event_log"))]
+pub struct ChainCommand {
+    /// A path to an event-log file to read from. Only works for log files with a single command in them.
+    #[clap(group = "event_log", value_name = "PATH")]
+    path: Option<PathArg>,
+
+    /// Which recent command to read the event log from.
+    #[clap(
+        long,
+        help = "Use the Nth most recent command (`--recent 0` is the most recent).",
+        group = "event_log",
+        value_name = "NUMBER"
+    )]
+    recent: Option<usize>,
+}
+
+impl ChainCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext) -> ExitResult {
+        let Self { path, recent } = self;
+
+        let path = match path {
+            Some(path) => path.resolve(&ctx.working_dir),
+            None => retrieve_nth_recent_log(&ctx, recent.unwrap_or(0))?.into_abs_path_buf(),
+        };
+        let log_path = EventLogPathBuf::infer(path)?;
+
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let lines = rt.block_on(async move {
+            let (invocation, _events) = log_path.unpack_stream().await?;
+
+            // Logs which can't be read, e.g. because they are still being written, are skipped.
+            let mut others = Vec::new();
+            for path in get_local_logs(&ctx.paths.log_dir())? {
+                if let Ok(log) = EventLogPathBuf::infer(path.into_abs_path_buf()) {
+                    if let Ok((other, _events)) = log.unpack_stream().await {
+                        others.push(other);
+                    }
+                }
+            }
+
+            anyhow::Ok(chain(&invocation, &others))
+        })?;
+
+        for line in lines {
+            buck2_client_ctx::println!("{}", line)?;
+        }
+        ExitResult::success()
+    }
+}
+
+fn describe(invocation: &Invocation) -> String {
+    format!(
+        "{}  {}",
+        invocation.trace_id.as_deref().unwrap_or("<unknown>"),
+        invocation.command_line_args.join(" ")
+    )
+}
+
+/// The lines to print for `invocation`: its parents, outermost first, then itself, marked with
+/// `*`, then its children, each indented one more level than the invocation which started it.
+fn chain(invocation: &Invocation, others: &[Invocation]) -> Vec<String> {
+    let find = |trace_id: &str| {
+        others
+            .iter()
+            .find(|other| other.trace_id.as_deref() == Some(trace_id))
+    };
+
+    let mut parents: Vec<String> = Vec::new();
+    let mut parent_trace_id = invocation.parent_trace_id.as_deref();
+    while let Some(trace_id) = parent_trace_id {
+        // Guard against cycles from reused trace IDs.
+        if parents.len() > others.len() {
+            break;
+        }
+        match find(trace_id) {
+            Some(parent) => {
+                parents.push(describe(parent));
+                parent_trace_id = parent.parent_trace_id.as_deref();
+            }
+            None => {
+                parents.push(format!("{}  <no local event log>", trace_id));
+                break;
+            }
+        }
+    }
+    parents.reverse();
+
+    let depth = parents.len();
+    let mut lines: Vec<String> = parents
+        .into_iter()
+        .enumerate()
+        .map(|(i, line)| format!("{}{}", "  ".repeat(i), line))
+        .collect();
+    lines.push(format!("{}{} *", "  ".repeat(depth), describe(invocation)));
+
+    if let Some(trace_id) = invocation.trace_id.as_deref() {
+        for child in others
+            .iter()
+            .filter(|other| other.parent_trace_id.as_deref() == Some(trace_id))
+        {
+            lines.push(format!("{}{}", "  ".repeat(depth + 1), describe(child)));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invocation(command: &str, trace_id: &str, parent_trace_id: Option<&str>) -> Invocation {
+        Invocation {
+            command_line_args: vec!["buck2".to_owned(), command.to_owned()],
+            working_dir: "/repo".to_owned(),
+            trace_id: Some(trace_id.to_owned()),
+            parent_trace_id: parent_trace_id.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_chain() {
+        let root = invocation("build", "a", Some("ci"));
+        let nested = invocation("run", "b", Some("a"));
+        let child = invocation("targets", "c", Some("b"));
+        let unrelated = invocation("test", "d", None);
+        let others = vec![root, nested.clone(), child, unrelated];
+
+        assert_eq!(
+            vec![
+                "ci  <no local event log>",
+                "  a  buck2 build",
+                "    b  buck2 run *",
+                "      c  buck2 targets",
+            ],
+            chain(&nested, &others)
+        );
+    }
+
+    #[test]
+    fn test_chain_cycle() {
+        let a = invocation("build", "a", Some("b"));
+        let b = invocation("build", "b", Some("a"));
+        let others = vec![a.clone(), b];
+        assert!(chain(&a, &others).len() <= 5);
+    }
+}
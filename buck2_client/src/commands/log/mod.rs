@@ -7,11 +7,15 @@
  * of this source tree.
  */
 
+pub mod chain;
 pub mod last_log;
 pub mod show_log;
+pub mod size_report;
+pub mod stats;
 pub mod what_failed;
 pub mod what_ran;
 pub mod what_up;
+pub mod what_uploaded;
 
 use buck2_client_ctx::client_ctx::ClientCommandContext;
 use buck2_client_ctx::exit_result::ExitResult;
@@ -36,6 +40,19 @@ pub enum LogCommand {
     /// Show all the spans that where open when the log ended
     #[clap(alias = "whatup")]
     WhatUp(what_up::WhatUpCommand),
+
+    /// Shows statistics about the actions executed, by category
+    Stats(stats::StatsCommand),
+
+    /// Shows the size of the outputs built, by target, and how it changed since a previous log
+    SizeReport(size_report::SizeReportCommand),
+
+    /// Shows the bytes actions transferred to and from remote execution
+    #[clap(alias = "whatuploaded")]
+    WhatUploaded(what_uploaded::WhatUploadedCommand),
+
+    /// Shows the invocations which started a command, and those it started
+    Chain(chain::ChainCommand),
 }
 
 impl LogCommand {
@@ -46,6 +63,50 @@ impl LogCommand {
             Self::Last(cmd) => cmd.exec(matches, ctx),
             Self::Show(cmd) => cmd.exec(matches, ctx),
             Self::WhatUp(cmd) => cmd.exec(matches, ctx),
+            Self::Stats(cmd) => cmd.exec(matches, ctx),
+            Self::SizeReport(cmd) => cmd.exec(matches, ctx),
+            Self::WhatUploaded(cmd) => cmd.exec(matches, ctx),
+            Self::Chain(cmd) => cmd.exec(matches, ctx),
+        }
+    }
+}
+
+/// The end of an action execution, if that's what this event is.
+fn action_execution_end(event: &buck2_data::BuckEvent) -> Option<&buck2_data::ActionExecutionEnd> {
+    match event.data.as_ref()? {
+        buck2_data::buck_event::Data::SpanEnd(span) => match span.data.as_ref()? {
+            buck2_data::span_end_event::Data::ActionExecution(action) => Some(action),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    /// The end of the execution of an action of `root//pkg:<target>`.
+    pub(crate) fn action_end(target: &str, category: &str) -> buck2_data::ActionExecutionEnd {
+        buck2_data::ActionExecutionEnd {
+            key: Some(buck2_data::ActionKey {
+                owner: Some(buck2_data::action_key::Owner::TargetLabel(
+                    buck2_data::ConfiguredTargetLabel {
+                        label: Some(buck2_data::TargetLabel {
+                            package: "root//pkg".to_owned(),
+                            name: target.to_owned(),
+                        }),
+                        configuration: Some(buck2_data::Configuration {
+                            full_name: "cfg".to_owned(),
+                        }),
+                        execution_configuration: None,
+                    },
+                )),
+                ..Default::default()
+            }),
+            name: Some(buck2_data::ActionName {
+                category: category.to_owned(),
+                identifier: "".to_owned(),
+            }),
+            ..Default::default()
         }
     }
 }
@@ -0,0 +1,330 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::path_arg::PathArg;
+use buck2_client_ctx::stream_value::StreamValue;
+use buck2_client_ctx::subscribers::display::display_configured_target_label;
+use buck2_client_ctx::subscribers::display::TargetDisplayOptions;
+use buck2_client_ctx::subscribers::event_log::file_names::retrieve_nth_recent_log;
+use buck2_client_ctx::subscribers::event_log::EventLogPathBuf;
+use buck2_core::fs::paths::abs_path::AbsPathBuf;
+use futures::TryStreamExt;
+use gazebo::dupe::Dupe;
+use tokio::runtime;
+
+#[derive(
+    Debug,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Dupe,
+    clap::ArgEnum
+)]
+#[clap(rename_all = "snake_case")]
+pub enum SizeReportSubcommandOutput {
+    Tabulated,
+    Json,
+}
+
+/// Shows the size of the outputs built by a command, by target, optionally compared to the sizes
+/// in a previous log.
+///
+/// Rules producing outputs whose size matters (binaries, APKs, ...) declare them with an
+/// `OutputSizeInfo` provider. The size of a target is the total size of these outputs, recorded
+/// every time the target is built, even if nothing had to be rebuilt, so the sizes of warm or
+/// incremental builds can be compared too.
+///
+/// With `--max-growth`, the command fails if a target grew by more bytes than that compared to
+/// the baseline, so CI can catch size regressions.
+#[derive(Debug, clap::Parser)]
+#[clap(group = clap::ArgGroup::with_name("event_log"))]
+pub struct SizeReportCommand {
+    /// A path to an event-log file to read from. Only works for log files with a single command in them.
+    #[clap(group = "event_log", value_name = "PATH")]
+    path: Option<PathArg>,
+
+    /// Which recent command to read the event log from.
+    #[clap(
+        long,
+        help = "Use the Nth most recent command (`--recent 0` is the most recent).",
+        group = "event_log",
+        value_name = "NUMBER"
+    )]
+    recent: Option<usize>,
+
+    /// An event-log file of a previous command to compare sizes against.
+    #[clap(long, value_name = "PATH")]
+    baseline: Option<PathArg>,
+
+    /// Fail if a target grew by more than this many bytes compared to the baseline.
+    #[clap(long, value_name = "BYTES", requires = "baseline")]
+    max_growth: Option<u64>,
+
+    #[clap(
+        long = "--format",
+        help = "Which output format to use for this command",
+        default_value = "tabulated",
+        ignore_case = true,
+        arg_enum
+    )]
+    output: SizeReportSubcommandOutput,
+}
+
+#[derive(serde::Serialize)]
+struct JsonTargetSize<'a> {
+    target: &'a str,
+    size: Option<u64>,
+    baseline_size: Option<u64>,
+    delta: i64,
+}
+
+/// A row of the report. Targets only present in one of the logs have no size in the other.
+#[derive(Debug, PartialEq, Eq)]
+struct TargetSize {
+    target: String,
+    size: Option<u64>,
+    baseline_size: Option<u64>,
+}
+
+impl TargetSize {
+    fn delta(&self) -> i64 {
+        self.size.unwrap_or(0) as i64 - self.baseline_size.unwrap_or(0) as i64
+    }
+}
+
+impl SizeReportCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext) -> ExitResult {
+        let Self {
+            path,
+            recent,
+            baseline,
+            max_growth,
+            output,
+        } = self;
+
+        let log = match path {
+            Some(path) => path.resolve(&ctx.working_dir),
+            None => retrieve_nth_recent_log(&ctx, recent.unwrap_or(0))?.into_abs_path_buf(),
+        };
+        let baseline = baseline.map(|path| path.resolve(&ctx.working_dir));
+
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let (sizes, baseline_sizes) = rt.block_on(async move {
+            let sizes = read_output_sizes(log).await?;
+            let baseline_sizes = match baseline {
+                Some(baseline) => Some(read_output_sizes(baseline).await?),
+                None => None,
+            };
+            anyhow::Ok((sizes, baseline_sizes))
+        })?;
+
+        let has_baseline = baseline_sizes.is_some();
+        let report = size_report(sizes, baseline_sizes.unwrap_or_default());
+
+        match output {
+            SizeReportSubcommandOutput::Tabulated => {
+                if has_baseline {
+                    buck2_client_ctx::println!("target\tsize\tbaseline\tdelta")?;
+                } else {
+                    buck2_client_ctx::println!("target\tsize")?;
+                }
+                for row in &report {
+                    let size = format_size(row.size);
+                    if has_baseline {
+                        buck2_client_ctx::println!(
+                            "{}\t{}\t{}\t{:+}",
+                            row.target,
+                            size,
+                            format_size(row.baseline_size),
+                            row.delta()
+                        )?;
+                    } else {
+                        buck2_client_ctx::println!("{}\t{}", row.target, size)?;
+                    }
+                }
+            }
+            SizeReportSubcommandOutput::Json => {
+                for row in &report {
+                    let json = JsonTargetSize {
+                        target: &row.target,
+                        size: row.size,
+                        baseline_size: row.baseline_size,
+                        delta: row.delta(),
+                    };
+                    buck2_client_ctx::println!("{}", serde_json::to_string(&json)?)?;
+                }
+            }
+        }
+
+        if let Some(max_growth) = max_growth {
+            let regressions: Vec<_> = report
+                .iter()
+                .filter(|row| row.delta() > max_growth as i64)
+                .collect();
+            if !regressions.is_empty() {
+                for row in &regressions {
+                    buck2_client_ctx::eprintln!(
+                        "{} grew by {} bytes, more than the allowed {}",
+                        row.target,
+                        row.delta(),
+                        max_growth
+                    )?;
+                }
+                return ExitResult::failure();
+            }
+        }
+
+        ExitResult::success()
+    }
+}
+
+async fn read_output_sizes(log: AbsPathBuf) -> anyhow::Result<BTreeMap<String, u64>> {
+    let log_path = EventLogPathBuf::infer(log)?;
+    let (invocation, mut events) = log_path.unpack_stream().await?;
+
+    buck2_client_ctx::eprintln!(
+        "Showing output sizes from: {}",
+        shlex::join(invocation.command_line_args.iter().map(|e| e.as_str()))
+    )?;
+
+    let mut sizes = BTreeMap::new();
+    while let Some(event) = events.try_next().await? {
+        if let StreamValue::Event(event) = event {
+            if let Some((target, size)) = target_output_size(&event)? {
+                // A target built several times by the command has the same size every time.
+                sizes.insert(target, size);
+            }
+        }
+    }
+    Ok(sizes)
+}
+
+/// The target and size of a `TargetOutputSize` event.
+fn target_output_size(event: &buck2_data::BuckEvent) -> anyhow::Result<Option<(String, u64)>> {
+    let output_size = match &event.data {
+        Some(buck2_data::buck_event::Data::Instant(instant)) => match &instant.data {
+            Some(buck2_data::instant_event::Data::TargetOutputSize(output_size)) => output_size,
+            _ => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let target = match &output_size.target {
+        Some(target) => target,
+        None => return Ok(None),
+    };
+    let target = display_configured_target_label(target, TargetDisplayOptions::for_console())?;
+    Ok(Some((target, output_size.size)))
+}
+
+/// Rows for all targets of both logs, biggest growth first.
+fn size_report(sizes: BTreeMap<String, u64>, baseline: BTreeMap<String, u64>) -> Vec<TargetSize> {
+    let mut report: BTreeMap<String, TargetSize> = BTreeMap::new();
+    for (target, size) in sizes {
+        report.insert(
+            target.clone(),
+            TargetSize {
+                target,
+                size: Some(size),
+                baseline_size: None,
+            },
+        );
+    }
+    for (target, size) in baseline {
+        report
+            .entry(target.clone())
+            .or_insert_with(|| TargetSize {
+                target,
+                size: None,
+                baseline_size: None,
+            })
+            .baseline_size = Some(size);
+    }
+
+    let mut report: Vec<_> = report.into_values().collect();
+    // The sort is stable, so targets with the same delta stay sorted by name.
+    report.sort_by_key(|row| Reverse(row.delta()));
+    report
+}
+
+fn format_size(size: Option<u64>) -> String {
+    match size {
+        Some(size) => size.to_string(),
+        None => "-".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target_output_size_event(size: u64) -> buck2_data::BuckEvent {
+        buck2_data::BuckEvent {
+            data: Some(buck2_data::buck_event::Data::Instant(
+                buck2_data::InstantEvent {
+                    data: Some(buck2_data::instant_event::Data::TargetOutputSize(
+                        buck2_data::TargetOutputSize {
+                            target: Some(buck2_data::ConfiguredTargetLabel {
+                                label: Some(buck2_data::TargetLabel {
+                                    package: "root//app".to_owned(),
+                                    name: "bin".to_owned(),
+                                }),
+                                configuration: Some(buck2_data::Configuration {
+                                    full_name: "cfg".to_owned(),
+                                }),
+                                execution_configuration: None,
+                            }),
+                            size,
+                        },
+                    )),
+                },
+            )),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_target_output_size() -> anyhow::Result<()> {
+        assert_eq!(
+            Some(("root//app:bin".to_owned(), 10)),
+            target_output_size(&target_output_size_event(10))?
+        );
+        assert_eq!(None, target_output_size(&buck2_data::BuckEvent::default())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_size_report() {
+        let sizes = BTreeMap::from([
+            ("a".to_owned(), 100),
+            ("b".to_owned(), 50),
+            ("new".to_owned(), 10),
+        ]);
+        let baseline = BTreeMap::from([
+            ("a".to_owned(), 100),
+            ("b".to_owned(), 20),
+            ("gone".to_owned(), 5),
+        ]);
+        let report = size_report(sizes, baseline);
+        assert_eq!(
+            vec![("b", 30), ("new", 10), ("a", 0), ("gone", -5)],
+            report
+                .iter()
+                .map(|row| (row.target.as_str(), row.delta()))
+                .collect::<Vec<_>>()
+        );
+    }
+}
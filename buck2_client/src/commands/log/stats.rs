@@ -0,0 +1,246 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::time::Duration;
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::path_arg::PathArg;
+use buck2_client_ctx::stream_value::StreamValue;
+use buck2_client_ctx::subscribers::event_log::file_names::retrieve_nth_recent_log;
+use buck2_client_ctx::subscribers::event_log::EventLogPathBuf;
+use futures::Stream;
+use futures::TryStreamExt;
+use gazebo::dupe::Dupe;
+use tokio::runtime;
+
+#[derive(
+    Debug,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Dupe,
+    clap::ArgEnum
+)]
+#[clap(rename_all = "snake_case")]
+pub enum StatsSubcommandOutput {
+    Tabulated,
+    Json,
+}
+
+/// Shows statistics about the actions executed by a command, by action category.
+///
+/// The output is one row per category, with the number of actions that executed successfully,
+/// how many of them were served by the action cache, how many command attempts had to be
+/// retried, percentiles of their wall time, and how many output bytes were downloaded from and
+/// uploaded to remote execution.
+///
+/// If the command did not finish, the statistics are those of the last snapshot the daemon sent.
+#[derive(Debug, clap::Parser)]
+#[clap(group = clap::ArgGroup::with_name("event_log"))]
+pub struct StatsCommand {
+    /// A path to an event-log file to read from. Only works for log files with a single command in them.
+    #[clap(group = "event_log", value_name = "PATH")]
+    path: Option<PathArg>,
+
+    /// Which recent command to read the event log from.
+    #[clap(
+        long,
+        help = "Use the Nth most recent command (`--recent 0` is the most recent).",
+        group = "event_log",
+        value_name = "NUMBER"
+    )]
+    recent: Option<usize>,
+
+    #[clap(
+        long = "--format",
+        help = "Which output format to use for this command",
+        default_value = "tabulated",
+        ignore_case = true,
+        arg_enum
+    )]
+    output: StatsSubcommandOutput,
+}
+
+#[derive(serde::Serialize)]
+struct JsonCategoryStats<'a> {
+    category: &'a str,
+    action_count: u64,
+    cache_hits: u64,
+    retries: u64,
+    total_wall_time_ms: u128,
+    p50_wall_time_ms: u128,
+    p90_wall_time_ms: u128,
+    p99_wall_time_ms: u128,
+    max_wall_time_ms: u128,
+    total_output_size: u64,
+    remote_output_size: u64,
+    bytes_uploaded: u64,
+}
+
+impl StatsCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext) -> ExitResult {
+        let Self {
+            path,
+            recent,
+            output,
+        } = self;
+
+        let log = match path {
+            Some(path) => path.resolve(&ctx.working_dir),
+            None => retrieve_nth_recent_log(&ctx, recent.unwrap_or(0))?.into_abs_path_buf(),
+        };
+
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let stats = rt.block_on(async move {
+            let log_path = EventLogPathBuf::infer(log)?;
+            // Only the latest statistics matter, so skip to the last snapshot if the log allows.
+            let (invocation, events) = log_path.unpack_stream_from_last_snapshot().await?;
+
+            buck2_client_ctx::eprintln!(
+                "Showing action statistics from: {}",
+                shlex::join(invocation.command_line_args.iter().map(|e| e.as_str()))
+            )?;
+
+            match latest_category_stats(events).await? {
+                Some(stats) => anyhow::Ok(Some(stats)),
+                None => {
+                    // The last chunk may start with a snapshot without statistics.
+                    let (_invocation, events) = log_path.unpack_stream().await?;
+                    latest_category_stats(events).await
+                }
+            }
+        })?;
+
+        let stats = match stats {
+            Some(stats) => stats,
+            None => {
+                buck2_client_ctx::eprintln!("No action statistics in this log")?;
+                return ExitResult::success();
+            }
+        };
+
+        match output {
+            StatsSubcommandOutput::Tabulated => {
+                buck2_client_ctx::println!(
+                    "category\tactions\tcache_hit_rate\tretries\ttotal\tp50\tp90\tp99\tmax\tremote_output\tuploaded"
+                )?;
+                for s in &stats {
+                    buck2_client_ctx::println!(
+                        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                        s.category,
+                        s.action_count,
+                        cache_hit_rate(s),
+                        s.retries,
+                        format_duration(s.total_wall_time.as_ref()),
+                        format_duration(s.p50_wall_time.as_ref()),
+                        format_duration(s.p90_wall_time.as_ref()),
+                        format_duration(s.p99_wall_time.as_ref()),
+                        format_duration(s.max_wall_time.as_ref()),
+                        s.remote_output_size,
+                        s.bytes_uploaded,
+                    )?;
+                }
+            }
+            StatsSubcommandOutput::Json => {
+                for s in &stats {
+                    let json = JsonCategoryStats {
+                        category: &s.category,
+                        action_count: s.action_count,
+                        cache_hits: s.cache_hits,
+                        retries: s.retries,
+                        total_wall_time_ms: to_duration(s.total_wall_time.as_ref()).as_millis(),
+                        p50_wall_time_ms: to_duration(s.p50_wall_time.as_ref()).as_millis(),
+                        p90_wall_time_ms: to_duration(s.p90_wall_time.as_ref()).as_millis(),
+                        p99_wall_time_ms: to_duration(s.p99_wall_time.as_ref()).as_millis(),
+                        max_wall_time_ms: to_duration(s.max_wall_time.as_ref()).as_millis(),
+                        total_output_size: s.total_output_size,
+                        remote_output_size: s.remote_output_size,
+                        bytes_uploaded: s.bytes_uploaded,
+                    };
+                    buck2_client_ctx::println!("{}", serde_json::to_string(&json)?)?;
+                }
+            }
+        }
+
+        ExitResult::success()
+    }
+}
+
+async fn latest_category_stats(
+    events: impl Stream<Item = anyhow::Result<StreamValue>>,
+) -> anyhow::Result<Option<Vec<buck2_data::ActionCategoryStats>>> {
+    futures::pin_mut!(events);
+    let mut stats = None;
+    while let Some(event) = events.try_next().await? {
+        if let StreamValue::Event(event) = event {
+            if let Some(latest) = category_stats(event) {
+                stats = Some(latest);
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// The statistics carried by `event`, if any. The final `BuildGraphExecutionInfo` comes after
+/// the last snapshot, so keeping the latest one seen gives the most complete numbers.
+fn category_stats(event: buck2_data::BuckEvent) -> Option<Vec<buck2_data::ActionCategoryStats>> {
+    match event.data? {
+        buck2_data::buck_event::Data::Instant(instant) => match instant.data? {
+            buck2_data::instant_event::Data::BuildGraphInfo(info) => Some(info.category_stats),
+            buck2_data::instant_event::Data::ActionCategoryStatsSnapshot(snapshot) => {
+                Some(snapshot.category_stats)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn cache_hit_rate(stats: &buck2_data::ActionCategoryStats) -> String {
+    if stats.action_count == 0 {
+        return "-".to_owned();
+    }
+    format!(
+        "{:.1}%",
+        stats.cache_hits as f64 * 100.0 / stats.action_count as f64
+    )
+}
+
+fn to_duration(duration: Option<&prost_types::Duration>) -> Duration {
+    match duration {
+        Some(d) => Duration::new(d.seconds as u64, d.nanos as u32),
+        None => Duration::ZERO,
+    }
+}
+
+fn format_duration(duration: Option<&prost_types::Duration>) -> String {
+    format!("{:.3}s", to_duration(duration).as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_hit_rate() {
+        let mut stats = buck2_data::ActionCategoryStats {
+            category: "cxx_compile".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(cache_hit_rate(&stats), "-");
+
+        stats.action_count = 8;
+        stats.cache_hits = 3;
+        assert_eq!(cache_hit_rate(&stats), "37.5%");
+    }
+}
@@ -7,28 +7,565 @@
  * of this source tree.
  */
 
+use std::collections::HashMap;
+
 use buck2_client_ctx::client_ctx::ClientCommandContext;
 use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::stream_value::StreamValue;
+use buck2_client_ctx::subscribers::display;
+use buck2_client_ctx::subscribers::display::TargetDisplayOptions;
+use buck2_client_ctx::subscribers::event_log::file_names::retrieve_nth_recent_log;
+use buck2_client_ctx::subscribers::event_log::EventLogPathBuf;
+use buck2_data::action_execution_end::Error as ActionError;
+use buck2_data::command_execution::Status;
+use futures::TryStreamExt;
+use indexmap::IndexMap;
+use termwiz::escape::Action;
+use termwiz::escape::ControlCode;
+use tokio::runtime;
 
+use crate::commands::log::action_execution_end;
 use crate::commands::log::what_ran::WhatRanCommand;
 use crate::commands::log::what_ran::WhatRanCommandCommon;
+use crate::commands::log::what_ran::WhatRanSubcommandOutput;
 
 /// This command outputs every command that failed in the last invocation of Buck2. Other
 /// invocations can be targeted using the flags.
 ///
 /// Look at the help for what-ran to understand the output format.
+///
+/// With `--summary`, failed actions and tests are instead grouped by their error, and the first
+/// failure which is not the consequence of another one (i.e. not an action that was cancelled) is
+/// shown first, along with an excerpt of its stderr.
 #[derive(Debug, clap::Parser)]
 pub struct WhatFailedCommand {
     #[clap(flatten)]
     pub common: WhatRanCommandCommon,
+
+    /// Summarize the failures grouped by error, starting with the first one, instead of listing
+    /// the commands that failed.
+    #[clap(long)]
+    pub summary: bool,
+
+    /// How many lines of stderr to show for each group of failures.
+    #[clap(
+        long,
+        default_value = "20",
+        value_name = "NUMBER",
+        requires = "summary"
+    )]
+    pub stderr_lines: usize,
 }
 
 impl WhatFailedCommand {
     pub fn exec(self, matches: &clap::ArgMatches, ctx: ClientCommandContext) -> ExitResult {
-        WhatRanCommand {
-            common: self.common,
+        if !self.summary {
+            return WhatRanCommand {
+                common: self.common,
+                failed: true,
+            }
+            .exec(matches, ctx);
+        }
+
+        let Self {
+            common:
+                WhatRanCommandCommon {
+                    path,
+                    recent,
+                    output,
+                    options: _,
+                },
+            summary: _,
+            stderr_lines,
+        } = self;
+
+        let log = match path {
+            Some(path) => path.resolve(&ctx.working_dir),
+            None => retrieve_nth_recent_log(&ctx, recent.unwrap_or(0))?.into_abs_path_buf(),
+        };
+
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let summary = rt.block_on(async move {
+            let log_path = EventLogPathBuf::infer(log)?;
+            let (invocation, mut events) = log_path.unpack_stream().await?;
+
+            buck2_client_ctx::eprintln!(
+                "Showing failures from: {}",
+                shlex::join(invocation.command_line_args.iter().map(|e| e.as_str()))
+            )?;
+
+            let mut failures = Failures::default();
+            while let Some(event) = events.try_next().await? {
+                if let StreamValue::Event(event) = event {
+                    failures.add(&event)?;
+                }
+            }
+
+            anyhow::Ok(Summary::new(failures.failures, stderr_lines))
+        })?;
+
+        match output {
+            WhatRanSubcommandOutput::Tabulated => summary.print()?,
+            WhatRanSubcommandOutput::Json => {
+                buck2_client_ctx::println!("{}", serde_json::to_string(&summary)?)?
+            }
+        }
+
+        ExitResult::success()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum FailureKind {
+    Action,
+    Test,
+}
+
+/// A failed action or test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Failure {
+    kind: FailureKind,
+    /// The category of the action, or the status of the test.
+    category: String,
+    identity: String,
+    /// What identifies this failure among the others: failures with the same category and
+    /// signature are grouped together.
+    signature: String,
+    stderr: String,
+    /// Whether this failure is the consequence of another failure, rather than a root cause.
+    cancelled: bool,
+}
+
+/// The failures of an event log, in the order they happened.
+#[derive(Default)]
+struct Failures {
+    /// The actions that are running, by span id, in case they get cancelled.
+    running_actions: HashMap<u64, buck2_data::ActionExecutionStart>,
+    failures: Vec<Failure>,
+}
+
+impl Failures {
+    fn add(&mut self, event: &buck2_data::BuckEvent) -> anyhow::Result<()> {
+        match &event.data {
+            Some(buck2_data::buck_event::Data::SpanStart(span)) => {
+                if let Some(buck2_data::span_start_event::Data::ActionExecution(action)) =
+                    &span.data
+                {
+                    self.running_actions.insert(event.span_id, action.clone());
+                }
+            }
+            Some(buck2_data::buck_event::Data::SpanEnd(span)) => {
+                let start = self.running_actions.remove(&event.span_id);
+                match (&span.data, action_execution_end(event), start) {
+                    (_, Some(action), _) if action.failed => {
+                        self.failures.push(Failure::from_action(action)?)
+                    }
+                    // The action was dropped before it finished, e.g. because the build failed
+                    // or was interrupted.
+                    (Some(buck2_data::span_end_event::Data::SpanCancelled(..)), _, Some(start)) => {
+                        self.failures.push(Failure::from_cancelled_action(&start)?)
+                    }
+                    _ => {}
+                }
+            }
+            Some(buck2_data::buck_event::Data::Instant(instant)) => {
+                if let Some(buck2_data::instant_event::Data::TestResult(test)) = &instant.data {
+                    self.failures.extend(Failure::from_test(test));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Failure {
+    fn from_action(action: &buck2_data::ActionExecutionEnd) -> anyhow::Result<Self> {
+        let (category, identity) = action_identity(action.key.as_ref(), action.name.as_ref())?;
+
+        // The command shown to the user is always the last one.
+        let command = action.commands.last();
+        let stderr = command
+            .and_then(|c| c.details.as_ref())
+            .map_or_else(String::new, |d| sanitize_output_colors(d.stderr.as_bytes()));
+        let error = match &action.error {
+            Some(ActionError::Unknown(error)) => Some(error.as_str()),
+            Some(ActionError::MissingOutputs(missing)) => Some(missing.message.as_str()),
+            _ => None,
+        };
+        let error = match command.and_then(|c| c.status.as_ref()) {
+            Some(Status::Error(e)) => Some(e.error.as_str()),
+            _ => error,
+        };
+
+        // Hybrid execution cancels the local command when the remote one wins the race (or the
+        // other way around), which is never the root cause of a failure.
+        let cancelled = matches!(
+            command.and_then(|c| c.status.as_ref()),
+            Some(Status::ClaimCancelled(..))
+        );
+
+        let exit_code = command
+            .and_then(|c| c.details.as_ref())
+            .and_then(|d| d.exit_code);
+        let signature = signature(&stderr, error, exit_code);
+
+        Ok(Self {
+            kind: FailureKind::Action,
+            category,
+            identity,
+            signature,
+            stderr,
+            cancelled,
+        })
+    }
+
+    fn from_cancelled_action(action: &buck2_data::ActionExecutionStart) -> anyhow::Result<Self> {
+        let (category, identity) = action_identity(action.key.as_ref(), action.name.as_ref())?;
+        Ok(Self {
+            kind: FailureKind::Action,
+            category,
+            identity,
+            signature: "<cancelled>".to_owned(),
+            stderr: String::new(),
+            cancelled: true,
+        })
+    }
+
+    fn from_test(test: &buck2_data::TestResult) -> Option<Self> {
+        let status = match buck2_data::TestStatus::from_i32(test.status) {
+            Some(
+                status @ (buck2_data::TestStatus::Fail
+                | buck2_data::TestStatus::Fatal
+                | buck2_data::TestStatus::Timeout),
+            ) => status,
+            _ => return None,
+        };
+        let msg = test.msg.as_ref().map(|m| m.msg.as_str());
+        let details = sanitize_output_colors(test.details.as_bytes());
+        Some(Self {
+            kind: FailureKind::Test,
+            category: format!("{:?}", status).to_uppercase(),
+            identity: test.name.clone(),
+            signature: signature(&details, msg, None),
+            stderr: details,
+            cancelled: false,
+        })
+    }
+}
+
+/// The category and the identity of an action.
+fn action_identity(
+    key: Option<&buck2_data::ActionKey>,
+    name: Option<&buck2_data::ActionName>,
+) -> anyhow::Result<(String, String)> {
+    let identity =
+        display::display_action_identity(key, name, TargetDisplayOptions::for_console())?;
+    let category = name.map_or_else(|| "<unknown>".to_owned(), |name| name.category.clone());
+    Ok((category, identity))
+}
+
+/// The line which best describes a failure: the first line of its stderr mentioning an error,
+/// else the first line of its stderr, else the first line of its error message, else its exit
+/// code.
+fn signature(stderr: &str, error: Option<&str>, exit_code: Option<u32>) -> String {
+    let first_line = |s: &str| {
+        s.lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .map(str::to_owned)
+    };
+    stderr
+        .lines()
+        .map(str::trim)
+        .find(|l| l.to_lowercase().contains("error"))
+        .map(str::to_owned)
+        .or_else(|| first_line(stderr))
+        .or_else(|| error.and_then(first_line))
+        .unwrap_or_else(|| match exit_code {
+            Some(code) => format!("exit code {}", code),
+            None => "<no error message>".to_owned(),
+        })
+}
+
+fn sanitize_output_colors(output: &[u8]) -> String {
+    let mut sanitized = String::with_capacity(output.len());
+    let mut parser = termwiz::escape::parser::Parser::new();
+    parser.parse(output, |a| match a {
+        Action::Print(c) => sanitized.push(c),
+        Action::Control(ControlCode::LineFeed) => sanitized.push('\n'),
+        Action::Control(ControlCode::HorizontalTab) => sanitized.push('\t'),
+        _ => {}
+    });
+    sanitized
+}
+
+/// The last `n` lines of `output`.
+fn excerpt(output: &str, n: usize) -> Vec<String> {
+    let lines: Vec<&str> = output.lines().collect();
+    lines[lines.len().saturating_sub(n)..]
+        .iter()
+        .map(|l| (*l).to_owned())
+        .collect()
+}
+
+/// Failures with the same category and signature.
+#[derive(Debug, serde::Serialize)]
+struct FailureGroup {
+    kind: FailureKind,
+    category: String,
+    signature: String,
+    /// The identities of the failures, in the order they failed.
+    identities: Vec<String>,
+    /// An excerpt of the stderr of the first failure in this group.
+    stderr: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Summary {
+    /// The first failure which isn't the consequence of another one, if any.
+    first_failure: Option<String>,
+    /// The groups of failures, starting with the one containing the first failure.
+    groups: Vec<FailureGroup>,
+    /// How many failed actions were ignored because they were cancelled.
+    cancelled: usize,
+}
+
+impl Summary {
+    fn new(failures: Vec<Failure>, stderr_lines: usize) -> Self {
+        let (cancelled, failures): (Vec<_>, Vec<_>) =
+            failures.into_iter().partition(|f| f.cancelled);
+
+        let mut groups: IndexMap<(FailureKind, String, String), FailureGroup> = IndexMap::new();
+        for failure in failures {
+            let key = (
+                failure.kind,
+                failure.category.clone(),
+                failure.signature.clone(),
+            );
+            groups
+                .entry(key)
+                .or_insert_with(|| FailureGroup {
+                    kind: failure.kind,
+                    category: failure.category,
+                    signature: failure.signature,
+                    identities: Vec::new(),
+                    stderr: excerpt(&failure.stderr, stderr_lines),
+                })
+                .identities
+                .push(failure.identity);
+        }
+
+        // Groups are in the order of their first failure, so the first one holds the root cause.
+        let groups: Vec<FailureGroup> = groups.into_values().collect();
+        Self {
+            first_failure: groups.first().map(|g| g.identities[0].clone()),
+            groups,
+            cancelled: cancelled.len(),
+        }
+    }
+
+    fn print(&self) -> anyhow::Result<()> {
+        match &self.first_failure {
+            Some(first) => buck2_client_ctx::println!("First failure: {}", first)?,
+            None => buck2_client_ctx::println!("No failures found")?,
+        }
+        for group in &self.groups {
+            buck2_client_ctx::println!()?;
+            buck2_client_ctx::println!(
+                "{} {} ({} failed): {}",
+                match group.kind {
+                    FailureKind::Action => "Action",
+                    FailureKind::Test => "Test",
+                },
+                group.category,
+                group.identities.len(),
+                group.signature
+            )?;
+            for identity in &group.identities {
+                buck2_client_ctx::println!("  {}", identity)?;
+            }
+            for line in &group.stderr {
+                buck2_client_ctx::println!("    | {}", line)?;
+            }
+        }
+        if self.cancelled > 0 {
+            buck2_client_ctx::println!()?;
+            buck2_client_ctx::println!("{} cancelled action(s) not shown", self.cancelled)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::log::testing::action_end;
+
+    fn failure(identity: &str, signature: &str, cancelled: bool) -> Failure {
+        Failure {
+            kind: FailureKind::Action,
+            category: "cxx_compile".to_owned(),
+            identity: identity.to_owned(),
+            signature: signature.to_owned(),
+            stderr: format!("line 1\nline 2\n{}", signature),
+            cancelled,
+        }
+    }
+
+    fn failed_action(status: Status) -> buck2_data::ActionExecutionEnd {
+        buck2_data::ActionExecutionEnd {
             failed: true,
+            commands: vec![buck2_data::CommandExecution {
+                details: None,
+                status: Some(status),
+            }],
+            ..action_end("bin", "cxx_compile")
         }
-        .exec(matches, ctx)
+    }
+
+    #[test]
+    fn test_cancelled() -> anyhow::Result<()> {
+        let claim_cancelled =
+            Failure::from_action(&failed_action(Status::ClaimCancelled(Default::default())))?;
+        assert!(claim_cancelled.cancelled);
+
+        // Only the status of the command tells whether it was cancelled, not its error message.
+        let error = Failure::from_action(&failed_action(Status::Error(
+            buck2_data::command_execution::Error {
+                stage: "execute".to_owned(),
+                error: "Request was cancelled by the user".to_owned(),
+            },
+        )))?;
+        assert!(!error.cancelled);
+        assert_eq!("Request was cancelled by the user", error.signature);
+
+        Ok(())
+    }
+
+    fn span_event(span_id: u64, data: buck2_data::buck_event::Data) -> buck2_data::BuckEvent {
+        buck2_data::BuckEvent {
+            span_id,
+            data: Some(data),
+            ..Default::default()
+        }
+    }
+
+    fn action_start(span_id: u64, target: &str) -> buck2_data::BuckEvent {
+        let end = action_end(target, "cxx_compile");
+        span_event(
+            span_id,
+            buck2_data::SpanStartEvent {
+                data: Some(
+                    buck2_data::ActionExecutionStart {
+                        key: end.key,
+                        name: end.name,
+                        ..Default::default()
+                    }
+                    .into(),
+                ),
+            }
+            .into(),
+        )
+    }
+
+    fn span_cancelled(span_id: u64) -> buck2_data::BuckEvent {
+        span_event(
+            span_id,
+            buck2_data::SpanEndEvent {
+                data: Some(buck2_data::SpanCancelled {}.into()),
+                ..Default::default()
+            }
+            .into(),
+        )
+    }
+
+    #[test]
+    fn test_cancelled_spans() -> anyhow::Result<()> {
+        let mut failures = Failures::default();
+        failures.add(&action_start(1, "cancelled"))?;
+        failures.add(&action_start(2, "failed"))?;
+        failures.add(&span_cancelled(1))?;
+        failures.add(&span_event(
+            2,
+            buck2_data::SpanEndEvent {
+                data: Some(failed_action(Status::Failure(Default::default())).into()),
+                ..Default::default()
+            }
+            .into(),
+        ))?;
+        // Not an action.
+        failures.add(&span_cancelled(3))?;
+
+        assert_eq!(2, failures.failures.len());
+        assert!(failures.failures[0].cancelled);
+        assert!(failures.failures[0].identity.contains("cancelled"));
+        assert!(!failures.failures[1].cancelled);
+        assert!(failures.running_actions.is_empty());
+
+        let summary = Summary::new(failures.failures, 1);
+        assert_eq!(1, summary.cancelled);
+        assert!(summary.first_failure.unwrap().contains("root//pkg:bin"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(
+            "foo.cpp:1: error: bad",
+            signature("In file foo.cpp\nfoo.cpp:1: error: bad\n", None, Some(1))
+        );
+        assert_eq!("warning: meh", signature("\n  warning: meh\n", None, None));
+        assert_eq!(
+            "Action failed",
+            signature("", Some("Action failed\ndetails"), Some(1))
+        );
+        assert_eq!("exit code 2", signature("", None, Some(2)));
+    }
+
+    #[test]
+    fn test_sanitize_output_colors() {
+        assert_eq!(
+            "error: bad\n",
+            sanitize_output_colors(b"\x1b[31merror\x1b[0m: bad\n")
+        );
+    }
+
+    #[test]
+    fn test_excerpt() {
+        assert_eq!(vec!["b", "c"], excerpt("a\nb\nc\n", 2));
+        assert_eq!(vec!["a"], excerpt("a", 5));
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary::new(
+            vec![
+                failure("//:cancelled", "cancelled", true),
+                failure("//:a", "error: a", false),
+                failure("//:b", "error: b", false),
+                failure("//:c", "error: a", false),
+            ],
+            1,
+        );
+
+        assert_eq!(Some("//:a"), summary.first_failure.as_deref());
+        assert_eq!(1, summary.cancelled);
+        assert_eq!(2, summary.groups.len());
+        assert_eq!(vec!["//:a", "//:c"], summary.groups[0].identities);
+        assert_eq!(vec!["error: a"], summary.groups[0].stderr);
+        assert_eq!(vec!["//:b"], summary.groups[1].identities);
+    }
+
+    #[test]
+    fn test_summary_no_failures() {
+        let summary = Summary::new(vec![failure("//:cancelled", "", true)], 1);
+        assert_eq!(None, summary.first_failure);
+        assert!(summary.groups.is_empty());
     }
 }
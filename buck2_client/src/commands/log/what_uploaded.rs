@@ -0,0 +1,480 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::path_arg::PathArg;
+use buck2_client_ctx::stream_value::StreamValue;
+use buck2_client_ctx::subscribers::display::display_action_key;
+use buck2_client_ctx::subscribers::display::TargetDisplayOptions;
+use buck2_client_ctx::subscribers::event_log::file_names::retrieve_nth_recent_log;
+use buck2_client_ctx::subscribers::event_log::EventLogPathBuf;
+use futures::TryStreamExt;
+use gazebo::dupe::Dupe;
+use tokio::runtime;
+
+use crate::commands::log::action_execution_end;
+
+#[derive(
+    Debug,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Dupe,
+    clap::ArgEnum
+)]
+#[clap(rename_all = "snake_case")]
+pub enum WhatUploadedSubcommandOutput {
+    Tabulated,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq, clap::ArgEnum)]
+#[clap(rename_all = "snake_case")]
+pub enum WhatUploadedAggregation {
+    /// One row per action.
+    Action,
+    /// One row per action category.
+    Category,
+    /// One row per target.
+    Target,
+}
+
+/// Shows the bytes transferred to and from remote execution by the actions of a command, and
+/// whether they were served by the action cache.
+///
+/// Uploads are split between the inputs of actions executed remotely that the CAS was missing,
+/// and the outputs of actions uploaded to the action cache. Downloads are the outputs of actions
+/// executed remotely or served by the action cache that were actually materialized. Outputs of
+/// actions of previous commands materialized by this one are counted as `unknown`. Rows are sorted
+/// by bytes transferred, the most first, so a build that is fully cached but still transfers a
+/// lot shows where from.
+#[derive(Debug, clap::Parser)]
+#[clap(group = clap::ArgGroup::with_name("event_log"))]
+pub struct WhatUploadedCommand {
+    /// A path to an event-log file to read from. Only works for log files with a single command in them.
+    #[clap(group = "event_log", value_name = "PATH")]
+    path: Option<PathArg>,
+
+    /// Which recent command to read the event log from.
+    #[clap(
+        long,
+        help = "Use the Nth most recent command (`--recent 0` is the most recent).",
+        group = "event_log",
+        value_name = "NUMBER"
+    )]
+    recent: Option<usize>,
+
+    /// How to aggregate actions.
+    #[clap(
+        long,
+        default_value = "action",
+        ignore_case = true,
+        arg_enum,
+        value_name = "AGGREGATION"
+    )]
+    aggregate: WhatUploadedAggregation,
+
+    #[clap(
+        long = "--format",
+        help = "Which output format to use for this command",
+        default_value = "tabulated",
+        ignore_case = true,
+        arg_enum
+    )]
+    output: WhatUploadedSubcommandOutput,
+}
+
+/// Transfers of one action, or of a group of actions.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+struct Transfers {
+    actions: u64,
+    cache_hits: u64,
+    inputs_uploaded: u64,
+    outputs_uploaded: u64,
+    downloaded: u64,
+}
+
+impl Transfers {
+    fn add(&mut self, other: &Transfers) {
+        self.actions += other.actions;
+        self.cache_hits += other.cache_hits;
+        self.inputs_uploaded += other.inputs_uploaded;
+        self.outputs_uploaded += other.outputs_uploaded;
+        self.downloaded += other.downloaded;
+    }
+
+    fn total(&self) -> u64 {
+        self.inputs_uploaded + self.outputs_uploaded + self.downloaded
+    }
+}
+
+#[derive(serde::Serialize)]
+struct JsonRow<'a> {
+    key: &'a str,
+    #[serde(flatten)]
+    transfers: &'a Transfers,
+}
+
+impl WhatUploadedCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext) -> ExitResult {
+        let Self {
+            path,
+            recent,
+            aggregate,
+            output,
+        } = self;
+
+        let log = match path {
+            Some(path) => path.resolve(&ctx.working_dir),
+            None => retrieve_nth_recent_log(&ctx, recent.unwrap_or(0))?.into_abs_path_buf(),
+        };
+
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let rows = rt.block_on(async move {
+            let log_path = EventLogPathBuf::infer(log)?;
+            let (invocation, mut events) = log_path.unpack_stream().await?;
+
+            buck2_client_ctx::eprintln!(
+                "Showing remote execution transfers from: {}",
+                shlex::join(invocation.command_line_args.iter().map(|e| e.as_str()))
+            )?;
+
+            let mut rows = Rows::new(aggregate);
+            while let Some(event) = events.try_next().await? {
+                if let StreamValue::Event(event) = event {
+                    if let Some(action) = action_execution_end(&event) {
+                        rows.add(action)?;
+                    } else if let Some(materialization) = cas_download(&event) {
+                        rows.add_download(materialization);
+                    }
+                }
+            }
+            anyhow::Ok(rows.finish())
+        })?;
+
+        match output {
+            WhatUploadedSubcommandOutput::Tabulated => {
+                let key = match aggregate {
+                    WhatUploadedAggregation::Action => "action",
+                    WhatUploadedAggregation::Category => "category",
+                    WhatUploadedAggregation::Target => "target",
+                };
+                buck2_client_ctx::println!(
+                    "{}\tactions\tcache_hits\tinputs_uploaded\toutputs_uploaded\tdownloaded",
+                    key
+                )?;
+                for (key, t) in &rows {
+                    buck2_client_ctx::println!(
+                        "{}\t{}\t{}\t{}\t{}\t{}",
+                        key,
+                        t.actions,
+                        t.cache_hits,
+                        t.inputs_uploaded,
+                        t.outputs_uploaded,
+                        t.downloaded
+                    )?;
+                }
+            }
+            WhatUploadedSubcommandOutput::Json => {
+                for (key, transfers) in &rows {
+                    let json = JsonRow { key, transfers };
+                    buck2_client_ctx::println!("{}", serde_json::to_string(&json)?)?;
+                }
+            }
+        }
+
+        ExitResult::success()
+    }
+}
+
+/// A successful download of outputs from the CAS.
+fn cas_download(event: &buck2_data::BuckEvent) -> Option<&buck2_data::MaterializationEnd> {
+    let materialization = match event.data.as_ref()? {
+        buck2_data::buck_event::Data::SpanEnd(span) => match span.data.as_ref()? {
+            buck2_data::span_end_event::Data::Materialization(materialization) => materialization,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let method = buck2_data::MaterializationMethod::from_i32(materialization.method?);
+    if materialization.success && method == Some(buck2_data::MaterializationMethod::CasDownload) {
+        Some(materialization)
+    } else {
+        None
+    }
+}
+
+/// The digest of the action, if it executed remotely or was served by the action cache, i.e. if
+/// its outputs may be downloaded.
+fn remote_action_digest(action: &buck2_data::ActionExecutionEnd) -> Option<&str> {
+    match action.commands.last()?.details.as_ref()?.command.as_ref()? {
+        buck2_data::command_execution_details::Command::RemoteCommand(command) => {
+            Some(&command.action_digest)
+        }
+        _ => None,
+    }
+}
+
+/// The transfers of an action, apart from its downloads, which happen when its outputs are
+/// materialized.
+fn transfers(action: &buck2_data::ActionExecutionEnd) -> Transfers {
+    let kind = buck2_data::ActionExecutionKind::from_i32(action.execution_kind);
+    let cache_hit = kind == Some(buck2_data::ActionExecutionKind::ActionCache);
+    Transfers {
+        actions: 1,
+        cache_hits: cache_hit as u64,
+        inputs_uploaded: action.input_bytes_uploaded,
+        outputs_uploaded: if action.did_cache_upload {
+            action.output_size
+        } else {
+            0
+        },
+        downloaded: 0,
+    }
+}
+
+struct Rows {
+    aggregate: WhatUploadedAggregation,
+    rows: HashMap<String, Transfers>,
+    /// The row of each action whose outputs may be downloaded, by action digest.
+    digests: HashMap<String, String>,
+    /// Bytes downloaded, by action digest. Outputs are usually materialized after their action
+    /// ends, but not necessarily, and maybe not even in the same command.
+    downloads: HashMap<String, u64>,
+}
+
+impl Rows {
+    fn new(aggregate: WhatUploadedAggregation) -> Self {
+        Self {
+            aggregate,
+            rows: HashMap::new(),
+            digests: HashMap::new(),
+            downloads: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, action: &buck2_data::ActionExecutionEnd) -> anyhow::Result<()> {
+        let target = match &action.key {
+            Some(key) => display_action_key(key, TargetDisplayOptions::for_console())?,
+            None => "unknown".to_owned(),
+        };
+        let (category, identifier) = match &action.name {
+            Some(name) => (name.category.as_str(), name.identifier.as_str()),
+            None => ("unknown", ""),
+        };
+        let key = match self.aggregate {
+            WhatUploadedAggregation::Action if identifier.is_empty() => {
+                format!("{} {}", target, category)
+            }
+            WhatUploadedAggregation::Action => format!("{} {} {}", target, category, identifier),
+            WhatUploadedAggregation::Category => category.to_owned(),
+            WhatUploadedAggregation::Target => target,
+        };
+        if let Some(digest) = remote_action_digest(action) {
+            self.digests.insert(digest.to_owned(), key.clone());
+        }
+        self.rows.entry(key).or_default().add(&transfers(action));
+        Ok(())
+    }
+
+    fn add_download(&mut self, materialization: &buck2_data::MaterializationEnd) {
+        let digest = materialization.action_digest.as_deref().unwrap_or_default();
+        *self.downloads.entry(digest.to_owned()).or_default() += materialization.total_bytes;
+    }
+
+    /// The rows, the most bytes transferred first.
+    fn finish(mut self) -> Vec<(String, Transfers)> {
+        for (digest, downloaded) in self.downloads {
+            let key = match self.digests.get(&digest) {
+                Some(key) => key.clone(),
+                None => "unknown".to_owned(),
+            };
+            self.rows.entry(key).or_default().downloaded += downloaded;
+        }
+
+        let mut rows: Vec<_> = self.rows.into_iter().collect();
+        rows.sort_by(|(a_key, a), (b_key, b)| {
+            Reverse(a.total())
+                .cmp(&Reverse(b.total()))
+                .then_with(|| a_key.cmp(b_key))
+        });
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::log::testing::action_end;
+
+    fn action(
+        target: &str,
+        category: &str,
+        kind: buck2_data::ActionExecutionKind,
+        input_bytes_uploaded: u64,
+        output_size: u64,
+    ) -> buck2_data::ActionExecutionEnd {
+        let command = match kind {
+            buck2_data::ActionExecutionKind::Local => None,
+            _ => Some(
+                buck2_data::command_execution_details::Command::RemoteCommand(
+                    buck2_data::RemoteCommand {
+                        action_digest: format!("{}-{}", target, category),
+                        ..Default::default()
+                    },
+                ),
+            ),
+        };
+        buck2_data::ActionExecutionEnd {
+            execution_kind: kind as i32,
+            input_bytes_uploaded,
+            output_size,
+            commands: vec![buck2_data::CommandExecution {
+                details: Some(buck2_data::CommandExecutionDetails {
+                    command,
+                    ..Default::default()
+                }),
+                status: None,
+            }],
+            ..action_end(target, category)
+        }
+    }
+
+    fn download(action_digest: &str, total_bytes: u64) -> buck2_data::MaterializationEnd {
+        buck2_data::MaterializationEnd {
+            action_digest: Some(action_digest.to_owned()),
+            total_bytes,
+            success: true,
+            method: Some(buck2_data::MaterializationMethod::CasDownload as i32),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_aggregate_by_category() -> anyhow::Result<()> {
+        let mut rows = Rows::new(WhatUploadedAggregation::Category);
+        rows.add(&action(
+            "a",
+            "cxx_compile",
+            buck2_data::ActionExecutionKind::ActionCache,
+            0,
+            10,
+        ))?;
+        rows.add(&action(
+            "b",
+            "cxx_compile",
+            buck2_data::ActionExecutionKind::Remote,
+            100,
+            20,
+        ))?;
+        rows.add(&action(
+            "b",
+            "cxx_link",
+            buck2_data::ActionExecutionKind::Local,
+            0,
+            1000,
+        ))?;
+        // Only the outputs of `a` are materialized, and only some of them.
+        rows.add_download(&download("a-cxx_compile", 4));
+
+        assert_eq!(
+            vec![
+                (
+                    "cxx_compile".to_owned(),
+                    Transfers {
+                        actions: 2,
+                        cache_hits: 1,
+                        inputs_uploaded: 100,
+                        outputs_uploaded: 0,
+                        downloaded: 4,
+                    }
+                ),
+                (
+                    "cxx_link".to_owned(),
+                    Transfers {
+                        actions: 1,
+                        ..Default::default()
+                    }
+                ),
+            ],
+            rows.finish()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregate_by_action() -> anyhow::Result<()> {
+        let mut rows = Rows::new(WhatUploadedAggregation::Action);
+        rows.add(&action(
+            "a",
+            "cxx_compile",
+            buck2_data::ActionExecutionKind::Remote,
+            5,
+            0,
+        ))?;
+        assert_eq!(
+            vec!["root//pkg:a cxx_compile".to_owned()],
+            rows.finish()
+                .into_iter()
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_downloads_of_previous_commands() {
+        let mut rows = Rows::new(WhatUploadedAggregation::Target);
+        rows.add_download(&download("previous", 7));
+        assert_eq!(
+            vec![(
+                "unknown".to_owned(),
+                Transfers {
+                    downloaded: 7,
+                    ..Default::default()
+                }
+            )],
+            rows.finish()
+        );
+    }
+
+    #[test]
+    fn test_cas_download() {
+        let event = |materialization| buck2_data::BuckEvent {
+            data: Some(buck2_data::buck_event::Data::SpanEnd(
+                buck2_data::SpanEndEvent {
+                    data: Some(buck2_data::span_end_event::Data::Materialization(
+                        materialization,
+                    )),
+                    ..Default::default()
+                },
+            )),
+            ..Default::default()
+        };
+
+        assert!(cas_download(&event(download("a", 1))).is_some());
+        assert!(cas_download(&event(buck2_data::MaterializationEnd {
+            success: false,
+            ..download("a", 1)
+        }))
+        .is_none());
+        assert!(cas_download(&event(buck2_data::MaterializationEnd {
+            method: Some(buck2_data::MaterializationMethod::LocalCopy as i32),
+            ..download("a", 1)
+        }))
+        .is_none());
+    }
+}
@@ -84,6 +84,7 @@ impl StreamingCommand for LspCommand {
         static SIMPLE_CONSOLE: Lazy<CommonConsoleOptions> = Lazy::new(|| CommonConsoleOptions {
             console_type: ConsoleType::Simple,
             ui: vec![],
+            ui_hide: vec![],
             no_interactive_console: true,
         });
         &SIMPLE_CONSOLE
@@ -14,6 +14,7 @@ pub mod clean;
 pub mod clean_stale;
 pub mod cquery;
 pub mod debug;
+pub mod doctor;
 pub mod init;
 pub mod install;
 pub mod kill;
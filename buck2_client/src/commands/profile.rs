@@ -116,8 +116,11 @@ pub struct BuckProfileOptions {
 
 #[derive(Debug, clap::Parser)]
 pub struct AnalysisLoadProfileOptions {
-    #[clap(value_name = "TARGET")]
-    target_pattern: String,
+    /// Patterns to profile. In analysis profiling, the profiles of all the targets they match
+    /// are merged, and totals are reported for each rule type. Loading profiling takes a single
+    /// package.
+    #[clap(value_name = "TARGET", required = true)]
+    target_patterns: Vec<String>,
 
     /// In analysis profiling, capture the profile of the target and its dependencies,
     /// and output the merged profile.
@@ -126,6 +129,10 @@ pub struct AnalysisLoadProfileOptions {
 }
 
 #[derive(Debug, clap::Parser)]
+#[clap(mut_arg(
+    "modifiers",
+    CommonBuildConfigurationOptions::modifier_arg_without_short
+))]
 pub struct ProfileCommonOptions {
     #[clap(flatten)]
     config_opts: CommonBuildConfigurationOptions,
@@ -210,9 +217,11 @@ impl StreamingCommand for ProfileSubcommand {
         let response = match self.opts {
             ProfileOptionsType::BuckProfileOptions { opts, action } => {
                 let target_opts = TargetProfile {
-                    target_pattern: Some(buck2_data::TargetPattern {
-                        value: opts.target_pattern,
-                    }),
+                    target_patterns: opts
+                        .target_patterns
+                        .into_iter()
+                        .map(|value| buck2_data::TargetPattern { value })
+                        .collect(),
                     recursive: opts.recursive,
                     action: action.into(),
                 };
@@ -254,6 +263,7 @@ impl StreamingCommand for ProfileSubcommand {
         let ProfileResponse {
             elapsed,
             total_retained_bytes,
+            rule_types,
         } = response;
 
         let elapsed = elapsed
@@ -271,6 +281,29 @@ impl StreamingCommand for ProfileSubcommand {
         buck2_client_ctx::println!("Elapsed: {:.3}s", elapsed.as_secs_f64())?;
         buck2_client_ctx::println!("Total retained bytes: {}", total_retained_bytes)?;
 
+        if !rule_types.is_empty() {
+            buck2_client_ctx::println!("By rule type:")?;
+            buck2_client_ctx::println!(
+                "  {:>10}  {:>8}  {:>14}  rule type",
+                "elapsed",
+                "targets",
+                "retained bytes"
+            )?;
+            for rule_type in rule_types {
+                let elapsed = rule_type
+                    .elapsed
+                    .and_then(|d| Duration::try_from(d).ok())
+                    .unwrap_or_default();
+                buck2_client_ctx::println!(
+                    "  {:>9.3}s  {:>8}  {:>14}  {}",
+                    elapsed.as_secs_f64(),
+                    rule_type.targets,
+                    rule_type.retained_bytes,
+                    rule_type.rule_type
+                )?;
+            }
+        }
+
         ExitResult::success()
     }
 
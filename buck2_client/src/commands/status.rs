@@ -76,6 +76,7 @@ impl StatusCommand {
                         "bytes_allocated" : status.bytes_allocated,
                         "bytes_resident" : status.bytes_resident,
                         "bytes_retained" : status.bytes_retained,
+                        "listing_cache": serde_json::to_value(status.listing_cache)?,
                         "snapshot": serde_json::to_value(status.snapshot)?,
                     });
                     buck2_client_ctx::println!("{}", serde_json::to_string_pretty(&json_status)?)?;
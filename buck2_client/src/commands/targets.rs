@@ -75,7 +75,8 @@ pub struct TargetsCommand {
     #[clap(
         long,
         alias = "resolvealias",
-        help = "Print the fully-qualified build target for the specified alias[es]"
+        help = "Print the fully-qualified build target for the specified alias[es], following \
+        alias rules to the target they stand for"
     )]
     resolve_alias: bool,
 
@@ -137,6 +138,24 @@ pub struct TargetsCommand {
 
     #[clap(long, help = "Show target call stacks")]
     target_call_stacks: bool,
+
+    /// Call a Starlark function on each target instead of printing it, given as
+    /// `path/to/file.bzl:name`.
+    ///
+    /// The function takes a struct with the `label`, `name`, `package`, `rule_type`, `deps` and
+    /// `attrs` of the unconfigured target. Each result other than `None` is printed as soon as it
+    /// is computed, as a line of JSON: `{"target": <label>, "result": <result>}`.
+    #[clap(
+        long,
+        value_name = "FILE:SYMBOL",
+        conflicts_with_all = &["json", "stats", "show-output", "show-full-output"]
+    )]
+    visitor: Option<String>,
+
+    /// Print a breakdown of where the time went, across pattern resolution, package loading,
+    /// configuration, analysis and execution, with the slowest packages to load.
+    #[clap(long)]
+    profile_phases: bool,
 }
 
 #[async_trait]
@@ -207,6 +226,7 @@ impl StreamingCommand for TargetsCommand {
             target_call_stacks: self.target_call_stacks,
             target_hash_graph_type,
             include_default_attributes: self.include_defaults,
+            visitor: self.visitor.take().unwrap_or_default(),
         };
 
         if self.show_output {
@@ -237,6 +257,14 @@ impl StreamingCommand for TargetsCommand {
     fn common_opts(&self) -> &CommonBuildConfigurationOptions {
         &self.config_opts
     }
+
+    fn profile_phases(&self) -> bool {
+        self.profile_phases
+    }
+
+    fn retry_after_restart(&self) -> bool {
+        true
+    }
 }
 
 async fn targets_show_outputs(
@@ -7,6 +7,9 @@
  * of this source tree.
  */
 
+use std::collections::HashMap;
+use std::time::Duration;
+
 use anyhow::Context;
 use async_trait::async_trait;
 use buck2_client_ctx::client_ctx::ClientCommandContext;
@@ -21,6 +24,7 @@ use buck2_client_ctx::streaming::StreamingCommand;
 use buck2_client_ctx::subscribers::superconsole::test::StylizedCount;
 use buck2_client_ctx::subscribers::superconsole::test::TestHeader;
 use cli_proto::CounterWithExamples;
+use cli_proto::TestOverrides;
 use cli_proto::TestRequest;
 use cli_proto::TestSessionOptions;
 use crossterm::style::Color;
@@ -48,6 +52,19 @@ fn print_error_counter(
     }
     Ok(())
 }
+
+fn parse_test_env(vars: &[String]) -> anyhow::Result<HashMap<String, String>> {
+    vars.iter()
+        .map(|var| match var.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+            _ => Err(anyhow::anyhow!(
+                "Invalid `--test-env` value `{}`, expected `KEY=VALUE`",
+                var
+            )),
+        })
+        .collect()
+}
+
 #[derive(Debug, clap::Parser)]
 #[clap(name = "test", about = "Build and test the specified targets")]
 pub struct TestCommand {
@@ -66,6 +83,12 @@ pub struct TestCommand {
     #[clap(name = "TARGET_PATTERNS", help = "Patterns to test")]
     patterns: Vec<String>,
 
+    /// Patterns of targets to exclude from the targets matched by the patterns to test. Patterns
+    /// prefixed with `-` (e.g. `-//foo/experimental/...`), on the command line or in `@argfiles`,
+    /// are passed as this option.
+    #[clap(long = "exclude-target-pattern", value_name = "PATTERN")]
+    exclude_target_patterns: Vec<String>,
+
     #[clap(
         long = "exclude",
         multiple_values = true,
@@ -117,6 +140,25 @@ If include patterns are present, regardless of whether exclude patterns are pres
     )]
     test_executor_args: Vec<String>,
 
+    /// Environment variables, as `KEY=VALUE`, set on every test executed, taking precedence over
+    /// the environment of the test.
+    #[clap(long = "test-env", value_name = "KEY=VALUE")]
+    test_env: Vec<String>,
+
+    /// Timeout, in seconds, of every test executed, replacing the one chosen by the test executor.
+    #[clap(long = "timeout", value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// Argument appended to the command of every test executed. Unlike the arguments following
+    /// `--`, these are passed to the tests rather than to the test executor.
+    #[clap(long = "test-arg", value_name = "ARG", allow_hyphen_values = true)]
+    test_args: Vec<String>,
+
+    /// Only run the test cases whose name matches this regex. The test executor is given the regex
+    /// in the spec of every test, and is responsible for applying it.
+    #[clap(long = "test-name-filter", value_name = "REGEX")]
+    test_name_filter: Option<String>,
+
     /// Will allow tests that are compatible with RE (setup to run from the repo root and
     /// use relative paths) to run from RE.
     #[clap(long, group = "re_options")]
@@ -139,6 +181,11 @@ impl StreamingCommand for TestCommand {
         mut ctx: ClientCommandContext,
     ) -> ExitResult {
         let context = ctx.client_context(&self.config_opts, matches, self.sanitized_argv())?;
+        let overrides = TestOverrides {
+            env: parse_test_env(&self.test_env)?,
+            timeout: self.timeout.map(|t| Duration::from_secs(t).into()),
+            args: self.test_args,
+        };
         let response = buckd
             .with_flushing()
             .test(
@@ -146,7 +193,11 @@ impl StreamingCommand for TestCommand {
                     context: Some(context),
                     target_patterns: self
                         .patterns
-                        .map(|pat| buck2_data::TargetPattern { value: pat.clone() }),
+                        .iter()
+                        .cloned()
+                        .chain(self.exclude_target_patterns.iter().map(|p| format!("-{}", p)))
+                        .map(|value| buck2_data::TargetPattern { value })
+                        .collect(),
                     test_executor_args: self.test_executor_args,
                     excluded_labels: self.exclude,
                     included_labels: self.include,
@@ -161,6 +212,8 @@ impl StreamingCommand for TestCommand {
                         force_use_project_relative_paths: self.unstable_force_tests_on_re,
                         force_run_from_project_root: self.unstable_force_tests_on_re,
                     }),
+                    overrides: Some(overrides),
+                    test_name_filter: self.test_name_filter,
                 },
                 ctx.stdin().console_interaction_stream(&self.console_opts),
             )
@@ -229,6 +282,15 @@ impl StreamingCommand for TestCommand {
         print_error_counter(&console, &listing_failed, "LISTINGS FAILED", "⚠")?;
         print_error_counter(&console, &failed, "TESTS FAILED", "✗")?;
         print_error_counter(&console, &fatals, "TESTS FATALS", "⚠")?;
+        if !response.skipped_incompatible.is_empty() {
+            console.print_warning(&format!(
+                "{} TARGETS SKIPPED AS INCOMPATIBLE",
+                response.skipped_incompatible.len()
+            ))?;
+            for reason in &response.skipped_incompatible {
+                console.print_warning(&format!("  - {}", reason))?;
+            }
+        }
         if passed.count + failed.count + fatals.count + skipped.count == 0 {
             console.print_warning("NO TESTS RAN")?;
         } else if !response.error_messages.is_empty() {
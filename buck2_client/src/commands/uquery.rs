@@ -7,6 +7,9 @@
  * of this source tree.
  */
 
+use std::path::PathBuf;
+
+use anyhow::Context;
 use async_trait::async_trait;
 use buck2_client_ctx::client_ctx::ClientCommandContext;
 use buck2_client_ctx::common::CommonBuildConfigurationOptions;
@@ -15,10 +18,13 @@ use buck2_client_ctx::common::CommonDaemonCommandOptions;
 use buck2_client_ctx::daemon::client::BuckdClientConnector;
 use buck2_client_ctx::exit_result::ExitResult;
 use buck2_client_ctx::streaming::StreamingCommand;
+use buck2_core::fs::fs_util;
 use buck2_core::soft_error;
+use cli_proto::NamedQuery;
 use cli_proto::QueryOutputFormat;
 use cli_proto::UqueryRequest;
 use gazebo::dupe::Dupe;
+use indexmap::IndexMap;
 use thiserror::Error;
 
 #[derive(Debug, Clone, Dupe, clap::ArgEnum)]
@@ -70,6 +76,12 @@ pub(crate) struct CommonAttributeArgs {
 
 #[derive(Error, Debug)]
 enum ArgErrors {
+    #[error("Line {0}: expected `name = query`")]
+    InvalidNamedQuery(usize),
+    #[error("Query `{0}` is defined more than once")]
+    DuplicateNamedQuery(String),
+    #[error("No queries in `{0}`")]
+    NoNamedQueries(String),
     #[error("`--output-attributes` is deprecated, use `--output-attribute` instead")]
     OutputAttributesDeprecated,
     #[error(
@@ -101,8 +113,24 @@ impl CommonAttributeArgs {
 #[derive(Debug, clap::Parser)]
 #[clap(group = clap::ArgGroup::new("output_attribute_flags").multiple(false))]
 pub struct CommonQueryArgs {
-    #[clap(name = "QUERY", help = "the query to evaluate")]
-    query: String,
+    #[clap(
+        name = "QUERY",
+        help = "the query to evaluate",
+        required_unless_present = "query-file"
+    )]
+    query: Option<String>,
+
+    /// File of named queries to evaluate together instead of `QUERY`, one `name = query` per line
+    /// (indented lines continue the previous query, lines starting with `#` are comments).
+    ///
+    /// The queries share a single universe and are evaluated in one request, which is much
+    /// cheaper than running them one by one. Results are printed as a JSON object keyed by name.
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = &["QUERY", "QUERY_ARGS"]
+    )]
+    query_file: Option<PathBuf>,
 
     #[clap(flatten)]
     pub(crate) attributes: CommonAttributeArgs,
@@ -138,9 +166,37 @@ pub struct CommonQueryArgs {
         help = "list of literals for a multi-query (one containing `%s` or `%Ss`)"
     )]
     query_args: Vec<String>,
+
+    /// Also read literals for a multi-query from stdin, one per line (blank lines and lines
+    /// starting with `#` are ignored), after `QUERY_ARGS`. Use this for sets of targets too long
+    /// to pass as arguments.
+    #[clap(long, conflicts_with = "query-file")]
+    stdin: bool,
+
+    /// `.bzl` file defining query functions usable in the query, such as
+    /// `def my_tests(x): return 'kind("_test$", rdeps(//..., {}))'.format(x)`, which return the
+    /// query a call expands to given the text of its arguments. Can be passed multiple times.
+    #[clap(long, value_name = "PATH", number_of_values = 1)]
+    pub query_lib: Vec<String>,
+
+    /// Print a breakdown of where the time went, across pattern resolution, package loading,
+    /// configuration, analysis and execution, with the slowest packages to load.
+    #[clap(long)]
+    pub profile_phases: bool,
+
+    /// Patterns of targets to remove from the results of the query. Patterns prefixed with `-`
+    /// (e.g. `-//foo/experimental/...`), on the command line or in `@argfiles`, are passed as
+    /// this option.
+    #[clap(long = "exclude-target-pattern", value_name = "PATTERN")]
+    pub exclude_target_patterns: Vec<String>,
 }
 
 impl CommonQueryArgs {
+    /// Whether the query reads literals from stdin.
+    pub fn reads_stdin(&self) -> bool {
+        self.stdin
+    }
+
     fn args_as_set(args: &[String]) -> String {
         let mut s = "set(".to_owned();
         for (i, v) in args.iter().enumerate() {
@@ -157,6 +213,8 @@ impl CommonQueryArgs {
 
     pub fn output_format(&self) -> QueryOutputFormat {
         match self.output_format {
+            // Named queries are only meaningful keyed by name.
+            None if self.query_file.is_some() => QueryOutputFormat::Json,
             Some(QueryOutputFormatArg::Json) => QueryOutputFormat::Json,
             Some(QueryOutputFormatArg::Dot) => QueryOutputFormat::Dot,
             Some(QueryOutputFormatArg::DotCompact) => QueryOutputFormat::DotCompact,
@@ -174,14 +232,77 @@ impl CommonQueryArgs {
         }
     }
 
-    pub fn get_query(&self) -> (String, Vec<String>) {
-        if self.query.contains("%Ss") {
-            let replacement = Self::args_as_set(&self.query_args);
-            (self.query.replace("%Ss", &replacement), vec![])
+    pub async fn get_query(
+        &self,
+        ctx: &mut ClientCommandContext,
+    ) -> anyhow::Result<(String, Vec<String>)> {
+        let query = match &self.query {
+            Some(query) => query.clone(),
+            // Only named queries are evaluated.
+            None => return Ok((String::new(), Vec::new())),
+        };
+        let mut query_args = self.query_args.clone();
+        if self.stdin {
+            query_args.extend(ctx.stdin().read_literals().await?);
+        }
+        if query.contains("%Ss") {
+            let replacement = Self::args_as_set(&query_args);
+            Ok((query.replace("%Ss", &replacement), vec![]))
         } else {
-            (self.query.clone(), self.query_args.clone())
+            Ok((query, query_args))
         }
     }
+
+    /// The queries from `--query-file`, if any.
+    pub fn get_named_queries(&self) -> anyhow::Result<Vec<NamedQuery>> {
+        let path = match &self.query_file {
+            Some(path) => path,
+            None => return Ok(Vec::new()),
+        };
+        let queries = Self::parse_named_queries(&fs_util::read_to_string(path)?)
+            .with_context(|| format!("Error loading queries from `{}`", path.display()))?;
+        if queries.is_empty() {
+            return Err(ArgErrors::NoNamedQueries(path.display().to_string()).into());
+        }
+        Ok(queries
+            .into_iter()
+            .map(|(name, query)| NamedQuery { name, query })
+            .collect())
+    }
+
+    fn parse_named_queries(source: &str) -> anyhow::Result<IndexMap<String, String>> {
+        let mut queries: IndexMap<String, String> = IndexMap::new();
+        for (i, line) in source.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if line.starts_with(char::is_whitespace) {
+                match queries.values_mut().next_back() {
+                    Some(query) => {
+                        query.push('\n');
+                        query.push_str(trimmed);
+                        continue;
+                    }
+                    None => return Err(ArgErrors::InvalidNamedQuery(i + 1).into()),
+                }
+            }
+            let (name, query) = line
+                .split_once('=')
+                .ok_or(ArgErrors::InvalidNamedQuery(i + 1))?;
+            let name = name.trim();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(ArgErrors::InvalidNamedQuery(i + 1).into());
+            }
+            if queries
+                .insert(name.to_owned(), query.trim().to_owned())
+                .is_some()
+            {
+                return Err(ArgErrors::DuplicateNamedQuery(name.to_owned()).into());
+            }
+        }
+        Ok(queries)
+    }
 }
 
 /// Perform queries on the unconfigured target graph.
@@ -210,9 +331,12 @@ impl CommonQueryArgs {
 ///
 /// When printed, values with `select()`s use a special json encoding.
 ///
-/// `1 + select({"//:a": 1, "DEFAULT": 2})` will be encoded as:
+/// `[1] + select({"//:a": [2], "DEFAULT": [3]})` will be encoded as:
+///
+/// `{"__concat__": [[1], {"__select__": [["//:a", [2]], ["DEFAULT", [3]]]}]}`
 ///
-/// `{"__type": "concat", "items": [1, {"__type": "selector", "entries": {"//:a": 1, "DEFAULT": 2}}]}`
+/// The entries of a `__select__` are `[condition, value]` pairs in the order they were declared,
+/// with `DEFAULT` last.
 #[derive(Debug, clap::Parser)]
 #[clap(name = "uquery")]
 pub struct UqueryCommand {
@@ -239,7 +363,8 @@ impl StreamingCommand for UqueryCommand {
         matches: &clap::ArgMatches,
         mut ctx: ClientCommandContext,
     ) -> ExitResult {
-        let (query, query_args) = self.query_common.get_query();
+        let (query, query_args) = self.query_common.get_query(&mut ctx).await?;
+        let named_queries = self.query_common.get_named_queries()?;
         let unstable_output_format = self.query_common.output_format() as i32;
         let output_attributes = self.query_common.attributes.get()?;
         let context = ctx.client_context(&self.config_opts, matches, self.sanitized_argv())?;
@@ -250,10 +375,13 @@ impl StreamingCommand for UqueryCommand {
                 UqueryRequest {
                     query,
                     query_args,
+                    named_queries,
                     context: Some(context),
                     output_attributes,
                     unstable_output_format,
                     target_call_stacks: self.query_common.target_call_stacks,
+                    exclude_target_patterns: self.query_common.exclude_target_patterns,
+                    query_libs: self.query_common.query_lib,
                 },
                 ctx.stdin().console_interaction_stream(&self.console_opts),
             )
@@ -281,4 +409,68 @@ impl StreamingCommand for UqueryCommand {
     fn common_opts(&self) -> &CommonBuildConfigurationOptions {
         &self.config_opts
     }
+
+    fn profile_phases(&self) -> bool {
+        self.query_common.profile_phases
+    }
+
+    fn retry_after_restart(&self) -> bool {
+        !self.query_common.reads_stdin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> anyhow::Result<Vec<(String, String)>> {
+        Ok(CommonQueryArgs::parse_named_queries(source)?
+            .into_iter()
+            .collect())
+    }
+
+    #[test]
+    fn test_parse_named_queries() -> anyhow::Result<()> {
+        let source = "# Comment\n\
+            deps = deps(//foo:bar)\n\
+            \n\
+            rdeps = rdeps(\n  //...,\n  //foo:bar\n  )\n\
+            \x20 # Comment inside a query\n\
+            owner=owner(foo/bar.rs)\n";
+        assert_eq!(
+            vec![
+                ("deps".to_owned(), "deps(//foo:bar)".to_owned()),
+                (
+                    "rdeps".to_owned(),
+                    "rdeps(\n//...,\n//foo:bar\n)".to_owned()
+                ),
+                ("owner".to_owned(), "owner(foo/bar.rs)".to_owned()),
+            ],
+            parse(source)?
+        );
+        assert_eq!(Vec::<(String, String)>::new(), parse("# Nothing\n\n")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_named_queries_errors() {
+        let error = |source| parse(source).unwrap_err().to_string();
+        assert_eq!(
+            "Line 2: expected `name = query`",
+            error("a = deps(//:a)\nnot a query\n")
+        );
+        assert_eq!(
+            "Line 1: expected `name = query`",
+            error("  continuation without a query\n")
+        );
+        assert_eq!(
+            "Line 1: expected `name = query`",
+            error("two words = deps(//:a)\n")
+        );
+        assert_eq!("Line 1: expected `name = query`", error(" = deps(//:a)\n"));
+        assert_eq!(
+            "Query `a` is defined more than once",
+            error("a = deps(//:a)\na = deps(//:b)\n")
+        );
+    }
 }
@@ -0,0 +1,25 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Tracks whether the state of the daemon is corrupted. Once it is, every command reports it when
+//! it ends, so that the client can restart the daemon and retry the command if it failed.
+
+use once_cell::sync::OnceCell;
+
+static CORRUPTION: OnceCell<buck2_data::DaemonCorruption> = OnceCell::new();
+
+/// Records that the state of the daemon is corrupted. Only the first reason is kept.
+pub fn mark_daemon_corrupted(reason: buck2_data::DaemonCorruption) {
+    let _ignored = CORRUPTION.set(reason);
+}
+
+/// Why the state of the daemon is corrupted, if it is.
+pub fn daemon_corruption() -> Option<buck2_data::DaemonCorruption> {
+    CORRUPTION.get().copied()
+}
@@ -37,4 +37,9 @@ impl DaemonDir {
     pub fn buckd_pid(&self) -> AbsNormPathBuf {
         self.path.join(FileName::new("buckd.pid").unwrap())
     }
+
+    /// Path to `crash_manifest.json` file, which lists the commands the daemon is running.
+    pub fn crash_manifest(&self) -> AbsNormPathBuf {
+        self.path.join(FileName::new("crash_manifest.json").unwrap())
+    }
 }
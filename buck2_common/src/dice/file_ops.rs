@@ -16,7 +16,9 @@ use std::sync::Arc;
 use allocative::Allocative;
 use async_trait::async_trait;
 use buck2_core::cells::cell_path::CellPath;
+use buck2_core::cells::cell_root_path::CellRootPath;
 use buck2_core::cells::cell_root_path::CellRootPathBuf;
+use buck2_core::cells::paths::CellRelativePath;
 use buck2_core::cells::CellName;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::project::ProjectRelativePath;
@@ -39,8 +41,10 @@ use crate::file_ops::FileOps;
 use crate::file_ops::RawPathMetadata;
 use crate::file_ops::ReadDirOutput;
 use crate::file_ops::SimpleDirEntry;
+use crate::file_ops::CELL_IGNORE_FILE;
 use crate::io::IoProvider;
 use crate::legacy_configs::dice::HasLegacyConfigs;
+use crate::package_listing::stats::DIR_LISTING_CACHE;
 use crate::result::SharedResult;
 
 pub trait HasFileOps<'c> {
@@ -88,6 +92,21 @@ pub mod keys {
     pub struct FileOpsValue(#[allocative(skip)] pub Arc<dyn FileOps>);
 }
 
+/// Reads the [`CELL_IGNORE_FILE`] at the root of a cell, if there is one.
+///
+/// Changes to it invalidate `FileOpsKey` (see `FileChangeTracker`), so it's not tracked as a
+/// regular file.
+async fn read_cell_ignore_file(
+    io: &dyn IoProvider,
+    cell_root: &CellRootPath,
+) -> anyhow::Result<Option<String>> {
+    let path = cell_root.join(CellRelativePath::unchecked_new(CELL_IGNORE_FILE));
+    match io.read_path_metadata_if_exists(path.clone()).await? {
+        None | Some(RawPathMetadata::Directory) => Ok(None),
+        Some(_) => Ok(Some(io.read_file(path).await?)),
+    }
+}
+
 async fn get_default_file_ops(dice: &DiceComputations) -> SharedResult<Arc<dyn FileOps>> {
     #[derive(Clone, Dupe, PartialEq, Allocative)]
     struct DiceFileOpsDelegate {
@@ -156,8 +175,14 @@ async fn get_default_file_ops(dice: &DiceComputations) -> SharedResult<Arc<dyn F
                 let ignore_spec = config.get("project", "ignore")?;
                 let ignore_spec = ignore_spec.as_ref().map_or("", |s| &**s);
 
-                let cell_ignores =
-                    FileIgnores::new_for_interpreter(ignore_spec, &cell_paths, this_path)?;
+                let ignore_file = read_cell_ignore_file(&**io, this_path).await?;
+
+                let cell_ignores = FileIgnores::new_for_interpreter(
+                    ignore_spec,
+                    ignore_file.as_deref(),
+                    &cell_paths,
+                    this_path,
+                )?;
                 ignores.insert(cell_name.clone(), cell_ignores);
             }
 
@@ -188,6 +213,8 @@ pub struct FileChangeTracker {
     files_to_dirty: HashSet<ReadFileKey>,
     dirs_to_dirty: HashSet<ReadDirKey>,
     paths_to_dirty: HashSet<PathMetadataKey>,
+    /// Whether the ignore file of a cell changed, which changes what's ignored in listings.
+    ignore_file_changed: bool,
 }
 
 impl FileChangeTracker {
@@ -196,6 +223,7 @@ impl FileChangeTracker {
             files_to_dirty: Default::default(),
             dirs_to_dirty: Default::default(),
             paths_to_dirty: Default::default(),
+            ignore_file_changed: false,
         }
     }
 
@@ -203,11 +231,17 @@ impl FileChangeTracker {
         ctx.changed(self.files_to_dirty)?;
         ctx.changed(self.dirs_to_dirty)?;
         ctx.changed(self.paths_to_dirty)?;
+        if self.ignore_file_changed {
+            ctx.changed([FileOpsKey()])?;
+        }
 
         Ok(())
     }
 
     fn file_contents_modify(&mut self, path: CellPath) {
+        if path.path().as_str() == CELL_IGNORE_FILE {
+            self.ignore_file_changed = true;
+        }
         self.files_to_dirty
             .insert(ReadFileKey(Arc::new(path.clone())));
         self.paths_to_dirty.insert(PathMetadataKey(path));
@@ -288,6 +322,7 @@ struct ReadDirKey(CellPath);
 impl Key for ReadDirKey {
     type Value = SharedResult<ReadDirOutput>;
     async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
+        DIR_LISTING_CACHE.record_computation();
         get_default_file_ops(ctx)
             .await?
             .read_dir_with_ignores(&self.0)
@@ -361,6 +396,7 @@ impl<'c> FileOps for DiceFileOps<'c> {
     }
 
     async fn read_dir_with_ignores(&self, path: &CellPath) -> SharedResult<ReadDirOutput> {
+        DIR_LISTING_CACHE.record_lookup();
         self.0.compute(&ReadDirKey(path.clone())).await?
     }
 
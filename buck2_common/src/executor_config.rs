@@ -18,8 +18,12 @@ use internment_tweaks::Intern;
 use internment_tweaks::StaticInterner;
 use once_cell::sync::Lazy;
 
-#[derive(Debug, Eq, Hash, PartialEq, Clone, Dupe, Allocative)]
-pub struct LocalExecutorOptions {}
+#[derive(Debug, Default, Eq, Hash, PartialEq, Clone, Allocative)]
+pub struct LocalExecutorOptions {
+    /// Environment variables whose value is fixed for this execution platform, regardless of
+    /// what the daemon's environment says.
+    pub env_pins: SortedMap<String, String>,
+}
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Dupe, Display, Allocative)]
 pub struct RemoteExecutorUseCase(Intern<String>);
@@ -57,6 +61,10 @@ pub struct RemoteExecutorOptions {
     pub re_action_key: Option<String>,
     pub re_max_input_files_bytes: Option<u64>,
     pub re_use_case: RemoteExecutorUseCase,
+    /// Whether the RE backend can keep persistent workers warm between actions. When set, actions
+    /// that declare a persistent worker are sent with the platform properties that let the
+    /// backend schedule them onto a worker that is already running.
+    pub re_persistent_workers: bool,
 }
 
 impl Default for RemoteExecutorOptions {
@@ -66,6 +74,7 @@ impl Default for RemoteExecutorOptions {
             re_action_key: Default::default(),
             re_max_input_files_bytes: Default::default(),
             re_use_case: RemoteExecutorUseCase::new("buck2-default".to_owned()),
+            re_persistent_workers: false,
         }
     }
 }
@@ -160,7 +169,7 @@ impl CommandExecutorKind {
 impl CommandExecutorConfig {
     pub fn testing_local() -> Self {
         Self {
-            executor_kind: CommandExecutorKind::Local(LocalExecutorOptions {}),
+            executor_kind: CommandExecutorKind::Local(LocalExecutorOptions::default()),
             path_separator: PathSeparatorKind::system_default(),
             cache_upload_behavior: CacheUploadBehavior::Disabled,
         }
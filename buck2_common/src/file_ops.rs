@@ -449,6 +449,9 @@ impl<T: DefaultFileOpsDelegate> FileOps for T {
     }
 }
 
+/// A file at the root of a cell listing extra paths to ignore, on top of `project.ignore`.
+pub const CELL_IGNORE_FILE: &str = ".buckignore";
+
 pub enum FileIgnoreResult {
     Ok,
     IgnoredByPattern(CellRelativePathBuf, String),
@@ -512,6 +515,27 @@ impl IgnoreSet {
     /// the RecursivePathMatcher behavior by identifying non-globby things and appending
     /// a '/**'.
     pub fn from_ignore_spec(spec: &str) -> anyhow::Result<Self> {
+        Self::from_patterns(spec.split(','))
+    }
+
+    /// Creates an IgnoreSet from an ignore spec and the contents of the cell's ignore file
+    /// ([`CELL_IGNORE_FILE`]), if it has one.
+    ///
+    /// The ignore file has one pattern per line, with the same syntax as the patterns of the spec.
+    /// Blank lines and lines starting with `#` are skipped. Patterns aren't split on commas, so
+    /// they can use `{a,b}` alternatives.
+    pub fn from_ignore_spec_and_file(
+        spec: &str,
+        ignore_file: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let file_patterns = ignore_file
+            .into_iter()
+            .flat_map(|contents| contents.lines())
+            .filter(|line| !line.trim_start().starts_with('#'));
+        Self::from_patterns(spec.split(',').chain(file_patterns))
+    }
+
+    fn from_patterns<'a>(patterns_iter: impl IntoIterator<Item = &'a str>) -> anyhow::Result<Self> {
         // TODO(cjhopman): There's opportunity to greatly improve the performance of IgnoreSet by
         // constructing special cases for a couple of common patterns we see in ignore specs. We
         // know that these can get large wins in some places where we've done this same ignore (watchman, buck1's ignores).
@@ -521,7 +545,7 @@ impl IgnoreSet {
         // `some/prefix/**`: a directory prefix. These can all be merged into one trie lookup.
         let mut patterns_builder = GlobSetBuilder::new();
         let mut patterns = Vec::new();
-        for val in spec.split(',') {
+        for val in patterns_iter {
             let val = val.trim();
             if val.is_empty() {
                 continue;
@@ -607,14 +631,16 @@ pub struct FileIgnores {
 impl FileIgnores {
     /// Creates a new FileIgnores intended for use by the interpreter.
     ///
-    /// This will ignore files/dirs in the ignore spec and those in other cells.
+    /// This will ignore files/dirs in the ignore spec, those in the cell's ignore file (given its
+    /// contents) and those in other cells.
     pub fn new_for_interpreter(
         ignore_spec: &str,
+        ignore_file: Option<&str>,
         all_cells: &[(&CellName, &CellRootPath)],
         this_cell: &CellRootPath,
     ) -> anyhow::Result<FileIgnores> {
         Ok(FileIgnores {
-            ignores: IgnoreSet::from_ignore_spec(ignore_spec)?,
+            ignores: IgnoreSet::from_ignore_spec_and_file(ignore_spec, ignore_file)?,
             cell_ignores: IgnoreSet::from_cell_roots(all_cells, this_cell)?,
         })
     }
@@ -848,6 +874,7 @@ mod tests {
         ];
         let ignores = FileIgnores::new_for_interpreter(
             "**/*.java , some/dir/**, one/*, \n    recursive, trailing_slash/",
+            Some("# Vendored.\nnode_modules\n\n**/{bazel-out,.git}\n"),
             cells,
             CellRootPath::new(ProjectRelativePath::unchecked_new("root")),
         )?;
@@ -901,6 +928,27 @@ mod tests {
                 .is_ignored()
         );
 
+        assert_eq!(
+            true,
+            ignores
+                .check(CellRelativePath::unchecked_new("node_modules/foo/BUCK"))
+                .is_ignored()
+        );
+
+        assert_eq!(
+            true,
+            ignores
+                .check(CellRelativePath::unchecked_new("sibling/bazel-out"))
+                .is_ignored()
+        );
+
+        assert_eq!(
+            false,
+            ignores
+                .check(CellRelativePath::unchecked_new("# Vendored."))
+                .is_ignored()
+        );
+
         Ok(())
     }
 
@@ -140,6 +140,12 @@ impl InvocationPaths {
             .join(ForwardRelativePath::unchecked_new("log"))
     }
 
+    /// Where processes persisting event logs to the configured sinks write their errors.
+    pub fn log_upload_errors_dir(&self) -> AbsNormPathBuf {
+        self.buck_out_path()
+            .join(ForwardRelativePath::unchecked_new("log_upload_errors"))
+    }
+
     pub fn re_logs_dir(&self) -> AbsNormPathBuf {
         self.buck_out_path()
             .join(ForwardRelativePath::unchecked_new("re_logs"))
@@ -155,6 +161,18 @@ impl InvocationPaths {
             .join(ForwardRelativePath::unchecked_new("dice_dump"))
     }
 
+    /// Where `buck2 rage` stages and writes its report archives.
+    pub fn rage_dir(&self) -> AbsNormPathBuf {
+        self.buck_out_path()
+            .join(ForwardRelativePath::unchecked_new("rage"))
+    }
+
+    /// Records which configurations the directories of the short output path layout belong to.
+    pub fn short_out_paths_path(&self) -> AbsNormPathBuf {
+        self.buck_out_path()
+            .join(ForwardRelativePath::unchecked_new("short_out_paths"))
+    }
+
     pub fn buck_out_dir_prefix() -> &'static ProjectRelativePath {
         ProjectRelativePath::unchecked_new("buck-out")
     }
@@ -187,8 +205,34 @@ impl InvocationPaths {
         FileName::unchecked_new("materializer_state")
     }
 
+    /// Subdirectory of `cache_dir` storing the digests of source files, when
+    /// `buck2.file_hashing_mode = mtime`
+    pub fn file_digest_cache_path(&self) -> AbsNormPathBuf {
+        self.cache_dir_path()
+            .join(self.file_digest_cache_dir_name())
+    }
+
+    pub fn file_digest_cache_dir_name(&self) -> &FileName {
+        FileName::unchecked_new("file_digests")
+    }
+
+    /// Subdirectory of `cache_dir` storing how long actions took to execute, used to estimate
+    /// build progress
+    pub fn action_duration_history_path(&self) -> AbsNormPathBuf {
+        self.cache_dir_path()
+            .join(self.action_duration_history_dir_name())
+    }
+
+    pub fn action_duration_history_dir_name(&self) -> &FileName {
+        FileName::unchecked_new("action_durations")
+    }
+
     pub fn valid_cache_dirs(&self) -> Vec<&FileName> {
-        vec![self.materializer_state_dir_name()]
+        vec![
+            self.materializer_state_dir_name(),
+            self.file_digest_cache_dir_name(),
+            self.action_duration_history_dir_name(),
+        ]
     }
 }
 
@@ -0,0 +1,698 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A cache of source file digests, keyed by path, size and mtime, that is persisted across daemon
+//! restarts so that files that didn't change don't need to be hashed again.
+//!
+//! Enabled with `buck2.file_hashing_mode = mtime`. Trusting size and mtime isn't entirely safe
+//! (a file can be rewritten without changing either), so each cached digest is verified by
+//! hashing the file again once `buck2.file_hashing_verify_interval_hours` have passed since it
+//! was last computed.
+//!
+//! Digests imported from a manifest weren't computed by buck2 at all, so they are only used until
+//! the daemon that imported them verifies them in the background, which it does right away.
+//!
+//! Saving appends the digests that changed to the cache file, which is only rewritten in full once
+//! most of its records are superseded, dropping the digests that weren't verified for a long time.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use allocative::Allocative;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use gazebo::prelude::*;
+use parking_lot::Mutex;
+use thiserror::Error;
+
+use crate::file_ops::FileDigest;
+use crate::legacy_configs::LegacyBuckConfig;
+use crate::persisted_records::PersistedRecords;
+
+const CACHE_FILE: &str = "digests";
+const CACHE_VERSION: &str = "v1";
+
+/// Files modified less than this long before they were hashed aren't cached: a write in the same
+/// mtime granularity window could change their contents without changing their mtime.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+const DEFAULT_VERIFY_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Digests that weren't verified for this long (or twice the verify interval, if that's longer)
+/// are forgotten: the digests of files that are still read are verified again every interval, so
+/// these are for files that were deleted or aren't built any more.
+const EXPIRY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+#[derive(Debug, Error)]
+#[error("Invalid file hashing mode `{0}`, expected `full` or `mtime`")]
+struct InvalidFileHashingMode(String);
+
+#[derive(Debug, Error)]
+#[error("Invalid digest manifest line {0}, expected `<sha1>:<size> <path>`, got `{1}`")]
+struct InvalidManifestLine(usize, String);
+
+/// How the I/O layer decides whether a source file needs to be hashed.
+#[derive(Clone, Copy, Dupe, Debug, PartialEq, Eq)]
+pub enum FileHashingMode {
+    /// Hash every file the first time the daemon reads it.
+    Full,
+    /// Reuse the digest from a previous daemon if the file's size and mtime didn't change.
+    Mtime,
+}
+
+impl FromStr for FileHashingMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "full" => Ok(Self::Full),
+            "mtime" => Ok(Self::Mtime),
+            _ => Err(InvalidFileHashingMode(s.to_owned()).into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CachedDigest {
+    size: u64,
+    mtime_nanos: u64,
+    /// Seconds since the epoch when the digest was last computed from the file contents, 0 for
+    /// imported digests never computed.
+    verified_at: u64,
+    digest: FileDigest,
+    /// Imported by this daemon and not verified yet. Not persisted, so that another daemon
+    /// verifies the digest before using it.
+    imported: bool,
+}
+
+impl CachedDigest {
+    fn record(&self, path: &ForwardRelativePath) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.size, self.mtime_nanos, self.verified_at, self.digest, path
+        )
+    }
+}
+
+#[derive(Default)]
+struct Entries {
+    digests: HashMap<ForwardRelativePathBuf, CachedDigest>,
+    /// Paths whose digest changed since the last save, which appends their records to the file.
+    changed: HashSet<ForwardRelativePathBuf>,
+    /// Whether the file can only be brought up to date by rewriting it, because digests were
+    /// removed or saving failed.
+    rewrite: bool,
+    /// Records in the file, including the ones superseded by a later record for the same path.
+    saved_records: usize,
+}
+
+impl Entries {
+    fn insert(&mut self, path: ForwardRelativePathBuf, cached: CachedDigest) {
+        self.changed.insert(path.clone());
+        self.digests.insert(path, cached);
+    }
+
+    fn remove(&mut self, path: &ForwardRelativePath) {
+        if self.digests.remove(path).is_some() {
+            self.changed.remove(path);
+            self.rewrite = true;
+        }
+    }
+
+    /// Forgets the digests that weren't verified in `expiry`.
+    fn expire(&mut self, now: Duration, expiry: Duration) {
+        let len = self.digests.len();
+        self.digests.retain(|_, cached| {
+            cached.imported || now.saturating_sub(Duration::from_secs(cached.verified_at)) < expiry
+        });
+        if self.digests.len() != len {
+            self.changed.retain(|path| self.digests.contains_key(path));
+            self.rewrite = true;
+        }
+    }
+}
+
+#[derive(Allocative)]
+pub struct FileDigestCache {
+    file: PersistedRecords,
+    #[allocative(skip)]
+    verify_interval: Duration,
+    #[allocative(skip)]
+    entries: Mutex<Entries>,
+    /// Held while saving, so that a rewrite doesn't overwrite records appended concurrently.
+    #[allocative(skip)]
+    save_lock: Mutex<()>,
+}
+
+impl PartialEq for FileDigestCache {
+    fn eq(&self, other: &Self) -> bool {
+        self.file == other.file
+    }
+}
+
+fn mtime_nanos(meta: &Metadata) -> Option<u64> {
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(mtime.as_nanos()).ok()
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+impl FileDigestCache {
+    /// Creates the cache if `buck2.file_hashing_mode` is `mtime`, loading the digests saved in
+    /// `cache_dir` by previous daemons.
+    pub fn from_config(
+        root_config: Option<&LegacyBuckConfig>,
+        cache_dir: &AbsNormPath,
+    ) -> anyhow::Result<Option<Self>> {
+        let root_config = match root_config {
+            Some(root_config) => root_config,
+            None => return Ok(None),
+        };
+        let mode = root_config
+            .parse::<FileHashingMode>("buck2", "file_hashing_mode")?
+            .unwrap_or(FileHashingMode::Full);
+        if mode == FileHashingMode::Full {
+            return Ok(None);
+        }
+        let verify_interval = root_config
+            .parse::<u64>("buck2", "file_hashing_verify_interval_hours")?
+            .map_or(DEFAULT_VERIFY_INTERVAL, |hours| {
+                Duration::from_secs(hours * 60 * 60)
+            });
+        Ok(Some(Self::load(cache_dir, verify_interval)?))
+    }
+
+    /// Opens the cache in `cache_dir` outside of the daemon, e.g. to import or export digests.
+    pub fn open(cache_dir: &AbsNormPath) -> anyhow::Result<Self> {
+        Self::load(cache_dir, DEFAULT_VERIFY_INTERVAL)
+    }
+
+    fn load(cache_dir: &AbsNormPath, verify_interval: Duration) -> anyhow::Result<Self> {
+        let file = PersistedRecords::new(cache_dir, CACHE_FILE, CACHE_VERSION)?;
+        let records = file.load(Self::parse_entry)?;
+        let mut entries = Entries {
+            saved_records: records.len(),
+            // Later records supersede earlier ones for the same path.
+            digests: records.into_iter().collect(),
+            ..Default::default()
+        };
+        entries.expire(now(), Self::expiry(verify_interval));
+        Ok(Self {
+            file,
+            verify_interval,
+            entries: Mutex::new(entries),
+            save_lock: Mutex::new(()),
+        })
+    }
+
+    fn expiry(verify_interval: Duration) -> Duration {
+        EXPIRY.max(verify_interval * 2)
+    }
+
+    fn parse_entry(line: &str) -> Option<(ForwardRelativePathBuf, CachedDigest)> {
+        let mut fields = line.splitn(5, '\t');
+        let size = fields.next()?.parse().ok()?;
+        let mtime_nanos = fields.next()?.parse().ok()?;
+        let verified_at = fields.next()?.parse().ok()?;
+        let digest = FileDigest::parse_digest_sha1(fields.next()?).ok()?;
+        let path = ForwardRelativePathBuf::new(fields.next()?.to_owned()).ok()?;
+        Some((
+            path,
+            CachedDigest {
+                size,
+                mtime_nanos,
+                verified_at,
+                digest,
+                imported: false,
+            },
+        ))
+    }
+
+    /// Returns the digest of the file at `abspath`, which is `relpath` relative to the project
+    /// root, and has metadata `meta`. Hashes the file unless a cached digest can be trusted.
+    pub fn get_or_compute(
+        &self,
+        relpath: &ForwardRelativePath,
+        abspath: &Path,
+        meta: &Metadata,
+    ) -> anyhow::Result<FileDigest> {
+        self.get_or_compute_with(relpath, meta, now(), || FileDigest::from_file(abspath))
+    }
+
+    fn get_or_compute_with(
+        &self,
+        relpath: &ForwardRelativePath,
+        meta: &Metadata,
+        now: Duration,
+        compute: impl FnOnce() -> anyhow::Result<FileDigest>,
+    ) -> anyhow::Result<FileDigest> {
+        let mtime_nanos = match mtime_nanos(meta) {
+            Some(mtime_nanos) => mtime_nanos,
+            None => return compute(),
+        };
+        let size = meta.len();
+
+        let cached = self
+            .entries
+            .lock()
+            .digests
+            .get(relpath)
+            .filter(|cached| cached.size == size && cached.mtime_nanos == mtime_nanos)
+            .cloned();
+        if let Some(cached) = &cached {
+            let since_verified = now.saturating_sub(Duration::from_secs(cached.verified_at));
+            if cached.imported || since_verified < self.verify_interval {
+                return Ok(cached.digest.dupe());
+            }
+        }
+
+        let digest = compute()?;
+        if let Some(cached) = &cached {
+            if cached.digest != digest {
+                tracing::warn!(
+                    "File `{}` changed without changing its size or mtime, cached digest was stale",
+                    relpath
+                );
+            }
+        }
+
+        if now.saturating_sub(Duration::from_nanos(mtime_nanos)) >= RACY_WINDOW {
+            self.entries.lock().insert(
+                relpath.to_buf(),
+                CachedDigest {
+                    size,
+                    mtime_nanos,
+                    verified_at: now.as_secs(),
+                    digest: digest.dupe(),
+                    imported: false,
+                },
+            );
+        }
+        Ok(digest)
+    }
+
+    /// Seeds the cache from a manifest of `<sha1>:<size> <path>` lines, with paths relative to
+    /// `project_root`, as produced by `export_manifest` or by the checkout system. The digests in
+    /// the manifest are used for the files as they are on disk now, so this should be done right
+    /// after checkout, and only until `verify_imported` checks them. Entries for files that are
+    /// missing or whose size doesn't match are skipped. Returns the number of entries imported.
+    pub fn import_manifest(
+        &self,
+        project_root: &AbsNormPath,
+        manifest: &str,
+    ) -> anyhow::Result<usize> {
+        let mut imported = 0;
+        let mut entries = self.entries.lock();
+        for (i, line) in manifest.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || InvalidManifestLine(i + 1, line.to_owned());
+            let (digest, path) = line.split_once(' ').ok_or_else(invalid)?;
+            let digest = FileDigest::parse_digest_sha1(digest).map_err(|_| invalid())?;
+            let path = ForwardRelativePath::new(path).map_err(|_| invalid())?;
+
+            let meta = match fs_util::metadata(project_root.join(path)) {
+                Ok(meta) if meta.is_file() && meta.len() == digest.size() => meta,
+                _ => continue,
+            };
+            let mtime_nanos = match mtime_nanos(&meta) {
+                Some(mtime_nanos) => mtime_nanos,
+                None => continue,
+            };
+            entries.insert(
+                path.to_buf(),
+                CachedDigest {
+                    size: meta.len(),
+                    mtime_nanos,
+                    verified_at: 0,
+                    digest,
+                    imported: true,
+                },
+            );
+            imported += 1;
+        }
+        Ok(imported)
+    }
+
+    /// Hashes the files whose digests were imported and not verified yet, replacing the digests
+    /// that were wrong. Returns the number of those.
+    pub fn verify_imported(&self, project_root: &AbsNormPath) -> anyhow::Result<usize> {
+        self.verify_imported_with(project_root, now(), |path| {
+            FileDigest::from_file(project_root.join(path).as_path())
+        })
+    }
+
+    fn verify_imported_with(
+        &self,
+        project_root: &AbsNormPath,
+        now: Duration,
+        compute: impl Fn(&ForwardRelativePath) -> anyhow::Result<FileDigest>,
+    ) -> anyhow::Result<usize> {
+        let imported = self
+            .entries
+            .lock()
+            .digests
+            .iter()
+            .filter(|(_, cached)| cached.imported)
+            .map(|(path, cached)| (path.clone(), cached.clone()))
+            .collect::<Vec<_>>();
+
+        let mut stale = 0;
+        for (path, cached) in imported {
+            // Files changed since the import are hashed the next time they are read anyway.
+            let unchanged = match fs_util::metadata(project_root.join(&path)) {
+                Ok(meta) => {
+                    meta.len() == cached.size && mtime_nanos(&meta) == Some(cached.mtime_nanos)
+                }
+                Err(_) => false,
+            };
+            let digest = if unchanged {
+                Some(compute(&path)?)
+            } else {
+                None
+            };
+
+            let mut entries = self.entries.lock();
+            // Skip entries replaced while hashing.
+            if entries.digests.get(&path) != Some(&cached) {
+                continue;
+            }
+            match digest {
+                Some(digest) => {
+                    if digest != cached.digest {
+                        tracing::warn!("Imported digest of `{}` was wrong", path);
+                        stale += 1;
+                    }
+                    entries.insert(
+                        path,
+                        CachedDigest {
+                            verified_at: now.as_secs(),
+                            digest,
+                            imported: false,
+                            ..cached
+                        },
+                    );
+                }
+                None => entries.remove(&path),
+            }
+        }
+        Ok(stale)
+    }
+
+    /// All the cached digests, as `<sha1>:<size> <path>` lines sorted by path.
+    pub fn export_manifest(&self) -> String {
+        let entries = self.entries.lock();
+        let entries = &entries.digests;
+        let mut paths: Vec<_> = entries.keys().collect();
+        paths.sort();
+        let mut manifest = String::new();
+        for path in paths {
+            if path.as_str().contains('\n') {
+                continue;
+            }
+            manifest.push_str(&format!("{} {}\n", entries[path].digest, path));
+        }
+        manifest
+    }
+
+    /// Writes the digests that changed since the last save to disk.
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.save_at(now())
+    }
+
+    fn save_at(&self, now: Duration) -> anyhow::Result<()> {
+        let _guard = self.save_lock.lock();
+        let (rewrite, records) = {
+            let mut entries = self.entries.lock();
+            if entries.changed.is_empty() && !entries.rewrite {
+                return Ok(());
+            }
+            // Rewrite once more than half the records in the file are superseded.
+            let rewrite = entries.rewrite
+                || entries.saved_records + entries.changed.len() > 2 * entries.digests.len();
+            let changed = std::mem::take(&mut entries.changed);
+            let records = if rewrite {
+                entries.expire(now, Self::expiry(self.verify_interval));
+                entries.saved_records = entries.digests.len();
+                entries
+                    .digests
+                    .iter()
+                    .map(|(path, cached)| cached.record(path))
+                    .collect::<Vec<_>>()
+            } else {
+                entries.saved_records += changed.len();
+                changed
+                    .iter()
+                    .filter_map(|path| Some(entries.digests.get(path)?.record(path)))
+                    .collect()
+            };
+            entries.rewrite = false;
+            (rewrite, records)
+        };
+
+        let res = if rewrite {
+            self.file.save(records)
+        } else {
+            self.file.append(records)
+        };
+        if res.is_err() {
+            // Whatever was lost is saved with everything else next time.
+            self.entries.lock().rewrite = true;
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn compute_counting<'a>(
+        count: &'a Cell<usize>,
+        digest: &'a FileDigest,
+    ) -> impl FnOnce() -> anyhow::Result<FileDigest> + 'a {
+        move || {
+            count.set(count.get() + 1);
+            Ok(digest.dupe())
+        }
+    }
+
+    #[test]
+    fn test_cache_hit_miss_and_verification() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = AbsNormPath::new(dir.path())?;
+        fs_util::write(dir.join(ForwardRelativePath::new("file")?), "contents")?;
+        let meta = fs_util::metadata(dir.join(ForwardRelativePath::new("file")?))?;
+        let mtime = Duration::from_nanos(mtime_nanos(&meta).unwrap());
+
+        let relpath = ForwardRelativePath::new("file")?;
+        let digest = FileDigest::from_bytes_sha1(b"contents");
+        let count = Cell::new(0);
+        let interval = Duration::from_secs(60);
+        let cache = FileDigestCache::load(&dir.join(ForwardRelativePath::new("cache")?), interval)?;
+
+        // Modified too recently to be cached.
+        cache.get_or_compute_with(relpath, &meta, mtime, compute_counting(&count, &digest))?;
+        cache.get_or_compute_with(relpath, &meta, mtime, compute_counting(&count, &digest))?;
+        assert_eq!(2, count.get());
+
+        // Computed once, then cached.
+        let later = mtime + RACY_WINDOW;
+        cache.get_or_compute_with(relpath, &meta, later, compute_counting(&count, &digest))?;
+        cache.get_or_compute_with(relpath, &meta, later, compute_counting(&count, &digest))?;
+        assert_eq!(3, count.get());
+
+        // Verified again once the interval passed.
+        let much_later = later + interval;
+        cache.get_or_compute_with(
+            relpath,
+            &meta,
+            much_later,
+            compute_counting(&count, &digest),
+        )?;
+        assert_eq!(4, count.get());
+
+        // Survives a restart.
+        cache.save()?;
+        let cache = FileDigestCache::load(&dir.join(ForwardRelativePath::new("cache")?), interval)?;
+        assert_eq!(
+            digest,
+            cache.get_or_compute_with(relpath, &meta, much_later, || panic!("cached"))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_appends_and_rewrites() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_dir = AbsNormPath::new(dir.path())?.join(ForwardRelativePath::new("cache")?);
+        let saved_lines = || -> anyhow::Result<usize> {
+            Ok(
+                fs_util::read_to_string(cache_dir.join(ForwardRelativePath::new(CACHE_FILE)?))?
+                    .lines()
+                    .count(),
+            )
+        };
+        let now = now();
+        let cached = |verified_at: Duration| CachedDigest {
+            size: 3,
+            mtime_nanos: 1,
+            verified_at: verified_at.as_secs(),
+            digest: FileDigest::from_bytes_sha1(b"aaa"),
+            imported: false,
+        };
+        let a = ForwardRelativePath::new("a")?;
+        let b = ForwardRelativePath::new("b")?;
+
+        let cache = FileDigestCache::open(&cache_dir)?;
+        cache.entries.lock().insert(a.to_buf(), cached(now));
+        cache.entries.lock().insert(b.to_buf(), cached(now));
+        cache.save_at(now)?;
+        assert_eq!(3, saved_lines()?);
+
+        // Only the changed digest is appended, until most records are superseded.
+        cache.entries.lock().insert(a.to_buf(), cached(now));
+        cache.save_at(now)?;
+        assert_eq!(4, saved_lines()?);
+        cache.entries.lock().insert(a.to_buf(), cached(now));
+        cache.save_at(now)?;
+        assert_eq!(5, saved_lines()?);
+        cache.entries.lock().insert(a.to_buf(), cached(now));
+        cache.save_at(now)?;
+        assert_eq!(3, saved_lines()?);
+
+        // Nothing changed.
+        cache.save_at(now)?;
+        assert_eq!(3, saved_lines()?);
+
+        // Digests that weren't verified for a long time are dropped by the next daemon.
+        let long_ago = now - EXPIRY - Duration::from_secs(1);
+        cache.entries.lock().insert(b.to_buf(), cached(long_ago));
+        cache.save_at(now)?;
+        assert_eq!(4, saved_lines()?);
+        let restarted = FileDigestCache::open(&cache_dir)?;
+        assert_eq!(
+            vec![&a.to_buf()],
+            restarted.entries.lock().digests.keys().collect::<Vec<_>>()
+        );
+        restarted.save_at(now)?;
+        assert_eq!(2, saved_lines()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_export_manifest() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = AbsNormPath::new(dir.path())?;
+        fs_util::create_dir_all(root.join(ForwardRelativePath::new("src")?))?;
+        fs_util::write(root.join(ForwardRelativePath::new("src/a")?), "aaa")?;
+        fs_util::write(root.join(ForwardRelativePath::new("src/b")?), "bb")?;
+        let a = FileDigest::from_bytes_sha1(b"aaa");
+        let b = FileDigest::from_bytes_sha1(b"bb");
+
+        // The last two lines are for a missing file and for a digest with the wrong size.
+        let manifest = format!("{} src/b\n{} src/a\n{} src/missing\n{} src/b\n", b, a, a, a);
+        let cache = FileDigestCache::open(&root.join(ForwardRelativePath::new("cache")?))?;
+        assert_eq!(2, cache.import_manifest(root, &manifest)?);
+        assert_eq!(
+            format!("{} src/a\n{} src/b\n", a, b),
+            cache.export_manifest()
+        );
+
+        let meta = fs_util::metadata(root.join(ForwardRelativePath::new("src/a")?))?;
+        assert_eq!(
+            a,
+            cache.get_or_compute(
+                ForwardRelativePath::new("src/a")?,
+                root.join(ForwardRelativePath::new("src/a")?).as_path(),
+                &meta
+            )?
+        );
+
+        assert!(cache.import_manifest(root, "not-a-digest src/a").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_imported() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = AbsNormPath::new(dir.path())?;
+        fs_util::write(root.join(ForwardRelativePath::new("a")?), "aaa")?;
+        fs_util::write(root.join(ForwardRelativePath::new("b")?), "bbb")?;
+        let a = FileDigest::from_bytes_sha1(b"aaa");
+        let b = FileDigest::from_bytes_sha1(b"bbb");
+        let meta = fs_util::metadata(root.join(ForwardRelativePath::new("b")?))?;
+        let now = Duration::from_nanos(mtime_nanos(&meta).unwrap()) + RACY_WINDOW;
+
+        // The digest of `b` is wrong, but has the right size.
+        let cache_dir = root.join(ForwardRelativePath::new("cache")?);
+        let cache = FileDigestCache::open(&cache_dir)?;
+        assert_eq!(
+            2,
+            cache.import_manifest(root, &format!("{} a\n{} b\n", a, a))?
+        );
+
+        // Imported digests aren't persisted as verified, so another daemon hashes the files.
+        cache.save()?;
+        let count = Cell::new(0);
+        let restarted = FileDigestCache::open(&cache_dir)?;
+        restarted.get_or_compute_with(
+            ForwardRelativePath::new("b")?,
+            &meta,
+            now,
+            compute_counting(&count, &b),
+        )?;
+        assert_eq!(1, count.get());
+
+        // The importing daemon uses them until it verified them.
+        assert_eq!(
+            a,
+            cache.get_or_compute_with(ForwardRelativePath::new("b")?, &meta, now, || panic!(
+                "imported"
+            ))?
+        );
+        let hashed = Cell::new(0);
+        let stale = cache.verify_imported_with(root, now, |path| {
+            hashed.set(hashed.get() + 1);
+            FileDigest::from_file(root.join(path).as_path())
+        })?;
+        assert_eq!(1, stale);
+        assert_eq!(2, hashed.get());
+        assert_eq!(format!("{} a\n{} b\n", a, b), cache.export_manifest());
+        assert_eq!(
+            b,
+            cache.get_or_compute_with(ForwardRelativePath::new("b")?, &meta, now, || panic!(
+                "verified"
+            ))?
+        );
+
+        // Verified digests aren't hashed again.
+        assert_eq!(
+            0,
+            cache.verify_imported_with(root, now, |_| panic!("verified"))?
+        );
+        Ok(())
+    }
+}
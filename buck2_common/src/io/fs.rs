@@ -36,16 +36,29 @@ use crate::file_ops::RawPathMetadata;
 use crate::file_ops::RawSymlink;
 use crate::file_ops::SimpleDirEntry;
 use crate::file_ops::TrackedFileDigest;
+use crate::io::digest_cache::FileDigestCache;
 use crate::io::IoProvider;
 
 #[derive(PartialEq, Clone, Dupe, Allocative)]
 pub struct FsIoProvider {
     fs: ProjectRoot,
+    digest_cache: Option<Arc<FileDigestCache>>,
 }
 
 impl FsIoProvider {
     pub fn new(fs: ProjectRoot) -> Self {
-        Self { fs }
+        Self {
+            fs,
+            digest_cache: None,
+        }
+    }
+
+    /// Like `new`, but reuses the digests of files whose size and mtime didn't change.
+    pub fn new_with_digest_cache(fs: ProjectRoot, digest_cache: Arc<FileDigestCache>) -> Self {
+        Self {
+            fs,
+            digest_cache: Some(digest_cache),
+        }
     }
 }
 
@@ -111,12 +124,13 @@ impl IoProvider for FsIoProvider {
         path: ProjectRelativePathBuf,
     ) -> anyhow::Result<Option<RawPathMetadata<ProjectRelativePathBuf>>> {
         let fs = self.fs.dupe();
+        let digest_cache = self.digest_cache.dupe();
         let path = path.into_forward_relative_path_buf();
 
         tokio::task::spawn_blocking(move || {
-            let meta = read_path_metadata(fs.root(), &path)?.map(|raw_meta_or_redirection| {
-                raw_meta_or_redirection.map(ProjectRelativePathBuf::from)
-            });
+            let meta = read_path_metadata(fs.root(), &path, digest_cache.as_deref())?.map(
+                |raw_meta_or_redirection| raw_meta_or_redirection.map(ProjectRelativePathBuf::from),
+            );
 
             Ok(meta)
         })
@@ -124,6 +138,12 @@ impl IoProvider for FsIoProvider {
     }
 
     async fn settle(&self) -> anyhow::Result<()> {
+        // Persist the digests computed by the previous command, so a restarted daemon can use them.
+        if let Some(digest_cache) = self.digest_cache.dupe() {
+            tokio::task::spawn_blocking(move || digest_cache.save())
+                .await?
+                .context("Error saving file digest cache")?;
+        }
         Ok(())
     }
 
@@ -138,11 +158,16 @@ impl IoProvider for FsIoProvider {
     fn project_root(&self) -> &ProjectRoot {
         &self.fs
     }
+
+    fn digest_cache(&self) -> Option<Arc<FileDigestCache>> {
+        self.digest_cache.dupe()
+    }
 }
 
 fn read_path_metadata<P: AsRef<AbsNormPath>>(
     root: P,
     relpath: &ForwardRelativePath,
+    digest_cache: Option<&FileDigestCache>,
 ) -> anyhow::Result<Option<RawPathMetadata<ForwardRelativePathBuf>>> {
     let root = root.as_ref().as_path();
 
@@ -211,8 +236,11 @@ fn read_path_metadata<P: AsRef<AbsNormPath>>(
     let meta = if meta.is_dir() {
         RawPathMetadata::Directory
     } else {
-        let digest = FileDigest::from_file(&curr_abspath)
-            .with_context(|| format!("Error collecting file digest for `{}`", curr_path))?;
+        let digest = match digest_cache {
+            Some(digest_cache) => digest_cache.get_or_compute(&curr_path, &curr_abspath, &meta),
+            None => FileDigest::from_file(&curr_abspath),
+        }
+        .with_context(|| format!("Error collecting file digest for `{}`", curr_path))?;
         let digest = TrackedFileDigest::new(digest);
         RawPathMetadata::File(FileMetadata {
             digest,
@@ -258,7 +286,11 @@ mod tests {
         fs_util::write(t.path().join("x"), "xx")?;
 
         assert_matches!(
-            read_path_metadata(AbsNormPath::new(t.path())?, ForwardRelativePath::new("x")?),
+            read_path_metadata(
+                AbsNormPath::new(t.path())?,
+                ForwardRelativePath::new("x")?,
+                None
+            ),
             Ok(Some(RawPathMetadata::File(..)))
         );
 
@@ -272,7 +304,11 @@ mod tests {
         unix::fs::symlink("y/z", t.path().join("x"))?;
 
         assert_matches!(
-            read_path_metadata(AbsNormPath::new(t.path())?, ForwardRelativePath::new("x")?),
+            read_path_metadata(
+                AbsNormPath::new(t.path())?,
+                ForwardRelativePath::new("x")?,
+                None
+            ),
             Ok(Some(RawPathMetadata::Symlink{at:_, to: RawSymlink::Relative(r)})) => {
                 assert_eq!(r, "y/z");
             }
@@ -289,7 +325,11 @@ mod tests {
         unix::fs::symlink("../y", t.path().join("x/xx/xxx"))?;
 
         assert_matches!(
-            read_path_metadata(AbsNormPath::new(t.path())?, ForwardRelativePath::new("x/xx/xxx")?),
+            read_path_metadata(
+                AbsNormPath::new(t.path())?,
+                ForwardRelativePath::new("x/xx/xxx")?,
+                None
+            ),
             Ok(Some(RawPathMetadata::Symlink{at:_, to: RawSymlink::Relative(r)})) => {
                 assert_eq!(r, "x/y");
             }
@@ -305,7 +345,11 @@ mod tests {
         unix::fs::symlink("y", t.path().join("x"))?;
 
         assert_matches!(
-            read_path_metadata(AbsNormPath::new(t.path())?, ForwardRelativePath::new("x/z/zz")?),
+            read_path_metadata(
+                AbsNormPath::new(t.path())?,
+                ForwardRelativePath::new("x/z/zz")?,
+                None
+            ),
             Ok(Some(RawPathMetadata::Symlink{at:_, to: RawSymlink::Relative(r)})) => {
                 assert_eq!(r, "y/z/zz");
             }
@@ -321,7 +365,11 @@ mod tests {
         unix::fs::symlink("../y", t.path().join("x"))?;
 
         assert_matches!(
-            read_path_metadata(AbsNormPath::new(t.path())?, ForwardRelativePath::new("x/xx/xxx")?),
+            read_path_metadata(
+                AbsNormPath::new(t.path())?,
+                ForwardRelativePath::new("x/xx/xxx")?,
+                None
+            ),
             Err(e) if format!("{:#}", e).contains("Invalid symlink")
         );
 
@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+pub mod digest_cache;
 #[cfg(any(fbcode_build, cargo_internal_build))]
 pub mod eden;
 
@@ -16,12 +17,14 @@ use std::sync::Arc;
 
 use allocative::Allocative;
 use async_trait::async_trait;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
 use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_core::fs::project::ProjectRoot;
 use gazebo::cmp::PartialEqAny;
 
 use crate::file_ops::RawPathMetadata;
 use crate::file_ops::SimpleDirEntry;
+use crate::io::digest_cache::FileDigestCache;
 use crate::legacy_configs::LegacyBuckConfig;
 
 #[async_trait]
@@ -44,6 +47,11 @@ pub trait IoProvider: Allocative + Send + Sync {
     fn eq_token(&self) -> PartialEqAny<'_>;
 
     fn project_root(&self) -> &ProjectRoot;
+
+    /// The source file digest cache, when `buck2.file_hashing_mode = mtime` enables it.
+    fn digest_cache(&self) -> Option<Arc<FileDigestCache>> {
+        None
+    }
 }
 
 impl PartialEq for dyn IoProvider {
@@ -56,6 +64,7 @@ pub async fn create_io_provider(
     fb: fbinit::FacebookInit,
     project_fs: ProjectRoot,
     root_config: Option<&LegacyBuckConfig>,
+    digest_cache_dir: &AbsNormPath,
 ) -> anyhow::Result<Arc<dyn IoProvider>> {
     #[cfg(any(fbcode_build, cargo_internal_build))]
     {
@@ -77,7 +86,13 @@ pub async fn create_io_provider(
     }
 
     let _allow_unused = fb;
-    let _allow_unused = root_config;
+
+    if let Some(digest_cache) = FileDigestCache::from_config(root_config, digest_cache_dir)? {
+        return Ok(Arc::new(fs::FsIoProvider::new_with_digest_cache(
+            project_fs,
+            Arc::new(digest_cache),
+        )));
+    }
 
     Ok(Arc::new(fs::FsIoProvider::new(project_fs)))
 }
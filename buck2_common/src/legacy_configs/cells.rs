@@ -12,6 +12,7 @@ use std::collections::HashMap;
 use anyhow::Context;
 use buck2_core::cells::cell_root_path::CellRootPathBuf;
 use buck2_core::cells::CellAlias;
+use buck2_core::cells::CellName;
 use buck2_core::cells::CellResolver;
 use buck2_core::cells::CellsAggregator;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
@@ -22,7 +23,9 @@ use buck2_core::fs::paths::RelativePath;
 use buck2_core::fs::project::ProjectRelativePath;
 use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_core::fs::project::ProjectRoot;
+use gazebo::dupe::Dupe;
 use once_cell::unsync::OnceCell;
+use thiserror::Error;
 
 use crate::legacy_configs::path::BuckConfigFile;
 use crate::legacy_configs::path::DEFAULT_BUCK_CONFIG_FILES;
@@ -36,6 +39,18 @@ use crate::legacy_configs::LegacyBuckConfigs;
 use crate::legacy_configs::LegacyConfigCmdArg;
 use crate::legacy_configs::MainConfigFile;
 
+#[derive(Debug, Error)]
+enum CellsError {
+    #[error(
+        "Invalid cell alias `{0}` in the `[repositories]` section of the buckconfig in `{1}`, cell aliases can't be empty or contain `/`, `:` or whitespace"
+    )]
+    InvalidCellAlias(String, CellRootPathBuf),
+    #[error(
+        "Cell `{0}` at `{1}` is nested inside cell `{2}` at `{3}`, but `{2}` has no alias for it. Add it to the `[repositories]` section of the buckconfig in `{3}`"
+    )]
+    UndeclaredNestedCell(CellName, CellRootPathBuf, CellName, CellRootPathBuf),
+}
+
 /// Used for creating a CellResolver in a buckv1-compatible way based on values
 /// in .buckconfig in each cell.
 ///
@@ -45,6 +60,9 @@ use crate::legacy_configs::MainConfigFile;
 ///
 /// We don't (currently) enforce that all aliases appear in the root config, but
 /// unlike v1, our cells implementation works just fine if that isn't the case.
+///
+/// We do enforce that a cell nested inside another cell is known to the enclosing
+/// cell, i.e. that the enclosing cell has an alias for it.
 pub struct BuckConfigBasedCells {
     pub configs_by_name: LegacyBuckConfigs,
     pub cell_resolver: CellResolver,
@@ -81,6 +99,27 @@ impl BuckConfigBasedCells {
         Ok(cells.cell_resolver)
     }
 
+    /// Performs a parse of the root `.buckconfig` (and the other config files of the root cell)
+    /// without following includes and without parsing any other cells, for the client to read
+    /// the few settings it needs without waiting for the daemon.
+    pub fn parse_immediate_config(project_fs: &ProjectRoot) -> anyhow::Result<LegacyBuckConfig> {
+        let opts = BuckConfigParseOptions {
+            follow_includes: false,
+            parse_cells: false,
+        };
+        let cells = Self::parse_with_file_ops_and_options(
+            project_fs,
+            &DefaultConfigParserFileOps {},
+            &[],
+            ProjectRelativePath::empty(),
+            opts,
+        )?;
+        Ok(cells
+            .configs_by_name
+            .get(cells.cell_resolver.root_cell())?
+            .dupe())
+    }
+
     pub fn parse(project_fs: &ProjectRoot) -> anyhow::Result<Self> {
         Self::parse_with_file_ops(
             project_fs,
@@ -144,13 +183,16 @@ impl BuckConfigBasedCells {
             let mut buckconfig_paths: Vec<MainConfigFile> = Vec::new();
 
             for buckconfig in DEFAULT_BUCK_CONFIG_FILES {
+                let layer = buckconfig.layer();
                 match buckconfig {
-                    BuckConfigFile::ProjectRelativeFile(file) => {
+                    BuckConfigFile::ProjectRelativeFile(file)
+                    | BuckConfigFile::ProjectRelativeLocalFile(file) => {
                         let buckconfig_path = ForwardRelativePath::new(file)?;
                         buckconfig_paths.push(MainConfigFile {
                             path: project_fs
                                 .resolve(&path.project_relative_path().join(buckconfig_path)),
                             owned_by_project: true,
+                            layer,
                         });
                     }
 
@@ -162,6 +204,7 @@ impl BuckConfigBasedCells {
                             &mut buckconfig_paths,
                             &buckconfig_folder_abs_path,
                             true,
+                            layer,
                         )?;
                     }
                     BuckConfigFile::UserFile(file) => {
@@ -172,6 +215,7 @@ impl BuckConfigBasedCells {
                                 path: AbsNormPath::new(&home_dir_path)?
                                     .join_normalized(buckconfig_path)?,
                                 owned_by_project: false,
+                                layer,
                             });
                         }
                     }
@@ -185,6 +229,7 @@ impl BuckConfigBasedCells {
                                 &mut buckconfig_paths,
                                 &buckconfig_folder_abs_path,
                                 false,
+                                layer,
                             )?;
                         }
                     }
@@ -192,6 +237,7 @@ impl BuckConfigBasedCells {
                         buckconfig_paths.push(MainConfigFile {
                             path: AbsNormPathBuf::from(String::from(*file))?,
                             owned_by_project: false,
+                            layer,
                         });
                     }
                     BuckConfigFile::GlobalFolder(folder) => {
@@ -201,6 +247,7 @@ impl BuckConfigBasedCells {
                             &mut buckconfig_paths,
                             &buckconfig_folder_abs_path,
                             false,
+                            layer,
                         )?;
                     }
                 }
@@ -231,6 +278,12 @@ impl BuckConfigBasedCells {
 
             if let Some(repositories) = config.get_section("repositories") {
                 for (alias, alias_path) in repositories.iter() {
+                    if !Self::is_valid_alias(alias) {
+                        return Err(anyhow::anyhow!(CellsError::InvalidCellAlias(
+                            alias.to_owned(),
+                            path.clone()
+                        )));
+                    }
                     let alias_path = alias_path.as_str();
                     let alias_path = path
                         .join_normalized(RelativePath::new(alias_path))
@@ -276,6 +329,7 @@ impl BuckConfigBasedCells {
         }
 
         let cell_resolver = cells_aggregator.make_cell_resolver()?;
+        Self::check_nested_cells(&cell_resolver)?;
         let configs_by_name = buckconfigs
             .into_iter()
             .map(|(path, config)| {
@@ -292,6 +346,43 @@ impl BuckConfigBasedCells {
         })
     }
 
+    fn is_valid_alias(alias: &str) -> bool {
+        !alias.is_empty() && !alias.contains(|c: char| c == '/' || c == ':' || c.is_whitespace())
+    }
+
+    /// A cell nested inside another cell takes over that part of the enclosing cell, so the
+    /// enclosing cell must know about it, otherwise the files under it silently disappear from
+    /// the enclosing cell.
+    ///
+    /// The root cell encloses every other cell, and what it doesn't alias itself is declared by
+    /// the cells it leads to, so this only applies to cells nested inside other cells.
+    fn check_nested_cells(cell_resolver: &CellResolver) -> anyhow::Result<()> {
+        let root_cell = cell_resolver.root_cell();
+        for (name, cell) in cell_resolver.cells() {
+            let parent = match cell.path().as_project_relative_path().parent() {
+                Some(parent) => parent,
+                None => continue,
+            };
+            let enclosing = cell_resolver.get(cell_resolver.find(parent)?)?;
+            if enclosing.name() == root_cell {
+                continue;
+            }
+            if !enclosing
+                .cell_alias_resolver()
+                .mappings()
+                .any(|(_, aliased)| aliased == name)
+            {
+                return Err(anyhow::anyhow!(CellsError::UndeclaredNestedCell(
+                    name.clone(),
+                    cell.path().to_buf(),
+                    enclosing.name().clone(),
+                    enclosing.path().to_buf(),
+                )));
+            }
+        }
+        Ok(())
+    }
+
     /// Deal with the `buildfile.name` key (and `name_v2`)
     fn parse_buildfile_name(config: &LegacyBuckConfig) -> anyhow::Result<Option<Vec<FileNameBuf>>> {
         fn parse_list(val: &str) -> impl Iterator<Item = &str> {
@@ -338,6 +429,7 @@ mod tests {
     use crate::legacy_configs::cells::BuckConfigBasedCells;
     use crate::legacy_configs::testing::TestConfigParserFileOps;
     use crate::legacy_configs::tests::assert_config_value;
+    use crate::legacy_configs::LegacyBuckConfigLayer;
     use crate::legacy_configs::LegacyConfigCmdArg;
 
     fn create_project_filesystem() -> ProjectRoot {
@@ -678,6 +770,16 @@ mod tests {
         // local override new section
         assert_config_value(config, "orange", "key", "value3");
 
+        let apple_section = config.get_section("apple").unwrap();
+        assert_eq!(
+            apple_section.get("key").unwrap().layer(),
+            LegacyBuckConfigLayer::Repo
+        );
+        assert_eq!(
+            apple_section.get("key2").unwrap().layer(),
+            LegacyBuckConfigLayer::RepoLocal
+        );
+
         Ok(())
     }
 
@@ -772,4 +874,161 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_invalid_cell_alias() -> anyhow::Result<()> {
+        let file_ops = TestConfigParserFileOps::new(&[(
+            "/.buckconfig",
+            indoc!(
+                r#"
+                            [repositories]
+                                root = .
+                                other/cell = other/
+                        "#
+            ),
+        )])?;
+
+        let project_fs = create_project_filesystem();
+        let err = BuckConfigBasedCells::parse_with_file_ops(
+            &project_fs,
+            &file_ops,
+            &[],
+            ProjectRelativePath::empty(),
+        )
+        .err()
+        .unwrap();
+        assert!(
+            err.to_string().contains("Invalid cell alias `other/cell`"),
+            "{}",
+            err
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cell_declared_outside_root_config() -> anyhow::Result<()> {
+        let file_ops = TestConfigParserFileOps::new(&[
+            (
+                "/.buckconfig",
+                indoc!(
+                    r#"
+                            [repositories]
+                                root = .
+                                other = other/
+                        "#
+                ),
+            ),
+            (
+                "/other/.buckconfig",
+                indoc!(
+                    r#"
+                            [repositories]
+                                other = .
+                                nested = nested/
+                        "#
+                ),
+            ),
+            (
+                "/other/nested/.buckconfig",
+                indoc!(
+                    r#"
+                            [repositories]
+                                nested = .
+                                sibling = ../../sibling/
+                        "#
+                ),
+            ),
+            (
+                "/sibling/.buckconfig",
+                indoc!(
+                    r#"
+                            [repositories]
+                                sibling = .
+                        "#
+                ),
+            ),
+        ])?;
+
+        let project_fs = create_project_filesystem();
+        // `other//nested` is declared by the cell it is nested in, and `sibling` only needs to be
+        // declared by some cell.
+        let cells = BuckConfigBasedCells::parse_with_file_ops(
+            &project_fs,
+            &file_ops,
+            &[],
+            ProjectRelativePath::empty(),
+        )?;
+        assert!(cells
+            .cell_resolver
+            .cells()
+            .any(|(name, _)| name.as_str() == "sibling"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_undeclared_nested_cell() -> anyhow::Result<()> {
+        let file_ops = TestConfigParserFileOps::new(&[
+            (
+                "/.buckconfig",
+                indoc!(
+                    r#"
+                            [repositories]
+                                root = .
+                                other = other/
+                                sibling = sibling/
+                        "#
+                ),
+            ),
+            (
+                "/other/.buckconfig",
+                indoc!(
+                    r#"
+                            [repositories]
+                                other = .
+                        "#
+                ),
+            ),
+            (
+                "/sibling/.buckconfig",
+                indoc!(
+                    r#"
+                            [repositories]
+                                sibling = .
+                                nested = ../other/nested/
+                        "#
+                ),
+            ),
+            (
+                "/other/nested/.buckconfig",
+                indoc!(
+                    r#"
+                            [repositories]
+                                nested = .
+                        "#
+                ),
+            ),
+        ])?;
+
+        let project_fs = create_project_filesystem();
+        let err = BuckConfigBasedCells::parse_with_file_ops(
+            &project_fs,
+            &file_ops,
+            &[],
+            ProjectRelativePath::empty(),
+        )
+        .err()
+        .unwrap();
+        // `nested` is declared by `sibling`, but not by `other`, which it is nested in.
+        assert!(
+            err.to_string().contains(
+                "Cell `nested` at `other/nested` is nested inside cell `other` at `other`"
+            ),
+            "{}",
+            err
+        );
+
+        Ok(())
+    }
 }
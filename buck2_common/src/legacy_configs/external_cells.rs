@@ -0,0 +1,456 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! External cells are cells whose contents aren't checked in to the repo, but fetched from a
+//! pinned origin the first time they are needed. They are declared next to `[repositories]` in
+//! the root `.buckconfig`:
+//!
+//! ```text
+//! [repositories]
+//!   shared_rules = third-party/shared_rules
+//! [external_cells]
+//!   shared_rules = git:https://github.com/org/shared_rules.git@0f3c8e2b9b7a6d2c1e4f5a6b7c8d9e0f1a2b3c4d
+//! ```
+//!
+//! The daemon materializes the origin at the cell path (which should be ignored by source control)
+//! when it loads the configs for a command, together with a stamp file recording the origin, so
+//! that it's only fetched again when the pin changes. Every cell's `.buckconfig` is read before
+//! anything is evaluated, so that's the first access to the cell: the configs are parsed again
+//! after a fetch so that the external cell's own `.buckconfig` is used by the same command. The
+//! client never fetches anything.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use std::process::Command;
+
+use anyhow::Context;
+use buck2_core::cells::CellAliasResolver;
+use buck2_core::cells::CellName;
+use buck2_core::cells::CellResolver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRoot;
+use parking_lot::Mutex;
+use sha1::Digest;
+use sha1::Sha1;
+use thiserror::Error;
+
+use crate::legacy_configs::LegacyBuckConfig;
+use crate::legacy_configs::LegacyBuckConfigs;
+
+/// Written at the root of a materialized external cell, contains the origin it came from.
+const STAMP_FILE: &str = ".buck2_external_cell";
+
+/// Held while materializing external cells, so that commands accessing the same cell concurrently
+/// don't fetch it twice.
+static MATERIALIZE_LOCK: Mutex<()> = parking_lot::const_mutex(());
+
+#[derive(Debug, Error)]
+enum ExternalCellError {
+    #[error(
+        "Invalid external cell origin `{0}`, expected `git:<repo>@<commit>` or `archive:<url>#sha1:<digest>`"
+    )]
+    InvalidOrigin(String),
+    #[error("External cell origin `{0}` must be pinned to a full 40 character hex hash")]
+    InvalidPin(String),
+    #[error(
+        "Path `{0}` already exists and wasn't materialized by buck2, refusing to overwrite it with an external cell"
+    )]
+    NotAnExternalCell(String),
+    #[error("Archive `{url}` has sha1 `{actual}`, but the external cell expected `{expected}`")]
+    DigestMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("`{0}` failed while materializing external cell:\n{1}")]
+    CommandFailed(String, String),
+}
+
+/// Where the contents of an external cell come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ExternalCellOrigin {
+    /// A commit of a git repo, fetched with `git`.
+    Git { repo: String, commit: String },
+    /// A tarball, downloaded with `curl` and extracted with `tar`.
+    Archive { url: String, sha1: String },
+}
+
+impl Display for ExternalCellOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExternalCellOrigin::Git { repo, commit } => write!(f, "git:{}@{}", repo, commit),
+            ExternalCellOrigin::Archive { url, sha1 } => write!(f, "archive:{}#sha1:{}", url, sha1),
+        }
+    }
+}
+
+fn is_full_hash(s: &str) -> bool {
+    s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+impl ExternalCellOrigin {
+    pub(crate) fn parse(origin: &str) -> anyhow::Result<Self> {
+        let invalid = || ExternalCellError::InvalidOrigin(origin.to_owned());
+        let (repo_or_url, pin, parsed) = if let Some(git) = origin.strip_prefix("git:") {
+            // Split on the last `@`, since ssh repos like `git@github.com:org/repo` contain one.
+            let (repo, commit) = git.rsplit_once('@').ok_or_else(invalid)?;
+            (
+                repo,
+                commit,
+                ExternalCellOrigin::Git {
+                    repo: repo.to_owned(),
+                    commit: commit.to_ascii_lowercase(),
+                },
+            )
+        } else if let Some(archive) = origin.strip_prefix("archive:") {
+            let (url, sha1) = archive.rsplit_once("#sha1:").ok_or_else(invalid)?;
+            (
+                url,
+                sha1,
+                ExternalCellOrigin::Archive {
+                    url: url.to_owned(),
+                    sha1: sha1.to_ascii_lowercase(),
+                },
+            )
+        } else {
+            return Err(invalid().into());
+        };
+
+        if repo_or_url.is_empty() {
+            return Err(invalid().into());
+        }
+        if !is_full_hash(pin) {
+            return Err(ExternalCellError::InvalidPin(origin.to_owned()).into());
+        }
+        Ok(parsed)
+    }
+}
+
+/// The origins of the external cells declared in the root config, by cell.
+pub(crate) fn external_cells(
+    root_config: &LegacyBuckConfig,
+    root_aliases: &CellAliasResolver,
+) -> anyhow::Result<HashMap<CellName, ExternalCellOrigin>> {
+    let mut cells = HashMap::new();
+    if let Some(section) = root_config.get_section("external_cells") {
+        for (alias, origin) in section.iter() {
+            cells.insert(
+                root_aliases.resolve(alias)?.clone(),
+                ExternalCellOrigin::parse(origin.as_str())?,
+            );
+        }
+    }
+    Ok(cells)
+}
+
+fn run(command: &mut Command) -> anyhow::Result<()> {
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(ExternalCellError::CommandFailed(
+            format!("{:?}", command),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Materializes all the external cells declared in the root config. Returns whether any of them
+/// was fetched, in which case the configs need to be parsed again to pick up their `.buckconfig`s.
+/// This blocks while fetching.
+pub fn materialize_external_cells(
+    project_root: &ProjectRoot,
+    cells: &CellResolver,
+    configs: &LegacyBuckConfigs,
+) -> anyhow::Result<bool> {
+    materialize_cells_with(project_root, cells, configs, fetch)
+}
+
+fn materialize_cells_with(
+    project_root: &ProjectRoot,
+    cells: &CellResolver,
+    configs: &LegacyBuckConfigs,
+    fetch: impl Fn(&ExternalCellOrigin, &AbsNormPath) -> anyhow::Result<()>,
+) -> anyhow::Result<bool> {
+    let root_config = configs.get(cells.root_cell())?;
+    let mut fetched = false;
+    for (cell, origin) in external_cells(root_config, cells.root_cell_cell_alias_resolver())? {
+        let cell_root = project_root.resolve(cells.get(&cell)?.path().project_relative_path());
+        fetched |= materialize_with(&origin, &cell_root, &fetch)
+            .with_context(|| format!("Materializing external cell `{}` from `{}`", cell, origin))?;
+    }
+    Ok(fetched)
+}
+
+/// Makes sure the contents of `origin` are at `cell_root`, fetching them if they aren't there yet
+/// or if they came from a different origin. Returns whether it fetched anything.
+fn materialize_with(
+    origin: &ExternalCellOrigin,
+    cell_root: &AbsNormPath,
+    fetch: impl FnOnce(&ExternalCellOrigin, &AbsNormPath) -> anyhow::Result<()>,
+) -> anyhow::Result<bool> {
+    let _guard = MATERIALIZE_LOCK.lock();
+
+    let stamp = cell_root.join(ForwardRelativePath::new(STAMP_FILE)?);
+    let stamp_contents = origin.to_string();
+
+    match fs_util::read_to_string_opt(&stamp)? {
+        Some(existing) if existing == stamp_contents => return Ok(false),
+        Some(_) => fs_util::remove_all(cell_root)?,
+        None => {
+            if fs_util::try_exists(cell_root)? && fs_util::read_dir(cell_root)?.next().is_some() {
+                return Err(ExternalCellError::NotAnExternalCell(cell_root.to_string()).into());
+            }
+        }
+    }
+
+    tracing::info!(
+        "Materializing external cell `{}` at `{}`",
+        origin,
+        cell_root
+    );
+    fs_util::create_dir_all(cell_root)?;
+    // Doesn't match any origin, so an interrupted fetch is cleaned up and retried next time.
+    fs_util::write(&stamp, "incomplete")?;
+    fetch(origin, cell_root)?;
+    fs_util::write(&stamp, stamp_contents)?;
+    Ok(true)
+}
+
+fn fetch(origin: &ExternalCellOrigin, cell_root: &AbsNormPath) -> anyhow::Result<()> {
+    match origin {
+        ExternalCellOrigin::Git { repo, commit } => {
+            let git = || {
+                let mut command = Command::new("git");
+                command
+                    .current_dir(cell_root.as_path())
+                    .arg("-c")
+                    .arg("advice.detachedHead=false");
+                command
+            };
+            run(git().args(["init", "--quiet"]))?;
+            run(git().args(["fetch", "--quiet", "--depth", "1", repo, commit]))?;
+            run(git().args(["checkout", "--quiet", "FETCH_HEAD"]))?;
+        }
+        ExternalCellOrigin::Archive { url, sha1 } => {
+            let archive =
+                cell_root.join(ForwardRelativePath::new(".buck2_external_cell.download")?);
+            run(Command::new("curl")
+                .args([
+                    "--fail",
+                    "--silent",
+                    "--show-error",
+                    "--location",
+                    "--output",
+                ])
+                .arg(archive.as_path())
+                .arg(url))?;
+
+            let mut hasher = Sha1::new();
+            hasher.update(fs_util::read(&archive)?);
+            let actual = hex::encode(hasher.finalize());
+            if &actual != sha1 {
+                fs_util::remove_all(cell_root)?;
+                return Err(ExternalCellError::DigestMismatch {
+                    url: url.clone(),
+                    expected: sha1.clone(),
+                    actual,
+                }
+                .into());
+            }
+
+            run(Command::new("tar")
+                .arg("-xf")
+                .arg(archive.as_path())
+                .arg("-C")
+                .arg(cell_root.as_path()))?;
+            fs_util::remove_file(&archive)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+
+    use super::*;
+    use crate::legacy_configs::cells::BuckConfigBasedCells;
+
+    const HASH: &str = "0f3c8e2b9b7a6d2c1e4f5a6b7c8d9e0f1a2b3c4d";
+
+    #[test]
+    fn test_parse_origin() -> anyhow::Result<()> {
+        let git = format!("git:git@github.com:org/rules.git@{}", HASH);
+        assert_eq!(
+            ExternalCellOrigin::Git {
+                repo: "git@github.com:org/rules.git".to_owned(),
+                commit: HASH.to_owned(),
+            },
+            ExternalCellOrigin::parse(&git)?
+        );
+        assert_eq!(git, ExternalCellOrigin::parse(&git)?.to_string());
+
+        let archive = format!("archive:https://example.com/rules.tar.gz#sha1:{}", HASH);
+        assert_eq!(
+            ExternalCellOrigin::Archive {
+                url: "https://example.com/rules.tar.gz".to_owned(),
+                sha1: HASH.to_owned(),
+            },
+            ExternalCellOrigin::parse(&archive)?
+        );
+        assert_eq!(archive, ExternalCellOrigin::parse(&archive)?.to_string());
+
+        assert!(ExternalCellOrigin::parse("https://example.com/rules.git").is_err());
+        assert!(ExternalCellOrigin::parse(&format!("git:@{}", HASH)).is_err());
+        assert!(ExternalCellOrigin::parse("git:https://example.com/rules.git@main").is_err());
+        assert!(ExternalCellOrigin::parse("archive:https://example.com/rules.tar.gz").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_materialize_refuses_to_overwrite() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs_util::write(dir.path().join("BUCK"), "")?;
+        let origin = ExternalCellOrigin::parse(&format!("git:https://example.com/rules@{}", HASH))?;
+        assert!(materialize_with(&origin, AbsNormPath::new(dir.path())?, fake_fetch).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_materialize_up_to_date() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let origin = ExternalCellOrigin::parse(&format!("git:https://example.com/rules@{}", HASH))?;
+        fs_util::write(dir.path().join(STAMP_FILE), origin.to_string())?;
+        assert!(!materialize_with(
+            &origin,
+            AbsNormPath::new(dir.path())?,
+            |_, _| panic!("Fetched an up to date cell")
+        )?);
+        Ok(())
+    }
+
+    /// Fetches by writing the origin to a `BUCK` file.
+    fn fake_fetch(origin: &ExternalCellOrigin, cell_root: &AbsNormPath) -> anyhow::Result<()> {
+        fs_util::write(
+            cell_root.join(ForwardRelativePath::new("BUCK")?),
+            origin.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_materialize_fetches_on_pin_change() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cell_root = AbsNormPath::new(dir.path())?.join(ForwardRelativePath::new("cell")?);
+        let buck = cell_root.join(ForwardRelativePath::new("BUCK")?);
+        let origin = ExternalCellOrigin::parse(&format!("git:https://example.com/rules@{}", HASH))?;
+
+        assert!(materialize_with(&origin, &cell_root, fake_fetch)?);
+        assert_eq!(origin.to_string(), fs_util::read_to_string(&buck)?);
+        assert!(!materialize_with(&origin, &cell_root, |_, _| panic!(
+            "Fetched twice"
+        ))?);
+
+        let other = ExternalCellOrigin::parse(&format!(
+            "git:https://example.com/rules@{}",
+            "1111111111111111111111111111111111111111"
+        ))?;
+        assert!(materialize_with(&other, &cell_root, fake_fetch)?);
+        assert_eq!(other.to_string(), fs_util::read_to_string(&buck)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetched_cell_config_is_used() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let project_root = ProjectRoot::new(AbsNormPathBuf::try_from(dir.path().to_owned())?);
+        fs_util::write(
+            dir.path().join(".buckconfig"),
+            format!(
+                "[repositories]\n  root = .\n  rules = rules\n[external_cells]\n  rules = git:https://example.com/rules@{}\n",
+                HASH
+            ),
+        )?;
+        let fetch_config = |_: &ExternalCellOrigin, cell_root: &AbsNormPath| {
+            fs_util::write(
+                cell_root.join(ForwardRelativePath::new(".buckconfig")?),
+                "[rules]\n  fetched = true\n",
+            )
+        };
+
+        let parsed = BuckConfigBasedCells::parse(&project_root)?;
+        let rules = CellName::unchecked_new("rules".to_owned());
+        assert!(parsed
+            .configs_by_name
+            .get(&rules)?
+            .get("rules", "fetched")
+            .is_none());
+        assert!(materialize_cells_with(
+            &project_root,
+            &parsed.cell_resolver,
+            &parsed.configs_by_name,
+            fetch_config
+        )?);
+
+        let parsed = BuckConfigBasedCells::parse(&project_root)?;
+        assert_eq!(
+            Some("true"),
+            parsed.configs_by_name.get(&rules)?.get("rules", "fetched")
+        );
+        assert!(!materialize_cells_with(
+            &project_root,
+            &parsed.cell_resolver,
+            &parsed.configs_by_name,
+            |_, _| panic!("Fetched twice")
+        )?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_materialize_retries_interrupted_fetch() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cell_root = AbsNormPath::new(dir.path())?.join(ForwardRelativePath::new("cell")?);
+        let origin = ExternalCellOrigin::parse(&format!("git:https://example.com/rules@{}", HASH))?;
+
+        assert!(
+            materialize_with(&origin, &cell_root, |_, _| Err(anyhow::anyhow!("offline"))).is_err()
+        );
+        materialize_with(&origin, &cell_root, fake_fetch)?;
+        assert_eq!(
+            origin.to_string(),
+            fs_util::read_to_string(cell_root.join(ForwardRelativePath::new("BUCK")?))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_materialize_concurrently() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cell_root = AbsNormPath::new(dir.path())?.join(ForwardRelativePath::new("cell")?);
+        let origin = ExternalCellOrigin::parse(&format!("git:https://example.com/rules@{}", HASH))?;
+        let fetches = std::sync::atomic::AtomicUsize::new(0);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    materialize_with(&origin, &cell_root, |origin, cell_root| {
+                        fetches.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                        fake_fetch(origin, cell_root)
+                    })
+                    .unwrap()
+                });
+            }
+        });
+        assert_eq!(1, fetches.into_inner());
+        Ok(())
+    }
+}
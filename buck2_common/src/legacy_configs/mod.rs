@@ -12,6 +12,7 @@
 
 pub mod cells;
 pub mod dice;
+pub mod external_cells;
 pub(crate) mod path;
 pub mod view;
 
@@ -136,12 +137,49 @@ struct MainConfigFile {
 
     /// if a main config file is in project or global
     owned_by_project: bool,
+
+    layer: LegacyBuckConfigLayer,
 }
 
 #[derive(Debug, Allocative)]
 struct ConfigFile {
     id: String,
     include_source: Option<Location>,
+    /// The layer of the main config file this file was included from (or of this file, if it is
+    /// a main config file).
+    layer: LegacyBuckConfigLayer,
+}
+
+/// The layers of the config stack, from lowest to highest priority. A value set in a layer
+/// overrides values set for the same key in the layers before it.
+#[derive(
+    Clone,
+    Copy,
+    Dupe,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Allocative,
+    derive_more::Display
+)]
+pub enum LegacyBuckConfigLayer {
+    /// Machine-wide config, like `/etc/buckconfig` and `/etc/buckconfig.d`.
+    #[display(fmt = "global")]
+    Global,
+    /// Config in the user's home directory, like `~/.buckconfig.local` and `~/.buckconfig.d`.
+    #[display(fmt = "user")]
+    User,
+    /// Config checked in to the repo, like `.buckconfig` and `.buckconfig.d`.
+    #[display(fmt = "repo")]
+    Repo,
+    /// Local overrides of the repo config, `.buckconfig.local`.
+    #[display(fmt = "repo local")]
+    RepoLocal,
+    /// Config passed with `--config` or `--config-file`.
+    #[display(fmt = "command line")]
+    CommandLine,
 }
 
 #[derive(Clone, Dupe, Debug, Allocative)]
@@ -479,9 +517,10 @@ impl<'a> LegacyConfigParser<'a> {
         &mut self,
         path: &AbsNormPath,
         source: Option<Location>,
+        layer: LegacyBuckConfigLayer,
         follow_includes: bool,
     ) -> anyhow::Result<()> {
-        self.start_file(path, source)?;
+        self.start_file(path, source, layer)?;
         self.parse_file_on_stack(path, follow_includes)
             .with_context(|| format!("When parsing buckconfig `{}`", path))?;
         self.finish_file();
@@ -498,16 +537,23 @@ impl<'a> LegacyConfigParser<'a> {
 
         let source_file = Arc::new(ConfigFile {
             id: self.file_ops.file_id(path),
+            layer: include_source.source_file.layer,
             include_source: Some(Location::File(include_source)),
         });
         self.current_file = Some(source_file);
         Ok(())
     }
 
-    fn start_file(&mut self, path: &AbsNormPath, source: Option<Location>) -> anyhow::Result<()> {
+    fn start_file(
+        &mut self,
+        path: &AbsNormPath,
+        source: Option<Location>,
+        layer: LegacyBuckConfigLayer,
+    ) -> anyhow::Result<()> {
         let source_file = Arc::new(ConfigFile {
             id: self.file_ops.file_id(path),
             include_source: source,
+            layer,
         });
         self.current_file = Some(source_file);
         Ok(())
@@ -944,6 +990,14 @@ impl<'a> LegacyBuckConfigValue<'a> {
         }
     }
 
+    /// The layer of the config stack this value was set in.
+    pub fn layer(&self) -> LegacyBuckConfigLayer {
+        match &self.value.source {
+            Location::File(file) => file.source_file.layer,
+            Location::CommandLineArgument => LegacyBuckConfigLayer::CommandLine,
+        }
+    }
+
     pub fn location_stack(&self) -> Vec<LegacyBuckConfigLocation> {
         let mut res = Vec::new();
         let mut location = Some(&self.value.source);
@@ -991,6 +1045,7 @@ impl LegacyBuckConfig {
             &[MainConfigFile {
                 path: path.to_buf(),
                 owned_by_project: true,
+                layer: LegacyBuckConfigLayer::Repo,
             }],
             file_ops,
             &processed_config_args,
@@ -1098,7 +1153,12 @@ impl LegacyBuckConfig {
         let mut parser = LegacyConfigParser::new(file_ops);
         let mut cell_path = None;
         for main_config_file in main_config_files {
-            parser.parse_file(&main_config_file.path, None, follow_includes)?;
+            parser.parse_file(
+                &main_config_file.path,
+                None,
+                main_config_file.layer,
+                follow_includes,
+            )?;
             if main_config_file.owned_by_project {
                 cell_path = match main_config_file.path.parent() {
                     Some(cell) => Some(cell),
@@ -1118,6 +1178,7 @@ impl LegacyBuckConfig {
                 ResolvedLegacyConfigArg::File(file_path) => parser.parse_file(
                     file_path,
                     Some(Location::CommandLineArgument),
+                    LegacyBuckConfigLayer::CommandLine,
                     follow_includes,
                 )?,
             };
@@ -1195,6 +1256,7 @@ fn push_all_files_from_a_directory(
     buckconfig_paths: &mut Vec<MainConfigFile>,
     folder_path: &AbsNormPath,
     owned_by_project: bool,
+    layer: LegacyBuckConfigLayer,
 ) -> anyhow::Result<()> {
     let readdir = match fs::read_dir(folder_path) {
         Ok(p) => p,
@@ -1218,6 +1280,7 @@ fn push_all_files_from_a_directory(
             buckconfig_paths.push(MainConfigFile {
                 path: AbsNormPath::new(&entry_path)?.to_buf(),
                 owned_by_project,
+                layer,
             });
         } else {
             tracing::warn!(
@@ -1660,6 +1723,7 @@ mod tests {
             key_value.location(),
             LegacyBuckConfigLocation::CommandLineArgument
         );
+        assert_eq!(key_value.layer(), LegacyBuckConfigLayer::CommandLine);
 
         Ok(())
     }
@@ -1783,6 +1847,7 @@ mod tests {
         #[cfg(windows)]
         let expected_path = LegacyBuckConfigLocation::File("C:/cli-config", 2);
         assert_eq!(key_value.location(), expected_path);
+        assert_eq!(key_value.layer(), LegacyBuckConfigLayer::CommandLine);
 
         Ok(())
     }
@@ -1812,12 +1877,13 @@ mod tests {
             let file = AbsNormPath::new(&file)?;
             let dir = AbsNormPath::new(&dir)?;
 
-            push_all_files_from_a_directory(&mut v, dir, false)?;
+            push_all_files_from_a_directory(&mut v, dir, false, LegacyBuckConfigLayer::User)?;
             assert_eq!(
                 v,
                 vec![MainConfigFile {
                     path: file.to_owned(),
                     owned_by_project: false,
+                    layer: LegacyBuckConfigLayer::User,
                 }]
             );
 
@@ -1830,7 +1896,7 @@ mod tests {
             let dir = tempfile::tempdir()?;
             let dir = AbsNormPath::new(&dir)?;
 
-            push_all_files_from_a_directory(&mut v, dir, false)?;
+            push_all_files_from_a_directory(&mut v, dir, false, LegacyBuckConfigLayer::User)?;
             assert_eq!(v, vec![]);
 
             Ok(())
@@ -1843,7 +1909,7 @@ mod tests {
             let dir = dir.path().join("bad");
             let dir = AbsNormPath::new(&dir)?;
 
-            push_all_files_from_a_directory(&mut v, dir, false)?;
+            push_all_files_from_a_directory(&mut v, dir, false, LegacyBuckConfigLayer::User)?;
             assert_eq!(v, vec![]);
 
             Ok(())
@@ -1856,7 +1922,7 @@ mod tests {
             fs_util::create_dir_all(&dir.path().join("bad"))?;
             let dir = AbsNormPath::new(&dir)?;
 
-            push_all_files_from_a_directory(&mut v, dir, false)?;
+            push_all_files_from_a_directory(&mut v, dir, false, LegacyBuckConfigLayer::User)?;
             assert_eq!(v, vec![]);
 
             Ok(())
@@ -1868,7 +1934,7 @@ mod tests {
             let file = tempfile::NamedTempFile::new()?;
             let file = AbsNormPath::new(file.path())?;
 
-            push_all_files_from_a_directory(&mut v, file, false)?;
+            push_all_files_from_a_directory(&mut v, file, false, LegacyBuckConfigLayer::User)?;
             assert_eq!(v, vec![]);
 
             Ok(())
@@ -7,10 +7,15 @@
  * of this source tree.
  */
 
+use crate::legacy_configs::LegacyBuckConfigLayer;
+
 pub(crate) enum BuckConfigFile {
-    // Buckconfig file in the cell relative to project root, such as .buckconfig or .buckconfig.local
+    // Buckconfig file in the cell relative to project root, such as .buckconfig
     ProjectRelativeFile(&'static str),
 
+    // Local overrides of the buckconfig in the cell, such as .buckconfig.local
+    ProjectRelativeLocalFile(&'static str),
+
     // Buckconfig folder in the cell, assuming all files in this folder are buckconfig
     ProjectRelativeFolder(&'static str),
 
@@ -27,6 +32,23 @@ pub(crate) enum BuckConfigFile {
     GlobalFolder(&'static str),
 }
 
+impl BuckConfigFile {
+    pub(crate) fn layer(&self) -> LegacyBuckConfigLayer {
+        match self {
+            BuckConfigFile::GlobalFile(_) | BuckConfigFile::GlobalFolder(_) => {
+                LegacyBuckConfigLayer::Global
+            }
+            BuckConfigFile::UserFile(_) | BuckConfigFile::UserFolder(_) => {
+                LegacyBuckConfigLayer::User
+            }
+            BuckConfigFile::ProjectRelativeFile(_) | BuckConfigFile::ProjectRelativeFolder(_) => {
+                LegacyBuckConfigLayer::Repo
+            }
+            BuckConfigFile::ProjectRelativeLocalFile(_) => LegacyBuckConfigLayer::RepoLocal,
+        }
+    }
+}
+
 /// The override order of buck config, from highest priority to lowest
 /// 1. .buckconfig.local in repo
 /// 2. .buckconfig in repo
@@ -49,5 +71,5 @@ pub(crate) static DEFAULT_BUCK_CONFIG_FILES: &[BuckConfigFile] = &[
     BuckConfigFile::UserFile(".buckconfig.local"),
     BuckConfigFile::ProjectRelativeFolder(".buckconfig.d"),
     BuckConfigFile::ProjectRelativeFile(".buckconfig"),
-    BuckConfigFile::ProjectRelativeFile(".buckconfig.local"),
+    BuckConfigFile::ProjectRelativeLocalFile(".buckconfig.local"),
 ];
@@ -28,6 +28,7 @@ pub mod buckd_connection;
 pub mod cas_digest;
 pub mod client_utils;
 pub mod convert;
+pub mod daemon_corruption;
 pub mod daemon_dir;
 pub mod dice;
 #[cfg(any(fbcode_build, cargo_internal_build))]
@@ -47,8 +48,10 @@ pub mod memory;
 pub mod package_boundary;
 pub mod package_listing;
 pub mod pattern;
+pub mod persisted_records;
 pub mod process_stats;
 pub mod result;
+pub mod source_changes;
 pub mod sqlite;
 pub mod target_aliases;
 pub mod temp_path;
@@ -22,6 +22,7 @@ use crate::dice::file_ops::HasFileOps;
 use crate::package_listing::interpreter::InterpreterPackageListingResolver;
 use crate::package_listing::listing::PackageListing;
 use crate::package_listing::resolver::PackageListingResolver;
+use crate::package_listing::stats::PACKAGE_LISTING_CACHE;
 use crate::result::SharedResult;
 
 pub trait HasPackageListingResolver<'c> {
@@ -59,6 +60,7 @@ impl<'c> PackageListingResolver for DicePackageListingResolver<'c> {
         impl Key for PackageListingKey {
             type Value = SharedResult<PackageListing>;
             async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
+                PACKAGE_LISTING_CACHE.record_computation();
                 let cell_resolver = ctx.get_cell_resolver().await?;
                 let file_ops = ctx.file_ops();
                 InterpreterPackageListingResolver::new(cell_resolver, Arc::new(file_ops))
@@ -74,6 +76,7 @@ impl<'c> PackageListingResolver for DicePackageListingResolver<'c> {
             }
         }
 
+        PACKAGE_LISTING_CACHE.record_lookup();
         self.0.compute(&PackageListingKey(package.dupe())).await?
     }
 
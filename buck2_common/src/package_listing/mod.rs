@@ -13,3 +13,4 @@ pub mod file_listing;
 pub(crate) mod interpreter;
 pub mod listing;
 pub mod resolver;
+pub mod stats;
@@ -0,0 +1,81 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Daemon-wide counters for the directory and package listing caches.
+//!
+//! Both listings are DICE keys, so they are shared by every command running on the daemon and are
+//! only recomputed when the file watcher dirties the directory. Comparing lookups to computations
+//! shows how many syscalls the caches save during pattern resolution.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+pub struct ListingCacheCounter {
+    lookups: AtomicU64,
+    computations: AtomicU64,
+}
+
+/// Snapshot of a [`ListingCacheCounter`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ListingCacheStats {
+    /// Listings requested.
+    pub lookups: u64,
+    /// Listings computed, i.e. lookups that missed the cache.
+    pub computations: u64,
+}
+
+impl ListingCacheCounter {
+    const fn new() -> Self {
+        Self {
+            lookups: AtomicU64::new(0),
+            computations: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record_lookup(&self) {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_computation(&self) {
+        self.computations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> ListingCacheStats {
+        ListingCacheStats {
+            lookups: self.lookups.load(Ordering::Relaxed),
+            computations: self.computations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Directory listings, each computation being a `read_dir` of the directory.
+pub static DIR_LISTING_CACHE: ListingCacheCounter = ListingCacheCounter::new();
+
+/// Package listings, each computation walking the package's directories.
+pub static PACKAGE_LISTING_CACHE: ListingCacheCounter = ListingCacheCounter::new();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter() {
+        let counter = ListingCacheCounter::new();
+        counter.record_lookup();
+        counter.record_lookup();
+        counter.record_computation();
+        assert_eq!(
+            ListingCacheStats {
+                lookups: 2,
+                computations: 1,
+            },
+            counter.get()
+        );
+    }
+}
@@ -7,12 +7,16 @@
  * of this source tree.
  */
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+
 use anyhow::Context;
 use buck2_core::cells::CellResolver;
 use buck2_core::package::Package;
 use buck2_core::pattern::PackageSpec;
 use buck2_core::pattern::ParsedPattern;
 use buck2_core::pattern::PatternType;
+use buck2_core::target::TargetName;
 use gazebo::dupe::Dupe;
 use indexmap::IndexMap;
 
@@ -49,8 +53,55 @@ where
                 .insert(package.dupe(), PackageSpec::Targets(vec![target.clone()]));
         }
     }
+
+    /// Removes the packages and targets matched by `excluded`.
+    ///
+    /// Targets excluded from a package that is included as a whole can only be removed once the
+    /// package is loaded, so they are returned instead.
+    pub fn exclude<E: PatternType>(&mut self, excluded: ResolvedPattern<E>) -> ExcludedTargets {
+        let mut excluded_targets = ExcludedTargets::default();
+        for (package, spec) in excluded.specs {
+            let remove = match (spec, self.specs.get_mut(&package)) {
+                (_, None) => false,
+                (PackageSpec::All, Some(_)) => true,
+                (PackageSpec::Targets(targets), Some(PackageSpec::Targets(included))) => {
+                    included.retain(|t| !targets.iter().any(|e| e.target() == t.target()));
+                    included.is_empty()
+                }
+                (PackageSpec::Targets(targets), Some(PackageSpec::All)) => {
+                    excluded_targets
+                        .0
+                        .entry(package.dupe())
+                        .or_default()
+                        .extend(targets.into_iter().map(|t| t.target().dupe()));
+                    false
+                }
+            };
+            if remove {
+                self.specs.shift_remove(&package);
+            }
+        }
+        excluded_targets
+    }
+}
+
+/// Targets excluded from packages matched as a whole (e.g. `//foo/... -//foo:bar`).
+#[derive(Debug, Default)]
+pub struct ExcludedTargets(HashMap<Package, HashSet<TargetName>>);
+
+impl ExcludedTargets {
+    pub fn is_excluded(&self, package: &Package, target: &TargetName) -> bool {
+        self.0
+            .get(package)
+            .map_or(false, |targets| targets.contains(target))
+    }
 }
 
+/// How many patterns are resolved together. The package roots of the recursive patterns of a
+/// chunk are looked up concurrently, while lists of hundreds of thousands of literals (as passed
+/// with `--stdin`) don't create a future per pattern.
+const RESOLVE_CHUNK_SIZE: usize = 1000;
+
 /// Resolves a list of [ParsedPattern] to a [ResolvedPattern].
 pub async fn resolve_target_patterns<
     'a,
@@ -61,21 +112,34 @@ pub async fn resolve_target_patterns<
     patterns: T,
     file_ops: &dyn FileOps,
 ) -> anyhow::Result<ResolvedPattern<P>> {
+    let patterns: Vec<_> = patterns.collect();
     let mut resolved = ResolvedPattern::new();
-    for pattern in patterns {
-        match pattern {
-            ParsedPattern::Target(package, target) => {
-                resolved.add_target(package, target);
-            }
-            ParsedPattern::Package(package) => {
-                resolved.add_package(package);
+    for chunk in patterns.chunks(RESOLVE_CHUNK_SIZE) {
+        let roots = futures::future::try_join_all(chunk.iter().map(|pattern| async move {
+            match pattern {
+                ParsedPattern::Recursive(cell_path) => {
+                    find_package_roots(cell_path.clone(), file_ops, cell_resolver)
+                        .await
+                        .context("When resolving recursive target pattern.")
+                        .map(Some)
+                }
+                ParsedPattern::Target(..) | ParsedPattern::Package(..) => Ok(None),
             }
-            ParsedPattern::Recursive(cell_path) => {
-                let roots = find_package_roots(cell_path.clone(), file_ops, cell_resolver)
-                    .await
-                    .context("When resolving recursive target pattern.")?;
-                for package in roots {
-                    resolved.add_package(&package);
+        }))
+        .await?;
+        // Added in order, so that the order of the patterns is kept.
+        for (pattern, roots) in chunk.iter().zip(roots) {
+            match pattern {
+                ParsedPattern::Target(package, target) => {
+                    resolved.add_target(package, target);
+                }
+                ParsedPattern::Package(package) => {
+                    resolved.add_package(package);
+                }
+                ParsedPattern::Recursive(..) => {
+                    for package in roots.into_iter().flatten() {
+                        resolved.add_package(&package);
+                    }
                 }
             }
         }
@@ -273,6 +337,52 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_exclude() -> anyhow::Result<()> {
+        let tester = TestPatternResolver::new(
+            &[("root", "")],
+            &[
+                "foo/BUCK",
+                "foo/bar/BUCK",
+                "foo/experimental/BUCK",
+                "baz/BUCK",
+            ],
+        )?;
+        let mut resolved = tester
+            .resolve::<ProvidersPattern>(&["//foo/...", "//baz:a", "//baz:b"])
+            .await?;
+        let excluded_targets = resolved.exclude(
+            tester
+                .resolve::<TargetPattern>(&[
+                    "//foo/experimental/...",
+                    "//foo:flaky",
+                    "//baz:b",
+                    "//unrelated:",
+                ])
+                .await?,
+        );
+        resolved.assert_eq(&[
+            (Package::testing_new("root", "foo"), PackageSpec::All),
+            (Package::testing_new("root", "foo/bar"), PackageSpec::All),
+            (
+                Package::testing_new("root", "baz"),
+                PackageSpec::Targets(vec![ProvidersPattern {
+                    target: TargetName::unchecked_new("a"),
+                    providers: ProvidersName::Default,
+                }]),
+            ),
+        ]);
+        assert!(excluded_targets.is_excluded(
+            &Package::testing_new("root", "foo"),
+            &TargetName::unchecked_new("flaky")
+        ));
+        assert!(!excluded_targets.is_excluded(
+            &Package::testing_new("root", "foo/bar"),
+            &TargetName::unchecked_new("flaky")
+        ));
+        Ok(())
+    }
+
     #[test_case(PhantomData::< TargetPattern >; "parsing TargetPattern")]
     #[test_case(PhantomData::< ProvidersPattern >; "parsing ProvidersPattern")]
     fn test_recursive_specs<T: PatternType>(_: PhantomData<T>) {
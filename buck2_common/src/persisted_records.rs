@@ -0,0 +1,134 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Files in which the daemon keeps what it learnt about the repo across restarts, e.g. the
+//! digests of source files or how long actions take, as one record per line.
+//!
+//! The first line is the version of the format of the records. A file written with a different
+//! version is ignored, so changing the format only costs learning its contents again.
+
+use std::fs::OpenOptions;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+
+use allocative::Allocative;
+use anyhow::Context;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+
+#[derive(Allocative, Debug, PartialEq, Eq)]
+pub struct PersistedRecords {
+    path: AbsNormPathBuf,
+    #[allocative(skip)]
+    version: &'static str,
+}
+
+impl PersistedRecords {
+    /// The records in the file `name` in `dir`, which is created if needed.
+    pub fn new(dir: &AbsNormPath, name: &str, version: &'static str) -> anyhow::Result<Self> {
+        fs_util::create_dir_all(dir)?;
+        Ok(Self {
+            path: dir.join(ForwardRelativePath::new(name)?),
+            version,
+        })
+    }
+
+    /// The records saved by previous daemons, in the order they were saved. Records that `parse`
+    /// rejects (e.g. because the file was partially written by an older version) are skipped.
+    pub fn load<T>(&self, parse: impl FnMut(&str) -> Option<T>) -> anyhow::Result<Vec<T>> {
+        let mut contents = match fs_util::read_to_string_opt(&self.path)? {
+            Some(contents) => contents,
+            None => return Ok(Vec::new()),
+        };
+        // The last record is incomplete if the daemon died while appending it.
+        contents.truncate(contents.rfind('\n').map_or(0, |i| i + 1));
+        let mut lines = contents.lines();
+        if lines.next() != Some(self.version) {
+            return Ok(Vec::new());
+        }
+        Ok(lines.filter_map(parse).collect())
+    }
+
+    fn push_records(contents: &mut String, records: impl IntoIterator<Item = String>) {
+        for record in records {
+            if record.contains('\n') {
+                continue;
+            }
+            contents.push_str(&record);
+            contents.push('\n');
+        }
+    }
+
+    /// Replaces the saved records. Records containing a newline can't be read back, and are
+    /// skipped.
+    pub fn save(&self, records: impl IntoIterator<Item = String>) -> anyhow::Result<()> {
+        let mut contents = String::new();
+        contents.push_str(self.version);
+        contents.push('\n');
+        Self::push_records(&mut contents, records);
+        fs_util::write_atomic(&self.path, contents)
+    }
+
+    /// Adds records after the saved ones, without rewriting them. Starts a new file if there is
+    /// none or it has another version.
+    pub fn append(&self, records: impl IntoIterator<Item = String>) -> anyhow::Result<()> {
+        let mut file = match OpenOptions::new().read(true).append(true).open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return self.save(records),
+            Err(e) => return Err(e).with_context(|| format!("open({})", self.path)),
+        };
+        let mut version = String::new();
+        BufReader::new(&file)
+            .read_line(&mut version)
+            .with_context(|| format!("read_line({})", self.path))?;
+        if version.strip_suffix('\n') != Some(self.version) {
+            return self.save(records);
+        }
+
+        let mut contents = String::new();
+        Self::push_records(&mut contents, records);
+        file.write_all(contents.as_bytes())
+            .with_context(|| format!("write_all({}, _)", self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = AbsNormPath::new(dir.path())?.join(ForwardRelativePath::new("records")?);
+        let records = PersistedRecords::new(&dir, "file", "v1")?;
+        assert_eq!(Vec::<String>::new(), records.load(|r| Some(r.to_owned()))?);
+
+        records.save(["a".to_owned(), "b\nc".to_owned(), "1".to_owned()])?;
+        assert_eq!(vec!["a", "1"], records.load(|r| Some(r.to_owned()))?);
+        assert_eq!(vec![1], records.load(|r| r.parse::<u32>().ok())?);
+
+        records.append(["2".to_owned(), "c\nd".to_owned()])?;
+        assert_eq!(vec![1, 2], records.load(|r| r.parse::<u32>().ok())?);
+
+        // A record that was only partially appended is dropped.
+        fs_util::write(dir.join(ForwardRelativePath::new("file")?), "v1\n1\n23")?;
+        assert_eq!(vec![1], records.load(|r| r.parse::<u32>().ok())?);
+
+        // Records of another version are dropped.
+        let records = PersistedRecords::new(&dir, "file", "v2")?;
+        assert_eq!(Vec::<String>::new(), records.load(|r| Some(r.to_owned()))?);
+        records.append(["3".to_owned()])?;
+        assert_eq!(vec![3], records.load(|r| r.parse::<u32>().ok())?);
+        Ok(())
+    }
+}
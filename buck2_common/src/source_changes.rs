@@ -0,0 +1,222 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A log of the source files that changed while commands were running.
+//!
+//! DICE only learns about changes to source files when a command starts and syncs the file
+//! watcher, so a file modified while a command runs is still assumed to have its old contents.
+//! File watchers record changes here, either as they receive them or when the log polls them,
+//! which lets executors detect commands that might have read a source half-way through a change.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use allocative::Allocative;
+use async_trait::async_trait;
+use buck2_core::cells::cell_path::CellPath;
+
+/// Records the changes a file watcher only learns about when it asks for them.
+#[async_trait]
+pub trait PollSourceChanges: Send + Sync + 'static {
+    /// Record into `log` the changes made since the last poll.
+    async fn poll(&self, log: &SourceChangeLog) -> anyhow::Result<()>;
+}
+
+#[derive(Default)]
+struct SourceChangeLogData {
+    clock: u64,
+    /// The changes recorded since the oldest watch started, in the order they were recorded.
+    changes: Vec<(u64, CellPath)>,
+    /// How many watches started at each clock.
+    watches: BTreeMap<u64, usize>,
+}
+
+impl SourceChangeLogData {
+    /// Drop the changes that no watch can see anymore.
+    fn prune(&mut self) {
+        match self.watches.keys().next() {
+            Some(&oldest) => {
+                let seen = self.changes.partition_point(|(clock, _)| *clock <= oldest);
+                self.changes.drain(..seen);
+            }
+            None => self.changes.clear(),
+        }
+    }
+}
+
+#[derive(Default, Allocative)]
+pub struct SourceChangeLog {
+    #[allocative(skip)]
+    data: Mutex<SourceChangeLogData>,
+    #[allocative(skip)]
+    poll: Option<Box<dyn PollSourceChanges>>,
+}
+
+impl SourceChangeLog {
+    /// A log the file watcher records changes into as it receives them.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A log that polls the file watcher for changes when a watch starts or is checked.
+    pub fn with_poll(poll: Box<dyn PollSourceChanges>) -> Self {
+        Self {
+            data: Mutex::default(),
+            poll: Some(poll),
+        }
+    }
+
+    async fn poll(&self) -> anyhow::Result<()> {
+        match &self.poll {
+            Some(poll) => poll.poll(self).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Record that the file or directory at `path` changed.
+    pub fn record(&self, path: CellPath) {
+        let mut data = self.data.lock().unwrap();
+        if data.watches.is_empty() {
+            // Nobody could see this change.
+            return;
+        }
+        data.clock += 1;
+        let clock = data.clock;
+        data.changes.push((clock, path));
+    }
+
+    /// Start watching for changes, e.g. before running a command. The changes are kept for as
+    /// long as a watch that started before them exists, regardless of the file watcher syncs.
+    pub async fn watch(&self) -> anyhow::Result<SourceChangeWatch<'_>> {
+        self.poll().await?;
+        let mut data = self.data.lock().unwrap();
+        let since = data.clock;
+        *data.watches.entry(since).or_default() += 1;
+        Ok(SourceChangeWatch { log: self, since })
+    }
+}
+
+/// The changes made since the watch started.
+pub struct SourceChangeWatch<'a> {
+    log: &'a SourceChangeLog,
+    since: u64,
+}
+
+impl<'a> SourceChangeWatch<'a> {
+    /// A path that changed since the watch started and is one of `paths`, or is inside one of
+    /// them.
+    pub async fn changed(&self, paths: &[CellPath]) -> anyhow::Result<Option<CellPath>> {
+        self.log.poll().await?;
+        let data = self.log.data.lock().unwrap();
+        let start = data
+            .changes
+            .partition_point(|(clock, _)| *clock <= self.since);
+        let changes = &data.changes[start..];
+        Ok(paths.iter().find_map(|path| {
+            changes
+                .iter()
+                .find(|(_, changed)| changed.starts_with(path))
+                .map(|(_, changed)| changed.clone())
+        }))
+    }
+}
+
+impl<'a> Drop for SourceChangeWatch<'a> {
+    fn drop(&mut self) {
+        let mut data = self.log.data.lock().unwrap();
+        if let Some(count) = data.watches.get_mut(&self.since) {
+            *count -= 1;
+            if *count == 0 {
+                data.watches.remove(&self.since);
+            }
+        }
+        data.prune();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use gazebo::prelude::*;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_changed() -> anyhow::Result<()> {
+        let log = SourceChangeLog::new();
+        let foo = CellPath::testing_new("root", "foo");
+        let bar = CellPath::testing_new("root", "bar");
+        let paths = [foo.clone(), bar.clone()];
+
+        // Nobody is watching.
+        log.record(CellPath::testing_new("root", "foo/a.txt"));
+        let watch = log.watch().await?;
+        assert_eq!(watch.changed(&paths).await?, None);
+
+        log.record(CellPath::testing_new("root", "baz/b.txt"));
+        assert_eq!(watch.changed(&paths).await?, None);
+
+        log.record(bar.clone());
+        assert_eq!(watch.changed(&paths).await?, Some(bar.clone()));
+        assert_eq!(log.watch().await?.changed(&paths).await?, None);
+
+        drop(watch);
+        assert!(log.data.lock().unwrap().changes.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_changes_kept_for_older_watches() -> anyhow::Result<()> {
+        let log = SourceChangeLog::new();
+        let foo = CellPath::testing_new("root", "foo");
+        let paths = [foo.clone()];
+
+        let old = log.watch().await?;
+        log.record(foo.clone());
+        let new = log.watch().await?;
+        log.record(CellPath::testing_new("root", "bar"));
+
+        // The change the old watch still needs is only dropped once it's gone.
+        drop(new);
+        assert_eq!(old.changed(&paths).await?, Some(foo.clone()));
+        drop(old);
+        assert!(log.data.lock().unwrap().changes.is_empty());
+        Ok(())
+    }
+
+    /// Records the pending changes when polled.
+    struct PollPending(Arc<Mutex<Vec<CellPath>>>);
+
+    #[async_trait]
+    impl PollSourceChanges for PollPending {
+        async fn poll(&self, log: &SourceChangeLog) -> anyhow::Result<()> {
+            for path in self.0.lock().unwrap().drain(..) {
+                log.record(path);
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_poll() -> anyhow::Result<()> {
+        let foo = CellPath::testing_new("root", "foo");
+        let paths = [foo.clone()];
+        let pending = Arc::new(Mutex::new(vec![foo.clone()]));
+        let log = SourceChangeLog::with_poll(box PollPending(pending.dupe()));
+
+        // Changes made before the watch started aren't seen, even if they weren't polled yet.
+        let watch = log.watch().await?;
+        assert_eq!(watch.changed(&paths).await?, None);
+
+        pending.lock().unwrap().push(foo.clone());
+        assert_eq!(watch.changed(&paths).await?, Some(foo.clone()));
+        Ok(())
+    }
+}
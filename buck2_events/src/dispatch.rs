@@ -34,9 +34,12 @@ use futures::future;
 use futures::Future;
 use gazebo::prelude::*;
 
+use crate::resource_usage::ActionResourceUsage;
 use crate::sink::null::NullEventSink;
 use crate::span::SpanId;
 use crate::trace::TraceId;
+use crate::warnings;
+use crate::warnings::CommandWarnings;
 use crate::BuckEvent;
 use crate::ControlEvent;
 use crate::EventSink;
@@ -50,6 +53,10 @@ pub struct EventDispatcher {
     /// The sink to log events to.
     #[allocative(skip)] // TODO(nga): do not skip.
     sink: Arc<dyn EventSink>,
+    /// The warnings emitted through this dispatcher.
+    warnings: Arc<CommandWarnings>,
+    /// The resources used by the local commands of the actions executed during this command.
+    action_resource_usage: Arc<ActionResourceUsage>,
 }
 
 impl EventDispatcher {
@@ -58,6 +65,8 @@ impl EventDispatcher {
         EventDispatcher {
             trace_id,
             sink: Arc::new(sink),
+            warnings: Default::default(),
+            action_resource_usage: Default::default(),
         }
     }
 
@@ -66,6 +75,8 @@ impl EventDispatcher {
         EventDispatcher {
             trace_id: TraceId::null(),
             sink: Arc::new(NullEventSink::new()),
+            warnings: Default::default(),
+            action_resource_usage: Default::default(),
         }
     }
 
@@ -74,6 +85,8 @@ impl EventDispatcher {
         EventDispatcher {
             trace_id,
             sink: Arc::new(NullEventSink::new()),
+            warnings: Default::default(),
+            action_resource_usage: Default::default(),
         }
     }
 
@@ -94,6 +107,43 @@ impl EventDispatcher {
         self.instant_event(buck2_data::ConsoleMessage { message })
     }
 
+    /// Emits a warning, unless the same warning was already emitted during this command.
+    pub fn warning(
+        &self,
+        category: buck2_data::WarningCategory,
+        location: String,
+        message: String,
+    ) {
+        let warning = buck2_data::StructuredWarning {
+            category: category as i32,
+            location,
+            message,
+        };
+        warnings::capture(&warning);
+        if self.warnings.add(&warning) {
+            self.instant_event(warning)
+        }
+    }
+
+    /// Emits a warning recorded by a computation whose value this command uses, unless it was
+    /// already emitted during this command.
+    pub(crate) fn replay_warning(&self, warning: &buck2_data::StructuredWarning) {
+        warnings::capture(warning);
+        if self.warnings.add_replayed(warning) {
+            self.instant_event(warning.clone())
+        }
+    }
+
+    /// The warnings emitted during this command.
+    pub fn warnings(&self) -> &CommandWarnings {
+        &self.warnings
+    }
+
+    /// The resources used by the local commands of the actions executed during this command.
+    pub fn action_resource_usage(&self) -> &ActionResourceUsage {
+        &self.action_resource_usage
+    }
+
     fn event_with_span_id<E: Into<buck_event::Data>>(
         &self,
         data: E,
@@ -380,6 +430,11 @@ pub fn console_message(message: String) {
     get_dispatcher().console_message(message)
 }
 
+/// Emits a warning from the server, see `EventDispatcher::warning`.
+pub fn warning(category: buck2_data::WarningCategory, location: String, message: String) {
+    get_dispatcher().warning(category, location, message)
+}
+
 // Logs mercurial data
 pub async fn instant_hg() {
     get_dispatcher().instant_hg().await
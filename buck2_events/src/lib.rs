@@ -28,10 +28,12 @@
 
 pub mod dispatch;
 pub mod metadata;
+pub mod resource_usage;
 pub mod sink;
 pub mod source;
 pub mod span;
 pub mod trace;
+pub mod warnings;
 
 use std::num::NonZeroU64;
 use std::time::SystemTime;
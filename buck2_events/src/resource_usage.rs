@@ -0,0 +1,75 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! The resources used by the local commands of the actions executed during a command, summed up
+//! from the action results so that they only cover this command, whichever way the commands ran.
+
+use std::sync::Mutex;
+
+use allocative::Allocative;
+
+/// The totals of the resources used by the local commands that ran during a command.
+#[derive(Default, Allocative)]
+pub struct ActionResourceUsage {
+    #[allocative(skip)]
+    inner: Mutex<buck2_data::CommandResourceUsage>,
+}
+
+impl ActionResourceUsage {
+    /// Records the resources used by a local command.
+    pub fn add(&self, usage: &buck2_data::CommandResourceUsage) {
+        let mut total = self.inner.lock().unwrap();
+        total.user_cpu_us += usage.user_cpu_us;
+        total.system_cpu_us += usage.system_cpu_us;
+        // Only reported on some platforms, which applies to all the commands.
+        if let Some(bytes) = usage.disk_read_bytes {
+            *total.disk_read_bytes.get_or_insert(0) += bytes;
+        }
+        if let Some(bytes) = usage.disk_write_bytes {
+            *total.disk_write_bytes.get_or_insert(0) += bytes;
+        }
+    }
+
+    pub fn total(&self) -> buck2_data::CommandResourceUsage {
+        self.inner.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_total() {
+        let usage = ActionResourceUsage::default();
+        assert_eq!(buck2_data::CommandResourceUsage::default(), usage.total());
+
+        usage.add(&buck2_data::CommandResourceUsage {
+            user_cpu_us: 10,
+            system_cpu_us: 1,
+            disk_read_bytes: Some(512),
+            disk_write_bytes: None,
+        });
+        usage.add(&buck2_data::CommandResourceUsage {
+            user_cpu_us: 5,
+            system_cpu_us: 2,
+            disk_read_bytes: Some(1024),
+            disk_write_bytes: None,
+        });
+        assert_eq!(
+            buck2_data::CommandResourceUsage {
+                user_cpu_us: 15,
+                system_cpu_us: 3,
+                disk_read_bytes: Some(1536),
+                disk_write_bytes: None,
+            },
+            usage.total()
+        );
+    }
+}
@@ -239,6 +239,8 @@ mod fbcode {
                     Some(Data::Materialization(..)) => false,
                     Some(Data::DiceCriticalSection(_)) => false,
                     Some(Data::DiceBlockConcurrentCommand(_)) => false,
+                    Some(Data::ResolveTargetPatterns(..)) => false,
+                    Some(Data::ConfigureTarget(..)) => false,
                     Some(Data::Fake(..)) => false,
                     None => false,
                 }
@@ -269,6 +271,8 @@ mod fbcode {
                     Some(Data::Materialization(..)) => true, // used in MaterializationProcessor
                     Some(Data::DiceCriticalSection(_)) => false,
                     Some(Data::DiceBlockConcurrentCommand(_)) => false,
+                    Some(Data::ResolveTargetPatterns(..)) => false,
+                    Some(Data::ConfigureTarget(..)) => false,
                     Some(Data::Fake(..)) => true,
                     None => false,
                 }
@@ -280,6 +284,7 @@ mod fbcode {
                     Some(Data::RawOutput(..)) => false,
                     Some(Data::Snapshot(..)) => false,
                     Some(Data::DiceStateSnapshot(..)) => false,
+                    Some(Data::ActionCategoryStatsSnapshot(..)) => false,
                     Some(Data::LspResult(..)) => false,
                     Some(Data::DiceEqualityCheck(..)) => false,
                     Some(Data::NoActiveDiceState(..)) => false,
@@ -0,0 +1,265 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Warnings from loading, analysis and execution. Rather than being printed to stderr as they
+//! happen, they are deduplicated within a command, summarized by the client at the end of the
+//! command and included in the build report.
+//!
+//! Loading, analysis and execution are cached across commands, so the warnings emitted while
+//! computing them are kept with their values, see [`WithWarnings`], and emitted again by the
+//! commands that reuse the values.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use allocative::Allocative;
+use gazebo::prelude::*;
+
+use crate::dispatch::EventDispatcher;
+
+/// A human readable name for a warning category.
+pub fn category_name(category: buck2_data::WarningCategory) -> &'static str {
+    match category {
+        buck2_data::WarningCategory::Other => "other",
+        buck2_data::WarningCategory::Deprecated => "deprecated",
+        buck2_data::WarningCategory::PackageBoundary => "package boundary",
+        buck2_data::WarningCategory::SlowGlob => "slow glob",
+        buck2_data::WarningCategory::ActionQuota => "action quota",
+        buck2_data::WarningCategory::NondeterministicAction => "nondeterministic action",
+        buck2_data::WarningCategory::SourceChangedDuringAction => "source changed during action",
+        buck2_data::WarningCategory::NondeterministicAnalysis => "nondeterministic analysis",
+    }
+}
+
+#[derive(Default)]
+struct CommandWarningsInner {
+    /// Index in `warnings` by category, location and message.
+    index: HashMap<(i32, String, String), usize>,
+    warnings: Vec<(buck2_data::StructuredWarning, u64)>,
+}
+
+/// The warnings emitted during a command.
+#[derive(Default, Allocative)]
+pub struct CommandWarnings {
+    #[allocative(skip)]
+    inner: Mutex<CommandWarningsInner>,
+}
+
+impl CommandWarnings {
+    /// Records a warning. Returns whether it's the first time this warning was emitted.
+    pub fn add(&self, warning: &buck2_data::StructuredWarning) -> bool {
+        self.add_impl(warning, 1)
+    }
+
+    /// Records a warning replayed from a cached computation, which doesn't count as being
+    /// emitted again if this command already emitted it. Returns whether it's the first time
+    /// this warning was emitted.
+    pub(crate) fn add_replayed(&self, warning: &buck2_data::StructuredWarning) -> bool {
+        self.add_impl(warning, 0)
+    }
+
+    fn add_impl(&self, warning: &buck2_data::StructuredWarning, count: u64) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let key = (
+            warning.category,
+            warning.location.clone(),
+            warning.message.clone(),
+        );
+        match inner.index.get(&key) {
+            Some(&i) => {
+                inner.warnings[i].1 += count;
+                false
+            }
+            None => {
+                let i = inner.warnings.len();
+                inner.warnings.push((warning.clone(), 1));
+                inner.index.insert(key, i);
+                true
+            }
+        }
+    }
+
+    /// The distinct warnings in the order they were first emitted, with how many times each was
+    /// emitted.
+    pub fn warnings(&self) -> Vec<(buck2_data::StructuredWarning, u64)> {
+        self.inner.lock().unwrap().warnings.clone()
+    }
+}
+
+type Captured = Arc<Mutex<Vec<buck2_data::StructuredWarning>>>;
+
+tokio::task_local! {
+    /// The warnings emitted by the computation running on this task, see [`WithWarnings`].
+    static CAPTURED: Captured;
+}
+
+/// Called for every warning emitted or replayed, to record it for the computation running on
+/// this task.
+pub(crate) fn capture(warning: &buck2_data::StructuredWarning) {
+    let _ignored = CAPTURED.try_with(|captured| {
+        let mut captured = captured.lock().unwrap();
+        if !captured.contains(warning) {
+            captured.push(warning.clone());
+        }
+    });
+}
+
+/// Wraps a future spawned by the computation running on this task, so that the warnings it emits
+/// are recorded for that computation too.
+pub fn propagate_capture<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+    let captured = CAPTURED.try_with(|captured| captured.dupe()).ok();
+    async move {
+        match captured {
+            Some(captured) => CAPTURED.scope(captured, fut).await,
+            None => fut.await,
+        }
+    }
+}
+
+/// The value of a computation cached across commands, with the warnings emitted by its
+/// computation, including those replayed by the computations it depends on, since those aren't
+/// requested again while its value is reused. The warnings are dropped with the value.
+#[derive(Clone, Dupe, Allocative)]
+pub struct WithWarnings<T> {
+    pub value: T,
+    #[allocative(skip)]
+    pub warnings: Arc<[buck2_data::StructuredWarning]>,
+}
+
+impl<T> WithWarnings<T> {
+    /// A value computed without warnings.
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            warnings: Arc::new([]),
+        }
+    }
+
+    /// Runs `computation`, recording the warnings it emits.
+    pub async fn compute<F: Future<Output = T>>(computation: F) -> Self {
+        let captured = Captured::default();
+        let value = CAPTURED.scope(captured.dupe(), computation).await;
+        let warnings = captured.lock().unwrap().drain(..).collect();
+        Self { value, warnings }
+    }
+
+    /// Emits the warnings again, for a command that uses the value. Warnings this command already
+    /// emitted are skipped, so this is called whether or not the value was just computed.
+    pub fn replay(&self, dispatcher: &EventDispatcher) {
+        for warning in self.warnings.iter() {
+            dispatcher.replay_warning(warning);
+        }
+    }
+
+    /// Replays the warnings and returns the value.
+    pub fn into_replayed(self, dispatcher: &EventDispatcher) -> T {
+        self.replay(dispatcher);
+        self.value
+    }
+
+    pub fn same_warnings(&self, other: &Self) -> bool {
+        self.warnings == other.warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warning(
+        category: buck2_data::WarningCategory,
+        message: &str,
+    ) -> buck2_data::StructuredWarning {
+        buck2_data::StructuredWarning {
+            category: category as i32,
+            location: "root//foo".to_owned(),
+            message: message.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_deduplicates() {
+        let warnings = CommandWarnings::default();
+        let glob = warning(buck2_data::WarningCategory::SlowGlob, "slow");
+        let deprecated = warning(buck2_data::WarningCategory::Deprecated, "slow");
+        assert!(warnings.add(&glob));
+        assert!(warnings.add(&deprecated));
+        assert!(!warnings.add(&glob));
+        assert_eq!(vec![(glob, 2), (deprecated, 1)], warnings.warnings());
+    }
+
+    #[tokio::test]
+    async fn test_with_warnings() {
+        let glob = warning(buck2_data::WarningCategory::SlowGlob, "slow");
+        let deprecated = warning(buck2_data::WarningCategory::Deprecated, "old");
+
+        let first = EventDispatcher::null();
+        let foo = WithWarnings::compute(async {
+            first.warning(
+                buck2_data::WarningCategory::SlowGlob,
+                "root//foo".to_owned(),
+                "slow".to_owned(),
+            );
+            // Replayed from a dependency.
+            let bar = WithWarnings::compute(async {
+                first.warning(
+                    buck2_data::WarningCategory::Deprecated,
+                    "root//foo".to_owned(),
+                    "old".to_owned(),
+                );
+            })
+            .await;
+            bar.replay(&first);
+            assert_eq!(vec![deprecated.clone()], &*bar.warnings);
+        })
+        .await;
+        foo.replay(&first);
+        assert_eq!(
+            vec![(glob.clone(), 1), (deprecated.clone(), 1)],
+            first.warnings().warnings()
+        );
+
+        // A later command reusing the value gets the warnings of both computations.
+        let second = EventDispatcher::null();
+        foo.replay(&second);
+        assert_eq!(
+            vec![(glob, 1), (deprecated, 1)],
+            second.warnings().warnings()
+        );
+
+        // Computing it again without warnings forgets them.
+        let foo = WithWarnings::compute(async {}).await;
+        assert!(foo.warnings.is_empty());
+        assert!(foo.same_warnings(&WithWarnings::new(())));
+    }
+
+    #[tokio::test]
+    async fn test_captures_spawned_tasks() {
+        let dispatcher = EventDispatcher::null();
+        let computed = WithWarnings::compute(async {
+            let dispatcher = dispatcher.dupe();
+            tokio::spawn(propagate_capture(async move {
+                dispatcher.warning(
+                    buck2_data::WarningCategory::SlowGlob,
+                    "root//foo".to_owned(),
+                    "slow".to_owned(),
+                );
+            }))
+            .await
+            .unwrap();
+        })
+        .await;
+        assert_eq!(
+            vec![warning(buck2_data::WarningCategory::SlowGlob, "slow")],
+            &*computed.warnings
+        );
+    }
+}
@@ -7,7 +7,10 @@
  * of this source tree.
  */
 
+use std::any::Any;
 use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::fmt::Debug;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::sync::Arc;
@@ -21,9 +24,84 @@ use buck2_data::ToProtoMessage;
 use buck2_node::attrs::configured_attr::ConfiguredAttr;
 use buck2_node::rule_type::StarlarkRuleType;
 use derive_more::Display;
+use gazebo::cmp::PartialEqAny;
+use gazebo::prelude::*;
 
+use crate::artifact::artifact_dyn::ArtifactDyn;
+
+trait AnonTargetArtifactDyn: ArtifactDyn + Debug + Allocative {
+    fn as_any(&self) -> &dyn Any;
+
+    fn eq_token(&self) -> PartialEqAny;
+
+    fn hash_dyn(&self, state: &mut dyn Hasher);
+}
+
+impl<A: ArtifactDyn + Debug + Allocative + Hash + Eq> AnonTargetArtifactDyn for A {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_token(&self) -> PartialEqAny {
+        PartialEqAny::new(self)
+    }
+
+    fn hash_dyn(&self, mut state: &mut dyn Hasher) {
+        self.hash(&mut state)
+    }
+}
+
+/// An artifact passed to an anon target as the value of a source attribute.
+///
+/// Artifacts are defined in a crate that depends on this one, so they are stored type-erased, but
+/// compare and hash as the artifact they hold: the path of an artifact doesn't identify it, e.g. a
+/// projected artifact and an artifact can have the same path.
+#[derive(Clone, Dupe, Allocative)]
+pub struct AnonTargetArtifact(Arc<dyn AnonTargetArtifactDyn>);
+
+impl AnonTargetArtifact {
+    pub fn new<A: ArtifactDyn + Debug + Allocative + Hash + Eq>(artifact: A) -> Self {
+        Self(Arc::new(artifact))
+    }
+
+    pub fn downcast_ref<A: 'static>(&self) -> Option<&A> {
+        self.0.as_any().downcast_ref()
+    }
+}
+
+impl Debug for AnonTargetArtifact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.0, f)
+    }
+}
+
+impl Hash for AnonTargetArtifact {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash_dyn(state)
+    }
+}
+
+impl PartialEq for AnonTargetArtifact {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq_token() == other.0.eq_token()
+    }
+}
+
+impl Eq for AnonTargetArtifact {}
+
+/// The value of a source attribute of an anon target, which can only be given artifacts.
+#[derive(Hash, Eq, PartialEq, Clone, Debug, Allocative)]
+pub enum AnonTargetSource {
+    /// An `attr.source()`, or an `attr.option(attr.source())` that is set.
+    Artifact(AnonTargetArtifact),
+    /// An `attr.list(attr.source())`.
+    List(Box<[AnonTargetArtifact]>),
+}
+
+/// Displayed as its name and hash, e.g. `anon//:my_rule@0123456789abcdef`, like the console
+/// does, since anon targets with the same name but different attributes are distinct.
 #[derive(Hash, Eq, PartialEq, Clone, Debug, Display, Allocative)]
-#[display(fmt = "{:?}", self)]
+#[display(fmt = "{}@{}", name, hash)]
 pub struct AnonTarget {
     /// Not necessarily a "real" target label that actually exists, but could be.
     name: TargetLabel,
@@ -32,7 +110,10 @@ pub struct AnonTarget {
     /// The attributes the target was defined with.
     /// We use a sorted map since we want to iterate in a defined order.
     attrs: SortedMap<String, ConfiguredAttr>,
-    /// The hash of the `rule_type` and `attrs`
+    /// The source attributes the target was defined with, which aren't part of `attrs`, because
+    /// attributes can't hold artifacts.
+    sources: SortedMap<String, AnonTargetSource>,
+    /// The hash of the `rule_type`, `attrs` and `sources`
     hash: String,
     /// The execution configuration - same as the parent.
     exec_cfg: Configuration,
@@ -51,12 +132,17 @@ impl ToProtoMessage for AnonTarget {
 }
 
 impl AnonTarget {
-    fn mk_hash(rule_type: &StarlarkRuleType, attrs: &SortedMap<String, ConfiguredAttr>) -> String {
+    fn mk_hash(
+        rule_type: &StarlarkRuleType,
+        attrs: &SortedMap<String, ConfiguredAttr>,
+        sources: &SortedMap<String, AnonTargetSource>,
+    ) -> String {
         // This is the same hasher as we use for Configuration, so is probably fine.
         // But quite possibly should be a crypto hasher in future.
         let mut hasher = DefaultHasher::new();
         rule_type.hash(&mut hasher);
         attrs.hash(&mut hasher);
+        sources.hash(&mut hasher);
         format!("{:x}", hasher.finish())
     }
 
@@ -64,13 +150,15 @@ impl AnonTarget {
         rule_type: Arc<StarlarkRuleType>,
         name: TargetLabel,
         attrs: SortedMap<String, ConfiguredAttr>,
+        sources: SortedMap<String, AnonTargetSource>,
         exec_cfg: Configuration,
     ) -> Self {
-        let hash = Self::mk_hash(&rule_type, &attrs);
+        let hash = Self::mk_hash(&rule_type, &attrs, &sources);
         Self {
             name,
             rule_type,
             attrs,
+            sources,
             hash,
             exec_cfg,
         }
@@ -88,6 +176,10 @@ impl AnonTarget {
         &self.attrs
     }
 
+    pub fn sources(&self) -> &SortedMap<String, AnonTargetSource> {
+        &self.sources
+    }
+
     pub fn rule_type_attrs_hash(&self) -> &str {
         &self.hash
     }
@@ -102,3 +194,123 @@ impl AnonTarget {
         self.name().configure(Configuration::unspecified())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hash;
+    use std::hash::Hasher;
+    use std::sync::Arc;
+
+    use allocative::Allocative;
+    use buck2_core::bzl::ImportPath;
+    use buck2_core::collections::sorted_map::SortedMap;
+    use buck2_core::configuration::Configuration;
+    use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+    use buck2_core::target::testing::TargetLabelExt;
+    use buck2_core::target::TargetLabel;
+    use buck2_node::rule_type::StarlarkRuleType;
+    use either::Either;
+    use gazebo::cell::ARef;
+
+    use crate::anon_target::AnonTarget;
+    use crate::anon_target::AnonTargetArtifact;
+    use crate::anon_target::AnonTargetSource;
+    use crate::artifact::artifact_dyn::ArtifactDyn;
+    use crate::base_deferred_key::BaseDeferredKey;
+    use crate::path::artifact_path::ArtifactPath;
+    use crate::path::buck_out_path::BuckOutPath;
+
+    /// An output of the action with the given index.
+    #[derive(Debug, Hash, Eq, PartialEq, Allocative)]
+    struct TestArtifact {
+        path: BuckOutPath,
+        action: u32,
+    }
+
+    impl TestArtifact {
+        fn new(path: &str, action: u32) -> Self {
+            Self {
+                path: BuckOutPath::new(
+                    BaseDeferredKey::TargetLabel(
+                        TargetLabel::testing_parse("root//foo:bar")
+                            .configure(Configuration::testing_new()),
+                    ),
+                    ForwardRelativePathBuf::unchecked_new(path.to_owned()),
+                ),
+                action,
+            }
+        }
+    }
+
+    impl ArtifactDyn for TestArtifact {
+        fn get_path(&self) -> ArtifactPath {
+            ArtifactPath {
+                base_path: Either::Left(ARef::new_ptr(&self.path)),
+                projected_path: None,
+            }
+        }
+
+        fn is_source(&self) -> bool {
+            false
+        }
+    }
+
+    fn hash(x: &impl Hash) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        x.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_display() {
+        let anon = AnonTarget::new(
+            Arc::new(StarlarkRuleType {
+                import_path: ImportPath::unchecked_new("root", "foo", "rules.bzl"),
+                name: "my_rule".to_owned(),
+            }),
+            TargetLabel::testing_parse("anon//:my_rule"),
+            SortedMap::default(),
+            SortedMap::default(),
+            Configuration::testing_new(),
+        );
+        let hash = anon.rule_type_attrs_hash().to_owned();
+        assert!(!hash.is_empty());
+        assert_eq!(format!("anon//:my_rule@{}", hash), anon.to_string());
+        assert_eq!(
+            format!("anon//:my_rule@{}", hash),
+            BaseDeferredKey::AnonTarget(Arc::new(anon)).to_string()
+        );
+    }
+
+    #[test]
+    fn test_artifact_eq_and_hash() {
+        let artifact = AnonTargetArtifact::new(TestArtifact::new("out.txt", 0));
+        let same = AnonTargetArtifact::new(TestArtifact::new("out.txt", 0));
+        assert_eq!(artifact, same);
+        assert_eq!(hash(&artifact), hash(&same));
+
+        // The same path, bound to another action.
+        let other_action = AnonTargetArtifact::new(TestArtifact::new("out.txt", 1));
+        assert_ne!(artifact, other_action);
+        assert_ne!(hash(&artifact), hash(&other_action));
+
+        let other_path = AnonTargetArtifact::new(TestArtifact::new("other.txt", 0));
+        assert_ne!(artifact, other_path);
+        assert_ne!(hash(&artifact), hash(&other_path));
+    }
+
+    #[test]
+    fn test_source_eq_and_hash() {
+        let artifact = || AnonTargetArtifact::new(TestArtifact::new("out.txt", 0));
+        let single = AnonTargetSource::Artifact(artifact());
+        let list = AnonTargetSource::List(vec![artifact()].into_boxed_slice());
+        assert_eq!(single, AnonTargetSource::Artifact(artifact()));
+        assert_eq!(
+            hash(&list),
+            hash(&AnonTargetSource::List(vec![artifact()].into_boxed_slice()))
+        );
+        assert_ne!(single, list);
+        assert_ne!(hash(&single), hash(&list));
+    }
+}
@@ -12,7 +12,9 @@ use std::sync::Arc;
 use allocative::Allocative;
 use anyhow::Context as _;
 use async_trait::async_trait;
+use buck2_common::legacy_configs::LegacyBuckConfig;
 use buck2_core::env_helper::EnvHelper;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
 use buck2_core::fs::project::ProjectRoot;
 use crossbeam_channel::unbounded;
 use dice::DiceComputations;
@@ -91,11 +93,41 @@ impl BuckBlockingExecutor {
     /// they are trying to write, and writing to multiple files doesn't have the negative scaling
     /// issues modifying the directory structure does.
     pub fn default_concurrency(fs: ProjectRoot) -> anyhow::Result<Self> {
+        Self::with_concurrency(fs, None, None)
+    }
+
+    /// Like `default_concurrency`, but lets the concurrency be overridden via buckconfig
+    /// (`[buck2] io_threads` and `[buck2] io_semaphore`). If `io_threads` is not set, we check
+    /// whether the disk holding the project is a rotational one, and if so only use a single
+    /// thread, since concurrent directory modifications on those thrash the disk head.
+    ///
+    /// The environment variables take precedence over the buckconfig.
+    pub fn from_config(fs: ProjectRoot, config: &LegacyBuckConfig) -> anyhow::Result<Self> {
+        let io_threads = match config.parse::<usize>("buck2", "io_threads")? {
+            Some(v) => Some(v),
+            None if is_rotational_disk(fs.root()) == Some(true) => {
+                tracing::info!("Detected rotational disk, using a single I/O thread");
+                Some(1)
+            }
+            None => None,
+        };
+        let io_semaphore = config.parse::<usize>("buck2", "io_semaphore")?;
+        Self::with_concurrency(fs, io_threads, io_semaphore)
+    }
+
+    fn with_concurrency(
+        fs: ProjectRoot,
+        io_threads: Option<usize>,
+        io_semaphore: Option<usize>,
+    ) -> anyhow::Result<Self> {
         static IO_THREADS: EnvHelper<usize> = EnvHelper::new("BUCK2_IO_THREADS");
         static IO_SEMAPHORE: EnvHelper<usize> = EnvHelper::new("BUCK2_IO_SEMAPHORE");
 
-        let io_threads = IO_THREADS.get_copied()?.unwrap_or(4);
-        let io_semaphore = IO_SEMAPHORE.get_copied()?.unwrap_or_else(num_cpus::get);
+        let io_threads = IO_THREADS.get_copied()?.or(io_threads).unwrap_or(4);
+        let io_semaphore = IO_SEMAPHORE
+            .get_copied()?
+            .or(io_semaphore)
+            .unwrap_or_else(num_cpus::get);
 
         let (command_sender, command_receiver) = unbounded();
 
@@ -151,6 +183,34 @@ impl BlockingExecutor for BuckBlockingExecutor {
     }
 }
 
+/// Whether the block device holding `path` is a rotational disk. Returns `None` if we can't
+/// tell.
+#[cfg(target_os = "linux")]
+fn is_rotational_disk(path: &AbsNormPath) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (major, minor) = major_minor(std::fs::metadata(path).ok()?.dev());
+    // For partitions, the `queue` directory lives on the parent device.
+    let sys = format!("/sys/dev/block/{}:{}", major, minor);
+    let rotational = std::fs::read_to_string(format!("{}/queue/rotational", sys))
+        .or_else(|_| std::fs::read_to_string(format!("{}/../queue/rotational", sys)))
+        .ok()?;
+    Some(rotational.trim() == "1")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_rotational_disk(_path: &AbsNormPath) -> Option<bool> {
+    None
+}
+
+/// Decode a device number the same way glibc's `major` / `minor` do.
+#[cfg(any(target_os = "linux", test))]
+fn major_minor(dev: u64) -> (u64, u64) {
+    let major = ((dev & 0x0000_0000_000f_ff00) >> 8) | ((dev & 0xffff_f000_0000_0000) >> 32);
+    let minor = (dev & 0x0000_0000_0000_00ff) | ((dev & 0x0000_0fff_fff0_0000) >> 12);
+    (major, minor)
+}
+
 pub trait SetBlockingExecutor {
     fn set_blocking_executor(&mut self, exec: Arc<dyn BlockingExecutor>);
 }
@@ -201,3 +261,15 @@ pub mod testing {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::execute::blocking::major_minor;
+
+    #[test]
+    fn test_major_minor() {
+        assert_eq!(major_minor(0x0803), (8, 3));
+        // Both numbers spill into the high bits, as `makedev(0x12345, 0x6789ab)`.
+        assert_eq!(major_minor(0x0001_2006_7893_45ab), (0x12345, 0x6789ab));
+    }
+}
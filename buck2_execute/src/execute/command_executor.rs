@@ -19,6 +19,7 @@ use buck2_core::directory::DirectoryEntry;
 use buck2_core::directory::DirectoryIterator;
 use buck2_core::directory::FingerprintedDirectory;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRelativePathBuf;
 use gazebo::coerce::coerce;
 use gazebo::prelude::*;
 use remote_execution as RE;
@@ -128,7 +129,11 @@ impl CommandExecutor {
     ) -> ControlFlow<CommandExecutionResult, (CommandExecutionManager, ActionPaths, PreparedAction)>
     {
         let (action_paths, action) = match manager.stage(buck2_data::PrepareAction {}, || {
-            let action_paths = self.preamble(request.inputs(), request.outputs())?;
+            let action_paths = self.preamble(
+                request.inputs(),
+                request.outputs(),
+                request.diagnostic_outputs(),
+            )?;
             let input_digest = action_paths.inputs.fingerprint();
 
             let mut output_files = Vec::new();
@@ -143,6 +148,19 @@ impl CommandExecutor {
                     OutputType::Directory => output_dirs.push(output.as_str().to_owned()),
                 }
             }
+            // RE uploads the outputs of failed actions too, which is how we get at these.
+            for output in request.diagnostic_outputs() {
+                output_files.push(output.as_str().to_owned());
+            }
+
+            let platform = self.0.inner.re_platform().map(|platform| {
+                match request.remote_persistent_worker() {
+                    Some(worker) if self.0.inner.supports_remote_persistent_workers() => {
+                        worker.platform(platform)
+                    }
+                    _ => platform.clone(),
+                }
+            });
 
             let action_metadata_blobs = request.inputs().iter().filter_map(|x| match x {
                 CommandExecutionInput::Artifact(_) => None,
@@ -159,8 +177,10 @@ impl CommandExecutor {
                 input_digest,
                 action_metadata_blobs,
                 None,
-                self.0.inner.re_platform().cloned(),
+                platform,
                 false,
+                // Recorded in the action digest, so that the cache key reflects network access.
+                request.network().salt(),
             );
 
             anyhow::Ok((action_paths, action))
@@ -178,6 +198,7 @@ impl CommandExecutor {
         &self,
         inputs: &[CommandExecutionInput],
         outputs: impl Iterator<Item = CommandExecutionOutputRef<'a>>,
+        diagnostic_outputs: &[ProjectRelativePathBuf],
     ) -> anyhow::Result<ActionPaths> {
         let mut builder = inputs_directory(inputs, &self.0.artifact_fs)?;
 
@@ -192,6 +213,12 @@ impl CommandExecutor {
             })
             .collect::<anyhow::Result<Vec<_>>>()?;
 
+        for output in diagnostic_outputs {
+            if let Some(dir) = output.parent() {
+                builder.mkdir(dir)?;
+            }
+        }
+
         insert_entry(
             &mut builder,
             ForwardRelativePath::unchecked_new(".buckconfig"),
@@ -230,6 +257,7 @@ fn re_create_action(
     timeout: Option<&Duration>,
     platform: Option<RE::Platform>,
     do_not_cache: bool,
+    salt: Vec<u8>,
 ) -> PreparedAction {
     // A rust HashMap is in an arbitrary order, so sort first to get a better cache hit rate
     let mut environment = environment.iter().collect::<Vec<_>>();
@@ -261,6 +289,7 @@ fn re_create_action(
         command_digest: Some(prepared_blobs.add_protobuf_message(&command).to_grpc()),
         timeout,
         do_not_cache,
+        salt,
     };
 
     let action = prepared_blobs.add_protobuf_message(&action);
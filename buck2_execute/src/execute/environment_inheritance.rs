@@ -77,6 +77,26 @@ impl EnvironmentInheritance {
         }
     }
 
+    /// Clear the environment, and only pass through the variables in `allowlist`, with the value
+    /// they have at the time this is called. The values are leaked, so this is meant to be called
+    /// once, when the daemon starts.
+    pub fn scrubbed(allowlist: &[String]) -> Self {
+        let values = allowlist
+            .iter()
+            .filter_map(|key| {
+                let value = std::env::var_os(key)?;
+                let key: &'static str = Box::leak(key.clone().into_boxed_str());
+                Some((key, value))
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            clear: true,
+            values: Box::leak(values.into_boxed_slice()),
+            exclusions: &[],
+        }
+    }
+
     pub fn empty() -> Self {
         Self {
             values: &[],
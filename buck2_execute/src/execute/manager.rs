@@ -116,10 +116,15 @@ impl CommandExecutionManagerLike for CommandExecutionManager {
                 timing,
                 std_streams,
                 exit_code,
+                diagnostic_outputs: Vec::new(),
+                scratch_dir: None,
+                scratch_dir_bytes: 0,
+                resource_usage: None,
             },
             rejected_execution: None,
             did_cache_upload: false,
             eligible_for_full_hybrid: false,
+            input_bytes_uploaded: 0,
         }
     }
 }
@@ -205,10 +210,15 @@ impl CommandExecutionManagerLike for CommandExecutionManagerWithClaim {
                 timing,
                 std_streams,
                 exit_code,
+                diagnostic_outputs: Vec::new(),
+                scratch_dir: None,
+                scratch_dir_bytes: 0,
+                resource_usage: None,
             },
             rejected_execution: None,
             did_cache_upload: false,
             eligible_for_full_hybrid: false,
+            input_bytes_uploaded: 0,
         }
     }
 }
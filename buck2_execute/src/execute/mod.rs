@@ -18,7 +18,9 @@ pub mod environment_inheritance;
 pub mod inputs_directory;
 pub mod kind;
 pub mod manager;
+pub mod network;
 pub mod output;
+pub mod persistent_worker;
 pub mod prepared;
 pub mod request;
 pub mod result;
@@ -0,0 +1,83 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Whether a command may access the network.
+//!
+//! Commands that are denied network access have the policy recorded in the salt of their action,
+//! so that their action digest (and hence their cache key) differs from that of the same command
+//! allowed to use the network. The salt is part of the RE API, so unlike a platform property, it
+//! doesn't need the RE backend to know about it. Local execution enforces the policy on Linux by
+//! running the command in its own network namespace.
+
+use allocative::Allocative;
+use derive_more::Display;
+use gazebo::prelude::*;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+enum NetworkPolicyError {
+    #[error("Invalid network policy: `{0}`, expected `none` or `allowed`")]
+    InvalidPolicy(String),
+}
+
+#[derive(Debug, Display, Copy, Clone, Dupe, Eq, PartialEq, Hash, Allocative)]
+pub enum NetworkPolicy {
+    /// The command must not access the network.
+    #[display(fmt = "none")]
+    None,
+    /// The command may access the network. This is the default.
+    #[display(fmt = "allowed")]
+    Allowed,
+}
+
+impl Default for NetworkPolicy {
+    fn default() -> Self {
+        Self::Allowed
+    }
+}
+
+impl NetworkPolicy {
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "allowed" => Ok(Self::Allowed),
+            _ => Err(NetworkPolicyError::InvalidPolicy(s.to_owned()).into()),
+        }
+    }
+
+    /// The salt recording the policy in the action. It is empty for commands allowed to access
+    /// the network, so that their digest is the same as before policies existed.
+    pub fn salt(self) -> Vec<u8> {
+        match self {
+            Self::Allowed => Vec::new(),
+            Self::None => format!("network={}", self).into_bytes(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(NetworkPolicy::parse("none").unwrap(), NetworkPolicy::None);
+        assert_eq!(
+            NetworkPolicy::parse("allowed").unwrap(),
+            NetworkPolicy::Allowed
+        );
+        assert!(NetworkPolicy::parse("offline").is_err());
+    }
+
+    #[test]
+    fn test_salt() {
+        assert_eq!(NetworkPolicy::Allowed.salt(), Vec::<u8>::new());
+        assert_eq!(NetworkPolicy::None.salt(), b"network=none".to_vec());
+    }
+}
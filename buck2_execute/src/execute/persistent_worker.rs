@@ -0,0 +1,242 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Persistent workers on remote execution.
+//!
+//! Some RE backends can keep the process that runs an action alive once it's done, and hand it
+//! the next action that needs the same tool. This follows the Bazel worker convention: the last
+//! argument of the command is a flagfile (`@path` or `--flagfile=path`) holding the arguments of
+//! this particular action, and everything before it is the command that starts the worker. The
+//! backend is told which actions can share a worker through two platform properties: the
+//! worker key, and the protocol the worker speaks.
+//!
+//! Backends that don't support this simply ignore the properties and run the command as usual,
+//! which is why tools used as workers must also accept being run as a one-off.
+
+use std::collections::HashMap;
+
+use allocative::Allocative;
+use derive_more::Display;
+use gazebo::prelude::*;
+use remote_execution as RE;
+use sha2::Digest;
+use sha2::Sha256;
+use thiserror::Error;
+
+/// The platform property holding the key identifying which worker can run an action.
+pub const PERSISTENT_WORKER_KEY_PROPERTY: &str = "persistentWorkerKey";
+
+/// The platform property holding the protocol the worker speaks.
+pub const PERSISTENT_WORKER_PROTOCOL_PROPERTY: &str = "persistentWorkerProtocol";
+
+#[derive(Debug, Error)]
+pub enum PersistentWorkerError {
+    #[error("Invalid persistent worker protocol: `{0}`, expected `proto` or `json`")]
+    InvalidProtocol(String),
+    #[error(
+        "Actions using a persistent worker must pass their arguments in a flagfile, as the last \
+        argument (`@path` or `--flagfile=path`), but the last argument is `{0}`"
+    )]
+    MissingFlagfile(String),
+    #[error("Actions using a persistent worker must have a command")]
+    EmptyCommand,
+}
+
+/// How the worker receives work requests.
+#[derive(Debug, Display, Copy, Clone, Dupe, Eq, PartialEq, Hash, Allocative)]
+pub enum PersistentWorkerProtocol {
+    #[display(fmt = "proto")]
+    Proto,
+    #[display(fmt = "json")]
+    Json,
+}
+
+impl PersistentWorkerProtocol {
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "proto" => Ok(Self::Proto),
+            "json" => Ok(Self::Json),
+            _ => Err(PersistentWorkerError::InvalidProtocol(s.to_owned()).into()),
+        }
+    }
+}
+
+/// Identifies the persistent worker a command can be sent to.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RemotePersistentWorker {
+    key: String,
+    protocol: PersistentWorkerProtocol,
+}
+
+impl RemotePersistentWorker {
+    /// Compute the worker for a command. Two commands can share a worker if they start it the
+    /// same way: same startup arguments, same environment, and same contents for the tools
+    /// (`tool_digests`) the startup arguments refer to.
+    pub fn new<'a>(
+        protocol: PersistentWorkerProtocol,
+        args: &[String],
+        env: &HashMap<String, String>,
+        tool_digests: impl IntoIterator<Item = &'a str>,
+    ) -> anyhow::Result<Self> {
+        let startup_args = Self::startup_args(args)?;
+
+        let mut env = env.iter().collect::<Vec<_>>();
+        env.sort();
+
+        let mut tool_digests = tool_digests.into_iter().collect::<Vec<_>>();
+        tool_digests.sort_unstable();
+        tool_digests.dedup();
+
+        // Every item is followed by a separator, so that the boundaries between them are part
+        // of the key.
+        let mut hasher = Sha256::new();
+        hasher.update(protocol.to_string());
+        hasher.update([0]);
+        for arg in startup_args {
+            hasher.update(arg);
+            hasher.update([0]);
+        }
+        hasher.update([1]);
+        for (k, v) in env {
+            hasher.update(k);
+            hasher.update([0]);
+            hasher.update(v);
+            hasher.update([0]);
+        }
+        hasher.update([1]);
+        for digest in tool_digests {
+            hasher.update(digest);
+            hasher.update([0]);
+        }
+
+        Ok(Self {
+            key: hex::encode(hasher.finalize()),
+            protocol,
+        })
+    }
+
+    /// The arguments that start the worker, i.e. all but the trailing flagfile.
+    fn startup_args(args: &[String]) -> anyhow::Result<&[String]> {
+        let (flagfile, startup_args) = args
+            .split_last()
+            .ok_or(PersistentWorkerError::EmptyCommand)?;
+        if flagfile.starts_with('@') || flagfile.starts_with("--flagfile=") {
+            Ok(startup_args)
+        } else {
+            Err(PersistentWorkerError::MissingFlagfile(flagfile.clone()).into())
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn protocol(&self) -> PersistentWorkerProtocol {
+        self.protocol
+    }
+
+    /// Add the worker properties to `platform`. Properties are kept sorted by name, as RE
+    /// requires.
+    pub fn platform(&self, platform: &RE::Platform) -> RE::Platform {
+        let mut properties = platform
+            .properties
+            .iter()
+            .filter(|p| {
+                p.name != PERSISTENT_WORKER_KEY_PROPERTY
+                    && p.name != PERSISTENT_WORKER_PROTOCOL_PROPERTY
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        properties.push(RE::Property {
+            name: PERSISTENT_WORKER_KEY_PROPERTY.to_owned(),
+            value: self.key.clone(),
+        });
+        properties.push(RE::Property {
+            name: PERSISTENT_WORKER_PROTOCOL_PROPERTY.to_owned(),
+            value: self.protocol.to_string(),
+        });
+        properties.sort_by(|a, b| a.name.cmp(&b.name));
+        RE::Platform { properties }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| (*a).to_owned()).collect()
+    }
+
+    #[test]
+    fn test_key_ignores_flagfile() -> anyhow::Result<()> {
+        let env = HashMap::new();
+        let a = RemotePersistentWorker::new(
+            PersistentWorkerProtocol::Proto,
+            &args(&["javac-worker", "--persistent", "@a.args"]),
+            &env,
+            ["abc:123"],
+        )?;
+        let b = RemotePersistentWorker::new(
+            PersistentWorkerProtocol::Proto,
+            &args(&["javac-worker", "--persistent", "--flagfile=b.args"]),
+            &env,
+            ["abc:123"],
+        )?;
+        assert_eq!(a.key(), b.key());
+
+        // A different tool means a different worker.
+        let c = RemotePersistentWorker::new(
+            PersistentWorkerProtocol::Proto,
+            &args(&["javac-worker", "--persistent", "@a.args"]),
+            &env,
+            ["def:456"],
+        )?;
+        assert_ne!(a.key(), c.key());
+        Ok(())
+    }
+
+    #[test]
+    fn test_requires_flagfile() {
+        assert!(RemotePersistentWorker::new(
+            PersistentWorkerProtocol::Json,
+            &args(&["javac-worker", "Foo.java"]),
+            &HashMap::new(),
+            [],
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_platform() -> anyhow::Result<()> {
+        let worker = RemotePersistentWorker::new(
+            PersistentWorkerProtocol::Json,
+            &args(&["worker", "@args"]),
+            &HashMap::new(),
+            [],
+        )?;
+        let platform = worker.platform(&RE::Platform {
+            properties: vec![RE::Property {
+                name: "platform".to_owned(),
+                value: "linux-remote-execution".to_owned(),
+            }],
+        });
+        assert_eq!(
+            platform
+                .properties
+                .map(|p| (p.name.as_str(), p.value.as_str())),
+            vec![
+                (PERSISTENT_WORKER_KEY_PROPERTY, worker.key()),
+                (PERSISTENT_WORKER_PROTOCOL_PROPERTY, "json"),
+                ("platform", "linux-remote-execution"),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -58,5 +58,9 @@ pub trait PreparedCommandExecutor: Send + Sync {
 
     fn re_platform(&self) -> Option<&RE::Platform>;
 
+    /// Whether the RE backend this executor sends commands to can keep persistent workers warm
+    /// between commands.
+    fn supports_remote_persistent_workers(&self) -> bool;
+
     fn re_use_case(&self) -> RemoteExecutorUseCase;
 }
@@ -12,6 +12,7 @@ use std::fmt::Display;
 use std::time::Duration;
 
 use allocative::Allocative;
+use async_trait::async_trait;
 use buck2_common::file_ops::TrackedFileDigest;
 use buck2_core::fs::project::ProjectRelativePath;
 use buck2_core::fs::project::ProjectRelativePathBuf;
@@ -24,9 +25,13 @@ use thiserror::Error;
 
 use crate::artifact::fs::ArtifactFs;
 use crate::artifact::group::artifact_group_values_dyn::ArtifactGroupValuesDyn;
+use crate::execute::action_digest::ActionDigest;
 use crate::execute::environment_inheritance::EnvironmentInheritance;
+use crate::execute::network::NetworkPolicy;
+use crate::execute::persistent_worker::RemotePersistentWorker;
 use crate::path::buck_out_path::BuckOutPath;
 use crate::path::buck_out_path::BuckOutTestPath;
+use crate::re::manager::ManagedRemoteExecutionClient;
 
 #[derive(Clone)]
 pub struct ActionMetadataBlob {
@@ -62,9 +67,13 @@ pub enum ExecutorPreference {
     LocalPreferred,
     /// Fails when executed by a local-only executor
     RemoteRequired,
+    /// Does not fail when executed by a local-only executor
+    RemotePreferred,
 }
 
 impl ExecutorPreference {
+    /// Combine two preferences, typically those of an executor and of a command. Requirements
+    /// can't be overridden, but when both prefer a different executor, `other` wins.
     pub fn and(self, other: &Self) -> anyhow::Result<Self> {
         let requires_remote = self.requires_remote() || other.requires_remote();
         let requires_local = self.requires_local() || other.requires_local();
@@ -81,8 +90,14 @@ impl ExecutorPreference {
             return Ok(Self::RemoteRequired);
         }
 
-        if self.prefers_local() || other.prefers_local() {
-            return Ok(Self::LocalPreferred);
+        for preference in [other, &self] {
+            if preference.prefers_local() {
+                return Ok(Self::LocalPreferred);
+            }
+
+            if preference.prefers_remote() {
+                return Ok(Self::RemotePreferred);
+            }
         }
 
         Ok(Self::Default)
@@ -93,6 +108,7 @@ impl ExecutorPreference {
             Self::LocalRequired => false,
             Self::LocalPreferred => false,
             Self::RemoteRequired => true,
+            Self::RemotePreferred => false,
             Self::Default => false,
         }
     }
@@ -102,6 +118,7 @@ impl ExecutorPreference {
             Self::LocalRequired => true,
             Self::LocalPreferred => false,
             Self::RemoteRequired => false,
+            Self::RemotePreferred => false,
             Self::Default => false,
         }
     }
@@ -111,11 +128,35 @@ impl ExecutorPreference {
             Self::LocalRequired => true,
             Self::LocalPreferred => true,
             Self::RemoteRequired => false,
+            Self::RemotePreferred => false,
+            Self::Default => false,
+        }
+    }
+
+    pub fn prefers_remote(&self) -> bool {
+        match self {
+            Self::LocalRequired => false,
+            Self::LocalPreferred => false,
+            Self::RemoteRequired => true,
+            Self::RemotePreferred => true,
             Self::Default => false,
         }
     }
 }
 
+/// Commands with dep files are stored in the action cache under a key covering only the inputs
+/// their dep files say they used, so they can be fetched even if other inputs changed. Finding
+/// that key takes action cache queries of its own, so executors only do it once the command's own
+/// action digest missed.
+#[async_trait]
+pub trait RemoteDepFileLookup: Send + Sync {
+    /// The key to query next, if a previous execution stored its dep files.
+    async fn lookup(
+        &self,
+        re_client: &ManagedRemoteExecutionClient,
+    ) -> anyhow::Result<Option<ActionDigest>>;
+}
+
 /// The data contains the information about the command to be executed.
 pub struct CommandExecutionRequest {
     args: Vec<String>,
@@ -143,6 +184,19 @@ pub struct CommandExecutionRequest {
     /// Whether this command should override the fallback-only behavior on an hybrid executor and
     /// thus always run as if the executor was full-hybrid, assuming it is capable.
     force_full_hybrid_if_capable: bool,
+    /// The persistent worker this command can be sent to, on RE backends that support them.
+    remote_persistent_worker: Option<RemotePersistentWorker>,
+    /// Files the command may write to help debug it. They are only retrieved if the command
+    /// fails.
+    diagnostic_outputs: Vec<ProjectRelativePathBuf>,
+    /// Whether to point the command at the jobserver of the host sharing broker when it runs
+    /// locally, so that the build systems it runs share the job slots of local actions.
+    use_jobserver: bool,
+    /// Finds a second action cache key to look the command up under when its own action digest
+    /// misses.
+    remote_dep_files: Option<Box<dyn RemoteDepFileLookup>>,
+    /// Whether the command may access the network.
+    network: NetworkPolicy,
 }
 
 impl CommandExecutionRequest {
@@ -168,6 +222,11 @@ impl CommandExecutionRequest {
             local_environment_inheritance: None,
             allow_cache_upload: false,
             force_full_hybrid_if_capable: false,
+            remote_persistent_worker: None,
+            diagnostic_outputs: Vec::new(),
+            use_jobserver: false,
+            remote_dep_files: None,
+            network: NetworkPolicy::default(),
         }
     }
 
@@ -302,6 +361,60 @@ impl CommandExecutionRequest {
     pub fn force_full_hybrid_if_capable(&self) -> bool {
         self.force_full_hybrid_if_capable
     }
+
+    pub fn with_remote_persistent_worker(
+        mut self,
+        remote_persistent_worker: Option<RemotePersistentWorker>,
+    ) -> Self {
+        self.remote_persistent_worker = remote_persistent_worker;
+        self
+    }
+
+    pub fn remote_persistent_worker(&self) -> Option<&RemotePersistentWorker> {
+        self.remote_persistent_worker.as_ref()
+    }
+
+    pub fn with_diagnostic_outputs(
+        mut self,
+        diagnostic_outputs: Vec<ProjectRelativePathBuf>,
+    ) -> Self {
+        self.diagnostic_outputs = diagnostic_outputs;
+        self
+    }
+
+    pub fn diagnostic_outputs(&self) -> &[ProjectRelativePathBuf] {
+        &self.diagnostic_outputs
+    }
+
+    pub fn with_use_jobserver(mut self, use_jobserver: bool) -> Self {
+        self.use_jobserver = use_jobserver;
+        self
+    }
+
+    pub fn use_jobserver(&self) -> bool {
+        self.use_jobserver
+    }
+
+    pub fn with_remote_dep_files(
+        mut self,
+        remote_dep_files: Option<Box<dyn RemoteDepFileLookup>>,
+    ) -> Self {
+        self.remote_dep_files = remote_dep_files;
+        self
+    }
+
+    pub fn remote_dep_files(&self) -> Option<&dyn RemoteDepFileLookup> {
+        self.remote_dep_files.as_deref()
+    }
+
+    pub fn with_network(mut self, network: NetworkPolicy) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub fn network(&self) -> NetworkPolicy {
+        self.network
+    }
 }
 
 /// Is an output a file or a directory
@@ -443,3 +556,38 @@ impl ResolvedCommandExecutionOutput {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_executor_preference_and() -> anyhow::Result<()> {
+        use ExecutorPreference::*;
+
+        assert!(matches!(Default.and(&Default)?, Default));
+        assert!(matches!(LocalPreferred.and(&Default)?, LocalPreferred));
+        assert!(matches!(Default.and(&RemotePreferred)?, RemotePreferred));
+        // The preference of the command wins over that of the executor.
+        assert!(matches!(
+            LocalPreferred.and(&RemotePreferred)?,
+            RemotePreferred
+        ));
+        assert!(matches!(
+            RemotePreferred.and(&LocalPreferred)?,
+            LocalPreferred
+        ));
+        // But not over its requirements.
+        assert!(matches!(
+            LocalRequired.and(&RemotePreferred)?,
+            LocalRequired
+        ));
+        assert!(matches!(
+            RemoteRequired.and(&LocalPreferred)?,
+            RemoteRequired
+        ));
+        assert!(LocalRequired.and(&RemoteRequired).is_err());
+
+        Ok(())
+    }
+}
@@ -14,6 +14,7 @@ use std::ops::FromResidual;
 use std::time::Duration;
 use std::time::SystemTime;
 
+use buck2_core::fs::project::ProjectRelativePathBuf;
 use gazebo::dupe::Dupe;
 use indexmap::IndexMap;
 
@@ -119,6 +120,8 @@ pub struct CommandExecutionResult {
     pub did_cache_upload: bool,
     /// Whether this command was eligible for hybrid execution.
     pub eligible_for_full_hybrid: bool,
+    /// How many bytes of inputs were uploaded to the CAS to run this command.
+    pub input_bytes_uploaded: u64,
 }
 
 /// Describes how a command executed.
@@ -129,6 +132,24 @@ pub struct CommandExecutionReport {
     pub timing: CommandExecutionTimingData,
     pub std_streams: CommandStdStreams,
     pub exit_code: Option<i32>,
+    /// The diagnostic outputs of a failed command that are available on disk.
+    pub diagnostic_outputs: Vec<ProjectRelativePathBuf>,
+    /// The temporary directory of a failed command, left on disk to debug it.
+    pub scratch_dir: Option<ProjectRelativePathBuf>,
+    /// How many bytes the command left in its temporary directory.
+    pub scratch_dir_bytes: u64,
+    /// The resources used by the command, if it ran locally on a platform that reports them.
+    pub resource_usage: Option<CommandResourceUsage>,
+}
+
+/// The resources used by a local command and the processes it waited for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandResourceUsage {
+    pub user_cpu_us: u64,
+    pub system_cpu_us: u64,
+    /// Only reported on Linux.
+    pub disk_read_bytes: Option<u64>,
+    pub disk_write_bytes: Option<u64>,
 }
 
 /// Implement FromResidual so that it's easier to refactor functions returning a CommandExecutionResult
@@ -13,6 +13,7 @@ use buck2_core::category::Category;
 use buck2_data::ToProtoMessage;
 use derivative::Derivative;
 use gazebo::dupe::Dupe;
+use host_sharing::Priority;
 
 use crate::base_deferred_key::BaseDeferredKey;
 use crate::path::buck_out_path::BuckOutScratchPath;
@@ -24,6 +25,9 @@ pub struct CommandExecutionTarget<'a> {
     pub owner: &'a BaseDeferredKey,
     pub category: &'a Category,
     pub identifier: Option<&'a str>,
+    /// Commands with a higher priority go first when they queue for local resources. For actions,
+    /// this comes from the critical path through them in previous builds.
+    pub priority: Priority,
 
     // For serialization in logging.
     #[derivative(Debug = "ignore")]
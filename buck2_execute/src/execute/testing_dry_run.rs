@@ -106,6 +106,10 @@ impl PreparedCommandExecutor for DryRunExecutor {
         None
     }
 
+    fn supports_remote_persistent_workers(&self) -> bool {
+        false
+    }
+
     fn re_use_case(&self) -> RemoteExecutorUseCase {
         RemoteExecutorUseCase::buck2_default()
     }
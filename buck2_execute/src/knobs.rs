@@ -11,4 +11,12 @@ use gazebo::dupe::Dupe;
 
 /// Daemon-level config that can tweak how the executors work.
 #[derive(Clone, Dupe, Default)]
-pub struct ExecutorGlobalKnobs {}
+pub struct ExecutorGlobalKnobs {
+    /// How many queued local actions can materialize their inputs while they wait for a slot to
+    /// run in. Zero disables this, in which case inputs are only materialized once the action is
+    /// about to run.
+    pub local_input_prefetch_depth: usize,
+    /// Whether commands denied network access run with it anyway when this host can't enforce
+    /// that. Otherwise, they fail.
+    pub allow_unenforced_network_policy: bool,
+}
@@ -207,6 +207,20 @@ pub trait Materializer: Allocative + Send + Sync + 'static {
             .await?)
     }
 
+    /// Starts materializing, in the background, the artifacts in `artifact_paths` that were
+    /// declared to be downloaded from the CAS but aren't on disk yet, skipping those that would
+    /// take the total over `max_bytes`. Returns how many bytes are being downloaded.
+    ///
+    /// This is only a hint that the artifacts are likely to be needed soon: materializers that
+    /// don't defer downloads do nothing.
+    async fn prefetch(
+        &self,
+        _artifact_paths: Vec<ProjectRelativePathBuf>,
+        _max_bytes: u64,
+    ) -> anyhow::Result<u64> {
+        Ok(0)
+    }
+
     /// Similar to `ensure_materialized`, but it relaxes its most important
     /// invariant: there's no guarantee that the artifact will be materialized
     /// after calling this method. It's meant for final artifacts that are NOT
@@ -26,6 +26,7 @@ use derive_more::Display;
 use gazebo::prelude::*;
 
 use crate::base_deferred_key::BaseDeferredKey;
+use crate::path::short_out_paths::ShortOutPaths;
 
 #[derive(Clone, Debug, Display, Derivative, Allocative)]
 #[derivative(Hash, Eq, PartialEq)]
@@ -215,18 +216,31 @@ impl BuckPathResolver {
 }
 
 #[derive(Clone, Allocative)]
-pub struct BuckOutPathResolver(ProjectRelativePathBuf);
+pub struct BuckOutPathResolver {
+    buck_out: ProjectRelativePathBuf,
+    /// Set when using the short output path layout, see `ShortOutPaths`.
+    short_out_paths: Option<Arc<ShortOutPaths>>,
+}
 
 impl BuckOutPathResolver {
     /// creates a 'BuckOutPathResolver' that will resolve outputs to the provided buck-out root.
     /// If not set, buck_out defaults to "buck-out/v2"
     pub fn new(buck_out: ProjectRelativePathBuf) -> Self {
-        BuckOutPathResolver(buck_out)
+        BuckOutPathResolver {
+            buck_out,
+            short_out_paths: None,
+        }
+    }
+
+    /// Resolves the outputs of configured targets using the short output path layout.
+    pub fn with_short_out_paths(mut self, short_out_paths: Arc<ShortOutPaths>) -> Self {
+        self.short_out_paths = Some(short_out_paths);
+        self
     }
 
     /// Returns the buck-out root.
     pub fn root(&self) -> &ProjectRelativePath {
-        &self.0
+        &self.buck_out
     }
 
     /// Resolves a 'BuckOutPath' into a 'ProjectRelativePath' based on the base
@@ -249,10 +263,32 @@ impl BuckOutPathResolver {
         )
     }
 
+    /// Resolves the directory holding the diagnostic outputs of an action. It is keyed like the
+    /// scratch directory of that action, but lives under `diagnostics`.
+    pub fn resolve_diagnostics(&self, path: &BuckOutScratchPath) -> ProjectRelativePathBuf {
+        self.prefixed_path_for_owner(
+            ForwardRelativePath::unchecked_new("diagnostics"),
+            &path.owner,
+            None,
+            &path.path,
+        )
+    }
+
+    /// Resolves the directory an incremental action finds the outputs of its previous run in. It
+    /// is keyed like the scratch directory of that action, which the local executor wipes.
+    pub fn resolve_previous_outputs(&self, path: &BuckOutScratchPath) -> ProjectRelativePathBuf {
+        self.prefixed_path_for_owner(
+            ForwardRelativePath::unchecked_new("previous"),
+            &path.owner,
+            None,
+            &path.path,
+        )
+    }
+
     /// Resolve a test path
     pub fn resolve_test(&self, path: &BuckOutTestPath) -> ProjectRelativePathBuf {
         ProjectRelativePathBuf::unchecked_new(join(&[
-            self.0.as_str(),
+            self.buck_out.as_str(),
             "/",
             "test",
             "/",
@@ -269,7 +305,13 @@ impl BuckOutPathResolver {
         action_key: Option<&str>,
         path: &ForwardRelativePath,
     ) -> ProjectRelativePathBuf {
-        owner.make_hashed_path(&self.0, prefix, action_key, path)
+        owner.make_hashed_path(
+            &self.buck_out,
+            prefix,
+            action_key,
+            path,
+            self.short_out_paths.as_deref(),
+        )
     }
 
     /// This function returns the exact location of the symlink of a given target.
@@ -278,7 +320,7 @@ impl BuckOutPathResolver {
     pub fn unhashed_gen(&self, path: &BuckOutPath) -> Option<ProjectRelativePathBuf> {
         Some(ProjectRelativePathBuf::from(
             ForwardRelativePathBuf::concat([
-                self.0.as_ref(),
+                self.buck_out.as_ref(),
                 ForwardRelativePath::unchecked_new("gen"),
                 &path.0.owner.make_unhashed_path()?,
                 path.path(),
@@ -316,11 +358,24 @@ impl BaseDeferredKey {
         prefix: &ForwardRelativePath,
         action_key: Option<&str>,
         path: &ForwardRelativePath,
+        short_out_paths: Option<&ShortOutPaths>,
     ) -> ProjectRelativePathBuf {
         match self {
             BaseDeferredKey::TargetLabel(target) => {
                 let cell_relative_path = target.pkg().cell_relative_path().as_str();
 
+                let short_hash;
+                let (cfg_hash, exec_cfg_hash) = match short_out_paths {
+                    Some(short_out_paths) => {
+                        short_hash = short_out_paths.short_hash(target.cfg(), target.exec_cfg());
+                        (&*short_hash, None)
+                    }
+                    None => (
+                        target.cfg().output_hash(),
+                        target.exec_cfg().map(|x| x.output_hash()),
+                    ),
+                };
+
                 // It is performance critical that we use slices and allocate via `join` instead of
                 // repeated calls to `join` on the path object because `join` allocates on each call,
                 // which has a significant impact.
@@ -331,9 +386,9 @@ impl BaseDeferredKey {
                     "/",
                     target.pkg().cell_name().as_str(),
                     "/",
-                    target.cfg().output_hash(),
-                    if target.exec_cfg().is_some() { "-" } else { "" },
-                    target.exec_cfg().map_or("", |x| x.output_hash()),
+                    cfg_hash,
+                    if exec_cfg_hash.is_some() { "-" } else { "" },
+                    exec_cfg_hash.unwrap_or_default(),
                     "/",
                     cell_relative_path,
                     if cell_relative_path.is_empty() {
@@ -440,6 +495,7 @@ mod tests {
     use buck2_core::cells::CellName;
     use buck2_core::cells::CellResolver;
     use buck2_core::configuration::Configuration;
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
     use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
     use buck2_core::fs::project::ProjectRelativePathBuf;
     use buck2_core::package::package_relative_path::PackageRelativePathBuf;
@@ -454,6 +510,7 @@ mod tests {
     use crate::path::buck_out_path::BuckOutPathResolver;
     use crate::path::buck_out_path::BuckOutScratchPath;
     use crate::path::buck_out_path::BuckPathResolver;
+    use crate::path::short_out_paths::ShortOutPaths;
 
     #[test]
     fn buck_path_resolves() -> anyhow::Result<()> {
@@ -571,6 +628,70 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn buck_short_output_path_resolves() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let short_out_paths = ShortOutPaths::load(&AbsNormPathBuf::try_from(
+            tempdir.path().join("short_out_paths"),
+        )?)?;
+        let path_resolver =
+            BuckOutPathResolver::new(ProjectRelativePathBuf::unchecked_new("buck-out".into()))
+                .with_short_out_paths(Arc::new(short_out_paths));
+
+        let pkg = Package::new(
+            &CellName::unchecked_new("foo".to_owned()),
+            CellRelativePath::unchecked_new("baz-package"),
+        );
+        let target = TargetLabel::new(pkg, TargetName::unchecked_new("target-name"));
+        let cfg_target = target.configure(Configuration::testing_new());
+
+        let resolved = path_resolver.resolve_gen(&BuckOutPath::new(
+            BaseDeferredKey::TargetLabel(cfg_target),
+            ForwardRelativePathBuf::unchecked_new("quux".to_owned()),
+        ));
+
+        let re = Regex::new("^buck-out/gen/foo/[0-9a-f]{6}/baz-package/__target-name__/quux$")?;
+        assert!(
+            re.is_match(resolved.as_str()),
+            "{}.is_match({})",
+            re,
+            resolved
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn buck_diagnostics_path_resolves() -> anyhow::Result<()> {
+        let path_resolver =
+            BuckOutPathResolver::new(ProjectRelativePathBuf::unchecked_new("buck-out".into()));
+
+        let pkg = Package::new(
+            &CellName::unchecked_new("foo".to_owned()),
+            CellRelativePath::unchecked_new("baz-package"),
+        );
+        let target = TargetLabel::new(pkg, TargetName::unchecked_new("target-name"));
+        let cfg_target = target.configure(Configuration::testing_new());
+
+        let resolved = path_resolver.resolve_diagnostics(&BuckOutScratchPath::new(
+            BaseDeferredKey::TargetLabel(cfg_target),
+            &Category::try_from("cxx_compile").unwrap(),
+            Some("main.cpp"),
+        )?);
+
+        let re = Regex::new(
+            "buck-out/diagnostics/foo/[0-9a-z]+/baz-package/__target-name__/cxx_compile/main.cpp",
+        )?;
+        assert!(
+            re.is_match(resolved.as_str()),
+            "{}.is_match({})",
+            re,
+            resolved
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn buck_out_path_eq() -> anyhow::Result<()> {
         let pkg = Package::new(
@@ -9,3 +9,4 @@
 
 pub mod artifact_path;
 pub mod buck_out_path;
+pub mod short_out_paths;
@@ -0,0 +1,216 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! The short output path layout, enabled with `buck2.short_out_paths = true`.
+//!
+//! By default, the outputs of a target live under the output hash of its configuration, followed
+//! by the one of its execution configuration if it has one, which adds up to 33 characters in
+//! every path. That's a lot on Windows, where paths are limited to 260 characters, and it bloats
+//! command lines listing many outputs.
+//!
+//! With this layout, the two hashes are replaced by the shortest prefix (of at least
+//! `MIN_SHORT_LEN` characters) of a hash of both that no other pair of configurations uses. The
+//! prefixes are appended to a mapping file in buck-out as they are assigned, so they stay stable
+//! across daemon restarts, and `buck2 debug resolve-out-path` can tell which configuration an
+//! output directory belongs to.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use allocative::Allocative;
+use buck2_core::configuration::Configuration;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use gazebo::prelude::*;
+use parking_lot::RwLock;
+use sha2::Digest;
+use sha2::Sha256;
+
+const MIN_SHORT_LEN: usize = 6;
+
+/// The name of the mapping file, in the buck-out directory.
+pub const SHORT_OUT_PATHS_FILE: &str = "short_out_paths";
+
+/// A configuration directory assigned by the short layout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShortOutPathEntry {
+    /// The directory name used by the short layout.
+    pub short: String,
+    /// The directory name the default layout uses for the same configurations.
+    pub full: String,
+    /// The name of the configurations.
+    pub configuration: String,
+}
+
+impl ShortOutPathEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(3, '\t');
+        Some(Self {
+            short: fields.next()?.to_owned(),
+            full: fields.next()?.to_owned(),
+            configuration: fields.next()?.to_owned(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct ShortOutPathsState {
+    /// Default layout directory to short one.
+    by_full: HashMap<String, Arc<str>>,
+    /// Short layout directory to its entry, used to detect collisions.
+    by_short: HashMap<Arc<str>, ShortOutPathEntry>,
+}
+
+impl ShortOutPathsState {
+    fn insert(&mut self, entry: ShortOutPathEntry) {
+        let short: Arc<str> = Arc::from(entry.short.as_str());
+        self.by_full.insert(entry.full.clone(), short.dupe());
+        self.by_short.insert(short, entry);
+    }
+}
+
+#[derive(Allocative)]
+pub struct ShortOutPaths {
+    path: AbsNormPathBuf,
+    #[allocative(skip)]
+    state: RwLock<ShortOutPathsState>,
+}
+
+impl ShortOutPaths {
+    /// Loads the directories assigned by previous daemons from the mapping file at `path`.
+    pub fn load(path: &AbsNormPath) -> anyhow::Result<Self> {
+        let mut state = ShortOutPathsState::default();
+        if let Some(contents) = fs_util::read_to_string_opt(path)? {
+            for entry in contents.lines().filter_map(ShortOutPathEntry::parse) {
+                state.insert(entry);
+            }
+        }
+        Ok(Self {
+            path: path.to_buf(),
+            state: RwLock::new(state),
+        })
+    }
+
+    /// The directory name for outputs built in `cfg`, with `exec_cfg` as execution configuration.
+    pub fn short_hash(&self, cfg: &Configuration, exec_cfg: Option<&Configuration>) -> Arc<str> {
+        let full = match exec_cfg {
+            Some(exec_cfg) => format!("{}-{}", cfg.output_hash(), exec_cfg.output_hash()),
+            None => cfg.output_hash().to_owned(),
+        };
+        if let Some(short) = self.state.read().by_full.get(&full) {
+            return short.dupe();
+        }
+
+        let mut state = self.state.write();
+        // Another thread may have assigned it while we weren't holding the lock.
+        if let Some(short) = state.by_full.get(&full) {
+            return short.dupe();
+        }
+
+        let hash = hex::encode(Sha256::digest(full.as_bytes()));
+        let short = (MIN_SHORT_LEN..=hash.len())
+            .map(|len| &hash[..len])
+            .find(|short| !state.by_short.contains_key(*short))
+            // Only a full SHA256 collision gets here, in which case the full name is unique.
+            .unwrap_or(full.as_str())
+            .to_owned();
+        if short.len() > MIN_SHORT_LEN {
+            tracing::debug!(
+                "Output directory `{}` collided, using `{}` for `{}`",
+                &short[..MIN_SHORT_LEN],
+                short,
+                full
+            );
+        }
+
+        let entry = ShortOutPathEntry {
+            short,
+            full,
+            configuration: match exec_cfg {
+                Some(exec_cfg) => format!("{} (exec: {})", cfg, exec_cfg),
+                None => cfg.to_string(),
+            },
+        };
+        // The directory is used whether or not we manage to record it, it just won't be stable
+        // across restarts nor resolvable by `buck2 debug resolve-out-path`.
+        if let Err(e) = self.append(&entry) {
+            tracing::warn!(
+                "Error recording output directory `{}` in `{}`: {:#}",
+                entry.short,
+                self.path,
+                e
+            );
+        }
+        let short = Arc::from(entry.short.as_str());
+        state.insert(entry);
+        short
+    }
+
+    fn append(&self, entry: &ShortOutPathEntry) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs_util::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(
+            file,
+            "{}\t{}\t{}",
+            entry.short, entry.full, entry.configuration
+        )?;
+        Ok(())
+    }
+
+    /// The entry for a directory name assigned by the short layout.
+    pub fn lookup(&self, short: &str) -> Option<ShortOutPathEntry> {
+        self.state.read().by_short.get(short).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_hash_is_stable_across_loads() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let path = AbsNormPathBuf::try_from(tempdir.path().join(SHORT_OUT_PATHS_FILE))?;
+        let cfg = Configuration::testing_new();
+
+        let short = ShortOutPaths::load(&path)?.short_hash(&cfg, None);
+        assert_eq!(short.len(), MIN_SHORT_LEN);
+
+        let reloaded = ShortOutPaths::load(&path)?;
+        let entry = reloaded.lookup(&short).unwrap();
+        assert_eq!(entry.full, cfg.output_hash());
+        assert_eq!(reloaded.short_hash(&cfg, None), short);
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_hash_extends_on_collision() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let path = AbsNormPathBuf::try_from(tempdir.path().join(SHORT_OUT_PATHS_FILE))?;
+        let cfg = Configuration::testing_new();
+        let hash = hex::encode(Sha256::digest(cfg.output_hash().as_bytes()));
+
+        // Pretend another configuration got the prefix first.
+        fs_util::write(
+            &path,
+            format!("{}\tother\tother configuration\n", &hash[..MIN_SHORT_LEN]),
+        )?;
+
+        let short = ShortOutPaths::load(&path)?.short_hash(&cfg, None);
+        assert_eq!(&*short, &hash[..MIN_SHORT_LEN + 1]);
+        Ok(())
+    }
+}
@@ -72,6 +72,7 @@ use crate::execute::manager::CommandExecutionManager;
 use crate::materialize::materializer::Materializer;
 use crate::re::action_identity::ReActionIdentity;
 use crate::re::metadata::RemoteExecutionMetadataExt;
+use crate::re::uploader::UploadStats;
 use crate::re::uploader::Uploader;
 
 static BUCK2_RE_CLIENT_CFG_SECTION: &str = "buck2_re_client";
@@ -324,7 +325,7 @@ impl RemoteExecutionClient {
         dir_path: &ProjectRelativePath,
         input_dir: &ActionImmutableDirectory,
         use_case: RemoteExecutorUseCase,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<UploadStats> {
         self.data
             .uploads
             .op(self
@@ -711,7 +712,7 @@ impl RemoteExecutionClientImpl {
         dir_path: &ProjectRelativePath,
         input_dir: &ActionImmutableDirectory,
         use_case: RemoteExecutorUseCase,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<UploadStats> {
         // Actually upload to CAS
         let _cas = self.cas_semaphore.acquire().await;
         Uploader::upload(
@@ -46,6 +46,7 @@ use crate::re::client::RemoteExecutionClient;
 use crate::re::client::RemoteExecutionClientStats;
 use crate::re::client::RemoteExecutionStaticMetadata;
 use crate::re::re_get_session_id::ReGetSessionId;
+use crate::re::uploader::UploadStats;
 
 /// Lifetime management of the Remote Execution connection (i.e. the RemoteExecutionClient).
 ///
@@ -316,7 +317,7 @@ impl ManagedRemoteExecutionClient {
         dir_path: &ProjectRelativePath,
         input_dir: &ActionImmutableDirectory,
         use_case: RemoteExecutorUseCase,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<UploadStats> {
         self.lock()?
             .get()
             .await?
@@ -45,6 +45,13 @@ use crate::materialize::materializer::CasDownloadInfo;
 use crate::materialize::materializer::Materializer;
 use crate::re::metadata::RemoteExecutionMetadataExt;
 
+/// What an upload actually sent to the CAS, i.e. the blobs that were missing from it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UploadStats {
+    pub digests_uploaded: u64,
+    pub bytes_uploaded: u64,
+}
+
 pub struct Uploader {}
 
 impl Uploader {
@@ -55,7 +62,7 @@ impl Uploader {
         input_dir: &ActionImmutableDirectory,
         blobs: &ActionBlobs,
         use_case: RemoteExecutorUseCase,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<UploadStats> {
         // RE mentions they usually take 5-10 minutes of leeway so we mirror this here.
         let now = Utc::now();
         let ttl_wanted = 600i64;
@@ -140,7 +147,7 @@ impl Uploader {
         }
 
         if upload_blobs.is_empty() && missing_digests.is_empty() {
-            return Ok(());
+            return Ok(UploadStats::default());
         }
 
         // Find the file paths and directory blobs that need to be uploaded
@@ -278,6 +285,16 @@ impl Uploader {
                 .context("Error materializing paths for upload")?;
         }
 
+        let mut stats = UploadStats::default();
+        for digest in upload_files
+            .iter()
+            .map(|f| &f.digest)
+            .chain(upload_blobs.iter().map(|b| &b.digest))
+        {
+            stats.digests_uploaded += 1;
+            stats.bytes_uploaded += digest.size_in_bytes as u64;
+        }
+
         // Upload
         let upload_res = if !upload_files.is_empty() || !upload_blobs.is_empty() {
             client
@@ -312,7 +329,7 @@ impl Uploader {
 
         upload_res.context("RE: upload")?;
 
-        Ok(())
+        Ok(stats)
     }
 }
 
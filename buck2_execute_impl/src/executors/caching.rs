@@ -98,7 +98,6 @@ impl CachingExecutor {
         request: &CommandExecutionRequest,
         action_paths: &ActionPaths,
         action_digest: &ActionDigest,
-        action_blobs: &ActionBlobs,
     ) -> ControlFlow<CommandExecutionResult, CommandExecutionManager> {
         let re_client = &self.re_client;
         let action_cache_response = manager
@@ -110,38 +109,48 @@ impl CachingExecutor {
             )
             .await;
 
-        if self.upload_all_actions {
-            match re_client
-                .upload(
-                    &self.materializer,
-                    action_blobs,
-                    ProjectRelativePath::empty(),
-                    &action_paths.inputs,
-                    self.re_use_case(),
-                )
-                .await
-            {
-                Err(e) => {
-                    return ControlFlow::Break(manager.error("upload", e));
-                }
-                Ok(()) => {}
-            };
-        }
-
-        let response = match action_cache_response {
+        let (action_digest, response) = match action_cache_response {
             Err(e) => return ControlFlow::Break(manager.error("remote_action_cache", e)),
-            Ok(Some(response)) => {
-                // we were able to go to the action cache, so can skip uploading and running
-                info!(
-                    "Action result is cached, skipping execution of:\n```\n$ {}\n```\n for action `{}`",
-                    request.args().join(" "),
-                    action_digest,
-                );
-                response
+            Ok(Some(response)) => (action_digest.dupe(), response),
+            Ok(None) => {
+                // The command may still have been cached under the inputs its dep files say it
+                // used, see `RemoteDepFileLookup`.
+                let remote_dep_file_key = match request.remote_dep_files() {
+                    Some(remote_dep_files) => match remote_dep_files.lookup(re_client).await {
+                        Ok(Some(key)) => key,
+                        Ok(None) => return ControlFlow::Continue(manager),
+                        Err(e) => {
+                            tracing::warn!("Error looking up remote dep files: {:#}", e);
+                            return ControlFlow::Continue(manager);
+                        }
+                    },
+                    None => return ControlFlow::Continue(manager),
+                };
+                let response = manager
+                    .stage_async(
+                        buck2_data::CacheQuery {
+                            action_digest: remote_dep_file_key.to_string(),
+                        },
+                        re_client.action_cache(remote_dep_file_key.dupe(), self.re_use_case()),
+                    )
+                    .await;
+                match response {
+                    Err(e) => {
+                        return ControlFlow::Break(manager.error("remote_dep_file_cache", e));
+                    }
+                    Ok(Some(response)) => (remote_dep_file_key, response),
+                    Ok(None) => return ControlFlow::Continue(manager),
+                }
             }
-            Ok(None) => return ControlFlow::Continue(manager),
         };
 
+        // we were able to go to the action cache, so can skip uploading and running
+        info!(
+            "Action result is cached, skipping execution of:\n```\n$ {}\n```\n for action `{}`",
+            request.args().join(" "),
+            action_digest,
+        );
+
         ControlFlow::Break(
             download_action_results(
                 request,
@@ -156,7 +165,7 @@ impl CachingExecutor {
                 .into(),
                 action_paths,
                 request.outputs(),
-                action_digest,
+                &action_digest,
                 &response,
             )
             .await,
@@ -167,22 +176,42 @@ impl CachingExecutor {
     /// the action must have been successful and must have run locally (not much point in caching
     /// something that ran on RE and is already cached), and cache uploads must be enabled, both
     /// for this executor and this particular action.
+    ///
+    /// With `upload_all_actions`, see [`CacheUploadPlan`].
     async fn maybe_perform_cache_upload(
         &self,
         request: &CommandExecutionRequest,
         target: CommandExecutionTarget<'_>,
+        action_paths: &ActionPaths,
         digest: &ActionDigest,
+        action_blobs: &ActionBlobs,
         result: &CommandExecutionResult,
     ) -> anyhow::Result<Option<CacheUploadOutcome>> {
-        let max_bytes = match self.cache_upload_behavior {
-            CacheUploadBehavior::Enabled { max_bytes } => max_bytes,
-            CacheUploadBehavior::Disabled => return Ok(None),
-        };
-
-        if !request.allow_cache_upload() {
-            return Ok(None);
+        let plan = CacheUploadPlan::new(
+            self.cache_upload_behavior,
+            self.upload_all_actions,
+            request.allow_cache_upload(),
+            &result.report.status,
+        );
+
+        if plan.inputs {
+            self.re_client
+                .upload(
+                    &self.materializer,
+                    action_blobs,
+                    ProjectRelativePath::empty(),
+                    &action_paths.inputs,
+                    self.re_use_case(),
+                )
+                .await
+                .context("Error uploading inputs")?;
         }
 
+        let max_bytes = match plan.result {
+            Some(max_bytes) => max_bytes,
+            None => return Ok(None),
+        };
+
         let output_bytes = result
             .outputs
             .values()
@@ -190,18 +219,6 @@ impl CachingExecutor {
             .map(|v| v.calc_output_count_and_bytes().bytes)
             .sum();
 
-        match &result.report.status {
-            CommandExecutionStatus::Success {
-                execution_kind: CommandExecutionKind::Local { .. },
-            } => {
-                // Fall through to the upload.
-            }
-            _ => {
-                // In all other cases, skip.
-                return Ok(None);
-            }
-        }
-
         let name = buck2_data::ActionName {
             category: target.category.as_str().to_owned(),
             identifier: target.identifier.unwrap_or("").to_owned(),
@@ -438,7 +455,6 @@ impl PreparedCommandExecutor for CachingExecutor {
                 command.request,
                 &command.action_paths,
                 &command.prepared_action.action,
-                &command.prepared_action.blobs,
             )
             .await?;
 
@@ -448,7 +464,9 @@ impl PreparedCommandExecutor for CachingExecutor {
             .maybe_perform_cache_upload(
                 command.request,
                 command.target,
+                &command.action_paths,
                 &command.prepared_action.action,
+                &command.prepared_action.blobs,
                 &res,
             )
             .await;
@@ -475,7 +493,10 @@ impl PreparedCommandExecutor for CachingExecutor {
                 );
             }
             Err(error) => {
-                if error_on_cache_upload {
+                // Don't hide the failure of an action behind that of the upload of its inputs.
+                if error_on_cache_upload
+                    && matches!(res.report.status, CommandExecutionStatus::Success { .. })
+                {
                     res.report.status = CommandExecutionStatus::Error {
                         stage: "cache_upload",
                         error,
@@ -497,11 +518,56 @@ impl PreparedCommandExecutor for CachingExecutor {
         self.inner.re_platform()
     }
 
+    fn supports_remote_persistent_workers(&self) -> bool {
+        self.inner.supports_remote_persistent_workers()
+    }
+
     fn re_use_case(&self) -> RemoteExecutorUseCase {
         self.inner.re_use_case()
     }
 }
 
+/// What to upload once a command has executed.
+#[derive(Debug, Eq, PartialEq)]
+struct CacheUploadPlan {
+    /// Upload the action and its inputs to the CAS. With `upload_all_actions`, this is done for
+    /// every action that ran locally, whether it succeeded or not, so that any action of the build
+    /// can be fetched and reproduced from its digest on another machine.
+    inputs: bool,
+    /// Upload the outputs and result of the action, if they don't exceed the size limit, if any.
+    /// Only successful actions that ran locally are uploaded: actions that ran on RE are already
+    /// cached. With `upload_all_actions`, they are uploaded regardless of the cache upload
+    /// settings.
+    result: Option<Option<u64>>,
+}
+
+impl CacheUploadPlan {
+    fn new(
+        cache_upload_behavior: CacheUploadBehavior,
+        upload_all_actions: bool,
+        allow_cache_upload: bool,
+        status: &CommandExecutionStatus,
+    ) -> Self {
+        let ran_locally = matches!(
+            status.execution_kind(),
+            Some(CommandExecutionKind::Local { .. })
+        );
+        let succeeded = matches!(status, CommandExecutionStatus::Success { .. });
+
+        let result = match cache_upload_behavior {
+            _ if !ran_locally || !succeeded => None,
+            _ if upload_all_actions => Some(None),
+            CacheUploadBehavior::Enabled { max_bytes } if allow_cache_upload => Some(max_bytes),
+            _ => None,
+        };
+
+        Self {
+            inputs: upload_all_actions && ran_locally,
+            result,
+        }
+    }
+}
+
 /// Whether we completed a cache upload.
 #[derive(Copy, Clone, Dupe, Debug)]
 enum CacheUploadOutcome {
@@ -527,3 +593,133 @@ fn systemtime_to_ttimestamp(time: SystemTime) -> anyhow::Result<TTimestamp> {
         ..Default::default()
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn local() -> CommandExecutionKind {
+        CommandExecutionKind::Local {
+            digest: ActionDigest::from_bytes_sha1(b"action"),
+            command: Vec::new(),
+            env: HashMap::new(),
+        }
+    }
+
+    fn remote() -> CommandExecutionKind {
+        CommandExecutionKind::Remote {
+            digest: ActionDigest::from_bytes_sha1(b"action"),
+        }
+    }
+
+    #[test]
+    fn test_cache_upload_plan() {
+        let enabled = CacheUploadBehavior::Enabled {
+            max_bytes: Some(10),
+        };
+        let success = |execution_kind| CommandExecutionStatus::Success { execution_kind };
+
+        assert_eq!(
+            CacheUploadPlan {
+                inputs: false,
+                result: Some(Some(10)),
+            },
+            CacheUploadPlan::new(enabled, false, true, &success(local()))
+        );
+        assert_eq!(
+            CacheUploadPlan {
+                inputs: false,
+                result: None,
+            },
+            CacheUploadPlan::new(enabled, false, false, &success(local()))
+        );
+        assert_eq!(
+            CacheUploadPlan {
+                inputs: false,
+                result: None,
+            },
+            CacheUploadPlan::new(enabled, false, true, &success(remote()))
+        );
+    }
+
+    #[test]
+    fn test_cache_upload_plan_upload_all_actions() {
+        let disabled = CacheUploadBehavior::Disabled;
+
+        // Uploaded regardless of the cache upload settings, with no size limit.
+        assert_eq!(
+            CacheUploadPlan {
+                inputs: true,
+                result: Some(None),
+            },
+            CacheUploadPlan::new(
+                disabled,
+                true,
+                false,
+                &CommandExecutionStatus::Success {
+                    execution_kind: local()
+                }
+            )
+        );
+        // Failed actions can't be cached, but their inputs are needed to reproduce them.
+        assert_eq!(
+            CacheUploadPlan {
+                inputs: true,
+                result: None,
+            },
+            CacheUploadPlan::new(
+                disabled,
+                true,
+                false,
+                &CommandExecutionStatus::Failure {
+                    execution_kind: local()
+                }
+            )
+        );
+        assert_eq!(
+            CacheUploadPlan {
+                inputs: true,
+                result: None,
+            },
+            CacheUploadPlan::new(
+                disabled,
+                true,
+                false,
+                &CommandExecutionStatus::TimedOut {
+                    execution_kind: local(),
+                    duration: Duration::from_secs(1),
+                }
+            )
+        );
+        // Actions that ran on RE already have their inputs in the CAS.
+        assert_eq!(
+            CacheUploadPlan {
+                inputs: false,
+                result: None,
+            },
+            CacheUploadPlan::new(
+                disabled,
+                true,
+                false,
+                &CommandExecutionStatus::Success {
+                    execution_kind: remote()
+                }
+            )
+        );
+        assert_eq!(
+            CacheUploadPlan {
+                inputs: false,
+                result: None,
+            },
+            CacheUploadPlan::new(
+                disabled,
+                true,
+                false,
+                &CommandExecutionStatus::ClaimCancelled
+            )
+        );
+    }
+}
@@ -0,0 +1,163 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use buck2_common::executor_config::RemoteExecutorUseCase;
+use buck2_data::ToProtoMessage;
+use buck2_execute::artifact::fs::ArtifactFs;
+use buck2_execute::execute::action_digest::ActionDigest;
+use buck2_execute::execute::claim::MutexClaimManager;
+use buck2_execute::execute::manager::CommandExecutionManager;
+use buck2_execute::execute::prepared::PreparedCommand;
+use buck2_execute::execute::prepared::PreparedCommandExecutor;
+use buck2_execute::execute::result::CommandExecutionResult;
+use buck2_execute::execute::result::CommandExecutionStatus;
+use gazebo::prelude::*;
+use remote_execution as RE;
+
+use crate::executors::local::LocalExecutor;
+
+/// Runs a sample of the commands twice on the local executor and reports the ones whose outputs
+/// differ between the two runs, which would make caching them unsound. Other commands go to
+/// `inner`.
+///
+/// The sample is picked by action digest, so the same actions get checked again when a build is
+/// repeated.
+pub struct DeterminismCheckExecutor {
+    pub inner: Arc<dyn PreparedCommandExecutor>,
+    pub local: LocalExecutor,
+    pub artifact_fs: ArtifactFs,
+    /// The fraction of the commands to check, between 0 and 1.
+    pub sample_rate: f64,
+}
+
+fn is_sampled(action: &ActionDigest, sample_rate: f64) -> bool {
+    let mut prefix = [0; 8];
+    let digest = action.digest();
+    let len = digest.len().min(prefix.len());
+    prefix[..len].copy_from_slice(&digest[..len]);
+    // Digests are uniformly distributed, so this picks `sample_rate` of the commands.
+    (u64::from_le_bytes(prefix) as f64) < sample_rate * (u64::MAX as f64)
+}
+
+impl DeterminismCheckExecutor {
+    /// The outputs whose values differ between the two results.
+    fn differing_outputs(
+        &self,
+        first: &CommandExecutionResult,
+        second: &CommandExecutionResult,
+    ) -> Vec<String> {
+        second
+            .outputs
+            .iter()
+            .filter(|(output, value)| first.outputs.get(*output) != Some(*value))
+            .map(|(output, _)| {
+                output
+                    .as_ref()
+                    .resolve(&self.artifact_fs)
+                    .path()
+                    .to_string()
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl PreparedCommandExecutor for DeterminismCheckExecutor {
+    async fn exec_cmd(
+        &self,
+        command: &PreparedCommand<'_, '_>,
+        manager: CommandExecutionManager,
+    ) -> CommandExecutionResult {
+        if command.request.executor_preference().requires_remote()
+            || !is_sampled(&command.prepared_action.action, self.sample_rate)
+        {
+            return self.inner.exec_cmd(command, manager).await;
+        }
+
+        // The first run takes a claim of its own, the outputs of the second one are the ones that
+        // get used.
+        let first = self
+            .local
+            .exec_cmd(
+                command,
+                CommandExecutionManager::new(
+                    box MutexClaimManager::new(),
+                    manager.events.dupe(),
+                    manager.liveliness_manager.dupe(),
+                ),
+            )
+            .await;
+        if !matches!(first.report.status, CommandExecutionStatus::Success { .. }) {
+            return first;
+        }
+
+        let events = manager.events.dupe();
+        let second = self.local.exec_cmd(command, manager).await;
+        if !matches!(second.report.status, CommandExecutionStatus::Success { .. }) {
+            return second;
+        }
+
+        let outputs = self.differing_outputs(&first, &second);
+        if !outputs.is_empty() {
+            let target = &command.target;
+            events.instant_event(buck2_data::NondeterministicAction {
+                key: Some(target.action_key.as_proto()),
+                name: Some(buck2_data::ActionName {
+                    category: target.category.as_str().to_owned(),
+                    identifier: target.identifier.unwrap_or("").to_owned(),
+                }),
+                argv: command.request.args().to_vec(),
+                outputs: outputs.clone(),
+            });
+            events.warning(
+                buck2_data::WarningCategory::NondeterministicAction,
+                target.to_string(),
+                format!(
+                    "Produced different outputs when run twice: {} (command: `{}`)",
+                    outputs.join(", "),
+                    command.request.args().join(" ")
+                ),
+            );
+        }
+
+        second
+    }
+
+    fn re_platform(&self) -> Option<&RE::Platform> {
+        self.inner.re_platform()
+    }
+
+    fn supports_remote_persistent_workers(&self) -> bool {
+        self.inner.supports_remote_persistent_workers()
+    }
+
+    fn re_use_case(&self) -> RemoteExecutorUseCase {
+        self.inner.re_use_case()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_sampled() {
+        let digests: Vec<_> = (0..1000)
+            .map(|i: u32| ActionDigest::from_bytes_sha1(&i.to_le_bytes()))
+            .collect();
+        let sampled = |rate| digests.iter().filter(|d| is_sampled(d, rate)).count();
+        assert_eq!(0, sampled(0.0));
+        assert_eq!(1000, sampled(1.0));
+        let half = sampled(0.5);
+        assert!((400..600).contains(&half), "{}", half);
+    }
+}
@@ -42,6 +42,12 @@ use crate::executors::local::LocalExecutor;
 use crate::executors::re::ReExecutor;
 use crate::low_pass_filter::LowPassFilter;
 
+/// Actions expected to have at least this much of the build left behind them (see
+/// `CommandExecutionTarget::priority`) aren't held back by the low-pass filter when racing both
+/// executors, so that a slow remote queue doesn't hold up the rest of the build. Configs that
+/// don't race (limited or fallback-only hybrid) are left as they are.
+const CRITICAL_PATH_RACING_THRESHOLD_MILLIS: u64 = 30_000;
+
 /// The [HybridExecutor] will accept requests and dispatch them to both a local and remote delegate
 /// executor, unless the CommandExecutionRequest expresses a preference. That will allow them to
 /// race and whichever claims the request first will get to execute it.
@@ -208,7 +214,8 @@ impl PreparedCommandExecutor for HybridExecutor {
             // If the command prefers local execution, then it'll count in the low-pass
             // filter but it will not wait for access. This ensures that commands that are
             // flagged as prefer_local for performance reasons get to run locally even when
-            // full hybrid is enabled.
+            // full hybrid is enabled. Likewise for commands on the critical path of the build,
+            // which race both executors right away.
             //
             // NOTE (@torozco): that we only use the command's executor preference here.
             // Ideally we'd probably just use `executor_preference` (which folds in this
@@ -216,10 +223,13 @@ impl PreparedCommandExecutor for HybridExecutor {
             // `--prefer-local` without necessarily knowing why so I'd like them to not get
             // this behavor by default.
             let command_prefers_local = command.request.executor_preference().prefers_local();
+            let command_prefers_remote = command.request.executor_preference().prefers_remote();
+            let on_critical_path = command.target.priority.critical_path_millis
+                >= CRITICAL_PATH_RACING_THRESHOLD_MILLIS;
 
             let jobs = jobs.map_local(move |local| {
                 if low_pass_filter {
-                    let bypass_low_pass_filter = if command_prefers_local {
+                    let bypass_low_pass_filter = if command_prefers_local || on_critical_path {
                         futures::future::ready(()).left_future()
                     } else {
                         futures::future::pending().right_future()
@@ -251,6 +261,10 @@ impl PreparedCommandExecutor for HybridExecutor {
                 // As noted above, don't race in this scenario, since this is typically used for
                 // actions that are too expensive to run on RE.
                 jobs.execute_sequential().await
+            } else if command_prefers_remote {
+                // Likewise, only run locally if remote execution fails: this is typically used for
+                // actions that are too expensive to run locally.
+                jobs.execute_sequential().await
             } else {
                 jobs.execute_concurrent().await
             }
@@ -298,6 +312,10 @@ impl PreparedCommandExecutor for HybridExecutor {
         self.remote.re_platform()
     }
 
+    fn supports_remote_persistent_workers(&self) -> bool {
+        self.remote.supports_remote_persistent_workers()
+    }
+
     fn re_use_case(&self) -> RemoteExecutorUseCase {
         self.remote.re_use_case()
     }
@@ -23,11 +23,14 @@ use buck2_common::file_ops::FileDigest;
 use buck2_common::file_ops::FileMetadata;
 use buck2_common::file_ops::TrackedFileDigest;
 use buck2_common::liveliness_manager::LivelinessManager;
+use buck2_core::async_once_cell::AsyncOnceCell;
 use buck2_core::directory::DirectoryEntry;
 use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
 use buck2_core::fs::paths::file_name::FileNameBuf;
 use buck2_core::fs::project::ProjectRelativePath;
+use buck2_core::fs::project::ProjectRoot;
 use buck2_core::process::background_command;
 use buck2_execute::artifact::fs::ArtifactFs;
 use buck2_execute::artifact_value::ArtifactValue;
@@ -45,6 +48,7 @@ use buck2_execute::execute::inputs_directory::inputs_directory;
 use buck2_execute::execute::kind::CommandExecutionKind;
 use buck2_execute::execute::manager::CommandExecutionManager;
 use buck2_execute::execute::manager::CommandExecutionManagerExt;
+use buck2_execute::execute::network::NetworkPolicy;
 use buck2_execute::execute::output::CommandStdStreams;
 use buck2_execute::execute::prepared::PreparedCommand;
 use buck2_execute::execute::prepared::PreparedCommandExecutor;
@@ -53,7 +57,9 @@ use buck2_execute::execute::request::CommandExecutionOutput;
 use buck2_execute::execute::request::CommandExecutionOutputRef;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::result::CommandExecutionResult;
+use buck2_execute::execute::result::CommandExecutionStatus;
 use buck2_execute::execute::result::CommandExecutionTimingData;
+use buck2_execute::execute::result::CommandResourceUsage;
 use buck2_execute::execute::target::CommandExecutionTarget;
 use buck2_execute::knobs::ExecutorGlobalKnobs;
 use buck2_execute::materialize::materializer::Materializer;
@@ -70,9 +76,16 @@ use gazebo::prelude::*;
 use host_sharing::HostSharingBroker;
 use indexmap::IndexMap;
 use more_futures::spawn::dropcancel_critical_section;
+use once_cell::sync::Lazy;
 use remote_execution as RE;
 use thiserror::Error;
+use tokio::sync::Semaphore;
 use tracing::info;
+use tracing::warn;
+
+/// The variables commonly used to look up the temporary directory, across platforms and tools. They
+/// all point to the temporary directory of the command, so that nothing writes to the system one.
+const TMPDIR_ENV_VARS: &[&str] = &["TMPDIR", "TMP", "TEMP", "TEMPDIR"];
 
 #[derive(Debug, Error)]
 enum LocalExecutionError {
@@ -81,18 +94,36 @@ enum LocalExecutionError {
 
     #[error("Trying to execute a remote-only action on a local executor")]
     RemoteOnlyAction,
+
+    #[error(
+        "Cannot run a command without network access: {0}. This requires `unshare` and \
+        unprivileged user namespaces. Set `buck2.allow_unenforced_network_policy = true` to run \
+        such commands with network access instead"
+    )]
+    NetworkSandboxUnavailable(String),
 }
 
+/// The command prefix that runs a command in a new network namespace, which only has a loopback
+/// interface that is down. That requires a user namespace too, in which the current user is mapped
+/// to itself, so the command still runs as that user.
+const NETWORK_SANDBOX_PREFIX: &[&str] = &["unshare", "--net", "--map-current-user", "--"];
+
+/// Whether commands can be run in a network sandbox on this host, or why not. This is probed once,
+/// the first time a command denied network access runs locally.
+static NETWORK_SANDBOX: Lazy<AsyncOnceCell<Result<(), String>>> = Lazy::new(AsyncOnceCell::new);
+
 #[derive(Clone)]
 pub struct LocalExecutor {
     artifact_fs: ArtifactFs,
     materializer: Arc<dyn Materializer>,
     blocking_executor: Arc<dyn BlockingExecutor>,
     host_sharing_broker: Arc<HostSharingBroker>,
+    /// Bounds how many queued actions can materialize their inputs ahead of running. This is
+    /// shared by all the local executors of a command, like the host sharing broker.
+    input_prefetch_slots: Option<Arc<Semaphore>>,
     root: AbsNormPathBuf,
     #[cfg_attr(not(unix), allow(unused))]
     forkserver: Option<ForkserverClient>,
-    #[allow(unused)]
     knobs: ExecutorGlobalKnobs,
 }
 
@@ -102,6 +133,7 @@ impl LocalExecutor {
         materializer: Arc<dyn Materializer>,
         blocking_executor: Arc<dyn BlockingExecutor>,
         host_sharing_broker: Arc<HostSharingBroker>,
+        input_prefetch_slots: Option<Arc<Semaphore>>,
         root: AbsNormPathBuf,
         forkserver: Option<ForkserverClient>,
         knobs: ExecutorGlobalKnobs,
@@ -111,6 +143,7 @@ impl LocalExecutor {
             materializer,
             blocking_executor,
             host_sharing_broker,
+            input_prefetch_slots,
             root,
             forkserver,
             knobs,
@@ -185,11 +218,38 @@ impl LocalExecutor {
         }
     }
 
+    /// Materialize everything the command needs on disk before it runs.
+    async fn prepare_inputs(&self, request: &CommandExecutionRequest) -> anyhow::Result<()> {
+        let (r1, r2) = future::join(
+            materialize_inputs(&self.artifact_fs, &self.materializer, request),
+            async {
+                // When user requests to not perform a cleanup for a specific action
+                // output from previous run of that action could actually be used as the
+                // input during current run (e.g. extra output which is an incremental state describing the actual output).
+                if !request.outputs_cleanup {
+                    materialize_build_outputs_from_previous_run(
+                        &self.artifact_fs,
+                        &self.materializer,
+                        request,
+                    )
+                    .await
+                } else {
+                    Ok(())
+                }
+            },
+        )
+        .await;
+        r1.and(r2)
+    }
+
+    /// Run the command. `prefetched_inputs` is the outcome of materializing the inputs, if that
+    /// was already done while the command was queued.
     async fn exec_request(
         &self,
         action_digest: &ActionDigest,
         action: CommandExecutionTarget<'_>,
         request: &CommandExecutionRequest,
+        prefetched_inputs: Option<anyhow::Result<()>>,
         mut manager: CommandExecutionManager,
     ) -> CommandExecutionResult {
         let args = request.args();
@@ -197,39 +257,34 @@ impl LocalExecutor {
             return manager.error("no_args", LocalExecutionError::NoArgs);
         }
 
-        match manager
-            .stage_async(
-                buck2_data::LocalStage {
-                    stage: Some(buck2_data::LocalMaterializeInputs {}.into()),
-                },
-                async {
-                    let (r1, r2) = future::join(
-                        materialize_inputs(&self.artifact_fs, &self.materializer, request),
-                        async {
-                            // When user requests to not perform a cleanup for a specific action
-                            // output from previous run of that action could actually be used as the
-                            // input during current run (e.g. extra output which is an incremental state describing the actual output).
-                            if !request.outputs_cleanup {
-                                materialize_build_outputs_from_previous_run(
-                                    &self.artifact_fs,
-                                    &self.materializer,
-                                    request,
-                                )
-                                .await
-                            } else {
-                                Ok(())
-                            }
+        let network_sandbox = match request.network() {
+            NetworkPolicy::None if cfg!(target_os = "linux") => use_network_sandbox(
+                NETWORK_SANDBOX.get_or_init(probe_network_sandbox()).await,
+                self.knobs.allow_unenforced_network_policy,
+            ),
+            _ => Ok(false),
+        };
+        let network_sandbox = match network_sandbox {
+            Ok(network_sandbox) => network_sandbox,
+            Err(e) => return manager.error("network_sandbox_unavailable", e),
+        };
+
+        let materialized = match prefetched_inputs {
+            Some(res) => res,
+            None => {
+                manager
+                    .stage_async(
+                        buck2_data::LocalStage {
+                            stage: Some(buck2_data::LocalMaterializeInputs {}.into()),
                         },
+                        self.prepare_inputs(request),
                     )
-                    .await;
-                    r1.and(r2)
-                },
-            )
-            .await
-        {
-            Ok(_) => {}
-            Err(e) => return manager.error("materialize_inputs_failed", e),
+                    .await
+            }
         };
+        if let Err(e) = materialized {
+            return manager.error("materialize_inputs_failed", e);
+        }
 
         let mut manager = manager.claim().await;
 
@@ -239,6 +294,7 @@ impl LocalExecutor {
             .resolve_scratch(&action.scratch_dir());
         // For the $TMPDIR - important it is absolute
         let scratch_dir_abs = self.artifact_fs.fs().resolve(&scratch_dir);
+        let scratch_dir = &scratch_dir;
 
         if let Err(e) = manager
             .stage_async(
@@ -249,8 +305,8 @@ impl LocalExecutor {
                     // TODO(cjhopman): This should be getting the action exec context so it get use io_blocking_section
                     if request.custom_tmpdir {
                         let project_fs = self.artifact_fs.fs();
-                        project_fs.remove_path_recursive(&scratch_dir)?;
-                        fs_util::create_dir_all(&*project_fs.resolve(&scratch_dir))?;
+                        project_fs.remove_path_recursive(scratch_dir)?;
+                        fs_util::create_dir_all(&*project_fs.resolve(scratch_dir))?;
                     }
 
                     create_output_dirs(
@@ -276,17 +332,40 @@ impl LocalExecutor {
         );
 
         let tmpdir = if request.custom_tmpdir {
-            Some(("TMPDIR", scratch_dir_abs.as_os_str()))
+            Some(scratch_dir_abs.as_os_str())
         } else {
             None
         };
 
         let daemon_uuid: &str = &buck2_events::metadata::DAEMON_UUID.to_string();
+        let trace_id: &str = &manager.events.trace_id().to_string();
+        let makeflags = if request.use_jobserver() {
+            match self.host_sharing_broker.jobserver() {
+                Ok(jobserver) => jobserver.map(|jobserver| jobserver.makeflags()),
+                Err(e) => {
+                    // The command can still run, it just won't share job slots.
+                    warn!("Not using the jobserver: {:#}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
 
         let iter_env = || {
             tmpdir
                 .into_iter()
-                .map(|(k, v)| (k, StrOrOsStr::from(v)))
+                .flat_map(|v| {
+                    TMPDIR_ENV_VARS
+                        .iter()
+                        .map(move |k| (*k, StrOrOsStr::from(v)))
+                })
+                // Before the action's env, so that `env` can override them.
+                .chain(makeflags.iter().flat_map(|makeflags| {
+                    ["MAKEFLAGS", "CARGO_MAKEFLAGS"]
+                        .into_iter()
+                        .map(|k| (k, StrOrOsStr::from(makeflags.as_str())))
+                }))
                 .chain(
                     request
                         .env()
@@ -297,6 +376,11 @@ impl LocalExecutor {
                     "BUCK2_DAEMON_UUID",
                     StrOrOsStr::from(daemon_uuid),
                 )))
+                // Link the event logs of buck2 invocations run by this action to this command.
+                .chain(std::iter::once((
+                    "BUCK2_PARENT_TRACE_ID",
+                    StrOrOsStr::from(trace_id),
+                )))
         };
 
         let liveliness_manager = manager.liveliness_manager.dupe();
@@ -326,6 +410,7 @@ impl LocalExecutor {
                     let start_time = SystemTime::now();
 
                     let env = iter_env().map(|(k, v)| (k, v.into_os_str()));
+                    let args = network_sandbox_args(args, network_sandbox);
                     let r = self
                         .exec(
                             &args[0],
@@ -365,14 +450,19 @@ impl LocalExecutor {
 
         let std_streams = CommandStdStreams::Local { stdout, stderr };
 
-        match status {
-            GatherOutputStatus::Finished(status) => {
+        let mut resource_usage = None;
+        let mut result = match status {
+            GatherOutputStatus::Finished {
+                exit_status,
+                resource_usage: usage,
+            } => {
+                resource_usage = usage;
                 let outputs = match self.calculate_and_declare_output_values(request).await {
                     Ok(output_values) => output_values,
                     Err(e) => return manager.error("calculate_output_values_failed", e),
                 };
 
-                match status.code() {
+                match exit_status.code() {
                     Some(0) => manager.success(execution_kind, outputs, std_streams, timing),
                     v => manager.failure(execution_kind, outputs, std_streams, v),
                 }
@@ -396,7 +486,52 @@ impl LocalExecutor {
                 manager.timeout(execution_kind, duration, std_streams, timing)
             }
             GatherOutputStatus::Cancelled => manager.cancel_claim(),
+        };
+
+        result.report.resource_usage = resource_usage.map(|usage| CommandResourceUsage {
+            user_cpu_us: usage.user_cpu_us,
+            system_cpu_us: usage.system_cpu_us,
+            disk_read_bytes: usage.disk_read_bytes,
+            disk_write_bytes: usage.disk_write_bytes,
+        });
+
+        let failed = matches!(
+            result.report.status,
+            CommandExecutionStatus::Failure { .. } | CommandExecutionStatus::TimedOut { .. }
+        );
+
+        if failed {
+            let project_fs = self.artifact_fs.fs();
+            result.report.diagnostic_outputs = request
+                .diagnostic_outputs()
+                .iter()
+                .filter(|path| project_fs.resolve(*path).exists())
+                .cloned()
+                .collect();
+        }
+
+        // The temporary directory is kept around to debug failed commands, it'll be cleaned up
+        // before the command runs again.
+        if request.custom_tmpdir {
+            let project_fs = self.artifact_fs.fs();
+            match self
+                .blocking_executor
+                .execute_io_inline(|| Ok(finish_scratch_dir(project_fs, scratch_dir, failed)))
+                .await
+            {
+                Ok(bytes) => result.report.scratch_dir_bytes = bytes,
+                Err(e) => warn!(
+                    "Error cleaning up temporary directory `{}`: {:#}",
+                    scratch_dir, e
+                ),
+            }
+
+            if failed {
+                result.report.scratch_dir = Some(scratch_dir.clone());
+            }
         }
+
+        result
     }
 
     async fn calculate_and_declare_output_values(
@@ -410,8 +545,7 @@ impl LocalExecutor {
         for output in request.outputs() {
             let path = output.resolve(&self.artifact_fs).into_path();
             let abspath = self.root.join(&path);
-            let entry = self
-                .build_entry_from_disk(abspath)
+            let entry = build_entry_from_disk(abspath)
                 .with_context(|| format!("collecting output {:?}", path))?;
             if let Some(entry) = entry {
                 insert_entry(&mut builder, path.as_ref(), entry)?;
@@ -445,74 +579,6 @@ impl LocalExecutor {
 
         Ok(mapped_outputs)
     }
-
-    fn build_entry_from_disk(
-        &self,
-        mut path: AbsNormPathBuf,
-    ) -> anyhow::Result<Option<ActionDirectoryEntry<ActionDirectoryBuilder>>> {
-        fn build_dir_from_disk(
-            disk_path: &mut AbsNormPathBuf,
-        ) -> anyhow::Result<ActionDirectoryBuilder> {
-            let mut builder = ActionDirectoryBuilder::empty();
-
-            for file in fs_util::read_dir(&disk_path)? {
-                let file = file?;
-                let filetype = file.file_type()?;
-                let filename = file.file_name();
-
-                let filename = filename
-                    .to_str()
-                    .context("Filename is not UTF-8")
-                    .and_then(|f| FileNameBuf::try_from(f.to_owned()))
-                    .with_context(|| format!("Invalid filename: {}", disk_path.display()))?;
-
-                disk_path.push(&filename);
-
-                if filetype.is_dir() {
-                    let dir = build_dir_from_disk(disk_path)?;
-                    builder.insert(filename, DirectoryEntry::Dir(dir))?;
-                } else if filetype.is_symlink() {
-                    builder.insert(
-                        filename,
-                        DirectoryEntry::Leaf(new_symlink(fs_util::read_link(&disk_path)?)?),
-                    )?;
-                } else if filetype.is_file() {
-                    let metadata = FileMetadata {
-                        digest: TrackedFileDigest::new(FileDigest::from_file(&disk_path)?),
-                        is_executable: file.path().executable(),
-                    };
-                    builder.insert(
-                        filename,
-                        DirectoryEntry::Leaf(ActionDirectoryMember::File(metadata)),
-                    )?;
-                }
-                disk_path.pop();
-            }
-
-            Ok(builder)
-        }
-
-        // Get file metadata. If the file is missing, ignore it.
-        let m = match std::fs::symlink_metadata(&path) {
-            Ok(m) => m,
-            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
-            Err(err) => return Err(err.into()),
-        };
-
-        let value = if m.file_type().is_symlink() {
-            DirectoryEntry::Leaf(new_symlink(fs_util::read_link(&path)?)?)
-        } else if m.is_file() {
-            DirectoryEntry::Leaf(ActionDirectoryMember::File(FileMetadata {
-                digest: TrackedFileDigest::new(FileDigest::from_file(&path)?),
-                is_executable: path.executable(),
-            }))
-        } else if m.is_dir() {
-            DirectoryEntry::Dir(build_dir_from_disk(&mut path)?)
-        } else {
-            unimplemented!("Path {:?} is of an unknown file type.", path)
-        };
-        Ok(Some(value))
-    }
 }
 
 #[async_trait]
@@ -533,13 +599,43 @@ impl PreparedCommandExecutor for LocalExecutor {
             prepared_action,
         } = command;
 
-        let _permit = manager
+        // If a prefetch slot is free, materialize the inputs while we wait for a permit, so that
+        // they are ready by the time we get to run. Otherwise, they get materialized once we
+        // hold the permit.
+        let prefetch_slot = self
+            .input_prefetch_slots
+            .as_ref()
+            .and_then(|slots| slots.dupe().try_acquire_owned().ok());
+
+        let (_permit, prefetched_inputs) = manager
             .stage_async(
                 buck2_data::LocalStage {
-                    stage: Some(buck2_data::LocalQueued {}.into()),
+                    stage: Some(
+                        buck2_data::LocalQueued {
+                            prefetching_inputs: prefetch_slot.is_some(),
+                        }
+                        .into(),
+                    ),
+                },
+                async {
+                    let permit = self
+                        .host_sharing_broker
+                        .acquire(request.host_sharing_requirements(), target.priority);
+                    match prefetch_slot {
+                        Some(slot) => {
+                            let prefetch = async move {
+                                let res = self.prepare_inputs(request).await;
+                                // Free up the slot for the next queued action as soon as we're
+                                // done, rather than when we finish running.
+                                drop(slot);
+                                res
+                            };
+                            let (permit, res) = future::join(permit, prefetch).await;
+                            (permit, Some(res))
+                        }
+                        None => (permit.await, None),
+                    }
                 },
-                self.host_sharing_broker
-                    .acquire(request.host_sharing_requirements()),
             )
             .await;
 
@@ -550,6 +646,7 @@ impl PreparedCommandExecutor for LocalExecutor {
             &prepared_action.action,
             *target,
             request,
+            prefetched_inputs,
             manager,
         ))
         .await
@@ -559,6 +656,10 @@ impl PreparedCommandExecutor for LocalExecutor {
         None
     }
 
+    fn supports_remote_persistent_workers(&self) -> bool {
+        false
+    }
+
     fn re_use_case(&self) -> RemoteExecutorUseCase {
         RemoteExecutorUseCase::buck2_default()
     }
@@ -588,6 +689,182 @@ impl<'a> StrOrOsStr<'a> {
     }
 }
 
+/// Check that commands can run in a network sandbox, by running `true` in one.
+async fn probe_network_sandbox() -> Result<(), String> {
+    let mut cmd = background_command(NETWORK_SANDBOX_PREFIX[0]);
+    cmd.args(&NETWORK_SANDBOX_PREFIX[1..]).arg("true");
+    match gather_output(
+        cmd,
+        timeout_into_cancellation(Some(Duration::from_secs(10))),
+    )
+    .await
+    {
+        Ok((GatherOutputStatus::Finished { exit_status, .. }, _, _)) if exit_status.success() => {
+            Ok(())
+        }
+        Ok((GatherOutputStatus::Finished { exit_status, .. }, _, stderr)) => Err(format!(
+            "`{}` failed with {}: {}",
+            NETWORK_SANDBOX_PREFIX[0],
+            exit_status,
+            String::from_utf8_lossy(&stderr).trim()
+        )),
+        Ok((status, _, _)) => Err(format!(
+            "`{}` did not run: {:?}",
+            NETWORK_SANDBOX_PREFIX[0], status
+        )),
+        Err(e) => Err(format!("{:#}", e)),
+    }
+}
+
+/// Whether a command denied network access must run in a network sandbox, given whether the
+/// sandbox is `available`. If it isn't, the command fails, unless the policy is allowed to go
+/// unenforced, in which case it runs as is.
+fn use_network_sandbox(
+    available: &Result<(), String>,
+    allow_unenforced: bool,
+) -> anyhow::Result<bool> {
+    match available {
+        Ok(()) => Ok(true),
+        Err(reason) if allow_unenforced => {
+            static WARNED: std::sync::Once = std::sync::Once::new();
+            WARNED.call_once(|| {
+                warn!(
+                    "Running commands denied network access without enforcing it: {}",
+                    reason
+                )
+            });
+            Ok(false)
+        }
+        Err(reason) => Err(LocalExecutionError::NetworkSandboxUnavailable(reason.clone()).into()),
+    }
+}
+
+/// The command line to spawn so that `args` runs in a network sandbox, if `network_sandbox` is
+/// set.
+fn network_sandbox_args(args: &[String], network_sandbox: bool) -> Cow<'_, [String]> {
+    if network_sandbox {
+        Cow::Owned(
+            NETWORK_SANDBOX_PREFIX
+                .iter()
+                .map(|s| (*s).to_owned())
+                .chain(args.iter().cloned())
+                .collect(),
+        )
+    } else {
+        Cow::Borrowed(args)
+    }
+}
+
+/// Read what's at `path` into a directory entry, hashing every file. Returns `None` if there is
+/// nothing at `path`.
+pub(crate) fn build_entry_from_disk(
+    mut path: AbsNormPathBuf,
+) -> anyhow::Result<Option<ActionDirectoryEntry<ActionDirectoryBuilder>>> {
+    fn build_dir_from_disk(
+        disk_path: &mut AbsNormPathBuf,
+    ) -> anyhow::Result<ActionDirectoryBuilder> {
+        let mut builder = ActionDirectoryBuilder::empty();
+
+        for file in fs_util::read_dir(&disk_path)? {
+            let file = file?;
+            let filetype = file.file_type()?;
+            let filename = file.file_name();
+
+            let filename = filename
+                .to_str()
+                .context("Filename is not UTF-8")
+                .and_then(|f| FileNameBuf::try_from(f.to_owned()))
+                .with_context(|| format!("Invalid filename: {}", disk_path.display()))?;
+
+            disk_path.push(&filename);
+
+            if filetype.is_dir() {
+                let dir = build_dir_from_disk(disk_path)?;
+                builder.insert(filename, DirectoryEntry::Dir(dir))?;
+            } else if filetype.is_symlink() {
+                builder.insert(
+                    filename,
+                    DirectoryEntry::Leaf(new_symlink(fs_util::read_link(&disk_path)?)?),
+                )?;
+            } else if filetype.is_file() {
+                let metadata = FileMetadata {
+                    digest: TrackedFileDigest::new(FileDigest::from_file(&disk_path)?),
+                    is_executable: file.path().executable(),
+                };
+                builder.insert(
+                    filename,
+                    DirectoryEntry::Leaf(ActionDirectoryMember::File(metadata)),
+                )?;
+            }
+            disk_path.pop();
+        }
+
+        Ok(builder)
+    }
+
+    // Get file metadata. If the file is missing, ignore it.
+    let m = match std::fs::symlink_metadata(&path) {
+        Ok(m) => m,
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let value = if m.file_type().is_symlink() {
+        DirectoryEntry::Leaf(new_symlink(fs_util::read_link(&path)?)?)
+    } else if m.is_file() {
+        DirectoryEntry::Leaf(ActionDirectoryMember::File(FileMetadata {
+            digest: TrackedFileDigest::new(FileDigest::from_file(&path)?),
+            is_executable: path.executable(),
+        }))
+    } else if m.is_dir() {
+        DirectoryEntry::Dir(build_dir_from_disk(&mut path)?)
+    } else {
+        unimplemented!("Path {:?} is of an unknown file type.", path)
+    };
+    Ok(Some(value))
+}
+
+/// Measure the temporary directory of a command, then remove it unless it is to be kept. Failing
+/// to do either is only logged. Returns the size of the directory, in bytes.
+fn finish_scratch_dir(fs: &ProjectRoot, scratch_dir: &ProjectRelativePath, keep: bool) -> u64 {
+    let bytes = match disk_usage(&fs.resolve(scratch_dir)) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!(
+                "Error measuring temporary directory `{}`: {:#}",
+                scratch_dir, e
+            );
+            0
+        }
+    };
+
+    if !keep {
+        if let Err(e) = fs.remove_path_recursive(scratch_dir) {
+            warn!(
+                "Error removing temporary directory `{}`: {:#}",
+                scratch_dir, e
+            );
+        }
+    }
+
+    bytes
+}
+
+/// The total size of the files under `path`, without following symlinks.
+fn disk_usage(path: &AbsNormPath) -> anyhow::Result<u64> {
+    let mut total = 0;
+    for entry in fs_util::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            total += disk_usage(&entry.path())?;
+        } else {
+            total += metadata.len();
+        }
+    }
+    Ok(total)
+}
+
 /// Materialize all inputs artifact for CommandExecutionRequest so the command can be executed locally.
 pub async fn materialize_inputs(
     artifact_fs: &ArtifactFs,
@@ -684,12 +961,32 @@ pub async fn create_output_dirs(
         }
     }
 
+    // Diagnostic outputs left behind by a previous run would be mistaken for ours if this one
+    // fails without writing them, so those are always deleted.
+    let diagnostic_outputs = request.diagnostic_outputs().to_vec();
+    if !diagnostic_outputs.is_empty() {
+        materializer
+            .invalidate_many(diagnostic_outputs.clone())
+            .await?;
+        blocking_executor
+            .execute_io(box CleanOutputPaths {
+                paths: diagnostic_outputs.clone(),
+            })
+            .await
+            .context("Failed to cleanup diagnostic outputs")?;
+    }
+
     let project_fs = artifact_fs.fs();
     for output in outputs {
         if let Some(path) = output.path_to_create() {
             fs_util::create_dir_all(project_fs.resolve(path))?;
         }
     }
+    for path in &diagnostic_outputs {
+        if let Some(dir) = path.parent() {
+            fs_util::create_dir_all(project_fs.resolve(dir))?;
+        }
+    }
 
     Ok(())
 }
@@ -849,6 +1146,7 @@ mod tests {
     use buck2_core::cells::CellResolver;
     use buck2_core::fs::project::ProjectRelativePathBuf;
     use buck2_core::fs::project::ProjectRoot;
+    use buck2_core::fs::project::ProjectRootTemp;
     use buck2_execute::artifact::fs::ArtifactFs;
     use buck2_execute::execute::blocking::testing::DummyBlockingExecutor;
     use buck2_execute::materialize::nodisk::NoDiskMaterializer;
@@ -858,6 +1156,27 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_finish_scratch_dir() -> anyhow::Result<()> {
+        let fs = ProjectRootTemp::new()?;
+        let fs = fs.path();
+        let scratch_dir = ProjectRelativePath::unchecked_new("buck-out/tmp/action");
+        let scratch_dir_abs = fs.resolve(scratch_dir);
+        fs_util::create_dir_all(scratch_dir_abs.as_path().join("nested"))?;
+        fs_util::write(scratch_dir_abs.as_path().join("a"), "12345")?;
+        fs_util::write(scratch_dir_abs.as_path().join("nested/b"), "678")?;
+
+        assert_eq!(8, finish_scratch_dir(fs, scratch_dir, true));
+        assert!(fs.resolve(scratch_dir).exists());
+
+        assert_eq!(8, finish_scratch_dir(fs, scratch_dir, false));
+        assert!(!fs.resolve(scratch_dir).exists());
+
+        // Nothing to measure or remove is only logged.
+        assert_eq!(0, finish_scratch_dir(fs, scratch_dir, false));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_gather_output() -> anyhow::Result<()> {
         let mut cmd = if cfg!(windows) {
@@ -868,7 +1187,9 @@ mod tests {
         cmd.args(["-c", "echo hello"]);
 
         let (status, stdout, stderr) = gather_output(cmd, futures::future::pending()).await?;
-        assert!(matches!(status, GatherOutputStatus::Finished(s) if s.code() == Some(0)));
+        assert!(
+            matches!(status, GatherOutputStatus::Finished { exit_status, .. } if exit_status.code() == Some(0))
+        );
         assert_eq!(str::from_utf8(&stdout)?.trim(), "hello");
         assert_eq!(stderr, b"");
 
@@ -899,7 +1220,7 @@ mod tests {
         )
         .await?;
         assert!(
-            matches!(status, GatherOutputStatus::Finished(s) if s.code() == Some(0)),
+            matches!(status, GatherOutputStatus::Finished { exit_status, .. } if exit_status.code() == Some(0)),
             "status: {:?}",
             status
         );
@@ -964,6 +1285,7 @@ mod tests {
                 HostSharingStrategy::SmallerTasksFirst,
                 1,
             )),
+            None,
             root.clone(),
             None,
             ExecutorGlobalKnobs::default(),
@@ -988,7 +1310,9 @@ mod tests {
                 NoopLivelinessManager::create(),
             )
             .await?;
-        assert!(matches!(status, GatherOutputStatus::Finished(s) if s.code() == Some(0)));
+        assert!(
+            matches!(status, GatherOutputStatus::Finished { exit_status, .. } if exit_status.code() == Some(0))
+        );
 
         let stdout = std::str::from_utf8(&stdout).context("Invalid stdout")?;
 
@@ -1023,9 +1347,70 @@ mod tests {
                 NoopLivelinessManager::create(),
             )
             .await?;
-        assert!(matches!(status, GatherOutputStatus::Finished(s) if s.code() == Some(0)));
+        assert!(
+            matches!(status, GatherOutputStatus::Finished { exit_status, .. } if exit_status.code() == Some(0))
+        );
         assert_eq!(stdout, b"\n");
 
         Ok(())
     }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_network_sandbox() -> anyhow::Result<()> {
+        // Not every host allows unprivileged user namespaces, which the sandbox needs.
+        if probe_network_sandbox().await.is_err() {
+            return Ok(());
+        }
+
+        let (executor, _root, _tmpdir) = test_executor()?;
+        let executor = &executor;
+
+        let run = |args: Vec<String>| async move {
+            let (status, stdout, _) = executor
+                .exec(
+                    &args[0],
+                    &args[1..],
+                    &HashMap::<String, String>::default(),
+                    None,
+                    None,
+                    None,
+                    NoopLivelinessManager::create(),
+                )
+                .await?;
+            assert!(
+                matches!(status, GatherOutputStatus::Finished { exit_status, .. } if exit_status.code() == Some(0))
+            );
+            String::from_utf8(stdout).context("Invalid stdout")
+        };
+
+        // Processes see the interfaces of their own network namespace in /proc/net/dev.
+        let script = "id -u && cat /proc/net/dev".to_owned();
+        let args = ["sh".to_owned(), "-c".to_owned(), script];
+        let stdout = run(network_sandbox_args(&args, true).into_owned()).await?;
+        let (uid, interfaces) = stdout.split_once('\n').context("Missing uid")?;
+        let interfaces = interfaces
+            .lines()
+            .skip(2)
+            .filter_map(|l| Some(l.split_once(':')?.0.trim()))
+            .collect::<Vec<_>>();
+        assert_eq!(interfaces, vec!["lo"]);
+
+        // The command runs as the same user as outside the sandbox.
+        let outside = run(vec!["id".to_owned(), "-u".to_owned()]).await?;
+        assert_eq!(uid, outside.trim_end());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_use_network_sandbox() {
+        assert!(use_network_sandbox(&Ok(()), false).unwrap());
+        assert!(use_network_sandbox(&Ok(()), true).unwrap());
+
+        let unavailable = Err("no user namespaces".to_owned());
+        assert!(!use_network_sandbox(&unavailable, true).unwrap());
+        let e = use_network_sandbox(&unavailable, false).unwrap_err();
+        assert!(e.to_string().contains("no user namespaces"), "{}", e);
+    }
 }
@@ -8,6 +8,8 @@
  */
 
 pub mod caching;
+pub mod determinism_check;
 pub mod hybrid;
 pub mod local;
 pub mod re;
+pub mod source_change_guard;
@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+use std::borrow::Cow;
 use std::ops::ControlFlow;
 use std::sync::Arc;
 
@@ -34,6 +35,7 @@ use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::re::action_identity::ReActionIdentity;
 use buck2_execute::re::manager::ManagedRemoteExecutionClient;
 use buck2_execute::re::remote_action_result::RemoteActionResult;
+use buck2_execute::re::uploader::UploadStats;
 use gazebo::prelude::*;
 use indexmap::IndexMap;
 use remote_execution as RE;
@@ -41,8 +43,10 @@ use remote_execution::ExecuteResponse;
 use remote_execution::TCode;
 use thiserror::Error;
 use tracing::info;
+use tracing::warn;
 
 use crate::re::download::download_action_results;
+use crate::re::download::download_diagnostic_outputs;
 
 // temporary platform like thing to build apple. We probably eventually want to replace this with
 // the action/target/execution group platform.
@@ -83,6 +87,7 @@ pub struct ReExecutor {
     pub re_action_key: Option<String>,
     pub re_max_input_files_bytes: u64,
     pub re_use_case: RemoteExecutorUseCase,
+    pub re_persistent_workers: bool,
     pub knobs: ExecutorGlobalKnobs,
     pub skip_cache_lookup: bool,
 }
@@ -97,6 +102,7 @@ impl ReExecutor {
         re_action_key: Option<String>,
         re_max_input_files_bytes: u64,
         re_use_case: RemoteExecutorUseCase,
+        re_persistent_workers: bool,
         knobs: ExecutorGlobalKnobs,
         skip_cache_lookup: bool,
     ) -> Self {
@@ -111,6 +117,7 @@ impl ReExecutor {
             re_action_key,
             re_max_input_files_bytes,
             re_use_case,
+            re_persistent_workers,
             knobs,
             skip_cache_lookup,
         }
@@ -126,7 +133,7 @@ impl ReExecutor {
         mut manager: CommandExecutionManager,
         blobs: &ActionBlobs,
         action_paths: &ActionPaths,
-    ) -> ControlFlow<CommandExecutionResult, CommandExecutionManager> {
+    ) -> ControlFlow<CommandExecutionResult, (CommandExecutionManager, UploadStats)> {
         let re_client = &self.re_client;
 
         let upload_response = manager
@@ -145,11 +152,9 @@ impl ReExecutor {
             .await;
 
         match upload_response {
-            Ok(()) => {}
-            Err(e) => return ControlFlow::Break(manager.error("remote_upload_error", e)),
-        };
-
-        ControlFlow::Continue(manager)
+            Ok(stats) => ControlFlow::Continue((manager, stats)),
+            Err(e) => ControlFlow::Break(manager.error("remote_upload_error", e)),
+        }
     }
 
     async fn re_execute(
@@ -168,11 +173,20 @@ impl ReExecutor {
 
         let identity = ReActionIdentity::new(action, self.re_action_key.as_deref(), action_paths);
 
+        // This has to match the platform in the command, which `CommandExecutor` computed the
+        // same way.
+        let platform = match request.remote_persistent_worker() {
+            Some(worker) if self.re_persistent_workers => {
+                Cow::Owned(worker.platform(&self.re_platform))
+            }
+            _ => Cow::Borrowed(&self.re_platform),
+        };
+
         let execute_response = self
             .re_client
             .execute(
                 action_digest.dupe(),
-                &self.re_platform,
+                &platform,
                 self.re_use_case,
                 &identity,
                 &mut manager,
@@ -197,7 +211,30 @@ impl ReExecutor {
             ));
         }
         if action_result.exit_code != 0 {
-            return ControlFlow::Break(manager.failure(
+            let diagnostic_outputs = manager
+                .stage_async(
+                    buck2_data::ReStage {
+                        stage: Some(buck2_data::ReDownload {}.into()),
+                    },
+                    download_diagnostic_outputs(
+                        request,
+                        &*self.materializer,
+                        self.re_use_case,
+                        action_digest,
+                        &response,
+                    ),
+                )
+                .await;
+            // The action failed either way, so don't let this hide the actual failure.
+            let diagnostic_outputs = diagnostic_outputs.unwrap_or_else(|e| {
+                warn!(
+                    "Failed to download diagnostic outputs for action `{}`: {:#}",
+                    action_digest, e
+                );
+                Vec::new()
+            });
+
+            let mut result = manager.failure(
                 CommandExecutionKind::Remote {
                     digest: action_digest.dupe(),
                 },
@@ -207,7 +244,9 @@ impl ReExecutor {
                 IndexMap::new(),
                 CommandStdStreams::Remote(response.std_streams(&self.re_client, self.re_use_case)),
                 Some(action_result.exit_code),
-            ));
+            );
+            result.report.diagnostic_outputs = diagnostic_outputs;
+            return ControlFlow::Break(result);
         }
 
         ControlFlow::Continue((manager, response))
@@ -237,13 +276,13 @@ impl PreparedCommandExecutor for ReExecutor {
             return ControlFlow::Break(manager.error("remote_prepare", error))?;
         }
 
-        let manager = self.upload(manager, blobs, action_paths).await?;
+        let (manager, upload_stats) = self.upload(manager, blobs, action_paths).await?;
 
         let (manager, response) = self
             .re_execute(manager, target, request, action_digest, action_paths)
             .await?;
 
-        download_action_results(
+        let mut res = download_action_results(
             request,
             &*self.materializer,
             &self.re_client,
@@ -258,13 +297,19 @@ impl PreparedCommandExecutor for ReExecutor {
             action_digest,
             &response,
         )
-        .await
+        .await;
+        res.input_bytes_uploaded = upload_stats.bytes_uploaded;
+        res
     }
 
     fn re_platform(&self) -> Option<&RE::Platform> {
         Some(&self.re_platform)
     }
 
+    fn supports_remote_persistent_workers(&self) -> bool {
+        self.re_persistent_workers
+    }
+
     fn re_use_case(&self) -> RemoteExecutorUseCase {
         self.re_use_case
     }
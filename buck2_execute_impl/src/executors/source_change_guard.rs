@@ -0,0 +1,253 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use buck2_common::executor_config::RemoteExecutorUseCase;
+use buck2_common::source_changes::SourceChangeLog;
+use buck2_common::source_changes::SourceChangeWatch;
+use buck2_core::cells::cell_path::CellPath;
+use buck2_execute::execute::claim::MutexClaimManager;
+use buck2_execute::execute::kind::CommandExecutionKind;
+use buck2_execute::execute::manager::CommandExecutionManager;
+use buck2_execute::execute::manager::CommandExecutionManagerExt;
+use buck2_execute::execute::prepared::PreparedCommand;
+use buck2_execute::execute::prepared::PreparedCommandExecutor;
+use buck2_execute::execute::request::CommandExecutionInput;
+use buck2_execute::execute::request::CommandExecutionRequest;
+use buck2_execute::execute::result::CommandExecutionResult;
+use buck2_execute::execute::result::CommandExecutionStatus;
+use gazebo::prelude::*;
+use remote_execution as RE;
+use thiserror::Error;
+use tracing::warn;
+
+/// How many times a command is re-run because its sources changed while it ran, before giving up.
+const MAX_RERUNS: usize = 2;
+
+#[derive(Debug, Error)]
+#[error(
+    "Source `{0}` kept changing while the command ran, so its outputs may not match any version \
+    of it. Re-run the build once the sources stop changing."
+)]
+struct SourceChangedError(CellPath);
+
+/// Re-runs commands whose source inputs changed while they ran locally, since they might have
+/// read a source half-way through a change, and their outputs would then be cached under the
+/// digest of the old version. Commands that run remotely are not affected: RE only ever sees
+/// the inputs that were uploaded, which match their digests.
+///
+/// This wraps the executor that actually runs commands, so that commands are checked before
+/// their results get uploaded to the cache.
+pub struct SourceChangeGuardExecutor {
+    pub inner: Arc<dyn PreparedCommandExecutor>,
+    pub source_changes: Arc<SourceChangeLog>,
+}
+
+/// The source files and directories the command reads.
+fn source_inputs(request: &CommandExecutionRequest) -> Vec<CellPath> {
+    let mut paths = Vec::new();
+    for input in request.inputs() {
+        if let CommandExecutionInput::Artifact(group) = input {
+            for (artifact, _) in group.iter() {
+                let path = artifact.get_path();
+                if let Some(source) = path.base_path.right() {
+                    let cell_path = source.to_cell_path();
+                    paths.push(match path.projected_path {
+                        Some(projected) => cell_path.join(projected),
+                        None => cell_path,
+                    });
+                }
+            }
+        }
+    }
+    paths
+}
+
+/// What to do with the result of a command.
+#[derive(Debug, PartialEq, Eq)]
+enum Verdict {
+    Keep,
+    Rerun(CellPath),
+    GiveUp(CellPath),
+}
+
+async fn verdict(
+    watch: &SourceChangeWatch<'_>,
+    status: &CommandExecutionStatus,
+    inputs: &[CellPath],
+    reruns: usize,
+) -> anyhow::Result<Verdict> {
+    if !matches!(
+        status,
+        CommandExecutionStatus::Success {
+            execution_kind: CommandExecutionKind::Local { .. },
+        }
+    ) {
+        return Ok(Verdict::Keep);
+    }
+    Ok(match watch.changed(inputs).await? {
+        None => Verdict::Keep,
+        Some(changed) if reruns == MAX_RERUNS => Verdict::GiveUp(changed),
+        Some(changed) => Verdict::Rerun(changed),
+    })
+}
+
+#[async_trait]
+impl PreparedCommandExecutor for SourceChangeGuardExecutor {
+    async fn exec_cmd(
+        &self,
+        command: &PreparedCommand<'_, '_>,
+        manager: CommandExecutionManager,
+    ) -> CommandExecutionResult {
+        let inputs = source_inputs(command.request);
+        // Nothing to guard, so don't wait for a poll of the file watcher.
+        if inputs.is_empty() || command.request.executor_preference().requires_remote() {
+            return self.inner.exec_cmd(command, manager).await;
+        }
+
+        let mut reruns = 0;
+        loop {
+            let watch = match self.source_changes.watch().await {
+                Ok(watch) => watch,
+                Err(e) => {
+                    warn!("Not checking the sources of `{}`: {:#}", command.target, e);
+                    return self.inner.exec_cmd(command, manager).await;
+                }
+            };
+
+            // Each run takes a claim of its own, `manager` is only used if we give up.
+            let res = self
+                .inner
+                .exec_cmd(
+                    command,
+                    CommandExecutionManager::new(
+                        box MutexClaimManager::new(),
+                        manager.events.dupe(),
+                        manager.liveliness_manager.dupe(),
+                    ),
+                )
+                .await;
+
+            let changed = match verdict(&watch, &res.report.status, &inputs, reruns).await {
+                Ok(Verdict::Keep) => return res,
+                Ok(Verdict::Rerun(changed)) => changed,
+                Ok(Verdict::GiveUp(changed)) => {
+                    return manager.error("source_changed", SourceChangedError(changed));
+                }
+                Err(e) => {
+                    warn!("Not checking the sources of `{}`: {:#}", command.target, e);
+                    return res;
+                }
+            };
+            reruns += 1;
+
+            manager.events.warning(
+                buck2_data::WarningCategory::SourceChangedDuringAction,
+                command.target.to_string(),
+                format!(
+                    "Source `{}` changed while the command ran, re-running it (command: `{}`)",
+                    changed,
+                    command.request.args().join(" ")
+                ),
+            );
+        }
+    }
+
+    fn re_platform(&self) -> Option<&RE::Platform> {
+        self.inner.re_platform()
+    }
+
+    fn supports_remote_persistent_workers(&self) -> bool {
+        self.inner.supports_remote_persistent_workers()
+    }
+
+    fn re_use_case(&self) -> RemoteExecutorUseCase {
+        self.inner.re_use_case()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use buck2_execute::execute::action_digest::ActionDigest;
+
+    use super::*;
+
+    fn success(execution_kind: CommandExecutionKind) -> CommandExecutionStatus {
+        CommandExecutionStatus::Success { execution_kind }
+    }
+
+    fn local() -> CommandExecutionKind {
+        CommandExecutionKind::Local {
+            digest: ActionDigest::from_bytes_sha1(b"action"),
+            command: Vec::new(),
+            env: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verdict() -> anyhow::Result<()> {
+        let log = SourceChangeLog::new();
+        let foo = CellPath::testing_new("root", "foo");
+        let inputs = [foo.clone()];
+
+        let watch = log.watch().await?;
+        assert_eq!(
+            Verdict::Keep,
+            verdict(&watch, &success(local()), &inputs, 0).await?
+        );
+
+        log.record(CellPath::testing_new("root", "bar/a.txt"));
+        assert_eq!(
+            Verdict::Keep,
+            verdict(&watch, &success(local()), &inputs, 0).await?
+        );
+
+        let changed = CellPath::testing_new("root", "foo/a.txt");
+        log.record(changed.clone());
+        assert_eq!(
+            Verdict::Rerun(changed.clone()),
+            verdict(&watch, &success(local()), &inputs, 0).await?
+        );
+        assert_eq!(
+            Verdict::GiveUp(changed),
+            verdict(&watch, &success(local()), &inputs, MAX_RERUNS).await?
+        );
+
+        // Only the outputs of commands that read the sources locally are affected.
+        assert_eq!(
+            Verdict::Keep,
+            verdict(
+                &watch,
+                &success(CommandExecutionKind::Remote {
+                    digest: ActionDigest::from_bytes_sha1(b"action"),
+                }),
+                &inputs,
+                0
+            )
+            .await?
+        );
+        assert_eq!(
+            Verdict::Keep,
+            verdict(
+                &watch,
+                &CommandExecutionStatus::Failure {
+                    execution_kind: local(),
+                },
+                &inputs,
+                0
+            )
+            .await?
+        );
+        Ok(())
+    }
+}
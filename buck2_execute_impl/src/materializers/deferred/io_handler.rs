@@ -21,6 +21,7 @@ use buck2_core::directory::DirectoryEntry;
 use buck2_core::env_helper::EnvHelper;
 use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::symlink_fallback::SymlinkFallback;
 use buck2_events::dispatch::EventDispatcher;
 use buck2_execute::digest::CasDigestFromReExt;
 use buck2_execute::digest::CasDigestToReExt;
@@ -60,7 +61,10 @@ use crate::materializers::deferred::MaterializerSender;
 use crate::materializers::deferred::SharedMaterializingError;
 use crate::materializers::deferred::WriteFile;
 use crate::materializers::io::materialize_files;
+use crate::materializers::io::MaterializeSymlinkFallbacks;
 use crate::materializers::io::MaterializeTreeStructure;
+use crate::materializers::local_cas::LocalCas;
+use crate::materializers::throttle::BandwidthThrottle;
 
 pub(super) struct DefaultIoHandler {
     pub(super) fs: ProjectRoot,
@@ -68,6 +72,13 @@ pub(super) struct DefaultIoHandler {
     pub(super) re_client_manager: Arc<ReConnectionManager>,
     /// Executor for blocking IO operations
     pub(super) io_executor: Arc<dyn BlockingExecutor>,
+    /// What to do if we are not permitted to create symlinks.
+    pub(super) symlink_fallback: SymlinkFallback,
+    /// If set, files downloaded from CAS go through this store and get hardlinked into
+    /// `buck-out`.
+    pub(super) local_cas: Option<Arc<LocalCas>>,
+    /// If set, caps the rate at which we write materialized bytes to disk.
+    pub(super) throttle: Option<Arc<BandwidthThrottle>>,
 }
 
 struct MaterializationStat {
@@ -115,11 +126,18 @@ impl DefaultIoHandler {
         entry: ActionDirectoryEntry<ActionSharedDirectory>,
         stat: &mut MaterializationStat,
     ) -> Result<(), MaterializeEntryError> {
+        if let Some(throttle) = &self.throttle {
+            throttle
+                .acquire(entry.calc_output_count_and_bytes().bytes)
+                .await;
+        }
+
         // Materialize the dir structure, and symlinks
         self.io_executor
             .execute_io(box MaterializeTreeStructure {
                 path: path.clone(),
                 entry: entry.dupe(),
+                symlink_fallback: self.symlink_fallback,
             })
             .await?;
 
@@ -156,10 +174,23 @@ impl DefaultIoHandler {
                     .map(|x| u64::try_from(x.named_digest.digest.size_in_bytes).unwrap_or_default())
                     .sum();
 
+                let local_cas_plan = match &self.local_cas {
+                    Some(local_cas) => {
+                        let local_cas = local_cas.dupe();
+                        let plan = self
+                            .io_executor
+                            .execute_io_inline(|| local_cas.plan(files))
+                            .await?;
+                        files = plan.to_download;
+                        Some((local_cas, plan.links))
+                    }
+                    None => None,
+                };
+
                 let connection = self.re_client_manager.get_re_connection();
                 let re_client = connection.get_client();
 
-                re_client
+                let res = re_client
                     .materialize_files(files, info.re_use_case)
                     .await
                     .map_err(|e| match e.downcast_ref::<REClientError>() {
@@ -169,7 +200,26 @@ impl DefaultIoHandler {
                         _ => MaterializeEntryError::Error(e.context({
                             format!("Error materializing files declared by action: {}", info)
                         })),
-                    })?;
+                    });
+                if let Err(e) = res {
+                    if let Some((local_cas, links)) = local_cas_plan {
+                        self.io_executor
+                            .execute_io_inline(|| {
+                                local_cas.discard(links);
+                                Ok(())
+                            })
+                            .await?;
+                    }
+                    return Err(e);
+                }
+
+                if let Some((local_cas, links)) = local_cas_plan {
+                    let local_cas_stats = self
+                        .io_executor
+                        .execute_io_inline(|| local_cas.link(&self.fs, links))
+                        .await?;
+                    tracing::debug!(path = %path, stats = ?local_cas_stats, "linked from local CAS");
+                }
             }
             ArtifactMaterializationMethod::HttpDownload { info } => {
                 async {
@@ -239,6 +289,16 @@ impl DefaultIoHandler {
             #[cfg(test)]
             ArtifactMaterializationMethod::Test => unimplemented!(),
         };
+
+        // Substitute the symlinks that could not be created, now that their targets exist.
+        let fallbacks = MaterializeSymlinkFallbacks {
+            path,
+            entry,
+            symlink_fallback: self.symlink_fallback,
+        };
+        if fallbacks.is_needed() {
+            self.io_executor.execute_io(box fallbacks).await?;
+        }
         Ok(())
     }
 }
@@ -282,13 +342,14 @@ impl IoHandler for DefaultIoHandler {
         entry: ActionDirectoryEntry<ActionSharedDirectory>,
         event_dispatcher: EventDispatcher,
     ) -> Result<(), MaterializeEntryError> {
+        let action_digest = match method.as_ref() {
+            ArtifactMaterializationMethod::CasDownload { info } => {
+                info.action_digest().map(|digest| digest.to_string())
+            }
+            _ => None,
+        };
         let materialization_start = buck2_data::MaterializationStart {
-            action_digest: match method.as_ref() {
-                ArtifactMaterializationMethod::CasDownload { info } => {
-                    info.action_digest().map(|digest| digest.to_string())
-                }
-                _ => None,
-            },
+            action_digest: action_digest.clone(),
         };
         event_dispatcher
             .span_async(materialization_start, async {
@@ -305,7 +366,7 @@ impl IoHandler for DefaultIoHandler {
                 (
                     res,
                     buck2_data::MaterializationEnd {
-                        action_digest: None,
+                        action_digest,
                         file_count: stat.file_count,
                         total_bytes: stat.total_bytes,
                         path: path_string,
@@ -23,6 +23,7 @@ use std::sync::Arc;
 use allocative::Allocative;
 use anyhow::Context;
 use async_trait::async_trait;
+use buck2_common::daemon_corruption::mark_daemon_corrupted;
 use buck2_common::file_ops::FileDigest;
 use buck2_common::file_ops::FileMetadata;
 use buck2_common::file_ops::TrackedFileDigest;
@@ -40,6 +41,7 @@ use buck2_core::fs::paths::RelativePathBuf;
 use buck2_core::fs::project::ProjectRelativePath;
 use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::symlink_fallback::SymlinkFallback;
 use buck2_core::soft_error;
 use buck2_events::dispatch::get_dispatcher;
 use buck2_events::dispatch::EventDispatcher;
@@ -58,6 +60,7 @@ use buck2_execute::materialize::materializer::HttpDownloadInfo;
 use buck2_execute::materialize::materializer::MaterializationError;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::materialize::materializer::WriteRequest;
+use buck2_execute::output_size::OutputSize;
 use buck2_execute::re::manager::ReConnectionManager;
 use chrono::DateTime;
 use chrono::Duration;
@@ -87,8 +90,11 @@ use crate::materializers::deferred::file_tree::FileTree;
 use crate::materializers::deferred::io_handler::DefaultIoHandler;
 use crate::materializers::deferred::io_handler::IoHandler;
 use crate::materializers::immediate;
+use crate::materializers::io::symlink_fallback_to_proto;
+use crate::materializers::local_cas::LocalCas;
 use crate::materializers::sqlite::MaterializerState;
 use crate::materializers::sqlite::MaterializerStateSqliteDb;
+use crate::materializers::throttle::BandwidthThrottle;
 
 /// Materializer implementation that defers materialization of declared
 /// artifacts until they are needed (i.e. `ensure_materialized` is called).
@@ -145,6 +151,9 @@ pub struct DeferredMaterializerConfigs {
     pub materialize_final_artifacts: bool,
     pub defer_write_actions: bool,
     pub ttl_refresh: TtlRefreshConfiguration,
+    pub symlink_fallback: SymlinkFallback,
+    pub local_cas: Option<Arc<LocalCas>>,
+    pub throttle: Option<Arc<BandwidthThrottle>>,
 }
 
 pub struct TtlRefreshConfiguration {
@@ -293,6 +302,17 @@ enum MaterializerCommand<T: ?Sized> {
         oneshot::Sender<BoxStream<'static, Result<(), MaterializationError>>>,
     ),
 
+    /// Takes a list of artifact paths and a byte budget, and starts materializing the artifacts
+    /// in the list that need to be downloaded from the CAS, as long as they fit in the budget.
+    /// The number of bytes being downloaded is sent back through the oneshot.
+    /// See `Materializer::prefetch` for more information.
+    Prefetch(
+        Vec<ProjectRelativePathBuf>,
+        u64,
+        EventDispatcher,
+        oneshot::Sender<u64>,
+    ),
+
     /// [Materialization task -> Command thread]
     /// Notifies the command thread that an artifact was materialized. It takes
     /// the artifact path and the version that was materialized, such that if
@@ -327,6 +347,9 @@ impl<T> std::fmt::Debug for MaterializerCommand<T> {
                 write!(f, "InvalidateFilePaths({:?})", paths)
             }
             MaterializerCommand::Ensure(paths, _, _) => write!(f, "Ensure({:?}, _)", paths,),
+            MaterializerCommand::Prefetch(paths, max_bytes, _, _) => {
+                write!(f, "Prefetch({:?}, {}, _)", paths, max_bytes)
+            }
             MaterializerCommand::MaterializationFinished {
                 path,
                 timestamp,
@@ -445,6 +468,17 @@ trait MaterializationMethodToProto {
     fn to_proto(&self) -> buck2_data::MaterializationMethod;
 }
 
+impl ArtifactMaterializationMethod {
+    fn is_cas_download(&self) -> bool {
+        match self {
+            ArtifactMaterializationMethod::CasDownload { .. } => true,
+            #[cfg(test)]
+            ArtifactMaterializationMethod::Test => true,
+            _ => false,
+        }
+    }
+}
+
 impl MaterializationMethodToProto for ArtifactMaterializationMethod {
     fn to_proto(&self) -> buck2_data::MaterializationMethod {
         match self {
@@ -646,6 +680,27 @@ impl Materializer for DeferredMaterializer {
         Ok(materialization_fut)
     }
 
+    async fn prefetch(
+        &self,
+        artifact_paths: Vec<ProjectRelativePathBuf>,
+        max_bytes: u64,
+    ) -> anyhow::Result<u64> {
+        if artifact_paths.is_empty() || max_bytes == 0 {
+            return Ok(0);
+        }
+        let (sender, recv) = oneshot::channel();
+        self.command_sender
+            .send(MaterializerCommand::Prefetch(
+                artifact_paths,
+                max_bytes,
+                get_dispatcher(),
+                sender,
+            ))
+            .context("Sending Prefetch() command.")?;
+        recv.await
+            .context("Recv'ing prefetched bytes from command thread.")
+    }
+
     async fn try_materialize_final_artifact(
         &self,
         artifact_path: ProjectRelativePathBuf,
@@ -713,6 +768,9 @@ impl DeferredMaterializer {
                 buck_out_path,
                 re_client_manager,
                 io_executor: io_executor.dupe(),
+                symlink_fallback: configs.symlink_fallback,
+                local_cas: configs.local_cas,
+                throttle: configs.throttle,
             }),
             sqlite_db,
             rt: Handle::current(),
@@ -722,6 +780,7 @@ impl DeferredMaterializer {
         let num_entries_from_sqlite = sqlite_state.as_ref().map_or(0, |s| s.len()) as u64;
         let materializer_state_info = buck2_data::MaterializerStateInfo {
             num_entries_from_sqlite,
+            symlink_fallback: symlink_fallback_to_proto(configs.symlink_fallback) as i32,
         };
 
         let mut tree = ArtifactTree::new();
@@ -881,6 +940,22 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
                                 ))
                                 .ok();
                         }
+                        MaterializerCommand::Prefetch(
+                            paths,
+                            max_bytes,
+                            event_dispatcher,
+                            sender,
+                        ) => {
+                            sender
+                                .send(self.prefetch_artifacts(
+                                    &mut tree,
+                                    paths,
+                                    max_bytes,
+                                    event_dispatcher,
+                                    &command_sender,
+                                ))
+                                .ok();
+                        }
                         // Materialization of artifact succeeded
                         MaterializerCommand::MaterializationFinished {
                             path,
@@ -972,6 +1047,44 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
         tasks.collect::<FuturesOrdered<_>>().boxed()
     }
 
+    /// Starts downloading the artifacts in `paths` that are only declared, for as long as they
+    /// fit in `max_bytes`. Nobody waits on the materializations: they run to completion, and the
+    /// artifacts go back to being declared if they fail.
+    fn prefetch_artifacts(
+        &mut self,
+        tree: &mut ArtifactTree,
+        paths: Vec<ProjectRelativePathBuf>,
+        max_bytes: u64,
+        event_dispatcher: EventDispatcher,
+        command_sender: &MaterializerSender<T>,
+    ) -> u64 {
+        let mut bytes = 0;
+        for path in paths {
+            let data = match tree.prefix_get_mut(&mut path.iter()) {
+                Some(data) => data,
+                None => continue,
+            };
+            let size = match (&data.stage, &data.processing_fut) {
+                (ArtifactMaterializationStage::Declared { entry, method }, None)
+                    if method.is_cas_download() =>
+                {
+                    entry.calc_output_count_and_bytes().bytes
+                }
+                _ => continue,
+            };
+            if bytes + size > max_bytes {
+                continue;
+            }
+            if self
+                .materialize_artifact(tree, path.as_ref(), event_dispatcher.dupe(), command_sender)
+                .is_some()
+            {
+                bytes += size;
+            }
+        }
+        bytes
+    }
+
     fn declare_existing<'a>(
         &mut self,
         tree: &'a mut ArtifactTree,
@@ -1407,13 +1520,16 @@ impl ArtifactTree {
                             .map_leaf(|l| l.dupe()),
                         info: info.dupe(),
                     }),
-                    None => Err(
-                        ArtifactNotMaterializedReason::DeferredMaterializerCorruption {
-                            path,
-                            entry: root_entry,
-                            info: info.dupe(),
-                        },
-                    ),
+                    None => {
+                        mark_daemon_corrupted(buck2_data::DaemonCorruption::MaterializerCorruption);
+                        Err(
+                            ArtifactNotMaterializedReason::DeferredMaterializerCorruption {
+                                path,
+                                entry: root_entry,
+                                info: info.dupe(),
+                            },
+                        )
+                    }
                 }
             }
             ArtifactMaterializationMethod::HttpDownload { .. }
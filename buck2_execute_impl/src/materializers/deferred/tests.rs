@@ -213,4 +213,77 @@ mod state_machine {
 
         Ok(())
     }
+
+    /// Waits for the materialization of `path` that was started in the background.
+    async fn materialization_finished(tree: &ArtifactTree, path: &ProjectRelativePath) {
+        let data = tree.prefix_get(&mut path.iter()).expect("declared");
+        match &data.processing_fut {
+            Some(ProcessingFuture::Materializing(fut)) => {
+                fut.clone().await.unwrap();
+            }
+            _ => panic!("`{}` isn't materializing", path),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_within_budget() -> anyhow::Result<()> {
+        let mut dm = DeferredMaterializerCommandProcessor {
+            io: Arc::new(StubIoHandler::default()),
+            sqlite_db: None,
+            rt: Handle::current(),
+            defer_write_actions: true,
+        };
+
+        let mut tree = ArtifactTree::new();
+        let file = |contents: &[u8]| {
+            ArtifactValue::file(FileMetadata {
+                digest: TrackedFileDigest::new(FileDigest::from_bytes_sha1(contents)),
+                is_executable: false,
+            })
+        };
+        let big = make_path("foo/big");
+        let small = make_path("foo/small");
+        let other = make_path("foo/other");
+        for (path, value) in [
+            (&big, file(b"0123456789")),
+            (&small, file(b"0123")),
+            (&other, file(b"0123")),
+        ] {
+            dm.declare(
+                &mut tree,
+                path.clone(),
+                value,
+                box ArtifactMaterializationMethod::Test,
+                0,
+                &command_sender(),
+            );
+        }
+        dm.io.take_log();
+
+        // The big artifact doesn't fit, the next one does, and the one after that would go over.
+        let bytes = dm.prefetch_artifacts(
+            &mut tree,
+            vec![big.clone(), small.clone(), other.clone()],
+            6,
+            EventDispatcher::null(),
+            &command_sender(),
+        );
+        assert_eq!(4, bytes);
+        materialization_finished(&tree, &small).await;
+        assert_eq!(dm.io.take_log(), &[(Op::Materialize, small.clone())]);
+
+        // Artifacts that are already materializing don't count against the budget.
+        let bytes = dm.prefetch_artifacts(
+            &mut tree,
+            vec![small, other.clone()],
+            4,
+            EventDispatcher::null(),
+            &command_sender(),
+        );
+        assert_eq!(4, bytes);
+        materialization_finished(&tree, &other).await;
+        assert_eq!(dm.io.take_log(), &[(Op::Materialize, other)]);
+
+        Ok(())
+    }
 }
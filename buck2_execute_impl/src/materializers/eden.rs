@@ -21,6 +21,7 @@ use buck2_core::directory::FingerprintedDirectory;
 use buck2_core::fs::project::ProjectRelativePath;
 use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::symlink_fallback::SymlinkFallback;
 use buck2_execute::artifact_value::ArtifactValue;
 use buck2_execute::digest::CasDigestToReExt;
 use buck2_execute::directory::insert_artifact;
@@ -255,6 +256,9 @@ impl EdenMaterializer {
                 fs.dupe(),
                 re_client_manager,
                 blocking_executor,
+                // Eden materialization is only supported on unix, where symlinks always work.
+                SymlinkFallback::None,
+                None,
             )),
             eden_buck_out,
             fs,
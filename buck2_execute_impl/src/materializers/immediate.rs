@@ -19,6 +19,9 @@ use buck2_core::directory::unordered_entry_walk;
 use buck2_core::directory::DirectoryEntry;
 use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::symlink_fallback::SymlinkFallback;
+use buck2_events::dispatch::span_async;
+use buck2_events::dispatch::EventDispatcher;
 use buck2_execute::artifact_value::ArtifactValue;
 use buck2_execute::digest::CasDigestToReExt;
 use buck2_execute::directory::ActionDirectoryMember;
@@ -35,6 +38,7 @@ use buck2_execute::materialize::materializer::HttpDownloadInfo;
 use buck2_execute::materialize::materializer::MaterializationError;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::materialize::materializer::WriteRequest;
+use buck2_execute::output_size::OutputSize;
 use buck2_execute::re::manager::ReConnectionManager;
 use futures::stream;
 use futures::stream::BoxStream;
@@ -44,7 +48,10 @@ use remote_execution::NamedDigest;
 use remote_execution::NamedDigestWithPermissions;
 
 use crate::materializers::io::materialize_files;
+use crate::materializers::io::symlink_fallback_to_proto;
+use crate::materializers::io::MaterializeSymlinkFallbacks;
 use crate::materializers::io::MaterializeTreeStructure;
+use crate::materializers::throttle::BandwidthThrottle;
 
 /// Materializer that materializes everything immediately on declare.
 #[derive(Allocative)]
@@ -52,6 +59,9 @@ pub struct ImmediateMaterializer {
     fs: ProjectRoot,
     re_client_manager: Arc<ReConnectionManager>,
     io_executor: Arc<dyn BlockingExecutor>,
+    symlink_fallback: SymlinkFallback,
+    #[allocative(skip)]
+    throttle: Option<Arc<BandwidthThrottle>>,
 }
 
 impl ImmediateMaterializer {
@@ -59,13 +69,42 @@ impl ImmediateMaterializer {
         fs: ProjectRoot,
         re_client_manager: Arc<ReConnectionManager>,
         io_executor: Arc<dyn BlockingExecutor>,
+        symlink_fallback: SymlinkFallback,
+        throttle: Option<Arc<BandwidthThrottle>>,
     ) -> Self {
         Self {
             fs,
             re_client_manager,
             io_executor,
+            symlink_fallback,
+            throttle,
         }
     }
+
+    /// Wait until we are allowed to write `bytes`, if the bandwidth is capped.
+    async fn throttle(&self, bytes: u64) {
+        if let Some(throttle) = &self.throttle {
+            throttle.acquire(bytes).await;
+        }
+    }
+
+    /// Substitute the symlinks of an artifact that could not be created, once its files are
+    /// materialized.
+    async fn materialize_symlink_fallbacks(
+        &self,
+        path: ProjectRelativePathBuf,
+        value: &ArtifactValue,
+    ) -> anyhow::Result<()> {
+        let fallbacks = MaterializeSymlinkFallbacks {
+            path,
+            entry: value.entry().dupe(),
+            symlink_fallback: self.symlink_fallback,
+        };
+        if fallbacks.is_needed() {
+            self.io_executor.execute_io(box fallbacks).await?;
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -89,12 +128,15 @@ impl Materializer for ImmediateMaterializer {
                 paths: vec![path.to_owned()],
             })
             .await?;
+        self.throttle(value.entry().calc_output_count_and_bytes().bytes)
+            .await;
 
         // TODO: display [materializing] in superconsole
         self.io_executor
             .execute_io(box MaterializeTreeStructure {
                 path: path.clone(),
                 entry: value.entry().dupe(),
+                symlink_fallback: self.symlink_fallback,
             })
             .await?;
 
@@ -117,7 +159,9 @@ impl Materializer for ImmediateMaterializer {
                 }
                 Ok(())
             })
-            .await
+            .await?;
+
+        self.materialize_symlink_fallbacks(path, &value).await
     }
 
     async fn declare_cas_many_impl<'a, 'b>(
@@ -136,15 +180,18 @@ impl Materializer for ImmediateMaterializer {
                 .execute_io(box MaterializeTreeStructure {
                     path: path.to_owned(),
                     entry: value.entry().dupe(),
+                    symlink_fallback: self.symlink_fallback,
                 })
                 .await?;
         }
 
         let mut files = Vec::new();
+        let mut total_bytes = 0;
         for (path, value) in artifacts.iter() {
             let mut walk = unordered_entry_walk(value.entry().as_ref());
             while let Some((entry_path, entry)) = walk.next() {
                 if let DirectoryEntry::Leaf(ActionDirectoryMember::File(m)) = entry {
+                    total_bytes += m.digest.size();
                     files.push(NamedDigestWithPermissions {
                         named_digest: NamedDigest {
                             digest: m.digest.to_re(),
@@ -157,9 +204,43 @@ impl Materializer for ImmediateMaterializer {
                 }
             }
         }
-        let re_conn = self.re_client_manager.get_re_connection();
-        let re_client = re_conn.get_client();
-        re_client.materialize_files(files, info.re_use_case).await?;
+        // Reported like the downloads of the deferred materializer, so that the bytes an action
+        // downloaded can be found in the event log whichever materializer is used.
+        let action_digest = info.action_digest().map(|digest| digest.to_string());
+        let file_count = files.len() as u64;
+        self.throttle(total_bytes).await;
+        let path = artifacts
+            .first()
+            .map(|(path, _)| path.as_str().to_owned())
+            .unwrap_or_default();
+        span_async(
+            buck2_data::MaterializationStart {
+                action_digest: action_digest.clone(),
+            },
+            async move {
+                let re_conn = self.re_client_manager.get_re_connection();
+                let re_client = re_conn.get_client();
+                let res = re_client.materialize_files(files, info.re_use_case).await;
+                let error = res.as_ref().err().map(|e| format!("{:#}", e));
+                (
+                    res,
+                    buck2_data::MaterializationEnd {
+                        action_digest,
+                        file_count,
+                        total_bytes,
+                        path,
+                        success: error.is_none(),
+                        error,
+                        method: Some(buck2_data::MaterializationMethod::CasDownload as i32),
+                    },
+                )
+            },
+        )
+        .await?;
+
+        for (path, value) in artifacts {
+            self.materialize_symlink_fallbacks(path, &value).await?;
+        }
         Ok(())
     }
 
@@ -173,6 +254,7 @@ impl Materializer for ImmediateMaterializer {
                 paths: vec![path.to_owned()],
             })
             .await?;
+        self.throttle(info.metadata.digest.size()).await;
 
         http_download(
             &http_client()?,
@@ -199,7 +281,17 @@ impl Materializer for ImmediateMaterializer {
         &self,
         gen: Box<dyn FnOnce() -> anyhow::Result<Vec<WriteRequest>> + Send + 'a>,
     ) -> anyhow::Result<Vec<ArtifactValue>> {
-        write_to_disk(&self.fs, self.io_executor.as_ref(), gen).await
+        let values = write_to_disk(&self.fs, self.io_executor.as_ref(), gen).await?;
+        // The contents are only known once generated, so the writes that follow wait for these
+        // instead.
+        self.throttle(
+            values
+                .iter()
+                .map(|v| v.entry().calc_output_count_and_bytes().bytes)
+                .sum(),
+        )
+        .await;
+        Ok(values)
     }
 
     async fn invalidate_many(&self, _paths: Vec<ProjectRelativePathBuf>) -> anyhow::Result<()> {
@@ -232,6 +324,13 @@ impl Materializer for ImmediateMaterializer {
         // materialized. We can simply return them as is.
         Ok(paths.into_map(Ok))
     }
+
+    fn log_materializer_state(&self, events: &EventDispatcher) {
+        events.instant_event(buck2_data::MaterializerStateInfo {
+            num_entries_from_sqlite: 0,
+            symlink_fallback: symlink_fallback_to_proto(self.symlink_fallback) as i32,
+        })
+    }
 }
 
 pub async fn write_to_disk<'a>(
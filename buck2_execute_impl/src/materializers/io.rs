@@ -8,13 +8,18 @@
  */
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 
+use buck2_core::directory::unordered_entry_walk;
 use buck2_core::directory::DirectoryEntry;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
 use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::symlink_fallback::apply_symlink_fallbacks;
+use buck2_core::fs::symlink_fallback::symlink_or_defer;
+use buck2_core::fs::symlink_fallback::SymlinkFallback;
 use buck2_execute::directory::ActionDirectory;
 use buck2_execute::directory::ActionDirectoryEntry;
 use buck2_execute::directory::ActionDirectoryMember;
@@ -24,20 +29,68 @@ use buck2_execute::execute::blocking::IoRequest;
 pub struct MaterializeTreeStructure {
     pub path: ProjectRelativePathBuf,
     pub entry: ActionDirectoryEntry<ActionSharedDirectory>,
+    /// What to do if we are not permitted to create symlinks.
+    pub symlink_fallback: SymlinkFallback,
 }
 
 impl IoRequest for MaterializeTreeStructure {
     fn execute(self: Box<Self>, project_fs: &ProjectRoot) -> anyhow::Result<()> {
-        materialize_dirs_and_syms(self.entry.as_ref(), &project_fs.root().join(&self.path))?;
+        materialize_dirs_and_syms(
+            self.entry.as_ref(),
+            &project_fs.root().join(&self.path),
+            self.symlink_fallback,
+        )?;
 
         Ok(())
     }
 }
 
+/// Substitutes the symlinks of an entry that could not be created by `MaterializeTreeStructure`,
+/// according to the symlink fallback. This must run once the files of the entry are materialized,
+/// since substitutes are made from the targets of the symlinks.
+pub struct MaterializeSymlinkFallbacks {
+    pub path: ProjectRelativePathBuf,
+    pub entry: ActionDirectoryEntry<ActionSharedDirectory>,
+    pub symlink_fallback: SymlinkFallback,
+}
+
+impl MaterializeSymlinkFallbacks {
+    /// Nothing is deferred when there is no fallback, or off Windows, where creating symlinks
+    /// never lacks privileges, so there is nothing to do either.
+    pub fn is_needed(&self) -> bool {
+        cfg!(windows) && self.symlink_fallback != SymlinkFallback::None
+    }
+}
+
+impl IoRequest for MaterializeSymlinkFallbacks {
+    fn execute(self: Box<Self>, project_fs: &ProjectRoot) -> anyhow::Result<()> {
+        let dest = project_fs.root().join(&self.path);
+        let mut links = Vec::new();
+        let mut walk = unordered_entry_walk(self.entry.as_ref());
+        while let Some((entry_path, entry)) = walk.next() {
+            let target = match entry {
+                DirectoryEntry::Leaf(ActionDirectoryMember::Symlink(s)) => {
+                    PathBuf::from(s.target().as_str())
+                }
+                DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(s)) => {
+                    s.target().to_owned()
+                }
+                _ => continue,
+            };
+            let link = dest.join(entry_path.get());
+            if fs_util::symlink_metadata(&link).is_err() {
+                links.push((target, link.into_path_buf()));
+            }
+        }
+        apply_symlink_fallbacks(links, self.symlink_fallback)
+    }
+}
+
 /// Materializes the entry at `dest`.
 ///
 /// - `materialize_dirs_and_syms`: if `true`, materializes directories and
 ///   symlinks.
+/// - `symlink_fallback`: what to do when symlinks cannot be created.
 /// - `file_src`: takes the destination path of a file, and returns its
 ///   source path (where it should be copied from). If it returns [`None`],
 ///   the file is not materialized.
@@ -45,6 +98,7 @@ fn materialize<F, D>(
     entry: DirectoryEntry<&D, &ActionDirectoryMember>,
     dest: &AbsNormPath,
     materialize_dirs_and_syms: bool,
+    symlink_fallback: SymlinkFallback,
     mut file_src: F,
 ) -> anyhow::Result<()>
 where
@@ -58,7 +112,13 @@ where
             fs_util::create_dir_all(parent)?;
         }
     }
-    materialize_recursively(entry, &mut dest, materialize_dirs_and_syms, &mut file_src)
+    materialize_recursively(
+        entry,
+        &mut dest,
+        materialize_dirs_and_syms,
+        symlink_fallback,
+        &mut file_src,
+    )
 }
 
 /// Materializes the directories and symlinks of an entry at `dest`. Files
@@ -66,12 +126,19 @@ where
 pub(crate) fn materialize_dirs_and_syms<P, D>(
     entry: DirectoryEntry<&D, &ActionDirectoryMember>,
     dest: P,
+    symlink_fallback: SymlinkFallback,
 ) -> anyhow::Result<()>
 where
     P: AsRef<AbsNormPath>,
     D: ActionDirectory,
 {
-    materialize(entry, dest.as_ref(), true, |_: &AbsNormPath| None)
+    materialize(
+        entry,
+        dest.as_ref(),
+        true,
+        symlink_fallback,
+        |_: &AbsNormPath| None,
+    )
 }
 
 /// Materializes the files of an the entry rooted at `dest`.
@@ -100,7 +167,7 @@ where
             Some(src.join(subpath))
         }
     };
-    materialize(entry, dest, false, file_src)
+    materialize(entry, dest, false, SymlinkFallback::None, file_src)
 }
 
 /// Materializes the files of an entry rooted at `dest`.
@@ -118,13 +185,14 @@ where
     D: ActionDirectory,
 {
     let file_src = |d: &AbsNormPath| srcs.remove(d);
-    materialize(entry, dest.as_ref(), false, file_src)
+    materialize(entry, dest.as_ref(), false, SymlinkFallback::None, file_src)
 }
 
 fn materialize_recursively<F, D>(
     entry: DirectoryEntry<&D, &ActionDirectoryMember>,
     dest: &mut AbsNormPathBuf,
     materialize_dirs_and_syms: bool,
+    symlink_fallback: SymlinkFallback,
     file_src: &mut F,
 ) -> anyhow::Result<()>
 where
@@ -138,7 +206,13 @@ where
             }
             for (name, entry) in d.entries() {
                 dest.push(name);
-                materialize_recursively(entry, dest, materialize_dirs_and_syms, file_src)?;
+                materialize_recursively(
+                    entry,
+                    dest,
+                    materialize_dirs_and_syms,
+                    symlink_fallback,
+                    file_src,
+                )?;
                 dest.pop();
             }
             Ok(())
@@ -153,15 +227,24 @@ where
         }
         DirectoryEntry::Leaf(ActionDirectoryMember::Symlink(s)) => {
             if materialize_dirs_and_syms && fs_util::symlink_metadata(&dest).is_err() {
-                fs_util::symlink(s.target().as_str(), dest)?;
+                symlink_or_defer(s.target().as_str(), dest, symlink_fallback)?;
             }
             Ok(())
         }
         DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(s)) => {
             if materialize_dirs_and_syms && fs_util::symlink_metadata(&dest).is_err() {
-                fs_util::symlink(s.target(), dest)?;
+                symlink_or_defer(s.target(), dest, symlink_fallback)?;
             }
             Ok(())
         }
     }
 }
+
+pub(crate) fn symlink_fallback_to_proto(fallback: SymlinkFallback) -> buck2_data::SymlinkFallback {
+    match fallback {
+        SymlinkFallback::None => buck2_data::SymlinkFallback::None,
+        SymlinkFallback::Copy => buck2_data::SymlinkFallback::Copy,
+        SymlinkFallback::Hardlink => buck2_data::SymlinkFallback::Hardlink,
+        SymlinkFallback::Junction => buck2_data::SymlinkFallback::Junction,
+    }
+}
@@ -0,0 +1,477 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A content-addressed store on local disk that the materializer can hardlink files out of.
+//!
+//! Files downloaded from RE are first written into the store, and then hardlinked into
+//! `buck-out`. When the same digest is needed again (another output, or the same output after
+//! a `buck2 clean`), it does not need to be downloaded again, and takes no additional disk space.
+//! If the store and `buck-out` are not on the same filesystem, we fall back to copying.
+//!
+//! Files in the store are read-only. Since they are hardlinked into `buck-out`, a process writing
+//! to one of those outputs in place would otherwise change the contents of the store, and of every
+//! other output with the same digest.
+//!
+//! The store is enabled with `buck2.materializer_local_cas_dir`, and its size is bounded with
+//! `buck2.materializer_local_cas_max_bytes`, see [`LocalCas::evict`].
+
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::SystemTime;
+
+use allocative::Allocative;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRelativePath;
+use buck2_core::fs::project::ProjectRoot;
+use remote_execution::NamedDigest;
+use remote_execution::NamedDigestWithPermissions;
+use remote_execution::TDigest;
+
+#[derive(Allocative)]
+pub struct LocalCas {
+    root: AbsNormPathBuf,
+    /// Set once we find out that the store and `buck-out` are on different filesystems, so that
+    /// hardlinks from one to the other never work and we don't keep trying.
+    #[allocative(skip)]
+    hardlinks_unsupported: AtomicBool,
+}
+
+/// A file that is (or will be, once downloaded) in the store, and must be linked into `buck-out`.
+pub struct LocalCasLink {
+    src: AbsNormPathBuf,
+    dest: String,
+    is_executable: bool,
+}
+
+/// The files to move into the store once downloaded, and to then link into `buck-out`.
+pub struct LocalCasLinks {
+    /// Files downloaded to a temporary name in the store, and their final name. Files are only
+    /// renamed into place once fully downloaded, so an interrupted download never leaves a
+    /// truncated file in the store.
+    staged: Vec<StagedFile>,
+    links: Vec<LocalCasLink>,
+}
+
+struct StagedFile {
+    temp: AbsNormPathBuf,
+    src: AbsNormPathBuf,
+    is_executable: bool,
+}
+
+/// The result of splitting a set of files to materialize between the store and RE.
+pub struct LocalCasPlan {
+    /// Files that need to be downloaded from RE. Their names point into the store.
+    pub to_download: Vec<NamedDigestWithPermissions>,
+    /// Files to link into `buck-out` once `to_download` is downloaded.
+    pub links: LocalCasLinks,
+}
+
+#[derive(Default, Debug)]
+pub struct LocalCasStats {
+    pub hardlinked: u64,
+    pub copied: u64,
+}
+
+impl LocalCas {
+    pub fn new(root: AbsNormPathBuf) -> Self {
+        Self {
+            root,
+            hardlinks_unsupported: AtomicBool::new(false),
+        }
+    }
+
+    pub fn root(&self) -> &AbsNormPathBuf {
+        &self.root
+    }
+
+    /// Executable and non-executable files are stored separately, since hardlinks share
+    /// permissions.
+    fn path_for(&self, digest: &TDigest, is_executable: bool) -> anyhow::Result<AbsNormPathBuf> {
+        let name = format!(
+            "{}/{}_{}{}",
+            &digest.hash[..std::cmp::min(2, digest.hash.len())],
+            digest.hash,
+            digest.size_in_bytes,
+            if is_executable { ".x" } else { "" }
+        );
+        Ok(self.root.join(ForwardRelativePath::new(&name)?))
+    }
+
+    /// A unique name to download a file to before moving it into the store. Temporary files are
+    /// kept in their own directory, which never clashes with the two character directories of
+    /// the store.
+    fn temp_path(&self) -> anyhow::Result<AbsNormPathBuf> {
+        static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+        let name = format!(
+            "tmp/{}_{}",
+            std::process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        );
+        Ok(self.root.join(ForwardRelativePath::new(&name)?))
+    }
+
+    /// Decide which files can be linked from the store as is, and which need to be downloaded
+    /// into the store first.
+    pub fn plan(&self, files: Vec<NamedDigestWithPermissions>) -> anyhow::Result<LocalCasPlan> {
+        let mut staged = HashMap::new();
+        let mut to_download = Vec::new();
+        let mut links = Vec::with_capacity(files.len());
+
+        for file in files {
+            let src = self.path_for(&file.named_digest.digest, file.is_executable)?;
+            if !staged.contains_key(&src) && fs_util::symlink_metadata(&src).is_err() {
+                let temp = self.temp_path()?;
+                to_download.push(NamedDigestWithPermissions {
+                    named_digest: NamedDigest {
+                        name: temp.to_string(),
+                        digest: file.named_digest.digest.clone(),
+                        ..Default::default()
+                    },
+                    is_executable: file.is_executable,
+                    ..Default::default()
+                });
+                staged.insert(
+                    src.clone(),
+                    StagedFile {
+                        temp,
+                        src: src.clone(),
+                        is_executable: file.is_executable,
+                    },
+                );
+            }
+            links.push(LocalCasLink {
+                src,
+                dest: file.named_digest.name,
+                is_executable: file.is_executable,
+            });
+        }
+
+        if !staged.is_empty() {
+            fs_util::create_dir_all(self.root.join(ForwardRelativePath::new("tmp")?))?;
+        }
+        for src in staged.keys() {
+            if let Some(parent) = src.parent() {
+                fs_util::create_dir_all(parent)?;
+            }
+        }
+
+        Ok(LocalCasPlan {
+            to_download,
+            links: LocalCasLinks {
+                staged: staged.into_values().collect(),
+                links,
+            },
+        })
+    }
+
+    /// Move downloaded files into the store, then link files out of the store into `buck-out`,
+    /// copying them if hardlinking fails.
+    pub fn link(&self, fs: &ProjectRoot, links: LocalCasLinks) -> anyhow::Result<LocalCasStats> {
+        let LocalCasLinks { staged, links } = links;
+        for StagedFile {
+            temp,
+            src,
+            is_executable,
+        } in staged
+        {
+            set_writable(&temp, is_executable, false)?;
+            fs_util::rename(&temp, &src)?;
+        }
+
+        let mut stats = LocalCasStats::default();
+        for LocalCasLink {
+            src,
+            dest,
+            is_executable,
+        } in links
+        {
+            let dest = fs.resolve(ProjectRelativePath::new(&dest)?);
+            if !self.hardlinks_unsupported.load(Ordering::Relaxed) {
+                match fs_util::hard_link(&src, &dest) {
+                    Ok(()) => {
+                        stats.hardlinked += 1;
+                        continue;
+                    }
+                    Err(e) if is_cross_device_error(&e) => {
+                        tracing::debug!(
+                            "Local CAS is on another filesystem than buck-out, falling back to copies: {:#}",
+                            e
+                        );
+                        self.hardlinks_unsupported.store(true, Ordering::Relaxed);
+                    }
+                    // E.g. the file has as many links as the filesystem allows.
+                    Err(e) => {
+                        tracing::debug!("Hardlinking from local CAS failed, copying: {:#}", e);
+                    }
+                }
+            }
+            fs_util::copy(&src, &dest)?;
+            // Copies aren't shared with the store, so they can be written like any other output.
+            set_writable(&dest, is_executable, true)?;
+            stats.copied += 1;
+        }
+        Ok(stats)
+    }
+
+    /// Remove the temporary files of a download that failed.
+    pub fn discard(&self, links: LocalCasLinks) {
+        for StagedFile { temp, .. } in links.staged {
+            let _ignored = fs_util::remove_file(&temp);
+        }
+    }
+
+    /// Removes the least recently used files of the store until it holds no more than
+    /// `max_bytes`, returning the number of bytes removed. Outputs hardlinked into `buck-out`
+    /// keep their contents, they only have to be downloaded again the next time they are needed.
+    ///
+    /// This must not run concurrently with materializations, which expect the files they found in
+    /// the store to still be there when linking them, so the daemon runs it when it starts.
+    pub fn evict(&self, max_bytes: u64) -> anyhow::Result<u64> {
+        if !fs_util::try_exists(&self.root)? {
+            return Ok(0);
+        }
+        let mut files = Vec::new();
+        let mut total = 0;
+        for dir in fs_util::read_dir(&self.root)? {
+            let dir = dir?;
+            // Temporary files are those of downloads in progress.
+            if dir.file_name() == "tmp" || !dir.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs_util::read_dir(dir.path())? {
+                let file = file?;
+                let metadata = file.metadata()?;
+                total += metadata.len();
+                files.push((last_used(&metadata)?, metadata.len(), file.path()));
+            }
+        }
+
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut evicted = 0;
+        for (_, len, path) in files {
+            if total - evicted <= max_bytes {
+                break;
+            }
+            if cfg!(windows) {
+                // Read-only files can't be removed on Windows.
+                set_writable(&path, false, true)?;
+            }
+            fs_util::remove_file(&path)?;
+            evicted += len;
+        }
+        Ok(evicted)
+    }
+}
+
+/// EXDEV on Linux and macOS, ERROR_NOT_SAME_DEVICE on Windows.
+const CROSS_DEVICE: i32 = if cfg!(windows) { 17 } else { 18 };
+
+/// Whether hardlinking failed because the store and `buck-out` are on different filesystems,
+/// which is the case for every file, unlike other errors.
+fn is_cross_device_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        e.downcast_ref::<std::io::Error>()
+            .and_then(|e| e.raw_os_error())
+            == Some(CROSS_DEVICE)
+    })
+}
+
+/// When a file of the store was last used. Adding a hardlink to a file updates its change time,
+/// so files only ever copied out of the store are evicted in the order they were added.
+fn last_used(metadata: &Metadata) -> anyhow::Result<SystemTime> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Ok(SystemTime::UNIX_EPOCH
+            + std::time::Duration::new(metadata.ctime() as u64, metadata.ctime_nsec() as u32))
+    }
+    #[cfg(not(unix))]
+    {
+        Ok(metadata.modified()?)
+    }
+}
+
+fn set_writable(path: &AbsNormPath, is_executable: bool, writable: bool) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    let permissions = {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::Permissions::from_mode(match (is_executable, writable) {
+            (false, false) => 0o444,
+            (true, false) => 0o555,
+            (false, true) => 0o644,
+            (true, true) => 0o755,
+        })
+    };
+    #[cfg(not(unix))]
+    let permissions = {
+        let _unused = is_executable;
+        let mut permissions = fs_util::metadata(path)?.permissions();
+        permissions.set_readonly(!writable);
+        permissions
+    };
+    fs_util::set_permissions(path, permissions)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use buck2_core::fs::fs_util;
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+    use buck2_core::fs::project::ProjectRoot;
+    use remote_execution::NamedDigest;
+    use remote_execution::NamedDigestWithPermissions;
+    use remote_execution::TDigest;
+
+    use crate::materializers::local_cas::is_cross_device_error;
+    use crate::materializers::local_cas::LocalCas;
+    use crate::materializers::local_cas::CROSS_DEVICE;
+
+    fn file(name: &str, hash: &str) -> NamedDigestWithPermissions {
+        NamedDigestWithPermissions {
+            named_digest: NamedDigest {
+                name: name.to_owned(),
+                digest: TDigest {
+                    hash: hash.to_owned(),
+                    size_in_bytes: 3,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            is_executable: false,
+            ..Default::default()
+        }
+    }
+
+    /// Pretend to be RE, writing the contents of the files to download.
+    fn download(plan: &[NamedDigestWithPermissions]) -> anyhow::Result<()> {
+        for f in plan {
+            fs_util::write(&f.named_digest.name, &f.named_digest.digest.hash)?;
+        }
+        Ok(())
+    }
+
+    fn setup() -> anyhow::Result<(tempfile::TempDir, ProjectRoot, LocalCas)> {
+        let temp = tempfile::tempdir()?;
+        let root = AbsNormPathBuf::try_from(temp.path().to_owned())?;
+        let fs = ProjectRoot::new(root.join_normalized("project")?);
+        fs_util::create_dir_all(fs.root())?;
+        let cas = LocalCas::new(root.join_normalized("cas")?);
+        Ok((temp, fs, cas))
+    }
+
+    #[test]
+    fn test_download_once() -> anyhow::Result<()> {
+        let (_temp, fs, cas) = setup()?;
+
+        let plan = cas.plan(vec![file("a", "aaa"), file("b", "aaa"), file("c", "ccc")])?;
+        assert_eq!(2, plan.to_download.len());
+        download(&plan.to_download)?;
+        let stats = cas.link(&fs, plan.links)?;
+        assert_eq!(3, stats.hardlinked + stats.copied);
+        assert_eq!(
+            "aaa",
+            fs_util::read_to_string(fs.root().join_normalized("b")?)?
+        );
+
+        // The same digest is now linked out of the store without downloading it.
+        let plan = cas.plan(vec![file("d", "aaa")])?;
+        assert!(plan.to_download.is_empty());
+        cas.link(&fs, plan.links)?;
+        assert_eq!(
+            "aaa",
+            fs_util::read_to_string(fs.root().join_normalized("d")?)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_interrupted_download() -> anyhow::Result<()> {
+        let (_temp, fs, cas) = setup()?;
+
+        // A download that fails half way doesn't leave anything in the store.
+        let plan = cas.plan(vec![file("a", "aaa")])?;
+        fs_util::write(&plan.to_download[0].named_digest.name, "a")?;
+        cas.discard(plan.links);
+
+        let plan = cas.plan(vec![file("a", "aaa")])?;
+        assert_eq!(1, plan.to_download.len());
+        download(&plan.to_download)?;
+        cas.link(&fs, plan.links)?;
+        assert_eq!(
+            "aaa",
+            fs_util::read_to_string(fs.root().join_normalized("a")?)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cross_device_error() {
+        let err = |code| {
+            anyhow::Error::new(std::io::Error::from_raw_os_error(code)).context("hard_link(..)")
+        };
+        assert!(is_cross_device_error(&err(CROSS_DEVICE)));
+        // Too many links: only this file needs copying.
+        assert!(!is_cross_device_error(&err(31)));
+        assert!(!is_cross_device_error(&anyhow::anyhow!("hard_link(..)")));
+    }
+
+    #[test]
+    fn test_store_is_read_only() -> anyhow::Result<()> {
+        let (_temp, fs, cas) = setup()?;
+
+        let plan = cas.plan(vec![file("a", "aaa")])?;
+        download(&plan.to_download)?;
+        cas.link(&fs, plan.links)?;
+        let a = fs.root().join_normalized("a")?;
+        assert!(fs_util::metadata(&a)?.permissions().readonly());
+
+        // Copies don't share the file of the store.
+        cas.hardlinks_unsupported.store(true, Ordering::Relaxed);
+        let plan = cas.plan(vec![file("b", "aaa")])?;
+        let stats = cas.link(&fs, plan.links)?;
+        assert_eq!(1, stats.copied);
+        let b = fs.root().join_normalized("b")?;
+        assert!(!fs_util::metadata(&b)?.permissions().readonly());
+        assert!(fs_util::metadata(&a)?.permissions().readonly());
+        Ok(())
+    }
+
+    #[test]
+    fn test_evict() -> anyhow::Result<()> {
+        let (_temp, fs, cas) = setup()?;
+
+        let files = || vec![file("a", "aaa"), file("b", "bbb"), file("c", "ccc")];
+        let plan = cas.plan(files())?;
+        download(&plan.to_download)?;
+        cas.link(&fs, plan.links)?;
+
+        assert_eq!(0, cas.evict(9)?);
+        // Each file is 3 bytes.
+        assert_eq!(3, cas.evict(7)?);
+        assert_eq!(1, cas.plan(files())?.to_download.len());
+        assert_eq!(6, cas.evict(0)?);
+        assert_eq!(3, cas.plan(files())?.to_download.len());
+
+        // Outputs linked out of the store are still there.
+        for name in ["a", "b", "c"] {
+            assert_eq!(
+                name.repeat(3),
+                fs_util::read_to_string(fs.root().join_normalized(name)?)?
+            );
+        }
+        Ok(())
+    }
+}
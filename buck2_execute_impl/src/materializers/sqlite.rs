@@ -17,11 +17,13 @@ use buck2_common::file_ops::FileMetadata;
 use buck2_common::file_ops::TrackedFileDigest;
 use buck2_common::sqlite::KeyValueSqliteTable;
 use buck2_core::directory::DirectoryEntry;
+use buck2_core::directory::FingerprintedDirectory;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
 use buck2_core::fs::paths::file_name::FileName;
 use buck2_core::fs::project::ProjectRelativePathBuf;
+use buck2_core::fs::project::ProjectRoot;
 use buck2_execute::directory::ActionDirectoryMember;
 use buck2_execute::directory::Symlink;
 use buck2_execute::execute::blocking::BlockingExecutor;
@@ -34,6 +36,7 @@ use parking_lot::Mutex;
 use rusqlite::Connection;
 use thiserror::Error;
 
+use crate::executors::local::build_entry_from_disk;
 use crate::materializers::deferred::ArtifactMetadata;
 
 /// Hand-maintained schema version for the materializer state sqlite db.
@@ -570,6 +573,54 @@ impl MaterializerStateSqliteDb {
         self.last_read_by_table.create_table()?;
         Ok(())
     }
+
+    /// Drop the entries of `state` that don't match what's on disk, which happens if the daemon
+    /// that recorded them was killed while it was writing them. Their paths are deleted, so that
+    /// they get materialized again when they are needed; failing to delete one is only logged, as
+    /// materializing it again overwrites it anyway. Returns the state that was kept and the paths
+    /// that were dropped.
+    pub fn discard_inconsistent(
+        &mut self,
+        state: MaterializerState,
+        fs: &ProjectRoot,
+    ) -> anyhow::Result<(MaterializerState, Vec<ProjectRelativePathBuf>)> {
+        let (kept, discarded): (Vec<_>, Vec<_>) = state
+            .into_iter()
+            .partition(|(path, (metadata, _))| matches_disk(metadata, &fs.resolve(path)));
+        let discarded = discarded.into_map(|(path, _)| path);
+
+        for path in &discarded {
+            if let Err(e) = fs.remove_path_recursive(path) {
+                tracing::warn!("Error deleting incomplete output `{}`: {:#}", path, e);
+            }
+        }
+        self.materializer_state_table.delete(discarded.clone())?;
+
+        Ok((kept, discarded))
+    }
+}
+
+/// Whether what's at `path` looks like the artifact described by `metadata`. Files are only
+/// checked by size, as hashing every file in buck-out would take too long. Directories are hashed,
+/// as any file in them may be missing or incomplete.
+fn matches_disk(metadata: &ArtifactMetadata, path: &AbsNormPath) -> bool {
+    let disk = match fs_util::symlink_metadata(path) {
+        Ok(disk) => disk,
+        Err(_) => return false,
+    };
+    match &metadata.0 {
+        DirectoryEntry::Dir(fingerprint) => match build_entry_from_disk(path.to_owned()) {
+            Ok(Some(DirectoryEntry::Dir(dir))) => dir.fingerprint().fingerprint() == fingerprint,
+            _ => false,
+        },
+        DirectoryEntry::Leaf(ActionDirectoryMember::File(file)) => {
+            disk.is_file() && disk.len() == file.digest.size()
+        }
+        // Symlinks may be copies when symlinks are not supported, so they only need to exist.
+        DirectoryEntry::Leaf(
+            ActionDirectoryMember::Symlink(_) | ActionDirectoryMember::ExternalSymlink(_),
+        ) => true,
+    }
 }
 
 #[cfg(test)]
@@ -727,6 +778,80 @@ mod tests {
         assert_eq!(artifacts, state.into_iter().collect::<HashMap<_, _>>());
     }
 
+    #[test]
+    fn test_discard_inconsistent() -> anyhow::Result<()> {
+        let fs = ProjectRootTemp::new()?;
+        let (mut db, _) = testing_materializer_state_sqlite_db(
+            fs.path(),
+            HashMap::from([("version".to_owned(), "0".to_owned())]),
+            HashMap::new(),
+        )?;
+
+        let file = |contents: &[u8]| {
+            ArtifactMetadata(DirectoryEntry::Leaf(ActionDirectoryMember::File(
+                FileMetadata {
+                    digest: TrackedFileDigest::new(FileDigest::from_bytes_sha1(contents)),
+                    is_executable: false,
+                },
+            )))
+        };
+        let complete = ProjectRelativePath::unchecked_new("complete").to_owned();
+        let truncated = ProjectRelativePath::unchecked_new("truncated").to_owned();
+        let missing = ProjectRelativePath::unchecked_new("missing").to_owned();
+        let complete_dir = ProjectRelativePath::unchecked_new("complete_dir").to_owned();
+        let partial_dir = ProjectRelativePath::unchecked_new("partial_dir").to_owned();
+        fs.path().write_file(&complete, "contents", false)?;
+        fs.path().write_file(&truncated, "cont", false)?;
+        fs.path().write_file(
+            ProjectRelativePath::unchecked_new("complete_dir/a"),
+            "contents",
+            false,
+        )?;
+        fs.path().write_file(
+            ProjectRelativePath::unchecked_new("partial_dir/a"),
+            "cont",
+            false,
+        )?;
+
+        let dir = match build_entry_from_disk(fs.path().resolve(&complete_dir))? {
+            Some(DirectoryEntry::Dir(dir)) => {
+                ArtifactMetadata(DirectoryEntry::Dir(dir.fingerprint().fingerprint().dupe()))
+            }
+            _ => unreachable!(),
+        };
+
+        let state = vec![
+            (complete.clone(), (file(b"contents"), now_seconds())),
+            (truncated.clone(), (file(b"contents"), now_seconds())),
+            (missing.clone(), (file(b"contents"), now_seconds())),
+            (complete_dir.clone(), (dir.clone(), now_seconds())),
+            (partial_dir.clone(), (dir, now_seconds())),
+        ];
+        for (path, (metadata, timestamp)) in &state {
+            db.materializer_state_table()
+                .insert(path.clone(), metadata.clone(), *timestamp)?;
+        }
+
+        let (kept, discarded) = db.discard_inconsistent(state, fs.path())?;
+        assert_eq!(
+            kept.into_map(|(path, _)| path),
+            vec![complete.clone(), complete_dir.clone()]
+        );
+        assert_eq!(
+            discarded,
+            vec![truncated.clone(), missing, partial_dir.clone()]
+        );
+        assert!(!fs.path().resolve(&truncated).exists());
+        assert!(!fs.path().resolve(&partial_dir).exists());
+        assert_eq!(
+            db.materializer_state_table()
+                .read_all()?
+                .into_map(|(path, _)| path),
+            vec![complete, complete_dir]
+        );
+        Ok(())
+    }
+
     fn testing_materializer_state_sqlite_db(
         fs: &ProjectRoot,
         versions: HashMap<String, String>,
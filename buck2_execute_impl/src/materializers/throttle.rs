@@ -0,0 +1,68 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Caps the rate at which the materializer writes bytes to disk.
+///
+/// Each caller reserves a slot for the bytes it is about to write, and waits until the bytes
+/// reserved before it would have been written at the configured rate. This smooths out bursts,
+/// so that materializing a large build does not saturate the disk.
+pub struct BandwidthThrottle {
+    bytes_per_second: u64,
+    /// The point in time at which all bytes reserved so far will have been written.
+    next_free: Mutex<Instant>,
+}
+
+impl BandwidthThrottle {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: std::cmp::max(bytes_per_second, 1),
+            next_free: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Reserve `bytes`, and return how long the caller must wait before writing them.
+    fn reserve(&self, bytes: u64) -> Duration {
+        let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64);
+        let now = Instant::now();
+        let mut next_free = self.next_free.lock().unwrap();
+        let start = std::cmp::max(*next_free, now);
+        *next_free = start + cost;
+        start - now
+    }
+
+    /// Wait until we are allowed to write `bytes`.
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::materializers::throttle::BandwidthThrottle;
+
+    #[test]
+    fn test_reserve() {
+        let throttle = BandwidthThrottle::new(100);
+        // The first reservation can go ahead immediately.
+        assert_eq!(throttle.reserve(100), Duration::ZERO);
+        // The second one has to wait for the first second to elapse.
+        let wait = throttle.reserve(100);
+        assert!(wait > Duration::from_millis(900), "{:?}", wait);
+        assert!(wait <= Duration::from_secs(1), "{:?}", wait);
+    }
+}
@@ -19,6 +19,7 @@ use buck2_common::file_ops::TrackedFileDigest;
 use buck2_core::directory::DirectoryEntry;
 use buck2_core::env_helper::EnvHelper;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_execute::artifact_value::ArtifactValue;
 use buck2_execute::digest::CasDigestFromReExt;
 use buck2_execute::directory::extract_artifact_value;
@@ -97,6 +98,74 @@ pub async fn download_action_results<'a>(
     )
 }
 
+/// Download the diagnostic outputs a failed remote action left behind, so that they are on disk
+/// when the failure is reported. Returns the paths that were downloaded.
+pub async fn download_diagnostic_outputs(
+    request: &CommandExecutionRequest,
+    materializer: &dyn Materializer,
+    re_use_case: RemoteExecutorUseCase,
+    action_digest: &ActionDigest,
+    response: &dyn RemoteActionResult,
+) -> anyhow::Result<Vec<ProjectRelativePathBuf>> {
+    if request.diagnostic_outputs().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let retrieved_instant = Instant::now();
+    let ttl = response.ttl();
+    let expires = Utc::now() + Duration::seconds(ttl);
+
+    let mut to_declare = Vec::new();
+    for x in response.output_files() {
+        let name = re_forward_path(x.name.as_str())?;
+        let path = match request
+            .diagnostic_outputs()
+            .iter()
+            .find(|p| p.as_str() == name.as_str())
+        {
+            Some(path) => path,
+            None => continue,
+        };
+
+        let digest = FileDigest::from_re(&x.digest.digest);
+        let digest = TrackedFileDigest::new_expires(digest, expires);
+        to_declare.push((
+            path.clone(),
+            ArtifactValue::file(FileMetadata {
+                digest,
+                is_executable: x.executable,
+            }),
+        ));
+    }
+
+    if to_declare.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let paths = to_declare.map(|(path, _)| path.clone());
+
+    materializer
+        .declare_cas_many(
+            Arc::new(CasDownloadInfo::new_execution(
+                TrackedActionDigest::new_expires(action_digest.dupe(), expires),
+                re_use_case,
+                retrieved_instant,
+                std::time::Duration::from_secs(ttl.try_into().unwrap_or(0)),
+            )),
+            to_declare,
+        )
+        .await
+        .context(DownloadError::Materialization)?;
+
+    // Unlike regular outputs, nothing would ever ask for these, so materialize them now.
+    materializer
+        .ensure_materialized(paths.clone())
+        .await
+        .context(DownloadError::DiagnosticOutputs)?;
+
+    Ok(paths)
+}
+
 pub struct CasDownloader<'a> {
     pub materializer: &'a dyn Materializer,
     pub re_client: &'a ManagedRemoteExecutionClient,
@@ -247,4 +316,7 @@ enum DownloadError {
 
     #[error("Path received from RE is not normalized.")]
     InvalidPathFromRe,
+
+    #[error("Failed to materialize diagnostic outputs")]
+    DiagnosticOutputs,
 }
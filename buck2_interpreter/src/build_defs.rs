@@ -7,6 +7,10 @@
  * of this source tree.
  */
 
+use std::time::Duration;
+use std::time::Instant;
+
+use buck2_data::WarningCategory;
 use sha2::Digest;
 use sha2::Sha256;
 use starlark::environment::GlobalsBuilder;
@@ -17,6 +21,9 @@ use crate::extra::BuildContext;
 use crate::globspec::GlobSpec;
 use crate::selector::Selector;
 
+/// Globs taking longer than this to evaluate are reported as warnings.
+const SLOW_GLOB_THRESHOLD: Duration = Duration::from_millis(500);
+
 #[starlark_module]
 pub fn native_module(builder: &mut GlobalsBuilder) {
     fn select<'v>(#[starlark(require = pos)] d: Value<'v>) -> anyhow::Result<Selector<'v>> {
@@ -56,11 +63,26 @@ pub fn native_module(builder: &mut GlobalsBuilder) {
     ) -> anyhow::Result<Value<'v>> {
         let extra = BuildContext::from_context(eval)?;
         let excludes = exclude.unwrap_or_default();
+        let start = Instant::now();
         let spec = GlobSpec::new(&include, &excludes)?;
-        let res = extra
-            .resolve_glob(&spec)?
-            .map(|path| eval.heap().alloc(path.as_str()));
-        Ok(eval.heap().alloc_list_iter(res))
+        let res: Vec<_> = extra.resolve_glob(&spec)?.collect();
+        let elapsed = start.elapsed();
+        if elapsed >= SLOW_GLOB_THRESHOLD {
+            buck2_events::dispatch::warning(
+                WarningCategory::SlowGlob,
+                extra.require_package()?.to_string(),
+                format!(
+                    "glob(include = {:?}, exclude = {:?}) took {}ms to match {} files",
+                    include,
+                    excludes,
+                    elapsed.as_millis(),
+                    res.len()
+                ),
+            );
+        }
+        Ok(eval
+            .heap()
+            .alloc_list_iter(res.into_iter().map(|path| eval.heap().alloc(path.as_str()))))
     }
 
     fn package(eval: &mut Evaluator) -> anyhow::Result<String> {
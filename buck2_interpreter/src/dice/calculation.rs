@@ -24,6 +24,7 @@ use buck2_common::package_listing::listing::PackageListing;
 use buck2_common::package_listing::resolver::PackageListingResolver;
 use buck2_common::result::SharedResult;
 use buck2_core::build_file_path::BuildFilePath;
+use buck2_core::bzl::ImportPath;
 use buck2_core::cells::build_file_cell::BuildFileCell;
 use buck2_core::cells::cell_path::CellPath;
 use buck2_core::cells::CellName;
@@ -35,6 +36,7 @@ use dice::DiceComputations;
 use dice::Key;
 use gazebo::prelude::*;
 use starlark::codemap::FileSpan;
+use starlark::environment::FrozenModule;
 use starlark::syntax::AstModule;
 use thiserror::Error;
 
@@ -328,14 +330,22 @@ impl<'c> DiceCalculationDelegate<'c> {
         starlark_file: StarlarkModulePath<'_>,
         starlark_profiler_instrumentation: Option<StarlarkProfilerInstrumentation>,
     ) -> anyhow::Result<LoadedModule> {
+        if let StarlarkModulePath::LoadFile(import) = starlark_file {
+            if import.build_file_cell().name() != import.cell() {
+                if let Some(shared) = self.shared_module(import).await? {
+                    return Ok(shared);
+                }
+            }
+        }
+
         let (ast, deps) = self.prepare_eval(starlark_file.into()).await?;
         let loaded_modules = deps.get_loaded_modules();
         let buckconfig = self.get_legacy_buck_config_for_starlark().await?;
 
-        let evaluation = self
+        let (evaluation, mut build_file_cell_reads) = self
             .get_interpreter_for_cell()
             .await?
-            .eval_module(
+            .eval_module_with_build_file_cell_reads(
                 starlark_file,
                 &buckconfig,
                 ast,
@@ -344,11 +354,77 @@ impl<'c> DiceCalculationDelegate<'c> {
             )
             .with_context(|| EvalModuleError(starlark_file.to_string()))?;
 
-        Ok(LoadedModule::new(
-            OwnedStarlarkModulePath::new(starlark_file),
-            loaded_modules,
-            evaluation,
-        ))
+        // The modules loaded into the same cell as this one are part of its result. Others (the
+        // prelude) are the same whatever the cell is.
+        let mut build_file_cell_reads_known = true;
+        for dep in &deps.0 {
+            if dep.path().build_file_cell() == starlark_file.build_file_cell() {
+                match dep.build_file_cell_reads() {
+                    Some(reads) => build_file_cell_reads.merge(reads),
+                    None => build_file_cell_reads_known = false,
+                }
+            }
+        }
+
+        let path = OwnedStarlarkModulePath::new(starlark_file);
+        Ok(if build_file_cell_reads_known {
+            LoadedModule::new_with_build_file_cell_reads(
+                path,
+                loaded_modules,
+                evaluation,
+                build_file_cell_reads,
+            )
+        } else {
+            LoadedModule::new(path, loaded_modules, evaluation)
+        })
+    }
+
+    /// Evaluate a `.bzl` or `.bxl` file, measuring the memory each of its definitions retains.
+    /// Unlike `eval_module`, this always evaluates the file again (the modules it loads come from
+    /// DICE as usual).
+    pub async fn eval_module_with_retention(
+        &self,
+        starlark_file: StarlarkModulePath<'_>,
+    ) -> anyhow::Result<FrozenModule> {
+        let (ast, deps) = self.prepare_eval(starlark_file.into()).await?;
+        let buckconfig = self.get_legacy_buck_config_for_starlark().await?;
+        self.get_interpreter_for_cell()
+            .await?
+            .eval_module_with_retention(starlark_file, &buckconfig, ast, deps.get_loaded_modules())
+            .with_context(|| EvalModuleError(starlark_file.to_string()))
+    }
+
+    /// A `.bzl` file loaded (perhaps transitively) into a build file of another cell is evaluated
+    /// in the context of that cell, as it can read the cell's buckconfig or resolve labels
+    /// relative to it. Most files don't, so evaluating them once per cell that loads them only
+    /// costs time and memory. Instead, the file is evaluated in the context of its own cell, and
+    /// that module is reused if it didn't use the cell and the buckconfig values it read are the
+    /// same in the cell it is loaded into. So a file loaded into another cell must also evaluate
+    /// in the context of its own cell, and its errors there are returned.
+    async fn shared_module(&self, import: &ImportPath) -> anyhow::Result<Option<LoadedModule>> {
+        let own_cell = BuildFileCell::new(import.cell().clone());
+        let own_import = ImportPath::new(import.path().clone(), own_cell.clone())?;
+        let module = self
+            .ctx
+            .get_interpreter_calculator(import.cell(), &own_cell)
+            .await?
+            .eval_module(StarlarkModulePath::LoadFile(&own_import))
+            .await?;
+
+        let reads = match module.build_file_cell_reads() {
+            Some(reads) if !reads.cell => reads,
+            _ => return Ok(None),
+        };
+        let buckconfig = self.get_legacy_buck_config_for_starlark().await?;
+        for (section, key, value) in &reads.buckconfig {
+            if buckconfig.get(section, key)?.as_deref() != value.as_deref() {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(module.shared_as(OwnedStarlarkModulePath::LoadFile(
+            import.clone(),
+        ))))
     }
 
     pub async fn eval_build_file<T: ExtraContext>(
@@ -31,6 +31,8 @@ pub struct LegacyBuckConfigForStarlark<'a> {
     /// So we hash the `key` even if the section does not exist,
     /// but this is practically not an issue, because keys usually come with cached hash.
     cache: RefCell<RawTable<BuckConfigEntry>>,
+    /// Every `(section, key)` that was read, with its value, in the order they were first read.
+    reads: RefCell<Vec<(String, String, Option<String>)>>,
 }
 
 impl<'a> fmt::Debug for LegacyBuckConfigForStarlark<'a> {
@@ -66,6 +68,7 @@ impl<'a> LegacyBuckConfigForStarlark<'a> {
             module,
             buckconfig,
             cache: RefCell::new(RawTable::new()),
+            reads: RefCell::new(Vec::new()),
         }
     }
 
@@ -82,10 +85,13 @@ impl<'a> LegacyBuckConfigForStarlark<'a> {
             return Ok(e.value);
         }
 
-        let value = self
-            .buckconfig
-            .get(section.key(), key.key())?
-            .map(|v| self.module.frozen_heap().alloc_str(&v));
+        let value = self.buckconfig.get(section.key(), key.key())?;
+        self.reads.borrow_mut().push((
+            (*section.key()).to_owned(),
+            (*key.key()).to_owned(),
+            value.as_deref().map(str::to_owned),
+        ));
+        let value = value.map(|v| self.module.frozen_heap().alloc_str(&v));
 
         cache.insert(
             hash,
@@ -100,6 +106,11 @@ impl<'a> LegacyBuckConfigForStarlark<'a> {
         Ok(value)
     }
 
+    /// The entries that were read so far, with their values.
+    pub(crate) fn reads(&self) -> Vec<(String, String, Option<String>)> {
+        self.reads.borrow().clone()
+    }
+
     /// Find the buckconfig entry.
     pub fn get(
         &self,
@@ -8,6 +8,7 @@
  */
 
 use std::any::Any;
+use std::cell::Cell;
 use std::fmt;
 use std::fmt::Debug;
 use std::sync::Arc;
@@ -31,6 +32,7 @@ use thiserror::Error;
 use crate::common::StarlarkPath;
 use crate::extra::buckconfig::LegacyBuckConfigForStarlark;
 use crate::extra::cell_info::InterpreterCellInfo;
+use crate::file_loader::BuildFileCellReads;
 use crate::file_loader::LoadedModules;
 use crate::globspec::GlobSpec;
 use crate::package_imports::ImplicitImport;
@@ -82,6 +84,10 @@ pub struct BuildContext<'a> {
     /// `load()` statements.
     cell_info: &'a InterpreterCellInfo,
 
+    /// Whether `cell_info` was used, in which case a `.bzl` file can't be shared with other
+    /// cells.
+    cell_info_used: Cell<bool>,
+
     pub(crate) buckconfig: LegacyBuckConfigForStarlark<'a>,
 
     /// The import path that is being evaluated
@@ -118,6 +124,7 @@ impl<'a> BuildContext<'a> {
         let buckconfig = LegacyBuckConfigForStarlark::new(module, buckconfig);
         BuildContext {
             cell_info,
+            cell_info_used: Cell::new(false),
             buckconfig,
             starlark_path,
             listing,
@@ -138,9 +145,18 @@ impl<'a> BuildContext<'a> {
     }
 
     pub fn cell_info(&self) -> &InterpreterCellInfo {
+        self.cell_info_used.set(true);
         self.cell_info
     }
 
+    /// What the evaluation read from the cell of the top-level module so far.
+    pub fn build_file_cell_reads(&self) -> BuildFileCellReads {
+        BuildFileCellReads {
+            cell: self.cell_info_used.get(),
+            buckconfig: self.buckconfig.reads(),
+        }
+    }
+
     pub fn require_package(&self) -> anyhow::Result<&Package> {
         match self.starlark_path {
             StarlarkPath::BuildFile(b) => Ok(b.package()),
@@ -294,6 +310,7 @@ pub mod testing {
     use crate::extra::InterpreterHostArchitecture;
     use crate::extra::InterpreterHostPlatform;
     use crate::file_loader::LoadedModules;
+    use crate::functions::read_config::register_read_config;
     use crate::interpreter::configure_base_globals;
     use crate::package_imports::ImplicitImport;
 
@@ -396,18 +413,21 @@ pub mod testing {
     impl InterpreterConfiguror for TesterConfiguror {
         fn build_file_globals(&self) -> Globals {
             let mut globals_builder = configure_base_globals(|_| {});
+            register_read_config(&mut globals_builder);
             add_builtins(&mut globals_builder, &self.rules);
             globals_builder.build()
         }
 
         fn extension_file_globals(&self) -> Globals {
             let mut globals_builder = configure_base_globals(|_| {});
+            register_read_config(&mut globals_builder);
             add_builtins(&mut globals_builder, &self.rules);
             globals_builder.build()
         }
 
         fn bxl_file_globals(&self) -> Globals {
             let mut globals_builder = configure_base_globals(|_| {});
+            register_read_config(&mut globals_builder);
             add_builtins(&mut globals_builder, &self.rules);
             globals_builder.build()
         }
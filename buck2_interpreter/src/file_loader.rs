@@ -58,6 +58,28 @@ impl ModuleDeps {
     }
 }
 
+/// What the evaluation of a `.bzl` file (and of the files it loads in the same context) read from
+/// the cell of the build file it was loaded into. A file that read nothing from it evaluates to
+/// the same module whatever that cell is.
+#[derive(Clone, Debug, Default, Allocative)]
+pub struct BuildFileCellReads {
+    /// Whether the cell itself was used, e.g. to resolve labels or get the cell name.
+    pub cell: bool,
+    /// The buckconfig values that were read, as `(section, key, value)`.
+    pub buckconfig: Vec<(String, String, Option<String>)>,
+}
+
+impl BuildFileCellReads {
+    pub fn merge(&mut self, other: &BuildFileCellReads) {
+        self.cell |= other.cell;
+        for read in &other.buckconfig {
+            if !self.buckconfig.contains(read) {
+                self.buckconfig.push(read.clone());
+            }
+        }
+    }
+}
+
 #[derive(Clone, Dupe, Allocative, Debug)]
 pub struct LoadedModule(Arc<LoadedModuleData>);
 
@@ -69,6 +91,8 @@ struct LoadedModuleData {
     loaded_modules: LoadedModules,
     #[derivative(Debug = "ignore")]
     env: FrozenModule,
+    /// `None` if unknown.
+    build_file_cell_reads: Option<BuildFileCellReads>,
 }
 
 impl LoadedModule {
@@ -81,6 +105,31 @@ impl LoadedModule {
             path,
             loaded_modules,
             env,
+            build_file_cell_reads: None,
+        }))
+    }
+
+    pub fn new_with_build_file_cell_reads(
+        path: OwnedStarlarkModulePath,
+        loaded_modules: LoadedModules,
+        env: FrozenModule,
+        build_file_cell_reads: BuildFileCellReads,
+    ) -> Self {
+        Self(Arc::new(LoadedModuleData {
+            path,
+            loaded_modules,
+            env,
+            build_file_cell_reads: Some(build_file_cell_reads),
+        }))
+    }
+
+    /// The same module, loaded as `path` (the same file, loaded into another cell).
+    pub fn shared_as(&self, path: OwnedStarlarkModulePath) -> Self {
+        Self(Arc::new(LoadedModuleData {
+            path,
+            loaded_modules: self.0.loaded_modules.clone(),
+            env: self.0.env.dupe(),
+            build_file_cell_reads: self.0.build_file_cell_reads.clone(),
         }))
     }
 
@@ -99,6 +148,10 @@ impl LoadedModule {
     pub fn env(&self) -> &FrozenModule {
         &self.0.env
     }
+
+    pub fn build_file_cell_reads(&self) -> Option<&BuildFileCellReads> {
+        self.0.build_file_cell_reads.as_ref()
+    }
 }
 
 pub struct InterpreterFileLoader {
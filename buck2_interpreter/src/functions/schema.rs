@@ -0,0 +1,192 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::fmt::Write;
+
+use starlark::environment::GlobalsBuilder;
+use starlark::values::dict::DictRef;
+use starlark::values::Heap;
+use starlark::values::Value;
+
+#[derive(Debug, thiserror::Error)]
+enum SchemaError {
+    #[error("`validate_schema` expects a dict or a struct to validate, got `{0}`")]
+    NotDictOrStruct(String),
+    #[error("Keys of {0} must be strings, got `{1}`")]
+    KeyNotString(&'static str, String),
+    #[error("Key `{0}` listed as optional is not in the schema")]
+    UnknownOptional(String),
+    #[error("Invalid {0}:{1}")]
+    Invalid(String, String),
+}
+
+/// The fields of a dict or struct, in order.
+fn fields<'v>(value: Value<'v>, heap: &'v Heap) -> anyhow::Result<Vec<(String, Value<'v>)>> {
+    if let Some(dict) = DictRef::from_value(value) {
+        dict.iter()
+            .map(|(k, v)| match k.unpack_str() {
+                Some(k) => Ok((k.to_owned(), v)),
+                None => Err(SchemaError::KeyNotString("validated dicts", k.to_repr()).into()),
+            })
+            .collect()
+    } else if value.get_type() == "struct" {
+        value
+            .dir_attr()
+            .into_iter()
+            .map(|k| {
+                let v = value.get_attr(&k, heap)?.unwrap_or_else(Value::new_none);
+                Ok((k, v))
+            })
+            .collect()
+    } else {
+        Err(SchemaError::NotDictOrStruct(value.get_type().to_owned()).into())
+    }
+}
+
+/// All the problems found validating `value` against `schema`, one per line.
+fn validate<'v>(
+    value: Value<'v>,
+    schema: DictRef<'v>,
+    optional: &[String],
+    allow_extra: bool,
+    heap: &'v Heap,
+) -> anyhow::Result<String> {
+    let fields = fields(value, heap)?;
+    let mut problems = String::new();
+
+    let mut expected = Vec::with_capacity(schema.len());
+    for (key, ty) in schema.iter() {
+        let key = key
+            .unpack_str()
+            .ok_or_else(|| SchemaError::KeyNotString("schemas", key.to_repr()))?;
+        expected.push(key);
+        match fields.iter().find(|(k, _)| k == key) {
+            Some((_, v)) => {
+                if !v.is_type(ty, heap)? {
+                    write!(
+                        problems,
+                        "\n  `{}`: expected `{}`, got `{}` of type `{}`",
+                        key,
+                        ty.to_str(),
+                        v.to_repr(),
+                        v.get_type()
+                    )?;
+                }
+            }
+            None if optional.iter().any(|o| o == key) => {}
+            None => write!(problems, "\n  `{}`: missing required key", key)?,
+        }
+    }
+
+    for key in optional {
+        if !expected.contains(&key.as_str()) {
+            return Err(SchemaError::UnknownOptional(key.clone()).into());
+        }
+    }
+
+    if !allow_extra {
+        for (key, _) in &fields {
+            if !expected.contains(&key.as_str()) {
+                write!(
+                    problems,
+                    "\n  `{}`: unexpected key, expected one of: {}",
+                    key,
+                    expected.join(", ")
+                )?;
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+#[starlark_module]
+pub(crate) fn validate_schema(builder: &mut GlobalsBuilder) {
+    /// Validate a dict (or struct) of structured configuration against a schema, and return it
+    /// unchanged. The schema maps every key to the type annotation its value must match, like
+    /// the ones of function parameters:
+    ///
+    /// ```python
+    /// config = validate_schema(
+    ///     config,
+    ///     {"name": str.type, "srcs": [str.type], "timeout": int.type},
+    ///     optional = ["timeout"],
+    ///     what = "my_macro(config)",
+    /// )
+    /// ```
+    ///
+    /// Keys in `optional` may be missing. Keys which are not in the schema are errors, unless
+    /// `allow_extra` is set. All the problems are reported at once.
+    fn validate_schema<'v>(
+        #[starlark(require = pos)] value: Value<'v>,
+        #[starlark(require = pos)] schema: DictRef<'v>,
+        #[starlark(require = named)] optional: Option<Vec<String>>,
+        #[starlark(require = named, default = false)] allow_extra: bool,
+        #[starlark(require = named, default = "value")] what: &str,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        let problems = validate(
+            value,
+            schema,
+            &optional.unwrap_or_default(),
+            allow_extra,
+            heap,
+        )?;
+        if problems.is_empty() {
+            Ok(value)
+        } else {
+            Err(SchemaError::Invalid(what.to_owned(), problems).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starlark::assert::Assert;
+
+    use crate::functions::schema::validate_schema;
+
+    fn assert() -> Assert {
+        let mut a = Assert::new();
+        a.globals_add(validate_schema);
+        a
+    }
+
+    #[test]
+    fn test_validate_schema() {
+        let a = assert();
+        a.pass(
+            r#"
+schema = {"name": str.type, "srcs": [str.type], "timeout": int.type}
+d = {"name": "a", "srcs": ["a.c"]}
+assert_eq(validate_schema(d, schema, optional = ["timeout"]), d)
+validate_schema(struct(name = "a", srcs = [], timeout = 1), schema)
+validate_schema({"name": "a", "srcs": [], "timeout": 1, "other": 1}, schema, allow_extra = True)
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_validate_schema_errors() {
+        let a = assert();
+        a.fail(
+            r#"validate_schema({"srcs": "a.c"}, {"name": str.type, "srcs": [str.type]}, what = "cfg")"#,
+            "Invalid cfg:\n  `name`: missing required key\n  `srcs`: expected `[\"string\"]`, got `\"a.c\"` of type `string`",
+        );
+        a.fail(
+            r#"validate_schema({"name": "a", "nme": 1}, {"name": str.type})"#,
+            "`nme`: unexpected key, expected one of: name",
+        );
+        a.fail(
+            r#"validate_schema({}, {"name": str.type}, optional = ["nmae"])"#,
+            "Key `nmae` listed as optional is not in the schema",
+        );
+        a.fail(r#"validate_schema([], {})"#, "expects a dict or a struct");
+    }
+}
@@ -49,10 +49,12 @@ use crate::extra::BuildContext;
 use crate::extra::ExtraContext;
 use crate::extra::ExtraContextDyn;
 use crate::extra::InterpreterConfiguror;
+use crate::file_loader::BuildFileCellReads;
 use crate::file_loader::InterpreterFileLoader;
 use crate::file_loader::LoadResolver;
 use crate::file_loader::LoadedModules;
 use crate::functions::dedupe::dedupe;
+use crate::functions::schema::validate_schema;
 use crate::import_paths::ImportPaths;
 use crate::package_imports::ImplicitImport;
 use crate::parse_import::parse_import;
@@ -230,7 +232,8 @@ pub fn configure_base_globals(
     let mut global_env = GlobalsBuilder::extended_by(&starlark_extensions)
         .with(register_globals)
         .with(register_natives)
-        .with(dedupe);
+        .with(dedupe)
+        .with(validate_schema);
     global_env.struct_("__internal__", |x| {
         register_natives(x);
         // If `native.` symbols need to be added to the global env, they should be done
@@ -621,7 +624,7 @@ impl InterpreterForCell {
         listing: Option<PackageListing>,
         extra_context: Option<Box<dyn ExtraContextDyn>>,
         profiler: &mut StarlarkProfilerOrInstrumentation,
-    ) -> anyhow::Result<Option<Box<dyn ExtraContextDyn>>> {
+    ) -> anyhow::Result<(Option<Box<dyn ExtraContextDyn>>, BuildFileCellReads)> {
         let globals = match import {
             StarlarkPath::BuildFile(_) => self.config.build_file_global_env(),
             StarlarkPath::LoadFile(_) => self.config.extension_file_global_env(),
@@ -660,7 +663,8 @@ impl InterpreterForCell {
                     .visit_frozen_module(None)
                     .context("Profiler heap visitation failed")?;
 
-                Ok(extra.additional)
+                let build_file_cell_reads = extra.build_file_cell_reads();
+                Ok((extra.additional, build_file_cell_reads))
             }
             Err(p) => Err(p),
         }
@@ -677,8 +681,29 @@ impl InterpreterForCell {
         loaded_modules: LoadedModules,
         starlark_profiler_instrumentation: Option<StarlarkProfilerInstrumentation>,
     ) -> anyhow::Result<FrozenModule> {
+        Ok(self
+            .eval_module_with_build_file_cell_reads(
+                starlark_path,
+                buckconfig,
+                ast,
+                loaded_modules,
+                starlark_profiler_instrumentation,
+            )?
+            .0)
+    }
+
+    /// Like `eval_module`, but also returns what the module itself (not the modules it loads)
+    /// read from the cell of the build file it is loaded into.
+    pub fn eval_module_with_build_file_cell_reads(
+        &self,
+        starlark_path: StarlarkModulePath<'_>,
+        buckconfig: &dyn LegacyBuckConfigView,
+        ast: AstModule,
+        loaded_modules: LoadedModules,
+        starlark_profiler_instrumentation: Option<StarlarkProfilerInstrumentation>,
+    ) -> anyhow::Result<(FrozenModule, BuildFileCellReads)> {
         let env = self.create_env(starlark_path.into(), &loaded_modules)?;
-        self.eval(
+        let (_, build_file_cell_reads) = self.eval(
             &env,
             ast,
             StarlarkPath::from(starlark_path),
@@ -690,6 +715,30 @@ impl InterpreterForCell {
                 starlark_profiler_instrumentation,
             ),
         )?;
+        Ok((env.freeze()?, build_file_cell_reads))
+    }
+
+    /// Like `eval_module`, but measures the memory each definition of the module retains, which
+    /// is then available from `FrozenModule::retention`.
+    pub fn eval_module_with_retention(
+        &self,
+        starlark_path: StarlarkModulePath<'_>,
+        buckconfig: &dyn LegacyBuckConfigView,
+        ast: AstModule,
+        loaded_modules: LoadedModules,
+    ) -> anyhow::Result<FrozenModule> {
+        let env = self.create_env(starlark_path.into(), &loaded_modules)?;
+        env.enable_retention();
+        self.eval(
+            &env,
+            ast,
+            StarlarkPath::from(starlark_path),
+            buckconfig,
+            loaded_modules,
+            None,
+            None,
+            &mut StarlarkProfilerOrInstrumentation::disabled(),
+        )?;
         env.freeze()
     }
 
@@ -712,18 +761,17 @@ impl InterpreterForCell {
             package_boundary_exception,
             &loaded_modules,
         )?;
-        let internals = self
-            .eval(
-                &env,
-                ast,
-                StarlarkPath::BuildFile(build_file),
-                buckconfig,
-                loaded_modules,
-                Some(listing),
-                Some(internals),
-                profiler,
-            )?
-            .expect("We sent a context, expect one back");
+        let (internals, _) = self.eval(
+            &env,
+            ast,
+            StarlarkPath::BuildFile(build_file),
+            buckconfig,
+            loaded_modules,
+            Some(listing),
+            Some(internals),
+            profiler,
+        )?;
+        let internals = internals.expect("We sent a context, expect one back");
 
         Ok(T::into_eval_result(internals).expect("The result to match the context type"))
     }
@@ -924,6 +972,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_build_file_cell_reads() -> anyhow::Result<()> {
+        let interpreter = Tester::new()?.interpreter()?;
+        let import_path = cross_cell_import("cell1", "imports", "one.bzl", "root");
+        let path = StarlarkModulePath::LoadFile(&import_path);
+        let eval = |content: &str| -> anyhow::Result<BuildFileCellReads> {
+            let ParseResult(ast, _) = interpreter.parse(path.into(), content.to_owned())?;
+            Ok(interpreter
+                .eval_module_with_build_file_cell_reads(
+                    path,
+                    &LegacyBuckConfig::empty(),
+                    ast,
+                    LoadedModules::default(),
+                    None,
+                )?
+                .1)
+        };
+
+        let reads = eval("one = 1")?;
+        assert!(!reads.cell);
+        assert!(reads.buckconfig.is_empty());
+
+        let reads = eval("name = get_cell_name()")?;
+        assert!(reads.cell);
+        Ok(())
+    }
+
     #[test]
     fn test_load() -> anyhow::Result<()> {
         let import_path = import("cell1", "imports", "one.bzl");
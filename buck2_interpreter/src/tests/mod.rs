@@ -10,6 +10,7 @@
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::dice::data::testing::SetTestingIoProvider;
 use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+use buck2_common::legacy_configs::testing::legacy_buck_config_from_entries;
 use buck2_common::legacy_configs::LegacyBuckConfig;
 use buck2_common::legacy_configs::LegacyBuckConfigs;
 use buck2_core::bzl::ImportPath;
@@ -44,6 +45,7 @@ use crate::dice::HasCalculationDelegate;
 use crate::dice::HasInterpreterContext;
 use crate::extra::testing::TesterConfiguror;
 use crate::extra::testing::TesterExtraContext;
+use crate::file_loader::LoadedModule;
 use crate::starlark_profiler::StarlarkProfilerOrInstrumentation;
 
 fn rules() -> Vec<String> {
@@ -72,15 +74,6 @@ fn root_cell() -> CellName {
 }
 
 fn calculation(fs: &ProjectRootTemp) -> anyhow::Result<DiceTransaction> {
-    let mut dice = Dice::builder();
-    dice.set(EventDispatcher::null());
-    dice.set_testing_io_provider(fs);
-    let dice = dice.build(DetectCycles::Enabled);
-
-    let mut per_transaction_data = UserComputationData::new();
-    per_transaction_data.data.set(EventDispatcher::null());
-    let ctx = dice.with_ctx_data(per_transaction_data);
-
     let resolver = CellResolver::with_names_and_paths_with_alias(&[(
         CellName::unchecked_new("".to_owned()),
         CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new("".to_owned())),
@@ -89,6 +82,22 @@ fn calculation(fs: &ProjectRootTemp) -> anyhow::Result<DiceTransaction> {
         ],
     )]);
     let cell_configs = empty_configs(&resolver);
+    calculation_with_cells(fs, resolver, cell_configs)
+}
+
+fn calculation_with_cells(
+    fs: &ProjectRootTemp,
+    resolver: CellResolver,
+    cell_configs: LegacyBuckConfigs,
+) -> anyhow::Result<DiceTransaction> {
+    let mut dice = Dice::builder();
+    dice.set(EventDispatcher::null());
+    dice.set_testing_io_provider(fs);
+    let dice = dice.build(DetectCycles::Enabled);
+
+    let mut per_transaction_data = UserComputationData::new();
+    per_transaction_data.data.set(EventDispatcher::null());
+    let ctx = dice.with_ctx_data(per_transaction_data);
 
     ctx.set_cell_resolver(resolver.dupe())?;
     ctx.set_interpreter_context(TesterConfiguror::new(rules()))?;
@@ -175,6 +184,96 @@ async fn test_eval_import_with_load() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Evaluate `other//pkg:<filename>` as loaded into a build file of `build_file_cell`.
+async fn eval_in_cell(
+    ctx: &DiceTransaction,
+    filename: &str,
+    build_file_cell: &str,
+) -> anyhow::Result<LoadedModule> {
+    let import = ImportPath::unchecked_new_cross_cell("other", "pkg", filename, build_file_cell);
+    Ok(ctx
+        .get_interpreter_calculator(import.cell(), import.build_file_cell())
+        .await?
+        .eval_module(StarlarkModulePath::LoadFile(&import))
+        .await?)
+}
+
+#[tokio::test]
+async fn test_eval_import_shared_between_cells() -> anyhow::Result<()> {
+    let fs = ProjectRootTemp::new()?;
+    fs.path().write_file(
+        ProjectRelativePath::new("other/pkg/defs.bzl")?,
+        indoc!(
+            r#"
+            names = []
+            "#
+        ),
+        false,
+    )?;
+    fs.path().write_file(
+        ProjectRelativePath::new("other/pkg/config.bzl")?,
+        indoc!(
+            r#"
+            value = read_config("foo", "bar")
+            "#
+        ),
+        false,
+    )?;
+
+    let other_cell = CellName::unchecked_new("other".to_owned());
+    let resolver = CellResolver::with_names_and_paths_with_alias(&[
+        (
+            root_cell(),
+            CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new("".to_owned())),
+            hashmap![
+                CellAlias::new("".to_owned()) => root_cell(),
+                CellAlias::new("other".to_owned()) => other_cell.clone(),
+            ],
+        ),
+        (
+            other_cell.clone(),
+            CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new("other".to_owned())),
+            hashmap![
+                CellAlias::new("".to_owned()) => other_cell.clone(),
+                CellAlias::new("other".to_owned()) => other_cell.clone(),
+            ],
+        ),
+    ]);
+    let cell_configs = LegacyBuckConfigs::new(hashmap![
+        root_cell() => legacy_buck_config_from_entries([("foo", "bar", "root")])?,
+        other_cell => legacy_buck_config_from_entries([("foo", "bar", "other")])?,
+    ]);
+    let ctx = calculation_with_cells(&fs, resolver, cell_configs)?;
+
+    // The file doesn't depend on the cell it is loaded into, so it is evaluated once.
+    let own = eval_in_cell(&ctx, "defs.bzl", "other").await?;
+    let shared = eval_in_cell(&ctx, "defs.bzl", "").await?;
+    assert!(own
+        .env()
+        .get("names")?
+        .value()
+        .to_value()
+        .ptr_eq(shared.env().get("names")?.value().to_value()));
+
+    // The file reads a buckconfig value that differs between the cells.
+    let own = eval_in_cell(&ctx, "config.bzl", "other").await?;
+    let not_shared = eval_in_cell(&ctx, "config.bzl", "").await?;
+    assert_eq!(
+        Some("other"),
+        own.env().get("value")?.value().to_value().unpack_str()
+    );
+    assert_eq!(
+        Some("root"),
+        not_shared
+            .env()
+            .get("value")?
+            .value()
+            .to_value()
+            .unpack_str()
+    );
+    Ok(())
+}
+
 // TODO: this test require imports extractions
 #[tokio::test]
 async fn test_eval_build_file() -> anyhow::Result<()> {
@@ -55,7 +55,9 @@ pub enum AttrLiteral<C: AttrConfig> {
     List(Box<[C]>, AttrType),
     // We make Tuple a Box<[C]> so we can share code paths with List
     Tuple(Box<[C]>),
-    Dict(Vec<(C, C)>),
+    // Whether the entries were sorted by key when coerced, rather than kept in the order they
+    // were written in.
+    Dict(Vec<(C, C)>, bool),
     None,
     Dep(Box<DepAttr<C::ProvidersType>>),
     ConfiguredDep(Box<DepAttr<ConfiguredProvidersLabel>>),
@@ -113,7 +115,7 @@ impl<C: AttrConfig> Display for AttrLiteral<C> {
                 write!(f, ")")?;
                 Ok(())
             }
-            AttrLiteral::Dict(v) => {
+            AttrLiteral::Dict(v, _) => {
                 write!(f, "{{")?;
                 for (i, (k, v)) in v.iter().enumerate() {
                     if i != 0 {
@@ -149,11 +151,16 @@ impl<C: AttrConfig> AttrLiteral<C> {
             AttrLiteral::List(list, _) | AttrLiteral::Tuple(list) => {
                 Ok(to_value(list.try_map(|c| c.to_json())?)?)
             }
-            AttrLiteral::Dict(dict) => {
+            AttrLiteral::Dict(dict, _) => {
                 let mut res: serde_json::Map<String, serde_json::Value> =
                     serde_json::Map::with_capacity(dict.len());
                 for (k, v) in dict {
-                    res.insert(k.to_json()?.as_str().unwrap().to_owned(), v.to_json()?);
+                    // JSON keys are strings, so other keys (e.g. ints) are printed as JSON.
+                    let k = match k.to_json()? {
+                        serde_json::Value::String(k) => k,
+                        k => k.to_string(),
+                    };
+                    res.insert(k, v.to_json()?);
                 }
                 Ok(res.into())
             }
@@ -186,7 +193,7 @@ impl<C: AttrConfig> AttrLiteral<C> {
                 }
                 Ok(false)
             }
-            AttrLiteral::Dict(d) => {
+            AttrLiteral::Dict(d, _) => {
                 for (k, v) in d {
                     if k.any_matches(filter)? || v.any_matches(filter)? {
                         return Ok(true);
@@ -227,7 +234,7 @@ impl AttrLiteral<ConfiguredAttr> {
                 }
                 Ok(())
             }
-            AttrLiteral::Dict(dict) => {
+            AttrLiteral::Dict(dict, _) => {
                 for (k, v) in dict {
                     k.traverse(traversal)?;
                     v.traverse(traversal)?;
@@ -273,11 +280,14 @@ impl AttrLiteral<CoercedAttr> {
             AttrLiteral::Tuple(list) => {
                 AttrLiteral::Tuple(list.try_map(|v| v.configure(ctx))?.into_boxed_slice())
             }
-            AttrLiteral::Dict(dict) => AttrLiteral::Dict(dict.try_map(|(k, v)| {
-                let k2 = k.configure(ctx)?;
-                let v2 = v.configure(ctx)?;
-                anyhow::Ok((k2, v2))
-            })?),
+            AttrLiteral::Dict(dict, sorted) => AttrLiteral::Dict(
+                dict.try_map(|(k, v)| {
+                    let k2 = k.configure(ctx)?;
+                    let v2 = v.configure(ctx)?;
+                    anyhow::Ok((k2, v2))
+                })?,
+                *sorted,
+            ),
             AttrLiteral::None => AttrLiteral::None,
             AttrLiteral::Dep(dep) => DepAttrType::configure(ctx, dep)?,
             AttrLiteral::ConfiguredDep(dep) => AttrLiteral::Dep(dep.clone()),
@@ -313,7 +323,7 @@ impl AttrLiteral<CoercedAttr> {
                 }
                 Ok(())
             }
-            AttrLiteral::Dict(dict) => {
+            AttrLiteral::Dict(dict, _) => {
                 for (k, v) in dict {
                     k.traverse(traversal)?;
                     v.traverse(traversal)?;
@@ -61,6 +61,15 @@ pub enum CoercedAttr {
     Concat(Vec<Self>),
 }
 
+/// Records which branch of a `select()` was chosen when an attribute was configured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectResolution {
+    /// The keys of the `select()`, in declaration order.
+    pub keys: Vec<TargetLabel>,
+    /// The key of the chosen branch, or `None` if the `DEFAULT` branch was chosen.
+    pub chosen: Option<TargetLabel>,
+}
+
 // This is just to help understand any impact that changes have to the size of this.
 // We store a lot of these, so we try to keep it to a reasonable size.
 static_assertions::assert_eq_size!(CoercedAttr, [usize; 4]);
@@ -94,6 +103,11 @@ impl Display for CoercedAttr {
     }
 }
 
+/// The key of the JSON object encoding a `select()`, see `CoercedAttr::to_json`.
+pub const SELECT_JSON_KEY: &str = "__select__";
+/// The key of the JSON object encoding a concatenation of `select()`s, see `CoercedAttr::to_json`.
+pub const CONCAT_JSON_KEY: &str = "__concat__";
+
 impl Serialize for CoercedAttr {
     fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
     where
@@ -115,38 +129,40 @@ impl CoercedAttr {
     /// things, a lot of the types will be dropped without special handling. For example, an artifact will just end
     /// up as the stringified version of its coerced value (i.e. while `//a:b` might represent some list of targets,
     /// in to_json it just appears as the string "//a:b").
+    ///
+    /// The structure of `select()`s and of their concatenations is kept, so that tooling can
+    /// reconstruct the expression from the JSON:
+    ///
+    /// * `select({"//:a": 1, "DEFAULT": 2})` is `{"__select__": [["//:a", 1], ["DEFAULT", 2]]}`,
+    ///   with the entries in declaration order and `DEFAULT` last.
+    /// * `[1] + select(...)` is `{"__concat__": [[1], {"__select__": [...]}]}`.
     pub fn to_json(&self) -> anyhow::Result<serde_json::Value> {
         match self {
             CoercedAttr::Literal(v) => v.to_json(),
             CoercedAttr::Selector(box (selector, default)) => {
-                let mut map = serde_json::Map::new();
+                let mut entries = Vec::with_capacity(selector.len() + 1);
                 for (k, v) in selector.iter() {
-                    map.insert(k.to_string(), v.to_json()?);
+                    entries.push(serde_json::Value::Array(vec![
+                        serde_json::Value::String(k.to_string()),
+                        v.to_json()?,
+                    ]));
                 }
                 if let Some(default) = default {
-                    map.insert("DEFAULT".to_owned(), default.to_json()?);
+                    entries.push(serde_json::Value::Array(vec![
+                        serde_json::Value::String("DEFAULT".to_owned()),
+                        default.to_json()?,
+                    ]));
                 }
-                let select = serde_json::Value::Object(map);
-
-                Ok(serde_json::Value::Object(serde_json::Map::from_iter([
-                    (
-                        "__type".to_owned(),
-                        serde_json::Value::String("selector".to_owned()),
-                    ),
-                    ("entries".to_owned(), select),
-                ])))
+                Ok(serde_json::Value::Object(serde_json::Map::from_iter([(
+                    SELECT_JSON_KEY.to_owned(),
+                    serde_json::Value::Array(entries),
+                )])))
             }
             CoercedAttr::Concat(items) => {
-                Ok(serde_json::Value::Object(serde_json::Map::from_iter([
-                    (
-                        "__type".to_owned(),
-                        serde_json::Value::String("concat".to_owned()),
-                    ),
-                    (
-                        "items".to_owned(),
-                        serde_json::Value::Array(items.try_map(CoercedAttr::to_json)?),
-                    ),
-                ])))
+                Ok(serde_json::Value::Object(serde_json::Map::from_iter([(
+                    CONCAT_JSON_KEY.to_owned(),
+                    serde_json::Value::Array(items.try_map(CoercedAttr::to_json)?),
+                )])))
             }
         }
     }
@@ -196,6 +212,14 @@ impl CoercedAttr {
         ctx: &dyn AttrConfigurationContext,
         select_entries: &'a OrderedMap<TargetLabel, CoercedAttr>,
     ) -> anyhow::Result<Option<&'a CoercedAttr>> {
+        Ok(Self::select_the_most_specific_entry(ctx, select_entries)?.map(|(_k, v)| v))
+    }
+
+    /// Like `select_the_most_specific`, but also returns the key of the chosen branch.
+    fn select_the_most_specific_entry<'a>(
+        ctx: &dyn AttrConfigurationContext,
+        select_entries: &'a OrderedMap<TargetLabel, CoercedAttr>,
+    ) -> anyhow::Result<Option<(&'a TargetLabel, &'a CoercedAttr)>> {
         let mut matching: Option<(&TargetLabel, &ConfigurationData, &CoercedAttr)> = None;
         for (k, v) in select_entries {
             matching = match (ctx.matches(k), matching) {
@@ -216,7 +240,7 @@ impl CoercedAttr {
                 }
             }
         }
-        Ok(matching.map(|(_k, _conf, v)| v))
+        Ok(matching.map(|(k, _conf, v)| (k, v)))
     }
 
     /// Returns the "configured" representation of the attribute in the provided context.
@@ -253,6 +277,53 @@ impl CoercedAttr {
         }
     }
 
+    /// Returns the branch chosen by each `select()` in this attr in the provided context, in
+    /// the order `configure` resolves them. Selects nested in a chosen branch follow the
+    /// select that contains them; selects in branches that were not chosen are not listed.
+    pub fn select_resolutions(
+        &self,
+        ctx: &dyn AttrConfigurationContext,
+    ) -> anyhow::Result<Vec<SelectResolution>> {
+        let mut resolutions = Vec::new();
+        self.collect_select_resolutions(ctx, &mut resolutions)?;
+        Ok(resolutions)
+    }
+
+    fn collect_select_resolutions(
+        &self,
+        ctx: &dyn AttrConfigurationContext,
+        resolutions: &mut Vec<SelectResolution>,
+    ) -> anyhow::Result<()> {
+        match self {
+            CoercedAttr::Literal(_) => Ok(()),
+            CoercedAttr::Selector(box (selector, default)) => {
+                let (chosen, value) = match Self::select_the_most_specific_entry(ctx, selector)? {
+                    Some((k, v)) => (Some(k.dupe()), v),
+                    None => (
+                        None,
+                        default.as_ref().ok_or_else(|| {
+                            SelectError::MissingDefault(
+                                ctx.cfg().dupe(),
+                                selector.keys().duped().collect(),
+                            )
+                        })?,
+                    ),
+                };
+                resolutions.push(SelectResolution {
+                    keys: selector.keys().duped().collect(),
+                    chosen,
+                });
+                value.collect_select_resolutions(ctx, resolutions)
+            }
+            CoercedAttr::Concat(items) => {
+                for item in items {
+                    item.collect_select_resolutions(ctx, resolutions)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
     /// Checks if this attr matches the filter. For selectors and container-like things, will return true if any
     /// contained item matches the filter.
     pub fn any_matches(
@@ -103,14 +103,14 @@ impl ConfiguredAttr {
                 }
                 Ok(Self(AttrLiteral::List(res.into_boxed_slice(), ty)))
             }
-            AttrLiteral::Dict(left) => {
+            AttrLiteral::Dict(left, sorted) => {
                 let mut res = OrderedMap::new();
                 for (k, v) in left {
                     res.insert(k, v);
                 }
                 for x in items {
                     match x?.0 {
-                        AttrLiteral::Dict(right) => {
+                        AttrLiteral::Dict(right, _) => {
                             for (k, v) in right {
                                 match res.entry(k) {
                                     small_map::Entry::Vacant(e) => {
@@ -128,7 +128,7 @@ impl ConfiguredAttr {
                         attr => return mismatch("dict", attr),
                     }
                 }
-                Ok(Self(AttrLiteral::Dict(res.into_iter().collect())))
+                Ok(Self(AttrLiteral::Dict(res.into_iter().collect(), sorted)))
             }
             AttrLiteral::String(mut res) => {
                 for x in items {
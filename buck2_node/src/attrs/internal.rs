@@ -41,6 +41,9 @@ pub const LEGACY_TARGET_COMPATIBLE_WITH_ATTRIBUTE_FIELD: &str = "compatible_with
 pub const EXEC_COMPATIBLE_WITH_ATTRIBUTE_FIELD: &str = "exec_compatible_with";
 
 pub const VISIBILITY_ATTRIBUTE_FIELD: &str = "visibility";
+/// Not an internal attribute: rules that want their targets to restrict their dependencies declare
+/// it, as a list of visibility patterns.
+pub const WITHIN_VIEW_ATTRIBUTE_FIELD: &str = "within_view";
 
 pub const TESTS_ATTRIBUTE_FIELD: &str = "tests";
 
@@ -144,6 +147,7 @@ pub fn attr_is_configurable(name: &str) -> AttrIsConfigurable {
         || name == DEFAULT_TARGET_PLATFORM_ATTRIBUTE_FIELD
         // visibility attributes aren't configurable so that we can cache them on targetnodes.
         || name == VISIBILITY_ATTRIBUTE_FIELD
+        || name == WITHIN_VIEW_ATTRIBUTE_FIELD
     {
         AttrIsConfigurable::No
     } else {
@@ -120,6 +120,20 @@ impl IncompatiblePlatformReason {
         }
         message
     }
+
+    /// The target that is incompatible itself rather than because of one of its dependencies,
+    /// and the constraint it doesn't satisfy.
+    pub fn root_cause(&self) -> (&ConfiguredTargetLabel, &TargetLabel) {
+        let mut reason = self;
+        loop {
+            match &reason.cause {
+                IncompatiblePlatformReasonCause::UnsatisfiedConfig(unsatisfied_config) => {
+                    return (&reason.target, unsatisfied_config);
+                }
+                IncompatiblePlatformReasonCause::Dependency(previous) => reason = previous,
+            }
+        }
+    }
 }
 
 impl Display for IncompatiblePlatformReason {
@@ -143,11 +157,30 @@ impl Display for IncompatiblePlatformReason {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use buck2_core::configuration::Configuration;
     use buck2_core::target::testing::TargetLabelExt;
     use buck2_core::target::TargetLabel;
 
     use crate::compatibility::IncompatiblePlatformReason;
+    use crate::compatibility::IncompatiblePlatformReasonCause;
+
+    #[test]
+    fn test_root_cause() {
+        let dep = TargetLabel::testing_parse("//foo:dep").configure(Configuration::testing_new());
+        let constraint = TargetLabel::testing_parse("//constraints:linux");
+        let reason = IncompatiblePlatformReason {
+            target: TargetLabel::testing_parse("//foo:bar").configure(Configuration::testing_new()),
+            cause: IncompatiblePlatformReasonCause::Dependency(Arc::new(
+                IncompatiblePlatformReason {
+                    target: dep.clone(),
+                    cause: IncompatiblePlatformReasonCause::UnsatisfiedConfig(constraint.clone()),
+                },
+            )),
+        };
+        assert_eq!((&dep, &constraint), reason.root_cause());
+    }
 
     #[test]
     fn test_skipping_message_for_multiple() {
@@ -40,6 +40,7 @@ use crate::attrs::attr_type::dep::ProviderIdSet;
 use crate::attrs::attr_type::query::ResolvedQueryLiterals;
 use crate::attrs::attr_type::AttrType;
 use crate::attrs::coerced_attr::CoercedAttr;
+use crate::attrs::coerced_attr::SelectResolution;
 use crate::attrs::configuration_context::AttrConfigurationContextImpl;
 use crate::attrs::configured_attr::ConfiguredAttr;
 use crate::attrs::configured_traversal::ConfiguredAttrTraversal;
@@ -152,9 +153,11 @@ struct ConfiguredTargetNodeData {
     resolved_transition_configurations: OrderedMap<Arc<TransitionId>, Arc<TransitionApplied>>,
     execution_platform_resolution: ExecutionPlatformResolution,
     // Deps includes regular deps and transitioned deps,
-    // but excludes exec deps or configuration deps.
+    // but excludes toolchain deps, exec deps or configuration deps.
     // TODO(cjhopman): Should this be a diff against the node's deps?
     deps: ConfiguredTargetNodeDeps,
+    // Toolchain deps are configured with both our configuration and our execution platform.
+    toolchain_deps: ConfiguredTargetNodeDeps,
     exec_deps: ConfiguredTargetNodeDeps,
     platform_cfgs: OrderedMap<TargetLabel, Configuration>,
 }
@@ -191,6 +194,7 @@ impl ConfiguredTargetNode {
             execution_platform_resolution,
             OrderedSet::new(),
             OrderedSet::new(),
+            OrderedSet::new(),
             OrderedMap::new(),
         )
     }
@@ -202,6 +206,7 @@ impl ConfiguredTargetNode {
         resolved_tr_configurations: OrderedMap<Arc<TransitionId>, Arc<TransitionApplied>>,
         execution_platform_resolution: ExecutionPlatformResolution,
         deps: OrderedSet<ConfiguredTargetNode>,
+        toolchain_deps: OrderedSet<ConfiguredTargetNode>,
         exec_deps: OrderedSet<ConfiguredTargetNode>,
         platform_cfgs: OrderedMap<TargetLabel, Configuration>,
     ) -> Self {
@@ -212,6 +217,7 @@ impl ConfiguredTargetNode {
             resolved_transition_configurations: resolved_tr_configurations,
             execution_platform_resolution,
             deps: ConfiguredTargetNodeDeps(deps),
+            toolchain_deps: ConfiguredTargetNodeDeps(toolchain_deps),
             exec_deps: ConfiguredTargetNodeDeps(exec_deps),
             platform_cfgs,
         })))
@@ -260,6 +266,7 @@ impl ConfiguredTargetNode {
             // Nothing to execute for a forward node.
             execution_platform_resolution: ExecutionPlatformResolution::unspecified(),
             deps: ConfiguredTargetNodeDeps(OrderedSet::from_iter([transitioned_node])),
+            toolchain_deps: ConfiguredTargetNodeDeps(OrderedSet::new()),
             exec_deps: ConfiguredTargetNodeDeps(OrderedSet::new()),
             platform_cfgs: OrderedMap::new(),
         })))
@@ -290,20 +297,16 @@ impl ConfiguredTargetNode {
     }
 
     /// Returns all deps for this node that we know about after processing the build file
-    /// (it may be missing things like toolchain deps or other things that are determined
-    /// later in the build process).
+    /// (it may be missing things that are determined later in the build process).
     // TODO(cjhopman): Should this include configuration deps? Should it include the configuration deps that were inspected resolving selects?
     pub fn deps(&self) -> impl Iterator<Item = &ConfiguredTargetNode> {
-        self.0.deps.iter().chain(self.0.exec_deps.iter())
+        self.target_deps().chain(self.0.exec_deps.iter())
     }
 
+    /// The toolchain rules this node depends on, each configured for this node's execution
+    /// platform.
     pub fn toolchain_deps(&self) -> impl Iterator<Item = &ConfiguredTargetNode> {
-        // Since we validate that all toolchain dependencies are of kind Toolchain,
-        // we can use that to filter the deps.
-        self.0
-            .deps
-            .iter()
-            .filter(|x| x.rule_kind() == RuleKind::Toolchain)
+        self.0.toolchain_deps.iter()
     }
 
     pub fn inputs(&self) -> impl Iterator<Item = CellPath> + '_ {
@@ -360,7 +363,7 @@ impl ConfiguredTargetNode {
     }
 
     pub fn target_deps(&self) -> impl Iterator<Item = &ConfiguredTargetNode> {
-        self.0.deps.iter()
+        self.0.deps.iter().chain(self.0.toolchain_deps.iter())
     }
 
     pub fn exec_deps(&self) -> impl Iterator<Item = &ConfiguredTargetNode> {
@@ -491,6 +494,29 @@ impl ConfiguredTargetNode {
         })
     }
 
+    /// The `select()` branches chosen when configuring each attribute of this node. Attributes
+    /// that don't use `select()` are skipped.
+    pub fn select_resolutions<'a>(
+        &'a self,
+        opts: AttrInspectOptions,
+    ) -> impl Iterator<Item = (&str, Vec<SelectResolution>)> + 'a {
+        self.0.target_node.attrs(opts).filter_map(move |(name, attr)| {
+            let resolutions = attr
+                .select_resolutions(&AttrConfigurationContextImpl {
+                    resolved_cfg: &self.0.resolved_configuration,
+                    exec_cfg: &self.0.execution_platform_resolution.cfg(),
+                    resolved_transitions: &self.0.resolved_transition_configurations,
+                    platform_cfgs: &self.0.platform_cfgs,
+                })
+                .expect("checked attr configuration in constructor");
+            if resolutions.is_empty() {
+                None
+            } else {
+                Some((name, resolutions))
+            }
+        })
+    }
+
     pub fn call_stack(&self) -> Option<String> {
         match &self.0.target_node {
             TargetNodeOrForward::TargetNode(n) => n.call_stack(),
@@ -39,6 +39,7 @@ use crate::nodes::attributes::PACKAGE;
 use crate::nodes::attributes::TYPE;
 use crate::rule_type::RuleType;
 use crate::visibility::VisibilitySpecification;
+use crate::visibility::WithinViewSpecification;
 
 /// Map of target -> details of those targets within a build file.
 pub type TargetsMap = OrderedMap<TargetName, TargetNode>;
@@ -63,6 +64,9 @@ pub enum RuleKind {
     Configuration,
     /// A toolchain rule, meaning it is only usable as a toolchain dep.
     Toolchain,
+    /// An alias rule, meaning its targets only stand for the target they depend on, so tools can
+    /// print that target as the canonical label.
+    Alias,
 }
 
 #[derive(Debug, Eq, PartialEq, Hash, Allocative)]
@@ -96,11 +100,17 @@ pub struct TargetNodeData {
     /// Visibility specification restricts what targets can depend on this one.
     visibility: VisibilitySpecification,
 
+    /// Within view specification restricts what targets this one can depend on.
+    within_view: WithinViewSpecification,
+
     /// Call stack for the target.
     call_stack: Option<StarlarkCallStack>,
 
     /// The oncall attribute, if set
     oncall: Option<Arc<String>>,
+
+    /// The default target platform declared for the whole package, if set
+    package_default_target_platform: Option<TargetLabel>,
 }
 
 impl TargetNode {
@@ -114,8 +124,10 @@ impl TargetNode {
         attributes: AttrValues,
         deps_cache: CoercedDepsCollector,
         visibility: VisibilitySpecification,
+        within_view: WithinViewSpecification,
         call_stack: Option<StarlarkCallStack>,
         oncall: Option<Arc<String>>,
+        package_default_target_platform: Option<TargetLabel>,
     ) -> TargetNode {
         TargetNode(Arc::new(TargetNodeData {
             label,
@@ -127,8 +139,10 @@ impl TargetNode {
             attributes,
             deps_cache,
             visibility,
+            within_view,
             call_stack,
             oncall,
+            package_default_target_platform,
         }))
     }
 
@@ -144,8 +158,14 @@ impl TargetNode {
         self.0.rule_kind == RuleKind::Toolchain
     }
 
+    pub fn is_alias_rule(&self) -> bool {
+        self.0.rule_kind == RuleKind::Alias
+    }
+
+    /// The target's `default_target_platform` attribute, or if it isn't set, the default target
+    /// platform declared for its package.
     pub fn get_default_target_platform(&self) -> Option<&TargetLabel> {
-        match self.attr_or_none(
+        let declared = match self.attr_or_none(
             DEFAULT_TARGET_PLATFORM_ATTRIBUTE_FIELD,
             AttrInspectOptions::All,
         ) {
@@ -160,7 +180,8 @@ impl TargetNode {
                 }
             },
             None => None,
-        }
+        };
+        declared.or(self.0.package_default_target_platform.as_ref())
     }
 
     pub fn rule_type(&self) -> &RuleType {
@@ -248,6 +269,14 @@ impl TargetNode {
         self.0.visibility.is_visible_to(target)
     }
 
+    /// Whether this target is allowed to depend on `dep` by its `within_view`.
+    pub fn is_within_view(&self, dep: &TargetLabel) -> bool {
+        if self.label().pkg() == dep.pkg() {
+            return true;
+        }
+        self.0.within_view.contains(dep)
+    }
+
     pub fn attrs(&self, opts: AttrInspectOptions) -> impl Iterator<Item = (&str, &CoercedAttr)> {
         self.0.attr_spec.attrs(&self.0.attributes, opts)
     }
@@ -433,6 +462,7 @@ pub mod testing {
     use crate::nodes::unconfigured::TargetsMap;
     use crate::rule_type::RuleType;
     use crate::visibility::VisibilitySpecification;
+    use crate::visibility::WithinViewSpecification;
 
     pub trait TargetNodeExt {
         fn testing_new(
@@ -440,6 +470,13 @@ pub mod testing {
             rule_type: RuleType,
             attrs: Vec<(&str, Attribute, CoercedAttr)>,
         ) -> Self;
+
+        fn testing_new_with_rule_kind(
+            label: TargetLabel,
+            rule_type: RuleType,
+            rule_kind: RuleKind,
+            attrs: Vec<(&str, Attribute, CoercedAttr)>,
+        ) -> Self;
     }
 
     impl TargetNodeExt for TargetNode {
@@ -447,6 +484,15 @@ pub mod testing {
             label: TargetLabel,
             rule_type: RuleType,
             attrs: Vec<(&str, Attribute, CoercedAttr)>,
+        ) -> TargetNode {
+            Self::testing_new_with_rule_kind(label, rule_type, RuleKind::Normal, attrs)
+        }
+
+        fn testing_new_with_rule_kind(
+            label: TargetLabel,
+            rule_type: RuleType,
+            rule_kind: RuleKind,
+            attrs: Vec<(&str, Attribute, CoercedAttr)>,
         ) -> TargetNode {
             let mut indices = OrderedMap::with_capacity(attrs.len());
             let mut instances = Vec::with_capacity(attrs.len());
@@ -472,12 +518,14 @@ pub mod testing {
                 label,
                 rule_type,
                 buildfile_path,
-                RuleKind::Normal,
+                rule_kind,
                 None,
                 Arc::new(AttributeSpec::testing_new(indices, instances)),
                 attributes,
                 deps_cache,
                 VisibilitySpecification::Public,
+                WithinViewSpecification::Public,
+                None,
                 None,
                 None,
             )
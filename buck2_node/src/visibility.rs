@@ -19,9 +19,13 @@ pub enum VisibilityError {
         "`{0}` is not visible to `{1}` (run `buck2 uquery --output-attribute visibility {0}` to check the visibility)"
     )]
     NotVisibleTo(TargetLabel, TargetLabel),
+    #[error(
+        "`{1}` depends on `{0}`, which is not within its view (run `buck2 uquery --output-attribute within_view {1}` to check the view)"
+    )]
+    NotWithinView(TargetLabel, TargetLabel),
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Allocative)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Allocative)]
 pub struct VisibilityPattern(pub ParsedPattern<TargetPattern>);
 
 /// Represents the visibility spec of a target. Note that targets in the same package will ignore the
@@ -50,3 +54,24 @@ impl VisibilitySpecification {
         }
     }
 }
+
+/// Represents the `within_view` spec of a target, which restricts what targets it can depend on
+/// (the inverse of visibility). Note that targets in the same package will ignore the
+/// `within_view` spec of each other.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Allocative)]
+pub enum WithinViewSpecification {
+    // Public is used when a target doesn't specify any `within_view`.
+    Public,
+    VisibleTo(Vec<VisibilityPattern>),
+}
+
+impl WithinViewSpecification {
+    pub fn contains(&self, dep: &TargetLabel) -> bool {
+        match self {
+            WithinViewSpecification::Public => true,
+            WithinViewSpecification::VisibleTo(patterns) => {
+                patterns.iter().any(|pattern| pattern.0.matches(dep))
+            }
+        }
+    }
+}
@@ -433,4 +433,14 @@ pub trait QueryEnvironment: Send + Sync {
     }
 
     async fn owner(&self, _paths: &FileSet) -> anyhow::Result<TargetSet<Self::Target>>;
+
+    /// The targets of `targets` that are compatible with the platform named by the literal
+    /// `platform`, configured for that platform. Only configured targets have a platform.
+    async fn compatible_with(
+        &self,
+        _platform: &str,
+        _targets: &TargetSet<Self::Target>,
+    ) -> anyhow::Result<TargetSet<Self::Target>> {
+        Err(QueryError::NotAvailableInContext("compatible_with").into())
+    }
 }
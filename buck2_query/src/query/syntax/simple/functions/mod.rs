@@ -282,6 +282,26 @@ impl<Env: QueryEnvironment> DefaultQueryFunctionsModule<Env> {
             .into())
     }
 
+    /// Computes the targets that are compatible with a platform.
+    ///
+    /// The `compatible_with(platform, targets)` function evaluates to the targets of the set `targets` that are compatible with the platform `platform`, configured for it, so that `cquery "compatible_with('//platforms:arm64', deps(//foo:bar))"` lists the dependencies of `//foo:bar` that can be built for `//platforms:arm64`.
+    ///
+    /// `targets` is configured before it is reconfigured for `platform`, so it doesn't include targets that are incompatible with the target platform.
+    ///
+    /// This is only available in cquery.
+    async fn compatible_with(
+        &self,
+        env: &Env,
+        platform: String,
+        targets: TargetSet<Env::Target>,
+    ) -> QueryFuncResult<Env> {
+        Ok(self
+            .implementation
+            .compatible_with(env, &platform, &targets)
+            .await?
+            .into())
+    }
+
     async fn deps(
         &self,
         evaluator: &QueryEvaluator<'_, Env>,
@@ -473,6 +493,15 @@ impl<Env: QueryEnvironment> DefaultQueryFunctions<Env> {
         env.rbuildfiles(universe, argset).await
     }
 
+    pub async fn compatible_with(
+        &self,
+        env: &Env,
+        platform: &str,
+        targets: &TargetSet<Env::Target>,
+    ) -> anyhow::Result<TargetSet<Env::Target>> {
+        env.compatible_with(platform, targets).await
+    }
+
     pub async fn deps(
         &self,
         env: &Env,
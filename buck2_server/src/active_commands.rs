@@ -8,6 +8,8 @@
  */
 
 use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -26,11 +28,20 @@ pub fn active_commands() -> Option<HashMap<TraceId, ActiveCommandHandle>> {
     Some(ACTIVE_COMMANDS.try_lock().ok()?.clone())
 }
 
+/// Whether other commands ran on the daemon at any point while this one was active, or `None` if
+/// it isn't active.
+pub fn ran_concurrently(trace_id: &TraceId) -> Option<bool> {
+    let active_commands = ACTIVE_COMMANDS.lock().unwrap();
+    let cmd = active_commands.get(trace_id)?;
+    Some(cmd.concurrent.load(Ordering::Relaxed))
+}
+
 /// Allows interactions with commands found via active_commands().
 #[derive(Clone, Dupe)]
 pub struct ActiveCommandHandle {
     dispatcher: EventDispatcher,
     daemon_shutdown_channel: Arc<Mutex<Option<oneshot::Sender<buck2_data::DaemonShutdown>>>>,
+    concurrent: Arc<AtomicBool>,
 }
 
 impl ActiveCommandHandle {
@@ -77,11 +88,16 @@ impl ActiveCommand {
                 None
             };
 
+            for cmd in active_commands.values() {
+                cmd.concurrent.store(true, Ordering::Relaxed);
+            }
+
             active_commands.insert(
                 trace_id.dupe(),
                 ActiveCommandHandle {
                     dispatcher: event_dispatcher.dupe(),
                     daemon_shutdown_channel: Arc::new(Mutex::new(Some(sender))),
+                    concurrent: Arc::new(AtomicBool::new(!active_commands.is_empty())),
                 },
             );
 
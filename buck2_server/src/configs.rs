@@ -9,6 +9,7 @@
 
 use anyhow::Context;
 use buck2_common::legacy_configs::cells::BuckConfigBasedCells;
+use buck2_common::legacy_configs::external_cells::materialize_external_cells;
 use buck2_common::legacy_configs::LegacyBuckConfigs;
 use buck2_common::legacy_configs::LegacyConfigCmdArg;
 use buck2_core::cells::CellResolver;
@@ -43,7 +44,8 @@ fn get_legacy_config_args<'a, Iter: Iterator<Item = &'a ConfigOverride>>(
         .collect::<anyhow::Result<Vec<LegacyConfigCmdArg>>>()
 }
 
-/// Read the configs, returning the cell resolver and the legacy configs
+/// Read the configs, returning the cell resolver and the legacy configs. This materializes any
+/// external cells that aren't there yet, so it blocks while fetching them.
 pub fn parse_legacy_cells<'a, Iter: Iterator<Item = &'a ConfigOverride>>(
     config_overrides: Iter,
     cwd: &ProjectRelativePath,
@@ -55,6 +57,10 @@ pub fn parse_legacy_cells<'a, Iter: Iterator<Item = &'a ConfigOverride>>(
     // the base configs derived from the config files. This requires us to
     // store the base configs + overlaid ones separately, so we can cheaply
     // recompose.
-    let res = BuckConfigBasedCells::parse_with_config_args(fs, &config_values, cwd)?;
+    let mut res = BuckConfigBasedCells::parse_with_config_args(fs, &config_values, cwd)?;
+    if materialize_external_cells(fs, &res.cell_resolver, &res.configs_by_name)? {
+        // Pick up the `.buckconfig`s of the external cells that were just fetched.
+        res = BuckConfigBasedCells::parse_with_config_args(fs, &config_values, cwd)?;
+    }
     Ok((res.cell_resolver, res.configs_by_name))
 }
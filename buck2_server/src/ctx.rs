@@ -18,10 +18,22 @@ use anyhow::Context;
 use async_trait::async_trait;
 use buck2_build_api::actions::build_listener::BuildSignalSender;
 use buck2_build_api::actions::build_listener::SetBuildSignals;
+use buck2_build_api::actions::duration_history::ActionDurationHistory;
+use buck2_build_api::actions::duration_history::HasActionDurationHistory;
 use buck2_build_api::actions::impls::run::knobs::HasRunActionKnobs;
 use buck2_build_api::actions::impls::run::knobs::RunActionKnobs;
+use buck2_build_api::actions::signal_export::BuildSignalSink;
+use buck2_build_api::analysis::prefetch::PrefetchBudget;
+use buck2_build_api::analysis::prefetch::SetPrefetchBudget;
+use buck2_build_api::analysis::queue::AnalysisQueue;
+use buck2_build_api::analysis::queue::SetAnalysisQueue;
 use buck2_build_api::build::HasCreateUnhashedSymlinkLock;
+use buck2_build_api::configuration::modifiers::HasConfigurationModifiers;
+use buck2_build_api::context::HasShortOutPaths;
 use buck2_build_api::context::SetBuildContextData;
+use buck2_build_api::graph_limits::GraphLimits;
+use buck2_build_api::graph_limits::GraphSizeTracker;
+use buck2_build_api::graph_limits::SetGraphSizeTracker;
 use buck2_build_api::interpreter::context::configure_build_file_globals;
 use buck2_build_api::interpreter::context::configure_extension_file_globals;
 use buck2_build_api::interpreter::context::prelude_path;
@@ -40,6 +52,7 @@ use buck2_common::legacy_configs::LegacyBuckConfig;
 use buck2_common::legacy_configs::LegacyBuckConfigs;
 use buck2_common::result::SharedResult;
 use buck2_common::result::ToSharedResultExt;
+use buck2_common::source_changes::SourceChangeLog;
 use buck2_core::async_once_cell::AsyncOnceCell;
 use buck2_core::cells::CellResolver;
 use buck2_core::facebook_only;
@@ -49,6 +62,7 @@ use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_core::fs::project::ProjectRoot;
 use buck2_core::pattern::ParsedPattern;
 use buck2_core::pattern::ProvidersPattern;
+use buck2_core::pattern::TargetPattern;
 use buck2_core::truncate::truncate_container;
 use buck2_events::dispatch::EventDispatcher;
 use buck2_events::metadata;
@@ -57,9 +71,11 @@ use buck2_execute::execute::blocking::SetBlockingExecutor;
 use buck2_execute::execute::dice_data::set_fallback_executor_config;
 use buck2_execute::execute::dice_data::SetCommandExecutor;
 use buck2_execute::execute::dice_data::SetReClient;
+use buck2_execute::execute::environment_inheritance::EnvironmentInheritance;
 use buck2_execute::knobs::ExecutorGlobalKnobs;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::materialize::materializer::SetMaterializer;
+use buck2_execute::path::short_out_paths::ShortOutPaths;
 use buck2_execute::re::client::RemoteExecutionClient;
 use buck2_execute::re::manager::ReConnectionHandle;
 use buck2_execute::re::manager::ReConnectionManager;
@@ -77,6 +93,7 @@ use buck2_server_ctx::concurrency::DiceUpdater;
 use buck2_server_ctx::ctx::DiceAccessor;
 use buck2_server_ctx::ctx::PrivateStruct;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::pattern::PatternParser;
 use buck2_server_ctx::raw_output::RawOuputGuard;
 use buck2_server_ctx::raw_output::RawOutputWriter;
 use buck2_server_ctx::raw_output::StdoutOrStderr;
@@ -112,6 +129,8 @@ use crate::host_info;
 enum DaemonCommunicationError {
     #[error("Got invalid working directory `{0}`")]
     InvalidWorkingDirectory(String),
+    #[error("Invalid fraction of actions to check for determinism `{0}`, must be between 0 and 1")]
+    InvalidDeterminismCheck(f64),
 }
 
 /// BaseCommandContext provides access to the global daemon state and information specific to a command (like the
@@ -144,10 +163,22 @@ pub struct BaseServerCommandContext {
     pub file_watcher: Arc<dyn FileWatcher>,
     /// Whether or not to hash all commands
     pub hash_all_commands: bool,
+    /// Whether or not to use dep files when querying and writing to the remote action cache.
+    pub remote_dep_files: bool,
+    /// Where to export the features of the actions each build executes, if anywhere.
+    pub build_signal_sink: Option<Arc<dyn BuildSignalSink>>,
+    /// The environment local commands are restricted to, if any.
+    pub scrubbed_local_env: Option<EnvironmentInheritance>,
     /// Start time to track daemon uptime
     pub daemon_start_time: Instant,
     /// Mutex for creating symlinks
     pub create_unhashed_outputs_lock: Arc<Mutex<()>>,
+    /// How long actions took to execute, used to estimate build progress.
+    pub action_duration_history: Arc<ActionDurationHistory>,
+    /// Limits the analyses running at once across all commands.
+    pub analysis_queue: Arc<AnalysisQueue>,
+    /// Set when using the short output path layout.
+    pub short_out_paths: Option<Arc<ShortOutPaths>>,
 }
 
 /// ServerCommandContext provides access to the global daemon state and information about the calling client for
@@ -167,6 +198,9 @@ pub struct ServerCommandContext {
     host_platform_override: HostPlatformOverride,
     host_arch_override: HostArchOverride,
 
+    /// The configuration modifiers specified by the client, as written on the command line.
+    target_modifiers: Vec<String>,
+
     // This ensures that there's only one RE connection during the lifetime of this context. It's possible
     // that we give out other handles, but we don't depend on the lifetimes of those for this guarantee. We
     // also use this to send a RemoteExecutionSessionCreated if the connection is made.
@@ -225,6 +259,15 @@ impl ServerCommandContext {
                 ))
             })?;
 
+        if let Some(opts) = build_options {
+            if !(0.0..=1.0).contains(&opts.unstable_determinism_check) {
+                return Err(DaemonCommunicationError::InvalidDeterminismCheck(
+                    opts.unstable_determinism_check,
+                )
+                .into());
+            }
+        }
+
         #[derive(Allocative)]
         struct Observer {
             events: EventDispatcher,
@@ -277,6 +320,7 @@ impl ServerCommandContext {
             working_dir: project_path.to_buf().into(),
             host_platform_override: client_context.host_platform(),
             host_arch_override: client_context.host_arch(),
+            target_modifiers: client_context.target_modifiers.clone(),
             oncall,
             _re_connection_handle: re_connection_handle,
             build_signals,
@@ -308,13 +352,22 @@ impl ServerCommandContext {
             .map(|opts| opts.no_remote_cache)
             .unwrap_or_default();
 
+        let determinism_check = self
+            .build_options
+            .as_ref()
+            .map(|opts| opts.unstable_determinism_check)
+            .unwrap_or_default();
+
         let mut run_action_knobs = RunActionKnobs {
             hash_all_commands: self.base_context.hash_all_commands,
+            remote_dep_files: self.base_context.remote_dep_files && !no_remote_cache,
+            scrubbed_local_env: self.base_context.scrubbed_local_env,
             ..Default::default()
         };
 
         if let Some(build_options) = self.build_options.as_ref() {
             run_action_knobs.eager_dep_files = build_options.eager_dep_files;
+            run_action_knobs.materialize_intermediates = build_options.materialize_intermediates;
         }
 
         let concurrency = self
@@ -338,6 +391,10 @@ impl ServerCommandContext {
             .map_or(false, |opts| opts.upload_all_actions);
 
         let create_unhashed_symlink_lock = self.base_context.create_unhashed_outputs_lock.dupe();
+        let action_duration_history = self.base_context.action_duration_history.dupe();
+        let analysis_queue = self.base_context.analysis_queue.dupe();
+        let short_out_paths = self.base_context.short_out_paths.dupe();
+        let source_changes = self.base_context.file_watcher.source_changes();
 
         DiceCommandDataProvider {
             cell_configs_loader: self.cell_configs_loader.dupe(),
@@ -353,7 +410,12 @@ impl ServerCommandContext {
             forkserver,
             upload_all_actions,
             no_remote_cache,
+            determinism_check,
             create_unhashed_symlink_lock,
+            action_duration_history,
+            analysis_queue,
+            short_out_paths,
+            source_changes,
         }
     }
 
@@ -373,6 +435,8 @@ impl ServerCommandContext {
             configure_bxl_file_globals: self.configure_bxl_file_globals,
             disable_starlark_types: self.disable_starlark_types,
             record_target_call_stacks: self.record_target_call_stacks,
+            working_dir: self.working_dir.clone(),
+            target_modifiers: self.target_modifiers.clone(),
         })
     }
 
@@ -419,8 +483,17 @@ impl CellConfigLoader {
                         );
                     }
                 }
-                parse_legacy_cells(self.config_overrides.iter(), &self.working_dir, &self.project_root)
-                    .shared_error()
+                // Parsing may fetch external cells, so keep it off the async runtime.
+                let config_overrides = self.config_overrides.clone();
+                let working_dir = self.working_dir.clone();
+                let project_root = self.project_root.clone();
+                tokio::task::spawn_blocking(move || {
+                    parse_legacy_cells(config_overrides.iter(), &working_dir, &project_root)
+                })
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|res| res)
+                .shared_error()
             })
             .await
             .clone()
@@ -441,7 +514,12 @@ struct DiceCommandDataProvider {
     upload_all_actions: bool,
     run_action_knobs: RunActionKnobs,
     no_remote_cache: bool,
+    determinism_check: f64,
     create_unhashed_symlink_lock: Arc<Mutex<()>>,
+    action_duration_history: Arc<ActionDurationHistory>,
+    analysis_queue: Arc<AnalysisQueue>,
+    short_out_paths: Option<Arc<ShortOutPaths>>,
+    source_changes: Option<Arc<SourceChangeLog>>,
 }
 
 #[async_trait]
@@ -466,11 +544,33 @@ impl DiceDataProvider for DiceCommandDataProvider {
             .concurrency
             .unwrap_or_else(|| parse_concurrency(config_threads))?;
 
-        let executor_global_knobs = ExecutorGlobalKnobs {};
+        // By default, let as many actions prefetch their inputs as can run at once, so that the
+        // next batch is ready when the current one finishes.
+        let local_input_prefetch_depth = root_config
+            .parse("buck2", "local_input_prefetch_depth")?
+            .unwrap_or(concurrency);
+
+        let allow_unenforced_network_policy = root_config
+            .parse("buck2", "allow_unenforced_network_policy")?
+            .unwrap_or(false);
+
+        let executor_global_knobs = ExecutorGlobalKnobs {
+            local_input_prefetch_depth,
+            allow_unenforced_network_policy,
+        };
 
-        let host_sharing_broker =
+        let mut host_sharing_broker =
             HostSharingBroker::new(HostSharingStrategy::SmallerTasksFirst, concurrency);
 
+        // Let build systems run by local actions (make, cargo...) take their extra jobs from the
+        // same pool as actions, rather than each running as many jobs as there are cores. This is
+        // opt-in for now, actions asking for the jobserver run without it otherwise.
+        if cfg!(unix) && root_config.parse("buck2", "jobserver")?.unwrap_or(false) {
+            host_sharing_broker.enable_jobserver(
+                std::env::temp_dir().join(format!("buck2-jobserver-{}", self.events.trace_id())),
+            );
+        }
+
         // We use the job count for the low pass filter too. The low pass filter prevents sending
         // RE-eligile tasks to local if their concurrency is higher than our threshold. While it
         // doesn't *have* to be the same as the concurrency we give the actual executor, it's a
@@ -478,6 +578,10 @@ impl DiceDataProvider for DiceCommandDataProvider {
         // would expect to start losing out to RE in terms of perf.
         let low_pass_filter = LowPassFilter::new(concurrency);
 
+        let graph_limits = GraphLimits::from_config(root_config)?;
+        let prefetch_budget = PrefetchBudget::from_config(root_config)?;
+        self.analysis_queue.reconfigure(root_config)?;
+
         let mut data = DiceData::new();
         data.set(self.events.dupe());
 
@@ -485,7 +589,7 @@ impl DiceDataProvider for DiceCommandDataProvider {
         set_fallback_executor_config(
             &mut data,
             CommandExecutorConfig {
-                executor_kind: CommandExecutorKind::Local(LocalExecutorOptions {}),
+                executor_kind: CommandExecutorKind::Local(LocalExecutorOptions::default()),
                 path_separator: PathSeparatorKind::system_default(),
                 cache_upload_behavior: CacheUploadBehavior::Disabled,
             },
@@ -510,6 +614,8 @@ impl DiceDataProvider for DiceCommandDataProvider {
             self.upload_all_actions,
             self.forkserver,
             self.no_remote_cache,
+            self.determinism_check,
+            self.source_changes,
             ctx.global_data()
                 .get_io_provider()
                 .project_root()
@@ -520,6 +626,17 @@ impl DiceDataProvider for DiceCommandDataProvider {
         data.set_build_signals(self.build_signals);
         data.set_run_action_knobs(self.run_action_knobs);
         data.set_create_unhashed_symlink_lock(self.create_unhashed_symlink_lock);
+        data.set_action_duration_history(self.action_duration_history);
+        data.set_analysis_queue(self.analysis_queue);
+        if let Some(short_out_paths) = self.short_out_paths {
+            data.set_short_out_paths(short_out_paths);
+        }
+        if !graph_limits.is_unlimited() {
+            data.set_graph_size_tracker(Arc::new(GraphSizeTracker::new(graph_limits)));
+        }
+        if let Some(prefetch_budget) = prefetch_budget {
+            data.set_prefetch_budget(Arc::new(prefetch_budget));
+        }
         data.spawner = Arc::new(BuckSpawner::default());
         Ok(data)
     }
@@ -535,6 +652,8 @@ struct DiceCommandUpdater {
     configure_bxl_file_globals: fn(&mut GlobalsBuilder),
     disable_starlark_types: bool,
     record_target_call_stacks: bool,
+    working_dir: ProjectRelativePathBuf,
+    target_modifiers: Vec<String>,
 }
 
 #[async_trait]
@@ -559,9 +678,21 @@ impl DiceUpdater for DiceCommandUpdater {
             Arc::new(ConfiguredGraphQueryEnvironment::functions()),
         );
 
+        // Modifiers are resolved like other targets on the command line, relative to the
+        // working directory and through the `[alias]` section.
+        let target_modifiers = {
+            let parser = PatternParser::new(&cell_resolver, &legacy_configs, &self.working_dir)?;
+            self.target_modifiers.try_map(|modifier| {
+                parser
+                    .parse_pattern::<TargetPattern>(modifier)?
+                    .as_target_label(modifier)
+            })?
+        };
+
         let ctx = self.file_watcher.sync(ctx).await?;
 
         ctx.set_buck_out_path(Some(self.buck_out_dir.clone()))?;
+        ctx.set_configuration_modifiers(target_modifiers)?;
 
         setup_interpreter(
             &ctx,
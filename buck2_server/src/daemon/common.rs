@@ -17,6 +17,7 @@ use buck2_common::executor_config::HybridExecutionLevel;
 use buck2_common::executor_config::LocalExecutorOptions;
 use buck2_common::executor_config::PathSeparatorKind;
 use buck2_common::executor_config::RemoteExecutorOptions;
+use buck2_common::source_changes::SourceChangeLog;
 use buck2_core::env_helper::EnvHelper;
 use buck2_core::fs::project::ProjectRoot;
 use buck2_execute::artifact::fs::ArtifactFs;
@@ -28,10 +29,12 @@ use buck2_execute::knobs::ExecutorGlobalKnobs;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::re::manager::ReConnectionHandle;
 use buck2_execute_impl::executors::caching::CachingExecutor;
+use buck2_execute_impl::executors::determinism_check::DeterminismCheckExecutor;
 use buck2_execute_impl::executors::hybrid::HybridExecutor;
 use buck2_execute_impl::executors::local::LocalExecutor;
 use buck2_execute_impl::executors::re::ReExecutionPlatform;
 use buck2_execute_impl::executors::re::ReExecutor;
+use buck2_execute_impl::executors::source_change_guard::SourceChangeGuardExecutor;
 use buck2_execute_impl::low_pass_filter::LowPassFilter;
 use buck2_forkserver::client::ForkserverClient;
 use cli_proto::client_context::HostPlatformOverride;
@@ -39,6 +42,7 @@ use cli_proto::common_build_options::ExecutionStrategy;
 use gazebo::prelude::*;
 use host_sharing::HostSharingBroker;
 use once_cell::sync::OnceCell;
+use tokio::sync::Semaphore;
 
 pub fn parse_concurrency(requested: u32) -> anyhow::Result<usize> {
     let mut ret = requested.try_into().context("Invalid concurrency")?;
@@ -59,6 +63,8 @@ pub struct CommandExecutorFactory {
     // sharing the same DICE context should be allowed to proceed concurrently, and we only have
     // one CommandExecutorFactory per DICE context).
     pub host_sharing_broker: Arc<HostSharingBroker>,
+    /// Shared by all local executors, see `ExecutorGlobalKnobs::local_input_prefetch_depth`.
+    pub local_input_prefetch_slots: Option<Arc<Semaphore>>,
     pub low_pass_filter: Arc<LowPassFilter>,
     pub materializer: Arc<dyn Materializer>,
     pub blocking_executor: Arc<dyn BlockingExecutor>,
@@ -67,6 +73,11 @@ pub struct CommandExecutorFactory {
     pub upload_all_actions: bool,
     pub forkserver: Option<ForkserverClient>,
     pub no_remote_cache: bool,
+    /// The fraction of actions to run twice to check they are deterministic, zero when disabled.
+    pub determinism_check: f64,
+    /// Set if the file watcher tracks source changes as they happen, to re-run local commands
+    /// whose sources changed while they ran.
+    pub source_changes: Option<Arc<SourceChangeLog>>,
     project_root: ProjectRoot,
 }
 
@@ -82,11 +93,18 @@ impl CommandExecutorFactory {
         upload_all_actions: bool,
         forkserver: Option<ForkserverClient>,
         no_remote_cache: bool,
+        determinism_check: f64,
+        source_changes: Option<Arc<SourceChangeLog>>,
         project_root: ProjectRoot,
     ) -> Self {
+        let local_input_prefetch_slots = match executor_global_knobs.local_input_prefetch_depth {
+            0 => None,
+            depth => Some(Arc::new(Semaphore::new(depth))),
+        };
         Self {
             re_connection,
             host_sharing_broker: Arc::new(host_sharing_broker),
+            local_input_prefetch_slots,
             low_pass_filter: Arc::new(low_pass_filter),
             materializer,
             blocking_executor,
@@ -95,6 +113,8 @@ impl CommandExecutorFactory {
             upload_all_actions,
             forkserver,
             no_remote_cache,
+            determinism_check,
+            source_changes,
             project_root,
         }
     }
@@ -106,17 +126,7 @@ impl HasCommandExecutor for CommandExecutorFactory {
         artifact_fs: &ArtifactFs,
         executor_config: &CommandExecutorConfig,
     ) -> anyhow::Result<Arc<dyn PreparedCommandExecutor>> {
-        let local_executor_new = |_options| {
-            LocalExecutor::new(
-                artifact_fs.clone(),
-                self.materializer.dupe(),
-                self.blocking_executor.dupe(),
-                self.host_sharing_broker.dupe(),
-                self.project_root.root().to_owned(),
-                self.forkserver.dupe(),
-                self.executor_global_knobs.dupe(),
-            )
-        };
+        let local_executor_new = |_options| self.local_executor_new(artifact_fs);
 
         if !cfg!(fbcode_build) {
             static WARN: OnceCell<()> = OnceCell::new();
@@ -131,7 +141,10 @@ impl HasCommandExecutor for CommandExecutorFactory {
                 ));
             }
 
-            return Ok(Arc::new(local_executor_new(&LocalExecutorOptions {})));
+            return Ok(self.local_checks(
+                Arc::new(local_executor_new(&LocalExecutorOptions::default())),
+                artifact_fs,
+            ));
         }
 
         let remote_executor_new = |options: &RemoteExecutorOptions| {
@@ -155,6 +168,7 @@ impl HasCommandExecutor for CommandExecutorFactory {
                     .re_max_input_files_bytes
                     .unwrap_or(DEFAULT_RE_MAX_INPUT_FILE_BYTES),
                 options.re_use_case,
+                options.re_persistent_workers,
                 self.executor_global_knobs.dupe(),
                 self.no_remote_cache,
             )
@@ -163,7 +177,7 @@ impl HasCommandExecutor for CommandExecutorFactory {
         let inner_executor: Arc<dyn PreparedCommandExecutor> = match &executor_config.executor_kind
         {
             CommandExecutorKind::Local(local) if !self.strategy.ban_local() => {
-                Arc::new(local_executor_new(local))
+                self.local_checks(Arc::new(local_executor_new(local)), artifact_fs)
             }
             CommandExecutorKind::Remote(remote) if !self.strategy.ban_remote() => {
                 Arc::new(remote_executor_new(remote))
@@ -172,13 +186,16 @@ impl HasCommandExecutor for CommandExecutorFactory {
                 local,
                 remote,
                 level,
-            } if !self.strategy.ban_hybrid() => Arc::new(HybridExecutor {
-                local: local_executor_new(local),
-                remote: remote_executor_new(remote),
-                level: *level,
-                executor_preference: self.strategy.hybrid_preference(),
-                low_pass_filter: self.low_pass_filter.dupe(),
-            }),
+            } if !self.strategy.ban_hybrid() => self.local_checks(
+                Arc::new(HybridExecutor {
+                    local: local_executor_new(local),
+                    remote: remote_executor_new(remote),
+                    level: *level,
+                    executor_preference: self.strategy.hybrid_preference(),
+                    low_pass_filter: self.low_pass_filter.dupe(),
+                }),
+                artifact_fs,
+            ),
             config => {
                 return Err(anyhow::anyhow!(
                     "The desired execution strategy (`{:?}`) is incompatible with the executor config that was selected: {:?}",
@@ -212,6 +229,64 @@ impl HasCommandExecutor for CommandExecutorFactory {
     }
 }
 
+impl CommandExecutorFactory {
+    /// Wraps an executor that may run commands locally. This goes inside the caching executor, so
+    /// that commands that read changing sources don't get uploaded.
+    fn local_checks(
+        &self,
+        executor: Arc<dyn PreparedCommandExecutor>,
+        artifact_fs: &ArtifactFs,
+    ) -> Arc<dyn PreparedCommandExecutor> {
+        self.guard_source_changes(self.check_determinism(executor, artifact_fs))
+    }
+
+    /// Reruns local commands whose source inputs changed while they ran. Remote executions read
+    /// uploaded inputs, which can't change underneath them.
+    fn guard_source_changes(
+        &self,
+        executor: Arc<dyn PreparedCommandExecutor>,
+    ) -> Arc<dyn PreparedCommandExecutor> {
+        match &self.source_changes {
+            Some(source_changes) => Arc::new(SourceChangeGuardExecutor {
+                inner: executor,
+                source_changes: source_changes.dupe(),
+            }),
+            None => executor,
+        }
+    }
+
+    /// Runs a sample of the commands twice if `--unstable-determinism-check` is set. Only for
+    /// executors that may run commands locally, since the check runs them locally.
+    fn check_determinism(
+        &self,
+        executor: Arc<dyn PreparedCommandExecutor>,
+        artifact_fs: &ArtifactFs,
+    ) -> Arc<dyn PreparedCommandExecutor> {
+        if self.determinism_check <= 0.0 {
+            return executor;
+        }
+        Arc::new(DeterminismCheckExecutor {
+            inner: executor,
+            local: self.local_executor_new(artifact_fs),
+            artifact_fs: artifact_fs.clone(),
+            sample_rate: self.determinism_check,
+        })
+    }
+
+    fn local_executor_new(&self, artifact_fs: &ArtifactFs) -> LocalExecutor {
+        LocalExecutor::new(
+            artifact_fs.clone(),
+            self.materializer.dupe(),
+            self.blocking_executor.dupe(),
+            self.host_sharing_broker.dupe(),
+            self.local_input_prefetch_slots.dupe(),
+            self.project_root.root().to_owned(),
+            self.forkserver.dupe(),
+            self.executor_global_knobs.dupe(),
+        )
+    }
+}
+
 trait ExecutionStrategyExt {
     fn ban_local(&self) -> bool;
     fn ban_remote(&self) -> bool;
@@ -261,7 +336,7 @@ pub fn get_executor_config_for_strategy(
         // kick in later.
         ExecutionStrategy::Default | ExecutionStrategy::NoExecution => {
             CommandExecutorKind::Hybrid {
-                local: LocalExecutorOptions {},
+                local: LocalExecutorOptions::default(),
                 remote: RemoteExecutorOptions {
                     re_properties: re_execution_platform.intrinsic_properties(),
                     ..Default::default()
@@ -273,7 +348,7 @@ pub fn get_executor_config_for_strategy(
         // later when we actually instantiate the Executor.
         ExecutionStrategy::Hybrid | ExecutionStrategy::HybridPreferLocal => {
             CommandExecutorKind::Hybrid {
-                local: LocalExecutorOptions {},
+                local: LocalExecutorOptions::default(),
                 remote: RemoteExecutorOptions {
                     re_properties: re_execution_platform.intrinsic_properties(),
                     ..Default::default()
@@ -281,7 +356,7 @@ pub fn get_executor_config_for_strategy(
                 level: HybridExecutionLevel::Limited,
             }
         }
-        ExecutionStrategy::LocalOnly => CommandExecutorKind::Local(LocalExecutorOptions {}),
+        ExecutionStrategy::LocalOnly => CommandExecutorKind::Local(LocalExecutorOptions::default()),
         ExecutionStrategy::RemoteOnly => CommandExecutorKind::Remote(RemoteExecutorOptions {
             re_properties: re_execution_platform.intrinsic_properties(),
             ..Default::default()
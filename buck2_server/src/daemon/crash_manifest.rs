@@ -0,0 +1,294 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A record of the commands the daemon is running, kept on disk.
+//!
+//! A daemon that gets killed (by `buck2 killall`, or by `buck2 kill` in the middle of a build)
+//! may have been writing outputs to buck-out, and may have recorded them in the materializer
+//! state before they were complete. The manifest is rewritten by a background thread whenever
+//! commands start or finish, so that when the next daemon starts, it knows whether commands were
+//! in flight and buck-out needs checking.
+
+use std::collections::BTreeMap;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+
+use allocative::Allocative;
+use anyhow::Context as _;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_events::trace::TraceId;
+use gazebo::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashManifest {
+    pub pid: u32,
+    /// The arguments of the commands in flight, by trace id.
+    pub commands: BTreeMap<String, Vec<String>>,
+    /// The materialization method, as set in `[buck2] materializations`.
+    pub materialization_method: String,
+    /// Whether the materializer persisted its state, in which case it may list artifacts that
+    /// were only partially written.
+    pub sqlite_materializer_state: bool,
+    /// Set if the daemon was asked to shut down while commands were in flight.
+    pub kill: Option<CrashManifestKill>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashManifestKill {
+    pub reason: String,
+    /// How many operations the materializer still had queued.
+    pub materializer_queue_size: Option<usize>,
+}
+
+impl CrashManifest {
+    /// The manifest left by the previous daemon, if any. A manifest we can't read is ignored.
+    pub fn load(path: &AbsNormPath) -> Option<Self> {
+        let res: anyhow::Result<Option<Self>> = try {
+            if !path.exists() {
+                return None;
+            }
+            let contents = fs_util::read_to_string(path)?;
+            Some(serde_json::from_str(&contents)?)
+        };
+        match res {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                tracing::warn!("Error reading crash manifest `{}`: {:#}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Whether the daemon that wrote this manifest stopped while commands were running.
+    pub fn had_commands_in_flight(&self) -> bool {
+        !self.commands.is_empty()
+    }
+
+    /// A description of what was in flight, for the user.
+    pub fn describe(&self) -> String {
+        let mut s = format!(
+            "The previous buck2 daemon (pid {}) stopped while running:",
+            self.pid
+        );
+        for (trace_id, argv) in &self.commands {
+            s.push_str(&format!("\n  `{}` (trace id {})", argv.join(" "), trace_id));
+        }
+        if let Some(kill) = &self.kill {
+            s.push_str(&format!("\nIt was killed: {}", kill.reason));
+            if let Some(queue_size) = kill.materializer_queue_size {
+                s.push_str(&format!(
+                    " ({} materializer operations were pending)",
+                    queue_size
+                ));
+            }
+        }
+        s
+    }
+}
+
+/// Keeps the manifest of the current daemon up to date.
+#[derive(Allocative)]
+pub struct CrashManifestWriter {
+    #[allocative(skip)]
+    file: Arc<CrashManifestFile>,
+    /// Wakes up the thread that writes the manifest. The thread exits once this is dropped.
+    #[allocative(skip)]
+    changed: Mutex<mpsc::Sender<()>>,
+}
+
+struct CrashManifestFile {
+    path: AbsNormPathBuf,
+    manifest: Mutex<CrashManifest>,
+    /// Held while writing, so that the manifest written last is the latest one.
+    write_lock: Mutex<()>,
+}
+
+impl CrashManifestFile {
+    fn write(&self) -> anyhow::Result<()> {
+        let _write_lock = self.write_lock.lock().unwrap();
+        let contents = serde_json::to_vec(&*self.manifest.lock().unwrap())?;
+        // Written atomically, so that a daemon killed half-way through writing doesn't leave a
+        // manifest that can't be read.
+        fs_util::write_atomic(&self.path, contents)
+            .with_context(|| format!("Error writing crash manifest `{}`", self.path))
+    }
+}
+
+impl CrashManifestWriter {
+    pub fn new(
+        path: AbsNormPathBuf,
+        materialization_method: String,
+        sqlite_materializer_state: bool,
+    ) -> anyhow::Result<Self> {
+        let file = Arc::new(CrashManifestFile {
+            path,
+            manifest: Mutex::new(CrashManifest {
+                pid: std::process::id(),
+                commands: BTreeMap::new(),
+                materialization_method,
+                sqlite_materializer_state,
+                kill: None,
+            }),
+            write_lock: Mutex::new(()),
+        });
+        file.write()?;
+
+        let (changed, receiver) = mpsc::channel::<()>();
+        thread::Builder::new()
+            .name("crash-manifest".to_owned())
+            .spawn({
+                let file = file.dupe();
+                move || {
+                    while receiver.recv().is_ok() {
+                        // Changes made meanwhile are all covered by this write.
+                        while receiver.try_recv().is_ok() {}
+                        if let Err(e) = file.write() {
+                            tracing::warn!("{:#}", e);
+                        }
+                    }
+                }
+            })
+            .context("Error starting the crash manifest thread")?;
+
+        Ok(Self {
+            file,
+            changed: Mutex::new(changed),
+        })
+    }
+
+    /// Apply `f` to the manifest. Returns whether it changed.
+    fn update(&self, f: impl FnOnce(&mut CrashManifest)) -> bool {
+        let mut manifest = self.file.manifest.lock().unwrap();
+        // Once killed, the manifest keeps the commands that were in flight, even as they get
+        // cancelled.
+        if manifest.kill.is_some() {
+            return false;
+        }
+        f(&mut manifest);
+        true
+    }
+
+    /// Apply `f` to the manifest, and have it written in the background.
+    fn update_in_background(&self, f: impl FnOnce(&mut CrashManifest)) {
+        if self.update(f) {
+            let _ignored = self.changed.lock().unwrap().send(());
+        }
+    }
+
+    /// Record that a command started. It's recorded as finished when the guard is dropped.
+    pub fn command_started(
+        self: &Arc<Self>,
+        trace_id: &TraceId,
+        argv: Vec<String>,
+    ) -> CrashManifestCommandGuard {
+        let trace_id = trace_id.to_string();
+        self.update_in_background(|manifest| {
+            manifest.commands.insert(trace_id.clone(), argv);
+        });
+        CrashManifestCommandGuard {
+            writer: self.dupe(),
+            trace_id,
+        }
+    }
+
+    /// Record that the daemon is being shut down, with the commands still in flight. This is
+    /// written right away, as the daemon may exit before the background thread gets to it.
+    pub fn killed(&self, reason: String, materializer_queue_size: Option<usize>) {
+        let changed = self.update(|manifest| {
+            if manifest.had_commands_in_flight() {
+                manifest.kill = Some(CrashManifestKill {
+                    reason,
+                    materializer_queue_size,
+                });
+            }
+        });
+        if changed {
+            if let Err(e) = self.file.write() {
+                tracing::warn!("{:#}", e);
+            }
+        }
+    }
+}
+
+pub struct CrashManifestCommandGuard {
+    writer: Arc<CrashManifestWriter>,
+    trace_id: String,
+}
+
+impl Drop for CrashManifestCommandGuard {
+    fn drop(&mut self) {
+        self.writer.update_in_background(|manifest| {
+            manifest.commands.remove(&self.trace_id);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use buck2_core::fs::paths::file_name::FileName;
+
+    use super::*;
+
+    /// Wait for the background thread to write a manifest that satisfies `f`.
+    fn wait_for(path: &AbsNormPath, f: impl Fn(&CrashManifest) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !CrashManifest::load(path).map_or(false, |m| f(&m)) {
+            assert!(Instant::now() < deadline, "crash manifest was not written");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_manifest() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let dir = AbsNormPathBuf::try_from(tempdir.path().to_owned())?;
+        let path = dir.join(FileName::new("crash_manifest.json")?);
+        assert_eq!(CrashManifest::load(&path), None);
+
+        let writer = Arc::new(CrashManifestWriter::new(
+            path.clone(),
+            "Deferred".to_owned(),
+            true,
+        )?);
+        assert!(!CrashManifest::load(&path).unwrap().had_commands_in_flight());
+
+        let build = writer.command_started(&TraceId::new(), vec!["buck2".to_owned()]);
+        wait_for(&path, |m| m.had_commands_in_flight());
+        drop(build);
+        wait_for(&path, |m| !m.had_commands_in_flight());
+
+        let build = writer.command_started(&TraceId::new(), vec!["buck2".to_owned()]);
+        // Written right away, regardless of the background thread.
+        writer.killed("buck2 kill".to_owned(), Some(3));
+        let manifest = CrashManifest::load(&path).unwrap();
+        assert!(manifest.had_commands_in_flight());
+        drop(build);
+        // Let the background thread run, in case it would overwrite the kill.
+        thread::sleep(Duration::from_millis(100));
+        let manifest = CrashManifest::load(&path).unwrap();
+        assert!(manifest.had_commands_in_flight());
+        assert_eq!(
+            manifest.kill,
+            Some(CrashManifestKill {
+                reason: "buck2 kill".to_owned(),
+                materializer_queue_size: Some(3),
+            })
+        );
+        Ok(())
+    }
+}
@@ -9,6 +9,7 @@
 
 pub mod check_working_dir;
 pub mod common;
+pub mod crash_manifest;
 pub mod daemon_tcp;
 pub mod dice_dump;
 pub mod disk_state;
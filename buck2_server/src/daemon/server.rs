@@ -29,12 +29,16 @@ use buck2_build_api::bxl::calculation::BxlCalculationDyn;
 use buck2_build_api::configure_dice::configure_dice_for_buck;
 use buck2_build_api::spawner::BuckSpawner;
 use buck2_common::buckd_connection::BUCK_AUTH_TOKEN_HEADER;
+use buck2_common::daemon_corruption::daemon_corruption;
 use buck2_common::invocation_paths::InvocationPaths;
 use buck2_common::io::IoProvider;
 use buck2_common::legacy_configs::LegacyBuckConfig;
 use buck2_common::memory;
+use buck2_common::package_listing::stats::DIR_LISTING_CACHE;
+use buck2_common::package_listing::stats::PACKAGE_LISTING_CACHE;
 use buck2_core::env_helper::EnvHelper;
 use buck2_core::error::reset_soft_error_counters;
+use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_path::AbsPathBuf;
 use buck2_events::dispatch::EventDispatcher;
 use buck2_events::ControlEvent;
@@ -198,6 +202,11 @@ pub trait BuckdServerDependencies: Send + Sync + 'static {
         ctx: Box<dyn ServerCommandContextTrait>,
         req: cli_proto::AqueryRequest,
     ) -> anyhow::Result<cli_proto::AqueryResponse>;
+    async fn action_exec(
+        &self,
+        ctx: Box<dyn ServerCommandContextTrait>,
+        req: cli_proto::ActionExecRequest,
+    ) -> anyhow::Result<cli_proto::ActionExecResponse>;
     async fn targets(
         &self,
         ctx: Box<dyn ServerCommandContextTrait>,
@@ -394,6 +403,11 @@ impl BuckdServer {
             data.start_time,
         ));
 
+        let crash_manifest_guard = data.crash_manifest.command_started(
+            dispatch.trace_id(),
+            req.get_ref().client_context()?.sanitized_argv.clone(),
+        );
+
         let configure_bxl_file_globals = self.0.callbacks.configure_bxl_file_globals();
 
         let resp = streaming(
@@ -404,23 +418,36 @@ impl BuckdServer {
             move |req| async move {
                 let result: anyhow::Result<Res> = try {
                     let base_context = daemon_state.prepare_command(dispatch.dupe(), guard).await?;
-                    build_listener::scope(base_context.events.dupe(), |build_sender| async {
-                        let context = ServerCommandContext::new(
-                            base_context,
-                            req.client_context()?,
-                            build_sender,
-                            opts.starlark_profiler_instrumentation_override(&req)?,
-                            req.build_options(),
-                            daemon_state.paths.buck_out_dir(),
-                            req.record_target_call_stacks(),
-                            configure_bxl_file_globals,
-                        )?;
-
-                        func(context, req).await
-                    })
+                    let build_signal_sink = base_context.build_signal_sink.dupe();
+                    build_listener::scope(
+                        base_context.events.dupe(),
+                        build_signal_sink,
+                        |build_sender| async {
+                            let context = ServerCommandContext::new(
+                                base_context,
+                                req.client_context()?,
+                                build_sender,
+                                opts.starlark_profiler_instrumentation_override(&req)?,
+                                req.build_options(),
+                                daemon_state.paths.buck_out_dir(),
+                                req.record_target_call_stacks(),
+                                configure_bxl_file_globals,
+                            )?;
+
+                            func(context, req).await
+                        },
+                    )
                     .await?
                 };
 
+                drop(crash_manifest_guard);
+
+                if let Some(reason) = daemon_corruption() {
+                    dispatch.instant_event(buck2_data::DaemonStateCorrupted {
+                        reason: reason as i32,
+                    });
+                }
+
                 let result: CommandResult = result_to_command_result(result);
                 dispatch.control_event(ControlEvent::CommandResult(result));
             },
@@ -494,6 +521,17 @@ fn convert_positive_duration(proto_duration: &prost_types::Duration) -> Result<D
         + Duration::from_nanos(proto_duration.nanos as u64))
 }
 
+fn listing_cache_status() -> ListingCacheStatus {
+    let dir = DIR_LISTING_CACHE.get();
+    let package = PACKAGE_LISTING_CACHE.get();
+    ListingCacheStatus {
+        dir_listing_lookups: dir.lookups,
+        dir_listing_computations: dir.computations,
+        package_listing_lookups: package.lookups,
+        package_listing_computations: package.computations,
+    }
+}
+
 fn error_to_command_result(e: anyhow::Error) -> CommandResult {
     let messages = vec![format!("{:?}", e)];
 
@@ -725,6 +763,17 @@ impl DaemonApi for BuckdServer {
                 callers: req.callers,
             };
 
+            // Commands still in flight get cancelled and may leave partial outputs behind, which
+            // the next daemon checks for.
+            if let Ok(data) = self.0.daemon_state.data() {
+                data.crash_manifest.killed(
+                    reason.reason.clone(),
+                    data.materializer
+                        .as_deferred_materializer_extension()
+                        .map(|ext| ext.queue_size()),
+                );
+            }
+
             self.0.daemon_shutdown.start_shutdown(reason, timeout);
             Ok(KillResponse {})
         })
@@ -746,6 +795,30 @@ impl DaemonApi for BuckdServer {
         .await
     }
 
+    async fn handshake(
+        &self,
+        req: Request<HandshakeRequest>,
+    ) -> Result<Response<CommandResult>, Status> {
+        #[derive(Debug, thiserror::Error)]
+        #[error(
+            "Client speaks version {0} of the daemon protocol, but the daemon speaks version {}",
+            protocol::PROTOCOL_VERSION
+        )]
+        struct ProtocolVersionMismatch(u32);
+
+        self.oneshot(req, DefaultCommandOptions, move |req| async move {
+            if req.protocol_version != protocol::PROTOCOL_VERSION {
+                return Err(ProtocolVersionMismatch(req.protocol_version).into());
+            }
+            Ok(HandshakeResponse {
+                protocol_version: protocol::PROTOCOL_VERSION,
+                capabilities: protocol::supported_capabilities(&req.capabilities),
+                daemon_version: self.0.process_info.version.clone(),
+            })
+        })
+        .await
+    }
+
     async fn status(&self, req: Request<StatusRequest>) -> Result<Response<CommandResult>, Status> {
         let daemon_state = self.0.daemon_state.dupe();
 
@@ -759,6 +832,7 @@ impl DaemonApi for BuckdServer {
                         data.start_time,
                         data.dice_manager.unsafe_dice().dupe(),
                         data.materializer.dupe(),
+                        data.analysis_queue.dupe(),
                     )
                     .create_snapshot(),
                 )
@@ -772,6 +846,7 @@ impl DaemonApi for BuckdServer {
                 start_time: Some(self.0.start_time.clone()),
                 uptime: Some(uptime.try_into()?),
                 snapshot,
+                listing_cache: Some(listing_cache_status()),
                 ..Default::default()
             };
             jemalloc_stats(&mut base);
@@ -792,6 +867,55 @@ impl DaemonApi for BuckdServer {
         .await
     }
 
+    async fn import_digests(
+        &self,
+        req: Request<ImportDigestsRequest>,
+    ) -> Result<Response<CommandResult>, Status> {
+        #[derive(Debug, thiserror::Error)]
+        #[error(
+            "The file digest cache is disabled, set `buck2.file_hashing_mode = mtime` to use it"
+        )]
+        struct DigestCacheDisabled;
+
+        let daemon_state = self.0.daemon_state.dupe();
+        self.oneshot(req, DefaultCommandOptions, move |req| async move {
+            let data = daemon_state.data()?;
+            let digest_cache = data.io.digest_cache().ok_or(DigestCacheDisabled)?;
+            let project_root = data.io.project_root().dupe();
+            let manifest_path = AbsPathBuf::try_from(req.manifest_path)?;
+
+            let imported = tokio::task::spawn_blocking({
+                let digest_cache = digest_cache.dupe();
+                let project_root = project_root.dupe();
+                move || {
+                    let manifest = fs_util::read_to_string(&manifest_path)?;
+                    let imported = digest_cache.import_manifest(project_root.root(), &manifest)?;
+                    digest_cache.save()?;
+                    anyhow::Ok(imported)
+                }
+            })
+            .await??;
+
+            // The imported digests are used until they are verified, which doesn't hold up the
+            // builds using them.
+            tokio::task::spawn_blocking(move || {
+                match digest_cache.verify_imported(project_root.root()) {
+                    Ok(0) => {}
+                    Ok(stale) => tracing::warn!("{} imported file digests were wrong", stale),
+                    Err(e) => tracing::warn!("Error verifying imported file digests: {:#}", e),
+                }
+                if let Err(e) = digest_cache.save() {
+                    tracing::warn!("Error saving file digest cache: {:#}", e);
+                }
+            });
+
+            Ok(ImportDigestsResponse {
+                imported: imported as u64,
+            })
+        })
+        .await
+    }
+
     type BuildStream = ResponseStream;
     async fn build(&self, req: Request<BuildRequest>) -> Result<Response<ResponseStream>, Status> {
         let callbacks = self.0.callbacks;
@@ -831,6 +955,18 @@ impl DaemonApi for BuckdServer {
         .await
     }
 
+    type ActionExecStream = ResponseStream;
+    async fn action_exec(
+        &self,
+        req: Request<ActionExecRequest>,
+    ) -> Result<Response<ResponseStream>, Status> {
+        let callbacks = self.0.callbacks;
+        self.run_streaming(req, DefaultCommandOptions, |ctx, req| {
+            callbacks.action_exec(box ctx, req)
+        })
+        .await
+    }
+
     type UqueryStream = ResponseStream;
     async fn uquery(
         &self,
@@ -7,24 +7,29 @@
  * of this source tree.
  */
 
-use std::collections::HashMap;
 use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Instant;
 
 use allocative::Allocative;
 use anyhow::Context;
-use buck2_common::file_ops::IgnoreSet;
+use buck2_build_api::actions::duration_history::ActionDurationHistory;
+use buck2_build_api::actions::signal_export::BuildSignalSink;
+use buck2_build_api::actions::signal_export::JsonLinesSignalSink;
+use buck2_build_api::analysis::queue::AnalysisQueue;
 use buck2_common::invocation_paths::InvocationPaths;
 use buck2_common::io::IoProvider;
 use buck2_common::legacy_configs::cells::BuckConfigBasedCells;
 use buck2_common::legacy_configs::LegacyBuckConfig;
 use buck2_common::result::SharedResult;
 use buck2_common::result::ToSharedResultExt;
-use buck2_core::cells::CellName;
 use buck2_core::facebook_only;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::project::ProjectRelativePath;
 use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::symlink_fallback::SymlinkFallback;
 use buck2_core::rollout_percentage::RolloutPercentage;
 use buck2_events::dispatch::EventDispatcher;
 use buck2_events::sink::scribe;
@@ -33,16 +38,20 @@ use buck2_events::trace::TraceId;
 use buck2_events::EventSource;
 use buck2_execute::execute::blocking::BlockingExecutor;
 use buck2_execute::execute::blocking::BuckBlockingExecutor;
+use buck2_execute::execute::environment_inheritance::EnvironmentInheritance;
 use buck2_execute::materialize::materializer::MaterializationMethod;
 use buck2_execute::materialize::materializer::Materializer;
+use buck2_execute::path::short_out_paths::ShortOutPaths;
 use buck2_execute::re::client::RemoteExecutionStaticMetadata;
 use buck2_execute::re::manager::ReConnectionManager;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializer;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializerConfigs;
 use buck2_execute_impl::materializers::deferred::TtlRefreshConfiguration;
 use buck2_execute_impl::materializers::immediate::ImmediateMaterializer;
+use buck2_execute_impl::materializers::local_cas::LocalCas;
 use buck2_execute_impl::materializers::sqlite::MaterializerState;
 use buck2_execute_impl::materializers::sqlite::MaterializerStateSqliteDb;
+use buck2_execute_impl::materializers::throttle::BandwidthThrottle;
 use buck2_forkserver::client::ForkserverClient;
 use buck2_server_ctx::concurrency::ConcurrencyHandler;
 use buck2_server_ctx::concurrency::NestedInvocation;
@@ -57,6 +66,8 @@ use tokio::sync::Mutex;
 use crate::active_commands::ActiveCommandDropGuard;
 use crate::ctx::BaseServerCommandContext;
 use crate::daemon::check_working_dir;
+use crate::daemon::crash_manifest::CrashManifest;
+use crate::daemon::crash_manifest::CrashManifestWriter;
 use crate::daemon::disk_state::delete_unknown_disk_state;
 use crate::daemon::disk_state::maybe_initialize_materializer_sqlite_db;
 use crate::daemon::disk_state::DiskStateOptions;
@@ -92,7 +103,16 @@ pub struct DaemonStateData {
     file_watcher: Arc<dyn FileWatcher>,
 
     /// Settled every time we run a command.
-    io: Arc<dyn IoProvider>,
+    pub(crate) io: Arc<dyn IoProvider>,
+
+    /// How long actions took to execute, saved every time we run a command.
+    action_duration_history: Arc<ActionDurationHistory>,
+
+    /// Limits the analyses running at once across all commands.
+    pub analysis_queue: Arc<AnalysisQueue>,
+
+    /// Set when `buck2.short_out_paths` selects the short output path layout.
+    pub short_out_paths: Option<Arc<ShortOutPaths>>,
 
     /// The RE connection, managed such that all build commands that are concurrently active uses
     /// the same connection. Once there are no active build commands, the connection will be
@@ -119,6 +139,18 @@ pub struct DaemonStateData {
     /// Whether or not to hash all commands
     pub hash_all_commands: bool,
 
+    /// Whether or not to use dep files when querying and writing to the remote action cache.
+    pub remote_dep_files: bool,
+
+    /// Where to export the features of the actions each build executes, if
+    /// `[buck2] build_signals_export_path` is set.
+    #[allocative(skip)]
+    pub build_signal_sink: Option<Arc<dyn BuildSignalSink>>,
+
+    /// The environment local commands are restricted to, if `[buck2] local_env_allowlist` is set.
+    #[allocative(skip)]
+    pub scrubbed_local_env: Option<EnvironmentInheritance>,
+
     /// What buck2 state to store on disk, ex. materializer state on sqlite
     pub disk_state_options: DiskStateOptions,
 
@@ -126,6 +158,10 @@ pub struct DaemonStateData {
 
     #[allocative(skip)]
     pub create_unhashed_outputs_lock: Arc<Mutex<()>>,
+
+    /// Records the commands in flight, for the next daemon to check buck-out against if this one
+    /// gets killed.
+    pub crash_manifest: Arc<CrashManifestWriter>,
 }
 
 impl DaemonStateData {
@@ -205,21 +241,13 @@ impl DaemonState {
             root_config,
         )?);
 
-        let ignore_specs: HashMap<CellName, IgnoreSet> = legacy_configs
-            .iter()
-            .map(|(cell, config)| {
-                Ok((
-                    cell.clone(),
-                    IgnoreSet::from_ignore_spec(config.get("project", "ignore").unwrap_or(""))?,
-                ))
-            })
-            .collect::<anyhow::Result<_>>()?;
-
         let materialization_method =
             MaterializationMethod::try_new_from_config(legacy_configs.get(cells.root_cell()).ok())?;
         let disk_state_options = DiskStateOptions::new(root_config, materialization_method.dupe())?;
-        let blocking_executor = Arc::new(BuckBlockingExecutor::default_concurrency(fs.dupe())?);
+        let blocking_executor =
+            Arc::new(BuckBlockingExecutor::from_config(fs.dupe(), root_config)?);
         let cache_dir_path = paths.cache_dir_path();
+        let digest_cache_dir = paths.file_digest_cache_path();
         let valid_cache_dirs = paths.valid_cache_dirs();
         let fs_duped = fs.dupe();
 
@@ -229,6 +257,7 @@ impl DaemonState {
                     fb,
                     fs.dupe(),
                     legacy_configs.get(cells.root_cell()).ok(),
+                    &digest_cache_dir,
                 ),
                 maybe_launch_forkserver(root_config),
                 (blocking_executor.dupe() as Arc<dyn BlockingExecutor>).execute_io_inline(|| {
@@ -247,6 +276,27 @@ impl DaemonState {
             )
             .await?;
 
+        let crash_manifest_path = paths.daemon_dir()?.crash_manifest();
+        // Checking buck-out hashes directories, which can take a while.
+        let (materializer_db, materializer_state) = tokio::task::spawn_blocking({
+            let crash_manifest_path = crash_manifest_path.clone();
+            let fs = io.project_root().dupe();
+            move || {
+                Self::discard_inconsistent_materializer_state(
+                    &crash_manifest_path,
+                    &fs,
+                    materializer_db,
+                    materializer_state,
+                )
+            }
+        })
+        .await??;
+        let crash_manifest = Arc::new(CrashManifestWriter::new(
+            crash_manifest_path,
+            format!("{:?}", materialization_method),
+            disk_state_options.sqlite_materializer_state,
+        )?);
+
         let re_client_manager = Arc::new(ReConnectionManager::new(
             fb,
             false,
@@ -255,6 +305,7 @@ impl DaemonState {
             Some(paths.re_logs_dir().to_string()),
             paths.buck_out_dir().to_string(),
         ));
+        let local_cas = Self::create_local_cas(io.project_root(), root_config).await?;
         let materializer = Self::create_materializer(
             fb,
             io.project_root().dupe(),
@@ -265,6 +316,7 @@ impl DaemonState {
             root_config,
             materializer_db,
             materializer_state,
+            local_cas,
         )?;
 
         let buffer_size = root_config
@@ -272,6 +324,25 @@ impl DaemonState {
             .unwrap_or(10000);
         let event_logging_data = Arc::new(EventLoggingData { buffer_size });
 
+        let action_duration_history = Arc::new(
+            ActionDurationHistory::load(&paths.action_duration_history_path())
+                .context("Error loading action duration history")?,
+        );
+
+        let analysis_queue = Arc::new(AnalysisQueue::from_config(root_config)?);
+
+        let short_out_paths = if root_config
+            .parse("buck2", "short_out_paths")?
+            .unwrap_or(false)
+        {
+            Some(Arc::new(
+                ShortOutPaths::load(&paths.short_out_paths_path())
+                    .context("Error loading short output paths")?,
+            ))
+        } else {
+            None
+        };
+
         let dice = dice_constructor.construct_dice(io.dupe(), root_config)?;
 
         // TODO(cjhopman): We want to use Expr::True here, but we need to workaround
@@ -282,7 +353,7 @@ impl DaemonState {
             paths.project_root(),
             root_config,
             cells.dupe(),
-            ignore_specs,
+            &legacy_configs,
         )
         .context("Error creating a FileWatcher")?;
 
@@ -291,6 +362,39 @@ impl DaemonState {
             .unwrap_or_else(RolloutPercentage::never)
             .roll();
 
+        let remote_dep_files = root_config
+            .parse("buck2", "remote_dep_files")?
+            .unwrap_or(false);
+
+        let build_signal_sink = root_config
+            .get("buck2", "build_signals_export_path")
+            .map(|path| {
+                let path = if Path::new(path).is_absolute() {
+                    AbsNormPathBuf::new(PathBuf::from(path))?
+                } else {
+                    paths
+                        .project_root()
+                        .resolve(ProjectRelativePath::new(path)?)
+                };
+                anyhow::Ok(Arc::new(JsonLinesSignalSink::new(path)) as Arc<dyn BuildSignalSink>)
+            })
+            .transpose()
+            .context("Invalid `buck2.build_signals_export_path`")?;
+
+        // The allowlisted values are captured now, so they stay stable for the lifetime of the
+        // daemon, regardless of the environment of the clients that talk to it.
+        let scrubbed_local_env = root_config
+            .get("buck2", "local_env_allowlist")
+            .map(|allowlist| {
+                let allowlist = allowlist
+                    .split(',')
+                    .map(|key| key.trim())
+                    .filter(|key| !key.is_empty())
+                    .map(|key| key.to_owned())
+                    .collect::<Vec<_>>();
+                EnvironmentInheritance::scrubbed(&allowlist)
+            });
+
         let nested_invocation_config = root_config
             .parse::<NestedInvocation>("buck2", "nested_invocation")?
             .unwrap_or(NestedInvocation::Run);
@@ -314,18 +418,88 @@ impl DaemonState {
             ),
             file_watcher,
             io,
+            action_duration_history,
+            analysis_queue,
+            short_out_paths,
             re_client_manager,
             blocking_executor,
             materializer,
             forkserver,
             event_logging_data,
             hash_all_commands,
+            remote_dep_files,
+            build_signal_sink,
+            scrubbed_local_env,
             disk_state_options,
             start_time: std::time::Instant::now(),
             create_unhashed_outputs_lock,
+            crash_manifest,
         }))
     }
 
+    /// If the previous daemon stopped while commands were in flight, the materializer state it
+    /// persisted may list artifacts it was still writing to buck-out. Those are dropped, so that
+    /// they get materialized again rather than being used half-written.
+    fn discard_inconsistent_materializer_state(
+        crash_manifest_path: &AbsNormPathBuf,
+        fs: &ProjectRoot,
+        materializer_db: Option<MaterializerStateSqliteDb>,
+        materializer_state: Option<MaterializerState>,
+    ) -> anyhow::Result<(Option<MaterializerStateSqliteDb>, Option<MaterializerState>)> {
+        let manifest = match CrashManifest::load(crash_manifest_path) {
+            Some(manifest) if manifest.had_commands_in_flight() => manifest,
+            _ => return Ok((materializer_db, materializer_state)),
+        };
+        tracing::warn!("{}", manifest.describe());
+
+        match (materializer_db, materializer_state) {
+            (Some(mut db), Some(state)) => {
+                let (state, discarded) = db
+                    .discard_inconsistent(state, fs)
+                    .context("Error checking buck-out against the materializer state")?;
+                if !discarded.is_empty() {
+                    tracing::warn!(
+                        "Deleted {} outputs left incomplete by the previous buck2 daemon",
+                        discarded.len()
+                    );
+                }
+                Ok((Some(db), Some(state)))
+            }
+            (materializer_db, materializer_state) => Ok((materializer_db, materializer_state)),
+        }
+    }
+
+    /// The store that files downloaded from CAS are kept in to be hardlinked into buck-out, if one
+    /// is configured. It is trimmed to its maximum size before the materializer starts using it.
+    async fn create_local_cas(
+        fs: &ProjectRoot,
+        root_config: &LegacyBuckConfig,
+    ) -> anyhow::Result<Option<Arc<LocalCas>>> {
+        let dir = match root_config.get("buck2", "materializer_local_cas_dir") {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+        let dir = if Path::new(dir).is_absolute() {
+            AbsNormPathBuf::try_from(dir.to_owned())?
+        } else {
+            fs.resolve(ProjectRelativePath::new(dir)?)
+        };
+        let local_cas = Arc::new(LocalCas::new(dir));
+
+        if let Some(max_bytes) =
+            root_config.parse::<u64>("buck2", "materializer_local_cas_max_bytes")?
+        {
+            let cas = local_cas.dupe();
+            match tokio::task::spawn_blocking(move || cas.evict(max_bytes)).await? {
+                Ok(0) => {}
+                Ok(evicted) => tracing::info!("Evicted {} bytes from the local CAS", evicted),
+                // The store only makes materializations faster, it is fine if it is too large.
+                Err(e) => tracing::warn!("Error evicting from the local CAS: {:#}", e),
+            }
+        }
+        Ok(Some(local_cas))
+    }
+
     fn create_materializer(
         fb: FacebookInit,
         fs: ProjectRoot,
@@ -336,12 +510,23 @@ impl DaemonState {
         root_config: &LegacyBuckConfig,
         materializer_db: Option<MaterializerStateSqliteDb>,
         materializer_state: Option<MaterializerState>,
+        local_cas: Option<Arc<LocalCas>>,
     ) -> anyhow::Result<Arc<dyn Materializer>> {
+        let symlink_fallback = root_config
+            .parse::<SymlinkFallback>("buck2", "windows_symlink_fallback")?
+            .unwrap_or_default();
+
+        let throttle = root_config
+            .parse::<u64>("buck2", "materializer_max_bytes_per_second")?
+            .map(|bytes_per_second| Arc::new(BandwidthThrottle::new(bytes_per_second)));
+
         match materialization_method {
             MaterializationMethod::Immediate => Ok(Arc::new(ImmediateMaterializer::new(
                 fs,
                 re_client_manager,
                 blocking_executor,
+                symlink_fallback,
+                throttle,
             ))),
             MaterializationMethod::Deferred | MaterializationMethod::DeferredSkipFinalArtifacts => {
                 let defer_write_actions = root_config
@@ -375,6 +560,9 @@ impl DaemonState {
                         min_ttl: chrono::Duration::seconds(ttl_refresh_min_ttl),
                         enabled: ttl_refresh_enabled,
                     },
+                    symlink_fallback,
+                    local_cas,
+                    throttle,
                 };
 
                 Ok(Arc::new(DeferredMaterializer::new(
@@ -485,6 +673,17 @@ impl DaemonState {
         // Sync any FS changes and invalidate DICE state if necessary.
         data.io.settle().await?;
 
+        // Persist the durations recorded by the previous command, so a restarted daemon can use
+        // them. They are only used for estimates, so failing to save them isn't an error.
+        let action_duration_history = data.action_duration_history.dupe();
+        let saved = tokio::task::spawn_blocking(move || action_duration_history.save())
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|saved| saved);
+        if let Err(e) = saved {
+            tracing::warn!("Error saving action duration history: {:#}", e);
+        }
+
         Ok(BaseServerCommandContext {
             _fb: self.fb,
             project_root: self.paths.project_root().clone(),
@@ -497,9 +696,15 @@ impl DaemonState {
             events: dispatcher,
             forkserver: data.forkserver.dupe(),
             hash_all_commands: data.hash_all_commands,
+            remote_dep_files: data.remote_dep_files,
+            build_signal_sink: data.build_signal_sink.dupe(),
+            scrubbed_local_env: data.scrubbed_local_env,
             _drop_guard: drop_guard,
             daemon_start_time: data.start_time,
             create_unhashed_outputs_lock: data.create_unhashed_outputs_lock.dupe(),
+            action_duration_history: data.action_duration_history.dupe(),
+            analysis_queue: data.analysis_queue.dupe(),
+            short_out_paths: data.short_out_paths.dupe(),
         })
     }
 
@@ -0,0 +1,109 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use allocative::Allocative;
+use anyhow::Context;
+use buck2_common::file_ops::IgnoreSet;
+use buck2_common::file_ops::CELL_IGNORE_FILE;
+use buck2_common::legacy_configs::LegacyBuckConfigs;
+use buck2_core::cells::cell_path::CellPath;
+use buck2_core::cells::paths::CellRelativePath;
+use buck2_core::cells::CellName;
+use buck2_core::cells::CellResolver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::project::ProjectRoot;
+use gazebo::prelude::*;
+use tracing::info;
+
+/// The paths whose changes the file watchers drop: those matching the `project.ignore` of their
+/// cell, or the patterns of its ignore file ([`CELL_IGNORE_FILE`]).
+///
+/// The ignore file of a cell is read again when a file watcher reports a change to it, so that
+/// directories it stops ignoring are watched from then on.
+#[derive(Allocative)]
+pub(crate) struct FileWatcherIgnores {
+    project_root: ProjectRoot,
+    cells: CellResolver,
+    /// The `project.ignore` of each cell, which only changes with a daemon restart.
+    ignore_specs: HashMap<CellName, String>,
+    #[allocative(skip)]
+    ignores: RwLock<HashMap<CellName, IgnoreSet>>,
+}
+
+impl FileWatcherIgnores {
+    pub(crate) fn new(
+        project_root: &ProjectRoot,
+        cells: CellResolver,
+        legacy_configs: &LegacyBuckConfigs,
+    ) -> anyhow::Result<Self> {
+        let ignore_specs: HashMap<CellName, String> = legacy_configs
+            .iter()
+            .map(|(cell, config)| {
+                (
+                    cell.clone(),
+                    config.get("project", "ignore").unwrap_or("").to_owned(),
+                )
+            })
+            .collect();
+        let mut res = Self {
+            project_root: project_root.dupe(),
+            cells,
+            ignore_specs,
+            ignores: RwLock::new(HashMap::new()),
+        };
+        let ignores = res
+            .ignore_specs
+            .keys()
+            .map(|cell| Ok((cell.clone(), res.load(cell)?)))
+            .collect::<anyhow::Result<_>>()?;
+        *res.ignores.get_mut().unwrap() = ignores;
+        Ok(res)
+    }
+
+    fn load(&self, cell: &CellName) -> anyhow::Result<IgnoreSet> {
+        let ignore_file = self
+            .cells
+            .get(cell)?
+            .path()
+            .join(CellRelativePath::unchecked_new(CELL_IGNORE_FILE));
+        let ignore_file = fs_util::read_to_string_opt(self.project_root.resolve(&ignore_file))
+            .with_context(|| format!("Error reading `{}`", ignore_file))?;
+        IgnoreSet::from_ignore_spec_and_file(&self.ignore_specs[cell], ignore_file.as_deref())
+    }
+
+    pub(crate) fn is_ignored(&self, path: &CellPath) -> bool {
+        self.ignores
+            .read()
+            .unwrap()
+            .get(path.cell())
+            .expect("unexpected cell name mismatch")
+            .is_match(path.path())
+    }
+
+    /// Called for every change that isn't ignored, reloads the ignores of the cell if `path` is
+    /// its ignore file.
+    pub(crate) fn on_change(&self, path: &CellPath) -> anyhow::Result<()> {
+        if path.path().as_str() != CELL_IGNORE_FILE {
+            return Ok(());
+        }
+        info!(
+            "FileWatcher: reloading the ignores of cell `{}`",
+            path.cell()
+        );
+        let ignores = self.load(path.cell())?;
+        self.ignores
+            .write()
+            .unwrap()
+            .insert(path.cell().clone(), ignores);
+        Ok(())
+    }
+}
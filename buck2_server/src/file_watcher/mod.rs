@@ -7,22 +7,24 @@
  * of this source tree.
  */
 
-use std::collections::HashMap;
 use std::sync::Arc;
 
 use allocative::Allocative;
 use async_trait::async_trait;
-use buck2_common::file_ops::IgnoreSet;
 use buck2_common::legacy_configs::LegacyBuckConfig;
-use buck2_core::cells::CellName;
+use buck2_common::legacy_configs::LegacyBuckConfigs;
+use buck2_common::source_changes::SourceChangeLog;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::project::ProjectRoot;
 use buck2_core::is_open_source;
 use dice::DiceTransaction;
+use gazebo::prelude::*;
 
+use crate::file_watcher::ignores::FileWatcherIgnores;
 use crate::file_watcher::notify::NotifyFileWatcher;
 use crate::file_watcher::watchman::interface::WatchmanFileWatcher;
 
+mod ignores;
 mod notify;
 mod stats;
 mod watchman;
@@ -30,6 +32,9 @@ mod watchman;
 #[async_trait]
 pub trait FileWatcher: Allocative + Send + Sync + 'static {
     async fn sync(&self, dice: DiceTransaction) -> anyhow::Result<DiceTransaction>;
+
+    /// The changes to source files made while commands run, if this watcher can track them.
+    fn source_changes(&self) -> Option<Arc<SourceChangeLog>>;
 }
 
 impl dyn FileWatcher {
@@ -39,8 +44,10 @@ impl dyn FileWatcher {
         project_root: &ProjectRoot,
         root_config: &LegacyBuckConfig,
         cells: CellResolver,
-        ignore_specs: HashMap<CellName, IgnoreSet>,
+        legacy_configs: &LegacyBuckConfigs,
     ) -> anyhow::Result<Arc<dyn FileWatcher>> {
+        let ignores = FileWatcherIgnores::new(project_root, cells.dupe(), legacy_configs)?;
+
         let default = if is_open_source() {
             "notify"
         } else {
@@ -52,12 +59,12 @@ impl dyn FileWatcher {
                 project_root.root(),
                 root_config,
                 cells,
-                ignore_specs,
+                ignores,
             )?)),
             "notify" => Ok(Arc::new(NotifyFileWatcher::new(
                 project_root,
                 cells,
-                ignore_specs,
+                ignores,
             )?)),
             other => Err(anyhow::anyhow!("Invalid buck2.file_watcher: {}", other)),
         }
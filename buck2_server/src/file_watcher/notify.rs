@@ -7,7 +7,6 @@
  * of this source tree.
  */
 
-use std::collections::HashMap;
 use std::mem;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -15,9 +14,8 @@ use std::sync::Mutex;
 use allocative::Allocative;
 use async_trait::async_trait;
 use buck2_common::dice::file_ops::FileChangeTracker;
-use buck2_common::file_ops::IgnoreSet;
 use buck2_common::invocation_paths::InvocationPaths;
-use buck2_core::cells::CellName;
+use buck2_common::source_changes::SourceChangeLog;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
 use buck2_core::fs::project::ProjectRoot;
@@ -33,6 +31,7 @@ use notify::RecommendedWatcher;
 use notify::Watcher;
 use tracing::info;
 
+use crate::file_watcher::ignores::FileWatcherIgnores;
 use crate::file_watcher::stats::FileWatcherStats;
 use crate::file_watcher::FileWatcher;
 
@@ -77,14 +76,17 @@ impl ChangeType {
 #[derive(Allocative)]
 struct NotifyFileData {
     changed: FileChangeTracker,
+    /// The changes as they happen, rather than when the watcher is synced.
+    source_changes: Arc<SourceChangeLog>,
     stats: FileWatcherStats,
     error: Option<anyhow::Error>,
 }
 
 impl NotifyFileData {
-    fn new() -> Self {
+    fn new(source_changes: Arc<SourceChangeLog>) -> Self {
         Self {
             changed: FileChangeTracker::new(),
+            source_changes,
             stats: FileWatcherStats::new(0, None),
             error: None,
         }
@@ -95,12 +97,12 @@ impl NotifyFileData {
         event: notify::Result<notify::Event>,
         root: &ProjectRoot,
         cells: &CellResolver,
-        ignore_specs: &HashMap<CellName, IgnoreSet>,
+        ignores: &FileWatcherIgnores,
     ) {
         if self.error.is_some() {
             return;
         }
-        if let Err(e) = self.process_err(event, root, cells, ignore_specs) {
+        if let Err(e) = self.process_err(event, root, cells, ignores) {
             self.error = Some(e);
             // Might as well clear out the memory we aren't going to use
             self.changed = FileChangeTracker::new();
@@ -113,7 +115,7 @@ impl NotifyFileData {
         event: notify::Result<notify::Event>,
         root: &ProjectRoot,
         cells: &CellResolver,
-        ignore_specs: &HashMap<CellName, IgnoreSet>,
+        ignores: &FileWatcherIgnores,
     ) -> anyhow::Result<()> {
         let event = event?;
         let change_type = ChangeType::new(event.kind);
@@ -129,10 +131,7 @@ impl NotifyFileData {
             // We do this in the notify-watcher, rather than a generic layer, as watchman users should configure
             // to ignore buck-out, to reduce the number of events, rather than hiding them later.
             let ignore = path.starts_with(InvocationPaths::buck_out_dir_prefix())
-                || ignore_specs
-                    .get(cell_path.cell())
-                    .expect("unexpected cell name mismatch")
-                    .is_match(cell_path.path());
+                || ignores.is_ignored(&cell_path);
 
             info!(
                 "FileWatcher: {:?} {:?} (ignore = {})",
@@ -142,6 +141,8 @@ impl NotifyFileData {
             if ignore || change_type == ChangeType::None {
                 self.stats.add_ignored();
             } else {
+                ignores.on_change(&cell_path)?;
+                self.source_changes.record(cell_path.clone());
                 match change_type {
                     ChangeType::None => {}
                     ChangeType::FileContents => self.changed.file_changed(cell_path),
@@ -181,25 +182,31 @@ pub struct NotifyFileWatcher {
     #[allocative(skip)]
     watcher: RecommendedWatcher,
     data: Arc<Mutex<NotifyFileData>>,
+    source_changes: Arc<SourceChangeLog>,
 }
 
 impl NotifyFileWatcher {
     pub fn new(
         root: &ProjectRoot,
         cells: CellResolver,
-        ignore_specs: HashMap<CellName, IgnoreSet>,
+        ignores: FileWatcherIgnores,
     ) -> anyhow::Result<Self> {
-        let data = Arc::new(Mutex::new(NotifyFileData::new()));
+        let source_changes = Arc::new(SourceChangeLog::new());
+        let data = Arc::new(Mutex::new(NotifyFileData::new(source_changes.dupe())));
         let data2 = data.dupe();
         let root2 = root.dupe();
         let mut watcher = notify::recommended_watcher(move |event| {
             data2
                 .lock()
                 .unwrap()
-                .process(event, &root2, &cells, &ignore_specs)
+                .process(event, &root2, &cells, &ignores)
         })?;
         watcher.watch(root.root().as_path(), notify::RecursiveMode::Recursive)?;
-        Ok(Self { watcher, data })
+        Ok(Self {
+            watcher,
+            data,
+            source_changes,
+        })
     }
 
     fn sync2(
@@ -229,4 +236,8 @@ impl FileWatcher for NotifyFileWatcher {
         )
         .await
     }
+
+    fn source_changes(&self) -> Option<Arc<SourceChangeLog>> {
+        Some(self.source_changes.dupe())
+    }
 }
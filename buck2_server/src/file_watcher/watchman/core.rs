@@ -72,7 +72,7 @@ mod types {
     }
 }
 
-use types::*;
+pub(crate) use types::BuckQueryResult;
 
 #[derive(Debug)]
 pub enum WatchmanEventType {
@@ -393,8 +393,26 @@ where
     }
 }
 
+/// The query for the changes matching `expr`, to be sent with a clock.
+pub(crate) fn buck_query(expr: Expr) -> QueryRequestCommon {
+    QueryRequestCommon {
+        expression: Some(expr),
+        fields: vec!["name"],
+        empty_on_fresh_instance: true,
+        relative_root: None,
+        case_sensitive: true,
+        dedup_results: false,
+        // Required or we miss directory events
+        always_include_directories: true,
+        // TODO(cjhopman): Figure out reasonable timeouts.
+        // sync_timeout: ???,
+        // lock_timeout: ???,
+        ..QueryRequestCommon::default()
+    }
+}
+
 /// Unpacks the clock returned for an scm-aware query into a tuple of the mergebase and the clockspec.
-fn unpack_clock(clock: Clock) -> (Option<String>, ClockSpec) {
+pub(crate) fn unpack_clock(clock: Clock) -> (Option<String>, ClockSpec) {
     match clock {
         Clock::Spec(clock_spec) => (None, clock_spec),
         Clock::ScmAware(FatClockData {
@@ -447,20 +465,7 @@ where
         let path = CanonicalPath::canonicalize(path)
             .with_context(|| format!("Error canonicalizing: `{}`", path.display()))?;
 
-        let query = QueryRequestCommon {
-            expression: Some(expr),
-            fields: vec!["name"],
-            empty_on_fresh_instance: true,
-            relative_root: None,
-            case_sensitive: true,
-            dedup_results: false,
-            // Required or we miss directory events
-            always_include_directories: true,
-            // TODO(cjhopman): Figure out reasonable timeouts.
-            // sync_timeout: ???,
-            // lock_timeout: ???,
-            ..QueryRequestCommon::default()
-        };
+        let query = buck_query(expr);
 
         let (control_tx, control_rx) =
             tokio::sync::mpsc::unbounded_channel::<SyncableQueryCommand<T, P>>();
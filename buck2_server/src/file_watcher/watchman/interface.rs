@@ -7,39 +7,41 @@
  * of this source tree.
  */
 
-use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
 
 use allocative::Allocative;
 use anyhow::Context as _;
 use async_trait::async_trait;
 use buck2_common::dice::file_ops::FileChangeTracker;
-use buck2_common::file_ops::IgnoreSet;
 use buck2_common::legacy_configs::LegacyBuckConfig;
-use buck2_core::cells::CellName;
+use buck2_common::source_changes::SourceChangeLog;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
 use buck2_core::fs::project::ProjectRelativePath;
 use buck2_core::rollout_percentage::RolloutPercentage;
 use buck2_events::dispatch::span_async;
 use dice::DiceTransaction;
+use gazebo::prelude::*;
 use tracing::info;
 use tracing::warn;
 use watchman_client::expr::Expr;
 use watchman_client::prelude::Connector;
 use watchman_client::prelude::FileType;
 
+use crate::file_watcher::ignores::FileWatcherIgnores;
 use crate::file_watcher::stats::FileWatcherStats;
 use crate::file_watcher::watchman::core::SyncableQuery;
 use crate::file_watcher::watchman::core::SyncableQueryProcessor;
 use crate::file_watcher::watchman::core::WatchmanEvent;
 use crate::file_watcher::watchman::core::WatchmanEventType;
 use crate::file_watcher::watchman::core::WatchmanKind;
+use crate::file_watcher::watchman::source_changes::WatchmanSourceChanges;
 use crate::file_watcher::FileWatcher;
 
 struct WatchmanQueryProcessor {
     cells: CellResolver,
-    ignore_specs: HashMap<CellName, IgnoreSet>,
+    ignores: Arc<FileWatcherIgnores>,
     retain_dep_files_on_watchman_fresh_instance: bool,
 }
 
@@ -98,17 +100,14 @@ impl WatchmanQueryProcessor {
     ) -> anyhow::Result<()> {
         let cell_path = self.cells.get_cell_path(path)?;
 
-        let ignore = self
-            .ignore_specs
-            .get(cell_path.cell())
-            .expect("unexpected cell name mismatch")
-            .is_match(cell_path.path());
+        let ignore = self.ignores.is_ignored(&cell_path);
 
         info!("Watchman: {:?} (ignore = {})", ev, ignore);
 
         if ignore {
             stats.add_ignored();
         } else {
+            self.ignores.on_change(&cell_path)?;
             let cell_path_str = cell_path.to_string();
             let log_kind;
             let log_event;
@@ -251,6 +250,7 @@ impl SyncableQueryProcessor for WatchmanQueryProcessor {
 pub(crate) struct WatchmanFileWatcher {
     #[allocative(skip)]
     query: SyncableQuery<buck2_data::FileWatcherStats, DiceTransaction>,
+    source_changes: Arc<SourceChangeLog>,
 }
 
 /// The watchman query is constructed once on daemon startup. It is an unfiltered watchman query
@@ -262,7 +262,7 @@ impl WatchmanFileWatcher {
         project_root: &AbsNormPath,
         root_config: &LegacyBuckConfig,
         cells: CellResolver,
-        ignore_specs: HashMap<CellName, IgnoreSet>,
+        ignores: FileWatcherIgnores,
     ) -> anyhow::Result<Self> {
         let watchman_merge_base = root_config
             .get("project", "watchman_merge_base")
@@ -273,23 +273,34 @@ impl WatchmanFileWatcher {
             .unwrap_or_else(RolloutPercentage::always)
             .roll();
 
+        let ignores = Arc::new(ignores);
+        let expr = Expr::Any(vec![
+            Expr::FileType(FileType::Regular),
+            Expr::FileType(FileType::Directory),
+            Expr::FileType(FileType::Symlink),
+        ]);
+        let source_changes = Arc::new(SourceChangeLog::with_poll(box WatchmanSourceChanges::new(
+            project_root,
+            expr.clone(),
+            cells.dupe(),
+            ignores.dupe(),
+        )?));
         let query = SyncableQuery::new(
             Connector::new(),
             project_root,
-            Expr::Any(vec![
-                Expr::FileType(FileType::Regular),
-                Expr::FileType(FileType::Directory),
-                Expr::FileType(FileType::Symlink),
-            ]),
+            expr,
             box WatchmanQueryProcessor {
                 cells,
-                ignore_specs,
+                ignores,
                 retain_dep_files_on_watchman_fresh_instance,
             },
             watchman_merge_base,
         )?;
 
-        Ok(Self { query })
+        Ok(Self {
+            query,
+            source_changes,
+        })
     }
 }
 
@@ -310,4 +321,8 @@ impl FileWatcher for WatchmanFileWatcher {
         )
         .await
     }
+
+    fn source_changes(&self) -> Option<Arc<SourceChangeLog>> {
+        Some(self.source_changes.dupe())
+    }
 }
@@ -12,3 +12,4 @@ mod test;
 
 pub(crate) mod core;
 pub(crate) mod interface;
+mod source_changes;
@@ -0,0 +1,149 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_common::source_changes::PollSourceChanges;
+use buck2_common::source_changes::SourceChangeLog;
+use buck2_core::cells::CellResolver;
+use buck2_core::fs::project::ProjectRelativePath;
+use thiserror::Error;
+use watchman_client::prelude::*;
+
+use crate::file_watcher::ignores::FileWatcherIgnores;
+use crate::file_watcher::watchman::core::buck_query;
+use crate::file_watcher::watchman::core::unpack_clock;
+use crate::file_watcher::watchman::core::BuckQueryResult;
+use crate::file_watcher::watchman::core::WatchmanClient;
+
+#[derive(Debug, Error)]
+#[error("Watchman lost track of the changes to source files (fresh instance)")]
+struct FreshInstanceError;
+
+/// Asks Watchman for the changes made since the last poll. This uses a connection of its own
+/// rather than the file watcher's query, which only runs when commands start.
+pub(crate) struct WatchmanSourceChanges {
+    connector: Connector,
+    path: CanonicalPath,
+    query: QueryRequestCommon,
+    cells: CellResolver,
+    ignores: Arc<FileWatcherIgnores>,
+    state: Mutex<PollState>,
+}
+
+#[derive(Default)]
+struct PollState {
+    /// The connection and the clock of the last poll, once connected.
+    connection: Option<(WatchmanClient, ClockSpec)>,
+    /// Bumped whenever `connection` changes, so that a poll which overlapped with another one
+    /// doesn't roll the clock back.
+    generation: u64,
+}
+
+impl PollState {
+    fn update(&mut self, generation: u64, connection: Option<(WatchmanClient, ClockSpec)>) {
+        if self.generation == generation {
+            self.connection = connection;
+            self.generation += 1;
+        }
+    }
+}
+
+impl WatchmanSourceChanges {
+    pub(crate) fn new(
+        path: impl AsRef<Path>,
+        expr: Expr,
+        cells: CellResolver,
+        ignores: Arc<FileWatcherIgnores>,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let path = CanonicalPath::canonicalize(path)
+            .with_context(|| format!("Error canonicalizing: `{}`", path.display()))?;
+        Ok(Self {
+            connector: Connector::new(),
+            path,
+            query: buck_query(expr),
+            cells,
+            ignores,
+            state: Mutex::new(PollState::default()),
+        })
+    }
+}
+
+#[async_trait]
+impl PollSourceChanges for WatchmanSourceChanges {
+    async fn poll(&self, log: &SourceChangeLog) -> anyhow::Result<()> {
+        // Watchman is queried without holding the lock, so that commands don't wait on each
+        // other's polls. Overlapping polls may record the same change twice, which is harmless.
+        let (connection, generation) = {
+            let state = self.state.lock().unwrap();
+            (state.connection.clone(), state.generation)
+        };
+        let (client, last_clock, connecting) = match connection {
+            Some((client, clock)) => (client, clock, false),
+            None => (
+                WatchmanClient::connect(&self.connector, self.path.clone())
+                    .await
+                    .context("Error connecting to Watchman")?,
+                ClockSpec::default(),
+                true,
+            ),
+        };
+
+        let mut query = self.query.clone();
+        query.since = Some(Clock::Spec(last_clock));
+        let QueryResult {
+            is_fresh_instance,
+            files,
+            clock,
+            ..
+        } = match client.query::<BuckQueryResult>(query).await {
+            Ok(result) => result,
+            Err(e) => {
+                // The next poll reconnects.
+                self.state.lock().unwrap().update(generation, None);
+                return Err(e);
+            }
+        };
+        let (_, clock) = unpack_clock(clock);
+        self.state
+            .lock()
+            .unwrap()
+            .update(generation, Some((client, clock)));
+
+        if is_fresh_instance {
+            // The first query after connecting is always a fresh instance, and there is nothing
+            // to record yet.
+            return if connecting {
+                Ok(())
+            } else {
+                Err(FreshInstanceError.into())
+            };
+        }
+
+        for event in files.unwrap_or_default() {
+            let event = match event.into_event() {
+                Some(event) => event,
+                None => continue,
+            };
+            // Paths that aren't valid or are ignored can't be inputs of commands either.
+            if let Ok(path) = ProjectRelativePath::new(&event.path) {
+                let cell_path = self.cells.get_cell_path(path)?;
+                if !self.ignores.is_ignored(&cell_path) {
+                    log.record(cell_path);
+                }
+            }
+        }
+        Ok(())
+    }
+}
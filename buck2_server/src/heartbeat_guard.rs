@@ -15,6 +15,7 @@ use buck2_events::dispatch::EventDispatcher;
 use gazebo::dupe::Dupe;
 use tokio::task::JoinHandle;
 
+use crate::active_commands;
 use crate::ctx::BaseServerCommandContext;
 use crate::snapshot;
 
@@ -23,17 +24,66 @@ pub struct HeartbeatGuard {
     handle: JoinHandle<()>,
     collector: snapshot::SnapshotCollector,
     events: Arc<Mutex<Option<EventDispatcher>>>,
+    usage: Arc<Mutex<ResourceUsage>>,
+}
+
+/// Accumulates the snapshots of a command to summarize the resources it used.
+#[derive(Default)]
+struct ResourceUsage {
+    first: Option<buck2_data::Snapshot>,
+    peak_rss: u64,
+}
+
+impl ResourceUsage {
+    fn observe(&mut self, snapshot: &buck2_data::Snapshot) {
+        if self.first.is_none() {
+            self.first = Some(snapshot.clone());
+        }
+        self.peak_rss = self
+            .peak_rss
+            .max(snapshot.buck2_rss.unwrap_or(snapshot.buck2_max_rss));
+    }
+
+    /// The resources used between the first snapshot and `last`, which must have been observed.
+    /// The action figures are the totals of the local commands run by the actions of the command,
+    /// since the children of the daemon miss the commands run by the forkserver and include those
+    /// of any concurrent command. The daemon CPU and RE transfers can only be measured for the
+    /// whole daemon, so they are left out if other commands ran `concurrently`.
+    fn summary(
+        &self,
+        last: &buck2_data::Snapshot,
+        actions: &buck2_data::CommandResourceUsage,
+        concurrently: bool,
+    ) -> buck2_data::ResourceUsageSummary {
+        let first = self.first.as_ref().unwrap_or(last);
+        let delta = |f: fn(&buck2_data::Snapshot) -> u64| {
+            (!concurrently).then(|| f(last).saturating_sub(f(first)))
+        };
+        buck2_data::ResourceUsageSummary {
+            daemon_user_cpu_us: delta(|s| s.buck2_user_cpu_us),
+            daemon_system_cpu_us: delta(|s| s.buck2_system_cpu_us),
+            actions_user_cpu_us: actions.user_cpu_us,
+            actions_system_cpu_us: actions.system_cpu_us,
+            peak_daemon_rss_bytes: self.peak_rss,
+            disk_read_bytes: actions.disk_read_bytes,
+            disk_write_bytes: actions.disk_write_bytes,
+            re_download_bytes: delta(|s| s.re_download_bytes),
+            re_upload_bytes: delta(|s| s.re_upload_bytes),
+        }
+    }
 }
 
 impl HeartbeatGuard {
     pub fn new(ctx: &BaseServerCommandContext) -> Self {
         let events = Arc::new(Mutex::new(Some(ctx.events.dupe())));
+        let usage = Arc::new(Mutex::new(ResourceUsage::default()));
         let collector = snapshot::SnapshotCollector::new(
             ctx.re_client_manager.dupe(),
             ctx.blocking_executor.dupe(),
             ctx.daemon_start_time,
             ctx.dice_manager.unsafe_dice().dupe(),
             ctx.materializer.dupe(),
+            ctx.analysis_queue.dupe(),
         );
 
         // NOTE: This doesn't use the ambient dispatcher wrappers because we want to control the
@@ -41,11 +91,13 @@ impl HeartbeatGuard {
         let handle = tokio::spawn({
             let events = events.dupe();
             let collector = collector.dupe();
+            let usage = usage.dupe();
             async move {
                 let mut interval = tokio::time::interval(Duration::from_secs(1));
                 interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
                 loop {
                     let snapshot = collector.create_snapshot();
+                    usage.lock().expect("Poisoned lock").observe(&snapshot);
                     match events.lock().expect("Poisoned lock").as_ref() {
                         Some(events) => events.instant_event(snapshot),
                         None => break,
@@ -59,6 +111,7 @@ impl HeartbeatGuard {
             handle,
             collector,
             events,
+            usage,
         }
     }
 }
@@ -68,10 +121,63 @@ impl Drop for HeartbeatGuard {
         let mut maybe_events = self.events.lock().expect("Poisoned lock");
         // Synchronously remove access for sending new heartbeats.
         if let Some(events) = maybe_events.take() {
-            // Send one last snapshot.
-            events.instant_event(self.collector.create_snapshot());
+            // Send one last snapshot, and the summary of the command up to it.
+            let snapshot = self.collector.create_snapshot();
+            let summary = {
+                let mut usage = self.usage.lock().expect("Poisoned lock");
+                usage.observe(&snapshot);
+                usage.summary(
+                    &snapshot,
+                    &events.action_resource_usage().total(),
+                    active_commands::ran_concurrently(events.trace_id()).unwrap_or(true),
+                )
+            };
+            events.instant_event(snapshot);
+            events.instant_event(summary);
         }
         // Cancel the task as well.
         self.handle.abort();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resource_usage_summary() {
+        let mut usage = ResourceUsage::default();
+        let snapshot = |cpu_us: u64, rss: u64| buck2_data::Snapshot {
+            buck2_user_cpu_us: cpu_us,
+            buck2_rss: Some(rss),
+            re_download_bytes: 10 * cpu_us,
+            ..Default::default()
+        };
+        let actions = buck2_data::CommandResourceUsage {
+            user_cpu_us: 400,
+            system_cpu_us: 20,
+            disk_read_bytes: Some(3000),
+            disk_write_bytes: None,
+        };
+
+        usage.observe(&snapshot(100, 5));
+        usage.observe(&snapshot(150, 9));
+        let last = snapshot(300, 7);
+        usage.observe(&last);
+
+        let summary = usage.summary(&last, &actions, false);
+        assert_eq!(Some(200), summary.daemon_user_cpu_us);
+        assert_eq!(400, summary.actions_user_cpu_us);
+        assert_eq!(20, summary.actions_system_cpu_us);
+        assert_eq!(9, summary.peak_daemon_rss_bytes);
+        assert_eq!(Some(3000), summary.disk_read_bytes);
+        assert_eq!(None, summary.disk_write_bytes);
+        assert_eq!(Some(2000), summary.re_download_bytes);
+
+        // Other commands used the daemon too, but their actions are not counted.
+        let summary = usage.summary(&last, &actions, true);
+        assert_eq!(None, summary.daemon_user_cpu_us);
+        assert_eq!(None, summary.re_download_bytes);
+        assert_eq!(400, summary.actions_user_cpu_us);
+    }
+}
@@ -57,6 +57,7 @@ use lsp_server::Message;
 use lsp_types::Range;
 use lsp_types::Url;
 use starlark::docs::Doc;
+use starlark::docs::DocItem;
 use starlark::docs::Location;
 use starlark::errors::EvalMessage;
 use starlark::lsp::server::server_with_connection;
@@ -150,6 +151,9 @@ struct DocsCache {
     global_urls: HashMap<String, LspUrl>,
     /// Mapping of starlark: urls to a synthesized starlark representation.
     native_starlark_files: HashMap<LspUrl, String>,
+    /// Mapping of global names to their documentation. Used to complete and describe the
+    /// attributes of rules.
+    global_docs: HashMap<String, DocItem>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -219,6 +223,7 @@ impl DocsCache {
     ) -> anyhow::Result<Self> {
         let mut global_urls = HashMap::with_capacity(builtin_symbols.len());
         let mut native_starlark_files = HashMap::new();
+        let mut global_docs = HashMap::with_capacity(builtin_symbols.len());
         for doc in builtin_symbols {
             let url = match &doc.id.location {
                 Some(l) => location_lookup(l).await?,
@@ -247,10 +252,12 @@ impl DocsCache {
                 }
                 .into());
             }
+            global_docs.insert(doc.id.name.clone(), doc.item.clone());
         }
         Ok(Self {
             global_urls,
             native_starlark_files,
+            global_docs,
         })
     }
 
@@ -263,6 +270,10 @@ impl DocsCache {
     fn url_for_symbol(&self, symbol: &str) -> Option<&LspUrl> {
         self.global_urls.get(symbol)
     }
+
+    fn docs_for_symbol(&self, symbol: &str) -> Option<&DocItem> {
+        self.global_docs.get(symbol)
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -711,6 +722,23 @@ impl LspContext for BuckLspContext {
                 Ok(docs_cache.url_for_symbol(symbol).cloned())
             }))
     }
+
+    fn get_docs_for_global_symbol(
+        &self,
+        _current_file: &LspUrl,
+        symbol: &str,
+    ) -> anyhow::Result<Option<DocItem>> {
+        let dispatcher = self.server_ctx.events().dupe();
+        self.runtime()
+            .block_on(with_dispatcher_async(dispatcher, async {
+                let docs_cache = self
+                    .with_dice_ctx(|dice_ctx| async {
+                        self.docs_cache_manager.get_cache(dice_ctx).await
+                    })
+                    .await?;
+                Ok(docs_cache.docs_for_symbol(symbol).cloned())
+            }))
+    }
 }
 
 pub(crate) async fn run_lsp_server_command(
@@ -922,6 +950,11 @@ mod test {
             &LspUrl::try_from(Url::parse("file:/usr/local/dir/prelude.bzl")?)?,
             cache.url_for_symbol("prelude_function").unwrap()
         );
+        assert_eq!(
+            Some(&DocItem::Function(Function::default())),
+            cache.docs_for_symbol("prelude_function")
+        );
+        assert_eq!(None, cache.docs_for_symbol("missing_function"));
 
         Ok(())
     }
@@ -7,14 +7,15 @@
  * of this source tree.
  */
 
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::slice;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Context as _;
 use async_trait::async_trait;
-use buck2_build_api::analysis::calculation::profile_analysis;
-use buck2_build_api::analysis::calculation::profile_analysis_recursively;
+use buck2_build_api::analysis::calculation::profile_analyses;
+use buck2_build_api::calculation::load_patterns;
 use buck2_build_api::calculation::Calculation;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::dice::file_ops::HasFileOps;
@@ -22,6 +23,7 @@ use buck2_common::legacy_configs::dice::HasLegacyConfigs;
 use buck2_core::cells::build_file_cell::BuildFileCell;
 use buck2_core::package::Package;
 use buck2_core::pattern::PackageSpec;
+use buck2_core::pattern::ParsedPattern;
 use buck2_core::pattern::TargetPattern;
 use buck2_core::target::TargetLabel;
 use buck2_interpreter::dice::starlark_profiler::StarlarkProfilerConfiguration;
@@ -30,6 +32,7 @@ use buck2_interpreter::starlark_profiler::StarlarkProfileDataAndStats;
 use buck2_interpreter::starlark_profiler::StarlarkProfiler;
 use buck2_interpreter::starlark_profiler::StarlarkProfilerOrInstrumentation;
 use buck2_interpreter_for_build::interpreter::module_internals::ModuleInternals;
+use buck2_node::rule_type::RuleType;
 use buck2_profile::get_profile_response;
 use buck2_profile::starlark_profiler_configuration_from_request;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
@@ -39,44 +42,93 @@ use buck2_server_ctx::pattern::target_platform_from_client_context;
 use buck2_server_ctx::template::run_server_command;
 use buck2_server_ctx::template::ServerCommandTemplate;
 use cli_proto::profile_request::ProfileOpts;
+use cli_proto::profile_response::RuleTypeProfile;
 use cli_proto::target_profile::Action;
 use cli_proto::ClientContext;
 use dice::DiceTransaction;
 use gazebo::prelude::*;
 
+/// Profile the analysis of all the targets matching `patterns`, returning the merged profile and
+/// the totals of each rule type.
 async fn generate_profile_analysis(
     ctx: DiceTransaction,
-    package: Package,
-    spec: PackageSpec<TargetPattern>,
+    patterns: Vec<ParsedPattern<TargetPattern>>,
     global_target_platform: Option<TargetLabel>,
     profile_mode: &StarlarkProfilerConfiguration,
-) -> anyhow::Result<Arc<StarlarkProfileDataAndStats>> {
-    let target = match spec {
-        PackageSpec::Targets(targets) => one(targets).context("Invalid targets"),
-        PackageSpec::All => Err(anyhow::Error::msg("Cannot use a package")),
+) -> anyhow::Result<(Arc<StarlarkProfileDataAndStats>, Vec<RuleTypeProfile>)> {
+    let loaded_patterns = load_patterns(&ctx, patterns).await?;
+    let mut targets = Vec::new();
+    for node in loaded_patterns.iter_loaded_targets() {
+        targets.push(
+            ctx.get_configured_target(node?.label(), global_target_platform.as_ref())
+                .await?,
+        );
     }
-    .context("Did not find exactly one target")?;
 
-    let label = TargetLabel::new(package.dupe(), target);
+    let recursive = match profile_mode {
+        StarlarkProfilerConfiguration::ProfileLastAnalysis(_) => false,
+        StarlarkProfilerConfiguration::ProfileAnalysisRecursively(_) => true,
+        _ => return Err(anyhow::anyhow!("Incorrect profile mode (internal error)")),
+    };
 
-    let configured_target = ctx
-        .get_configured_target(&label, global_target_platform.as_ref())
-        .await?;
+    let profiles = profile_analyses(
+        &ctx,
+        &targets,
+        profile_mode.profile_last_analysis()?,
+        recursive,
+    )
+    .await
+    .context("Analysis failed")?;
+    if profiles.is_empty() {
+        return Err(anyhow::anyhow!("No compatible targets to profile"));
+    }
 
-    match profile_mode {
-        StarlarkProfilerConfiguration::ProfileLastAnalysis(profile_mode) => {
-            profile_analysis(&ctx, &configured_target, profile_mode)
-                .await
-                .context("Analysis failed")
-        }
-        StarlarkProfilerConfiguration::ProfileAnalysisRecursively(_) => {
-            profile_analysis_recursively(&ctx, &configured_target)
-                .await
-                .context("Analysis failed")
-                .map(Arc::new)
-        }
-        _ => Err(anyhow::anyhow!("Incorrect profile mode (internal error)")),
+    let profile_data = match profiles.as_slice() {
+        [(_, profile)] => profile.dupe(),
+        _ => Arc::new(StarlarkProfileDataAndStats::merge(
+            profiles.iter().map(|(_, profile)| &**profile),
+        )?),
+    };
+
+    let rule_type_totals = rule_type_totals(profiles.iter().map(|(rule_type, profile)| {
+        (
+            rule_type,
+            profile.elapsed(),
+            profile.total_retained_bytes() as u64,
+        )
+    }))?;
+    Ok((profile_data, rule_type_totals))
+}
+
+/// Sums up the analysis time and retained memory of each rule type, given those of each target.
+/// The rule types which took the longest come first.
+fn rule_type_totals<'a>(
+    profiles: impl IntoIterator<Item = (&'a RuleType, Duration, u64)>,
+) -> anyhow::Result<Vec<RuleTypeProfile>> {
+    let mut totals: HashMap<&RuleType, (u64, Duration, u64)> = HashMap::new();
+    for (rule_type, profile_elapsed, profile_retained_bytes) in profiles {
+        let (targets, elapsed, retained_bytes) = totals.entry(rule_type).or_default();
+        *targets += 1;
+        *elapsed += profile_elapsed;
+        *retained_bytes += profile_retained_bytes;
     }
+
+    let mut totals: Vec<_> = totals.into_iter().collect();
+    totals.sort_by(|(a_rule_type, a), (b_rule_type, b)| {
+        b.1.cmp(&a.1)
+            .then_with(|| a_rule_type.to_string().cmp(&b_rule_type.to_string()))
+    });
+    totals
+        .into_iter()
+        .map(|(rule_type, (targets, elapsed, retained_bytes))| {
+            Ok(RuleTypeProfile {
+                rule_type: rule_type.to_string(),
+                targets,
+                elapsed: Some(elapsed.try_into()?),
+                retained_bytes,
+            })
+        })
+        .collect()
 }
 
 async fn generate_profile_loading(
@@ -146,10 +198,6 @@ impl ServerCommandTemplate for ProfileServerCommand {
             ProfileOpts::TargetProfile(opts) => {
                 let action = cli_proto::target_profile::Action::from_i32(opts.action)
                     .context("Invalid action")?;
-                let target_pattern = opts
-                    .target_pattern
-                    .as_ref()
-                    .context("Missing target pattern")?;
 
                 let context = self
                     .req
@@ -157,17 +205,20 @@ impl ServerCommandTemplate for ProfileServerCommand {
                     .as_ref()
                     .context("Missing client context")?;
 
-                let profile_data = generate_profile(
+                let (profile_data, rule_types) = generate_profile(
                     server_ctx,
                     ctx,
                     context,
-                    target_pattern,
+                    &opts.target_patterns,
                     action,
                     &profile_mode,
                 )
                 .await?;
 
-                get_profile_response(profile_data, &self.req, output)
+                Ok(cli_proto::ProfileResponse {
+                    rule_types,
+                    ..get_profile_response(profile_data, &self.req, output)?
+                })
             }
             _ => {
                 return Err(anyhow::anyhow!(
@@ -187,10 +238,10 @@ async fn generate_profile(
     server_ctx: &dyn ServerCommandContextTrait,
     ctx: DiceTransaction,
     client_ctx: &ClientContext,
-    pattern: &buck2_data::TargetPattern,
+    patterns: &[buck2_data::TargetPattern],
     action: Action,
     profile_mode: &StarlarkProfilerConfiguration,
-) -> anyhow::Result<Arc<StarlarkProfileDataAndStats>> {
+) -> anyhow::Result<(Arc<StarlarkProfileDataAndStats>, Vec<RuleTypeProfile>)> {
     let cells = ctx.get_cell_resolver().await?;
 
     let global_target_platform =
@@ -198,23 +249,25 @@ async fn generate_profile(
             .await?;
 
     let parsed_patterns = parse_patterns_from_cli_args::<TargetPattern>(
-        slice::from_ref(pattern),
+        patterns,
         &cells,
         &ctx.get_legacy_configs().await?,
         server_ctx.working_dir(),
     )?;
 
-    let resolved_pattern = resolve_patterns(&parsed_patterns, &cells, &ctx.file_ops()).await?;
-
-    let (package, spec) =
-        one(resolved_pattern.specs).context("Did not find exactly one pattern")?;
-
     match action {
         Action::Analysis => {
-            generate_profile_analysis(ctx, package, spec, global_target_platform, profile_mode)
+            generate_profile_analysis(ctx, parsed_patterns, global_target_platform, profile_mode)
                 .await
         }
-        Action::Loading => generate_profile_loading(ctx, package, spec, profile_mode).await,
+        Action::Loading => {
+            let resolved_pattern =
+                resolve_patterns(&parsed_patterns, &cells, &ctx.file_ops()).await?;
+            let (package, spec) =
+                one(resolved_pattern.specs).context("Did not find exactly one pattern")?;
+            let profile_data = generate_profile_loading(ctx, package, spec, profile_mode).await?;
+            Ok((profile_data, Vec::new()))
+        }
     }
 }
 
@@ -226,3 +279,59 @@ fn one<T>(it: impl IntoIterator<Item = T>) -> anyhow::Result<T> {
     }
     Ok(val)
 }
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::bzl::ImportPath;
+    use buck2_node::rule_type::StarlarkRuleType;
+
+    use super::*;
+
+    fn rule_type(name: &str) -> RuleType {
+        RuleType::Starlark(Arc::new(StarlarkRuleType {
+            import_path: ImportPath::unchecked_new("root", "rules", "defs.bzl"),
+            name: name.to_owned(),
+        }))
+    }
+
+    #[test]
+    fn test_rule_type_totals() -> anyhow::Result<()> {
+        let library = rule_type("library");
+        let binary = rule_type("binary");
+        let ms = Duration::from_millis;
+
+        let totals = rule_type_totals([
+            (&library, ms(10), 100),
+            (&binary, ms(30), 1000),
+            (&library, ms(15), 200),
+            (&RuleType::Forward, ms(25), 0),
+        ])?;
+        assert_eq!(
+            vec![
+                RuleTypeProfile {
+                    rule_type: binary.to_string(),
+                    targets: 1,
+                    elapsed: Some(ms(30).try_into()?),
+                    retained_bytes: 1000,
+                },
+                // Ties are broken by name.
+                RuleTypeProfile {
+                    rule_type: "forward".to_owned(),
+                    targets: 1,
+                    elapsed: Some(ms(25).try_into()?),
+                    retained_bytes: 0,
+                },
+                RuleTypeProfile {
+                    rule_type: library.to_string(),
+                    targets: 2,
+                    elapsed: Some(ms(25).try_into()?),
+                    retained_bytes: 300,
+                },
+            ],
+            totals
+        );
+
+        assert_eq!(Vec::<RuleTypeProfile>::new(), rule_type_totals([])?);
+        Ok(())
+    }
+}
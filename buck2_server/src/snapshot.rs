@@ -11,6 +11,7 @@ use std::sync::Arc;
 use std::time::Instant;
 
 use anyhow::Context as _;
+use buck2_build_api::analysis::queue::AnalysisQueue;
 use buck2_common::process_stats::process_stats;
 use buck2_core::io_counters::IoCounterKey;
 use buck2_execute::execute::blocking::BlockingExecutor;
@@ -29,6 +30,7 @@ pub struct SnapshotCollector {
     daemon_start_time: Instant,
     dice: Arc<Dice>,
     materializer: Arc<dyn Materializer>,
+    analysis_queue: Arc<AnalysisQueue>,
 }
 
 impl SnapshotCollector {
@@ -38,6 +40,7 @@ impl SnapshotCollector {
         daemon_start_time: Instant,
         dice: Arc<Dice>,
         materializer: Arc<dyn Materializer>,
+        analysis_queue: Arc<AnalysisQueue>,
     ) -> SnapshotCollector {
         SnapshotCollector {
             re_client_manager,
@@ -45,6 +48,7 @@ impl SnapshotCollector {
             daemon_start_time,
             dice,
             materializer,
+            analysis_queue,
         }
     }
 
@@ -65,6 +69,7 @@ impl SnapshotCollector {
         self.add_io_metrics(&mut snapshot);
         self.add_dice_metrics(&mut snapshot);
         self.add_materializer_metrics(&mut snapshot);
+        self.add_analysis_metrics(&mut snapshot);
         snapshot
     }
 
@@ -163,6 +168,11 @@ impl SnapshotCollector {
             snapshot.deferred_materializer_queue_size = dm.queue_size() as _;
         }
     }
+
+    fn add_analysis_metrics(&self, snapshot: &mut buck2_data::Snapshot) {
+        snapshot.analysis_running = self.analysis_queue.running() as _;
+        snapshot.analysis_queued = self.analysis_queue.queued() as _;
+    }
 }
 
 fn add_system_metrics(snapshot: &mut buck2_data::Snapshot, daemon_start_time: Instant) {
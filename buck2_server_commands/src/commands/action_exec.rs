@@ -0,0 +1,397 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Server side of `buck2 debug action-exec`: prepare a single action so that its command can be
+//! run by hand, outside of a build.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_build_api::actions::impls::run::knobs::HasRunActionKnobs;
+use buck2_build_api::actions::RegisteredAction;
+use buck2_build_api::analysis::calculation::RuleAnalysisCalculation;
+use buck2_build_api::calculation::Calculation;
+use buck2_build_api::query::aquery::evaluator::get_aquery_evaluator;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::pattern::TargetPattern;
+use buck2_core::target::TargetLabel;
+use buck2_execute::artifact::fs::ExecutorFs;
+use buck2_execute::materialize::materializer::HasMaterializer;
+use buck2_execute::path::buck_out_path::BuckOutScratchPath;
+use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::pattern::parse_patterns_from_cli_args;
+use buck2_server_ctx::pattern::target_platform_from_client_context;
+use buck2_server_ctx::template::run_server_command;
+use buck2_server_ctx::template::ServerCommandTemplate;
+use cli_proto::action_exec_response::EnvironmentEntry;
+use cli_proto::ActionExecRequest;
+use cli_proto::ActionExecResponse;
+use dice::DiceTransaction;
+use gazebo::prelude::*;
+use itertools::Itertools;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+enum ActionExecError {
+    #[error("`{0}` must resolve to a single action, but it resolved to {1}")]
+    NotOneAction(String, usize),
+    #[error("`{0}` is not in the output directory of a configured target")]
+    NotAnOutput(String),
+    #[error("Multi-queries (using `%s`) are not supported")]
+    MultiQuery,
+    #[error("Action `{0}` does not run a command")]
+    NoCommand(String),
+}
+
+pub async fn action_exec_command(
+    ctx: Box<dyn ServerCommandContextTrait>,
+    req: ActionExecRequest,
+) -> anyhow::Result<ActionExecResponse> {
+    run_server_command(ActionExecServerCommand { req }, ctx).await
+}
+
+struct ActionExecServerCommand {
+    req: ActionExecRequest,
+}
+
+#[async_trait]
+impl ServerCommandTemplate for ActionExecServerCommand {
+    type StartEvent = buck2_data::ActionExecCommandStart;
+    type EndEvent = buck2_data::ActionExecCommandEnd;
+    type Response = ActionExecResponse;
+
+    async fn command<'v>(
+        &self,
+        server_ctx: &'v dyn ServerCommandContextTrait,
+        ctx: DiceTransaction,
+    ) -> anyhow::Result<Self::Response> {
+        action_exec(server_ctx, ctx, &self.req).await
+    }
+
+    fn is_success(&self, _response: &Self::Response) -> bool {
+        true
+    }
+}
+
+async fn action_exec(
+    server_ctx: &dyn ServerCommandContextTrait,
+    ctx: DiceTransaction,
+    request: &ActionExecRequest,
+) -> anyhow::Result<ActionExecResponse> {
+    let cell_resolver = ctx.get_cell_resolver().await?;
+
+    let global_target_platform = target_platform_from_client_context(
+        request.context.as_ref(),
+        &cell_resolver,
+        server_ctx.working_dir(),
+    )
+    .await?;
+
+    let artifact_fs = ctx.get_artifact_fs().await?;
+
+    let action = if !request.query.is_empty() {
+        let evaluator =
+            get_aquery_evaluator(&ctx, server_ctx.working_dir(), global_target_platform).await?;
+
+        let actions = match evaluator.eval_query(&request.query, &[]).await? {
+            QueryEvaluationResult::Single(value) => value.try_into_targets()?,
+            QueryEvaluationResult::Multiple(_) => return Err(ActionExecError::MultiQuery.into()),
+        };
+
+        match actions.iter().exactly_one() {
+            Ok(node) => node.action().dupe(),
+            Err(_) => {
+                return Err(
+                    ActionExecError::NotOneAction(request.query.clone(), actions.len()).into(),
+                );
+            }
+        }
+    } else if !request.category.is_empty() {
+        let actions = target_actions(server_ctx, &ctx, &request.action, global_target_platform)
+            .await?
+            .into_iter()
+            .filter(|action| {
+                action.category().as_str() == request.category
+                    && action.identifier() == request.identifier.as_deref()
+            })
+            .collect::<Vec<_>>();
+
+        let name = match &request.identifier {
+            Some(identifier) => format!("{} {} {}", request.action, request.category, identifier),
+            None => format!("{} {}", request.action, request.category),
+        };
+        exactly_one_action(name, actions)?
+    } else {
+        let path = if Path::new(&request.action).is_absolute() {
+            artifact_fs
+                .fs()
+                .relativize(AbsNormPath::new(&request.action)?)?
+                .into_owned()
+        } else {
+            server_ctx
+                .working_dir()
+                .join_normalized(request.action.as_str())?
+        };
+
+        let gen = artifact_fs
+            .buck_out_path_resolver()
+            .root()
+            .join(ForwardRelativePath::unchecked_new("gen"));
+        let owners = path
+            .strip_prefix(&gen)
+            .map(output_owners)
+            .unwrap_or_default();
+        if owners.is_empty() {
+            return Err(ActionExecError::NotAnOutput(request.action.clone()).into());
+        }
+
+        // Only the owner the path is actually an output of has an action producing it, so the
+        // other candidates, which usually don't even exist, are only reported if none does.
+        let mut actions = Vec::new();
+        let mut first_error = None;
+        for owner in &owners {
+            match target_actions(server_ctx, &ctx, owner, global_target_platform.dupe()).await {
+                Ok(owner_actions) => actions.extend(owner_actions.into_iter().filter(|action| {
+                    action.outputs().map_or(false, |outputs| {
+                        outputs.iter().any(|output| {
+                            path.starts_with(artifact_fs.resolve_build(output.get_path()))
+                        })
+                    })
+                })),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if let (true, Some(e)) = (actions.is_empty(), first_error) {
+            return Err(e);
+        }
+
+        exactly_one_action(request.action.clone(), actions)?
+    };
+    let action_name = format!("{} {}", action.owner(), action.name());
+
+    let executor_fs = ExecutorFs::new(&artifact_fs, action.execution_config().path_separator);
+
+    let scrubbed_local_env = ctx
+        .per_transaction_data()
+        .get_run_action_knobs()
+        .scrubbed_local_env;
+    let (command, env_inheritance) = action
+        .action()
+        .expanded_command_line(&executor_fs, action.execution_config(), scrubbed_local_env)?
+        .ok_or_else(|| ActionExecError::NoCommand(action_name.clone()))?;
+
+    // Build the inputs, then make sure they are actually on disk, since the deferred
+    // materializer would otherwise only have declared them.
+    let mut input_paths = Vec::new();
+    for input in action.inputs()?.iter() {
+        let values = ctx
+            .ensure_artifact_group(input)
+            .await
+            .with_context(|| format!("Failed to build input `{}`", input))?;
+        for (artifact, _) in values.iter() {
+            if !artifact.is_source() {
+                input_paths.push(artifact_fs.resolve(artifact.get_path())?);
+            }
+        }
+    }
+    ctx.per_transaction_data()
+        .get_materializer()
+        .ensure_materialized(input_paths)
+        .await
+        .context("Failed to materialize inputs")?;
+
+    // The command expects the directories its outputs go into to exist.
+    for output in action.outputs()?.iter() {
+        let path = artifact_fs.resolve_build(output.get_path());
+        if let Some(parent) = path.parent() {
+            fs_util::create_dir_all(artifact_fs.fs().resolve(parent))?;
+        }
+    }
+
+    let scratch_dir =
+        artifact_fs
+            .buck_out_path_resolver()
+            .resolve_scratch(&BuckOutScratchPath::new(
+                action.owner().dupe(),
+                action.category(),
+                action.identifier(),
+            )?);
+    artifact_fs.fs().remove_path_recursive(&scratch_dir)?;
+    fs_util::create_dir_all(artifact_fs.fs().resolve(&scratch_dir))?;
+
+    // Like in the local executor, declared variables take precedence over inherited ones.
+    let mut env = command.env;
+    for (key, value) in env_inheritance.values() {
+        if !env.contains_key(key) {
+            env.insert(key.to_owned(), value.to_string_lossy().into_owned());
+        }
+    }
+    let mut env = env
+        .into_iter()
+        .map(|(key, value)| EnvironmentEntry { key, value })
+        .collect::<Vec<_>>();
+    env.sort_by(|a, b| a.key.cmp(&b.key));
+
+    Ok(ActionExecResponse {
+        action: action_name,
+        argv: command.cli,
+        env,
+        working_dir: artifact_fs.fs().root().to_string(),
+        scratch_dir: artifact_fs.fs().resolve(&scratch_dir).to_string(),
+        clear_env: env_inheritance.clear(),
+        unset_env: env_inheritance
+            .exclusions()
+            .map(|key| key.to_owned())
+            .collect(),
+    })
+}
+
+/// The actions registered by the analysis of a target, configured for the target platform.
+async fn target_actions(
+    server_ctx: &dyn ServerCommandContextTrait,
+    ctx: &DiceTransaction,
+    target: &str,
+    global_target_platform: Option<TargetLabel>,
+) -> anyhow::Result<Vec<Arc<RegisteredAction>>> {
+    let label = parse_patterns_from_cli_args::<TargetPattern>(
+        &[buck2_data::TargetPattern {
+            value: target.to_owned(),
+        }],
+        &ctx.get_cell_resolver().await?,
+        &ctx.get_legacy_configs().await?,
+        server_ctx.working_dir(),
+    )?
+    .into_iter()
+    .next()
+    .context("Parsing patterns returned nothing")?
+    .as_target_label(target)?;
+
+    let label = ctx
+        .get_configured_target(&label, global_target_platform.as_ref())
+        .await?;
+
+    let analysis = ctx
+        .get_analysis_result(&label)
+        .await?
+        .require_compatible()?;
+
+    Ok(analysis.actions().duped().collect())
+}
+
+fn exactly_one_action(
+    name: String,
+    actions: Vec<Arc<RegisteredAction>>,
+) -> anyhow::Result<Arc<RegisteredAction>> {
+    let len = actions.len();
+    actions
+        .into_iter()
+        .exactly_one()
+        .map_err(|_| ActionExecError::NotOneAction(name, len).into())
+}
+
+/// The targets that may own an output, as patterns, given the path of the output relative to
+/// `buck-out/v2/gen`, i.e. `<cell>/<configuration hash>/<package>/__<name>__[<action key>__]/..`.
+/// Package directories and target names can themselves look like `__<name>__`, or contain `__`,
+/// so every way of reading the path is returned.
+fn output_owners(path: &ForwardRelativePath) -> Vec<String> {
+    let mut parts = path.iter();
+    let cell = match parts.next() {
+        Some(cell) => cell,
+        None => return Vec::new(),
+    };
+    // The configuration is that of the target platform, so its hash is only checked once the
+    // outputs of the target are resolved.
+    if parts.next().is_none() {
+        return Vec::new();
+    }
+
+    let mut owners = Vec::new();
+    let mut package = Vec::new();
+    for part in parts {
+        let part = part.as_str();
+        if let Some(inner) = part
+            .strip_prefix("__")
+            .and_then(|p| p.strip_suffix("__"))
+            .filter(|inner| !inner.is_empty())
+        {
+            // Outputs of a given action, rather than of the whole target, go in
+            // `__<name>__<action key>__`.
+            let names = inner
+                .char_indices()
+                .filter(|(i, _)| *i > 0 && inner[*i..].starts_with("__"))
+                .map(|(i, _)| &inner[..i])
+                .chain(std::iter::once(inner));
+            for name in names {
+                let owner = format!("{}//{}:{}", cell, package.join("/"), name);
+                if !owners.contains(&owner) {
+                    owners.push(owner);
+                }
+            }
+        }
+        package.push(part);
+    }
+    owners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_owners() {
+        let owners = |path| output_owners(ForwardRelativePath::new(path).unwrap());
+
+        assert_eq!(
+            vec!["root//foo/bar:baz"],
+            owners("root/0123456789abcdef/foo/bar/__baz__/baz.o")
+        );
+        assert_eq!(
+            vec!["root//foo:baz"],
+            owners("root/0123456789abcdef-fedcba9876543210/foo/__baz__/dir/baz.o")
+        );
+        assert_eq!(
+            vec!["cell//:baz", "cell//:baz__action_key"],
+            owners("cell/0123456789abcdef/__baz__action_key__/baz.o")
+        );
+        assert_eq!(
+            Vec::<String>::new(),
+            owners("root/0123456789abcdef/foo/bar")
+        );
+        assert_eq!(Vec::<String>::new(), owners("root"));
+    }
+
+    #[test]
+    fn test_output_owners_ambiguous() {
+        let owners = |path| output_owners(ForwardRelativePath::new(path).unwrap());
+
+        // A package directory named like an output directory.
+        assert_eq!(
+            vec!["root//:pkg", "root//__pkg__:baz"],
+            owners("root/0123456789abcdef/__pkg__/__baz__/baz.o")
+        );
+        // Target names containing `__`, or themselves named `__<name>__`.
+        assert_eq!(
+            vec!["root//foo:a", "root//foo:a__b", "root//foo:a__b__key"],
+            owners("root/0123456789abcdef/foo/__a__b__key__/out")
+        );
+        assert_eq!(
+            vec!["root//foo:__x", "root//foo:__x__"],
+            owners("root/0123456789abcdef/foo/____x____/out")
+        );
+    }
+}
@@ -0,0 +1,294 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Symlinks to the default outputs of the targets built by the last build, at stable
+//! project-relative locations: `buck-out/<isolation dir>/latest/<cell>/<package>/<target>`, e.g.
+//! `buck-out/v2/latest`. Enabled with `buck2.create_latest_links = true`.
+//!
+//! A target with a single default output gets a symlink to it, a target with several gets a
+//! directory of symlinks named after the short paths of its outputs. Links that would clash, e.g.
+//! a target built in two configurations, or `//foo:bar` with several outputs and `//foo/bar:baz`,
+//! are not created, with a warning.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+
+use anyhow::Context as _;
+use buck2_build_api::build::BuildProviderType;
+use buck2_build_api::build::ProviderArtifacts;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::provider::label::ConfiguredProvidersLabel;
+use buck2_execute::artifact::fs::ArtifactFs;
+use indexmap::IndexMap;
+use tracing::warn;
+
+use crate::commands::build::unhashed_outputs::create_unhashed_link;
+
+/// Point the links at the outputs of `target_artifacts`, and remove those of previous builds.
+/// Returns how many links there are.
+pub(crate) fn create_latest_links(
+    target_artifacts: Vec<(ConfiguredProvidersLabel, ProviderArtifacts)>,
+    artifact_fs: &ArtifactFs,
+    fs: &ProjectRoot,
+) -> anyhow::Result<u64> {
+    let latest_root = fs.resolve(
+        &artifact_fs
+            .buck_out_path_resolver()
+            .root()
+            .join(ForwardRelativePath::unchecked_new("latest")),
+    );
+
+    let mut candidates: IndexMap<ForwardRelativePathBuf, HashSet<AbsNormPathBuf>> = IndexMap::new();
+    for (label, provider_artifacts) in target_artifacts {
+        if !matches!(provider_artifacts.provider_type, BuildProviderType::Default) {
+            continue;
+        }
+
+        let link_path = match latest_link_path(&label) {
+            Ok(path) => path,
+            // Flavors and the like don't always make valid paths, those targets just don't get
+            // a link.
+            Err(_) => continue,
+        };
+
+        let values = provider_artifacts.values.iter().collect::<Vec<_>>();
+        for (artifact, _) in &values {
+            let path = fs.resolve(&artifact_fs.resolve(artifact.get_path())?);
+            let link = if values.len() == 1 {
+                link_path.clone()
+            } else {
+                let short_path: ForwardRelativePathBuf =
+                    artifact.get_path().with_short_path(|p| p.to_buf());
+                link_path.join(short_path)
+            };
+            candidates.entry(link).or_default().insert(path);
+        }
+    }
+
+    update_links(&latest_root, &without_conflicts(candidates))
+}
+
+fn latest_link_path(label: &ConfiguredProvidersLabel) -> anyhow::Result<ForwardRelativePathBuf> {
+    let target = label.target();
+    Ok(ForwardRelativePath::new(target.pkg().cell_name().as_str())?
+        .join(target.pkg().cell_relative_path())
+        .join(ForwardRelativePath::new(&format!(
+            "{}{}",
+            target.name(),
+            label.name()
+        ))?))
+}
+
+/// The links to create, leaving out those that would point to several outputs, and those that
+/// would be in the way of another: a link can't also be the directory of other links.
+fn without_conflicts(
+    candidates: IndexMap<ForwardRelativePathBuf, HashSet<AbsNormPathBuf>>,
+) -> BTreeMap<ForwardRelativePathBuf, AbsNormPathBuf> {
+    let parents = candidates
+        .keys()
+        .flat_map(|link| parent_dirs(link))
+        .map(|dir| dir.to_buf())
+        .collect::<HashSet<_>>();
+
+    let mut links = BTreeMap::new();
+    for (link, targets) in candidates {
+        if targets.len() != 1 {
+            warn!(
+                "Not creating the latest link `{}`, as it would point to several outputs: {:?}",
+                link, targets
+            );
+        } else if parents.contains(&link) {
+            warn!(
+                "Not creating the latest link `{}`, as other latest links are in that directory",
+                link
+            );
+        } else {
+            links.insert(link, targets.into_iter().next().unwrap());
+        }
+    }
+    links
+}
+
+fn parent_dirs(link: &ForwardRelativePath) -> impl Iterator<Item = &ForwardRelativePath> {
+    std::iter::successors(link.parent(), |dir| dir.parent())
+}
+
+/// Make `latest_root` contain exactly `links`, leaving alone those already in place, so that they
+/// don't disappear while the build updates them.
+fn update_links(
+    latest_root: &AbsNormPath,
+    links: &BTreeMap<ForwardRelativePathBuf, AbsNormPathBuf>,
+) -> anyhow::Result<u64> {
+    let dirs = links
+        .keys()
+        .flat_map(|link| parent_dirs(link))
+        .map(|dir| latest_root.join(dir))
+        .collect::<BTreeSet<_>>();
+    let links = links
+        .iter()
+        .map(|(link, target)| (latest_root.join(link), target))
+        .collect::<BTreeMap<_, _>>();
+
+    if fs_util::try_exists(latest_root)? {
+        remove_stale(latest_root, &dirs, &links)
+            .context("Error removing the latest links of the previous build")?;
+    }
+
+    let latest_root = latest_root.to_buf();
+    for (link, target) in &links {
+        match fs_util::read_link(link) {
+            Ok(existing) if existing == target.as_path() => {}
+            _ => create_unhashed_link(link, target, &latest_root)?,
+        }
+    }
+    Ok(links.len() as u64)
+}
+
+/// Remove what's in `dir` but is neither one of `links` nor one of the `dirs` containing them.
+fn remove_stale(
+    dir: &AbsNormPath,
+    dirs: &BTreeSet<AbsNormPathBuf>,
+    links: &BTreeMap<AbsNormPathBuf, &AbsNormPathBuf>,
+) -> anyhow::Result<()> {
+    for entry in fs_util::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if links.contains_key(&path) {
+            // Replaced if it isn't the right link.
+            continue;
+        }
+        if file_type.is_dir() && dirs.contains(&path) {
+            remove_stale(&path, dirs, links)?;
+        } else if file_type.is_dir() {
+            fs_util::remove_dir_all(&path)?;
+        } else {
+            fs_util::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::fs::project::ProjectRootTemp;
+
+    use super::*;
+
+    fn candidates(
+        links: &[(&str, &[&str])],
+    ) -> IndexMap<ForwardRelativePathBuf, HashSet<AbsNormPathBuf>> {
+        links
+            .iter()
+            .map(|(link, targets)| {
+                (
+                    ForwardRelativePathBuf::unchecked_new((*link).to_owned()),
+                    targets
+                        .iter()
+                        .map(|t| AbsNormPathBuf::from((*t).to_owned()).unwrap())
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_without_conflicts() {
+        let links = without_conflicts(candidates(&[
+            ("root/foo/bar", &["/out/bar"]),
+            // Built in two configurations.
+            ("root/foo/baz", &["/out/cfg1/baz", "/out/cfg2/baz"]),
+            // Several outputs of `//foo:qux`, and `//foo/qux:quux`.
+            ("root/foo/qux/a", &["/out/qux/a"]),
+            ("root/foo/qux/b", &["/out/qux/b"]),
+            ("root/foo/qux", &["/out/quux"]),
+        ]));
+        assert_eq!(
+            vec!["root/foo/bar", "root/foo/qux/a", "root/foo/qux/b"],
+            links.keys().map(|l| l.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            AbsNormPathBuf::from("/out/bar".to_owned()).unwrap(),
+            links[ForwardRelativePath::unchecked_new("root/foo/bar")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_update_links() -> anyhow::Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let fs = ProjectRootTemp::new()?;
+        let root = fs.path().root();
+        let latest_root = root.join(ForwardRelativePath::unchecked_new("latest"));
+        for out in ["a", "b", "c"] {
+            fs_util::write(root.join(ForwardRelativePath::unchecked_new(out)), out)?;
+        }
+        let links = |links: &[(&str, &str)]| {
+            links
+                .iter()
+                .map(|(link, out)| {
+                    (
+                        ForwardRelativePathBuf::unchecked_new((*link).to_owned()),
+                        root.join(ForwardRelativePath::unchecked_new(out)),
+                    )
+                })
+                .collect::<BTreeMap<_, _>>()
+        };
+        let read = |link: &str| {
+            fs_util::read_to_string(latest_root.join(ForwardRelativePath::unchecked_new(link)))
+        };
+
+        assert_eq!(
+            3,
+            update_links(
+                &latest_root,
+                &links(&[("cell/x", "a"), ("cell/y", "b"), ("cell/pkg/z", "c")])
+            )?
+        );
+        assert_eq!("a", read("cell/x")?);
+        assert_eq!("c", read("cell/pkg/z")?);
+        let inode = |link: &str| {
+            anyhow::Ok(
+                fs_util::symlink_metadata(
+                    latest_root.join(ForwardRelativePath::unchecked_new(link)),
+                )?
+                .ino(),
+            )
+        };
+        let unchanged = inode("cell/x")?;
+
+        // `x` is kept, `y` now points elsewhere, `pkg` is gone.
+        assert_eq!(
+            2,
+            update_links(&latest_root, &links(&[("cell/x", "a"), ("cell/y", "c")]))?
+        );
+        assert_eq!("a", read("cell/x")?);
+        assert_eq!("c", read("cell/y")?);
+        assert_eq!(unchanged, inode("cell/x")?);
+        assert!(!fs_util::try_exists(
+            latest_root.join(ForwardRelativePath::unchecked_new("cell/pkg"))
+        )?);
+
+        // A link replaced by a directory of links.
+        assert_eq!(1, update_links(&latest_root, &links(&[("cell/x/a", "a")]))?);
+        assert_eq!("a", read("cell/x/a")?);
+        assert!(!fs_util::try_exists(
+            latest_root.join(ForwardRelativePath::unchecked_new("cell/y"))
+        )?);
+
+        Ok(())
+    }
+}
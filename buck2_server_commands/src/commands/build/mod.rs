@@ -8,7 +8,6 @@
  */
 
 use std::collections::BTreeMap;
-use std::future;
 use std::io::BufWriter;
 use std::sync::Arc;
 
@@ -21,17 +20,18 @@ use buck2_build_api::build::HasCreateUnhashedSymlinkLock;
 use buck2_build_api::build::MaterializationContext;
 use buck2_build_api::build::ProvidersToBuild;
 use buck2_build_api::calculation::Calculation;
+use buck2_build_api::nodes::calculation::resolve_alias_patterns;
 use buck2_build_api::query::cquery::evaluator::universe_from_literals;
 use buck2_build_api::query::cquery::universe::CqueryUniverse;
 use buck2_build_api::query::dice::get_dice_query_delegate;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::dice::file_ops::HasFileOps;
 use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+use buck2_common::pattern::resolve::ExcludedTargets;
 use buck2_common::pattern::resolve::ResolvedPattern;
 use buck2_core::fs::fs_util;
 use buck2_core::package::Package;
 use buck2_core::pattern::PackageSpec;
-use buck2_core::pattern::ParsedPattern;
 use buck2_core::pattern::ProvidersPattern;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_core::provider::label::ProvidersLabel;
@@ -39,11 +39,13 @@ use buck2_core::target::TargetLabel;
 use buck2_events::dispatch::span_async;
 use buck2_execute::materialize::materializer::HasMaterializer;
 use buck2_interpreter_for_build::interpreter::calculation::InterpreterCalculation;
+use buck2_node::compatibility::MaybeCompatible;
 use buck2_node::nodes::eval_result::EvaluationResult;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
-use buck2_server_ctx::pattern::parse_patterns_from_cli_args;
-use buck2_server_ctx::pattern::resolve_patterns;
+use buck2_server_ctx::pattern::parse_patterns_with_exclusions_from_cli_args;
+use buck2_server_ctx::pattern::resolve_patterns_with_exclusions;
 use buck2_server_ctx::pattern::target_platform_from_client_context;
+use buck2_server_ctx::pattern::PatternsWithExclusions;
 use buck2_server_ctx::template::run_server_command;
 use buck2_server_ctx::template::ServerCommandTemplate;
 use cli_proto::build_request::build_providers::Action as BuildProviderAction;
@@ -57,6 +59,7 @@ use futures::stream::TryStreamExt;
 use gazebo::prelude::*;
 use itertools::Itertools;
 
+use crate::commands::build::latest_outputs::create_latest_links;
 use crate::commands::build::results::build_report::BuildReportCollector;
 use crate::commands::build::results::providers::ProvidersPrinter;
 use crate::commands::build::results::result_report::ResultReporter;
@@ -65,6 +68,7 @@ use crate::commands::build::results::BuildOwner;
 use crate::commands::build::results::BuildResultCollector;
 use crate::commands::build::unhashed_outputs::create_unhashed_outputs;
 
+mod latest_outputs;
 mod results;
 mod unhashed_outputs;
 
@@ -134,20 +138,27 @@ async fn build(
         .parse_legacy_config_property(cell_resolver.root_cell(), "buck2", "create_unhashed_links")
         .await?;
 
-    let parsed_patterns: Vec<ParsedPattern<ProvidersPattern>> = parse_patterns_from_cli_args(
-        &request.target_patterns,
-        &cell_resolver,
-        &ctx.get_legacy_configs().await?,
-        cwd,
-    )?;
-    server_ctx.log_target_pattern(&parsed_patterns);
+    let should_create_latest_links = ctx
+        .parse_legacy_config_property(cell_resolver.root_cell(), "buck2", "create_latest_links")
+        .await?
+        .unwrap_or(false);
+
+    let parsed_patterns: PatternsWithExclusions<ProvidersPattern> =
+        parse_patterns_with_exclusions_from_cli_args(
+            &request.target_patterns,
+            &cell_resolver,
+            &ctx.get_legacy_configs().await?,
+            cwd,
+        )?;
+    server_ctx.log_target_pattern(&parsed_patterns.patterns);
 
     ctx.per_transaction_data()
         .get_materializer()
         .log_materializer_state(server_ctx.events());
 
-    let resolved_pattern: ResolvedPattern<ProvidersPattern> =
-        resolve_patterns(&parsed_patterns, &cell_resolver, &ctx.file_ops()).await?;
+    let (resolved_pattern, excluded_targets): (ResolvedPattern<ProvidersPattern>, _) =
+        resolve_patterns_with_exclusions(&parsed_patterns, &cell_resolver, &ctx.file_ops()).await?;
+    let resolved_pattern = resolve_alias_patterns(&ctx, resolved_pattern).await?;
 
     let target_resolution_config: TargetResolutionConfig = if request.target_universe.is_empty() {
         TargetResolutionConfig::Default(global_target_platform)
@@ -221,21 +232,36 @@ async fn build(
         ConvertMaterializationContext::from(final_artifact_materializations);
 
     let mut provider_artifacts = Vec::new();
+    let mut target_artifacts = Vec::new();
     for (k, v) in build_targets(
         &ctx,
         resolved_pattern,
+        Arc::new(excluded_targets),
         target_resolution_config,
         build_providers,
         &materialization_context,
     )
     .await?
     {
+        let v = match v {
+            MaybeCompatible::Compatible(v) => v,
+            MaybeCompatible::Incompatible(reason) => {
+                result_collectors.collect_skipped(&k, &reason);
+                continue;
+            }
+        };
         result_collectors.collect_result(&BuildOwner::Target(&k), &v);
         let mut outputs = v.outputs.into_iter().filter_map(|output| match output {
             Ok(output) => Some(output),
             _ => None,
         });
-        provider_artifacts.extend(&mut outputs);
+        if should_create_latest_links {
+            let outputs = outputs.collect::<Vec<_>>();
+            target_artifacts.extend(outputs.iter().map(|output| (k.clone(), output.clone())));
+            provider_artifacts.extend(outputs);
+        } else {
+            provider_artifacts.extend(&mut outputs);
+        }
     }
 
     if should_create_unhashed_links.unwrap_or(false) {
@@ -255,9 +281,18 @@ async fn build(
         .await?;
     }
 
+    if should_create_latest_links {
+        let lock = ctx
+            .per_transaction_data()
+            .get_create_unhashed_symlink_lock();
+        let _guard = lock.lock().await;
+        create_latest_links(target_artifacts, &artifact_fs, fs)
+            .context("Error creating `buck-out/<isolation dir>/latest` links")?;
+    }
+
     let mut serialized_build_report = None;
     if let Some(build_report_collector) = build_report_collector {
-        let report = build_report_collector.into_report();
+        let report = build_report_collector.into_report(server_ctx.events().warnings());
         if !build_opts.unstable_build_report_filename.is_empty() {
             let file = fs_util::create_file(
                 fs.resolve(cwd)
@@ -302,15 +337,17 @@ async fn build(
 async fn build_targets(
     ctx: &DiceComputations,
     spec: ResolvedPattern<ProvidersPattern>,
+    excluded_targets: Arc<ExcludedTargets>,
     target_resolution_config: TargetResolutionConfig,
     build_providers: Arc<BuildProviders>,
     materialization_context: &MaterializationContext,
-) -> anyhow::Result<BTreeMap<ConfiguredProvidersLabel, BuildTargetResult>> {
+) -> anyhow::Result<BTreeMap<ConfiguredProvidersLabel, MaybeCompatible<BuildTargetResult>>> {
     match target_resolution_config {
         TargetResolutionConfig::Default(global_target_platform) => {
             build_targets_with_global_target_platform(
                 ctx,
                 spec,
+                excluded_targets,
                 global_target_platform,
                 build_providers,
                 materialization_context,
@@ -321,6 +358,7 @@ async fn build_targets(
             build_targets_in_universe(
                 ctx,
                 spec,
+                &excluded_targets,
                 universe,
                 build_providers,
                 materialization_context,
@@ -333,19 +371,24 @@ async fn build_targets(
 async fn build_targets_in_universe(
     ctx: &DiceComputations,
     spec: ResolvedPattern<ProvidersPattern>,
+    excluded_targets: &ExcludedTargets,
     universe: CqueryUniverse,
     build_providers: Arc<BuildProviders>,
     materialization_context: &MaterializationContext,
-) -> anyhow::Result<BTreeMap<ConfiguredProvidersLabel, BuildTargetResult>> {
+) -> anyhow::Result<BTreeMap<ConfiguredProvidersLabel, MaybeCompatible<BuildTargetResult>>> {
     let providers_to_build = build_providers_to_providers_to_build(&build_providers);
     let provider_labels = universe.get_provider_labels(&spec);
     let futs: FuturesUnordered<_> = provider_labels
         .into_iter()
+        .filter(|p| {
+            let target = p.target().unconfigured();
+            !excluded_targets.is_excluded(target.pkg(), target.name())
+        })
         .map(|p| {
             let materialization_context = materialization_context.dupe();
             let providers_to_build = providers_to_build.clone();
             ctx.temporary_spawn(|ctx| async move {
-                let option = build::build_configured_label(
+                let result = build::build_configured_label(
                     &ctx,
                     &materialization_context,
                     &p,
@@ -353,26 +396,26 @@ async fn build_targets_in_universe(
                     false,
                 )
                 .await?;
-                Ok(option.map(|r| (p, r)))
+                anyhow::Ok((p, result))
             })
         })
         .collect();
-    futs.try_filter_map(|o| future::ready(Ok(o)))
-        .try_collect()
-        .await
+    futs.try_collect().await
 }
 
 async fn build_targets_with_global_target_platform(
     ctx: &DiceComputations,
     spec: ResolvedPattern<ProvidersPattern>,
+    excluded_targets: Arc<ExcludedTargets>,
     global_target_platform: Option<TargetLabel>,
     build_providers: Arc<BuildProviders>,
     materialization_context: &MaterializationContext,
-) -> anyhow::Result<BTreeMap<ConfiguredProvidersLabel, BuildTargetResult>> {
+) -> anyhow::Result<BTreeMap<ConfiguredProvidersLabel, MaybeCompatible<BuildTargetResult>>> {
     let futs: FuturesUnordered<_> = spec
         .specs
         .into_iter()
         .map(|(package, spec)| {
+            let excluded_targets = excluded_targets.dupe();
             let build_providers = build_providers.dupe();
             let global_target_platform = global_target_platform.dupe();
             let materialization_context = materialization_context.dupe();
@@ -382,6 +425,7 @@ async fn build_targets_with_global_target_platform(
                     &ctx,
                     package,
                     spec,
+                    &excluded_targets,
                     global_target_platform,
                     res,
                     build_providers,
@@ -427,16 +471,18 @@ async fn build_targets_for_spec(
     ctx: &DiceComputations,
     package: Package,
     spec: PackageSpec<ProvidersPattern>,
+    excluded_targets: &ExcludedTargets,
     global_target_platform: Option<TargetLabel>,
     res: Arc<EvaluationResult>,
     build_providers: Arc<BuildProviders>,
     materialization_context: &MaterializationContext,
-) -> anyhow::Result<BTreeMap<ConfiguredProvidersLabel, BuildTargetResult>> {
+) -> anyhow::Result<BTreeMap<ConfiguredProvidersLabel, MaybeCompatible<BuildTargetResult>>> {
     let available_targets = res.targets();
 
     let todo_targets: Vec<TargetBuildSpec> = match spec {
         PackageSpec::All => available_targets
             .keys()
+            .filter(|t| !excluded_targets.is_excluded(&package, t))
             .duped()
             .map(|t| TargetBuildSpec {
                 target: ProvidersLabel::default_for(TargetLabel::new(package.dupe(), t)),
@@ -476,9 +522,7 @@ async fn build_targets_for_spec(
         })
         .collect();
 
-    futs.try_filter_map(|o| future::ready(Ok(o)))
-        .try_collect()
-        .await
+    futs.try_collect().await
 }
 
 async fn build_target(
@@ -486,7 +530,7 @@ async fn build_target(
     spec: TargetBuildSpec,
     providers_to_build: &ProvidersToBuild,
     materialization_context: &MaterializationContext,
-) -> anyhow::Result<Option<(ConfiguredProvidersLabel, BuildTargetResult)>> {
+) -> anyhow::Result<(ConfiguredProvidersLabel, MaybeCompatible<BuildTargetResult>)> {
     let providers_label = ctx
         .get_configured_target(&spec.target, spec.global_target_platform.as_ref())
         .await?;
@@ -500,5 +544,5 @@ async fn build_target(
     )
     .await?;
 
-    Ok(result.map(|r| (providers_label, r)))
+    Ok((providers_label, result))
 }
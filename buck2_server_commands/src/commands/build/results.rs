@@ -12,6 +12,7 @@
 use buck2_build_api::build::BuildTargetResult;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_execute::bxl::types::BxlFunctionLabel;
+use buck2_node::compatibility::IncompatiblePlatformReason;
 
 pub(crate) enum BuildOwner<'a> {
     Target(&'a ConfiguredProvidersLabel),
@@ -21,6 +22,14 @@ pub(crate) enum BuildOwner<'a> {
 /// Collects the results of the build and processes it
 pub(crate) trait BuildResultCollector: Send {
     fn collect_result(&mut self, label: &BuildOwner, result: &BuildTargetResult);
+
+    /// A target that was skipped because it's incompatible with its platform.
+    fn collect_skipped(
+        &mut self,
+        _label: &ConfiguredProvidersLabel,
+        _reason: &IncompatiblePlatformReason,
+    ) {
+    }
 }
 
 impl BuildResultCollector for Vec<&mut dyn BuildResultCollector> {
@@ -29,6 +38,16 @@ impl BuildResultCollector for Vec<&mut dyn BuildResultCollector> {
             collector.collect_result(label, result);
         }
     }
+
+    fn collect_skipped(
+        &mut self,
+        label: &ConfiguredProvidersLabel,
+        reason: &IncompatiblePlatformReason,
+    ) {
+        for collector in self {
+            collector.collect_skipped(label, reason);
+        }
+    }
 }
 
 pub mod result_report {
@@ -185,11 +204,16 @@ pub mod build_report {
     use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
     use buck2_core::fs::project::ProjectRelativePathBuf;
     use buck2_core::fs::project::ProjectRoot;
+    use buck2_core::provider::label::ConfiguredProvidersLabel;
     use buck2_core::provider::label::ProvidersName;
     use buck2_core::target::TargetLabel;
     use buck2_events::trace::TraceId;
+    use buck2_events::warnings::category_name;
+    use buck2_events::warnings::CommandWarnings;
     use buck2_execute::artifact::fs::ArtifactFs;
+    use buck2_execute::artifact_value::ArtifactValue;
     use buck2_execute::bxl::types::BxlFunctionLabel;
+    use buck2_node::compatibility::IncompatiblePlatformReason;
     use derivative::Derivative;
     use gazebo::prelude::*;
     use indexmap::IndexSet;
@@ -223,6 +247,33 @@ pub mod build_report {
         failures: HashMap<EntryLabel, ProjectRelativePathBuf>,
         project_root: AbsNormPathBuf,
         truncated: bool,
+        /// warnings emitted while building, deduplicated
+        warnings: Vec<BuildReportWarning>,
+        /// targets matched by a pattern that were not built because they are incompatible with
+        /// their platform
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        skipped_incompatible: Vec<BuildReportSkipped>,
+    }
+
+    #[derive(Debug, Serialize, Eq, PartialEq, Ord, PartialOrd)]
+    struct BuildReportSkipped {
+        /// the configured target that was skipped
+        target: String,
+        /// the target that is incompatible: the skipped target itself, or the dependency that
+        /// made it incompatible
+        incompatible_target: String,
+        /// the constraint the incompatible target doesn't satisfy
+        unsatisfied_constraint: String,
+    }
+
+    #[derive(Debug, Serialize)]
+    struct BuildReportWarning {
+        category: &'static str,
+        /// what the warning is about, e.g. a package or a target, may be empty
+        location: String,
+        message: String,
+        /// how many times this warning was emitted
+        count: u64,
     }
 
     #[derive(Default, Debug, Serialize)]
@@ -236,6 +287,35 @@ pub mod build_report {
         /// the hidden, implicitly built outputs of the subtarget. There are multiple outputs
         /// per subtarget
         other_outputs: HashMap<String, Vec<ProjectRelativePathBuf>>,
+        /// the CAS digest of each of the outputs above, so that they can be fetched and checked
+        /// without relying on the local paths. Symlinks have no digest and aren't listed
+        #[serde(skip_serializing_if = "HashMap::is_empty")]
+        output_digests: HashMap<ProjectRelativePathBuf, BuildReportOutputDigest>,
+        /// the errors building the outputs of this target, as printed on the console. They name
+        /// the action that failed and the target it belongs to, which may be a dependency or an
+        /// anonymous target of this one
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        errors: Vec<String>,
+    }
+
+    #[derive(Clone, Debug, Serialize)]
+    struct BuildReportOutputDigest {
+        /// hex-encoded hash of the file, or of the directory tree for directories
+        digest: String,
+        /// size in bytes of the blob the digest refers to
+        size: u64,
+        is_dir: bool,
+    }
+
+    impl BuildReportOutputDigest {
+        fn new(value: &ArtifactValue) -> Option<Self> {
+            let digest = value.digest()?;
+            Some(Self {
+                digest: hex::encode(digest.digest()),
+                size: digest.size(),
+                is_dir: value.is_dir(),
+            })
+        }
     }
 
     #[derive(Debug, Serialize)]
@@ -262,6 +342,7 @@ pub mod build_report {
         trace_id: &'a TraceId,
         artifact_fs: &'a ArtifactFs,
         build_report_results: HashMap<EntryLabel, ConfiguredBuildReportEntry>,
+        skipped_incompatible: Vec<BuildReportSkipped>,
         overall_success: bool,
         project_root: &'a ProjectRoot,
         include_unconfigured_section: bool,
@@ -280,6 +361,7 @@ pub mod build_report {
                 trace_id,
                 artifact_fs,
                 build_report_results: HashMap::new(),
+                skipped_incompatible: Vec::new(),
                 overall_success: true,
                 project_root,
                 include_unconfigured_section,
@@ -287,7 +369,22 @@ pub mod build_report {
             }
         }
 
-        pub(crate) fn into_report(self) -> BuildReport {
+        pub(crate) fn into_report(self, warnings: &CommandWarnings) -> BuildReport {
+            let warnings = warnings
+                .warnings()
+                .into_iter()
+                .map(|(warning, count)| BuildReportWarning {
+                    category: category_name(
+                        buck2_data::WarningCategory::from_i32(warning.category)
+                            .unwrap_or(buck2_data::WarningCategory::Other),
+                    ),
+                    location: warning.location,
+                    message: warning.message,
+                    count,
+                })
+                .collect();
+            let mut skipped_incompatible = self.skipped_incompatible;
+            skipped_incompatible.sort();
             BuildReport {
                 trace_id: self.trace_id.dupe(),
                 success: self.overall_success,
@@ -297,16 +394,19 @@ pub mod build_report {
                 // In buck1 we may truncate build report for a large number of targets.
                 // Setting this to false since we don't currently truncate buck2's build report.
                 truncated: false,
+                warnings,
+                skipped_incompatible,
             }
         }
     }
 
     impl<'a> BuildResultCollector for BuildReportCollector<'a> {
         fn collect_result(&mut self, label: &BuildOwner, result: &BuildTargetResult) {
-            let (default_outs, other_outs, success) = {
+            let (default_outs, other_outs, output_digests, errors) = {
                 let mut default_outs = IndexSet::new();
                 let mut other_outs = IndexSet::new();
-                let mut success = true;
+                let mut output_digests = HashMap::new();
+                let mut errors = IndexSet::new();
 
                 result.outputs.iter().for_each(|res| {
                     match res {
@@ -332,25 +432,30 @@ pub mod build_report {
                                 }
                             }
 
-                            for (artifact, _value) in artifacts.values.iter() {
-                                if is_default {
-                                    default_outs.insert(
-                                        self.artifact_fs.resolve(artifact.get_path()).unwrap(),
-                                    );
+                            for (artifact, value) in artifacts.values.iter() {
+                                let reported =
+                                    is_default || (is_other && self.include_other_outputs);
+                                if !reported {
+                                    continue;
                                 }
-
-                                if is_other && self.include_other_outputs {
-                                    other_outs.insert(
-                                        self.artifact_fs.resolve(artifact.get_path()).unwrap(),
-                                    );
+                                let path = self.artifact_fs.resolve(artifact.get_path()).unwrap();
+                                if let Some(digest) = BuildReportOutputDigest::new(value) {
+                                    output_digests.insert(path.clone(), digest);
+                                }
+                                if is_default {
+                                    default_outs.insert(path);
+                                } else {
+                                    other_outs.insert(path);
                                 }
                             }
                         }
-                        Err(..) => success = false,
+                        Err(e) => {
+                            errors.insert(format!("{:#}", e));
+                        }
                     }
                 });
 
-                (default_outs, other_outs, success)
+                (default_outs, other_outs, output_digests, errors)
             };
 
             let report_results = self
@@ -403,14 +508,38 @@ pub mod build_report {
                 );
             }
 
-            if !success {
+            if let Some(report) = unconfigured_report {
+                report.output_digests.extend(
+                    output_digests
+                        .iter()
+                        .map(|(path, digest)| (path.clone(), digest.clone())),
+                );
+            }
+            configured_report.output_digests.extend(output_digests);
+
+            if !errors.is_empty() {
                 if let Some(report) = unconfigured_report {
                     report.success = BuildOutcome::FAIL;
+                    report.errors.extend(errors.iter().cloned());
                 }
                 configured_report.success = BuildOutcome::FAIL;
+                configured_report.errors.extend(errors);
                 self.overall_success = false;
             }
         }
+
+        fn collect_skipped(
+            &mut self,
+            label: &ConfiguredProvidersLabel,
+            reason: &IncompatiblePlatformReason,
+        ) {
+            let (incompatible_target, unsatisfied_constraint) = reason.root_cause();
+            self.skipped_incompatible.push(BuildReportSkipped {
+                target: label.target().to_string(),
+                incompatible_target: incompatible_target.to_string(),
+                unsatisfied_constraint: unsatisfied_constraint.to_string(),
+            });
+        }
     }
 
     fn report_providers_name(label: &BuildOwner) -> String {
@@ -425,6 +554,260 @@ pub mod build_report {
             BuildOwner::_Bxl(_) => "DEFAULT".to_owned(),
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use std::path::PathBuf;
+        use std::sync::Arc;
+
+        use buck2_build_api::actions::artifact::testing::BuildArtifactTestingExt;
+        use buck2_build_api::actions::artifact::Artifact;
+        use buck2_build_api::actions::artifact::BuildArtifact;
+        use buck2_build_api::analysis::rule_testing::RuleTest;
+        use buck2_build_api::analysis::rule_testing::RuleTester;
+        use buck2_build_api::artifact_groups::ArtifactGroupValues;
+        use buck2_build_api::build::ProviderArtifacts;
+        use buck2_build_api::deferred::types::testing::DeferredIdExt;
+        use buck2_build_api::deferred::types::DeferredId;
+        use buck2_build_api::interpreter::rule_defs::provider::collection::FrozenProviderCollectionValue;
+        use buck2_common::external_symlink::ExternalSymlink;
+        use buck2_common::file_ops::FileDigest;
+        use buck2_common::file_ops::FileMetadata;
+        use buck2_common::file_ops::TrackedFileDigest;
+        use buck2_common::result::SharedError;
+        use buck2_common::result::SharedResult;
+        use buck2_core::cells::cell_root_path::CellRootPathBuf;
+        use buck2_core::cells::testing::CellResolverExt;
+        use buck2_core::cells::CellName;
+        use buck2_core::cells::CellResolver;
+        use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+        use buck2_core::provider::label::testing::ProvidersLabelTestExt;
+        use buck2_core::provider::label::ProvidersLabel;
+        use buck2_core::target::testing::TargetLabelExt;
+        use buck2_execute::path::buck_out_path::BuckOutPathResolver;
+        use buck2_execute::path::buck_out_path::BuckPathResolver;
+        use buck2_node::compatibility::IncompatiblePlatformReasonCause;
+        use indoc::indoc;
+
+        use super::*;
+
+        fn artifact_fs(project_root: &ProjectRoot) -> ArtifactFs {
+            ArtifactFs::new(
+                BuckPathResolver::new(CellResolver::of_names_and_paths(&[(
+                    CellName::unchecked_new("cell".into()),
+                    CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new("cell".into())),
+                )])),
+                BuckOutPathResolver::new(ProjectRelativePathBuf::unchecked_new("buck-out".into())),
+                project_root.dupe(),
+            )
+        }
+
+        /// The providers of a target with no outputs.
+        fn providers() -> anyhow::Result<FrozenProviderCollectionValue> {
+            let mut tester = RuleTester::new()?;
+            tester.add_bzl(
+                "rules.bzl",
+                indoc!(
+                    r#"
+                    def _foo_impl(_ctx):
+                        return [DefaultInfo()]
+
+                    foo = rule(impl = _foo_impl, attrs = {})
+                    "#
+                ),
+            )?;
+            Ok(tester
+                .analyze(&RuleTest::new("rules.bzl", "foo", "root//:foo"))?
+                .providers()
+                .dupe())
+        }
+
+        #[test]
+        fn test_errors() -> anyhow::Result<()> {
+            let project_root =
+                ProjectRoot::new(AbsNormPathBuf::try_from(std::env::current_dir()?)?);
+            let artifact_fs = artifact_fs(&project_root);
+            let trace_id = TraceId::null();
+            let mut collector =
+                BuildReportCollector::new(&trace_id, &artifact_fs, &project_root, true, false);
+
+            let label = ConfiguredProvidersLabel::new(
+                ProvidersLabel::testing_new("cell", "pkg", "foo", None),
+                Configuration::testing_new(),
+            );
+            let error = || {
+                Err(SharedError::from(anyhow::anyhow!("exit code 1").context(
+                    "Failed to build action `cxx_link` of target `cell//pkg:foo`",
+                )))
+            };
+            // The same error is only reported once.
+            collector.collect_result(
+                &BuildOwner::Target(&label),
+                &BuildTargetResult {
+                    outputs: vec![error(), error()],
+                    providers: providers()?,
+                    run_args: None,
+                },
+            );
+
+            let report = serde_json::to_value(collector.into_report(&CommandWarnings::default()))?;
+            let expected = serde_json::json!([
+                "Failed to build action `cxx_link` of target `cell//pkg:foo`: exit code 1"
+            ]);
+            let entry = &report["results"]["cell//pkg:foo"];
+            assert_eq!(entry["success"], "FAIL");
+            assert_eq!(entry["errors"], expected);
+            assert_eq!(
+                entry["configured"][Configuration::testing_new().to_string()]["errors"],
+                expected
+            );
+            assert_eq!(report["success"], false);
+            Ok(())
+        }
+
+        #[test]
+        fn test_output_digests() -> anyhow::Result<()> {
+            let project_root =
+                ProjectRoot::new(AbsNormPathBuf::try_from(std::env::current_dir()?)?);
+            let artifact_fs = artifact_fs(&project_root);
+            let trace_id = TraceId::null();
+            let mut collector =
+                BuildReportCollector::new(&trace_id, &artifact_fs, &project_root, true, false);
+
+            let target =
+                TargetLabel::testing_parse("cell//pkg:foo").configure(Configuration::testing_new());
+            let output = |name: &str, id, value| -> anyhow::Result<_> {
+                let artifact = Artifact::from(BuildArtifact::testing_new(
+                    target.dupe(),
+                    ForwardRelativePathBuf::unchecked_new(name.to_owned()),
+                    DeferredId::testing_new(id),
+                ));
+                let path = artifact_fs.resolve(artifact.get_path())?.to_string();
+                let output: SharedResult<_> = Ok(ProviderArtifacts {
+                    values: ArtifactGroupValues::from_artifact(artifact, value),
+                    provider_type: BuildProviderType::Default,
+                });
+                Ok((path, output))
+            };
+            let (file, file_output) = output(
+                "file",
+                0,
+                ArtifactValue::file(FileMetadata {
+                    digest: TrackedFileDigest::new(FileDigest::from_bytes_sha1(b"contents")),
+                    is_executable: false,
+                }),
+            )?;
+            let (dir, dir_output) = output("dir", 1, ArtifactValue::empty_dir())?;
+            let (symlink, symlink_output) = output(
+                "symlink",
+                2,
+                ArtifactValue::external_symlink(Arc::new(ExternalSymlink::new(
+                    PathBuf::from("/usr/bin/true"),
+                    None,
+                )?)),
+            )?;
+
+            let label = ConfiguredProvidersLabel::new(
+                ProvidersLabel::testing_new("cell", "pkg", "foo", None),
+                Configuration::testing_new(),
+            );
+            collector.collect_result(
+                &BuildOwner::Target(&label),
+                &BuildTargetResult {
+                    outputs: vec![file_output, dir_output, symlink_output],
+                    providers: providers()?,
+                    run_args: None,
+                },
+            );
+
+            let report = serde_json::to_value(collector.into_report(&CommandWarnings::default()))?;
+            let entry = &report["results"]["cell//pkg:foo"];
+            assert_eq!(
+                entry["outputs"]["DEFAULT"],
+                serde_json::json!([file, dir, symlink])
+            );
+            let digests = &entry["output_digests"];
+            assert_eq!(
+                digests[&file],
+                serde_json::json!({
+                    "digest": "4a756ca07e9487f482465a99e8286abc86ba4dc7",
+                    "size": 8,
+                    "is_dir": false,
+                })
+            );
+            assert_eq!(digests[&dir]["is_dir"], true);
+            // Symlinks have no digest.
+            assert_eq!(digests[&symlink], serde_json::Value::Null);
+            assert_eq!(
+                entry["configured"][Configuration::testing_new().to_string()]["output_digests"],
+                *digests
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn test_skipped() -> anyhow::Result<()> {
+            let project_root =
+                ProjectRoot::new(AbsNormPathBuf::try_from(std::env::current_dir()?)?);
+            let artifact_fs = artifact_fs(&project_root);
+            let trace_id = TraceId::null();
+            let mut collector =
+                BuildReportCollector::new(&trace_id, &artifact_fs, &project_root, true, false);
+
+            let label = |name| {
+                ConfiguredProvidersLabel::new(
+                    ProvidersLabel::testing_new("cell", "pkg", name, None),
+                    Configuration::testing_new(),
+                )
+            };
+            let dep =
+                TargetLabel::testing_parse("cell//pkg:dep").configure(Configuration::testing_new());
+            let constraint = TargetLabel::testing_parse("cell//constraints:arm64");
+            let dep_reason = Arc::new(IncompatiblePlatformReason {
+                target: dep.dupe(),
+                cause: IncompatiblePlatformReasonCause::UnsatisfiedConfig(constraint.dupe()),
+            });
+
+            // Reported in order, whatever the order they were skipped in, and with the root cause
+            // of the incompatibility rather than the dependency it comes through.
+            let foo = label("foo");
+            collector.collect_skipped(
+                &foo,
+                &IncompatiblePlatformReason {
+                    target: foo.target().dupe(),
+                    cause: IncompatiblePlatformReasonCause::Dependency(dep_reason.dupe()),
+                },
+            );
+            let bar = label("bar");
+            collector.collect_skipped(
+                &bar,
+                &IncompatiblePlatformReason {
+                    target: bar.target().dupe(),
+                    cause: IncompatiblePlatformReasonCause::UnsatisfiedConfig(constraint.dupe()),
+                },
+            );
+
+            let report = serde_json::to_value(collector.into_report(&CommandWarnings::default()))?;
+            assert_eq!(
+                report["skipped_incompatible"],
+                serde_json::json!([
+                    {
+                        "target": bar.target().to_string(),
+                        "incompatible_target": bar.target().to_string(),
+                        "unsatisfied_constraint": "cell//constraints:arm64",
+                    },
+                    {
+                        "target": foo.target().to_string(),
+                        "incompatible_target": dep.to_string(),
+                        "unsatisfied_constraint": "cell//constraints:arm64",
+                    },
+                ])
+            );
+            // Skipped targets aren't failures.
+            assert_eq!(report["success"], true);
+            Ok(())
+        }
+    }
 }
 
 pub mod providers {
@@ -80,7 +80,7 @@ pub(crate) fn create_unhashed_outputs(
     Ok(num_unhashed_links_made)
 }
 
-fn create_unhashed_link(
+pub(crate) fn create_unhashed_link(
     unhashed_path: &AbsNormPathBuf,
     original_path: &AbsNormPathBuf,
     buck_out_root: &AbsNormPathBuf,
@@ -7,8 +7,10 @@
  * of this source tree.
  */
 
+pub mod action_exec;
 pub mod build;
 pub mod install;
 pub mod query;
 pub mod targets;
 pub mod targets_show_outputs;
+pub(crate) mod targets_visitor;
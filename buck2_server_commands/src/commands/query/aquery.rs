@@ -8,8 +8,10 @@
  */
 
 use async_trait::async_trait;
+use buck2_build_api::query::aquery::environment::ActionQueryNode;
 use buck2_build_api::query::aquery::evaluator::get_aquery_evaluator;
 use buck2_common::dice::cells::HasCellResolver;
+use buck2_execute::base_deferred_key::BaseDeferredKey;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use buck2_server_ctx::pattern::target_platform_from_client_context;
@@ -19,8 +21,12 @@ use cli_proto::AqueryRequest;
 use cli_proto::AqueryResponse;
 use dice::DiceTransaction;
 
+use crate::commands::query::filter_query_result;
 use crate::commands::query::printer::QueryResultPrinter;
+use crate::commands::query::printer::ShouldPrintOutputs;
 use crate::commands::query::printer::ShouldPrintProviders;
+use crate::commands::query::query_lib::QueryLib;
+use crate::commands::query::ExcludedPatterns;
 
 pub async fn aquery_command(
     ctx: Box<dyn ServerCommandContextTrait>,
@@ -69,9 +75,14 @@ async fn aquery(
         query,
         query_args,
         context,
+        include_anon,
+        exclude_target_patterns,
+        query_libs,
         ..
     } = request;
 
+    let excluded = ExcludedPatterns::parse(server_ctx, &ctx, exclude_target_patterns).await?;
+
     let global_target_platform = target_platform_from_client_context(
         context.as_ref(),
         &cell_resolver,
@@ -82,19 +93,39 @@ async fn aquery(
     let evaluator =
         get_aquery_evaluator(&ctx, server_ctx.working_dir(), global_target_platform).await?;
 
-    let query_result = evaluator.eval_query(query, query_args).await?;
+    let query_lib = QueryLib::load(server_ctx, &ctx, query_libs).await?;
+    let mut query_result = evaluator
+        .eval_query(&query_lib.expand(query)?, query_args)
+        .await?;
+    if !include_anon || !excluded.is_empty() {
+        query_result = filter_query_result(query_result, |node: &ActionQueryNode| {
+            is_included(node.action().owner(), *include_anon, &excluded)
+        })?;
+    }
 
     let mut stdout = server_ctx.stdout()?;
 
     let result = match query_result {
         QueryEvaluationResult::Single(targets) => {
             output_configuration
-                .print_single_output(&mut stdout, targets, false, ShouldPrintProviders::No)
+                .print_single_output(
+                    &mut stdout,
+                    targets,
+                    false,
+                    ShouldPrintProviders::No,
+                    ShouldPrintOutputs::No,
+                )
                 .await
         }
         QueryEvaluationResult::Multiple(results) => {
             output_configuration
-                .print_multi_output(&mut stdout, results, false, ShouldPrintProviders::No)
+                .print_multi_output(
+                    &mut stdout,
+                    results,
+                    false,
+                    ShouldPrintProviders::No,
+                    ShouldPrintOutputs::No,
+                )
                 .await
         }
     };
@@ -104,3 +135,62 @@ async fn aquery(
     };
     Ok(AqueryResponse { error_messages })
 }
+
+/// Whether the actions owned by `owner` are output. Actions owned by anon targets are still
+/// traversed, so e.g. `deps()` returns the actions they depend on, but they are omitted from the
+/// results unless `include_anon` is set.
+fn is_included(owner: &BaseDeferredKey, include_anon: bool, excluded: &ExcludedPatterns) -> bool {
+    match owner {
+        BaseDeferredKey::TargetLabel(label) => !excluded.is_excluded(label.unconfigured()),
+        BaseDeferredKey::AnonTarget(_) => include_anon,
+        BaseDeferredKey::BxlLabel(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use buck2_core::bzl::ImportPath;
+    use buck2_core::collections::sorted_map::SortedMap;
+    use buck2_core::configuration::Configuration;
+    use buck2_core::target::testing::TargetLabelExt;
+    use buck2_core::target::TargetLabel;
+    use buck2_execute::anon_target::AnonTarget;
+    use buck2_node::rule_type::StarlarkRuleType;
+
+    use super::*;
+
+    fn anon_target() -> BaseDeferredKey {
+        BaseDeferredKey::AnonTarget(Arc::new(AnonTarget::new(
+            Arc::new(StarlarkRuleType {
+                import_path: ImportPath::unchecked_new("root", "foo", "rules.bzl"),
+                name: "my_rule".to_owned(),
+            }),
+            TargetLabel::testing_parse("anon//:my_rule"),
+            SortedMap::default(),
+            SortedMap::default(),
+            Configuration::testing_new(),
+        )))
+    }
+
+    fn target() -> BaseDeferredKey {
+        BaseDeferredKey::TargetLabel(
+            TargetLabel::testing_parse("root//foo:bar").configure(Configuration::testing_new()),
+        )
+    }
+
+    #[test]
+    fn test_anon_actions_excluded_by_default() {
+        let excluded = ExcludedPatterns(Vec::new());
+        assert!(!is_included(&anon_target(), false, &excluded));
+        assert!(is_included(&target(), false, &excluded));
+    }
+
+    #[test]
+    fn test_include_anon() {
+        let excluded = ExcludedPatterns(Vec::new());
+        assert!(is_included(&anon_target(), true, &excluded));
+        assert!(is_included(&target(), true, &excluded));
+    }
+}
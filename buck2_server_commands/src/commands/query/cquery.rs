@@ -14,25 +14,37 @@ use buck2_build_api::query::cquery::environment::CqueryOwnerBehavior;
 use buck2_build_api::query::cquery::evaluator::get_cquery_evaluator;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::result::ToUnsharedResultExt;
+use buck2_core::fs::project::ProjectRoot;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_core::provider::label::ProvidersName;
 use buck2_core::truncate::truncate;
+use buck2_data::ToProtoMessage;
+use buck2_execute::artifact::fs::ArtifactFs;
 use buck2_node::compatibility::MaybeCompatible;
 use buck2_node::nodes::configured::ConfiguredTargetNode;
+use buck2_query::query::syntax::simple::eval::set::TargetSet;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use buck2_server_ctx::pattern::target_platform_from_client_context;
 use buck2_server_ctx::template::run_server_command;
 use buck2_server_ctx::template::ServerCommandTemplate;
+use cli_proto::CqueryProvider;
 use cli_proto::CqueryRequest;
 use cli_proto::CqueryResponse;
+use cli_proto::CqueryTarget;
 use dice::DiceComputations;
 use dice::DiceTransaction;
 use gazebo::prelude::*;
+use thiserror::Error;
 
+use crate::commands::query::filter_query_result;
+use crate::commands::query::printer::OutputLookUp;
 use crate::commands::query::printer::ProviderLookUp;
 use crate::commands::query::printer::QueryResultPrinter;
+use crate::commands::query::printer::ShouldPrintOutputs;
 use crate::commands::query::printer::ShouldPrintProviders;
+use crate::commands::query::query_lib::QueryLib;
+use crate::commands::query::ExcludedPatterns;
 
 pub async fn cquery_command(
     ctx: Box<dyn ServerCommandContextTrait>,
@@ -91,7 +103,13 @@ async fn cquery(
         context,
         target_call_stacks,
         show_providers,
+        show_outputs,
+        show_full_outputs,
         correct_owner,
+        named_queries,
+        exclude_target_patterns,
+        return_targets,
+        query_libs,
         ..
     } = request;
     // The request will always have a universe value, an empty one indicates the user didn't provide a universe.
@@ -122,9 +140,38 @@ async fn cquery(
 
     let evaluator = &evaluator;
 
-    let query_result = evaluator
-        .eval_query(query, query_args, target_universe.as_ref().map(|v| &v[..]))
-        .await?;
+    let target_universe = target_universe.as_ref().map(|v| &v[..]);
+    let query_lib = QueryLib::load(server_ctx, &ctx, query_libs).await?;
+    let query_result = if named_queries.is_empty() {
+        evaluator
+            .eval_query(&query_lib.expand(query)?, query_args, target_universe)
+            .await?
+    } else {
+        evaluator
+            .eval_named_queries(&query_lib.expand_named(named_queries)?, target_universe)
+            .await?
+    };
+    let excluded = ExcludedPatterns::parse(server_ctx, &ctx, exclude_target_patterns).await?;
+    let query_result = if excluded.is_empty() {
+        query_result
+    } else {
+        filter_query_result(query_result, |node: &ConfiguredTargetNode| {
+            !excluded.is_excluded(node.name().unconfigured())
+        })?
+    };
+
+    if *return_targets {
+        let targets = match query_result {
+            QueryEvaluationResult::Single(targets) => targets,
+            QueryEvaluationResult::Multiple(..) => {
+                return Err(ReturnTargetsOfMultipleQueries.into())
+            }
+        };
+        return Ok(CqueryResponse {
+            targets: query_targets(&ctx, &targets, *show_providers).await?,
+            error_messages: Vec::new(),
+        });
+    }
 
     let mut stdout = server_ctx.stdout()?;
 
@@ -134,6 +181,20 @@ async fn cquery(
         ShouldPrintProviders::No
     };
 
+    let output_lookup = if *show_outputs || *show_full_outputs {
+        Some(DefaultOutputLookUp {
+            ctx: &ctx,
+            artifact_fs: ctx.get_artifact_fs().await?,
+            project_root: show_full_outputs.then(|| server_ctx.project_root()),
+        })
+    } else {
+        None
+    };
+    let should_print_outputs = match &output_lookup {
+        Some(lookup) => ShouldPrintOutputs::Yes(lookup as &dyn OutputLookUp<ConfiguredTargetNode>),
+        None => ShouldPrintOutputs::No,
+    };
+
     let result = match query_result {
         QueryEvaluationResult::Single(targets) => {
             output_configuration
@@ -142,6 +203,7 @@ async fn cquery(
                     targets,
                     *target_call_stacks,
                     should_print_providers,
+                    should_print_outputs,
                 )
                 .await
         }
@@ -152,6 +214,7 @@ async fn cquery(
                     results,
                     *target_call_stacks,
                     should_print_providers,
+                    should_print_outputs,
                 )
                 .await
         }
@@ -162,7 +225,43 @@ async fn cquery(
         Err(e) => vec![format!("{:#}", e)],
     };
 
-    Ok(CqueryResponse { error_messages })
+    Ok(CqueryResponse {
+        targets: Vec::new(),
+        error_messages,
+    })
+}
+
+#[derive(Debug, Error)]
+#[error("Targets can only be returned for a single query")]
+struct ReturnTargetsOfMultipleQueries;
+
+/// The targets of a query as returned to programs, see `CqueryRequest::return_targets`.
+async fn query_targets(
+    ctx: &DiceComputations,
+    targets: &TargetSet<ConfiguredTargetNode>,
+    show_providers: bool,
+) -> anyhow::Result<Vec<CqueryTarget>> {
+    futures::future::try_join_all(targets.iter().map(|node| async move {
+        let mut providers = Vec::new();
+        if show_providers {
+            let collection = ctx.lookup(node).await?.require_compatible()?;
+            let collection = collection.provider_collection();
+            for id in collection.provider_ids() {
+                let value = collection
+                    .get_provider_raw(id)
+                    .expect("Provider is in the collection");
+                providers.push(CqueryProvider {
+                    name: id.name().to_owned(),
+                    json: serde_json::to_string(value)?,
+                });
+            }
+        }
+        anyhow::Ok(CqueryTarget {
+            label: Some(node.name().as_proto()),
+            providers,
+        })
+    }))
+    .await
 }
 
 #[async_trait]
@@ -179,3 +278,32 @@ impl ProviderLookUp<ConfiguredTargetNode> for DiceComputations {
         .unshared_error()
     }
 }
+
+/// Looks up the default outputs of configured targets from their analysis, without building them.
+struct DefaultOutputLookUp<'a> {
+    ctx: &'a DiceComputations,
+    artifact_fs: ArtifactFs,
+    /// Set to print absolute paths.
+    project_root: Option<&'a ProjectRoot>,
+}
+
+#[async_trait]
+impl OutputLookUp<ConfiguredTargetNode> for DefaultOutputLookUp<'_> {
+    async fn lookup_outputs(&self, t: &ConfiguredTargetNode) -> anyhow::Result<Vec<String>> {
+        let providers = self.ctx.lookup(t).await?.require_compatible()?;
+
+        let mut outputs = Vec::new();
+        providers
+            .provider_collection()
+            .default_info()
+            .for_each_default_output_artifact_only(&mut |o| {
+                let path = self.artifact_fs.resolve(o.get_path())?;
+                outputs.push(match self.project_root {
+                    Some(project_root) => project_root.resolve(&path).to_string(),
+                    None => path.to_string(),
+                });
+                Ok(())
+            })?;
+        Ok(outputs)
+    }
+}
@@ -7,13 +7,88 @@
  * of this source tree.
  */
 
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+use buck2_core::pattern::ParsedPattern;
+use buck2_core::pattern::TargetPattern;
+use buck2_core::target::TargetLabel;
+use buck2_query::query::environment::QueryTarget;
+use buck2_query::query::syntax::simple::eval::multi_query::MultiQueryResult;
+use buck2_query::query::syntax::simple::eval::set::TargetSetExt;
+use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
+use buck2_query::query::syntax::simple::eval::values::QueryEvaluationValue;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::pattern::PatternParser;
+use dice::DiceComputations;
+use gazebo::prelude::*;
 use thiserror::Error;
 
 pub mod aquery;
 pub mod cquery;
 pub mod printer;
+mod query_lib;
 pub mod uquery;
 
+/// Patterns of targets excluded from the results of a query (e.g. `-//foo/experimental/...`).
+struct ExcludedPatterns(Vec<ParsedPattern<TargetPattern>>);
+
+impl ExcludedPatterns {
+    async fn parse(
+        server_ctx: &dyn ServerCommandContextTrait,
+        ctx: &DiceComputations,
+        patterns: &[String],
+    ) -> anyhow::Result<Self> {
+        if patterns.is_empty() {
+            return Ok(Self(Vec::new()));
+        }
+        let parser = PatternParser::new(
+            &ctx.get_cell_resolver().await?,
+            &ctx.get_legacy_configs().await?,
+            server_ctx.working_dir(),
+        )?;
+        Ok(Self(patterns.try_map(|p| parser.parse_pattern(p))?))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn is_excluded(&self, target: &TargetLabel) -> bool {
+        self.0.iter().any(|p| p.matches(target))
+    }
+}
+
+/// Removes the nodes for which `keep` returns false from the results. Files are kept.
+fn filter_query_result<T: QueryTarget>(
+    result: QueryEvaluationResult<T>,
+    keep: impl Fn(&T) -> bool + Copy,
+) -> anyhow::Result<QueryEvaluationResult<T>> {
+    fn filter<T: QueryTarget>(
+        value: QueryEvaluationValue<T>,
+        keep: impl Fn(&T) -> bool,
+    ) -> anyhow::Result<QueryEvaluationValue<T>> {
+        match value {
+            QueryEvaluationValue::TargetSet(targets) => Ok(QueryEvaluationValue::TargetSet(
+                targets.filter(|node| Ok(keep(node)))?,
+            )),
+            files @ QueryEvaluationValue::FileSet(_) => Ok(files),
+        }
+    }
+
+    Ok(match result {
+        QueryEvaluationResult::Single(value) => QueryEvaluationResult::Single(filter(value, keep)?),
+        QueryEvaluationResult::Multiple(results) => {
+            QueryEvaluationResult::Multiple(MultiQueryResult(
+                results
+                    .0
+                    .into_iter()
+                    .map(|(query, value)| (query, value.and_then(|value| filter(value, keep))))
+                    .collect(),
+            ))
+        }
+    })
+}
+
 #[derive(Debug, Error)]
 enum QueryCommandError {
     #[error(
@@ -49,6 +49,18 @@ pub trait ProviderLookUp<T: QueryTarget>: Send + Sync {
     -> anyhow::Result<MaybeCompatible<FrozenProviderCollectionValue>>;
 }
 
+#[derive(Copy_, Dupe_, Clone_, UnpackVariants)]
+pub enum ShouldPrintOutputs<'a, T> {
+    No,
+    Yes(&'a dyn OutputLookUp<T>),
+}
+
+#[async_trait]
+pub trait OutputLookUp<T: QueryTarget>: Send + Sync {
+    /// The paths of the default outputs of the target, whether they are built or not.
+    async fn lookup_outputs(&self, t: &T) -> anyhow::Result<Vec<String>>;
+}
+
 #[derive(Debug)]
 pub struct QueryResultPrinter<'a> {
     resolver: &'a CellResolver,
@@ -65,15 +77,23 @@ impl<'a, T: QueryTarget> TargetSetJsonPrinter<'a, T> {
     async fn new(
         target_call_stacks: bool,
         print_providers: ShouldPrintProviders<'a, T>,
+        print_outputs: ShouldPrintOutputs<'a, T>,
         attributes: &'a Option<RegexSet>,
         targets: &'a TargetSet<T>,
     ) -> anyhow::Result<TargetSetJsonPrinter<'a, T>> {
         Ok(TargetSetJsonPrinter {
-            value: printable_targets(targets, print_providers, attributes, target_call_stacks)
-                .await?,
+            value: printable_targets(
+                targets,
+                print_providers,
+                print_outputs,
+                attributes,
+                target_call_stacks,
+            )
+            .await?,
             is_complex: attributes.is_some()
                 || target_call_stacks
-                || print_providers.unpack_yes().is_some(),
+                || print_providers.unpack_yes().is_some()
+                || print_outputs.unpack_yes().is_some(),
         })
     }
 }
@@ -82,6 +102,7 @@ struct PrintableQueryTarget<'a, T: QueryTarget> {
     value: &'a T,
     attributes: &'a Option<RegexSet>,
     providers: Option<FrozenProviderCollectionValue>,
+    outputs: Option<Vec<String>>,
     target_call_stacks: bool,
 }
 
@@ -95,6 +116,12 @@ impl<'a, T: QueryTarget> Display for PrintableQueryTarget<'a, T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.value.node_ref())?;
 
+        if let Some(outputs) = &self.outputs {
+            for output in outputs {
+                write!(f, " {}", output)?;
+            }
+        }
+
         if self.target_call_stacks || self.providers.is_some() {
             writeln!(f)?;
         }
@@ -149,6 +176,10 @@ impl<'a, T: QueryTarget> Serialize for PrintableQueryTarget<'a, T> {
             map.serialize_entry("buck.providers", providers)?;
         }
 
+        if let Some(outputs) = &self.outputs {
+            map.serialize_entry("buck.outputs", outputs)?;
+        }
+
         map.end()
     }
 }
@@ -235,6 +266,7 @@ impl<'a> QueryResultPrinter<'a> {
         multi_result: MultiQueryResult<T>,
         target_call_stacks: bool,
         print_providers: ShouldPrintProviders<'b, T>,
+        print_outputs: ShouldPrintOutputs<'b, T>,
     ) -> anyhow::Result<()> {
         match (self.output_format, &self.attributes) {
             // A multi-query only has interesting output with --json output. For non-json output it gets merged together.
@@ -255,6 +287,7 @@ impl<'a> QueryResultPrinter<'a> {
                                 &TargetSetJsonPrinter::new(
                                     target_call_stacks,
                                     print_providers,
+                                    print_outputs,
                                     &self.attributes,
                                     &targets,
                                 )
@@ -289,6 +322,7 @@ impl<'a> QueryResultPrinter<'a> {
                     multi_result.merged()?,
                     target_call_stacks,
                     print_providers,
+                    print_outputs,
                 )
                 .await
             }
@@ -301,13 +335,19 @@ impl<'a> QueryResultPrinter<'a> {
         result: QueryEvaluationValue<T>,
         call_stack: bool,
         print_providers: ShouldPrintProviders<'b, T>,
+        print_outputs: ShouldPrintOutputs<'b, T>,
     ) -> anyhow::Result<()> {
         match result {
             QueryEvaluationValue::TargetSet(targets) => match self.output_format {
                 QueryOutputFormat::Default => {
-                    for target in
-                        printable_targets(&targets, print_providers, &self.attributes, call_stack)
-                            .await?
+                    for target in printable_targets(
+                        &targets,
+                        print_providers,
+                        print_outputs,
+                        &self.attributes,
+                        call_stack,
+                    )
+                    .await?
                     {
                         writeln!(&mut output, "{}", target)?;
                     }
@@ -317,6 +357,7 @@ impl<'a> QueryResultPrinter<'a> {
                     TargetSetJsonPrinter::new(
                         call_stack,
                         print_providers,
+                        print_outputs,
                         &self.attributes,
                         &targets,
                     )
@@ -383,11 +424,13 @@ impl<'a> QueryResultPrinter<'a> {
 async fn printable_targets<'a, T: QueryTarget>(
     targets: &'a TargetSet<T>,
     print_providers: ShouldPrintProviders<'a, T>,
+    print_outputs: ShouldPrintOutputs<'a, T>,
     attributes: &'a Option<RegexSet>,
     target_call_stacks: bool,
 ) -> anyhow::Result<Vec<PrintableQueryTarget<'a, T>>> {
     futures::future::join_all(targets.iter().map(|t| {
         let print_providers = &print_providers;
+        let print_outputs = &print_outputs;
         async move {
             Ok(PrintableQueryTarget {
                 value: t,
@@ -399,6 +442,10 @@ async fn printable_targets<'a, T: QueryTarget>(
                         Some(lookup.lookup(t).await?.require_compatible()?)
                     }
                 },
+                outputs: match print_outputs {
+                    ShouldPrintOutputs::No => None,
+                    ShouldPrintOutputs::Yes(lookup) => Some(lookup.lookup_outputs(t).await?),
+                },
             })
         }
     }))
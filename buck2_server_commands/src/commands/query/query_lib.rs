@@ -0,0 +1,328 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Reusable query functions, defined in `.bzl` files passed to the query commands with
+//! `--query-lib`.
+//!
+//! The public functions of a library are loaded through the interpreter like any other `.bzl`
+//! file, and return the query a call expands to, given the source text of its arguments:
+//!
+//! ```python
+//! # Tests depending on `x`.
+//! def my_tests(x):
+//!     return 'kind("_test$", rdeps(//..., {}))'.format(x)
+//! ```
+//!
+//! Calls to library functions are expanded before the query is evaluated, so the literals they
+//! expand to are resolved along with those of the query. The query a function returns can call
+//! other library functions (but not recursively), and library functions take precedence over
+//! builtin functions of the same name.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::Context as _;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_interpreter_for_build::interpreter::calculation::InterpreterCalculation;
+use buck2_query_parser::parse_expr;
+use buck2_query_parser::Expr;
+use buck2_query_parser::SpannedExpr;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use cli_proto::NamedQuery;
+use dice::DiceComputations;
+use gazebo::prelude::*;
+use starlark::environment::FrozenModule;
+use starlark::environment::Module;
+use starlark::eval::Evaluator;
+use starlark::values::OwnedFrozenValue;
+use thiserror::Error;
+
+use crate::commands::targets_visitor::parse_import_path;
+
+/// Upper bound on the number of expansion rounds, reached by recursive functions.
+const MAX_EXPANSION_DEPTH: usize = 100;
+
+#[derive(Debug, Error)]
+enum QueryLibError {
+    #[error("Query function `{name}` is defined by both `{first}` and `{second}`")]
+    Duplicate {
+        name: String,
+        first: String,
+        second: String,
+    },
+    #[error("Query function `{0}` returned a `{1}`, expected the query it expands to as a string")]
+    NotAString(String, &'static str),
+    #[error(
+        "Query functions still not fully expanded after {0} rounds, they are probably recursive"
+    )]
+    TooDeep(usize),
+}
+
+struct QueryFunction {
+    /// The library defining the function.
+    lib: String,
+    function: OwnedFrozenValue,
+}
+
+/// The query functions of the libraries of a query command.
+#[derive(Default)]
+pub(crate) struct QueryLib {
+    functions: HashMap<String, QueryFunction>,
+}
+
+impl QueryLib {
+    /// Loads the libraries `libs`, paths of `.bzl` files relative to the working directory.
+    pub(crate) async fn load(
+        server_ctx: &dyn ServerCommandContextTrait,
+        ctx: &DiceComputations,
+        libs: &[String],
+    ) -> anyhow::Result<Self> {
+        let mut query_lib = Self::default();
+        if libs.is_empty() {
+            return Ok(query_lib);
+        }
+        let cell_resolver = ctx.get_cell_resolver().await?;
+        for lib in libs {
+            let path = parse_import_path(server_ctx.working_dir(), lib, &cell_resolver)?;
+            let module = ctx
+                .get_loaded_module_from_import_path(&path)
+                .await
+                .with_context(|| format!("Error loading query library `{}`", lib))?;
+            query_lib.add_lib(lib, module.env())?;
+        }
+        Ok(query_lib)
+    }
+
+    /// Adds the public functions of the module of the library `lib`.
+    fn add_lib(&mut self, lib: &str, module: &FrozenModule) -> anyhow::Result<()> {
+        for name in module.names() {
+            let name = name.as_str();
+            // Private and loaded symbols are helpers, not query functions.
+            let function = match module.get_option(name) {
+                Ok(Some(function)) if function.value().get_type() == "function" => function,
+                _ => continue,
+            };
+            if let Some(existing) = self.functions.get(name) {
+                return Err(QueryLibError::Duplicate {
+                    name: name.to_owned(),
+                    first: existing.lib.clone(),
+                    second: lib.to_owned(),
+                }
+                .into());
+            }
+            self.functions.insert(
+                name.to_owned(),
+                QueryFunction {
+                    lib: lib.to_owned(),
+                    function,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Expands the calls to library functions in `query`.
+    pub(crate) fn expand(&self, query: &str) -> anyhow::Result<String> {
+        if self.functions.is_empty() {
+            return Ok(query.to_owned());
+        }
+        let env = Module::new();
+        let mut eval = Evaluator::new(&env);
+        let mut query = query.to_owned();
+        for _ in 0..MAX_EXPANSION_DEPTH {
+            let expr = parse_expr(&query)?;
+            let mut replacements = Vec::new();
+            self.collect_calls(&mut eval, &query, &expr, &mut replacements)?;
+            if replacements.is_empty() {
+                return Ok(query);
+            }
+            query = replace_ranges(&query, replacements);
+        }
+        Err(QueryLibError::TooDeep(MAX_EXPANSION_DEPTH).into())
+    }
+
+    /// Expands the named queries `queries`, as `(name, query)` pairs.
+    pub(crate) fn expand_named(
+        &self,
+        queries: &[NamedQuery],
+    ) -> anyhow::Result<Vec<(String, String)>> {
+        queries.try_map(|q| {
+            let query = self
+                .expand(&q.query)
+                .with_context(|| format!("Error in query `{}`", q.name))?;
+            Ok((q.name.clone(), query))
+        })
+    }
+
+    /// Finds the outermost calls to library functions in `expr`, and their expansions. Calls in the
+    /// arguments of those are expanded in the next round, if the expansion keeps them.
+    fn collect_calls(
+        &self,
+        eval: &mut Evaluator,
+        source: &str,
+        expr: &SpannedExpr,
+        out: &mut Vec<(Range<usize>, String)>,
+    ) -> anyhow::Result<()> {
+        match &expr.value {
+            Expr::Function {
+                function_name,
+                args,
+            } => match self.functions.get(*function_name.fragment()) {
+                Some(function) => {
+                    let args = args.map(|a| &source[a.position.clone()]);
+                    let expansion = Self::call(eval, function_name.fragment(), function, &args)?;
+                    out.push((expr.position.clone(), expansion));
+                }
+                None => {
+                    for a in args {
+                        self.collect_calls(eval, source, a, out)?;
+                    }
+                }
+            },
+            Expr::BinaryOpSequence(left, rest) => {
+                self.collect_calls(eval, source, left, out)?;
+                for (_, e) in rest {
+                    self.collect_calls(eval, source, e, out)?;
+                }
+            }
+            Expr::String(_) | Expr::Integer(_) | Expr::Set(_) | Expr::FileSet(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Calls the function `name` with the source text of its arguments, returning the query it
+    /// expands to, in parentheses to keep its precedence.
+    fn call(
+        eval: &mut Evaluator,
+        name: &str,
+        function: &QueryFunction,
+        args: &[&str],
+    ) -> anyhow::Result<String> {
+        let args = args.map(|a| eval.heap().alloc(*a));
+        let result = eval
+            .eval_function(function.function.value(), &args, &[])
+            .with_context(|| {
+                format!(
+                    "Error calling query function `{}` from `{}`",
+                    name, function.lib
+                )
+            })?;
+        let query = result
+            .unpack_str()
+            .ok_or_else(|| QueryLibError::NotAString(name.to_owned(), result.get_type()))?;
+        // Report syntax errors in the function rather than in whichever query uses it.
+        parse_expr(query).with_context(|| format!("Error in the query returned by `{}`", name))?;
+        Ok(format!("({})", query))
+    }
+}
+
+/// Replaces the non-overlapping `ranges` of `source`.
+fn replace_ranges(source: &str, mut replacements: Vec<(Range<usize>, String)>) -> String {
+    replacements.sort_by_key(|(range, _)| range.start);
+    let mut res = String::with_capacity(source.len());
+    let mut pos = 0;
+    for (range, replacement) in replacements {
+        res.push_str(&source[pos..range.start]);
+        res.push_str(&replacement);
+        pos = range.end;
+    }
+    res.push_str(&source[pos..]);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use starlark::environment::Globals;
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+
+    use super::*;
+
+    fn module(source: &str) -> FrozenModule {
+        let module = Module::new();
+        {
+            let mut eval = Evaluator::new(&module);
+            let ast = AstModule::parse("lib.bzl", source.to_owned(), &Dialect::Extended).unwrap();
+            eval.eval_module(ast, &Globals::extended()).unwrap();
+        }
+        module.freeze().unwrap()
+    }
+
+    fn lib(source: &str) -> QueryLib {
+        let mut lib = QueryLib::default();
+        lib.add_lib("lib.bzl", &module(source)).unwrap();
+        lib
+    }
+
+    #[test]
+    fn test_expand() -> anyhow::Result<()> {
+        let lib = lib(r#"
+# Tests depending on `x`.
+def my_tests(x):
+    return 'kind("_test$", rdeps(//..., {}))'.format(x)
+
+def both(a, b):
+    return _plus(a, b)
+
+def _plus(a, b):
+    return "{} + {}".format(a, b)
+
+NOT_A_FUNCTION = "deps"
+"#);
+        assert_eq!(
+            lib.expand("my_tests(//foo:bar)")?,
+            r#"(kind("_test$", rdeps(//..., //foo:bar)))"#
+        );
+        // Arguments are passed as written, so calls in them are expanded in the next round.
+        assert_eq!(
+            lib.expand("my_tests(both(//a:a, deps(//b:b)))")?,
+            r#"(kind("_test$", rdeps(//..., (//a:a + deps(//b:b)))))"#
+        );
+        assert_eq!(
+            lib.expand("deps(//a:a) - both(//b:b, //c:c)")?,
+            "deps(//a:a) - (//b:b + //c:c)"
+        );
+        // Private functions and other values aren't query functions.
+        assert_eq!(lib.expand("_plus(//a:a, //b:b)")?, "_plus(//a:a, //b:b)");
+        assert_eq!(
+            lib.expand("NOT_A_FUNCTION(//a:a)")?,
+            "NOT_A_FUNCTION(//a:a)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let lib = lib(r#"
+def f(x):
+    return "g({})".format(x)
+
+def g(x):
+    return "f({})".format(x)
+
+def not_a_query(x):
+    return 1
+
+def invalid(x):
+    return "deps("
+"#);
+        assert!(lib.expand("f(//a:a)").is_err());
+        assert!(lib.expand("f(//a:a, //b:b)").is_err());
+        assert!(lib.expand("not_a_query(//a:a)").is_err());
+        assert!(lib.expand("invalid(//a:a)").is_err());
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let mut lib = lib("def f(x):\n    return x\n");
+        assert!(lib
+            .add_lib("other.bzl", &module("def f(x):\n    return x\n"))
+            .is_err());
+    }
+}
@@ -10,6 +10,7 @@
 use async_trait::async_trait;
 use buck2_build_api::query::uquery::evaluator::get_uquery_evaluator;
 use buck2_common::dice::cells::HasCellResolver;
+use buck2_node::nodes::unconfigured::TargetNode;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use buck2_server_ctx::pattern::target_platform_from_client_context;
@@ -19,8 +20,12 @@ use cli_proto::UqueryRequest;
 use cli_proto::UqueryResponse;
 use dice::DiceTransaction;
 
+use crate::commands::query::filter_query_result;
 use crate::commands::query::printer::QueryResultPrinter;
+use crate::commands::query::printer::ShouldPrintOutputs;
 use crate::commands::query::printer::ShouldPrintProviders;
+use crate::commands::query::query_lib::QueryLib;
+use crate::commands::query::ExcludedPatterns;
 
 pub async fn uquery_command(
     ctx: Box<dyn ServerCommandContextTrait>,
@@ -69,9 +74,14 @@ async fn uquery(
         query_args,
         context,
         target_call_stacks,
+        named_queries,
+        exclude_target_patterns,
+        query_libs,
         ..
     } = request;
 
+    let excluded = ExcludedPatterns::parse(server_ctx, &ctx, exclude_target_patterns).await?;
+
     let global_target_platform = target_platform_from_client_context(
         context.as_ref(),
         &cell_resolver,
@@ -83,7 +93,23 @@ async fn uquery(
         get_uquery_evaluator(&ctx, server_ctx.working_dir(), global_target_platform).await?;
     let evaluator = &evaluator;
 
-    let query_result = evaluator.eval_query(query, query_args).await?;
+    let query_lib = QueryLib::load(server_ctx, &ctx, query_libs).await?;
+    let query_result = if named_queries.is_empty() {
+        evaluator
+            .eval_query(&query_lib.expand(query)?, query_args)
+            .await?
+    } else {
+        evaluator
+            .eval_named_queries(&query_lib.expand_named(named_queries)?)
+            .await?
+    };
+    let query_result = if excluded.is_empty() {
+        query_result
+    } else {
+        filter_query_result(query_result, |node: &TargetNode| {
+            !excluded.is_excluded(node.label())
+        })?
+    };
 
     let mut stdout = server_ctx.stdout()?;
 
@@ -95,6 +121,7 @@ async fn uquery(
                     targets,
                     *target_call_stacks,
                     ShouldPrintProviders::No,
+                    ShouldPrintOutputs::No,
                 )
                 .await
         }
@@ -105,6 +132,7 @@ async fn uquery(
                     results,
                     *target_call_stacks,
                     ShouldPrintProviders::No,
+                    ShouldPrintOutputs::No,
                 )
                 .await
         }
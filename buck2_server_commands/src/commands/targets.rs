@@ -15,6 +15,7 @@ use std::path::Path;
 use anyhow::Context as _;
 use async_trait::async_trait;
 use buck2_build_api::calculation::load_patterns;
+use buck2_build_api::calculation::Calculation;
 use buck2_build_api::nodes::hacks::value_to_json;
 use buck2_build_api::nodes::lookup::ConfiguredTargetNodeLookup;
 use buck2_build_api::nodes::lookup::TargetNodeLookup;
@@ -50,6 +51,7 @@ use gazebo::prelude::*;
 use itertools::Itertools;
 use regex::RegexSet;
 
+use crate::commands::targets_visitor::targets_visitor;
 use crate::json::quote_json_string;
 use crate::target_hash::BuckTargetHash;
 use crate::target_hash::TargetHashes;
@@ -333,7 +335,8 @@ async fn targets(
     )?;
 
     // If we are only asked to resolve aliases, then don't expand any of the patterns, and just
-    // print them out. This expects the aliases to resolve to individual targets.
+    // print them out. This expects the aliases to resolve to individual targets, and prints the
+    // canonical label of the target, following alias rules.
     if request.unstable_resolve_aliases {
         let parsed_target_patterns =
             std::iter::zip(&request.target_patterns, parsed_target_patterns)
@@ -386,7 +389,11 @@ async fn targets(
                 })
                 .with_context(|| format!("Invalid alias: `{}`", alias.value))?;
 
-            writeln!(output, "{}", node.label())?;
+            let label = ctx
+                .canonical_target_label(node.label())
+                .await
+                .with_context(|| format!("Invalid alias: `{}`", alias.value))?;
+            writeln!(output, "{}", label)?;
         }
 
         return Ok(TargetsResponse {
@@ -394,6 +401,10 @@ async fn targets(
         });
     }
 
+    if !request.visitor.is_empty() {
+        return targets_visitor(server_ctx, &ctx, &request.visitor, parsed_target_patterns).await;
+    }
+
     let target_hash_modified_paths = request
         .target_hash_modified_paths
         .iter()
@@ -0,0 +1,213 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! `buck2 targets --visitor=path/to/file.bzl:name`: calls a Starlark function on each target of the
+//! unconfigured graph, for lint or census tools that don't need the rest of BXL.
+
+use std::io::Write;
+
+use anyhow::Context as _;
+use buck2_build_api::calculation::load_patterns;
+use buck2_build_api::nodes::hacks::value_to_json;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_core::bzl::ImportPath;
+use buck2_core::cells::build_file_cell::BuildFileCell;
+use buck2_core::cells::CellResolver;
+use buck2_core::fs::project::ProjectRelativePath;
+use buck2_core::pattern::ParsedPattern;
+use buck2_core::pattern::TargetPattern;
+use buck2_interpreter::parse_import::parse_import_with_config;
+use buck2_interpreter::parse_import::ParseImportOptions;
+use buck2_interpreter_for_build::interpreter::calculation::InterpreterCalculation;
+use buck2_node::attrs::inspect_options::AttrInspectOptions;
+use buck2_node::nodes::unconfigured::TargetNode;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use cli_proto::TargetsResponse;
+use dice::DiceComputations;
+use gazebo::prelude::*;
+use starlark::collections::SmallMap;
+use starlark::environment::Module;
+use starlark::eval::Evaluator;
+use starlark::values::dict::Dict;
+use starlark::values::structs::StructBuilder;
+use starlark::values::Heap;
+use starlark::values::OwnedFrozenValue;
+use starlark::values::Value;
+use thiserror::Error;
+
+use crate::json::quote_json_string;
+
+#[derive(Debug, Error)]
+enum TargetsVisitorError {
+    #[error("Invalid visitor `{0}`, expected `path/to/file.bzl:name`")]
+    InvalidVisitor(String),
+    #[error("Visitor `{0}` is a `{1}`, not a function")]
+    NotCallable(String, String),
+}
+
+/// Resolves `path/to/file.bzl:name` relative to the working directory, like a BXL label.
+fn parse_visitor<'a>(
+    cwd: &ProjectRelativePath,
+    visitor: &'a str,
+    cell_resolver: &CellResolver,
+) -> anyhow::Result<(ImportPath, &'a str)> {
+    let (path, name) = visitor
+        .rsplit_once(':')
+        .ok_or_else(|| TargetsVisitorError::InvalidVisitor(visitor.to_owned()))?;
+    Ok((parse_import_path(cwd, path, cell_resolver)?, name))
+}
+
+/// Resolves the path of a `.bzl` file (e.g. `path/to/file.bzl` or `cell//path:file.bzl`) relative
+/// to the working directory.
+pub(crate) fn parse_import_path(
+    cwd: &ProjectRelativePath,
+    path: &str,
+    cell_resolver: &CellResolver,
+) -> anyhow::Result<ImportPath> {
+    let current_cell = cell_resolver.get_cell_path(cwd)?;
+    let cell_alias_resolver = cell_resolver
+        .get(current_cell.cell())?
+        .cell_alias_resolver();
+
+    const OPTS: ParseImportOptions = ParseImportOptions {
+        allow_missing_at_symbol: true,
+        allow_relative_imports: true,
+    };
+    let path = parse_import_with_config(cell_alias_resolver, &current_cell, path, &OPTS)?;
+    let build_file_cell = BuildFileCell::new(path.cell().clone());
+    ImportPath::new(path, build_file_cell)
+}
+
+fn json_to_starlark<'v>(value: serde_json::Value, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+    Ok(match value {
+        serde_json::Value::Null => Value::new_none(),
+        serde_json::Value::Bool(b) => Value::new_bool(b),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => heap.alloc(i),
+            (None, Some(f)) => heap.alloc(f),
+            (None, None) => heap.alloc(n.to_string()),
+        },
+        serde_json::Value::String(s) => heap.alloc(s),
+        serde_json::Value::Array(values) => {
+            heap.alloc_list_iter(values.into_try_map(|v| json_to_starlark(v, heap))?)
+        }
+        serde_json::Value::Object(entries) => {
+            let mut map = SmallMap::with_capacity(entries.len());
+            for (k, v) in entries {
+                map.insert_hashed(
+                    heap.alloc_str(&k).get_hashed_value(),
+                    json_to_starlark(v, heap)?,
+                );
+            }
+            heap.alloc(Dict::new(map))
+        }
+    })
+}
+
+/// The value the visitor is called with.
+fn target_to_starlark<'v>(node: &TargetNode, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+    let mut attrs = SmallMap::new();
+    for (k, v) in node.attrs(AttrInspectOptions::All) {
+        attrs.insert_hashed(
+            heap.alloc_str(k).get_hashed_value(),
+            json_to_starlark(value_to_json(v)?, heap)?,
+        );
+    }
+
+    let mut target = StructBuilder::new(heap);
+    target.add("label", node.label().to_string());
+    target.add("name", node.label().name().value());
+    target.add("package", node.label().pkg().to_string());
+    target.add("rule_type", node.rule_type().to_string());
+    target.add(
+        "deps",
+        heap.alloc_list_iter(node.deps().map(|d| heap.alloc(d.to_string()))),
+    );
+    target.add("attrs", heap.alloc(Dict::new(attrs)));
+    Ok(heap.alloc(target.build()))
+}
+
+/// Calls the visitor on a target, returning the JSON line to print, if any.
+fn visit(
+    eval: &mut Evaluator,
+    visitor: &OwnedFrozenValue,
+    node: &TargetNode,
+) -> anyhow::Result<Option<String>> {
+    let target = target_to_starlark(node, eval.heap())?;
+    let result = eval.eval_function(visitor.value(), &[target], &[])?;
+    if result.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(format!(
+        "{{\"target\": {}, \"result\": {}}}",
+        quote_json_string(&node.label().to_string()),
+        result.to_json()?
+    )))
+}
+
+pub(crate) async fn targets_visitor(
+    server_ctx: &dyn ServerCommandContextTrait,
+    ctx: &DiceComputations,
+    visitor: &str,
+    parsed_patterns: Vec<ParsedPattern<TargetPattern>>,
+) -> anyhow::Result<TargetsResponse> {
+    let cell_resolver = ctx.get_cell_resolver().await?;
+    let (path, name) = parse_visitor(server_ctx.working_dir(), visitor, &cell_resolver)?;
+    let module = ctx.get_loaded_module_from_import_path(&path).await?;
+    let function = module
+        .env()
+        .get(name)
+        .with_context(|| format!("Error loading visitor `{}`", visitor))?;
+    let typ = function.value().get_type();
+    if typ != "function" {
+        return Err(TargetsVisitorError::NotCallable(visitor.to_owned(), typ.to_owned()).into());
+    }
+
+    let results = load_patterns(ctx, parsed_patterns).await?;
+
+    let mut stdout = server_ctx.stdout()?;
+    let mut error_count = 0;
+    for (package, result) in results.iter() {
+        match result {
+            Ok(res) => {
+                // One module per package, so that the garbage of the calls doesn't pile up.
+                let env = Module::new();
+                let mut eval = Evaluator::new(&env);
+                for (_, node) in res.iter() {
+                    let line = visit(&mut eval, &function, node)
+                        .with_context(|| format!("Error visiting `{}`", node.label()))?;
+                    if let Some(line) = line {
+                        writeln!(stdout, "{}", line)?;
+                    }
+                }
+                stdout.flush()?;
+            }
+            Err(e) => {
+                eprintln!("Error parsing {}", package);
+                eprintln!("{:?}", e.inner());
+                error_count += 1;
+            }
+        }
+    }
+
+    if error_count != 0 {
+        return Err(anyhow::anyhow!(
+            "Failed to parse {} {}",
+            error_count,
+            if error_count == 1 {
+                "package"
+            } else {
+                "packages"
+            }
+        ));
+    }
+    Ok(TargetsResponse {
+        serialized_targets_output: String::new(),
+    })
+}
@@ -11,6 +11,7 @@ use anyhow::Context;
 use buck2_common::file_ops::FileOps;
 use buck2_common::legacy_configs::LegacyBuckConfigs;
 use buck2_common::pattern::resolve::resolve_target_patterns;
+use buck2_common::pattern::resolve::ExcludedTargets;
 use buck2_common::pattern::resolve::ResolvedPattern;
 use buck2_common::target_aliases::BuckConfigTargetAliasResolver;
 use buck2_core::cells::CellInstance;
@@ -19,11 +20,19 @@ use buck2_core::fs::project::ProjectRelativePath;
 use buck2_core::package::Package;
 use buck2_core::pattern::ParsedPattern;
 use buck2_core::pattern::PatternType;
+use buck2_core::pattern::TargetPattern;
 use buck2_core::target::TargetLabel;
+use buck2_events::dispatch::span_async;
 use cli_proto::ClientContext;
 use gazebo::dupe::Dupe;
 use gazebo::prelude::*;
 
+#[derive(Debug, thiserror::Error)]
+enum PatternError {
+    #[error("Excluding targets with `-{0}` is not supported by this command")]
+    ExclusionNotSupported(String),
+}
+
 pub struct PatternParser {
     cell: CellInstance,
     cwd: Package,
@@ -78,7 +87,39 @@ pub fn parse_patterns_from_cli_args<T: PatternType>(
 ) -> anyhow::Result<Vec<ParsedPattern<T>>> {
     let parser = PatternParser::new(cell_resolver, configs, cwd)?;
 
-    target_patterns.try_map(|value| parser.parse_pattern(&value.value))
+    target_patterns.try_map(|value| {
+        if let Some(excluded) = value.value.strip_prefix('-') {
+            return Err(PatternError::ExclusionNotSupported(excluded.to_owned()).into());
+        }
+        parser.parse_pattern(&value.value)
+    })
+}
+
+/// Target patterns passed on the command line, and the patterns prefixed with `-` excluding
+/// targets from them (e.g. `//foo/... -//foo/experimental/...`).
+pub struct PatternsWithExclusions<T: PatternType> {
+    pub patterns: Vec<ParsedPattern<T>>,
+    pub excluded: Vec<ParsedPattern<TargetPattern>>,
+}
+
+/// Like [`parse_patterns_from_cli_args`], for commands supporting exclusions.
+pub fn parse_patterns_with_exclusions_from_cli_args<T: PatternType>(
+    target_patterns: &[buck2_data::TargetPattern],
+    cell_resolver: &CellResolver,
+    configs: &LegacyBuckConfigs,
+    cwd: &ProjectRelativePath,
+) -> anyhow::Result<PatternsWithExclusions<T>> {
+    let parser = PatternParser::new(cell_resolver, configs, cwd)?;
+
+    let mut patterns = Vec::new();
+    let mut excluded = Vec::new();
+    for value in target_patterns {
+        match value.value.strip_prefix('-') {
+            Some(pattern) => excluded.push(parser.parse_pattern(pattern)?),
+            None => patterns.push(parser.parse_pattern(&value.value)?),
+        }
+    }
+    Ok(PatternsWithExclusions { patterns, excluded })
 }
 
 pub async fn resolve_patterns<T: PatternType>(
@@ -86,7 +127,43 @@ pub async fn resolve_patterns<T: PatternType>(
     cell_resolver: &CellResolver,
     file_ops: &dyn FileOps,
 ) -> anyhow::Result<ResolvedPattern<T>> {
-    resolve_target_patterns(cell_resolver, patterns.iter(), file_ops).await
+    span_async(buck2_data::ResolveTargetPatternsStart {}, async {
+        (
+            resolve_target_patterns(cell_resolver, patterns.iter(), file_ops).await,
+            buck2_data::ResolveTargetPatternsEnd {},
+        )
+    })
+    .await
+}
+
+/// Resolves patterns, then removes the packages and targets matched by the exclusions. Targets
+/// excluded from packages included as a whole are returned, to be filtered out once the packages
+/// are loaded.
+pub async fn resolve_patterns_with_exclusions<T: PatternType>(
+    patterns: &PatternsWithExclusions<T>,
+    cell_resolver: &CellResolver,
+    file_ops: &dyn FileOps,
+) -> anyhow::Result<(ResolvedPattern<T>, ExcludedTargets)> {
+    async fn resolve<T: PatternType>(
+        patterns: &PatternsWithExclusions<T>,
+        cell_resolver: &CellResolver,
+        file_ops: &dyn FileOps,
+    ) -> anyhow::Result<(ResolvedPattern<T>, ExcludedTargets)> {
+        let mut resolved =
+            resolve_target_patterns(cell_resolver, patterns.patterns.iter(), file_ops).await?;
+        let excluded =
+            resolve_target_patterns(cell_resolver, patterns.excluded.iter(), file_ops).await?;
+        let excluded_targets = resolved.exclude(excluded);
+        Ok((resolved, excluded_targets))
+    }
+
+    span_async(buck2_data::ResolveTargetPatternsStart {}, async {
+        (
+            resolve(patterns, cell_resolver, file_ops).await,
+            buck2_data::ResolveTargetPatternsEnd {},
+        )
+    })
+    .await
 }
 
 /// Extract target configuration (platform) label from [`ClientContext`].
@@ -0,0 +1,374 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Shell completion.
+//!
+//! `buck2 completion <shell>` prints a script which completes by calling `buck2 complete` with
+//! the words of the command line. Subcommands and flags are completed from the definition of the
+//! commands. Other words are completed as target labels: packages come from the filesystem, and
+//! target names from the daemon, if one is running, with a short timeout so that loading a
+//! package that isn't cached doesn't hang the shell.
+
+use std::time::Duration;
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::daemon::client::connect::BuckdConnectOptions;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_common::legacy_configs::cells::BuckConfigBasedCells;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRelativePath;
+use buck2_core::fs::project::ProjectRelativePathBuf;
+use cli_proto::TargetsRequest;
+
+const BASH_SCRIPT: &str = r##"_buck2() {
+    local line="${COMP_LINE:0:COMP_POINT}"
+    local -a words
+    read -ra words <<< "$line"
+    [[ "$line" == *[[:space:]] ]] && words+=("")
+    local cur="${words[${#words[@]}-1]}"
+    local IFS=$'\n'
+    COMPREPLY=($(buck2 complete -- "${words[@]:1}" 2>/dev/null))
+    # Bash splits words on colons, so only what follows the last one is replaced.
+    if [[ "$cur" == *:* && "$COMP_WORDBREAKS" == *:* ]]; then
+        local colon_prefix="${cur%"${cur##*:}"}"
+        COMPREPLY=("${COMPREPLY[@]#"$colon_prefix"}")
+    fi
+    if [[ ${#COMPREPLY[@]} -eq 1 && ( "${COMPREPLY[0]}" == */ || "${COMPREPLY[0]}" == *: ) ]]; then
+        compopt -o nospace
+    fi
+}
+complete -F _buck2 buck2
+"##;
+
+const ZSH_SCRIPT: &str = r##"#compdef buck2
+_buck2() {
+    local -a candidates partial whole
+    local c
+    candidates=("${(@f)$(buck2 complete -- "${(@)words[2,CURRENT]}" 2>/dev/null)}")
+    for c in "${candidates[@]}"; do
+        [[ -z "$c" ]] && continue
+        if [[ "$c" == */ || "$c" == *: ]]; then
+            partial+=("$c")
+        else
+            whole+=("$c")
+        fi
+    done
+    compadd -S '' -- "${partial[@]}"
+    compadd -- "${whole[@]}"
+}
+compdef _buck2 buck2
+"##;
+
+const FISH_SCRIPT: &str = r##"function __buck2_complete
+    set -l words (commandline -opc) (commandline -ct)
+    buck2 complete -- $words[2..-1] 2>/dev/null
+end
+complete -c buck2 -f -a '(__buck2_complete)'
+"##;
+
+/// How long to wait for the daemon to list the targets of a package.
+const TARGETS_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, clap::ArgEnum)]
+#[clap(rename_all = "snake_case")]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Debug, clap::Parser)]
+#[clap(
+    about = "Print a shell completion script",
+    long_about = "Print a shell completion script, which completes subcommands, flags and target labels.\n\n\
+    For example, add `source <(buck2 completion bash)` to `.bashrc`."
+)]
+pub(crate) struct CompletionCommand {
+    #[clap(arg_enum)]
+    shell: Shell,
+}
+
+impl CompletionCommand {
+    pub(crate) fn exec(&self) -> ExitResult {
+        let script = match self.shell {
+            Shell::Bash => BASH_SCRIPT,
+            Shell::Zsh => ZSH_SCRIPT,
+            Shell::Fish => FISH_SCRIPT,
+        };
+        buck2_client_ctx::print!("{}", script)?;
+        ExitResult::success()
+    }
+}
+
+#[derive(Debug, clap::Parser)]
+#[clap(about = "Complete a command line, used by the scripts of `buck2 completion`")]
+pub(crate) struct CompleteCommand {
+    /// The words of the command line after `buck2`, the last one being the one to complete.
+    #[clap(last = true)]
+    words: Vec<String>,
+}
+
+/// What the word being completed is.
+#[derive(Debug, PartialEq)]
+enum Completion {
+    /// Subcommands or flags, which don't need the project.
+    Candidates(Vec<String>),
+    /// A target label.
+    Label(String),
+}
+
+/// Completes from the definition of the commands: flags when the word starts with `-`,
+/// subcommands if the command has any, and target labels for the positional arguments of other
+/// commands.
+fn complete_command(app: &clap::Command, words: &[String]) -> Completion {
+    let (cur, previous) = match words.split_last() {
+        Some((cur, previous)) => (cur.as_str(), previous),
+        None => ("", &[][..]),
+    };
+
+    let mut cmd = app;
+    for word in previous {
+        if let Some(sub) = cmd
+            .get_subcommands()
+            .find(|sub| sub.get_name() == word || sub.get_all_aliases().any(|alias| alias == word))
+        {
+            cmd = sub;
+        }
+    }
+
+    let mut candidates: Vec<String> = if cur.starts_with('-') {
+        cmd.get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .flat_map(|arg| {
+                arg.get_long()
+                    .map(|long| format!("--{}", long))
+                    .into_iter()
+                    .chain(arg.get_short().map(|short| format!("-{}", short)))
+            })
+            .filter(|flag| flag.starts_with(cur))
+            .collect()
+    } else if cmd.has_subcommands() {
+        cmd.get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .map(|sub| sub.get_name().to_owned())
+            .filter(|name| name.starts_with(cur))
+            .collect()
+    } else if cmd.get_positionals().next().is_some() {
+        return Completion::Label(cur.to_owned());
+    } else {
+        Vec::new()
+    };
+    candidates.sort();
+    candidates.dedup();
+    Completion::Candidates(candidates)
+}
+
+/// The packages and directories under the directory of the partial label `cur`, which has no
+/// colon. `dir` is that directory, and `prefix` is what follows its last slash.
+fn complete_packages(
+    ctx: &ClientCommandContext,
+    cells: &BuckConfigBasedCells,
+    cur: &str,
+    dir: &ProjectRelativePath,
+    prefix: &str,
+) -> anyhow::Result<Vec<String>> {
+    let typed = &cur[..cur.len() - prefix.len()];
+    let project_root = ctx.paths.project_root();
+    let mut res = Vec::new();
+    for entry in fs_util::read_dir(project_root.resolve(dir))? {
+        let entry = entry?;
+        let name = match entry.file_name().to_str() {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        if !name.starts_with(prefix)
+            || (name.starts_with('.') && !prefix.starts_with('.'))
+            || name == "buck-out"
+            || !entry.file_type()?.is_dir()
+        {
+            continue;
+        }
+
+        let package = dir.join(ForwardRelativePath::new(&name)?);
+        let buildfiles = cells
+            .cell_resolver
+            .get(cells.cell_resolver.find(&package)?)?
+            .buildfiles();
+        let mut has_buildfile = false;
+        let mut has_subdirs = false;
+        for child in fs_util::read_dir(project_root.resolve(&package))? {
+            let child = child?;
+            if child.file_type()?.is_dir() {
+                has_subdirs = true;
+            } else if buildfiles
+                .iter()
+                .any(|b| child.file_name().to_str() == Some(b.as_str()))
+            {
+                has_buildfile = true;
+            }
+        }
+        if has_buildfile {
+            res.push(format!("{}{}:", typed, name));
+        }
+        if has_subdirs {
+            res.push(format!("{}{}/", typed, name));
+        }
+    }
+    Ok(res)
+}
+
+/// The targets of `package` whose name starts with `prefix`, if the daemon lists them quickly.
+async fn complete_targets(
+    ctx: &ClientCommandContext,
+    package: &str,
+    prefix: &str,
+) -> anyhow::Result<Vec<String>> {
+    let request = TargetsRequest {
+        context: Some(ctx.empty_client_context()?),
+        target_patterns: vec![buck2_data::TargetPattern {
+            value: format!("{}:", package),
+        }],
+        ..Default::default()
+    };
+    let response = tokio::time::timeout(TARGETS_TIMEOUT, async {
+        // Don't start a daemon just to complete.
+        let mut buckd = ctx
+            .connect_buckd(BuckdConnectOptions::existing_only_no_console())
+            .await?;
+        buckd.with_flushing().targets(request, None).await
+    })
+    .await;
+
+    let output = match response {
+        Ok(Ok(buck2_client_ctx::command_outcome::CommandOutcome::Success(response))) => {
+            response.serialized_targets_output
+        }
+        _ => return Ok(Vec::new()),
+    };
+    Ok(output
+        .lines()
+        .filter_map(|line| line.split_whitespace().next()?.rsplit_once(':'))
+        .map(|(_, name)| name)
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| format!("{}:{}", package, name))
+        .collect())
+}
+
+impl CompleteCommand {
+    /// The completions which don't need the project, if the word isn't a target label.
+    pub(crate) fn complete_without_project(&self, app: &clap::Command) -> Option<Vec<String>> {
+        match complete_command(app, &self.words) {
+            Completion::Candidates(candidates) => Some(candidates),
+            Completion::Label(..) => None,
+        }
+    }
+
+    pub(crate) fn print(candidates: &[String]) -> ExitResult {
+        for candidate in candidates {
+            buck2_client_ctx::println!("{}", candidate)?;
+        }
+        ExitResult::success()
+    }
+
+    pub(crate) fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext) -> ExitResult {
+        let cur = self.words.last().map_or("", |x| x.as_str()).to_owned();
+        let candidates = ctx.with_runtime(async move |ctx| -> anyhow::Result<Vec<String>> {
+            let cells = BuckConfigBasedCells::parse(ctx.paths.project_root())?;
+            let cwd = ctx
+                .paths
+                .project_root()
+                .relativize(ctx.working_dir.path())?
+                .into_owned();
+
+            let (base, path): (ProjectRelativePathBuf, &str) = match cur.split_once("//") {
+                Some((alias, path)) => {
+                    let cwd_cell = cells.cell_resolver.find(&cwd)?;
+                    let cell = cells
+                        .cell_resolver
+                        .get(cwd_cell)?
+                        .cell_alias_resolver()
+                        .resolve(alias)?;
+                    let root = cells.cell_resolver.get(cell)?.path();
+                    (root.as_project_relative_path().to_buf(), path)
+                }
+                None => (cwd, cur.as_str()),
+            };
+
+            match path.rsplit_once(':') {
+                Some((_, prefix)) => {
+                    let package = &cur[..cur.len() - prefix.len() - 1];
+                    complete_targets(&ctx, package, prefix).await
+                }
+                None => {
+                    let (dir, prefix) = match path.rsplit_once('/') {
+                        Some((dir, prefix)) => (base.join(ForwardRelativePath::new(dir)?), prefix),
+                        None => (base, path),
+                    };
+                    complete_packages(&ctx, &cells, &cur, &dir, prefix)
+                }
+            }
+        });
+        // Completing nothing is better than printing errors in the middle of the command line.
+        let mut candidates = candidates.unwrap_or_default();
+        candidates.sort();
+        Self::print(&candidates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, clap::Parser)]
+    enum TestCommand {
+        Build {
+            #[clap(long)]
+            show_output: bool,
+            #[clap(short, long)]
+            num_threads: Option<u32>,
+            patterns: Vec<String>,
+        },
+        #[clap(subcommand)]
+        Audit(TestAudit),
+        Kill,
+    }
+
+    #[derive(Debug, clap::Subcommand)]
+    enum TestAudit {
+        Cell,
+        Config,
+    }
+
+    fn complete(words: &[&str]) -> Completion {
+        use clap::CommandFactory;
+        let words: Vec<String> = words.iter().map(|w| (*w).to_owned()).collect();
+        complete_command(&TestCommand::command(), &words)
+    }
+
+    fn candidates(xs: &[&str]) -> Completion {
+        Completion::Candidates(xs.iter().map(|x| (*x).to_owned()).collect())
+    }
+
+    #[test]
+    fn test_complete_command() {
+        assert_eq!(candidates(&["audit", "build", "kill"]), complete(&[""]));
+        assert_eq!(candidates(&["build"]), complete(&["bu"]));
+        assert_eq!(candidates(&["cell", "config"]), complete(&["audit", "c"]));
+        assert_eq!(
+            candidates(&["--num-threads", "--show-output", "-n"]),
+            complete(&["build", "-"])
+        );
+        assert_eq!(
+            Completion::Label("//foo".to_owned()),
+            complete(&["build", "--show-output", "//foo"])
+        );
+        assert_eq!(candidates(&[]), complete(&["kill", ""]));
+    }
+}
@@ -38,6 +38,7 @@ use buck2_server::daemon::server::BuckdServer;
 use buck2_server::daemon::server::BuckdServerDelegate;
 use buck2_server::daemon::server::BuckdServerDependencies;
 use buck2_server::profile::profile_command;
+use buck2_server_commands::commands::action_exec::action_exec_command;
 use buck2_server_commands::commands::build::build_command;
 use buck2_server_commands::commands::install::install_command;
 use buck2_server_commands::commands::query::aquery::aquery_command;
@@ -176,6 +177,13 @@ impl BuckdServerDependencies for BuckdServerDependenciesImpl {
     ) -> anyhow::Result<cli_proto::AqueryResponse> {
         aquery_command(ctx, req).await
     }
+    async fn action_exec(
+        &self,
+        ctx: Box<dyn ServerCommandContextTrait>,
+        req: cli_proto::ActionExecRequest,
+    ) -> anyhow::Result<cli_proto::ActionExecResponse> {
+        action_exec_command(ctx, req).await
+    }
     async fn targets(
         &self,
         ctx: Box<dyn ServerCommandContextTrait>,
@@ -568,7 +576,10 @@ mod tests {
     use buck2_server::daemon::daemon_tcp::create_listener;
     use buck2_server::daemon::server::BuckdServer;
     use buck2_server::daemon::server::BuckdServerDelegate;
+    use cli_proto::command_result;
+    use cli_proto::protocol;
     use cli_proto::DaemonProcessInfo;
+    use cli_proto::HandshakeRequest;
     use cli_proto::KillRequest;
     use cli_proto::PingRequest;
 
@@ -627,6 +638,37 @@ mod tests {
 
         client.ping(PingRequest::default()).await.unwrap();
 
+        let handshake = client
+            .handshake(HandshakeRequest {
+                protocol_version: protocol::PROTOCOL_VERSION,
+                capabilities: vec!["build".to_owned(), "unknown".to_owned()],
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        match handshake.result {
+            Some(command_result::Result::HandshakeResponse(response)) => {
+                assert_eq!(protocol::PROTOCOL_VERSION, response.protocol_version);
+                assert_eq!(vec!["build".to_owned()], response.capabilities);
+                assert_eq!("13.17.19", response.daemon_version);
+            }
+            result => panic!("Unexpected handshake result: {:?}", result),
+        }
+
+        let handshake = client
+            .handshake(HandshakeRequest {
+                protocol_version: protocol::PROTOCOL_VERSION + 1,
+                capabilities: Vec::new(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(
+            matches!(handshake.result, Some(command_result::Result::Error(_))),
+            "Unexpected handshake result: {:?}",
+            handshake.result
+        );
+
         let mut client_with_wrong_token = new_daemon_api_client(endpoint, "wrong_token".to_owned())
             .await
             .unwrap();
@@ -27,12 +27,14 @@ use std::thread;
 use anyhow::Context as _;
 use buck2_audit::AuditCommand;
 use buck2_client::args::expand_argfiles;
+use buck2_client::args::rewrite_exclusion_patterns;
 use buck2_client::commands::aquery::AqueryCommand;
 use buck2_client::commands::build::BuildCommand;
 use buck2_client::commands::bxl::BxlCommand;
 use buck2_client::commands::clean::CleanCommand;
 use buck2_client::commands::cquery::CqueryCommand;
 use buck2_client::commands::debug::DebugCommand;
+use buck2_client::commands::doctor::DoctorCommand;
 use buck2_client::commands::init::InitCommand;
 use buck2_client::commands::install::InstallCommand;
 use buck2_client::commands::kill::KillCommand;
@@ -50,21 +52,27 @@ use buck2_client::commands::uquery::UqueryCommand;
 use buck2_client_ctx::client_ctx::ClientCommandContext;
 use buck2_client_ctx::client_ctx::ProcessContext;
 use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::immediate_config::ImmediateConfig;
 use buck2_client_ctx::replayer::Replayer;
 use buck2_client_ctx::streaming::BuckSubcommand;
 use buck2_client_ctx::verbosity::Verbosity;
 use buck2_client_ctx::version::BuckVersion;
+use buck2_client_ctx::version_required::check_version_required;
 use buck2_common::invocation_paths::InvocationPaths;
 use buck2_common::invocation_roots::find_invocation_roots;
 use buck2_core::env_helper::EnvHelper;
 use buck2_core::fs::paths::file_name::FileNameBuf;
 use buck2_core::fs::working_dir::WorkingDir;
+use buck2_events::trace::TraceId;
 use clap::AppSettings;
 use clap::Parser;
 use dice::cycles::DetectCycles;
+use gazebo::dupe::Dupe;
 use gazebo::variants::VariantName;
 
 use crate::check_user_allowed::check_user_allowed;
+use crate::commands::completion::CompleteCommand;
+use crate::commands::completion::CompletionCommand;
 use crate::commands::daemon::DaemonCommand;
 use crate::commands::docs::DocsCommand;
 use crate::commands::forkserver::ForkserverCommand;
@@ -115,6 +123,21 @@ pub(crate) struct CommonOptions {
     /// This is an unsupported option used only for development work.
     #[clap(long, global(true))]
     no_buckd: bool,
+
+    /// The trace ID of this invocation, which names its event log. Must be a UUID. Defaults to a
+    /// new ID.
+    #[clap(long, global(true), env("BUCK_WRAPPER_UUID"), value_name = "UUID")]
+    trace_id: Option<TraceId>,
+
+    /// The trace ID of the invocation which started this one, to link their event logs. Set for
+    /// actions run locally, so nested buck2 invocations are linked to the build running them.
+    #[clap(long, global(true), env("BUCK2_PARENT_TRACE_ID"), value_name = "UUID")]
+    parent_trace_id: Option<TraceId>,
+
+    /// Passed by the client when it re-runs a command after restarting the daemon, with the
+    /// reason of the restart. Commands are only retried once.
+    #[clap(long, global(true), hidden(true), value_name = "REASON")]
+    restarted_after: Option<String>,
 }
 
 #[derive(Debug, clap::Parser)]
@@ -159,8 +182,8 @@ pub fn exec(
     init: fbinit::FacebookInit,
     replay: Option<(ProcessContext, Replayer)>,
 ) -> ExitResult {
-    let mut expanded_args =
-        expand_argfiles(args, &working_dir).context("Error expanding argsfiles")?;
+    let expanded_args = expand_argfiles(args, &working_dir).context("Error expanding argsfiles")?;
+    let mut expanded_args = rewrite_exclusion_patterns(&Opt::clap(), expanded_args);
 
     // Override arg0 in `buck2 help`.
     static BUCK2_ARG0: EnvHelper<String> = EnvHelper::new("BUCK2_ARG0");
@@ -173,7 +196,10 @@ pub fn exec(
     let opt: Opt = Opt::from_clap(&matches);
 
     match &opt.cmd {
-        CommandKind::Clean(..) | CommandKind::Daemon(..) | CommandKind::Forkserver(..) => {}
+        CommandKind::Clean(..)
+        | CommandKind::Daemon(..)
+        | CommandKind::Forkserver(..)
+        | CommandKind::Completion(..) => {}
         _ => {
             check_user_allowed()?;
         }
@@ -208,6 +234,7 @@ pub(crate) enum CommandKind {
     #[clap(subcommand, setting(AppSettings::Hidden))]
     Debug(DebugCommand),
     Docs(DocsCommand),
+    Doctor(DoctorCommand),
     #[clap(subcommand)]
     Profile(ProfileCommand),
     Rage(RageCommand),
@@ -215,6 +242,9 @@ pub(crate) enum CommandKind {
     #[clap(subcommand)]
     Log(LogCommand),
     Lsp(LspCommand),
+    Completion(CompletionCommand),
+    #[clap(setting(AppSettings::Hidden))]
+    Complete(CompleteCommand),
 }
 
 impl CommandKind {
@@ -231,7 +261,31 @@ impl CommandKind {
         init: fbinit::FacebookInit,
         replay: Option<(ProcessContext, Replayer)>,
     ) -> ExitResult {
+        // Completion scripts are used outside of projects too, and completing subcommands and
+        // flags doesn't need one.
+        match &self {
+            CommandKind::Completion(cmd) => return cmd.exec(),
+            CommandKind::Complete(cmd) => {
+                if let Some(candidates) = cmd.complete_without_project(&Opt::clap()) {
+                    return CompleteCommand::print(&candidates);
+                }
+            }
+            _ => {}
+        }
+
         let roots = find_invocation_roots(working_dir.path())?;
+        let immediate_config = ImmediateConfig::new(roots.project_root.dupe());
+
+        match &self {
+            // Still work with a binary too old for the project, e.g. to kill its daemon.
+            CommandKind::Daemon(..)
+            | CommandKind::Forkserver(..)
+            | CommandKind::Kill(..)
+            | CommandKind::Status(..)
+            | CommandKind::Doctor(..) => {}
+            _ => check_version_required(&immediate_config)?,
+        }
+
         let paths = InvocationPaths {
             roots,
             isolation: common_opts.isolation_dir,
@@ -309,6 +363,10 @@ impl CommandKind {
             command_name: self.command_name(),
             working_dir,
             sanitized_argv: Vec::new(),
+            trace_id: common_opts.trace_id.unwrap_or_else(TraceId::new),
+            parent_trace_id: common_opts.parent_trace_id,
+            restarted_after: common_opts.restarted_after,
+            immediate_config,
         };
 
         match self {
@@ -336,12 +394,15 @@ impl CommandKind {
             CommandKind::Uquery(cmd) => cmd.exec(matches, command_ctx),
             CommandKind::Debug(cmd) => cmd.exec(matches, command_ctx, exec),
             CommandKind::Docs(cmd) => cmd.exec(matches, command_ctx),
+            CommandKind::Doctor(cmd) => cmd.exec(matches, command_ctx),
             CommandKind::Profile(cmd) => cmd.exec(matches, command_ctx),
             CommandKind::Rage(cmd) => cmd.exec(matches, command_ctx),
             CommandKind::Init(cmd) => cmd.exec(matches, command_ctx),
             CommandKind::Install(cmd) => cmd.exec(matches, command_ctx),
             CommandKind::Log(cmd) => cmd.exec(matches, command_ctx),
             CommandKind::Lsp(cmd) => cmd.exec(matches, command_ctx),
+            CommandKind::Completion(..) => unreachable!("Checked earlier"),
+            CommandKind::Complete(cmd) => cmd.exec(matches, command_ctx),
         }
     }
 }
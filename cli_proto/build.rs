@@ -8,7 +8,9 @@
  */
 
 use std::env;
+use std::fs;
 use std::io;
+use std::path::Path;
 use std::path::PathBuf;
 
 fn main() -> io::Result<()> {
@@ -30,5 +32,19 @@ fn main() -> io::Result<()> {
         .field_attribute("ProfileResponse.elapsed", "#[serde(with = \"serialize_duration\")]")
 
         .extern_path(".buck.data", "::buck2_data")
-        .compile(proto_files, &[".", &events_include])
+        .compile(proto_files, &[".", &events_include])?;
+
+    // Publish the definitions next to the generated code, see `cli_proto::protocol`.
+    // Buck likes to set $OUT in a genrule, while Cargo likes to set $OUT_DIR.
+    let out_dir = PathBuf::from(
+        env::var_os("OUT_DIR")
+            .or_else(|| env::var_os("OUT"))
+            .unwrap(),
+    );
+    fs::copy("daemon.proto", out_dir.join("daemon.proto"))?;
+    fs::copy(
+        Path::new(&events_include).join("data.proto"),
+        out_dir.join("data.proto"),
+    )?;
+    Ok(())
 }
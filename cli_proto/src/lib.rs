@@ -10,6 +10,8 @@
 #![feature(min_specialization)]
 #![allow(clippy::large_enum_variant)]
 
+pub mod protocol;
+
 use thiserror::Error;
 
 use crate::BuckDaemonProtoError::MissingClientContext;
@@ -236,6 +238,7 @@ mod serialize_duration {
 result_convert!(AqueryResponse);
 result_convert!(KillResponse);
 result_convert!(PingResponse);
+result_convert!(HandshakeResponse);
 result_convert!(StatusResponse);
 result_convert!(BuildResponse);
 result_convert!(BxlResponse);
@@ -252,10 +255,13 @@ result_convert!(MaterializeResponse);
 result_convert!(CleanStaleResponse);
 result_convert!(LspResponse);
 result_convert!(AllocativeResponse);
+result_convert!(ActionExecResponse);
+result_convert!(ImportDigestsResponse);
 
 define_request!(KillRequest);
 define_request!(StatusRequest);
 define_request!(PingRequest);
+define_request!(HandshakeRequest);
 
 define_request!(BuildRequest, has(context, build_options));
 define_request!(BxlRequest, has(context, build_options));
@@ -270,5 +276,6 @@ define_request!(ProfileRequest, has(context));
 define_request!(MaterializeRequest, has(context));
 define_request!(AllocativeRequest, has(context));
 define_request!(CleanStaleRequest, has(context));
+define_request!(ActionExecRequest, has(context));
 
 define_request!(InstallRequest, has(context, build_options));
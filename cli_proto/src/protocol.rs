@@ -0,0 +1,94 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Versioning of the daemon protocol, for clients which are not built with the daemon.
+//!
+//! Such clients (IDE plugins, dashboards...) call `Handshake` before other requests. The
+//! protocol version is bumped on changes breaking existing clients, e.g. removing a request or
+//! changing the meaning of a field, and the daemon refuses clients speaking another version.
+//! Additions which clients can ignore don't bump it: the ones clients may want to use are
+//! listed as capabilities instead, and the daemon returns which of the capabilities requested
+//! by the client it has.
+
+/// The version of the protocol spoken by this daemon.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The capabilities of this daemon. The names of the commands mean their requests are served,
+/// and names are never reused for something else once removed.
+pub const CAPABILITIES: &[&str] = &[
+    "aquery",
+    "audit",
+    "build",
+    "bxl",
+    "clean_stale",
+    "cquery",
+    "cquery.named_queries",
+    "install",
+    "lsp",
+    "materialize",
+    "targets",
+    "test",
+    "uquery",
+];
+
+/// The definition of the protocol, `daemon.proto`.
+pub const DAEMON_PROTO: &str = include_str!(concat!(env!("OUT_DIR"), "/daemon.proto"));
+
+/// The definition of the events streamed by the daemon, `data.proto`, imported by
+/// `daemon.proto`.
+pub const DATA_PROTO: &str = include_str!(concat!(env!("OUT_DIR"), "/data.proto"));
+
+/// The capabilities in `requested` which this daemon has.
+pub fn supported_capabilities(requested: &[String]) -> Vec<String> {
+    requested
+        .iter()
+        .filter(|c| CAPABILITIES.contains(&c.as_str()))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::CAPABILITIES;
+    use crate::protocol::DAEMON_PROTO;
+
+    /// The names of the requests of `DaemonApi` in `daemon.proto`, in snake case.
+    fn daemon_api_requests() -> Vec<String> {
+        DAEMON_PROTO
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("rpc "))
+            .map(|rpc| {
+                let name = rpc.split('(').next().unwrap().trim();
+                let mut snake = String::new();
+                for (i, c) in name.chars().enumerate() {
+                    if c.is_ascii_uppercase() && i != 0 && !snake.ends_with('_') {
+                        snake.push('_');
+                    }
+                    snake.push(c.to_ascii_lowercase());
+                }
+                snake
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_capabilities_are_requests() {
+        let requests = daemon_api_requests();
+        for capability in CAPABILITIES {
+            // Capabilities of a request are named after it, e.g. `cquery.named_queries`.
+            let request = capability.split('.').next().unwrap();
+            assert!(
+                requests.iter().any(|r| r == request),
+                "`{}` is not a request of `DaemonApi`: {:?}",
+                capability,
+                requests
+            );
+        }
+    }
+}
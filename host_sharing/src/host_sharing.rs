@@ -7,9 +7,15 @@
  * of this source tree.
  */
 
-use futures_intrusive::sync::SharedSemaphore;
+use std::path::PathBuf;
+
 use futures_intrusive::sync::SharedSemaphoreReleaser;
+use once_cell::sync::OnceCell;
 
+use crate::priority_semaphore::Priority;
+use crate::priority_semaphore::PrioritySemaphore;
+use crate::priority_semaphore::PrioritySemaphoreGuard;
+use crate::JobServer;
 use crate::NamedSemaphores;
 
 const SINGLE_RUN: usize = 1;
@@ -54,15 +60,17 @@ impl Default for HostSharingRequirements {
 /// Keeps the data structures received from semaphores after acquiring.
 /// Semaphores are held until this struct is dropped.
 pub struct HostSharingGuard {
-    _run_guard: SharedSemaphoreReleaser,
+    _run_guard: PrioritySemaphoreGuard,
     _name_guard: Option<SharedSemaphoreReleaser>,
 }
 
 /// Used to ensure that host resources are properly reserved before executing a command spec.
 pub struct HostSharingBroker {
-    permits: SharedSemaphore,
+    permits: PrioritySemaphore,
     num_machine_permits: usize,
     named_semaphores: NamedSemaphores,
+    /// Where to create the jobserver, and the jobserver once a command asked for it.
+    jobserver: Option<(PathBuf, OnceCell<JobServer>)>,
 }
 
 impl HostSharingBroker {
@@ -78,9 +86,9 @@ impl HostSharingBroker {
 
     pub fn new(host_sharing_strategy: HostSharingStrategy, num_machine_permits: usize) -> Self {
         let permits = match host_sharing_strategy {
-            HostSharingStrategy::Fifo => SharedSemaphore::new(true, num_machine_permits),
+            HostSharingStrategy::Fifo => PrioritySemaphore::new(true, num_machine_permits),
             HostSharingStrategy::SmallerTasksFirst => {
-                SharedSemaphore::new(false, num_machine_permits)
+                PrioritySemaphore::new(false, num_machine_permits)
             }
         };
 
@@ -88,6 +96,7 @@ impl HostSharingBroker {
             permits,
             num_machine_permits,
             named_semaphores: NamedSemaphores::new(),
+            jobserver: None,
         }
     }
 
@@ -95,21 +104,47 @@ impl HostSharingBroker {
         self.num_machine_permits
     }
 
+    /// Lets build systems run by commands borrow permits through a jobserver at `path`, see
+    /// `JobServer`. It is only created once a command asks for it, since free permits then go
+    /// through its pipe.
+    pub fn enable_jobserver(&mut self, path: PathBuf) {
+        self.jobserver = Some((path, OnceCell::new()));
+    }
+
+    /// The jobserver, creating it if this is the first command using it. `None` if it's not
+    /// enabled. Must be called from a tokio runtime.
+    pub fn jobserver(&self) -> anyhow::Result<Option<&JobServer>> {
+        match &self.jobserver {
+            Some((path, jobserver)) => {
+                Ok(Some(jobserver.get_or_try_init(|| {
+                    JobServer::new(self.permits.clone(), path.clone())
+                })?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Waits for the resources a command needs. Commands with a higher `priority` go ahead of
+    /// others waiting for permits.
     pub async fn acquire(
         &self,
         host_sharing_requirements: &HostSharingRequirements,
+        priority: Priority,
     ) -> HostSharingGuard {
         match host_sharing_requirements {
             HostSharingRequirements::Shared(weight_class) => {
                 let permits = self.requested_permits(weight_class);
-                let _run_guard = self.permits.acquire(permits).await;
+                let _run_guard = self.permits.acquire(permits, priority).await;
                 HostSharingGuard {
                     _run_guard,
                     _name_guard: None,
                 }
             }
             HostSharingRequirements::ExclusiveAccess => {
-                let _run_guard = self.permits.acquire(self.num_machine_permits).await;
+                let _run_guard = self
+                    .permits
+                    .acquire(self.num_machine_permits, priority)
+                    .await;
                 HostSharingGuard {
                     _run_guard,
                     _name_guard: None,
@@ -123,7 +158,7 @@ impl HostSharingBroker {
                 let run_semaphore = self.named_semaphores.get(identifier);
                 let _name_guard = Some(run_semaphore.acquire(SINGLE_RUN).await);
                 let permits = self.requested_permits(weight_class);
-                let _run_guard = self.permits.acquire(permits).await;
+                let _run_guard = self.permits.acquire(permits, priority).await;
                 HostSharingGuard {
                     _run_guard,
                     _name_guard,
@@ -133,7 +168,8 @@ impl HostSharingBroker {
     }
 }
 
-/// Determines whether a fair or unfair semaphore is used to manage host sharing
+/// Determines whether a fair or unfair semaphore is used to manage host sharing. Either way,
+/// commands waiting for permits are served by priority first.
 pub enum HostSharingStrategy {
     SmallerTasksFirst,
     Fifo,
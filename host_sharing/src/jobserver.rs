@@ -0,0 +1,309 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A GNU make compatible jobserver backed by the permits of a `HostSharingBroker`, so that build
+//! systems run by actions (make, cargo, ninja) take their extra jobs from the same pool as local
+//! actions instead of oversubscribing the host.
+//!
+//! The jobserver is a named pipe holding one byte per free job slot: clients read a byte to take a
+//! slot, and write it back when they are done. A client always owns one implicit slot, which for
+//! an action is the permit it runs with. The permits nobody is waiting for are moved to the pipe as
+//! they are released. When an action starts waiting for permits, the idle slots in the pipe are
+//! read back and their permits released, and so are the slots clients return while actions are
+//! waiting. So the jobserver never holds a permit an action is waiting for, and clients only get
+//! extra slots when permits are free.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use tokio::task::JoinHandle;
+
+use crate::priority_semaphore::PrioritySemaphore;
+
+/// The byte GNU make uses for its job slots. Clients are expected to write back the byte they read.
+const TOKEN: u8 = b'+';
+
+pub struct JobServer {
+    path: PathBuf,
+    lender: JoinHandle<()>,
+}
+
+impl JobServer {
+    /// Creates the named pipe at `path` and starts lending `permits` to it. Must be called from a
+    /// tokio runtime.
+    pub(crate) fn new(permits: PrioritySemaphore, path: PathBuf) -> anyhow::Result<Self> {
+        let pipe = imp::Pipe::create(&path)?;
+        let lender = tokio::spawn(async move {
+            // The lender only stops when the pipe is broken, clients then can't get more slots.
+            let _ignored = lend(permits, pipe).await;
+        });
+        Ok(Self { path, lender })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The value of `MAKEFLAGS` for clients of this jobserver. The `fifo` style of
+    /// `--jobserver-auth` doesn't need file descriptors to be inherited, so it also works for
+    /// actions spawned by the forkserver.
+    pub fn makeflags(&self) -> String {
+        format!("-j --jobserver-auth=fifo:{}", self.path.display())
+    }
+}
+
+impl Drop for JobServer {
+    fn drop(&mut self) {
+        // The permits lent to the pipe are lost along with the broker that owns this jobserver.
+        self.lender.abort();
+        let _ignored = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn lend(permits: PrioritySemaphore, pipe: imp::Pipe) -> std::io::Result<()> {
+    // Permits lent to the pipe, whether they are idle in the pipe or taken by a client.
+    let mut lent = 0;
+    loop {
+        let free = permits.take_available();
+        for i in 0..free {
+            if let Err(e) = pipe.write_token() {
+                permits.release(free - i);
+                return Err(e);
+            }
+        }
+        lent += free;
+
+        let waiting = permits.has_waiters();
+        if waiting && lent > 0 {
+            let reclaimed = pipe.read_tokens(pipe.available()?)?.min(lent);
+            permits.release(reclaimed);
+            lent -= reclaimed;
+        }
+
+        if waiting && lent > 0 {
+            // The other slots are taken by clients, wait for them to return one.
+            tokio::select! {
+                _ = permits.changed() => {}
+                res = pipe.readable() => res?,
+            }
+        } else {
+            permits.changed().await;
+        }
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::io;
+    use std::io::Read;
+    use std::io::Write;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    use anyhow::Context;
+    use tokio::io::unix::AsyncFd;
+
+    use super::TOKEN;
+
+    pub(super) struct Pipe {
+        file: AsyncFd<File>,
+    }
+
+    impl Pipe {
+        pub(super) fn create(path: &Path) -> anyhow::Result<Self> {
+            let c_path = CString::new(path.as_os_str().as_bytes())?;
+            if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                return Err(io::Error::last_os_error())
+                    .with_context(|| format!("Error creating jobserver at `{}`", path.display()));
+            }
+            Self::open(path)
+                .with_context(|| format!("Error opening jobserver at `{}`", path.display()))
+        }
+
+        pub(super) fn open(path: &Path) -> io::Result<Self> {
+            // Opening for both reading and writing doesn't wait for the other end to be opened.
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path)?;
+            Ok(Self {
+                file: AsyncFd::new(file)?,
+            })
+        }
+
+        /// How many tokens are waiting in the pipe.
+        pub(super) fn available(&self) -> io::Result<usize> {
+            let fd = self.file.get_ref().as_raw_fd();
+            let mut available: libc::c_int = 0;
+            if unsafe { libc::ioctl(fd, libc::FIONREAD, &mut available) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(available as usize)
+        }
+
+        pub(super) fn write_token(&self) -> io::Result<()> {
+            self.file.get_ref().write_all(&[TOKEN])
+        }
+
+        /// Reads up to `n` tokens, returning how many were read. Clients may take them first.
+        pub(super) fn read_tokens(&self, n: usize) -> io::Result<usize> {
+            let mut buf = vec![0; n];
+            match self.file.get_ref().read(&mut buf) {
+                Ok(read) => Ok(read),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(0),
+                Err(e) => Err(e),
+            }
+        }
+
+        /// Waits for tokens to be in the pipe.
+        pub(super) async fn readable(&self) -> io::Result<()> {
+            loop {
+                let mut guard = self.file.readable().await?;
+                if self.available()? > 0 {
+                    return Ok(());
+                }
+                guard.clear_ready();
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::io;
+    use std::path::Path;
+
+    pub(super) struct Pipe {}
+
+    impl Pipe {
+        pub(super) fn create(_path: &Path) -> anyhow::Result<Self> {
+            Err(anyhow::anyhow!("The jobserver is only supported on unix"))
+        }
+
+        pub(super) fn available(&self) -> io::Result<usize> {
+            unreachable!()
+        }
+
+        pub(super) fn write_token(&self) -> io::Result<()> {
+            unreachable!()
+        }
+
+        pub(super) fn read_tokens(&self, _n: usize) -> io::Result<usize> {
+            unreachable!()
+        }
+
+        pub(super) async fn readable(&self) -> io::Result<()> {
+            unreachable!()
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::priority_semaphore::Priority;
+
+    async fn wait_for(what: &str, mut cond: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !cond() {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "Timed out waiting for {}",
+                what
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    fn test_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "buck2-jobserver-test-{}-{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_lend_and_reclaim() -> anyhow::Result<()> {
+        let path = test_path("lend");
+        let permits = PrioritySemaphore::new(false, 3);
+        let jobserver = JobServer::new(permits.clone(), path.clone())?;
+        assert_eq!(
+            format!("-j --jobserver-auth=fifo:{}", path.display()),
+            jobserver.makeflags()
+        );
+        // Acts as a client, taking and returning slots.
+        let client = imp::Pipe::open(&path)?;
+
+        // An action runs, the other permits are lent to the pipe.
+        let action = permits.acquire(1, Priority::default()).await;
+        wait_for("idle slots", || client.available().unwrap() == 2).await;
+        assert_eq!(0, permits.permits());
+
+        // A client takes a slot, another action waiting for a permit gets the idle one.
+        assert_eq!(1, client.read_tokens(1)?);
+        let other = tokio::time::timeout(
+            Duration::from_secs(10),
+            permits.acquire(1, Priority::default()),
+        )
+        .await?;
+        assert_eq!(0, client.available()?);
+
+        // The slot the client returns goes to actions waiting for it.
+        let waiting = tokio::spawn({
+            let permits = permits.clone();
+            async move { permits.acquire(1, Priority::default()).await.disarm() }
+        });
+        wait_for("a waiting action", || permits.has_waiters()).await;
+        client.write_token()?;
+        assert_eq!(
+            1,
+            tokio::time::timeout(Duration::from_secs(10), waiting).await??
+        );
+
+        // Permits released are lent to the pipe again.
+        drop(action);
+        drop(other);
+        wait_for("idle slots", || client.available().unwrap() == 2).await;
+
+        drop(jobserver);
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_single_permit() -> anyhow::Result<()> {
+        // With `-j1`, the client only has the permit of its action, and actions never wait for
+        // the jobserver.
+        let path = test_path("single");
+        let permits = PrioritySemaphore::new(false, 1);
+        let _jobserver = JobServer::new(permits.clone(), path.clone())?;
+        let client = imp::Pipe::open(&path)?;
+
+        for _ in 0..3 {
+            let action = tokio::time::timeout(
+                Duration::from_secs(10),
+                permits.acquire(1, Priority::default()),
+            )
+            .await?;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(0, client.available()?);
+            drop(action);
+        }
+        Ok(())
+    }
+}
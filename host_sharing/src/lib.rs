@@ -12,7 +12,11 @@ mod named_semaphores;
 pub use named_semaphores::NamedSemaphores;
 
 pub mod host_sharing;
+pub mod jobserver;
+pub mod priority_semaphore;
 pub use crate::host_sharing::HostSharingBroker;
 pub use crate::host_sharing::HostSharingRequirements;
 pub use crate::host_sharing::HostSharingStrategy;
 pub use crate::host_sharing::WeightClass;
+pub use crate::jobserver::JobServer;
+pub use crate::priority_semaphore::Priority;
@@ -0,0 +1,359 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A semaphore whose waiters are served by priority rather than in the order they arrived, so
+//! that actions on the critical path of a build don't queue behind ones that can wait.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+use tokio::sync::Notify;
+
+/// How urgent a command waiting for permits is. Commands are compared by how long the build is
+/// expected to take at least once they start, and then by how many actions that path goes
+/// through, which tells apart the commands on long chains of quick actions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Priority {
+    pub critical_path_millis: u64,
+    pub depth: u64,
+}
+
+struct Waiter {
+    priority: Priority,
+    /// Breaks ties between waiters with the same priority in favor of the oldest.
+    seq: u64,
+    permits: usize,
+    granted: oneshot::Sender<()>,
+}
+
+impl Waiter {
+    fn key(&self) -> (Priority, std::cmp::Reverse<u64>) {
+        (self.priority, std::cmp::Reverse(self.seq))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+struct State {
+    available: usize,
+    next_seq: u64,
+    waiters: BinaryHeap<Waiter>,
+}
+
+impl State {
+    /// Hands out the available permits to the waiters, highest priority first. When fair, a waiter
+    /// that doesn't fit yet blocks the ones behind it, otherwise they can go ahead of it.
+    fn grant(&mut self, fair: bool) {
+        let mut skipped = Vec::new();
+        while let Some(waiter) = self.waiters.pop() {
+            if waiter.granted.is_closed() {
+                continue;
+            }
+            if waiter.permits > self.available {
+                skipped.push(waiter);
+                if fair {
+                    break;
+                }
+                continue;
+            }
+            if waiter.granted.send(()).is_ok() {
+                self.available -= waiter.permits;
+            }
+        }
+        self.waiters.extend(skipped);
+    }
+}
+
+#[derive(Clone)]
+pub struct PrioritySemaphore {
+    fair: bool,
+    state: Arc<Mutex<State>>,
+    /// Notified when permits are released or a waiter is queued, see `changed`.
+    changed: Arc<Notify>,
+}
+
+impl PrioritySemaphore {
+    pub fn new(fair: bool, permits: usize) -> Self {
+        Self {
+            fair,
+            state: Arc::new(Mutex::new(State {
+                available: permits,
+                next_seq: 0,
+                waiters: BinaryHeap::new(),
+            })),
+            changed: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Waits for `permits` to be available, going ahead of waiters with a lower `priority`.
+    pub async fn acquire(&self, permits: usize, priority: Priority) -> PrioritySemaphoreGuard {
+        let receiver = {
+            let mut state = self.state.lock();
+            if state.waiters.is_empty() && permits <= state.available {
+                state.available -= permits;
+                return PrioritySemaphoreGuard::new(self.clone(), permits);
+            }
+            let (granted, receiver) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiters.push(Waiter {
+                priority,
+                seq,
+                permits,
+                granted,
+            });
+            state.grant(self.fair);
+            receiver
+        };
+        self.changed.notify_one();
+        let mut pending = PendingAcquire {
+            semaphore: self,
+            permits,
+            receiver,
+        };
+        // The sender is only dropped when the permits are granted, or when the waiter is purged
+        // after the receiver was dropped, which can't have happened yet.
+        let _ignored = (&mut pending.receiver).await;
+        pending.permits = 0;
+        PrioritySemaphoreGuard::new(self.clone(), permits)
+    }
+
+    /// Returns permits that were taken out with `PrioritySemaphoreGuard::disarm`.
+    pub fn release(&self, permits: usize) {
+        {
+            let mut state = self.state.lock();
+            state.available += permits;
+            state.grant(self.fair);
+        }
+        self.changed.notify_one();
+    }
+
+    /// How many permits are available right now.
+    pub fn permits(&self) -> usize {
+        self.state.lock().available
+    }
+
+    /// Takes all the available permits, unless some are waited for. They must be given back with
+    /// `release`.
+    pub(crate) fn take_available(&self) -> usize {
+        let mut state = self.state.lock();
+        if state.waiters.iter().any(|w| !w.granted.is_closed()) {
+            return 0;
+        }
+        std::mem::take(&mut state.available)
+    }
+
+    /// Whether someone is waiting for permits.
+    pub(crate) fn has_waiters(&self) -> bool {
+        self.state
+            .lock()
+            .waiters
+            .iter()
+            .any(|w| !w.granted.is_closed())
+    }
+
+    /// Waits until permits are released or someone starts waiting for permits, since the last
+    /// call. Only meant for a single caller, the jobserver.
+    pub(crate) async fn changed(&self) {
+        self.changed.notified().await
+    }
+}
+
+/// An `acquire` that is still waiting. If it's dropped, e.g. because the command was cancelled,
+/// permits that were granted in the meantime are given back.
+struct PendingAcquire<'a> {
+    semaphore: &'a PrioritySemaphore,
+    permits: usize,
+    receiver: oneshot::Receiver<()>,
+}
+
+impl Drop for PendingAcquire<'_> {
+    fn drop(&mut self) {
+        if self.permits == 0 {
+            return;
+        }
+        self.receiver.close();
+        if self.receiver.try_recv().is_ok() {
+            self.semaphore.release(self.permits);
+        }
+    }
+}
+
+/// Holds permits until it's dropped.
+pub struct PrioritySemaphoreGuard {
+    semaphore: PrioritySemaphore,
+    permits: usize,
+}
+
+impl PrioritySemaphoreGuard {
+    fn new(semaphore: PrioritySemaphore, permits: usize) -> Self {
+        Self { semaphore, permits }
+    }
+
+    /// Keeps the permits after the guard is dropped. They must be given back with `release`.
+    pub fn disarm(mut self) -> usize {
+        std::mem::take(&mut self.permits)
+    }
+}
+
+impl Drop for PrioritySemaphoreGuard {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.release(self.permits);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::Poll;
+
+    use futures::poll;
+    use futures::FutureExt;
+
+    use super::*;
+
+    fn priority(critical_path_millis: u64) -> Priority {
+        Priority {
+            critical_path_millis,
+            depth: 0,
+        }
+    }
+
+    type Acquire = Pin<Box<dyn Future<Output = PrioritySemaphoreGuard>>>;
+
+    /// Starts acquiring permits, which queues the acquire if they aren't available.
+    async fn start(semaphore: &PrioritySemaphore, permits: usize, priority: Priority) -> Acquire {
+        let semaphore = semaphore.clone();
+        let mut acquire = async move { semaphore.acquire(permits, priority).await }.boxed_local();
+        assert!(poll!(&mut acquire).is_pending());
+        acquire
+    }
+
+    /// Queues up `requests` of (permits, priority) while all permits are taken, then returns the
+    /// order they were granted in.
+    async fn granted_order(fair: bool, requests: &[(usize, Priority)]) -> Vec<usize> {
+        let semaphore = PrioritySemaphore::new(fair, 2);
+        let mut guard = Some(semaphore.acquire(2, Priority::default()).await);
+        let mut waiting = Vec::new();
+        for (i, (permits, priority)) in requests.iter().copied().enumerate() {
+            waiting.push((i, start(&semaphore, permits, priority).await));
+        }
+
+        let mut order = Vec::new();
+        while !waiting.is_empty() {
+            drop(guard.take());
+            let mut granted = Vec::new();
+            for (i, (_, acquire)) in waiting.iter_mut().enumerate() {
+                if let Poll::Ready(g) = poll!(acquire) {
+                    granted.push(i);
+                    guard = Some(g);
+                }
+            }
+            assert_eq!(1, granted.len(), "all the requests take all the permits");
+            order.push(waiting.remove(granted[0]).0);
+        }
+        order
+    }
+
+    #[tokio::test]
+    async fn test_highest_priority_first() {
+        assert_eq!(
+            vec![1, 2, 0],
+            granted_order(
+                true,
+                &[(2, priority(1)), (2, priority(10)), (2, priority(5))]
+            )
+            .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_depth_breaks_ties() {
+        let deep = Priority {
+            critical_path_millis: 5,
+            depth: 3,
+        };
+        assert_eq!(
+            vec![1, 2, 0],
+            granted_order(true, &[(2, priority(5)), (2, priority(6)), (2, deep)]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fair_waits_for_head() {
+        // The head doesn't fit while one permit is taken, and the smaller request has to wait
+        // behind it even though it would.
+        let semaphore = PrioritySemaphore::new(true, 2);
+        let one = semaphore.acquire(1, Priority::default()).await;
+        let mut big = start(&semaphore, 2, priority(10)).await;
+        let mut small = start(&semaphore, 1, priority(1)).await;
+        drop(one);
+        assert!(poll!(&mut small).is_pending());
+        match poll!(&mut big) {
+            Poll::Ready(guard) => assert_eq!(2, guard.disarm()),
+            Poll::Pending => panic!("the head is granted the released permit"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unfair_lets_smaller_through() {
+        // The head doesn't fit while one permit is taken, the smaller request does.
+        let semaphore = PrioritySemaphore::new(false, 2);
+        let one = semaphore.acquire(1, Priority::default()).await;
+        let mut big = start(&semaphore, 2, priority(10)).await;
+        let small = semaphore.acquire(1, priority(1)).now_or_never();
+        assert!(small.is_some());
+        drop(one);
+        assert!(poll!(&mut big).is_pending());
+        drop(small);
+        match poll!(&mut big) {
+            Poll::Ready(guard) => assert_eq!(2, guard.disarm()),
+            Poll::Pending => panic!("the head is granted the released permits"),
+        }
+        assert_eq!(0, semaphore.permits());
+        semaphore.release(2);
+        assert_eq!(2, semaphore.permits());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_acquire_returns_permits() {
+        let semaphore = PrioritySemaphore::new(true, 1);
+        let guard = semaphore.acquire(1, Priority::default()).await;
+        let waiting = start(&semaphore, 1, Priority::default()).await;
+        // Granted before the acquire sees it, then cancelled.
+        drop(guard);
+        assert_eq!(0, semaphore.permits());
+        drop(waiting);
+        assert_eq!(1, semaphore.permits());
+    }
+}
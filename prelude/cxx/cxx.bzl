@@ -234,6 +234,7 @@ def cxx_binary_impl(ctx: "context") -> ["provider"]:
             sub_targets = output.sub_targets,
         ),
         RunInfo(args = cmd_args(output.binary).hidden(output.runtime_files)),
+        OutputSizeInfo(outputs = [output.binary]),
         comp_db_info,
         xcode_data_info,
     ]
@@ -9,6 +9,7 @@
 
 load("@prelude//:cache_mode.bzl", "CacheModeInfo")
 load("@prelude//:genrule_local_labels.bzl", "genrule_labels_require_local")
+load("@prelude//:genrule_toolchain.bzl", "GenruleToolchainInfo")
 load("@prelude//utils:utils.bzl", "value_or")
 
 # Currently, some rules require running from the project root, so provide an
@@ -72,13 +73,22 @@ def _should_handle_special_case_whole_out_dir_is_output(ctx: "context", outs_att
 # @oss-disable: _USE_CACHE_MODE = True 
 _USE_CACHE_MODE = False # @oss-enable
 
+# The genrule toolchain is opt-in, since most repos don't define one: set
+# `[genrule] toolchain = toolchains//:genrule` to use it.
+_GENRULE_TOOLCHAIN = read_config("genrule", "toolchain", None)
+
 # Extra attributes required by every genrule based on genrule_impl
 def genrule_attributes() -> {str.type: "attribute"}:
+    attributes = {
+        # Whether the command may access the network: "allowed" or "none".
+        "network": attrs.enum(["allowed", "none"], default = "allowed"),
+    }
+    if _GENRULE_TOOLCHAIN != None:
+        attributes["_genrule_toolchain"] = attrs.default_only(attrs.toolchain_dep(default = _GENRULE_TOOLCHAIN, providers = [GenruleToolchainInfo]))
     if _USE_CACHE_MODE:
         # FIXME: prelude// should be standalone (not refer to fbsource//)
-        return {"_cache_mode": attrs.dep(default = "fbsource//xplat/buck2/platform/cache_mode:cache_mode")}
-    else:
-        return {}
+        attributes["_cache_mode"] = attrs.dep(default = "fbsource//xplat/buck2/platform/cache_mode:cache_mode")
+    return attributes
 
 def _get_cache_mode(ctx: "context") -> CacheModeInfo.type:
     if _USE_CACHE_MODE:
@@ -165,8 +175,17 @@ def process_genrule(
     else:
         fail("One of `out` or `outs` should be set. Got `%s`" % repr(ctx.attrs))
 
-    # Some custom rules use `process_genrule` but doesn't set this attrbiute.
-    is_windows = hasattr(ctx.attrs, "_target_os_type") and ctx.attrs._target_os_type == "windows"
+    # The toolchain picks the shell for the execution platform. Some custom rules use
+    # `process_genrule` without a toolchain, they pick it from the target platform instead, and
+    # some don't even set `_target_os_type`.
+    toolchain = ctx.attrs._genrule_toolchain[GenruleToolchainInfo] if hasattr(ctx.attrs, "_genrule_toolchain") else None
+    if toolchain != None:
+        shell = toolchain.shell
+    elif hasattr(ctx.attrs, "_target_os_type") and ctx.attrs._target_os_type == "windows":
+        shell = "cmd"
+    else:
+        shell = "bash"
+    is_windows = shell == "cmd"
     if is_windows:
         path_sep = "\\"
         cmd = ctx.attrs.cmd_exe if ctx.attrs.cmd_exe != None else ctx.attrs.cmd
@@ -218,6 +237,11 @@ def process_genrule(
         "SRCS": srcs,
     } | {k: cmd_args(v) for k, v in getattr(ctx.attrs, "env", {}).items()}
 
+    # The command sees the environment of the toolchain, on top of its own, and with `scrub_env`
+    # nothing else, so it behaves the same on every machine and on RE.
+    if toolchain != None:
+        env_vars = {k: cmd_args(v) for k, v in toolchain.env.items()} | env_vars
+
     # RE will cache successful actions that don't produce the desired outptuts,
     # so if that happens and _then_ we add a local-only label, we'll get a
     # cache hit on the action that didn't produce the outputs and get the error
@@ -280,6 +304,8 @@ def process_genrule(
     )
     if is_windows:
         script_args = ["cmd.exe", "/c", sh_script]
+    elif shell == "busybox":
+        script_args = [toolchain.busybox, "sh", "-e", sh_script]
     else:
         script_args = ["/bin/bash", "-e", sh_script]
 
@@ -294,6 +320,8 @@ def process_genrule(
         allow_cache_upload = cacheable,
         category = category,
         identifier = identifier,
+        scrub_env = toolchain != None and toolchain.scrub_env,
+        network = ctx.attrs.network,
     )
 
     if handle_whole_out_dir_is_output:
@@ -0,0 +1,25 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+# How the script of a genrule is run:
+#  - `bash`: with `/bin/bash`.
+#  - `cmd`: with `cmd.exe`, and `cmd_exe` taking precedence over `cmd`.
+#  - `busybox`: with the `sh` of the toolchain's `busybox`, which works the same on every
+#    platform, including Windows.
+GenruleShell = enum("bash", "cmd", "busybox")
+
+GenruleToolchainInfo = provider(fields = [
+    # `GenruleShell` for the execution platform of the genrule.
+    "shell",
+    # `RunInfo` of busybox, if `shell` is `busybox`.
+    "busybox",
+    # Environment variables (`{str.type: str.type}`) set for every genrule.
+    "env",
+    # Whether the commands of genrules only see `env` and their own `env` when run locally,
+    # rather than the environment of buck2 too.
+    "scrub_env",
+])
@@ -0,0 +1,23 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+load("@prelude//:attributes.bzl", "Platform")
+
+# The OS of an execution platform. Rules which depend on `prelude//os:exec_os` through an
+# `attrs.exec_dep()` get the OS of the platform their actions run on, rather than the one they
+# target, and can pick tools accordingly.
+ExecOsInfo = provider(fields = ["os"])
+
+def _exec_os_impl(ctx: "context") -> ["provider"]:
+    return [DefaultInfo(), ExecOsInfo(os = ctx.attrs.os)]
+
+exec_os = rule(
+    impl = _exec_os_impl,
+    attrs = {
+        "os": attrs.enum(Platform),
+    },
+)
@@ -46,6 +46,7 @@ def _mk_rule(name: str.type, attributes: {str.type: "attribute"}) -> "rule":
         impl = getattr(implemented_rules, name, _unimplemented_impl(name)),
         attrs = attributes,
         is_configuration_rule = name in _config_implemented_rules,
+        is_alias_rule = name == "alias",
     )
 
 def _merge_attributes() -> {str.type: {str.type: "attribute"}}:
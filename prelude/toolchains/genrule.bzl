@@ -0,0 +1,80 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+load("@prelude//:genrule_toolchain.bzl", "GenruleShell", "GenruleToolchainInfo")
+load("@prelude//os:exec_os.bzl", "ExecOsInfo")
+
+_DEFAULT_ENV = {
+    "linux": {
+        "LANG": "C.UTF-8",
+        "PATH": "/usr/local/bin:/usr/bin:/bin:/usr/local/sbin:/usr/sbin:/sbin",
+    },
+    "macos": {
+        "LANG": "en_US.UTF-8",
+        "PATH": "/opt/homebrew/bin:/usr/local/bin:/usr/bin:/bin:/usr/sbin:/sbin",
+    },
+    "windows": {
+        "COMSPEC": "C:\\Windows\\System32\\cmd.exe",
+        "PATH": "C:\\Windows\\System32;C:\\Windows;C:\\Windows\\System32\\WindowsPowerShell\\v1.0",
+        "PATHEXT": ".COM;.EXE;.BAT;.CMD",
+        "SystemRoot": "C:\\Windows",
+    },
+}
+
+def _system_genrule_toolchain_impl(ctx):
+    exec_os = ctx.attrs._exec_os[ExecOsInfo].os
+
+    shell = ctx.attrs.shell
+    if shell == None:
+        shell = "cmd" if exec_os == "windows" else "bash"
+    if shell == "busybox" and ctx.attrs.busybox == None:
+        fail("`busybox` must be set when `shell` is `busybox`")
+
+    env = ctx.attrs.env
+    if env == None:
+        env = _DEFAULT_ENV.get(exec_os, _DEFAULT_ENV["linux"])
+
+    return [
+        DefaultInfo(),
+        GenruleToolchainInfo(
+            shell = shell,
+            busybox = ctx.attrs.busybox[RunInfo] if ctx.attrs.busybox else None,
+            env = env,
+            scrub_env = ctx.attrs.scrub_env,
+        ),
+    ]
+
+# A genrule toolchain picking the shell and a minimal environment for the OS of each execution
+# platform: bash on Linux and macOS, cmd.exe on Windows. You may use it in your toolchain cell as
+# follows:
+#
+# ```bzl
+# load("@prelude//toolchains:genrule.bzl", "system_genrule_toolchain")
+#
+# system_genrule_toolchain(
+#     name = "genrule",
+#     visibility = ["PUBLIC"],
+# )
+# ```
+#
+# and point genrules at it with `[genrule] toolchain = toolchains//:genrule` in `.buckconfig`.
+#
+# Set `shell = "busybox"` and `busybox` to run genrules with the same shell everywhere, and `env`
+# to replace the default environment, e.g. to add the directories of the tools genrules use to
+# `PATH`. Set `scrub_env = True` to stop genrules run locally from seeing the environment of
+# buck2.
+system_genrule_toolchain = rule(
+    impl = _system_genrule_toolchain_impl,
+    attrs = {
+        "busybox": attrs.option(attrs.exec_dep(providers = [RunInfo]), default = None),
+        "env": attrs.option(attrs.dict(key = attrs.string(), value = attrs.string(), sorted = False), default = None),
+        "scrub_env": attrs.bool(default = False),
+        "shell": attrs.option(attrs.enum(GenruleShell.values()), default = None),
+        "_exec_os": attrs.exec_dep(default = "prelude//os:exec_os", providers = [ExecOsInfo]),
+    },
+    is_toolchain_rule = True,
+)
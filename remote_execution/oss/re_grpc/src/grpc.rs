@@ -20,6 +20,8 @@ pub struct Action {
     pub timeout: Option<Duration>,
     #[prost(bool, tag = "7")]
     pub do_not_cache: bool,
+    #[prost(bytes = "vec", tag = "9")]
+    pub salt: Vec<u8>,
 }
 
 #[derive(Clone, PartialEq, Message)]
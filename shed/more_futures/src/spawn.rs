@@ -14,13 +14,18 @@
 use std::cell::RefCell;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Weak;
 use std::task::Context;
 use std::task::Poll;
 
 use allocative::Allocative;
 use futures::future::BoxFuture;
+use futures::task::AtomicWaker;
 use futures::FutureExt;
+use gazebo::prelude::*;
 use pin_project::pin_project;
 use tokio::sync::oneshot;
 use tracing::Span;
@@ -30,11 +35,53 @@ use crate::instrumented_shared::SharedEventsFuture;
 use crate::spawner::Spawner;
 use crate::util::guarded_rc::guarded_rc;
 use crate::util::guarded_rc::GuardedRcStrongGuard;
-use crate::util::guarded_rc::GuardedRcWeakGuard;
 use crate::util::guarded_rc::GuardedWeakRc;
 
 thread_local! {
-    static CURRENT_TASK_GUARD: RefCell<Option<GuardedRcWeakGuard>> = RefCell::new(None);
+    static CURRENT_TASK: RefCell<Option<CurrentTask>> = RefCell::new(None);
+}
+
+/// The DropCancel future being polled on this thread.
+#[derive(Clone)]
+struct CurrentTask {
+    waiters: Weak<TaskWaiters>,
+    cancellation: CancellationObserver,
+    /// Woken once the task is cancelled, so that the DropCancel future finishes even if the
+    /// future it polls would never be woken again.
+    waker: Arc<AtomicWaker>,
+}
+
+/// Held by everything that waits for the result of a task, or keeps it alive in a critical
+/// section. Once the last one is dropped, the task is cancelled and can't be resumed anymore.
+struct TaskWaiters {
+    _guard: GuardedRcStrongGuard,
+    cancelled: Arc<AtomicBool>,
+    waker: Arc<AtomicWaker>,
+}
+
+impl Drop for TaskWaiters {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.waker.wake();
+    }
+}
+
+/// Tells whether a task was cancelled. The task is only dropped once it yields, so synchronous
+/// work that runs for a long time within it can check this to stop early.
+#[derive(Clone, Dupe)]
+pub struct CancellationObserver {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationObserver {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// The flag that is set once the task is cancelled.
+    pub fn as_flag(&self) -> &AtomicBool {
+        &self.cancelled
+    }
 }
 
 /// A unit of computation within Dice. Futures to the result of this computation should be obtained
@@ -43,7 +90,8 @@ thread_local! {
 pub struct WeakJoinHandle<T: Clone> {
     #[allocative(skip)] // TODO(nga): `Shared` requires `Clone`.
     join_handle: SharedEventsFuture<BoxFuture<'static, T>>,
-    ref_handle: GuardedRcWeakGuard,
+    #[allocative(skip)]
+    ref_handle: Weak<TaskWaiters>,
 }
 
 impl<T: Clone + 'static> WeakJoinHandle<T> {
@@ -72,6 +120,7 @@ impl<T: Clone + 'static> WeakJoinHandle<T> {
 struct DropCancel<T> {
     #[pin]
     inner: GuardedWeakRc<RefCell<DropCancelInner<T>>>,
+    task: CurrentTask,
     instrumented_span: Span,
 }
 
@@ -97,8 +146,8 @@ where
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
 
-        let guard = this.inner.make_weak_guard();
-        let old_guard = CURRENT_TASK_GUARD.with(|g| g.replace(Some(guard)));
+        this.task.waker.register(cx.waker());
+        let old_task = CURRENT_TASK.with(|t| t.replace(Some(this.task.clone())));
 
         let res = match this.inner.upgrade() {
             Some(inner) => {
@@ -116,7 +165,7 @@ where
             None => Poll::Ready(None),
         };
 
-        CURRENT_TASK_GUARD.with(|g| g.replace(old_guard));
+        CURRENT_TASK.with(|t| t.replace(old_task));
 
         res.map(|_| ())
     }
@@ -125,7 +174,7 @@ where
 /// The actual pollable future that returns the result of the task. This keeps the future alive
 #[pin_project]
 pub struct StrongCancellableJoinHandle<F> {
-    _ref: GuardedRcStrongGuard,
+    _ref: Arc<TaskWaiters>,
     #[pin]
     fut: F,
 }
@@ -174,9 +223,11 @@ where
         future: task.boxed(),
         on_cancel,
     }));
+    let (guard, task) = task_waiters(guard);
 
     let drop = DropCancel {
         inner: weak_task,
+        task,
         instrumented_span: span,
     };
 
@@ -190,7 +241,7 @@ where
 
     let task = WeakJoinHandle {
         join_handle: fut.clone(),
-        ref_handle: guard.downgrade(),
+        ref_handle: Arc::downgrade(&guard),
     };
 
     let poll = StrongCancellableJoinHandle { _ref: guard, fut };
@@ -224,9 +275,11 @@ where
         future: task.boxed(),
         on_cancel,
     }));
+    let (guard, task) = task_waiters(guard);
 
     let drop = DropCancel {
         inner: weak_task,
+        task,
         instrumented_span: span,
     };
 
@@ -240,11 +293,34 @@ where
     StrongCancellableJoinHandle { _ref: guard, fut }
 }
 
-/// Obtain a GuardedRcStrongGuard that keeps the current task alive, assuming it polls a DropCancel
-/// future. While this guard is alive, the future will not be dropped. Use to protect critical
+fn task_waiters(guard: GuardedRcStrongGuard) -> (Arc<TaskWaiters>, CurrentTask) {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let waker = Arc::new(AtomicWaker::new());
+    let waiters = Arc::new(TaskWaiters {
+        _guard: guard,
+        cancelled: cancelled.dupe(),
+        waker: waker.dupe(),
+    });
+    let task = CurrentTask {
+        waiters: Arc::downgrade(&waiters),
+        cancellation: CancellationObserver { cancelled },
+        waker,
+    };
+    (waiters, task)
+}
+
+/// Obtain a reference that keeps the current task alive, assuming it polls a DropCancel
+/// future. While this reference is alive, the future will not be dropped. Use to protect critical
 /// sections during which a task should not be cancelled.
-fn current_task_guard() -> Option<GuardedRcStrongGuard> {
-    CURRENT_TASK_GUARD.with(|g| g.borrow().as_ref().and_then(|g| g.upgrade()))
+fn current_task_guard() -> Option<Arc<TaskWaiters>> {
+    CURRENT_TASK.with(|t| t.borrow().as_ref().and_then(|t| t.waiters.upgrade()))
+}
+
+/// Observe the cancellation of the DropCancel future being polled on this thread, if any, i.e.
+/// whether nothing waits for its result anymore. Obtain it when the task starts and pass it to the
+/// work to stop once cancelled.
+pub fn current_task_cancellation() -> Option<CancellationObserver> {
+    CURRENT_TASK.with(|t| t.borrow().as_ref().map(|t| t.cancellation.dupe()))
 }
 
 /// Enter a critical section during which the current DropCancel future (if any) should not be
@@ -345,6 +421,90 @@ mod tests {
         recv_success.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_current_task_cancellation() {
+        let (send_poll, recv_poll) = oneshot::channel::<
+            StrongCancellableJoinHandle<SharedEventsFuture<BoxFuture<'static, ()>>>,
+        >();
+        let (notify_cancelled, recv_cancelled) = oneshot::channel();
+
+        let sp: Arc<dyn Spawner<MockCtx>> = Arc::new(TokioSpawner::default());
+
+        let (task, poll) = spawn_task(
+            async move {
+                let cancellation = current_task_cancellation().unwrap();
+                let poll = recv_poll.await.unwrap();
+                let before = cancellation.is_cancelled();
+                // The task now holds the only strong handle to itself: dropping it cancels the
+                // task, but it keeps running until it yields.
+                drop(poll);
+                notify_cancelled
+                    .send((before, cancellation.is_cancelled()))
+                    .unwrap();
+            },
+            None,
+            &sp,
+            &MockCtx::default(),
+            tracing::debug_span!("test"),
+        );
+
+        assert!(current_task_cancellation().is_none());
+        send_poll.send(poll).ok().unwrap();
+        assert_eq!((false, true), recv_cancelled.await.unwrap());
+        // A cancelled task can't be resumed.
+        assert!(task.pollable().is_none());
+    }
+
+    /// Tells when the futures it spawns finish.
+    #[derive(Default)]
+    struct RecordingSpawner(std::sync::Mutex<Vec<oneshot::Receiver<()>>>);
+
+    impl Spawner<MockCtx> for RecordingSpawner {
+        fn spawn(
+            &self,
+            _ctx: &MockCtx,
+            fut: BoxFuture<'static, ()>,
+        ) -> tokio::task::JoinHandle<()> {
+            let (finished, recv_finished) = oneshot::channel();
+            self.0.lock().unwrap().push(recv_finished);
+            tokio::spawn(async move {
+                fut.await;
+                let _ignored = finished.send(());
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_pending_task_finishes() {
+        let (send_poll, recv_poll) = oneshot::channel::<
+            StrongCancellableJoinHandle<SharedEventsFuture<BoxFuture<'static, ()>>>,
+        >();
+
+        let spawner = Arc::new(RecordingSpawner::default());
+        let sp: Arc<dyn Spawner<MockCtx>> = spawner.dupe();
+
+        let (_task, poll) = spawn_task(
+            async move {
+                let poll = recv_poll.await.unwrap();
+                // Cancel the task from within, then wait for something that never happens.
+                drop(poll);
+                futures::future::pending::<()>().await;
+            },
+            None,
+            &sp,
+            &MockCtx::default(),
+            tracing::debug_span!("test"),
+        );
+        send_poll.send(poll).ok().unwrap();
+
+        // The task is woken once it's cancelled, rather than left pending forever.
+        let finished = spawner.0.lock().unwrap().pop().unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(10), finished)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
     #[tokio::test]
     async fn test_spawn() {
         let sp: Arc<dyn Spawner<MockCtx>> = Arc::new(TokioSpawner::default());
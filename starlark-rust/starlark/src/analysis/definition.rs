@@ -26,6 +26,7 @@ use crate::codemap::Span;
 use crate::codemap::Spanned;
 use crate::syntax::ast::ArgumentP;
 use crate::syntax::ast::AssignP;
+use crate::syntax::ast::AstArgument;
 use crate::syntax::ast::AstLiteral;
 use crate::syntax::ast::AstNoPayload;
 use crate::syntax::ast::AstString;
@@ -201,6 +202,33 @@ impl Definition {
     }
 }
 
+/// Where within a [`FunctionCall`] a position is.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum CallPosition {
+    /// Within the name of the function.
+    Function,
+    /// Within the name of a named argument.
+    ArgumentName { name: String, span: ResolvedSpan },
+    /// Where the name of a new argument could be written, e.g. between arguments, or within
+    /// a bare identifier that is passed positionally.
+    NewArgument,
+    /// Within the value of an argument.
+    ArgumentValue,
+}
+
+/// A call of a function by name. See [`LspModule::find_function_call_at`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct FunctionCall {
+    /// The name of the function that is called.
+    pub(crate) function: String,
+    /// The location of the name of the function.
+    pub(crate) function_span: ResolvedSpan,
+    /// The names of the named arguments that are passed, in order.
+    pub(crate) named_args: Vec<String>,
+    /// Where within the call the requested position is.
+    pub(crate) position: CallPosition,
+}
+
 /// Container that holds an AST module and returns things like definition locations,
 /// lists of symbols, etc.
 pub(crate) struct LspModule {
@@ -557,6 +585,78 @@ impl LspModule {
         );
         ret.unwrap_or(IdentifierDefinition::NotFound)
     }
+
+    /// Find the innermost call of a function by name (e.g. `foo(...)`, not `x.foo(...)`) that
+    /// encloses a given position, and where within that call the position is.
+    ///
+    /// `line` and `col` are zero based indexes of the position.
+    pub(crate) fn find_function_call_at(&self, line: u32, col: u32) -> Option<FunctionCall> {
+        let line_span = self.ast.codemap.line_span(line as usize);
+        let pos = std::cmp::min(line_span.begin() + col, line_span.end());
+
+        fn visit_node<'a>(
+            pos: Pos,
+            ret: &mut Option<(&'a AstString, &'a [AstArgument])>,
+            node: Visit<'a, AstNoPayload>,
+        ) {
+            if let Visit::Expr(Spanned {
+                node: ExprP::Call(function, args),
+                span,
+            }) = node
+            {
+                if span.contains(pos) {
+                    // Calls within the arguments are visited afterwards, so the innermost one wins.
+                    *ret = match &function.node {
+                        ExprP::Identifier(name, _) => Some((name, args.as_slice())),
+                        _ => None,
+                    };
+                }
+            }
+            node.visit_children(|node| visit_node(pos, ret, node));
+        }
+
+        let mut ret = None;
+        visit_node(pos, &mut ret, Visit::Stmt(&self.ast.statement));
+        let (function, args) = ret?;
+
+        let mut position = if function.span.contains(pos) {
+            Some(CallPosition::Function)
+        } else {
+            None
+        };
+        let mut named_args = Vec::new();
+        for arg in args {
+            match &arg.node {
+                ArgumentP::Named(name, _) => {
+                    named_args.push(name.node.clone());
+                    if position.is_none() && name.span.contains(pos) {
+                        position = Some(CallPosition::ArgumentName {
+                            name: name.node.clone(),
+                            span: self.ast.codemap.resolve_span(name.span),
+                        });
+                    }
+                }
+                // A bare identifier may be the start of an argument name that is being typed.
+                ArgumentP::Positional(Spanned {
+                    node: ExprP::Identifier(..),
+                    ..
+                }) if arg.span.contains(pos) => {
+                    position.get_or_insert(CallPosition::NewArgument);
+                }
+                _ => {}
+            }
+            if position.is_none() && arg.span.contains(pos) {
+                position = Some(CallPosition::ArgumentValue);
+            }
+        }
+
+        Some(FunctionCall {
+            function: function.node.clone(),
+            function_span: self.ast.codemap.resolve_span(function.span),
+            named_args,
+            position: position.unwrap_or(CallPosition::NewArgument),
+        })
+    }
 }
 
 impl AstModule {
@@ -805,7 +905,9 @@ mod test {
     use textwrap::dedent;
 
     use super::helpers::FixtureWithRanges;
+    use crate::analysis::definition::CallPosition;
     use crate::analysis::definition::DottedDefinition;
+    use crate::analysis::definition::FunctionCall;
     use crate::analysis::Definition;
     use crate::analysis::IdentifierDefinition;
     use crate::analysis::LspModule;
@@ -1306,4 +1408,52 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn find_function_call_at() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+        <rule>my_<r>r</r>ule</rule>(
+            vis<new>i</new>,<between> </between>
+            <name>na<n>m</n>e</name> = "foo",
+            srcs = [<v>"</v>a.c"],
+            deps = glob(<g>[</g>"*"]),
+        )
+        x.<dotted>f</dotted>(1)
+        <outside>y</outside> = 1
+        "#,
+        )
+        .trim()
+        .to_owned();
+        let parsed = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = parsed.module()?;
+
+        let find =
+            |id: &str| module.find_function_call_at(parsed.begin_line(id), parsed.begin_column(id));
+        let expected = |position: CallPosition| FunctionCall {
+            function: "my_rule".to_owned(),
+            function_span: parsed.span("rule"),
+            named_args: vec!["name".to_owned(), "srcs".to_owned(), "deps".to_owned()],
+            position,
+        };
+
+        assert_eq!(Some(expected(CallPosition::Function)), find("r"));
+        assert_eq!(
+            Some(expected(CallPosition::ArgumentName {
+                name: "name".to_owned(),
+                span: parsed.span("name"),
+            })),
+            find("n")
+        );
+        assert_eq!(Some(expected(CallPosition::ArgumentValue)), find("v"));
+        assert_eq!(Some(expected(CallPosition::NewArgument)), find("new"));
+        assert_eq!(Some(expected(CallPosition::NewArgument)), find("between"));
+        assert_eq!(
+            Some("glob"),
+            find("g").as_ref().map(|call| call.function.as_str())
+        );
+        assert_eq!(None, find("dotted"));
+        assert_eq!(None, find("outside"));
+        Ok(())
+    }
 }
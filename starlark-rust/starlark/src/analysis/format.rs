@@ -0,0 +1,659 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A deterministic formatter for modules, aimed at build files. See [`AstModule::format`].
+//!
+//! The AST does not record comments, so they are recovered from the source. Anything the
+//! formatter does not understand (e.g. the body of a `def`) is copied verbatim, and a top-level
+//! statement whose comments could not all be placed is left exactly as written.
+
+use std::ops::Range;
+
+use crate::codemap::Span;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AstArgument;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::BinOp;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::Stmt;
+use crate::syntax::AstModule;
+
+const INDENT: &str = "    ";
+
+/// Sequences which would make a line longer than this are split over several lines.
+const MAX_LINE_LENGTH: usize = 100;
+
+/// Options for [`AstModule::format`].
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    /// Sort the named arguments of top-level calls (usually the attributes of a target) with
+    /// `name` first and the rest alphabetically. Comments move with their argument.
+    pub sort_named_arguments: bool,
+}
+
+impl AstModule {
+    /// Format the module. The result is stable: formatting it again gives the same text.
+    ///
+    /// Calls, lists and dicts are placed on one line if they fit and were written on one line,
+    /// otherwise one item per line with a trailing comma. Top-level calls with more than one
+    /// argument (i.e. targets) are always one argument per line.
+    pub fn format(&self, options: &FormatOptions) -> String {
+        let source = self.codemap.source();
+        let statements = match &self.statement.node {
+            Stmt::Statements(xs) => xs.iter().collect(),
+            _ => vec![&self.statement],
+        };
+
+        let mut formatter = Formatter::new(source, options);
+        let mut res = String::new();
+        let mut pos = 0;
+        for statement in statements {
+            let range = statement.span.byte_range();
+            formatter.gap(&mut res, pos, range.start);
+            res.push_str(&formatter.statement(statement));
+            // Some statements (e.g. `def`) include the newlines which follow them.
+            pos = range.start + source[range].trim_end().len();
+        }
+        formatter.gap(&mut res, pos, source.len());
+
+        let len = res.trim_end().len();
+        res.truncate(len);
+        if !res.is_empty() {
+            res.push('\n');
+        }
+        res
+    }
+}
+
+/// An item of a call, list or dict, before layout.
+struct Item {
+    /// The source of the item, which excludes any comments around it.
+    range: Range<usize>,
+    /// Items are sorted by this key, if sorting is enabled.
+    key: (u8, String),
+    text: String,
+}
+
+/// An item placed with the comments which belong to it.
+struct Entry {
+    blank_before: bool,
+    leading: Vec<usize>,
+    text: String,
+    trailing: Option<usize>,
+    key: (u8, String),
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    options: &'a FormatOptions,
+    /// The comments of the source, in order.
+    comments: Vec<Range<usize>>,
+    /// Which comments have been written to the output.
+    used: Vec<bool>,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: &'a str, options: &'a FormatOptions) -> Self {
+        let comments = find_comments(source);
+        let used = vec![false; comments.len()];
+        Self {
+            source,
+            options,
+            comments,
+            used,
+        }
+    }
+
+    /// The indices of the comments starting within `range`.
+    fn comments_in(&self, range: Range<usize>) -> impl Iterator<Item = usize> + '_ {
+        let first = self.comments.partition_point(|c| c.start < range.start);
+        (first..self.comments.len()).take_while(move |&i| self.comments[i].start < range.end)
+    }
+
+    fn comment(&self, i: usize) -> &'a str {
+        self.source[self.comments[i].clone()].trim_end()
+    }
+
+    /// The source of `span`, marking the comments inside as written.
+    fn verbatim(&mut self, span: Span) -> &'a str {
+        let range = span.byte_range();
+        for i in self.comments_in(range.clone()).collect::<Vec<_>>() {
+            self.used[i] = true;
+        }
+        &self.source[range]
+    }
+
+    /// Write the text between two top-level statements, which is only whitespace and comments.
+    /// Comments are kept and runs of blank lines are collapsed to a single blank line.
+    fn gap(&self, res: &mut String, from: usize, to: usize) {
+        let mut lines: Vec<&str> = self.source[from..to].split('\n').collect();
+        if from != 0 {
+            // The rest of the line of the previous statement, e.g. a trailing comment.
+            let rest = lines.remove(0).trim();
+            if !rest.is_empty() {
+                res.push_str("  ");
+                res.push_str(rest);
+            }
+            res.push('\n');
+        }
+        if to != self.source.len() {
+            // The indentation before the next statement.
+            lines.pop();
+        }
+
+        let mut blank = false;
+        for line in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                blank = true;
+                continue;
+            }
+            if blank && !res.is_empty() {
+                res.push('\n');
+            }
+            blank = false;
+            res.push_str(line);
+            res.push('\n');
+        }
+        if blank && !res.is_empty() && to != self.source.len() {
+            res.push('\n');
+        }
+    }
+
+    fn statement(&mut self, statement: &AstStmt) -> String {
+        let range = statement.span.byte_range();
+        let used = self.used.clone();
+        let res = match &statement.node {
+            // The AST does not record parentheses around the expression, which may be needed
+            // for it to span several lines, so keep those statements as they are.
+            Stmt::Expression(x) if x.span != statement.span => {
+                self.verbatim(statement.span).trim_end().to_owned()
+            }
+            Stmt::Expression(x) => match &x.node {
+                Expr::Call(f, args) => self.call(
+                    x,
+                    f,
+                    args,
+                    args.len() > 1,
+                    self.options.sort_named_arguments,
+                    0,
+                    0,
+                ),
+                _ => self.expr(x, 0, 0),
+            },
+            Stmt::Assign(lhs, ty_rhs) => match &**ty_rhs {
+                (None, rhs)
+                    if self.source[lhs.span.byte_range().end..rhs.span.byte_range().start]
+                        .trim()
+                        == "=" =>
+                {
+                    let lhs = self.verbatim(lhs.span);
+                    let rhs = self.expr(rhs, lhs.len() + 3, 0);
+                    format!("{} = {}", lhs, rhs)
+                }
+                _ => self.verbatim(statement.span).trim_end().to_owned(),
+            },
+            Stmt::Load(load) => {
+                let open = range.start + self.source[range.clone()].find('(').unwrap_or(0);
+                let mut items = vec![Item {
+                    range: load.module.span.byte_range(),
+                    key: (0, String::new()),
+                    text: string_literal(self.verbatim(load.module.span)),
+                }];
+                for (local, name) in &load.args {
+                    let name_text = string_literal(self.verbatim(name.span));
+                    let text = if local.span == name.span {
+                        name_text
+                    } else {
+                        format!("{} = {}", local.node.0, name_text)
+                    };
+                    items.push(Item {
+                        range: local.span.merge(name.span).byte_range(),
+                        key: (0, String::new()),
+                        text,
+                    });
+                }
+                self.sequence("load", open, range.end - 1, items, false, false, 0, 0)
+            }
+            _ => self.verbatim(statement.span).trim_end().to_owned(),
+        };
+
+        if self.comments_in(range).all(|i| self.used[i]) {
+            res
+        } else {
+            self.used = used;
+            self.verbatim(statement.span).trim_end().to_owned()
+        }
+    }
+
+    /// Format an expression whose first line starts at `column`, with continuation lines
+    /// indented by `indent` levels.
+    fn expr(&mut self, x: &AstExpr, column: usize, indent: usize) -> String {
+        let range = x.span.byte_range();
+        let inner = (indent + 1) * INDENT.len();
+        match &x.node {
+            Expr::Call(f, args) => self.call(x, f, args, false, false, column, indent),
+            Expr::List(xs) if self.delimited(range.clone(), '[', ']') => {
+                let mut items = Vec::with_capacity(xs.len());
+                for x in xs {
+                    items.push(Item {
+                        range: x.span.byte_range(),
+                        key: (0, String::new()),
+                        text: self.expr(x, inner, indent + 1),
+                    });
+                }
+                self.sequence(
+                    "",
+                    range.start,
+                    range.end - 1,
+                    items,
+                    false,
+                    false,
+                    column,
+                    indent,
+                )
+            }
+            Expr::Dict(xs) if self.delimited(range.clone(), '{', '}') => {
+                let mut items = Vec::with_capacity(xs.len());
+                for (k, v) in xs {
+                    let k_text = self.expr(k, inner, indent + 1);
+                    let v_text = self.expr(v, end_column(inner, &k_text) + 2, indent + 1);
+                    items.push(Item {
+                        range: k.span.merge(v.span).byte_range(),
+                        key: (0, String::new()),
+                        text: format!("{}: {}", k_text, v_text),
+                    });
+                }
+                self.sequence(
+                    "",
+                    range.start,
+                    range.end - 1,
+                    items,
+                    false,
+                    false,
+                    column,
+                    indent,
+                )
+            }
+            // Only when nothing but `+` is between the operands, otherwise the operands might
+            // be parenthesized, which the AST does not record.
+            Expr::Op(l, BinOp::Add, r)
+                if self.source[l.span.byte_range().end..r.span.byte_range().start].trim()
+                    == "+" =>
+            {
+                let l_text = self.expr(l, column, indent);
+                let r_text = self.expr(r, end_column(column, &l_text) + 3, indent);
+                format!("{} + {}", l_text, r_text)
+            }
+            Expr::Literal(AstLiteral::String(_)) => string_literal(self.verbatim(x.span)),
+            _ => self.verbatim(x.span).to_owned(),
+        }
+    }
+
+    fn call(
+        &mut self,
+        x: &AstExpr,
+        f: &AstExpr,
+        args: &[AstArgument],
+        force_multiline: bool,
+        sort: bool,
+        column: usize,
+        indent: usize,
+    ) -> String {
+        let open = f.span.byte_range().end;
+        let end = x.span.byte_range().end;
+        if !self.delimited(open..end, '(', ')') {
+            return self.verbatim(x.span).to_owned();
+        }
+        let head = self.verbatim(f.span);
+
+        let inner = (indent + 1) * INDENT.len();
+        let mut items = Vec::with_capacity(args.len());
+        for arg in args {
+            let (key, text) = match &arg.node {
+                ArgumentP::Positional(x) => ((0, String::new()), self.expr(x, inner, indent + 1)),
+                ArgumentP::Named(name, x) => {
+                    let key = if name.node == "name" {
+                        (1, String::new())
+                    } else {
+                        (2, name.node.clone())
+                    };
+                    let text = self.expr(x, inner + name.node.len() + 3, indent + 1);
+                    (key, format!("{} = {}", name.node, text))
+                }
+                ArgumentP::Args(x) => (
+                    (0, String::new()),
+                    format!("*{}", self.expr(x, inner + 1, indent + 1)),
+                ),
+                ArgumentP::KwArgs(x) => (
+                    (3, String::new()),
+                    format!("**{}", self.expr(x, inner + 2, indent + 1)),
+                ),
+            };
+            items.push(Item {
+                range: arg.span.byte_range(),
+                key,
+                text,
+            });
+        }
+        self.sequence(
+            head,
+            open,
+            end - 1,
+            items,
+            force_multiline,
+            sort,
+            column,
+            indent,
+        )
+    }
+
+    /// Whether the text of `range` starts with `open` and ends with `close`.
+    fn delimited(&self, range: Range<usize>, open: char, close: char) -> bool {
+        let text = &self.source[range];
+        text.starts_with(open) && text.ends_with(close)
+    }
+
+    /// Lay out the items between the brackets at `open` and `close`, keeping the comments
+    /// between them.
+    fn sequence(
+        &mut self,
+        head: &str,
+        open: usize,
+        close: usize,
+        items: Vec<Item>,
+        force_multiline: bool,
+        sort: bool,
+        column: usize,
+        indent: usize,
+    ) -> String {
+        let source = self.source;
+        let open_char = &source[open..open + 1];
+        let close_char = &source[close..close + 1];
+
+        let mut between: Vec<usize> = self
+            .comments_in(open + 1..close)
+            .filter(|&i| {
+                let start = self.comments[i].start;
+                !items.iter().any(|x| x.range.contains(&start))
+            })
+            .collect();
+        for &i in &between {
+            self.used[i] = true;
+        }
+
+        let newline_after_open =
+            self.source[open + 1..items.first().map_or(close, |x| x.range.start)].contains('\n');
+        let mut entries = Vec::with_capacity(items.len());
+        let mut prev_end = open + 1;
+        for item in items {
+            let split = between.partition_point(|&i| self.comments[i].start < item.range.start);
+            let leading: Vec<usize> = between.drain(..split).collect();
+            let gap_end = leading
+                .first()
+                .map_or(item.range.start, |&i| self.comments[i].start);
+            let blank_before = !entries.is_empty()
+                && !sort
+                && self.source[prev_end..gap_end].matches('\n').count() >= 2;
+            let trailing = match between.first() {
+                Some(&i) if !self.source[item.range.end..self.comments[i].start].contains('\n') => {
+                    between.remove(0);
+                    Some(i)
+                }
+                _ => None,
+            };
+            prev_end = trailing.map_or(item.range.end, |i| self.comments[i].end);
+            entries.push(Entry {
+                blank_before,
+                leading,
+                text: item.text,
+                trailing,
+                key: item.key,
+            });
+        }
+        let tail = between;
+
+        if sort {
+            entries.sort_by(|a, b| a.key.cmp(&b.key));
+        }
+
+        let has_comments = !tail.is_empty()
+            || entries
+                .iter()
+                .any(|x| !x.leading.is_empty() || x.trailing.is_some());
+        let mut multiline = force_multiline
+            || has_comments
+            || newline_after_open
+            || entries.iter().any(|x| x.text.contains('\n'));
+        if !multiline {
+            let res = format!(
+                "{}{}{}{}",
+                head,
+                open_char,
+                entries
+                    .iter()
+                    .map(|x| x.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                close_char
+            );
+            if entries.is_empty() || column + res.len() <= MAX_LINE_LENGTH {
+                return res;
+            }
+            multiline = true;
+        }
+        assert!(multiline);
+
+        let outer = INDENT.repeat(indent);
+        let inner = INDENT.repeat(indent + 1);
+        let mut res = format!("{}{}\n", head, open_char);
+        for entry in &entries {
+            if entry.blank_before {
+                res.push('\n');
+            }
+            for &i in &entry.leading {
+                res.push_str(&inner);
+                res.push_str(self.comment(i));
+                res.push('\n');
+            }
+            res.push_str(&inner);
+            res.push_str(&entry.text);
+            res.push(',');
+            if let Some(i) = entry.trailing {
+                res.push_str("  ");
+                res.push_str(self.comment(i));
+            }
+            res.push('\n');
+        }
+        for &i in &tail {
+            res.push_str(&inner);
+            res.push_str(self.comment(i));
+            res.push('\n');
+        }
+        res.push_str(&outer);
+        res.push_str(close_char);
+        res
+    }
+}
+
+/// The column after `text`, if it is written starting at `column`.
+fn end_column(column: usize, text: &str) -> usize {
+    match text.rfind('\n') {
+        Some(i) => text.len() - i - 1,
+        None => column + text.len(),
+    }
+}
+
+/// Use double quotes for strings, unless that would require escaping.
+fn string_literal(text: &str) -> String {
+    if let Some(inner) = text.strip_prefix('\'').and_then(|x| x.strip_suffix('\'')) {
+        if !inner.starts_with('\'') && !inner.contains(&['"', '\\'][..]) {
+            return format!("\"{}\"", inner);
+        }
+    }
+    text.to_owned()
+}
+
+/// The byte ranges of the comments in `source`, ignoring `#` inside string literals.
+fn find_comments(source: &str) -> Vec<Range<usize>> {
+    let bytes = source.as_bytes();
+    let mut res = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'#' => {
+                let end = source[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                res.push(i..end);
+                i = end;
+            }
+            q @ (b'"' | b'\'') => {
+                let triple = bytes[i..].starts_with(&[q, q, q]);
+                i += if triple { 3 } else { 1 };
+                while i < bytes.len() {
+                    if bytes[i] == b'\\' {
+                        i += 2;
+                    } else if triple && bytes[i..].starts_with(&[q, q, q]) {
+                        i += 3;
+                        break;
+                    } else if !triple && (bytes[i] == q || bytes[i] == b'\n') {
+                        i += 1;
+                        break;
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::Dialect;
+
+    fn format(x: &str, options: &FormatOptions) -> String {
+        let res = AstModule::parse("X", x.to_owned(), &Dialect::Extended)
+            .unwrap()
+            .format(options);
+        let again = AstModule::parse("X", res.clone(), &Dialect::Extended)
+            .unwrap()
+            .format(options);
+        assert_eq!(res, again, "Formatting is not stable");
+        res
+    }
+
+    #[test]
+    fn test_format_targets() {
+        let res = format(
+            r#"
+load('//:defs.bzl', 'java_library', lib = "java_library_v2")
+
+
+java_library(name='foo', srcs=glob(['*.java']), deps=[':b', ':a'],)  # Trailing.
+java_library(name = "bar")
+x = {
+  'a': 1, "b": [1, 2]}
+"#,
+            &FormatOptions::default(),
+        );
+        assert_eq!(
+            res,
+            r#"load("//:defs.bzl", "java_library", lib = "java_library_v2")
+
+java_library(
+    name = "foo",
+    srcs = glob(["*.java"]),
+    deps = [":b", ":a"],
+)  # Trailing.
+java_library(name = "bar")
+x = {
+    "a": 1,
+    "b": [1, 2],
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let res = format(
+            r##"
+# Header.
+cc_library(
+  srcs = ['a.c'],
+  # The dependencies.
+  deps = [
+    ':a',  # First.
+    # Second.
+    ':b',
+    # Last.
+  ],
+  name = 'x',
+  visibility = ["PUBLIC"] + ["#not a comment"],
+)
+"##,
+            &FormatOptions {
+                sort_named_arguments: true,
+            },
+        );
+        assert_eq!(
+            res,
+            r##"# Header.
+cc_library(
+    name = "x",
+    # The dependencies.
+    deps = [
+        ":a",  # First.
+        # Second.
+        ":b",
+        # Last.
+    ],
+    srcs = ["a.c"],
+    visibility = ["PUBLIC"] + ["#not a comment"],
+)
+"##
+        );
+    }
+
+    #[test]
+    fn test_format_long_lines() {
+        let deps = (0..10).map(|i| format!("\"//some/package:target_{}\"", i));
+        let res = format(
+            &format!("x = [{}]\n", deps.collect::<Vec<_>>().join(", ")),
+            &FormatOptions::default(),
+        );
+        assert_eq!(res.lines().count(), 12);
+        assert!(res.lines().all(|x| x.len() <= MAX_LINE_LENGTH));
+    }
+
+    #[test]
+    fn test_format_verbatim() {
+        // Bodies of definitions are kept, as are statements whose comments can't be placed.
+        let source = r#"def f(x):
+    return x  # Identity.
+
+y = {"a":  # A.
+    1}
+"#;
+        assert_eq!(format(source, &FormatOptions::default()), source);
+    }
+}
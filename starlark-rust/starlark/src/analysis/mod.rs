@@ -17,10 +17,19 @@
 
 #[cfg(all(test, not(windows)))]
 pub(crate) use definition::helpers::FixtureWithRanges;
+pub(crate) use definition::CallPosition;
 pub(crate) use definition::Definition;
 pub(crate) use definition::DottedDefinition;
+pub(crate) use definition::FunctionCall;
 pub(crate) use definition::IdentifierDefinition;
 pub(crate) use definition::LspModule;
+pub use format::FormatOptions;
+pub use queries::CallArgument;
+pub use queries::ListLiteral;
+pub use queries::LoadStatement;
+pub use queries::LoadedSymbol;
+pub use queries::StringListArgument;
+pub use queries::TargetCall;
 pub use types::EvalMessage;
 pub use types::EvalSeverity;
 pub use types::Lint;
@@ -33,9 +42,11 @@ mod definition;
 mod dubious;
 mod exported;
 mod flow;
+mod format;
 mod incompatible;
 mod names;
 mod performance;
+mod queries;
 mod types;
 
 impl AstModule {
@@ -59,4 +70,13 @@ impl AstModule {
         res.extend(performance::performance(self).into_iter().map(LintT::erase));
         res
     }
+
+    /// Find the variables in functions, lambdas and comprehensions that shadow a top-level
+    /// variable. This isn't part of [`lint`](AstModule::lint), as shadowing is often deliberate.
+    pub fn lint_shadowed_variables(&self) -> Vec<Lint> {
+        names::shadowed_variable_warnings(self)
+            .into_iter()
+            .map(LintT::erase)
+            .collect()
+    }
 }
@@ -52,6 +52,8 @@ pub(crate) enum NameWarning {
     UnderscoreFunction(String),
     #[error("Used ignored variable `{0}`")]
     UsingIgnored(String),
+    #[error("Variable `{0}` shadows a top-level variable of the same name")]
+    ShadowedVariable(String),
 }
 
 impl LintWarning for NameWarning {
@@ -94,6 +96,39 @@ pub(crate) fn name_warnings(
     res
 }
 
+/// Not part of [`name_warnings`], as shadowing is often deliberate.
+pub(crate) fn shadowed_variable_warnings(module: &AstModule) -> Vec<LintT<NameWarning>> {
+    let mut res = Vec::new();
+    let scope = bind::scope(module);
+    shadowed_variable(&module.codemap, &scope, &scope, &mut res);
+    res
+}
+
+/// Variables in functions, lambdas and comprehensions that have the same name as a top-level
+/// variable (including loaded symbols), making the top-level one inaccessible.
+fn shadowed_variable(
+    codemap: &CodeMap,
+    top: &Scope,
+    scope: &Scope,
+    res: &mut Vec<LintT<NameWarning>>,
+) {
+    for x in &scope.inner {
+        if let Bind::Scope(inner) = x {
+            for (name, (_, span)) in &inner.bound {
+                // Ignored variables are expected to be reused.
+                if !name.starts_with('_') && top.bound.contains_key(name) {
+                    res.push(LintT::new(
+                        codemap,
+                        *span,
+                        NameWarning::ShadowedVariable(name.clone()),
+                    ));
+                }
+            }
+            shadowed_variable(codemap, top, inner, res);
+        }
+    }
+}
+
 fn undefined_variable(
     codemap: &CodeMap,
     scope: &Scope,
@@ -315,6 +350,7 @@ mod tests {
                 NameWarning::UsingUndefined(x) => x,
                 NameWarning::UnderscoreFunction(x) => x,
                 NameWarning::UsingIgnored(x) => x,
+                NameWarning::ShadowedVariable(x) => x,
             }
         }
     }
@@ -501,4 +537,30 @@ def foo():
         res.sort();
         assert_eq!(res, &["_no1", "_no2", "_no3", "_no4"])
     }
+
+    #[test]
+    fn test_lint_shadowed() {
+        let m = module(
+            r#"
+load("test", "loaded")
+x = 1
+_private = 2
+def f(x, y):
+    loaded = 3
+    z = [y for y in range(3)]
+    w = [x for x in range(3)]
+    return z + w
+def g(_private):
+    def h(f): pass
+    return h
+"#,
+        );
+        let mut res = shadowed_variable_warnings(&m).map(|x| x.problem.about().clone());
+        res.sort();
+        assert_eq!(res, &["f", "loaded", "x", "x"]);
+        // Only reported when asked for.
+        assert!(name_warnings(&m, None)
+            .iter()
+            .all(|x| !matches!(x.problem, NameWarning::ShadowedVariable(_))));
+    }
 }
@@ -0,0 +1,402 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Queries over the AST for tools that check or rewrite modules, e.g. linters.
+
+use crate::analysis::bind;
+use crate::analysis::bind::Bind;
+use crate::analysis::bind::Scope;
+use crate::codemap::FileSpan;
+use crate::codemap::Spanned;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::AstString;
+use crate::syntax::ast::BinOp;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::Stmt;
+use crate::syntax::uniplate::Visit;
+use crate::syntax::AstModule;
+
+/// A `load()` statement. See [`AstModule::load_statements`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadStatement<'a> {
+    /// The location of the whole statement.
+    pub span: FileSpan,
+    /// The module that symbols are loaded from.
+    pub module: &'a str,
+    /// The symbols that are loaded, in order.
+    pub symbols: Vec<LoadedSymbol<'a>>,
+}
+
+/// A symbol in a [`LoadStatement`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedSymbol<'a> {
+    /// The location of the whole argument, e.g. `local = "name"`.
+    pub span: FileSpan,
+    /// The location where the symbol is bound. Lints about the symbol (e.g. that it is unused)
+    /// are reported at this location.
+    pub local_span: FileSpan,
+    /// The name that the symbol is bound to in this module.
+    pub local: &'a str,
+}
+
+/// A list of string literals passed as a named argument. See
+/// [`AstModule::string_list_arguments`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringListArgument<'a> {
+    /// The location of the list.
+    pub span: FileSpan,
+    /// The strings in the list, in order, with the locations of their literals.
+    pub items: Vec<(FileSpan, &'a str)>,
+}
+
+/// A top-level call with a `name` argument, which is usually the declaration of a target. See
+/// [`AstModule::target_calls`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetCall<'a> {
+    /// The location of the whole call.
+    pub span: FileSpan,
+    /// The value of the `name` argument.
+    pub name: &'a str,
+    /// The arguments, in order.
+    pub arguments: Vec<CallArgument<'a>>,
+}
+
+/// An argument of a [`TargetCall`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallArgument<'a> {
+    /// The location of the whole argument, e.g. `deps = [...]`.
+    pub span: FileSpan,
+    /// The name of a named argument, `None` for other arguments.
+    pub name: Option<&'a str>,
+    /// The location of the value.
+    pub value: FileSpan,
+    /// The list literal which is the value, or its first operand if the value is a concatenation,
+    /// e.g. `[...] + select(...)`.
+    pub list: Option<ListLiteral<'a>>,
+}
+
+/// A list literal in a [`CallArgument`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListLiteral<'a> {
+    /// The location of the list, including the brackets.
+    pub span: FileSpan,
+    /// The locations of the items, with their values if they are string literals.
+    pub items: Vec<(FileSpan, Option<&'a str>)>,
+}
+
+impl AstModule {
+    /// The `load()` statements of the module.
+    pub fn load_statements(&self) -> Vec<LoadStatement> {
+        // Like `loads`, we know that `load` statements must be at the top-level.
+        fn f<'a>(ast: &'a AstStmt, module: &AstModule, vec: &mut Vec<LoadStatement<'a>>) {
+            match &ast.node {
+                Stmt::Load(load) => vec.push(LoadStatement {
+                    span: module.file_span(ast.span),
+                    module: &load.module.node,
+                    symbols: load
+                        .args
+                        .iter()
+                        .map(|(local, name)| LoadedSymbol {
+                            span: module.file_span(local.span.merge(name.span)),
+                            local_span: module.file_span(local.span),
+                            local: &local.node.0,
+                        })
+                        .collect(),
+                }),
+                Stmt::Statements(stmts) => {
+                    for s in stmts {
+                        f(s, module, vec);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut loads = Vec::new();
+        f(&self.statement, self, &mut loads);
+        loads
+    }
+
+    /// The lists passed as the named argument `argument` to any function call (e.g. the `deps`
+    /// of rules), if they consist only of string literals.
+    pub fn string_list_arguments(&self, argument: &str) -> Vec<StringListArgument> {
+        fn visit<'a>(
+            module: &AstModule,
+            argument: &str,
+            res: &mut Vec<StringListArgument<'a>>,
+            node: Visit<'a, AstNoPayload>,
+        ) {
+            if let Visit::Expr(Spanned {
+                node: Expr::Call(_, args),
+                ..
+            }) = node
+            {
+                for arg in args {
+                    if let ArgumentP::Named(
+                        name,
+                        Spanned {
+                            node: Expr::List(items),
+                            span,
+                        },
+                    ) = &arg.node
+                    {
+                        if name.node != argument {
+                            continue;
+                        }
+                        let items: Option<Vec<_>> = items
+                            .iter()
+                            .map(|item| match &item.node {
+                                Expr::Literal(AstLiteral::String(s)) => {
+                                    Some((module.file_span(item.span), s.node.as_str()))
+                                }
+                                _ => None,
+                            })
+                            .collect();
+                        if let Some(items) = items {
+                            res.push(StringListArgument {
+                                span: module.file_span(*span),
+                                items,
+                            });
+                        }
+                    }
+                }
+            }
+            node.visit_children(|node| visit(module, argument, res, node));
+        }
+
+        let mut res = Vec::new();
+        visit(self, argument, &mut res, Visit::Stmt(&self.statement));
+        res
+    }
+
+    /// The top-level calls with a string literal `name` argument, in order. Build files declare
+    /// targets this way.
+    pub fn target_calls(&self) -> Vec<TargetCall> {
+        fn list<'a>(module: &AstModule, x: &'a AstExpr) -> Option<ListLiteral<'a>> {
+            match &x.node {
+                Expr::List(items) => Some(ListLiteral {
+                    span: module.file_span(x.span),
+                    items: items
+                        .iter()
+                        .map(|item| match &item.node {
+                            Expr::Literal(AstLiteral::String(s)) => {
+                                (module.file_span(item.span), Some(s.node.as_str()))
+                            }
+                            _ => (module.file_span(item.span), None),
+                        })
+                        .collect(),
+                }),
+                Expr::Op(l, BinOp::Add, _) => list(module, l),
+                _ => None,
+            }
+        }
+
+        let statements = match &self.statement.node {
+            Stmt::Statements(xs) => xs.iter().collect(),
+            _ => vec![&self.statement],
+        };
+        let mut res = Vec::new();
+        for statement in statements {
+            let (call, args) = match &statement.node {
+                Stmt::Expression(
+                    call @ Spanned {
+                        node: Expr::Call(_, args),
+                        ..
+                    },
+                ) => (call, args),
+                _ => continue,
+            };
+            let name = args.iter().find_map(|arg| match &arg.node {
+                ArgumentP::Named(
+                    name,
+                    Spanned {
+                        node: Expr::Literal(AstLiteral::String(value)),
+                        ..
+                    },
+                ) if name.node == "name" => Some(value.node.as_str()),
+                _ => None,
+            });
+            if let Some(name) = name {
+                res.push(TargetCall {
+                    span: self.file_span(call.span),
+                    name,
+                    arguments: args
+                        .iter()
+                        .map(|arg| {
+                            let (name, value) = match &arg.node {
+                                ArgumentP::Named(name, x) => (Some(name.node.as_str()), x),
+                                ArgumentP::Positional(x)
+                                | ArgumentP::Args(x)
+                                | ArgumentP::KwArgs(x) => (None, x),
+                            };
+                            CallArgument {
+                                span: self.file_span(arg.span),
+                                name,
+                                value: self.file_span(value.span),
+                                list: list(self, value),
+                            }
+                        })
+                        .collect(),
+                });
+            }
+        }
+        res
+    }
+
+    /// All the references to variables that are not defined in the module, i.e. to globals
+    /// provided by the interpreter, in the order they appear.
+    pub fn global_references(&self) -> Vec<(FileSpan, &str)> {
+        fn visit<'a>(
+            scope: &'a Scope,
+            enclosing: &mut Vec<&'a Scope>,
+            res: &mut Vec<&'a AstString>,
+        ) {
+            enclosing.push(scope);
+            for x in &scope.inner {
+                let name = match x {
+                    Bind::Get(x) => x,
+                    Bind::GetDotted(x) => x.root_identifier(),
+                    Bind::Scope(inner) => {
+                        visit(inner, enclosing, res);
+                        continue;
+                    }
+                    Bind::Set(..) | Bind::Flow => continue,
+                };
+                if !enclosing.iter().any(|s| s.bound.contains_key(&name.node)) {
+                    res.push(name);
+                }
+            }
+            enclosing.pop();
+        }
+
+        let scope = bind::scope(self);
+        let mut res = Vec::new();
+        visit(&scope, &mut Vec::new(), &mut res);
+        let mut res: Vec<_> = res
+            .into_iter()
+            .map(|name| (name.span, name.node.as_str()))
+            .collect();
+        res.sort_by_key(|(span, _)| span.begin());
+        res.into_iter()
+            .map(|(span, name)| (self.file_span(span), name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gazebo::prelude::*;
+
+    use super::*;
+    use crate::syntax::Dialect;
+
+    fn module(x: &str) -> AstModule {
+        AstModule::parse("X", x.to_owned(), &Dialect::Extended).unwrap()
+    }
+
+    #[test]
+    fn test_load_statements() {
+        let modu = module(
+            r#"
+load("a.bzl", "x", z = "y")
+load("b.bzl", "w")
+"#,
+        );
+        let res = modu.load_statements();
+        assert_eq!(
+            res.map(|load| format!(
+                "{} {} {}",
+                load.span,
+                load.module,
+                load.symbols
+                    .map(|s| format!("{}={}@{}", s.local, s.span.source_span(), s.local_span))
+                    .join(" ")
+            )),
+            &[
+                r#"X:2:1-28 a.bzl x="x"@X:2:15-18 z=z = "y"@X:2:20-21"#,
+                r#"X:3:1-19 b.bzl w="w"@X:3:15-18"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_list_arguments() {
+        let modu = module(
+            r#"
+rule(deps = ["b", "a"], srcs = ["c"])
+rule(deps = ["b", x])
+def f():
+    return g(deps = [])
+"#,
+        );
+        let res = modu.string_list_arguments("deps");
+        assert_eq!(
+            res.map(|arg| format!("{} {}", arg.span, arg.items.map(|(_, s)| *s).join(","))),
+            &["X:2:13-23 b,a", "X:5:21-23 "]
+        );
+    }
+
+    #[test]
+    fn test_target_calls() {
+        let modu = module(
+            r#"
+rule(name = "a", deps = ["b", x] + select({}), *args)
+rule(srcs = [])
+def f():
+    rule(name = "c")
+"#,
+        );
+        let res = modu.target_calls();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].name, "a");
+        assert_eq!(
+            res[0].arguments.map(|arg| format!(
+                "{:?} {} {:?}",
+                arg.name,
+                arg.value,
+                arg.list.as_ref().map(|l| l.items.map(|(_, s)| *s))
+            )),
+            &[
+                r#"Some("name") X:2:13-16 None"#,
+                r#"Some("deps") X:2:25-46 Some([Some("b"), None])"#,
+                "None X:2:49-53 None",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_global_references() {
+        let modu = module(
+            r#"
+load("a.bzl", "loaded")
+x = 1
+def f(y):
+    return y + x + loaded + g1
+g2.member(x, [z for z in g3])
+"#,
+        );
+        let res = modu.global_references();
+        assert_eq!(
+            res.map(|(span, name)| format!("{} {}", span, name)),
+            &["X:5:29-31 g1", "X:6:1-3 g2", "X:6:26-28 g3"]
+        );
+    }
+}
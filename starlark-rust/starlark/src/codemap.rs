@@ -88,6 +88,11 @@ impl Span {
         self.end.0 - self.begin.0
     }
 
+    /// The byte offsets of the span within the source of the codemap.
+    pub(crate) fn byte_range(self) -> std::ops::Range<usize> {
+        self.begin.0 as usize..self.end.0 as usize
+    }
+
     /// Create a span that encloses both `self` and `other`.
     pub fn merge(self, other: Span) -> Span {
         Span {
@@ -460,6 +465,11 @@ impl FileSpan {
         self.file.source_span(self.span)
     }
 
+    /// The byte offsets of the span within the source of the file.
+    pub fn byte_range(&self) -> std::ops::Range<usize> {
+        self.span.begin.0 as usize..self.span.end.0 as usize
+    }
+
     /// Cheap reference to the span.
     pub fn as_ref(&self) -> FileSpanRef {
         FileSpanRef {
@@ -26,10 +26,14 @@ mod globals;
 mod module_dump;
 mod modules;
 pub(crate) mod names;
+pub(crate) mod retention;
 pub(crate) mod slots;
 
 pub use globals::*;
 pub use modules::*;
+pub use retention::ModuleRetention;
+pub use retention::RetainedDefinition;
+pub use retention::RetentionReference;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
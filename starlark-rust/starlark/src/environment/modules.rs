@@ -41,10 +41,12 @@ use crate::docs::DocString;
 use crate::docs::DocStringKind;
 use crate::environment::names::FrozenNames;
 use crate::environment::names::MutableNames;
+use crate::environment::retention::RetentionRecorder;
 use crate::environment::slots::FrozenSlots;
 use crate::environment::slots::ModuleSlotId;
 use crate::environment::slots::MutableSlots;
 use crate::environment::EnvironmentError;
+use crate::environment::ModuleRetention;
 use crate::errors::did_you_mean::did_you_mean;
 use crate::eval::runtime::profile::heap::RetainedHeapProfileMode;
 use crate::eval::ProfileData;
@@ -67,6 +69,8 @@ use crate::values::Value;
 enum ModuleError {
     #[error("Retained memory profiling is not enabled")]
     RetainedMemoryProfileNotEnabled,
+    #[error("Retention by definition is not enabled")]
+    RetentionNotEnabled,
 }
 
 /// The result of freezing a [`Module`], making it and its contained values immutable.
@@ -115,6 +119,8 @@ pub(crate) struct FrozenModuleData {
     docstring: Option<String>,
     /// When heap profile enabled, this field stores retained memory info.
     heap_profile: Option<RetainedHeapProfile>,
+    /// When retention enabled, the memory each definition retains.
+    retention: Option<ModuleRetention>,
 }
 
 /// Container for the documentation for a module
@@ -153,6 +159,8 @@ pub struct Module {
     extra_value: Cell<Option<Value<'static>>>,
     /// When `Some`, heap profile is collected on freeze.
     heap_profile_on_freeze: Cell<Option<RetainedHeapProfileMode>>,
+    /// When `true`, the memory each definition retains is measured on freeze.
+    retention_on_freeze: Cell<bool>,
 }
 
 impl FrozenModule {
@@ -269,6 +277,16 @@ impl FrozenModule {
             Some(p) => Ok(p.to_profile()),
         }
     }
+
+    /// The memory each definition retains, or error if not enabled with
+    /// [`Module::enable_retention`].
+    pub fn retention(&self) -> anyhow::Result<&ModuleRetention> {
+        self.module
+            .0
+            .retention
+            .as_ref()
+            .ok_or_else(|| ModuleError::RetentionNotEnabled.into())
+    }
 }
 
 impl FrozenModuleData {
@@ -328,6 +346,7 @@ impl Module {
             eval_duration: Cell::new(Duration::ZERO),
             extra_value: Cell::new(None),
             heap_profile_on_freeze: Cell::new(None),
+            retention_on_freeze: Cell::new(false),
         }
     }
 
@@ -335,6 +354,12 @@ impl Module {
         self.heap_profile_on_freeze.set(Some(mode));
     }
 
+    /// Measure the memory each definition of the module retains when it is frozen, which is then
+    /// available from [`FrozenModule::retention`]. Makes freezing slower.
+    pub fn enable_retention(&self) {
+        self.retention_on_freeze.set(true);
+    }
+
     /// Get the heap on which values are allocated by this module.
     pub fn heap(&self) -> &Heap {
         &self.heap
@@ -385,6 +410,7 @@ impl Module {
             eval_duration,
             extra_value: extra_v,
             heap_profile_on_freeze,
+            retention_on_freeze,
         } = self;
         let _ = extra_v;
         let start = Instant::now();
@@ -392,8 +418,15 @@ impl Module {
         // Note that we even freeze anonymous slots, since they are accessed by
         // slot-index in the code, and we don't walk into them, so don't know if
         // they are used.
-        let freezer = Freezer::new(frozen_heap);
+        let mut freezer = Freezer::new(frozen_heap);
+        if retention_on_freeze.get() {
+            freezer.retention = Some(RefCell::new(RetentionRecorder::new(&freezer.heap)));
+        }
         let slots = slots.freeze(&freezer)?;
+        let retention = freezer
+            .retention
+            .take()
+            .map(|r| r.into_inner().finish(&names.all_names()));
         let stacks = if let Some(mode) = heap_profile_on_freeze.get() {
             // TODO(nga): retained heap profile does not store information about data
             //   allocated in frozen heap before freeze starts.
@@ -410,6 +443,7 @@ impl Module {
             slots,
             docstring: docstring.into_inner(),
             heap_profile: stacks,
+            retention,
         }));
         let frozen_module_ref = freezer.heap.alloc_any(rest.dupe());
         for frozen_def in freezer.frozen_defs.borrow().as_slice() {
@@ -0,0 +1,240 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The memory the definitions of a module retain once it is frozen.
+
+use std::collections::HashMap;
+
+use allocative::Allocative;
+
+use crate::environment::slots::ModuleSlotId;
+use crate::values::FrozenHeap;
+use crate::values::FrozenStringValue;
+use crate::values::FrozenValue;
+
+/// The memory a slot of a module retains on its frozen heap.
+#[derive(Debug, Clone, PartialEq, Eq, Allocative)]
+pub struct RetainedDefinition {
+    /// The name of the slot, `None` for the slots of the module that have no name.
+    pub name: Option<String>,
+    /// The bytes of the values first reached from the slot.
+    pub bytes: usize,
+    /// The number of values first reached from the slot.
+    pub values: usize,
+}
+
+/// References from the values a definition retains to values another definition retains.
+#[derive(Debug, Clone, PartialEq, Eq, Allocative)]
+pub struct RetentionReference {
+    /// The index of the referencing definition in [`ModuleRetention::definitions`].
+    pub from: usize,
+    /// The index of the referenced definition in [`ModuleRetention::definitions`].
+    pub to: usize,
+    /// How many references there are.
+    pub count: usize,
+}
+
+/// What the definitions of a module retain on its frozen heap, measured while freezing it when
+/// enabled with [`Module::enable_retention`](crate::environment::Module::enable_retention).
+///
+/// Slots are frozen one after the other, in the order the module defines them, and each value is
+/// attributed to the first definition it is reached from. When a definition reaches values
+/// retained by a definition frozen before it, the memory isn't counted twice, but recorded as a
+/// reference between the two, which makes a graph of what keeps what alive.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Allocative)]
+pub struct ModuleRetention {
+    /// Bytes the frozen heap held before the module was frozen, e.g. constants and the code of
+    /// its functions, which aren't attributed to any definition.
+    pub unattributed_bytes: usize,
+    /// The definitions of the module, in the order of their slots.
+    pub definitions: Vec<RetainedDefinition>,
+    /// References between definitions, sorted by `from`, then `to`.
+    pub references: Vec<RetentionReference>,
+}
+
+/// Records which slot first retains each value while a module is frozen.
+pub(crate) struct RetentionRecorder {
+    unattributed_bytes: usize,
+    /// The slot being frozen, and the bytes the frozen heap held when freezing it started.
+    current: Option<(usize, usize)>,
+    /// The slot each frozen value was first reached from, by address.
+    owners: HashMap<usize, usize>,
+    /// Bytes and values retained by each slot.
+    retained: Vec<(usize, usize)>,
+    /// Number of references from a slot to values retained by another, by both slots.
+    references: HashMap<(usize, usize), usize>,
+}
+
+impl RetentionRecorder {
+    pub(crate) fn new(heap: &FrozenHeap) -> Self {
+        Self {
+            unattributed_bytes: heap.allocated_bytes(),
+            current: None,
+            owners: HashMap::new(),
+            retained: Vec::new(),
+            references: HashMap::new(),
+        }
+    }
+
+    /// The values frozen next are reached from `slot`.
+    pub(crate) fn enter_slot(&mut self, slot: usize, heap: &FrozenHeap) {
+        self.exit_slot(heap);
+        if self.retained.len() <= slot {
+            self.retained.resize(slot + 1, (0, 0));
+        }
+        self.current = Some((slot, heap.allocated_bytes()));
+    }
+
+    pub(crate) fn exit_slot(&mut self, heap: &FrozenHeap) {
+        if let Some((slot, start)) = self.current.take() {
+            self.retained[slot].0 += heap.allocated_bytes() - start;
+        }
+    }
+
+    /// `value` was just frozen.
+    pub(crate) fn frozen(&mut self, value: FrozenValue) {
+        if let Some((slot, _)) = self.current {
+            self.owners.insert(value.ptr_value().ptr_value(), slot);
+            self.retained[slot].1 += 1;
+        }
+    }
+
+    /// `value`, which was frozen before, is referenced again.
+    pub(crate) fn referenced(&mut self, value: FrozenValue) {
+        if let Some((slot, _)) = self.current {
+            // Values not frozen yet are being frozen, and reference themselves through a cycle.
+            if let Some(&owner) = self.owners.get(&value.ptr_value().ptr_value()) {
+                if owner != slot {
+                    *self.references.entry((slot, owner)).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn finish(self, names: &[(FrozenStringValue, ModuleSlotId)]) -> ModuleRetention {
+        let mut definitions = self
+            .retained
+            .into_iter()
+            .map(|(bytes, values)| RetainedDefinition {
+                name: None,
+                bytes,
+                values,
+            })
+            .collect::<Vec<_>>();
+        for (name, slot) in names {
+            if let Some(definition) = definitions.get_mut(slot.0 as usize) {
+                definition.name = Some(name.as_str().to_owned());
+            }
+        }
+        let mut references = self
+            .references
+            .into_iter()
+            .map(|((from, to), count)| RetentionReference { from, to, count })
+            .collect::<Vec<_>>();
+        references.sort_by_key(|r| (r.from, r.to));
+        ModuleRetention {
+            unattributed_bytes: self.unattributed_bytes,
+            definitions,
+            references,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::environment::ModuleRetention;
+    use crate::eval::Evaluator;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn retention(program: &str) -> ModuleRetention {
+        let module = Module::new();
+        module.enable_retention();
+        {
+            let mut eval = Evaluator::new(&module);
+            let ast = AstModule::parse("x.star", program.to_owned(), &Dialect::Extended).unwrap();
+            eval.eval_module(ast, &Globals::standard()).unwrap();
+        }
+        module.freeze().unwrap().retention().unwrap().clone()
+    }
+
+    fn definition<'a>(
+        retention: &'a ModuleRetention,
+        name: &str,
+    ) -> (usize, &'a super::RetainedDefinition) {
+        retention
+            .definitions
+            .iter()
+            .enumerate()
+            .find(|(_, d)| d.name.as_deref() == Some(name))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_retention_by_definition() {
+        let retention = retention(
+            r#"
+big = ["s" + str(i) for i in range(1000)]
+small = [1]
+"#,
+        );
+        let (_, big) = definition(&retention, "big");
+        let (_, small) = definition(&retention, "small");
+        // The list and its strings.
+        assert_eq!(1001, big.values);
+        assert_eq!(1, small.values);
+        assert!(big.bytes > 100 * small.bytes, "{:?}", retention);
+        assert_eq!(Vec::new(), retention.references);
+    }
+
+    #[test]
+    fn test_shared_memory_is_a_reference() {
+        let retention = retention(
+            r#"
+table = {"k" + str(i): [i] for i in range(100)}
+alias = table
+uses = [table, table]
+"#,
+        );
+        let (table_index, table) = definition(&retention, "table");
+        let (alias_index, alias) = definition(&retention, "alias");
+        let (uses_index, uses) = definition(&retention, "uses");
+        assert_eq!(201, table.values);
+        // Neither retains the table again.
+        assert_eq!((0, 0), (alias.bytes, alias.values));
+        assert_eq!(1, uses.values);
+
+        let references = retention
+            .references
+            .iter()
+            .map(|r| (r.from, r.to, r.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![(alias_index, table_index, 1), (uses_index, table_index, 2)],
+            references
+        );
+    }
+
+    #[test]
+    fn test_retention_not_enabled() {
+        let module = Module::new();
+        module.set("x", module.heap().alloc(1));
+        assert!(module.freeze().unwrap().retention().is_err());
+    }
+}
@@ -80,7 +80,14 @@ impl<'v> MutableSlots<'v> {
         let slots = self
             .0
             .into_inner()
-            .try_map(|x| x.into_try_map(|x| x.freeze(freezer)))?;
+            .into_iter()
+            .enumerate()
+            .map(|(slot, x)| {
+                freezer.enter_slot(slot);
+                x.into_try_map(|x| x.freeze(freezer))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        freezer.exit_slot();
         Ok(FrozenSlots(slots))
     }
 }
@@ -1196,6 +1196,9 @@ impl BcInstr for InstrIfNotBr {
     }
 }
 
+/// Number of loop iterations between checks whether evaluation was cancelled.
+const LOOP_CANCELLATION_CHECK_INTERVAL: usize = 1024;
+
 pub(crate) struct InstrForLoop;
 pub(crate) struct InstrBreak;
 pub(crate) struct InstrContinue;
@@ -1215,11 +1218,18 @@ impl BcInstr for InstrForLoop {
             Ok,
             Return(Value<'v>),
             Err(EvalException),
+            Cancelled(anyhow::Error),
         }
 
         let iter_ret = collection.with_iterator(eval.heap(), |iter| {
             let loop_start = ip.add_instr::<Self>();
-            for item in iter {
+            for (i, item) in iter.enumerate() {
+                // Checking on every iteration would slow down tight loops.
+                if i % LOOP_CANCELLATION_CHECK_INTERVAL == 0 {
+                    if let Err(e) = eval.check_cancelled() {
+                        return LoopResult::Cancelled(e);
+                    }
+                }
                 frame.set_bc_slot(*var, item);
                 match run_block(eval, loop_start) {
                     RunBlockResult::Continue => {}
@@ -1234,6 +1244,7 @@ impl BcInstr for InstrForLoop {
             Ok(LoopResult::Ok) => InstrControl::Next(ip.add_rel(*loop_end)),
             Ok(LoopResult::Return(v)) => InstrControl::Return(v),
             Ok(LoopResult::Err(e)) => InstrControl::Err(e.0),
+            Ok(LoopResult::Cancelled(e)) => InstrControl::Err(e),
             Err(e) => InstrControl::Err(e),
         }
     }
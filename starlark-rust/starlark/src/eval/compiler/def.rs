@@ -704,6 +704,8 @@ where
     fn invoke_raw(&self, eval: &mut Evaluator<'v, '_>) -> anyhow::Result<Value<'v>> {
         // println!("invoking {}", self.def.stmt.name.node);
 
+        eval.check_cancelled()?;
+
         if !self.parameter_types.is_empty() {
             self.check_parameter_types(eval)?;
         }
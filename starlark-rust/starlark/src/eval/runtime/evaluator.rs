@@ -19,6 +19,8 @@ use std::collections::HashSet;
 use std::mem;
 use std::mem::MaybeUninit;
 use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 
 use gazebo::any::AnyLifetime;
 use gazebo::cast;
@@ -100,6 +102,8 @@ pub(crate) enum EvaluatorError {
     CoverageNotImplemented,
     #[error("Coverage not enabled")]
     CoverageNotEnabled,
+    #[error("Evaluation cancelled")]
+    Cancelled,
 }
 
 /// Number of bytes to allocate between GC's.
@@ -155,6 +159,8 @@ pub struct Evaluator<'v, 'a> {
     pub(crate) breakpoint_handler: Option<Box<dyn Fn() -> Box<dyn BreakpointConsole>>>,
     /// Use in implementation of `print` function.
     pub(crate) print_handler: &'a (dyn PrintHandler + 'a),
+    /// Once set, evaluation stops at the next call of a `def` function or in the next loop.
+    pub(crate) cancellation_flag: Option<&'a AtomicBool>,
     // The Starlark-level call-stack of functions.
     // Must go last because it's quite a big structure
     pub(crate) call_stack: CheapCallStack<'v>,
@@ -197,6 +203,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             string_pool: StringPool::default(),
             breakpoint_handler: None,
             print_handler: &StderrPrintHandler,
+            cancellation_flag: None,
             verbose_gc: false,
         }
     }
@@ -401,6 +408,23 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.print_handler = handler;
     }
 
+    /// Set a flag that someone else sets once evaluation should stop, e.g. because its result is
+    /// no longer needed. It is checked on entry to `def` functions and periodically in loops,
+    /// and evaluation fails at that point once it is set.
+    pub fn set_cancellation_flag(&mut self, flag: &'a AtomicBool) {
+        self.cancellation_flag = Some(flag);
+    }
+
+    /// Fail if the flag set with [`set_cancellation_flag`](Evaluator::set_cancellation_flag) is
+    /// set.
+    #[inline(always)]
+    pub(crate) fn check_cancelled(&self) -> anyhow::Result<()> {
+        match self.cancellation_flag {
+            Some(flag) if flag.load(Ordering::Relaxed) => Err(EvaluatorError::Cancelled.into()),
+            _ => Ok(()),
+        }
+    }
+
     /// Called to add an entry to the call stack, by the function being invoked.
     /// Called for all types of function, including those written in Rust.
     #[inline(always)]
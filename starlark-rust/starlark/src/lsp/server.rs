@@ -39,17 +39,31 @@ use lsp_types::notification::DidCloseTextDocument;
 use lsp_types::notification::DidOpenTextDocument;
 use lsp_types::notification::LogMessage;
 use lsp_types::notification::PublishDiagnostics;
+use lsp_types::request::Completion;
 use lsp_types::request::GotoDefinition;
+use lsp_types::request::HoverRequest;
+use lsp_types::CompletionItem;
+use lsp_types::CompletionItemKind;
+use lsp_types::CompletionOptions;
+use lsp_types::CompletionParams;
+use lsp_types::CompletionResponse;
 use lsp_types::DefinitionOptions;
 use lsp_types::Diagnostic;
 use lsp_types::DidChangeTextDocumentParams;
 use lsp_types::DidCloseTextDocumentParams;
 use lsp_types::DidOpenTextDocumentParams;
+use lsp_types::Documentation;
 use lsp_types::GotoDefinitionParams;
 use lsp_types::GotoDefinitionResponse;
+use lsp_types::Hover;
+use lsp_types::HoverContents;
+use lsp_types::HoverParams;
+use lsp_types::HoverProviderCapability;
 use lsp_types::InitializeParams;
 use lsp_types::LocationLink;
 use lsp_types::LogMessageParams;
+use lsp_types::MarkupContent;
+use lsp_types::MarkupKind;
 use lsp_types::MessageType;
 use lsp_types::OneOf;
 use lsp_types::PublishDiagnosticsParams;
@@ -65,11 +79,17 @@ use serde::Deserializer;
 use serde::Serialize;
 use serde::Serializer;
 
+use crate::analysis::CallPosition;
 use crate::analysis::Definition;
 use crate::analysis::DottedDefinition;
+use crate::analysis::FunctionCall;
 use crate::analysis::IdentifierDefinition;
 use crate::analysis::LspModule;
 use crate::codemap::ResolvedSpan;
+use crate::docs::DocItem;
+use crate::docs::DocString;
+use crate::docs::Function;
+use crate::docs::Param;
 use crate::lsp::server::LoadContentsError::WrongScheme;
 use crate::syntax::AstModule;
 
@@ -292,6 +312,18 @@ pub trait LspContext {
         current_file: &LspUrl,
         symbol: &str,
     ) -> anyhow::Result<Option<LspUrl>>;
+
+    /// Get the documentation for a global symbol if possible.
+    ///
+    /// This is used to complete the names of the parameters of a global function that is being
+    /// called (e.g. the attributes of a rule), and to show their documentation on hover.
+    fn get_docs_for_global_symbol(
+        &self,
+        _current_file: &LspUrl,
+        _symbol: &str,
+    ) -> anyhow::Result<Option<DocItem>> {
+        Ok(None)
+    }
 }
 
 /// Errors when [`LspContext::resolve_load()`] cannot resolve a given path.
@@ -331,6 +363,8 @@ impl<T: LspContext> Backend<T> {
         ServerCapabilities {
             text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
             definition_provider,
+            completion_provider: Some(CompletionOptions::default()),
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
             ..ServerCapabilities::default()
         }
     }
@@ -400,6 +434,20 @@ impl<T: LspContext> Backend<T> {
         self.send_response(new_response(id, self.find_definition(params)));
     }
 
+    /// Complete the names of the parameters of the global function being called at the current
+    /// cursor, e.g. the attributes of a rule in a build file.
+    ///
+    /// NOTE: Like `goto_definition`, this uses the last valid parse of a file.
+    fn completion(&self, id: RequestId, params: CompletionParams) {
+        self.send_response(new_response(id, self.completion_options(params)));
+    }
+
+    /// Show the documentation of the global function, or of the parameter of that function,
+    /// at the current cursor.
+    fn hover(&self, id: RequestId, params: HoverParams) {
+        self.send_response(new_response(id, self.hover_info(params)));
+    }
+
     /// Get the file contents of a starlark: URI.
     fn get_starlark_file_contents(&self, id: RequestId, params: StarlarkFileContentsParams) {
         let response: anyhow::Result<_> = match params.uri {
@@ -586,6 +634,139 @@ impl<T: LspContext> Backend<T> {
         };
         Ok(GotoDefinitionResponse::Link(response))
     }
+
+    /// Find the function call at a position, and the documentation of the function, if the
+    /// function is a global one that the `LspContext` has documentation for.
+    fn find_documented_call(
+        &self,
+        uri: &LspUrl,
+        line: u32,
+        character: u32,
+    ) -> anyhow::Result<Option<(FunctionCall, Function)>> {
+        let ast = match self.get_ast(uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        let call = match ast.find_function_call_at(line, character) {
+            Some(call) => call,
+            None => return Ok(None),
+        };
+        // Locals and loaded symbols shadow globals of the same name.
+        match ast.find_definition(
+            call.function_span.begin_line as u32,
+            call.function_span.begin_column as u32,
+        ) {
+            Definition::Identifier(IdentifierDefinition::Unresolved { .. }) => {}
+            _ => return Ok(None),
+        }
+        match self
+            .context
+            .get_docs_for_global_symbol(uri, &call.function)?
+        {
+            Some(DocItem::Function(function)) => Ok(Some((call, function))),
+            _ => Ok(None),
+        }
+    }
+
+    fn completion_options(&self, params: CompletionParams) -> anyhow::Result<CompletionResponse> {
+        let uri = params.text_document_position.text_document.uri.try_into()?;
+        let line = params.text_document_position.position.line;
+        let character = params.text_document_position.position.character;
+
+        let items = match self.find_documented_call(&uri, line, character)? {
+            Some((call, function)) if call.position == CallPosition::NewArgument => function
+                .params
+                .iter()
+                .filter_map(|param| match param {
+                    Param::Arg {
+                        name, docs, typ, ..
+                    } if !call.named_args.contains(name) => Some(CompletionItem {
+                        label: name.clone(),
+                        kind: Some(CompletionItemKind::PROPERTY),
+                        detail: typ.as_ref().map(|typ| typ.raw_type.clone()),
+                        documentation: docs
+                            .as_ref()
+                            .map(|docs| Documentation::MarkupContent(markdown(render_docs(docs)))),
+                        insert_text: Some(format!("{} = ", name)),
+                        ..CompletionItem::default()
+                    }),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+        Ok(CompletionResponse::Array(items))
+    }
+
+    fn hover_info(&self, params: HoverParams) -> anyhow::Result<Option<Hover>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .try_into()?;
+        let line = params.text_document_position_params.position.line;
+        let character = params.text_document_position_params.position.character;
+
+        let (call, function) = match self.find_documented_call(&uri, line, character)? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let (contents, span) = match call.position {
+            CallPosition::Function => match &function.docs {
+                Some(docs) => (render_docs(docs), call.function_span),
+                None => return Ok(None),
+            },
+            CallPosition::ArgumentName { name, span } => {
+                let param = function.params.iter().find_map(|param| match param {
+                    Param::Arg {
+                        name: param_name,
+                        docs,
+                        typ,
+                        default_value,
+                    } if *param_name == name => Some((docs, typ, default_value)),
+                    _ => None,
+                });
+                match param {
+                    Some((docs, typ, default_value)) => {
+                        let mut signature = name;
+                        if let Some(typ) = typ {
+                            signature.push_str(&format!(": {}", typ.raw_type));
+                        }
+                        if let Some(default_value) = default_value {
+                            signature.push_str(&format!(" = {}", default_value));
+                        }
+                        let mut contents = format!("```python\n{}\n```", signature);
+                        if let Some(docs) = docs {
+                            contents.push_str("\n\n");
+                            contents.push_str(&render_docs(docs));
+                        }
+                        (contents, span)
+                    }
+                    None => return Ok(None),
+                }
+            }
+            CallPosition::NewArgument | CallPosition::ArgumentValue => return Ok(None),
+        };
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(markdown(contents)),
+            range: Some(span.into()),
+        }))
+    }
+}
+
+/// Render a doc string as markdown.
+fn render_docs(docs: &DocString) -> String {
+    match &docs.details {
+        Some(details) => format!("{}\n\n{}", docs.summary, details),
+        None => docs.summary.clone(),
+    }
+}
+
+fn markdown(value: String) -> MarkupContent {
+    MarkupContent {
+        kind: MarkupKind::Markdown,
+        value,
+    }
 }
 
 /// The library style pieces
@@ -623,6 +804,10 @@ impl<T: LspContext> Backend<T> {
                     //            be handled client side.
                     if let Some(params) = as_request::<GotoDefinition>(&req) {
                         self.goto_definition(req.id, params);
+                    } else if let Some(params) = as_request::<Completion>(&req) {
+                        self.completion(req.id, params);
+                    } else if let Some(params) = as_request::<HoverRequest>(&req) {
+                        self.hover(req.id, params);
                     } else if let Some(params) = as_request::<StarlarkFileContentsRequest>(&req) {
                         self.get_starlark_file_contents(req.id, params);
                     } else if self.connection.handle_shutdown(&req)? {
@@ -769,9 +954,16 @@ mod test {
     use anyhow::Context;
     use lsp_server::Request;
     use lsp_server::RequestId;
+    use lsp_types::request::Completion;
     use lsp_types::request::GotoDefinition;
+    use lsp_types::request::HoverRequest;
+    use lsp_types::CompletionParams;
+    use lsp_types::CompletionResponse;
     use lsp_types::GotoDefinitionParams;
     use lsp_types::GotoDefinitionResponse;
+    use lsp_types::Hover;
+    use lsp_types::HoverContents;
+    use lsp_types::HoverParams;
     use lsp_types::LocationLink;
     use lsp_types::Position;
     use lsp_types::Range;
@@ -856,6 +1048,53 @@ mod test {
         }
     }
 
+    fn text_document_position(uri: Url, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: Position { line, character },
+        }
+    }
+
+    fn completion_labels(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+    ) -> anyhow::Result<Vec<String>> {
+        let req = server.new_request::<Completion>(CompletionParams {
+            text_document_position: text_document_position(uri, line, character),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        });
+        let request_id = server.send_request(req)?;
+        match server.get_response::<CompletionResponse>(request_id)? {
+            CompletionResponse::Array(items) => Ok(items.into_iter().map(|i| i.label).collect()),
+            response => Err(anyhow::anyhow!("Got invalid message type: {:?}", response)),
+        }
+    }
+
+    fn hover(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+    ) -> anyhow::Result<Option<(String, Range)>> {
+        let req = server.new_request::<HoverRequest>(HoverParams {
+            text_document_position_params: text_document_position(uri, line, character),
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(req)?;
+        match server.get_response::<Option<Hover>>(request_id)? {
+            Some(Hover {
+                contents: HoverContents::Markup(contents),
+                range: Some(range),
+            }) => Ok(Some((contents.value, range))),
+            None => Ok(None),
+            response => Err(anyhow::anyhow!("Got invalid message type: {:?}", response)),
+        }
+    }
+
     #[cfg(windows)]
     fn temp_file_uri(rel_path: &str) -> Url {
         Url::from_file_path(&PathBuf::from("C:/tmp").join(rel_path)).unwrap()
@@ -1804,4 +2043,89 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    fn completes_parameters_of_global_functions() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+
+        let foo_contents = dedent(
+            r#"
+            native_rule(<new>)</new>
+            native_rule(name = "foo",<after_name> </after_name>)
+            native_rule(srcs = [<in_list>]</in_list>)
+            def native_function1(native_rule):
+                native_rule(<shadowed>)</shadowed>
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+
+        let mut complete = |id: &str| {
+            completion_labels(
+                &mut server,
+                foo_uri.clone(),
+                foo.begin_line(id),
+                foo.begin_column(id),
+            )
+        };
+
+        assert_eq!(vec!["name", "srcs"], complete("new")?);
+        assert_eq!(vec!["srcs"], complete("after_name")?);
+        assert!(complete("in_list")?.is_empty());
+        assert!(complete("shadowed")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn hovers_over_global_functions_and_parameters() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+
+        let foo_contents = dedent(
+            r#"
+            <rule>native_<rule_click>r</rule_click>ule</rule>(
+                <name>na<name_click>m</name_click>e</name> = "foo",
+                <srcs><srcs_click>s</srcs_click>rcs</srcs> = [],
+                <other_click>o</other_click>ther = 1,
+            )
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+
+        let mut hover_at = |id: &str| {
+            hover(
+                &mut server,
+                foo_uri.clone(),
+                foo.begin_line(id),
+                foo.begin_column(id),
+            )
+        };
+
+        assert_eq!(
+            Some(("Builds a thing.".to_owned(), foo.span("rule").into())),
+            hover_at("rule_click")?
+        );
+        assert_eq!(
+            Some((
+                "```python\nname: str\n```\n\nThe name of the target.".to_owned(),
+                foo.span("name").into()
+            )),
+            hover_at("name_click")?
+        );
+        assert_eq!(
+            Some((
+                "```python\nsrcs = []\n```".to_owned(),
+                foo.span("srcs").into()
+            )),
+            hover_at("srcs_click")?
+        );
+        assert_eq!(None, hover_at("other_click")?);
+        Ok(())
+    }
 }
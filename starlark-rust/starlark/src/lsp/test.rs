@@ -59,9 +59,13 @@ use serde::de::DeserializeOwned;
 use crate::docs::render_docs_as_code;
 use crate::docs::Doc;
 use crate::docs::DocItem;
+use crate::docs::DocString;
+use crate::docs::DocStringKind;
 use crate::docs::Function;
 use crate::docs::Identifier;
 use crate::docs::Location;
+use crate::docs::Param;
+use crate::docs::Type;
 use crate::errors::EvalMessage;
 use crate::lsp::server::new_notification;
 use crate::lsp::server::server_with_connection;
@@ -118,6 +122,7 @@ struct TestServerContext {
     dirs: Arc<RwLock<HashSet<PathBuf>>>,
     builtin_docs: Arc<HashMap<LspUrl, String>>,
     builtin_symbols: Arc<HashMap<String, LspUrl>>,
+    builtin_symbol_docs: Arc<HashMap<String, DocItem>>,
 }
 
 impl LspContext for TestServerContext {
@@ -225,6 +230,14 @@ impl LspContext for TestServerContext {
     ) -> anyhow::Result<Option<LspUrl>> {
         Ok(self.builtin_symbols.get(symbol).cloned())
     }
+
+    fn get_docs_for_global_symbol(
+        &self,
+        _current_file: &LspUrl,
+        symbol: &str,
+    ) -> anyhow::Result<Option<DocItem>> {
+        Ok(self.builtin_symbol_docs.get(symbol).cloned())
+    }
 }
 
 /// A server for use in testing that provides helpers for sending requests, correlating
@@ -308,6 +321,41 @@ impl TestServer {
                     item: DocItem::Function(Function::default()),
                     custom_attrs: Default::default(),
                 },
+                Doc {
+                    id: Identifier {
+                        name: "native_rule".to_owned(),
+                        location: None,
+                    },
+                    item: DocItem::Function(Function {
+                        docs: DocString::from_docstring(DocStringKind::Starlark, "Builds a thing."),
+                        params: vec![
+                            Param::Arg {
+                                name: "name".to_owned(),
+                                docs: DocString::from_docstring(
+                                    DocStringKind::Starlark,
+                                    "The name of the target.",
+                                ),
+                                typ: Some(Type {
+                                    raw_type: "str".to_owned(),
+                                }),
+                                default_value: None,
+                            },
+                            Param::Arg {
+                                name: "srcs".to_owned(),
+                                docs: None,
+                                typ: None,
+                                default_value: Some("[]".to_owned()),
+                            },
+                            Param::Kwargs {
+                                name: "kwargs".to_owned(),
+                                docs: None,
+                                typ: None,
+                            },
+                        ],
+                        ret: Default::default(),
+                    }),
+                    custom_attrs: Default::default(),
+                },
             ],
             LspUrl::try_from(Url::from_file_path(prelude_path).unwrap())? => vec![
                 Doc {
@@ -355,16 +403,19 @@ impl TestServer {
         let builtin = Self::testing_builtins(&std::env::current_dir()?)?;
         let mut builtin_docs = HashMap::with_capacity(builtin.len());
         let mut builtin_symbols = HashMap::new();
+        let mut builtin_symbol_docs = HashMap::new();
 
         for (u, ds) in builtin {
             builtin_docs.insert(u.clone(), render_docs_as_code(&ds));
             for d in ds {
-                builtin_symbols.insert(d.id.name, u.clone());
+                builtin_symbols.insert(d.id.name.clone(), u.clone());
+                builtin_symbol_docs.insert(d.id.name, d.item);
             }
         }
 
         let builtin_docs = Arc::new(builtin_docs);
         let builtin_symbols = Arc::new(builtin_symbols);
+        let builtin_symbol_docs = Arc::new(builtin_symbol_docs);
 
         let prelude_file_contents = builtin_docs
             .iter()
@@ -380,6 +431,7 @@ impl TestServer {
             dirs: dirs.dupe(),
             builtin_docs: builtin_docs.dupe(),
             builtin_symbols,
+            builtin_symbol_docs,
         };
 
         let server_thread = std::thread::spawn(|| {
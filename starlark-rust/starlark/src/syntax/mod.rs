@@ -21,6 +21,14 @@ pub use ast::AstModule;
 pub use dialect::Dialect;
 pub use dialect::DialectTypes;
 
+pub use crate::analysis::CallArgument;
+pub use crate::analysis::FormatOptions;
+pub use crate::analysis::ListLiteral;
+pub use crate::analysis::LoadStatement;
+pub use crate::analysis::LoadedSymbol;
+pub use crate::analysis::StringListArgument;
+pub use crate::analysis::TargetCall;
+
 #[cfg(test)]
 mod grammar_tests;
 #[cfg(test)]
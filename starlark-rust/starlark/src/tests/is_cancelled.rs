@@ -0,0 +1,59 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use crate::environment::Globals;
+use crate::environment::Module;
+use crate::eval::Evaluator;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+fn eval_with_flag(flag: &AtomicBool, program: &str) -> anyhow::Result<()> {
+    let module = Module::new();
+    let globals = Globals::standard();
+    let mut evaluator = Evaluator::new(&module);
+    evaluator.set_cancellation_flag(flag);
+    let ast = AstModule::parse("a.star", program.to_owned(), &Dialect::Extended).unwrap();
+    evaluator.eval_module(ast, &globals)?;
+    Ok(())
+}
+
+#[test]
+fn is_cancelled() {
+    let program = "\
+def f():
+  for x in range(10000):
+    pass
+f()
+";
+    let flag = AtomicBool::new(false);
+    eval_with_flag(&flag, program).unwrap();
+
+    flag.store(true, Ordering::Relaxed);
+    let err = format!("{:#}", eval_with_flag(&flag, program).unwrap_err());
+    assert!(err.contains("Evaluation cancelled"), "{}", err);
+    let err = format!(
+        "{:#}",
+        eval_with_flag(&flag, "for x in range(10):\n  pass\n").unwrap_err()
+    );
+    assert!(err.contains("Evaluation cancelled"), "{}", err);
+
+    // Only calls of `def` functions and loops check the flag.
+    eval_with_flag(&flag, "x = len([1, 2])").unwrap();
+}
@@ -26,6 +26,7 @@ mod docstring;
 mod freeze_access_value;
 mod go;
 mod interop;
+mod is_cancelled;
 mod opt;
 mod runtime;
 mod type_annot;
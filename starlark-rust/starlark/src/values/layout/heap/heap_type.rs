@@ -46,6 +46,7 @@ use crate::collections::maybe_uninit_backport::maybe_uninit_write_slice;
 use crate::collections::maybe_uninit_backport::maybe_uninit_write_slice_cloned;
 use crate::collections::Hashed;
 use crate::collections::StarlarkHashValue;
+use crate::environment::retention::RetentionRecorder;
 use crate::eval::compiler::def::FrozenDef;
 use crate::values::any::StarlarkAny;
 use crate::values::array::Array;
@@ -437,6 +438,8 @@ pub struct Freezer {
     pub(crate) heap: FrozenHeap,
     /// Defs frozen by this freezer.
     pub(crate) frozen_defs: RefCell<Vec<FrozenRef<'static, FrozenDef>>>,
+    /// When freezing a module with retention enabled, what its slots retain.
+    pub(crate) retention: Option<RefCell<RetentionRecorder>>,
 }
 
 impl Freezer {
@@ -444,6 +447,20 @@ impl Freezer {
         Freezer {
             heap,
             frozen_defs: RefCell::new(Vec::new()),
+            retention: None,
+        }
+    }
+
+    /// The values frozen next are reached from the slot `slot` of the module.
+    pub(crate) fn enter_slot(&self, slot: usize) {
+        if let Some(retention) = &self.retention {
+            retention.borrow_mut().enter_slot(slot, &self.heap);
+        }
+    }
+
+    pub(crate) fn exit_slot(&self) {
+        if let Some(retention) = &self.retention {
+            retention.borrow_mut().exit_slot(&self.heap);
         }
     }
 
@@ -487,8 +504,20 @@ impl Freezer {
         // Case 2: We have already been replaced with a forwarding, or need to freeze
         let value = value.0.unpack_ptr().unwrap();
         match value.unpack_overwrite() {
-            Either::Left(x) => Ok(unsafe { x.unpack_frozen_value() }),
-            Either::Right(v) => unsafe { v.heap_freeze(self) },
+            Either::Left(x) => {
+                let x = unsafe { x.unpack_frozen_value() };
+                if let Some(retention) = &self.retention {
+                    retention.borrow_mut().referenced(x);
+                }
+                Ok(x)
+            }
+            Either::Right(v) => {
+                let x = unsafe { v.heap_freeze(self) }?;
+                if let Some(retention) = &self.retention {
+                    retention.borrow_mut().frozen(x);
+                }
+                Ok(x)
+            }
         }
     }
 
@@ -370,7 +370,9 @@ fn invalid_type_annotation<'v>(ty: Value<'v>, heap: &'v Heap) -> TypingError {
 }
 
 impl<'v> Value<'v> {
-    pub(crate) fn is_type(self, ty: Value<'v>, heap: &'v Heap) -> anyhow::Result<bool> {
+    /// Whether this value matches the type annotation `ty`, like the one of a parameter.
+    /// Fails if `ty` is not a valid type annotation.
+    pub fn is_type(self, ty: Value<'v>, heap: &'v Heap) -> anyhow::Result<bool> {
         Ok(TypeCompiled::new(ty, heap)?.matches(self))
     }
 
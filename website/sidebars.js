@@ -187,6 +187,7 @@ const manualSidebar = [
       isInternal() ? 'developers/heap_profiling' : [],
       'developers/parity_script',
       'developers/what-ran',
+      'developers/daemon_api',
       {
         type: 'category',
         label: 'Starlark Language',